@@ -0,0 +1,25 @@
+#![no_main]
+
+use aac::{AdtsHeader, AdtsIterator, AudioSpecificConfig, PartialAudioSpecificConfig};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(config) = PartialAudioSpecificConfig::parse(data) {
+        let _ = config.mux(&mut Vec::new());
+    }
+
+    if let Ok(config) = AudioSpecificConfig::parse(data) {
+        let _ = config.output_sampling_frequency();
+        let _ = config.effective_channel_count();
+    }
+
+    if let Ok(header) = AdtsHeader::parse(data) {
+        let _ = header.payload_len();
+        if let Ok(config) = header.to_audio_specific_config() {
+            let _ = config.mux(&mut Vec::new());
+        }
+        let _ = header.mux(&mut Vec::new());
+    }
+
+    AdtsIterator::new(data).for_each(drop);
+});
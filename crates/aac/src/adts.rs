@@ -0,0 +1,427 @@
+use std::io;
+
+use bytes_util::{BitReader, BitWriter};
+
+use crate::{AudioObjectType, PartialAudioSpecificConfig, SampleFrequencyIndex};
+
+/// `syncword`
+const SYNCWORD: u64 = 0xfff;
+
+/// The maximum value of the 13-bit `frame_length` field.
+const MAX_FRAME_LENGTH: usize = (1 << 13) - 1;
+
+/// `adts_buffer_fullness` value that signals a variable bitrate stream.
+const BUFFER_FULLNESS_VBR: u16 = 0x7ff;
+
+/// ADTS Header
+/// ISO/IEC 14496-3:2019(E) - 1.A.2.2 (Table 1.A.5, 1.A.6, 1.A.7)
+///
+/// Consists of `adts_fixed_header`, `adts_variable_header` and, if
+/// `protection_absent` is 0, the 16-bit `crc_check` that follows them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct AdtsHeader {
+    /// `ID`
+    ///
+    /// `false` for MPEG-4, `true` for MPEG-2.
+    pub mpeg2: bool,
+    /// `profile_ObjectType`
+    ///
+    /// This is the audio object type minus 1.
+    pub profile: u8,
+    /// `sampling_frequency_index`
+    pub sampling_frequency_index: SampleFrequencyIndex,
+    /// `private_bit`
+    pub private_bit: bool,
+    /// `channel_configuration`
+    pub channel_configuration: u8,
+    /// `original_copy`
+    pub original_copy: bool,
+    /// `home`
+    pub home: bool,
+    /// `copyright_identification_bit`
+    pub copyright_identification_bit: bool,
+    /// `copyright_identification_start`
+    pub copyright_identification_start: bool,
+    /// `frame_length`
+    ///
+    /// The length of the frame in bytes, including the header.
+    pub frame_length: u16,
+    /// `adts_buffer_fullness`
+    pub buffer_fullness: u16,
+    /// `number_of_raw_data_blocks_in_frame + 1`
+    pub number_of_raw_data_blocks: u8,
+    /// `crc_check` if `protection_absent` is 0
+    pub crc: Option<u16>,
+}
+
+impl AdtsHeader {
+    /// Parse the ADTS header at the start of the given bytes.
+    ///
+    /// The header is validated against the `syncword` and `frame_length` must
+    /// be at least the length of the header itself.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut bitreader = BitReader::new_from_slice(data);
+
+        if bitreader.read_bits(12)? != SYNCWORD {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid ADTS syncword",
+            ));
+        }
+
+        let mpeg2 = bitreader.read_bit()?;
+        let layer = bitreader.read_bits(2)?;
+        if layer != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ADTS layer is not 0",
+            ));
+        }
+        let protection_absent = bitreader.read_bit()?;
+        let profile = bitreader.read_bits(2)? as u8;
+        let sampling_frequency_index = SampleFrequencyIndex::from_u8(bitreader.read_bits(4)? as u8)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid sampling frequency index",
+                )
+            })?;
+        let private_bit = bitreader.read_bit()?;
+        let channel_configuration = bitreader.read_bits(3)? as u8;
+        let original_copy = bitreader.read_bit()?;
+        let home = bitreader.read_bit()?;
+
+        let copyright_identification_bit = bitreader.read_bit()?;
+        let copyright_identification_start = bitreader.read_bit()?;
+        let frame_length = bitreader.read_bits(13)? as u16;
+        let buffer_fullness = bitreader.read_bits(11)? as u16;
+        let number_of_raw_data_blocks = bitreader.read_bits(2)? as u8 + 1;
+
+        let crc = if protection_absent {
+            None
+        } else {
+            Some(bitreader.read_bits(16)? as u16)
+        };
+
+        let header = Self {
+            mpeg2,
+            profile,
+            sampling_frequency_index,
+            private_bit,
+            channel_configuration,
+            original_copy,
+            home,
+            copyright_identification_bit,
+            copyright_identification_start,
+            frame_length,
+            buffer_fullness,
+            number_of_raw_data_blocks,
+            crc,
+        };
+
+        if (frame_length as usize) < header.header_len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ADTS frame_length is smaller than the header",
+            ));
+        }
+
+        Ok(header)
+    }
+
+    /// Creates a header for a single raw data block of `payload_len` bytes.
+    ///
+    /// Only object types 1 to 4 and sampling frequencies with a
+    /// [`SampleFrequencyIndex`] can be represented. The header has no CRC and
+    /// signals a variable bitrate buffer fullness.
+    pub fn from_config(
+        config: &PartialAudioSpecificConfig,
+        payload_len: usize,
+    ) -> io::Result<Self> {
+        let profile = match config.audio_object_type.as_u16() {
+            aot @ 1..=4 => aot as u8 - 1,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "audio object type cannot be represented in ADTS",
+                ));
+            }
+        };
+
+        let sampling_frequency_index = SampleFrequencyIndex::from_freq(config.sampling_frequency)
+            .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sampling frequency cannot be represented in ADTS",
+            )
+        })?;
+
+        if config.channel_configuration > 7 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "channel configuration cannot be represented in ADTS",
+            ));
+        }
+
+        let mut header = Self {
+            mpeg2: false,
+            profile,
+            sampling_frequency_index,
+            private_bit: false,
+            channel_configuration: config.channel_configuration,
+            original_copy: false,
+            home: false,
+            copyright_identification_bit: false,
+            copyright_identification_start: false,
+            frame_length: 0,
+            buffer_fullness: BUFFER_FULLNESS_VBR,
+            number_of_raw_data_blocks: 1,
+            crc: None,
+        };
+
+        let frame_length = header.header_len() + payload_len;
+        if frame_length > MAX_FRAME_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "ADTS frame_length exceeds 8191 bytes",
+            ));
+        }
+        header.frame_length = frame_length as u16;
+
+        Ok(header)
+    }
+
+    /// Returns the length of the header in bytes, 7 or 9 with a CRC.
+    pub const fn header_len(&self) -> usize {
+        if self.crc.is_some() { 9 } else { 7 }
+    }
+
+    /// Returns the length of the payload following the header in bytes.
+    pub const fn payload_len(&self) -> usize {
+        (self.frame_length as usize).saturating_sub(self.header_len())
+    }
+
+    /// Returns the config that describes the frames following this header.
+    pub fn to_audio_specific_config(&self) -> io::Result<PartialAudioSpecificConfig> {
+        let sampling_frequency = self.sampling_frequency_index.to_freq().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid sampling frequency index",
+            )
+        })?;
+
+        Ok(PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::from(self.profile as u16 + 1),
+            sampling_frequency,
+            channel_configuration: self.channel_configuration,
+        })
+    }
+
+    /// Writes the header to the given writer.
+    pub fn mux<T: io::Write>(&self, writer: &mut T) -> io::Result<()> {
+        let mut bit_writer = BitWriter::new(writer);
+
+        bit_writer.write_bits(SYNCWORD, 12)?;
+        bit_writer.write_bit(self.mpeg2)?;
+        bit_writer.write_bits(0, 2)?; // layer
+        bit_writer.write_bit(self.crc.is_none())?; // protection_absent
+        bit_writer.write_bits(self.profile as u64, 2)?;
+        bit_writer.write_bits(self.sampling_frequency_index as u64, 4)?;
+        bit_writer.write_bit(self.private_bit)?;
+        bit_writer.write_bits(self.channel_configuration as u64, 3)?;
+        bit_writer.write_bit(self.original_copy)?;
+        bit_writer.write_bit(self.home)?;
+
+        bit_writer.write_bit(self.copyright_identification_bit)?;
+        bit_writer.write_bit(self.copyright_identification_start)?;
+        bit_writer.write_bits(self.frame_length as u64, 13)?;
+        bit_writer.write_bits(self.buffer_fullness as u64, 11)?;
+        bit_writer.write_bits(self.number_of_raw_data_blocks.saturating_sub(1) as u64, 2)?;
+
+        if let Some(crc) = self.crc {
+            bit_writer.write_bits(crc as u64, 16)?;
+        }
+
+        bit_writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Writes a raw AAC frame prefixed with an ADTS header built from the given config.
+pub fn write_adts_frame<T: io::Write>(
+    writer: &mut T,
+    config: &PartialAudioSpecificConfig,
+    payload: &[u8],
+) -> io::Result<()> {
+    AdtsHeader::from_config(config, payload.len())?.mux(writer)?;
+    writer.write_all(payload)
+}
+
+/// Iterator over the ADTS frames in a buffer.
+///
+/// Yields each header together with the raw payload that follows it. The
+/// iterator stops after the first error.
+pub struct AdtsIterator<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> AdtsIterator<'a> {
+    /// Creates a new iterator over the ADTS frames in `data`.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for AdtsIterator<'a> {
+    type Item = io::Result<(AdtsHeader, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.data.get(self.offset..).filter(|r| !r.is_empty())?;
+
+        let header = match AdtsHeader::parse(remaining) {
+            Ok(header) => header,
+            Err(err) => {
+                self.offset = self.data.len();
+                return Some(Err(err));
+            }
+        };
+
+        let frame_length = header.frame_length as usize;
+        if frame_length > remaining.len() {
+            self.offset = self.data.len();
+            return Some(Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "ADTS frame_length exceeds the remaining data",
+            )));
+        }
+
+        self.offset += frame_length;
+        Some(Ok((header, &remaining[header.header_len()..frame_length])))
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adts_header_parse() {
+        // AAC LC, 44100 Hz, stereo, 371 byte frame, VBR
+        let data = [0xff, 0xf1, 0x50, 0x80, 0x2e, 0x7f, 0xfc];
+        let header = AdtsHeader::parse(&data).unwrap();
+
+        assert!(!header.mpeg2);
+        assert_eq!(header.profile, 1);
+        assert_eq!(
+            header.sampling_frequency_index,
+            SampleFrequencyIndex::Freq44100
+        );
+        assert_eq!(header.channel_configuration, 2);
+        assert_eq!(header.frame_length, 371);
+        assert_eq!(header.buffer_fullness, 0x7ff);
+        assert_eq!(header.number_of_raw_data_blocks, 1);
+        assert_eq!(header.crc, None);
+        assert_eq!(header.header_len(), 7);
+        assert_eq!(header.payload_len(), 364);
+
+        let config = header.to_audio_specific_config().unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 44100);
+        assert_eq!(config.channel_configuration, 2);
+
+        let mut buf = Vec::new();
+        header.mux(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn test_adts_header_parse_with_crc() {
+        // protection_absent=0, AAC LC, 48000 Hz, mono, 11 byte frame, CRC 0xabcd
+        let data = [
+            0xff, 0xf0, 0x4c, 0x40, 0x01, 0x7f, 0xfc, 0xab, 0xcd, 0x01, 0x02,
+        ];
+        let header = AdtsHeader::parse(&data).unwrap();
+
+        assert_eq!(header.crc, Some(0xabcd));
+        assert_eq!(header.header_len(), 9);
+        assert_eq!(header.payload_len(), 2);
+
+        let mut iter = AdtsIterator::new(&data);
+        let (_, payload) = iter.next().unwrap().unwrap();
+        assert_eq!(payload, &[0x01, 0x02]);
+        assert!(iter.next().is_none());
+
+        let mut buf = Vec::new();
+        header.mux(&mut buf).unwrap();
+        assert_eq!(buf, &data[..9]);
+    }
+
+    #[test]
+    fn test_adts_header_parse_errors() {
+        let err = AdtsHeader::parse(&[0xff, 0xe1, 0x50, 0x80, 0x2e, 0x7f, 0xfc]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // frame_length of 6 is smaller than the 7 byte header
+        let err = AdtsHeader::parse(&[0xff, 0xf1, 0x50, 0x80, 0x00, 0xdf, 0xfc]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = AdtsHeader::parse(&[0xff, 0xf1, 0x50]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_adts_iterator() {
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::AacLowComplexity,
+            sampling_frequency: 48000,
+            channel_configuration: 2,
+        };
+
+        let mut data = Vec::new();
+        write_adts_frame(&mut data, &config, &[0xaa; 3]).unwrap();
+        write_adts_frame(&mut data, &config, &[0xbb; 5]).unwrap();
+
+        let frames: Vec<_> = AdtsIterator::new(&data).collect::<io::Result<_>>().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].1, &[0xaa; 3]);
+        assert_eq!(frames[1].1, &[0xbb; 5]);
+        assert_eq!(frames[1].0.to_audio_specific_config().unwrap(), config);
+
+        // Truncated second frame
+        let mut iter = AdtsIterator::new(&data[..data.len() - 1]);
+        assert!(iter.next().unwrap().is_ok());
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_adts_from_config_errors() {
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::Unknown(5),
+            sampling_frequency: 48000,
+            channel_configuration: 2,
+        };
+        let err = AdtsHeader::from_config(&config, 10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::AacLowComplexity,
+            sampling_frequency: 44000,
+            channel_configuration: 2,
+        };
+        let err = AdtsHeader::from_config(&config, 10).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::AacLowComplexity,
+            sampling_frequency: 44100,
+            channel_configuration: 2,
+        };
+        let err = AdtsHeader::from_config(&config, 8190).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,464 @@
+use std::io;
+
+use bytes_util::BitReader;
+
+use crate::{AudioObjectType, SampleFrequencyIndex};
+
+/// `SBR` audio object type
+const AOT_SBR: u16 = 5;
+/// `PS` audio object type
+const AOT_PS: u16 = 29;
+/// `ER BSAC` audio object type
+const AOT_ER_BSAC: u16 = 22;
+
+/// `syncExtensionType` for the backward-compatible SBR extension
+const SYNC_EXTENSION_SBR: u64 = 0x2b7;
+/// `syncExtensionType` for the backward-compatible PS extension
+const SYNC_EXTENSION_PS: u64 = 0x548;
+
+/// Audio Specific Config
+/// ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.19)
+///
+/// Unlike [`PartialAudioSpecificConfig`](crate::PartialAudioSpecificConfig) this also
+/// parses the `GASpecificConfig` and the SBR/PS signaling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct AudioSpecificConfig {
+    /// Audio Object Type of the core codec
+    ///
+    /// For explicitly signaled SBR/PS this is the object type that follows the
+    /// extension sampling frequency, e.g. AAC LC.
+    pub audio_object_type: AudioObjectType,
+    /// Sampling Frequency of the core codec
+    pub sampling_frequency: u32,
+    /// Channel Configuration
+    pub channel_configuration: u8,
+    /// How the SBR extension is signaled
+    pub sbr_signaling: SbrSignaling,
+    /// `extensionAudioObjectType` if an extension is signaled
+    pub extension_audio_object_type: Option<AudioObjectType>,
+    /// Sampling Frequency of the extension if an extension is signaled
+    pub extension_sampling_frequency: Option<u32>,
+    /// `sbrPresentFlag`
+    pub sbr_present: bool,
+    /// `psPresentFlag`
+    pub ps_present: bool,
+    /// `GASpecificConfig` for general audio object types
+    pub ga_specific_config: Option<GaSpecificConfig>,
+}
+
+/// How SBR is signaled in an [`AudioSpecificConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub enum SbrSignaling {
+    /// No SBR signaling was found.
+    ///
+    /// SBR may still be present implicitly in the access units.
+    None,
+    /// Explicit hierarchical signaling (audio object type 5 or 29 followed by the core type)
+    Explicit,
+    /// Backward-compatible signaling using `syncExtensionType` 0x2b7 after the core config
+    BackwardCompatible,
+}
+
+/// GA Specific Config
+/// ISO/IEC 14496-3:2019(E) - 4.4.1 (Table 4.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct GaSpecificConfig {
+    /// `frameLengthFlag`
+    ///
+    /// If set, frames have 960 instead of 1024 samples.
+    pub frame_length_flag: bool,
+    /// `coreCoderDelay` if `dependsOnCoreCoder` is set
+    pub core_coder_delay: Option<u16>,
+    /// `extensionFlag`
+    pub extension_flag: bool,
+    /// The number of channels described by the `program_config_element` if
+    /// `channelConfiguration` is 0
+    pub program_config_channel_count: Option<u8>,
+}
+
+impl AudioSpecificConfig {
+    /// Parse the Audio Specific Config from given bytes
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let mut bitreader = BitReader::new_from_slice(data);
+        let total_bits = data.len() as u64 * 8;
+
+        let (mut audio_object_type, sampling_frequency, channel_configuration) =
+            read_header(&mut bitreader)?;
+
+        let mut config = Self {
+            audio_object_type: audio_object_type.into(),
+            sampling_frequency,
+            channel_configuration,
+            sbr_signaling: SbrSignaling::None,
+            extension_audio_object_type: None,
+            extension_sampling_frequency: None,
+            sbr_present: false,
+            ps_present: false,
+            ga_specific_config: None,
+        };
+
+        if audio_object_type == AOT_SBR || audio_object_type == AOT_PS {
+            config.sbr_signaling = SbrSignaling::Explicit;
+            config.extension_audio_object_type = Some(AudioObjectType::from(AOT_SBR));
+            config.sbr_present = true;
+            config.ps_present = audio_object_type == AOT_PS;
+            config.extension_sampling_frequency = Some(read_sampling_frequency(&mut bitreader)?);
+
+            audio_object_type = read_audio_object_type(&mut bitreader)?;
+            config.audio_object_type = audio_object_type.into();
+            if audio_object_type == AOT_ER_BSAC {
+                bitreader.read_bits(4)?; // extensionChannelConfiguration
+            }
+        }
+
+        match audio_object_type {
+            1 | 2 | 3 | 4 | 6 | 7 | 17 | 19 | 20 | 21 | 22 | 23 => {
+                config.ga_specific_config = Some(GaSpecificConfig::parse(
+                    &mut bitreader,
+                    channel_configuration,
+                    audio_object_type,
+                )?);
+            }
+            // Other object types carry configs this parser does not understand,
+            // so anything after them cannot be located.
+            _ => return Ok(config),
+        }
+
+        if matches!(audio_object_type, 17 | 19..=27 | 39) {
+            let ep_config = bitreader.read_bits(2)?;
+            if ep_config == 2 || ep_config == 3 {
+                // ErrorProtectionSpecificConfig is not supported.
+                return Ok(config);
+            }
+        }
+
+        if config.sbr_signaling != SbrSignaling::Explicit
+            && total_bits.saturating_sub(bitreader.bit_stream_position()?) >= 16
+        {
+            let sync_extension_type = bitreader.read_bits(11)?;
+            if sync_extension_type == SYNC_EXTENSION_SBR {
+                let extension_audio_object_type = read_audio_object_type(&mut bitreader)?;
+                config.extension_audio_object_type = Some(extension_audio_object_type.into());
+
+                if extension_audio_object_type == AOT_SBR {
+                    config.sbr_present = bitreader.read_bit()?;
+                    if config.sbr_present {
+                        config.sbr_signaling = SbrSignaling::BackwardCompatible;
+                        config.extension_sampling_frequency =
+                            Some(read_sampling_frequency(&mut bitreader)?);
+
+                        if total_bits.saturating_sub(bitreader.bit_stream_position()?) >= 12
+                            && bitreader.read_bits(11)? == SYNC_EXTENSION_PS
+                        {
+                            config.ps_present = bitreader.read_bit()?;
+                        }
+                    }
+                } else if extension_audio_object_type == AOT_ER_BSAC {
+                    config.sbr_present = bitreader.read_bit()?;
+                    if config.sbr_present {
+                        config.sbr_signaling = SbrSignaling::BackwardCompatible;
+                        config.extension_sampling_frequency =
+                            Some(read_sampling_frequency(&mut bitreader)?);
+                    }
+                    bitreader.read_bits(4)?; // extensionChannelConfiguration
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the sampling frequency of the decoded output.
+    ///
+    /// When SBR is present this is the extension sampling frequency, which is
+    /// usually twice the core sampling frequency.
+    pub fn output_sampling_frequency(&self) -> u32 {
+        match self.extension_sampling_frequency {
+            Some(frequency) if self.sbr_present => frequency,
+            _ => self.sampling_frequency,
+        }
+    }
+
+    /// Returns the number of channels of the decoded output.
+    ///
+    /// Parametric stereo upmixes a mono core to two channels. Returns `None`
+    /// for reserved channel configurations.
+    pub fn effective_channel_count(&self) -> Option<u8> {
+        let channels = match self.channel_configuration {
+            0 => self
+                .ga_specific_config
+                .and_then(|ga| ga.program_config_channel_count)?,
+            1..=6 => self.channel_configuration,
+            7 | 12 | 14 => 8,
+            11 => 7,
+            13 => 24,
+            _ => return None,
+        };
+
+        if self.ps_present && channels == 1 {
+            Some(2)
+        } else {
+            Some(channels)
+        }
+    }
+}
+
+impl GaSpecificConfig {
+    fn parse<R: io::Read + io::Seek>(
+        bitreader: &mut BitReader<R>,
+        channel_configuration: u8,
+        audio_object_type: u16,
+    ) -> io::Result<Self> {
+        let frame_length_flag = bitreader.read_bit()?;
+        let core_coder_delay = if bitreader.read_bit()? {
+            // dependsOnCoreCoder
+            Some(bitreader.read_bits(14)? as u16)
+        } else {
+            None
+        };
+        let extension_flag = bitreader.read_bit()?;
+
+        let program_config_channel_count = if channel_configuration == 0 {
+            Some(parse_program_config_element(bitreader)?)
+        } else {
+            None
+        };
+
+        if audio_object_type == 6 || audio_object_type == 20 {
+            bitreader.read_bits(3)?; // layerNr
+        }
+
+        if extension_flag {
+            if audio_object_type == AOT_ER_BSAC {
+                bitreader.read_bits(5)?; // numOfSubFrame
+                bitreader.read_bits(11)?; // layer_length
+            }
+            if matches!(audio_object_type, 17 | 19 | 20 | 23) {
+                // aacSectionDataResilienceFlag, aacScalefactorDataResilienceFlag,
+                // aacSpectralDataResilienceFlag
+                bitreader.read_bits(3)?;
+            }
+            bitreader.read_bit()?; // extensionFlag3
+        }
+
+        Ok(Self {
+            frame_length_flag,
+            core_coder_delay,
+            extension_flag,
+            program_config_channel_count,
+        })
+    }
+}
+
+/// Parses a `program_config_element` and returns the number of channels it describes.
+/// ISO/IEC 14496-3:2019(E) - 4.4.1.1 (Table 4.2)
+fn parse_program_config_element<R: io::Read>(bitreader: &mut BitReader<R>) -> io::Result<u8> {
+    bitreader.read_bits(4)?; // element_instance_tag
+    bitreader.read_bits(2)?; // object_type
+    bitreader.read_bits(4)?; // sampling_frequency_index
+
+    let num_front_channel_elements = bitreader.read_bits(4)?;
+    let num_side_channel_elements = bitreader.read_bits(4)?;
+    let num_back_channel_elements = bitreader.read_bits(4)?;
+    let num_lfe_channel_elements = bitreader.read_bits(2)?;
+    let num_assoc_data_elements = bitreader.read_bits(3)?;
+    let num_valid_cc_elements = bitreader.read_bits(4)?;
+
+    if bitreader.read_bit()? {
+        bitreader.read_bits(4)?; // mono_mixdown_element_number
+    }
+    if bitreader.read_bit()? {
+        bitreader.read_bits(4)?; // stereo_mixdown_element_number
+    }
+    if bitreader.read_bit()? {
+        bitreader.read_bits(3)?; // matrix_mixdown_idx, pseudo_surround_enable
+    }
+
+    let mut channels = 0u8;
+    for _ in 0..num_front_channel_elements + num_side_channel_elements + num_back_channel_elements {
+        let is_cpe = bitreader.read_bit()?;
+        bitreader.read_bits(4)?; // element_tag_select
+        channels = channels.saturating_add(if is_cpe { 2 } else { 1 });
+    }
+    for _ in 0..num_lfe_channel_elements {
+        bitreader.read_bits(4)?; // lfe_element_tag_select
+        channels = channels.saturating_add(1);
+    }
+    for _ in 0..num_assoc_data_elements {
+        bitreader.read_bits(4)?; // assoc_data_element_tag_select
+    }
+    for _ in 0..num_valid_cc_elements {
+        bitreader.read_bits(5)?; // cc_element_is_ind_sw, valid_cc_element_tag_select
+    }
+
+    // byte_alignment() relative to the start of the AudioSpecificConfig
+    bitreader.align()?;
+
+    let comment_field_bytes = bitreader.read_bits(8)?;
+    for _ in 0..comment_field_bytes {
+        bitreader.read_bits(8)?; // comment_field_data
+    }
+
+    Ok(channels)
+}
+
+/// Reads the top fields shared by every Audio Specific Config:
+/// the audio object type, sampling frequency and channel configuration.
+pub(crate) fn read_header<R: io::Read>(bitreader: &mut BitReader<R>) -> io::Result<(u16, u32, u8)> {
+    let audio_object_type = read_audio_object_type(bitreader)?;
+    let sampling_frequency = read_sampling_frequency(bitreader)?;
+
+    // 4 Bits to get the channel configuration
+    let channel_configuration = bitreader.read_bits(4)? as u8;
+
+    Ok((audio_object_type, sampling_frequency, channel_configuration))
+}
+
+/// GetAudioObjectType() # ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.20)
+fn read_audio_object_type<R: io::Read>(bitreader: &mut BitReader<R>) -> io::Result<u16> {
+    let mut audio_object_type = bitreader.read_bits(5)? as u16;
+    if audio_object_type == 31 {
+        audio_object_type = 32 + bitreader.read_bits(6)? as u16;
+    }
+    Ok(audio_object_type)
+}
+
+fn read_sampling_frequency<R: io::Read>(bitreader: &mut BitReader<R>) -> io::Result<u32> {
+    // The table calls for us to read a 4-bit value. If the value is type FreqEscape
+    // (0xF), we need to read 24 bits to get the sampling frequency.
+    let sampling_frequency_index = SampleFrequencyIndex::from_u8(bitreader.read_bits(4)? as u8)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid sampling frequency index",
+            )
+        })?;
+
+    match sampling_frequency_index {
+        // Uses the extended sampling frequency to represent the freq as a non-common value
+        SampleFrequencyIndex::FreqEscape => Ok(bitreader.read_bits(24)? as u32),
+        _ => sampling_frequency_index.to_freq().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid sampling frequency index",
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aac_lc() {
+        // AAC LC, 44100 Hz, stereo
+        let config = AudioSpecificConfig::parse(&[0x12, 0x10]).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 44100);
+        assert_eq!(config.channel_configuration, 2);
+        assert_eq!(config.sbr_signaling, SbrSignaling::None);
+        assert!(!config.sbr_present);
+        assert!(!config.ps_present);
+        assert_eq!(
+            config.ga_specific_config,
+            Some(GaSpecificConfig {
+                frame_length_flag: false,
+                core_coder_delay: None,
+                extension_flag: false,
+                program_config_channel_count: None,
+            })
+        );
+        assert_eq!(config.output_sampling_frequency(), 44100);
+        assert_eq!(config.effective_channel_count(), Some(2));
+    }
+
+    #[test]
+    fn test_he_aac_v1_explicit() {
+        // SBR, 24000 Hz core, stereo, 48000 Hz extension, AAC LC
+        let config = AudioSpecificConfig::parse(&[0x2b, 0x11, 0x88, 0x00]).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 24000);
+        assert_eq!(config.channel_configuration, 2);
+        assert_eq!(config.sbr_signaling, SbrSignaling::Explicit);
+        assert!(config.sbr_present);
+        assert!(!config.ps_present);
+        assert_eq!(config.extension_sampling_frequency, Some(48000));
+        assert_eq!(config.output_sampling_frequency(), 48000);
+        assert_eq!(config.effective_channel_count(), Some(2));
+    }
+
+    #[test]
+    fn test_he_aac_v1_backward_compatible() {
+        // AAC LC, 24000 Hz, stereo, then syncExtensionType 0x2b7, SBR, 48000 Hz
+        let config = AudioSpecificConfig::parse(&[0x13, 0x10, 0x56, 0xe5, 0x98]).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 24000);
+        assert_eq!(config.sbr_signaling, SbrSignaling::BackwardCompatible);
+        assert_eq!(
+            config.extension_audio_object_type,
+            Some(AudioObjectType::Unknown(5))
+        );
+        assert!(config.sbr_present);
+        assert!(!config.ps_present);
+        assert_eq!(config.output_sampling_frequency(), 48000);
+    }
+
+    #[test]
+    fn test_he_aac_v2_explicit() {
+        // PS, 24000 Hz core, mono, 48000 Hz extension, AAC LC
+        let config = AudioSpecificConfig::parse(&[0xeb, 0x09, 0x88, 0x00]).unwrap();
+        assert_eq!(config.audio_object_type, AudioObjectType::AacLowComplexity);
+        assert_eq!(config.sampling_frequency, 24000);
+        assert_eq!(config.channel_configuration, 1);
+        assert_eq!(config.sbr_signaling, SbrSignaling::Explicit);
+        assert!(config.sbr_present);
+        assert!(config.ps_present);
+        assert_eq!(config.output_sampling_frequency(), 48000);
+        assert_eq!(config.effective_channel_count(), Some(2));
+    }
+
+    #[test]
+    fn test_he_aac_v2_backward_compatible() {
+        // AAC LC, 24000 Hz, mono, then 0x2b7 SBR 48000 Hz and 0x548 PS
+        let config =
+            AudioSpecificConfig::parse(&[0x13, 0x08, 0x56, 0xe5, 0x9d, 0x48, 0x80]).unwrap();
+        assert_eq!(config.sbr_signaling, SbrSignaling::BackwardCompatible);
+        assert!(config.sbr_present);
+        assert!(config.ps_present);
+        assert_eq!(config.output_sampling_frequency(), 48000);
+        assert_eq!(config.effective_channel_count(), Some(2));
+    }
+
+    #[test]
+    fn test_program_config_element() {
+        // AAC LC, 48000 Hz, channel configuration 0 followed by a PCE with
+        // one front CPE, one front SCE, one back CPE and one LFE (5.1)
+        let config = AudioSpecificConfig::parse(&[
+            0x11, 0x80, 0x04, 0xc8, 0x05, 0x00, 0x20, 0x18, 0x00, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(config.channel_configuration, 0);
+        assert_eq!(
+            config
+                .ga_specific_config
+                .and_then(|ga| ga.program_config_channel_count),
+            Some(6)
+        );
+        assert_eq!(config.effective_channel_count(), Some(6));
+    }
+
+    #[test]
+    fn test_frame_length_and_core_coder_delay() {
+        // AAC LC, 48000 Hz, mono, frameLengthFlag=1, dependsOnCoreCoder=1 (delay 0x1234), extensionFlag=0
+        let config = AudioSpecificConfig::parse(&[0x11, 0x8e, 0x91, 0xa0]).unwrap();
+        let ga = config.ga_specific_config.unwrap();
+        assert!(ga.frame_length_flag);
+        assert_eq!(ga.core_coder_delay, Some(0x1234));
+        assert!(!ga.extension_flag);
+    }
+}
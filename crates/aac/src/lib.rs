@@ -1,4 +1,4 @@
-//! A crate for decoding AAC audio headers.
+//! A crate for decoding and encoding AAC audio headers.
 //!
 //! ## License
 //!
@@ -13,13 +13,19 @@
 
 use std::io;
 
-use bytes_util::BitReader;
+use bytes_util::{BitReader, BitWriter};
+
+mod adts;
+mod config;
+
+pub use adts::{AdtsHeader, AdtsIterator, write_adts_frame};
+pub use config::{AudioSpecificConfig, GaSpecificConfig, SbrSignaling};
 
 /// A Partial Audio Specific Config
 /// ISO/IEC 14496-3:2019(E) - 1.6
 ///
 /// This struct does not represent the full AudioSpecificConfig, it only
-/// represents the top few fields.
+/// represents the top few fields. See [`AudioSpecificConfig`] for the full config.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[must_use]
 pub struct PartialAudioSpecificConfig {
@@ -146,6 +152,26 @@ impl SampleFrequencyIndex {
         }
     }
 
+    /// Convert a frequency in Hz to a `SampleFrequencyIndex`, returning `None` if it is not in the table.
+    pub const fn from_freq(freq: u32) -> Option<Self> {
+        match freq {
+            96000 => Some(Self::Freq96000),
+            88200 => Some(Self::Freq88200),
+            64000 => Some(Self::Freq64000),
+            48000 => Some(Self::Freq48000),
+            44100 => Some(Self::Freq44100),
+            32000 => Some(Self::Freq32000),
+            24000 => Some(Self::Freq24000),
+            22050 => Some(Self::Freq22050),
+            16000 => Some(Self::Freq16000),
+            12000 => Some(Self::Freq12000),
+            11025 => Some(Self::Freq11025),
+            8000 => Some(Self::Freq8000),
+            7350 => Some(Self::Freq7350),
+            _ => None,
+        }
+    }
+
     /// Convert the SampleFrequencyIndex to the actual frequency in Hz
     pub const fn to_freq(&self) -> Option<u32> {
         match self {
@@ -179,36 +205,8 @@ impl PartialAudioSpecificConfig {
     /// - Channel Configuration
     pub fn parse(data: &[u8]) -> io::Result<Self> {
         let mut bitreader = BitReader::new_from_slice(data);
-
-        // GetAudioObjectType() # ISO/IEC 14496-3:2019(E) - 1.6.2.1 (Table 1.20)
-        let mut audio_object_type = bitreader.read_bits(5)? as u16;
-        if audio_object_type == 31 {
-            audio_object_type = 32 + bitreader.read_bits(6)? as u16;
-        }
-
-        // The table calls for us to read a 4-bit value. If the value is type FreqEscape
-        // (0xF), we need to read 24 bits to get the sampling frequency.
-        let sampling_frequency_index = SampleFrequencyIndex::from_u8(bitreader.read_bits(4)? as u8)
-            .ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid sampling frequency index",
-                )
-            })?;
-
-        let sampling_frequency = match sampling_frequency_index {
-            // Uses the extended sampling frequency to represent the freq as a non-common value
-            SampleFrequencyIndex::FreqEscape => bitreader.read_bits(24)? as u32,
-            _ => sampling_frequency_index.to_freq().ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Invalid sampling frequency index",
-                )
-            })?,
-        };
-
-        // 4 Bits to get the channel configuration
-        let channel_configuration = bitreader.read_bits(4)? as u8;
+        let (audio_object_type, sampling_frequency, channel_configuration) =
+            config::read_header(&mut bitreader)?;
 
         Ok(Self {
             audio_object_type: audio_object_type.into(),
@@ -216,6 +214,46 @@ impl PartialAudioSpecificConfig {
             channel_configuration,
         })
     }
+
+    /// Writes the Audio Specific Config to the given writer.
+    ///
+    /// The fields are followed by an all-zero GASpecificConfig (1024 sample
+    /// frames, no core coder, no extension), which is what raw AAC in FLV
+    /// and MP4 expects for AAC LC.
+    pub fn mux<T: io::Write>(&self, writer: &mut T) -> io::Result<()> {
+        let mut bit_writer = BitWriter::new(writer);
+
+        let audio_object_type = self.audio_object_type.as_u16();
+        if audio_object_type == 31 {
+            // 31 is the escape value, it cannot be written as an object type
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "audio object type 31 is reserved as escape value",
+            ));
+        }
+        if audio_object_type > 31 {
+            bit_writer.write_bits(31, 5)?;
+            bit_writer.write_bits((audio_object_type - 32) as u64 & 0x3f, 6)?;
+        } else {
+            bit_writer.write_bits(audio_object_type as u64, 5)?;
+        }
+
+        match SampleFrequencyIndex::from_freq(self.sampling_frequency) {
+            Some(index) => bit_writer.write_bits(index as u64, 4)?,
+            None => {
+                bit_writer.write_bits(SampleFrequencyIndex::FreqEscape as u64, 4)?;
+                bit_writer.write_bits(self.sampling_frequency as u64 & 0xff_ffff, 24)?;
+            }
+        }
+
+        bit_writer.write_bits(self.channel_configuration as u64 & 0xf, 4)?;
+
+        // frameLengthFlag, dependsOnCoreCoder, extensionFlag
+        bit_writer.write_bits(0, 3)?;
+
+        bit_writer.finish()?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -236,6 +274,38 @@ mod tests {
         assert_eq!(config.channel_configuration, 2);
     }
 
+    #[test]
+    fn test_aac_config_mux() {
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::AacLowComplexity,
+            sampling_frequency: 44100,
+            channel_configuration: 2,
+        };
+
+        let mut buf = Vec::new();
+        config.mux(&mut buf).unwrap();
+        assert_eq!(buf, [0x12, 0x10]);
+        assert_eq!(PartialAudioSpecificConfig::parse(&buf).unwrap(), config);
+
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::Unknown(42),
+            sampling_frequency: 44000,
+            channel_configuration: 1,
+        };
+
+        let mut buf = Vec::new();
+        config.mux(&mut buf).unwrap();
+        assert_eq!(PartialAudioSpecificConfig::parse(&buf).unwrap(), config);
+
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::Unknown(31),
+            sampling_frequency: 44100,
+            channel_configuration: 2,
+        };
+        let err = config.mux(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
     #[test]
     fn test_idx_to_freq() {
         let cases = [
@@ -259,6 +329,9 @@ mod tests {
 
         for (idx, freq) in cases {
             assert_eq!(freq, idx.to_freq(), "Expected frequency for {idx:?}");
+            if let Some(freq) = freq {
+                assert_eq!(SampleFrequencyIndex::from_freq(freq), Some(idx));
+            }
         }
     }
 }
@@ -0,0 +1,15 @@
+#![no_main]
+
+use amf0::Amf0Decoder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = Amf0Decoder::new(data);
+    while !decoder.is_empty() {
+        if decoder.decode().is_err() {
+            break;
+        }
+    }
+
+    let _ = Amf0Decoder::new(data).decode_all_lossy(64);
+});
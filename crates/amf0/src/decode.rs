@@ -17,6 +17,10 @@ pub struct LossyDecodeResult<'a> {
     pub error: Option<Amf0ReadError>,
 }
 
+/// How deeply objects and arrays can be nested before decoding fails, so that
+/// crafted input cannot overflow the stack.
+pub(crate) const MAX_NESTING_DEPTH: usize = 64;
+
 /// An AMF0 Decoder.
 ///
 /// This decoder takes a reference to a byte slice and reads the AMF0 data from
@@ -25,12 +29,17 @@ pub struct LossyDecodeResult<'a> {
 pub struct Amf0Decoder<'a> {
     data: &'a [u8],
     pos: usize,
+    depth: usize,
 }
 
 impl<'a> Amf0Decoder<'a> {
     /// Create a new AMF0 decoder.
     pub const fn new(data: &'a [u8]) -> Self {
-        Self { data, pos: 0 }
+        Self {
+            data,
+            pos: 0,
+            depth: 0,
+        }
     }
 
     /// Check if the decoder has reached the end of the AMF0 data.
@@ -168,17 +177,37 @@ impl<'a> Amf0Decoder<'a> {
             Amf0Marker::Number => Ok(Amf0Value::Number(self.read_number()?)),
             Amf0Marker::Boolean => Ok(Amf0Value::Boolean(self.read_bool()?)),
             Amf0Marker::String => Ok(Amf0Value::String(self.read_string()?)),
-            Amf0Marker::Object => Ok(Amf0Value::Object(self.read_object()?.into())),
+            Amf0Marker::Object => Ok(Amf0Value::Object(self.nested(Self::read_object)?.into())),
             Amf0Marker::Null => Ok(Amf0Value::Null),
             Amf0Marker::Undefined => Ok(Amf0Value::Undefined),
-            Amf0Marker::EcmaArray => Ok(Amf0Value::EcmaArray(self.read_ecma_array()?.into())),
+            Amf0Marker::EcmaArray => Ok(Amf0Value::EcmaArray(
+                self.nested(Self::read_ecma_array)?.into(),
+            )),
             Amf0Marker::LongString => Ok(Amf0Value::LongString(self.read_long_string()?)),
-            Amf0Marker::StrictArray => Ok(Amf0Value::StrictArray(self.read_strict_array()?.into())),
+            Amf0Marker::StrictArray => Ok(Amf0Value::StrictArray(
+                self.nested(Self::read_strict_array)?.into(),
+            )),
             Amf0Marker::Date => self.read_date(),
+            Amf0Marker::XmlDocument => Ok(Amf0Value::XmlDocument(self.read_long_string()?)),
             _ => Err(Amf0ReadError::UnsupportedType(marker)),
         }
     }
 
+    /// Read the contents of an object or array one nesting level deeper.
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, Amf0ReadError>,
+    ) -> Result<T, Amf0ReadError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(Amf0ReadError::NestingTooDeep(MAX_NESTING_DEPTH));
+        }
+
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
     /// Read the next encoded value from the decoder and check if it matches the
     /// specified marker.
     pub fn decode_with_type(
@@ -260,6 +289,12 @@ impl<'a> Amf0Decoder<'a> {
         let mut properties = Vec::new();
 
         for _ in 0..len {
+            // Some encoders declare more properties than they write and
+            // terminate the array early with the object end marker.
+            if self.is_read_object_eof()? {
+                return Ok(properties);
+            }
+
             let key = self.read_string()?;
             let val = self.decode()?;
             properties.push((key, val));
@@ -282,7 +317,9 @@ impl<'a> Amf0Decoder<'a> {
     fn read_strict_array(&mut self) -> Result<Vec<Amf0Value<'a>>, Amf0ReadError> {
         let len = self.read_u32_be()?;
 
-        let mut values = Vec::with_capacity(len as usize);
+        // Every value takes at least a byte, a longer length is not to be trusted
+        let remaining = self.data.len() - self.pos;
+        let mut values = Vec::with_capacity((len as usize).min(remaining));
 
         for _ in 0..len {
             let val = self.decode()?;
@@ -314,6 +351,36 @@ impl<'a> Iterator for Amf0Decoder<'a> {
     }
 }
 
+/// onMetaData script data written by OBS Studio 30
+#[cfg(test)]
+pub(crate) const OBS_ON_METADATA: &[u8] = &[
+    0x02, 0x00, 0x0a, 0x6f, 0x6e, 0x4d, 0x65, 0x74, 0x61, 0x44, 0x61, 0x74, 0x61, 0x08, 0x00, 0x00,
+    0x00, 0x14, 0x00, 0x08, 0x64, 0x75, 0x72, 0x61, 0x74, 0x69, 0x6f, 0x6e, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x66, 0x69, 0x6c, 0x65, 0x53, 0x69, 0x7a, 0x65, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x77, 0x69, 0x64, 0x74, 0x68, 0x00,
+    0x40, 0x9e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x68, 0x65, 0x69, 0x67, 0x68, 0x74,
+    0x00, 0x40, 0x90, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x76, 0x69, 0x64, 0x65, 0x6f,
+    0x63, 0x6f, 0x64, 0x65, 0x63, 0x69, 0x64, 0x00, 0x40, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x0d, 0x76, 0x69, 0x64, 0x65, 0x6f, 0x64, 0x61, 0x74, 0x61, 0x72, 0x61, 0x74, 0x65, 0x00,
+    0x40, 0xa3, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x66, 0x72, 0x61, 0x6d, 0x65, 0x72,
+    0x61, 0x74, 0x65, 0x00, 0x40, 0x4e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x61, 0x75,
+    0x64, 0x69, 0x6f, 0x63, 0x6f, 0x64, 0x65, 0x63, 0x69, 0x64, 0x00, 0x40, 0x24, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x0d, 0x61, 0x75, 0x64, 0x69, 0x6f, 0x64, 0x61, 0x74, 0x61, 0x72, 0x61,
+    0x74, 0x65, 0x00, 0x40, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x61, 0x75, 0x64,
+    0x69, 0x6f, 0x73, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x72, 0x61, 0x74, 0x65, 0x00, 0x40, 0xe7, 0x70,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x61, 0x75, 0x64, 0x69, 0x6f, 0x73, 0x61, 0x6d, 0x70,
+    0x6c, 0x65, 0x73, 0x69, 0x7a, 0x65, 0x00, 0x40, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x0d, 0x61, 0x75, 0x64, 0x69, 0x6f, 0x63, 0x68, 0x61, 0x6e, 0x6e, 0x65, 0x6c, 0x73, 0x00, 0x40,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x73, 0x74, 0x65, 0x72, 0x65, 0x6f, 0x01,
+    0x01, 0x00, 0x03, 0x32, 0x2e, 0x31, 0x01, 0x00, 0x00, 0x03, 0x33, 0x2e, 0x31, 0x01, 0x00, 0x00,
+    0x03, 0x34, 0x2e, 0x30, 0x01, 0x00, 0x00, 0x03, 0x34, 0x2e, 0x31, 0x01, 0x00, 0x00, 0x03, 0x35,
+    0x2e, 0x31, 0x01, 0x00, 0x00, 0x03, 0x37, 0x2e, 0x31, 0x01, 0x00, 0x00, 0x07, 0x65, 0x6e, 0x63,
+    0x6f, 0x64, 0x65, 0x72, 0x02, 0x00, 0x29, 0x6f, 0x62, 0x73, 0x2d, 0x6f, 0x75, 0x74, 0x70, 0x75,
+    0x74, 0x20, 0x6d, 0x6f, 0x64, 0x75, 0x6c, 0x65, 0x20, 0x28, 0x6c, 0x69, 0x62, 0x6f, 0x62, 0x73,
+    0x20, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x20, 0x33, 0x30, 0x2e, 0x30, 0x2e, 0x32, 0x29,
+    0x00, 0x00, 0x09,
+];
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
@@ -407,6 +474,77 @@ mod tests {
         assert!(amf_reader.is_empty());
     }
 
+    #[test]
+    fn test_reader_ecma_array_early_object_end() {
+        let mut amf0_object = vec![0x08, 0x00, 0x00, 0x00, 0x03]; // 3 properties declared
+        amf0_object.extend_from_slice(&[0x00, 0x04]); // 4 bytes
+        amf0_object.extend_from_slice(b"test");
+        amf0_object.extend_from_slice(&[0x05]); // null
+        amf0_object.extend_from_slice(&[0x00, 0x00, 0x09]); // object end after 1
+        amf0_object.extend_from_slice(&[0x01, 0x01]); // Boolean true
+
+        let mut amf_reader = Amf0Decoder::new(&amf0_object);
+        let value = amf_reader.decode_with_type(Amf0Marker::EcmaArray).unwrap();
+
+        assert_eq!(
+            value,
+            Amf0Value::EcmaArray(vec![("test".into(), Amf0Value::Null)].into())
+        );
+        assert_eq!(amf_reader.decode().unwrap(), Amf0Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_reader_xml_document() {
+        let mut amf0_xml = vec![0x0f, 0x00, 0x00, 0x00, 0x0a]; // 10 bytes
+        amf0_xml.extend_from_slice(b"<a>b</a>\r\n");
+
+        let mut amf_reader = Amf0Decoder::new(&amf0_xml);
+        let value = amf_reader
+            .decode_with_type(Amf0Marker::XmlDocument)
+            .unwrap();
+        assert_eq!(value, Amf0Value::XmlDocument(Cow::Borrowed("<a>b</a>\r\n")));
+        assert!(amf_reader.is_empty());
+    }
+
+    #[test]
+    fn test_reader_obs_on_metadata() {
+        let mut amf_reader = Amf0Decoder::new(OBS_ON_METADATA);
+        let (values, error) = amf_reader.decode_all();
+        assert!(error.is_none());
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].as_str(), Some("onMetaData"));
+
+        let metadata = &values[1];
+        assert_eq!(metadata.marker(), Amf0Marker::EcmaArray);
+        assert_eq!(metadata.as_object_properties().map(|p| p.len()), Some(20));
+        assert_eq!(
+            metadata.get("width").and_then(|v| v.as_number()),
+            Some(1920.0)
+        );
+        assert_eq!(
+            metadata.get("height").and_then(|v| v.as_number()),
+            Some(1080.0)
+        );
+        assert_eq!(
+            metadata.get("framerate").and_then(|v| v.as_number()),
+            Some(60.0)
+        );
+        assert_eq!(
+            metadata.get("videocodecid").and_then(|v| v.as_number()),
+            Some(7.0)
+        );
+        assert_eq!(
+            metadata.get("audiosamplerate").and_then(|v| v.as_number()),
+            Some(48000.0)
+        );
+        assert_eq!(metadata.get("stereo").and_then(|v| v.as_bool()), Some(true));
+        assert_eq!(
+            metadata.get("encoder").and_then(|v| v.as_str()),
+            Some("obs-output module (libobs version 30.0.2)")
+        );
+        assert_eq!(metadata.get("missing"), None);
+    }
+
     #[test]
     fn test_reader_strict_array() {
         let mut amf0_array = vec![0x0a, 0x00, 0x00, 0x00, 0x03]; // StrictArray marker with 3 elements
@@ -554,6 +692,48 @@ mod tests {
         assert!(matches!(result, Err(Amf0ReadError::Io(_))));
     }
 
+    #[test]
+    fn test_deep_nesting_returns_error() {
+        // Objects nested inside each other under the key "a", never closed
+        let mut nested = Vec::new();
+        for _ in 0..10_000 {
+            nested.extend_from_slice(&[0x03, 0x00, 0x01, b'a']);
+        }
+        let mut reader = Amf0Decoder::new(&nested);
+        let result = reader.decode();
+        assert!(matches!(
+            result,
+            Err(Amf0ReadError::NestingTooDeep(MAX_NESTING_DEPTH))
+        ));
+
+        // Strict arrays of a single strict array
+        let mut nested = Vec::new();
+        for _ in 0..10_000 {
+            nested.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x01]);
+        }
+        let mut reader = Amf0Decoder::new(&nested);
+        let result = reader.decode();
+        assert!(matches!(result, Err(Amf0ReadError::NestingTooDeep(_))));
+
+        // Nesting up to the limit is fine
+        let mut nested = Vec::new();
+        for _ in 0..MAX_NESTING_DEPTH {
+            nested.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x01]);
+        }
+        nested.push(0x05);
+        let mut reader = Amf0Decoder::new(&nested);
+        assert!(reader.decode().is_ok());
+    }
+
+    #[test]
+    fn test_strict_array_length_not_trusted() {
+        // Claims u32::MAX values but holds a single one
+        let data = [0x0a, 0xff, 0xff, 0xff, 0xff, 0x05];
+        let mut reader = Amf0Decoder::new(&data);
+        let result = reader.decode();
+        assert!(matches!(result, Err(Amf0ReadError::Io(_))));
+    }
+
     #[test]
     fn test_date_round_trip() {
         use crate::Amf0Encoder;
@@ -113,6 +113,8 @@ pub enum Amf0Value<'a> {
     },
     /// LongString Type defined section 2.14
     LongString(Cow<'a, str>),
+    /// XML Document Type defined section 2.17
+    XmlDocument(Cow<'a, str>),
 }
 
 impl<'a> Amf0Value<'a> {
@@ -130,6 +132,7 @@ impl<'a> Amf0Value<'a> {
             Self::StrictArray(_) => Amf0Marker::StrictArray,
             Self::Date { .. } => Amf0Marker::Date,
             Self::LongString(_) => Amf0Marker::LongString,
+            Self::XmlDocument(_) => Amf0Marker::XmlDocument,
         }
     }
 
@@ -144,6 +147,7 @@ impl<'a> Amf0Value<'a> {
             Self::Boolean(b) => Amf0Value::Boolean(*b),
             Self::String(s) => Amf0Value::String(Cow::Owned(s.to_string())),
             Self::LongString(s) => Amf0Value::LongString(Cow::Owned(s.to_string())),
+            Self::XmlDocument(s) => Amf0Value::XmlDocument(Cow::Owned(s.to_string())),
             Self::Object(o) => Amf0Value::Object(
                 o.iter()
                     .map(|(k, v)| (Cow::Owned(k.to_string()), v.into_owned()))
@@ -219,6 +223,39 @@ impl<'a> Amf0Value<'a> {
             _ => None,
         }
     }
+
+    /// Returns the `(timestamp, timezone)` pair if this is a `Date`,
+    /// or `None` otherwise.
+    #[inline]
+    pub fn as_date(&self) -> Option<(f64, i16)> {
+        match self {
+            Self::Date {
+                timestamp,
+                timezone,
+            } => Some((*timestamp, *timezone)),
+            _ => None,
+        }
+    }
+
+    /// Returns the inner XML string if this is an `XmlDocument`,
+    /// or `None` otherwise.
+    #[inline]
+    pub fn as_xml(&self) -> Option<&str> {
+        match self {
+            Self::XmlDocument(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the first property named `key` if this is an
+    /// `Object` or `EcmaArray`, or `None` otherwise.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&Amf0Value<'a>> {
+        self.as_object_properties()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
 }
 
 #[cfg(test)]
@@ -260,6 +297,10 @@ mod tests {
                 },
                 Amf0Marker::Date,
             ),
+            (
+                Amf0Value::XmlDocument(Cow::Borrowed("<a/>")),
+                Amf0Marker::XmlDocument,
+            ),
         ];
 
         for (value, marker) in cases {
@@ -328,6 +369,13 @@ mod tests {
             )]))
         );
 
+        let value = Amf0Value::XmlDocument(Cow::Borrowed("<a/>"));
+        let owned = value.into_owned();
+        assert_eq!(
+            owned,
+            Amf0Value::XmlDocument(Cow::Owned("<a/>".to_string()))
+        );
+
         let value = Amf0Value::Date {
             timestamp: 1234567890.0,
             timezone: -120,
@@ -399,9 +447,27 @@ mod tests {
         assert!(ecma.as_object_properties().is_some());
         assert!(Amf0Value::Null.as_object_properties().is_none());
 
+        assert_eq!(obj.get("k"), Some(&Amf0Value::Null));
+        assert_eq!(ecma.get("k"), Some(&Amf0Value::Null));
+        assert_eq!(ecma.get("missing"), None);
+        assert_eq!(Amf0Value::Null.get("k"), None);
+
         let arr = Amf0Value::StrictArray(Cow::Borrowed(&[Amf0Value::Number(1.0)]));
         assert!(arr.as_array().is_some());
         assert!(Amf0Value::Null.as_array().is_none());
+
+        let date = Amf0Value::Date {
+            timestamp: 1000.0,
+            timezone: -60,
+        };
+        assert_eq!(date.as_date(), Some((1000.0, -60)));
+        assert_eq!(Amf0Value::Null.as_date(), None);
+
+        assert_eq!(
+            Amf0Value::XmlDocument(Cow::Borrowed("<a/>")).as_xml(),
+            Some("<a/>")
+        );
+        assert_eq!(Amf0Value::String(Cow::Borrowed("<a/>")).as_xml(), None);
     }
 
     #[test]
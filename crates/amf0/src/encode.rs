@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::io;
 
 use byteorder::{BigEndian, WriteBytesExt};
+use bytes::{BufMut, BytesMut};
 
 use super::define::Amf0Marker;
 use super::{Amf0Value, Amf0WriteError};
@@ -13,10 +14,15 @@ pub struct Amf0Encoder;
 
 impl Amf0Encoder {
     /// Encode a generic AMF0 value
+    ///
+    /// Strings longer than `u16::MAX` bytes are written with the LongString marker.
     pub fn encode(writer: &mut impl io::Write, value: &Amf0Value) -> Result<(), Amf0WriteError> {
         match value {
             Amf0Value::Number(val) => Self::encode_number(writer, *val),
             Amf0Value::Boolean(val) => Self::encode_bool(writer, *val),
+            Amf0Value::String(val) if val.len() > u16::MAX as usize => {
+                Self::encode_long_string(writer, val)
+            }
             Amf0Value::String(val) => Self::encode_string(writer, val),
             Amf0Value::Object(val) => Self::encode_object(writer, val),
             Amf0Value::Null => Self::encode_null(writer),
@@ -28,9 +34,53 @@ impl Amf0Encoder {
                 timezone,
             } => Self::encode_date(writer, *timestamp, *timezone),
             Amf0Value::LongString(val) => Self::encode_long_string(writer, val),
+            Amf0Value::XmlDocument(val) => Self::encode_xml_document(writer, val),
+        }
+    }
+
+    /// Returns the number of bytes [`encode`](Self::encode) writes for the given value.
+    pub fn encoded_size(value: &Amf0Value) -> usize {
+        match value {
+            Amf0Value::Number(_) => 1 + 8,
+            Amf0Value::Boolean(_) => 1 + 1,
+            Amf0Value::String(val) if val.len() > u16::MAX as usize => 1 + 4 + val.len(),
+            Amf0Value::String(val) => 1 + 2 + val.len(),
+            Amf0Value::Object(val) => 1 + Self::properties_size(val) + 3,
+            Amf0Value::Null | Amf0Value::Undefined => 1,
+            Amf0Value::EcmaArray(val) => 1 + 4 + Self::properties_size(val) + 3,
+            Amf0Value::StrictArray(val) => {
+                1 + 4 + val.iter().map(Self::encoded_size).sum::<usize>()
+            }
+            Amf0Value::Date { .. } => 1 + 8 + 2,
+            Amf0Value::LongString(val) | Amf0Value::XmlDocument(val) => 1 + 4 + val.len(),
         }
     }
 
+    /// Returns the encoded size of the properties of an object or ECMA array,
+    /// excluding the marker, count and object end marker.
+    fn properties_size(properties: &[(Cow<'_, str>, Amf0Value<'_>)]) -> usize {
+        properties
+            .iter()
+            .map(|(key, value)| 2 + key.len() + Self::encoded_size(value))
+            .sum()
+    }
+
+    /// Encode a generic AMF0 value into a buffer, reserving the required
+    /// capacity up front.
+    pub fn encode_into(buf: &mut BytesMut, value: &Amf0Value) -> Result<(), Amf0WriteError> {
+        buf.reserve(Self::encoded_size(value));
+        Self::encode(&mut buf.writer(), value)
+    }
+
+    /// Encode an AMF0 object into a buffer, reserving the required capacity up front.
+    pub fn encode_object_with_capacity(
+        buf: &mut BytesMut,
+        properties: &[(Cow<'_, str>, Amf0Value<'_>)],
+    ) -> Result<(), Amf0WriteError> {
+        buf.reserve(1 + Self::properties_size(properties) + 3);
+        Self::encode_object(&mut buf.writer(), properties)
+    }
+
     /// Write object end marker to signify the end of an AMF0 object
     pub fn object_eof(writer: &mut impl io::Write) -> Result<(), Amf0WriteError> {
         writer.write_u24::<BigEndian>(Amf0Marker::ObjectEnd as u32)?;
@@ -44,6 +94,10 @@ impl Amf0Encoder {
         writer: &mut impl io::Write,
         key: &str,
     ) -> Result<(), Amf0WriteError> {
+        if key.len() > (u16::MAX as usize) {
+            return Err(Amf0WriteError::NormalStringTooLong);
+        }
+
         writer.write_u16::<BigEndian>(key.len() as u16)?;
         writer.write_all(key.as_bytes())?;
         Ok(())
@@ -100,6 +154,17 @@ impl Amf0Encoder {
         Ok(())
     }
 
+    /// Encode an AMF0 XML document (u32 length prefix)
+    pub fn encode_xml_document(
+        writer: &mut impl io::Write,
+        value: &str,
+    ) -> Result<(), Amf0WriteError> {
+        writer.write_u8(Amf0Marker::XmlDocument as u8)?;
+        writer.write_u32::<BigEndian>(value.len() as u32)?;
+        writer.write_all(value.as_bytes())?;
+        Ok(())
+    }
+
     /// Encode an AMF0 null
     pub fn encode_null(writer: &mut impl io::Write) -> Result<(), Amf0WriteError> {
         writer.write_u8(Amf0Marker::Null as u8)?;
@@ -249,6 +314,17 @@ mod tests {
         assert_eq!(vec, expected);
     }
 
+    #[test]
+    fn test_write_xml_document() {
+        let mut expected = vec![0x0f, 0x00, 0x00, 0x00, 0x04];
+        expected.extend_from_slice(b"<a/>");
+
+        let mut vec = Vec::<u8>::new();
+        Amf0Encoder::encode(&mut vec, &Amf0Value::XmlDocument(Cow::Borrowed("<a/>"))).unwrap();
+
+        assert_eq!(vec, expected);
+    }
+
     #[test]
     fn test_write_null() {
         let amf0_null = vec![0x05];
@@ -466,4 +542,150 @@ mod tests {
 
         assert_eq!(vec, expected);
     }
+
+    #[test]
+    fn test_encode_generic_string_promotes_to_long_string() {
+        let long_string = "a".repeat(u16::MAX as usize + 1);
+        let value = Amf0Value::String(Cow::Borrowed(&long_string));
+
+        let mut vec = Vec::<u8>::new();
+        Amf0Encoder::encode(&mut vec, &value).unwrap();
+
+        assert_eq!(vec[0], Amf0Marker::LongString as u8);
+        assert_eq!(&vec[1..5], &(long_string.len() as u32).to_be_bytes());
+        assert_eq!(vec.len(), Amf0Encoder::encoded_size(&value));
+    }
+
+    #[test]
+    fn test_write_property_key_too_long() {
+        let long_key = "a".repeat(u16::MAX as usize + 1);
+        let result = Amf0Encoder::write_property_key(&mut Vec::<u8>::new(), &long_key);
+        assert!(matches!(result, Err(Amf0WriteError::NormalStringTooLong)));
+    }
+
+    #[test]
+    fn test_encode_into() {
+        let value = Amf0Value::EcmaArray(
+            vec![
+                ("duration".into(), Amf0Value::Number(120.5)),
+                ("encoder".into(), Amf0Value::String("test".into())),
+            ]
+            .into(),
+        );
+
+        let mut buf = BytesMut::new();
+        Amf0Encoder::encode_into(&mut buf, &value).unwrap();
+        assert!(buf.capacity() >= Amf0Encoder::encoded_size(&value));
+
+        let mut vec = Vec::<u8>::new();
+        Amf0Encoder::encode(&mut vec, &value).unwrap();
+        assert_eq!(buf.as_ref(), vec.as_slice());
+    }
+
+    #[test]
+    fn test_encode_object_with_capacity() {
+        let properties = [("test".into(), Amf0Value::Null)];
+
+        let mut buf = BytesMut::new();
+        Amf0Encoder::encode_object_with_capacity(&mut buf, &properties).unwrap();
+
+        let mut vec = Vec::<u8>::new();
+        Amf0Encoder::encode_object(&mut vec, &properties).unwrap();
+        assert_eq!(buf.as_ref(), vec.as_slice());
+        assert_eq!(
+            buf.len(),
+            Amf0Encoder::encoded_size(&Amf0Value::Object(properties.to_vec().into()))
+        );
+    }
+
+    /// A small xorshift generator so the randomized test is reproducible
+    /// without extra dependencies.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn string(&mut self) -> String {
+            let len = match self.below(20) {
+                0 => u16::MAX as usize + 1 + self.below(16) as usize,
+                1 => u16::MAX as usize,
+                _ => self.below(32) as usize,
+            };
+            // Mix in multi-byte characters so byte and char lengths differ.
+            let mut string = String::with_capacity(len);
+            while string.len() < len {
+                string.push(if string.len() % 7 == 3 && string.len() + 2 <= len {
+                    'é'
+                } else {
+                    'a'
+                });
+            }
+            string
+        }
+
+        fn value(&mut self, depth: u32) -> Amf0Value<'static> {
+            let kinds = if depth == 0 { 8 } else { 11 };
+            match self.below(kinds) {
+                0 => Amf0Value::Number(self.next() as f64),
+                1 => Amf0Value::Boolean(self.below(2) == 1),
+                2 => Amf0Value::String(self.string().into()),
+                3 => Amf0Value::Null,
+                4 => Amf0Value::Undefined,
+                5 => Amf0Value::Date {
+                    timestamp: self.next() as f64,
+                    timezone: self.next() as i16,
+                },
+                6 => Amf0Value::LongString(self.string().into()),
+                7 => Amf0Value::XmlDocument(self.string().into()),
+                8 => Amf0Value::Object(self.properties(depth - 1).into()),
+                9 => Amf0Value::EcmaArray(self.properties(depth - 1).into()),
+                _ => Amf0Value::StrictArray(
+                    (0..self.below(5))
+                        .map(|_| self.value(depth - 1))
+                        .collect::<Vec<_>>()
+                        .into(),
+                ),
+            }
+        }
+
+        fn properties(&mut self, depth: u32) -> Vec<(Cow<'static, str>, Amf0Value<'static>)> {
+            (0..self.below(5))
+                .map(|_| {
+                    let key = "k".repeat(self.below(10) as usize);
+                    (key.into(), self.value(depth))
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_encoded_size_matches_encode() {
+        let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..200 {
+            let value = rng.value(3);
+
+            let mut vec = Vec::<u8>::new();
+            Amf0Encoder::encode(&mut vec, &value).unwrap();
+            assert_eq!(
+                Amf0Encoder::encoded_size(&value),
+                vec.len(),
+                "{:?}",
+                value.marker()
+            );
+
+            let mut buf = BytesMut::new();
+            Amf0Encoder::encode_into(&mut buf, &value).unwrap();
+            assert_eq!(buf.as_ref(), vec.as_slice());
+        }
+    }
 }
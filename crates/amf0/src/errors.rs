@@ -27,6 +27,9 @@ pub enum Amf0ReadError {
         /// The actual type.
         got: Amf0Marker,
     },
+    /// Objects and arrays were nested deeper than the decoder allows.
+    #[error("nesting deeper than {0} levels")]
+    NestingTooDeep(usize),
 }
 
 impl Amf0ReadError {
@@ -53,6 +56,55 @@ pub enum Amf0WriteError {
     UnsupportedType(Amf0Marker),
 }
 
+/// Errors that can occur when patching encoded AMF0 data in place.
+#[derive(Debug, thiserror::Error)]
+pub enum Amf0PatchError {
+    /// No value was found at the path.
+    #[error("no value at `{0}`")]
+    NotFound(String),
+    /// The value at the path cannot be replaced without changing the size of
+    /// the data.
+    #[error("cannot patch the {marker:?} at `{path}` in place")]
+    SizeMismatch {
+        /// The path of the value.
+        path: String,
+        /// The marker of the value found.
+        marker: Amf0Marker,
+    },
+}
+
+/// Errors that can occur when converting between AMF0 values and serde types.
+#[cfg(feature = "serde")]
+#[derive(Debug, thiserror::Error)]
+pub enum Amf0SerdeError {
+    /// A custom error reported by a `Serialize` or `Deserialize` implementation.
+    #[error("{0}")]
+    Custom(String),
+    /// A map was serialized with keys that are not strings.
+    #[error("map keys must be strings")]
+    NonStringKey,
+    /// An integer is too large to be represented exactly as an AMF0 number.
+    #[error("integer {0} cannot be represented exactly as an AMF0 number")]
+    IntegerOutOfRange(i128),
+    /// A value could not be converted into the requested integer type.
+    #[error("{value} cannot be represented as {ty}")]
+    InvalidInteger {
+        /// The offending value.
+        value: String,
+        /// The requested integer type.
+        ty: &'static str,
+    },
+    /// Data remained after the first decoded value.
+    #[error("trailing data after AMF0 value")]
+    TrailingData,
+    /// A decoding error occurred.
+    #[error("read error: {0}")]
+    Read(#[from] Amf0ReadError),
+    /// An encoding error occurred.
+    #[error("write error: {0}")]
+    Write(#[from] Amf0WriteError),
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
@@ -87,6 +139,10 @@ mod tests {
                 Amf0ReadError::Io(Cursor::new(Vec::<u8>::new()).read_u8().unwrap_err()),
                 "io error: failed to fill whole buffer",
             ),
+            (
+                Amf0ReadError::NestingTooDeep(64),
+                "nesting deeper than 64 levels",
+            ),
         ];
 
         for (err, expected) in cases {
@@ -115,4 +171,25 @@ mod tests {
             assert_eq!(err.to_string(), expected);
         }
     }
+
+    #[test]
+    fn test_patch_error_display() {
+        let cases = [
+            (
+                Amf0PatchError::NotFound("keyframes.times[3]".to_string()),
+                "no value at `keyframes.times[3]`",
+            ),
+            (
+                Amf0PatchError::SizeMismatch {
+                    path: "encoder".to_string(),
+                    marker: Amf0Marker::String,
+                },
+                "cannot patch the String at `encoder` in place",
+            ),
+        ];
+
+        for (err, expected) in cases {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
 }
@@ -25,6 +25,16 @@
 //! # }
 //! # test().expect("test failed");
 //! ```
+//!
+//! Encoded data can also be queried without decoding it: [`Amf0Scanner`]
+//! locates a value by its path, e.g. `keyframes.filepositions[3]`, and
+//! [`patch_number_in_place`] overwrites a number without touching any other
+//! byte.
+//!
+//! # Features
+//!
+//! - `serde`: Conversion between [`Amf0Value`] and serde types via
+//!   `to_value`, `from_value`, `to_writer` and `from_slice`.
 #![cfg_attr(all(coverage_nightly, test), feature(coverage_attribute))]
 #![deny(missing_docs)]
 #![deny(unsafe_code)]
@@ -33,8 +43,16 @@ mod decode;
 mod define;
 mod encode;
 mod errors;
+mod scan;
+#[cfg(feature = "serde")]
+mod serde;
 
 pub use crate::decode::{Amf0Decoder, LossyDecodeResult};
 pub use crate::define::{Amf0Marker, Amf0Value};
 pub use crate::encode::Amf0Encoder;
-pub use crate::errors::{Amf0ReadError, Amf0WriteError};
+#[cfg(feature = "serde")]
+pub use crate::errors::Amf0SerdeError;
+pub use crate::errors::{Amf0PatchError, Amf0ReadError, Amf0WriteError};
+pub use crate::scan::{Amf0Scanner, patch_number_in_place};
+#[cfg(feature = "serde")]
+pub use crate::serde::{from_slice, from_value, to_value, to_writer};
@@ -0,0 +1,588 @@
+use std::io;
+use std::ops::Range;
+
+use super::decode::MAX_NESTING_DEPTH;
+use super::{Amf0Marker, Amf0PatchError, Amf0ReadError};
+
+/// A step of a path into AMF0 data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Step<'p> {
+    /// Property of an object or ECMA array
+    Key(&'p [u8]),
+    /// Element of a strict array
+    Index(usize),
+}
+
+/// Split a path such as `keyframes.filepositions[3]` into its steps.
+fn parse_path(path: &str) -> Option<Vec<Step<'_>>> {
+    let mut steps = Vec::new();
+
+    for (i, segment) in path.split('.').enumerate() {
+        let (key, mut indexes) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+        if !key.is_empty() {
+            steps.push(Step::Key(key.as_bytes()));
+        } else if i > 0 || indexes.is_empty() {
+            // Only the first segment may start with an index, no segment may be empty
+            return None;
+        }
+
+        while !indexes.is_empty() {
+            let (index, rest) = indexes.strip_prefix('[')?.split_once(']')?;
+            if !index.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            steps.push(Step::Index(index.parse().ok()?));
+            indexes = rest;
+        }
+    }
+
+    Some(steps)
+}
+
+/// Where a walk through the entries of a container stopped.
+enum Lookup {
+    /// Start of the value looked for
+    Found(usize),
+    /// End of what was walked without finding the value, a container or a property
+    End(usize),
+}
+
+impl Lookup {
+    fn position(self) -> usize {
+        match self {
+            Self::Found(pos) | Self::End(pos) => pos,
+        }
+    }
+
+    fn found(self) -> Option<usize> {
+        match self {
+            Self::Found(pos) => Some(pos),
+            Self::End(_) => None,
+        }
+    }
+}
+
+fn eof() -> Amf0ReadError {
+    Amf0ReadError::Io(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "not enough data",
+    ))
+}
+
+/// Locates values in encoded AMF0 data without decoding it.
+///
+/// The scanner only reads the markers and lengths of the values it walks past,
+/// so a single field of a large script data payload can be found without
+/// materializing the value tree, and patched in place with
+/// [`patch_number_in_place`].
+///
+/// Values are addressed by paths of property names separated by dots, with
+/// the elements of strict arrays selected by index, e.g.
+/// `keyframes.filepositions[3]`. The first property is looked up in every
+/// top-level object or ECMA array in turn, so `duration` is found in the
+/// ECMA array following the `onMetaData` name of a script tag. A path starting
+/// with an index selects a top-level value by position instead: `[1].duration`.
+///
+/// ECMA arrays are walked up to their object end marker whatever the count
+/// they declare. Arrays without the marker end after their count, at the end of
+/// the data or, past the count, where the next bytes are not a property.
+#[derive(Debug, Clone, Copy)]
+pub struct Amf0Scanner<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Amf0Scanner<'a> {
+    /// Create a new scanner over encoded AMF0 values.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Locate the value at `path`.
+    ///
+    /// Returns the byte range of the encoded value, marker included, and its
+    /// marker, or `None` if the path is malformed, leads nowhere, or runs into
+    /// data that cannot be scanned.
+    pub fn locate(&self, path: &str) -> Option<(Range<usize>, Amf0Marker)> {
+        let steps = parse_path(path)?;
+        let (first, rest) = steps.split_first()?;
+
+        let mut start = match *first {
+            Step::Index(index) => self.top_level().nth(index)?,
+            step => self
+                .top_level()
+                .find_map(|start| self.child(start, step).ok().flatten())?,
+        };
+        for &step in rest {
+            start = self.child(start, step).ok()??;
+        }
+
+        let end = self.value_end(start, 0).ok()?;
+        let marker = self.marker(start).ok()?;
+        Some((start..end, marker))
+    }
+
+    /// Start of each top-level value, up to the first one that cannot be scanned.
+    fn top_level(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut next = Some(0);
+        std::iter::from_fn(move || {
+            let start = next.filter(|&pos| pos < self.data.len())?;
+            next = self.value_end(start, 0).ok();
+            Some(start)
+        })
+    }
+
+    /// Start of the child of the value at `start` that `step` leads to.
+    fn child(&self, start: usize, step: Step<'_>) -> Result<Option<usize>, Amf0ReadError> {
+        let pos = start + 1;
+        let lookup = match (self.marker(start)?, step) {
+            (Amf0Marker::Object, Step::Key(key)) => self.entries(pos, None, Some(key), 1)?,
+            (Amf0Marker::EcmaArray, Step::Key(key)) => {
+                let count = self.read_u32(pos)?;
+                self.entries(pos + 4, Some(count), Some(key), 1)?
+            }
+            (Amf0Marker::StrictArray, Step::Index(index)) => {
+                let count = self.read_u32(pos)?;
+                self.elements(pos + 4, count, Some(index), 1)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(lookup.found())
+    }
+
+    /// End of the value starting at `start`, nested `depth` levels deep.
+    fn value_end(&self, start: usize, depth: usize) -> Result<usize, Amf0ReadError> {
+        if depth > MAX_NESTING_DEPTH {
+            return Err(Amf0ReadError::NestingTooDeep(MAX_NESTING_DEPTH));
+        }
+
+        let pos = start + 1;
+        match self.marker(start)? {
+            Amf0Marker::Number => self.skip(pos, 8),
+            Amf0Marker::Boolean => self.skip(pos, 1),
+            Amf0Marker::String => self.skip(pos + 2, self.read_u16(pos)? as usize),
+            Amf0Marker::Object => Ok(self.entries(pos, None, None, depth + 1)?.position()),
+            Amf0Marker::Null | Amf0Marker::Undefined => Ok(pos),
+            Amf0Marker::EcmaArray => {
+                let count = self.read_u32(pos)?;
+                Ok(self
+                    .entries(pos + 4, Some(count), None, depth + 1)?
+                    .position())
+            }
+            Amf0Marker::StrictArray => {
+                let count = self.read_u32(pos)?;
+                Ok(self.elements(pos + 4, count, None, depth + 1)?.position())
+            }
+            Amf0Marker::Date => self.skip(pos, 10),
+            Amf0Marker::LongString | Amf0Marker::XmlDocument => {
+                self.skip(pos + 4, self.read_u32(pos)? as usize)
+            }
+            marker => Err(Amf0ReadError::UnsupportedType(marker)),
+        }
+    }
+
+    /// Walk the properties of an object, or of an ECMA array declaring `count`
+    /// properties, from `pos` until the value of `key`.
+    fn entries(
+        &self,
+        mut pos: usize,
+        count: Option<u32>,
+        key: Option<&[u8]>,
+        depth: usize,
+    ) -> Result<Lookup, Amf0ReadError> {
+        let mut read = 0;
+
+        loop {
+            if self.is_object_end(pos) {
+                return Ok(Lookup::End(pos + 3));
+            }
+            if count.is_some() && pos == self.data.len() {
+                return Ok(Lookup::End(pos));
+            }
+
+            let entry = self
+                .read_u16(pos)
+                .and_then(|len| self.skip(pos + 2, len as usize))
+                .and_then(|value| {
+                    if key.is_some_and(|key| key == &self.data[pos + 2..value]) {
+                        Ok(Lookup::Found(value))
+                    } else {
+                        self.value_end(value, depth).map(Lookup::End)
+                    }
+                });
+            match entry {
+                found @ Ok(Lookup::Found(_)) => return found,
+                // End of the property, the next one starts there
+                Ok(Lookup::End(end)) => pos = end,
+                // The count is not to be trusted, but it is all there is to end
+                // an array without the object end marker
+                Err(_) if count.is_some_and(|count| read >= count) => {
+                    return Ok(Lookup::End(pos));
+                }
+                Err(err) => return Err(err),
+            }
+            read += 1;
+        }
+    }
+
+    /// Walk the `count` values of a strict array from `pos` until the one at `index`.
+    fn elements(
+        &self,
+        mut pos: usize,
+        count: u32,
+        index: Option<usize>,
+        depth: usize,
+    ) -> Result<Lookup, Amf0ReadError> {
+        // Every value takes at least a byte, a count past the end of the data
+        // fails there
+        for i in 0..count as usize {
+            if index == Some(i) {
+                return Ok(Lookup::Found(pos));
+            }
+            pos = self.value_end(pos, depth)?;
+        }
+        Ok(Lookup::End(pos))
+    }
+
+    fn marker(&self, pos: usize) -> Result<Amf0Marker, Amf0ReadError> {
+        let byte = *self.data.get(pos).ok_or_else(eof)?;
+        Amf0Marker::try_from(byte).map_err(Amf0ReadError::UnknownMarker)
+    }
+
+    fn is_object_end(&self, pos: usize) -> bool {
+        self.data.get(pos..pos + 3).is_some_and(|bytes| {
+            Amf0Marker::is_object_end_u24(u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]))
+        })
+    }
+
+    /// Position `len` bytes after `pos`, if the data holds them.
+    fn skip(&self, pos: usize, len: usize) -> Result<usize, Amf0ReadError> {
+        pos.checked_add(len)
+            .filter(|&end| end <= self.data.len())
+            .ok_or_else(eof)
+    }
+
+    fn read_u16(&self, pos: usize) -> Result<u16, Amf0ReadError> {
+        let bytes = self.data.get(pos..pos + 2).ok_or_else(eof)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&self, pos: usize) -> Result<u32, Amf0ReadError> {
+        let bytes = self.data.get(pos..pos + 4).ok_or_else(eof)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Overwrite the number at `path` in the encoded AMF0 data of `buf` with `value`.
+///
+/// Only the 8 bytes of the number change, every other byte of `buf` is left as
+/// is. Paths are those of [`Amf0Scanner::locate`]. A value of another type
+/// cannot be replaced by a number without changing the size of the data, so
+/// it is an error.
+pub fn patch_number_in_place(buf: &mut [u8], path: &str, value: f64) -> Result<(), Amf0PatchError> {
+    let (range, marker) = Amf0Scanner::new(buf)
+        .locate(path)
+        .ok_or_else(|| Amf0PatchError::NotFound(path.to_string()))?;
+    if marker != Amf0Marker::Number {
+        return Err(Amf0PatchError::SizeMismatch {
+            path: path.to_string(),
+            marker,
+        });
+    }
+
+    buf[range.start + 1..range.end].copy_from_slice(&value.to_be_bytes());
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::decode::OBS_ON_METADATA;
+    use crate::{Amf0Decoder, Amf0Encoder, Amf0Value};
+
+    /// Position of the ECMA array count of [`OBS_ON_METADATA`]
+    const OBS_COUNT: Range<usize> = 14..18;
+
+    fn decode_at(data: &[u8], range: Range<usize>) -> Amf0Value<'_> {
+        let mut decoder = Amf0Decoder::new(&data[range]);
+        let value = decoder.decode().unwrap();
+        assert!(decoder.is_empty());
+        value
+    }
+
+    /// onMetaData with a keyframe index, a long string and nested strict arrays
+    fn keyframes_metadata() -> Vec<u8> {
+        let numbers = |values: &[f64]| {
+            Amf0Value::StrictArray(values.iter().map(|&v| Amf0Value::Number(v)).collect())
+        };
+        let chapter = |time: f64, title: &'static str| {
+            Amf0Value::StrictArray(Cow::Owned(vec![
+                Amf0Value::Number(time),
+                Amf0Value::String(title.into()),
+            ]))
+        };
+        let keyframes: Vec<(Cow<str>, Amf0Value)> = vec![
+            ("times".into(), numbers(&[0.0, 2.0, 4.0, 6.0, 8.0])),
+            (
+                "filepositions".into(),
+                numbers(&[13.0, 90_421.0, 180_117.0, 270_342.0, 360_906.0]),
+            ),
+        ];
+        let metadata: Vec<(Cow<str>, Amf0Value)> = vec![
+            ("duration".into(), Amf0Value::Number(10.0)),
+            (
+                "comment".into(),
+                Amf0Value::LongString("x".repeat(70_000).into()),
+            ),
+            (
+                "chapters".into(),
+                Amf0Value::StrictArray(Cow::Owned(vec![
+                    chapter(0.0, "intro"),
+                    chapter(4.0, "main"),
+                ])),
+            ),
+            ("keyframes".into(), Amf0Value::Object(keyframes.into())),
+            ("lasttimestamp".into(), Amf0Value::Number(8.0)),
+        ];
+
+        let mut buf = Vec::new();
+        Amf0Encoder::encode_string(&mut buf, "onMetaData").unwrap();
+        Amf0Encoder::encode_ecma_array(&mut buf, &metadata).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("keyframes.filepositions[3]"),
+            Some(vec![
+                Step::Key(b"keyframes"),
+                Step::Key(b"filepositions"),
+                Step::Index(3),
+            ])
+        );
+        assert_eq!(
+            parse_path("[1].a[0][2]"),
+            Some(vec![
+                Step::Index(1),
+                Step::Key(b"a"),
+                Step::Index(0),
+                Step::Index(2),
+            ])
+        );
+        for path in ["", "a..b", "a.[0]", "a[", "a[x]", "a[-1]", "a[1]b", "a[+1]"] {
+            assert_eq!(parse_path(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_locate_in_obs_on_metadata() {
+        let scanner = Amf0Scanner::new(OBS_ON_METADATA);
+
+        let (range, marker) = scanner.locate("[0]").unwrap();
+        assert_eq!((range.clone(), marker), (0..13, Amf0Marker::String));
+        assert_eq!(
+            decode_at(OBS_ON_METADATA, range),
+            Amf0Value::String("onMetaData".into())
+        );
+
+        let (range, marker) = scanner.locate("width").unwrap();
+        assert_eq!(marker, Amf0Marker::Number);
+        assert_eq!(decode_at(OBS_ON_METADATA, range), Amf0Value::Number(1920.0));
+        assert_eq!(scanner.locate("[1].width"), scanner.locate("width"));
+
+        let (range, marker) = scanner.locate("stereo").unwrap();
+        assert_eq!(marker, Amf0Marker::Boolean);
+        assert_eq!(decode_at(OBS_ON_METADATA, range), Amf0Value::Boolean(true));
+
+        // The last property, past every other one
+        let (range, marker) = scanner.locate("encoder").unwrap();
+        assert_eq!(marker, Amf0Marker::String);
+        assert_eq!(range.end, OBS_ON_METADATA.len() - 3);
+        assert_eq!(
+            decode_at(OBS_ON_METADATA, range),
+            Amf0Value::String("obs-output module (libobs version 30.0.2)".into())
+        );
+
+        let (range, marker) = scanner.locate("[1]").unwrap();
+        assert_eq!(
+            (range, marker),
+            (13..OBS_ON_METADATA.len(), Amf0Marker::EcmaArray)
+        );
+
+        for path in ["missing", "width.height", "width[0]", "[2]", "[0].width"] {
+            assert_eq!(scanner.locate(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_patch_obs_on_metadata() {
+        let mut data = OBS_ON_METADATA.to_vec();
+        patch_number_in_place(&mut data, "duration", 3600.5).unwrap();
+        patch_number_in_place(&mut data, "fileSize", 1_234_567_890.0).unwrap();
+
+        let duration = Amf0Scanner::new(&data).locate("duration").unwrap().0;
+        let file_size = Amf0Scanner::new(&data).locate("fileSize").unwrap().0;
+        for (i, (patched, original)) in data.iter().zip(OBS_ON_METADATA).enumerate() {
+            let patched_range = |range: &Range<usize>| (range.start + 1..range.end).contains(&i);
+            if !patched_range(&duration) && !patched_range(&file_size) {
+                assert_eq!(patched, original, "byte {i}");
+            }
+        }
+
+        let values = Amf0Decoder::new(&data).decode_all();
+        assert!(values.1.is_none());
+        let metadata = &values.0[1];
+        assert_eq!(
+            metadata.get("duration").and_then(|v| v.as_number()),
+            Some(3600.5)
+        );
+        assert_eq!(
+            metadata.get("fileSize").and_then(|v| v.as_number()),
+            Some(1_234_567_890.0)
+        );
+    }
+
+    #[test]
+    fn test_ecma_array_count_ignored() {
+        // OBS declares its 20 properties; the scanner does not rely on the count
+        for count in [0u32, 3, 20, 1000] {
+            let mut data = OBS_ON_METADATA.to_vec();
+            data[OBS_COUNT].copy_from_slice(&count.to_be_bytes());
+            let scanner = Amf0Scanner::new(&data);
+
+            let (range, _) = scanner.locate("encoder").unwrap();
+            assert_eq!(range.end, data.len() - 3, "count {count}");
+            assert_eq!(scanner.locate("[1]").unwrap().0, 13..data.len());
+        }
+
+        // Without the object end marker, the array ends after its count
+        let mut data = OBS_ON_METADATA[..OBS_ON_METADATA.len() - 3].to_vec();
+        data.extend_from_slice(&[0x00]);
+        data.extend_from_slice(&42.0_f64.to_be_bytes());
+        let scanner = Amf0Scanner::new(&data);
+        assert_eq!(
+            scanner.locate("[1]").unwrap().0,
+            13..OBS_ON_METADATA.len() - 3
+        );
+        assert_eq!(
+            scanner.locate("[2]"),
+            Some((OBS_ON_METADATA.len() - 3..data.len(), Amf0Marker::Number))
+        );
+        assert!(scanner.locate("encoder").is_some());
+
+        // Or at the end of the data, when the count is too large
+        data.truncate(OBS_ON_METADATA.len() - 3);
+        data[OBS_COUNT].copy_from_slice(&1000u32.to_be_bytes());
+        let scanner = Amf0Scanner::new(&data);
+        assert_eq!(scanner.locate("[1]").unwrap().0, 13..data.len());
+        assert!(scanner.locate("encoder").is_some());
+    }
+
+    #[test]
+    fn test_patch_keyframe_index() {
+        let original = keyframes_metadata();
+        let mut data = original.clone();
+        let scanner = Amf0Scanner::new(&original);
+
+        let (range, marker) = scanner.locate("keyframes.filepositions[3]").unwrap();
+        assert_eq!(marker, Amf0Marker::Number);
+        assert_eq!(range.len(), 9);
+        assert_eq!(
+            decode_at(&original, range.clone()),
+            Amf0Value::Number(270_342.0)
+        );
+
+        patch_number_in_place(&mut data, "keyframes.filepositions[3]", 271_000.0).unwrap();
+        assert_eq!(data[..range.start + 1], original[..range.start + 1]);
+        assert_eq!(data[range.end..], original[range.end..]);
+        assert_eq!(decode_at(&data, range), Amf0Value::Number(271_000.0));
+
+        // Past the long string and the nested arrays
+        let (range, _) = scanner.locate("lasttimestamp").unwrap();
+        assert_eq!(decode_at(&original, range), Amf0Value::Number(8.0));
+        let (range, marker) = scanner.locate("chapters[1][1]").unwrap();
+        assert_eq!(marker, Amf0Marker::String);
+        assert_eq!(
+            decode_at(&original, range),
+            Amf0Value::String("main".into())
+        );
+        let (range, marker) = scanner.locate("comment").unwrap();
+        assert_eq!((range.len(), marker), (5 + 70_000, Amf0Marker::LongString));
+
+        assert_eq!(scanner.locate("keyframes.filepositions[5]"), None);
+        assert_eq!(scanner.locate("chapters[2][0]"), None);
+    }
+
+    #[test]
+    fn test_patch_errors() {
+        let original = keyframes_metadata();
+        let mut data = original.clone();
+
+        for (path, marker) in [
+            ("comment", Amf0Marker::LongString),
+            ("keyframes", Amf0Marker::Object),
+            ("chapters[0]", Amf0Marker::StrictArray),
+            ("[0]", Amf0Marker::String),
+        ] {
+            let err = patch_number_in_place(&mut data, path, 1.0).unwrap_err();
+            assert!(
+                matches!(&err, Amf0PatchError::SizeMismatch { marker: m, .. } if *m == marker),
+                "{path}: {err}"
+            );
+        }
+        assert!(matches!(
+            patch_number_in_place(&mut data, "keyframes.times[9]", 1.0),
+            Err(Amf0PatchError::NotFound(path)) if path == "keyframes.times[9]"
+        ));
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn test_truncated_and_deep_data() {
+        let data = keyframes_metadata();
+        // Cut anywhere, the scanner never reads past the data nor panics
+        for len in (0..data.len())
+            .step_by(997)
+            .chain(data.len() - 40..data.len())
+        {
+            let scanner = Amf0Scanner::new(&data[..len]);
+            for path in ["[1]", "lasttimestamp", "keyframes.filepositions[3]"] {
+                if let Some((range, _)) = scanner.locate(path) {
+                    assert!(range.end <= len, "{path} cut at {len}");
+                }
+            }
+        }
+        assert_eq!(Amf0Scanner::new(&data[..100]).locate("[1]"), None);
+
+        // ECMA arrays declaring no property, each holding the next one under `a`
+        let mut nested = Vec::new();
+        for _ in 0..MAX_NESTING_DEPTH {
+            nested.extend_from_slice(&[0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, b'a']);
+        }
+        nested.push(0x05);
+        for _ in 0..MAX_NESTING_DEPTH {
+            nested.extend_from_slice(&[0x00, 0x00, 0x09]);
+        }
+        let path = ["a"; MAX_NESTING_DEPTH].join(".");
+        let null = MAX_NESTING_DEPTH * 8;
+        assert_eq!(
+            Amf0Scanner::new(&nested).locate(&path),
+            Some((null..null + 1, Amf0Marker::Null))
+        );
+
+        // Strict arrays of a single strict array
+        let mut nested = Vec::new();
+        for _ in 0..10_000 {
+            nested.extend_from_slice(&[0x0a, 0x00, 0x00, 0x00, 0x01]);
+        }
+        nested.push(0x05);
+        assert_eq!(Amf0Scanner::new(&nested).locate("[0]"), None);
+        assert_eq!(
+            Amf0Scanner::new(&nested[nested.len() - 5 * MAX_NESTING_DEPTH - 1..]).locate("[0]"),
+            Some((0..5 * MAX_NESTING_DEPTH + 1, Amf0Marker::StrictArray))
+        );
+    }
+}
@@ -0,0 +1,1133 @@
+//! [`serde`] integration for AMF0 values.
+//!
+//! The mapping between the serde data model and AMF0 is:
+//!
+//! | serde                         | AMF0                                   |
+//! |-------------------------------|----------------------------------------|
+//! | `bool`                        | Boolean                                |
+//! | integers, `f32`, `f64`        | Number                                 |
+//! | `char`, `str`                 | String (LongString above 65535 bytes)  |
+//! | bytes, sequences, tuples      | StrictArray                            |
+//! | `None`, `()`, unit structs    | Null                                   |
+//! | `Some(value)`                 | `value`                                |
+//! | structs                       | Object                                 |
+//! | maps                          | EcmaArray (keys must be strings)       |
+//! | unit variants                 | String holding the variant name        |
+//! | other enum variants           | Object with a single variant-name key  |
+//!
+//! When deserializing, Object and EcmaArray are interchangeable, both Null
+//! and Undefined deserialize as `None`/`()`, a missing struct field of type
+//! `Option` deserializes as `None`, and a Date deserializes as its
+//! millisecond timestamp.
+//!
+//! AMF0 only has one numeric type, a 64-bit float. Integers whose magnitude
+//! exceeds 2^53 are rejected on serialization because they cannot be
+//! represented exactly, and numbers are only deserialized into an integer
+//! type if they are integral and within that type's range.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::ser::{self, Impossible, Serialize};
+
+use crate::{Amf0Decoder, Amf0Encoder, Amf0SerdeError, Amf0Value};
+
+/// The largest integer magnitude an `f64` represents exactly.
+const MAX_SAFE_INTEGER: i128 = (1 << 53) - 1;
+
+impl ser::Error for Amf0SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for Amf0SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// Convert a serializable value into an [`Amf0Value`].
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Amf0Value<'static>, Amf0SerdeError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Serialize a value and encode it as AMF0 into a writer.
+pub fn to_writer<W: io::Write, T: Serialize + ?Sized>(
+    writer: &mut W,
+    value: &T,
+) -> Result<(), Amf0SerdeError> {
+    Ok(Amf0Encoder::encode(writer, &to_value(value)?)?)
+}
+
+/// Deserialize a value from an [`Amf0Value`].
+pub fn from_value<T: DeserializeOwned>(value: &Amf0Value) -> Result<T, Amf0SerdeError> {
+    T::deserialize(ValueDeserializer(value))
+}
+
+/// Decode a single AMF0 value from a byte slice and deserialize it.
+///
+/// Returns [`Amf0SerdeError::TrailingData`] if the slice contains more than
+/// one value. Use [`Amf0Decoder`] with [`from_value`] for payloads holding
+/// several values, such as FLV script tags.
+pub fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, Amf0SerdeError> {
+    let mut decoder = Amf0Decoder::new(data);
+    let value = decoder.decode()?;
+    if !decoder.is_empty() {
+        return Err(Amf0SerdeError::TrailingData);
+    }
+
+    from_value(&value)
+}
+
+fn integer_to_number(value: i128) -> Result<Amf0Value<'static>, Amf0SerdeError> {
+    if !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&value) {
+        return Err(Amf0SerdeError::IntegerOutOfRange(value));
+    }
+
+    Ok(Amf0Value::Number(value as f64))
+}
+
+fn variant_object(variant: &'static str, value: Amf0Value<'static>) -> Amf0Value<'static> {
+    Amf0Value::Object(vec![(Cow::Borrowed(variant), value)].into())
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        integer_to_number(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        integer_to_number(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        integer_to_number(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        integer_to_number(i128::try_from(v).unwrap_or(i128::MAX))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Number(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::String(Cow::Owned(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::String(Cow::Owned(v.to_owned())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::StrictArray(
+            v.iter().map(|b| Amf0Value::Number((*b).into())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(Amf0Value::String(Cow::Borrowed(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant_object(variant, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(MapSerializer {
+            ecma_array: true,
+            variant: None,
+            properties: Vec::with_capacity(len.unwrap_or(0)),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(MapSerializer {
+            ecma_array: false,
+            variant: None,
+            properties: Vec::with_capacity(len),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(MapSerializer {
+            ecma_array: false,
+            variant: Some(variant),
+            properties: Vec::with_capacity(len),
+            next_key: None,
+        })
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    values: Vec<Amf0Value<'static>>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Amf0SerdeError> {
+        self.values.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Amf0Value<'static> {
+        let array = Amf0Value::StrictArray(self.values.into());
+        match self.variant {
+            Some(variant) => variant_object(variant, array),
+            None => array,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+struct MapSerializer {
+    ecma_array: bool,
+    variant: Option<&'static str>,
+    properties: Vec<(Cow<'static, str>, Amf0Value<'static>)>,
+    next_key: Option<String>,
+}
+
+impl MapSerializer {
+    fn push<T: Serialize + ?Sized>(
+        &mut self,
+        key: Cow<'static, str>,
+        value: &T,
+    ) -> Result<(), Amf0SerdeError> {
+        self.properties
+            .push((key, value.serialize(ValueSerializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Amf0Value<'static> {
+        let properties = self.properties.into();
+        let value = if self.ecma_array {
+            Amf0Value::EcmaArray(properties)
+        } else {
+            Amf0Value::Object(properties)
+        };
+
+        match self.variant {
+            Some(variant) => variant_object(variant, value),
+            None => value,
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        self.next_key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| Amf0SerdeError::Custom("map value serialized before its key".into()))?;
+        self.push(Cow::Owned(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.push(Cow::Borrowed(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Amf0Value<'static>;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.push(Cow::Borrowed(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.finish())
+    }
+}
+
+/// Serializes map keys, which AMF0 requires to be strings.
+struct MapKeySerializer;
+
+macro_rules! reject_key {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, _v: $ty) -> Result<Self::Ok, Self::Error> {
+                Err(Amf0SerdeError::NonStringKey)
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = Amf0SerdeError;
+
+    type SerializeSeq = Impossible<String, Amf0SerdeError>;
+    type SerializeTuple = Impossible<String, Amf0SerdeError>;
+    type SerializeTupleStruct = Impossible<String, Amf0SerdeError>;
+    type SerializeTupleVariant = Impossible<String, Amf0SerdeError>;
+    type SerializeMap = Impossible<String, Amf0SerdeError>;
+    type SerializeStruct = Impossible<String, Amf0SerdeError>;
+    type SerializeStructVariant = Impossible<String, Amf0SerdeError>;
+
+    reject_key!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_bytes(&[u8]),
+    );
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(v.to_owned())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<Self::Ok, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(variant.to_owned())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(Amf0SerdeError::NonStringKey)
+    }
+}
+
+/// Deserializes from a borrowed [`Amf0Value`].
+struct ValueDeserializer<'a, 'b>(&'a Amf0Value<'b>);
+
+impl ValueDeserializer<'_, '_> {
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self.0 {
+            Amf0Value::Number(n) => Unexpected::Float(*n),
+            Amf0Value::Boolean(b) => Unexpected::Bool(*b),
+            Amf0Value::String(s) | Amf0Value::LongString(s) | Amf0Value::XmlDocument(s) => {
+                Unexpected::Str(s)
+            }
+            Amf0Value::Object(_) | Amf0Value::EcmaArray(_) => Unexpected::Map,
+            Amf0Value::StrictArray(_) => Unexpected::Seq,
+            Amf0Value::Null | Amf0Value::Undefined => Unexpected::Unit,
+            Amf0Value::Date { .. } => Unexpected::Other("date"),
+        }
+    }
+
+    fn invalid_type<'de, V: Visitor<'de>>(&self, visitor: &V) -> Amf0SerdeError {
+        de::Error::invalid_type(self.unexpected(), visitor)
+    }
+
+    /// Converts a Number into an integer, rejecting fractional and out of
+    /// range values.
+    fn integer<T: TryFrom<i128>>(&self, ty: &'static str) -> Result<T, Amf0SerdeError> {
+        let Amf0Value::Number(n) = *self.0 else {
+            return Err(Amf0SerdeError::InvalidInteger {
+                value: format!("{:?}", self.0.marker()),
+                ty,
+            });
+        };
+
+        if n.is_finite() && n.fract() == 0.0 {
+            // Saturating conversion, anything clamped is outside every target range.
+            if let Ok(value) = T::try_from(n as i128) {
+                return Ok(value);
+            }
+        }
+
+        Err(Amf0SerdeError::InvalidInteger {
+            value: n.to_string(),
+            ty,
+        })
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.integer::<$ty>(stringify!($ty))?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'_, '_> {
+    type Error = Amf0SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Amf0Value::Number(n) => {
+                let n = *n;
+                if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER as f64 {
+                    if n >= 0.0 {
+                        visitor.visit_u64(n as u64)
+                    } else {
+                        visitor.visit_i64(n as i64)
+                    }
+                } else {
+                    visitor.visit_f64(n)
+                }
+            }
+            Amf0Value::Boolean(b) => visitor.visit_bool(*b),
+            Amf0Value::String(s) | Amf0Value::LongString(s) | Amf0Value::XmlDocument(s) => {
+                visitor.visit_str(s)
+            }
+            Amf0Value::Object(properties) | Amf0Value::EcmaArray(properties) => {
+                visitor.visit_map(PropertiesDeserializer {
+                    iter: properties.iter(),
+                    value: None,
+                })
+            }
+            Amf0Value::StrictArray(values) => visitor.visit_seq(SeqDeserializer {
+                iter: values.iter(),
+            }),
+            Amf0Value::Null | Amf0Value::Undefined => visitor.visit_unit(),
+            Amf0Value::Date { timestamp, .. } => visitor.visit_f64(*timestamp),
+        }
+    }
+
+    deserialize_integer!(
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_i128 => visit_i128(i128),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_u128 => visit_u128(u128),
+    );
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Amf0Value::Number(n) => visitor.visit_f32(*n as f32),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Amf0Value::Number(n) => visitor.visit_f64(*n),
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Amf0Value::Null | Amf0Value::Undefined => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Amf0Value::String(s) | Amf0Value::LongString(s) => {
+                visitor.visit_enum(EnumDeserializer {
+                    variant: s,
+                    value: None,
+                })
+            }
+            Amf0Value::Object(properties) | Amf0Value::EcmaArray(properties)
+                if properties.len() == 1 =>
+            {
+                let (variant, value) = &properties[0];
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool char str string bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier
+    }
+}
+
+struct SeqDeserializer<'a, 'b> {
+    iter: std::slice::Iter<'a, Amf0Value<'b>>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer<'_, '_> {
+    type Error = Amf0SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.iter
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer(value)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct PropertiesDeserializer<'a, 'b> {
+    iter: std::slice::Iter<'a, (Cow<'b, str>, Amf0Value<'b>)>,
+    value: Option<&'a Amf0Value<'b>>,
+}
+
+impl<'de> MapAccess<'de> for PropertiesDeserializer<'_, '_> {
+    type Error = Amf0SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+
+        self.value = Some(value);
+        seed.deserialize(IntoDeserializer::<Self::Error>::into_deserializer(
+            key.as_ref(),
+        ))
+        .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Amf0SerdeError::Custom("map value requested before its key".into()))?;
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumDeserializer<'a, 'b> {
+    variant: &'a str,
+    value: Option<&'a Amf0Value<'b>>,
+}
+
+impl<'de, 'a, 'b> EnumAccess<'de> for EnumDeserializer<'a, 'b> {
+    type Error = Amf0SerdeError;
+    type Variant = VariantDeserializer<'a, 'b>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(IntoDeserializer::<Self::Error>::into_deserializer(
+            self.variant,
+        ))?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+struct VariantDeserializer<'a, 'b>(Option<&'a Amf0Value<'b>>);
+
+impl<'a, 'b> VariantDeserializer<'a, 'b> {
+    fn value(self, expected: &'static str) -> Result<ValueDeserializer<'a, 'b>, Amf0SerdeError> {
+        self.0
+            .map(ValueDeserializer)
+            .ok_or_else(|| de::Error::invalid_type(Unexpected::UnitVariant, &expected))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer<'_, '_> {
+    type Error = Amf0SerdeError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.0 {
+            None | Some(Amf0Value::Null | Amf0Value::Undefined) => Ok(()),
+            Some(value) => Err(de::Error::invalid_type(
+                ValueDeserializer(value).unexpected(),
+                &"unit variant",
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(self.value("newtype variant")?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(self.value("tuple variant")?, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(self.value("struct variant")?, visitor)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::Amf0Marker;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Keyframes {
+        times: Vec<f64>,
+        filepositions: Vec<u64>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct OnMetaData {
+        duration: f64,
+        width: u32,
+        height: u32,
+        framerate: f64,
+        videocodecid: u8,
+        stereo: bool,
+        encoder: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        audiocodecid: Option<u8>,
+        keyframes: Keyframes,
+    }
+
+    fn metadata() -> OnMetaData {
+        OnMetaData {
+            duration: 120.5,
+            width: 1920,
+            height: 1080,
+            framerate: 30.0,
+            videocodecid: 7,
+            stereo: true,
+            encoder: "Lavf61.7.100".to_string(),
+            audiocodecid: None,
+            keyframes: Keyframes {
+                times: vec![0.0, 2.0, 4.0],
+                filepositions: vec![13, 100_000, 200_000],
+            },
+        }
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let metadata = metadata();
+
+        let value = to_value(&metadata).unwrap();
+        assert_eq!(value.marker(), Amf0Marker::Object);
+        assert_eq!(value.get("width"), Some(&Amf0Value::Number(1920.0)));
+        assert_eq!(value.get("audiocodecid"), None);
+        assert_eq!(
+            value.get("keyframes").and_then(|k| k.get("times")),
+            Some(&Amf0Value::StrictArray(
+                vec![
+                    Amf0Value::Number(0.0),
+                    Amf0Value::Number(2.0),
+                    Amf0Value::Number(4.0),
+                ]
+                .into()
+            ))
+        );
+
+        assert_eq!(from_value::<OnMetaData>(&value).unwrap(), metadata);
+
+        let mut buf = Vec::new();
+        to_writer(&mut buf, &metadata).unwrap();
+        assert_eq!(from_slice::<OnMetaData>(&buf).unwrap(), metadata);
+    }
+
+    #[test]
+    fn test_metadata_from_ecma_array() {
+        let mut buf = Vec::new();
+        Amf0Encoder::encode_string(&mut buf, "onMetaData").unwrap();
+        Amf0Encoder::encode_ecma_array(
+            &mut buf,
+            &[
+                ("duration".into(), Amf0Value::Number(120.5)),
+                ("width".into(), Amf0Value::Number(1920.0)),
+                ("height".into(), Amf0Value::Number(1080.0)),
+                ("framerate".into(), Amf0Value::Number(30.0)),
+                ("videocodecid".into(), Amf0Value::Number(7.0)),
+                ("audiocodecid".into(), Amf0Value::Undefined),
+                ("stereo".into(), Amf0Value::Boolean(true)),
+                ("encoder".into(), Amf0Value::String("Lavf61.7.100".into())),
+                (
+                    "unknown".into(),
+                    Amf0Value::Date {
+                        timestamp: 0.0,
+                        timezone: 0,
+                    },
+                ),
+                (
+                    "keyframes".into(),
+                    Amf0Value::Object(
+                        vec![
+                            (
+                                "times".into(),
+                                Amf0Value::StrictArray(
+                                    vec![
+                                        Amf0Value::Number(0.0),
+                                        Amf0Value::Number(2.0),
+                                        Amf0Value::Number(4.0),
+                                    ]
+                                    .into(),
+                                ),
+                            ),
+                            (
+                                "filepositions".into(),
+                                Amf0Value::StrictArray(
+                                    vec![
+                                        Amf0Value::Number(13.0),
+                                        Amf0Value::Number(100_000.0),
+                                        Amf0Value::Number(200_000.0),
+                                    ]
+                                    .into(),
+                                ),
+                            ),
+                        ]
+                        .into(),
+                    ),
+                ),
+            ],
+        )
+        .unwrap();
+
+        let mut decoder = Amf0Decoder::new(&buf);
+        let name = decoder.decode().unwrap();
+        assert_eq!(from_value::<String>(&name).unwrap(), "onMetaData");
+
+        let value = decoder.decode().unwrap();
+        assert_eq!(from_value::<OnMetaData>(&value).unwrap(), metadata());
+
+        let result = from_slice::<String>(&buf);
+        assert!(matches!(result, Err(Amf0SerdeError::TrailingData)));
+    }
+
+    #[test]
+    fn test_map_round_trip() {
+        let map = BTreeMap::from([("a".to_string(), 1.5), ("b".to_string(), -2.0)]);
+
+        let value = to_value(&map).unwrap();
+        assert_eq!(value.marker(), Amf0Marker::EcmaArray);
+        assert_eq!(from_value::<BTreeMap<String, f64>>(&value).unwrap(), map);
+    }
+
+    #[test]
+    fn test_non_string_keys() {
+        let map = BTreeMap::from([(1u32, true)]);
+        assert!(matches!(to_value(&map), Err(Amf0SerdeError::NonStringKey)));
+    }
+
+    #[test]
+    fn test_integer_conversion() {
+        assert_eq!(from_value::<u8>(&Amf0Value::Number(255.0)).unwrap(), 255);
+        assert_eq!(from_value::<i64>(&Amf0Value::Number(-5.0)).unwrap(), -5);
+
+        for (value, ty) in [(256.0, "u8"), (-1.0, "u8"), (1.5, "u8"), (f64::NAN, "u8")] {
+            let err = from_value::<u8>(&Amf0Value::Number(value)).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("{value} cannot be represented as {ty}")
+            );
+        }
+
+        assert!(matches!(
+            from_value::<u64>(&Amf0Value::Number(18_446_744_073_709_551_616.0)),
+            Err(Amf0SerdeError::InvalidInteger { ty: "u64", .. })
+        ));
+        assert!(matches!(
+            from_value::<u32>(&Amf0Value::String("1".into())),
+            Err(Amf0SerdeError::InvalidInteger { ty: "u32", .. })
+        ));
+
+        assert_eq!(
+            to_value(&(MAX_SAFE_INTEGER as u64)).unwrap(),
+            Amf0Value::Number(MAX_SAFE_INTEGER as f64)
+        );
+        assert!(matches!(
+            to_value(&u64::MAX),
+            Err(Amf0SerdeError::IntegerOutOfRange(_))
+        ));
+        assert!(matches!(
+            to_value(&(i64::MIN)),
+            Err(Amf0SerdeError::IntegerOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    fn test_option_mapping() {
+        assert_eq!(to_value(&None::<u8>).unwrap(), Amf0Value::Null);
+        assert_eq!(to_value(&Some(1u8)).unwrap(), Amf0Value::Number(1.0));
+
+        assert_eq!(from_value::<Option<u8>>(&Amf0Value::Null).unwrap(), None);
+        assert_eq!(
+            from_value::<Option<u8>>(&Amf0Value::Undefined).unwrap(),
+            None
+        );
+        assert_eq!(
+            from_value::<Option<u8>>(&Amf0Value::Number(1.0)).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_enum_round_trip() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Codec {
+            Avc,
+            Custom(u8),
+            Pair(u8, bool),
+            Named { id: u8 },
+        }
+
+        assert_eq!(
+            to_value(&Codec::Avc).unwrap(),
+            Amf0Value::String("Avc".into())
+        );
+
+        for codec in [
+            Codec::Avc,
+            Codec::Custom(12),
+            Codec::Pair(1, true),
+            Codec::Named { id: 7 },
+        ] {
+            let value = to_value(&codec).unwrap();
+            assert_eq!(from_value::<Codec>(&value).unwrap(), codec);
+        }
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let err = from_value::<bool>(&Amf0Value::String("yes".into())).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid type: string \"yes\", expected a boolean"
+        );
+    }
+}
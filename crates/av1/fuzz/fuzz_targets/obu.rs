@@ -0,0 +1,25 @@
+#![no_main]
+
+use std::io::{self, Read};
+
+use av1::seq::SequenceHeaderObu;
+use av1::{ObuHeader, ObuType};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut cursor = io::Cursor::new(data);
+    let Ok(header) = ObuHeader::parse(&mut cursor) else {
+        return;
+    };
+    if header.obu_type != ObuType::SequenceHeader {
+        return;
+    }
+
+    let mut payload = cursor.take(header.size.unwrap_or(u64::MAX));
+    let Ok(seq_header) = SequenceHeaderObu::parse(header, &mut payload) else {
+        return;
+    };
+
+    let mut built = Vec::new();
+    let _ = seq_header.build(&mut built);
+});
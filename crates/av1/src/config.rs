@@ -4,6 +4,8 @@ use byteorder::ReadBytesExt;
 use bytes::Bytes;
 use bytes_util::{BitReader, BitWriter, BytesCursorExt};
 
+use crate::seq::SequenceHeaderObu;
+
 /// AV1 Video Descriptor
 ///
 /// <https://aomediacodec.github.io/av1-mpeg2-ts/#av1-video-descriptor>
@@ -46,6 +48,25 @@ impl AV1VideoDescriptor {
             codec_configuration_record: AV1CodecConfigurationRecord::demux_mpeg2_ts(reader)?,
         })
     }
+
+    /// Demuxes the AV1 Video Descriptor from its payload, the bytes following the
+    /// tag and length of the descriptor, as found in a PMT descriptor loop.
+    pub fn demux_payload(payload: Bytes) -> io::Result<Self> {
+        if payload.len() != 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid AV1 video descriptor length",
+            ));
+        }
+
+        Ok(AV1VideoDescriptor {
+            tag: 0x80,
+            length: 4,
+            codec_configuration_record: AV1CodecConfigurationRecord::demux_mpeg2_ts(
+                &mut io::Cursor::new(payload),
+            )?,
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -245,6 +266,41 @@ impl AV1CodecConfigurationRecord {
         })
     }
 
+    /// Builds an AV1 Codec Configuration Record from the given sequence header.
+    ///
+    /// The profile, level, tier and color fields are taken from the sequence header and its first
+    /// operating point. The sequence header OBU is written into `config_obu`.
+    pub fn build(seq_header: &SequenceHeaderObu) -> io::Result<Self> {
+        let Some(operating_point) = seq_header.operating_points.first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sequence header has no operating points",
+            ));
+        };
+
+        let mut config_obu = Vec::new();
+        seq_header.build(&mut config_obu)?;
+
+        let color_config = &seq_header.color_config;
+
+        Ok(AV1CodecConfigurationRecord {
+            seq_profile: seq_header.seq_profile,
+            seq_level_idx_0: operating_point.seq_level_idx,
+            seq_tier_0: operating_point.seq_tier,
+            high_bitdepth: color_config.bit_depth > 8,
+            twelve_bit: color_config.bit_depth == 12,
+            monochrome: color_config.mono_chrome,
+            chroma_subsampling_x: color_config.subsampling_x,
+            chroma_subsampling_y: color_config.subsampling_y,
+            chroma_sample_position: color_config.chroma_sample_position,
+            hdr_wcg_idc: 0,
+            initial_presentation_delay_minus_one: operating_point
+                .initial_display_delay
+                .map(|delay| delay.saturating_sub(1)),
+            config_obu: config_obu.into(),
+        })
+    }
+
     /// Returns the size of the AV1 Codec Configuration Record.
     pub fn size(&self) -> u64 {
         1 // marker, version
@@ -313,6 +369,8 @@ impl AV1CodecConfigurationRecord {
 mod tests {
 
     use super::*;
+    use crate::seq::{ColorConfig, OperatingPoint};
+    use crate::{ObuHeader, ObuType};
 
     #[test]
     fn test_config_demux() {
@@ -419,6 +477,104 @@ mod tests {
         "#);
     }
 
+    #[test]
+    fn test_config_build_round_trip() {
+        let data = Bytes::from_static(
+            b"\x81\r\x0c\0\n\x0f\0\0\0j\xef\xbf\xe1\xbc\x02\x19\x90\x10\x10\x10@",
+        );
+        let config =
+            AV1CodecConfigurationRecord::demux(&mut io::Cursor::new(data.clone())).unwrap();
+
+        let mut cursor = io::Cursor::new(config.config_obu.clone());
+        let header = ObuHeader::parse(&mut cursor).unwrap();
+        let seq_header = SequenceHeaderObu::parse(header, &mut cursor).unwrap();
+
+        let built = AV1CodecConfigurationRecord::build(&seq_header).unwrap();
+        assert_eq!(built, config);
+
+        let mut buf = Vec::new();
+        built.mux(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn test_config_build_still_picture() {
+        let seq_header = SequenceHeaderObu {
+            header: ObuHeader {
+                obu_type: ObuType::SequenceHeader,
+                size: None,
+                extension_header: None,
+            },
+            seq_profile: 0,
+            still_picture: true,
+            reduced_still_picture_header: true,
+            timing_info: None,
+            decoder_model_info: None,
+            operating_points: vec![OperatingPoint {
+                idc: 0,
+                seq_level_idx: 8,
+                seq_tier: false,
+                operating_parameters_info: None,
+                initial_display_delay: None,
+            }],
+            max_frame_width: 1920,
+            max_frame_height: 1080,
+            frame_ids: None,
+            use_128x128_superblock: true,
+            enable_filter_intra: true,
+            enable_intra_edge_filter: true,
+            enable_interintra_compound: false,
+            enable_masked_compound: false,
+            enable_warped_motion: false,
+            enable_dual_filter: false,
+            enable_order_hint: false,
+            enable_jnt_comp: false,
+            enable_ref_frame_mvs: false,
+            seq_force_screen_content_tools: 2,
+            seq_force_integer_mv: 2,
+            order_hint_bits: 0,
+            enable_superres: false,
+            enable_cdef: true,
+            enable_restoration: true,
+            color_config: ColorConfig {
+                bit_depth: 10,
+                mono_chrome: false,
+                num_planes: 3,
+                color_primaries: 9,
+                transfer_characteristics: 16,
+                matrix_coefficients: 9,
+                full_color_range: false,
+                subsampling_x: true,
+                subsampling_y: true,
+                chroma_sample_position: 1,
+                separate_uv_delta_q: false,
+            },
+            film_grain_params_present: false,
+        };
+
+        let config = AV1CodecConfigurationRecord::build(&seq_header).unwrap();
+        assert_eq!(config.seq_level_idx_0, 8);
+        assert!(config.high_bitdepth);
+        assert!(!config.twelve_bit);
+        assert_eq!(config.chroma_sample_position, 1);
+        assert_eq!(config.initial_presentation_delay_minus_one, None);
+
+        let mut cursor = io::Cursor::new(config.config_obu.clone());
+        let header = ObuHeader::parse(&mut cursor).unwrap();
+        assert_eq!(header.size, Some(config.config_obu.len() as u64 - 2));
+
+        let parsed = SequenceHeaderObu::parse(header, &mut cursor).unwrap();
+        assert!(parsed.still_picture);
+        assert!(parsed.reduced_still_picture_header);
+        assert_eq!(
+            SequenceHeaderObu {
+                header: seq_header.header,
+                ..parsed
+            },
+            seq_header
+        );
+    }
+
     #[test]
     fn test_config_mux() {
         let config = AV1CodecConfigurationRecord {
@@ -558,6 +714,25 @@ mod tests {
         "#);
     }
 
+    #[test]
+    fn test_video_descriptor_demux_payload() {
+        let descriptor =
+            AV1VideoDescriptor::demux_payload(Bytes::from_static(b"\x81\x0d\x0c\x3f")).unwrap();
+
+        assert_eq!(descriptor.tag, 0x80);
+        assert_eq!(descriptor.length, 4);
+        let config = descriptor.codec_configuration_record;
+        assert_eq!(config.seq_level_idx_0, 13);
+        assert!(config.chroma_subsampling_x && config.chroma_subsampling_y);
+        assert_eq!(config.hdr_wcg_idc, 0);
+        assert_eq!(config.initial_presentation_delay_minus_one, Some(15));
+        assert!(config.config_obu.is_empty());
+
+        let err = AV1VideoDescriptor::demux_payload(Bytes::from_static(b"\x81\x0d\x0c\x3f\x00"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Invalid AV1 video descriptor length");
+    }
+
     #[test]
     fn test_video_descriptor_demux_invalid_tag() {
         let data = b"\x81".to_vec();
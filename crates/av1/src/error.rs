@@ -34,6 +34,15 @@ pub enum Av1Error {
     #[error("invalid OBU: {0}")]
     InvalidObu(String),
 
+    /// Invalid OBU data at a known byte offset in the stream.
+    #[error("invalid OBU at byte offset {offset}: {reason}")]
+    InvalidObuAt {
+        /// Byte offset of the OBU header within the stream.
+        offset: usize,
+        /// Description of the problem.
+        reason: String,
+    },
+
     /// LEB128 value overflow.
     #[error("LEB128 overflow: value exceeds maximum")]
     Leb128Overflow,
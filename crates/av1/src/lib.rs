@@ -3,7 +3,7 @@
 //! Supports:
 //! - OBU (Open Bitstream Unit) header parsing and writing
 //! - AV1 Codec Configuration Record (ISO BMFF / MPEG-2 TS)
-//! - Sequence header OBU parsing
+//! - Sequence header OBU parsing and writing
 //! - IVF container format parsing and writing
 //! - Low-overhead OBU bitstream parsing and writing
 //! - Annex B length-delimited bitstream parsing and writing
@@ -2,11 +2,11 @@
 
 use std::io;
 
-use byteorder::{BigEndian, ReadBytesExt};
-use bytes_util::BitReader;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes_util::{BitReader, BitWriter};
 
-use super::ObuHeader;
-use crate::obu::utils::read_uvlc;
+use super::{ObuHeader, ObuType};
+use crate::obu::utils::{read_uvlc, write_uvlc};
 
 /// Sequence Header OBU
 ///
@@ -183,6 +183,23 @@ impl TimingInfo {
             num_ticks_per_picture,
         })
     }
+
+    /// Writes the timing info to the given writer.
+    pub fn build(&self, bit_writer: &mut BitWriter<impl io::Write>) -> io::Result<()> {
+        bit_writer.write_u32::<BigEndian>(self.num_units_in_display_tick)?;
+        bit_writer.write_u32::<BigEndian>(self.time_scale)?;
+        bit_writer.write_bit(self.num_ticks_per_picture.is_some())?; // equal_picture_interval
+        if let Some(num_ticks_per_picture) = self.num_ticks_per_picture {
+            let Some(num_ticks_per_picture_minus_1) = num_ticks_per_picture.checked_sub(1) else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "num_ticks_per_picture must be at least 1",
+                ));
+            };
+            write_uvlc(bit_writer, num_ticks_per_picture_minus_1)?;
+        }
+        Ok(())
+    }
 }
 
 /// Decoder model info
@@ -222,6 +239,27 @@ impl DecoderModelInfo {
             frame_presentation_time_length,
         })
     }
+
+    /// Writes the decoder model info to the given writer.
+    pub fn build(&self, bit_writer: &mut BitWriter<impl io::Write>) -> io::Result<()> {
+        bit_writer.write_bits(minus_1(self.buffer_delay_length, "buffer_delay_length")?, 5)?;
+        bit_writer.write_u32::<BigEndian>(self.num_units_in_decoding_tick)?;
+        bit_writer.write_bits(
+            minus_1(
+                self.buffer_removal_time_length,
+                "buffer_removal_time_length",
+            )?,
+            5,
+        )?;
+        bit_writer.write_bits(
+            minus_1(
+                self.frame_presentation_time_length,
+                "frame_presentation_time_length",
+            )?,
+            5,
+        )?;
+        Ok(())
+    }
 }
 
 /// Operating parameters info
@@ -255,6 +293,18 @@ impl OperatingParametersInfo {
             low_delay_mode_flag,
         })
     }
+
+    /// Writes the operating parameters info to the given writer.
+    pub fn build(
+        &self,
+        delay_bit_length: u8,
+        bit_writer: &mut BitWriter<impl io::Write>,
+    ) -> io::Result<()> {
+        bit_writer.write_bits(self.decoder_buffer_delay, delay_bit_length)?;
+        bit_writer.write_bits(self.encoder_buffer_delay, delay_bit_length)?;
+        bit_writer.write_bit(self.low_delay_mode_flag)?;
+        Ok(())
+    }
 }
 
 /// Color config
@@ -445,6 +495,75 @@ impl ColorConfig {
             })
         }
     }
+
+    /// Writes the color config to the given writer.
+    ///
+    /// `color_description_present_flag` is only set when the color description differs from
+    /// the unspecified defaults.
+    pub fn build(
+        &self,
+        seq_profile: u8,
+        bit_writer: &mut BitWriter<impl io::Write>,
+    ) -> io::Result<()> {
+        let high_bitdepth = match self.bit_depth {
+            8 => false,
+            10 => true,
+            12 if seq_profile == 2 => true,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "unsupported bit_depth for seq_profile",
+                ));
+            }
+        };
+        bit_writer.write_bit(high_bitdepth)?;
+        if seq_profile == 2 && high_bitdepth {
+            bit_writer.write_bit(self.bit_depth == 12)?; // twelve_bit
+        }
+
+        if seq_profile != 1 {
+            bit_writer.write_bit(self.mono_chrome)?;
+        }
+
+        let color_description_present_flag = self.color_primaries != 2
+            || self.transfer_characteristics != 2
+            || self.matrix_coefficients != 2;
+        bit_writer.write_bit(color_description_present_flag)?;
+        if color_description_present_flag {
+            bit_writer.write_bits(self.color_primaries as u64, 8)?;
+            bit_writer.write_bits(self.transfer_characteristics as u64, 8)?;
+            bit_writer.write_bits(self.matrix_coefficients as u64, 8)?;
+        }
+
+        if self.mono_chrome {
+            bit_writer.write_bit(self.full_color_range)?;
+            return Ok(());
+        }
+
+        const CP_BT_709: u8 = 1;
+        const TC_SRGB: u8 = 13;
+        const MC_IDENTITY: u8 = 0;
+
+        if !(self.color_primaries == CP_BT_709
+            && self.transfer_characteristics == TC_SRGB
+            && self.matrix_coefficients == MC_IDENTITY)
+        {
+            bit_writer.write_bit(self.full_color_range)?;
+            if seq_profile > 1 && self.bit_depth == 12 {
+                bit_writer.write_bit(self.subsampling_x)?;
+                if self.subsampling_x {
+                    bit_writer.write_bit(self.subsampling_y)?;
+                }
+            }
+        }
+
+        if self.subsampling_x && self.subsampling_y {
+            bit_writer.write_bits(self.chroma_sample_position as u64, 2)?;
+        }
+
+        bit_writer.write_bit(self.separate_uv_delta_q)?;
+        Ok(())
+    }
 }
 
 impl SequenceHeaderObu {
@@ -666,6 +785,206 @@ impl SequenceHeaderObu {
             film_grain_params_present,
         })
     }
+
+    /// Writes the sequence header OBU, including its OBU header, to the given writer.
+    ///
+    /// The OBU header is always written with `obu_has_size_field` set, `obu_size` is computed
+    /// from the encoded payload. The frame size bit widths are the smallest that fit
+    /// `max_frame_width` and `max_frame_height`.
+    pub fn build(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        let mut bit_writer = BitWriter::new(Vec::new());
+        self.build_payload(&mut bit_writer)?;
+        let payload = bit_writer.finish()?;
+
+        ObuHeader {
+            obu_type: ObuType::SequenceHeader,
+            size: Some(payload.len() as u64),
+            extension_header: self.header.extension_header,
+        }
+        .mux(writer)?;
+        writer.write_all(&payload)
+    }
+
+    fn build_payload(&self, bit_writer: &mut BitWriter<impl io::Write>) -> io::Result<()> {
+        if !self.still_picture && self.reduced_still_picture_header {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "reduced_still_picture_header is true but still_picture is false",
+            ));
+        }
+
+        bit_writer.write_bits(self.seq_profile as u64, 3)?;
+        bit_writer.write_bit(self.still_picture)?;
+        bit_writer.write_bit(self.reduced_still_picture_header)?;
+
+        if self.reduced_still_picture_header {
+            let Some(operating_point) = self.operating_points.first() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "at least one operating point is required",
+                ));
+            };
+            bit_writer.write_bits(operating_point.seq_level_idx as u64, 5)?;
+        } else {
+            bit_writer.write_bit(self.timing_info.is_some())?; // timing_info_present_flag
+            if let Some(timing_info) = &self.timing_info {
+                timing_info.build(bit_writer)?;
+
+                bit_writer.write_bit(self.decoder_model_info.is_some())?; // decoder_model_info_present_flag
+                if let Some(decoder_model_info) = &self.decoder_model_info {
+                    decoder_model_info.build(bit_writer)?;
+                }
+            }
+
+            let initial_display_delay_present_flag = self
+                .operating_points
+                .iter()
+                .any(|op| op.initial_display_delay.is_some());
+            bit_writer.write_bit(initial_display_delay_present_flag)?;
+
+            if self.operating_points.is_empty() || self.operating_points.len() > 32 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "operating_points must contain between 1 and 32 entries",
+                ));
+            }
+            bit_writer.write_bits(self.operating_points.len() as u64 - 1, 5)?; // operating_points_cnt_minus_1
+
+            for op in &self.operating_points {
+                bit_writer.write_bits(op.idc as u64, 12)?;
+                bit_writer.write_bits(op.seq_level_idx as u64, 5)?;
+                if op.seq_level_idx > 7 {
+                    bit_writer.write_bit(op.seq_tier)?;
+                }
+
+                if let Some(decoder_model_info) = &self.decoder_model_info {
+                    // decoder_model_present_for_this_op
+                    bit_writer.write_bit(op.operating_parameters_info.is_some())?;
+                    if let Some(operating_parameters_info) = &op.operating_parameters_info {
+                        operating_parameters_info
+                            .build(decoder_model_info.buffer_delay_length, bit_writer)?;
+                    }
+                }
+
+                if initial_display_delay_present_flag {
+                    // initial_display_delay_present_for_this_op
+                    bit_writer.write_bit(op.initial_display_delay.is_some())?;
+                    if let Some(initial_display_delay) = op.initial_display_delay {
+                        bit_writer.write_bits(
+                            minus_1(initial_display_delay, "initial_display_delay")? as u64,
+                            4,
+                        )?;
+                    }
+                }
+            }
+        }
+
+        let max_frame_width_minus_1 = self.max_frame_width.checked_sub(1);
+        let max_frame_height_minus_1 = self.max_frame_height.checked_sub(1);
+        let (Some(max_frame_width_minus_1), Some(max_frame_height_minus_1)) =
+            (max_frame_width_minus_1, max_frame_height_minus_1)
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "max_frame_width and max_frame_height must be at least 1",
+            ));
+        };
+        let frame_width_bits = bit_width(max_frame_width_minus_1);
+        let frame_height_bits = bit_width(max_frame_height_minus_1);
+        if frame_width_bits > 16 || frame_height_bits > 16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "max_frame_width and max_frame_height must fit in 16 bits",
+            ));
+        }
+        bit_writer.write_bits(frame_width_bits as u64 - 1, 4)?; // frame_width_bits_minus_1
+        bit_writer.write_bits(frame_height_bits as u64 - 1, 4)?; // frame_height_bits_minus_1
+        bit_writer.write_bits(max_frame_width_minus_1, frame_width_bits)?;
+        bit_writer.write_bits(max_frame_height_minus_1, frame_height_bits)?;
+
+        if !self.reduced_still_picture_header {
+            bit_writer.write_bit(self.frame_ids.is_some())?; // frame_id_numbers_present_flag
+        }
+        if let Some(frame_ids) = &self.frame_ids {
+            let Some(delta_frame_id_length_minus_2) =
+                frame_ids.delta_frame_id_length.checked_sub(2)
+            else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "delta_frame_id_length must be at least 2",
+                ));
+            };
+            bit_writer.write_bits(delta_frame_id_length_minus_2 as u64, 4)?;
+            bit_writer.write_bits(
+                minus_1(
+                    frame_ids.additional_frame_id_length,
+                    "additional_frame_id_length",
+                )?,
+                3,
+            )?;
+        }
+
+        bit_writer.write_bit(self.use_128x128_superblock)?;
+        bit_writer.write_bit(self.enable_filter_intra)?;
+        bit_writer.write_bit(self.enable_intra_edge_filter)?;
+
+        if !self.reduced_still_picture_header {
+            bit_writer.write_bit(self.enable_interintra_compound)?;
+            bit_writer.write_bit(self.enable_masked_compound)?;
+            bit_writer.write_bit(self.enable_warped_motion)?;
+            bit_writer.write_bit(self.enable_dual_filter)?;
+            bit_writer.write_bit(self.enable_order_hint)?;
+            if self.enable_order_hint {
+                bit_writer.write_bit(self.enable_jnt_comp)?;
+                bit_writer.write_bit(self.enable_ref_frame_mvs)?;
+            }
+
+            // seq_choose_screen_content_tools
+            bit_writer.write_bit(self.seq_force_screen_content_tools == 2)?;
+            if self.seq_force_screen_content_tools != 2 {
+                bit_writer.write_bits(self.seq_force_screen_content_tools as u64, 1)?;
+            }
+
+            if self.seq_force_screen_content_tools > 0 {
+                // seq_choose_integer_mv
+                bit_writer.write_bit(self.seq_force_integer_mv == 2)?;
+                if self.seq_force_integer_mv != 2 {
+                    bit_writer.write_bits(self.seq_force_integer_mv as u64, 1)?;
+                }
+            }
+
+            if self.enable_order_hint {
+                bit_writer.write_bits(minus_1(self.order_hint_bits, "order_hint_bits")?, 3)?;
+            }
+        }
+
+        bit_writer.write_bit(self.enable_superres)?;
+        bit_writer.write_bit(self.enable_cdef)?;
+        bit_writer.write_bit(self.enable_restoration)?;
+
+        self.color_config.build(self.seq_profile, bit_writer)?;
+
+        bit_writer.write_bit(self.film_grain_params_present)?;
+
+        // trailing_bits
+        bit_writer.write_bit(true)?;
+        bit_writer.align()
+    }
+}
+
+/// Returns the number of bits needed to represent `value`, at least 1.
+fn bit_width(value: u64) -> u8 {
+    (64 - value.leading_zeros()).max(1) as u8
+}
+
+/// Returns `value - 1`, or an error naming `field` if `value` is 0.
+fn minus_1(value: u8, field: &str) -> io::Result<u64> {
+    value.checked_sub(1).map(u64::from).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{field} must be at least 1"),
+        )
+    })
 }
 
 #[cfg(test)]
@@ -677,6 +996,25 @@ mod tests {
     use super::*;
     use crate::ObuType;
 
+    #[test]
+    fn test_seq_obu_build_round_trip() {
+        let data = b"\n\x0f\0\0\0j\xef\xbf\xe1\xbc\x02\x19\x90\x10\x10\x10@";
+        let mut cursor = io::Cursor::new(data.as_slice());
+        let header = ObuHeader::parse(&mut cursor).unwrap();
+        let seq_header = SequenceHeaderObu::parse(header, &mut cursor).unwrap();
+
+        let mut buf = Vec::new();
+        seq_header.build(&mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        let mut cursor = io::Cursor::new(buf.as_slice());
+        let header = ObuHeader::parse(&mut cursor).unwrap();
+        assert_eq!(
+            SequenceHeaderObu::parse(header, &mut cursor).unwrap(),
+            seq_header
+        );
+    }
+
     #[test]
     fn test_seq_obu_parse() {
         let obu = b"\0\0\0j\xef\xbf\xe1\xbc\x02\x19\x90\x10\x10\x10@";
@@ -1,6 +1,6 @@
 use std::io;
 
-use bytes_util::BitReader;
+use bytes_util::{BitReader, BitWriter};
 
 /// Read a little-endian variable-length integer.
 /// AV1-Spec-2 - 4.10.5
@@ -59,9 +59,9 @@ pub fn leb128_size(mut value: u64) -> usize {
 /// Read a variable-length unsigned integer.
 /// AV1-Spec-2 - 4.10.3
 pub fn read_uvlc<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<u64> {
-    let mut leading_zeros = 0;
+    let mut leading_zeros: u8 = 0;
     while !reader.read_bit()? {
-        leading_zeros += 1;
+        leading_zeros = leading_zeros.saturating_add(1);
     }
 
     if leading_zeros >= 32 {
@@ -72,6 +72,29 @@ pub fn read_uvlc<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<u64> {
     Ok(value + (1 << leading_zeros) - 1)
 }
 
+/// Write a variable-length unsigned integer.
+/// AV1-Spec-2 - 4.10.3
+///
+/// Values above `(1 << 32) - 1` cannot be represented and are rejected.
+pub fn write_uvlc<W: io::Write>(writer: &mut BitWriter<W>, value: u64) -> io::Result<()> {
+    if value > u32::MAX as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "uvlc value exceeds u32::MAX",
+        ));
+    }
+
+    if value == u32::MAX as u64 {
+        writer.write_bits(0, 32)?;
+        return writer.write_bit(true);
+    }
+
+    let leading_zeros = 63 - (value + 1).leading_zeros() as u8;
+    writer.write_bits(0, leading_zeros)?;
+    writer.write_bit(true)?;
+    writer.write_bits((value + 1) & ((1 << leading_zeros) - 1), leading_zeros)
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
@@ -171,5 +194,48 @@ mod tests {
         let mut cursor = std::io::Cursor::new([0x00, 0x00, 0x00, 0x00, 0x01]);
         let mut reader = BitReader::new(&mut cursor);
         assert_eq!(read_uvlc(&mut reader).unwrap(), (1 << 32) - 1);
+
+        // More leading zeros than fit in a u8
+        let mut data = vec![0x00; 40];
+        data.push(0x80);
+        let mut cursor = std::io::Cursor::new(data);
+        let mut reader = BitReader::new(&mut cursor);
+        assert_eq!(read_uvlc(&mut reader).unwrap(), (1 << 32) - 1);
+
+        let mut cursor = std::io::Cursor::new([0x00; 40]);
+        let mut reader = BitReader::new(&mut cursor);
+        let err = read_uvlc(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_write_uvlc_round_trip() {
+        let values = [
+            0,
+            1,
+            2,
+            0xfe,
+            0xff,
+            1 << 20,
+            u32::MAX as u64 - 1,
+            u32::MAX as u64,
+        ];
+        for value in values {
+            let mut writer = BitWriter::new(Vec::new());
+            write_uvlc(&mut writer, value).unwrap();
+            let buf = writer.finish().unwrap();
+
+            let mut cursor = std::io::Cursor::new(buf);
+            let mut reader = BitReader::new(&mut cursor);
+            assert_eq!(
+                read_uvlc(&mut reader).unwrap(),
+                value,
+                "round-trip failed for {value}"
+            );
+        }
+
+        let mut writer = BitWriter::new(Vec::new());
+        let err = write_uvlc(&mut writer, u32::MAX as u64 + 1).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
     }
 }
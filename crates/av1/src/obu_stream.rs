@@ -10,19 +10,22 @@
 //! `obu_has_size_field=0`, since the container frame boundary implies
 //! the remaining size.
 //!
-//! This module provides two iterators:
+//! This module provides three iterators:
 //! - [`ObuIterator`] for strict low-overhead streams (`obu_has_size_field=1`)
 //! - [`ContainerObuIterator`] for container sample/block payloads
 //!   (allows last OBU without a size field)
+//! - [`ObuSliceIterator`] for borrowed byte slices, reporting the byte offset
+//!   of malformed OBUs
 
 use std::io;
 
 use bytes::Bytes;
-use bytes_util::BytesCursorExt;
+use bytes_util::{BitReader, BytesCursorExt};
 
 use crate::error::{Av1Error, Result};
 use crate::obu::utils::leb128_size;
 use crate::obu::{ObuExtensionHeader, ObuHeader, ObuType};
+use crate::seq::SequenceHeaderObu;
 
 /// A single OBU with its header and payload data.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -90,6 +93,106 @@ impl Iterator for ContainerObuIterator<'_> {
     }
 }
 
+/// Iterator over OBUs in a borrowed byte slice.
+///
+/// Yields each OBU header together with its payload. If `obu_has_size_field=0`,
+/// the payload consumes the remaining bytes. Errors include the byte offset of
+/// the offending OBU header, and the iterator stops after the first error.
+pub struct ObuSliceIterator<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ObuSliceIterator<'a> {
+    /// Creates a new iterator over the OBUs in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for ObuSliceIterator<'a> {
+    type Item = Result<(ObuHeader, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.data.get(self.offset..).filter(|r| !r.is_empty())?;
+        let offset = self.offset;
+
+        let mut cursor = io::Cursor::new(remaining);
+        let header = match ObuHeader::parse(&mut cursor) {
+            Ok(header) => header,
+            Err(err) => {
+                self.offset = self.data.len();
+                return Some(Err(Av1Error::InvalidObuAt {
+                    offset,
+                    reason: err.to_string(),
+                }));
+            }
+        };
+
+        let header_len = cursor.position() as usize;
+        let available = remaining.len() - header_len;
+        let size = header.size.unwrap_or(available as u64);
+        if size > available as u64 {
+            self.offset = self.data.len();
+            return Some(Err(Av1Error::InvalidObuAt {
+                offset,
+                reason: format!("obu_size {size} exceeds the {available} remaining bytes"),
+            }));
+        }
+
+        let payload = &remaining[header_len..header_len + size as usize];
+        self.offset += header_len + size as usize;
+
+        Some(Ok((header, payload)))
+    }
+}
+
+/// `KEY_FRAME`
+const KEY_FRAME: u64 = 0;
+
+/// Returns whether the given temporal unit starts with a shown key frame.
+///
+/// Walks the OBUs in `data` and inspects the first frame header (from an
+/// `OBU_FRAME_HEADER` or `OBU_FRAME`). Temporal delimiters, padding and other
+/// OBUs are skipped. A sequence header in the same temporal unit is used to
+/// detect `reduced_still_picture_header`, which implies a key frame.
+///
+/// Frames shown with `show_existing_frame=1` are not reported as key frames,
+/// as the type of the referenced frame is not known without decoder state.
+pub fn is_keyframe_temporal_unit(data: &[u8]) -> io::Result<bool> {
+    let mut reduced_still_picture_header = false;
+
+    for obu in ObuSliceIterator::new(data) {
+        let (header, payload) =
+            obu.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+        match header.obu_type {
+            ObuType::SequenceHeader => {
+                let seq_header = SequenceHeaderObu::parse(header, &mut io::Cursor::new(payload))?;
+                reduced_still_picture_header = seq_header.reduced_still_picture_header;
+            }
+            ObuType::FrameHeader | ObuType::Frame => {
+                if reduced_still_picture_header {
+                    return Ok(true);
+                }
+
+                let mut bit_reader = BitReader::new(io::Cursor::new(payload));
+                let show_existing_frame = bit_reader.read_bit()?;
+                if show_existing_frame {
+                    return Ok(false);
+                }
+
+                let frame_type = bit_reader.read_bits(2)?;
+                let show_frame = bit_reader.read_bit()?;
+                return Ok(frame_type == KEY_FRAME && show_frame);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(false)
+}
+
 /// Parses a single OBU from a `Cursor<Bytes>`, using zero-copy for the payload.
 fn parse_obu(reader: &mut io::Cursor<Bytes>) -> Result<Obu> {
     let header = ObuHeader::parse(reader)?;
@@ -344,4 +447,106 @@ mod tests {
 
         assert!(iter.next().is_none());
     }
+
+    #[test]
+    fn test_slice_iterator_sized_and_unsized() {
+        let mut data = Vec::new();
+        write_obu(&mut data, ObuType::TemporalDelimiter, None, &[]).unwrap();
+        write_obu(
+            &mut data,
+            ObuType::Padding,
+            Some(ObuExtensionHeader {
+                temporal_id: 1,
+                spatial_id: 0,
+            }),
+            &[0x00, 0x00],
+        )
+        .unwrap();
+        ObuHeader {
+            obu_type: ObuType::Frame,
+            size: None,
+            extension_header: None,
+        }
+        .mux(&mut data)
+        .unwrap();
+        data.extend_from_slice(&[0xAA, 0xBB]);
+
+        let obus: Vec<_> = ObuSliceIterator::new(&data).collect::<Result<_>>().unwrap();
+        assert_eq!(obus.len(), 3);
+        assert_eq!(obus[0].0.obu_type, ObuType::TemporalDelimiter);
+        assert_eq!(obus[1].0.obu_type, ObuType::Padding);
+        assert_eq!(obus[1].0.extension_header.unwrap().temporal_id, 1);
+        assert_eq!(obus[1].1, &[0x00, 0x00]);
+        assert_eq!(obus[2].0.size, None);
+        assert_eq!(obus[2].1, &[0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn test_slice_iterator_forbidden_bit_reports_offset() {
+        let mut data = Vec::new();
+        write_obu(&mut data, ObuType::TemporalDelimiter, None, &[]).unwrap();
+        write_obu(&mut data, ObuType::Metadata, None, &[0x01, 0x02]).unwrap();
+        data.push(0x80);
+
+        let mut iter = ObuSliceIterator::new(&data);
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_ok());
+        let err = iter.next().unwrap().unwrap_err();
+        assert!(matches!(err, Av1Error::InvalidObuAt { offset: 6, .. }));
+        assert!(err.to_string().contains("obu_forbidden_bit"));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_slice_iterator_size_exceeds_buffer() {
+        let data = [0x12, 0x05, 0x00];
+        let err = ObuSliceIterator::new(&data).next().unwrap().unwrap_err();
+        assert!(matches!(err, Av1Error::InvalidObuAt { offset: 0, .. }));
+    }
+
+    #[test]
+    fn test_is_keyframe_temporal_unit() {
+        let temporal_unit = |frame_header: u8| {
+            let mut data = Vec::new();
+            write_obu(&mut data, ObuType::TemporalDelimiter, None, &[]).unwrap();
+            write_obu(&mut data, ObuType::Padding, None, &[0x00]).unwrap();
+            write_obu(
+                &mut data,
+                ObuType::Frame,
+                Some(ObuExtensionHeader {
+                    temporal_id: 0,
+                    spatial_id: 0,
+                }),
+                &[frame_header, 0x00],
+            )
+            .unwrap();
+            data
+        };
+
+        // show_existing_frame=0, frame_type=KEY_FRAME, show_frame=1
+        assert!(is_keyframe_temporal_unit(&temporal_unit(0b0001_0000)).unwrap());
+        // show_existing_frame=0, frame_type=INTER_FRAME, show_frame=1
+        assert!(!is_keyframe_temporal_unit(&temporal_unit(0b0011_0000)).unwrap());
+        // show_existing_frame=0, frame_type=KEY_FRAME, show_frame=0
+        assert!(!is_keyframe_temporal_unit(&temporal_unit(0b0000_0000)).unwrap());
+        // show_existing_frame=1
+        assert!(!is_keyframe_temporal_unit(&temporal_unit(0b1000_0000)).unwrap());
+
+        // No frame header at all
+        let mut data = Vec::new();
+        write_obu(&mut data, ObuType::TemporalDelimiter, None, &[]).unwrap();
+        assert!(!is_keyframe_temporal_unit(&data).unwrap());
+
+        let err = is_keyframe_temporal_unit(&[0x80]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_is_keyframe_temporal_unit_with_sequence_header() {
+        let mut data = Vec::new();
+        write_obu(&mut data, ObuType::TemporalDelimiter, None, &[]).unwrap();
+        data.extend_from_slice(b"\n\x0f\0\0\0j\xef\xbf\xe1\xbc\x02\x19\x90\x10\x10\x10@");
+        write_obu(&mut data, ObuType::FrameHeader, None, &[0b0001_0000]).unwrap();
+        assert!(is_keyframe_temporal_unit(&data).unwrap());
+    }
 }
@@ -7,6 +7,7 @@ pub struct BitReader<T> {
     data: T,
     bit_pos: u8,
     current_byte: u8,
+    position: u64,
 }
 
 impl<T> BitReader<T> {
@@ -16,6 +17,7 @@ impl<T> BitReader<T> {
             data,
             bit_pos: 0,
             current_byte: 0,
+            position: 0,
         }
     }
 }
@@ -30,6 +32,7 @@ impl<T: io::Read> BitReader<T> {
         let bit = (self.current_byte >> (7 - self.bit_pos)) & 1;
 
         self.bit_pos = (self.bit_pos + 1) % 8;
+        self.position += 1;
 
         Ok(bit == 1)
     }
@@ -60,6 +63,9 @@ impl<T: io::Read> BitReader<T> {
     pub fn align(&mut self) -> io::Result<()> {
         // This has the effect of making the next read_bit call read the next byte
         // and is equivalent to calling read_bits(8 - self.bit_pos)
+        if !self.is_aligned() {
+            self.position += 8 - self.bit_pos as u64;
+        }
         self.bit_pos = 0;
         Ok(())
     }
@@ -87,6 +93,18 @@ impl<T> BitReader<T> {
         self.bit_pos
     }
 
+    /// Returns the number of bits consumed since the reader was created
+    ///
+    /// Seeking moves this position by the same amount as the stream position,
+    /// so for readers created at the start of their stream (such as
+    /// [`BitReader::new_from_slice`]) this is equal to
+    /// [`bit_stream_position`](Self::bit_stream_position).
+    #[inline(always)]
+    #[must_use]
+    pub const fn bit_position(&self) -> u64 {
+        self.position
+    }
+
     /// Checks if the reader is aligned to the byte boundary
     #[inline(always)]
     #[must_use]
@@ -100,7 +118,9 @@ impl<T: io::Read> io::Read for BitReader<T> {
         // If we are aligned this will be essentially the same as just reading directly
         // from the underlying reader.
         if self.is_aligned() {
-            return self.data.read(buf);
+            let read = self.data.read(buf)?;
+            self.position += read as u64 * 8;
+            return Ok(read);
         }
 
         // However if we are not aligned we need to shift all the bits into the correct
@@ -154,6 +174,8 @@ impl<W: io::Seek + io::Read> BitReader<W> {
             return self.bit_stream_position();
         }
 
+        let before = self.bit_stream_position()?;
+
         let count = self.bit_pos as i64 + count;
 
         // Otherwise we need to do some work to move the bit position to the desired
@@ -192,8 +214,63 @@ impl<W: io::Seek + io::Read> BitReader<W> {
             pos += self.bit_pos as u64;
         }
 
+        self.move_position(before, pos);
+
         Ok(pos)
     }
+
+    /// Seeks to a position in bits, relative to the start, the end or the
+    /// current position of the stream
+    /// Returns the new stream position in bits
+    pub fn seek_to_bit(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        // Byte seeks go through the io::Seek impl below, which keeps bit_position in sync
+        let target = match pos {
+            io::SeekFrom::Start(bits) => bits,
+            io::SeekFrom::Current(offset) => return self.seek_bits(offset),
+            io::SeekFrom::End(offset) => {
+                let end = io::Seek::seek(self, io::SeekFrom::End(0))? * 8;
+                end.checked_add_signed(offset).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "seek to a negative bit position",
+                    )
+                })?
+            }
+        };
+
+        io::Seek::seek(self, io::SeekFrom::Start(target / 8))?;
+
+        // Same as in seek_bits, a partially read byte has to be loaded now
+        if target % 8 != 0 {
+            self.update_byte()?;
+            self.bit_pos = (target % 8) as u8;
+            self.position += self.bit_pos as u64;
+        }
+
+        Ok(target)
+    }
+
+    /// Reads up to 64 bits without advancing the reader
+    ///
+    /// The reader is restored to its previous position even if there are not
+    /// enough bits left.
+    pub fn peek_bits(&mut self, count: u8) -> io::Result<u64> {
+        let pos = self.bit_stream_position()?;
+        let bits = self.read_bits(count);
+        self.seek_to_bit(io::SeekFrom::Start(pos))?;
+        bits
+    }
+
+    /// Reads a single bit without advancing the reader
+    pub fn peek_bit(&mut self) -> io::Result<bool> {
+        Ok(self.peek_bits(1)? == 1)
+    }
+
+    /// Moves [`bit_position`](Self::bit_position) by the distance between two
+    /// stream positions.
+    fn move_position(&mut self, before: u64, after: u64) {
+        self.position = self.position.wrapping_add(after.wrapping_sub(before));
+    }
 }
 
 impl<T: io::Seek + io::Read> io::Seek for BitReader<T> {
@@ -208,8 +285,11 @@ impl<T: io::Seek + io::Read> io::Seek for BitReader<T> {
             // Otherwise we are seeking to a position relative to the start or end of the stream, we dont care about the bit
             // position Or the bit position is already 0 so we can just seek to the new position
             _ => {
+                let before = self.bit_stream_position()?;
                 self.bit_pos = 0;
-                self.data.seek(pos)
+                let pos = self.data.seek(pos)?;
+                self.move_position(before, pos * 8);
+                Ok(pos)
             }
         }
     }
@@ -378,4 +458,76 @@ mod tests {
         assert_eq!(reader.bit_pos(), 1);
         assert_eq!(reader.data.stream_position().unwrap(), 4);
     }
+
+    #[test]
+    fn test_bit_reader_peek() {
+        let mut reader = BitReader::new_from_slice([0b1010_0101, 0b1100_0011]);
+
+        assert_eq!(reader.peek_bits(4).unwrap(), 0b1010);
+        assert!(reader.peek_bit().unwrap());
+        assert_eq!(reader.bit_position(), 0);
+
+        assert_eq!(reader.read_bits(6).unwrap(), 0b101001);
+
+        // across the byte boundary
+        assert_eq!(reader.peek_bits(6).unwrap(), 0b01_1100);
+        assert_eq!(reader.bit_pos(), 6);
+        assert_eq!(reader.bit_position(), 6);
+        assert_eq!(reader.read_bits(2).unwrap(), 0b01);
+
+        // at the byte boundary the next byte has not been consumed
+        assert!(reader.is_aligned());
+        assert!(reader.peek_bit().unwrap());
+        assert_eq!(reader.data.stream_position().unwrap(), 1);
+        assert_eq!(reader.read_bits(8).unwrap(), 0b1100_0011);
+        assert_eq!(reader.bit_position(), 16);
+    }
+
+    #[test]
+    fn test_bit_reader_peek_eof() {
+        let mut reader = BitReader::new_from_slice([0b1010_0101]);
+        assert_eq!(reader.read_bits(5).unwrap(), 0b10100);
+
+        let err = reader.peek_bits(4).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // the failed peek did not move the reader
+        assert_eq!(reader.bit_position(), 5);
+        assert_eq!(reader.bit_stream_position().unwrap(), 5);
+        assert_eq!(reader.peek_bits(3).unwrap(), 0b101);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+
+        assert!(reader.peek_bit().is_err());
+        assert_eq!(reader.bit_position(), 8);
+        assert!(reader.read_bit().is_err());
+    }
+
+    #[test]
+    fn test_bit_reader_seek_to_bit() {
+        let binary = 0b10101010110011001111000101010101u32;
+        let mut reader = BitReader::new_from_slice(binary.to_be_bytes());
+
+        assert_eq!(reader.seek_to_bit(io::SeekFrom::Start(9)).unwrap(), 9);
+        assert_eq!(reader.bit_position(), 9);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b100);
+
+        assert_eq!(reader.seek_to_bit(io::SeekFrom::Current(-4)).unwrap(), 8);
+        assert_eq!(reader.bit_position(), 8);
+        assert_eq!(reader.read_bits(4).unwrap(), 0b1100);
+
+        assert_eq!(reader.seek_to_bit(io::SeekFrom::End(-3)).unwrap(), 29);
+        assert_eq!(reader.bit_position(), 29);
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert!(reader.read_bit().is_err());
+
+        assert_eq!(reader.seek_to_bit(io::SeekFrom::End(-32)).unwrap(), 0);
+        assert_eq!(reader.bit_position(), 0);
+        assert!(reader.seek_to_bit(io::SeekFrom::End(-33)).is_err());
+
+        assert_eq!(reader.seek(io::SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(reader.bit_position(), 16);
+        reader.read_bits(3).unwrap();
+        reader.align().unwrap();
+        assert_eq!(reader.bit_position(), 24);
+    }
 }
@@ -6,6 +6,7 @@ use std::io;
 pub struct BitWriter<W> {
     bit_pos: u8,
     current_byte: u8,
+    bytes_written: u64,
     writer: W,
 }
 
@@ -30,6 +31,7 @@ impl<W: io::Write> BitWriter<W> {
             self.writer.write_all(&[self.current_byte])?;
             self.current_byte = 0;
             self.bit_pos = 0;
+            self.bytes_written += 1;
         }
 
         Ok(())
@@ -62,7 +64,9 @@ impl<W: io::Write> BitWriter<W> {
         Ok(self.writer)
     }
 
-    /// Aligns the writer to the byte boundary
+    /// Aligns the writer to the byte boundary by writing zero bits
+    ///
+    /// Does nothing if the writer is already aligned.
     pub fn align(&mut self) -> io::Result<()> {
         if !self.is_aligned() {
             self.write_bits(0, 8 - self.bit_pos())?;
@@ -70,6 +74,35 @@ impl<W: io::Write> BitWriter<W> {
 
         Ok(())
     }
+
+    /// Writes the `rbsp_trailing_bits`: a single one bit followed by zero bits
+    /// up to the next byte boundary
+    ///
+    /// If the writer is already aligned this writes a full `0x80` byte.
+    pub fn write_trailing_one_and_align(&mut self) -> io::Result<()> {
+        self.write_bit(true)?;
+        self.align()
+    }
+
+    /// Writes whole bytes to the stream, failing if the writer is not aligned
+    ///
+    /// Unlike [`io::Write::write_all`], which shifts the bytes across the
+    /// current bit offset, this returns an [`io::ErrorKind::InvalidInput`]
+    /// error so a missing [`align`](Self::align) is caught instead of silently
+    /// misaligning the output.
+    pub fn write_bytes_aligned(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if !self.is_aligned() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "writer is not byte aligned",
+            ));
+        }
+
+        self.writer.write_all(bytes)?;
+        self.bytes_written += bytes.len() as u64;
+
+        Ok(())
+    }
 }
 
 impl<W> BitWriter<W> {
@@ -78,6 +111,7 @@ impl<W> BitWriter<W> {
         Self {
             bit_pos: 0,
             current_byte: 0,
+            bytes_written: 0,
             writer,
         }
     }
@@ -89,6 +123,14 @@ impl<W> BitWriter<W> {
         self.bit_pos % 8
     }
 
+    /// Returns the number of bits written since the writer was created,
+    /// including bits still buffered in the current byte
+    #[inline(always)]
+    #[must_use]
+    pub const fn bit_position(&self) -> u64 {
+        self.bytes_written * 8 + self.bit_pos as u64
+    }
+
     /// Checks if the writer is aligned to the byte boundary
     #[inline(always)]
     #[must_use]
@@ -107,7 +149,9 @@ impl<W> BitWriter<W> {
 impl<W: io::Write> io::Write for BitWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         if self.is_aligned() {
-            return self.writer.write(buf);
+            let n = self.writer.write(buf)?;
+            self.bytes_written += n as u64;
+            return Ok(n);
         }
 
         for byte in buf {
@@ -241,6 +285,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_align_emits_zero_padding() {
+        let mut bit_writer = BitWriter::<Vec<u8>>::default();
+
+        // Aligned: nothing is written
+        bit_writer.align().unwrap();
+        assert_eq!(bit_writer.bit_position(), 0);
+        assert!(bit_writer.get_ref().is_empty());
+
+        // Misaligned: the remaining 5 bits are zero, even if set before
+        bit_writer.write_bits(0b101, 3).unwrap();
+        assert_eq!(bit_writer.bit_position(), 3);
+        bit_writer.align().unwrap();
+        assert_eq!(bit_writer.bit_position(), 8);
+        assert_eq!(bit_writer.get_ref().as_slice(), &[0b10100000]);
+    }
+
+    #[test]
+    fn test_write_trailing_one_and_align() {
+        // Aligned: a full 0x80 byte
+        let mut bit_writer = BitWriter::<Vec<u8>>::default();
+        bit_writer.write_trailing_one_and_align().unwrap();
+        assert_eq!(bit_writer.bit_position(), 8);
+        assert_eq!(bit_writer.finish().unwrap(), vec![0b10000000]);
+
+        // Misaligned: stop bit then zeros in the same byte
+        let mut bit_writer = BitWriter::<Vec<u8>>::default();
+        bit_writer.write_bits(0b11, 2).unwrap();
+        bit_writer.write_trailing_one_and_align().unwrap();
+        assert_eq!(bit_writer.finish().unwrap(), vec![0b11100000]);
+
+        // One bit left: the stop bit fills the byte with no padding
+        let mut bit_writer = BitWriter::<Vec<u8>>::default();
+        bit_writer.write_bits(0, 7).unwrap();
+        bit_writer.write_trailing_one_and_align().unwrap();
+        assert_eq!(bit_writer.bit_position(), 8);
+        assert_eq!(bit_writer.finish().unwrap(), vec![0b00000001]);
+    }
+
+    #[test]
+    fn test_write_bytes_aligned() {
+        let mut bit_writer = BitWriter::<Vec<u8>>::default();
+
+        bit_writer.write_bytes_aligned(&[1, 2]).unwrap();
+        assert_eq!(bit_writer.bit_position(), 16);
+
+        bit_writer.write_bit(true).unwrap();
+        let err = bit_writer.write_bytes_aligned(&[3]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "writer is not byte aligned");
+        assert_eq!(bit_writer.bit_position(), 17);
+
+        bit_writer.align().unwrap();
+        bit_writer.write_bytes_aligned(&[3]).unwrap();
+        assert_eq!(bit_writer.bit_position(), 32);
+        assert_eq!(bit_writer.finish().unwrap(), vec![1, 2, 0b10000000, 3]);
+    }
+
+    #[test]
+    fn test_bit_position_counts_io_writes() {
+        let mut bit_writer = BitWriter::<Vec<u8>>::default();
+
+        bit_writer.write_all(&[1, 2, 3]).unwrap();
+        assert_eq!(bit_writer.bit_position(), 24);
+
+        bit_writer.write_bits(0b1, 1).unwrap();
+        bit_writer.write_all(&[0xff]).unwrap();
+        assert_eq!(bit_writer.bit_position(), 33);
+    }
+
     #[test]
     fn test_flush() {
         let mut inner = Vec::new();
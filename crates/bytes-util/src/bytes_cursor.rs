@@ -32,6 +32,26 @@ pub trait BytesCursorExt {
     /// buffer, however this is more efficient as it does not copy the
     /// bytes.
     fn extract_bytes(&mut self, size: usize) -> io::Result<Bytes>;
+
+    /// Reads a length using `read_len` and extracts that many bytes from the
+    /// cursor.
+    ///
+    /// This does not do a copy of the bytes. Returns an
+    /// [`io::ErrorKind::UnexpectedEof`] error if either the length or the bytes
+    /// cannot be read.
+    fn read_slice_prefixed<F>(&mut self, read_len: F) -> io::Result<Bytes>
+    where
+        F: FnOnce(&mut Self) -> io::Result<usize>;
+
+    /// Reads a big endian `u16` length followed by that many bytes.
+    ///
+    /// See [`read_slice_prefixed`](Self::read_slice_prefixed).
+    fn read_slice_u16_be(&mut self) -> io::Result<Bytes>;
+
+    /// Reads a big endian `u32` length followed by that many bytes.
+    ///
+    /// See [`read_slice_prefixed`](Self::read_slice_prefixed).
+    fn read_slice_u32_be(&mut self) -> io::Result<Bytes>;
 }
 
 fn remaining(cursor: &BytesCursor) -> usize {
@@ -57,10 +77,11 @@ impl BytesCursorExt for BytesCursor {
 
         // If the size is greater than the remaining bytes we can just return an
         // error.
-        if size > remaining(self) {
+        let available = remaining(self);
+        if size > available {
             return Err(io::Error::new(
                 io::ErrorKind::UnexpectedEof,
-                "not enough bytes",
+                format!("not enough bytes: requested {size}, available {available}"),
             ));
         }
 
@@ -75,6 +96,30 @@ impl BytesCursorExt for BytesCursor {
 
         Ok(slice)
     }
+
+    fn read_slice_prefixed<F>(&mut self, read_len: F) -> io::Result<Bytes>
+    where
+        F: FnOnce(&mut Self) -> io::Result<usize>,
+    {
+        let len = read_len(self)?;
+        self.extract_bytes(len)
+    }
+
+    fn read_slice_u16_be(&mut self) -> io::Result<Bytes> {
+        self.read_slice_prefixed(|cursor| {
+            let mut buf = [0; 2];
+            io::Read::read_exact(cursor, &mut buf)?;
+            Ok(u16::from_be_bytes(buf) as usize)
+        })
+    }
+
+    fn read_slice_u32_be(&mut self) -> io::Result<Bytes> {
+        self.read_slice_prefixed(|cursor| {
+            let mut buf = [0; 4];
+            io::Read::read_exact(cursor, &mut buf)?;
+            Ok(u32::from_be_bytes(buf) as usize)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -112,6 +157,79 @@ mod tests {
         assert_eq!(remaining(&cursor), 0);
     }
 
+    #[test]
+    fn test_bytes_cursor_extract_bytes_error() {
+        let mut cursor = io::Cursor::new(Bytes::from_static(&[1, 2, 3]));
+        let err = cursor.extract_bytes(4).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "not enough bytes: requested 4, available 3"
+        );
+        assert_eq!(remaining(&cursor), 3);
+    }
+
+    #[test]
+    fn test_bytes_cursor_read_slice_u16_be() {
+        // length exactly equal to the remaining bytes
+        let data = Bytes::from_static(&[0, 3, 1, 2, 3]);
+        let mut cursor = io::Cursor::new(data.clone());
+        let slice = cursor.read_slice_u16_be().unwrap();
+        assert_eq!(slice, Bytes::from_static(&[1, 2, 3]));
+        assert_eq!(
+            slice.as_ptr(),
+            data[2..].as_ptr(),
+            "the slice should not be copied"
+        );
+        assert_eq!(remaining(&cursor), 0);
+
+        // length one past the remaining bytes
+        let mut cursor = io::Cursor::new(Bytes::from_static(&[0, 4, 1, 2, 3]));
+        let err = cursor.read_slice_u16_be().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "not enough bytes: requested 4, available 3"
+        );
+
+        // truncated length
+        let mut cursor = io::Cursor::new(Bytes::from_static(&[0]));
+        let err = cursor.read_slice_u16_be().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut cursor = io::Cursor::new(Bytes::from_static(&[0, 0]));
+        assert_eq!(cursor.read_slice_u16_be().unwrap(), Bytes::new());
+    }
+
+    #[test]
+    fn test_bytes_cursor_read_slice_u32_be() {
+        let mut cursor = io::Cursor::new(Bytes::from_static(&[0, 0, 0, 2, 1, 2, 0, 0, 0, 1]));
+        assert_eq!(
+            cursor.read_slice_u32_be().unwrap(),
+            Bytes::from_static(&[1, 2])
+        );
+
+        let err = cursor.read_slice_u32_be().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "not enough bytes: requested 1, available 0"
+        );
+    }
+
+    #[test]
+    fn test_bytes_cursor_read_slice_prefixed() {
+        let mut cursor = io::Cursor::new(Bytes::from_static(&[2, 1, 2, 3]));
+        let slice = cursor
+            .read_slice_prefixed(|cursor| {
+                let mut buf = [0];
+                io::Read::read_exact(cursor, &mut buf)?;
+                Ok(buf[0] as usize)
+            })
+            .unwrap();
+        assert_eq!(slice, Bytes::from_static(&[1, 2]));
+        assert_eq!(cursor.extract_remaining(), Bytes::from_static(&[3]));
+    }
+
     #[test]
     fn seek_out_of_bounds() {
         let mut cursor = io::Cursor::new(Bytes::from_static(&[1, 2, 3, 4, 5]));
@@ -1,9 +1,14 @@
+use std::io;
+
 /// A wrapper around a [`std::io::Read`] or [`std::io::Write`] that automatically inserts or removes
 /// [NAL](https://en.wikipedia.org/wiki/Network_Abstraction_Layer) emulation prevention bytes, when reading or writing respectively.
 ///
 /// Defined by:
 /// - ISO/IEC 14496-10 - 7.4.1.1
 /// - ISO/IEC 23008-2 - 7.4.2.3
+///
+/// The state is carried across calls, so a `0x00 0x00` run split over multiple reads or writes is
+/// handled the same as one contained in a single buffer.
 pub struct EmulationPreventionIo<I> {
     inner: I,
     zero_count: u8,
@@ -19,34 +24,67 @@ impl<I> EmulationPreventionIo<I> {
             zero_count: 0,
         }
     }
+
+    /// Returns a reference to the underlying reader or writer.
+    pub fn get_ref(&self) -> &I {
+        &self.inner
+    }
+
+    /// Consumes the wrapper and returns the underlying reader or writer.
+    ///
+    /// When writing, prefer [`EmulationPreventionIo::finish`] so a trailing zero byte is escaped.
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    fn track(&mut self, byte: u8) {
+        if byte == 0x00 {
+            self.zero_count = self.zero_count.saturating_add(1);
+        } else {
+            self.zero_count = 0;
+        }
+    }
 }
 
-impl<I: std::io::Write> std::io::Write for EmulationPreventionIo<I> {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+impl<I: io::Write> EmulationPreventionIo<I> {
+    /// Terminates the NAL unit and returns the underlying writer.
+    ///
+    /// If the payload ended in a `0x00 0x00` run (a trailing `cabac_zero_word`), a final `0x03`
+    /// is appended as required by ISO/IEC 14496-10 - 7.4.1 so the payload cannot run into the
+    /// next start code.
+    pub fn finish(mut self) -> io::Result<I> {
+        if self.zero_count >= 2 {
+            self.inner.write_all(&[0x03])?;
+            self.zero_count = 0;
+        }
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<I: io::Write> io::Write for EmulationPreventionIo<I> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         for &byte in buf {
-            if self.zero_count >= 2 && (byte <= 0x03) {
-                self.inner.write_all(&[0x3])?;
+            if self.zero_count >= 2 && byte <= 0x03 {
+                self.inner.write_all(&[0x03])?;
                 self.zero_count = 0;
             }
 
             self.inner.write_all(&[byte])?;
-            if byte == 0x00 {
-                self.zero_count += 1;
-            } else {
-                self.zero_count = 0;
-            }
+            self.track(byte);
         }
 
         Ok(buf.len())
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
+    fn flush(&mut self) -> io::Result<()> {
         self.inner.flush()
     }
 }
 
-impl<I: std::io::Read> std::io::Read for EmulationPreventionIo<I> {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+impl<I: io::Read> io::Read for EmulationPreventionIo<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         let mut read_size = 0;
         let mut one_byte = [0; 1];
         while buf.len() > read_size {
@@ -56,19 +94,12 @@ impl<I: std::io::Read> std::io::Read for EmulationPreventionIo<I> {
             }
 
             let byte = one_byte[0];
-            match byte {
-                0x03 if self.zero_count >= 2 => {
-                    self.zero_count = 0;
-                    continue;
-                }
-                0x00 => {
-                    self.zero_count += 1;
-                }
-                _ => {
-                    self.zero_count = 0;
-                }
+            if byte == 0x03 && self.zero_count >= 2 {
+                self.zero_count = 0;
+                continue;
             }
 
+            self.track(byte);
             buf[read_size] = byte;
             read_size += 1;
         }
@@ -77,12 +108,99 @@ impl<I: std::io::Read> std::io::Read for EmulationPreventionIo<I> {
     }
 }
 
+/// Escapes a raw RBSP payload, returning the NAL payload with emulation prevention bytes inserted.
+///
+/// This is a convenience over [`EmulationPreventionIo`] and includes the trailing `0x03`
+/// appended by [`EmulationPreventionIo::finish`].
+pub fn insert_emulation_prevention(raw: &[u8]) -> Vec<u8> {
+    let mut writer = EmulationPreventionIo::new(Vec::with_capacity(raw.len() + raw.len() / 2));
+    // Writing to a Vec never fails.
+    io::Write::write_all(&mut writer, raw).expect("write to Vec");
+    writer.finish().expect("write to Vec")
+}
+
+/// Returns the size of a raw RBSP payload once emulation prevention bytes are inserted, as written
+/// through an [`EmulationPreventionIo`] writer.
+///
+/// The trailing `0x03` appended by [`EmulationPreventionIo::finish`] is not counted, so this is
+/// the size of the payload as escaped by the `build_with_emulation_prevention` methods of the
+/// parameter sets, which end in a nonzero `rbsp_stop_one_bit` byte.
+pub fn emulation_prevention_size(raw: &[u8]) -> usize {
+    let mut zero_count = 0u8;
+    let mut size = raw.len();
+    for &byte in raw {
+        if zero_count >= 2 && byte <= 0x03 {
+            size += 1;
+            zero_count = 0;
+        }
+
+        if byte == 0x00 {
+            zero_count = zero_count.saturating_add(1);
+        } else {
+            zero_count = 0;
+        }
+    }
+
+    size
+}
+
+/// Unescapes a NAL payload, returning the RBSP with all emulation prevention bytes removed.
+pub fn remove_emulation_prevention(escaped: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(escaped.len());
+    io::Read::read_to_end(&mut EmulationPreventionIo::new(escaped), &mut out)
+        .expect("read from slice");
+    out
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
     use std::io::{Read, Write};
 
-    use crate::nal_emulation_prevention::EmulationPreventionIo;
+    use crate::nal_emulation_prevention::{
+        EmulationPreventionIo, emulation_prevention_size, insert_emulation_prevention,
+        remove_emulation_prevention,
+    };
+
+    /// A reader that hands out at most `chunk` bytes per call, to exercise buffer boundaries.
+    struct ChunkedReader<'a> {
+        data: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for ChunkedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    fn write_in_chunks(raw: &[u8], chunk: usize) -> Vec<u8> {
+        let mut writer = EmulationPreventionIo::new(Vec::new());
+        for part in raw.chunks(chunk) {
+            writer.write_all(part).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    fn read_in_chunks(escaped: &[u8], inner_chunk: usize, outer_chunk: usize) -> Vec<u8> {
+        let mut reader = EmulationPreventionIo::new(ChunkedReader {
+            data: escaped,
+            chunk: inner_chunk,
+        });
+        let mut out = Vec::new();
+        let mut buf = vec![0; outer_chunk];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&buf[..n]);
+        }
+        out
+    }
 
     #[test]
     fn test_write_emulation_prevention_single() {
@@ -147,4 +265,137 @@ mod tests {
         // Should match original after roundtrip
         assert_eq!(original, decoded);
     }
+
+    #[test]
+    fn test_write_tricky_sequences() {
+        let cases: &[(&[u8], &[u8])] = &[
+            (&[0x00, 0x00, 0x00], &[0x00, 0x00, 0x03, 0x00]),
+            (&[0x00, 0x00, 0x01], &[0x00, 0x00, 0x03, 0x01]),
+            (&[0x00, 0x00, 0x02], &[0x00, 0x00, 0x03, 0x02]),
+            (&[0x00, 0x00, 0x03], &[0x00, 0x00, 0x03, 0x03]),
+            (&[0x00, 0x00, 0x04], &[0x00, 0x00, 0x04]),
+            (
+                &[0x00, 0x00, 0x00, 0x00],
+                &[0x00, 0x00, 0x03, 0x00, 0x00, 0x03],
+            ),
+            (
+                &[0x00, 0x00, 0x00, 0x00, 0x00],
+                &[0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00],
+            ),
+        ];
+
+        for (raw, expected) in cases {
+            for chunk in 1..=raw.len() {
+                assert_eq!(
+                    write_in_chunks(raw, chunk),
+                    *expected,
+                    "raw {raw:02x?} chunk {chunk}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_zero_run_at_end_of_nal() {
+        // Without finish the trailing zeros are left as-is.
+        let mut writer = EmulationPreventionIo::new(Vec::new());
+        writer.write_all(&[0x65, 0x00, 0x00]).unwrap();
+        assert_eq!(writer.into_inner(), vec![0x65, 0x00, 0x00]);
+
+        // A single trailing zero is not a start code prefix and is left alone.
+        assert_eq!(write_in_chunks(&[0x65, 0x00], 1), vec![0x65, 0x00]);
+        assert_eq!(
+            write_in_chunks(&[0x65, 0x00, 0x00], 1),
+            vec![0x65, 0x00, 0x00, 0x03]
+        );
+        assert_eq!(write_in_chunks(&[0x65, 0x80], 1), vec![0x65, 0x80]);
+        assert_eq!(write_in_chunks(&[], 1), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_read_tricky_sequences_at_boundaries() {
+        let cases: &[(&[u8], &[u8])] = &[
+            (&[0x00, 0x00, 0x03, 0x00, 0x03], &[0x00, 0x00, 0x00, 0x03]),
+            (&[0x00, 0x00, 0x03, 0x01], &[0x00, 0x00, 0x01]),
+            (&[0x00, 0x00, 0x03, 0x03], &[0x00, 0x00, 0x03]),
+            (&[0x00, 0x00, 0x03], &[0x00, 0x00]),
+            (&[0x00, 0x03, 0x00, 0x03], &[0x00, 0x03, 0x00, 0x03]),
+            (
+                &[0x00, 0x00, 0x03, 0x00, 0x00, 0x03],
+                &[0x00, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (escaped, expected) in cases {
+            for inner in 1..=escaped.len() {
+                for outer in 1..=escaped.len() {
+                    assert_eq!(
+                        read_in_chunks(escaped, inner, outer),
+                        *expected,
+                        "escaped {escaped:02x?} inner {inner} outer {outer}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_zero_run_does_not_overflow() {
+        let raw = vec![0x00; 1024];
+        let escaped = insert_emulation_prevention(&raw);
+        assert!(escaped.windows(3).all(|w| w != [0x00, 0x00, 0x00]));
+        assert_eq!(remove_emulation_prevention(&escaped), raw);
+
+        // A reader must also survive a long zero run without escapes.
+        assert_eq!(remove_emulation_prevention(&raw), raw);
+    }
+
+    #[test]
+    fn test_fuzz_roundtrip() {
+        // xorshift so the test is deterministic without pulling in a rand dependency.
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..2000 {
+            let len = (next() % 64) as usize;
+            // Bias heavily towards 0x00..=0x03 so escape sequences are common.
+            let raw: Vec<u8> = (0..len)
+                .map(|_| match next() % 8 {
+                    0..=3 => 0x00,
+                    4 => 0x01,
+                    5 => 0x03,
+                    _ => next() as u8,
+                })
+                .collect();
+
+            let escaped = insert_emulation_prevention(&raw);
+            for w in escaped.windows(3) {
+                assert!(
+                    !(w[0] == 0x00 && w[1] == 0x00 && w[2] <= 0x02),
+                    "start code emulation in {escaped:02x?}"
+                );
+            }
+            assert!(!escaped.ends_with(&[0x00, 0x00]), "{escaped:02x?}");
+
+            let mut writer = EmulationPreventionIo::new(Vec::new());
+            writer.write_all(&raw).unwrap();
+            let unfinished = writer.into_inner();
+            assert_eq!(
+                emulation_prevention_size(&raw),
+                unfinished.len(),
+                "raw {raw:02x?}"
+            );
+            assert!(escaped.starts_with(&unfinished));
+
+            let chunk = (next() % 7 + 1) as usize;
+            assert_eq!(write_in_chunks(&raw, chunk), escaped);
+            assert_eq!(remove_emulation_prevention(&escaped), raw, "raw {raw:02x?}");
+            assert_eq!(read_in_chunks(&escaped, chunk, chunk + 1), raw);
+        }
+    }
 }
@@ -53,11 +53,32 @@ use bytes_util::{BitReader, BitWriter};
 /// - [`BitReader`]
 pub trait BitReaderExpGolombExt {
     /// Reads an Exp-Golomb encoded number
-    fn read_exp_golomb(&mut self) -> io::Result<u64>;
+    ///
+    /// Codes with more than 63 leading zeros do not fit in a `u64` and are
+    /// rejected with [`io::ErrorKind::InvalidData`].
+    fn read_exp_golomb(&mut self) -> io::Result<u64> {
+        self.read_exp_golomb_bounded(63)
+    }
+
+    /// Reads an Exp-Golomb encoded number with at most `max_leading_zeros`
+    /// leading zeros
+    ///
+    /// Returns [`io::ErrorKind::InvalidData`] as soon as more leading zeros are
+    /// read, so corrupted input is not read any further. The bound is capped
+    /// at 63.
+    fn read_exp_golomb_bounded(&mut self, max_leading_zeros: u8) -> io::Result<u64>;
 
     /// Reads a signed Exp-Golomb encoded number
     fn read_signed_exp_golomb(&mut self) -> io::Result<i64> {
-        let exp_glob = self.read_exp_golomb()?;
+        self.read_signed_exp_golomb_bounded(63)
+    }
+
+    /// Reads a signed Exp-Golomb encoded number with at most
+    /// `max_leading_zeros` leading zeros
+    ///
+    /// See [`read_exp_golomb_bounded`](Self::read_exp_golomb_bounded).
+    fn read_signed_exp_golomb_bounded(&mut self, max_leading_zeros: u8) -> io::Result<i64> {
+        let exp_glob = self.read_exp_golomb_bounded(max_leading_zeros)?;
 
         if exp_glob % 2 == 0 {
             Ok(-((exp_glob / 2) as i64))
@@ -67,20 +88,29 @@ pub trait BitReaderExpGolombExt {
     }
 }
 
+/// A leading zero bound for [`BitReaderExpGolombExt::read_exp_golomb_bounded`]
+/// that fits every Exp-Golomb coded syntax element of H.264 and H.265
+/// parameter sets, whose values are at most 2^32 - 2.
+pub const DEFAULT_MAX_LEADING_ZEROS: u8 = 32;
+
 impl<R: io::Read> BitReaderExpGolombExt for BitReader<R> {
-    fn read_exp_golomb(&mut self) -> io::Result<u64> {
+    fn read_exp_golomb_bounded(&mut self, max_leading_zeros: u8) -> io::Result<u64> {
+        let max_leading_zeros = max_leading_zeros.min(63);
+
         let mut leading_zeros = 0;
         while !self.read_bit()? {
             leading_zeros += 1;
+            if leading_zeros > max_leading_zeros {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "exp-golomb code exceeds the maximum number of leading zeros",
+                ));
+            }
         }
 
-        let mut result = 1;
-        for _ in 0..leading_zeros {
-            result <<= 1;
-            result |= self.read_bit()? as u64;
-        }
+        let suffix = self.read_bits(leading_zeros)?;
 
-        Ok(result - 1)
+        Ok(((1 << leading_zeros) | suffix) - 1)
     }
 }
 
@@ -265,6 +295,43 @@ mod tests {
         assert_eq!(get_remaining_bits(&bit_reader), remaining_bits - 27);
     }
 
+    #[test]
+    fn test_exp_glob_decode_too_long() {
+        let mut bit_reader = BitReader::new_from_slice([0u8; 9]);
+        let err = bit_reader.read_exp_golomb().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_exp_glob_decode_bounded() {
+        // 3 leading zeros, value 0b1010 - 1 = 9
+        let mut bit_reader = BitReader::new_from_slice([0b0001_0100]);
+        assert_eq!(bit_reader.read_exp_golomb_bounded(3).unwrap(), 9);
+
+        let mut bit_reader = BitReader::new_from_slice([0b0001_0100]);
+        let err = bit_reader.read_exp_golomb_bounded(2).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        // reading stopped right after the bound was exceeded
+        assert_eq!(bit_reader.bit_position(), 3);
+
+        let mut bit_reader = BitReader::new_from_slice([0b0001_0000]);
+        assert_eq!(bit_reader.read_signed_exp_golomb_bounded(3).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_exp_glob_decode_never_ending_reader() {
+        // A reader that never reaches EOF must not make the decoder spin forever.
+        let mut bit_reader = BitReader::new(std::io::repeat(0));
+        let err = bit_reader
+            .read_exp_golomb_bounded(crate::DEFAULT_MAX_LEADING_ZEROS)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(bit_reader.bit_position(), 33);
+
+        let err = bit_reader.read_exp_golomb().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn test_exp_glob_encode() {
         let mut bit_writer = BitWriter::<Vec<u8>>::default();
@@ -0,0 +1,160 @@
+use std::hint::black_box;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use flv::data::FlvData;
+use flv::header::FlvHeader;
+use flv::tag::{FlvTag, FlvTagType};
+use flv_fix::{AnomalyDetectorOperator, DuplicateTagFilterConfig, DuplicateTagFilterOperator};
+use pipeline_common::{
+    AnomalyConfig, CancellationToken, PipelineError, Processor, StreamerContext,
+};
+
+fn make_tag(tag_type: FlvTagType, timestamp_ms: u32, data: Vec<u8>) -> FlvData {
+    FlvData::Tag(FlvTag {
+        timestamp_ms,
+        stream_id: 0,
+        tag_type,
+        is_filtered: false,
+        data: Bytes::from(data),
+    })
+}
+
+/// Payload filled with pseudo-random bytes, so every tag hashes differently.
+fn make_payload(header: &[u8], size: usize, seed: &mut u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(size);
+    data.extend_from_slice(header);
+    while data.len() < size {
+        *seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        data.push((*seed >> 56) as u8);
+    }
+    data
+}
+
+/// Ten seconds of a 30fps stream with ~2Mbps video and AAC audio.
+fn make_stream() -> (Vec<FlvData>, u64) {
+    let mut seed = 0x2545F4914F6CDD1D;
+    let mut items = vec![FlvData::Header(FlvHeader::new(true, true))];
+    let mut audio_ts = 0;
+    for frame in 0..300u32 {
+        let video_ts = frame * 1000 / 30;
+        while audio_ts <= video_ts {
+            let data = make_payload(&[0xAF, 0x01], 372, &mut seed);
+            items.push(make_tag(FlvTagType::Audio, audio_ts, data));
+            audio_ts += 23;
+        }
+        let (header, size) = if frame % 60 == 0 {
+            ([0x17, 0x01, 0x00, 0x00, 0x00], 60_000)
+        } else {
+            ([0x27, 0x01, 0x00, 0x00, 0x00], 7_000)
+        };
+        let data = make_payload(&header, size, &mut seed);
+        items.push(make_tag(FlvTagType::Video, video_ts, data));
+    }
+
+    let bytes = items.iter().map(|item| item.size() as u64).sum();
+    (items, bytes)
+}
+
+fn run_filter(
+    context: &Arc<StreamerContext>,
+    config: DuplicateTagFilterConfig,
+    stream: &[FlvData],
+) -> usize {
+    let mut operator = DuplicateTagFilterOperator::with_config(context.clone(), config);
+    let mut count = 0;
+    let mut output = |item: FlvData| -> Result<(), PipelineError> {
+        black_box(item);
+        count += 1;
+        Ok(())
+    };
+    for item in stream {
+        operator
+            .process(context, item.clone(), &mut output)
+            .unwrap();
+    }
+    operator.finish(context, &mut output).unwrap();
+    count
+}
+
+fn bench_duplicate_filter(c: &mut Criterion) {
+    let context = StreamerContext::arc_new(CancellationToken::new());
+    let (stream, bytes) = make_stream();
+
+    let mut group = c.benchmark_group("Duplicate Filter");
+    group.throughput(Throughput::Bytes(bytes));
+
+    group.bench_function("disabled", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for item in &stream {
+                black_box(item.clone());
+                count += 1;
+            }
+            count
+        })
+    });
+
+    group.bench_function("exact_match", |b| {
+        let config = DuplicateTagFilterConfig {
+            content_window_ms: 0,
+            ..Default::default()
+        };
+        b.iter(|| run_filter(&context, config.clone(), black_box(&stream)))
+    });
+
+    group.bench_function("content_window", |b| {
+        let config = DuplicateTagFilterConfig::default();
+        b.iter(|| run_filter(&context, config.clone(), black_box(&stream)))
+    });
+
+    group.finish();
+}
+
+/// The detector only observes the stream, it should cost about as much as passing it on.
+fn bench_anomaly_detector(c: &mut Criterion) {
+    let context = StreamerContext::arc_new(CancellationToken::new());
+    let (stream, bytes) = make_stream();
+
+    let mut group = c.benchmark_group("Anomaly Detector");
+    group.throughput(Throughput::Bytes(bytes));
+
+    group.bench_function("passthrough", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            for item in &stream {
+                black_box(item.clone());
+                count += 1;
+            }
+            count
+        })
+    });
+
+    group.bench_function("enabled", |b| {
+        b.iter(|| {
+            let mut operator =
+                AnomalyDetectorOperator::new(context.clone(), AnomalyConfig::default());
+            let mut count = 0;
+            let mut output = |item: FlvData| -> Result<(), PipelineError> {
+                black_box(item);
+                count += 1;
+                Ok(())
+            };
+            for item in black_box(&stream) {
+                operator
+                    .process(&context, item.clone(), &mut output)
+                    .unwrap();
+            }
+            operator.finish(&context, &mut output).unwrap();
+            count
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_duplicate_filter, bench_anomaly_detector);
+criterion_main!(benches);
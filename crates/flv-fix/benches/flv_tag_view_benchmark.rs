@@ -0,0 +1,151 @@
+use std::hint::black_box;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use flv::data::FlvData;
+use flv::header::FlvHeader;
+use flv::tag::{FlvTag, FlvTagType};
+use flv_fix::{
+    ContinuityMode, DuplicateTagFilterOperator, LimitConfig, LimitOperator, TimeConsistencyOperator,
+};
+use pipeline_common::{CancellationToken, PipelineError, Processor, StreamerContext};
+
+fn make_tag(tag_type: FlvTagType, timestamp_ms: u32, data: Bytes) -> FlvTag {
+    FlvTag {
+        timestamp_ms,
+        stream_id: 0,
+        tag_type,
+        is_filtered: false,
+        data,
+    }
+}
+
+/// Sixty seconds of a 30fps AVC stream with AAC audio. Tags of the same kind share
+/// their payload, like tags demuxed from one download buffer.
+fn make_stream() -> (Vec<FlvData>, u64) {
+    let payload = |header: &[u8], size: usize| {
+        let mut data: Vec<u8> = (0..size).map(|i| (i * 31 % 251) as u8).collect();
+        data[..header.len()].copy_from_slice(header);
+        Bytes::from(data)
+    };
+    let video_sequence = Bytes::from_static(&[0x17, 0x00, 0, 0, 0, 0x01, 0x64]);
+    let audio_sequence = Bytes::from_static(&[0xAF, 0x00, 0x12, 0x10]);
+    let key_frame = payload(&[0x17, 0x01, 0, 0, 0], 60_000);
+    let inter_frame = payload(&[0x27, 0x01, 0, 0, 0], 7_000);
+    let audio_frame = payload(&[0xAF, 0x01], 372);
+
+    let mut items = vec![
+        FlvData::Header(FlvHeader::new(true, true)),
+        FlvData::Tag(make_tag(FlvTagType::Video, 0, video_sequence)),
+        FlvData::Tag(make_tag(FlvTagType::Audio, 0, audio_sequence)),
+    ];
+    let mut audio_ts = 0;
+    for frame in 0..1800u32 {
+        let video_ts = frame * 1000 / 30;
+        while audio_ts <= video_ts {
+            let tag = make_tag(FlvTagType::Audio, audio_ts, audio_frame.clone());
+            items.push(FlvData::Tag(tag));
+            audio_ts += 23;
+        }
+        let data = if frame % 60 == 0 {
+            key_frame.clone()
+        } else {
+            inter_frame.clone()
+        };
+        items.push(FlvData::Tag(make_tag(FlvTagType::Video, video_ts, data)));
+    }
+
+    let bytes = items.iter().map(|item| item.size() as u64).sum();
+    (items, bytes)
+}
+
+/// The checks the limit operator makes on each tag, asked of the owned tag
+fn inspect_owned(tag: &FlvTag) -> (bool, bool, bool, bool, bool) {
+    (
+        tag.is_script_tag(),
+        tag.is_video_sequence_header(),
+        tag.is_audio_sequence_header(),
+        tag.is_key_frame_nalu(),
+        tag.is_key_frame_nalu(),
+    )
+}
+
+/// The same checks asked of a view, which parses the video header once
+fn inspect_view(tag: &FlvTag) -> (bool, bool, bool, bool, bool) {
+    let view = tag.view();
+    (
+        view.is_script_tag(),
+        view.is_video_sequence_header(),
+        view.is_audio_sequence_header(),
+        view.is_key_frame_nalu(),
+        view.is_key_frame_nalu(),
+    )
+}
+
+fn run_chain(context: &Arc<StreamerContext>, stream: &[FlvData]) -> usize {
+    let mut limit = LimitOperator::with_config(
+        context.clone(),
+        LimitConfig {
+            max_duration_ms: Some(20_000),
+            ..Default::default()
+        },
+    );
+    let mut continuity = TimeConsistencyOperator::new(context.clone(), ContinuityMode::Reset);
+    let mut dedup = DuplicateTagFilterOperator::new(context.clone());
+
+    let mut count = 0;
+    let mut sink = |item: FlvData| -> Result<(), PipelineError> {
+        black_box(item);
+        count += 1;
+        Ok(())
+    };
+    let mut after_continuity = |item: FlvData| dedup.process(context, item, &mut sink);
+    let mut after_limit = |item: FlvData| continuity.process(context, item, &mut after_continuity);
+    for item in stream {
+        limit
+            .process(context, item.clone(), &mut after_limit)
+            .unwrap();
+    }
+    count
+}
+
+fn bench_tag_view(c: &mut Criterion) {
+    let context = StreamerContext::arc_new(CancellationToken::new());
+    let (stream, bytes) = make_stream();
+    let tags: Vec<&FlvTag> = stream
+        .iter()
+        .filter_map(|item| match item {
+            FlvData::Tag(tag) => Some(tag),
+            _ => None,
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("FLV Tag Inspection");
+    group.throughput(Throughput::Bytes(bytes));
+
+    group.bench_function("owned_tag", |b| {
+        b.iter(|| {
+            for tag in &tags {
+                black_box(inspect_owned(black_box(tag)));
+            }
+        })
+    });
+
+    group.bench_function("tag_view", |b| {
+        b.iter(|| {
+            for tag in &tags {
+                black_box(inspect_view(black_box(tag)));
+            }
+        })
+    });
+
+    group.bench_function("operator_chain", |b| {
+        b.iter(|| run_chain(&context, black_box(&stream)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_tag_view);
+criterion_main!(benches);
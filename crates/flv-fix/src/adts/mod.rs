@@ -0,0 +1,16 @@
+//! # Raw AAC Output
+//!
+//! Writes the AAC audio of the FLV tag stream as raw `.aac` files, one ADTS frame per
+//! audio tag, for audio-only recordings.
+//!
+//! - `writer_task`: [`AdtsFormatStrategy`], building ADTS headers from the AAC sequence header
+//! - `writer`: [`AdtsWriter`], the [`ProtocolWriter`](pipeline_common::ProtocolWriter) to run it
+//!
+//! Video, script data and non-AAC audio are dropped, so the writer is usually combined with
+//! [`TrackFilter::AudioOnly`](crate::TrackFilter::AudioOnly) in the pipeline.
+
+mod writer;
+mod writer_task;
+
+pub use writer::{AdtsWriter, AdtsWriterConfig};
+pub use writer_task::{AdtsFormatStrategy, AdtsStrategyError};
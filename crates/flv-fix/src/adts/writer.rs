@@ -0,0 +1,179 @@
+use std::path::PathBuf;
+
+use flv::data::FlvData;
+use pipeline_common::{
+    OutputSink, PipelineError, ProgressConfig, ProtocolWriter, SegmentHook, SplitReason,
+    WriterConfig, WriterError, WriterProgress, WriterState, WriterStats, WriterTask,
+};
+
+use super::writer_task::AdtsFormatStrategy;
+
+/// Configuration of the raw AAC writer.
+#[derive(Debug, Clone)]
+pub struct AdtsWriterConfig {
+    pub output_dir: PathBuf,
+    pub base_name: String,
+}
+
+/// A writer task extracting the AAC audio of FLV data into raw `.aac` (ADTS) files.
+///
+/// Files are split wherever the FLV writer would split them, on every header the pipeline
+/// emits after tags.
+pub struct AdtsWriter {
+    writer_task: WriterTask<FlvData, AdtsFormatStrategy>,
+}
+
+impl AdtsWriter {
+    pub fn new(config: AdtsWriterConfig) -> Self {
+        let writer_config =
+            WriterConfig::new(config.output_dir, config.base_name, "aac".to_string());
+        let writer_task = WriterTask::new(writer_config, AdtsFormatStrategy::new());
+        Self { writer_task }
+    }
+
+    /// Set a callback to be invoked when a new segment starts recording.
+    ///
+    /// The callback receives the file path and sequence number (0-based).
+    pub fn set_on_segment_start_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&std::path::Path, u32) + Send + Sync + 'static,
+    {
+        self.writer_task.set_on_file_open_callback(callback);
+    }
+
+    /// Set a callback to be invoked when a segment is completed.
+    ///
+    /// The callback receives the file path, sequence number (0-based), duration in seconds,
+    /// size in bytes, and an optional split reason.
+    pub fn set_on_segment_complete_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&std::path::Path, u32, f64, u64, Option<&SplitReason>) + Send + Sync + 'static,
+    {
+        self.writer_task.set_on_file_close_callback(callback);
+    }
+
+    /// Add a hook invoked on the writer thread when segment files are opened and closed.
+    pub fn add_segment_hook<H: SegmentHook>(&mut self, hook: H) {
+        self.writer_task.add_segment_hook(hook);
+    }
+
+    /// Name files that would overwrite an existing one `name_1`, `name_2`, ...
+    pub fn set_numbered_collisions(&mut self, numbered_collisions: bool) {
+        self.writer_task.config_mut().numbered_collisions = numbered_collisions;
+    }
+
+    /// Write files to `sink` instead of the local disk.
+    pub fn set_output_sink(&mut self, sink: OutputSink) {
+        self.writer_task.config_mut().sink = sink;
+    }
+
+    /// Set a progress callback with default intervals (1MB bytes, 1000ms time).
+    pub fn set_progress_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(WriterProgress) + Send + Sync + 'static,
+    {
+        self.writer_task.set_progress_callback(callback);
+    }
+
+    /// Set a progress callback with custom intervals.
+    pub fn set_progress_callback_with_config<F>(&mut self, callback: F, config: ProgressConfig)
+    where
+        F: Fn(WriterProgress) + Send + Sync + 'static,
+    {
+        self.writer_task
+            .set_progress_callback_with_config(callback, config);
+    }
+
+    /// Get the total media duration in seconds across all files.
+    pub fn media_duration_secs(&self) -> f64 {
+        self.writer_task.get_state().media_duration_secs_total
+    }
+}
+
+impl ProtocolWriter for AdtsWriter {
+    type Item = FlvData;
+
+    fn get_state(&self) -> &WriterState {
+        self.writer_task.get_state()
+    }
+
+    fn run(
+        &mut self,
+        input: tokio::sync::mpsc::Receiver<Result<Self::Item, PipelineError>>,
+    ) -> Result<WriterStats, WriterError> {
+        self.writer_task.run_from_channel(input, |_, _| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_test_header, create_test_tag, create_video_tag};
+    use aac::AdtsIterator;
+    use flv::tag::FlvTagType;
+
+    fn aac_sequence_header(timestamp: u32) -> FlvData {
+        // AAC LC, 44.1kHz, stereo
+        create_test_tag(FlvTagType::Audio, timestamp, vec![0xAF, 0x00, 0x12, 0x10])
+    }
+
+    fn aac_frame(timestamp: u32, payload: &[u8]) -> FlvData {
+        let mut data = vec![0xAF, 0x01];
+        data.extend_from_slice(payload);
+        create_test_tag(FlvTagType::Audio, timestamp, data)
+    }
+
+    fn run_writer(base_name: &str, items: Vec<FlvData>) -> (tempfile::TempDir, WriterStats) {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+        let mut writer = AdtsWriter::new(AdtsWriterConfig {
+            output_dir: tempdir.path().to_path_buf(),
+            base_name: base_name.to_string(),
+        });
+
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<FlvData, PipelineError>>(64);
+        let handle = std::thread::spawn(move || writer.run(rx));
+        for item in items {
+            tx.blocking_send(Ok(item)).unwrap();
+        }
+        drop(tx);
+
+        let stats = handle
+            .join()
+            .expect("writer thread join")
+            .expect("writer ok");
+        (tempdir, stats)
+    }
+
+    #[test]
+    fn writes_one_adts_frame_per_audio_tag() {
+        let items = vec![
+            create_test_header(),
+            // Frames before the sequence header cannot be described and are dropped
+            aac_frame(0, &[0xFF]),
+            aac_sequence_header(0),
+            create_video_tag(0, true),
+            aac_frame(0, &[0x21, 0x10, 0x04]),
+            aac_frame(23, &[0x21, 0x10, 0x05, 0x06]),
+            aac_frame(46, &[0x21]),
+        ];
+
+        let (tempdir, stats) = run_writer("audio", items);
+        assert_eq!(stats.files_created, 1);
+
+        let data = std::fs::read(tempdir.path().join("audio.aac")).unwrap();
+        let frames: Vec<_> = AdtsIterator::new(&data)
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        for (header, _) in &frames {
+            // AAC LC (object type 2) at 44.1kHz in stereo
+            assert_eq!(header.profile, 1);
+            assert_eq!(header.channel_configuration, 2);
+            let config = header.to_audio_specific_config().unwrap();
+            assert_eq!(config.sampling_frequency, 44100);
+        }
+        assert_eq!(frames[0].1, &[0x21, 0x10, 0x04]);
+        assert_eq!(frames[1].1, &[0x21, 0x10, 0x05, 0x06]);
+        assert_eq!(frames[2].1, &[0x21]);
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use aac::{AdtsHeader, PartialAudioSpecificConfig};
+use flv::{FlvData, FlvTag, audio::SoundFormat};
+use pipeline_common::{
+    FormatStrategy, PostWriteAction, SinkWriter, SplitReason, StreamMetadata, WriterConfig,
+    WriterState, expand_filename_template_with,
+};
+use tracing::{debug, info, warn};
+
+/// Error type for the ADTS strategy
+#[derive(Debug, thiserror::Error)]
+pub enum AdtsStrategyError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Format strategy writing the AAC frames of FLV audio tags as ADTS.
+///
+/// Each frame gets an ADTS header built from the last AAC sequence header. Frames before
+/// the first sequence header, or under a configuration ADTS cannot represent (e.g. HE-AAC
+/// signalled explicitly), are dropped.
+pub struct AdtsFormatStrategy {
+    config: Option<PartialAudioSpecificConfig>,
+    /// Timestamp of the first frame of the current file.
+    first_timestamp: Option<u32>,
+    last_timestamp: u32,
+    frame_count: u64,
+    last_header_received: bool,
+    current_tag_count: u64,
+    /// The most recent split reason received, if any.
+    last_split_reason: Option<SplitReason>,
+}
+
+impl Default for AdtsFormatStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdtsFormatStrategy {
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            first_timestamp: None,
+            last_timestamp: 0,
+            frame_count: 0,
+            last_header_received: false,
+            current_tag_count: 0,
+            last_split_reason: None,
+        }
+    }
+
+    fn handle_audio_tag(
+        &mut self,
+        writer: &mut BufWriter<SinkWriter>,
+        tag: &FlvTag,
+    ) -> Result<u64, AdtsStrategyError> {
+        if tag.get_audio_codec_id() != Some(SoundFormat::Aac) || tag.data.len() < 2 {
+            debug!("Skipping non-AAC audio tag");
+            return Ok(0);
+        }
+        let payload = tag.data.slice(2..);
+
+        if tag.is_audio_sequence_header() {
+            self.config = match PartialAudioSpecificConfig::parse(&payload) {
+                Ok(config) if AdtsHeader::from_config(&config, 0).is_ok() => Some(config),
+                Ok(config) => {
+                    warn!(?config, "AAC configuration cannot be represented in ADTS");
+                    None
+                }
+                Err(e) => {
+                    warn!(error = %e, "Invalid AAC sequence header");
+                    None
+                }
+            };
+            return Ok(0);
+        }
+
+        let Some(config) = &self.config else {
+            debug!("Skipping audio frame without a usable AAC sequence header");
+            return Ok(0);
+        };
+        let header = match AdtsHeader::from_config(config, payload.len()) {
+            Ok(header) => header,
+            Err(e) => {
+                warn!(error = %e, "Skipping audio frame that does not fit an ADTS frame");
+                return Ok(0);
+            }
+        };
+        header.mux(writer)?;
+        writer.write_all(&payload)?;
+
+        self.first_timestamp.get_or_insert(tag.timestamp_ms);
+        self.last_timestamp = self.last_timestamp.max(tag.timestamp_ms);
+        self.frame_count += 1;
+        Ok((header.header_len() + payload.len()) as u64)
+    }
+}
+
+impl FormatStrategy<FlvData> for AdtsFormatStrategy {
+    type Writer = BufWriter<SinkWriter>;
+    type StrategyError = AdtsStrategyError;
+
+    fn create_writer(
+        &self,
+        path: &Path,
+        config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
+        let output = config.open_output(path)?;
+        Ok(BufWriter::with_capacity(256 * 1024, output))
+    }
+
+    fn write_item(
+        &mut self,
+        writer: &mut Self::Writer,
+        item: &FlvData,
+    ) -> Result<u64, Self::StrategyError> {
+        match item {
+            FlvData::Header(_) => {
+                self.last_header_received = true;
+                Ok(0)
+            }
+            FlvData::Tag(tag) => {
+                self.last_header_received = false;
+                self.current_tag_count += 1;
+
+                if tag.is_audio_tag() {
+                    self.handle_audio_tag(writer, tag)
+                } else {
+                    // Raw AAC carries neither video nor script data
+                    Ok(0)
+                }
+            }
+            FlvData::Split(reason) => {
+                self.last_split_reason = Some(reason.clone());
+                Ok(0)
+            }
+            FlvData::EndOfSequence(_) => {
+                debug!("Received EndOfSequence, stream ending");
+                Ok(0)
+            }
+        }
+    }
+
+    fn should_rotate_file(&self, _config: &WriterConfig, _state: &WriterState) -> bool {
+        // Like the FLV writer, start a new file on every header following tags
+        self.last_header_received && self.current_tag_count > 0
+    }
+
+    fn next_file_path(&self, config: &WriterConfig, state: &WriterState) -> PathBuf {
+        let metadata = StreamMetadata {
+            audio_codec: Some("aac".to_string()),
+            ..StreamMetadata::default()
+        };
+        let file_name = expand_filename_template_with(
+            &config.file_name_template,
+            Some(state.file_sequence_number),
+            &metadata,
+        );
+        config
+            .base_path
+            .join(format!("{file_name}.{}", config.file_extension))
+    }
+
+    fn on_file_open(
+        &mut self,
+        _writer: &mut Self::Writer,
+        path: &Path,
+        _config: &WriterConfig,
+        _state: &WriterState,
+    ) -> Result<u64, Self::StrategyError> {
+        self.first_timestamp = None;
+        self.last_timestamp = 0;
+        self.frame_count = 0;
+        self.last_header_received = false;
+        self.current_tag_count = 0;
+        self.last_split_reason = None;
+
+        info!(path = %path.display(), "Opening segment");
+        Ok(0)
+    }
+
+    fn on_file_close(
+        &mut self,
+        writer: &mut Self::Writer,
+        path: &Path,
+        _config: &WriterConfig,
+        _state: &WriterState,
+    ) -> Result<u64, Self::StrategyError> {
+        writer.flush()?;
+        writer.get_mut().finish()?;
+
+        info!(
+            path = %path.display(),
+            frames = self.frame_count,
+            duration_secs = self.current_media_duration_secs(),
+            "Closed segment"
+        );
+        Ok(0)
+    }
+
+    fn after_item_written(
+        &mut self,
+        _item: &FlvData,
+        _bytes_written: u64,
+        _state: &WriterState,
+    ) -> Result<PostWriteAction, Self::StrategyError> {
+        Ok(PostWriteAction::None)
+    }
+
+    fn current_media_duration_secs(&self) -> f64 {
+        let first = self.first_timestamp.unwrap_or(self.last_timestamp);
+        f64::from(self.last_timestamp.saturating_sub(first)) / 1000.0
+    }
+
+    fn close_context(&self) -> Option<SplitReason> {
+        self.last_split_reason.clone()
+    }
+}
@@ -1,10 +1,19 @@
-use crate::amf::model::{AmfScriptData, KeyframeData};
+use crate::amf::model::{AmfScriptData, CuePoint, KeyframeData};
 use crate::analyzer::FlvStats;
 use amf0::{Amf0Encoder, Amf0Marker, Amf0Value, Amf0WriteError};
 use byteorder::{BigEndian, WriteBytesExt};
-use flv::{audio::SoundFormat, video::VideoCodecId};
+use flv::{
+    audio::SoundFormat,
+    video::{VideoCodecId, VideoFourCC},
+};
 use std::borrow::Cow;
-use tracing::debug;
+use tracing::{debug, warn};
+
+/// Bytes taken by one keyframe entry: an AMF0 number in both `times` and `filepositions`.
+pub const RESERVED_BYTES_PER_KEYFRAME: usize = 2 * 9;
+
+/// Key of the spare string property that pads a reserved `keyframes` object.
+pub const KEYFRAMES_PADDING_KEY: &str = "padding";
 
 const NATURAL_METADATA_KEY_ORDER: &[&str] = &[
     "duration",
@@ -57,14 +66,16 @@ impl OnMetaDataBuilder {
 
     /// Populates the builder with data from an `FlvStats` object.
     pub fn with_stats(mut self, stats: &FlvStats) -> Self {
-        self.data.duration = Some(stats.duration as f64);
+        self.data.duration = Some(stats.calculate_duration_ms() as f64 / 1000.0);
         if let Some(video_stats) = &stats.video_stats {
             if let Some(res) = &video_stats.resolution {
                 self.data.width = Some(res.width as f64);
                 self.data.height = Some(res.height as f64);
             }
             self.data.framerate = Some(video_stats.video_frame_rate as f64);
+            self.data.videodatarate = Some(video_stats.video_data_rate as f64);
             self.data.videocodecid = video_stats.video_codec;
+            self.data.video_fourcc = video_stats.video_fourcc;
             self.data.videosize = Some(video_stats.video_data_size);
             self.data.lastkeyframetimestamp = Some(video_stats.last_keyframe_timestamp);
             self.data.lastkeyframelocation = Some(video_stats.last_keyframe_position);
@@ -73,15 +84,43 @@ impl OnMetaDataBuilder {
                 Some(video_stats.last_keyframe_timestamp == stats.last_timestamp);
         }
         self.data.audiocodecid = stats.audio_codec;
+        if stats.has_audio {
+            self.data.audiodatarate = Some(stats.audio_data_rate as f64);
+        }
         self.data.filesize = Some(stats.file_size);
         self.data.audiosize = Some(stats.audio_data_size);
         self.data.lasttimestamp = Some(stats.last_timestamp);
         self.data.has_video = Some(stats.has_video);
         self.data.has_audio = Some(stats.has_audio);
         self.data.has_metadata = Some(true);
+        if !stats.cue_points.is_empty() {
+            // Cue points past the end of the file point nowhere
+            let end_ms = stats.last_timestamp;
+            let (cue_points, dropped): (Vec<_>, Vec<_>) = stats
+                .cue_points
+                .iter()
+                .cloned()
+                .partition(|cue| cue.time_ms <= end_ms);
+            for cue in dropped {
+                warn!(
+                    "Dropping cue point '{}' at {}ms, past the end of the file at {end_ms}ms",
+                    cue.name, cue.time_ms
+                );
+            }
+            self = self.with_cue_points(&cue_points);
+        }
         self
     }
 
+    /// Sets the `cuePoints` array.
+    pub fn with_cue_points(self, cue_points: &[CuePoint]) -> Self {
+        let cue_points = cue_points.iter().map(CuePoint::to_amf).collect();
+        self.with_custom_property(
+            crate::METADATA_CUE_POINTS,
+            Amf0Value::StrictArray(Cow::Owned(cue_points)),
+        )
+    }
+
     /// Sets the duration in seconds.
     pub fn with_duration(mut self, duration: f64) -> Self {
         self.data.duration = Some(duration);
@@ -109,6 +148,13 @@ impl OnMetaDataBuilder {
     /// Sets the video codec ID.
     pub fn with_video_codec(mut self, codec: VideoCodecId) -> Self {
         self.data.videocodecid = Some(codec);
+        self.data.video_fourcc = None;
+        self
+    }
+
+    /// Sets the FourCC of an enhanced video codec, written in place of the codec ID.
+    pub fn with_video_fourcc(mut self, fourcc: VideoFourCC) -> Self {
+        self.data.video_fourcc = Some(fourcc);
         self
     }
 
@@ -143,6 +189,13 @@ impl OnMetaDataBuilder {
         self
     }
 
+    /// Configures the builder to generate an empty `keyframes` object padded
+    /// with enough spare bytes to be patched in place with up to `capacity` keyframes.
+    pub fn with_reserved_keyframes(mut self, capacity: usize) -> Self {
+        self.data.keyframes = Some(KeyframeData::Reserved { capacity });
+        self
+    }
+
     /// Builds the final `AmfScriptData` model.
     pub fn build_model(self) -> AmfScriptData {
         self.data
@@ -288,8 +341,13 @@ impl OnMetaDataBuilder {
                 .or(Some(Amf0Value::Number(0.0))),
             "videocodecid" => self
                 .data
-                .videocodecid
-                .map(|v| Amf0Value::Number(v as u8 as f64))
+                .video_fourcc
+                .map(|v| Amf0Value::Number(v.as_u32() as f64))
+                .or_else(|| {
+                    self.data
+                        .videocodecid
+                        .map(|v| Amf0Value::Number(v as u8 as f64))
+                })
                 .or(Some(Amf0Value::Number(0.0))),
             "videodatarate" => self
                 .data
@@ -435,6 +493,17 @@ impl OnMetaDataBuilder {
                     size += "spacer".len() + 2 + 1 + 4 + (*spacer_size * 9);
                 }
             }
+            KeyframeData::Reserved { capacity } => {
+                // empty "times" and "filepositions" arrays
+                size += "times".len() + 2 + 1 + 4;
+                size += "filepositions".len() + 2 + 1 + 4;
+                // "padding" property key + long string marker + length + spaces
+                size += KEYFRAMES_PADDING_KEY.len()
+                    + 2
+                    + 1
+                    + 4
+                    + (*capacity * RESERVED_BYTES_PER_KEYFRAME);
+            }
         }
 
         // Object EOF marker
@@ -527,6 +596,22 @@ impl OnMetaDataBuilder {
                     }
                 }
             }
+            KeyframeData::Reserved { capacity } => {
+                Amf0Encoder::write_property_key(buf, "times")?;
+                buf.write_u8(Amf0Marker::StrictArray as u8)?;
+                buf.write_u32::<BigEndian>(0)?;
+
+                Amf0Encoder::write_property_key(buf, "filepositions")?;
+                buf.write_u8(Amf0Marker::StrictArray as u8)?;
+                buf.write_u32::<BigEndian>(0)?;
+
+                // Always a long string, so the padding overhead does not depend on its length
+                Amf0Encoder::write_property_key(buf, KEYFRAMES_PADDING_KEY)?;
+                Amf0Encoder::encode_long_string(
+                    buf,
+                    &" ".repeat(capacity * RESERVED_BYTES_PER_KEYFRAME),
+                )?;
+            }
         }
 
         Amf0Encoder::object_eof(buf)?;
@@ -678,4 +763,26 @@ mod tests {
             panic!("Expected object for metadata");
         }
     }
+
+    #[test]
+    fn test_on_meta_data_builder_video_fourcc() {
+        let builder = OnMetaDataBuilder::new()
+            .with_video_codec(VideoCodecId::Avc)
+            .with_video_fourcc(VideoFourCC::Hvc1);
+
+        let (bytes, _) = builder.build_bytes(0, false).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&bytes);
+        let _name = decoder.decode().unwrap();
+        let data = decoder.decode().unwrap();
+        let props = data.as_object_properties().unwrap();
+        let codec_id = props.iter().find(|(k, _)| k == "videocodecid").unwrap();
+        assert_eq!(
+            codec_id.1,
+            Amf0Value::Number(VideoFourCC::Hvc1.as_u32() as f64)
+        );
+
+        let model = AmfScriptData::from_amf_object_ref(props).unwrap();
+        assert_eq!(model.video_fourcc, Some(VideoFourCC::Hvc1));
+    }
 }
@@ -1,5 +1,9 @@
-use amf0::{Amf0Value, Amf0WriteError};
-use flv::{audio::SoundFormat, video::VideoCodecId};
+use amf0::{Amf0Encoder, Amf0Marker, Amf0Value, Amf0WriteError};
+use flv::{
+    audio::SoundFormat,
+    video::{VideoCodecId, VideoFourCC},
+};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use time::OffsetDateTime;
 
@@ -12,6 +16,8 @@ pub struct AmfScriptData {
     pub height: Option<f64>,
     pub framerate: Option<f64>,
     pub videocodecid: Option<VideoCodecId>,
+    /// FourCC of an enhanced (E-RTMP) codec, written as `videocodecid` instead of the legacy id
+    pub video_fourcc: Option<VideoFourCC>,
     pub videodatarate: Option<f64>,
 
     // Audio Properties
@@ -59,6 +65,9 @@ pub enum KeyframeData {
     },
     /// For the `script_filler` use case, with placeholder arrays and a spacer.
     Placeholder { spacer_size: usize },
+    /// For in-place patching, with empty arrays followed by a `padding` string
+    /// that reserves room for `capacity` keyframes.
+    Reserved { capacity: usize },
 }
 
 /// Extract f64 values from a StrictArray-like value.
@@ -97,9 +106,9 @@ impl AmfScriptData {
                 "height" => data.height = value.as_number(),
                 "framerate" => data.framerate = value.as_number(),
                 "videocodecid" => {
-                    data.videocodecid = value
-                        .as_number()
-                        .and_then(|v| VideoCodecId::try_from(v as u8).ok())
+                    let id = value.as_number();
+                    data.videocodecid = id.and_then(|v| VideoCodecId::try_from(v as u8).ok());
+                    data.video_fourcc = id.and_then(|v| VideoFourCC::try_from(v as u32).ok());
                 }
                 "videodatarate" => data.videodatarate = value.as_number(),
                 "audiocodecid" => {
@@ -173,3 +182,101 @@ impl AmfScriptData {
         Ok(data)
     }
 }
+
+/// A cue point, written as an `onCuePoint` script tag and listed in the `cuePoints`
+/// array of `onMetaData`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CuePoint {
+    /// Position of the cue on the timeline of the tags, in milliseconds
+    pub time_ms: u32,
+    pub name: String,
+    /// Cue type, `event` or `navigation`
+    pub typ: String,
+    pub parameters: HashMap<String, Amf0Value<'static>>,
+}
+
+impl CuePoint {
+    /// Creates a cue point without parameters.
+    pub fn new(time_ms: u32, name: impl Into<String>, typ: impl Into<String>) -> Self {
+        Self {
+            time_ms,
+            name: name.into(),
+            typ: typ.into(),
+            parameters: HashMap::new(),
+        }
+    }
+
+    /// Sets a parameter of the cue point.
+    pub fn with_parameter(mut self, key: impl Into<String>, value: Amf0Value<'static>) -> Self {
+        self.parameters.insert(key.into(), value);
+        self
+    }
+
+    /// The cue point as an AMF0 object, with its time in seconds and its parameters in
+    /// key order.
+    pub fn to_amf(&self) -> Amf0Value<'static> {
+        let mut parameters: Vec<_> = self
+            .parameters
+            .iter()
+            .map(|(key, value)| (Cow::Owned(key.clone()), value.clone()))
+            .collect();
+        parameters.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Amf0Value::Object(Cow::Owned(vec![
+            (
+                Cow::Borrowed("name"),
+                Amf0Value::String(Cow::Owned(self.name.clone())),
+            ),
+            (
+                Cow::Borrowed("time"),
+                Amf0Value::Number(self.time_ms as f64 / 1000.0),
+            ),
+            (
+                Cow::Borrowed("type"),
+                Amf0Value::String(Cow::Owned(self.typ.clone())),
+            ),
+            (
+                Cow::Borrowed("parameters"),
+                Amf0Value::Object(Cow::Owned(parameters)),
+            ),
+        ]))
+    }
+
+    /// Reads a cue point from an AMF0 object written by [`Self::to_amf`].
+    pub fn from_amf(value: &Amf0Value<'_>) -> Option<Self> {
+        let time = value.get("time")?.as_number()?;
+        let parameters = value
+            .get("parameters")
+            .and_then(|parameters| parameters.as_object_properties())
+            .unwrap_or_default()
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.into_owned()))
+            .collect();
+        Some(Self {
+            time_ms: (time * 1000.0).round() as u32,
+            name: value.get("name")?.as_str()?.to_string(),
+            typ: value
+                .get("type")
+                .and_then(|typ| typ.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            parameters,
+        })
+    }
+
+    /// Payload of the `onCuePoint` script tag of the cue point.
+    pub fn to_script_payload(&self) -> Result<Vec<u8>, Amf0WriteError> {
+        let mut buffer = Vec::new();
+        Amf0Encoder::encode_string(&mut buffer, crate::AMF0_ON_CUE_POINT)?;
+        Amf0Encoder::encode(&mut buffer, &self.to_amf())?;
+        Ok(buffer)
+    }
+
+    /// Whether a script tag payload is an `onCuePoint`, without decoding it.
+    pub fn is_cue_point_payload(payload: &[u8]) -> bool {
+        let name = crate::AMF0_ON_CUE_POINT.as_bytes();
+        payload.len() >= 3 + name.len()
+            && payload[0] == Amf0Marker::String as u8
+            && u16::from_be_bytes([payload[1], payload[2]]) as usize == name.len()
+            && &payload[3..3 + name.len()] == name
+    }
+}
@@ -1,15 +1,23 @@
+use bytes::Bytes;
 use flv::{
     audio::{AudioTagUtils, SoundFormat, SoundRate, SoundSize, SoundType},
     header::FlvHeader,
     resolution::Resolution,
+    script::ScriptData,
     tag::FlvTag,
-    video::VideoCodecId,
+    video::{VideoCodecId, VideoFourCC},
 };
 
+use pipeline_common::split_reason::{AudioCodecInfo, VideoCodecInfo};
 use std::fmt;
 use tracing::{debug, trace};
 
-use crate::operators::MIN_INTERVAL_BETWEEN_KEYFRAMES_MS;
+use crate::amf::model::CuePoint;
+use crate::operators::{MIN_INTERVAL_BETWEEN_KEYFRAMES_MS, SplitOperator};
+use crate::report::{
+    AUDIO_GAP_THRESHOLD_MS, AnalysisReport, AudioParams, FileInfo, Issue, IssueKind,
+    MAX_REPORTED_ISSUES, TagCounts, VideoParams,
+};
 use crate::utils::{FLV_HEADER_SIZE, FLV_PREVIOUS_TAG_SIZE};
 
 /// Error type for FLV analysis operations
@@ -39,10 +47,14 @@ pub struct Keyframe {
 #[derive(Debug, Clone, Default)]
 pub struct VideoStats {
     pub video_codec: Option<VideoCodecId>,
+    /// FourCC of enhanced (E-RTMP) streams, which have no legacy codec id
+    pub video_fourcc: Option<VideoFourCC>,
     pub video_tag_count: u32,
     pub video_tags_size: u64,
     pub video_data_size: u64,
     pub video_frame_rate: f32,
+    /// Frame rate signalled by the SPS timing info of the sequence header
+    pub sps_frame_rate: Option<f32>,
     pub video_data_rate: f32,
     pub last_video_timestamp: u32,
     pub first_video_timestamp: Option<u32>,
@@ -110,6 +122,9 @@ pub struct FlvStats {
     pub last_audio_timestamp: u32,
 
     pub first_audio_timestamp: Option<u32>,
+
+    /// Cue points of the `onCuePoint` script tags, in tag order
+    pub cue_points: Vec<CuePoint>,
 }
 
 impl Default for FlvStats {
@@ -134,6 +149,7 @@ impl Default for FlvStats {
             audio_sample_size: 0,
             first_audio_timestamp: None,
             audio_data_rate: 0.0,
+            cue_points: Vec::new(),
         }
     }
 }
@@ -178,6 +194,11 @@ impl FlvStats {
     ///
     /// Returns the duration in seconds as a u32.
     pub fn calculate_duration(&self) -> u32 {
+        self.calculate_duration_ms() / 1000
+    }
+
+    /// Calculates the duration of the FLV file in milliseconds, see [`Self::calculate_duration`].
+    pub fn calculate_duration_ms(&self) -> u32 {
         let first_video_timestamp = self
             .video_stats
             .as_ref()
@@ -204,8 +225,7 @@ impl FlvStats {
             (false, false) => self.last_timestamp, // Fallback to general timestamp
         };
 
-        // Calculate duration in seconds (timestamps are in milliseconds)
-        last_timestamp.saturating_sub(first_timestamp) / 1000
+        last_timestamp.saturating_sub(first_timestamp)
     }
 
     pub fn is_valid(&self) -> bool {
@@ -258,11 +278,14 @@ impl FlvStats {
         writeln!(f, "  Media:")?;
         writeln!(f, "    Has video: {}", self.has_video)?;
         if let Some(video_stats) = &self.video_stats {
-            writeln!(
-                f,
-                "    Video codec: {:?}",
-                video_stats.video_codec.unwrap_or(VideoCodecId::Avc)
-            )?;
+            match video_stats.video_fourcc {
+                Some(fourcc) => writeln!(f, "    Video codec: {fourcc}")?,
+                None => writeln!(
+                    f,
+                    "    Video codec: {:?}",
+                    video_stats.video_codec.unwrap_or(VideoCodecId::Avc)
+                )?,
+            }
             if let Some(resolution) = &video_stats.resolution {
                 writeln!(
                     f,
@@ -420,6 +443,16 @@ impl FlvStats {
     }
 }
 
+/// Per-track state used to detect stream issues
+#[derive(Default)]
+struct TrackCheck {
+    last_timestamp: Option<u32>,
+    last_data: Option<Bytes>,
+    last_was_sequence_header: bool,
+    missing_header_reported: bool,
+    sequence_headers: u32,
+}
+
 #[derive(Default)]
 pub struct FlvAnalyzer {
     pub stats: FlvStats,
@@ -427,14 +460,19 @@ pub struct FlvAnalyzer {
     pub header_analyzed: bool,
     pub has_video_sequence_header: bool,
     pub has_audio_sequence_header: bool,
+
+    issues: Vec<Issue>,
+    suppressed_issues: u64,
+    video_check: TrackCheck,
+    audio_check: TrackCheck,
+    last_tag_size: u32,
+    video_codec_info: Option<VideoCodecInfo>,
+    audio_codec_info: Option<AudioCodecInfo>,
 }
 
 impl FlvAnalyzer {
     pub fn reset(&mut self) {
-        self.stats.reset();
-        self.header_analyzed = false;
-        self.has_video_sequence_header = false;
-        self.has_audio_sequence_header = false;
+        *self = Self::default();
     }
 
     pub fn analyze_header(&mut self, header: &FlvHeader) -> Result<(), AnalyzerError> {
@@ -543,10 +581,16 @@ impl FlvAnalyzer {
                 }
             }
 
-            if video_stats.video_codec.is_none() {
-                // parse the codec id
+            if video_stats.sps_frame_rate.is_none() {
+                video_stats.sps_frame_rate = tag.get_video_frame_rate().map(|rate| rate as f32);
+            }
+
+            if video_stats.video_codec.is_none() && video_stats.video_fourcc.is_none() {
+                // parse the codec id, or the FourCC of enhanced tags
                 if let Some(codec_id) = tag.get_video_codec_id() {
                     video_stats.video_codec = Some(codec_id);
+                } else if let Some(fourcc) = tag.get_video_fourcc() {
+                    video_stats.video_fourcc = Some(fourcc);
                 } else {
                     debug!(
                         ts_ms = tag.timestamp_ms,
@@ -599,13 +643,176 @@ impl FlvAnalyzer {
         video_stats.last_video_timestamp = timestamp;
     }
 
+    /// Record an issue, or only count it once the report is full
+    fn record_issue(
+        &mut self,
+        kind: IssueKind,
+        offset: u64,
+        timestamp_ms: u32,
+        tag_index: u64,
+        message: String,
+    ) {
+        if self.issues.len() >= MAX_REPORTED_ISSUES {
+            self.suppressed_issues += 1;
+            return;
+        }
+        debug!(?kind, offset, tag_index, "{message}");
+        self.issues.push(Issue {
+            kind,
+            offset,
+            tag_index,
+            timestamp_ms,
+            message,
+        });
+    }
+
+    /// Check a media tag against the previous tag of its track.
+    /// Must run before the tag is added to the stats.
+    fn check_media_tag(&mut self, tag: &FlvTag) {
+        let is_audio = tag.is_audio_tag();
+        let (track, is_sequence_header, needs_sequence_header, has_sequence_header) = if is_audio {
+            (
+                "audio",
+                tag.is_audio_sequence_header(),
+                tag.get_audio_codec_id() == Some(SoundFormat::Aac),
+                self.has_audio_sequence_header,
+            )
+        } else {
+            let enhanced = tag.data.first().is_some_and(|b| b & 0x80 != 0);
+            (
+                "video",
+                tag.is_video_sequence_header(),
+                enhanced
+                    || matches!(
+                        tag.get_video_codec_id(),
+                        Some(VideoCodecId::Avc | VideoCodecId::LegacyHevc)
+                    ),
+                self.has_video_sequence_header,
+            )
+        };
+        let check = if is_audio {
+            &mut self.audio_check
+        } else {
+            &mut self.video_check
+        };
+
+        let timestamp = tag.timestamp_ms;
+        let mut found = Vec::new();
+
+        if needs_sequence_header
+            && !is_sequence_header
+            && !has_sequence_header
+            && !check.missing_header_reported
+        {
+            check.missing_header_reported = true;
+            found.push((
+                IssueKind::MissingSequenceHeader,
+                format!("{track} data at {timestamp}ms before any {track} sequence header"),
+            ));
+        }
+
+        if let Some(last) = check.last_timestamp {
+            if timestamp < last {
+                found.push((
+                    IssueKind::TimestampRegression,
+                    format!("{track} timestamp went back from {last}ms to {timestamp}ms"),
+                ));
+            } else if is_audio
+                && !is_sequence_header
+                && !check.last_was_sequence_header
+                && timestamp - last > AUDIO_GAP_THRESHOLD_MS
+            {
+                found.push((
+                    IssueKind::GapInAudio,
+                    format!("no audio between {last}ms and {timestamp}ms"),
+                ));
+            }
+
+            if timestamp == last && check.last_data.as_ref() == Some(&tag.data) {
+                found.push((
+                    IssueKind::DuplicateTag,
+                    format!("{track} tag at {timestamp}ms repeats the previous one"),
+                ));
+            }
+        }
+
+        check.last_timestamp = Some(timestamp);
+        check.last_data = Some(tag.data.clone());
+        check.last_was_sequence_header = is_sequence_header;
+        if is_sequence_header {
+            check.sequence_headers += 1;
+        }
+
+        let offset = self.stats.file_size;
+        let tag_index = self.stats.tag_count as u64;
+        for (kind, message) in found {
+            self.record_issue(kind, offset, timestamp, tag_index, message);
+        }
+    }
+
+    /// Check a PreviousTagSize field against the size of the last analyzed tag.
+    ///
+    /// Only needed when the raw byte stream is available, see [`crate::analyze_reader`].
+    pub fn analyze_previous_tag_size(&mut self, previous_tag_size: u32) {
+        let expected = self.last_tag_size;
+        if previous_tag_size != expected {
+            self.record_issue(
+                IssueKind::BrokenBackpointer,
+                self.stats.file_size - FLV_PREVIOUS_TAG_SIZE as u64,
+                self.stats.last_timestamp,
+                self.stats.tag_count as u64,
+                format!("PreviousTagSize is {previous_tag_size}, expected {expected}"),
+            );
+        }
+    }
+
+    /// Record that the input ended after `read` of `expected` bytes of the next tag
+    pub(crate) fn report_truncated_tag(
+        &mut self,
+        read: usize,
+        expected: usize,
+        timestamp_ms: Option<u32>,
+    ) {
+        self.record_issue(
+            IssueKind::TruncatedTag,
+            self.stats.file_size,
+            timestamp_ms.unwrap_or(self.stats.last_timestamp),
+            self.stats.tag_count as u64,
+            format!("input ends after {read} of {expected} bytes of the tag"),
+        );
+    }
+
+    /// Record a tag that cannot be analyzed because of its type
+    pub(crate) fn report_unknown_tag(&mut self, tag: &FlvTag) {
+        self.record_issue(
+            IssueKind::UnknownTagType,
+            self.stats.file_size,
+            tag.timestamp_ms,
+            self.stats.tag_count as u64,
+            format!("unknown tag type {}", u8::from(tag.tag_type)),
+        );
+    }
+
     pub fn analyze_tag(&mut self, tag: &FlvTag) -> Result<(), AnalyzerError> {
+        if tag.is_audio_tag() || tag.is_video_tag() {
+            self.check_media_tag(tag);
+        }
+
         if tag.is_audio_tag() {
+            if self.audio_codec_info.is_none() && tag.is_audio_sequence_header() {
+                self.audio_codec_info = Some(SplitOperator::extract_audio_codec_info(tag, 0));
+            }
             self.analyze_audio_tag(tag);
         } else if tag.is_video_tag() {
+            if self.video_codec_info.is_none() && tag.is_video_sequence_header() {
+                self.video_codec_info = Some(SplitOperator::extract_video_codec_info(tag, 0));
+            }
             self.analyze_video_tag(tag);
         } else if tag.is_script_tag() {
             self.stats.script_tag_count += 1;
+            if CuePoint::is_cue_point_payload(&tag.data) {
+                self.analyze_cue_point(tag);
+            }
         } else {
             return Err(AnalyzerError::UnknownTagType(tag.tag_type.into()));
         }
@@ -619,10 +826,33 @@ impl FlvAnalyzer {
             data_size + flv::framing::TAG_HEADER_SIZE as u64 + FLV_PREVIOUS_TAG_SIZE as u64;
 
         self.stats.last_timestamp = tag.timestamp_ms;
+        self.last_tag_size = data_size as u32 + flv::framing::TAG_HEADER_SIZE as u32;
 
         Ok(())
     }
 
+    fn analyze_cue_point(&mut self, tag: &FlvTag) {
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        match ScriptData::demux(&mut cursor) {
+            Ok(script) => {
+                if let Some(cue) = script.data.first().and_then(CuePoint::from_amf) {
+                    self.stats.cue_points.push(cue);
+                }
+            }
+            Err(e) => debug!("Ignoring malformed onCuePoint tag: {e}"),
+        }
+    }
+
+    /// Codec configuration of the first video sequence header, if one was analyzed.
+    pub fn video_codec_info(&self) -> Option<&VideoCodecInfo> {
+        self.video_codec_info.as_ref()
+    }
+
+    /// Codec configuration of the first audio sequence header, if one was analyzed.
+    pub fn audio_codec_info(&self) -> Option<&AudioCodecInfo> {
+        self.audio_codec_info.as_ref()
+    }
+
     pub fn build_stats(&mut self) -> Result<&FlvStats, AnalyzerError> {
         if !self.header_analyzed {
             return Err(AnalyzerError::HeaderNotAnalyzed);
@@ -630,7 +860,10 @@ impl FlvAnalyzer {
 
         if let Some(video_stats) = self.stats.video_stats.as_mut() {
             video_stats.video_data_rate = video_stats.calculate_video_bitrate();
-            video_stats.video_frame_rate = video_stats.calculate_frame_rate();
+            // Prefer the rate the encoder signalled over the one measured from tag timestamps
+            video_stats.video_frame_rate = video_stats
+                .sps_frame_rate
+                .unwrap_or_else(|| video_stats.calculate_frame_rate());
         }
 
         if self.stats.has_audio {
@@ -642,6 +875,83 @@ impl FlvAnalyzer {
 
         Ok(&self.stats)
     }
+
+    /// Build a structured report from the stats and every issue found so far.
+    ///
+    /// The analyzer keeps its state, so this can be called repeatedly while streaming.
+    pub fn finalize_report(&mut self) -> Result<AnalysisReport, AnalyzerError> {
+        self.build_stats()?;
+        let stats = &self.stats;
+
+        let video_params = stats.video_stats.as_ref().map(|vs| {
+            let info = self.video_codec_info.as_ref();
+            VideoParams {
+                codec: info
+                    .map(|i| i.codec.clone())
+                    .or_else(|| vs.video_codec.map(|c| format!("{c:?}")))
+                    .or_else(|| vs.video_fourcc.map(|fourcc| fourcc.to_string()))
+                    .unwrap_or_else(|| "unknown".to_string()),
+                profile: info.and_then(|i| i.profile),
+                level: info.and_then(|i| i.level),
+                width: info
+                    .and_then(|i| i.width)
+                    .or(vs.resolution.as_ref().map(|r| r.width as u32)),
+                height: info
+                    .and_then(|i| i.height)
+                    .or(vs.resolution.as_ref().map(|r| r.height as u32)),
+                frame_rate: vs.video_frame_rate,
+                data_rate: vs.video_data_rate,
+            }
+        });
+
+        let audio_params = stats.has_audio.then(|| match &self.audio_codec_info {
+            Some(info) => AudioParams {
+                codec: info.codec.clone(),
+                sample_rate: info.sample_rate,
+                channels: info.channels,
+                data_rate: stats.audio_data_rate,
+            },
+            // No sequence header, fall back to the legacy audio tag header
+            None => AudioParams {
+                codec: stats
+                    .audio_codec
+                    .map(|c| format!("{c:?}"))
+                    .unwrap_or_else(|| "unknown".to_string()),
+                sample_rate: (stats.audio_sample_rate > 0.0)
+                    .then_some(stats.audio_sample_rate as u32),
+                channels: Some(if stats.audio_stereo { 2 } else { 1 }),
+                data_rate: stats.audio_data_rate,
+            },
+        });
+
+        Ok(AnalysisReport {
+            file_info: FileInfo {
+                file_size: stats.file_size,
+                duration: stats.duration,
+                has_video: stats.has_video,
+                has_audio: stats.has_audio,
+                keyframe_count: stats
+                    .video_stats
+                    .as_ref()
+                    .map_or(0, |vs| vs.keyframes.len()),
+            },
+            video_params,
+            audio_params,
+            tag_counts: TagCounts {
+                total: stats.tag_count,
+                video: stats
+                    .video_stats
+                    .as_ref()
+                    .map_or(0, |vs| vs.video_tag_count),
+                audio: stats.audio_tag_count,
+                script: stats.script_tag_count,
+                video_sequence_headers: self.video_check.sequence_headers,
+                audio_sequence_headers: self.audio_check.sequence_headers,
+            },
+            issues: self.issues.clone(),
+            suppressed_issues: self.suppressed_issues,
+        })
+    }
 }
 
 #[cfg(test)]
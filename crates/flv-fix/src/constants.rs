@@ -30,6 +30,7 @@ pub const METADATA_LASTKEYFRAMELOCATION: &str = "lastkeyframelocation";
 pub const METADATA_LASTKEYFRAMETIMESTAMP: &str = "lastkeyframetimestamp";
 pub const METADATA_METADATACREATOR: &str = "metadatacreator";
 pub const METADATA_METADATADATE: &str = "metadatadate";
+pub const METADATA_ENCODER: &str = "encoder";
 pub const METADATA_KEYFRAMES: &str = "keyframes";
 
 // Keyframe property keys
@@ -43,6 +44,8 @@ pub const ERROR_NO_ACTIVE_WRITER: &str = "Attempted write_tag with no active wri
 
 // AMF0 script data names
 pub const AMF0_ON_METADATA: &str = "onMetaData";
+pub const AMF0_ON_CUE_POINT: &str = "onCuePoint";
+pub const METADATA_CUE_POINTS: &str = "cuePoints";
 
 // Default creator value
 pub const DEFAULT_CREATOR: &str = "Srec";
@@ -0,0 +1,369 @@
+//! ISOBMFF boxes of the fragmented MP4 output: the `ftyp`/`moov` init segment and the
+//! `moof`/`mdat` media fragments.
+
+use bytes::{BufMut, Bytes};
+
+/// Timescale of both tracks, matching the millisecond timestamps of FLV tags.
+pub(super) const TIMESCALE: u32 = 1000;
+
+pub(super) const VIDEO_TRACK_ID: u32 = 1;
+pub(super) const AUDIO_TRACK_ID: u32 = 2;
+
+/// Unity transformation matrix of `mvhd` and `tkhd`.
+const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// `trun` flags: data offset, and per sample duration, size, flags and composition offset.
+const TRUN_FLAGS: u32 = 0x000001 | 0x000100 | 0x000200 | 0x000400 | 0x000800;
+
+/// `tfhd` flag: data offsets are relative to the start of the `moof` box.
+const TFHD_DEFAULT_BASE_IS_MOOF: u32 = 0x020000;
+
+/// Sample flags of a sync sample (`sample_depends_on` = 2).
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+
+/// Sample flags of a non-sync sample (`sample_depends_on` = 1, `sample_is_non_sync_sample`).
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+/// Video codec of a video track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum VideoCodec {
+    Avc,
+    Hevc,
+}
+
+/// Configuration of the video track, from the FLV sequence header.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct VideoTrack {
+    pub codec: VideoCodec,
+    /// `AVCDecoderConfigurationRecord` or `HEVCDecoderConfigurationRecord` bytes.
+    pub config: Bytes,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Configuration of the audio track, from the FLV AAC sequence header.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct AudioTrack {
+    /// `AudioSpecificConfig` bytes.
+    pub config: Bytes,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// A sample of a media fragment.
+#[derive(Debug, Clone)]
+pub(super) struct Sample {
+    pub duration: u32,
+    pub composition_offset: i32,
+    pub keyframe: bool,
+    pub data: Bytes,
+}
+
+/// The samples of one track in a media fragment.
+pub(super) struct TrackFragment<'a> {
+    pub track_id: u32,
+    pub base_decode_time: u64,
+    pub samples: &'a [Sample],
+}
+
+/// Appends a box, its body written by `body`.
+fn write_box(out: &mut Vec<u8>, fourcc: &[u8; 4], body: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.put_u32(0);
+    out.put_slice(fourcc);
+    body(out);
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+/// Appends a full box with `version` and `flags`, its body written by `body`.
+fn write_full_box(
+    out: &mut Vec<u8>,
+    fourcc: &[u8; 4],
+    version: u8,
+    flags: u32,
+    body: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, fourcc, |out| {
+        out.put_u32((u32::from(version) << 24) | (flags & 0x00FF_FFFF));
+        body(out);
+    });
+}
+
+fn write_matrix(out: &mut Vec<u8>) {
+    for value in MATRIX {
+        out.put_u32(value);
+    }
+}
+
+/// Build the init segment describing the given tracks.
+pub(super) fn init_segment(video: Option<&VideoTrack>, audio: Option<&AudioTrack>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1024);
+
+    write_box(&mut out, b"ftyp", |out| {
+        out.put_slice(b"isom");
+        out.put_u32(0x200);
+        out.put_slice(b"isomiso6mp41");
+        match video.map(|video| video.codec) {
+            Some(VideoCodec::Avc) => out.put_slice(b"avc1"),
+            Some(VideoCodec::Hevc) => out.put_slice(b"hvc1"),
+            None => {}
+        }
+    });
+
+    write_box(&mut out, b"moov", |out| {
+        write_full_box(out, b"mvhd", 0, 0, |out| {
+            out.put_u32(0); // creation_time
+            out.put_u32(0); // modification_time
+            out.put_u32(TIMESCALE);
+            out.put_u32(0); // duration, unknown for fragmented files
+            out.put_u32(0x0001_0000); // rate 1.0
+            out.put_u16(0x0100); // volume 1.0
+            out.put_bytes(0, 10);
+            write_matrix(out);
+            out.put_bytes(0, 24);
+            out.put_u32(AUDIO_TRACK_ID + 1); // next_track_ID
+        });
+
+        if let Some(video) = video {
+            write_video_trak(out, video);
+        }
+        if let Some(audio) = audio {
+            write_audio_trak(out, audio);
+        }
+
+        write_box(out, b"mvex", |out| {
+            let track_ids = [video.map(|_| VIDEO_TRACK_ID), audio.map(|_| AUDIO_TRACK_ID)];
+            for track_id in track_ids.into_iter().flatten() {
+                write_full_box(out, b"trex", 0, 0, |out| {
+                    out.put_u32(track_id);
+                    out.put_u32(1); // default_sample_description_index
+                    out.put_u32(0); // default_sample_duration
+                    out.put_u32(0); // default_sample_size
+                    out.put_u32(0); // default_sample_flags
+                });
+            }
+        });
+    });
+
+    out
+}
+
+fn write_video_trak(out: &mut Vec<u8>, video: &VideoTrack) {
+    write_trak(
+        out,
+        VIDEO_TRACK_ID,
+        (video.width, video.height),
+        b"vide",
+        b"VideoHandler\0",
+        |out| {
+            write_full_box(out, b"vmhd", 0, 1, |out| out.put_bytes(0, 8));
+        },
+        |out| {
+            let (entry, config_box) = match video.codec {
+                VideoCodec::Avc => (b"avc1", b"avcC"),
+                VideoCodec::Hevc => (b"hvc1", b"hvcC"),
+            };
+            write_box(out, entry, |out| {
+                out.put_bytes(0, 6);
+                out.put_u16(1); // data_reference_index
+                out.put_bytes(0, 16);
+                out.put_u16(video.width);
+                out.put_u16(video.height);
+                out.put_u32(0x0048_0000); // horizresolution 72 dpi
+                out.put_u32(0x0048_0000); // vertresolution 72 dpi
+                out.put_u32(0);
+                out.put_u16(1); // frame_count
+                out.put_bytes(0, 32); // compressorname
+                out.put_u16(0x0018); // depth
+                out.put_i16(-1);
+                write_box(out, config_box, |out| out.put_slice(&video.config));
+            });
+        },
+    );
+}
+
+fn write_audio_trak(out: &mut Vec<u8>, audio: &AudioTrack) {
+    write_trak(
+        out,
+        AUDIO_TRACK_ID,
+        (0, 0),
+        b"soun",
+        b"SoundHandler\0",
+        |out| {
+            write_full_box(out, b"smhd", 0, 0, |out| out.put_u32(0));
+        },
+        |out| {
+            write_box(out, b"mp4a", |out| {
+                out.put_bytes(0, 6);
+                out.put_u16(1); // data_reference_index
+                out.put_bytes(0, 8);
+                out.put_u16(audio.channels);
+                out.put_u16(16); // samplesize
+                out.put_u32(0);
+                out.put_u32(audio.sample_rate.min(0xFFFF) << 16);
+                write_full_box(out, b"esds", 0, 0, |out| write_es_descriptor(out, audio));
+            });
+        },
+    );
+}
+
+/// Appends the `ES_Descriptor` of an AAC track (ISO/IEC 14496-1).
+fn write_es_descriptor(out: &mut Vec<u8>, audio: &AudioTrack) {
+    let mut decoder_specific_info = Vec::new();
+    write_descriptor(&mut decoder_specific_info, 0x05, &audio.config);
+
+    let mut decoder_config = vec![
+        0x40, // objectTypeIndication: MPEG-4 Audio
+        0x15, // streamType: audio, upStream 0, reserved 1
+    ];
+    decoder_config.put_bytes(0, 3); // bufferSizeDB
+    decoder_config.put_u32(0); // maxBitrate
+    decoder_config.put_u32(0); // avgBitrate
+    decoder_config.extend_from_slice(&decoder_specific_info);
+
+    let mut es = Vec::new();
+    es.put_u16(AUDIO_TRACK_ID as u16); // ES_ID
+    es.put_u8(0); // flags
+    write_descriptor(&mut es, 0x04, &decoder_config);
+    write_descriptor(&mut es, 0x06, &[0x02]); // SLConfigDescriptor, predefined MP4
+
+    write_descriptor(out, 0x03, &es);
+}
+
+/// Appends a descriptor with its size in the 4-byte expandable form.
+fn write_descriptor(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+    let size = body.len() as u32;
+    out.put_u8(tag);
+    out.put_u8(0x80 | ((size >> 21) & 0x7F) as u8);
+    out.put_u8(0x80 | ((size >> 14) & 0x7F) as u8);
+    out.put_u8(0x80 | ((size >> 7) & 0x7F) as u8);
+    out.put_u8((size & 0x7F) as u8);
+    out.put_slice(body);
+}
+
+fn write_trak(
+    out: &mut Vec<u8>,
+    track_id: u32,
+    (width, height): (u16, u16),
+    handler_type: &[u8; 4],
+    handler_name: &[u8],
+    write_media_header: impl FnOnce(&mut Vec<u8>),
+    write_sample_entry: impl FnOnce(&mut Vec<u8>),
+) {
+    let is_audio = handler_type == b"soun";
+    write_box(out, b"trak", |out| {
+        // Track enabled and in movie
+        write_full_box(out, b"tkhd", 0, 0x3, |out| {
+            out.put_u32(0); // creation_time
+            out.put_u32(0); // modification_time
+            out.put_u32(track_id);
+            out.put_u32(0);
+            out.put_u32(0); // duration
+            out.put_bytes(0, 8);
+            out.put_u16(0); // layer
+            out.put_u16(0); // alternate_group
+            out.put_u16(if is_audio { 0x0100 } else { 0 }); // volume
+            out.put_u16(0);
+            write_matrix(out);
+            out.put_u32(u32::from(width) << 16);
+            out.put_u32(u32::from(height) << 16);
+        });
+
+        write_box(out, b"mdia", |out| {
+            write_full_box(out, b"mdhd", 0, 0, |out| {
+                out.put_u32(0); // creation_time
+                out.put_u32(0); // modification_time
+                out.put_u32(TIMESCALE);
+                out.put_u32(0); // duration
+                out.put_u16(0x55C4); // language: und
+                out.put_u16(0);
+            });
+            write_full_box(out, b"hdlr", 0, 0, |out| {
+                out.put_u32(0);
+                out.put_slice(handler_type);
+                out.put_bytes(0, 12);
+                out.put_slice(handler_name);
+            });
+            write_box(out, b"minf", |out| {
+                write_media_header(out);
+                write_box(out, b"dinf", |out| {
+                    write_full_box(out, b"dref", 0, 0, |out| {
+                        out.put_u32(1);
+                        // Media data in the same file
+                        write_full_box(out, b"url ", 0, 1, |_| {});
+                    });
+                });
+                write_box(out, b"stbl", |out| {
+                    write_full_box(out, b"stsd", 0, 0, |out| {
+                        out.put_u32(1);
+                        write_sample_entry(out);
+                    });
+                    // The samples are described by the fragments
+                    write_full_box(out, b"stts", 0, 0, |out| out.put_u32(0));
+                    write_full_box(out, b"stsc", 0, 0, |out| out.put_u32(0));
+                    write_full_box(out, b"stsz", 0, 0, |out| {
+                        out.put_u32(0);
+                        out.put_u32(0);
+                    });
+                    write_full_box(out, b"stco", 0, 0, |out| out.put_u32(0));
+                });
+            });
+        });
+    });
+}
+
+/// Build a media fragment holding the samples of `tracks`, in order.
+pub(super) fn media_fragment(sequence_number: u32, tracks: &[TrackFragment<'_>]) -> Vec<u8> {
+    // The data offsets do not change the size of the `moof` box, so measure it first
+    let moof_size = moof(sequence_number, tracks, &vec![0; tracks.len()]).len();
+
+    let mut data_offsets = Vec::with_capacity(tracks.len());
+    let mut offset = moof_size + 8; // past the mdat header
+    for track in tracks {
+        data_offsets.push(offset as i32);
+        offset += track.samples.iter().map(|s| s.data.len()).sum::<usize>();
+    }
+
+    let mut out = moof(sequence_number, tracks, &data_offsets);
+    write_box(&mut out, b"mdat", |out| {
+        for sample in tracks.iter().flat_map(|track| track.samples) {
+            out.put_slice(&sample.data);
+        }
+    });
+    out
+}
+
+fn moof(sequence_number: u32, tracks: &[TrackFragment<'_>], data_offsets: &[i32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_box(&mut out, b"moof", |out| {
+        write_full_box(out, b"mfhd", 0, 0, |out| out.put_u32(sequence_number));
+
+        for (track, data_offset) in tracks.iter().zip(data_offsets) {
+            write_box(out, b"traf", |out| {
+                write_full_box(out, b"tfhd", 0, TFHD_DEFAULT_BASE_IS_MOOF, |out| {
+                    out.put_u32(track.track_id);
+                });
+                write_full_box(out, b"tfdt", 1, 0, |out| {
+                    out.put_u64(track.base_decode_time)
+                });
+                // Version 1 for signed composition offsets
+                write_full_box(out, b"trun", 1, TRUN_FLAGS, |out| {
+                    out.put_u32(track.samples.len() as u32);
+                    out.put_i32(*data_offset);
+                    for sample in track.samples {
+                        out.put_u32(sample.duration);
+                        out.put_u32(sample.data.len() as u32);
+                        out.put_u32(if sample.keyframe {
+                            SYNC_SAMPLE_FLAGS
+                        } else {
+                            NON_SYNC_SAMPLE_FLAGS
+                        });
+                        out.put_i32(sample.composition_offset);
+                    }
+                });
+            });
+        }
+    });
+    out
+}
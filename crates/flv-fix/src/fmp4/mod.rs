@@ -0,0 +1,18 @@
+//! # Fragmented MP4 Output
+//!
+//! Remuxes the FLV tag stream into fragmented MP4 files instead of writing it back as FLV.
+//!
+//! - `boxes`: Serialization of the init segment (`ftyp`/`moov`) and `moof`/`mdat` fragments
+//! - `writer_task`: [`Fmp4FormatStrategy`], mapping FLV tags to fMP4 tracks and samples
+//! - `writer`: [`Fmp4Writer`], the [`ProtocolWriter`](pipeline_common::ProtocolWriter) to run it
+//!
+//! H.264 and H.265 video (legacy and enhanced FLV) and AAC audio are supported, other codecs
+//! are dropped. Tag timestamps become decode times in milliseconds and AVC/HEVC composition
+//! times become the sample composition offsets.
+
+mod boxes;
+mod writer;
+mod writer_task;
+
+pub use writer::{Fmp4Writer, Fmp4WriterConfig};
+pub use writer_task::{Fmp4FormatStrategy, Fmp4StrategyError};
@@ -0,0 +1,252 @@
+use std::path::PathBuf;
+
+use flv::data::FlvData;
+use pipeline_common::{
+    OutputSink, PipelineError, ProgressConfig, ProtocolWriter, SegmentHook, SplitReason,
+    WriterConfig, WriterError, WriterProgress, WriterState, WriterStats, WriterTask,
+};
+
+use super::writer_task::Fmp4FormatStrategy;
+
+/// Configuration of the fMP4 writer.
+#[derive(Debug, Clone)]
+pub struct Fmp4WriterConfig {
+    pub output_dir: PathBuf,
+    pub base_name: String,
+}
+
+/// A writer task remuxing FLV data into fragmented MP4 files.
+///
+/// Files are split wherever the FLV writer would split them, on every header the pipeline
+/// emits after tags, so size and duration limits of the pipeline apply unchanged.
+pub struct Fmp4Writer {
+    writer_task: WriterTask<FlvData, Fmp4FormatStrategy>,
+}
+
+impl Fmp4Writer {
+    pub fn new(config: Fmp4WriterConfig) -> Self {
+        let writer_config =
+            WriterConfig::new(config.output_dir, config.base_name, "mp4".to_string());
+        let writer_task = WriterTask::new(writer_config, Fmp4FormatStrategy::new());
+        Self { writer_task }
+    }
+
+    /// Set a callback to be invoked when a new segment starts recording.
+    ///
+    /// The callback receives the file path and sequence number (0-based).
+    pub fn set_on_segment_start_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&std::path::Path, u32) + Send + Sync + 'static,
+    {
+        self.writer_task.set_on_file_open_callback(callback);
+    }
+
+    /// Set a callback to be invoked when a segment is completed.
+    ///
+    /// The callback receives the file path, sequence number (0-based), duration in seconds,
+    /// size in bytes, and an optional split reason.
+    pub fn set_on_segment_complete_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(&std::path::Path, u32, f64, u64, Option<&SplitReason>) + Send + Sync + 'static,
+    {
+        self.writer_task.set_on_file_close_callback(callback);
+    }
+
+    /// Add a hook invoked on the writer thread when segment files are opened and closed.
+    pub fn add_segment_hook<H: SegmentHook>(&mut self, hook: H) {
+        self.writer_task.add_segment_hook(hook);
+    }
+
+    /// Name files that would overwrite an existing one `name_1`, `name_2`, ...
+    pub fn set_numbered_collisions(&mut self, numbered_collisions: bool) {
+        self.writer_task.config_mut().numbered_collisions = numbered_collisions;
+    }
+
+    /// Write files to `sink` instead of the local disk.
+    pub fn set_output_sink(&mut self, sink: OutputSink) {
+        self.writer_task.config_mut().sink = sink;
+    }
+
+    /// Set a progress callback with default intervals (1MB bytes, 1000ms time).
+    pub fn set_progress_callback<F>(&mut self, callback: F)
+    where
+        F: Fn(WriterProgress) + Send + Sync + 'static,
+    {
+        self.writer_task.set_progress_callback(callback);
+    }
+
+    /// Set a progress callback with custom intervals.
+    pub fn set_progress_callback_with_config<F>(&mut self, callback: F, config: ProgressConfig)
+    where
+        F: Fn(WriterProgress) + Send + Sync + 'static,
+    {
+        self.writer_task
+            .set_progress_callback_with_config(callback, config);
+    }
+
+    /// Get the total media duration in seconds across all files.
+    pub fn media_duration_secs(&self) -> f64 {
+        self.writer_task.get_state().media_duration_secs_total
+    }
+}
+
+impl ProtocolWriter for Fmp4Writer {
+    type Item = FlvData;
+
+    fn get_state(&self) -> &WriterState {
+        self.writer_task.get_state()
+    }
+
+    fn run(
+        &mut self,
+        input: tokio::sync::mpsc::Receiver<Result<Self::Item, PipelineError>>,
+    ) -> Result<WriterStats, WriterError> {
+        self.writer_task.run_from_channel(input, |_, _| true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_test_header, create_test_tag, create_video_tag};
+    use bytes::Bytes;
+    use flv::tag::FlvTagType;
+    use hls_fix::fragment_timing::{FragmentTiming, TrackTimescales};
+    use mp4::isobmff::{ParseOptions, parse_init_segment_with_options};
+
+    /// Baseline-profile SPS for 1920x1080
+    const SPS_1080P: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+
+    fn avc_sequence_header(timestamp: u32) -> FlvData {
+        let mut data = vec![
+            0x17, // Keyframe (1) + AVC (7)
+            0x00, // AVC sequence header
+            0x00,
+            0x00,
+            0x00, // Composition time
+            0x01, // configurationVersion
+            SPS_1080P[1],
+            SPS_1080P[2],
+            SPS_1080P[3],
+            0xff, // lengthSizeMinusOne = 3
+            0xe1, // one SPS
+        ];
+        data.extend_from_slice(&(SPS_1080P.len() as u16).to_be_bytes());
+        data.extend_from_slice(SPS_1080P);
+        data.extend_from_slice(&[0x01, 0x00, 0x04, 0x68, 0xce, 0x38, 0x80]);
+        create_test_tag(FlvTagType::Video, timestamp, data)
+    }
+
+    fn aac_sequence_header(timestamp: u32) -> FlvData {
+        // AAC LC, 44.1kHz, stereo
+        create_test_tag(FlvTagType::Audio, timestamp, vec![0xAF, 0x00, 0x12, 0x10])
+    }
+
+    fn aac_frame(timestamp: u32) -> FlvData {
+        create_test_tag(
+            FlvTagType::Audio,
+            timestamp,
+            vec![0xAF, 0x01, 0x21, 0x10, 0x04],
+        )
+    }
+
+    fn run_writer(base_name: &str, items: Vec<FlvData>) -> (tempfile::TempDir, WriterStats) {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+        let mut writer = Fmp4Writer::new(Fmp4WriterConfig {
+            output_dir: tempdir.path().to_path_buf(),
+            base_name: base_name.to_string(),
+        });
+
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<FlvData, PipelineError>>(64);
+        let handle = std::thread::spawn(move || writer.run(rx));
+        for item in items {
+            tx.blocking_send(Ok(item)).unwrap();
+        }
+        drop(tx);
+
+        let stats = handle
+            .join()
+            .expect("writer thread join")
+            .expect("writer ok");
+        (tempdir, stats)
+    }
+
+    fn gop(start: u32) -> Vec<FlvData> {
+        vec![
+            create_video_tag(start, true),
+            aac_frame(start),
+            create_video_tag(start + 33, false),
+            aac_frame(start + 23),
+            create_video_tag(start + 66, false),
+            aac_frame(start + 46),
+            aac_frame(start + 69),
+            aac_frame(start + 92),
+        ]
+    }
+
+    #[test]
+    fn remuxes_tags_into_fragments_per_gop() {
+        let mut items = vec![
+            create_test_header(),
+            avc_sequence_header(0),
+            aac_sequence_header(0),
+        ];
+        items.extend(gop(0));
+        items.extend(gop(100));
+
+        let (tempdir, stats) = run_writer("remux", items);
+        assert_eq!(stats.files_created, 1);
+
+        let data = Bytes::from(std::fs::read(tempdir.path().join("remux.mp4")).unwrap());
+        let info = parse_init_segment_with_options(
+            &data,
+            ParseOptions {
+                include_resolution: true,
+            },
+        );
+        assert!(info.has_h264);
+        assert!(info.has_aac);
+        let resolution = info.video_resolution.expect("resolution from avcC");
+        assert_eq!((resolution.width, resolution.height), (1920.0, 1080.0));
+
+        let timescales = TrackTimescales::from_init(&data).unwrap();
+        assert_eq!(timescales.get(1).unwrap().timescale, 1000);
+        assert_eq!(timescales.get(2).unwrap().timescale, 1000);
+
+        let timing = FragmentTiming::parse(&data).unwrap();
+        assert_eq!(timing.sequence_number, Some(1));
+        let video = &timing.tracks[0];
+        assert_eq!(video.track_id, 1);
+        assert_eq!(video.base_media_decode_time, Some(0));
+        // The last frame reuses the duration of the frame before the second keyframe
+        assert_eq!(video.sample_durations, vec![33, 33, 34, 33, 33, 34]);
+        let audio = &timing.tracks[1];
+        assert_eq!(audio.track_id, 2);
+        assert_eq!(audio.sample_count, 10);
+        assert_eq!(audio.sample_durations[..5], [23, 23, 23, 23, 23]);
+    }
+
+    #[test]
+    fn starts_a_new_file_with_its_own_init_segment_on_header() {
+        let mut items = vec![
+            create_test_header(),
+            avc_sequence_header(0),
+            aac_sequence_header(0),
+        ];
+        items.extend(gop(0));
+        items.push(create_test_header());
+        items.push(avc_sequence_header(100));
+        items.push(aac_sequence_header(100));
+        items.extend(gop(100));
+
+        let (tempdir, stats) = run_writer("part_%i", items);
+        assert_eq!(stats.files_created, 2);
+
+        let second = Bytes::from(std::fs::read(tempdir.path().join("part_1.mp4")).unwrap());
+        assert!(parse_init_segment_with_options(&second, ParseOptions::default()).has_h264);
+        let timing = FragmentTiming::parse(&second).unwrap();
+        // Each file has its own timeline and fragment numbering
+        assert_eq!(timing.sequence_number, Some(1));
+        assert_eq!(timing.tracks[0].base_media_decode_time, Some(0));
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bytes::Bytes;
+use flv::{
+    FlvData, FlvTag,
+    audio::SoundFormat,
+    avc::AvcPacket,
+    hevc::HevcPacket,
+    resolution::Resolution,
+    video::{EnhancedPacket, VideoData, VideoFrameType, VideoTagBody},
+};
+use pipeline_common::{
+    FormatStrategy, PostWriteAction, SinkWriter, SplitReason, StreamMetadata, WriterConfig,
+    WriterState, expand_filename_template_with, template_uses_stream_metadata,
+};
+use tracing::{debug, info, warn};
+
+use crate::utils::parse_avc_sequence_header;
+
+use super::boxes::{
+    AUDIO_TRACK_ID, AudioTrack, Sample, TrackFragment, VIDEO_TRACK_ID, VideoCodec, VideoTrack,
+    init_segment, media_fragment,
+};
+
+/// Samples per AAC frame.
+const AAC_FRAME_SAMPLES: u32 = 1024;
+
+/// Fragment length of audio-only streams, which have no keyframes to cut at.
+const AUDIO_ONLY_FRAGMENT_MS: u32 = 1000;
+
+/// Error type for the fMP4 strategy
+#[derive(Debug, thiserror::Error)]
+pub enum Fmp4StrategyError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A sample waiting for the end of its fragment, timed by its FLV tag.
+struct PendingSample {
+    timestamp: u32,
+    composition_offset: i32,
+    keyframe: bool,
+    data: Bytes,
+}
+
+/// Format strategy remuxing FLV tags into fragmented MP4.
+///
+/// The init segment is written before the first sample of each file, from the AVC/HEVC
+/// and AAC sequence headers seen so far. Samples are then grouped into one `moof`/`mdat`
+/// fragment per video GOP, so every fragment starts with a keyframe.
+pub struct Fmp4FormatStrategy {
+    video: Option<VideoTrack>,
+    audio: Option<AudioTrack>,
+    /// Whether the init segment of the current file was written.
+    init_written: bool,
+    video_samples: Vec<PendingSample>,
+    audio_samples: Vec<PendingSample>,
+    /// Timestamp of the first sample of the current file, the origin of its timeline.
+    base_timestamp: Option<u32>,
+    last_timestamp: u32,
+    /// Duration of the last video sample of a fragment when the next one is not known.
+    last_video_duration: u32,
+    fragment_sequence: u32,
+    last_header_received: bool,
+    current_tag_count: u64,
+    /// The most recent split reason received, if any.
+    last_split_reason: Option<SplitReason>,
+}
+
+impl Default for Fmp4FormatStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fmp4FormatStrategy {
+    pub fn new() -> Self {
+        Self {
+            video: None,
+            audio: None,
+            init_written: false,
+            video_samples: Vec::new(),
+            audio_samples: Vec::new(),
+            base_timestamp: None,
+            last_timestamp: 0,
+            last_video_duration: 0,
+            fragment_sequence: 0,
+            last_header_received: false,
+            current_tag_count: 0,
+            last_split_reason: None,
+        }
+    }
+
+    /// Stream metadata of the configured tracks, for the variables of the file name template.
+    fn stream_metadata(&self) -> StreamMetadata {
+        StreamMetadata {
+            resolution: self
+                .video
+                .as_ref()
+                .map(|video| (u32::from(video.width), u32::from(video.height))),
+            video_codec: self.video.as_ref().map(|video| {
+                match video.codec {
+                    VideoCodec::Avc => "h264",
+                    VideoCodec::Hevc => "hevc",
+                }
+                .to_string()
+            }),
+            audio_codec: self.audio.as_ref().map(|_| "aac".to_string()),
+            title: None,
+        }
+    }
+
+    fn handle_video_tag(
+        &mut self,
+        writer: &mut BufWriter<SinkWriter>,
+        tag: &FlvTag,
+    ) -> Result<u64, Fmp4StrategyError> {
+        if let Some(config) = parse_avc_sequence_header(tag) {
+            // Rebuilt so the avcC box is spec compliant even if the record was not
+            let mut record = Vec::with_capacity(config.encoded_size());
+            if let Err(e) = config.build(&mut record) {
+                warn!(error = %e, "Invalid AVC sequence header");
+                return Ok(0);
+            }
+            let resolution = AvcPacket::SequenceHeader(config).get_video_resolution();
+            self.set_video_track(VideoCodec::Avc, Bytes::from(record), resolution);
+            return Ok(0);
+        }
+
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        let video = match VideoData::demux(&mut cursor) {
+            Ok(video) => video,
+            Err(e) => {
+                debug!(error = %e, "Skipping undecodable video tag");
+                return Ok(0);
+            }
+        };
+        let keyframe = video.frame_type == VideoFrameType::KeyFrame;
+
+        let (composition_offset, data) = match video.body {
+            VideoTagBody::Hevc(HevcPacket::SequenceStart(_))
+            | VideoTagBody::Enhanced(EnhancedPacket::Hevc(HevcPacket::SequenceStart(_))) => {
+                // The configuration record follows the 5-byte legacy or enhanced video header
+                let record = tag.data.slice(5.min(tag.data.len())..);
+                self.set_video_track(VideoCodec::Hevc, record, tag.get_video_resolution());
+                return Ok(0);
+            }
+            VideoTagBody::Avc(AvcPacket::Nalu {
+                composition_time,
+                data,
+            })
+            | VideoTagBody::Enhanced(EnhancedPacket::Avc(AvcPacket::Nalu {
+                composition_time,
+                data,
+            })) => (composition_time, data),
+            VideoTagBody::Hevc(HevcPacket::Nalu {
+                composition_time,
+                data,
+            })
+            | VideoTagBody::Enhanced(EnhancedPacket::Hevc(HevcPacket::Nalu {
+                composition_time,
+                data,
+            })) => (composition_time.unwrap_or(0), data),
+            _ => return Ok(0),
+        };
+
+        if self.video.is_none() {
+            debug!("Skipping video frame before the video sequence header");
+            return Ok(0);
+        }
+
+        // Every fragment starts with a keyframe
+        let mut bytes_written = 0;
+        if keyframe && !self.video_samples.is_empty() {
+            bytes_written += self.flush_fragment(writer, Some(tag.timestamp_ms))?;
+        }
+        bytes_written += self.ensure_init_segment(writer)?;
+        self.push_sample(
+            true,
+            PendingSample {
+                timestamp: tag.timestamp_ms,
+                composition_offset,
+                keyframe,
+                data,
+            },
+        );
+        Ok(bytes_written)
+    }
+
+    fn set_video_track(
+        &mut self,
+        codec: VideoCodec,
+        config: Bytes,
+        resolution: Option<Resolution>,
+    ) {
+        let track = VideoTrack {
+            codec,
+            config,
+            width: resolution.as_ref().map_or(0, |r| r.width as u16),
+            height: resolution.as_ref().map_or(0, |r| r.height as u16),
+        };
+        update_track(&mut self.video, track, self.init_written);
+    }
+
+    fn handle_audio_tag(
+        &mut self,
+        writer: &mut BufWriter<SinkWriter>,
+        tag: &FlvTag,
+    ) -> Result<u64, Fmp4StrategyError> {
+        if tag.get_audio_codec_id() != Some(SoundFormat::Aac) || tag.data.len() < 2 {
+            debug!("Skipping non-AAC audio tag");
+            return Ok(0);
+        }
+        let payload = tag.data.slice(2..);
+
+        if tag.is_audio_sequence_header() {
+            match aac::PartialAudioSpecificConfig::parse(&payload) {
+                Ok(config) => {
+                    let track = AudioTrack {
+                        config: payload,
+                        sample_rate: config.sampling_frequency,
+                        channels: u16::from(config.channel_configuration),
+                    };
+                    update_track(&mut self.audio, track, self.init_written);
+                }
+                Err(e) => warn!(error = %e, "Invalid AAC sequence header"),
+            }
+            return Ok(0);
+        }
+
+        if self.audio.is_none() {
+            debug!("Skipping audio frame before the audio sequence header");
+            return Ok(0);
+        }
+
+        let mut bytes_written = 0;
+        // Without video there are no keyframes, cut fragments by length instead
+        if self.video.is_none()
+            && let Some(first) = self.audio_samples.first()
+            && tag.timestamp_ms.saturating_sub(first.timestamp) >= AUDIO_ONLY_FRAGMENT_MS
+        {
+            bytes_written += self.flush_fragment(writer, None)?;
+        }
+        bytes_written += self.ensure_init_segment(writer)?;
+        self.push_sample(
+            false,
+            PendingSample {
+                timestamp: tag.timestamp_ms,
+                composition_offset: 0,
+                keyframe: true,
+                data: payload,
+            },
+        );
+        Ok(bytes_written)
+    }
+
+    fn ensure_init_segment(
+        &mut self,
+        writer: &mut BufWriter<SinkWriter>,
+    ) -> Result<u64, Fmp4StrategyError> {
+        if self.init_written {
+            return Ok(0);
+        }
+        let init = init_segment(self.video.as_ref(), self.audio.as_ref());
+        writer.write_all(&init)?;
+        self.init_written = true;
+        Ok(init.len() as u64)
+    }
+
+    fn push_sample(&mut self, is_video: bool, sample: PendingSample) {
+        self.base_timestamp.get_or_insert(sample.timestamp);
+        self.last_timestamp = self.last_timestamp.max(sample.timestamp);
+        if is_video {
+            self.video_samples.push(sample);
+        } else {
+            self.audio_samples.push(sample);
+        }
+    }
+
+    /// Write the pending samples as a fragment.
+    ///
+    /// `next_video_timestamp` is the timestamp of the video frame following the fragment,
+    /// which times its last video sample.
+    fn flush_fragment(
+        &mut self,
+        writer: &mut BufWriter<SinkWriter>,
+        next_video_timestamp: Option<u32>,
+    ) -> Result<u64, Fmp4StrategyError> {
+        if self.video_samples.is_empty() && self.audio_samples.is_empty() {
+            return Ok(0);
+        }
+        let base = self.base_timestamp.unwrap_or(0);
+
+        let video_next = next_video_timestamp
+            .or_else(|| {
+                self.video_samples
+                    .last()
+                    .map(|last| last.timestamp + self.last_video_duration)
+            })
+            .unwrap_or(0);
+        let video_samples = to_samples(&self.video_samples, video_next);
+        if let Some(last) = video_samples.last() {
+            self.last_video_duration = last.duration;
+        }
+
+        let audio_frame_duration = self.audio.as_ref().map_or(0, |audio| {
+            AAC_FRAME_SAMPLES * 1000 / audio.sample_rate.max(1)
+        });
+        let audio_next = self
+            .audio_samples
+            .last()
+            .map_or(0, |last| last.timestamp + audio_frame_duration);
+        let audio_samples = to_samples(&self.audio_samples, audio_next);
+
+        let mut tracks = Vec::with_capacity(2);
+        if let Some(first) = self.video_samples.first() {
+            tracks.push(TrackFragment {
+                track_id: VIDEO_TRACK_ID,
+                base_decode_time: u64::from(first.timestamp.saturating_sub(base)),
+                samples: &video_samples,
+            });
+        }
+        if let Some(first) = self.audio_samples.first() {
+            tracks.push(TrackFragment {
+                track_id: AUDIO_TRACK_ID,
+                base_decode_time: u64::from(first.timestamp.saturating_sub(base)),
+                samples: &audio_samples,
+            });
+        }
+
+        self.fragment_sequence += 1;
+        let fragment = media_fragment(self.fragment_sequence, &tracks);
+        writer.write_all(&fragment)?;
+
+        self.video_samples.clear();
+        self.audio_samples.clear();
+        Ok(fragment.len() as u64)
+    }
+}
+
+/// Replace the configuration of a track.
+///
+/// A changed configuration can only take effect in the init segment of the next file,
+/// which the pipeline starts on codec changes.
+fn update_track<T: PartialEq>(current: &mut Option<T>, new: T, init_written: bool) {
+    if init_written && current.as_ref().is_some_and(|current| *current != new) {
+        warn!("Codec configuration changed within a file, keeping the previous one");
+        return;
+    }
+    *current = Some(new);
+}
+
+/// Samples timed by the difference of consecutive timestamps, the last one ending at `end`.
+fn to_samples(pending: &[PendingSample], end: u32) -> Vec<Sample> {
+    pending
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| {
+            let next = pending.get(i + 1).map_or(end, |next| next.timestamp);
+            Sample {
+                duration: next.saturating_sub(sample.timestamp),
+                composition_offset: sample.composition_offset,
+                keyframe: sample.keyframe,
+                data: sample.data.clone(),
+            }
+        })
+        .collect()
+}
+
+impl FormatStrategy<FlvData> for Fmp4FormatStrategy {
+    type Writer = BufWriter<SinkWriter>;
+    type StrategyError = Fmp4StrategyError;
+
+    fn create_writer(
+        &self,
+        path: &Path,
+        config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
+        let output = config.open_output(path)?;
+        Ok(BufWriter::with_capacity(1024 * 1024, output))
+    }
+
+    fn write_item(
+        &mut self,
+        writer: &mut Self::Writer,
+        item: &FlvData,
+    ) -> Result<u64, Self::StrategyError> {
+        match item {
+            FlvData::Header(_) => {
+                self.last_header_received = true;
+                Ok(0)
+            }
+            FlvData::Tag(tag) => {
+                self.last_header_received = false;
+                self.current_tag_count += 1;
+
+                if tag.is_video_tag() {
+                    self.handle_video_tag(writer, tag)
+                } else if tag.is_audio_tag() {
+                    self.handle_audio_tag(writer, tag)
+                } else {
+                    // Script data has no counterpart in the fragments
+                    Ok(0)
+                }
+            }
+            FlvData::Split(reason) => {
+                self.last_split_reason = Some(reason.clone());
+                Ok(0)
+            }
+            FlvData::EndOfSequence(_) => {
+                debug!("Received EndOfSequence, stream ending");
+                Ok(0)
+            }
+        }
+    }
+
+    fn should_rotate_file(&self, _config: &WriterConfig, _state: &WriterState) -> bool {
+        // Like the FLV writer, start a new file on every header following tags
+        self.last_header_received && self.current_tag_count > 0
+    }
+
+    fn next_file_path(&self, config: &WriterConfig, state: &WriterState) -> PathBuf {
+        let file_name = expand_filename_template_with(
+            &config.file_name_template,
+            Some(state.file_sequence_number),
+            &self.stream_metadata(),
+        );
+        config
+            .base_path
+            .join(format!("{file_name}.{}", config.file_extension))
+    }
+
+    fn resolved_file_path(
+        &mut self,
+        config: &WriterConfig,
+        state: &WriterState,
+    ) -> Option<PathBuf> {
+        // The tracks are settled once the init segment is written
+        if !self.init_written || self.fragment_sequence > 0 || self.current_tag_count == 0 {
+            return None;
+        }
+        if !template_uses_stream_metadata(&config.file_name_template) {
+            return None;
+        }
+        let path = self.next_file_path(config, state);
+        (path != state.current_path).then_some(path)
+    }
+
+    fn on_file_open(
+        &mut self,
+        _writer: &mut Self::Writer,
+        path: &Path,
+        _config: &WriterConfig,
+        _state: &WriterState,
+    ) -> Result<u64, Self::StrategyError> {
+        self.init_written = false;
+        self.video_samples.clear();
+        self.audio_samples.clear();
+        self.base_timestamp = None;
+        self.last_timestamp = 0;
+        self.fragment_sequence = 0;
+        self.last_header_received = false;
+        self.current_tag_count = 0;
+        self.last_split_reason = None;
+
+        info!(path = %path.display(), "Opening segment");
+        Ok(0)
+    }
+
+    fn on_file_close(
+        &mut self,
+        writer: &mut Self::Writer,
+        path: &Path,
+        _config: &WriterConfig,
+        _state: &WriterState,
+    ) -> Result<u64, Self::StrategyError> {
+        let bytes_written = self.flush_fragment(writer, None)?;
+        writer.flush()?;
+        writer.get_mut().finish()?;
+
+        info!(
+            path = %path.display(),
+            fragments = self.fragment_sequence,
+            duration_secs = self.current_media_duration_secs(),
+            "Closed segment"
+        );
+        Ok(bytes_written)
+    }
+
+    fn after_item_written(
+        &mut self,
+        _item: &FlvData,
+        _bytes_written: u64,
+        _state: &WriterState,
+    ) -> Result<PostWriteAction, Self::StrategyError> {
+        Ok(PostWriteAction::None)
+    }
+
+    fn current_media_duration_secs(&self) -> f64 {
+        let base = self.base_timestamp.unwrap_or(self.last_timestamp);
+        f64::from(self.last_timestamp.saturating_sub(base)) / 1000.0
+    }
+
+    fn close_context(&self) -> Option<SplitReason> {
+        self.last_split_reason.clone()
+    }
+}
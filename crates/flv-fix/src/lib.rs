@@ -15,20 +15,26 @@
 //!
 //! ## Component Overview
 //!
+//! - `adts`: Extraction of the AAC audio into raw ADTS (`.aac`) files
 //! - `analyzer`: Tools for analyzing FLV stream structure and content
 //! - `constants`: String constants to avoid repeated allocations
+//! - `fmp4`: Remuxing of FLV streams into fragmented MP4 files
 //! - `operators`: Modular pipeline operators for stream transformations
 //! - `pipeline`: Stream processing pipeline implementation
+//! - `report`: Structured analysis reports, serializable with the `serde` feature
 //! - `script_modifier`: Utilities for manipulating FLV script tags
 //! - `utils`: Helper functions and utilities
 //! - `writer`: Asynchronous FLV writing functionality
 
+mod adts;
 pub mod amf;
 mod analyzer;
 mod constants;
 mod crc32;
+mod fmp4;
 mod operators;
 mod pipeline;
+mod report;
 mod script_modifier;
 mod utils;
 pub mod writer;
@@ -37,10 +43,18 @@ mod writer_task;
 #[cfg(test)]
 pub mod test_utils;
 
+pub use adts::{AdtsFormatStrategy, AdtsStrategyError, AdtsWriter, AdtsWriterConfig};
 pub use analyzer::{AnalyzerError, FlvAnalyzer};
 pub use constants::*;
+pub use fmp4::{Fmp4FormatStrategy, Fmp4StrategyError, Fmp4Writer, Fmp4WriterConfig};
 pub use operators::*;
 pub use pipeline::*;
+#[cfg(feature = "serde")]
+pub use report::analyze_to_json;
+pub use report::{
+    AUDIO_GAP_THRESHOLD_MS, AnalysisReport, AudioParams, FileInfo, Issue, IssueKind,
+    MAX_REPORTED_ISSUES, ReportError, TagCounts, VideoParams, analyze_file, analyze_reader,
+};
 pub use script_modifier::*;
 pub use utils::*;
 
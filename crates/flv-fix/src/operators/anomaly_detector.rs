@@ -0,0 +1,301 @@
+//! # Anomaly Detector Operator
+//!
+//! Reports the anomalies of the upstream encoder found in an FLV stream, without changing
+//! the stream.
+//!
+//! ## How it Works
+//!
+//! The operator forwards every item unchanged and measures the media tags:
+//!
+//! 1. The bytes and video frames of fixed windows, giving the bitrate and frame rate
+//! 2. The interval between video keyframes
+//! 3. The distance between the last audio and video timestamps
+//!
+//! The measures are fed to an [`AnomalyDetector`], and the anomalies it reports are
+//! emitted as [`PipelineEvent::AnomalyDetected`](pipeline_common::PipelineEvent) events.
+//! The statistics start over at every FLV header. Timestamps going back or jumping by more
+//! than a window restart the current window, the jump itself is left to the timing
+//! operators.
+
+use std::sync::Arc;
+
+use flv::data::FlvData;
+use flv::tag::FlvTag;
+use pipeline_common::{
+    Anomaly, AnomalyConfig, AnomalyDetector, PipelineError, Processor, StreamerContext,
+};
+
+/// Operator reporting bitrate, keyframe interval, frame rate and drift anomalies
+pub struct AnomalyDetectorOperator {
+    context: Arc<StreamerContext>,
+    detector: AnomalyDetector,
+    /// Start of the current window
+    window_start: Option<u32>,
+    window_bytes: u64,
+    window_frames: u64,
+    last_video_ts: Option<u32>,
+    last_audio_ts: Option<u32>,
+}
+
+impl AnomalyDetectorOperator {
+    pub fn new(context: Arc<StreamerContext>, config: AnomalyConfig) -> Self {
+        Self {
+            context,
+            detector: AnomalyDetector::new(config),
+            window_start: None,
+            window_bytes: 0,
+            window_frames: 0,
+            last_video_ts: None,
+            last_audio_ts: None,
+        }
+    }
+
+    fn report(&self, anomalies: impl IntoIterator<Item = Anomaly>) {
+        for anomaly in anomalies {
+            self.context.emit(anomaly.to_event(self.name()));
+        }
+    }
+
+    /// Report the anomalies going on and start over
+    fn restart(&mut self) {
+        let anomalies = self.detector.finish();
+        self.report(anomalies);
+        self.window_start = None;
+        self.window_bytes = 0;
+        self.window_frames = 0;
+        self.last_video_ts = None;
+        self.last_audio_ts = None;
+    }
+
+    /// Close the current window if `timestamp` is past its end
+    fn advance(&mut self, timestamp: u32) {
+        let window_ms = self.detector.config().window_ms.max(1);
+        let start = *self.window_start.get_or_insert(timestamp);
+        let end = u64::from(start) + window_ms;
+        if timestamp < start {
+            self.window_start = Some(timestamp);
+        } else if u64::from(timestamp) >= end {
+            let anomalies = self.detector.window(
+                u64::from(start),
+                end,
+                self.window_bytes,
+                Some(self.window_frames),
+            );
+            self.report(anomalies);
+            self.window_start = Some(if u64::from(timestamp) < end + window_ms {
+                end as u32
+            } else {
+                timestamp
+            });
+        } else {
+            return;
+        }
+        self.window_bytes = 0;
+        self.window_frames = 0;
+    }
+
+    fn analyze(&mut self, tag: &FlvTag) {
+        let timestamp = tag.timestamp_ms;
+        self.advance(timestamp);
+        self.window_bytes += tag.data.len() as u64;
+
+        if tag.is_video_tag() {
+            if !tag.is_key_frame() {
+                self.window_frames += 1;
+            } else if tag.is_key_frame_nalu() {
+                self.window_frames += 1;
+                let anomaly = self.detector.keyframe(u64::from(timestamp));
+                self.report(anomaly);
+            }
+            self.last_video_ts = Some(timestamp);
+        } else {
+            self.last_audio_ts = Some(timestamp);
+        }
+
+        if let (Some(video), Some(audio)) = (self.last_video_ts, self.last_audio_ts) {
+            let anomaly = self
+                .detector
+                .drift(u64::from(timestamp), u64::from(video.abs_diff(audio)));
+            self.report(anomaly);
+        }
+    }
+}
+
+impl Processor<FlvData> for AnomalyDetectorOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: FlvData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        match &input {
+            FlvData::Header(_) => self.restart(),
+            FlvData::Tag(tag) if tag.is_video_tag() || tag.is_audio_tag() => self.analyze(tag),
+            _ => {}
+        }
+
+        output(input)
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        self.restart();
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "AnomalyDetectorOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_audio_tag, create_test_header, create_video_tag_with_size};
+    use pipeline_common::{CancellationToken, EventCollector, PipelineEvent, init_test_tracing};
+
+    /// A 30fps stream with a video frame of `frame_size(timestamp)` bytes every 33ms, keyframes
+    /// at the timestamps for which `keyframe` holds, and audio every 23ms
+    fn stream(
+        seconds: u32,
+        keyframe: impl Fn(u32) -> bool,
+        frame_size: impl Fn(u32) -> usize,
+    ) -> Vec<FlvData> {
+        let mut items = vec![create_test_header()];
+        let mut audio_ts = 0;
+        for frame in 0..seconds * 30 {
+            let video_ts = frame * 1000 / 30;
+            while audio_ts <= video_ts {
+                items.push(create_audio_tag(audio_ts));
+                audio_ts += 23;
+            }
+            items.push(create_video_tag_with_size(
+                video_ts,
+                keyframe(video_ts),
+                frame_size(video_ts),
+            ));
+        }
+        items
+    }
+
+    /// Runs `items` through the operator and returns the events it emitted
+    fn run(items: Vec<FlvData>) -> Vec<PipelineEvent> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let collector = Arc::new(EventCollector::default());
+        context.events.add_handler(collector.clone());
+
+        let mut operator = AnomalyDetectorOperator::new(context.clone(), AnomalyConfig::default());
+        let mut forwarded = Vec::new();
+        let mut output = |item: FlvData| -> Result<(), PipelineError> {
+            forwarded.push(item);
+            Ok(())
+        };
+        for item in items.clone() {
+            operator.process(&context, item, &mut output).unwrap();
+        }
+        operator.finish(&context, &mut output).unwrap();
+        assert_eq!(forwarded, items);
+
+        collector.events()
+    }
+
+    /// Keyframe every two seconds
+    fn gop_2s(ts: u32) -> bool {
+        ts % 2000 < 33
+    }
+
+    #[test]
+    fn test_steady_stream_is_quiet() {
+        init_test_tracing!();
+        let events = run(stream(60, gop_2s, |_| 8_000));
+        assert!(events.is_empty(), "{events:?}");
+    }
+
+    #[test]
+    fn test_bitrate_dip() {
+        init_test_tracing!();
+        // 10 seconds at a twentieth of the bitrate from 20s
+        let frame_size = |ts: u32| {
+            if (20_000..30_000).contains(&ts) {
+                400
+            } else {
+                8_000
+            }
+        };
+        let events = run(stream(60, gop_2s, frame_size));
+
+        assert_eq!(events.len(), 1, "{events:?}");
+        let PipelineEvent::AnomalyDetected {
+            operator,
+            kind,
+            offset,
+            detail,
+        } = &events[0]
+        else {
+            panic!("unexpected event {:?}", events[0]);
+        };
+        assert_eq!(*operator, "AnomalyDetectorOperator");
+        assert_eq!(*kind, "bitrate_collapse");
+        assert_eq!(*offset, Some(20_000));
+        assert!(
+            detail.starts_with("bitrate collapsed from 1") && detail.ends_with("and 30000ms"),
+            "{detail}"
+        );
+    }
+
+    #[test]
+    fn test_gop_change() {
+        init_test_tracing!();
+        // 2s GOPs, then 10s GOPs from 30s
+        let keyframe = |ts: u32| {
+            if ts < 30_000 {
+                gop_2s(ts)
+            } else {
+                ts % 10_000 < 33
+            }
+        };
+        let events = run(stream(80, keyframe, |_| 8_000));
+
+        assert_eq!(
+            events,
+            [PipelineEvent::AnomalyDetected {
+                operator: "AnomalyDetectorOperator",
+                kind: "gop_change",
+                offset: Some(30_000),
+                detail: "keyframe interval changed from 2000.0ms to 10000.0ms between 30000ms \
+                         and 60000ms"
+                    .to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_audio_dropout_is_drift() {
+        init_test_tracing!();
+        // Audio stops for four seconds from 20s
+        let items: Vec<_> = stream(40, gop_2s, |_| 8_000)
+            .into_iter()
+            .filter(|item| match item {
+                FlvData::Tag(tag) if tag.is_audio_tag() => {
+                    !(20_000..24_000).contains(&tag.timestamp_ms)
+                }
+                _ => true,
+            })
+            .collect();
+        let events = run(items);
+
+        assert_eq!(events.len(), 1, "{events:?}");
+        assert!(matches!(
+            &events[0],
+            PipelineEvent::AnomalyDetected { kind: "av_drift", offset: Some(offset), .. }
+                if (21_000..21_100).contains(offset)
+        ));
+    }
+}
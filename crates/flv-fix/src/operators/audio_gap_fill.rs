@@ -0,0 +1,496 @@
+//! # AudioGapFillOperator
+//!
+//! The `AudioGapFillOperator` fills holes in the AAC audio track with silent frames
+//! while video keeps playing.
+//!
+//! ## Purpose
+//!
+//! Some ingest sources drop audio for seconds at a time while video continues. Players
+//! that pace playback on the audio clock then stall or drift out of sync. This operator
+//! keeps the audio timeline populated by:
+//!
+//! 1. Deriving the audio frame cadence from the AAC sequence header
+//! 2. Detecting when audio falls behind video by more than a configurable threshold
+//! 3. Inserting canned silent AAC frames with correctly spaced timestamps
+//!
+//! ## Operation
+//!
+//! The operator:
+//! - Parses the `AudioSpecificConfig` of each AAC sequence header
+//! - Tracks the expected timestamp of the next audio frame (1024 samples per tag)
+//! - On each video tag, inserts silence until audio is within the threshold of video
+//! - Does not fill across video stalls, since a stall of both tracks is not an audio gap
+//! - Only supports AAC LC mono and stereo, other formats pass through untouched
+//!
+//! Silence is kept one threshold behind video so that it never overlaps real audio
+//! that resumes with the usual interleaving offset.
+//!
+//! ## License
+//!
+//! MIT License
+//!
+//! ## Authors
+//!
+//! - hua0512
+//!
+
+use aac::{AudioObjectType, PartialAudioSpecificConfig};
+use bytes::{BufMut, Bytes, BytesMut};
+use flv::data::FlvData;
+use flv::tag::{FlvTag, FlvTagType};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, info, warn};
+
+/// Samples per raw AAC frame, FLV carries one frame per tag
+const AAC_SAMPLES_PER_FRAME: f64 = 1024.0;
+
+/// Raw AAC LC frame decoding to silence, single channel element
+const AAC_LC_SILENCE_MONO: &[u8] = &[0x00, 0xc8, 0x00, 0x80, 0x23, 0x80];
+
+/// Raw AAC LC frame decoding to silence, channel pair element
+const AAC_LC_SILENCE_STEREO: &[u8] = &[0x21, 0x00, 0x49, 0x90, 0x02, 0x19, 0x00, 0x23, 0x80];
+
+/// Counters shared between the operator and the code that built the pipeline
+#[derive(Debug, Default)]
+pub struct AudioGapFillStats {
+    filled_duration_ms: AtomicU64,
+    filled_frames: AtomicU64,
+    gaps: AtomicU64,
+}
+
+impl AudioGapFillStats {
+    /// Total duration of inserted silence in milliseconds
+    pub fn filled_duration_ms(&self) -> u64 {
+        self.filled_duration_ms.load(Ordering::Relaxed)
+    }
+
+    /// Number of silent frames inserted
+    pub fn filled_frames(&self) -> u64 {
+        self.filled_frames.load(Ordering::Relaxed)
+    }
+
+    /// Number of separate audio gaps that were filled
+    pub fn gaps(&self) -> u64 {
+        self.gaps.load(Ordering::Relaxed)
+    }
+}
+
+/// Configuration options for the AudioGapFillOperator
+#[derive(Debug, Clone)]
+pub struct AudioGapFillConfig {
+    /// How far audio may fall behind video, in milliseconds, before silence is inserted.
+    /// Defaults to 300ms.
+    pub gap_threshold_ms: u32,
+
+    /// A jump between consecutive video tags larger than this, in milliseconds, is
+    /// treated as a stream stall and is not filled.
+    /// Defaults to 1 second.
+    pub max_video_gap_ms: u32,
+
+    /// Statistics updated by the operator.
+    /// Keep a clone of the `Arc` to read them while or after the pipeline runs.
+    pub stats: Arc<AudioGapFillStats>,
+}
+
+impl Default for AudioGapFillConfig {
+    fn default() -> Self {
+        Self {
+            gap_threshold_ms: 300,
+            max_video_gap_ms: 1000,
+            stats: Arc::new(AudioGapFillStats::default()),
+        }
+    }
+}
+
+/// Audio timing state, reset at every FLV header
+#[derive(Default)]
+struct GapState {
+    /// Tag payload of a silent frame for the current AAC config
+    silent_payload: Option<Bytes>,
+
+    /// Duration of one AAC frame in milliseconds
+    frame_duration_ms: f64,
+
+    /// Expected timestamp of the next audio frame
+    next_audio_ts: Option<f64>,
+
+    /// Last video timestamp seen
+    last_video_ts: Option<u32>,
+
+    /// Whether the previous video tag already triggered filling
+    filling: bool,
+}
+
+/// Operator that inserts silent AAC frames into audio dropouts
+pub struct AudioGapFillOperator {
+    context: Arc<StreamerContext>,
+    config: AudioGapFillConfig,
+    state: GapState,
+    /// Unrounded total of inserted silence, mirrored into the stats
+    filled_ms: f64,
+}
+
+impl AudioGapFillOperator {
+    /// Create a new AudioGapFillOperator with the specified configuration
+    pub fn new(context: Arc<StreamerContext>, config: AudioGapFillConfig) -> Self {
+        Self {
+            context,
+            config,
+            state: GapState::default(),
+            filled_ms: 0.0,
+        }
+    }
+
+    /// Returns a raw AAC frame that decodes to silence for the given config
+    fn silent_frame(config: &PartialAudioSpecificConfig) -> Option<&'static [u8]> {
+        if config.audio_object_type != AudioObjectType::AacLowComplexity {
+            return None;
+        }
+        match config.channel_configuration {
+            1 => Some(AAC_LC_SILENCE_MONO),
+            2 => Some(AAC_LC_SILENCE_STEREO),
+            _ => None,
+        }
+    }
+
+    fn handle_audio_sequence_header(&mut self, tag: &FlvTag) {
+        self.state.silent_payload = None;
+
+        let is_aac = tag.data.first().is_some_and(|b| b >> 4 == 10);
+        let config = match tag.data.get(2..) {
+            Some(asc) if is_aac => PartialAudioSpecificConfig::parse(asc).ok(),
+            _ => None,
+        };
+        let Some(config) = config.filter(|c| c.sampling_frequency > 0) else {
+            debug!(
+                "{} Audio is not AAC or has an unreadable config, gap filling disabled",
+                self.context.name
+            );
+            return;
+        };
+
+        let Some(frame) = Self::silent_frame(&config) else {
+            warn!(
+                "{} No silent frame for {:?} with {} channels, gap filling disabled",
+                self.context.name, config.audio_object_type, config.channel_configuration
+            );
+            return;
+        };
+
+        let mut payload = BytesMut::with_capacity(2 + frame.len());
+        payload.put_u8(tag.data[0]);
+        payload.put_u8(1); // AAC raw
+        payload.put_slice(frame);
+
+        self.state.silent_payload = Some(payload.freeze());
+        self.state.frame_duration_ms =
+            AAC_SAMPLES_PER_FRAME * 1000.0 / config.sampling_frequency as f64;
+        debug!(
+            "{} AAC {} Hz, {} channels: frame duration {:.2}ms",
+            self.context.name,
+            config.sampling_frequency,
+            config.channel_configuration,
+            self.state.frame_duration_ms
+        );
+    }
+
+    /// Emit silence until audio is within the threshold of `video_ts`
+    fn fill_until(
+        &mut self,
+        video_ts: u32,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        let (Some(payload), Some(mut next)) =
+            (self.state.silent_payload.clone(), self.state.next_audio_ts)
+        else {
+            return Ok(());
+        };
+
+        let threshold = self.config.gap_threshold_ms as f64;
+        if video_ts as f64 - next <= threshold {
+            self.state.filling = false;
+            return Ok(());
+        }
+
+        if !self.state.filling {
+            self.state.filling = true;
+            self.config.stats.gaps.fetch_add(1, Ordering::Relaxed);
+            self.context.emit(PipelineEvent::RepairApplied {
+                operator: self.name(),
+                kind: "audio_gap",
+                offset: Some(next.round() as u64),
+                detail: format!(
+                    "Audio missing since {next:.0}ms while video is at {video_ts}ms, \
+                     inserting silence"
+                ),
+            });
+        }
+
+        let mut frames = 0u64;
+        while video_ts as f64 - next > threshold {
+            output(FlvData::Tag(FlvTag {
+                timestamp_ms: next.round() as u32,
+                stream_id: 0,
+                tag_type: FlvTagType::Audio,
+                is_filtered: false,
+                data: payload.clone(),
+            }))?;
+            next += self.state.frame_duration_ms;
+            frames += 1;
+        }
+
+        self.state.next_audio_ts = Some(next);
+        self.filled_ms += frames as f64 * self.state.frame_duration_ms;
+        let stats = &self.config.stats;
+        stats.filled_frames.fetch_add(frames, Ordering::Relaxed);
+        stats
+            .filled_duration_ms
+            .store(self.filled_ms.round() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Processor<FlvData> for AudioGapFillOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: FlvData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+        match input {
+            FlvData::Header(_) => {
+                self.state = GapState::default();
+                output(input)
+            }
+            FlvData::Tag(tag) if tag.is_audio_sequence_header() => {
+                self.handle_audio_sequence_header(&tag);
+                output(FlvData::Tag(tag))
+            }
+            FlvData::Tag(tag) if tag.is_audio_tag() => {
+                if self.state.filling {
+                    debug!(
+                        "{} Audio resumed at {}ms",
+                        self.context.name, tag.timestamp_ms
+                    );
+                    self.state.filling = false;
+                }
+                self.state.next_audio_ts =
+                    Some(tag.timestamp_ms as f64 + self.state.frame_duration_ms);
+                output(FlvData::Tag(tag))
+            }
+            FlvData::Tag(tag) if tag.is_video_tag() => {
+                let video_ts = tag.timestamp_ms;
+                if let Some(last) = self.state.last_video_ts
+                    && video_ts > last.saturating_add(self.config.max_video_gap_ms)
+                {
+                    // Both tracks stalled: restart the audio expectation at the resume point
+                    debug!(
+                        "{} Video stalled {}ms -> {}ms, not filling audio",
+                        self.context.name, last, video_ts
+                    );
+                    self.state.filling = false;
+                    if let Some(next) = self.state.next_audio_ts.as_mut() {
+                        *next = next.max(video_ts as f64);
+                    }
+                }
+                self.state.last_video_ts = Some(video_ts);
+
+                self.fill_until(video_ts, output)?;
+                output(FlvData::Tag(tag))
+            }
+            _ => output(input),
+        }
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        let stats = &self.config.stats;
+        info!(
+            "{} Audio gap filler completed: {} gaps, {} silent frames, {}ms filled",
+            self.context.name,
+            stats.gaps(),
+            stats.filled_frames(),
+            stats.filled_duration_ms()
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "AudioGapFillOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        create_audio_sequence_header, create_audio_tag, create_test_header, create_video_tag,
+    };
+    use pipeline_common::{CancellationToken, init_test_tracing};
+
+    /// 44.1kHz stereo AAC LC
+    const ASC_44100_STEREO: u8 = 0x12;
+
+    /// Interleaved 25fps video and 44.1kHz audio from 0 to `end_ms`, skipping audio
+    /// inside `audio_hole` and everything inside `stall`
+    fn stream(end_ms: u32, audio_hole: (u32, u32), stall: (u32, u32)) -> Vec<FlvData> {
+        let in_range = |ts: u32, (start, end): (u32, u32)| ts >= start && ts < end;
+        let mut tags = Vec::new();
+
+        for i in 0.. {
+            let ts = (i as f64 * 1024.0 * 1000.0 / 44100.0).round() as u32;
+            if ts >= end_ms {
+                break;
+            }
+            if !in_range(ts, audio_hole) && !in_range(ts, stall) {
+                tags.push(create_audio_tag(ts));
+            }
+        }
+        for ts in (0..end_ms).step_by(40) {
+            if !in_range(ts, stall) {
+                tags.push(create_video_tag(ts, ts % 2000 == 0));
+            }
+        }
+        tags.sort_by_key(|item| match item {
+            FlvData::Tag(tag) => tag.timestamp_ms,
+            _ => 0,
+        });
+
+        let mut input = vec![
+            create_test_header(),
+            create_audio_sequence_header(0, ASC_44100_STEREO),
+        ];
+        input.extend(tags);
+        input
+    }
+
+    fn run(config: AudioGapFillConfig, input: Vec<FlvData>) -> Vec<FlvTag> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = AudioGapFillOperator::new(context.clone(), config);
+        let mut output_items = Vec::new();
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        for item in input {
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+        operator.finish(&context, &mut output_fn).unwrap();
+
+        output_items
+            .into_iter()
+            .filter_map(|item| match item {
+                FlvData::Tag(tag) => Some(tag),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn is_silence(tag: &FlvTag) -> bool {
+        tag.is_audio_tag() && tag.data.get(2..) == Some(AAC_LC_SILENCE_STEREO)
+    }
+
+    #[test]
+    fn test_fills_three_second_audio_hole() {
+        init_test_tracing!();
+        let config = AudioGapFillConfig::default();
+        let stats = config.stats.clone();
+        let output = run(config, stream(6000, (1000, 4000), (0, 0)));
+
+        let silent: Vec<u32> = output
+            .iter()
+            .filter(|tag| is_silence(tag))
+            .map(|tag| tag.timestamp_ms)
+            .collect();
+
+        // Silence starts where audio stopped and stays clear of the resumed audio
+        assert!(!silent.is_empty());
+        assert!(silent[0] >= 1000 && silent[0] < 1030, "{silent:?}");
+        assert!(*silent.last().unwrap() < 4000);
+        for pair in silent.windows(2) {
+            let delta = pair[1] - pair[0];
+            assert!(delta == 23 || delta == 24, "uneven spacing {pair:?}");
+        }
+
+        // Audio timestamps never go backwards
+        let audio: Vec<u32> = output
+            .iter()
+            .filter(|tag| tag.is_audio_tag() && !tag.is_audio_sequence_header())
+            .map(|tag| tag.timestamp_ms)
+            .collect();
+        assert!(audio.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // Everything except the last threshold of the hole is filled
+        assert_eq!(stats.gaps(), 1);
+        assert_eq!(stats.filled_frames(), silent.len() as u64);
+        let filled = stats.filled_duration_ms();
+        assert!((2600..=3000).contains(&filled), "filled {filled}ms");
+    }
+
+    #[test]
+    fn test_no_fill_on_full_stall() {
+        init_test_tracing!();
+        let config = AudioGapFillConfig::default();
+        let stats = config.stats.clone();
+        let output = run(config, stream(6000, (0, 0), (1000, 4000)));
+
+        assert!(!output.iter().any(is_silence));
+        assert_eq!(stats.filled_duration_ms(), 0);
+        assert_eq!(stats.gaps(), 0);
+    }
+
+    #[test]
+    fn test_no_fill_on_jitter() {
+        init_test_tracing!();
+        let config = AudioGapFillConfig::default();
+        let stats = config.stats.clone();
+
+        // Audio arrives in bursts that lag video by up to 200ms
+        let mut input = vec![
+            create_test_header(),
+            create_audio_sequence_header(0, ASC_44100_STEREO),
+        ];
+        let mut audio_ts = 0u32;
+        for video_ts in (0..4000).step_by(40) {
+            input.push(create_video_tag(video_ts, video_ts == 0));
+            if video_ts % 200 == 0 {
+                while audio_ts <= video_ts {
+                    input.push(create_audio_tag(audio_ts));
+                    audio_ts += 23;
+                }
+            }
+        }
+
+        let output = run(config, input);
+        assert!(!output.iter().any(is_silence));
+        assert_eq!(stats.filled_frames(), 0);
+    }
+
+    #[test]
+    fn test_unsupported_config_passes_through() {
+        init_test_tracing!();
+        let config = AudioGapFillConfig::default();
+        let stats = config.stats.clone();
+
+        // AAC LC 44.1kHz with 6 channels
+        let mut input = vec![
+            create_test_header(),
+            crate::test_utils::create_test_tag(FlvTagType::Audio, 0, vec![0xAF, 0x00, 0x12, 0x30]),
+            create_audio_tag(0),
+        ];
+        input.extend((0..100).map(|i| create_video_tag(i * 40, i == 0)));
+
+        let output = run(config, input);
+        assert_eq!(output.len(), 102);
+        assert_eq!(stats.filled_frames(), 0);
+    }
+}
@@ -0,0 +1,507 @@
+//! # ClipOperator
+//!
+//! The `ClipOperator` keeps only the part of a stream between a start and an end time,
+//! e.g. to cut 00:05:00 to 00:35:00 out of a recording while fixing it.
+//!
+//! ## Operation
+//!
+//! Start and end times are on the timeline of the source stream, as the operator runs
+//! before any timestamp repair. Until the start point it drops media tags but remembers
+//! the latest FLV header, onMetaData tag and audio/video sequence headers, which it emits
+//! again at the start of the clip so the output is decodable on its own.
+//!
+//! Video starts at a keyframe: the last one at or before the start point, or the first one
+//! after it, depending on [`ClipStartMode`]. Audio older than that keyframe is dropped, so
+//! both tracks start within one frame of each other. Timestamps are rebased so the clip
+//! starts at zero.
+//!
+//! At the end point the operator drops the rest of the stream and reports itself finished,
+//! which ends the pipeline run: downstream operators flush their buffers and the writer
+//! closes its file.
+//!
+//! The operator runs right after the header check, so the continuity, timing repair and
+//! limit operators only ever see the clip, with timestamps starting at zero.
+//!
+//! ## License
+//!
+//! MIT License
+//!
+//! ## Authors
+//!
+//! - hua0512
+//!
+
+use flv::data::FlvData;
+use flv::tag::{FlvTag, FlvTagType};
+use pipeline_common::{PipelineError, Processor, StreamerContext};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+/// Where video starts relative to the requested start point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClipStartMode {
+    /// Start at the last keyframe at or before the start point, so the start point is
+    /// included in the clip.
+    #[default]
+    KeyframeBefore,
+    /// Start at the first keyframe at or after the start point, so nothing before the
+    /// start point is included in the clip.
+    KeyframeAfter,
+}
+
+/// Configuration options for the ClipOperator
+#[derive(Debug, Clone, Default)]
+pub struct ClipConfig {
+    /// Start of the clip in milliseconds (None = from the beginning)
+    pub start_ms: Option<u32>,
+
+    /// End of the clip in milliseconds (None = to the end of the stream)
+    pub end_ms: Option<u32>,
+
+    /// Keyframe the clip starts at
+    pub start_mode: ClipStartMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClipState {
+    /// Before the start point
+    Waiting,
+    /// Within the clip, with the source timestamp rebased to zero
+    Clipping { base_ms: u32 },
+    /// Past the end point
+    Ended,
+}
+
+/// Operator that cuts a stream to a time range
+pub struct ClipOperator {
+    context: Arc<StreamerContext>,
+    config: ClipConfig,
+    state: ClipState,
+    header: Option<FlvData>,
+    metadata: Option<FlvTag>,
+    video_sequence_header: Option<FlvTag>,
+    audio_sequence_header: Option<FlvTag>,
+    /// Tags since the last keyframe before the start point, with `ClipStartMode::KeyframeBefore`
+    gop: Vec<FlvTag>,
+    /// Audio tags after the start point, waiting for the first keyframe
+    early_audio: Vec<FlvTag>,
+    has_video: bool,
+    dropped_count: u64,
+}
+
+impl ClipOperator {
+    /// Create a new ClipOperator with the specified configuration
+    pub fn new(context: Arc<StreamerContext>, config: ClipConfig) -> Self {
+        Self {
+            context,
+            config,
+            state: ClipState::Waiting,
+            header: None,
+            metadata: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+            gop: Vec::new(),
+            early_audio: Vec::new(),
+            has_video: false,
+            dropped_count: 0,
+        }
+    }
+
+    fn drop_tags(&mut self, count: u64) {
+        self.dropped_count += count;
+        self.context.stats.record_dropped(count);
+    }
+
+    fn is_media_tag(tag: &FlvTag) -> bool {
+        matches!(tag.tag_type, FlvTagType::Audio | FlvTagType::Video)
+            && !tag.is_video_sequence_header()
+            && !tag.is_audio_sequence_header()
+    }
+
+    fn is_past_end(&self, tag: &FlvTag) -> bool {
+        self.config
+            .end_ms
+            .is_some_and(|end_ms| Self::is_media_tag(tag) && tag.timestamp_ms >= end_ms)
+    }
+
+    fn end(&mut self, tag: &FlvTag) {
+        info!(
+            "{} Clip end reached at {}ms, ending the stream",
+            self.context.name, tag.timestamp_ms
+        );
+        self.state = ClipState::Ended;
+        self.drop_tags(self.gop.len() as u64 + self.early_audio.len() as u64 + 1);
+        self.gop.clear();
+        self.early_audio.clear();
+    }
+
+    /// Handle a tag before the start point
+    fn process_waiting(
+        &mut self,
+        tag: FlvTag,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if tag.is_script_tag() {
+            if tag
+                .decode_script()
+                .is_ok_and(|script| script.name == crate::AMF0_ON_METADATA)
+            {
+                self.metadata = Some(tag);
+            }
+            return Ok(());
+        }
+        if tag.is_video_sequence_header() {
+            self.has_video = true;
+            self.video_sequence_header = Some(tag);
+            return Ok(());
+        }
+        if tag.is_audio_sequence_header() {
+            self.audio_sequence_header = Some(tag);
+            return Ok(());
+        }
+        if self.is_past_end(&tag) {
+            self.end(&tag);
+            return Ok(());
+        }
+
+        let start_ms = self.config.start_ms.unwrap_or(0);
+        let before_start = tag.timestamp_ms < start_ms;
+        let keep_gop = self.config.start_mode == ClipStartMode::KeyframeBefore;
+        let is_video = tag.is_video_tag();
+        self.has_video |= is_video;
+
+        if before_start {
+            if is_video && tag.is_key_frame() && keep_gop {
+                self.drop_tags(self.gop.len() as u64);
+                self.gop.clear();
+                self.gop.push(tag);
+            } else if !self.gop.is_empty() {
+                self.gop.push(tag);
+            } else {
+                self.drop_tags(1);
+            }
+            return Ok(());
+        }
+
+        if is_video && tag.is_key_frame() && (tag.timestamp_ms == start_ms || self.gop.is_empty()) {
+            self.drop_tags(self.gop.len() as u64);
+            self.gop.clear();
+            self.start(tag.timestamp_ms, output)?;
+            return self.process_clipping(tag, output);
+        }
+        if !self.gop.is_empty() {
+            // The GOP in progress contains the start point
+            let base_ms = self.gop[0].timestamp_ms;
+            self.start(base_ms, output)?;
+            return self.process_clipping(tag, output);
+        }
+        if !self.has_video {
+            self.start(tag.timestamp_ms, output)?;
+            return self.process_clipping(tag, output);
+        }
+
+        // Waiting for the first keyframe. Audio may still belong to the clip if the keyframe
+        // follows it with the same timestamp, but not once older than a video frame.
+        if is_video {
+            self.drop_tags(self.early_audio.len() as u64 + 1);
+            self.early_audio.clear();
+        } else {
+            self.early_audio.push(tag);
+        }
+        Ok(())
+    }
+
+    /// Emit the stream headers and the buffered GOP, starting the clip at `base_ms`
+    fn start(
+        &mut self,
+        base_ms: u32,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        info!(
+            "{} Clip starts at {}ms (requested {}ms)",
+            self.context.name,
+            base_ms,
+            self.config.start_ms.unwrap_or(0)
+        );
+        self.state = ClipState::Clipping { base_ms };
+
+        if let Some(header) = self.header.take() {
+            output(header)?;
+        }
+        let headers = [
+            self.metadata.take(),
+            self.video_sequence_header.take(),
+            self.audio_sequence_header.take(),
+        ];
+        for mut tag in headers.into_iter().flatten() {
+            tag.timestamp_ms = 0;
+            output(FlvData::Tag(tag))?;
+        }
+
+        let gop = std::mem::take(&mut self.gop);
+        let early_audio = std::mem::take(&mut self.early_audio);
+        for tag in gop.into_iter().chain(early_audio) {
+            self.process_clipping(tag, output)?;
+        }
+        Ok(())
+    }
+
+    /// Handle a tag within the clip
+    fn process_clipping(
+        &mut self,
+        mut tag: FlvTag,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        let ClipState::Clipping { base_ms } = self.state else {
+            return Ok(());
+        };
+        if self.is_past_end(&tag) {
+            self.end(&tag);
+            return Ok(());
+        }
+        // Keep audio from starting before the first video frame
+        if Self::is_media_tag(&tag) && tag.timestamp_ms < base_ms {
+            debug!(
+                "{} Dropping {:?} tag at {}ms before the clip start",
+                self.context.name, tag.tag_type, tag.timestamp_ms
+            );
+            self.drop_tags(1);
+            return Ok(());
+        }
+
+        tag.timestamp_ms = tag.timestamp_ms.saturating_sub(base_ms);
+        output(FlvData::Tag(tag))
+    }
+}
+
+impl Processor<FlvData> for ClipOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: FlvData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+        match (self.state, input) {
+            (ClipState::Ended, _) => {
+                self.drop_tags(1);
+                Ok(())
+            }
+            (ClipState::Waiting, FlvData::Header(header)) => {
+                // A new segment of the source starts over with its own GOP
+                self.drop_tags(self.gop.len() as u64 + self.early_audio.len() as u64);
+                self.gop.clear();
+                self.early_audio.clear();
+                self.header = Some(FlvData::Header(header));
+                Ok(())
+            }
+            (ClipState::Waiting, FlvData::Tag(tag)) => self.process_waiting(tag, output),
+            // Split and end markers before the clip concern dropped tags
+            (ClipState::Waiting, _) => Ok(()),
+            (ClipState::Clipping { .. }, FlvData::Tag(tag)) => self.process_clipping(tag, output),
+            (ClipState::Clipping { .. }, input) => output(input),
+        }
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if self.state == ClipState::Waiting {
+            warn!(
+                "{} Stream ended before the clip start at {}ms, nothing was emitted",
+                self.context.name,
+                self.config.start_ms.unwrap_or(0)
+            );
+        }
+        info!(
+            "{} Clip operator completed: {} tags dropped",
+            self.context.name, self.dropped_count
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ClipOperator"
+    }
+
+    fn is_finished(&self) -> bool {
+        self.state == ClipState::Ended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        create_audio_sequence_header, create_audio_tag, create_script_tag, create_test_header,
+        create_video_sequence_header, create_video_tag,
+    };
+    use pipeline_common::{CancellationToken, init_test_tracing};
+
+    /// Three seconds of stream with a keyframe every second, 30fps video and AAC audio
+    fn create_stream() -> Vec<FlvData> {
+        let mut items = vec![
+            create_test_header(),
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ];
+        let mut audio_ts = 0;
+        for frame in 0..90u32 {
+            let video_ts = frame * 1000 / 30;
+            while audio_ts <= video_ts {
+                items.push(create_audio_tag(audio_ts));
+                audio_ts += 23;
+            }
+            items.push(create_video_tag(video_ts, frame % 30 == 0));
+        }
+        items
+    }
+
+    fn run(config: ClipConfig, input: Vec<FlvData>) -> (Vec<FlvData>, bool) {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = ClipOperator::new(context.clone(), config);
+        let mut output_items = Vec::new();
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        for item in input {
+            operator.process(&context, item, &mut output_fn).unwrap();
+            if operator.is_finished() {
+                break;
+            }
+        }
+        let finished = operator.is_finished();
+        operator.finish(&context, &mut output_fn).unwrap();
+        (output_items, finished)
+    }
+
+    fn media_tags(items: &[FlvData]) -> Vec<&FlvTag> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                FlvData::Tag(tag) if ClipOperator::is_media_tag(tag) => Some(tag),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn assert_clip_start(items: &[FlvData]) {
+        assert!(matches!(items[0], FlvData::Header(_)));
+        let FlvData::Tag(metadata) = &items[1] else {
+            panic!("expected the metadata tag");
+        };
+        assert!(metadata.is_script_tag());
+        assert!(matches!(&items[2], FlvData::Tag(tag) if tag.is_video_sequence_header()));
+        assert!(matches!(&items[3], FlvData::Tag(tag) if tag.is_audio_sequence_header()));
+
+        let media = media_tags(items);
+        let first_video = media.iter().find(|tag| tag.is_video_tag()).unwrap();
+        let first_audio = media.iter().find(|tag| tag.is_audio_tag()).unwrap();
+        assert!(first_video.is_key_frame());
+        assert_eq!(first_video.timestamp_ms, 0);
+        // Audio starts within one video frame of video
+        assert!(first_audio.timestamp_ms <= 34);
+    }
+
+    #[test]
+    fn test_starts_at_keyframe_before_start() {
+        init_test_tracing!();
+        let (output, finished) = run(
+            ClipConfig {
+                start_ms: Some(1500),
+                ..ClipConfig::default()
+            },
+            create_stream(),
+        );
+
+        assert!(!finished);
+        assert_clip_start(&output);
+        // The clip starts at the keyframe at 1000ms
+        let video: Vec<_> = media_tags(&output)
+            .into_iter()
+            .filter(|tag| tag.is_video_tag())
+            .collect();
+        assert_eq!(video.len(), 60);
+    }
+
+    #[test]
+    fn test_starts_at_keyframe_after_start() {
+        init_test_tracing!();
+        let (output, _) = run(
+            ClipConfig {
+                start_ms: Some(1500),
+                start_mode: ClipStartMode::KeyframeAfter,
+                ..ClipConfig::default()
+            },
+            create_stream(),
+        );
+
+        assert_clip_start(&output);
+        // The clip starts at the keyframe at 2000ms
+        let video: Vec<_> = media_tags(&output)
+            .into_iter()
+            .filter(|tag| tag.is_video_tag())
+            .collect();
+        assert_eq!(video.len(), 30);
+    }
+
+    #[test]
+    fn test_start_on_keyframe_keeps_it() {
+        init_test_tracing!();
+        let (output, _) = run(
+            ClipConfig {
+                start_ms: Some(1000),
+                ..ClipConfig::default()
+            },
+            create_stream(),
+        );
+
+        assert_clip_start(&output);
+        assert_eq!(
+            media_tags(&output)
+                .iter()
+                .filter(|tag| tag.is_video_tag())
+                .count(),
+            60
+        );
+    }
+
+    #[test]
+    fn test_end_finishes_the_stream() {
+        init_test_tracing!();
+        let (output, finished) = run(
+            ClipConfig {
+                start_ms: Some(1000),
+                end_ms: Some(2500),
+                ..ClipConfig::default()
+            },
+            create_stream(),
+        );
+
+        assert!(finished);
+        assert_clip_start(&output);
+        let media = media_tags(&output);
+        // Everything before 2500ms of the source, rebased to the keyframe at 1000ms
+        assert!(media.iter().all(|tag| tag.timestamp_ms < 1500));
+        assert_eq!(media.iter().filter(|tag| tag.is_video_tag()).count(), 45);
+    }
+
+    #[test]
+    fn test_without_start_passes_the_stream_through() {
+        init_test_tracing!();
+        let input = create_stream();
+        let input_len = input.len();
+        let (output, finished) = run(ClipConfig::default(), input);
+
+        assert!(!finished);
+        assert_eq!(output.len(), input_len);
+        assert_clip_start(&output);
+    }
+}
@@ -34,9 +34,9 @@
 //! - hua0512
 //!
 use flv::data::FlvData;
-use pipeline_common::{PipelineError, Processor, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
 use std::sync::Arc;
-use tracing::{debug, warn};
+use tracing::debug;
 
 /// An operator that buffers and validates FLV stream fragments to ensure continuity and validity.
 ///
@@ -74,15 +74,25 @@ impl DefragmentOperator {
         self.buffer.clear();
     }
 
+    // Report the buffered items as discarded
+    fn report_discarded(&self, detail: String) {
+        self.context.emit(PipelineEvent::AnomalyDetected {
+            operator: self.name(),
+            kind: "fragment_discarded",
+            offset: None,
+            detail,
+        });
+        self.context.stats.record_dropped(self.buffer.len() as u64);
+    }
+
     // Handle a new header detection
     fn handle_new_header(&mut self) {
         if !self.buffer.is_empty() {
-            warn!(
-                "{} Discarded {} items, total size: {}",
-                self.context.name,
+            self.report_discarded(format!(
+                "Discarded {} items, total size: {}",
                 self.buffer.len(),
                 self.buffer.iter().map(|d| d.size()).sum::<usize>()
-            );
+            ));
             self.reset();
         }
         self.is_gathering = true;
@@ -159,11 +169,10 @@ impl Processor<FlvData> for DefragmentOperator {
             if self.buffer.len() >= Self::MIN_TAGS_NUM {
                 self.emit_buffer(output)?;
             } else {
-                warn!(
-                    "{} End of stream with only {} items in buffer, discarding",
-                    self.context.name,
+                self.report_discarded(format!(
+                    "End of stream with only {} items in buffer, discarding",
                     self.buffer.len()
-                );
+                ));
                 self.reset();
             }
         }
@@ -7,10 +7,14 @@
 //! identical FLV tags (often with repeated timestamps).
 //!
 //! This operator performs a conservative deduplication:
-//! - Only applies to audio/video *media* tags (script tags and sequence headers
-//!   are passed through).
-//! - Considers a tag duplicate if `(tag_type, timestamp_ms, crc32(data), len)`
+//! - Only applies to audio/video *media* tags. Script tags and sequence headers
+//!   are always passed through; duplicates among them are only logged and counted.
+//! - Considers a tag duplicate if `(tag_type, timestamp_ms, xxh64(data), len)`
 //!   matches one seen recently.
+//! - Keeps the payload digests of the last few seconds of stream time, and also
+//!   considers a tag duplicate if the same payload was seen within a small
+//!   timestamp tolerance. This catches overlaps re-sent with slightly shifted
+//!   timestamps, e.g. after a reconnect.
 //! - Additionally, if a large timestamp back-jump is detected, it will try to
 //!   detect "replay loops" where the same content is re-sent with a constant
 //!   timestamp offset and drop those tags as well.
@@ -24,8 +28,7 @@ use pipeline_common::{PipelineError, Processor, StreamerContext};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use tracing::{debug, trace};
-
-use crate::crc32;
+use xxhash_rust::xxh64::xxh64;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct TagKey(u64);
@@ -33,6 +36,12 @@ struct TagKey(u64);
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct FingerprintKey(u64);
 
+/// Digest of a tag payload, computed once per tag.
+#[inline]
+fn payload_digest(tag: &FlvTag) -> u64 {
+    xxh64(tag.data.as_ref(), 0)
+}
+
 #[inline]
 fn mix64(mut x: u64) -> u64 {
     // SplitMix64
@@ -43,31 +52,87 @@ fn mix64(mut x: u64) -> u64 {
 }
 
 impl TagKey {
-    fn new(tag: &FlvTag) -> Self {
-        Self::new_with_timestamp(tag, tag.timestamp_ms)
+    fn new(tag: &FlvTag, digest: u64) -> Self {
+        Self::new_with_timestamp(tag, tag.timestamp_ms, digest)
     }
 
-    fn new_with_timestamp(tag: &FlvTag, timestamp_ms: u32) -> Self {
+    fn new_with_timestamp(tag: &FlvTag, timestamp_ms: u32, digest: u64) -> Self {
         let tag_type: u8 = tag.tag_type.into();
         let ts = timestamp_ms as u64;
         let len = tag.data.len() as u64;
-        let crc = crc32::crc32(tag.data.as_ref()) as u64;
 
-        let x = ((tag_type as u64) << 56) ^ (len.rotate_left(17)) ^ ts ^ (crc.rotate_left(1));
+        let x = ((tag_type as u64) << 56) ^ (len.rotate_left(17)) ^ ts ^ (digest.rotate_left(1));
         TagKey(mix64(x))
     }
 }
 
 impl FingerprintKey {
-    fn new(tag: &FlvTag) -> Self {
+    fn new(tag: &FlvTag, digest: u64) -> Self {
         let tag_type: u8 = tag.tag_type.into();
         let len = tag.data.len() as u64;
-        let crc = crc32::crc32(tag.data.as_ref()) as u64;
-        let x = ((tag_type as u64) << 56) ^ (len.rotate_left(17)) ^ (crc.rotate_left(1));
+        let x = ((tag_type as u64) << 56) ^ (len.rotate_left(17)) ^ (digest.rotate_left(1));
         FingerprintKey(mix64(x))
     }
 }
 
+#[derive(Clone, Copy, Debug)]
+struct ContentEntry {
+    tag_type: u8,
+    digest: u64,
+    timestamp_ms: u32,
+}
+
+/// Payload digests of recent tags with the timestamps they were seen at.
+#[derive(Debug, Default)]
+struct ContentWindow {
+    order: VecDeque<ContentEntry>,
+    timestamps: HashMap<(u8, u64), Vec<u32>>,
+}
+
+impl ContentWindow {
+    fn contains(&self, entry: ContentEntry, tolerance_ms: u32) -> bool {
+        self.timestamps
+            .get(&(entry.tag_type, entry.digest))
+            .is_some_and(|seen| {
+                seen.iter()
+                    .any(|&ts| ts.abs_diff(entry.timestamp_ms) <= tolerance_ms)
+            })
+    }
+
+    /// Add an entry, then forget entries more than `window_ms` away from it or beyond
+    /// `capacity` entries.
+    fn insert(&mut self, entry: ContentEntry, window_ms: u32, capacity: usize) {
+        self.order.push_back(entry);
+        self.timestamps
+            .entry((entry.tag_type, entry.digest))
+            .or_default()
+            .push(entry.timestamp_ms);
+
+        while let Some(front) = self.order.front().copied() {
+            if self.order.len() <= capacity
+                && front.timestamp_ms.abs_diff(entry.timestamp_ms) <= window_ms
+            {
+                break;
+            }
+            self.order.pop_front();
+            let key = (front.tag_type, front.digest);
+            if let Some(seen) = self.timestamps.get_mut(&key) {
+                if let Some(pos) = seen.iter().position(|&ts| ts == front.timestamp_ms) {
+                    seen.swap_remove(pos);
+                }
+                if seen.is_empty() {
+                    self.timestamps.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.order.clear();
+        self.timestamps.clear();
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DuplicateTagFilterConfig {
     /// Maximum number of recently-seen tags to remember for exact duplicate
@@ -82,6 +147,15 @@ pub struct DuplicateTagFilterConfig {
     /// find an offset that maps incoming replay timestamps to a previously seen
     /// region and drop tags that match the mapped timestamps.
     pub enable_replay_offset_matching: bool,
+    /// Stream time (ms) for which payload digests of recent tags are remembered
+    /// to catch duplicates with shifted timestamps (0 = disabled).
+    pub content_window_ms: u32,
+    /// Maximum timestamp difference (ms) between two tags with the same payload
+    /// for the later one to count as a duplicate.
+    ///
+    /// Keep this below the shortest frame interval: silent AAC frames carry
+    /// identical payloads about 21ms apart.
+    pub content_timestamp_tolerance_ms: u32,
 }
 
 impl Default for DuplicateTagFilterConfig {
@@ -90,6 +164,8 @@ impl Default for DuplicateTagFilterConfig {
             window_capacity_tags: 8 * 1024,
             replay_backjump_threshold_ms: 2_000,
             enable_replay_offset_matching: true,
+            content_window_ms: 3_000,
+            content_timestamp_tolerance_ms: 10,
         }
     }
 }
@@ -100,6 +176,7 @@ pub struct DuplicateTagFilterOperator {
     order: VecDeque<SeenEntry>,
     seen: HashSet<TagKey>,
     fingerprint_last: HashMap<FingerprintKey, (u32, u64)>,
+    content: ContentWindow,
     seq: u64,
     max_timestamp_seen: u32,
     replay_active: bool,
@@ -121,6 +198,7 @@ impl DuplicateTagFilterOperator {
             order: VecDeque::with_capacity(cap.min(1024)),
             seen: HashSet::with_capacity(cap.min(1024)),
             fingerprint_last: HashMap::with_capacity(cap.min(1024)),
+            content: ContentWindow::default(),
             seq: 0,
             max_timestamp_seen: 0,
             replay_active: false,
@@ -144,6 +222,7 @@ impl DuplicateTagFilterOperator {
         self.order.clear();
         self.seen.clear();
         self.fingerprint_last.clear();
+        self.content.clear();
         self.seq = 0;
         self.max_timestamp_seen = 0;
         self.replay_active = false;
@@ -152,9 +231,35 @@ impl DuplicateTagFilterOperator {
         self.next_drop_log_at = 1_000;
     }
 
-    fn track_tag(&mut self, tag: &FlvTag) {
-        let fingerprint = FingerprintKey::new(tag);
-        let key = TagKey::new(tag);
+    fn content_entry(tag: &FlvTag, digest: u64) -> ContentEntry {
+        ContentEntry {
+            tag_type: tag.tag_type.into(),
+            digest,
+            timestamp_ms: tag.timestamp_ms,
+        }
+    }
+
+    fn is_content_duplicate(&self, tag: &FlvTag, digest: u64) -> bool {
+        self.config.content_window_ms > 0
+            && self.content.contains(
+                Self::content_entry(tag, digest),
+                self.config.content_timestamp_tolerance_ms,
+            )
+    }
+
+    fn track_content(&mut self, tag: &FlvTag, digest: u64) {
+        if self.config.content_window_ms > 0 {
+            self.content.insert(
+                Self::content_entry(tag, digest),
+                self.config.content_window_ms,
+                self.config.window_capacity_tags,
+            );
+        }
+    }
+
+    fn track_tag(&mut self, tag: &FlvTag, digest: u64) {
+        let fingerprint = FingerprintKey::new(tag, digest);
+        let key = TagKey::new(tag, digest);
 
         self.seq = self.seq.wrapping_add(1);
         let seq = self.seq;
@@ -180,13 +285,20 @@ impl DuplicateTagFilterOperator {
                 }
             }
         }
+
+        self.track_content(tag, digest);
     }
 
     fn is_exact_duplicate(&self, key: TagKey) -> bool {
         self.seen.contains(&key)
     }
 
-    fn replay_mapped_key(&mut self, tag: &FlvTag, fingerprint: FingerprintKey) -> Option<TagKey> {
+    fn replay_mapped_key(
+        &mut self,
+        tag: &FlvTag,
+        fingerprint: FingerprintKey,
+        digest: u64,
+    ) -> Option<TagKey> {
         if !self.replay_active || !self.config.enable_replay_offset_matching {
             return None;
         }
@@ -201,7 +313,7 @@ impl DuplicateTagFilterOperator {
         {
             let candidate = (prev_ts - ts) as i64;
             let mapped_ts = prev_ts;
-            let mapped_key = TagKey::new_with_timestamp(tag, mapped_ts);
+            let mapped_key = TagKey::new_with_timestamp(tag, mapped_ts, digest);
             if self.is_exact_duplicate(mapped_key) {
                 self.replay_offset_ms = Some(candidate);
                 return Some(mapped_key);
@@ -216,25 +328,26 @@ impl DuplicateTagFilterOperator {
         }
         let mapped_ts = mapped_ts_i64 as u32;
 
-        Some(TagKey::new_with_timestamp(tag, mapped_ts))
+        Some(TagKey::new_with_timestamp(tag, mapped_ts, digest))
     }
 
-    fn track_and_check(&mut self, tag: &FlvTag) -> bool {
+    fn track_and_check(&mut self, tag: &FlvTag, digest: u64) -> bool {
         // 1) Exact match (type + timestamp + payload).
-        let key = TagKey::new(tag);
+        let key = TagKey::new(tag, digest);
         if self.seen.contains(&key) {
             return true;
         }
 
         // 2) Replay-mode match: same payload, but timestamp shifted by a constant offset.
-        let fp = FingerprintKey::new(tag);
-        if let Some(mapped_key) = self.replay_mapped_key(tag, fp)
+        let fp = FingerprintKey::new(tag, digest);
+        if let Some(mapped_key) = self.replay_mapped_key(tag, fp, digest)
             && self.is_exact_duplicate(mapped_key)
         {
             return true;
         }
 
-        false
+        // 3) Content match: same payload seen recently at a nearby timestamp.
+        self.is_content_duplicate(tag, digest)
     }
 }
 
@@ -263,10 +376,23 @@ impl Processor<FlvData> for DuplicateTagFilterOperator {
             }
             FlvData::Tag(tag) => {
                 // Keep metadata and codec config tags as-is. Dedicated operators handle those.
-                if tag.is_script_tag()
-                    || tag.is_video_sequence_header()
-                    || tag.is_audio_sequence_header()
+                let view = tag.view();
+                if view.is_script_tag()
+                    || view.is_video_sequence_header()
+                    || view.is_audio_sequence_header()
                 {
+                    if self.config.content_window_ms > 0 {
+                        let digest = payload_digest(&tag);
+                        if self.is_content_duplicate(&tag, digest) {
+                            debug!(
+                                "{} Passing on duplicate {:?} header tag at {}ms",
+                                self.context.name, tag.tag_type, tag.timestamp_ms
+                            );
+                            self.context.stats.record_duplicate_kept();
+                        } else {
+                            self.track_content(&tag, digest);
+                        }
+                    }
                     return output(FlvData::Tag(tag));
                 }
 
@@ -287,8 +413,10 @@ impl Processor<FlvData> for DuplicateTagFilterOperator {
                     self.replay_active = true;
                 }
 
-                if self.track_and_check(&tag) {
+                let digest = payload_digest(&tag);
+                if self.track_and_check(&tag, digest) {
                     self.dropped_duplicates = self.dropped_duplicates.saturating_add(1);
+                    self.context.stats.record_duplicate();
                     trace!(
                         "{} Dropping duplicate media tag: type={:?} ts={} len={}",
                         self.context.name,
@@ -306,7 +434,7 @@ impl Processor<FlvData> for DuplicateTagFilterOperator {
                     return Ok(());
                 }
 
-                self.track_tag(&tag);
+                self.track_tag(&tag, digest);
 
                 output(FlvData::Tag(tag))
             }
@@ -333,7 +461,9 @@ mod tests {
     use pipeline_common::CancellationToken;
 
     use super::*;
-    use crate::test_utils::{create_audio_tag, create_test_header, create_video_tag};
+    use crate::test_utils::{
+        create_audio_tag, create_test_header, create_video_sequence_header, create_video_tag,
+    };
 
     #[test]
     fn test_drops_exact_duplicate_media_tags_within_window() {
@@ -550,4 +680,106 @@ mod tests {
         // Only the first tail should remain.
         assert_eq!(media_tag_count, 4);
     }
+
+    #[test]
+    fn test_drops_duplicate_with_shifted_timestamp() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = DuplicateTagFilterOperator::new(context.clone());
+        let mut output_items = Vec::new();
+
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        operator
+            .process(&context, create_test_header(), &mut output_fn)
+            .unwrap();
+
+        // The overlap is re-sent a few milliseconds off the original timestamps.
+        for item in [
+            create_video_tag(1000, true),
+            create_audio_tag(1010),
+            create_video_tag(1004, true),
+            create_audio_tag(1013),
+            create_audio_tag(1033),
+        ] {
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+
+        let timestamps: Vec<u32> = output_items
+            .iter()
+            .filter_map(|i| match i {
+                FlvData::Tag(t) => Some(t.timestamp_ms),
+                _ => None,
+            })
+            .collect();
+
+        // Identical payloads outside the tolerance are legitimate frames.
+        assert_eq!(timestamps, vec![1000, 1010, 1033]);
+        assert_eq!(context.stats.snapshot().duplicates, 2);
+    }
+
+    #[test]
+    fn test_keeps_duplicate_sequence_headers_and_counts_them() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = DuplicateTagFilterOperator::new(context.clone());
+        let mut output_items = Vec::new();
+
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        operator
+            .process(&context, create_test_header(), &mut output_fn)
+            .unwrap();
+        for _ in 0..2 {
+            operator
+                .process(&context, create_video_sequence_header(0, 1), &mut output_fn)
+                .unwrap();
+        }
+
+        let sequence_headers = output_items
+            .iter()
+            .filter(|i| matches!(i, FlvData::Tag(t) if t.is_video_sequence_header()))
+            .count();
+        assert_eq!(sequence_headers, 2);
+
+        let stats = context.stats.snapshot();
+        assert_eq!(stats.duplicates, 0);
+        assert_eq!(stats.duplicates_kept, 1);
+    }
+
+    #[test]
+    fn test_content_window_can_be_disabled() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let cfg = DuplicateTagFilterConfig {
+            content_window_ms: 0,
+            ..Default::default()
+        };
+        let mut operator = DuplicateTagFilterOperator::with_config(context.clone(), cfg);
+        let mut output_items = Vec::new();
+
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        operator
+            .process(&context, create_test_header(), &mut output_fn)
+            .unwrap();
+        operator
+            .process(&context, create_video_tag(1000, true), &mut output_fn)
+            .unwrap();
+        operator
+            .process(&context, create_video_tag(1004, true), &mut output_fn)
+            .unwrap();
+
+        let media_tag_count = output_items
+            .iter()
+            .filter(|i| matches!(i, FlvData::Tag(_)))
+            .count();
+        assert_eq!(media_tag_count, 2);
+    }
 }
@@ -32,9 +32,8 @@
 
 use flv::data::FlvData;
 use flv::header::FlvHeader;
-use pipeline_common::{PipelineError, Processor, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
 use std::sync::Arc;
-use tracing::warn;
 
 /// An operator that validates and ensures FLV streams have a proper header.
 ///
@@ -82,13 +81,15 @@ impl Processor<FlvData> for HeaderCheckOperator {
 
             // If the first item is not a header, insert a default one
             if !input.is_header() {
-                warn!(
-                    "{} FLV header is missing, inserted a default header",
-                    self.context.name
-                );
                 // Send a default header
                 let default_header = FlvHeader::new(self.has_audio, self.has_video);
                 output(FlvData::Header(default_header))?;
+                self.context.emit(PipelineEvent::RepairApplied {
+                    operator: self.name(),
+                    kind: "missing_header",
+                    offset: None,
+                    detail: "FLV header is missing, inserted a default header".to_string(),
+                });
             } else {
                 // input is a header, update the flags
                 if let FlvData::Header(ref header) = input {
@@ -39,7 +39,7 @@ use pipeline_common::split_reason::SplitReason;
 use pipeline_common::{PipelineError, Processor, StreamerContext};
 use std::sync::Arc;
 use std::time::Instant;
-use tracing::{debug, info};
+use tracing::debug;
 
 /// Optional callback for when a stream split occurs
 pub type SplitCallback = Box<dyn Fn(SplitReason, u64, u32) + Send + Sync>;
@@ -183,15 +183,21 @@ impl LimitOperator {
     fn split_stream(
         &mut self,
         reason: SplitReason,
+        timestamp: u32,
         output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
     ) -> Result<(), PipelineError> {
-        info!(
+        debug!(
             "{} Splitting stream at size={} bytes, duration={}ms (segment #{})",
             self.context.name,
             self.state.accumulated_size,
             self.state.current_duration(),
             self.state.split_count + 1
         );
+        self.context.emit(PipelineEvent::SplitOccurred {
+            operator: self.name(),
+            reason: Some(reason.clone()),
+            offset: Some(timestamp as u64),
+        });
 
         // Emit the Split marker before re-injecting the header.
         output(FlvData::Split(reason))?;
@@ -248,18 +254,21 @@ impl Processor<FlvData> for LimitOperator {
                 output(FlvData::Header(header))?;
             }
             FlvData::Tag(tag) => {
+                // Parse the video header once for the checks below
+                let view = tag.view();
+
                 // Update size counter
                 let tag_size = tag.size() as u64;
                 self.state.accumulated_size += tag_size;
 
                 // Track key metadata
-                if tag.is_script_tag() {
+                if view.is_script_tag() {
                     self.state.metadata = Some(tag.clone());
-                } else if tag.is_video_sequence_header() {
+                } else if view.is_video_sequence_header() {
                     let mut tag = tag.clone();
                     tag.timestamp_ms = 0; // Reset timestamp for video sequence header
                     self.state.video_sequence_tag = Some(tag);
-                } else if tag.is_audio_sequence_header() {
+                } else if view.is_audio_sequence_header() {
                     let mut tag = tag.clone();
                     tag.timestamp_ms = 0; // Reset timestamp for audio sequence header
                     self.state.audio_sequence_tag = Some(tag);
@@ -283,7 +292,7 @@ impl Processor<FlvData> for LimitOperator {
                 }
 
                 // Track keyframes for optimal split points
-                if tag.is_key_frame_nalu() {
+                if view.is_key_frame_nalu() {
                     self.state.last_keyframe_position =
                         Some((self.state.accumulated_size, self.state.max_timestamp));
                 }
@@ -294,7 +303,7 @@ impl Processor<FlvData> for LimitOperator {
                 // Inside the process method where split decisions are made
                 let has_video = self.state.header.as_ref().is_some_and(|h| h.has_video);
                 let can_split_on_tag = if has_video {
-                    tag.is_key_frame_nalu()
+                    view.is_key_frame_nalu()
                 } else {
                     // For audio-only, we can split on any tag
                     true
@@ -311,7 +320,7 @@ impl Processor<FlvData> for LimitOperator {
                     }
 
                     // Perform the split
-                    self.split_stream(split_reason, output)?;
+                    self.split_stream(split_reason, tag.timestamp_ms, output)?;
 
                     // Emit current tag after the split
                     output(FlvData::Tag(tag))?;
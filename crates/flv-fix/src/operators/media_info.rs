@@ -0,0 +1,373 @@
+//! # Media Info Operator
+//!
+//! Describes an FLV stream in [`StreamerContext::media_info`] as soon as its parameters
+//! are known.
+//!
+//! ## How it Works
+//!
+//! The operator forwards every item unchanged and:
+//!
+//! 1. Takes the codec, profile, level, dimensions and frame rate of the video track from
+//!    its sequence header
+//! 2. Takes the codec, sample rate and channels of the audio track from its sequence
+//!    header, or from the first audio tag for codecs without one
+//! 3. Takes the advertised frame rate, data rates and encoder from `onMetaData`
+//! 4. Publishes once every track announced by the FLV header is described, or one second
+//!    of media after the first media tag otherwise
+//! 5. Publishes again whenever a later sequence header or `onMetaData` changes the
+//!    description
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use flv::audio::SoundFormat;
+use flv::data::FlvData;
+use flv::script::ScriptData;
+use flv::tag::FlvTag;
+use pipeline_common::{
+    AudioInfo, MediaInfo, MediaInfoValue, PipelineError, Processor, StreamerContext, VideoInfo,
+};
+use tracing::info;
+
+use super::SplitOperator;
+use crate::constants::*;
+
+/// How long the tracks announced by the FLV header are waited for, from the first media tag
+const MAX_WAIT_MS: u32 = 1000;
+
+/// Operator publishing the [`MediaInfo`] of an FLV stream
+pub struct MediaInfoOperator {
+    context: Arc<StreamerContext>,
+    video: Option<VideoInfo>,
+    audio: Option<AudioInfo>,
+    /// Frame rate advertised by `onMetaData`, preferred over the one of the SPS
+    metadata_frame_rate: Option<f64>,
+    extras: BTreeMap<String, MediaInfoValue>,
+    expects_video: bool,
+    expects_audio: bool,
+    first_media_timestamp: Option<u32>,
+    published: bool,
+    /// Whether the description changed since it was last published
+    dirty: bool,
+}
+
+impl MediaInfoOperator {
+    pub fn new(context: Arc<StreamerContext>) -> Self {
+        Self {
+            context,
+            video: None,
+            audio: None,
+            metadata_frame_rate: None,
+            extras: BTreeMap::new(),
+            expects_video: false,
+            expects_audio: false,
+            first_media_timestamp: None,
+            published: false,
+            dirty: false,
+        }
+    }
+
+    fn media_info(&self) -> MediaInfo {
+        MediaInfo {
+            video: self.video.clone().map(|video| VideoInfo {
+                frame_rate: self.metadata_frame_rate.or(video.frame_rate),
+                ..video
+            }),
+            audio: self.audio.clone(),
+            extras: self.extras.clone(),
+            changed: false,
+        }
+    }
+
+    fn analyze_metadata(&mut self, tag: &FlvTag) {
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        let Ok(script) = ScriptData::demux(&mut cursor) else {
+            return;
+        };
+        if script.name != AMF0_ON_METADATA {
+            return;
+        }
+        let Some(properties) = script.data.first() else {
+            return;
+        };
+        self.dirty = true;
+
+        self.metadata_frame_rate = properties
+            .get(METADATA_FRAMERATE)
+            .and_then(|value| value.as_number())
+            .filter(|rate| *rate > 0.0);
+        for key in [METADATA_VIDEODATARATE, METADATA_AUDIODATARATE] {
+            match properties.get(key).and_then(|value| value.as_number()) {
+                Some(rate) => self
+                    .extras
+                    .insert(key.to_string(), MediaInfoValue::Number(rate)),
+                None => self.extras.remove(key),
+            };
+        }
+        match properties
+            .get(METADATA_ENCODER)
+            .and_then(|value| value.as_str())
+        {
+            Some(encoder) => self.extras.insert(
+                METADATA_ENCODER.to_string(),
+                MediaInfoValue::Text(encoder.to_string()),
+            ),
+            None => self.extras.remove(METADATA_ENCODER),
+        };
+    }
+
+    fn analyze_video(&mut self, tag: &FlvTag) {
+        if tag.is_video_sequence_header() {
+            let codec_info = SplitOperator::extract_video_codec_info(tag, 0);
+            self.video = Some(VideoInfo {
+                frame_rate: tag.get_video_frame_rate().filter(|rate| *rate > 0.0),
+                ..VideoInfo::from(&codec_info)
+            });
+            self.dirty = true;
+        } else {
+            self.first_media_timestamp.get_or_insert(tag.timestamp_ms);
+        }
+    }
+
+    fn analyze_audio(&mut self, tag: &FlvTag) {
+        if tag.is_audio_sequence_header() {
+            let codec_info = SplitOperator::extract_audio_codec_info(tag, 0);
+            self.audio = Some(AudioInfo::from(&codec_info));
+            self.dirty = true;
+            return;
+        }
+
+        self.first_media_timestamp.get_or_insert(tag.timestamp_ms);
+        // Only AAC is configured by a sequence header
+        if self.audio.is_none() && tag.get_audio_codec_id() != Some(SoundFormat::Aac) {
+            let codec_info = SplitOperator::extract_audio_codec_info(tag, 0);
+            self.audio = Some(AudioInfo::from(&codec_info));
+            self.dirty = true;
+        }
+    }
+
+    /// Whether the stream is described well enough to be published
+    fn is_ready(&self, timestamp: u32) -> bool {
+        let described = (!self.expects_video || self.video.is_some())
+            && (!self.expects_audio || self.audio.is_some());
+        let waited = self
+            .first_media_timestamp
+            .is_some_and(|first| timestamp.saturating_sub(first) >= MAX_WAIT_MS);
+        (described && (self.video.is_some() || self.audio.is_some())) || waited
+    }
+
+    fn publish(&mut self) {
+        let media_info = self.media_info();
+        if self.context.media_info.publish(media_info.clone()) {
+            info!(
+                "{} Media info{}: {}",
+                self.context.name,
+                if self.published { " changed" } else { "" },
+                media_info
+            );
+        }
+        self.published = true;
+        self.dirty = false;
+    }
+}
+
+impl Processor<FlvData> for MediaInfoOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: FlvData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        match &input {
+            FlvData::Header(header) => {
+                self.expects_video = header.has_video;
+                self.expects_audio = header.has_audio;
+            }
+            FlvData::Tag(tag) => {
+                if tag.is_script_tag() {
+                    self.analyze_metadata(tag);
+                } else if tag.is_video_tag() {
+                    self.analyze_video(tag);
+                } else if tag.is_audio_tag() {
+                    self.analyze_audio(tag);
+                }
+                let publish = if self.published {
+                    self.dirty
+                } else {
+                    self.is_ready(tag.timestamp_ms)
+                };
+                if publish {
+                    self.publish();
+                }
+            }
+            _ => {}
+        }
+
+        output(input)
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        // Describe short streams with whatever is known
+        if !self.published && !self.media_info().is_empty() {
+            self.publish();
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "MediaInfoOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        create_audio_sequence_header, create_audio_tag, create_enhanced_sequence_start,
+        create_enhanced_video_tag, create_script_tag, create_test_header, create_test_tag,
+    };
+    use amf0::{Amf0Encoder, Amf0Value};
+    use flv::tag::FlvTagType;
+    use flv::video::VideoFourCC;
+    use pipeline_common::{CancellationToken, ProgressEvent, init_test_tracing};
+    use std::borrow::Cow;
+    use std::sync::Mutex;
+
+    /// Runs `items` through the operator and returns the published media infos.
+    fn run(items: Vec<FlvData>) -> Vec<MediaInfo> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let sink = published.clone();
+        context.media_info.subscribe(move |event| {
+            if let ProgressEvent::MediaInfo(info) = event {
+                sink.lock().unwrap().push(info);
+            }
+        });
+
+        let mut operator = MediaInfoOperator::new(context.clone());
+        let mut forwarded = 0;
+        let mut output = |_: FlvData| -> Result<(), PipelineError> {
+            forwarded += 1;
+            Ok(())
+        };
+        let count = items.len();
+        for item in items {
+            operator.process(&context, item, &mut output).unwrap();
+        }
+        operator.finish(&context, &mut output).unwrap();
+        assert_eq!(forwarded, count);
+
+        published.lock().unwrap().clone()
+    }
+
+    fn metadata_tag(frame_rate: f64, encoder: &str) -> FlvData {
+        let properties = vec![
+            (
+                Cow::Borrowed(METADATA_FRAMERATE),
+                Amf0Value::Number(frame_rate),
+            ),
+            (
+                Cow::Borrowed(METADATA_VIDEODATARATE),
+                Amf0Value::Number(2500.0),
+            ),
+            (
+                Cow::Borrowed(METADATA_ENCODER),
+                Amf0Value::String(Cow::Owned(encoder.to_string())),
+            ),
+        ];
+        let mut buffer = Vec::new();
+        Amf0Encoder::encode_string(&mut buffer, AMF0_ON_METADATA).unwrap();
+        Amf0Encoder::encode(&mut buffer, &Amf0Value::Object(Cow::Owned(properties))).unwrap();
+        create_test_tag(FlvTagType::ScriptData, 0, buffer)
+    }
+
+    #[test]
+    fn test_publishes_once_all_tracks_are_described() {
+        init_test_tracing!();
+        let mut items = vec![
+            create_test_header(),
+            metadata_tag(30.0, "obs-output module"),
+            create_enhanced_sequence_start(0, VideoFourCC::Hvc1),
+            // AAC LC, 44.1kHz, stereo
+            create_audio_sequence_header(0, 0x12),
+        ];
+        for i in 0..50 {
+            items.push(create_enhanced_video_tag(i * 40, VideoFourCC::Hvc1, i == 0));
+            items.push(create_audio_tag(i * 40));
+        }
+
+        let published = run(items);
+        assert_eq!(published.len(), 1);
+        let info = &published[0];
+        assert!(!info.changed);
+
+        let video = info.video.as_ref().unwrap();
+        assert_eq!(video.codec, "HEVC");
+        assert_eq!(video.profile, Some(1));
+        assert_eq!(video.level, Some(153));
+        assert_eq!(info.resolution(), Some((2560, 1440)));
+        assert_eq!(video.frame_rate, Some(30.0));
+
+        let audio = info.audio.as_ref().unwrap();
+        assert_eq!(audio.codec, "AAC");
+        assert_eq!(audio.sample_rate, Some(44100));
+        assert_eq!(audio.channels, Some(2));
+
+        assert_eq!(
+            info.extras.get(METADATA_ENCODER),
+            Some(&MediaInfoValue::Text("obs-output module".to_string()))
+        );
+        assert_eq!(
+            info.extras.get(METADATA_VIDEODATARATE),
+            Some(&MediaInfoValue::Number(2500.0))
+        );
+    }
+
+    #[test]
+    fn test_codec_change_is_published_as_change() {
+        init_test_tracing!();
+        let published = run(vec![
+            create_test_header(),
+            create_script_tag(0, false),
+            create_enhanced_sequence_start(0, VideoFourCC::Hvc1),
+            create_audio_sequence_header(0, 0x12),
+            create_enhanced_video_tag(0, VideoFourCC::Hvc1, true),
+            // Same configuration again, no change
+            create_audio_sequence_header(40, 0x12),
+            create_enhanced_sequence_start(80, VideoFourCC::Av01),
+            create_enhanced_video_tag(80, VideoFourCC::Av01, true),
+        ]);
+
+        assert_eq!(published.len(), 2);
+        assert!(!published[0].changed);
+        assert_eq!(published[0].video.as_ref().unwrap().codec, "HEVC");
+        assert!(published[1].changed);
+        assert_eq!(published[1].video.as_ref().unwrap().codec, "AV1");
+        assert_eq!(published[1].audio, published[0].audio);
+    }
+
+    #[test]
+    fn test_missing_track_is_waited_for_a_second() {
+        init_test_tracing!();
+        let mut items = vec![
+            create_test_header(),
+            create_enhanced_sequence_start(0, VideoFourCC::Av01),
+        ];
+        for i in 0..30 {
+            items.push(create_enhanced_video_tag(i * 40, VideoFourCC::Av01, i == 0));
+        }
+
+        let published = run(items);
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].video.as_ref().unwrap().codec, "AV1");
+        assert_eq!(published[0].audio, None);
+    }
+}
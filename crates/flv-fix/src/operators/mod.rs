@@ -4,18 +4,28 @@
 //! These operators can be combined into a pipeline to perform various transformations and
 //! validations on FLV data.
 
+mod anomaly_detector;
+mod audio_gap_fill;
+mod clip;
 mod defragment;
 mod duplicate_filter;
 mod gop_sort;
 mod header_check;
 mod limit;
+mod media_info;
 mod script_filler;
 mod script_filter;
 mod split;
 mod time_consistency;
+mod timestamp_normalizer;
 mod timing_repair;
+mod track_filter;
 
 // Re-export common operators
+pub use crate::amf::model::CuePoint;
+pub use anomaly_detector::AnomalyDetectorOperator;
+pub use audio_gap_fill::{AudioGapFillConfig, AudioGapFillOperator, AudioGapFillStats};
+pub use clip::{ClipConfig, ClipOperator, ClipStartMode};
 pub use defragment::DefragmentOperator;
 pub use duplicate_filter::DuplicateTagFilterConfig;
 pub use duplicate_filter::DuplicateTagFilterOperator;
@@ -23,10 +33,15 @@ pub use gop_sort::GopSortOperator;
 pub use header_check::HeaderCheckOperator;
 pub use limit::LimitConfig;
 pub use limit::LimitOperator;
+pub use media_info::MediaInfoOperator;
 pub use script_filler::MIN_INTERVAL_BETWEEN_KEYFRAMES_MS;
-pub use script_filler::{ScriptFillerConfig, ScriptKeyframesFillerOperator};
+pub use script_filler::{
+    CuePointReceiver, ScriptFillerConfig, ScriptKeyframesFillerOperator, cue_point_channel,
+};
 pub use script_filter::ScriptFilterOperator;
 pub use split::SequenceHeaderChangeMode;
 pub use split::SplitOperator;
 pub use time_consistency::{ContinuityMode, TimeConsistencyOperator};
+pub use timestamp_normalizer::{TimestampNormalizerConfig, TimestampNormalizerOperator};
 pub use timing_repair::{RepairStrategy, TimingRepairConfig, TimingRepairOperator};
+pub use track_filter::{TrackFilter, TrackFilterOperator};
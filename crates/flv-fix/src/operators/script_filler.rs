@@ -34,6 +34,10 @@
 //! The operator supports configuration for:
 //! - Target keyframe interval in milliseconds
 //! - (Default to 3.5 hours for long recording sessions)
+//! - Reserving a padded keyframes object that `ScriptModifier::patch_in_place`
+//!   can fill without changing the tag size
+//! - Cue points, inserted as `onCuePoint` tags in front of the first media tag at or
+//!   after their time; more can be pushed while recording through [`cue_point_channel`]
 //!
 //!
 //! ## License
@@ -45,7 +49,10 @@
 //! - hua0512
 //!
 
-use crate::amf::{builder::OnMetaDataBuilder, model::AmfScriptData};
+use crate::amf::{
+    builder::OnMetaDataBuilder,
+    model::{AmfScriptData, CuePoint},
+};
 use amf0::Amf0Value;
 use bytes::Bytes;
 use flv::data::FlvData;
@@ -53,7 +60,9 @@ use flv::script::ScriptData;
 use flv::tag::{FlvTag, FlvTagType};
 use pipeline_common::{PipelineError, Processor, StreamerContext};
 use std::borrow::Cow;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
 use tracing::{debug, info, trace, warn};
 
 const DEFAULT_KEYFRAME_INTERVAL_MS: u32 = (3.5 * 60.0 * 60.0 * 1000.0) as u32; // 3.5 hours in ms
@@ -65,35 +74,134 @@ pub struct ScriptFillerConfig {
     /// The target maximum duration of keyframes in milliseconds.
     /// Defaults to 3.5 hours.
     pub keyframe_duration_ms: u32,
+    /// Reserve room for this many keyframes using a padding string instead of
+    /// the `spacer` array, so the index can later be written in place with
+    /// `ScriptModifier::patch_in_place`.
+    /// Defaults to `None`.
+    pub reserved_keyframes: Option<usize>,
+    /// Cue points to insert as `onCuePoint` tags, on the timeline of the output tags.
+    /// Defaults to none.
+    pub cue_points: Vec<CuePoint>,
+    /// Cue points pushed while recording, see [`cue_point_channel`].
+    /// Defaults to `None`.
+    pub cue_receiver: Option<CuePointReceiver>,
 }
 
 impl Default for ScriptFillerConfig {
     fn default() -> Self {
         Self {
             keyframe_duration_ms: DEFAULT_KEYFRAME_INTERVAL_MS,
+            reserved_keyframes: None,
+            cue_points: Vec::new(),
+            cue_receiver: None,
         }
     }
 }
 
+/// Receiving end of a channel of cue points pushed while recording.
+///
+/// Clones share the channel, a cue point goes to the first operator receiving it.
+#[derive(Clone, Debug)]
+pub struct CuePointReceiver(Arc<Mutex<mpsc::UnboundedReceiver<CuePoint>>>);
+
+impl CuePointReceiver {
+    /// Cue points pushed since the last call.
+    fn drain(&self) -> Vec<CuePoint> {
+        let Ok(mut receiver) = self.0.lock() else {
+            return Vec::new();
+        };
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+}
+
+/// Creates a channel to push cue points into a running pipeline, for
+/// [`ScriptFillerConfig::cue_receiver`].
+pub fn cue_point_channel() -> (mpsc::UnboundedSender<CuePoint>, CuePointReceiver) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (tx, CuePointReceiver(Arc::new(Mutex::new(rx))))
+}
+
 /// Operator to modify the first script tag with keyframe information.
 pub struct ScriptKeyframesFillerOperator {
     context: Arc<StreamerContext>,
     config: ScriptFillerConfig,
     seen_first_script_tag: bool,
     has_video: bool,
+    /// Cue points yet to be inserted, by time
+    pending_cues: VecDeque<CuePoint>,
+    /// Timestamp of the last media tag of the current file
+    last_timestamp: u32,
 }
 
 impl ScriptKeyframesFillerOperator {
     /// Creates a new ScriptInjectorOperator with the given configuration.
     pub fn new(context: Arc<StreamerContext>, config: ScriptFillerConfig) -> Self {
+        let mut pending_cues = config.cue_points.clone();
+        pending_cues.sort_by_key(|cue| cue.time_ms);
         Self {
             context,
             config,
             seen_first_script_tag: false,
             has_video: true,
+            pending_cues: pending_cues.into(),
+            last_timestamp: 0,
+        }
+    }
+
+    /// Queues the cue points pushed through the channel, keeping the queue in time order.
+    fn receive_cue_points(&mut self) {
+        let Some(receiver) = &self.config.cue_receiver else {
+            return;
+        };
+        for cue in receiver.drain() {
+            debug!(
+                "{} Received cue point '{}' at {}ms",
+                self.context.name, cue.name, cue.time_ms
+            );
+            let index = self
+                .pending_cues
+                .partition_point(|pending| pending.time_ms <= cue.time_ms);
+            self.pending_cues.insert(index, cue);
         }
     }
 
+    /// Outputs the pending cue points due at `timestamp`, ahead of the media tag there.
+    ///
+    /// A cue point pushed after its time has passed is inserted at the current position.
+    fn emit_cue_points(
+        &mut self,
+        timestamp: u32,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        while self
+            .pending_cues
+            .front()
+            .is_some_and(|cue| cue.time_ms <= timestamp)
+        {
+            let Some(cue) = self.pending_cues.pop_front() else {
+                break;
+            };
+            let payload = cue.to_script_payload().map_err(|e| {
+                PipelineError::Strategy(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    e.to_string(),
+                )))
+            })?;
+            debug!(
+                "{} Inserting cue point '{}' at {}ms",
+                self.context.name, cue.name, cue.time_ms
+            );
+            output(FlvData::Tag(FlvTag {
+                timestamp_ms: cue.time_ms.max(self.last_timestamp),
+                stream_id: 0,
+                tag_type: FlvTagType::ScriptData,
+                is_filtered: false,
+                data: Bytes::from(payload),
+            }))?;
+        }
+        Ok(())
+    }
+
     fn create_script_tag_payload() -> Bytes {
         // Create a new script tag with empty data
         let mut buffer = Vec::new();
@@ -152,9 +260,17 @@ impl ScriptKeyframesFillerOperator {
 
         trace!("Script data model: {:?}", script_data_model);
 
+        let builder = OnMetaDataBuilder::from_script_data(script_data_model);
+        let builder = match self.config.reserved_keyframes {
+            Some(capacity) => {
+                debug!("reserving room for {capacity} keyframes");
+                builder.with_reserved_keyframes(capacity)
+            }
+            None => builder.with_placeholder_keyframes(spacer_size),
+        };
+
         // new buffer with placeholder keyframes
-        let (buffer, _) = OnMetaDataBuilder::from_script_data(script_data_model)
-            .with_placeholder_keyframes(spacer_size)
+        let (buffer, _) = builder
             .build_bytes(original_payload_size, false)
             .map_err(|e| {
                 PipelineError::Strategy(Box::new(std::io::Error::new(
@@ -251,12 +367,14 @@ impl Processor<FlvData> for ScriptKeyframesFillerOperator {
         if context.token.is_cancelled() {
             return Err(PipelineError::Cancelled);
         }
+        self.receive_cue_points();
         match input {
             FlvData::Header(header) => {
                 debug!("{} Received Header. Forwarding.", self.context.name);
                 // reset flag
                 self.seen_first_script_tag = false;
                 self.has_video = header.has_video;
+                self.last_timestamp = 0;
 
                 output(FlvData::Header(header))
             }
@@ -276,6 +394,15 @@ impl Processor<FlvData> for ScriptKeyframesFillerOperator {
                     output(FlvData::Tag(tag))
                 }
             }
+            FlvData::Tag(tag)
+                if (tag.is_audio_tag() || tag.is_video_tag())
+                    && !tag.is_audio_sequence_header()
+                    && !tag.is_video_sequence_header() =>
+            {
+                self.emit_cue_points(tag.timestamp_ms, output)?;
+                self.last_timestamp = tag.timestamp_ms;
+                output(FlvData::Tag(tag))
+            }
             // Handle other FlvData types if necessary
             _ => {
                 // trace!(
@@ -292,6 +419,13 @@ impl Processor<FlvData> for ScriptKeyframesFillerOperator {
         _context: &Arc<StreamerContext>,
         _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
     ) -> Result<(), PipelineError> {
+        self.receive_cue_points();
+        for cue in self.pending_cues.drain(..) {
+            warn!(
+                "{} Dropping cue point '{}' at {}ms, past the end of the stream at {}ms",
+                self.context.name, cue.name, cue.time_ms, self.last_timestamp
+            );
+        }
         info!(
             "{} Script modification operator finished.",
             self.context.name
@@ -381,6 +515,39 @@ mod tests {
         assert!(keyframes.get("filepositions").unwrap().is_empty());
     }
 
+    #[test]
+    fn test_add_reserved_keyframes_to_amf() {
+        init_test_tracing!();
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let config = ScriptFillerConfig {
+            reserved_keyframes: Some(100),
+            ..Default::default()
+        };
+        let operator = ScriptKeyframesFillerOperator::new(context, config);
+
+        let FlvData::Tag(tag) = create_script_tag(0, true) else {
+            panic!("Expected FlvData::Tag but got something else");
+        };
+        let modified_tag = operator.add_keyframes_to_amf(tag).unwrap();
+
+        let keyframes = extract_keyframes(&modified_tag).unwrap();
+        assert!(keyframes.get("times").unwrap().is_empty());
+        assert!(keyframes.get("filepositions").unwrap().is_empty());
+        assert!(!keyframes.contains_key("spacer"));
+
+        let mut cursor = std::io::Cursor::new(modified_tag.data.clone());
+        let amf_data = ScriptData::demux(&mut cursor).unwrap();
+        let props = amf_data.data[0].as_object_properties().unwrap();
+        let (_, keyframes) = props.iter().find(|(k, _)| k == "keyframes").unwrap();
+        let (_, padding) = keyframes
+            .as_object_properties()
+            .unwrap()
+            .iter()
+            .find(|(k, _)| k == "padding")
+            .unwrap();
+        assert_eq!(padding.as_str().unwrap().len(), 100 * 18);
+    }
+
     #[test]
     fn test_process_flow() {
         init_test_tracing!();
@@ -524,4 +691,105 @@ mod tests {
             panic!("Expected script tag as second output item");
         }
     }
+
+    /// Timestamp and kind of each output tag, with the name of the cue points
+    fn describe(items: &[FlvData]) -> Vec<(u32, String)> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                FlvData::Tag(tag) if CuePoint::is_cue_point_payload(&tag.data) => {
+                    let mut cursor = std::io::Cursor::new(tag.data.clone());
+                    let script = ScriptData::demux(&mut cursor).unwrap();
+                    let cue = CuePoint::from_amf(&script.data[0]).unwrap();
+                    Some((tag.timestamp_ms, format!("cue {}", cue.name)))
+                }
+                FlvData::Tag(tag) if tag.is_script_tag() => {
+                    Some((tag.timestamp_ms, "script".into()))
+                }
+                FlvData::Tag(tag) if tag.is_video_tag() => Some((tag.timestamp_ms, "video".into())),
+                FlvData::Tag(tag) => Some((tag.timestamp_ms, "audio".into())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_cue_points_inserted_between_media_tags() {
+        init_test_tracing!();
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let (tx, cue_receiver) = cue_point_channel();
+        let config = ScriptFillerConfig {
+            cue_points: vec![
+                CuePoint::new(40, "second", "navigation"),
+                CuePoint::new(0, "first", "navigation")
+                    .with_parameter("title", Amf0Value::String("Intro".into())),
+                CuePoint::new(1000, "late", "event"),
+            ],
+            cue_receiver: Some(cue_receiver),
+            ..Default::default()
+        };
+        let mut operator = ScriptKeyframesFillerOperator::new(context.clone(), config);
+        let mut output_items = Vec::new();
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        let mut items = vec![
+            FlvData::Header(FlvHeader::new(true, true)),
+            create_script_tag(0, false),
+            test_utils::create_video_sequence_header(0, 1),
+        ];
+        for i in 0..4 {
+            items.push(test_utils::create_video_tag(i * 40, i == 0));
+            items.push(test_utils::create_audio_tag(i * 40 + 20));
+        }
+        for (i, item) in items.into_iter().enumerate() {
+            // Pushed while the stream runs: one due at the next tag, one already past
+            match i {
+                6 => tx.send(CuePoint::new(50, "live", "event")).unwrap(),
+                8 => tx.send(CuePoint::new(10, "past", "event")).unwrap(),
+                _ => {}
+            }
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+        operator.finish(&context, &mut output_fn).unwrap();
+
+        // Cue points go in front of the first media tag at or after their time, the media
+        // order is untouched and the one past the end of the stream is dropped
+        let expected = [
+            (0, "script"),
+            (0, "video"),
+            (0, "cue first"),
+            (0, "video"),
+            (20, "audio"),
+            (40, "cue second"),
+            (40, "video"),
+            (50, "cue live"),
+            (60, "audio"),
+            (80, "video"),
+            (80, "cue past"),
+            (100, "audio"),
+            (120, "video"),
+            (140, "audio"),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(timestamp, kind)| (timestamp, kind.to_string()))
+            .collect();
+        assert_eq!(describe(&output_items), expected);
+
+        let FlvData::Tag(first) = &output_items[3] else {
+            panic!("Expected the first cue point");
+        };
+        let mut cursor = std::io::Cursor::new(first.data.clone());
+        let script = ScriptData::demux(&mut cursor).unwrap();
+        assert_eq!(script.name, crate::AMF0_ON_CUE_POINT);
+        let cue = CuePoint::from_amf(&script.data[0]).unwrap();
+        assert_eq!(
+            cue,
+            CuePoint::new(0, "first", "navigation")
+                .with_parameter("title", Amf0Value::String("Intro".into()))
+        );
+    }
 }
@@ -8,7 +8,7 @@
 //! can only properly handle a single metadata tag. This operator ensures that:
 //!
 //! 1. The first script data tag is preserved (typically containing essential metadata)
-//! 2. Subsequent script data tags are discarded
+//! 2. Subsequent script data tags are discarded, except `onCuePoint` tags
 //!
 //! This improves compatibility with various players and reduces unnecessary data.
 //!
@@ -28,6 +28,7 @@
 //! - hua0512
 //!
 
+use crate::amf::model::CuePoint;
 use flv::data::FlvData;
 use flv::tag::FlvTagType;
 use pipeline_common::{PipelineError, Processor, StreamerContext};
@@ -74,7 +75,14 @@ impl Processor<FlvData> for ScriptFilterOperator {
                 self.reset();
                 // Forward the header
                 output(input)
-            } // Check if this is a script tag
+            } // Cue points mark positions in the media, keep them all
+            FlvData::Tag(tag)
+                if tag.tag_type == FlvTagType::ScriptData
+                    && CuePoint::is_cue_point_payload(&tag.data) =>
+            {
+                output(FlvData::Tag(tag))
+            }
+            // Check if this is a script tag
             FlvData::Tag(tag) if tag.tag_type == FlvTagType::ScriptData => {
                 self.script_tag_count += 1;
                 if !self.seen_script_tag {
@@ -89,6 +97,7 @@ impl Processor<FlvData> for ScriptFilterOperator {
                         self.context.name, self.script_tag_count
                     );
                     // Skip sending this to output
+                    self.context.stats.record_dropped(1);
                     Ok(())
                 }
             }
@@ -269,13 +278,11 @@ mod tests {
                 FlvData::Header(_) => {
                     in_first_segment = false; // Switch to second segment after seeing second header
                 }
-                FlvData::Tag(tag) => {
-                    if tag.tag_type == FlvTagType::ScriptData {
-                        if in_first_segment {
-                            first_segment_script_count += 1;
-                        } else {
-                            second_segment_script_count += 1;
-                        }
+                FlvData::Tag(tag) if tag.tag_type == FlvTagType::ScriptData => {
+                    if in_first_segment {
+                        first_segment_script_count += 1;
+                    } else {
+                        second_segment_script_count += 1;
                     }
                 }
                 _ => {}
@@ -35,11 +35,12 @@ use flv::data::FlvData;
 use flv::header::FlvHeader;
 use flv::tag::FlvTag;
 use pipeline_common::split_reason::{AudioCodecInfo, SplitReason, VideoCodecInfo};
-use pipeline_common::{PipelineError, Processor, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
 use std::sync::Arc;
 use tracing::{debug, info};
 
 use crate::crc32;
+use crate::utils::parse_avc_sequence_header;
 
 /// Controls how `SplitOperator` decides whether a sequence header "changed".
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -56,6 +57,13 @@ pub enum SequenceHeaderChangeMode {
     /// This reduces unnecessary splits caused by non-config fields changing
     /// (e.g. AVC composition-time bytes or legacy FLV audio header bits).
     SemanticSignature,
+    /// Compare the decoded stream parameters: video codec and dimensions from
+    /// the SPS, audio codec, sample rate and channel count.
+    ///
+    /// Only changes that players cannot seek across trigger a split, so e.g. a
+    /// new profile or level at the same resolution is kept in one file.
+    /// Falls back to `SemanticSignature` when a header cannot be parsed.
+    Parameters,
 }
 
 // Store data wrapped in Arc for efficient cloning
@@ -162,6 +170,7 @@ impl SplitOperator {
             SequenceHeaderChangeMode::SemanticSignature => {
                 Self::calculate_video_sequence_signature(tag)
             }
+            SequenceHeaderChangeMode::Parameters => Self::calculate_video_parameters_key(tag),
         }
     }
 
@@ -171,9 +180,34 @@ impl SplitOperator {
             SequenceHeaderChangeMode::SemanticSignature => {
                 Self::calculate_audio_sequence_signature(tag)
             }
+            SequenceHeaderChangeMode::Parameters => Self::calculate_audio_parameters_key(tag),
         }
     }
 
+    /// Compute a change key from the video codec and dimensions.
+    fn calculate_video_parameters_key(tag: &FlvTag) -> u32 {
+        let info = Self::extract_video_codec_info(tag, 0);
+        let (Some(width), Some(height)) = (info.width, info.height) else {
+            return Self::calculate_video_sequence_signature(tag);
+        };
+
+        let mut state = crc32::crc32_update(0, info.codec.as_bytes());
+        state = crc32::crc32_update(state, &width.to_be_bytes());
+        crc32::crc32_update(state, &height.to_be_bytes())
+    }
+
+    /// Compute a change key from the audio codec, sample rate and channel count.
+    fn calculate_audio_parameters_key(tag: &FlvTag) -> u32 {
+        let info = Self::extract_audio_codec_info(tag, 0);
+        let (Some(sample_rate), Some(channels)) = (info.sample_rate, info.channels) else {
+            return Self::calculate_audio_sequence_signature(tag);
+        };
+
+        let mut state = crc32::crc32_update(0, info.codec.as_bytes());
+        state = crc32::crc32_update(state, &sample_rate.to_be_bytes());
+        crc32::crc32_update(state, &[channels])
+    }
+
     /// Compute a "semantic signature" for video sequence headers.
     ///
     /// The old approach used a raw CRC32 of the entire tag payload (`tag.data`),
@@ -244,30 +278,32 @@ impl SplitOperator {
     /// Extract video codec configuration info from a sequence header tag.
     ///
     /// Does best-effort deep parsing to extract codec name, profile, level,
-    /// and resolution from the tag data.
-    fn extract_video_codec_info(tag: &FlvTag, signature: u32) -> VideoCodecInfo {
+    /// and resolution from the tag data. AVC configuration records are parsed
+    /// leniently, see [`parse_avc_sequence_header`].
+    pub(crate) fn extract_video_codec_info(tag: &FlvTag, signature: u32) -> VideoCodecInfo {
         use flv::av1::Av1Packet;
         use flv::avc::AvcPacket;
         use flv::hevc::HevcPacket;
         use flv::video::{EnhancedPacket, VideoData, VideoTagBody};
 
+        if let Some(config) = parse_avc_sequence_header(tag) {
+            let (profile, level) = (config.profile_indication, config.level_indication);
+            let resolution = AvcPacket::SequenceHeader(config).get_video_resolution();
+            return VideoCodecInfo {
+                codec: "AVC".to_string(),
+                profile: Some(profile),
+                level: Some(level),
+                width: resolution.as_ref().map(|r| r.width as u32),
+                height: resolution.as_ref().map(|r| r.height as u32),
+                signature,
+            };
+        }
+
         let data = tag.data.clone();
         let mut cursor = std::io::Cursor::new(data);
 
         match VideoData::demux(&mut cursor) {
             Ok(video) => match video.body {
-                VideoTagBody::Avc(AvcPacket::SequenceHeader(config)) => {
-                    let resolution =
-                        AvcPacket::SequenceHeader(config.clone()).get_video_resolution();
-                    VideoCodecInfo {
-                        codec: "AVC".to_string(),
-                        profile: Some(config.profile_indication),
-                        level: Some(config.level_indication),
-                        width: resolution.as_ref().map(|r| r.width as u32),
-                        height: resolution.as_ref().map(|r| r.height as u32),
-                        signature,
-                    }
-                }
                 VideoTagBody::Hevc(HevcPacket::SequenceStart(config)) => {
                     let resolution =
                         HevcPacket::SequenceStart(config.clone()).get_video_resolution();
@@ -292,18 +328,6 @@ impl SplitOperator {
                         signature,
                     }
                 }
-                VideoTagBody::Enhanced(EnhancedPacket::Avc(AvcPacket::SequenceHeader(config))) => {
-                    let resolution =
-                        AvcPacket::SequenceHeader(config.clone()).get_video_resolution();
-                    VideoCodecInfo {
-                        codec: "AVC".to_string(),
-                        profile: Some(config.profile_indication),
-                        level: Some(config.level_indication),
-                        width: resolution.as_ref().map(|r| r.width as u32),
-                        height: resolution.as_ref().map(|r| r.height as u32),
-                        signature,
-                    }
-                }
                 VideoTagBody::Enhanced(EnhancedPacket::Hevc(HevcPacket::SequenceStart(config))) => {
                     let resolution =
                         HevcPacket::SequenceStart(config.clone()).get_video_resolution();
@@ -321,6 +345,7 @@ impl SplitOperator {
                     let codec = tag
                         .get_video_codec_id()
                         .map(|id| format!("{id:?}"))
+                        .or_else(|| tag.get_video_fourcc().map(|fourcc| fourcc.to_string()))
                         .unwrap_or_else(|| "unknown".to_string());
                     VideoCodecInfo {
                         codec,
@@ -347,7 +372,7 @@ impl SplitOperator {
     ///
     /// For AAC, parses AudioSpecificConfig to extract sample rate and channels.
     /// For other codecs, returns the codec name only.
-    fn extract_audio_codec_info(tag: &FlvTag, signature: u32) -> AudioCodecInfo {
+    pub(crate) fn extract_audio_codec_info(tag: &FlvTag, signature: u32) -> AudioCodecInfo {
         let codec_name = tag
             .get_audio_codec_id()
             .map(|sf| format!("{sf:?}"))
@@ -407,8 +432,10 @@ impl SplitOperator {
     // Split stream and re-inject header+sequence data
     fn split_stream(
         &mut self,
+        timestamp: u32,
         output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
     ) -> Result<(), PipelineError> {
+        let mut reason = None;
         // Emit Split markers before the header re-injection.
         if self.state.buffered_video_sequence_tag
             && let Some(from) = self.state.prev_video_codec_info.take()
@@ -427,7 +454,9 @@ impl SplitOperator {
                     height: None,
                     signature: new_sig,
                 });
-            output(FlvData::Split(SplitReason::VideoCodecChange { from, to }))?;
+            let split = SplitReason::VideoCodecChange { from, to };
+            reason = Some(split.clone());
+            output(FlvData::Split(split))?;
         }
         if self.state.buffered_audio_sequence_tag
             && let Some(from) = self.state.prev_audio_codec_info.take()
@@ -444,7 +473,11 @@ impl SplitOperator {
                     channels: None,
                     signature: new_sig,
                 });
-            output(FlvData::Split(SplitReason::AudioCodecChange { from, to }))?;
+            let split = SplitReason::AudioCodecChange { from, to };
+            if reason.is_none() {
+                reason = Some(split.clone());
+            }
+            output(FlvData::Split(split))?;
         }
 
         // Note on timestamp handling:
@@ -470,7 +503,11 @@ impl SplitOperator {
         self.state.buffered_audio_sequence_tag = false;
         self.state.buffered_video_sequence_tag = false;
         self.state.has_emitted_media_tag = false;
-        info!("{} Stream split", self.context.name);
+        self.context.emit(PipelineEvent::SplitOccurred {
+            operator: self.name(),
+            reason,
+            offset: Some(timestamp as u64),
+        });
         Ok(())
     }
 
@@ -590,7 +627,7 @@ impl Processor<FlvData> for SplitOperator {
                     }
 
                     // First regular tag after a pending change: split now, then emit the tag.
-                    self.split_stream(output)?;
+                    self.split_stream(tag.timestamp_ms, output)?;
                     self.state.has_emitted_media_tag = true;
                     return output(FlvData::Tag(tag));
                 }
@@ -613,6 +650,7 @@ impl Processor<FlvData> for SplitOperator {
                             "{} Dropping duplicate video sequence header (sig: {:x})",
                             self.context.name, sig
                         );
+                        self.context.stats.record_duplicate();
                         self.state.video_sequence_tag = Some(tag);
                         self.state.video_sig = Some(sig);
                         return Ok(());
@@ -667,6 +705,7 @@ impl Processor<FlvData> for SplitOperator {
                             "{} Dropping duplicate audio sequence header (sig: {:x})",
                             self.context.name, sig
                         );
+                        self.context.stats.record_duplicate();
                         self.state.audio_sequence_tag = Some(tag);
                         self.state.audio_sig = Some(sig);
                         return Ok(());
@@ -706,7 +745,7 @@ impl Processor<FlvData> for SplitOperator {
 
                 // Regular media tag: if a change was detected earlier, split before emitting.
                 if self.state.changed {
-                    self.split_stream(output)?;
+                    self.split_stream(tag.timestamp_ms, output)?;
                 }
                 self.state.has_emitted_media_tag = true;
                 output(FlvData::Tag(tag))
@@ -1332,6 +1371,139 @@ mod tests {
         );
     }
 
+    /// Baseline-profile SPS for 1920x1080 (1088 coded, cropped by 8)
+    const SPS_1080P: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+    /// Baseline-profile SPS for 1280x720
+    const SPS_720P: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0x40, 0x16, 0xe4];
+
+    fn create_avc_sequence_header(timestamp: u32, sps: &[u8]) -> FlvData {
+        let mut data = vec![
+            0x17, // Keyframe (1) + AVC (7)
+            0x00, // AVC sequence header
+            0x00, 0x00, 0x00, // Composition time
+            0x01, // configurationVersion
+            sps[1], sps[2], sps[3], // profile, compatibility, level
+            0xff,   // lengthSizeMinusOne = 3
+            0xe1,   // one SPS
+        ];
+        data.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        data.extend_from_slice(sps);
+        data.extend_from_slice(&[0x01, 0x00, 0x04, 0x68, 0xce, 0x38, 0x80]);
+        crate::test_utils::create_test_tag(flv::tag::FlvTagType::Video, timestamp, data)
+    }
+
+    #[test]
+    fn test_parameters_mode_splits_on_resolution_change() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = SplitOperator::with_config(
+            context.clone(),
+            SequenceHeaderChangeMode::Parameters,
+            false,
+        );
+        let mut output_items = Vec::new();
+
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        let input = [
+            create_test_header(),
+            crate::test_utils::create_script_tag(0, false),
+            create_avc_sequence_header(0, SPS_1080P),
+            create_video_tag(0, true),
+            create_video_tag(33, false),
+            // Broadcaster switches to 720p
+            create_avc_sequence_header(66, SPS_720P),
+            create_video_tag(66, true),
+            create_video_tag(100, false),
+        ];
+        for item in input {
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+        operator.finish(&context, &mut output_fn).unwrap();
+
+        let split_at = output_items
+            .iter()
+            .position(|item| matches!(item, FlvData::Split(_)))
+            .expect("Expected a split marker");
+        match &output_items[split_at] {
+            FlvData::Split(SplitReason::VideoCodecChange { from, to }) => {
+                assert_eq!((from.width, from.height), (Some(1920), Some(1080)));
+                assert_eq!((to.width, to.height), (Some(1280), Some(720)));
+                assert_eq!(from.codec, "AVC");
+            }
+            other => panic!("Expected VideoCodecChange, got {other:?}"),
+        }
+
+        // The new segment is self-contained: header, metadata and the 720p config
+        // come before the first 720p frame.
+        let segment = &output_items[split_at + 1..];
+        assert!(matches!(segment[0], FlvData::Header(_)));
+        assert!(matches!(&segment[1], FlvData::Tag(tag) if tag.is_script_tag()));
+        match &segment[2] {
+            FlvData::Tag(tag) => {
+                assert!(tag.is_video_sequence_header());
+                assert!(tag.data.ends_with(&[0x68, 0xce, 0x38, 0x80]));
+                assert!(
+                    tag.data
+                        .windows(SPS_720P.len())
+                        .any(|window| window == SPS_720P)
+                );
+            }
+            other => panic!("Expected video sequence header, got {other:?}"),
+        }
+        assert!(matches!(&segment[3], FlvData::Tag(tag) if tag.timestamp_ms == 66));
+
+        let header_count = output_items
+            .iter()
+            .filter(|item| matches!(item, FlvData::Header(_)))
+            .count();
+        assert_eq!(header_count, 2);
+    }
+
+    #[test]
+    fn test_parameters_mode_ignores_level_change_at_same_resolution() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = SplitOperator::with_config(
+            context.clone(),
+            SequenceHeaderChangeMode::Parameters,
+            false,
+        );
+        let mut output_items = Vec::new();
+
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        // Same dimensions, level 3.1 -> 4.0
+        let mut sps_level_40 = SPS_1080P.to_vec();
+        sps_level_40[3] = 0x28;
+
+        let input = [
+            create_test_header(),
+            create_avc_sequence_header(0, SPS_1080P),
+            create_video_tag(0, true),
+            create_avc_sequence_header(33, &sps_level_40),
+            create_video_tag(33, true),
+        ];
+        for item in input {
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+
+        assert!(
+            !output_items
+                .iter()
+                .any(|item| matches!(item, FlvData::Split(_)))
+        );
+        let header_count = output_items
+            .iter()
+            .filter(|item| matches!(item, FlvData::Header(_)))
+            .count();
+        assert_eq!(header_count, 1);
+    }
+
     #[test]
     fn test_split_marker_emitted_on_video_codec_change() {
         let context = StreamerContext::arc_new(CancellationToken::new());
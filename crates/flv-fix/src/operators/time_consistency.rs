@@ -27,6 +27,10 @@
 //! - `Continuous`: Maintains an ever-increasing timeline across all segments
 //! - `Reset`: Resets the timeline to zero at each split point
 //!
+//! When a download resumes into an existing recording, the operator can be given the
+//! timestamp the recording ended at, and the first segment then continues from it
+//! regardless of the mode.
+//!
 //! ## Usage in Pipeline
 //!
 //! This operator provides basic timestamp continuity across stream splits, but does not
@@ -111,6 +115,8 @@ pub struct TimeConsistencyOperator {
     context: Arc<StreamerContext>,
     continuity_mode: ContinuityMode,
     state: TimelineState,
+    /// Timestamp the first segment continues from when resuming a recording
+    resume_timestamp: Option<u32>,
 }
 
 impl TimeConsistencyOperator {
@@ -120,9 +126,22 @@ impl TimeConsistencyOperator {
             context,
             continuity_mode,
             state: TimelineState::new(),
+            resume_timestamp: None,
         }
     }
 
+    /// Continue the timeline of an existing recording that ended at `timestamp_ms`
+    pub fn with_resume_timestamp(mut self, timestamp_ms: Option<u32>) -> Self {
+        self.resume_timestamp = timestamp_ms;
+        self
+    }
+
+    /// Timestamp the current segment continues from, if it resumes a recording
+    fn segment_resume_timestamp(&self) -> Option<u32> {
+        self.resume_timestamp
+            .filter(|_| self.state.segment_count <= 1)
+    }
+
     fn apply_offset(timestamp_ms: u32, offset: i64) -> u32 {
         let expected = timestamp_ms as i128 + offset as i128;
         if expected <= 0 {
@@ -192,29 +211,33 @@ impl Processor<FlvData> for TimeConsistencyOperator {
 
                 // For normal media tags, handle timestamp adjustment
                 if self.state.new_segment {
-                    // For sequence headers, always set timestamp to 0
-                    if tag.is_video_sequence_header() || tag.is_audio_sequence_header() {
+                    // A resumed recording already has its start, keep appended tags monotonic
+                    let segment_start = self.segment_resume_timestamp().unwrap_or(0);
+
+                    // For sequence headers, always set timestamp to the segment start
+                    let view = tag.view();
+                    if view.is_video_sequence_header() || view.is_audio_sequence_header() {
                         // Save original timestamp for debugging
                         let original = tag.timestamp_ms;
-                        if original != 0 {
+                        if original != segment_start {
                             debug!(
-                                "{} Reset sequence header timestamp from {}ms to 0ms",
-                                self.context.name, original
+                                "{} Reset sequence header timestamp from {}ms to {}ms",
+                                self.context.name, original, segment_start
                             );
-                            // Set timestamp to 0
-                            tag.timestamp_ms = 0;
+                            tag.timestamp_ms = segment_start;
                         }
 
                         return output(FlvData::Tag(tag));
                     } else if tag.is_script_tag() {
-                        // Keep script metadata at timestamp 0 for maximum player compatibility.
-                        if tag.timestamp_ms != 0 {
+                        // Keep script metadata at the segment start (0 unless resuming) for
+                        // maximum player compatibility.
+                        if tag.timestamp_ms != segment_start {
                             debug!(
-                                "{} Reset script data timestamp: {}ms -> 0ms",
-                                self.context.name, original_timestamp
+                                "{} Reset script data timestamp: {}ms -> {}ms",
+                                self.context.name, original_timestamp, segment_start
                             );
                         }
-                        tag.timestamp_ms = 0;
+                        tag.timestamp_ms = segment_start;
                         return output(FlvData::Tag(tag));
                     }
 
@@ -226,7 +249,17 @@ impl Processor<FlvData> for TimeConsistencyOperator {
                             self.context.name, self.state.segment_count, tag.timestamp_ms
                         );
 
-                        if self.state.segment_count > 1 && self.state.needs_offset_calculation {
+                        if let Some(resume_timestamp) = self.segment_resume_timestamp() {
+                            // Continue the existing recording where it ended
+                            self.state.timestamp_offset =
+                                resume_timestamp as i64 - tag.timestamp_ms as i64;
+                            debug!(
+                                "{} Resuming timeline at {}ms: offset = {}ms",
+                                self.context.name, resume_timestamp, self.state.timestamp_offset
+                            );
+                        } else if self.state.segment_count > 1
+                            && self.state.needs_offset_calculation
+                        {
                             self.calculate_timestamp_offset();
                         } else if self.state.segment_count == 1
                             && self.continuity_mode == ContinuityMode::Reset
@@ -278,7 +311,9 @@ impl Processor<FlvData> for TimeConsistencyOperator {
 #[cfg(test)]
 mod tests {
 
-    use crate::test_utils::{create_audio_tag, create_test_header, create_video_tag};
+    use crate::test_utils::{
+        create_audio_tag, create_test_header, create_video_sequence_header, create_video_tag,
+    };
     use pipeline_common::{CancellationToken, StreamerContext, init_test_tracing};
 
     use super::*;
@@ -693,4 +728,39 @@ mod tests {
             );
         }
     }
+    #[test]
+    fn test_resume_continues_existing_recording() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = TimeConsistencyOperator::new(context.clone(), ContinuityMode::Reset)
+            .with_resume_timestamp(Some(5000));
+        let mut output_items = Vec::new();
+
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        // The restarted stream begins on a new timeline
+        for item in [
+            create_test_header(),
+            create_video_sequence_header(0, 1),
+            create_video_tag(200, true),
+            create_audio_tag(210),
+            create_video_tag(240, false),
+            // A later split is handled as usual
+            create_test_header(),
+            create_video_tag(100, true),
+        ] {
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+
+        let timestamps: Vec<u32> = output_items
+            .iter()
+            .filter_map(|item| match item {
+                FlvData::Tag(tag) => Some(tag.timestamp_ms),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(timestamps, vec![5000, 5000, 5010, 5040, 0]);
+    }
 }
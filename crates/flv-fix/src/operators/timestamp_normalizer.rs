@@ -0,0 +1,498 @@
+//! # TimestampNormalizerOperator
+//!
+//! The `TimestampNormalizerOperator` keeps per-track timestamps monotonic when the
+//! source stream wraps or briefly jumps backwards.
+//!
+//! ## Purpose
+//!
+//! Streams from some CDNs exhibit timestamp faults that the continuity operators
+//! treat as a new timeline, which players render as a stutter:
+//!
+//! 1. The timestamp wraps around, either at the full 32 bits (24-bit field plus the
+//!    extension byte) or at 24 bits when the encoder never sets the extension
+//! 2. The timestamp steps back by a few milliseconds after an encoder reconnect
+//! 3. One track jumps far back in time while the other keeps going
+//!
+//! ## Operation
+//!
+//! Audio and video are tracked independently. For each tag the operator:
+//! - Adds the track's running offset to the original timestamp
+//! - On a wrap-around, advances the offset by the wrap period so time continues. When
+//!   the continued timestamp no longer fits in 32 bits, the track restarts from the
+//!   wrapped timestamp instead, a new timeline for the continuity operators downstream
+//! - On a backward step within the configured threshold, reuses the previous timestamp
+//! - On a larger backward jump, re-bases the track to continue from its previous
+//!   timestamp, the same way `ContinuityMode::Continuous` joins segments, and logs
+//!   the jump size
+//!
+//! Offsets are reset whenever a new FLV header starts a segment.
+//!
+//! ## License
+//!
+//! MIT License
+//!
+//! ## Authors
+//!
+//! - hua0512
+//!
+
+use flv::data::FlvData;
+use flv::tag::{FlvTag, FlvTagType};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
+use std::sync::Arc;
+use tracing::{info, trace};
+
+/// Wrap period of an FLV timestamp without the extension byte
+const WRAP_24_BIT: i64 = 1 << 24;
+
+/// Wrap period of a full FLV timestamp
+const WRAP_32_BIT: i64 = 1 << 32;
+
+/// Configuration options for the TimestampNormalizerOperator
+#[derive(Debug, Clone)]
+pub struct TimestampNormalizerConfig {
+    /// Backward steps up to this many milliseconds reuse the previous timestamp.
+    /// Defaults to 5ms.
+    pub negative_delta_threshold_ms: u32,
+
+    /// How close to the wrap point, in milliseconds, the previous and current
+    /// timestamps must be for a backward step to count as a wrap-around.
+    /// Defaults to 10 seconds.
+    pub rollover_window_ms: u32,
+}
+
+impl Default for TimestampNormalizerConfig {
+    fn default() -> Self {
+        Self {
+            negative_delta_threshold_ms: 5,
+            rollover_window_ms: 10_000,
+        }
+    }
+}
+
+/// Timestamp state of a single track
+#[derive(Debug, Default)]
+struct TrackState {
+    /// Offset added to original timestamps
+    offset: i64,
+
+    /// Last original timestamp seen
+    last_original: Option<u32>,
+
+    /// Last timestamp emitted
+    last_output: u32,
+}
+
+/// Operator that repairs timestamp rollovers and backward steps per track
+pub struct TimestampNormalizerOperator {
+    context: Arc<StreamerContext>,
+    config: TimestampNormalizerConfig,
+    audio: TrackState,
+    video: TrackState,
+    rollover_count: u32,
+    clamp_count: u32,
+    jump_count: u32,
+}
+
+impl TimestampNormalizerOperator {
+    /// Create a new TimestampNormalizerOperator with the specified configuration
+    pub fn new(context: Arc<StreamerContext>, config: TimestampNormalizerConfig) -> Self {
+        Self {
+            context,
+            config,
+            audio: TrackState::default(),
+            video: TrackState::default(),
+            rollover_count: 0,
+            clamp_count: 0,
+            jump_count: 0,
+        }
+    }
+
+    /// Returns the wrap period if `previous` -> `current` looks like a wrap-around
+    fn rollover_period(&self, previous: u32, current: u32) -> Option<i64> {
+        let window = self.config.rollover_window_ms as i64;
+        if current as i64 >= window {
+            return None;
+        }
+        [WRAP_24_BIT, WRAP_32_BIT]
+            .into_iter()
+            .find(|&wrap| (previous as i64) < wrap && previous as i64 >= wrap - window)
+    }
+
+    fn normalize(&mut self, tag: &mut FlvTag) {
+        let original = tag.timestamp_ms;
+        let (name, mut track) = match tag.tag_type {
+            FlvTagType::Audio => ("audio", std::mem::take(&mut self.audio)),
+            FlvTagType::Video => ("video", std::mem::take(&mut self.video)),
+            _ => return,
+        };
+
+        let Some(last_original) = track.last_original else {
+            track.last_original = Some(original);
+            track.last_output = original;
+            tag.timestamp_ms = track.last_output;
+            self.put_track(tag.tag_type, track);
+            return;
+        };
+
+        let mut corrected = original as i64 + track.offset;
+        if corrected < track.last_output as i64
+            && let Some(wrap) = self.rollover_period(last_original, original)
+        {
+            self.rollover_count += 1;
+            track.offset += wrap;
+            corrected += wrap;
+            self.context.emit(PipelineEvent::RepairApplied {
+                operator: self.name(),
+                kind: "timestamp_rollover",
+                offset: Some(original as u64),
+                detail: format!(
+                    "{name} timestamp wrapped around {wrap}ms: {last_original}ms -> {original}ms"
+                ),
+            });
+        }
+
+        if corrected > u32::MAX as i64 {
+            // Time cannot continue past 32 bits, start a new timeline
+            self.context.emit(PipelineEvent::RepairApplied {
+                operator: self.name(),
+                kind: "timestamp_restart",
+                offset: Some(original as u64),
+                detail: format!(
+                    "{name} timestamp {corrected}ms does not fit in 32 bits, restarting from \
+                     {original}ms"
+                ),
+            });
+            track.offset = 0;
+            track.last_original = Some(original);
+            track.last_output = original;
+            tag.timestamp_ms = original;
+            self.put_track(tag.tag_type, track);
+            return;
+        }
+
+        let backward = track.last_output as i64 - corrected;
+        if backward > 0 {
+            if backward <= self.config.negative_delta_threshold_ms as i64 {
+                // Small encoder hiccup: hold the previous timestamp
+                self.clamp_count += 1;
+                trace!(
+                    "{} {} timestamp stepped back {}ms, reusing {}ms",
+                    self.context.name, name, backward, track.last_output
+                );
+            } else {
+                // Continue from where the track left off
+                self.jump_count += 1;
+                track.offset = track.last_output as i64 - original as i64;
+                self.context.emit(PipelineEvent::RepairApplied {
+                    operator: self.name(),
+                    kind: "timestamp_jump",
+                    offset: Some(original as u64),
+                    detail: format!(
+                        "{name} timestamp jumped back {backward}ms ({last_original}ms -> \
+                         {original}ms), continuing from {}ms",
+                        track.last_output
+                    ),
+                });
+            }
+            corrected = track.last_output as i64;
+        }
+
+        track.last_original = Some(original);
+        track.last_output = corrected.clamp(0, u32::MAX as i64) as u32;
+        tag.timestamp_ms = track.last_output;
+        self.put_track(tag.tag_type, track);
+    }
+
+    fn put_track(&mut self, tag_type: FlvTagType, track: TrackState) {
+        match tag_type {
+            FlvTagType::Audio => self.audio = track,
+            FlvTagType::Video => self.video = track,
+            _ => {}
+        }
+    }
+}
+
+impl Processor<FlvData> for TimestampNormalizerOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: FlvData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+        match input {
+            FlvData::Header(_) => {
+                // A new segment starts its own timeline
+                self.audio = TrackState::default();
+                self.video = TrackState::default();
+                output(input)
+            }
+            FlvData::Tag(mut tag) => {
+                self.normalize(&mut tag);
+                output(FlvData::Tag(tag))
+            }
+            _ => output(input),
+        }
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        info!(
+            "{} Timestamp normalizer completed: {} rollovers, {} clamped steps, {} backward jumps",
+            self.context.name, self.rollover_count, self.clamp_count, self.jump_count
+        );
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "TimestampNormalizerOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        create_audio_tag, create_script_tag, create_test_header, create_video_tag,
+        extract_timestamps,
+    };
+    use pipeline_common::{CancellationToken, init_test_tracing};
+
+    fn run(config: TimestampNormalizerConfig, input: Vec<FlvData>) -> Vec<FlvData> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = TimestampNormalizerOperator::new(context.clone(), config);
+        let mut output_items = Vec::new();
+        let mut output_fn = |item: FlvData| -> Result<(), PipelineError> {
+            output_items.push(item);
+            Ok(())
+        };
+
+        for item in input {
+            operator.process(&context, item, &mut output_fn).unwrap();
+        }
+        operator.finish(&context, &mut output_fn).unwrap();
+        output_items
+    }
+
+    #[test]
+    fn test_monotonic_stream_unchanged() {
+        init_test_tracing!();
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_script_tag(0, false),
+                create_video_tag(0, true),
+                create_audio_tag(10),
+                create_video_tag(33, false),
+                create_audio_tag(33),
+            ],
+        );
+
+        assert_eq!(extract_timestamps(&output), vec![0, 0, 10, 33, 33]);
+    }
+
+    #[test]
+    fn test_32_bit_rollover() {
+        init_test_tracing!();
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(u32::MAX - 66, true),
+                create_audio_tag(u32::MAX - 50),
+                create_video_tag(u32::MAX - 33, false),
+                create_video_tag(0, false),
+                create_audio_tag(u32::MAX - 27),
+                create_video_tag(33, false),
+                create_audio_tag(u32::MAX - 4),
+                create_video_tag(66, false),
+                create_audio_tag(19),
+                // Later faults are repaired on the new timeline
+                create_video_tag(64, false),
+                create_video_tag(100, false),
+            ],
+        );
+
+        // Each track restarts from its wrapped timestamp and keeps increasing
+        assert_eq!(
+            extract_timestamps(&output),
+            vec![
+                u32::MAX - 66,
+                u32::MAX - 50,
+                u32::MAX - 33,
+                0,
+                u32::MAX - 27,
+                33,
+                u32::MAX - 4,
+                66,
+                19,
+                66,
+                100
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offset_past_32_bits_restarts_timeline() {
+        init_test_tracing!();
+        let wrap = 1u32 << 24;
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(wrap - 33, true),
+                create_video_tag(0, false),
+                // The offset of the 24-bit wrap pushes these past 32 bits
+                create_video_tag(u32::MAX - 66, false),
+                create_video_tag(u32::MAX - 33, false),
+            ],
+        );
+
+        assert_eq!(
+            extract_timestamps(&output),
+            vec![wrap - 33, wrap, u32::MAX - 66, u32::MAX - 33]
+        );
+    }
+
+    #[test]
+    fn test_24_bit_rollover() {
+        init_test_tracing!();
+        let wrap = 1u32 << 24;
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(wrap - 40, true),
+                create_audio_tag(wrap - 20),
+                create_video_tag(wrap - 7, false),
+                create_audio_tag(3),
+                create_video_tag(26, false),
+                create_audio_tag(26),
+            ],
+        );
+
+        assert_eq!(
+            extract_timestamps(&output),
+            vec![
+                wrap - 40,
+                wrap - 20,
+                wrap - 7,
+                wrap + 3,
+                wrap + 26,
+                wrap + 26
+            ]
+        );
+    }
+
+    #[test]
+    fn test_small_negative_delta_is_clamped() {
+        init_test_tracing!();
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(1000, true),
+                create_video_tag(1033, false),
+                create_video_tag(1030, false),
+                create_video_tag(1066, false),
+            ],
+        );
+
+        // The 3ms step back reuses the previous timestamp and later tags are not shifted
+        assert_eq!(extract_timestamps(&output), vec![1000, 1033, 1033, 1066]);
+    }
+
+    #[test]
+    fn test_negative_delta_threshold_is_configurable() {
+        init_test_tracing!();
+        let config = TimestampNormalizerConfig {
+            negative_delta_threshold_ms: 1,
+            ..Default::default()
+        };
+        let output = run(
+            config,
+            vec![
+                create_test_header(),
+                create_video_tag(1000, true),
+                create_video_tag(1033, false),
+                create_video_tag(1030, false),
+                create_video_tag(1063, false),
+            ],
+        );
+
+        // 3ms exceeds the threshold, so the track is re-based instead
+        assert_eq!(extract_timestamps(&output), vec![1000, 1033, 1033, 1066]);
+    }
+
+    #[test]
+    fn test_large_backward_jump_continues_timeline() {
+        init_test_tracing!();
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(50_000, true),
+                create_video_tag(50_033, false),
+                create_video_tag(200, true),
+                create_video_tag(233, false),
+            ],
+        );
+
+        assert_eq!(
+            extract_timestamps(&output),
+            vec![50_000, 50_033, 50_033, 50_066]
+        );
+    }
+
+    #[test]
+    fn test_only_audio_track_jumps() {
+        init_test_tracing!();
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(10_000, true),
+                create_audio_tag(10_010),
+                create_video_tag(10_033, false),
+                create_audio_tag(10_033),
+                // Audio restarts from zero, video carries on
+                create_video_tag(10_066, false),
+                create_audio_tag(0),
+                create_video_tag(10_100, false),
+                create_audio_tag(23),
+                create_video_tag(10_133, false),
+                create_audio_tag(46),
+            ],
+        );
+
+        assert_eq!(
+            extract_timestamps(&output),
+            vec![
+                10_000, 10_010, 10_033, 10_033, 10_066, 10_033, 10_100, 10_056, 10_133, 10_079
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_resets_tracks() {
+        init_test_tracing!();
+        let output = run(
+            TimestampNormalizerConfig::default(),
+            vec![
+                create_test_header(),
+                create_video_tag(5000, true),
+                create_test_header(),
+                create_video_tag(0, true),
+            ],
+        );
+
+        assert_eq!(extract_timestamps(&output), vec![5000, 0]);
+    }
+}
@@ -48,7 +48,7 @@ use amf0::Amf0Value;
 use flv::data::FlvData;
 use flv::script::ScriptData;
 use flv::tag::{FlvTag, FlvTagType};
-use pipeline_common::{PipelineError, Processor, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
 use std::cmp::max;
 use std::f64;
 use std::sync::Arc;
@@ -232,20 +232,14 @@ impl TimingState {
 
         for (key, value) in props.iter() {
             match key.as_ref() {
-                "fps" => {
-                    if fps_value.is_none() {
-                        fps_value = Some(value);
-                    }
+                "fps" if fps_value.is_none() => {
+                    fps_value = Some(value);
                 }
-                "framerate" => {
-                    if framerate_value.is_none() {
-                        framerate_value = Some(value);
-                    }
+                "framerate" if framerate_value.is_none() => {
+                    framerate_value = Some(value);
                 }
-                "audiosamplerate" => {
-                    if audio_rate_value.is_none() {
-                        audio_rate_value = Some(value);
-                    }
+                "audiosamplerate" if audio_rate_value.is_none() => {
+                    audio_rate_value = Some(value);
                 }
                 _ => {}
             }
@@ -557,13 +551,17 @@ impl Processor<FlvData> for TimingRepairOperator {
                     self.state.rebound_count += 1;
                     let new_delta = self.state.calculate_delta_correction(&tag);
 
-                    debug!(
-                        "{} TimingRepair: Timestamp rebound detected: {}ms, last ts: {}ms,  would go back in time - applying correction delta: {}ms",
-                        self.context.name,
-                        tag.timestamp_ms,
-                        self.state.last_tag.as_ref().map_or(0, |t| t.timestamp_ms),
-                        new_delta
-                    );
+                    self.context.emit(PipelineEvent::RepairApplied {
+                        operator: self.name(),
+                        kind: "timestamp_rebound",
+                        offset: Some(tag.timestamp_ms as u64),
+                        detail: format!(
+                            "Timestamp rebound detected: {}ms, last ts: {}ms, would go back in \
+                             time - applying correction delta: {new_delta}ms",
+                            tag.timestamp_ms,
+                            self.state.last_tag.as_ref().map_or(0, |t| t.timestamp_ms),
+                        ),
+                    });
 
                     self.state.delta = new_delta;
                     need_correction = true;
@@ -573,13 +571,17 @@ impl Processor<FlvData> for TimingRepairOperator {
                     self.state.discontinuity_count += 1;
                     let new_delta = self.state.calculate_delta_correction(&tag);
 
-                    warn!(
-                        "{} TimingRepair: Timestamp discontinuity detected: {}ms, last ts: {}ms, applying correction delta: {}ms",
-                        self.context.name,
-                        tag.timestamp_ms,
-                        self.state.last_tag.as_ref().map_or(0, |t| t.timestamp_ms),
-                        new_delta
-                    );
+                    self.context.emit(PipelineEvent::RepairApplied {
+                        operator: self.name(),
+                        kind: "timestamp_discontinuity",
+                        offset: Some(tag.timestamp_ms as u64),
+                        detail: format!(
+                            "Timestamp discontinuity detected: {}ms, last ts: {}ms, applying \
+                             correction delta: {new_delta}ms",
+                            tag.timestamp_ms,
+                            self.state.last_tag.as_ref().map_or(0, |t| t.timestamp_ms),
+                        ),
+                    });
 
                     self.state.delta = new_delta;
                     need_correction = true;
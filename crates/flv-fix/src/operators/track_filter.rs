@@ -0,0 +1,372 @@
+//! # Track Filter Operator
+//!
+//! Extracts a single media track from an FLV stream, for audio-only or video-only
+//! recordings.
+//!
+//! ## How it Works
+//!
+//! The operator:
+//!
+//! 1. Rewrites every FLV header so that only the kept track is flagged
+//! 2. Drops all tags of the other media type
+//! 3. Removes the dropped track's fields (`videocodecid`, `audiosamplerate`, ...) from
+//!    `onMetaData` and clears its `hasVideo`/`hasAudio` flag
+//! 4. Re-sends the last sequence header of the kept track when a file would otherwise
+//!    start with media it cannot decode
+//!
+//! Script data other than `onMetaData` is forwarded unchanged.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use amf0::{Amf0Encoder, Amf0Value};
+use bytes::Bytes;
+use flv::data::FlvData;
+use flv::header::FlvHeader;
+use flv::script::ScriptData;
+use flv::tag::FlvTag;
+use pipeline_common::{PipelineError, Processor, StreamerContext};
+use tracing::{debug, info, warn};
+
+use crate::constants::*;
+
+/// onMetaData fields describing the video track
+const VIDEO_METADATA_FIELDS: &[&str] = &[
+    METADATA_VIDEOCODECID,
+    METADATA_VIDEODATARATE,
+    METADATA_VIDEOSIZE,
+    METADATA_WIDTH,
+    METADATA_HEIGHT,
+    METADATA_FRAMERATE,
+    METADATA_HAS_KEYFRAMES,
+    METADATA_KEYFRAMES,
+    METADATA_LASTKEYFRAMELOCATION,
+    METADATA_LASTKEYFRAMETIMESTAMP,
+];
+
+/// onMetaData fields describing the audio track
+const AUDIO_METADATA_FIELDS: &[&str] = &[
+    METADATA_AUDIOCODECID,
+    METADATA_AUDIODATARATE,
+    METADATA_AUDIOSIZE,
+    METADATA_AUDIOSAMPLERATE,
+    METADATA_AUDIOSAMPLESIZE,
+    METADATA_STEREO,
+];
+
+/// Media track kept by the [`TrackFilterOperator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackFilter {
+    /// Keep audio tags, drop video tags
+    AudioOnly,
+    /// Keep video tags, drop audio tags
+    VideoOnly,
+}
+
+impl TrackFilter {
+    /// Whether tags of this kind are kept
+    fn keeps(&self, tag: &FlvTag) -> bool {
+        match self {
+            TrackFilter::AudioOnly => !tag.is_video_tag(),
+            TrackFilter::VideoOnly => !tag.is_audio_tag(),
+        }
+    }
+
+    fn is_sequence_header(&self, tag: &FlvTag) -> bool {
+        match self {
+            TrackFilter::AudioOnly => tag.is_audio_sequence_header(),
+            TrackFilter::VideoOnly => tag.is_video_sequence_header(),
+        }
+    }
+
+    fn is_media(&self, tag: &FlvTag) -> bool {
+        match self {
+            TrackFilter::AudioOnly => tag.is_audio_tag(),
+            TrackFilter::VideoOnly => tag.is_video_tag(),
+        }
+    }
+
+    /// onMetaData fields of the dropped track
+    fn dropped_fields(&self) -> &'static [&'static str] {
+        match self {
+            TrackFilter::AudioOnly => VIDEO_METADATA_FIELDS,
+            TrackFilter::VideoOnly => AUDIO_METADATA_FIELDS,
+        }
+    }
+
+    /// onMetaData flag of the dropped track
+    fn dropped_flag(&self) -> &'static str {
+        match self {
+            TrackFilter::AudioOnly => METADATA_HAS_VIDEO,
+            TrackFilter::VideoOnly => METADATA_HAS_AUDIO,
+        }
+    }
+}
+
+/// Operator keeping a single media track of an FLV stream
+pub struct TrackFilterOperator {
+    context: Arc<StreamerContext>,
+    filter: TrackFilter,
+    /// Last sequence header of the kept track
+    sequence_header: Option<FlvTag>,
+    /// Whether a sequence header was forwarded since the last FLV header
+    sequence_header_sent: bool,
+    dropped_tags: u64,
+}
+
+impl TrackFilterOperator {
+    pub fn new(context: Arc<StreamerContext>, filter: TrackFilter) -> Self {
+        Self {
+            context,
+            filter,
+            sequence_header: None,
+            sequence_header_sent: false,
+            dropped_tags: 0,
+        }
+    }
+
+    fn filter_header(&self, header: FlvHeader) -> FlvHeader {
+        let (has_audio, has_video) = match self.filter {
+            TrackFilter::AudioOnly => (true, false),
+            TrackFilter::VideoOnly => (false, true),
+        };
+        FlvHeader {
+            has_audio,
+            has_video,
+            ..header
+        }
+    }
+
+    /// Remove the dropped track from an onMetaData tag, other script tags are kept as is.
+    fn filter_metadata(&self, tag: FlvTag) -> FlvTag {
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        let script = match ScriptData::demux(&mut cursor) {
+            Ok(script) if script.name == AMF0_ON_METADATA => script,
+            Ok(_) => return tag,
+            Err(e) => {
+                warn!(
+                    "{} Failed to parse script tag, forwarding it unchanged: {}",
+                    self.context.name, e
+                );
+                return tag;
+            }
+        };
+
+        let mut buffer = Vec::with_capacity(tag.data.len());
+        let encoded = Amf0Encoder::encode_string(&mut buffer, AMF0_ON_METADATA).and_then(|_| {
+            script
+                .data
+                .iter()
+                .try_for_each(|value| Amf0Encoder::encode(&mut buffer, &self.filter_value(value)))
+        });
+        if let Err(e) = encoded {
+            warn!(
+                "{} Failed to encode filtered onMetaData, forwarding it unchanged: {}",
+                self.context.name, e
+            );
+            return tag;
+        }
+
+        FlvTag {
+            data: Bytes::from(buffer),
+            ..tag
+        }
+    }
+
+    fn filter_value<'a>(&self, value: &Amf0Value<'a>) -> Amf0Value<'a> {
+        match value {
+            Amf0Value::Object(props) => Amf0Value::Object(self.filter_properties(props)),
+            Amf0Value::EcmaArray(props) => Amf0Value::EcmaArray(self.filter_properties(props)),
+            other => other.clone(),
+        }
+    }
+
+    fn filter_properties<'a>(
+        &self,
+        props: &[(Cow<'a, str>, Amf0Value<'a>)],
+    ) -> Cow<'a, [(Cow<'a, str>, Amf0Value<'a>)]> {
+        props
+            .iter()
+            .filter(|(key, _)| !self.filter.dropped_fields().contains(&key.as_ref()))
+            .map(|(key, value)| {
+                if key == self.filter.dropped_flag() {
+                    (key.clone(), Amf0Value::Boolean(false))
+                } else {
+                    (key.clone(), value.clone())
+                }
+            })
+            .collect()
+    }
+}
+
+impl Processor<FlvData> for TrackFilterOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: FlvData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        match input {
+            FlvData::Header(header) => {
+                self.sequence_header_sent = false;
+                output(FlvData::Header(self.filter_header(header)))
+            }
+            FlvData::Tag(tag) if !self.filter.keeps(&tag) => {
+                self.dropped_tags += 1;
+                self.context.stats.record_dropped(1);
+                Ok(())
+            }
+            FlvData::Tag(tag) if tag.is_script_tag() => {
+                output(FlvData::Tag(self.filter_metadata(tag)))
+            }
+            FlvData::Tag(tag) if self.filter.is_sequence_header(&tag) => {
+                self.sequence_header = Some(tag.clone());
+                self.sequence_header_sent = true;
+                output(FlvData::Tag(tag))
+            }
+            FlvData::Tag(tag) if self.filter.is_media(&tag) => {
+                if !self.sequence_header_sent
+                    && let Some(sequence_header) = &self.sequence_header
+                {
+                    debug!(
+                        "{} Re-sending sequence header before media at {}ms",
+                        self.context.name, tag.timestamp_ms
+                    );
+                    self.sequence_header_sent = true;
+                    output(FlvData::Tag(FlvTag {
+                        timestamp_ms: tag.timestamp_ms,
+                        ..sequence_header.clone()
+                    }))?;
+                }
+                output(FlvData::Tag(tag))
+            }
+            other => output(other),
+        }
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if self.dropped_tags > 0 {
+            info!(
+                "{} Track filter ({:?}) dropped {} tags",
+                self.context.name, self.filter, self.dropped_tags
+            );
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "TrackFilterOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        create_audio_sequence_header, create_audio_tag, create_script_tag, create_test_header,
+        create_video_sequence_header, create_video_tag,
+    };
+    use pipeline_common::{CancellationToken, init_test_tracing};
+
+    fn run(filter: TrackFilter, items: Vec<FlvData>) -> Vec<FlvData> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = TrackFilterOperator::new(context.clone(), filter);
+        let mut out = Vec::new();
+        let mut output = |item: FlvData| -> Result<(), PipelineError> {
+            out.push(item);
+            Ok(())
+        };
+        for item in items {
+            operator.process(&context, item, &mut output).unwrap();
+        }
+        operator.finish(&context, &mut output).unwrap();
+        out
+    }
+
+    fn metadata(item: &FlvData) -> Amf0Value<'static> {
+        let FlvData::Tag(tag) = item else {
+            panic!("expected a script tag");
+        };
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        ScriptData::demux(&mut cursor).unwrap().data.remove(0)
+    }
+
+    #[test]
+    fn test_audio_only_drops_video() {
+        init_test_tracing!();
+        let out = run(
+            TrackFilter::AudioOnly,
+            vec![
+                create_test_header(),
+                create_script_tag(0, true),
+                create_video_sequence_header(0, 1),
+                create_audio_sequence_header(0, 1),
+                create_video_tag(0, true),
+                create_audio_tag(0),
+                create_video_tag(40, false),
+                create_audio_tag(23),
+            ],
+        );
+
+        let FlvData::Header(header) = &out[0] else {
+            panic!("expected a header");
+        };
+        assert!(header.has_audio && !header.has_video);
+        assert_eq!(out.len(), 5);
+        assert!(out.iter().all(|item| match item {
+            FlvData::Tag(tag) => !tag.is_video_tag(),
+            _ => true,
+        }));
+
+        let metadata = metadata(&out[1]);
+        assert!(metadata.get(METADATA_VIDEOCODECID).is_none());
+        assert!(metadata.get(METADATA_WIDTH).is_none());
+        assert!(metadata.get(METADATA_KEYFRAMES).is_none());
+        assert_eq!(
+            metadata
+                .get(METADATA_AUDIOCODECID)
+                .and_then(|v| v.as_number()),
+            Some(10.0)
+        );
+        assert_eq!(
+            metadata.get(METADATA_DURATION).and_then(|v| v.as_number()),
+            Some(120.5)
+        );
+    }
+
+    #[test]
+    fn test_video_only_resends_sequence_header_after_split() {
+        init_test_tracing!();
+        let out = run(
+            TrackFilter::VideoOnly,
+            vec![
+                create_test_header(),
+                create_video_sequence_header(0, 1),
+                create_audio_sequence_header(0, 1),
+                create_video_tag(0, true),
+                create_audio_tag(0),
+                create_test_header(),
+                create_video_tag(1000, true),
+            ],
+        );
+
+        assert_eq!(out.len(), 5);
+        let FlvData::Header(header) = &out[2] else {
+            panic!("expected a header");
+        };
+        assert!(!header.has_audio && header.has_video);
+        let FlvData::Tag(tag) = &out[3] else {
+            panic!("expected the sequence header");
+        };
+        assert!(tag.is_video_sequence_header());
+        assert_eq!(tag.timestamp_ms, 1000);
+    }
+}
@@ -6,33 +6,44 @@
 //!
 //! ## Pipeline Architecture
 //!
-//! Input → Defragment → HeaderCheck → Split → GopSort → TimeConsistency →
-//!        TimingRepair → Limit → TimeConsistency2 → ScriptKeyframesFiller → ScriptFilter → Output
+//! Input → Defragment → HeaderCheck → [TrackFilter] → MediaInfo → [AnomalyDetector] → [Clip] →
+//!        Split → GopSort → [TimestampNormalizer] → TimeConsistency → TimingRepair →
+//!        [AudioGapFill] → Limit → TimeConsistency2 → ScriptKeyframesFiller → ScriptFilter →
+//!        Output
 //!
 //! Each operator addresses specific issues that can occur in FLV streams:
 //!
 //! - **Defragment**: Handles fragmented streams by buffering and validating segments
 //! - **HeaderCheck**: Ensures streams begin with a valid FLV header
+//! - **TrackFilter** (optional): Keeps only the audio or only the video track
+//! - **MediaInfo**: Publishes the codecs and parameters of the stream to the context
+//! - **AnomalyDetector** (optional, on by default): Reports encoder anomalies without changing
+//!   the stream
+//! - **Clip** (optional): Cuts the stream to a time range, before any timestamp repair
 //! - **Split**: Divides content at appropriate points for better playability
 //! - **GopSort**: Ensures video tags are properly ordered by GOP (Group of Pictures)
+//! - **TimestampNormalizer** (optional): Repairs timestamp rollovers and backward steps per track
 //! - **TimeConsistency**: Maintains consistent timestamps throughout the stream
 //! - **TimingRepair**: Fixes timestamp anomalies like negative values or jumps
+//! - **AudioGapFill** (optional): Inserts silent AAC frames into audio dropouts
 //! - **Limit**: Enforces file size and duration limits
 //! - **ScriptKeyframesFiller**: Prepares metadata for proper seeking by adding keyframe placeholders
 //! - **ScriptFilter**: Removes or modifies problematic script tags
 
 use crate::operators::{
+    AnomalyDetectorOperator, AudioGapFillConfig, AudioGapFillOperator, ClipConfig, ClipOperator,
     ContinuityMode, DefragmentOperator, DuplicateTagFilterConfig, DuplicateTagFilterOperator,
-    GopSortOperator, HeaderCheckOperator, LimitConfig, LimitOperator, RepairStrategy,
-    ScriptFillerConfig, ScriptFilterOperator, ScriptKeyframesFillerOperator,
-    SequenceHeaderChangeMode, SplitOperator, TimeConsistencyOperator, TimingRepairConfig,
-    TimingRepairOperator,
+    GopSortOperator, HeaderCheckOperator, LimitConfig, LimitOperator, MediaInfoOperator,
+    RepairStrategy, ScriptFillerConfig, ScriptFilterOperator, ScriptKeyframesFillerOperator,
+    SequenceHeaderChangeMode, SplitOperator, TimeConsistencyOperator, TimestampNormalizerConfig,
+    TimestampNormalizerOperator, TimingRepairConfig, TimingRepairOperator, TrackFilter,
+    TrackFilterOperator,
 };
 use flv::data::FlvData;
 use flv::error::FlvError;
 use futures::stream::Stream;
 use pipeline_common::config::PipelineConfig;
-use pipeline_common::{ChannelPipeline, PipelineProvider, StreamerContext};
+use pipeline_common::{AnomalyConfig, ChannelPipeline, PipelineProvider, StreamerContext};
 use std::pin::Pin;
 use std::sync::Arc;
 
@@ -66,6 +77,31 @@ pub struct FlvPipelineConfig {
     /// Mode for timeline continuity
     pub continuity_mode: ContinuityMode,
 
+    /// Timestamp of the last tag of a recording this stream resumes (None = new recording).
+    ///
+    /// The first segment continues from it instead of following `continuity_mode`.
+    pub resume_timestamp_ms: Option<u32>,
+
+    /// Configuration for timestamp rollover and backward-step repair (None = disabled)
+    pub timestamp_normalizer_config: Option<TimestampNormalizerConfig>,
+
+    /// Configuration for silent audio gap filling (None = disabled)
+    pub audio_gap_fill_config: Option<AudioGapFillConfig>,
+
+    /// Configuration for anomaly detection (None = disabled)
+    ///
+    /// The detector only reports the anomalies of the source stream as events.
+    pub anomaly_detector_config: Option<AnomalyConfig>,
+
+    /// Configuration for clipping the stream to a time range (None = disabled)
+    ///
+    /// Clip times are on the timeline of the source stream; the clip starts at zero for
+    /// all later operators.
+    pub clip_config: Option<ClipConfig>,
+
+    /// Track to keep for audio-only or video-only output (None = keep both)
+    pub track_filter: Option<TrackFilter>,
+
     /// Configuration for keyframe index injection
     pub keyframe_index_config: Option<ScriptFillerConfig>,
 
@@ -83,6 +119,12 @@ impl Default for FlvPipelineConfig {
             drop_duplicate_sequence_headers: false,
             repair_strategy: RepairStrategy::Strict,
             continuity_mode: ContinuityMode::Reset,
+            resume_timestamp_ms: None,
+            timestamp_normalizer_config: None,
+            audio_gap_fill_config: None,
+            anomaly_detector_config: Some(AnomalyConfig::default()),
+            clip_config: None,
+            track_filter: None,
             keyframe_index_config: Some(ScriptFillerConfig::default()),
             enable_low_latency: true,
             pipe_mode: false,
@@ -147,6 +189,45 @@ impl FlvPipelineConfigBuilder {
         self
     }
 
+    pub fn resume_timestamp_ms(mut self, resume_timestamp_ms: Option<u32>) -> Self {
+        self.config.resume_timestamp_ms = resume_timestamp_ms;
+        self
+    }
+
+    pub fn timestamp_normalizer_config(
+        mut self,
+        timestamp_normalizer_config: Option<TimestampNormalizerConfig>,
+    ) -> Self {
+        self.config.timestamp_normalizer_config = timestamp_normalizer_config;
+        self
+    }
+
+    pub fn audio_gap_fill_config(
+        mut self,
+        audio_gap_fill_config: Option<AudioGapFillConfig>,
+    ) -> Self {
+        self.config.audio_gap_fill_config = audio_gap_fill_config;
+        self
+    }
+
+    pub fn anomaly_detector_config(
+        mut self,
+        anomaly_detector_config: Option<AnomalyConfig>,
+    ) -> Self {
+        self.config.anomaly_detector_config = anomaly_detector_config;
+        self
+    }
+
+    pub fn clip_config(mut self, clip_config: Option<ClipConfig>) -> Self {
+        self.config.clip_config = clip_config;
+        self
+    }
+
+    pub fn track_filter(mut self, track_filter: Option<TrackFilter>) -> Self {
+        self.config.track_filter = track_filter;
+        self
+    }
+
     pub fn keyframe_index_config(
         mut self,
         keyframe_index_config: Option<ScriptFillerConfig>,
@@ -194,6 +275,7 @@ impl PipelineProvider for FlvPipeline {
         common_config: &PipelineConfig,
         config: FlvPipelineConfig,
     ) -> Self {
+        context.events.add_handlers(&common_config.event_handlers);
         Self {
             context,
             config,
@@ -244,10 +326,32 @@ impl PipelineProvider for FlvPipeline {
         } else {
             None
         };
+        let timestamp_normalizer_operator = config
+            .timestamp_normalizer_config
+            .clone()
+            .map(|c| TimestampNormalizerOperator::new(context.clone(), c));
+        let audio_gap_fill_operator = config
+            .audio_gap_fill_config
+            .clone()
+            .map(|c| AudioGapFillOperator::new(context.clone(), c));
+        let track_filter_operator = config
+            .track_filter
+            .map(|filter| TrackFilterOperator::new(context.clone(), filter));
+        let media_info_operator = MediaInfoOperator::new(context.clone());
+        let anomaly_detector_operator = config
+            .anomaly_detector_config
+            .clone()
+            .map(|c| AnomalyDetectorOperator::new(context.clone(), c));
+        let clip_operator = config
+            .clip_config
+            .clone()
+            .map(|c| ClipOperator::new(context.clone(), c));
         let time_consistency_operator =
-            TimeConsistencyOperator::new(context.clone(), config.continuity_mode);
+            TimeConsistencyOperator::new(context.clone(), config.continuity_mode)
+                .with_resume_timestamp(config.resume_timestamp_ms);
         let time_consistency_operator_2 =
-            TimeConsistencyOperator::new(context.clone(), config.continuity_mode);
+            TimeConsistencyOperator::new(context.clone(), config.continuity_mode)
+                .with_resume_timestamp(config.resume_timestamp_ms);
 
         // Determine if we're in pipe mode - skip script-related operators
         // In pipe mode, AMF0 metadata modification is unnecessary overhead
@@ -271,8 +375,28 @@ impl PipelineProvider for FlvPipeline {
 
         // Build the synchronous pipeline
         let mut sync_pipeline = pipeline_common::Pipeline::new(context.clone())
+            .with_error_policy(self.common_config.error_policy)
             .add_processor(defrag_operator)
-            .add_processor(header_check_operator)
+            .add_processor(header_check_operator);
+
+        if let Some(op) = track_filter_operator {
+            sync_pipeline = sync_pipeline.add_processor(op);
+        }
+
+        // Describe the stream as the output carries it, before any repair
+        sync_pipeline = sync_pipeline.add_processor(media_info_operator);
+
+        // Anomalies of the source stream, before the repairs hide them
+        if let Some(op) = anomaly_detector_operator {
+            sync_pipeline = sync_pipeline.add_processor(op);
+        }
+
+        // Clip first, so timestamp repair and limits only ever see the clip
+        if let Some(op) = clip_operator {
+            sync_pipeline = sync_pipeline.add_processor(op);
+        }
+
+        sync_pipeline = sync_pipeline
             .add_processor(split_operator)
             .add_processor(gop_sort_operator);
 
@@ -280,9 +404,19 @@ impl PipelineProvider for FlvPipeline {
             sync_pipeline = sync_pipeline.add_processor(op);
         }
 
+        if let Some(op) = timestamp_normalizer_operator {
+            sync_pipeline = sync_pipeline.add_processor(op);
+        }
+
         sync_pipeline = sync_pipeline
             .add_processor(time_consistency_operator)
-            .add_processor(timing_repair_operator)
+            .add_processor(timing_repair_operator);
+
+        if let Some(op) = audio_gap_fill_operator {
+            sync_pipeline = sync_pipeline.add_processor(op);
+        }
+
+        sync_pipeline = sync_pipeline
             .add_processor(limit_operator)
             .add_processor(time_consistency_operator_2);
 
@@ -299,7 +433,9 @@ impl PipelineProvider for FlvPipeline {
         };
 
         // Wrap it in a ChannelPipeline to offload processing to a dedicated thread
-        ChannelPipeline::new(context).add_processor(sync_pipeline)
+        ChannelPipeline::new(context)
+            .with_max_in_flight_bytes(self.common_config.max_in_flight_bytes)
+            .add_processor(sync_pipeline)
     }
 }
 
@@ -307,18 +443,31 @@ impl PipelineProvider for FlvPipeline {
 /// Tests for the FLV processing pipeline
 mod test {
     use super::*;
+    use crate::operators::{CuePoint, cue_point_channel};
+    use crate::report::analyze_file;
+    use crate::test_utils::{
+        create_audio_sequence_header, create_audio_tag, create_enhanced_sequence_start,
+        create_enhanced_video_tag, create_script_tag, create_test_header,
+        create_video_sequence_header, create_video_tag, create_video_tag_with_size,
+    };
     use crate::writer::FlvWriter;
     use crate::writer_task::FlvWriterConfig;
 
     use flv::data::FlvData;
-    use flv::parser_async::FlvDecoderStream;
+    use flv::parser_async::{FlvDecoderStream, Repair, ResyncConfig};
+    use flv::video::VideoFourCC;
     use futures::StreamExt;
+    use pipeline_common::channel_pipeline::SpawnedPipeline;
+    use pipeline_common::output_sink::mock::MockUploadServer;
     use pipeline_common::{
-        CancellationToken, PipelineError, ProtocolWriter, WriterError, WriterStats,
-        init_test_tracing,
+        CancellationToken, CurrentFileHook, EventCollector, OutputSink, PipelineError,
+        PipelineEvent, ProgressEvent, ProtocolWriter, ShutdownHandle, StatsSnapshot, WriterError,
+        WriterStats, init_test_tracing, run_until_shutdown,
     };
 
     use std::path::Path;
+    use std::sync::Mutex;
+    use std::time::Duration;
     use tracing::info;
 
     #[tokio::test]
@@ -447,4 +596,749 @@ mod test {
 
         Ok(())
     }
+
+    /// Writes an FLV file with a repeated script tag, which the pipeline drops.
+    fn write_test_file(path: &Path) {
+        let mut items = vec![
+            create_test_header(),
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ];
+        for i in 0..60 {
+            items.push(create_video_tag(i * 40, i % 20 == 0));
+            items.push(create_audio_tag(i * 40));
+            if i == 30 {
+                items.push(create_script_tag(i * 40, false));
+            }
+        }
+        write_items(path, items);
+    }
+
+    fn write_items(path: &Path, items: Vec<FlvData>) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut writer = flv::writer::FlvWriter::new(file).unwrap();
+        for item in items {
+            match item {
+                FlvData::Header(header) => writer.write_header(&header).unwrap(),
+                FlvData::Tag(tag) => writer.write_tag_f(&tag).unwrap(),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_final_stats_match_analyzer() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("input.flv");
+        let output_dir = dir.path().join("fix");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        write_test_file(&input_path);
+
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let snapshots = Arc::new(Mutex::new(Vec::<StatsSnapshot>::new()));
+        let pipeline = FlvPipeline::with_config(
+            context.clone(),
+            &PipelineConfig::default(),
+            FlvPipelineConfig::default(),
+        )
+        .build_pipeline()
+        .with_stats_reporter(Duration::from_millis(10), {
+            let snapshots = snapshots.clone();
+            move |event| {
+                if let ProgressEvent::Stats(snapshot) = event {
+                    snapshots.lock().unwrap().push(snapshot);
+                }
+            }
+        });
+        let SpawnedPipeline {
+            input_tx,
+            output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        let mut writer = FlvWriter::new(FlvWriterConfig {
+            output_dir: output_dir.clone(),
+            base_name: "output".to_string(),
+            enable_low_latency: true,
+        });
+        writer.add_segment_hook(CurrentFileHook::new(context.stats.clone()));
+        let writer_task = tokio::task::spawn_blocking(move || writer.run(output_rx));
+
+        let file = tokio::fs::File::open(&input_path).await.unwrap();
+        let file_reader = tokio::io::BufReader::new(file);
+        let mut decoder_stream = FlvDecoderStream::with_capacity(file_reader, 32 * 1024);
+        while let Some(result) = decoder_stream.next().await {
+            let item = result.map_err(|e| PipelineError::Strategy(Box::new(e)));
+            input_tx.send(item).await.unwrap();
+        }
+        drop(input_tx);
+
+        let stats = writer_task.await.unwrap().unwrap();
+        assert_eq!(stats.files_created, 1);
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let input_report = analyze_file(&input_path).unwrap();
+        let output_path = context.stats.snapshot().current_file.unwrap();
+        let output_report = analyze_file(&output_path).unwrap();
+
+        let snapshots = snapshots.lock().unwrap();
+        let last = snapshots.last().expect("final snapshot");
+        // Both files start with a header besides their tags
+        assert_eq!(last.items_in, u64::from(input_report.tag_counts.total) + 1);
+        assert_eq!(
+            last.items_out,
+            u64::from(output_report.tag_counts.total) + 1
+        );
+        assert_eq!(
+            last.dropped,
+            u64::from(input_report.tag_counts.script - output_report.tag_counts.script)
+        );
+        assert_eq!(last.dropped, 1);
+        assert_eq!(
+            last.items_in - last.items_out,
+            last.dropped + last.duplicates
+        );
+        assert!(last.bytes_in > 0 && last.bytes_out > 0);
+
+        // The stream was described once to the consumers of the context
+        let media_info = context.media_info.get().expect("media info");
+        assert!(!media_info.changed);
+        let audio = media_info.audio.expect("audio info");
+        assert_eq!(audio.codec, "AAC");
+        assert_eq!((audio.sample_rate, audio.channels), (Some(64000), Some(2)));
+    }
+
+    /// Runs `input_path` through the default pipeline and returns the fixed file.
+    async fn fix_file(input_path: &Path, output_dir: &Path) -> std::path::PathBuf {
+        fix_file_with(input_path, output_dir, FlvPipelineConfig::default()).await
+    }
+
+    /// Runs `input_path` through a pipeline with `config` and returns the fixed file.
+    async fn fix_file_with(
+        input_path: &Path,
+        output_dir: &Path,
+        config: FlvPipelineConfig,
+    ) -> std::path::PathBuf {
+        fix_file_to(input_path, output_dir, config, OutputSink::Local).await
+    }
+
+    /// Runs `input_path` through a pipeline with `config` into a writer on `sink` and
+    /// returns the path of the fixed file.
+    async fn fix_file_to(
+        input_path: &Path,
+        output_dir: &Path,
+        config: FlvPipelineConfig,
+        sink: OutputSink,
+    ) -> std::path::PathBuf {
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline =
+            FlvPipeline::with_config(context.clone(), &PipelineConfig::default(), config)
+                .build_pipeline();
+        let SpawnedPipeline {
+            input_tx,
+            output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        let mut writer = FlvWriter::new(FlvWriterConfig {
+            output_dir: output_dir.to_path_buf(),
+            base_name: "output".to_string(),
+            enable_low_latency: true,
+        });
+        writer.set_output_sink(sink);
+        writer.add_segment_hook(CurrentFileHook::new(context.stats.clone()));
+        let writer_task = tokio::task::spawn_blocking(move || writer.run(output_rx));
+
+        let file = tokio::fs::File::open(input_path).await.unwrap();
+        let file_reader = tokio::io::BufReader::new(file);
+        let mut decoder_stream = FlvDecoderStream::with_capacity(file_reader, 32 * 1024);
+        while let Some(result) = decoder_stream.next().await {
+            let item = result.map_err(|e| PipelineError::Strategy(Box::new(e)));
+            input_tx.send(item).await.unwrap();
+        }
+        drop(input_tx);
+
+        let stats = writer_task.await.unwrap().unwrap();
+        assert_eq!(stats.files_created, 1);
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        context.stats.snapshot().current_file.unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_upload_sinks_match_local_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("input.flv");
+        let mut items = vec![
+            create_test_header(),
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ];
+        for i in 0..100 {
+            items.push(create_video_tag_with_size(i * 40, i % 50 == 0, 1000));
+            items.push(create_audio_tag(i * 40));
+        }
+        write_items(&input_path, items);
+
+        let config = || {
+            FlvPipelineConfig::builder()
+                .keyframe_index_config(Some(ScriptFillerConfig {
+                    reserved_keyframes: Some(100),
+                    ..ScriptFillerConfig::default()
+                }))
+                .build()
+        };
+        let server = MockUploadServer::start();
+        let put = OutputSink::HttpPut {
+            url_template: format!("{}/put/{{name}}", server.url()),
+            headers: Vec::new(),
+        };
+        let multipart = OutputSink::Multipart {
+            endpoint: format!("{}/parts", server.url()),
+            part_size: 16 * 1024,
+        };
+        let local_dir = dir.path().join("local");
+        let local = fix_file_to(&input_path, &local_dir, config(), OutputSink::Local).await;
+        fix_file_to(&input_path, &dir.path().join("put"), config(), put).await;
+        fix_file_to(&input_path, &dir.path().join("parts"), config(), multipart).await;
+        assert!(!dir.path().join("put").exists() && !dir.path().join("parts").exists());
+
+        // The multipart upload holds back the onMetaData until it is finalized, like the
+        // local file is in the background
+        let uploaded = server.object("/parts/output.flv").unwrap();
+        let mut local_bytes = Vec::new();
+        for _ in 0..100 {
+            local_bytes = std::fs::read(&local).unwrap();
+            if local_bytes == uploaded {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(local_bytes, uploaded);
+
+        // A streamed PUT keeps the onMetaData as first written, everything else is the same
+        let streamed = server.object("/put/output.flv").unwrap();
+        assert_eq!(streamed.len(), local_bytes.len());
+        let metadata_size =
+            u32::from_be_bytes([0, local_bytes[14], local_bytes[15], local_bytes[16]]) as usize;
+        let metadata_end = 13 + 11 + metadata_size + 4;
+        assert_eq!(streamed[..13], local_bytes[..13]);
+        assert_ne!(streamed[..metadata_end], local_bytes[..metadata_end]);
+        assert_eq!(streamed[metadata_end..], local_bytes[metadata_end..]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_enhanced_flv_streams() {
+        for (fourcc, codec) in [(VideoFourCC::Hvc1, "HEVC"), (VideoFourCC::Av01, "AV1")] {
+            let dir = tempfile::tempdir().unwrap();
+            let input_path = dir.path().join("input.flv");
+            let output_dir = dir.path().join("fix");
+            std::fs::create_dir_all(&output_dir).unwrap();
+
+            let mut items = vec![
+                create_test_header(),
+                create_script_tag(0, false),
+                create_enhanced_sequence_start(0, fourcc),
+                create_audio_sequence_header(0, 1),
+            ];
+            for i in 0..100 {
+                items.push(create_enhanced_video_tag(i * 40, fourcc, i % 50 == 0));
+                items.push(create_audio_tag(i * 40));
+            }
+            write_items(&input_path, items);
+
+            let output_path = fix_file(&input_path, &output_dir).await;
+            let report = analyze_file(&output_path).unwrap();
+
+            assert_eq!(report.file_info.keyframe_count, 2, "{codec}");
+            assert_eq!(report.tag_counts.video, 101, "{codec}");
+            assert_eq!(report.tag_counts.video_sequence_headers, 1, "{codec}");
+            let video = report.video_params.expect("video parameters");
+            assert_eq!(video.codec, codec);
+            if fourcc == VideoFourCC::Hvc1 {
+                assert_eq!((video.width, video.height), (Some(2560), Some(1440)));
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_track_filter_modes() {
+        for filter in [TrackFilter::AudioOnly, TrackFilter::VideoOnly] {
+            let dir = tempfile::tempdir().unwrap();
+            let input_path = dir.path().join("input.flv");
+            let output_dir = dir.path().join("fix");
+            std::fs::create_dir_all(&output_dir).unwrap();
+            write_test_file(&input_path);
+
+            let config = FlvPipelineConfig::builder()
+                .track_filter(Some(filter))
+                .build();
+            let output_path = fix_file_with(&input_path, &output_dir, config).await;
+            let report = analyze_file(&output_path).unwrap();
+
+            let mut file = std::fs::File::open(&output_path).unwrap();
+            let header = flv::header::FlvHeader::parse(&mut file).unwrap();
+            let audio_only = filter == TrackFilter::AudioOnly;
+            assert_eq!(header.has_audio, audio_only, "{filter:?}");
+            assert_eq!(header.has_video, !audio_only, "{filter:?}");
+            assert_eq!(report.file_info.has_audio, audio_only, "{filter:?}");
+            assert_eq!(report.file_info.has_video, !audio_only, "{filter:?}");
+
+            let counts = &report.tag_counts;
+            if audio_only {
+                assert_eq!(counts.video, 0);
+                assert_eq!(counts.video_sequence_headers, 0);
+                assert_eq!(counts.audio_sequence_headers, 1);
+                assert!(counts.audio > 0);
+            } else {
+                assert_eq!(counts.audio, 0);
+                assert_eq!(counts.audio_sequence_headers, 0);
+                assert_eq!(counts.video_sequence_headers, 1);
+                assert!(counts.video > 0);
+            }
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_mid_stream_finalizes_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut items = vec![
+            create_test_header(),
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ];
+        for i in 0..100 {
+            items.push(create_video_tag(i * 40, i % 25 == 0));
+            items.push(create_audio_tag(i * 40));
+        }
+
+        // A live stream that never ends, stopped once the last tag was received
+        let shutdown = ShutdownHandle::new(CancellationToken::new());
+        let last = items.len();
+        let stream = futures::stream::iter(items)
+            .enumerate()
+            .map({
+                let shutdown = shutdown.clone();
+                move |(index, item)| {
+                    if index + 1 == last {
+                        shutdown.shutdown();
+                    }
+                    Ok(item)
+                }
+            })
+            .chain(futures::stream::pending());
+
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = FlvPipeline::with_config(
+            context.clone(),
+            &PipelineConfig::default(),
+            FlvPipelineConfig::default(),
+        )
+        .build_pipeline();
+        let mut writer = FlvWriter::new(FlvWriterConfig {
+            output_dir: dir.path().to_path_buf(),
+            base_name: "output".to_string(),
+            enable_low_latency: true,
+        });
+        writer.add_segment_hook(CurrentFileHook::new(context.stats.clone()));
+
+        let outcome = run_until_shutdown(stream, pipeline, writer, &shutdown)
+            .await
+            .unwrap();
+        assert!(outcome.is_interrupted());
+        let stats = outcome.into_stats();
+        assert_eq!(stats.files_created, 1);
+
+        // The onMetaData is patched in the background once the file is closed
+        let output_path = context.stats.snapshot().current_file.unwrap();
+        let mut report = None;
+        for _ in 0..100 {
+            report = analyze_file(&output_path).ok();
+            if report.as_ref().is_some_and(|report| !report.has_issues()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let report = report.expect("analyzable output");
+        assert!(!report.has_issues(), "{:?}", report.issues);
+        assert_eq!(stats.items_written, report.tag_counts.total as usize + 1);
+        assert_eq!(report.tag_counts.video, 101);
+        assert_eq!(report.tag_counts.audio, 101);
+        assert_eq!(report.file_info.keyframe_count, 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cue_points_pushed_while_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let (cue_tx, cue_receiver) = cue_point_channel();
+        let config = FlvPipelineConfig::builder()
+            .keyframe_index_config(Some(ScriptFillerConfig {
+                cue_points: vec![
+                    CuePoint::new(1000, "chapter 1", "navigation"),
+                    CuePoint::new(60_000, "after the end", "navigation"),
+                ],
+                cue_receiver: Some(cue_receiver),
+                ..ScriptFillerConfig::default()
+            }))
+            .build();
+
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline =
+            FlvPipeline::with_config(context.clone(), &PipelineConfig::default(), config)
+                .build_pipeline();
+        let SpawnedPipeline {
+            input_tx,
+            output_rx,
+            tasks,
+        } = pipeline.spawn();
+        let mut writer = FlvWriter::new(FlvWriterConfig {
+            output_dir: dir.path().to_path_buf(),
+            base_name: "output".to_string(),
+            enable_low_latency: true,
+        });
+        writer.add_segment_hook(CurrentFileHook::new(context.stats.clone()));
+        let writer_task = tokio::task::spawn_blocking(move || writer.run(output_rx));
+
+        for item in [
+            create_test_header(),
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ] {
+            input_tx.send(Ok(item)).await.unwrap();
+        }
+        for i in 0..100 {
+            // The first half of the stream is in, the cue point lies in the second half
+            if i == 50 {
+                cue_tx
+                    .send(
+                        CuePoint::new(3000, "live", "event")
+                            .with_parameter("title", amf0::Amf0Value::String("Second part".into())),
+                    )
+                    .unwrap();
+            }
+            input_tx
+                .send(Ok(create_video_tag(i * 40, i % 25 == 0)))
+                .await
+                .unwrap();
+            input_tx.send(Ok(create_audio_tag(i * 40))).await.unwrap();
+        }
+        drop(input_tx);
+
+        let stats = writer_task.await.unwrap().unwrap();
+        assert_eq!(stats.files_created, 1);
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        // The onMetaData is patched in the background once the file is closed
+        let output_path = context.stats.snapshot().current_file.unwrap();
+        let mut cue_points = None;
+        let mut tags = Vec::new();
+        for _ in 0..100 {
+            tags.clear();
+            let file = tokio::fs::File::open(&output_path).await.unwrap();
+            let mut decoder_stream = FlvDecoderStream::new(tokio::io::BufReader::new(file));
+            while let Some(item) = decoder_stream.next().await {
+                if let FlvData::Tag(tag) = item.unwrap() {
+                    tags.push(tag);
+                }
+            }
+            let mut cursor = std::io::Cursor::new(tags[0].data.clone());
+            let metadata = flv::script::ScriptData::demux(&mut cursor).unwrap();
+            cue_points = metadata.data[0]
+                .get(crate::METADATA_CUE_POINTS)
+                .and_then(|cue_points| cue_points.as_array())
+                .map(|cue_points| {
+                    cue_points
+                        .iter()
+                        .map(|cue| CuePoint::from_amf(cue).unwrap())
+                        .collect::<Vec<_>>()
+                });
+            if cue_points.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // Each cue point sits between the media tags around its time
+        let mut written = Vec::new();
+        for (index, tag) in tags.iter().enumerate() {
+            if !CuePoint::is_cue_point_payload(&tag.data) {
+                continue;
+            }
+            let mut cursor = std::io::Cursor::new(tag.data.clone());
+            let script = flv::script::ScriptData::demux(&mut cursor).unwrap();
+            let cue = CuePoint::from_amf(&script.data[0]).unwrap();
+            let previous = tags[..index].iter().rev().find(|tag| !tag.is_script_tag());
+            let next = tags[index + 1..].iter().find(|tag| !tag.is_script_tag());
+            assert!(previous.unwrap().timestamp_ms < cue.time_ms);
+            assert!(next.unwrap().timestamp_ms >= cue.time_ms);
+            assert_eq!(tag.timestamp_ms, cue.time_ms);
+            written.push(cue);
+        }
+        let expected = vec![
+            CuePoint::new(1000, "chapter 1", "navigation"),
+            CuePoint::new(3000, "live", "event")
+                .with_parameter("title", amf0::Amf0Value::String("Second part".into())),
+        ];
+        assert_eq!(written, expected);
+
+        // The cue point after the end of the stream was dropped
+        assert_eq!(cue_points, Some(expected));
+    }
+
+    #[tokio::test]
+    async fn test_file_renamed_once_stream_metadata_is_known() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let mut writer = FlvWriter::new(FlvWriterConfig {
+            output_dir: output_dir.path().to_path_buf(),
+            base_name: "rec_%acodec%".to_string(),
+            enable_low_latency: true,
+        });
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        for item in [
+            create_test_header(),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+            create_video_tag(0, true),
+            create_audio_tag(0),
+        ] {
+            tx.send(Ok(item)).await.unwrap();
+        }
+        drop(tx);
+
+        let stats = tokio::task::spawn_blocking(move || writer.run(rx))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stats.files_created, 1);
+        assert!(output_dir.path().join("rec_aac.flv").exists());
+        assert!(!output_dir.path().join("rec_unknown.flv").exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_resync_recovers_tags_after_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let input_path = dir.path().join("input.flv");
+        let mut items = vec![
+            create_test_header(),
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ];
+        // Distinct sizes tell the video tags apart in the output
+        for i in 0..200 {
+            items.push(create_video_tag_with_size(i * 40, true, 500 + i as usize));
+            items.push(create_audio_tag(i * 40));
+        }
+        write_items(&input_path, items);
+
+        // Start and size of every video tag of the intact file
+        let mut video_tags = Vec::new();
+        let file = tokio::fs::File::open(&input_path).await.unwrap();
+        let mut decoder_stream = FlvDecoderStream::new(tokio::io::BufReader::new(file));
+        while let Some(item) = decoder_stream.next().await {
+            if let FlvData::Tag(tag) = item.unwrap()
+                && tag.is_video_tag()
+            {
+                let size = 11 + tag.data.len() as u64;
+                video_tags.push((decoder_stream.position() - size, tag.data.len()));
+            }
+        }
+
+        // Overwrite 10 KB in the middle of the file with random bytes
+        let mut bytes = std::fs::read(&input_path).unwrap();
+        let corrupt = bytes.len() / 2..bytes.len() / 2 + 10 * 1024;
+        let mut seed = 0x9E37_79B9_u32;
+        for byte in &mut bytes[corrupt.clone()] {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            *byte = seed as u8;
+        }
+        std::fs::write(&input_path, &bytes).unwrap();
+        let expected: Vec<_> = video_tags
+            .iter()
+            .filter(|(start, _)| *start >= corrupt.end as u64)
+            .map(|(_, size)| *size)
+            .collect();
+        let first_intact = video_tags
+            .iter()
+            .map(|(start, _)| *start)
+            .find(|start| *start >= corrupt.end as u64)
+            .unwrap();
+
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = FlvPipeline::with_config(
+            context.clone(),
+            &PipelineConfig::default(),
+            FlvPipelineConfig::default(),
+        )
+        .build_pipeline();
+        let SpawnedPipeline {
+            input_tx,
+            mut output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        let repairs = Arc::new(Mutex::new(Vec::<Repair>::new()));
+        let file = tokio::fs::File::open(&input_path).await.unwrap();
+        let decoder_stream = FlvDecoderStream::with_resync(
+            tokio::io::BufReader::new(file),
+            32 * 1024,
+            ResyncConfig::default(),
+        )
+        .on_repair({
+            let repairs = repairs.clone();
+            let stats = context.stats.clone();
+            move |repair| {
+                stats.record_repair("resync");
+                repairs.lock().unwrap().push(repair);
+            }
+        });
+        let feeder = tokio::spawn(async move {
+            let mut decoder_stream = decoder_stream;
+            while let Some(result) = decoder_stream.next().await {
+                let item = result.map_err(|e| PipelineError::Strategy(Box::new(e)));
+                input_tx.send(item).await.unwrap();
+            }
+        });
+
+        let mut output_sizes = Vec::new();
+        while let Some(item) = output_rx.recv().await {
+            if let FlvData::Tag(tag) = item.unwrap()
+                && tag.is_video_tag()
+            {
+                output_sizes.push(tag.data.len());
+            }
+        }
+        feeder.await.unwrap();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        // Every tag after the corrupt region made it through
+        assert!(
+            output_sizes.ends_with(&expected),
+            "expected the {} tags after the corruption, got {output_sizes:?}",
+            expected.len()
+        );
+        let repairs = repairs.lock().unwrap();
+        let [repair] = repairs.as_slice() else {
+            panic!("expected a single repair, got {repairs:?}");
+        };
+        assert!(repair.position < corrupt.end as u64);
+        assert_eq!(repair.position + repair.skipped_bytes, first_intact);
+        assert_eq!(context.stats.snapshot().repairs.get("resync"), Some(&1));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_repairs_reported_as_events() {
+        let dir = tempfile::tempdir().unwrap();
+        // No header, and the timestamps jump 10s ahead halfway through
+        let mut items = vec![
+            create_script_tag(0, false),
+            create_video_sequence_header(0, 1),
+            create_audio_sequence_header(0, 1),
+        ];
+        for i in 0..60 {
+            let timestamp = if i < 30 { i * 40 } else { i * 40 + 10_000 };
+            items.push(create_video_tag(timestamp, i % 20 == 0));
+            items.push(create_audio_tag(timestamp));
+        }
+
+        let collector = Arc::new(EventCollector::default());
+        let common_config = PipelineConfig::builder()
+            .event_handler(collector.clone())
+            .build();
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = FlvPipeline::with_config(
+            context.clone(),
+            &common_config,
+            FlvPipelineConfig::default(),
+        )
+        .build_pipeline();
+        let SpawnedPipeline {
+            input_tx,
+            output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        let mut writer = FlvWriter::new(FlvWriterConfig {
+            output_dir: dir.path().to_path_buf(),
+            base_name: "output".to_string(),
+            enable_low_latency: true,
+        });
+        writer.set_event_context(context.clone());
+        let writer_task = tokio::task::spawn_blocking(move || writer.run(output_rx));
+        for item in items {
+            input_tx.send(Ok(item)).await.unwrap();
+        }
+        drop(input_tx);
+        writer_task.await.unwrap().unwrap();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let repairs: Vec<_> = collector
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                PipelineEvent::RepairApplied {
+                    operator,
+                    kind,
+                    offset,
+                    detail,
+                } => Some((operator, kind, offset, detail)),
+                _ => None,
+            })
+            .collect();
+        let summary: Vec<_> = repairs
+            .iter()
+            .map(|(operator, kind, offset, _)| (*operator, *kind, *offset))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("HeaderCheckOperator", "missing_header", None),
+                (
+                    "TimingRepairOperator",
+                    "timestamp_discontinuity",
+                    Some(11_200)
+                ),
+            ]
+        );
+        assert_eq!(
+            repairs[0].3,
+            "FLV header is missing, inserted a default header"
+        );
+
+        let snapshot = context.stats.snapshot();
+        assert_eq!(snapshot.events.get("repair_applied"), Some(&2));
+        assert_eq!(snapshot.repairs.get("HeaderCheckOperator"), Some(&1));
+        assert_eq!(snapshot.repairs.get("TimingRepairOperator"), Some(&1));
+
+        // The onMetaData is patched in the background once the file is closed
+        for _ in 0..100 {
+            if has_metadata_rewrite(&collector) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(has_metadata_rewrite(&collector));
+    }
+
+    fn has_metadata_rewrite(collector: &EventCollector) -> bool {
+        collector
+            .events()
+            .iter()
+            .any(|event| matches!(event, PipelineEvent::MetadataRewritten { .. }))
+    }
 }
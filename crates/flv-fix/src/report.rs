@@ -0,0 +1,456 @@
+//! Structured analysis reports for FLV files and streams.
+//!
+//! [`AnalysisReport`] is produced by [`FlvAnalyzer::finalize_report`] and collects the
+//! stream parameters together with every problem the analyzer found, each located by
+//! byte offset and tag index. With the `serde` feature enabled the report can be
+//! serialized, e.g. to diff findings between recordings in CI.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use bytes::Bytes;
+use flv::framing::{self, PREV_TAG_SIZE_FIELD_SIZE, TAG_HEADER_SIZE};
+use flv::header::FlvHeader;
+use flv::tag::{FlvTag, FlvTagType};
+
+use crate::analyzer::{AnalyzerError, FlvAnalyzer};
+
+/// Maximum number of issues kept in a report, further issues are only counted
+pub const MAX_REPORTED_ISSUES: usize = 1000;
+
+/// Error type for report generation
+#[derive(Debug, thiserror::Error)]
+pub enum ReportError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Analyzer error: {0}")]
+    Analyzer(#[from] AnalyzerError),
+    #[cfg(feature = "serde")]
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Category of a problem found during analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IssueKind {
+    /// A tag is timestamped before the previous tag of the same track
+    TimestampRegression,
+    /// Media data arrived before the sequence header needed to decode it
+    MissingSequenceHeader,
+    /// A tag repeats the previous tag of the same track byte for byte
+    DuplicateTag,
+    /// A PreviousTagSize field does not match the size of the preceding tag
+    BrokenBackpointer,
+    /// Consecutive audio tags are further apart than [`AUDIO_GAP_THRESHOLD_MS`]
+    GapInAudio,
+    /// The input ended in the middle of a tag
+    TruncatedTag,
+    /// A tag has a type that is neither audio, video nor script data
+    UnknownTagType,
+}
+
+/// A single problem found during analysis
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Issue {
+    pub kind: IssueKind,
+    /// Byte offset of the offending tag or field from the start of the file
+    pub offset: u64,
+    /// Zero-based index of the offending tag (the next tag for backpointer issues)
+    pub tag_index: u64,
+    /// Timestamp of the offending tag in milliseconds
+    pub timestamp_ms: u32,
+    pub message: String,
+}
+
+/// Audio tags further apart than this are reported as [`IssueKind::GapInAudio`]
+pub const AUDIO_GAP_THRESHOLD_MS: u32 = 1000;
+
+/// General information about the analyzed file
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FileInfo {
+    pub file_size: u64,
+    /// Duration in seconds
+    pub duration: u32,
+    pub has_video: bool,
+    pub has_audio: bool,
+    pub keyframe_count: usize,
+}
+
+/// Video parameters, parsed from the first video sequence header
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct VideoParams {
+    /// Codec name, e.g. "AVC", "HEVC" or "AV1"
+    pub codec: String,
+    pub profile: Option<u8>,
+    pub level: Option<u8>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub frame_rate: f32,
+    /// Average video data rate in kbps
+    pub data_rate: f32,
+}
+
+/// Audio parameters, parsed from the first audio sequence header when available
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AudioParams {
+    /// Codec name, e.g. "Aac" or "Mp3"
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u8>,
+    /// Average audio data rate in kbps
+    pub data_rate: f32,
+}
+
+/// Number of tags per type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TagCounts {
+    pub total: u32,
+    pub video: u32,
+    pub audio: u32,
+    pub script: u32,
+    pub video_sequence_headers: u32,
+    pub audio_sequence_headers: u32,
+}
+
+/// Structured result of analyzing an FLV file or stream
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AnalysisReport {
+    pub file_info: FileInfo,
+    pub video_params: Option<VideoParams>,
+    pub audio_params: Option<AudioParams>,
+    pub tag_counts: TagCounts,
+    pub issues: Vec<Issue>,
+    /// Number of issues dropped after reaching [`MAX_REPORTED_ISSUES`]
+    pub suppressed_issues: u64,
+}
+
+impl AnalysisReport {
+    /// Whether any issue was found
+    pub fn has_issues(&self) -> bool {
+        !self.issues.is_empty() || self.suppressed_issues > 0
+    }
+
+    /// Number of recorded issues of the given kind
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.kind == kind)
+            .count()
+    }
+
+    /// Serialize the report as pretty-printed JSON
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> Result<String, ReportError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Analyze an FLV file tag by tag, without loading it into memory
+pub fn analyze_file(path: impl AsRef<Path>) -> Result<AnalysisReport, ReportError> {
+    let file = File::open(path)?;
+    analyze_reader(BufReader::new(file))
+}
+
+/// Analyze `input` and write the report as JSON to `output`
+///
+/// Entry point for `--analyze-json <path>` style command line options.
+#[cfg(feature = "serde")]
+pub fn analyze_to_json(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+) -> Result<AnalysisReport, ReportError> {
+    let report = analyze_file(input)?;
+    std::fs::write(output, report.to_json()?)?;
+    Ok(report)
+}
+
+/// Analyze an FLV byte stream, including PreviousTagSize backpointers
+///
+/// Only one tag is held in memory at a time. Reading stops at the end of input, at a
+/// truncated tag or at a tag of unknown type; the latter two are reported as issues.
+pub fn analyze_reader<R: Read>(mut reader: R) -> Result<AnalysisReport, ReportError> {
+    let mut analyzer = FlvAnalyzer::default();
+    let header = FlvHeader::parse(&mut reader)?;
+    analyzer.analyze_header(&header)?;
+
+    let mut prev_tag_size = [0u8; PREV_TAG_SIZE_FIELD_SIZE];
+    if read_full(&mut reader, &mut prev_tag_size)? != PREV_TAG_SIZE_FIELD_SIZE {
+        return Ok(analyzer.finalize_report()?);
+    }
+    analyzer.analyze_previous_tag_size(framing::parse_prev_tag_size(prev_tag_size));
+
+    loop {
+        let mut header_bytes = [0u8; TAG_HEADER_SIZE];
+        match read_full(&mut reader, &mut header_bytes)? {
+            0 => break,
+            TAG_HEADER_SIZE => {}
+            read => {
+                analyzer.report_truncated_tag(read, TAG_HEADER_SIZE, None);
+                break;
+            }
+        }
+        let tag_header = framing::parse_tag_header_bytes(header_bytes)?;
+
+        let mut data = vec![0u8; tag_header.data_size as usize];
+        let read = read_full(&mut reader, &mut data)?;
+        if read != data.len() {
+            analyzer.report_truncated_tag(
+                TAG_HEADER_SIZE + read,
+                TAG_HEADER_SIZE + data.len(),
+                Some(tag_header.timestamp_ms),
+            );
+            break;
+        }
+
+        let tag = FlvTag {
+            timestamp_ms: tag_header.timestamp_ms,
+            stream_id: tag_header.stream_id,
+            tag_type: tag_header.tag_type,
+            is_filtered: tag_header.is_filtered,
+            data: Bytes::from(data),
+        };
+        if let FlvTagType::Unknown(_) = tag.tag_type {
+            analyzer.report_unknown_tag(&tag);
+            break;
+        }
+        analyzer.analyze_tag(&tag)?;
+
+        // A missing final PreviousTagSize is common for live recordings and not reported
+        if read_full(&mut reader, &mut prev_tag_size)? != PREV_TAG_SIZE_FIELD_SIZE {
+            break;
+        }
+        analyzer.analyze_previous_tag_size(framing::parse_prev_tag_size(prev_tag_size));
+    }
+
+    Ok(analyzer.finalize_report()?)
+}
+
+/// Read until `buf` is full or the reader is exhausted, returning the bytes read
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const FLV_HEADER: [u8; 9] = [b'F', b'L', b'V', 1, 0x05, 0, 0, 0, 9];
+
+    /// Builds an FLV byte stream tag by tag
+    struct FileBuilder {
+        buf: Vec<u8>,
+    }
+
+    impl FileBuilder {
+        fn new() -> Self {
+            let mut buf = FLV_HEADER.to_vec();
+            buf.extend_from_slice(&0u32.to_be_bytes());
+            Self { buf }
+        }
+
+        /// Current length, i.e. the offset of the next tag
+        fn offset(&self) -> u64 {
+            self.buf.len() as u64
+        }
+
+        fn tag_with_backpointer(
+            mut self,
+            tag_type: u8,
+            timestamp_ms: u32,
+            data: &[u8],
+            backpointer: Option<u32>,
+        ) -> Self {
+            let size = data.len() as u32;
+            self.buf.push(tag_type);
+            self.buf.extend_from_slice(&size.to_be_bytes()[1..]);
+            self.buf.extend_from_slice(&timestamp_ms.to_be_bytes()[1..]);
+            self.buf.push((timestamp_ms >> 24) as u8);
+            self.buf.extend_from_slice(&[0, 0, 0]);
+            self.buf.extend_from_slice(data);
+            let backpointer = backpointer.unwrap_or(TAG_HEADER_SIZE as u32 + size);
+            self.buf.extend_from_slice(&backpointer.to_be_bytes());
+            self
+        }
+
+        fn tag(self, tag_type: u8, timestamp_ms: u32, data: &[u8]) -> Self {
+            self.tag_with_backpointer(tag_type, timestamp_ms, data, None)
+        }
+
+        fn video_header(self) -> Self {
+            // AVC sequence header with a 1920x1080 baseline SPS
+            let mut data = vec![
+                0x17, 0x00, 0, 0, 0, 0x01, 0x42, 0xc0, 0x1f, 0xff, 0xe1, 0x00,
+            ];
+            let sps = [0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+            data.push(sps.len() as u8);
+            data.extend_from_slice(&sps);
+            data.extend_from_slice(&[0x01, 0x00, 0x04, 0x68, 0xce, 0x3c, 0x80]);
+            self.tag(9, 0, &data)
+        }
+
+        fn audio_header(self) -> Self {
+            // AAC LC, 44.1kHz, stereo
+            self.tag(8, 0, &[0xAF, 0x00, 0x12, 0x10])
+        }
+
+        fn video(self, timestamp_ms: u32, keyframe: bool) -> Self {
+            let frame_type = if keyframe { 0x17 } else { 0x27 };
+            let marker = (timestamp_ms & 0xff) as u8;
+            self.tag(9, timestamp_ms, &[frame_type, 0x01, 0, 0, 0, marker])
+        }
+
+        fn audio(self, timestamp_ms: u32) -> Self {
+            let marker = (timestamp_ms & 0xff) as u8;
+            self.tag(8, timestamp_ms, &[0xAF, 0x01, 0x21, marker])
+        }
+
+        fn build(self) -> Vec<u8> {
+            self.buf
+        }
+    }
+
+    fn analyze(bytes: Vec<u8>) -> AnalysisReport {
+        analyze_reader(Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn test_clean_stream_has_no_issues() {
+        let mut builder = FileBuilder::new().video_header().audio_header();
+        for i in 0..100 {
+            builder = builder.video(i * 40, i % 50 == 0).audio(i * 40 + 10);
+        }
+        let report = analyze(builder.build());
+
+        assert!(!report.has_issues(), "{:?}", report.issues);
+        assert_eq!(report.tag_counts.total, 202);
+        assert_eq!(report.tag_counts.video, 101);
+        assert_eq!(report.tag_counts.audio, 101);
+        assert_eq!(report.tag_counts.video_sequence_headers, 1);
+        assert_eq!(report.tag_counts.audio_sequence_headers, 1);
+        assert_eq!(report.file_info.keyframe_count, 2);
+
+        let video = report.video_params.unwrap();
+        assert_eq!(video.codec, "AVC");
+        assert_eq!(video.width, Some(1920));
+        assert_eq!(video.height, Some(1080));
+
+        let audio = report.audio_params.unwrap();
+        assert_eq!(audio.sample_rate, Some(44100));
+        assert_eq!(audio.channels, Some(2));
+    }
+
+    #[test]
+    fn test_issues_are_located() {
+        let builder = FileBuilder::new()
+            .video(0, true) // index 0: no sequence header yet
+            .video_header()
+            .audio_header()
+            .video(40, true)
+            .audio(40);
+        let regression_offset = builder.offset();
+        let builder = builder
+            .video(20, false) // index 5: goes backwards
+            .audio(60)
+            .audio(60); // index 7: exact duplicate
+        let backpointer_offset = builder.offset();
+        let builder = builder
+            .tag_with_backpointer(9, 80, &[0x27, 0x01, 0, 0, 0, 0x50], Some(1234))
+            .audio(2000); // index 9: gap in audio
+        let report = analyze(builder.build());
+
+        let kinds: Vec<_> = report.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                IssueKind::MissingSequenceHeader,
+                IssueKind::TimestampRegression,
+                IssueKind::DuplicateTag,
+                IssueKind::BrokenBackpointer,
+                IssueKind::GapInAudio,
+            ]
+        );
+
+        let missing = &report.issues[0];
+        assert_eq!((missing.offset, missing.tag_index), (13, 0));
+
+        let regression = &report.issues[1];
+        assert_eq!(regression.offset, regression_offset);
+        assert_eq!(regression.tag_index, 5);
+        assert_eq!(regression.timestamp_ms, 20);
+
+        assert_eq!(report.issues[2].tag_index, 7);
+
+        // The backpointer field follows the 17 byte video tag
+        let backpointer = &report.issues[3];
+        assert_eq!(backpointer.offset, backpointer_offset + 17);
+        assert_eq!(backpointer.tag_index, 9);
+        assert!(backpointer.message.contains("1234"));
+
+        assert_eq!(report.issues[4].tag_index, 9);
+        assert_eq!(report.issues[4].timestamp_ms, 2000);
+    }
+
+    #[test]
+    fn test_truncated_tag_stops_analysis() {
+        let mut bytes = FileBuilder::new()
+            .video_header()
+            .video(0, true)
+            .video(40, false)
+            .build();
+        // Cut the last tag in half, including its backpointer
+        bytes.truncate(bytes.len() - 12);
+        let report = analyze(bytes);
+
+        assert_eq!(report.tag_counts.video, 2);
+        assert_eq!(report.count(IssueKind::TruncatedTag), 1);
+        assert_eq!(report.issues[0].tag_index, 2);
+    }
+
+    #[test]
+    fn test_issue_limit() {
+        let mut builder = FileBuilder::new().audio_header();
+        for i in 0..(MAX_REPORTED_ISSUES as u32 + 10) {
+            builder = builder.audio(i * 2000);
+        }
+        let report = analyze(builder.build());
+
+        assert_eq!(report.issues.len(), MAX_REPORTED_ISSUES);
+        assert_eq!(report.suppressed_issues, 9);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_report_json() {
+        let bytes = FileBuilder::new()
+            .video_header()
+            .video(40, true)
+            .video(0, false)
+            .build();
+        let report = analyze(bytes);
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["issues"][0]["kind"], "timestamp_regression");
+        assert_eq!(json["issues"][0]["tag_index"], 2);
+        assert_eq!(json["video_params"]["width"], 1920);
+        assert_eq!(json["tag_counts"]["video"], 3);
+    }
+}
@@ -8,6 +8,8 @@
 //! - Updates metadata in FLV files with accurate statistics
 //! - Handles both direct replacement and file rewriting when metadata size changes
 //! - Manages keyframe indices for proper seeking functionality
+//! - Patches a reserved keyframes placeholder in place without moving any tag
+//! - Rewrites the statistics of such a tag in place when a file is finalized
 //!
 //! ## License
 //!
@@ -24,12 +26,17 @@ use std::{
     path::Path,
 };
 
-use flv::tag::FlvTagType;
+use amf0::{Amf0Encoder, Amf0Marker};
+use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use flv::{framing, tag::FlvTagType};
 use tracing::{debug, info, trace, warn};
 
 use crate::{
-    amf::{builder::OnMetaDataBuilder, model::AmfScriptData},
-    analyzer::FlvStats,
+    amf::{
+        builder::{KEYFRAMES_PADDING_KEY, OnMetaDataBuilder, RESERVED_BYTES_PER_KEYFRAME},
+        model::AmfScriptData,
+    },
+    analyzer::{FlvStats, Keyframe},
     utils::{self, shift_content_backward, shift_content_forward, write_flv_tag},
 };
 
@@ -42,6 +49,301 @@ pub enum ScriptModifierError {
     Amf0Write(#[from] amf0::Amf0WriteError),
     #[error("Script data error: {0}")]
     ScriptData(&'static str),
+    #[error("No reserved keyframes placeholder found in onMetaData")]
+    PlaceholderNotFound,
+    #[error("Keyframes placeholder too small: {required} keyframes, room for {capacity}")]
+    PlaceholderTooSmall { required: usize, capacity: usize },
+    #[error("PreviousTagSize mismatch after script tag: expected {expected}, found {found}")]
+    PreviousTagSizeMismatch { expected: u32, found: u32 },
+    #[error("Updated onMetaData takes {found} bytes, the tag has {expected}")]
+    MetadataSizeChanged { expected: usize, found: usize },
+}
+
+/// In-place editing of an `onMetaData` tag written with reserved keyframe space.
+pub struct ScriptModifier;
+
+/// Location of the reserved `keyframes` object within the file.
+struct ReservedKeyframes {
+    /// Absolute offset of the `times` property key.
+    position: u64,
+    /// Size of the region from `times` up to and including the object end marker.
+    len: usize,
+    /// Number of keyframes the region can hold.
+    capacity: usize,
+}
+
+impl ScriptModifier {
+    /// Writes `keyframes` into the placeholder reserved by
+    /// [`OnMetaDataBuilder::with_reserved_keyframes`], leaving the tag size unchanged.
+    ///
+    /// Only the keyframes object is rewritten, so every tag after the script tag
+    /// keeps its offset and the given file positions stay valid. The placeholder
+    /// may be patched again as more keyframes become available.
+    ///
+    /// Returns the number of bytes written.
+    pub fn patch_in_place<F: Read + Write + Seek>(
+        file: &mut F,
+        keyframes: &[Keyframe],
+    ) -> Result<u64, ScriptModifierError> {
+        let reserved = Self::find_reserved_keyframes(file)?;
+        if keyframes.len() > reserved.capacity {
+            return Err(ScriptModifierError::PlaceholderTooSmall {
+                required: keyframes.len(),
+                capacity: reserved.capacity,
+            });
+        }
+
+        let mut buf = Vec::with_capacity(reserved.len);
+        Amf0Encoder::write_property_key(&mut buf, "times")?;
+        buf.write_u8(Amf0Marker::StrictArray as u8)?;
+        buf.write_u32::<BigEndian>(keyframes.len() as u32)?;
+        for keyframe in keyframes {
+            Amf0Encoder::encode_number(&mut buf, keyframe.timestamp_s)?;
+        }
+
+        Amf0Encoder::write_property_key(&mut buf, "filepositions")?;
+        buf.write_u8(Amf0Marker::StrictArray as u8)?;
+        buf.write_u32::<BigEndian>(keyframes.len() as u32)?;
+        for keyframe in keyframes {
+            Amf0Encoder::encode_number(&mut buf, keyframe.file_position as f64)?;
+        }
+
+        let padding = (reserved.capacity - keyframes.len()) * RESERVED_BYTES_PER_KEYFRAME;
+        Amf0Encoder::write_property_key(&mut buf, KEYFRAMES_PADDING_KEY)?;
+        Amf0Encoder::encode_long_string(&mut buf, &" ".repeat(padding))?;
+        Amf0Encoder::object_eof(&mut buf)?;
+        debug_assert_eq!(buf.len(), reserved.len);
+
+        file.seek(io::SeekFrom::Start(reserved.position))?;
+        file.write_all(&buf)?;
+        file.flush()?;
+
+        debug!(
+            "Patched {} keyframes in place ({} bytes at {})",
+            keyframes.len(),
+            buf.len(),
+            reserved.position
+        );
+        Ok(buf.len() as u64)
+    }
+
+    /// Rewrites the whole `onMetaData` tag from `stats` without changing its size.
+    ///
+    /// Duration, file size, data rates, frame rate, resolution and the last
+    /// timestamps are replaced or added, then the keyframes are patched into the
+    /// reserved placeholder as in [`Self::patch_in_place`]. The fields always
+    /// emitted by [`OnMetaDataBuilder`] have a fixed size, so the reserved padding
+    /// keeps the tag size unchanged and no tag is moved.
+    ///
+    /// Returns the number of bytes written.
+    pub fn finalize_in_place<F: Read + Write + Seek>(
+        file: &mut F,
+        stats: &FlvStats,
+    ) -> Result<u64, ScriptModifierError> {
+        let (position, payload) = Self::find_metadata_payload(file)?;
+        let (_, _, capacity) =
+            parse_reserved_keyframes(&payload).ok_or(ScriptModifierError::PlaceholderNotFound)?;
+
+        let keyframes = stats
+            .video_stats
+            .as_ref()
+            .map_or(&[][..], |video_stats| video_stats.keyframes.as_slice());
+        if keyframes.len() > capacity {
+            return Err(ScriptModifierError::PlaceholderTooSmall {
+                required: keyframes.len(),
+                capacity,
+            });
+        }
+
+        let script = flv::script::ScriptData::demux(&mut io::Cursor::new(payload.clone()))?;
+        let props = script
+            .data
+            .first()
+            .and_then(|value| value.as_object_properties())
+            .ok_or(ScriptModifierError::ScriptData(
+                "First script tag data is not an object",
+            ))?;
+        let (buffer, _) =
+            OnMetaDataBuilder::from_script_data(AmfScriptData::from_amf_object_ref(props)?)
+                .with_stats(stats)
+                .with_reserved_keyframes(capacity)
+                .build_bytes(payload.len() as u32, false)?;
+        if buffer.len() != payload.len() {
+            return Err(ScriptModifierError::MetadataSizeChanged {
+                expected: payload.len(),
+                found: buffer.len(),
+            });
+        }
+
+        file.seek(io::SeekFrom::Start(position))?;
+        file.write_all(&buffer)?;
+        let written = buffer.len() as u64 + Self::patch_in_place(file, keyframes)?;
+
+        debug!(
+            "Finalized onMetaData in place with {} keyframes ({written} bytes at {position})",
+            keyframes.len()
+        );
+        Ok(written)
+    }
+
+    /// Rebuilds an `onMetaData` payload with room for `capacity` keyframes, so that it can
+    /// be finalized in place once the file is complete.
+    ///
+    /// Payloads that already reserve keyframes are returned unchanged.
+    pub fn reserve_keyframes(
+        payload: &bytes::Bytes,
+        capacity: usize,
+    ) -> Result<bytes::Bytes, ScriptModifierError> {
+        if parse_reserved_keyframes(payload).is_some() {
+            return Ok(payload.clone());
+        }
+
+        let script = flv::script::ScriptData::demux(&mut io::Cursor::new(payload.clone()))?;
+        let props = script
+            .data
+            .first()
+            .and_then(|value| value.as_object_properties())
+            .ok_or(ScriptModifierError::ScriptData(
+                "First script tag data is not an object",
+            ))?;
+        let (buffer, _) =
+            OnMetaDataBuilder::from_script_data(AmfScriptData::from_amf_object_ref(props)?)
+                .with_reserved_keyframes(capacity)
+                .build_bytes(payload.len() as u32, false)?;
+        Ok(bytes::Bytes::from(buffer))
+    }
+
+    /// Scans for the `onMetaData` tag and validates its reserved keyframes object.
+    fn find_reserved_keyframes<F: Read + Seek>(
+        file: &mut F,
+    ) -> Result<ReservedKeyframes, ScriptModifierError> {
+        let (position, payload) = Self::find_metadata_payload(file)?;
+        let (offset, len, capacity) =
+            parse_reserved_keyframes(&payload).ok_or(ScriptModifierError::PlaceholderNotFound)?;
+        Ok(ReservedKeyframes {
+            position: position + offset as u64,
+            len,
+            capacity,
+        })
+    }
+
+    /// Scans for the `onMetaData` tag, returning the absolute offset of its payload
+    /// and the payload itself.
+    fn find_metadata_payload<F: Read + Seek>(
+        file: &mut F,
+    ) -> Result<(u64, bytes::Bytes), ScriptModifierError> {
+        // 9-byte header + 4-byte PreviousTagSize0
+        file.seek(io::SeekFrom::Start(13))?;
+
+        loop {
+            let tag_start_pos = file.stream_position()?;
+            let mut header = [0u8; framing::TAG_HEADER_SIZE];
+            if let Err(e) = file.read_exact(&mut header) {
+                if e.kind() == io::ErrorKind::UnexpectedEof {
+                    return Err(ScriptModifierError::ScriptData(
+                        "No onMetaData script tag found",
+                    ));
+                }
+                return Err(e.into());
+            }
+            let header = framing::parse_tag_header_bytes(header)?;
+            let data_size = header.data_size as usize;
+
+            if header.tag_type != FlvTagType::ScriptData {
+                file.seek(io::SeekFrom::Current(
+                    (data_size + framing::PREV_TAG_SIZE_FIELD_SIZE) as i64,
+                ))?;
+                continue;
+            }
+
+            let mut payload = vec![0u8; data_size];
+            file.read_exact(&mut payload)?;
+            let payload = bytes::Bytes::from(payload);
+
+            let mut cursor = io::Cursor::new(payload.clone());
+            let is_metadata = flv::script::ScriptData::demux(&mut cursor)
+                .is_ok_and(|data| data.name == crate::AMF0_ON_METADATA);
+
+            let mut prev_tag_size = [0u8; framing::PREV_TAG_SIZE_FIELD_SIZE];
+            file.read_exact(&mut prev_tag_size)?;
+            if !is_metadata {
+                continue;
+            }
+
+            // The tag size never changes, so a valid back-pointer stays valid after patching
+            let expected = (framing::TAG_HEADER_SIZE + data_size) as u32;
+            let found = framing::parse_prev_tag_size(prev_tag_size);
+            if found != expected {
+                return Err(ScriptModifierError::PreviousTagSizeMismatch { expected, found });
+            }
+
+            return Ok((tag_start_pos + framing::TAG_HEADER_SIZE as u64, payload));
+        }
+    }
+}
+
+/// Finds the reserved keyframes object in an `onMetaData` payload.
+///
+/// Returns the offset of the `times` key, the length of the region up to the end of
+/// the `keyframes` object, and the number of keyframes the region can hold.
+fn parse_reserved_keyframes(payload: &[u8]) -> Option<(usize, usize, usize)> {
+    fn expect_key(payload: &[u8], pos: &mut usize, key: &str) -> Option<()> {
+        let end = *pos + 2 + key.len();
+        let bytes = payload.get(*pos..end)?;
+        if BigEndian::read_u16(bytes) as usize != key.len() || &bytes[2..] != key.as_bytes() {
+            return None;
+        }
+        *pos = end;
+        Some(())
+    }
+
+    fn read_u32(payload: &[u8], pos: &mut usize) -> Option<usize> {
+        let value = BigEndian::read_u32(payload.get(*pos..*pos + 4)?) as usize;
+        *pos += 4;
+        Some(value)
+    }
+
+    fn skip_numbers(payload: &[u8], pos: &mut usize) -> Option<usize> {
+        if *payload.get(*pos)? != Amf0Marker::StrictArray as u8 {
+            return None;
+        }
+        *pos += 1;
+        let count = read_u32(payload, pos)?;
+        *pos = pos.checked_add(count.checked_mul(9)?)?;
+        Some(count)
+    }
+
+    let mut marker = Vec::with_capacity(2 + "keyframes".len() + 1);
+    Amf0Encoder::write_property_key(&mut marker, "keyframes").ok()?;
+    marker.push(Amf0Marker::Object as u8);
+    let start = payload
+        .windows(marker.len())
+        .position(|w| w == marker.as_slice())?
+        + marker.len();
+
+    let mut pos = start;
+    expect_key(payload, &mut pos, "times")?;
+    let times = skip_numbers(payload, &mut pos)?;
+    expect_key(payload, &mut pos, "filepositions")?;
+    let filepositions = skip_numbers(payload, &mut pos)?;
+    if times != filepositions {
+        return None;
+    }
+
+    expect_key(payload, &mut pos, KEYFRAMES_PADDING_KEY)?;
+    if *payload.get(pos)? != Amf0Marker::LongString as u8 {
+        return None;
+    }
+    pos += 1;
+    let padding = read_u32(payload, &mut pos)?;
+    pos = pos.checked_add(padding)?;
+    if payload.get(pos..pos + 3)? != [0x00, 0x00, Amf0Marker::ObjectEnd as u8] {
+        return None;
+    }
+    pos += 3;
+
+    let capacity = times + padding / RESERVED_BYTES_PER_KEYFRAME;
+    Some((start, pos - start, capacity))
 }
 
 /// Injects stats into the script data section of an FLV file.
@@ -56,6 +358,20 @@ pub fn inject_stats_into_script_data(
 ) -> Result<(), ScriptModifierError> {
     debug!("Injecting stats into script data section.");
 
+    // Tags written with a reserved keyframes placeholder are updated without moving the media
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(file_path)?;
+    match ScriptModifier::finalize_in_place(&mut file, stats) {
+        Ok(written) => {
+            debug!("Injected stats in place ({written} bytes written)");
+            return Ok(());
+        }
+        Err(e) => debug!("Cannot inject stats in place, rewriting script data: {e}"),
+    }
+    drop(file);
+
     // Create a backup of the file
     // create_backup(file_path)?;
 
@@ -341,7 +657,427 @@ mod tests {
             .map(|(_, v)| v)
             .expect("Expected duration field");
 
-        assert_eq!(*duration, Amf0Value::Number(stats.duration as f64));
+        assert_eq!(*duration, Amf0Value::Number(0.1));
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    /// File wrapper counting the bytes written through it
+    struct CountingFile {
+        inner: File,
+        written: u64,
+    }
+
+    impl Read for CountingFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl Write for CountingFile {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = self.inner.write(buf)?;
+            self.written += n as u64;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    impl Seek for CountingFile {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("flv_fix_{name}_{unique}.flv"))
+    }
+
+    /// Writes an FLV whose first tag is `script_payload`, followed by a video tag,
+    /// then grows it to `total_size` bytes.
+    fn write_flv_with_script(path: &Path, script_payload: Vec<u8>, total_size: u64) {
+        use crate::test_utils;
+        use flv::{FlvData, FlvHeader, FlvWriter};
+        use std::io::BufWriter;
+
+        {
+            let file = File::create(path).unwrap();
+            let mut writer = FlvWriter::new(BufWriter::new(file)).unwrap();
+            writer.write_header(&FlvHeader::new(true, true)).unwrap();
+
+            for data in [
+                test_utils::create_test_tag(FlvTagType::ScriptData, 0, script_payload),
+                test_utils::create_video_tag(0, true),
+            ] {
+                let FlvData::Tag(tag) = data else {
+                    panic!("Expected tag");
+                };
+                writer.write_tag_f(&tag).unwrap();
+            }
+            writer.close().unwrap();
+        }
+
+        // The tail stands in for hundreds of MB of media and is never touched
+        let file = fs::OpenOptions::new().write(true).open(path).unwrap();
+        if file.metadata().unwrap().len() < total_size {
+            file.set_len(total_size).unwrap();
+        }
+    }
+
+    fn reserved_payload(capacity: usize) -> Vec<u8> {
+        OnMetaDataBuilder::new()
+            .with_duration(10.0)
+            .with_reserved_keyframes(capacity)
+            .build_bytes(0, false)
+            .unwrap()
+            .0
+    }
+
+    fn read_metadata(path: &Path) -> (Vec<f64>, Vec<f64>, usize) {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        reader.seek(io::SeekFrom::Start(13)).unwrap();
+        let (tag, _) = FlvParser::parse_tag(&mut reader).unwrap().unwrap();
+        let script = ScriptData::demux(&mut Cursor::new(tag.data.clone())).unwrap();
+        let props = script.data[0].as_object_properties().unwrap();
+        let (_, keyframes) = props.iter().find(|(k, _)| k == "keyframes").unwrap();
+        let keyframes = keyframes.as_object_properties().unwrap();
+
+        let numbers = |key: &str| -> Vec<f64> {
+            let (_, value) = keyframes.iter().find(|(k, _)| k == key).unwrap();
+            value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_number().unwrap())
+                .collect()
+        };
+        let (_, padding) = keyframes.iter().find(|(k, _)| k == "padding").unwrap();
+
+        (
+            numbers("times"),
+            numbers("filepositions"),
+            padding.as_str().unwrap().len(),
+        )
+    }
+
+    #[test]
+    fn patch_in_place_large_file_writes_only_index() {
+        let path = temp_path("patch_in_place");
+        let total_size = 300 * 1024 * 1024;
+        write_flv_with_script(&path, reserved_payload(1000), total_size);
+
+        let keyframes: Vec<Keyframe> = (0..600)
+            .map(|i| Keyframe {
+                timestamp_s: i as f64 * 2.0,
+                file_position: 1000 + i as u64 * 500_000,
+            })
+            .collect();
+
+        let mut file = CountingFile {
+            inner: fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .unwrap(),
+            written: 0,
+        };
+        let written = ScriptModifier::patch_in_place(&mut file, &keyframes).unwrap();
+        assert_eq!(written, file.written);
+        drop(file);
+
+        // Only the reserved keyframes object was rewritten
+        let index_size = (1000 * RESERVED_BYTES_PER_KEYFRAME + 64) as u64;
+        assert!(written <= index_size, "wrote {written} bytes");
+        assert_eq!(fs::metadata(&path).unwrap().len(), total_size);
+
+        let (times, filepositions, padding) = read_metadata(&path);
+        assert_eq!(times.len(), 600);
+        assert_eq!(times[599], 1198.0);
+        assert_eq!(filepositions[1], 501_000.0);
+        assert_eq!(padding, 400 * RESERVED_BYTES_PER_KEYFRAME);
+
+        // The tag after the script tag did not move
+        let mut reader = BufReader::new(File::open(&path).unwrap());
+        reader.seek(io::SeekFrom::Start(13)).unwrap();
+        let (script, _) = FlvParser::parse_tag(&mut reader).unwrap().unwrap();
+        let mut prev_tag_size = [0u8; 4];
+        reader.read_exact(&mut prev_tag_size).unwrap();
+        assert_eq!(
+            u32::from_be_bytes(prev_tag_size) as usize,
+            11 + script.data.len()
+        );
+        let (_, tag_type) = FlvParser::parse_tag(&mut reader).unwrap().unwrap();
+        assert_eq!(tag_type, FlvTagType::Video);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn patch_in_place_can_be_repeated() {
+        let path = temp_path("patch_in_place_repeat");
+        write_flv_with_script(&path, reserved_payload(10), 0);
+        let original_size = fs::metadata(&path).unwrap().len();
+
+        let keyframe = |i: u64| Keyframe {
+            timestamp_s: i as f64,
+            file_position: 100 * i,
+        };
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+
+        let first: Vec<_> = (0..3).map(keyframe).collect();
+        ScriptModifier::patch_in_place(&mut file, &first).unwrap();
+        let all: Vec<_> = (0..10).map(keyframe).collect();
+        ScriptModifier::patch_in_place(&mut file, &all).unwrap();
+        drop(file);
+
+        let (times, filepositions, padding) = read_metadata(&path);
+        assert_eq!(times.len(), 10);
+        assert_eq!(filepositions[9], 900.0);
+        assert_eq!(padding, 0);
+        assert_eq!(fs::metadata(&path).unwrap().len(), original_size);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn patch_in_place_rejects_missing_or_small_placeholder() {
+        let keyframes: Vec<_> = (0..5)
+            .map(|i| Keyframe {
+                timestamp_s: i as f64,
+                file_position: i,
+            })
+            .collect();
+
+        let path = temp_path("patch_in_place_small");
+        write_flv_with_script(&path, reserved_payload(4), 0);
+        let before = fs::read(&path).unwrap();
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        assert!(matches!(
+            ScriptModifier::patch_in_place(&mut file, &keyframes),
+            Err(ScriptModifierError::PlaceholderTooSmall {
+                required: 5,
+                capacity: 4
+            })
+        ));
+        drop(file);
+        assert_eq!(fs::read(&path).unwrap(), before);
+        std::fs::remove_file(&path).ok();
+
+        // Spacer-style placeholders have no padding to reuse
+        let path = temp_path("patch_in_place_missing");
+        let payload = OnMetaDataBuilder::new()
+            .with_placeholder_keyframes(100)
+            .build_bytes(0, false)
+            .unwrap()
+            .0;
+        write_flv_with_script(&path, payload, 0);
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        assert!(matches!(
+            ScriptModifier::patch_in_place(&mut file, &keyframes),
+            Err(ScriptModifierError::PlaceholderNotFound)
+        ));
+        drop(file);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn reserve_keyframes_adds_placeholder_once() {
+        let payload = bytes::Bytes::from(
+            OnMetaDataBuilder::new()
+                .with_duration(10.0)
+                .with_placeholder_keyframes(100)
+                .build_bytes(0, false)
+                .unwrap()
+                .0,
+        );
+        assert!(parse_reserved_keyframes(&payload).is_none());
+
+        let reserved = ScriptModifier::reserve_keyframes(&payload, 50).unwrap();
+        let (_, _, capacity) = parse_reserved_keyframes(&reserved).unwrap();
+        assert_eq!(capacity, 50);
+        let script = ScriptData::demux(&mut Cursor::new(reserved.clone())).unwrap();
+        assert_eq!(
+            script.data[0].get("duration").and_then(|v| v.as_number()),
+            Some(10.0)
+        );
+
+        // Already reserved payloads are kept as they are
+        assert_eq!(
+            ScriptModifier::reserve_keyframes(&reserved, 10).unwrap(),
+            reserved
+        );
+    }
+
+    #[test]
+    fn patch_in_place_checks_previous_tag_size() {
+        let path = temp_path("patch_in_place_prev_size");
+        let payload = reserved_payload(4);
+        let prev_tag_size_pos = 13 + 11 + payload.len() as u64;
+        write_flv_with_script(&path, payload, 0);
+
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        file.seek(io::SeekFrom::Start(prev_tag_size_pos)).unwrap();
+        file.write_all(&[0, 0, 0, 1]).unwrap();
+
+        assert!(matches!(
+            ScriptModifier::patch_in_place(&mut file, &[]),
+            Err(ScriptModifierError::PreviousTagSizeMismatch { found: 1, .. })
+        ));
+        std::fs::remove_file(&path).ok();
+    }
+
+    /// Analyzes every tag of the file at `path` from scratch
+    fn analyze_file(path: &Path) -> FlvStats {
+        let mut analyzer = FlvAnalyzer::default();
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        let header = FlvParser::parse_header(&mut reader).unwrap();
+        analyzer.analyze_header(&header).unwrap();
+        FlvParser::parse_tags(
+            &mut reader,
+            |tag, _, _| analyzer.analyze_tag(tag).unwrap(),
+            9,
+        )
+        .unwrap();
+        analyzer.build_stats().unwrap().clone()
+    }
+
+    #[test]
+    fn finalize_in_place_matches_reanalysis() {
+        use crate::test_utils::create_test_tag;
+        use flv::{FlvData, FlvHeader, FlvWriter};
+        use std::io::BufWriter;
+
+        // x264 High@3.1 1280x720, 25fps signalled in the VUI timing info
+        let sps = [
+            0x67, 0x64, 0x00, 0x1f, 0xac, 0xd1, 0x40, 0x50, 0x05, 0xba, 0x10, 0x00, 0x00, 0x03,
+            0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0x2e, 0x06, 0x00, 0x02, 0xdc, 0x6c, 0x00, 0x05,
+            0xb8, 0xda, 0x6c, 0x30, 0x07, 0x8c, 0x18, 0xcb,
+        ];
+        let mut sequence_header = vec![0x17, 0x00, 0, 0, 0, 0x01, 0x64, 0x00, 0x1f, 0xff, 0xe1];
+        sequence_header.extend_from_slice(&(sps.len() as u16).to_be_bytes());
+        sequence_header.extend_from_slice(&sps);
+        sequence_header.extend_from_slice(&[0x01, 0x00, 0x04, 0x68, 0xce, 0x3c, 0x80]);
+
+        let mut tags = vec![
+            create_test_tag(FlvTagType::ScriptData, 0, reserved_payload(16)),
+            create_test_tag(FlvTagType::Video, 0, sequence_header),
+            create_test_tag(FlvTagType::Audio, 0, vec![0xAF, 0x00, 0x12, 0x10]),
+        ];
+        let mut audio_ts = 0;
+        // 5 seconds with a keyframe every 2 seconds, tags sent at ~24fps so the
+        // measured rate differs from the signalled one
+        for frame in 0..120u32 {
+            let video_ts = frame * 42;
+            while audio_ts <= video_ts {
+                let mut data = vec![0xAF, 0x01];
+                data.resize(300, audio_ts as u8);
+                tags.push(create_test_tag(FlvTagType::Audio, audio_ts, data));
+                audio_ts += 23;
+            }
+            let keyframe = frame % 48 == 0;
+            let mut data = vec![if keyframe { 0x17 } else { 0x27 }, 0x01, 0, 0, 0];
+            data.resize(if keyframe { 4000 } else { 900 }, frame as u8);
+            tags.push(create_test_tag(FlvTagType::Video, video_ts, data));
+        }
+
+        // Write the file while analyzing it, as the writer task does
+        let path = temp_path("finalize_in_place");
+        let mut analyzer = FlvAnalyzer::default();
+        {
+            let file = File::create(&path).unwrap();
+            let mut writer = FlvWriter::new(BufWriter::new(file)).unwrap();
+            let header = FlvHeader::new(true, true);
+            writer.write_header(&header).unwrap();
+            analyzer.analyze_header(&header).unwrap();
+            for data in &tags {
+                let FlvData::Tag(tag) = data else {
+                    panic!("Expected tag");
+                };
+                writer.write_tag_f(tag).unwrap();
+                analyzer.analyze_tag(tag).unwrap();
+            }
+            writer.close().unwrap();
+        }
+        let file_size = fs::metadata(&path).unwrap().len();
+        let stats = analyzer.build_stats().unwrap().clone();
+        assert_eq!(stats.file_size, file_size);
+
+        let mut file = CountingFile {
+            inner: fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&path)
+                .unwrap(),
+            written: 0,
+        };
+        let written = ScriptModifier::finalize_in_place(&mut file, &stats).unwrap();
+        assert_eq!(written, file.written);
+        drop(file);
+        assert_eq!(fs::metadata(&path).unwrap().len(), file_size);
+
+        // The rewritten metadata agrees with a fresh analysis of the finished file
+        let truth = analyze_file(&path);
+        let video = truth.video_stats.as_ref().unwrap();
+        let mut reader = BufReader::new(File::open(&path).unwrap());
+        reader.seek(io::SeekFrom::Start(13)).unwrap();
+        let (tag, _) = FlvParser::parse_tag(&mut reader).unwrap().unwrap();
+        let script = ScriptData::demux(&mut Cursor::new(tag.data.clone())).unwrap();
+        let props = script.data[0].as_object_properties().unwrap();
+        let metadata = AmfScriptData::from_amf_object_ref(props).unwrap();
+
+        assert_eq!(
+            metadata.duration,
+            Some(truth.last_timestamp as f64 / 1000.0)
+        );
+        assert_eq!(metadata.filesize, Some(truth.file_size));
+        assert_eq!(metadata.width, Some(1280.0));
+        assert_eq!(metadata.height, Some(720.0));
+        assert_eq!(metadata.framerate, Some(25.0));
+        assert_eq!(video.video_frame_rate, 25.0);
+        assert_eq!(metadata.videodatarate, Some(video.video_data_rate as f64));
+        assert_eq!(metadata.audiodatarate, Some(truth.audio_data_rate as f64));
+        assert!(video.video_data_rate > 0.0 && truth.audio_data_rate > 0.0);
+        assert_eq!(metadata.lasttimestamp, Some(truth.last_timestamp));
+        assert_eq!(
+            metadata.lastkeyframetimestamp,
+            Some(video.last_keyframe_timestamp)
+        );
+        assert_eq!(
+            metadata.lastkeyframelocation,
+            Some(video.last_keyframe_position)
+        );
+
+        let (times, filepositions, _) = read_metadata(&path);
+        assert_eq!(times.len(), 3);
+        for ((time, position), keyframe) in times.iter().zip(&filepositions).zip(&video.keyframes) {
+            assert_eq!(*time, keyframe.timestamp_s);
+            assert_eq!(*position, keyframe.file_position as f64);
+        }
 
         std::fs::remove_file(&path).ok();
     }
@@ -13,6 +13,8 @@ use flv::header::FlvHeader;
 #[cfg(test)]
 use flv::tag::{FlvTag, FlvTagType};
 #[cfg(test)]
+use flv::video::VideoFourCC;
+#[cfg(test)]
 use std::borrow::Cow;
 
 /// Create a standard FLV header for testing
@@ -134,6 +136,51 @@ pub fn create_audio_sequence_header(timestamp: u32, version: u8) -> FlvData {
     create_test_tag(FlvTagType::Audio, timestamp, data)
 }
 
+/// HEVC decoder configuration record of a 2560x1440 stream
+#[cfg(test)]
+const HEVC_CONFIG_RECORD: &[u8] = b"\x01\x01@\0\0\0\x90\0\0\0\0\0\x99\xf0\0\xfc\xfd\xf8\xf8\0\0\x0f\x03 \0\x01\0\x18@\x01\x0c\x01\xff\xff\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\x95@\x90!\0\x01\0=B\x01\x01\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\xa0\x01@ \x05\xa1e\x95R\x90\x84d_\xf8\xc0Z\x80\x80\x80\x82\0\0\x03\0\x02\0\0\x03\x01 \xc0\x0b\xbc\xa2\0\x02bX\0\x011-\x08\"\0\x01\0\x07D\x01\xc0\x93|\x0c\xc9";
+
+/// AV1 codec configuration record (av1C) with its sequence header OBU
+#[cfg(test)]
+const AV1_CONFIG_RECORD: &[u8] = &[
+    0x81, 0x0D, 0x0C, 0x00, // marker + version, profile + level, flags, no presentation delay
+    0x0A, 0x0F, 0x00, 0x00, 0x00, 0x6A, 0xEF, 0xBF, 0xE1, 0xBC, 0x02, 0x19, 0x90, 0x10, 0x10, 0x10,
+    0x40,
+];
+
+/// Create an Enhanced FLV sequence start for `fourcc` (hvc1 or av01)
+#[cfg(test)]
+pub fn create_enhanced_sequence_start(timestamp: u32, fourcc: VideoFourCC) -> FlvData {
+    let record = match fourcc {
+        VideoFourCC::Hvc1 => HEVC_CONFIG_RECORD,
+        VideoFourCC::Av01 => AV1_CONFIG_RECORD,
+        _ => unimplemented!("no test configuration record for {fourcc:?}"),
+    };
+    // IsExHeader (1) + keyframe (1) + PacketTypeSequenceStart (0)
+    let mut data = vec![0x90];
+    data.extend_from_slice(&fourcc.as_bytes());
+    data.extend_from_slice(record);
+    create_test_tag(FlvTagType::Video, timestamp, data)
+}
+
+/// Create an Enhanced FLV coded frame for `fourcc` with the specified keyframe flag
+#[cfg(test)]
+pub fn create_enhanced_video_tag(
+    timestamp: u32,
+    fourcc: VideoFourCC,
+    is_keyframe: bool,
+) -> FlvData {
+    // IsExHeader (1) + frame type (1=keyframe, 2=inter) + PacketTypeCodedFrames (1)
+    let frame_type = if is_keyframe { 1 } else { 2 };
+    let mut data = vec![0x80 | (frame_type << 4) | 1];
+    data.extend_from_slice(&fourcc.as_bytes());
+    if fourcc == VideoFourCC::Hvc1 {
+        data.extend_from_slice(&[0, 0, 0]); // Composition time
+    }
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x02, 0x26, 0x01]); // Dummy NALU
+    create_test_tag(FlvTagType::Video, timestamp, data)
+}
+
 /// Extract timestamps from processed items
 #[cfg(test)]
 pub fn extract_timestamps(items: &[FlvData]) -> Vec<u32> {
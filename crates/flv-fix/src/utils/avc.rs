@@ -0,0 +1,84 @@
+use std::io;
+
+use flv::FlvTag;
+use flv::video::{VideoCodecId, VideoFourCC, VideoTagCodec};
+use h264::AVCDecoderConfigurationRecord;
+use tracing::warn;
+
+/// Parses the decoder configuration record of a legacy or enhanced AVC sequence header tag.
+///
+/// Encoders in the wild put junk into the reserved bits or declare parameter sets longer
+/// than the record, so the record is parsed leniently and every problem recovered from is
+/// logged. Returns `None` for other tags, or if not even the record header is present.
+pub fn parse_avc_sequence_header(tag: &FlvTag) -> Option<AVCDecoderConfigurationRecord> {
+    let header = tag.video_header()?;
+    let is_avc = matches!(
+        header.codec,
+        VideoTagCodec::Legacy(VideoCodecId::Avc) | VideoTagCodec::FourCC(VideoFourCC::Avc1)
+    );
+    if !is_avc || !header.is_sequence_header() {
+        return None;
+    }
+
+    // The configuration record follows the 5-byte legacy or enhanced video header
+    let record = tag.data.slice(5.min(tag.data.len())..);
+    match AVCDecoderConfigurationRecord::parse_lenient(&mut io::Cursor::new(record)) {
+        Ok((config, warnings)) => {
+            for warning in warnings {
+                warn!(
+                    timestamp = tag.timestamp_ms,
+                    %warning,
+                    "Recovered from a malformed AVC sequence header"
+                );
+            }
+            Some(config)
+        }
+        Err(e) => {
+            warn!(timestamp = tag.timestamp_ms, error = %e, "Invalid AVC sequence header");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use flv::FlvTag;
+    use flv::tag::FlvTagType;
+
+    use super::parse_avc_sequence_header;
+
+    fn video_tag(data: Vec<u8>) -> FlvTag {
+        FlvTag {
+            timestamp_ms: 0,
+            stream_id: 0,
+            tag_type: FlvTagType::Video,
+            is_filtered: false,
+            data: Bytes::from(data),
+        }
+    }
+
+    #[test]
+    fn test_parse_avc_sequence_header_junk_reserved_bits() {
+        let sps = b"\x67\x64\x00\x1F\xAC\xD9\x41\xE0\x6D\xF9\xE6\xA0\x20\x20\x28\x00\x00\x03\x00\x08\x00\x00\x03\x01\xE0";
+        // keyframe + AVC, sequence header, composition time
+        let mut data = vec![0x17, 0x00, 0x00, 0x00, 0x00];
+        // the reserved bits before length_size_minus_one and the SPS count are unset
+        data.extend_from_slice(&[0x01, 0x64, 0x00, 0x1F, 0x03, 0x01, 0x00, sps.len() as u8]);
+        data.extend_from_slice(sps);
+        // the record ends before the PPS count
+
+        let config = parse_avc_sequence_header(&video_tag(data)).unwrap();
+        assert_eq!(config.length_size_minus_one, 3);
+        assert_eq!(config.sps, vec![Bytes::from_static(sps)]);
+        assert!(config.pps.is_empty());
+    }
+
+    #[test]
+    fn test_parse_avc_sequence_header_other_tags() {
+        // an AVC NALU
+        assert!(parse_avc_sequence_header(&video_tag(vec![0x17, 0x01, 0, 0, 0, 1, 2])).is_none());
+        // too short to hold the record header
+        assert!(parse_avc_sequence_header(&video_tag(vec![0x17, 0x00, 0, 0, 0, 1])).is_none());
+    }
+}
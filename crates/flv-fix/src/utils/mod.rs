@@ -1,5 +1,7 @@
+pub mod avc;
 pub mod file_utils;
 
+pub use avc::parse_avc_sequence_header;
 pub use file_utils::{
     DEFAULT_BUFFER_SIZE, FLV_HEADER_SIZE, FLV_PREVIOUS_TAG_SIZE, create_backup,
     shift_content_backward, shift_content_forward, write_flv_tag,
@@ -1,11 +1,12 @@
 use pipeline_common::{
-    PipelineError, ProgressConfig, ProtocolWriter, SplitReason, WriterError, WriterProgress,
-    WriterStats,
+    OutputSink, PipelineError, ProgressConfig, ProtocolWriter, SegmentHook, SplitReason,
+    WriterError, WriterProgress, WriterStats,
 };
 
 use crate::writer_task::{FlvFormatStrategy, FlvWriterConfig};
 use flv::data::FlvData;
-use pipeline_common::{WriterConfig, WriterState, WriterTask};
+use pipeline_common::{StreamerContext, WriterConfig, WriterState, WriterTask};
+use std::sync::Arc;
 
 /// A specialized writer task for FLV data.
 pub struct FlvWriter {
@@ -42,6 +43,43 @@ impl FlvWriter {
         self.writer_task.set_on_file_close_callback(callback);
     }
 
+    /// Report the files whose metadata is rewritten once complete as
+    /// [`PipelineEvent::MetadataRewritten`] events of `context`.
+    ///
+    /// [`PipelineEvent::MetadataRewritten`]: pipeline_common::PipelineEvent::MetadataRewritten
+    pub fn set_event_context(&mut self, context: Arc<StreamerContext>) {
+        self.writer_task.strategy_mut().set_context(context);
+    }
+
+    /// Add a hook invoked on the writer thread when segment files are opened and closed.
+    ///
+    /// A hook can rename or delete a segment once it is closed, see [`SegmentHook`].
+    pub fn add_segment_hook<H: SegmentHook>(&mut self, hook: H) {
+        self.writer_task.add_segment_hook(hook);
+    }
+
+    /// Hash every segment as it is written and record it in the manifest of `manifest`.
+    ///
+    /// Stats are then injected into local files before the segment hooks run instead of in
+    /// the background, so that the hashes cover the final files.
+    #[cfg(feature = "integrity")]
+    pub fn add_integrity_manifest(
+        &mut self,
+        manifest: pipeline_common::integrity::IntegrityManifest,
+    ) {
+        self.writer_task.add_integrity_manifest(manifest);
+    }
+
+    /// Name files that would overwrite an existing one `name_1`, `name_2`, ...
+    pub fn set_numbered_collisions(&mut self, numbered_collisions: bool) {
+        self.writer_task.config_mut().numbered_collisions = numbered_collisions;
+    }
+
+    /// Write files to `sink` instead of the local disk.
+    pub fn set_output_sink(&mut self, sink: OutputSink) {
+        self.writer_task.config_mut().sink = sink;
+    }
+
     /// Set a progress callback with default intervals (1MB bytes, 1000ms time).
     pub fn set_progress_callback<F>(&mut self, callback: F)
     where
@@ -1,20 +1,22 @@
 use crate::{
-    analyzer::{AnalyzerError, FlvAnalyzer},
-    script_modifier,
+    analyzer::{AnalyzerError, FlvAnalyzer, FlvStats},
+    operators::{MIN_INTERVAL_BETWEEN_KEYFRAMES_MS, ScriptFillerConfig},
+    script_modifier::{self, ScriptModifier},
 };
-use flv::{FlvData, FlvHeader, FlvWriter};
+use flv::{FlvData, FlvHeader, FlvWriter, script::ScriptData, tag::FlvTag};
 use pipeline_common::split_reason::SplitReason;
 use pipeline_common::{
-    FormatStrategy, PostWriteAction, WriterConfig, WriterState, expand_filename_template,
+    FormatStrategy, PipelineEvent, PostWriteAction, SinkWriter, StreamMetadata, StreamerContext,
+    WriterConfig, WriterState, expand_filename_template_with, template_uses_stream_metadata,
 };
 use std::{
-    fs::OpenOptions,
-    io::BufWriter,
+    io::{BufWriter, Cursor},
     path::{Path, PathBuf},
+    sync::Arc,
     time::Instant,
 };
 
-use tracing::{Span, info};
+use tracing::{Span, info, warn};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 
 /// Error type for FLV strategy
@@ -50,6 +52,17 @@ pub struct FlvFormatStrategy {
     last_status_bytes: u64,
     /// The most recent split reason received, if any.
     last_split_reason: Option<SplitReason>,
+    /// Stream title from onMetaData, kept across files.
+    title: Option<String>,
+    /// Whether the name of the current file no longer depends on stream metadata to come.
+    name_settled: bool,
+    /// Whether a media tag other than a sequence header was written to the current file.
+    media_tag_written: bool,
+    /// Whether the next onMetaData needs room reserved for the keyframes, as the output
+    /// cannot be rewritten once the file is complete.
+    reserve_metadata: bool,
+    /// Context of the pipeline feeding the writer, told about the rewritten metadata.
+    context: Option<Arc<StreamerContext>>,
 
     // Whether to use low-latency mode for metadata modification.
     enable_low_latency: bool,
@@ -66,10 +79,128 @@ impl FlvFormatStrategy {
             last_status_update: None,
             last_status_bytes: 0,
             last_split_reason: None,
+            title: None,
+            name_settled: true,
+            media_tag_written: false,
+            reserve_metadata: false,
+            context: None,
             enable_low_latency,
         }
     }
 
+    /// Emit a [`PipelineEvent::MetadataRewritten`] on `context` for every file whose
+    /// onMetaData is rewritten once complete.
+    pub fn set_context(&mut self, context: Arc<StreamerContext>) {
+        self.context = Some(context);
+    }
+
+    /// Report the rewritten metadata of `path`, logging it without a context.
+    fn metadata_rewritten(context: Option<&StreamerContext>, path: &Path, detail: &str) {
+        match context {
+            Some(context) => context.emit(PipelineEvent::MetadataRewritten {
+                path: path.to_path_buf(),
+                detail: detail.to_string(),
+            }),
+            None => info!(path = %path.display(), "{detail}"),
+        }
+    }
+
+    /// Stream metadata of the current file, for the variables of the file name template.
+    fn stream_metadata(&self) -> StreamMetadata {
+        let video = self.analyzer.video_codec_info();
+        StreamMetadata {
+            resolution: video.and_then(|info| info.width.zip(info.height)),
+            video_codec: video.map(|info| codec_name(&info.codec)),
+            audio_codec: self
+                .analyzer
+                .audio_codec_info()
+                .map(|info| codec_name(&info.codec)),
+            title: self.title.clone(),
+        }
+    }
+
+    /// Keep the title of an onMetaData script tag.
+    fn update_title(&mut self, tag: &FlvTag) {
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        if let Ok(amf_data) = ScriptData::demux(&mut cursor)
+            && amf_data.name == crate::AMF0_ON_METADATA
+            && let Some(title) = amf_data
+                .data
+                .first()
+                .and_then(|metadata| metadata.get("title"))
+                .and_then(|title| title.as_str())
+            && !title.is_empty()
+        {
+            self.title = Some(title.to_string());
+        }
+    }
+
+    /// Rebuilds the first onMetaData of a file on a non-seekable output with a reserved
+    /// keyframes placeholder, so that it can still be finalized before the upload completes.
+    fn reserve_metadata(&mut self, tag: &FlvTag) -> Option<FlvTag> {
+        if !self.reserve_metadata || !tag.is_script_tag() {
+            return None;
+        }
+        let mut cursor = std::io::Cursor::new(tag.data.clone());
+        if !ScriptData::demux(&mut cursor).is_ok_and(|data| data.name == crate::AMF0_ON_METADATA) {
+            return None;
+        }
+        self.reserve_metadata = false;
+
+        // As many keyframes as the keyframe index of the pipeline holds by default
+        let capacity = ScriptFillerConfig::default()
+            .keyframe_duration_ms
+            .div_ceil(MIN_INTERVAL_BETWEEN_KEYFRAMES_MS) as usize;
+        match ScriptModifier::reserve_keyframes(&tag.data, capacity) {
+            Ok(data) => Some(FlvTag {
+                data,
+                ..tag.clone()
+            }),
+            Err(e) => {
+                warn!(error = %e, "Failed to reserve room for the final onMetaData");
+                None
+            }
+        }
+    }
+
+    /// Finalizes the onMetaData of a file on a non-seekable output, possible as long as
+    /// the output holds back the start of the file.
+    fn finalize_remote_metadata(
+        output: &mut SinkWriter,
+        stats: &FlvStats,
+        path: &Path,
+        context: Option<&StreamerContext>,
+    ) {
+        let Some(head) = output.retained_head() else {
+            info!(path = %path.display(), "Output cannot be rewritten, onMetaData kept as is");
+            return;
+        };
+        let mut result = ScriptModifier::finalize_in_place(&mut Cursor::new(&mut *head), stats);
+        if matches!(
+            result,
+            Err(script_modifier::ScriptModifierError::MetadataSizeChanged { .. })
+        ) && !stats.cue_points.is_empty()
+        {
+            // The reserved room only fits the keyframes, the tag cannot grow anymore
+            warn!(path = %path.display(), "No room for the cue points in onMetaData");
+            let stats = FlvStats {
+                cue_points: Vec::new(),
+                ..stats.clone()
+            };
+            result = ScriptModifier::finalize_in_place(&mut Cursor::new(head), &stats);
+        }
+        match result {
+            Ok(_) => {
+                Self::metadata_rewritten(context, path, "Successfully injected stats before upload")
+            }
+            Err(e) => warn!(
+                path = %path.display(),
+                error = ?e,
+                "Failed to inject stats before upload"
+            ),
+        }
+    }
+
     fn calculate_duration(&self) -> u32 {
         self.analyzer.stats.calculate_duration()
     }
@@ -115,16 +246,16 @@ impl FlvFormatStrategy {
 }
 
 impl FormatStrategy<FlvData> for FlvFormatStrategy {
-    type Writer = FlvWriter<BufWriter<std::fs::File>>;
+    type Writer = FlvWriter<BufWriter<SinkWriter>>;
     type StrategyError = FlvStrategyError;
 
-    fn create_writer(&self, path: &Path) -> Result<Self::Writer, Self::StrategyError> {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
-        let buf_writer = BufWriter::with_capacity(1024 * 1024, file);
+    fn create_writer(
+        &self,
+        path: &Path,
+        config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
+        let output = config.open_output(path)?;
+        let buf_writer = BufWriter::with_capacity(1024 * 1024, output);
         Ok(FlvWriter::new(buf_writer)?)
     }
 
@@ -141,6 +272,8 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
             }
             FlvData::Tag(tag) => {
                 let mut bytes_written = 0;
+                let reserved = self.reserve_metadata(tag);
+                let tag = reserved.as_ref().unwrap_or(tag);
 
                 // If a header is pending, write it first.
                 if let Some(header) = self.pending_header.take() {
@@ -161,6 +294,14 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
                     .analyze_tag(tag)
                     .map_err(FlvStrategyError::Analysis)?;
 
+                if !self.name_settled {
+                    if tag.is_script_tag() {
+                        self.update_title(tag);
+                    } else if !tag.is_video_sequence_header() && !tag.is_audio_sequence_header() {
+                        self.media_tag_written = true;
+                    }
+                }
+
                 writer.write_tag_f(tag)?;
                 bytes_written += (11 + 4 + tag.data.len()) as u64;
                 Ok(bytes_written)
@@ -185,15 +326,38 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
         let sequence = state.file_sequence_number;
 
         let extension = &config.file_extension;
-        let file_name = expand_filename_template(&config.file_name_template, Some(sequence));
+        let file_name = expand_filename_template_with(
+            &config.file_name_template,
+            Some(sequence),
+            &self.stream_metadata(),
+        );
         config.base_path.join(format!("{file_name}.{extension}"))
     }
 
+    fn resolved_file_path(
+        &mut self,
+        config: &WriterConfig,
+        state: &WriterState,
+    ) -> Option<PathBuf> {
+        if self.name_settled {
+            return None;
+        }
+        // Settle once every variable is known, or once media follows the sequence headers
+        // and the missing ones will not show up in this file.
+        if !self.media_tag_written && !self.stream_metadata().covers(&config.file_name_template) {
+            return None;
+        }
+        self.name_settled = true;
+
+        let path = self.next_file_path(config, state);
+        (path != state.current_path).then_some(path)
+    }
+
     fn on_file_open(
         &mut self,
-        _writer: &mut Self::Writer,
+        writer: &mut Self::Writer,
         path: &Path,
-        _config: &WriterConfig,
+        config: &WriterConfig,
         _state: &WriterState,
     ) -> Result<u64, Self::StrategyError> {
         self.file_start_instant = Some(Instant::now());
@@ -202,6 +366,9 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
         self.last_status_update = None;
         self.last_status_bytes = 0;
         self.last_split_reason = None;
+        self.name_settled = !template_uses_stream_metadata(&config.file_name_template);
+        self.media_tag_written = false;
+        self.reserve_metadata = !writer.writer.get_ref().is_seekable();
 
         info!(path = %path.display(), "Opening segment");
 
@@ -217,19 +384,34 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
         &mut self,
         writer: &mut Self::Writer,
         path: &Path,
-        _config: &WriterConfig,
+        config: &WriterConfig,
         _state: &WriterState,
     ) -> Result<u64, Self::StrategyError> {
         writer.flush()?;
+        let output = writer.writer.get_mut();
 
         let duration_secs = self.calculate_duration();
         let tag_count = self.current_tag_count;
         let mut analyzer = std::mem::take(&mut self.analyzer);
 
-        if let Ok(stats) = analyzer.build_stats().cloned() {
+        if !output.is_seekable() {
+            // Remote files are finalized before the upload completes, nothing can follow
+            if let Ok(stats) = analyzer.build_stats() {
+                info!("Path : {}: {}", path.display(), stats);
+                Self::finalize_remote_metadata(output, stats, path, self.context.as_deref());
+            }
+            output.finish()?;
+            info!(
+                path = %path.display(),
+                tags = tag_count,
+                duration_secs = ?duration_secs,
+                "Closed segment"
+            );
+        } else if let Ok(stats) = analyzer.build_stats().cloned() {
             info!("Path : {}: {}", path.display(), &stats);
             let path_buf = path.to_path_buf();
             let enable_low_latency = self.enable_low_latency;
+            let context = self.context.clone();
 
             let task = move || {
                 match script_modifier::inject_stats_into_script_data(
@@ -237,9 +419,11 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
                     &stats,
                     enable_low_latency,
                 ) {
-                    Ok(_) => {
-                        tracing::info!(path = %path_buf.display(), "Successfully injected stats in background task");
-                    }
+                    Ok(_) => Self::metadata_rewritten(
+                        context.as_deref(),
+                        &path_buf,
+                        "Successfully injected stats in background task",
+                    ),
                     Err(e) => {
                         // The consumer may delete discarded/small segments immediately after close.
                         // Treat a missing file as an expected race rather than a warning.
@@ -271,8 +455,11 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
                 );
             };
 
-            // Prefer tokio's blocking pool when available, otherwise fall back to a plain thread.
-            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            // Files hashed on close must be final by then. Otherwise, prefer tokio's blocking
+            // pool when available and fall back to a plain thread.
+            if config.records_integrity() {
+                task();
+            } else if let Ok(handle) = tokio::runtime::Handle::try_current() {
                 handle.spawn_blocking(task);
             } else {
                 std::thread::spawn(task);
@@ -319,3 +506,12 @@ impl FormatStrategy<FlvData> for FlvFormatStrategy {
         self.last_split_reason.clone()
     }
 }
+
+/// File name form of a codec reported by the analyzer, e.g. `h264` for `AVC`.
+fn codec_name(codec: &str) -> String {
+    match codec {
+        "AVC" => "h264".to_string(),
+        "HEVC" => "hevc".to_string(),
+        codec => codec.to_ascii_lowercase(),
+    }
+}
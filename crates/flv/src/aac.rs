@@ -71,25 +71,22 @@ impl AacPacket {
 
     pub(crate) fn is_stereo(&self) -> bool {
         match self {
-            AacPacket::SequenceHeader(data) => {
+            AacPacket::SequenceHeader(data)
                 // Check if the first byte is 0xFF and the second byte is 0xF1
-                if data.len() >= 2 && data[0] == 0xFF && data[1] == 0xF1 {
+                if data.len() >= 2 && data[0] == 0xFF && data[1] == 0xF1 => {
                     // Check the channel configuration in the 4th byte
                     let channel_config = (data[3] >> 3) & 0x0F;
                     channel_config == 2 // Stereo
-                } else {
-                    false
                 }
-            }
             _ => false,
         }
     }
 
     pub(crate) fn sample_rate(&self) -> f32 {
         match self {
-            AacPacket::SequenceHeader(data) => {
+            AacPacket::SequenceHeader(data)
                 // Check if the first byte is 0xFF and the second byte is 0xF1
-                if data.len() >= 2 && data[0] == 0xFF && data[1] == 0xF1 {
+                if data.len() >= 2 && data[0] == 0xFF && data[1] == 0xF1 => {
                     // Check the sample rate index in the 2nd byte
                     let sample_rate_index = (data[2] >> 2) & 0x03;
                     match sample_rate_index {
@@ -99,26 +96,20 @@ impl AacPacket {
                         3 => 48000.0,
                         _ => 44100.0, // Default to 44100 Hz
                     }
-                } else {
-                    44100.0 // Default to 44100 Hz
                 }
-            }
             _ => 44100.0, // Default to 44100 Hz
         }
     }
 
     pub(crate) fn sample_size(&self) -> u32 {
         match self {
-            AacPacket::SequenceHeader(data) => {
+            AacPacket::SequenceHeader(data)
                 // Check if the first byte is 0xFF and the second byte is 0xF1
-                if data.len() >= 2 && data[0] == 0xFF && data[1] == 0xF1 {
+                if data.len() >= 2 && data[0] == 0xFF && data[1] == 0xF1 => {
                     // Check the sample size in the 3rd byte
                     let sample_size = (data[2] >> 4) & 0x0F;
                     sample_size as u32
-                } else {
-                    16 // Default to 16 bits
                 }
-            }
             _ => 16, // Default to 16 bits
         }
     }
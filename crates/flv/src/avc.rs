@@ -72,6 +72,19 @@ impl AvcPacket {
             _ => None,
         }
     }
+
+    /// Frame rate signalled by the timing info of the first SPS, if any.
+    pub fn get_frame_rate(&self) -> Option<f64> {
+        match self {
+            AvcPacket::SequenceHeader(config) => {
+                let sps = config.sps.first()?;
+                h264::Sps::parse_with_emulation_prevention(std::io::Cursor::new(sps))
+                    .ok()?
+                    .frame_rate()
+            }
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for AvcPacket {
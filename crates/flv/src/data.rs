@@ -1,4 +1,5 @@
 use bytes::Bytes;
+use pipeline_common::MemSized;
 pub use pipeline_common::split_reason::SplitReason;
 
 use crate::{header::FlvHeader, tag::FlvTag};
@@ -48,3 +49,9 @@ impl FlvData {
         }
     }
 }
+
+impl MemSized for FlvData {
+    fn mem_size(&self) -> usize {
+        self.size()
+    }
+}
@@ -10,8 +10,8 @@ pub enum FlvError {
     IncompleteData, // Keep this if FramedRead needs it, but decoder uses it less now
     #[error("Error parsing tag data: {0}")]
     TagParseError(String), // More specific error for demux failures
-    #[error("Resynchronization failed to find valid tag")]
-    ResyncFailed, // Optional: if resync gives up entirely
+    #[error("No valid tag found within {skipped} bytes after position {position}")]
+    ResyncFailed { position: u64, skipped: u64 },
     #[error("Invalid tag type encountered: {0}")]
     InvalidTagType(u8),
     #[error("Tag data size too large: {0}")]
@@ -103,6 +103,24 @@ impl HevcPacket {
             _ => None,
         }
     }
+
+    /// Frame rate signalled by the VUI timing info of the first SPS, if any.
+    pub fn get_frame_rate(&self) -> Option<f64> {
+        match self {
+            HevcPacket::SequenceStart(config) => {
+                let sps = config
+                    .arrays
+                    .iter()
+                    .find(|array| array.nal_unit_type == NALUnitType::SpsNut)?
+                    .nalus
+                    .first()?;
+                let sps_nalu = h265::SpsNALUnit::parse(std::io::Cursor::new(sps.clone())).ok()?;
+                let timing = sps_nalu.rbsp.vui_parameters?.vui_timing_info?;
+                Some(timing.time_scale.get() as f64 / timing.num_units_in_tick.get() as f64)
+            }
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for HevcPacket {
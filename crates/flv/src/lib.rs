@@ -23,6 +23,6 @@ pub use data::FlvData;
 pub use error::FlvError;
 pub use header::FlvHeader;
 pub use pipeline_common::split_reason::{AudioCodecInfo, SplitReason, VideoCodecInfo};
-pub use tag::{FlvTag, FlvTagType};
+pub use tag::{FlvTag, FlvTagRef, FlvTagType};
 pub use writer::FlvWriter;
 pub use writer_async::FlvEncoder;
@@ -26,6 +26,46 @@ const TAG_HEADER_SIZE: usize = framing::TAG_HEADER_SIZE;
 // Need at least a tag header and the *next* prev tag size
 const MIN_REQUIRED_AFTER_RESYNC: usize = TAG_HEADER_SIZE + PREV_TAG_SIZE_FIELD_SIZE;
 
+/// How a decoder in resynchronization mode skips corrupt data, see
+/// [`FlvDecoder::with_resync`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResyncConfig {
+    /// Bytes scanned for the next valid tag before decoding fails with
+    /// [`FlvError::ResyncFailed`].
+    pub max_scan_bytes: u64,
+    /// Largest difference between the timestamp of a tag found by scanning and the one of
+    /// the last tag decoded, in milliseconds.
+    pub timestamp_window_ms: u32,
+}
+
+impl Default for ResyncConfig {
+    fn default() -> Self {
+        Self {
+            max_scan_bytes: 64 * 1024 * 1024,
+            timestamp_window_ms: 60_000,
+        }
+    }
+}
+
+/// A corrupt region skipped by a decoder in resynchronization mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Repair {
+    /// Offset of the first skipped byte in the stream.
+    pub position: u64,
+    /// Number of bytes skipped.
+    pub skipped_bytes: u64,
+}
+
+/// Outcome of checking the tag at the start of the buffer.
+enum TagCheck {
+    /// A plausible tag.
+    Valid(framing::ParsedTagHeader),
+    /// Not a tag.
+    Invalid,
+    /// This many bytes are needed to tell.
+    Incomplete(usize),
+}
+
 /// An FLV format decoder that implements Tokio's Decoder trait
 #[derive(Default)]
 pub struct FlvDecoder {
@@ -39,14 +79,227 @@ pub struct FlvDecoder {
     last_tag_size: u32,
     // Tracks the current byte position in the stream
     position: u64,
+    // Skips corrupt regions when set, see `with_resync`
+    resync: Option<ResyncConfig>,
+    // Timestamp of the last successfully parsed tag
+    last_timestamp: Option<u32>,
+    // Whether the next tag header follows a PreviousTagSize mismatch and is checked strictly
+    verify_next: bool,
+    // Start and length of the corrupt region being skipped
+    skipping: Option<(u64, u64)>,
+    // Whether the end of the stream was reached
+    at_eof: bool,
+    // Regions skipped since the last call to `take_repairs`
+    repairs: Vec<Repair>,
 }
 
 impl FlvDecoder {
+    /// A decoder that skips corrupt data in the middle of the stream.
+    ///
+    /// When a tag header fails validation, or the PreviousTagSize before it does not match
+    /// and the header is not otherwise convincing, the decoder scans forward byte by byte
+    /// for the next plausible tag: a known tag type, a sane data size, a stream ID of 0, a
+    /// timestamp close to the last one, and a matching PreviousTagSize after its data. The
+    /// skipped regions are reported as [`Repair`]s.
+    pub fn with_resync(config: ResyncConfig) -> Self {
+        Self {
+            resync: Some(config),
+            ..Self::default()
+        }
+    }
+
     /// Get the current byte position in the stream
     pub fn position(&self) -> u64 {
         self.position
     }
 
+    /// Takes the corrupt regions skipped since the last call.
+    pub fn take_repairs(&mut self) -> Vec<Repair> {
+        std::mem::take(&mut self.repairs)
+    }
+
+    /// Demuxes the tag whose header starts `src`, once all of its data arrived.
+    fn take_tag(
+        &mut self,
+        src: &mut BytesMut,
+        tag_type: u8,
+        data_size: u32,
+    ) -> Result<Option<FlvData>, FlvError> {
+        // --- 5. Check for Full Tag Data ---
+        let total_tag_size = TAG_HEADER_SIZE + data_size as usize;
+        if src.len() < total_tag_size {
+            trace!(
+                "Awaiting full tag data ({} bytes needed, have {})",
+                total_tag_size,
+                src.len()
+            );
+            src.reserve(total_tag_size - src.len());
+            return Ok(None); // Need more data for the tag body
+        }
+
+        // --- 6. Demux Tag ---
+        // We have the full tag. Create a Bytes slice containing the *entire* tag.
+        let tag_bytes = src.split_to(total_tag_size).freeze();
+        self.position += total_tag_size as u64;
+        // Cursor now owns the tag's Bytes
+        let mut cursor = Cursor::new(tag_bytes);
+
+        match FlvTag::demux(&mut cursor) {
+            Ok(tag) => {
+                trace!(
+                    "Successfully parsed FLV tag: Type={}, Timestamp={}, Size={}",
+                    tag.tag_type, tag.timestamp_ms, data_size
+                );
+                // Store the *full* size of the tag (header + data) for the next PreviousTagSize check
+                self.last_tag_size = total_tag_size as u32;
+                self.last_timestamp = Some(tag.timestamp_ms);
+                // After a successful tag, we expect the PreviousTagSize field next
+                self.expecting_tag_header = false;
+                Ok(Some(FlvData::Tag(tag))) // Successfully decoded a tag
+            }
+            Err(e) => {
+                // Demux failed (e.g., bad data *within* the tag body, or unexpected EOF *within* demux)
+                warn!(
+                    "Failed to demux FLV tag (type: {}, data_size: {}): {:?}. Discarded {} bytes.",
+                    tag_type, data_size, e, total_tag_size
+                );
+                // `split_to` already removed the bytes from `src`.
+                // We failed parsing, so the next item should be PreviousTagSize, but we don't trust the stream.
+                self.expecting_tag_header = false; // Expect PreviousTagSize next, potentially bad one
+                self.last_tag_size = 0; // Can't trust the size
+                // Return None to signal progress (discarded bad tag)
+                // without producing a full item. Let the next call handle PreviousTagSize.
+                trace!("Demux failed, returning None to yield after discarding tag.");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Checks whether a tag starts `src`.
+    ///
+    /// Outside of `strict` checks, only the fields of the header are validated. Strict
+    /// checks also require a timestamp within the window and a matching PreviousTagSize
+    /// after the data, which is not needed for the last tag of the stream.
+    fn check_tag(&self, src: &BytesMut, config: &ResyncConfig, strict: bool) -> TagCheck {
+        if src.len() < TAG_HEADER_SIZE {
+            return if self.at_eof && !src.is_empty() {
+                TagCheck::Invalid
+            } else {
+                TagCheck::Incomplete(TAG_HEADER_SIZE)
+            };
+        }
+
+        let mut header_bytes = [0u8; TAG_HEADER_SIZE];
+        header_bytes.copy_from_slice(&src[..TAG_HEADER_SIZE]);
+        let Ok(header) = framing::parse_tag_header_bytes(header_bytes) else {
+            return TagCheck::Invalid;
+        };
+        // The two reserved bits of the tag type byte are always 0
+        if src[0] & 0xC0 != 0
+            || !matches!(src[0] & 0x1F, 8 | 9 | 18)
+            || header.stream_id != 0
+            || header.data_size > MAX_TAG_DATA_SIZE
+        {
+            return TagCheck::Invalid;
+        }
+        if !strict {
+            return TagCheck::Valid(header);
+        }
+
+        if let Some(last) = self.last_timestamp
+            && last.abs_diff(header.timestamp_ms) > config.timestamp_window_ms
+        {
+            return TagCheck::Invalid;
+        }
+        let tag_size = TAG_HEADER_SIZE + header.data_size as usize;
+        let needed = tag_size + PREV_TAG_SIZE_FIELD_SIZE;
+        if src.len() < needed {
+            return match (self.at_eof, src.len() >= tag_size) {
+                (false, _) => TagCheck::Incomplete(needed),
+                (true, true) => TagCheck::Valid(header),
+                (true, false) => TagCheck::Invalid,
+            };
+        }
+        let prev_tag_size = framing::parse_prev_tag_size([
+            src[tag_size],
+            src[tag_size + 1],
+            src[tag_size + 2],
+            src[tag_size + 3],
+        ]);
+        if prev_tag_size == tag_size as u32 {
+            TagCheck::Valid(header)
+        } else {
+            TagCheck::Invalid
+        }
+    }
+
+    /// Decodes the next tag in resynchronization mode, skipping corrupt data before it.
+    fn decode_resyncing(
+        &mut self,
+        src: &mut BytesMut,
+        config: ResyncConfig,
+    ) -> Result<Option<FlvData>, FlvError> {
+        if !self.expecting_tag_header {
+            if src.len() < PREV_TAG_SIZE_FIELD_SIZE {
+                src.reserve(PREV_TAG_SIZE_FIELD_SIZE - src.len());
+                return Ok(None);
+            }
+            let prev_tag_size = framing::parse_prev_tag_size([src[0], src[1], src[2], src[3]]);
+            if self.last_tag_size > 0 && prev_tag_size != self.last_tag_size {
+                debug!(
+                    "PreviousTagSize mismatch: Expected {}, found {}. Checking the next tag strictly.",
+                    self.last_tag_size, prev_tag_size
+                );
+                self.verify_next = true;
+            }
+            src.advance(PREV_TAG_SIZE_FIELD_SIZE);
+            self.position += PREV_TAG_SIZE_FIELD_SIZE as u64;
+            self.expecting_tag_header = true;
+        }
+
+        loop {
+            let strict = self.verify_next || self.skipping.is_some();
+            match self.check_tag(src, &config, strict) {
+                TagCheck::Incomplete(needed) => {
+                    src.reserve(needed.saturating_sub(src.len()));
+                    return Ok(None);
+                }
+                TagCheck::Valid(header) => {
+                    if let Some((position, skipped_bytes)) = self.skipping.take() {
+                        warn!(
+                            position,
+                            skipped_bytes, "Skipped corrupt data, resynchronized on the next tag"
+                        );
+                        self.repairs.push(Repair {
+                            position,
+                            skipped_bytes,
+                        });
+                    }
+                    self.verify_next = false;
+                    return self.take_tag(src, u8::from(header.tag_type), header.data_size);
+                }
+                TagCheck::Invalid => {
+                    let (position, skipped) = match self.skipping {
+                        Some(skipping) => skipping,
+                        None => {
+                            warn!(
+                                position = self.position,
+                                "Corrupt tag header, scanning for the next valid tag"
+                            );
+                            (self.position, 0)
+                        }
+                    };
+                    if skipped >= config.max_scan_bytes {
+                        return Err(FlvError::ResyncFailed { position, skipped });
+                    }
+                    src.advance(1);
+                    self.position += 1;
+                    self.skipping = Some((position, skipped + 1));
+                }
+            }
+        }
+    }
+
     // Helper function to attempt resynchronization by finding the next potential tag start
     // Returns true if resync advanced the buffer, false otherwise.
     fn try_resync(&mut self, src: &mut BytesMut) -> bool {
@@ -201,6 +454,10 @@ impl Decoder for FlvDecoder {
             }
         }
 
+        if let Some(config) = self.resync {
+            return self.decode_resyncing(src, config);
+        }
+
         // --- Loop to handle multiple tags/skips within the available buffer ---
 
         trace!(
@@ -304,56 +561,11 @@ impl Decoder for FlvDecoder {
             return Ok(None);
         }
 
-        // --- 5. Check for Full Tag Data ---
-        let total_tag_size = TAG_HEADER_SIZE + data_size as usize;
-        if src.len() < total_tag_size {
-            trace!(
-                "Awaiting full tag data ({} bytes needed, have {})",
-                total_tag_size,
-                src.len()
-            );
-            src.reserve(total_tag_size - src.len());
-            return Ok(None); // Need more data for the tag body
-        }
-
-        // --- 6. Demux Tag ---
-        // We have the full tag. Create a Bytes slice containing the *entire* tag.
-        let tag_bytes = src.split_to(total_tag_size).freeze();
-        self.position += total_tag_size as u64;
-        // Cursor now owns the tag's Bytes
-        let mut cursor = Cursor::new(tag_bytes);
-
-        match FlvTag::demux(&mut cursor) {
-            Ok(tag) => {
-                trace!(
-                    "Successfully parsed FLV tag: Type={}, Timestamp={}, Size={}",
-                    tag.tag_type, tag.timestamp_ms, data_size
-                );
-                // Store the *full* size of the tag (header + data) for the next PreviousTagSize check
-                self.last_tag_size = total_tag_size as u32;
-                // After a successful tag, we expect the PreviousTagSize field next
-                self.expecting_tag_header = false;
-                Ok(Some(FlvData::Tag(tag))) // Successfully decoded a tag
-            }
-            Err(e) => {
-                // Demux failed (e.g., bad data *within* the tag body, or unexpected EOF *within* demux)
-                warn!(
-                    "Failed to demux FLV tag (type: {}, data_size: {}): {:?}. Discarded {} bytes.",
-                    tag_type, data_size, e, total_tag_size
-                );
-                // `split_to` already removed the bytes from `src`.
-                // We failed parsing, so the next item should be PreviousTagSize, but we don't trust the stream.
-                self.expecting_tag_header = false; // Expect PreviousTagSize next, potentially bad one
-                self.last_tag_size = 0; // Can't trust the size
-                // Return None to signal progress (discarded bad tag)
-                // without producing a full item. Let the next call handle PreviousTagSize.
-                trace!("Demux failed, returning None to yield after discarding tag.");
-                Ok(None)
-            }
-        }
+        self.take_tag(src, tag_type, data_size)
     }
 
     fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.at_eof = true;
         // At EOF, we attempt to decode all remaining complete frames in the buffer.
         let mut first_frame = None;
 
@@ -403,23 +615,42 @@ impl Decoder for FlvDecoder {
     }
 }
 
+type RepairCallback = Box<dyn FnMut(Repair) + Send>;
+
 pub struct FlvDecoderStream<R> {
     framed: FramedRead<R, FlvDecoder>,
+    on_repair: Option<RepairCallback>,
 }
 
 impl<R: AsyncRead + Unpin> FlvDecoderStream<R> {
     pub fn new(reader: R) -> Self {
-        Self {
-            framed: FramedRead::with_capacity(reader, FlvDecoder::default(), BUFFER_SIZE),
-        }
+        Self::with_capacity(reader, BUFFER_SIZE)
     }
 
     pub fn with_capacity(reader: R, capacity: usize) -> Self {
         Self {
             framed: FramedRead::with_capacity(reader, FlvDecoder::default(), capacity),
+            on_repair: None,
         }
     }
 
+    /// A stream that skips corrupt data instead of failing, see [`FlvDecoder::with_resync`].
+    pub fn with_resync(reader: R, capacity: usize, config: ResyncConfig) -> Self {
+        Self {
+            framed: FramedRead::with_capacity(reader, FlvDecoder::with_resync(config), capacity),
+            on_repair: None,
+        }
+    }
+
+    /// Calls `callback` for every corrupt region skipped, as soon as decoding resumes after it.
+    pub fn on_repair<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(Repair) + Send + 'static,
+    {
+        self.on_repair = Some(Box::new(callback));
+        self
+    }
+
     pub fn position(&self) -> u64 {
         self.framed.decoder().position()
     }
@@ -429,7 +660,12 @@ impl<R: AsyncRead + Unpin> Stream for FlvDecoderStream<R> {
     type Item = Result<FlvData, FlvError>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        self.framed.poll_next_unpin(cx)
+        let poll = self.framed.poll_next_unpin(cx);
+        let repairs = self.framed.decoder_mut().take_repairs();
+        if let Some(callback) = &mut self.on_repair {
+            repairs.into_iter().for_each(callback);
+        }
+        poll
     }
 }
 
@@ -1190,4 +1426,102 @@ mod tests {
         let _ = decoder.decode(&mut buffer).unwrap();
         assert_eq!(decoder.position(), initial_pos + prev_tag_size_field as u64);
     }
+
+    /// A video tag with `len` bytes of data, followed by its PreviousTagSize.
+    fn video_tag_bytes(timestamp_ms: u32, len: usize) -> Vec<u8> {
+        let size = (len as u32).to_be_bytes();
+        let ts = timestamp_ms.to_be_bytes();
+        let mut bytes = vec![
+            9, size[1], size[2], size[3], ts[1], ts[2], ts[3], ts[0], 0, 0, 0,
+        ];
+        bytes.push(0x27);
+        bytes.extend((1..len).map(|i| i as u8));
+        bytes.extend_from_slice(&((TAG_HEADER_SIZE + len) as u32).to_be_bytes());
+        bytes
+    }
+
+    /// Decodes everything in `buffer` the way a `FramedRead` does at the end of the input.
+    fn decode_all(
+        decoder: &mut FlvDecoder,
+        buffer: &mut BytesMut,
+    ) -> Result<Vec<FlvData>, FlvError> {
+        let mut items = Vec::new();
+        loop {
+            let len = buffer.len();
+            match decoder.decode(buffer)? {
+                Some(item) => items.push(item),
+                None if buffer.len() == len => break,
+                None => {}
+            }
+        }
+        items.extend(decoder.decode_eof(buffer)?);
+        Ok(items)
+    }
+
+    #[test]
+    fn test_resync_skips_corrupt_region() {
+        init_tracing();
+        let header = [
+            0x46, 0x4C, 0x56, 0x01, 0x01, 0x00, 0x00, 0x00, 0x09, 0, 0, 0, 0,
+        ];
+        let mut input = header.to_vec();
+        input.extend(video_tag_bytes(0, 40));
+        input.extend(video_tag_bytes(40, 40));
+        let corrupt_start = input.len() - 20;
+        // Garbage over the end of the second tag, including a valid tag type byte
+        let mut seed = 0x2545_F491_u32;
+        for byte in &mut input[corrupt_start..] {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            *byte = seed as u8;
+        }
+        input.extend([9, 0, 0, 0xFF, 0x12]);
+        let good_start = input.len();
+        input.extend(video_tag_bytes(80, 40));
+        input.extend(video_tag_bytes(120, 40));
+
+        let mut decoder = FlvDecoder::with_resync(ResyncConfig::default());
+        let items = decode_all(&mut decoder, &mut BytesMut::from(&input[..])).unwrap();
+        let timestamps: Vec<_> = items
+            .iter()
+            .filter_map(|item| match item {
+                FlvData::Tag(tag) => Some(tag.timestamp_ms),
+                _ => None,
+            })
+            .collect();
+        // The second tag was read intact up to its corrupt PreviousTagSize
+        assert_eq!(timestamps, [0, 40, 80, 120]);
+
+        let repairs = decoder.take_repairs();
+        let tag_start = header.len() + 2 * (TAG_HEADER_SIZE + 40 + 4);
+        assert_eq!(
+            repairs,
+            [Repair {
+                position: tag_start as u64,
+                skipped_bytes: (good_start - tag_start) as u64,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_resync_gives_up_after_max_scan_bytes() {
+        init_tracing();
+        let mut input = vec![
+            0x46, 0x4C, 0x56, 0x01, 0x01, 0x00, 0x00, 0x00, 0x09, 0, 0, 0, 0,
+        ];
+        input.extend(video_tag_bytes(0, 40));
+        input.extend([0xEE; 4096]);
+        input.extend(video_tag_bytes(40, 40));
+
+        let mut decoder = FlvDecoder::with_resync(ResyncConfig {
+            max_scan_bytes: 1024,
+            ..ResyncConfig::default()
+        });
+        let result = decode_all(&mut decoder, &mut BytesMut::from(&input[..]));
+        assert!(matches!(
+            result,
+            Err(FlvError::ResyncFailed { skipped: 1024, .. })
+        ));
+    }
 }
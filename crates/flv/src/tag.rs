@@ -1,3 +1,4 @@
+use std::cell::OnceCell;
 use std::fmt;
 use std::io::Read;
 
@@ -7,7 +8,9 @@ use tracing::{debug, trace};
 
 use crate::audio::SoundFormat;
 use crate::resolution::Resolution;
-use crate::video::{EnhancedPacketType, VideoCodecId, VideoFrameType, VideoPacketType};
+use crate::video::{
+    EnhancedPacketType, VideoCodecId, VideoFourCC, VideoFrameType, VideoTagCodec, VideoTagHeader,
+};
 use crate::{framing, framing::ParsedTagHeader};
 
 use super::audio::AudioData;
@@ -76,63 +79,21 @@ impl FlvTag {
     }
 
     pub fn is_key_frame(&self) -> bool {
-        if self.is_filtered {
+        if self.is_filtered || self.tag_type != FlvTagType::Video {
             return false;
         }
-        match self.tag_type {
-            FlvTagType::Video => {
-                if self.data.is_empty() {
-                    return false;
-                }
+        let Some(&first_byte) = self.data.first() else {
+            return false;
+        };
 
-                let bytes = self.data.as_ref();
-                let first_byte = bytes[0];
-
-                // Check if this is an enhanced type
-                let enhanced = (first_byte & 0b1000_0000) != 0;
-
-                if enhanced {
-                    // For enhanced video, frame type is in bits 0-3
-                    let frame_type = first_byte & 0x0F;
-                    // VideoFrameType::KeyFrame = 1
-                    frame_type == VideoFrameType::KeyFrame as u8
-                } else {
-                    // For legacy video, frame type is in bits 4-7
-                    let frame_type = (first_byte >> 4) & 0x0F;
-                    // VideoFrameType::KeyFrame = 1
-                    frame_type == VideoFrameType::KeyFrame as u8
-                }
-            }
-            _ => false,
-        }
+        // The frame type is in bits 4-6 for both forms, bit 7 flags the enhanced header
+        let frame_type = (first_byte >> 4) & 0x07;
+        frame_type == VideoFrameType::KeyFrame as u8
     }
 
     pub fn is_video_sequence_header(&self) -> bool {
-        if self.is_filtered {
-            return false;
-        }
-        match self.tag_type {
-            FlvTagType::Video => {
-                let bytes = self.data.as_ref();
-                // peek the first byte
-                let enhanced = (bytes[0] & 0b1000_0000) != 0;
-                // for legacy formats, we detect the sequence header by checking the packet type
-                if !enhanced {
-                    let video_packet_type = bytes.get(1).unwrap_or(&0) & 0x0F;
-                    video_packet_type == 0x0
-                } else {
-                    let video_packet_type = bytes.first().unwrap_or(&0) & 0x0F;
-                    let video_packet_type = VideoPacketType::new(video_packet_type, enhanced);
-                    match video_packet_type {
-                        VideoPacketType::Enhanced(packet) => {
-                            packet == EnhancedPacketType::SEQUENCE_START
-                        }
-                        _ => false,
-                    }
-                }
-            }
-            _ => false,
-        }
+        self.video_header()
+            .is_some_and(|header| header.is_sequence_header())
     }
 
     /// Determines if the audio tag is a sequence header.
@@ -272,6 +233,33 @@ impl FlvTag {
         }
     }
 
+    /// Get the frame rate signalled by the SPS of a video sequence header
+    pub fn get_video_frame_rate(&self) -> Option<f64> {
+        if self.is_filtered || self.tag_type != FlvTagType::Video || self.data.len() < 5 {
+            return None;
+        }
+
+        let mut reader = std::io::Cursor::new(self.data.clone());
+        VideoData::demux(&mut reader)
+            .ok()?
+            .body
+            .get_frame_rate()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+    }
+
+    /// Parse the legacy or enhanced (E-RTMP) header of a video tag
+    pub fn video_header(&self) -> Option<VideoTagHeader> {
+        if self.is_filtered || self.tag_type != FlvTagType::Video {
+            return None;
+        }
+        VideoTagHeader::parse(&self.data)
+    }
+
+    /// Get the FourCC of an enhanced video tag
+    pub fn get_video_fourcc(&self) -> Option<VideoFourCC> {
+        self.video_header()?.fourcc()
+    }
+
     pub fn get_video_codec_id(&self) -> Option<VideoCodecId> {
         if self.is_filtered {
             return None;
@@ -292,7 +280,7 @@ impl FlvTag {
             let video_packet_type = first_byte & 0x0F;
             VideoCodecId::try_from(video_packet_type).ok()
         } else {
-            // unable to parse the codec id for enhanced formats
+            // enhanced formats use a FourCC instead, see `get_video_fourcc`
             None
         }
     }
@@ -317,45 +305,115 @@ impl FlvTag {
 
     /// Check if the tag is a key frame NALU
     pub fn is_key_frame_nalu(&self) -> bool {
-        if self.is_filtered {
-            return false;
+        self.video_header()
+            .is_some_and(|header| is_key_frame_nalu(&header))
+    }
+
+    /// Borrow the tag as a view that parses its packet headers at most once
+    pub fn view(&self) -> FlvTagRef<'_> {
+        FlvTagRef::new(self)
+    }
+}
+
+fn is_key_frame_nalu(header: &VideoTagHeader) -> bool {
+    if !header.is_key_frame() {
+        return false;
+    }
+
+    match header.codec {
+        // Legacy AVC/HEVC: AVC packet type 1 is a NALU
+        VideoTagCodec::Legacy(VideoCodecId::Avc | VideoCodecId::LegacyHevc) => {
+            header.packet_type == EnhancedPacketType::CODED_FRAMES
         }
-        // Only applicable for video tags
-        if self.tag_type != FlvTagType::Video {
-            return false;
+        VideoTagCodec::FourCC(VideoFourCC::Avc1 | VideoFourCC::Hvc1 | VideoFourCC::Av01) => {
+            header.is_coded_frames()
         }
+        _ => false,
+    }
+}
 
-        // Make sure we have enough data
-        if self.data.len() < 2 {
-            return false;
+/// A borrowed view of an [`FlvTag`] for operators that only inspect it.
+///
+/// The payload is not copied: the view reads the tag's `Bytes` in place and parses the
+/// video packet header on first use, so asking several questions about the same tag
+/// (sequence header, key frame, NALU) parses it only once. Operators that change the
+/// tag drop the view and mutate the owned [`FlvTag`].
+#[derive(Debug, Clone)]
+pub struct FlvTagRef<'a> {
+    tag: &'a FlvTag,
+    video_header: OnceCell<Option<VideoTagHeader>>,
+}
+
+impl<'a> FlvTagRef<'a> {
+    pub fn new(tag: &'a FlvTag) -> Self {
+        Self {
+            tag,
+            video_header: OnceCell::new(),
         }
+    }
 
-        let bytes = self.data.as_ref();
+    /// The viewed tag
+    pub fn tag(&self) -> &'a FlvTag {
+        self.tag
+    }
 
-        // check if its keyframe
-        let frame_type = (bytes[0] >> 4) & 0x07;
-        if frame_type != VideoFrameType::KeyFrame as u8 {
-            return false;
-        }
+    pub fn timestamp_ms(&self) -> u32 {
+        self.tag.timestamp_ms
+    }
 
-        // Check if this is an enhanced type
-        let enhanced = (bytes[0] & 0b1000_0000) != 0;
+    pub fn tag_type(&self) -> FlvTagType {
+        self.tag.tag_type
+    }
 
-        // For non-enhanced types, the codec type is in the lower 4 bits of the first byte
-        if !enhanced {
-            let codec_id = bytes[0] & 0x0F;
+    /// The payload, shared with the tag
+    pub fn data(&self) -> &'a Bytes {
+        &self.tag.data
+    }
 
-            // Check for AVC/H.264 (codec ID 7) or HEVC (codec ID 12)
-            if codec_id == VideoCodecId::Avc as u8 || codec_id == VideoCodecId::LegacyHevc as u8 {
-                // The packet type is in the second byte:
-                // 0 = sequence header, 1 = NALU, 2 = end of sequence
+    pub fn size(&self) -> usize {
+        self.tag.size()
+    }
 
-                // Check if this is a NALU packet (type 1)
-                return bytes[1] == 1;
-            }
-        }
+    pub fn is_script_tag(&self) -> bool {
+        self.tag.is_script_tag()
+    }
+
+    pub fn is_audio_tag(&self) -> bool {
+        self.tag.is_audio_tag()
+    }
 
-        false
+    pub fn is_video_tag(&self) -> bool {
+        self.tag.is_video_tag()
+    }
+
+    /// The video packet header, parsed on first use
+    pub fn video_header(&self) -> Option<VideoTagHeader> {
+        *self.video_header.get_or_init(|| self.tag.video_header())
+    }
+
+    pub fn is_key_frame(&self) -> bool {
+        self.tag.is_key_frame()
+    }
+
+    pub fn is_video_sequence_header(&self) -> bool {
+        self.video_header()
+            .is_some_and(|header| header.is_sequence_header())
+    }
+
+    /// See [`FlvTag::is_audio_sequence_header`]; the AAC packet type is read in place
+    pub fn is_audio_sequence_header(&self) -> bool {
+        self.tag.is_audio_sequence_header()
+    }
+
+    pub fn is_key_frame_nalu(&self) -> bool {
+        self.video_header()
+            .is_some_and(|header| is_key_frame_nalu(&header))
+    }
+}
+
+impl<'a> From<&'a FlvTag> for FlvTagRef<'a> {
+    fn from(tag: &'a FlvTag) -> Self {
+        Self::new(tag)
     }
 }
 
@@ -413,3 +471,81 @@ impl fmt::Display for FlvTagType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(tag_type: FlvTagType, is_filtered: bool, data: &[u8]) -> FlvTag {
+        FlvTag {
+            timestamp_ms: 40,
+            stream_id: 0,
+            tag_type,
+            is_filtered,
+            data: Bytes::copy_from_slice(data),
+        }
+    }
+
+    #[test]
+    fn test_view_matches_owned_tag() {
+        let tags = [
+            // AVC sequence header, key frame NALU and inter frame NALU
+            tag(FlvTagType::Video, false, &[0x17, 0x00, 0, 0, 0, 0x01, 0x64]),
+            tag(FlvTagType::Video, false, &[0x17, 0x01, 0, 0, 0, 0xAA]),
+            tag(FlvTagType::Video, false, &[0x27, 0x01, 0, 0, 0, 0xAA]),
+            // AVC end of sequence
+            tag(FlvTagType::Video, false, &[0x17, 0x02, 0, 0, 0]),
+            // Enhanced HEVC sequence start and key coded frames
+            tag(
+                FlvTagType::Video,
+                false,
+                &[0x90, b'h', b'v', b'c', b'1', 0x01],
+            ),
+            tag(
+                FlvTagType::Video,
+                false,
+                &[0x91, b'h', b'v', b'c', b'1', 0, 0, 0, 0xAA],
+            ),
+            // AAC sequence header and raw frame, MP3 frame
+            tag(FlvTagType::Audio, false, &[0xAF, 0x00, 0x12, 0x10]),
+            tag(FlvTagType::Audio, false, &[0xAF, 0x01, 0x21]),
+            tag(FlvTagType::Audio, false, &[0x2F, 0xFF, 0xFB]),
+            tag(FlvTagType::ScriptData, false, &[0x02, 0x00, 0x0A]),
+            // Filtered, empty and unknown tags
+            tag(FlvTagType::Video, true, &[0x17, 0x00, 0, 0, 0]),
+            tag(FlvTagType::Video, false, &[]),
+            tag(FlvTagType::Unknown(15), false, &[0x17, 0x00]),
+        ];
+
+        for tag in &tags {
+            let view = tag.view();
+            assert_eq!(view.timestamp_ms(), tag.timestamp_ms);
+            assert_eq!(view.tag_type(), tag.tag_type);
+            assert_eq!(view.size(), tag.size());
+            assert_eq!(view.is_script_tag(), tag.is_script_tag(), "{tag:?}");
+            assert_eq!(view.is_audio_tag(), tag.is_audio_tag(), "{tag:?}");
+            assert_eq!(view.is_video_tag(), tag.is_video_tag(), "{tag:?}");
+            assert_eq!(view.video_header(), tag.video_header(), "{tag:?}");
+            assert_eq!(view.is_key_frame(), tag.is_key_frame(), "{tag:?}");
+            assert_eq!(
+                view.is_video_sequence_header(),
+                tag.is_video_sequence_header(),
+                "{tag:?}"
+            );
+            assert_eq!(
+                view.is_audio_sequence_header(),
+                tag.is_audio_sequence_header(),
+                "{tag:?}"
+            );
+            assert_eq!(view.is_key_frame_nalu(), tag.is_key_frame_nalu(), "{tag:?}");
+        }
+    }
+
+    #[test]
+    fn test_view_shares_payload() {
+        let tag = tag(FlvTagType::Video, false, &[0x17, 0x01, 0, 0, 0, 0xAA]);
+        let view = tag.view();
+        assert_eq!(view.data().as_ptr(), tag.data.as_ptr());
+        assert!(std::ptr::eq(view.clone().tag(), &tag));
+    }
+}
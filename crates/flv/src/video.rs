@@ -462,6 +462,117 @@ pub enum EnhancedPacket {
     },
 }
 
+/// Codec of a video tag, from the legacy codec id or the enhanced FourCC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoTagCodec {
+    /// Legacy `CodecID` of the `VideoTagHeader`
+    Legacy(VideoCodecId),
+    /// FourCC of the `ExVideoTagHeader`
+    FourCC(VideoFourCC),
+}
+
+/// Header fields of a legacy or enhanced video tag, read without demuxing the payload.
+///
+/// Enhanced tags (E-RTMP) set the `IsExHeader` bit and carry a 3-bit frame type,
+/// an [`EnhancedPacketType`] and a FourCC instead of the legacy codec id. The AVC
+/// packet types of legacy AVC and HEVC tags (sequence header, NALU, end of sequence)
+/// are reported as the matching [`EnhancedPacketType`], so both forms can be
+/// inspected the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoTagHeader {
+    pub frame_type: VideoFrameType,
+    pub codec: VideoTagCodec,
+    pub packet_type: EnhancedPacketType,
+    /// Composition time offset in milliseconds, for packets that carry one and are
+    /// long enough to hold it
+    pub composition_time: Option<i32>,
+}
+
+impl VideoTagHeader {
+    /// Parses the header at the start of a video tag body.
+    ///
+    /// Returns `None` when the body is too short or uses an unknown codec.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let first = *data.first()?;
+        let enhanced = first & 0b1000_0000 != 0;
+        let frame_type = VideoFrameType::try_from((first >> 4) & 0b0111).ok()?;
+
+        if enhanced {
+            let packet_type = EnhancedPacketType(first & 0x0F);
+            let fourcc: [u8; 4] = data.get(1..5)?.try_into().ok()?;
+            let fourcc = VideoFourCC::try_from(fourcc).ok()?;
+            // Only AVC and HEVC coded frames carry a composition time, CodedFramesX implies 0
+            let composition_time = match fourcc {
+                VideoFourCC::Avc1 | VideoFourCC::Hvc1
+                    if packet_type == EnhancedPacketType::CODED_FRAMES =>
+                {
+                    data.get(5..8).map(read_si24)
+                }
+                _ => None,
+            };
+            return Some(Self {
+                frame_type,
+                codec: VideoTagCodec::FourCC(fourcc),
+                packet_type,
+                composition_time,
+            });
+        }
+
+        let codec_id = VideoCodecId::try_from(first & 0x0F).ok()?;
+        let (packet_type, composition_time) = match codec_id {
+            VideoCodecId::Avc | VideoCodecId::LegacyHevc => (
+                EnhancedPacketType(*data.get(1)?),
+                data.get(2..5).map(read_si24),
+            ),
+            _ => (EnhancedPacketType::CODED_FRAMES, None),
+        };
+        Some(Self {
+            frame_type,
+            codec: VideoTagCodec::Legacy(codec_id),
+            packet_type,
+            composition_time,
+        })
+    }
+
+    pub fn is_key_frame(&self) -> bool {
+        self.frame_type == VideoFrameType::KeyFrame
+    }
+
+    /// Whether the tag starts a sequence, i.e. carries the decoder configuration
+    pub fn is_sequence_header(&self) -> bool {
+        self.packet_type == EnhancedPacketType::SEQUENCE_START
+            || self.packet_type == EnhancedPacketType::MPEG2_SEQUENCE_START
+    }
+
+    /// Whether the tag carries coded video frames
+    pub fn is_coded_frames(&self) -> bool {
+        self.packet_type == EnhancedPacketType::CODED_FRAMES
+            || self.packet_type == EnhancedPacketType::CODED_FRAMES_X
+    }
+
+    /// FourCC of enhanced tags
+    pub fn fourcc(&self) -> Option<VideoFourCC> {
+        match self.codec {
+            VideoTagCodec::FourCC(fourcc) => Some(fourcc),
+            VideoTagCodec::Legacy(_) => None,
+        }
+    }
+
+    /// Codec id of legacy tags
+    pub fn codec_id(&self) -> Option<VideoCodecId> {
+        match self.codec {
+            VideoTagCodec::Legacy(codec_id) => Some(codec_id),
+            VideoTagCodec::FourCC(_) => None,
+        }
+    }
+}
+
+/// Reads a big-endian signed 24-bit integer
+fn read_si24(bytes: &[u8]) -> i32 {
+    let value = ((bytes[0] as i32) << 16) | ((bytes[1] as i32) << 8) | bytes[2] as i32;
+    (value << 8) >> 8
+}
+
 /// FLV Tag Video Header
 /// This is a container for video data.
 /// This enum contains the data for the different types of video tags.
@@ -623,6 +734,18 @@ impl VideoTagBody {
             _ => None,
         }
     }
+
+    /// Frame rate signalled by the SPS of an AVC or HEVC sequence header, if any.
+    pub fn get_frame_rate(&self) -> Option<f64> {
+        match self {
+            VideoTagBody::Avc(avc_data) | VideoTagBody::Enhanced(EnhancedPacket::Avc(avc_data)) => {
+                avc_data.get_frame_rate()
+            }
+            VideoTagBody::Hevc(hevc_data)
+            | VideoTagBody::Enhanced(EnhancedPacket::Hevc(hevc_data)) => hevc_data.get_frame_rate(),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for VideoData {
@@ -693,6 +816,44 @@ mod tests {
     use super::*;
     use crate::avc::AvcPacketType;
 
+    #[test]
+    fn test_video_tag_header() {
+        // Legacy AVC NALU with a negative composition time
+        let header = VideoTagHeader::parse(&[0x27, 0x01, 0xFF, 0xFF, 0xD8, 0x65]).unwrap();
+        assert_eq!(header.frame_type, VideoFrameType::InterFrame);
+        assert_eq!(header.codec_id(), Some(VideoCodecId::Avc));
+        assert!(header.is_coded_frames());
+        assert_eq!(header.composition_time, Some(-40));
+
+        // Enhanced HEVC key frame: IsExHeader | KeyFrame | CodedFrames, then "hvc1" and a cts
+        let header = VideoTagHeader::parse(&[0x91, b'h', b'v', b'c', b'1', 0, 0, 0x50]).unwrap();
+        assert!(header.is_key_frame());
+        assert!(header.is_coded_frames());
+        assert!(!header.is_sequence_header());
+        assert_eq!(header.fourcc(), Some(VideoFourCC::Hvc1));
+        assert_eq!(header.codec_id(), None);
+        assert_eq!(header.composition_time, Some(80));
+
+        // Enhanced inter frame with CodedFramesX has no composition time
+        let header = VideoTagHeader::parse(&[0xA3, b'h', b'v', b'c', b'1', 0x26]).unwrap();
+        assert!(!header.is_key_frame());
+        assert!(header.is_coded_frames());
+        assert_eq!(header.composition_time, None);
+
+        // Enhanced AV1 sequence start
+        let header = VideoTagHeader::parse(&[0x90, b'a', b'v', b'0', b'1', 0x81]).unwrap();
+        assert!(header.is_key_frame());
+        assert!(header.is_sequence_header());
+        assert_eq!(header.codec, VideoTagCodec::FourCC(VideoFourCC::Av01));
+
+        // Truncated or unknown headers
+        assert!(VideoTagHeader::parse(&[]).is_none());
+        assert!(VideoTagHeader::parse(&[0x91, b'h', b'v']).is_none());
+        assert!(VideoTagHeader::parse(&[0x91, b'x', b'x', b'x', b'x']).is_none());
+        let header = VideoTagHeader::parse(&[0x91, b'h', b'v', b'c', b'1', 0]).unwrap();
+        assert_eq!(header.composition_time, None);
+    }
+
     #[test]
     fn test_video_fourcc() {
         let cases = [
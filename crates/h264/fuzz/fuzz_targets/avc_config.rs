@@ -0,0 +1,21 @@
+#![no_main]
+
+use std::io;
+
+use bytes::Bytes;
+use h264::AVCDecoderConfigurationRecord;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let data = Bytes::copy_from_slice(data);
+    let _ = AVCDecoderConfigurationRecord::parse(&mut io::Cursor::new(data.clone()));
+    let _ = AVCDecoderConfigurationRecord::parse_strict(&mut io::Cursor::new(data.clone()));
+
+    let Ok((config, _)) = AVCDecoderConfigurationRecord::parse_lenient(&mut io::Cursor::new(data))
+    else {
+        return;
+    };
+
+    let mut built = Vec::with_capacity(config.encoded_size());
+    let _ = config.build(&mut built);
+});
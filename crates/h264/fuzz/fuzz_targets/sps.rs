@@ -0,0 +1,20 @@
+#![no_main]
+
+use std::io;
+
+use h264::Sps;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(sps) = Sps::parse_with_emulation_prevention(io::Cursor::new(data)) else {
+        return;
+    };
+
+    let _ = (sps.width(), sps.height(), sps.frame_rate());
+    let _ = sps.max_num_reorder_frames();
+    let _ = sps.size();
+    let _ = sps.size_with_emulation_prevention();
+
+    let mut built = Vec::new();
+    let _ = sps.build_with_emulation_prevention(&mut built);
+});
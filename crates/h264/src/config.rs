@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io::{
     Write, {self},
 };
@@ -6,7 +8,9 @@ use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use bytes::{Buf, Bytes};
 use bytes_util::{BitReader, BitWriter, BytesCursorExt};
 
+use crate::nal::{NalUnitIter, START_CODE};
 use crate::sps::SpsExtended;
+use crate::{NALUnitType, Pps, Sps};
 
 /// The AVC (H.264) Decoder Configuration Record.
 /// ISO/IEC 14496-15:2022(E) - 5.3.2.1.2
@@ -91,6 +95,113 @@ pub struct AvccExtendedConfig {
     pub sequence_parameter_set_ext: Vec<SpsExtended>,
 }
 
+/// A spec violation in an AVCDecoderConfigurationRecord.
+///
+/// [`AVCDecoderConfigurationRecord::parse_lenient`] recovers from these and returns them
+/// alongside the record, while [`AVCDecoderConfigurationRecord::parse_strict`] fails with the
+/// first one as the inner error of the returned [`io::Error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigWarning {
+    /// The `configuration_version` is not 1. It is kept as is.
+    UnsupportedVersion(u8),
+    /// The reserved bits of the byte holding `field` are not all ones. They are ignored.
+    ReservedBits {
+        /// The field sharing the byte with the reserved bits.
+        field: &'static str,
+        /// The whole byte as read.
+        value: u8,
+    },
+    /// A parameter set is longer than the rest of the record. It is clamped to the
+    /// available bytes, and any parameter sets declared after it are dropped.
+    LengthOverrun {
+        /// The kind of parameter set, `sps`, `pps` or `sequence_parameter_set_ext`.
+        field: &'static str,
+        /// The index of the parameter set in its list.
+        index: usize,
+        /// The declared length.
+        declared: u16,
+        /// The number of bytes left in the record.
+        available: usize,
+    },
+    /// The record ends before `field`. Everything from `field` on is left out.
+    Truncated {
+        /// The first field that is missing.
+        field: &'static str,
+    },
+    /// A `sequence_parameter_set_ext` could not be parsed. It is dropped.
+    InvalidSpsExtension {
+        /// The index of the extension.
+        index: usize,
+        /// Why parsing failed.
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported configuration_version {version}, expected 1")
+            }
+            Self::ReservedBits { field, value } => {
+                write!(
+                    f,
+                    "reserved bits next to {field} are not set: {value:#010b}"
+                )
+            }
+            Self::LengthOverrun {
+                field,
+                index,
+                declared,
+                available,
+            } => write!(
+                f,
+                "{field} {index} declares {declared} bytes but only {available} remain"
+            ),
+            Self::Truncated { field } => write!(f, "record ends before {field}"),
+            Self::InvalidSpsExtension { index, reason } => {
+                write!(f, "invalid sequence_parameter_set_ext {index}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigWarning {}
+
+impl From<ConfigWarning> for io::Error {
+    fn from(warning: ConfigWarning) -> Self {
+        let kind = match warning {
+            ConfigWarning::Truncated { .. } => io::ErrorKind::UnexpectedEof,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, warning)
+    }
+}
+
+/// Collects the [`ConfigWarning`]s of a parse, or fails on the first one in strict mode.
+struct Diagnostics {
+    strict: bool,
+    warnings: Vec<ConfigWarning>,
+}
+
+impl Diagnostics {
+    fn report(&mut self, warning: ConfigWarning) -> io::Result<()> {
+        if self.strict {
+            return Err(warning.into());
+        }
+        self.warnings.push(warning);
+        Ok(())
+    }
+
+    /// Reports a `ReservedBits` warning unless all bits of `mask` are set in `value`.
+    fn check_reserved(&mut self, field: &'static str, value: u8, mask: u8) -> io::Result<()> {
+        if value & mask == mask {
+            return Ok(());
+        }
+        self.report(ConfigWarning::ReservedBits { field, value })
+    }
+}
+
 impl AVCDecoderConfigurationRecord {
     /// Returns the first SPS NAL unit as a zero-copy `Bytes` slice.
     ///
@@ -147,6 +258,10 @@ impl AVCDecoderConfigurationRecord {
 
     /// Parses an AVCDecoderConfigurationRecord from a byte stream.
     /// Returns a parsed AVCDecoderConfigurationRecord.
+    ///
+    /// Reserved bits and the `configuration_version` are not checked, but any length
+    /// running past the end of the data is an error. Use [`Self::parse_strict`] or
+    /// [`Self::parse_lenient`] for consistent handling of records that break the spec.
     pub fn parse(reader: &mut io::Cursor<Bytes>) -> io::Result<Self> {
         let configuration_version = reader.read_u8()?;
         let profile_indication = reader.read_u8()?;
@@ -157,16 +272,14 @@ impl AVCDecoderConfigurationRecord {
 
         let mut sps = Vec::with_capacity(num_of_sequence_parameter_sets as usize);
         for _ in 0..num_of_sequence_parameter_sets {
-            let sps_length = reader.read_u16::<BigEndian>()?;
-            let sps_data = reader.extract_bytes(sps_length as usize)?;
+            let sps_data = reader.read_slice_u16_be()?;
             sps.push(sps_data);
         }
 
         let num_of_picture_parameter_sets = reader.read_u8()?;
         let mut pps = Vec::with_capacity(num_of_picture_parameter_sets as usize);
         for _ in 0..num_of_picture_parameter_sets {
-            let pps_length = reader.read_u16::<BigEndian>()?;
-            let pps_data = reader.extract_bytes(pps_length as usize)?;
+            let pps_data = reader.read_slice_u16_be()?;
             pps.push(pps_data);
         }
 
@@ -185,8 +298,7 @@ impl AVCDecoderConfigurationRecord {
                     let mut sequence_parameter_set_ext =
                         Vec::with_capacity(number_of_sequence_parameter_set_ext as usize);
                     for _ in 0..number_of_sequence_parameter_set_ext {
-                        let sps_ext_length = reader.read_u16::<BigEndian>()?;
-                        let sps_ext_data = reader.extract_bytes(sps_ext_length as usize)?;
+                        let sps_ext_data = reader.read_slice_u16_be()?;
 
                         let mut bit_reader = BitReader::new_from_slice(sps_ext_data);
                         let sps_ext_parsed = SpsExtended::parse(&mut bit_reader)?;
@@ -219,6 +331,242 @@ impl AVCDecoderConfigurationRecord {
         })
     }
 
+    /// Parses an AVCDecoderConfigurationRecord, rejecting anything that breaks the spec.
+    ///
+    /// Fails on a `configuration_version` other than 1, reserved bits that are not all ones,
+    /// a parameter set running past the end of the record, a cut off extended config, or an
+    /// invalid `sequence_parameter_set_ext`. The error wraps the [`ConfigWarning`] describing
+    /// the problem, and is of kind [`io::ErrorKind::UnexpectedEof`] for a truncated record
+    /// and [`io::ErrorKind::InvalidData`] otherwise.
+    pub fn parse_strict(reader: &mut io::Cursor<Bytes>) -> io::Result<Self> {
+        let mut diagnostics = Diagnostics {
+            strict: true,
+            warnings: Vec::new(),
+        };
+        Self::parse_checked(reader, &mut diagnostics)
+    }
+
+    /// Parses an AVCDecoderConfigurationRecord, recovering what it can from records that
+    /// break the spec.
+    ///
+    /// Reserved bits are ignored, a parameter set running past the end of the record is
+    /// clamped to the available bytes, and whatever follows the end of the record is left
+    /// out. Each problem is returned as a [`ConfigWarning`] alongside the record. Only a
+    /// record too short to hold the 6 byte header is an error.
+    pub fn parse_lenient(reader: &mut io::Cursor<Bytes>) -> io::Result<(Self, Vec<ConfigWarning>)> {
+        let mut diagnostics = Diagnostics {
+            strict: false,
+            warnings: Vec::new(),
+        };
+        let record = Self::parse_checked(reader, &mut diagnostics)?;
+        Ok((record, diagnostics.warnings))
+    }
+
+    fn parse_checked(
+        reader: &mut io::Cursor<Bytes>,
+        diagnostics: &mut Diagnostics,
+    ) -> io::Result<Self> {
+        let configuration_version = reader.read_u8()?;
+        if configuration_version != 1 {
+            diagnostics.report(ConfigWarning::UnsupportedVersion(configuration_version))?;
+        }
+        let profile_indication = reader.read_u8()?;
+        let profile_compatibility = reader.read_u8()?;
+        let level_indication = reader.read_u8()?;
+
+        let byte = reader.read_u8()?;
+        diagnostics.check_reserved("length_size_minus_one", byte, 0b1111_1100)?;
+        let length_size_minus_one = byte & 0b0000_0011;
+
+        let byte = reader.read_u8()?;
+        diagnostics.check_reserved("num_of_sequence_parameter_sets", byte, 0b1110_0000)?;
+        let sps = Self::read_parameter_sets(reader, "sps", byte & 0b0001_1111, diagnostics)?;
+
+        let pps = if reader.has_remaining() {
+            let count = reader.read_u8()?;
+            Self::read_parameter_sets(reader, "pps", count, diagnostics)?
+        } else {
+            diagnostics.report(ConfigWarning::Truncated {
+                field: "num_of_picture_parameter_sets",
+            })?;
+            Vec::new()
+        };
+
+        // As in `parse`, a missing extended config is accepted for any profile
+        let extended_config = match profile_indication {
+            66 | 77 | 88 => None,
+            _ if !reader.has_remaining() => None,
+            _ if reader.remaining() < 4 => {
+                diagnostics.report(ConfigWarning::Truncated {
+                    field: "extended_config",
+                })?;
+                None
+            }
+            _ => {
+                let byte = reader.read_u8()?;
+                diagnostics.check_reserved("chroma_format_idc", byte, 0b1111_1100)?;
+                let chroma_format_idc = byte & 0b0000_0011;
+
+                let byte = reader.read_u8()?;
+                diagnostics.check_reserved("bit_depth_luma_minus8", byte, 0b1111_1000)?;
+                let bit_depth_luma_minus8 = byte & 0b0000_0111;
+
+                let byte = reader.read_u8()?;
+                diagnostics.check_reserved("bit_depth_chroma_minus8", byte, 0b1111_1000)?;
+                let bit_depth_chroma_minus8 = byte & 0b0000_0111;
+
+                let count = reader.read_u8()?;
+                let sets = Self::read_parameter_sets(
+                    reader,
+                    "sequence_parameter_set_ext",
+                    count,
+                    diagnostics,
+                )?;
+
+                let mut sequence_parameter_set_ext = Vec::with_capacity(sets.len());
+                for (index, data) in sets.into_iter().enumerate() {
+                    let mut bit_reader = BitReader::new_from_slice(data);
+                    match SpsExtended::parse(&mut bit_reader) {
+                        Ok(sps_ext) => sequence_parameter_set_ext.push(sps_ext),
+                        Err(e) => diagnostics.report(ConfigWarning::InvalidSpsExtension {
+                            index,
+                            reason: e.to_string(),
+                        })?,
+                    }
+                }
+
+                Some(AvccExtendedConfig {
+                    chroma_format_idc,
+                    bit_depth_luma_minus8,
+                    bit_depth_chroma_minus8,
+                    sequence_parameter_set_ext,
+                })
+            }
+        };
+
+        Ok(Self {
+            configuration_version,
+            profile_indication,
+            profile_compatibility,
+            level_indication,
+            length_size_minus_one,
+            sps,
+            pps,
+            extended_config,
+        })
+    }
+
+    /// Reads `count` parameter sets with a u16 length prefix each.
+    fn read_parameter_sets(
+        reader: &mut io::Cursor<Bytes>,
+        field: &'static str,
+        count: u8,
+        diagnostics: &mut Diagnostics,
+    ) -> io::Result<Vec<Bytes>> {
+        let mut sets = Vec::with_capacity(count as usize);
+        for index in 0..count as usize {
+            if reader.remaining() < 2 {
+                diagnostics.report(ConfigWarning::Truncated { field })?;
+                break;
+            }
+
+            let declared = reader.read_u16::<BigEndian>()?;
+            let available = reader.remaining();
+            if declared as usize > available {
+                diagnostics.report(ConfigWarning::LengthOverrun {
+                    field,
+                    index,
+                    declared,
+                    available,
+                })?;
+                sets.push(reader.extract_bytes(available)?);
+                break;
+            }
+
+            sets.push(reader.extract_bytes(declared as usize)?);
+        }
+
+        Ok(sets)
+    }
+
+    /// Builds an AVCDecoderConfigurationRecord from the SPS and PPS NAL units of an Annex B
+    /// byte stream.
+    ///
+    /// The profile, compatibility and level are taken from the SPS with the lowest
+    /// `seq_parameter_set_id`, and the extended config is filled in from it when the profile
+    /// requires one. If a parameter set id is repeated, the first NAL unit with that id is kept.
+    /// Returns an error if the stream contains no SPS.
+    pub fn from_annexb(data: &[u8]) -> io::Result<Self> {
+        let mut sps_by_id = BTreeMap::new();
+        let mut pps_by_id = BTreeMap::new();
+
+        for nal in NalUnitIter::annex_b(data) {
+            let nal = nal?;
+            match nal.nal_unit_type {
+                NALUnitType::SPS => {
+                    let sps = Sps::parse_with_emulation_prevention(nal.data)?;
+                    sps_by_id
+                        .entry(sps.seq_parameter_set_id)
+                        .or_insert((sps, Bytes::copy_from_slice(nal.data)));
+                }
+                NALUnitType::PPS => {
+                    let pps = Pps::parse_with_emulation_prevention(nal.data)?;
+                    pps_by_id
+                        .entry(pps.pic_parameter_set_id)
+                        .or_insert_with(|| Bytes::copy_from_slice(nal.data));
+                }
+                _ => {}
+            }
+        }
+
+        let Some((first_sps, first_sps_bytes)) = sps_by_id.values().next() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no SPS NAL unit found in Annex B stream",
+            ));
+        };
+
+        // The header bytes are read directly since `Sps` does not keep the raw constraint flags
+        let profile_indication = first_sps_bytes[1];
+        let profile_compatibility = first_sps_bytes[2];
+        let level_indication = first_sps_bytes[3];
+
+        let extended_config = match profile_indication {
+            66 | 77 | 88 => None,
+            _ => {
+                let ext = first_sps.ext.clone().unwrap_or_default();
+                Some(AvccExtendedConfig {
+                    chroma_format_idc: ext.chroma_format_idc,
+                    bit_depth_luma_minus8: ext.bit_depth_luma_minus8,
+                    bit_depth_chroma_minus8: ext.bit_depth_chroma_minus8,
+                    sequence_parameter_set_ext: Vec::new(),
+                })
+            }
+        };
+
+        Ok(Self {
+            configuration_version: 1,
+            profile_indication,
+            profile_compatibility,
+            level_indication,
+            length_size_minus_one: 3,
+            sps: sps_by_id.into_values().map(|(_, bytes)| bytes).collect(),
+            pps: pps_by_id.into_values().collect(),
+            extended_config,
+        })
+    }
+
+    /// Writes the SPS and PPS NAL units as an Annex B byte stream with 4 byte start codes,
+    /// ready to be prepended to an IDR access unit.
+    pub fn to_annexb<T: io::Write>(&self, writer: &mut T) -> io::Result<()> {
+        for nal in self.sps.iter().chain(&self.pps) {
+            writer.write_all(&START_CODE)?;
+            writer.write_all(nal)?;
+        }
+
+        Ok(())
+    }
+
     /// Returns the total byte size of the AVCDecoderConfigurationRecord.
     pub fn size(&self) -> u64 {
         1 // configuration_version
@@ -251,6 +599,16 @@ impl AVCDecoderConfigurationRecord {
         }
     }
 
+    /// Returns the number of bytes written by [`Self::build`], to give it a buffer of the
+    /// right size.
+    ///
+    /// Accounts for every SPS and PPS entry and the extended config. The entries are stored as
+    /// NAL units, with their emulation prevention bytes, so an entry built from an [`Sps`] is
+    /// [`Sps::size_with_emulation_prevention`] bytes long rather than [`Sps::size`].
+    pub fn encoded_size(&self) -> usize {
+        self.size() as usize
+    }
+
     /// Builds the AVCDecoderConfigurationRecord into a byte stream.
     /// Returns a built byte stream.
     pub fn build<T: io::Write>(&self, writer: &mut T) -> io::Result<()> {
@@ -315,8 +673,8 @@ mod tests {
     use bytes::Bytes;
     use bytes_util::BitWriter;
 
-    use crate::config::{AVCDecoderConfigurationRecord, AvccExtendedConfig};
-    use crate::sps::SpsExtended;
+    use crate::config::{AVCDecoderConfigurationRecord, AvccExtendedConfig, ConfigWarning};
+    use crate::sps::{Sps, SpsExtended};
 
     #[test]
     fn test_config_parse() {
@@ -559,4 +917,302 @@ mod tests {
         }
         "#);
     }
+
+    // The SPS and PPS from `test_config_build`
+    const SPS: &[u8] = b"\x67\x64\x00\x1F\xAC\xD9\x41\xE0\x6D\xF9\xE6\xA0\x20\x20\x28\x00\x00\x03\x00\x08\x00\x00\x03\x01\xE0";
+    const PPS: &[u8] = b"h\xeb\xe3\xcb\"\xc0";
+
+    fn annexb(nals: &[&[u8]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for nal in nals {
+            data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            data.extend_from_slice(nal);
+        }
+        data
+    }
+
+    #[test]
+    fn test_config_from_annexb() {
+        let mut sps1 = Sps::parse_with_emulation_prevention(io::Cursor::new(SPS)).unwrap();
+        sps1.seq_parameter_set_id = 1;
+        let mut sps1_bytes = Vec::new();
+        sps1.build_with_emulation_prevention(&mut sps1_bytes)
+            .unwrap();
+
+        let idr = [0x65, 0x88, 0x84, 0x00, 0x33, 0xff];
+        // The parameter sets are repeated in front of every IDR, the one with id 1 comes first
+        let data = annexb(&[&sps1_bytes, SPS, PPS, &idr, SPS, PPS, &idr]);
+
+        let config = AVCDecoderConfigurationRecord::from_annexb(&data).unwrap();
+        assert_eq!(config.profile_indication, 100);
+        assert_eq!(config.profile_compatibility, 0);
+        assert_eq!(config.level_indication, 31);
+        assert_eq!(config.length_size_minus_one, 3);
+        assert_eq!(
+            config.sps,
+            vec![Bytes::from_static(SPS), Bytes::from(sps1_bytes)]
+        );
+        assert_eq!(config.pps, vec![Bytes::from_static(PPS)]);
+        assert_eq!(
+            config.extended_config,
+            Some(AvccExtendedConfig {
+                chroma_format_idc: 1,
+                bit_depth_luma_minus8: 0,
+                bit_depth_chroma_minus8: 0,
+                sequence_parameter_set_ext: vec![],
+            })
+        );
+
+        let mut built = Vec::new();
+        config.build(&mut built).unwrap();
+        let parsed =
+            AVCDecoderConfigurationRecord::parse(&mut io::Cursor::new(Bytes::from(built))).unwrap();
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn test_config_to_annexb_round_trip() {
+        let config = AVCDecoderConfigurationRecord::from_annexb(&annexb(&[SPS, PPS])).unwrap();
+
+        let mut data = Vec::new();
+        config.to_annexb(&mut data).unwrap();
+        assert_eq!(data, annexb(&[SPS, PPS]));
+
+        assert_eq!(
+            AVCDecoderConfigurationRecord::from_annexb(&data).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_config_from_annexb_without_sps() {
+        let err = AVCDecoderConfigurationRecord::from_annexb(&annexb(&[PPS])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "no SPS NAL unit found in Annex B stream");
+    }
+
+    // The record from `test_config_build`
+    const RECORD: &[u8] = b"\x01d\0\x1f\xff\xe1\0\x19\x67\x64\x00\x1F\xAC\xD9\x41\xE0\x6D\xF9\xE6\xA0\x20\x20\x28\x00\x00\x03\x00\x08\x00\x00\x03\x01\xE0\x01\0\x06h\xeb\xe3\xcb\"\xc0\xfd\xf8\xf8\0";
+
+    fn patched(patch: impl FnOnce(&mut Vec<u8>)) -> io::Cursor<Bytes> {
+        let mut data = RECORD.to_vec();
+        patch(&mut data);
+        io::Cursor::new(Bytes::from(data))
+    }
+
+    fn strict_error(mut reader: io::Cursor<Bytes>) -> (io::ErrorKind, ConfigWarning) {
+        let err = AVCDecoderConfigurationRecord::parse_strict(&mut reader).unwrap_err();
+        let warning = err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<ConfigWarning>())
+            .unwrap()
+            .clone();
+        (err.kind(), warning)
+    }
+
+    #[test]
+    fn test_config_parse_strict_and_lenient_valid() {
+        let expected = AVCDecoderConfigurationRecord::parse(&mut patched(|_| {})).unwrap();
+
+        let strict = AVCDecoderConfigurationRecord::parse_strict(&mut patched(|_| {})).unwrap();
+        assert_eq!(strict, expected);
+
+        let (lenient, warnings) =
+            AVCDecoderConfigurationRecord::parse_lenient(&mut patched(|_| {})).unwrap();
+        assert_eq!(lenient, expected);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_config_junk_reserved_bits() {
+        // Some encoders leave every reserved bit unset
+        let junk = || {
+            patched(|data| {
+                data[4] = 0x03;
+                data[5] = 0x01;
+                data[42] = 0x01;
+                data[43] = 0x00;
+                data[44] = 0x00;
+            })
+        };
+
+        assert_eq!(
+            strict_error(junk()),
+            (
+                io::ErrorKind::InvalidData,
+                ConfigWarning::ReservedBits {
+                    field: "length_size_minus_one",
+                    value: 0x03,
+                }
+            )
+        );
+
+        let (config, warnings) = AVCDecoderConfigurationRecord::parse_lenient(&mut junk()).unwrap();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| match warning {
+                    ConfigWarning::ReservedBits { field, .. } => *field,
+                    other => panic!("unexpected warning {other}"),
+                })
+                .collect::<Vec<_>>(),
+            [
+                "length_size_minus_one",
+                "num_of_sequence_parameter_sets",
+                "chroma_format_idc",
+                "bit_depth_luma_minus8",
+                "bit_depth_chroma_minus8",
+            ]
+        );
+
+        // the builder sets the reserved bits again
+        let mut buf = Vec::new();
+        config.build(&mut buf).unwrap();
+        assert_eq!(buf, RECORD);
+    }
+
+    #[test]
+    fn test_config_sps_length_overrun() {
+        // The SPS length counts bytes past the end of the record
+        let overrun = || {
+            patched(|data| {
+                data[7] = 0x40;
+                data.truncate(33);
+            })
+        };
+
+        let err = AVCDecoderConfigurationRecord::parse_strict(&mut overrun()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "sps 0 declares 64 bytes but only 25 remain"
+        );
+
+        let (config, warnings) =
+            AVCDecoderConfigurationRecord::parse_lenient(&mut overrun()).unwrap();
+        assert_eq!(config.sps, vec![Bytes::from_static(SPS)]);
+        assert!(config.pps.is_empty());
+        assert_eq!(config.extended_config, None);
+        assert_eq!(
+            warnings,
+            vec![
+                ConfigWarning::LengthOverrun {
+                    field: "sps",
+                    index: 0,
+                    declared: 64,
+                    available: 25,
+                },
+                ConfigWarning::Truncated {
+                    field: "num_of_picture_parameter_sets",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_unsupported_version() {
+        let version = || patched(|data| data[0] = 0);
+
+        let err = AVCDecoderConfigurationRecord::parse_strict(&mut version()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsupported configuration_version 0, expected 1"
+        );
+
+        let (config, warnings) =
+            AVCDecoderConfigurationRecord::parse_lenient(&mut version()).unwrap();
+        assert_eq!(config.configuration_version, 0);
+        assert_eq!(warnings, vec![ConfigWarning::UnsupportedVersion(0)]);
+    }
+
+    #[test]
+    fn test_config_truncated_extended_config() {
+        let truncated = || patched(|data| data.truncate(44));
+
+        assert_eq!(
+            strict_error(truncated()),
+            (
+                io::ErrorKind::UnexpectedEof,
+                ConfigWarning::Truncated {
+                    field: "extended_config",
+                }
+            )
+        );
+
+        let (config, warnings) =
+            AVCDecoderConfigurationRecord::parse_lenient(&mut truncated()).unwrap();
+        assert_eq!(config.extended_config, None);
+        assert_eq!(config.pps, vec![Bytes::from_static(PPS)]);
+        assert_eq!(
+            warnings,
+            vec![ConfigWarning::Truncated {
+                field: "extended_config",
+            }]
+        );
+    }
+
+    #[test]
+    fn test_encoded_size_randomized() {
+        let base = Sps::parse_with_emulation_prevention(io::Cursor::new(
+            b"\x67\x64\x00\x1F\xAC\xD9\x41\xE0\x6D\xF9\xE6\xA0\x20\x20\x28\x00\x00\x03\x00\x08\x00\x00\x03\x01\xE0",
+        ))
+        .unwrap();
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..500 {
+            let sps = (0..next() % 4)
+                .map(|id| {
+                    let mut sps = base.clone();
+                    sps.seq_parameter_set_id = id as u16;
+                    sps.level_idc = (next() % 4) as u8;
+                    sps.pic_width_in_mbs_minus1 = next() % 512;
+
+                    let predicted = sps.size_with_emulation_prevention().unwrap() as usize;
+                    let mut nal = Vec::with_capacity(predicted);
+                    sps.build_with_emulation_prevention(&mut nal).unwrap();
+                    assert_eq!(nal.len(), predicted);
+                    Bytes::from(nal)
+                })
+                .collect();
+            let pps = (0..next() % 3)
+                .map(|_| (0..next() % 16).map(|_| next() as u8).collect())
+                .collect();
+            let extended_config = (next() % 2 == 0).then(|| AvccExtendedConfig {
+                chroma_format_idc: 1,
+                bit_depth_luma_minus8: 0,
+                bit_depth_chroma_minus8: 0,
+                sequence_parameter_set_ext: (0..next() % 3)
+                    .map(|_| SpsExtended {
+                        chroma_format_idc: (next() % 4) as u8,
+                        separate_color_plane_flag: false,
+                        bit_depth_luma_minus8: (next() % 5) as u8,
+                        bit_depth_chroma_minus8: (next() % 5) as u8,
+                        qpprime_y_zero_transform_bypass_flag: next() % 2 == 0,
+                        scaling_matrix: vec![],
+                    })
+                    .collect(),
+            });
+            let config = AVCDecoderConfigurationRecord {
+                configuration_version: 1,
+                profile_indication: 100,
+                profile_compatibility: 0,
+                level_indication: 31,
+                length_size_minus_one: 3,
+                sps,
+                pps,
+                extended_config,
+            };
+
+            let mut buf = Vec::with_capacity(config.encoded_size());
+            config.build(&mut buf).unwrap();
+            assert_eq!(buf.len(), config.encoded_size(), "{config:?}");
+        }
+    }
 }
@@ -6,3 +6,6 @@ pub use aspect_ratio_idc::*;
 
 mod video_format;
 pub use video_format::*;
+
+mod slice_type;
+pub use slice_type::*;
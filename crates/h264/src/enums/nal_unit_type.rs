@@ -157,7 +157,7 @@ impl TryFrom<u8> for NALUnitType {
             21 => Ok(NALUnitType::SliceLayerExtension2),
             22 => Ok(NALUnitType::Reserved3),
             23 => Ok(NALUnitType::Reserved4),
-            24..31 => Ok(NALUnitType::Unspecified2), // Application-specific
+            24..=31 => Ok(NALUnitType::Unspecified2), // Application-specific
             _ => Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Invalid NAL unit type: {value}"),
@@ -0,0 +1,66 @@
+use std::fmt;
+use std::io;
+
+/// The `SliceType` is a nutype enum for `slice_type` as defined in
+/// ISO/IEC-14496-10-2022 - 7.4.3 Table 7-6.
+///
+/// Values 5 to 9 map to the same types as 0 to 4, and additionally signal that all
+/// other slices of the picture have the same type.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SliceType {
+    /// Predicted slice.
+    P = 0,
+
+    /// Bi-predicted slice.
+    B = 1,
+
+    /// Intra slice.
+    I = 2,
+
+    /// Switching P slice.
+    SP = 3,
+
+    /// Switching I slice.
+    SI = 4,
+}
+
+impl SliceType {
+    /// Returns true for slices that are decoded without reference to other pictures.
+    pub fn is_intra(&self) -> bool {
+        matches!(self, Self::I | Self::SI)
+    }
+}
+
+// Implement Debug manually to ensure it includes the enum name in output
+impl fmt::Debug for SliceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SliceType::")?;
+        match self {
+            Self::P => write!(f, "P"),
+            Self::B => write!(f, "B"),
+            Self::I => write!(f, "I"),
+            Self::SP => write!(f, "SP"),
+            Self::SI => write!(f, "SI"),
+        }
+    }
+}
+
+impl TryFrom<u8> for SliceType {
+    type Error = io::Error;
+    /// Converts a u8 value to a `SliceType` enum.
+    /// Returns an error if the value is not in the range \[0, 9\].
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 | 5 => Ok(SliceType::P),
+            1 | 6 => Ok(SliceType::B),
+            2 | 7 => Ok(SliceType::I),
+            3 | 8 => Ok(SliceType::SP),
+            4 | 9 => Ok(SliceType::SI),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid slice type: {value}"),
+            )),
+        }
+    }
+}
@@ -104,11 +104,21 @@
 
 mod config;
 mod enums;
-mod io;
+mod nal;
+mod pps;
+mod sei;
+mod slice_header;
 mod sps;
 
+pub use bytes_util::nal_emulation_prevention::EmulationPreventionIo;
 pub use enums::*;
-pub use io::EmulationPreventionIo;
+pub use nal::{NalFraming, NalUnit, NalUnitIter, annex_b_to_avcc, avcc_to_annex_b};
+pub use pps::Pps;
+pub use sei::{
+    BufferingPeriod, ClockTimestamp, InitialCpbRemovalDelay, PicTiming, Sei, SeiMessage,
+    UserDataUnregistered,
+};
+pub use slice_header::SliceHeader;
 pub use sps::*;
 
-pub use self::config::{AVCDecoderConfigurationRecord, AvccExtendedConfig};
+pub use self::config::{AVCDecoderConfigurationRecord, AvccExtendedConfig, ConfigWarning};
@@ -0,0 +1,403 @@
+use std::io;
+
+use crate::NALUnitType;
+
+pub(crate) const START_CODE: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
+
+/// How NAL units are delimited in a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NalFraming {
+    /// Annex B byte stream, where every NAL unit is preceded by a `0x000001` or
+    /// `0x00000001` start code.
+    AnnexB,
+    /// AVCC framing, where every NAL unit is preceded by its big-endian length.
+    ///
+    /// `length_size_minus_one` is the value from the
+    /// [`AVCDecoderConfigurationRecord`](crate::AVCDecoderConfigurationRecord), in the range \[0, 3\].
+    LengthPrefixed {
+        /// The size of the length prefix in bytes, minus one.
+        length_size_minus_one: u8,
+    },
+}
+
+/// A single NAL unit borrowed from the buffer it was split from.
+///
+/// To get a [`Bytes`](bytes::Bytes) without copying, use
+/// [`Bytes::slice_ref`](bytes::Bytes::slice_ref) with [`NalUnit::data`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NalUnit<'a> {
+    /// The `nal_ref_idc` from the NAL unit header. ISO/IEC-14496-10-2022 - 7.4.1
+    pub nal_ref_idc: u8,
+    /// The `nal_unit_type` from the NAL unit header. ISO/IEC-14496-10-2022 - 7.4.1
+    pub nal_unit_type: NALUnitType,
+    /// The whole NAL unit, including the one byte header, without any framing.
+    pub data: &'a [u8],
+}
+
+impl<'a> NalUnit<'a> {
+    /// Parses the NAL unit header of `data`, which must not include any framing.
+    pub fn parse(data: &'a [u8]) -> io::Result<Self> {
+        let Some(&header) = data.first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "NAL unit is empty",
+            ));
+        };
+
+        if header & 0x80 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Forbidden zero bit is set",
+            ));
+        }
+
+        Ok(NalUnit {
+            nal_ref_idc: (header >> 5) & 0b11,
+            nal_unit_type: NALUnitType::try_from(header & 0b11111)?,
+            data,
+        })
+    }
+
+    /// The NAL unit payload after the one byte header, still containing emulation prevention bytes.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[1..]
+    }
+}
+
+/// An iterator that splits a buffer into [`NalUnit`]s without copying.
+///
+/// Trailing zero bytes after the last NAL unit are ignored. Once an error is returned the
+/// iterator is exhausted.
+#[derive(Debug, Clone)]
+pub struct NalUnitIter<'a> {
+    data: &'a [u8],
+    framing: NalFraming,
+    offset: usize,
+}
+
+impl<'a> NalUnitIter<'a> {
+    /// Creates an iterator over `data` using the given framing.
+    pub fn new(data: &'a [u8], framing: NalFraming) -> Self {
+        Self {
+            data,
+            framing,
+            offset: 0,
+        }
+    }
+
+    /// Creates an iterator over an Annex B byte stream.
+    pub fn annex_b(data: &'a [u8]) -> Self {
+        Self::new(data, NalFraming::AnnexB)
+    }
+
+    /// Creates an iterator over AVCC length-prefixed NAL units.
+    pub fn length_prefixed(data: &'a [u8], length_size_minus_one: u8) -> Self {
+        Self::new(
+            data,
+            NalFraming::LengthPrefixed {
+                length_size_minus_one,
+            },
+        )
+    }
+
+    fn next_annex_b(&mut self) -> Option<io::Result<&'a [u8]>> {
+        loop {
+            let rest = &self.data[self.offset..];
+            let Some(start) = find_start_code(rest) else {
+                let tail = rest;
+                self.offset = self.data.len();
+                if tail.iter().all(|&b| b == 0) {
+                    return None;
+                }
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "data does not start with an Annex B start code",
+                )));
+            };
+
+            if rest[..start].iter().any(|&b| b != 0) {
+                self.offset = self.data.len();
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "data does not start with an Annex B start code",
+                )));
+            }
+
+            let nal_start = start + 3;
+            let nal_end =
+                find_start_code(&rest[nal_start..]).map_or(rest.len(), |end| nal_start + end);
+            self.offset += nal_end;
+
+            // trailing_zero_8bits and the zero_byte of a 4 byte start code belong to the framing
+            let nal = trim_trailing_zeros(&rest[nal_start..nal_end]);
+            if !nal.is_empty() {
+                return Some(Ok(nal));
+            }
+        }
+    }
+
+    fn next_length_prefixed(&mut self, length_size_minus_one: u8) -> Option<io::Result<&'a [u8]>> {
+        if length_size_minus_one > 3 {
+            self.offset = self.data.len();
+            return Some(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("length_size_minus_one must be in range [0, 3]: {length_size_minus_one}"),
+            )));
+        }
+        let length_size = length_size_minus_one as usize + 1;
+
+        loop {
+            let rest = &self.data[self.offset..];
+            if rest.iter().all(|&b| b == 0) {
+                self.offset = self.data.len();
+                return None;
+            }
+
+            if rest.len() < length_size {
+                self.offset = self.data.len();
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "truncated NAL unit length prefix at offset {}: {} of {length_size} bytes available",
+                        self.offset,
+                        rest.len()
+                    ),
+                )));
+            }
+
+            let length = rest[..length_size]
+                .iter()
+                .fold(0usize, |acc, &b| (acc << 8) | b as usize);
+            let available = rest.len() - length_size;
+            if length > available {
+                let offset = self.offset;
+                self.offset = self.data.len();
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "NAL unit length {length} at offset {offset} exceeds the {available} remaining bytes"
+                    ),
+                )));
+            }
+
+            self.offset += length_size + length;
+            if length > 0 {
+                return Some(Ok(&rest[length_size..length_size + length]));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for NalUnitIter<'a> {
+    type Item = io::Result<NalUnit<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+
+        let nal = match self.framing {
+            NalFraming::AnnexB => self.next_annex_b(),
+            NalFraming::LengthPrefixed {
+                length_size_minus_one,
+            } => self.next_length_prefixed(length_size_minus_one),
+        }?;
+
+        let result = nal.and_then(NalUnit::parse);
+        if result.is_err() {
+            self.offset = self.data.len();
+        }
+        Some(result)
+    }
+}
+
+/// Converts an Annex B byte stream to AVCC length-prefixed NAL units.
+pub fn annex_b_to_avcc<W: io::Write>(
+    data: &[u8],
+    length_size_minus_one: u8,
+    writer: &mut W,
+) -> io::Result<()> {
+    if length_size_minus_one > 3 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("length_size_minus_one must be in range [0, 3]: {length_size_minus_one}"),
+        ));
+    }
+    let length_size = length_size_minus_one as usize + 1;
+    let max_length = (1u64 << (8 * length_size)) - 1;
+
+    for nal in NalUnitIter::annex_b(data) {
+        let nal = nal?;
+        let length = nal.data.len() as u64;
+        if length > max_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "NAL unit of {length} bytes does not fit in a {length_size} byte length prefix"
+                ),
+            ));
+        }
+        writer.write_all(&length.to_be_bytes()[8 - length_size..])?;
+        writer.write_all(nal.data)?;
+    }
+
+    Ok(())
+}
+
+/// Converts AVCC length-prefixed NAL units to an Annex B byte stream with 4 byte start codes.
+pub fn avcc_to_annex_b<W: io::Write>(
+    data: &[u8],
+    length_size_minus_one: u8,
+    writer: &mut W,
+) -> io::Result<()> {
+    for nal in NalUnitIter::length_prefixed(data, length_size_minus_one) {
+        writer.write_all(&START_CODE)?;
+        writer.write_all(nal?.data)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the first `0x000001` start code in `data`.
+fn find_start_code(data: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i + 2 < data.len() {
+        match data[i + 2] {
+            0x01 if data[i] == 0 && data[i + 1] == 0 => return Some(i),
+            // The third byte cannot be part of a start code, so skip past it
+            0x02.. => i += 3,
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn trim_trailing_zeros(data: &[u8]) -> &[u8] {
+    let end = data.iter().rposition(|&b| b != 0).map_or(0, |pos| pos + 1);
+    &data[..end]
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::io;
+
+    use crate::NALUnitType;
+    use crate::nal::{NalUnitIter, annex_b_to_avcc, avcc_to_annex_b};
+
+    const SPS: &[u8] = &[0x67, 0x42, 0x00, 0x1f, 0x96, 0x52];
+    const PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+    const IDR: &[u8] = &[0x65, 0x88, 0x84, 0x00, 0x00, 0x03, 0x00, 0x21];
+
+    fn annex_b_stream() -> Vec<u8> {
+        let mut data = vec![0x00, 0x00, 0x00, 0x01];
+        data.extend_from_slice(SPS);
+        data.extend_from_slice(&[0x00, 0x00, 0x01]);
+        data.extend_from_slice(PPS);
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        data.extend_from_slice(IDR);
+        // trailing_zero_8bits
+        data.extend_from_slice(&[0x00, 0x00, 0x00]);
+        data
+    }
+
+    #[test]
+    fn test_split_annex_b() {
+        let data = annex_b_stream();
+        let units: Vec<_> = NalUnitIter::annex_b(&data)
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(units.len(), 3);
+        assert_eq!(units[0].nal_unit_type, NALUnitType::SPS);
+        assert_eq!(units[0].nal_ref_idc, 3);
+        assert_eq!(units[0].data, SPS);
+        assert_eq!(units[1].nal_unit_type, NALUnitType::PPS);
+        assert_eq!(units[1].data, PPS);
+        assert_eq!(
+            units[2].nal_unit_type,
+            NALUnitType::IDRSliceLayerWithoutPartitioning
+        );
+        assert_eq!(units[2].data, IDR);
+        assert_eq!(units[2].payload(), &IDR[1..]);
+    }
+
+    #[test]
+    fn test_split_annex_b_rejects_missing_start_code() {
+        let data = [0x67, 0x42, 0x00, 0x00, 0x01, 0x68, 0xce];
+        let mut iter = NalUnitIter::annex_b(&data);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_split_length_prefixed() {
+        let mut data = Vec::new();
+        for nal in [SPS, PPS, IDR] {
+            data.extend_from_slice(&(nal.len() as u16).to_be_bytes());
+            data.extend_from_slice(nal);
+        }
+        data.extend_from_slice(&[0x00, 0x00, 0x00]);
+
+        let units: Vec<_> = NalUnitIter::length_prefixed(&data, 1)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            units.iter().map(|nal| nal.data).collect::<Vec<_>>(),
+            vec![SPS, PPS, IDR]
+        );
+    }
+
+    #[test]
+    fn test_split_length_prefixed_overrun() {
+        let mut data = vec![0x00, 0x00, 0x00, 0x20];
+        data.extend_from_slice(IDR);
+
+        let mut iter = NalUnitIter::length_prefixed(&data, 3);
+        let err = iter.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "NAL unit length 32 at offset 0 exceeds the 8 remaining bytes"
+        );
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_forbidden_zero_bit() {
+        let data = [0x00, 0x00, 0x01, 0xe7, 0x42];
+        let err = NalUnitIter::annex_b(&data).next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "Forbidden zero bit is set");
+    }
+
+    #[test]
+    fn test_convert_framing_round_trip() {
+        let annex_b = annex_b_stream();
+
+        let mut avcc = Vec::new();
+        annex_b_to_avcc(&annex_b, 3, &mut avcc).unwrap();
+        assert_eq!(&avcc[..4], &(SPS.len() as u32).to_be_bytes());
+        assert_eq!(avcc.len(), 3 * 4 + SPS.len() + PPS.len() + IDR.len());
+
+        let mut converted = Vec::new();
+        avcc_to_annex_b(&avcc, 3, &mut converted).unwrap();
+
+        let mut expected = Vec::new();
+        for nal in [SPS, PPS, IDR] {
+            expected.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            expected.extend_from_slice(nal);
+        }
+        assert_eq!(converted, expected);
+    }
+
+    #[test]
+    fn test_annex_b_to_avcc_length_overflow() {
+        let mut annex_b = vec![0x00, 0x00, 0x01, 0x65];
+        annex_b.extend_from_slice(&[0xAA; 255]);
+
+        let mut avcc = Vec::new();
+        let err = annex_b_to_avcc(&annex_b, 0, &mut avcc).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
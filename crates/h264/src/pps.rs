@@ -0,0 +1,305 @@
+use std::io;
+
+use bytes_util::{BitReader, range_check};
+use expgolomb::BitReaderExpGolombExt;
+
+use crate::{EmulationPreventionIo, NALUnitType};
+
+/// The Picture Parameter Set.
+/// ISO/IEC-14496-10-2022 - 7.3.2.2
+///
+/// Only the fields up to and including `redundant_pic_cnt_present_flag` are parsed, the
+/// optional High profile extension after them is not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pps {
+    /// The `nal_ref_idc` is comprised of 2 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.1
+    pub nal_ref_idc: u8,
+
+    /// The `pic_parameter_set_id` identifies this PPS in slice headers.
+    ///
+    /// The value of this ranges from \[0, 255\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub pic_parameter_set_id: u8,
+
+    /// The `seq_parameter_set_id` of the SPS this PPS refers to.
+    ///
+    /// The value of this ranges from \[0, 31\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub seq_parameter_set_id: u8,
+
+    /// The `entropy_coding_mode_flag` is a single bit.
+    ///
+    /// 0 means CAVLC is used, 1 means CABAC is used.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub entropy_coding_mode_flag: bool,
+
+    /// The `bottom_field_pic_order_in_frame_present_flag` is a single bit.
+    ///
+    /// 1 means `delta_pic_order_cnt_bottom` and `delta_pic_order_cnt[1]` may be present
+    /// in the slice headers.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub bottom_field_pic_order_in_frame_present_flag: bool,
+
+    /// The `num_slice_groups_minus1` plus 1 is the number of slice groups of a picture.
+    ///
+    /// The value of this ranges from \[0, 7\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub num_slice_groups_minus1: u8,
+
+    /// The `slice_group_map_type`, only present when `num_slice_groups_minus1 > 0`.
+    /// The map parameters that follow it are skipped.
+    ///
+    /// The value of this ranges from \[0, 6\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub slice_group_map_type: Option<u8>,
+
+    /// The `num_ref_idx_l0_default_active_minus1` is the inferred `num_ref_idx_l0_active_minus1`
+    /// of slices that do not override it.
+    ///
+    /// The value of this ranges from \[0, 31\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub num_ref_idx_l0_default_active_minus1: u8,
+
+    /// The `num_ref_idx_l1_default_active_minus1` is the inferred `num_ref_idx_l1_active_minus1`
+    /// of slices that do not override it.
+    ///
+    /// The value of this ranges from \[0, 31\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub num_ref_idx_l1_default_active_minus1: u8,
+
+    /// The `weighted_pred_flag` is a single bit.
+    ///
+    /// 1 means explicit weighted prediction is applied to P and SP slices.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub weighted_pred_flag: bool,
+
+    /// The `weighted_bipred_idc` is comprised of 2 bits and ranges from \[0, 2\].
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub weighted_bipred_idc: u8,
+
+    /// The `pic_init_qp_minus26` plus 26 is the initial SliceQP of each slice.
+    ///
+    /// The value of this ranges from \[-(26 + QpBdOffset), 25\].
+    /// It is encoded by an exp golomb (signed).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub pic_init_qp_minus26: i8,
+
+    /// The `pic_init_qs_minus26` plus 26 is the initial SliceQS of SP and SI slices.
+    ///
+    /// The value of this ranges from \[-26, 25\]. It is encoded by an exp golomb (signed).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub pic_init_qs_minus26: i8,
+
+    /// The `chroma_qp_index_offset` is added to QP when addressing the table of QPc values.
+    ///
+    /// The value of this ranges from \[-12, 12\]. It is encoded by an exp golomb (signed).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub chroma_qp_index_offset: i8,
+
+    /// The `deblocking_filter_control_present_flag` is a single bit.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub deblocking_filter_control_present_flag: bool,
+
+    /// The `constrained_intra_pred_flag` is a single bit.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub constrained_intra_pred_flag: bool,
+
+    /// The `redundant_pic_cnt_present_flag` is a single bit.
+    ///
+    /// 1 means `redundant_pic_cnt` is present in the slice headers.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.2.2
+    pub redundant_pic_cnt_present_flag: bool,
+}
+
+impl Pps {
+    /// Parses a Pps from the input bytes.
+    ///
+    /// Returns a `Pps` struct.
+    pub fn parse(reader: impl io::Read) -> io::Result<Self> {
+        let mut bit_reader = BitReader::new(reader);
+
+        let forbidden_zero_bit = bit_reader.read_bit()?;
+        if forbidden_zero_bit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Forbidden zero bit is set",
+            ));
+        }
+
+        let nal_ref_idc = bit_reader.read_bits(2)? as u8;
+        let nal_unit_type = bit_reader.read_bits(5)? as u8;
+        if NALUnitType::try_from(nal_unit_type)? != NALUnitType::PPS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "NAL unit type is not PPS",
+            ));
+        }
+
+        let pic_parameter_set_id = bit_reader.read_exp_golomb()?;
+        range_check!(pic_parameter_set_id, 0, 255)?;
+        let seq_parameter_set_id = bit_reader.read_exp_golomb()?;
+        range_check!(seq_parameter_set_id, 0, 31)?;
+
+        let entropy_coding_mode_flag = bit_reader.read_bit()?;
+        let bottom_field_pic_order_in_frame_present_flag = bit_reader.read_bit()?;
+
+        let num_slice_groups_minus1 = bit_reader.read_exp_golomb()?;
+        range_check!(num_slice_groups_minus1, 0, 7)?;
+
+        let mut slice_group_map_type = None;
+        if num_slice_groups_minus1 > 0 {
+            let map_type = bit_reader.read_exp_golomb()?;
+            range_check!(map_type, 0, 6)?;
+            skip_slice_group_map(&mut bit_reader, map_type, num_slice_groups_minus1)?;
+            slice_group_map_type = Some(map_type as u8);
+        }
+
+        let num_ref_idx_l0_default_active_minus1 = bit_reader.read_exp_golomb()?;
+        range_check!(num_ref_idx_l0_default_active_minus1, 0, 31)?;
+        let num_ref_idx_l1_default_active_minus1 = bit_reader.read_exp_golomb()?;
+        range_check!(num_ref_idx_l1_default_active_minus1, 0, 31)?;
+
+        let weighted_pred_flag = bit_reader.read_bit()?;
+        let weighted_bipred_idc = bit_reader.read_bits(2)? as u8;
+
+        let pic_init_qp_minus26 = bit_reader.read_signed_exp_golomb()?;
+        range_check!(pic_init_qp_minus26, -(26 + 6 * 6), 25)?;
+        let pic_init_qs_minus26 = bit_reader.read_signed_exp_golomb()?;
+        range_check!(pic_init_qs_minus26, -26, 25)?;
+        let chroma_qp_index_offset = bit_reader.read_signed_exp_golomb()?;
+        range_check!(chroma_qp_index_offset, -12, 12)?;
+
+        let deblocking_filter_control_present_flag = bit_reader.read_bit()?;
+        let constrained_intra_pred_flag = bit_reader.read_bit()?;
+        let redundant_pic_cnt_present_flag = bit_reader.read_bit()?;
+
+        Ok(Pps {
+            nal_ref_idc,
+            pic_parameter_set_id: pic_parameter_set_id as u8,
+            seq_parameter_set_id: seq_parameter_set_id as u8,
+            entropy_coding_mode_flag,
+            bottom_field_pic_order_in_frame_present_flag,
+            num_slice_groups_minus1: num_slice_groups_minus1 as u8,
+            slice_group_map_type,
+            num_ref_idx_l0_default_active_minus1: num_ref_idx_l0_default_active_minus1 as u8,
+            num_ref_idx_l1_default_active_minus1: num_ref_idx_l1_default_active_minus1 as u8,
+            weighted_pred_flag,
+            weighted_bipred_idc,
+            pic_init_qp_minus26: pic_init_qp_minus26 as i8,
+            pic_init_qs_minus26: pic_init_qs_minus26 as i8,
+            chroma_qp_index_offset: chroma_qp_index_offset as i8,
+            deblocking_filter_control_present_flag,
+            constrained_intra_pred_flag,
+            redundant_pic_cnt_present_flag,
+        })
+    }
+
+    /// Parses the Pps struct from a reader that may contain emulation prevention bytes.
+    /// Is the same as calling [`Self::parse`] with an [`EmulationPreventionIo`] wrapper.
+    pub fn parse_with_emulation_prevention(reader: impl io::Read) -> io::Result<Self> {
+        Self::parse(EmulationPreventionIo::new(reader))
+    }
+}
+
+/// Skips the slice group map parameters of `slice_group_map_type`. ISO/IEC-14496-10-2022 - 7.3.2.2
+fn skip_slice_group_map<T: io::Read>(
+    bit_reader: &mut BitReader<T>,
+    map_type: u64,
+    num_slice_groups_minus1: u64,
+) -> io::Result<()> {
+    match map_type {
+        0 => {
+            for _ in 0..=num_slice_groups_minus1 {
+                // run_length_minus1
+                bit_reader.read_exp_golomb()?;
+            }
+        }
+        2 => {
+            for _ in 0..num_slice_groups_minus1 {
+                // top_left, bottom_right
+                bit_reader.read_exp_golomb()?;
+                bit_reader.read_exp_golomb()?;
+            }
+        }
+        3..=5 => {
+            // slice_group_change_direction_flag, slice_group_change_rate_minus1
+            bit_reader.read_bit()?;
+            bit_reader.read_exp_golomb()?;
+        }
+        6 => {
+            let pic_size_in_map_units_minus1 = bit_reader.read_exp_golomb()?;
+            let bits = (num_slice_groups_minus1 + 1)
+                .next_power_of_two()
+                .trailing_zeros() as u8;
+            for _ in 0..=pic_size_in_map_units_minus1 {
+                // slice_group_id
+                bit_reader.read_bits(bits)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::io;
+
+    use crate::Pps;
+
+    #[test]
+    fn test_parse_pps() {
+        // The PPS from the `AVCDecoderConfigurationRecord` tests
+        let pps =
+            Pps::parse_with_emulation_prevention(io::Cursor::new(b"h\xeb\xe3\xcb\"\xc0")).unwrap();
+
+        insta::assert_debug_snapshot!(pps, @r"
+        Pps {
+            nal_ref_idc: 3,
+            pic_parameter_set_id: 0,
+            seq_parameter_set_id: 0,
+            entropy_coding_mode_flag: true,
+            bottom_field_pic_order_in_frame_present_flag: false,
+            num_slice_groups_minus1: 0,
+            slice_group_map_type: None,
+            num_ref_idx_l0_default_active_minus1: 2,
+            num_ref_idx_l1_default_active_minus1: 0,
+            weighted_pred_flag: true,
+            weighted_bipred_idc: 2,
+            pic_init_qp_minus26: -3,
+            pic_init_qs_minus26: 0,
+            chroma_qp_index_offset: -2,
+            deblocking_filter_control_present_flag: true,
+            constrained_intra_pred_flag: false,
+            redundant_pic_cnt_present_flag: false,
+        }
+        ");
+    }
+
+    #[test]
+    fn test_parse_pps_wrong_nal_unit_type() {
+        let err = Pps::parse(io::Cursor::new(b"\x67\x64\x00\x1f")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "NAL unit type is not PPS");
+    }
+}
@@ -0,0 +1,675 @@
+use std::io;
+
+use bytes::Bytes;
+use bytes_util::BitReader;
+use expgolomb::BitReaderExpGolombExt;
+
+use crate::{EmulationPreventionIo, HrdParameters, NALUnitType, Sps};
+
+/// A Supplemental Enhancement Information NAL unit.
+/// ISO/IEC-14496-10-2022 - 7.3.2.3
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sei {
+    /// The `nal_ref_idc` is comprised of 2 bits. It must be 0 for SEI NAL units.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.1
+    pub nal_ref_idc: u8,
+
+    /// The SEI messages in the order they appear in the NAL unit.
+    pub messages: Vec<SeiMessage>,
+}
+
+/// A single SEI message. ISO/IEC-14496-10-2022 - 7.3.2.3.1
+#[derive(Debug, Clone, PartialEq)]
+pub enum SeiMessage {
+    /// `payloadType == 0`. ISO/IEC-14496-10-2022 - D.1.2
+    BufferingPeriod(BufferingPeriod),
+    /// `payloadType == 1`. ISO/IEC-14496-10-2022 - D.1.3
+    PicTiming(PicTiming),
+    /// `payloadType == 5`. ISO/IEC-14496-10-2022 - D.1.7
+    UserDataUnregistered(UserDataUnregistered),
+    /// Any other payload type, kept as the raw payload bytes.
+    ///
+    /// Buffering period and picture timing messages also end up here when no [`Sps`]
+    /// is given to [`Sei::parse`], since they cannot be decoded without it.
+    Raw {
+        /// The `payloadType` of the message.
+        payload_type: u32,
+        /// The payload bytes, with emulation prevention bytes removed.
+        payload: Bytes,
+    },
+}
+
+impl SeiMessage {
+    /// Returns the `payloadType` of the message.
+    pub fn payload_type(&self) -> u32 {
+        match self {
+            SeiMessage::BufferingPeriod(_) => 0,
+            SeiMessage::PicTiming(_) => 1,
+            SeiMessage::UserDataUnregistered(_) => 5,
+            SeiMessage::Raw { payload_type, .. } => *payload_type,
+        }
+    }
+}
+
+/// The buffering period SEI message. ISO/IEC-14496-10-2022 - D.1.2
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferingPeriod {
+    /// The `seq_parameter_set_id` of the SPS this message refers to.
+    ///
+    /// The value of this ranges from \[0, 31\]. It is encoded by an exp golomb (unsigned).
+    pub seq_parameter_set_id: u16,
+
+    /// One entry per CPB of the NAL HRD. Empty if the SPS has no `nal_hrd_parameters`.
+    pub nal_initial_cpb_removal_delays: Vec<InitialCpbRemovalDelay>,
+
+    /// One entry per CPB of the VCL HRD. Empty if the SPS has no `vcl_hrd_parameters`.
+    pub vcl_initial_cpb_removal_delays: Vec<InitialCpbRemovalDelay>,
+}
+
+/// The initial CPB removal delay of a single CPB in a [`BufferingPeriod`].
+/// ISO/IEC-14496-10-2022 - D.2.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitialCpbRemovalDelay {
+    /// The delay between the arrival of the first bit of the access unit and its removal
+    /// from the CPB, in units of a 90 kHz clock.
+    ///
+    /// It is comprised of `initial_cpb_removal_delay_length_minus1 + 1` bits.
+    pub initial_cpb_removal_delay: u32,
+
+    /// Used together with `initial_cpb_removal_delay` to specify the initial delivery time
+    /// of coded access units to the CPB, in units of a 90 kHz clock.
+    ///
+    /// It is comprised of `initial_cpb_removal_delay_length_minus1 + 1` bits.
+    pub initial_cpb_removal_delay_offset: u32,
+}
+
+/// The picture timing SEI message. ISO/IEC-14496-10-2022 - D.1.3
+#[derive(Debug, Clone, PartialEq)]
+pub struct PicTiming {
+    /// The number of clock ticks between the removal of the access unit associated with the
+    /// most recent buffering period and this access unit.
+    ///
+    /// Present when the SPS has NAL or VCL HRD parameters, and is comprised of
+    /// `cpb_removal_delay_length_minus1 + 1` bits.
+    pub cpb_removal_delay: Option<u32>,
+
+    /// The number of clock ticks between the removal of this access unit from the CPB and its
+    /// output from the DPB.
+    ///
+    /// Present when the SPS has NAL or VCL HRD parameters, and is comprised of
+    /// `dpb_output_delay_length_minus1 + 1` bits.
+    pub dpb_output_delay: Option<u32>,
+
+    /// The `pic_struct` indicates whether the picture is a frame or one or more fields.
+    ///
+    /// Present when `pic_struct_present_flag` is set in the SPS. It is comprised of 4 bits
+    /// and ranges from \[0, 8\].
+    ///
+    /// ISO/IEC-14496-10-2022 - Table D-1
+    pub pic_struct: Option<u8>,
+
+    /// One entry per `NumClockTS` derived from `pic_struct`, `None` when `clock_timestamp_flag`
+    /// is not set for that entry. Empty when `pic_struct` is not present.
+    pub clock_timestamps: Vec<Option<ClockTimestamp>>,
+}
+
+/// A clock timestamp inside a [`PicTiming`] message. ISO/IEC-14496-10-2022 - D.2.3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTimestamp {
+    /// The `ct_type` is comprised of 2 bits. 0 is progressive, 1 is interlaced, 2 is unknown.
+    pub ct_type: u8,
+
+    /// The `nuit_field_based_flag` is a single bit.
+    pub nuit_field_based_flag: bool,
+
+    /// The `counting_type` is comprised of 5 bits. ISO/IEC-14496-10-2022 - Table D-3
+    pub counting_type: u8,
+
+    /// The `full_timestamp_flag` is a single bit.
+    ///
+    /// 1 means `seconds`, `minutes` and `hours` are all present.
+    pub full_timestamp_flag: bool,
+
+    /// The `discontinuity_flag` is a single bit.
+    pub discontinuity_flag: bool,
+
+    /// The `cnt_dropped_flag` is a single bit.
+    pub cnt_dropped_flag: bool,
+
+    /// The `n_frames` is comprised of 8 bits.
+    pub n_frames: u8,
+
+    /// The `seconds_value` is comprised of 6 bits and ranges from \[0, 59\].
+    pub seconds: Option<u8>,
+
+    /// The `minutes_value` is comprised of 6 bits and ranges from \[0, 59\].
+    ///
+    /// Only present when `seconds` is present.
+    pub minutes: Option<u8>,
+
+    /// The `hours_value` is comprised of 5 bits and ranges from \[0, 23\].
+    ///
+    /// Only present when `minutes` is present.
+    pub hours: Option<u8>,
+
+    /// The `time_offset` is a signed value comprised of `time_offset_length` bits.
+    /// 0 when `time_offset_length` is 0.
+    pub time_offset: i32,
+}
+
+/// The user data unregistered SEI message. ISO/IEC-14496-10-2022 - D.1.7
+///
+/// x264 uses this to embed its version and encoding settings as a NUL terminated string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserDataUnregistered {
+    /// The `uuid_iso_iec_11578` identifying the kind of user data.
+    pub uuid: [u8; 16],
+    /// The user data following the UUID.
+    pub payload: Bytes,
+}
+
+impl Sei {
+    /// Parses an SEI NAL unit from the input bytes.
+    ///
+    /// `sps` is the active SPS, which is needed to decode buffering period and picture timing
+    /// messages. If it is `None`, those messages are returned as [`SeiMessage::Raw`].
+    ///
+    /// Returns an `Sei` struct.
+    pub fn parse(mut reader: impl io::Read, sps: Option<&Sps>) -> io::Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let data = Bytes::from(data);
+
+        let Some(&header) = data.first() else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "SEI NAL unit is empty",
+            ));
+        };
+
+        if header & 0x80 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Forbidden zero bit is set",
+            ));
+        }
+
+        if NALUnitType::try_from(header & 0b11111)? != NALUnitType::SEI {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "NAL unit type is not SEI",
+            ));
+        }
+
+        let mut messages = Vec::new();
+        let mut offset = 1;
+        while more_rbsp_data(&data[offset..]) {
+            let payload_type = read_ff_coded(&data, &mut offset)?;
+            let payload_size = read_ff_coded(&data, &mut offset)? as usize;
+
+            let available = data.len() - offset;
+            if payload_size > available {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "SEI payload type {payload_type} has size {payload_size} but only {available} bytes remain"
+                    ),
+                ));
+            }
+
+            let payload = data.slice(offset..offset + payload_size);
+            offset += payload_size;
+
+            messages.push(SeiMessage::parse(payload_type, payload, sps)?);
+        }
+
+        Ok(Sei {
+            nal_ref_idc: (header >> 5) & 0b11,
+            messages,
+        })
+    }
+
+    /// Parses the Sei struct from a reader that may contain emulation prevention bytes.
+    /// Is the same as calling [`Self::parse`] with an [`EmulationPreventionIo`] wrapper.
+    pub fn parse_with_emulation_prevention(
+        reader: impl io::Read,
+        sps: Option<&Sps>,
+    ) -> io::Result<Self> {
+        Self::parse(EmulationPreventionIo::new(reader), sps)
+    }
+}
+
+impl SeiMessage {
+    fn parse(payload_type: u32, payload: Bytes, sps: Option<&Sps>) -> io::Result<Self> {
+        match (payload_type, sps) {
+            (0, Some(sps)) => BufferingPeriod::parse(&mut BitReader::new_from_slice(&payload), sps)
+                .map(SeiMessage::BufferingPeriod),
+            (1, Some(sps)) => PicTiming::parse(&mut BitReader::new_from_slice(&payload), sps)
+                .map(SeiMessage::PicTiming),
+            (5, _) => UserDataUnregistered::parse(payload).map(SeiMessage::UserDataUnregistered),
+            _ => Ok(SeiMessage::Raw {
+                payload_type,
+                payload,
+            }),
+        }
+    }
+}
+
+impl BufferingPeriod {
+    /// Parses a buffering period payload using the HRD parameters of `sps`.
+    pub fn parse<T: io::Read>(reader: &mut BitReader<T>, sps: &Sps) -> io::Result<Self> {
+        let seq_parameter_set_id = reader.read_exp_golomb()?;
+        if seq_parameter_set_id > 31 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("seq_parameter_set_id must be in range [0, 31]: {seq_parameter_set_id}"),
+            ));
+        }
+
+        let nal_initial_cpb_removal_delays =
+            InitialCpbRemovalDelay::parse_all(reader, sps.nal_hrd_parameters.as_ref())?;
+        let vcl_initial_cpb_removal_delays =
+            InitialCpbRemovalDelay::parse_all(reader, sps.vcl_hrd_parameters.as_ref())?;
+
+        Ok(BufferingPeriod {
+            seq_parameter_set_id: seq_parameter_set_id as u16,
+            nal_initial_cpb_removal_delays,
+            vcl_initial_cpb_removal_delays,
+        })
+    }
+}
+
+impl InitialCpbRemovalDelay {
+    fn parse_all<T: io::Read>(
+        reader: &mut BitReader<T>,
+        hrd: Option<&HrdParameters>,
+    ) -> io::Result<Vec<Self>> {
+        let Some(hrd) = hrd else {
+            return Ok(Vec::new());
+        };
+
+        let length = hrd.initial_cpb_removal_delay_length_minus1 + 1;
+        hrd.cpb_specs
+            .iter()
+            .map(|_| {
+                Ok(InitialCpbRemovalDelay {
+                    initial_cpb_removal_delay: reader.read_bits(length)? as u32,
+                    initial_cpb_removal_delay_offset: reader.read_bits(length)? as u32,
+                })
+            })
+            .collect()
+    }
+}
+
+impl PicTiming {
+    /// Parses a picture timing payload using the HRD and `pic_struct_present_flag` of `sps`.
+    pub fn parse<T: io::Read>(reader: &mut BitReader<T>, sps: &Sps) -> io::Result<Self> {
+        let hrd = sps
+            .nal_hrd_parameters
+            .as_ref()
+            .or(sps.vcl_hrd_parameters.as_ref());
+
+        let (cpb_removal_delay, dpb_output_delay) = match hrd {
+            Some(hrd) => (
+                Some(reader.read_bits(hrd.cpb_removal_delay_length_minus1 + 1)? as u32),
+                Some(reader.read_bits(hrd.dpb_output_delay_length_minus1 + 1)? as u32),
+            ),
+            None => (None, None),
+        };
+
+        if !sps.pic_struct_present_flag {
+            return Ok(PicTiming {
+                cpb_removal_delay,
+                dpb_output_delay,
+                pic_struct: None,
+                clock_timestamps: Vec::new(),
+            });
+        }
+
+        let pic_struct = reader.read_bits(4)? as u8;
+        // ISO/IEC-14496-10-2022 - Table D-1
+        let num_clock_ts = match pic_struct {
+            0..=2 => 1,
+            3 | 4 | 7 => 2,
+            5 | 6 | 8 => 3,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("pic_struct must be in range [0, 8]: {pic_struct}"),
+                ));
+            }
+        };
+
+        // time_offset_length is inferred to be 24 without HRD parameters. ISO/IEC-14496-10-2022 - E.2.2
+        let time_offset_length = hrd.map_or(24, |hrd| hrd.time_offset_length);
+
+        let mut clock_timestamps = Vec::with_capacity(num_clock_ts);
+        for _ in 0..num_clock_ts {
+            let clock_timestamp_flag = reader.read_bit()?;
+            clock_timestamps.push(if clock_timestamp_flag {
+                Some(ClockTimestamp::parse(reader, time_offset_length)?)
+            } else {
+                None
+            });
+        }
+
+        Ok(PicTiming {
+            cpb_removal_delay,
+            dpb_output_delay,
+            pic_struct: Some(pic_struct),
+            clock_timestamps,
+        })
+    }
+}
+
+impl ClockTimestamp {
+    fn parse<T: io::Read>(reader: &mut BitReader<T>, time_offset_length: u8) -> io::Result<Self> {
+        let ct_type = reader.read_bits(2)? as u8;
+        let nuit_field_based_flag = reader.read_bit()?;
+        let counting_type = reader.read_bits(5)? as u8;
+        let full_timestamp_flag = reader.read_bit()?;
+        let discontinuity_flag = reader.read_bit()?;
+        let cnt_dropped_flag = reader.read_bit()?;
+        let n_frames = reader.read_bits(8)? as u8;
+
+        let mut seconds = None;
+        let mut minutes = None;
+        let mut hours = None;
+        if full_timestamp_flag {
+            seconds = Some(reader.read_bits(6)? as u8);
+            minutes = Some(reader.read_bits(6)? as u8);
+            hours = Some(reader.read_bits(5)? as u8);
+        } else if reader.read_bit()? {
+            seconds = Some(reader.read_bits(6)? as u8);
+            if reader.read_bit()? {
+                minutes = Some(reader.read_bits(6)? as u8);
+                if reader.read_bit()? {
+                    hours = Some(reader.read_bits(5)? as u8);
+                }
+            }
+        }
+
+        let time_offset = if time_offset_length > 0 {
+            let value = reader.read_bits(time_offset_length)? as i64;
+            // sign extend the two's complement value
+            (value - ((value >> (time_offset_length - 1)) << time_offset_length)) as i32
+        } else {
+            0
+        };
+
+        Ok(ClockTimestamp {
+            ct_type,
+            nuit_field_based_flag,
+            counting_type,
+            full_timestamp_flag,
+            discontinuity_flag,
+            cnt_dropped_flag,
+            n_frames,
+            seconds,
+            minutes,
+            hours,
+            time_offset,
+        })
+    }
+}
+
+impl UserDataUnregistered {
+    /// Parses a user data unregistered payload.
+    pub fn parse(payload: Bytes) -> io::Result<Self> {
+        let Some(uuid) = payload.get(..16) else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "user_data_unregistered payload is shorter than its 16 byte UUID",
+            ));
+        };
+
+        let mut uuid_bytes = [0; 16];
+        uuid_bytes.copy_from_slice(uuid);
+
+        Ok(UserDataUnregistered {
+            uuid: uuid_bytes,
+            payload: payload.slice(16..),
+        })
+    }
+}
+
+/// Reads a `payloadType` or `payloadSize`, which are coded as a run of `0xFF` bytes
+/// each adding 255, followed by a final byte. ISO/IEC-14496-10-2022 - 7.3.2.3.1
+fn read_ff_coded(data: &[u8], offset: &mut usize) -> io::Result<u32> {
+    let mut value = 0u32;
+    loop {
+        let Some(&byte) = data.get(*offset) else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "SEI message header is truncated",
+            ));
+        };
+        *offset += 1;
+
+        value = value.checked_add(byte as u32).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "SEI message header overflows")
+        })?;
+        if byte != 0xFF {
+            return Ok(value);
+        }
+    }
+}
+
+/// Returns whether `data` contains more than the `rbsp_trailing_bits`.
+fn more_rbsp_data(data: &[u8]) -> bool {
+    match data.split_first() {
+        None => false,
+        Some((&0x80, rest)) => rest.iter().any(|&b| b != 0),
+        Some(_) => true,
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::io;
+
+    use bytes_util::BitWriter;
+
+    use crate::{ClockTimestamp, InitialCpbRemovalDelay, Sei, SeiMessage, Sps};
+
+    // High@3.1 1280x720 25fps with NAL HRD, see `test_parse_build_sps_x264_nal_hrd`
+    const X264_NAL_HRD_SPS: [u8; 36] = [
+        0x67, 0x64, 0x00, 0x1f, 0xac, 0xd1, 0x40, 0x50, 0x05, 0xba, 0x10, 0x00, 0x00, 0x03, 0x00,
+        0x10, 0x00, 0x00, 0x03, 0x03, 0x2e, 0x06, 0x00, 0x02, 0xdc, 0x6c, 0x00, 0x05, 0xb8, 0xda,
+        0x6c, 0x30, 0x07, 0x8c, 0x18, 0xcb,
+    ];
+
+    const X264_UUID: [u8; 16] = [
+        0xdc, 0x45, 0xe9, 0xbd, 0xe6, 0xd9, 0x48, 0xb7, 0x96, 0x2c, 0xd8, 0x20, 0xd9, 0x23, 0xee,
+        0xef,
+    ];
+
+    const X264_SETTINGS: &[u8] = b"x264 - core 164 r3095 baee400 - H.264/MPEG-4 AVC codec - \
+        Copyleft 2003-2022 - http://www.videolan.org/x264.html - options: cabac=1 ref=3 \
+        deblock=1:0:0 analyse=0x3:0x113 me=hex subme=7 psy=1 psy_rd=1.00:0.00 mixed_ref=1 \
+        me_range=16 chroma_me=1 trellis=1 8x8dct=1 cqm=0 deadzone=21,11 fast_pskip=1 \
+        chroma_qp_offset=-2 threads=12 lookahead_threads=2 sliced_threads=0 nr=0 decimate=1 \
+        interlaced=0 bluray_compat=0 constrained_intra=0 bframes=3 b_pyramid=2 b_adapt=1 \
+        b_bias=0 direct=1 weightb=1 open_gop=0 weightp=2 keyint=250 keyint_min=25 scenecut=40 \
+        intra_refresh=0 rc_lookahead=40 rc=abr mbtree=1 bitrate=3000 ratetol=1.0 qcomp=0.60 \
+        qpmin=0 qpmax=69 qpstep=4 vbv_maxrate=3000 vbv_bufsize=6000 nal_hrd=vbr filler=0 \
+        ip_ratio=1.40 aq=1:1.00\0";
+
+    fn x264_sps() -> Sps {
+        Sps::parse_with_emulation_prevention(io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_sei_user_data_unregistered_x264() {
+        // The SEI x264 writes in front of the first IDR, with a payloadSize above 255
+        let payload_size = X264_UUID.len() + X264_SETTINGS.len();
+        let mut nal = vec![0x06, 0x05];
+        nal.extend(std::iter::repeat_n(0xFF, payload_size / 255));
+        nal.push((payload_size % 255) as u8);
+        nal.extend_from_slice(&X264_UUID);
+        nal.extend_from_slice(X264_SETTINGS);
+        nal.push(0x80);
+
+        let sei = Sei::parse_with_emulation_prevention(io::Cursor::new(&nal), None).unwrap();
+        assert_eq!(sei.nal_ref_idc, 0);
+        assert_eq!(sei.messages.len(), 1);
+
+        let SeiMessage::UserDataUnregistered(user_data) = &sei.messages[0] else {
+            panic!("expected user_data_unregistered, got {:?}", sei.messages[0]);
+        };
+        assert_eq!(user_data.uuid, X264_UUID);
+        assert_eq!(user_data.payload.as_ref(), X264_SETTINGS);
+        assert_eq!(sei.messages[0].payload_type(), 5);
+    }
+
+    #[test]
+    fn test_parse_sei_buffering_period_pic_timing_x264() {
+        // buffering_period followed by pic_timing as x264 writes them with `--nal-hrd vbr`,
+        // the 0x03 is an emulation prevention byte
+        let nal = [
+            0x06, 0x00, 0x06, 0x94, 0x00, 0x00, 0x03, 0x00, 0x00, 0x40, 0x01, 0x03, 0x00, 0x00,
+            0x48, 0x80,
+        ];
+        let sps = x264_sps();
+
+        let sei = Sei::parse_with_emulation_prevention(io::Cursor::new(&nal), Some(&sps)).unwrap();
+        assert_eq!(sei.messages.len(), 2);
+
+        let SeiMessage::BufferingPeriod(buffering_period) = &sei.messages[0] else {
+            panic!("expected buffering_period, got {:?}", sei.messages[0]);
+        };
+        assert_eq!(buffering_period.seq_parameter_set_id, 0);
+        assert_eq!(
+            buffering_period.nal_initial_cpb_removal_delays,
+            vec![InitialCpbRemovalDelay {
+                initial_cpb_removal_delay: 163840,
+                initial_cpb_removal_delay_offset: 0,
+            }]
+        );
+        assert!(buffering_period.vcl_initial_cpb_removal_delays.is_empty());
+
+        let SeiMessage::PicTiming(pic_timing) = &sei.messages[1] else {
+            panic!("expected pic_timing, got {:?}", sei.messages[1]);
+        };
+        assert_eq!(pic_timing.cpb_removal_delay, Some(0));
+        assert_eq!(pic_timing.dpb_output_delay, Some(4));
+        assert_eq!(pic_timing.pic_struct, None);
+        assert!(pic_timing.clock_timestamps.is_empty());
+
+        // Without the SPS the timing messages are kept as is
+        let sei = Sei::parse_with_emulation_prevention(io::Cursor::new(&nal), None).unwrap();
+        assert!(matches!(
+            &sei.messages[1],
+            SeiMessage::Raw { payload_type: 1, payload } if payload.as_ref() == [0x00, 0x00, 0x48]
+        ));
+    }
+
+    #[test]
+    fn test_parse_sei_pic_timing_clock_timestamp() {
+        let mut sps = x264_sps();
+        sps.pic_struct_present_flag = true;
+        if let Some(hrd) = sps.nal_hrd_parameters.as_mut() {
+            hrd.time_offset_length = 8;
+        }
+
+        let mut payload = Vec::new();
+        let mut writer = BitWriter::new(&mut payload);
+        // cpb_removal_delay
+        writer.write_bits(2, 13).unwrap();
+        // dpb_output_delay
+        writer.write_bits(4, 7).unwrap();
+        // pic_struct, top field then bottom field
+        writer.write_bits(3, 4).unwrap();
+        // clock_timestamp_flag
+        writer.write_bit(true).unwrap();
+        // ct_type
+        writer.write_bits(1, 2).unwrap();
+        // nuit_field_based_flag
+        writer.write_bit(true).unwrap();
+        // counting_type
+        writer.write_bits(0, 5).unwrap();
+        // full_timestamp_flag
+        writer.write_bit(false).unwrap();
+        // discontinuity_flag
+        writer.write_bit(false).unwrap();
+        // cnt_dropped_flag
+        writer.write_bit(false).unwrap();
+        // n_frames
+        writer.write_bits(12, 8).unwrap();
+        // seconds_flag, seconds_value
+        writer.write_bit(true).unwrap();
+        writer.write_bits(42, 6).unwrap();
+        // minutes_flag, minutes_value
+        writer.write_bit(true).unwrap();
+        writer.write_bits(7, 6).unwrap();
+        // hours_flag
+        writer.write_bit(false).unwrap();
+        // time_offset
+        writer.write_bits(0xFE, 8).unwrap();
+        // clock_timestamp_flag
+        writer.write_bit(false).unwrap();
+        writer.finish().unwrap();
+
+        let mut nal = vec![0x06, 0x01, payload.len() as u8];
+        nal.extend_from_slice(&payload);
+        nal.push(0x80);
+
+        let sei = Sei::parse(io::Cursor::new(&nal), Some(&sps)).unwrap();
+        let SeiMessage::PicTiming(pic_timing) = &sei.messages[0] else {
+            panic!("expected pic_timing, got {:?}", sei.messages[0]);
+        };
+        assert_eq!(pic_timing.cpb_removal_delay, Some(2));
+        assert_eq!(pic_timing.pic_struct, Some(3));
+        assert_eq!(
+            pic_timing.clock_timestamps,
+            vec![
+                Some(ClockTimestamp {
+                    ct_type: 1,
+                    nuit_field_based_flag: true,
+                    counting_type: 0,
+                    full_timestamp_flag: false,
+                    discontinuity_flag: false,
+                    cnt_dropped_flag: false,
+                    n_frames: 12,
+                    seconds: Some(42),
+                    minutes: Some(7),
+                    hours: None,
+                    time_offset: -2,
+                }),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_sei_unknown_payload_type() {
+        // recovery_point, which is not decoded
+        let nal = [0x06, 0x06, 0x01, 0xc4, 0x80];
+
+        let sei = Sei::parse(io::Cursor::new(&nal), None).unwrap();
+        assert_eq!(
+            sei.messages,
+            vec![SeiMessage::Raw {
+                payload_type: 6,
+                payload: bytes::Bytes::from_static(&[0xc4]),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_sei_payload_overrun() {
+        let nal = [0x06, 0x05, 0x20, 0xdc, 0x45, 0x80];
+
+        let err = Sei::parse(io::Cursor::new(&nal), None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_parse_sei_wrong_nal_unit_type() {
+        let err = Sei::parse(io::Cursor::new(&X264_NAL_HRD_SPS), None).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "NAL unit type is not SEI");
+    }
+}
@@ -0,0 +1,354 @@
+use std::io;
+
+use bytes_util::{BitReader, range_check};
+use expgolomb::BitReaderExpGolombExt;
+
+use crate::{EmulationPreventionIo, NALUnitType, Pps, SliceType, Sps};
+
+/// The leading fields of a slice header, up to and including the picture order count syntax.
+/// ISO/IEC-14496-10-2022 - 7.3.3
+///
+/// This is enough to classify a slice and to detect reordering, the reference picture list
+/// modification and everything after it is not parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceHeader {
+    /// The `nal_ref_idc` is comprised of 2 bits.
+    ///
+    /// 0 means the slice belongs to a non-reference picture.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.1
+    pub nal_ref_idc: u8,
+
+    /// The `nal_unit_type` of the slice NAL unit.
+    pub nal_unit_type: NALUnitType,
+
+    /// The `first_mb_in_slice` is the address of the first macroblock in the slice.
+    /// 0 means this is the first slice of the picture.
+    ///
+    /// It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub first_mb_in_slice: u32,
+
+    /// The `slice_type` of the slice.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub slice_type: SliceType,
+
+    /// The `pic_parameter_set_id` of the PPS in use.
+    ///
+    /// The value of this ranges from \[0, 255\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub pic_parameter_set_id: u8,
+
+    /// The `colour_plane_id` is comprised of 2 bits.
+    ///
+    /// Only present when `separate_color_plane_flag` is set in the SPS.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub colour_plane_id: Option<u8>,
+
+    /// The `frame_num` is comprised of `log2_max_frame_num_minus4 + 4` bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub frame_num: u16,
+
+    /// The `field_pic_flag` is a single bit. 1 means the slice belongs to a coded field.
+    ///
+    /// Only read when `frame_mbs_only_flag` is not set in the SPS, false otherwise.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub field_pic_flag: bool,
+
+    /// The `bottom_field_flag` is a single bit. 1 means the slice belongs to a bottom field.
+    ///
+    /// Only read when `field_pic_flag` is set, false otherwise.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub bottom_field_flag: bool,
+
+    /// The `idr_pic_id` identifies an IDR picture.
+    ///
+    /// Only present for IDR slices. The value of this ranges from \[0, 65535\].
+    /// It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub idr_pic_id: Option<u16>,
+
+    /// The `pic_order_cnt_lsb` is comprised of `log2_max_pic_order_cnt_lsb_minus4 + 4` bits.
+    ///
+    /// Only present when `pic_order_cnt_type == 0`.
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub pic_order_cnt_lsb: Option<u16>,
+
+    /// The `delta_pic_order_cnt_bottom` is the difference between the bottom and top field
+    /// picture order counts of a frame.
+    ///
+    /// Only present when `pic_order_cnt_type == 0`, `bottom_field_pic_order_in_frame_present_flag`
+    /// is set in the PPS and the slice is not a field. It is encoded by an exp golomb (signed).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub delta_pic_order_cnt_bottom: Option<i32>,
+
+    /// The `delta_pic_order_cnt[0]`, only present when `pic_order_cnt_type == 1` and
+    /// `delta_pic_order_always_zero_flag` is not set. It is encoded by an exp golomb (signed).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub delta_pic_order_cnt0: Option<i32>,
+
+    /// The `delta_pic_order_cnt[1]`, present under the same conditions as `delta_pic_order_cnt0`
+    /// when the slice is not a field and `bottom_field_pic_order_in_frame_present_flag` is set
+    /// in the PPS. It is encoded by an exp golomb (signed).
+    ///
+    /// ISO/IEC-14496-10-2022 - 7.4.3
+    pub delta_pic_order_cnt1: Option<i32>,
+}
+
+impl SliceHeader {
+    /// Parses the leading fields of a slice header from the input bytes.
+    ///
+    /// The field widths depend on the `sps` and `pps` the slice refers to, which must be the
+    /// active parameter sets.
+    ///
+    /// Returns a `SliceHeader` struct.
+    pub fn parse(sps: &Sps, pps: &Pps, reader: impl io::Read) -> io::Result<Self> {
+        let mut bit_reader = BitReader::new(reader);
+
+        let forbidden_zero_bit = bit_reader.read_bit()?;
+        if forbidden_zero_bit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Forbidden zero bit is set",
+            ));
+        }
+
+        let nal_ref_idc = bit_reader.read_bits(2)? as u8;
+        let nal_unit_type = NALUnitType::try_from(bit_reader.read_bits(5)? as u8)?;
+        if !matches!(
+            nal_unit_type,
+            NALUnitType::NonIDRSliceLayerWithoutPartitioning
+                | NALUnitType::SliceDataPartitionALayer
+                | NALUnitType::IDRSliceLayerWithoutPartitioning
+        ) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("NAL unit type {nal_unit_type:?} does not start with a slice header"),
+            ));
+        }
+
+        let first_mb_in_slice = bit_reader.read_exp_golomb()?;
+        range_check!(first_mb_in_slice, 0, u32::MAX as u64)?;
+
+        let slice_type = bit_reader.read_exp_golomb()?;
+        range_check!(slice_type, 0, 9)?;
+        let slice_type = SliceType::try_from(slice_type as u8)?;
+
+        let pic_parameter_set_id = bit_reader.read_exp_golomb()?;
+        if pic_parameter_set_id != pps.pic_parameter_set_id as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "slice refers to PPS {pic_parameter_set_id} but PPS {} was given",
+                    pps.pic_parameter_set_id
+                ),
+            ));
+        }
+        if pps.seq_parameter_set_id as u16 != sps.seq_parameter_set_id {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "PPS refers to SPS {} but SPS {} was given",
+                    pps.seq_parameter_set_id, sps.seq_parameter_set_id
+                ),
+            ));
+        }
+
+        let separate_color_plane_flag = sps
+            .ext
+            .as_ref()
+            .is_some_and(|ext| ext.separate_color_plane_flag);
+        let colour_plane_id = if separate_color_plane_flag {
+            Some(bit_reader.read_bits(2)? as u8)
+        } else {
+            None
+        };
+
+        let frame_num = bit_reader.read_bits(sps.log2_max_frame_num_minus4 + 4)? as u16;
+
+        let mut field_pic_flag = false;
+        let mut bottom_field_flag = false;
+        // mb_adaptive_frame_field_flag is only present when frame_mbs_only_flag is not set
+        if sps.mb_adaptive_frame_field_flag.is_some() {
+            field_pic_flag = bit_reader.read_bit()?;
+            if field_pic_flag {
+                bottom_field_flag = bit_reader.read_bit()?;
+            }
+        }
+
+        let idr_pic_id = if nal_unit_type == NALUnitType::IDRSliceLayerWithoutPartitioning {
+            let idr_pic_id = bit_reader.read_exp_golomb()?;
+            range_check!(idr_pic_id, 0, u16::MAX as u64)?;
+            Some(idr_pic_id as u16)
+        } else {
+            None
+        };
+
+        let bottom_field_pic_order_present =
+            pps.bottom_field_pic_order_in_frame_present_flag && !field_pic_flag;
+
+        let mut pic_order_cnt_lsb = None;
+        let mut delta_pic_order_cnt_bottom = None;
+        let mut delta_pic_order_cnt0 = None;
+        let mut delta_pic_order_cnt1 = None;
+        match sps.pic_order_cnt_type {
+            0 => {
+                let lsb_bits = sps.log2_max_pic_order_cnt_lsb_minus4.unwrap_or(0) + 4;
+                pic_order_cnt_lsb = Some(bit_reader.read_bits(lsb_bits)? as u16);
+                if bottom_field_pic_order_present {
+                    delta_pic_order_cnt_bottom = Some(bit_reader.read_signed_exp_golomb()? as i32);
+                }
+            }
+            1 if sps
+                .pic_order_cnt_type1
+                .as_ref()
+                .is_some_and(|poc| !poc.delta_pic_order_always_zero_flag) =>
+            {
+                delta_pic_order_cnt0 = Some(bit_reader.read_signed_exp_golomb()? as i32);
+                if bottom_field_pic_order_present {
+                    delta_pic_order_cnt1 = Some(bit_reader.read_signed_exp_golomb()? as i32);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(SliceHeader {
+            nal_ref_idc,
+            nal_unit_type,
+            first_mb_in_slice: first_mb_in_slice as u32,
+            slice_type,
+            pic_parameter_set_id: pic_parameter_set_id as u8,
+            colour_plane_id,
+            frame_num,
+            field_pic_flag,
+            bottom_field_flag,
+            idr_pic_id,
+            pic_order_cnt_lsb,
+            delta_pic_order_cnt_bottom,
+            delta_pic_order_cnt0,
+            delta_pic_order_cnt1,
+        })
+    }
+
+    /// Parses the SliceHeader struct from a reader that may contain emulation prevention bytes.
+    /// Is the same as calling [`Self::parse`] with an [`EmulationPreventionIo`] wrapper.
+    pub fn parse_with_emulation_prevention(
+        sps: &Sps,
+        pps: &Pps,
+        reader: impl io::Read,
+    ) -> io::Result<Self> {
+        Self::parse(sps, pps, EmulationPreventionIo::new(reader))
+    }
+
+    /// Returns true if the slice belongs to an IDR picture.
+    pub fn is_idr(&self) -> bool {
+        self.nal_unit_type == NALUnitType::IDRSliceLayerWithoutPartitioning
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use std::io;
+
+    use crate::{Pps, SliceHeader, SliceType, Sps};
+
+    fn parameter_sets() -> (Sps, Pps) {
+        // The SPS and PPS from the `AVCDecoderConfigurationRecord` tests
+        let sps = Sps::parse_with_emulation_prevention(io::Cursor::new(
+            b"\x67\x64\x00\x1F\xAC\xD9\x41\xE0\x6D\xF9\xE6\xA0\x20\x20\x28\x00\x00\x03\x00\x08\x00\x00\x03\x01\xE0",
+        ))
+        .unwrap();
+        let pps =
+            Pps::parse_with_emulation_prevention(io::Cursor::new(b"h\xeb\xe3\xcb\"\xc0")).unwrap();
+        (sps, pps)
+    }
+
+    #[test]
+    fn test_parse_slice_header_idr() {
+        let (sps, pps) = parameter_sets();
+
+        // The first bytes of an x264 IDR slice
+        let slice = [0x65, 0x88, 0x84, 0x00, 0x33, 0xff];
+        let header =
+            SliceHeader::parse_with_emulation_prevention(&sps, &pps, io::Cursor::new(&slice))
+                .unwrap();
+
+        insta::assert_debug_snapshot!(header, @r"
+        SliceHeader {
+            nal_ref_idc: 3,
+            nal_unit_type: NALUnitType::IDRSliceLayerWithoutPartitioning,
+            first_mb_in_slice: 0,
+            slice_type: SliceType::I,
+            pic_parameter_set_id: 0,
+            colour_plane_id: None,
+            frame_num: 0,
+            field_pic_flag: false,
+            bottom_field_flag: false,
+            idr_pic_id: Some(
+                0,
+            ),
+            pic_order_cnt_lsb: Some(
+                0,
+            ),
+            delta_pic_order_cnt_bottom: None,
+            delta_pic_order_cnt0: None,
+            delta_pic_order_cnt1: None,
+        }
+        ");
+        assert!(header.is_idr());
+        assert!(header.slice_type.is_intra());
+    }
+
+    #[test]
+    fn test_parse_slice_header_p_and_b() {
+        let (sps, pps) = parameter_sets();
+
+        let p_slice = [0x41, 0x9a, 0x22, 0x40];
+        let header = SliceHeader::parse(&sps, &pps, io::Cursor::new(&p_slice)).unwrap();
+        assert!(!header.is_idr());
+        assert_eq!(header.nal_ref_idc, 2);
+        assert_eq!(header.slice_type, SliceType::P);
+        assert_eq!(header.frame_num, 1);
+        assert_eq!(header.idr_pic_id, None);
+        assert_eq!(header.pic_order_cnt_lsb, Some(4));
+
+        // A non-reference B frame shown before the P frame it depends on
+        let b_slice = [0x01, 0x9e, 0x41, 0x40];
+        let header = SliceHeader::parse(&sps, &pps, io::Cursor::new(&b_slice)).unwrap();
+        assert_eq!(header.nal_ref_idc, 0);
+        assert_eq!(header.slice_type, SliceType::B);
+        assert_eq!(header.frame_num, 2);
+        assert_eq!(header.pic_order_cnt_lsb, Some(2));
+    }
+
+    #[test]
+    fn test_parse_slice_header_wrong_pps() {
+        let (sps, mut pps) = parameter_sets();
+        pps.pic_parameter_set_id = 1;
+
+        let slice = [0x65, 0x88, 0x84, 0x00, 0x33, 0xff];
+        let err = SliceHeader::parse(&sps, &pps, io::Cursor::new(&slice)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "slice refers to PPS 0 but PPS 1 was given");
+    }
+
+    #[test]
+    fn test_parse_slice_header_not_a_slice() {
+        let (sps, pps) = parameter_sets();
+
+        let err = SliceHeader::parse(&sps, &pps, io::Cursor::new(b"h\xeb\xe3\xcb")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
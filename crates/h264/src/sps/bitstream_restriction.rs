@@ -0,0 +1,197 @@
+use std::io;
+
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+};
+
+/// `BitstreamRestriction` contains the fields that are set when
+/// `bitstream_restriction_flag == 1`.
+///
+/// ISO/IEC-14496-10-2022 - E.1.1
+///
+/// Refer to the direct fields for more information.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitstreamRestriction {
+    /// The `motion_vectors_over_pic_boundaries_flag` is a single bit.
+    ///
+    /// 0 means no sample outside the picture boundaries is used for inter prediction.
+    /// 1 means samples outside the picture boundaries may be used.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub motion_vectors_over_pic_boundaries_flag: bool,
+
+    /// The `max_bytes_per_pic_denom` bounds the size of the VCL NAL units of any coded picture.
+    /// 0 means no limit is signalled.
+    ///
+    /// The value of this ranges from \[0, 16\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub max_bytes_per_pic_denom: u8,
+
+    /// The `max_bits_per_mb_denom` bounds the number of coded bits of any macroblock.
+    /// 0 means no limit is signalled.
+    ///
+    /// The value of this ranges from \[0, 16\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub max_bits_per_mb_denom: u8,
+
+    /// The `log2_max_mv_length_horizontal` is the maximum absolute horizontal motion vector
+    /// component in 1/4 luma sample units, as a power of 2.
+    ///
+    /// The value of this ranges from \[0, 15\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub log2_max_mv_length_horizontal: u8,
+
+    /// The `log2_max_mv_length_vertical` is the maximum absolute vertical motion vector
+    /// component in 1/4 luma sample units, as a power of 2.
+    ///
+    /// The value of this ranges from \[0, 15\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub log2_max_mv_length_vertical: u8,
+
+    /// The `max_num_reorder_frames` is the maximum number of frames that precede any frame
+    /// in decoding order and follow it in output order. This is the decoder delay in frames.
+    ///
+    /// The value of this ranges from \[0, `max_dec_frame_buffering`\].
+    /// It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub max_num_reorder_frames: u8,
+
+    /// The `max_dec_frame_buffering` is the required size of the decoded picture buffer
+    /// in frame buffers.
+    ///
+    /// The value of this ranges from \[0, 16\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub max_dec_frame_buffering: u8,
+}
+
+impl BitstreamRestriction {
+    /// Parses the fields defined when the `bitstream_restriction_flag == 1` from a bitstream.
+    /// Returns a `BitstreamRestriction` struct.
+    pub fn parse<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<Self> {
+        let motion_vectors_over_pic_boundaries_flag = reader.read_bit()?;
+
+        let max_bytes_per_pic_denom = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        range_check!(max_bytes_per_pic_denom, 0, 16)?;
+        let max_bits_per_mb_denom = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        range_check!(max_bits_per_mb_denom, 0, 16)?;
+        let log2_max_mv_length_horizontal =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        range_check!(log2_max_mv_length_horizontal, 0, 15)?;
+        let log2_max_mv_length_vertical =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        range_check!(log2_max_mv_length_vertical, 0, 15)?;
+        let max_num_reorder_frames = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let max_dec_frame_buffering = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        range_check!(max_dec_frame_buffering, 0, 16)?;
+        range_check!(max_num_reorder_frames, 0, max_dec_frame_buffering)?;
+
+        Ok(BitstreamRestriction {
+            motion_vectors_over_pic_boundaries_flag,
+            max_bytes_per_pic_denom: max_bytes_per_pic_denom as u8,
+            max_bits_per_mb_denom: max_bits_per_mb_denom as u8,
+            log2_max_mv_length_horizontal: log2_max_mv_length_horizontal as u8,
+            log2_max_mv_length_vertical: log2_max_mv_length_vertical as u8,
+            max_num_reorder_frames: max_num_reorder_frames as u8,
+            max_dec_frame_buffering: max_dec_frame_buffering as u8,
+        })
+    }
+
+    /// Builds the BitstreamRestriction struct into a byte stream.
+    /// Returns a built byte stream.
+    pub fn build<T: io::Write>(&self, writer: &mut BitWriter<T>) -> io::Result<()> {
+        writer.write_bit(self.motion_vectors_over_pic_boundaries_flag)?;
+        writer.write_exp_golomb(self.max_bytes_per_pic_denom as u64)?;
+        writer.write_exp_golomb(self.max_bits_per_mb_denom as u64)?;
+        writer.write_exp_golomb(self.log2_max_mv_length_horizontal as u64)?;
+        writer.write_exp_golomb(self.log2_max_mv_length_vertical as u64)?;
+        writer.write_exp_golomb(self.max_num_reorder_frames as u64)?;
+        writer.write_exp_golomb(self.max_dec_frame_buffering as u64)?;
+        Ok(())
+    }
+
+    /// Returns the total bits of the BitstreamRestriction struct.
+    ///
+    /// Note that this isn't the bytesize since aligning it may cause some values to be different.
+    pub fn bitsize(&self) -> u64 {
+        1 + // motion_vectors_over_pic_boundaries_flag
+        size_of_exp_golomb(self.max_bytes_per_pic_denom as u64) +
+        size_of_exp_golomb(self.max_bits_per_mb_denom as u64) +
+        size_of_exp_golomb(self.log2_max_mv_length_horizontal as u64) +
+        size_of_exp_golomb(self.log2_max_mv_length_vertical as u64) +
+        size_of_exp_golomb(self.max_num_reorder_frames as u64) +
+        size_of_exp_golomb(self.max_dec_frame_buffering as u64)
+    }
+
+    /// Returns the total bytes of the BitstreamRestriction struct.
+    ///
+    /// Note that this calls [`BitstreamRestriction::bitsize()`] and calculates the number of bytes
+    /// including any necessary padding such that the bitstream is byte aligned.
+    pub fn bytesize(&self) -> u64 {
+        self.bitsize().div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes_util::{BitReader, BitWriter};
+    use expgolomb::BitWriterExpGolombExt;
+
+    use crate::sps::BitstreamRestriction;
+
+    fn write_restriction(reorder: u64, dec_buffering: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut writer = BitWriter::new(&mut data);
+
+        // motion_vectors_over_pic_boundaries_flag
+        writer.write_bit(true).unwrap();
+        // max_bytes_per_pic_denom
+        writer.write_exp_golomb(0).unwrap();
+        // max_bits_per_mb_denom
+        writer.write_exp_golomb(0).unwrap();
+        // log2_max_mv_length_horizontal
+        writer.write_exp_golomb(11).unwrap();
+        // log2_max_mv_length_vertical
+        writer.write_exp_golomb(11).unwrap();
+        // max_num_reorder_frames
+        writer.write_exp_golomb(reorder).unwrap();
+        // max_dec_frame_buffering
+        writer.write_exp_golomb(dec_buffering).unwrap();
+        writer.finish().unwrap();
+        data
+    }
+
+    #[test]
+    fn test_build_size_bitstream_restriction() {
+        let mut data = write_restriction(2, 4);
+
+        let mut reader = BitReader::new_from_slice(&mut data);
+        let restriction = BitstreamRestriction::parse(&mut reader).unwrap();
+        assert_eq!(restriction.max_num_reorder_frames, 2);
+        assert_eq!(restriction.max_dec_frame_buffering, 4);
+
+        let mut buf = Vec::new();
+        let mut writer2 = BitWriter::new(&mut buf);
+        restriction.build(&mut writer2).unwrap();
+        writer2.finish().unwrap();
+
+        assert_eq!(buf, data);
+        assert_eq!(restriction.bytesize(), data.len() as u64);
+    }
+
+    #[test]
+    fn test_parse_reorder_exceeds_dec_frame_buffering() {
+        let mut data = write_restriction(5, 4);
+
+        let mut reader = BitReader::new_from_slice(&mut data);
+        let err = BitstreamRestriction::parse(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -1,7 +1,9 @@
 use std::io;
 
 use bytes_util::{BitReader, BitWriter};
-use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, size_of_exp_golomb};
+use expgolomb::{
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+};
 
 /// `ChromaSampleLoc` contains the fields that are set when `chroma_loc_info_present_flag == 1`,
 ///
@@ -45,8 +47,10 @@ impl ChromaSampleLoc {
     /// Parses the fields defined when the `chroma_loc_info_present_flag == 1` from a bitstream.
     /// Returns a `ChromaSampleLoc` struct.
     pub fn parse<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<Self> {
-        let chroma_sample_loc_type_top_field = reader.read_exp_golomb()? as u8;
-        let chroma_sample_loc_type_bottom_field = reader.read_exp_golomb()? as u8;
+        let chroma_sample_loc_type_top_field =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
+        let chroma_sample_loc_type_bottom_field =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
 
         Ok(ChromaSampleLoc {
             chroma_sample_loc_type_top_field,
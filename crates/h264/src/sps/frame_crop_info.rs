@@ -1,7 +1,9 @@
 use std::io;
 
 use bytes_util::{BitReader, BitWriter};
-use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, size_of_exp_golomb};
+use expgolomb::{
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+};
 
 /// `FrameCropInfo` contains the frame cropping info.
 ///
@@ -64,10 +66,10 @@ impl FrameCropInfo {
     /// Parses the fields defined when the `frame_cropping_flag == 1` from a bitstream.
     /// Returns a `FrameCropInfo` struct.
     pub fn parse<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<Self> {
-        let frame_crop_left_offset = reader.read_exp_golomb()?;
-        let frame_crop_right_offset = reader.read_exp_golomb()?;
-        let frame_crop_top_offset = reader.read_exp_golomb()?;
-        let frame_crop_bottom_offset = reader.read_exp_golomb()?;
+        let frame_crop_left_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let frame_crop_right_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let frame_crop_top_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let frame_crop_bottom_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
         Ok(FrameCropInfo {
             frame_crop_left_offset,
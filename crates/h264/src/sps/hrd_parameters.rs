@@ -0,0 +1,262 @@
+use std::io;
+
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+};
+
+/// `HrdParameters` contains the fields that are set when `nal_hrd_parameters_present_flag == 1`
+/// or `vcl_hrd_parameters_present_flag == 1`.
+///
+/// ISO/IEC-14496-10-2022 - E.1.2
+///
+/// Refer to the direct fields for more information.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HrdParameters {
+    /// The `bit_rate_scale` together with `bit_rate_value_minus1` specifies the
+    /// maximum input bit rate of each CPB.
+    ///
+    /// It is comprised of 4 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub bit_rate_scale: u8,
+
+    /// The `cpb_size_scale` together with `cpb_size_value_minus1` specifies the
+    /// size of each CPB.
+    ///
+    /// It is comprised of 4 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub cpb_size_scale: u8,
+
+    /// One entry per alternative CPB specification. The length of this vec is
+    /// `cpb_cnt_minus1 + 1`, which ranges from \[1, 32\].
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub cpb_specs: Vec<CpbSpec>,
+
+    /// The `initial_cpb_removal_delay_length_minus1` plus 1 is the length in bits of the
+    /// `initial_cpb_removal_delay` and `initial_cpb_removal_delay_offset` fields of the
+    /// buffering period SEI message.
+    ///
+    /// It is comprised of 5 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub initial_cpb_removal_delay_length_minus1: u8,
+
+    /// The `cpb_removal_delay_length_minus1` plus 1 is the length in bits of the
+    /// `cpb_removal_delay` field of the picture timing SEI message.
+    ///
+    /// It is comprised of 5 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub cpb_removal_delay_length_minus1: u8,
+
+    /// The `dpb_output_delay_length_minus1` plus 1 is the length in bits of the
+    /// `dpb_output_delay` field of the picture timing SEI message.
+    ///
+    /// It is comprised of 5 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub dpb_output_delay_length_minus1: u8,
+
+    /// The `time_offset_length` is the length in bits of the `time_offset` field of the
+    /// picture timing SEI message. 0 means `time_offset` is not present.
+    ///
+    /// It is comprised of 5 bits.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub time_offset_length: u8,
+}
+
+/// A single CPB specification inside [`HrdParameters`].
+///
+/// ISO/IEC-14496-10-2022 - E.1.2
+#[derive(Debug, Clone, PartialEq)]
+pub struct CpbSpec {
+    /// The maximum input bit rate of this CPB is
+    /// `(bit_rate_value_minus1 + 1) * 2^(6 + bit_rate_scale)` bits per second.
+    ///
+    /// The value of this ranges from \[0, 2^32 - 2\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub bit_rate_value_minus1: u32,
+
+    /// The size of this CPB is `(cpb_size_value_minus1 + 1) * 2^(4 + cpb_size_scale)` bits.
+    ///
+    /// The value of this ranges from \[0, 2^32 - 2\]. It is encoded by an exp golomb (unsigned).
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub cpb_size_value_minus1: u32,
+
+    /// The `cbr_flag` is a single bit.
+    ///
+    /// 1 means this CPB operates in constant bit rate mode, 0 means variable bit rate.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.2
+    pub cbr_flag: bool,
+}
+
+impl HrdParameters {
+    /// Parses the fields of `hrd_parameters()` from a bitstream.
+    /// Returns a `HrdParameters` struct.
+    pub fn parse<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<Self> {
+        let cpb_cnt_minus1 = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        range_check!(cpb_cnt_minus1, 0, 31)?;
+
+        let bit_rate_scale = reader.read_bits(4)? as u8;
+        let cpb_size_scale = reader.read_bits(4)? as u8;
+
+        let mut cpb_specs = Vec::with_capacity(cpb_cnt_minus1 as usize + 1);
+        for _ in 0..=cpb_cnt_minus1 {
+            let bit_rate_value_minus1 =
+                reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            range_check!(bit_rate_value_minus1, 0, u32::MAX as u64 - 1)?;
+            let cpb_size_value_minus1 =
+                reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            range_check!(cpb_size_value_minus1, 0, u32::MAX as u64 - 1)?;
+            let cbr_flag = reader.read_bit()?;
+
+            cpb_specs.push(CpbSpec {
+                bit_rate_value_minus1: bit_rate_value_minus1 as u32,
+                cpb_size_value_minus1: cpb_size_value_minus1 as u32,
+                cbr_flag,
+            });
+        }
+
+        let initial_cpb_removal_delay_length_minus1 = reader.read_bits(5)? as u8;
+        let cpb_removal_delay_length_minus1 = reader.read_bits(5)? as u8;
+        let dpb_output_delay_length_minus1 = reader.read_bits(5)? as u8;
+        let time_offset_length = reader.read_bits(5)? as u8;
+
+        Ok(HrdParameters {
+            bit_rate_scale,
+            cpb_size_scale,
+            cpb_specs,
+            initial_cpb_removal_delay_length_minus1,
+            cpb_removal_delay_length_minus1,
+            dpb_output_delay_length_minus1,
+            time_offset_length,
+        })
+    }
+
+    /// Builds the HrdParameters struct into a byte stream.
+    /// Returns a built byte stream.
+    pub fn build<T: io::Write>(&self, writer: &mut BitWriter<T>) -> io::Result<()> {
+        if self.cpb_specs.is_empty() || self.cpb_specs.len() > 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "hrd_parameters must contain between 1 and 32 CPB specifications",
+            ));
+        }
+
+        writer.write_exp_golomb(self.cpb_specs.len() as u64 - 1)?;
+        writer.write_bits(self.bit_rate_scale as u64, 4)?;
+        writer.write_bits(self.cpb_size_scale as u64, 4)?;
+
+        for spec in &self.cpb_specs {
+            writer.write_exp_golomb(spec.bit_rate_value_minus1 as u64)?;
+            writer.write_exp_golomb(spec.cpb_size_value_minus1 as u64)?;
+            writer.write_bit(spec.cbr_flag)?;
+        }
+
+        writer.write_bits(self.initial_cpb_removal_delay_length_minus1 as u64, 5)?;
+        writer.write_bits(self.cpb_removal_delay_length_minus1 as u64, 5)?;
+        writer.write_bits(self.dpb_output_delay_length_minus1 as u64, 5)?;
+        writer.write_bits(self.time_offset_length as u64, 5)?;
+        Ok(())
+    }
+
+    /// Returns the total bits of the HrdParameters struct.
+    ///
+    /// Note that this isn't the bytesize since aligning it may cause some values to be different.
+    pub fn bitsize(&self) -> u64 {
+        size_of_exp_golomb(self.cpb_specs.len().saturating_sub(1) as u64)
+            + 4 // bit_rate_scale
+            + 4 // cpb_size_scale
+            + self
+                .cpb_specs
+                .iter()
+                .map(|spec| {
+                    size_of_exp_golomb(spec.bit_rate_value_minus1 as u64)
+                        + size_of_exp_golomb(spec.cpb_size_value_minus1 as u64)
+                        + 1 // cbr_flag
+                })
+                .sum::<u64>()
+            + 20 // four 5-bit length fields
+    }
+
+    /// Returns the total bytes of the HrdParameters struct.
+    ///
+    /// Note that this calls [`HrdParameters::bitsize()`] and calculates the number of bytes
+    /// including any necessary padding such that the bitstream is byte aligned.
+    pub fn bytesize(&self) -> u64 {
+        self.bitsize().div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes_util::{BitReader, BitWriter};
+    use expgolomb::BitWriterExpGolombExt;
+
+    use crate::sps::HrdParameters;
+
+    #[test]
+    fn test_build_size_hrd_parameters() {
+        let mut data = Vec::new();
+        let mut writer = BitWriter::new(&mut data);
+
+        // cpb_cnt_minus1
+        writer.write_exp_golomb(1).unwrap();
+        // bit_rate_scale
+        writer.write_bits(0, 4).unwrap();
+        // cpb_size_scale
+        writer.write_bits(3, 4).unwrap();
+        for value in [46874, 23436] {
+            // bit_rate_value_minus1
+            writer.write_exp_golomb(value).unwrap();
+            // cpb_size_value_minus1
+            writer.write_exp_golomb(value).unwrap();
+            // cbr_flag
+            writer.write_bit(value == 23436).unwrap();
+        }
+        // initial_cpb_removal_delay_length_minus1
+        writer.write_bits(23, 5).unwrap();
+        // cpb_removal_delay_length_minus1
+        writer.write_bits(23, 5).unwrap();
+        // dpb_output_delay_length_minus1
+        writer.write_bits(5, 5).unwrap();
+        // time_offset_length
+        writer.write_bits(24, 5).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = BitReader::new_from_slice(&mut data);
+        let hrd = HrdParameters::parse(&mut reader).unwrap();
+        assert_eq!(hrd.cpb_specs.len(), 2);
+        assert!(hrd.cpb_specs[1].cbr_flag);
+        assert_eq!(hrd.cpb_removal_delay_length_minus1, 23);
+        assert_eq!(hrd.time_offset_length, 24);
+
+        let mut buf = Vec::new();
+        let mut writer2 = BitWriter::new(&mut buf);
+        hrd.build(&mut writer2).unwrap();
+        writer2.finish().unwrap();
+
+        assert_eq!(buf, data);
+        assert_eq!(hrd.bytesize(), data.len() as u64);
+    }
+
+    #[test]
+    fn test_parse_hrd_parameters_cpb_cnt_out_of_range() {
+        let mut data = Vec::new();
+        let mut writer = BitWriter::new(&mut data);
+        writer.write_exp_golomb(32).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = BitReader::new_from_slice(&mut data);
+        let err = HrdParameters::parse(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
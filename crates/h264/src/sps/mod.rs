@@ -1,3 +1,6 @@
+mod bitstream_restriction;
+pub use self::bitstream_restriction::BitstreamRestriction;
+
 mod chroma_sample_loc;
 use self::chroma_sample_loc::ChromaSampleLoc;
 
@@ -7,6 +10,9 @@ use self::color_config::ColorConfig;
 mod frame_crop_info;
 use self::frame_crop_info::FrameCropInfo;
 
+mod hrd_parameters;
+pub use self::hrd_parameters::{CpbSpec, HrdParameters};
+
 mod pic_order_count_type1;
 use self::pic_order_count_type1::PicOrderCountType1;
 
@@ -17,11 +23,20 @@ mod sps_ext;
 pub use self::sps_ext::SpsExtended;
 
 mod timing_info;
+
+mod trailing_bits;
+use self::trailing_bits::ResumePoint;
+pub use self::trailing_bits::TrailingBits;
+
 use std::io;
+use std::num::NonZeroU32;
 
 use byteorder::ReadBytesExt;
+use bytes_util::nal_emulation_prevention::emulation_prevention_size;
 use bytes_util::{BitReader, BitWriter};
-use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, size_of_exp_golomb};
+use expgolomb::{
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+};
 
 pub use self::timing_info::TimingInfo;
 use crate::{EmulationPreventionIo, NALUnitType};
@@ -348,19 +363,105 @@ pub struct Sps {
     /// An optional `TimingInfo`. This is computed from other fields, and isn't directly set.
     ///
     /// If `timing_info_present_flag` is set, then the `TimingInfo` will be computed, and
-    /// is comprised of `num_units_in_tick` and `time_scale`.
+    /// is comprised of `num_units_in_tick`, `time_scale` and `fixed_frame_rate_flag`.
     ///
     /// Refer to the TimingInfo struct for more info.
     pub timing_info: Option<TimingInfo>,
+
+    /// An optional `HrdParameters` for the NAL HRD.
+    ///
+    /// If `nal_hrd_parameters_present_flag` is set, then the NAL HRD parameters will be read and stored.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    ///
+    /// Refer to the HrdParameters struct for more info.
+    pub nal_hrd_parameters: Option<HrdParameters>,
+
+    /// An optional `HrdParameters` for the VCL HRD.
+    ///
+    /// If `vcl_hrd_parameters_present_flag` is set, then the VCL HRD parameters will be read and stored.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    ///
+    /// Refer to the HrdParameters struct for more info.
+    pub vcl_hrd_parameters: Option<HrdParameters>,
+
+    /// An optional `low_delay_hrd_flag` is a single bit.
+    ///
+    /// It is only present when `nal_hrd_parameters` or `vcl_hrd_parameters` is set.
+    ///
+    /// 1 means the HRD operates in low delay mode, where big pictures may be removed from
+    /// the CPB late.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub low_delay_hrd_flag: Option<bool>,
+
+    /// The `pic_struct_present_flag` is a single bit.
+    ///
+    /// 1 means picture timing SEI messages carry the `pic_struct` field.
+    ///
+    /// ISO/IEC-14496-10-2022 - E.2.1
+    pub pic_struct_present_flag: bool,
+
+    /// An optional `BitstreamRestriction`.
+    ///
+    /// If `bitstream_restriction_flag` is set, then the `BitstreamRestriction` will be read and stored.
+    ///
+    /// Refer to the BitstreamRestriction struct for more info.
+    pub bitstream_restriction: Option<BitstreamRestriction>,
+
+    /// An optional `TrailingBits`, the bits following the last field above.
+    ///
+    /// Only [`Sps::parse_lossless`] sets it, in which case [`Sps::build`] writes these bits
+    /// in place of the `rbsp_trailing_bits` to reproduce the input exactly. Fields before
+    /// them can be changed freely, but building fails if the VUI is added or removed, or
+    /// if the VUI fields after `timing_info` are set while the input was cut off after it.
+    /// Set this to `None` to build such an SPS anyway.
+    ///
+    /// Refer to the TrailingBits struct for more info.
+    pub trailing_bits: Option<TrailingBits>,
 }
 
+/// The maximum number of bytes read while parsing an SPS.
+///
+/// Real world SPS NAL units are a few hundred bytes at most, this only stops
+/// corrupted input or never ending readers from being read indefinitely.
+const MAX_SPS_SIZE: u64 = 64 * 1024;
+
 impl Sps {
     /// Parses an Sps from the input bytes.
     ///
+    /// At most 64 KiB are read from the reader.
+    ///
     /// Returns an `Sps` struct.
     pub fn parse(reader: impl io::Read) -> io::Result<Self> {
-        let mut bit_reader = BitReader::new(reader);
+        let mut bit_reader = BitReader::new(io::Read::take(reader, MAX_SPS_SIZE));
+        Self::parse_fields(&mut bit_reader).map(|(sps, _)| sps)
+    }
 
+    /// Parses an Sps from the input bytes, keeping the bits after the last parsed field
+    /// in [`Sps::trailing_bits`].
+    ///
+    /// Building the result without changes reproduces the input exactly, including VUI
+    /// fields cut off after `timing_info` and anything following the `rbsp_trailing_bits`.
+    ///
+    /// At most 64 KiB are read from the reader.
+    pub fn parse_lossless(reader: impl io::Read) -> io::Result<Self> {
+        let mut rbsp = Vec::new();
+        io::Read::read_to_end(&mut io::Read::take(reader, MAX_SPS_SIZE), &mut rbsp)?;
+
+        let mut bit_reader = BitReader::new_from_slice(&rbsp);
+        let (mut sps, (resume_point, position)) = Self::parse_fields(&mut bit_reader)?;
+        sps.trailing_bits = Some(TrailingBits::from_rbsp(&rbsp, position, resume_point));
+
+        Ok(sps)
+    }
+
+    /// Parses the fields of an Sps, along with where the parsed fields end and the bit
+    /// position there.
+    fn parse_fields<T: io::Read>(
+        bit_reader: &mut BitReader<T>,
+    ) -> io::Result<(Self, (ResumePoint, u64))> {
         let forbidden_zero_bit = bit_reader.read_bit()?;
         if forbidden_zero_bit {
             return Err(io::Error::new(
@@ -428,31 +529,38 @@ impl Sps {
         bit_reader.read_bits(2)?;
 
         let level_idc = bit_reader.read_u8()?;
-        let seq_parameter_set_id = bit_reader.read_exp_golomb()? as u16;
+        let seq_parameter_set_id =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u16;
 
         let sps_ext = match profile_idc {
             100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135 => {
-                Some(SpsExtended::parse(&mut bit_reader)?)
+                Some(SpsExtended::parse(bit_reader)?)
             }
             _ => None,
         };
 
-        let log2_max_frame_num_minus4 = bit_reader.read_exp_golomb()? as u8;
-        let pic_order_cnt_type = bit_reader.read_exp_golomb()? as u8;
+        let log2_max_frame_num_minus4 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
+        let pic_order_cnt_type =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
 
         let mut log2_max_pic_order_cnt_lsb_minus4 = None;
         let mut pic_order_cnt_type1 = None;
 
         if pic_order_cnt_type == 0 {
-            log2_max_pic_order_cnt_lsb_minus4 = Some(bit_reader.read_exp_golomb()? as u8);
+            log2_max_pic_order_cnt_lsb_minus4 =
+                Some(bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8);
         } else if pic_order_cnt_type == 1 {
-            pic_order_cnt_type1 = Some(PicOrderCountType1::parse(&mut bit_reader)?)
+            pic_order_cnt_type1 = Some(PicOrderCountType1::parse(bit_reader)?)
         }
 
-        let max_num_ref_frames = bit_reader.read_exp_golomb()? as u8;
+        let max_num_ref_frames =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
         let gaps_in_frame_num_value_allowed_flag = bit_reader.read_bit()?;
-        let pic_width_in_mbs_minus1 = bit_reader.read_exp_golomb()?;
-        let pic_height_in_map_units_minus1 = bit_reader.read_exp_golomb()?;
+        let pic_width_in_mbs_minus1 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let pic_height_in_map_units_minus1 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
         let frame_mbs_only_flag = bit_reader.read_bit()?;
         let mut mb_adaptive_frame_field_flag = None;
@@ -466,7 +574,7 @@ impl Sps {
 
         let frame_cropping_flag = bit_reader.read_bit()?;
         if frame_cropping_flag {
-            frame_crop_info = Some(FrameCropInfo::parse(&mut bit_reader)?)
+            frame_crop_info = Some(FrameCropInfo::parse(bit_reader)?)
         }
 
         // setting default values for vui section
@@ -475,14 +583,16 @@ impl Sps {
         let mut color_config = None;
         let mut chroma_sample_loc = None;
         let mut timing_info = None;
+        let mut vui_tail = VuiTail::default();
 
         let vui_parameters_present_flag = bit_reader.read_bit()?;
+        let mut resume = (ResumePoint::NoVui, bit_reader.bit_position());
         if vui_parameters_present_flag {
             // We read the VUI parameters to get the frame rate.
 
             let aspect_ratio_info_present_flag = bit_reader.read_bit()?;
             if aspect_ratio_info_present_flag {
-                sample_aspect_ratio = Some(SarDimensions::parse(&mut bit_reader)?)
+                sample_aspect_ratio = Some(SarDimensions::parse(bit_reader)?)
             }
 
             let overscan_info_present_flag = bit_reader.read_bit()?;
@@ -492,7 +602,7 @@ impl Sps {
 
             let video_signal_type_present_flag = bit_reader.read_bit()?;
             if video_signal_type_present_flag {
-                color_config = Some(ColorConfig::parse(&mut bit_reader)?)
+                color_config = Some(ColorConfig::parse(bit_reader)?)
             }
 
             let chroma_loc_info_present_flag = bit_reader.read_bit()?;
@@ -510,16 +620,28 @@ impl Sps {
             }
 
             if chroma_loc_info_present_flag {
-                chroma_sample_loc = Some(ChromaSampleLoc::parse(&mut bit_reader)?)
+                chroma_sample_loc = Some(ChromaSampleLoc::parse(bit_reader)?)
             }
 
             let timing_info_present_flag = bit_reader.read_bit()?;
             if timing_info_present_flag {
-                timing_info = Some(TimingInfo::parse(&mut bit_reader)?)
+                timing_info = Some(TimingInfo::parse(bit_reader)?)
+            }
+
+            // Some muxers cut the VUI off after timing_info; like ffmpeg, treat the
+            // missing fields as absent rather than rejecting the whole SPS.
+            resume = (ResumePoint::AfterTimingInfo, bit_reader.bit_position());
+            match VuiTail::parse(bit_reader) {
+                Ok(tail) => {
+                    vui_tail = tail;
+                    resume = (ResumePoint::AfterVui, bit_reader.bit_position());
+                }
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+                Err(e) => return Err(e),
             }
         }
 
-        Ok(Sps {
+        let sps = Sps {
             nal_ref_idc,
             nal_unit_type: NALUnitType::try_from(nal_unit_type)?,
             profile_idc,
@@ -548,12 +670,25 @@ impl Sps {
             color_config,
             chroma_sample_loc,
             timing_info,
-        })
+            nal_hrd_parameters: vui_tail.nal_hrd_parameters,
+            vcl_hrd_parameters: vui_tail.vcl_hrd_parameters,
+            low_delay_hrd_flag: vui_tail.low_delay_hrd_flag,
+            pic_struct_present_flag: vui_tail.pic_struct_present_flag,
+            bitstream_restriction: vui_tail.bitstream_restriction,
+            trailing_bits: None,
+        };
+
+        Ok((sps, resume))
     }
 
     /// Builds the Sps struct into a byte stream.
     /// Returns a built byte stream.
+    ///
+    /// The output ends with the `rbsp_trailing_bits`, so it is always byte aligned. If
+    /// [`Sps::trailing_bits`] is set, those bits are written instead, and an error is returned
+    /// when the fields they follow have changed.
     pub fn build(&self, writer: impl io::Write) -> io::Result<()> {
+        let resume_point = self.check_trailing_bits()?;
         let mut bit_writer = BitWriter::new(writer);
 
         bit_writer.write_bit(false)?;
@@ -581,10 +716,26 @@ impl Sps {
         bit_writer.write_exp_golomb(self.log2_max_frame_num_minus4 as u64)?;
         bit_writer.write_exp_golomb(self.pic_order_cnt_type as u64)?;
 
-        if self.pic_order_cnt_type == 0 {
-            bit_writer.write_exp_golomb(self.log2_max_pic_order_cnt_lsb_minus4.unwrap() as u64)?;
-        } else if let Some(pic_order_cnt) = &self.pic_order_cnt_type1 {
-            pic_order_cnt.build(&mut bit_writer)?;
+        match self.pic_order_cnt_type {
+            0 => {
+                let lsb = self.log2_max_pic_order_cnt_lsb_minus4.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "pic_order_cnt_type 0 without log2_max_pic_order_cnt_lsb_minus4",
+                    )
+                })?;
+                bit_writer.write_exp_golomb(lsb as u64)?;
+            }
+            1 => {
+                let pic_order_cnt = self.pic_order_cnt_type1.as_ref().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "pic_order_cnt_type 1 without pic_order_cnt_type1",
+                    )
+                })?;
+                pic_order_cnt.build(&mut bit_writer)?;
+            }
+            _ => {}
         }
 
         bit_writer.write_exp_golomb(self.max_num_ref_frames as u64)?;
@@ -604,62 +755,114 @@ impl Sps {
             frame_crop_info.build(&mut bit_writer)?;
         }
 
-        match (
-            &self.sample_aspect_ratio,
-            &self.overscan_appropriate_flag,
-            &self.color_config,
-            &self.chroma_sample_loc,
-            &self.timing_info,
-        ) {
-            (None, None, None, None, None) => {
-                bit_writer.write_bit(false)?;
+        // vui_parameters_present_flag
+        bit_writer.write_bit(self.has_vui_parameters())?;
+        if self.has_vui_parameters() {
+            // aspect_ratio_info_present_flag
+            bit_writer.write_bit(self.sample_aspect_ratio.is_some())?;
+            if let Some(sar) = &self.sample_aspect_ratio {
+                sar.build(&mut bit_writer)?;
             }
-            _ => {
-                // vui_parameters_present_flag
-                bit_writer.write_bit(true)?;
 
-                // aspect_ratio_info_present_flag
-                bit_writer.write_bit(self.sample_aspect_ratio.is_some())?;
-                if let Some(sar) = &self.sample_aspect_ratio {
-                    sar.build(&mut bit_writer)?;
-                }
+            // overscan_info_present_flag
+            bit_writer.write_bit(self.overscan_appropriate_flag.is_some())?;
+            if let Some(overscan) = &self.overscan_appropriate_flag {
+                bit_writer.write_bit(*overscan)?;
+            }
 
-                // overscan_info_present_flag
-                bit_writer.write_bit(self.overscan_appropriate_flag.is_some())?;
-                if let Some(overscan) = &self.overscan_appropriate_flag {
-                    bit_writer.write_bit(*overscan)?;
-                }
+            // video_signal_type_prsent_flag
+            bit_writer.write_bit(self.color_config.is_some())?;
+            if let Some(color) = &self.color_config {
+                color.build(&mut bit_writer)?;
+            }
 
-                // video_signal_type_prsent_flag
-                bit_writer.write_bit(self.color_config.is_some())?;
-                if let Some(color) = &self.color_config {
-                    color.build(&mut bit_writer)?;
-                }
+            // chroma_log_info_present_flag
+            bit_writer.write_bit(self.chroma_sample_loc.is_some())?;
+            if let Some(chroma) = &self.chroma_sample_loc {
+                chroma.build(&mut bit_writer)?;
+            }
 
-                // chroma_log_info_present_flag
-                bit_writer.write_bit(self.chroma_sample_loc.is_some())?;
-                if let Some(chroma) = &self.chroma_sample_loc {
-                    chroma.build(&mut bit_writer)?;
-                }
+            // timing_info_present_flag
+            bit_writer.write_bit(self.timing_info.is_some())?;
+            if let Some(timing) = &self.timing_info {
+                timing.build(&mut bit_writer)?;
+            }
+        }
 
-                // timing_info_present_flag
-                bit_writer.write_bit(self.timing_info.is_some())?;
-                if let Some(timing) = &self.timing_info {
-                    timing.build(&mut bit_writer)?;
-                }
+        // the rest of a VUI that was cut off is part of the trailing bits
+        if self.has_vui_parameters() && resume_point != Some(ResumePoint::AfterTimingInfo) {
+            // nal_hrd_parameters_present_flag
+            bit_writer.write_bit(self.nal_hrd_parameters.is_some())?;
+            if let Some(hrd) = &self.nal_hrd_parameters {
+                hrd.build(&mut bit_writer)?;
+            }
+
+            // vcl_hrd_parameters_present_flag
+            bit_writer.write_bit(self.vcl_hrd_parameters.is_some())?;
+            if let Some(hrd) = &self.vcl_hrd_parameters {
+                hrd.build(&mut bit_writer)?;
+            }
+
+            if self.nal_hrd_parameters.is_some() || self.vcl_hrd_parameters.is_some() {
+                bit_writer.write_bit(self.low_delay_hrd_flag.unwrap_or(false))?;
+            }
+
+            bit_writer.write_bit(self.pic_struct_present_flag)?;
+
+            // bitstream_restriction_flag
+            bit_writer.write_bit(self.bitstream_restriction.is_some())?;
+            if let Some(restriction) = &self.bitstream_restriction {
+                restriction.build(&mut bit_writer)?;
             }
         }
-        bit_writer.finish()?;
+
+        if let Some(trailing_bits) = &self.trailing_bits {
+            trailing_bits.build(&mut bit_writer)?;
+            // only needed if a changed field altered the length of the fields before
+            bit_writer.align()?;
+        } else {
+            // rbsp_stop_one_bit, followed by zero bits up to the byte boundary
+            bit_writer.write_trailing_one_and_align()?;
+        }
 
         Ok(())
     }
 
+    /// Checks that the [`Sps::trailing_bits`] still follow the fields they were captured
+    /// after, returning where they were captured.
+    fn check_trailing_bits(&self) -> io::Result<Option<ResumePoint>> {
+        let Some(trailing_bits) = &self.trailing_bits else {
+            return Ok(None);
+        };
+
+        let valid = match trailing_bits.resume_point {
+            ResumePoint::NoVui => !self.has_vui_parameters(),
+            ResumePoint::AfterTimingInfo => self.has_vui_parameters() && !self.has_vui_tail(),
+            ResumePoint::AfterVui => self.has_vui_parameters(),
+        };
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the trailing bits no longer follow the fields they were parsed after",
+            ));
+        }
+
+        Ok(Some(trailing_bits.resume_point))
+    }
+
     /// Parses the Sps struct from a reader that may contain emulation prevention bytes.
     /// Is the same as calling [`Self::parse`] with an [`EmulationPreventionIo`] wrapper.
     pub fn parse_with_emulation_prevention(reader: impl io::Read) -> io::Result<Self> {
         Self::parse(EmulationPreventionIo::new(reader))
     }
 
+    /// Parses the Sps struct losslessly from a reader that may contain emulation prevention
+    /// bytes. Is the same as calling [`Self::parse_lossless`] with an [`EmulationPreventionIo`]
+    /// wrapper.
+    pub fn parse_lossless_with_emulation_prevention(reader: impl io::Read) -> io::Result<Self> {
+        Self::parse_lossless(EmulationPreventionIo::new(reader))
+    }
+
     /// Builds the Sps struct into a byte stream that may contain emulation prevention bytes.
     /// Is the same as calling [`Self::build`] with an [`EmulationPreventionIo`] wrapper.
     pub fn build_with_emulation_prevention(self, writer: impl io::Write) -> io::Result<()> {
@@ -668,6 +871,11 @@ impl Sps {
 
     /// Returns the total byte size of the Sps struct.
     pub fn size(&self) -> u64 {
+        let vui_cut_off = self
+            .trailing_bits
+            .as_ref()
+            .is_some_and(|bits| bits.resume_point == ResumePoint::AfterTimingInfo);
+
         (1 + // forbidden zero bit
         2 + // nal_ref_idc
         5 + // nal_unit_type
@@ -679,8 +887,8 @@ impl Sps {
         size_of_exp_golomb(self.log2_max_frame_num_minus4 as u64) +
         size_of_exp_golomb(self.pic_order_cnt_type as u64) +
         match self.pic_order_cnt_type {
-            0 => size_of_exp_golomb(self.log2_max_pic_order_cnt_lsb_minus4.unwrap() as u64),
-            1 => self.pic_order_cnt_type1.as_ref().unwrap().bitsize(),
+            0 => self.log2_max_pic_order_cnt_lsb_minus4.map_or(0, |n| size_of_exp_golomb(n as u64)),
+            1 => self.pic_order_cnt_type1.as_ref().map_or(0, |poc| poc.bitsize()),
             _ => 0
         } +
         size_of_exp_golomb(self.max_num_ref_frames as u64) +
@@ -693,24 +901,64 @@ impl Sps {
         1 + // frame_cropping_flag
         self.frame_crop_info.as_ref().map_or(0, |frame| frame.bitsize()) +
         1 + // vui_parameters_present_flag
-        if matches!(
-            (&self.sample_aspect_ratio, &self.overscan_appropriate_flag, &self.color_config, &self.chroma_sample_loc, &self.timing_info),
-            (None, None, None, None, None)
-        ) {
-            0
-        } else {
+        if self.has_vui_parameters() {
             self.sample_aspect_ratio.as_ref().map_or(1, |sar| 1 + sar.bitsize()) +
             self.overscan_appropriate_flag.map_or(1, |_| 2) +
             self.color_config.as_ref().map_or(1, |color| 1 + color.bitsize()) +
             self.chroma_sample_loc.as_ref().map_or(1, |chroma| 1 + chroma.bitsize()) +
             self.timing_info.as_ref().map_or(1, |timing| 1 + timing.bitsize())
-        }).div_ceil(8)
+        } else {
+            0
+        } +
+        if self.has_vui_parameters() && !vui_cut_off {
+            self.nal_hrd_parameters.as_ref().map_or(1, |hrd| 1 + hrd.bitsize()) +
+            self.vcl_hrd_parameters.as_ref().map_or(1, |hrd| 1 + hrd.bitsize()) +
+            (self.nal_hrd_parameters.is_some() || self.vcl_hrd_parameters.is_some()) as u64 +
+            1 + // pic_struct_present_flag
+            self.bitstream_restriction.as_ref().map_or(1, |restriction| 1 + restriction.bitsize())
+        } else {
+            0
+        } +
+        // rbsp_stop_one_bit, or the trailing bits replacing it
+        self.trailing_bits.as_ref().map_or(1, |bits| bits.len()))
+        .div_ceil(8)
+    }
+
+    /// Returns the byte size of the Sps struct once emulation prevention bytes are inserted, as
+    /// written by [`Self::build_with_emulation_prevention`].
+    ///
+    /// This is the size the SPS takes in an AVCDecoderConfigurationRecord or an Annex B
+    /// stream, which can be larger than [`Self::size`]. Fails like [`Self::build`].
+    pub fn size_with_emulation_prevention(&self) -> io::Result<u64> {
+        let mut rbsp = Vec::with_capacity(self.size() as usize);
+        self.build(&mut rbsp)?;
+        Ok(emulation_prevention_size(&rbsp) as u64)
+    }
+
+    /// Whether any VUI field is set, in which case `vui_parameters_present_flag` is written.
+    fn has_vui_parameters(&self) -> bool {
+        self.sample_aspect_ratio.is_some()
+            || self.overscan_appropriate_flag.is_some()
+            || self.color_config.is_some()
+            || self.chroma_sample_loc.is_some()
+            || self.timing_info.is_some()
+            || self.has_vui_tail()
+    }
+
+    /// Whether any VUI field after `timing_info` is set.
+    fn has_vui_tail(&self) -> bool {
+        self.nal_hrd_parameters.is_some()
+            || self.vcl_hrd_parameters.is_some()
+            || self.pic_struct_present_flag
+            || self.bitstream_restriction.is_some()
     }
 
     /// The height as a u64. This is computed from other fields, and isn't directly set.
     ///
     /// `height = ((2 - frame_mbs_only_flag as u64) * (pic_height_in_map_units_minus1 + 1) * 16) -
-    /// frame_crop_bottom_offset * 2 - frame_crop_top_offset * 2`
+    /// (frame_crop_bottom_offset + frame_crop_top_offset) * CropUnitY`
+    ///
+    /// `CropUnitY` is 2 for progressive 4:2:0 video. ISO/IEC-14496-10-2022 - 7.4.2.1.1
     ///
     /// We don't directly store `frame_mbs_only_flag` since we can tell if it's set:
     /// If `mb_adaptive_frame_field_flag` is None, then `frame_mbs_only_flag` is set (1).
@@ -721,21 +969,108 @@ impl Sps {
             * 16;
 
         self.frame_crop_info.as_ref().map_or(base_height, |crop| {
-            base_height - (crop.frame_crop_top_offset + crop.frame_crop_bottom_offset) * 2
+            base_height.saturating_sub(
+                (crop.frame_crop_top_offset + crop.frame_crop_bottom_offset) * self.crop_unit_y(),
+            )
         })
     }
 
     /// The width as a u64. This is computed from other fields, and isn't directly set.
     ///
-    /// `width = ((pic_width_in_mbs_minus1 + 1) * 16) - frame_crop_right_offset * 2 - frame_crop_left_offset * 2`
+    /// `width = ((pic_width_in_mbs_minus1 + 1) * 16) - (frame_crop_right_offset + frame_crop_left_offset) * CropUnitX`
+    ///
+    /// `CropUnitX` is 2 for 4:2:0 video. ISO/IEC-14496-10-2022 - 7.4.2.1.1
     pub fn width(&self) -> u64 {
         let base_width = (self.pic_width_in_mbs_minus1 + 1) * 16;
 
         self.frame_crop_info.as_ref().map_or(base_width, |crop| {
-            base_width - (crop.frame_crop_left_offset + crop.frame_crop_right_offset) * 2
+            base_width.saturating_sub(
+                (crop.frame_crop_left_offset + crop.frame_crop_right_offset) * self.crop_unit_x(),
+            )
         })
     }
 
+    /// Sets the coded size and cropping so that [`Self::width`] and [`Self::height`] return
+    /// `width` and `height`.
+    ///
+    /// The picture is padded to whole macroblocks (or macroblock pairs for interlaced video)
+    /// and cropped from the right and bottom, any existing left and top crop is dropped.
+    /// Returns an error if the dimensions are not a multiple of the crop unit of the
+    /// chroma format.
+    pub fn set_dimensions(&mut self, width: u64, height: u64) -> io::Result<()> {
+        let crop_unit_x = self.crop_unit_x();
+        let crop_unit_y = self.crop_unit_y();
+        if width == 0
+            || height == 0
+            || !width.is_multiple_of(crop_unit_x)
+            || !height.is_multiple_of(crop_unit_y)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{width}x{height} is not a non-zero multiple of the {crop_unit_x}x{crop_unit_y} crop unit"
+                ),
+            ));
+        }
+
+        let map_unit_height = (2 - self.mb_adaptive_frame_field_flag.is_none() as u64) * 16;
+        let pic_width_in_mbs = width.div_ceil(16);
+        let pic_height_in_map_units = height.div_ceil(map_unit_height);
+
+        let frame_crop_right_offset = (pic_width_in_mbs * 16 - width) / crop_unit_x;
+        let frame_crop_bottom_offset =
+            (pic_height_in_map_units * map_unit_height - height) / crop_unit_y;
+
+        self.pic_width_in_mbs_minus1 = pic_width_in_mbs - 1;
+        self.pic_height_in_map_units_minus1 = pic_height_in_map_units - 1;
+        self.frame_crop_info = (frame_crop_right_offset != 0 || frame_crop_bottom_offset != 0)
+            .then_some(FrameCropInfo {
+                frame_crop_left_offset: 0,
+                frame_crop_right_offset,
+                frame_crop_top_offset: 0,
+                frame_crop_bottom_offset,
+            });
+
+        Ok(())
+    }
+
+    /// Sets the `num_units_in_tick` and `time_scale` of the timing info, so that
+    /// [`Self::frame_rate`] returns `time_scale / (2 * num_units_in_tick)`.
+    ///
+    /// If the SPS has no timing info yet it is added, which also enables the VUI parameters
+    /// when they were absent. An existing `fixed_frame_rate_flag` is kept.
+    pub fn set_frame_rate(&mut self, num_units_in_tick: NonZeroU32, time_scale: NonZeroU32) {
+        let fixed_frame_rate_flag = self
+            .timing_info
+            .as_ref()
+            .is_some_and(|timing| timing.fixed_frame_rate_flag);
+
+        self.timing_info = Some(TimingInfo {
+            num_units_in_tick,
+            time_scale,
+            fixed_frame_rate_flag,
+        });
+    }
+
+    /// `SubWidthC`, or 1 when `ChromaArrayType == 0`. ISO/IEC-14496-10-2022 - 7.4.2.1.1
+    fn crop_unit_x(&self) -> u64 {
+        match self.ext.as_ref().map_or(1, |ext| ext.chroma_format_idc) {
+            1 | 2 => 2,
+            _ => 1,
+        }
+    }
+
+    /// `SubHeightC * (2 - frame_mbs_only_flag)`, where `SubHeightC` is 1 when
+    /// `ChromaArrayType == 0`. ISO/IEC-14496-10-2022 - 7.4.2.1.1
+    fn crop_unit_y(&self) -> u64 {
+        let sub_height_c = match self.ext.as_ref().map_or(1, |ext| ext.chroma_format_idc) {
+            1 => 2,
+            _ => 1,
+        };
+
+        sub_height_c * (2 - self.mb_adaptive_frame_field_flag.is_none() as u64)
+    }
+
     /// Returns the frame rate as a f64.
     ///
     /// If `timing_info_present_flag` is set, then the `frame_rate` will be computed, and
@@ -744,17 +1079,67 @@ impl Sps {
     pub fn frame_rate(&self) -> Option<f64> {
         self.timing_info.as_ref().map(|timing| timing.frame_rate())
     }
+
+    /// Returns the `max_num_reorder_frames` signalled in the bitstream restriction, if present.
+    ///
+    /// This is the number of frames a decoder has to buffer before it can output the first
+    /// one, which is what determines the decoder delay of the stream.
+    pub fn max_num_reorder_frames(&self) -> Option<u8> {
+        self.bitstream_restriction
+            .as_ref()
+            .map(|restriction| restriction.max_num_reorder_frames)
+    }
+}
+
+/// The VUI fields that follow `timing_info`. ISO/IEC-14496-10-2022 - E.1.1
+#[derive(Default)]
+struct VuiTail {
+    nal_hrd_parameters: Option<HrdParameters>,
+    vcl_hrd_parameters: Option<HrdParameters>,
+    low_delay_hrd_flag: Option<bool>,
+    pic_struct_present_flag: bool,
+    bitstream_restriction: Option<BitstreamRestriction>,
+}
+
+impl VuiTail {
+    fn parse<T: io::Read>(bit_reader: &mut BitReader<T>) -> io::Result<Self> {
+        let mut tail = VuiTail::default();
+
+        let nal_hrd_parameters_present_flag = bit_reader.read_bit()?;
+        if nal_hrd_parameters_present_flag {
+            tail.nal_hrd_parameters = Some(HrdParameters::parse(bit_reader)?)
+        }
+
+        let vcl_hrd_parameters_present_flag = bit_reader.read_bit()?;
+        if vcl_hrd_parameters_present_flag {
+            tail.vcl_hrd_parameters = Some(HrdParameters::parse(bit_reader)?)
+        }
+
+        if nal_hrd_parameters_present_flag || vcl_hrd_parameters_present_flag {
+            tail.low_delay_hrd_flag = Some(bit_reader.read_bit()?);
+        }
+
+        tail.pic_struct_present_flag = bit_reader.read_bit()?;
+
+        let bitstream_restriction_flag = bit_reader.read_bit()?;
+        if bitstream_restriction_flag {
+            tail.bitstream_restriction = Some(BitstreamRestriction::parse(bit_reader)?)
+        }
+
+        Ok(tail)
+    }
 }
 
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
     use std::io;
+    use std::num::NonZeroU32;
 
     use bytes_util::BitWriter;
     use expgolomb::{BitWriterExpGolombExt, size_of_exp_golomb, size_of_signed_exp_golomb};
 
-    use crate::sps::Sps;
+    use crate::sps::{FrameCropInfo, Sps};
 
     #[test]
     fn test_parse_sps_set_forbidden_bit() {
@@ -893,6 +1278,18 @@ mod tests {
         // 28800 = time_scale
         // time_scale is a u32
         writer.write_bits(28800, 32).unwrap();
+        // fixed_frame_rate_flag
+        writer.write_bit(false).unwrap();
+        // nal_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // vcl_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // pic_struct_present_flag
+        writer.write_bit(false).unwrap();
+        // bitstream_restriction_flag
+        writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(sps)).unwrap();
@@ -949,8 +1346,15 @@ mod tests {
                 TimingInfo {
                     num_units_in_tick: 100,
                     time_scale: 28800,
+                    fixed_frame_rate_flag: false,
                 },
             ),
+            nal_hrd_parameters: None,
+            vcl_hrd_parameters: None,
+            low_delay_hrd_flag: None,
+            pic_struct_present_flag: false,
+            bitstream_restriction: None,
+            trailing_bits: None,
         }
         ");
 
@@ -1148,6 +1552,18 @@ mod tests {
         // 960 000 = time_scale
         // time_scale is a u32
         writer.write_bits(960000, 32).unwrap();
+        // fixed_frame_rate_flag
+        writer.write_bit(false).unwrap();
+        // nal_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // vcl_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // pic_struct_present_flag
+        writer.write_bit(false).unwrap();
+        // bitstream_restriction_flag
+        writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -1243,13 +1659,21 @@ mod tests {
                 TimingInfo {
                     num_units_in_tick: 1000,
                     time_scale: 960000,
+                    fixed_frame_rate_flag: false,
                 },
             ),
+            nal_hrd_parameters: None,
+            vcl_hrd_parameters: None,
+            low_delay_hrd_flag: None,
+            pic_struct_present_flag: false,
+            bitstream_restriction: None,
+            trailing_bits: None,
         }
         ");
 
         assert_eq!(Some(480.0), result.frame_rate());
-        assert_eq!(1920, result.width());
+        // 4:4:4 crops horizontally in single luma samples, interlaced vertically in pairs
+        assert_eq!(1928, result.width());
         assert_eq!(1080, result.height());
 
         // create a writer for the builder
@@ -1344,6 +1768,16 @@ mod tests {
 
         // timing_info_present_flag
         writer.write_bit(false).unwrap();
+        // nal_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // vcl_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // pic_struct_present_flag
+        writer.write_bit(false).unwrap();
+        // bitstream_restriction_flag
+        writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -1393,6 +1827,12 @@ mod tests {
                 },
             ),
             timing_info: None,
+            nal_hrd_parameters: None,
+            vcl_hrd_parameters: None,
+            low_delay_hrd_flag: None,
+            pic_struct_present_flag: false,
+            bitstream_restriction: None,
+            trailing_bits: None,
         }
         ");
 
@@ -1434,8 +1874,6 @@ mod tests {
         writer.write_exp_golomb(0).unwrap();
         // pic_order_cnt_type is expg
         writer.write_exp_golomb(2).unwrap();
-        // log2_max_pic_order_cnt_lsb_minus4
-        writer.write_exp_golomb(0).unwrap();
 
         // max_num_ref_frames is expg
         writer.write_exp_golomb(0).unwrap();
@@ -1455,6 +1893,8 @@ mod tests {
         // enter vui to set redundant parameters so they get reduced
         // vui_parameters_present_flag
         writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -1478,17 +1918,23 @@ mod tests {
             log2_max_pic_order_cnt_lsb_minus4: None,
             pic_order_cnt_type1: None,
             max_num_ref_frames: 0,
-            gaps_in_frame_num_value_allowed_flag: true,
-            pic_width_in_mbs_minus1: 3,
-            pic_height_in_map_units_minus1: 0,
+            gaps_in_frame_num_value_allowed_flag: false,
+            pic_width_in_mbs_minus1: 1,
+            pic_height_in_map_units_minus1: 2,
             mb_adaptive_frame_field_flag: None,
-            direct_8x8_inference_flag: true,
+            direct_8x8_inference_flag: false,
             frame_crop_info: None,
             sample_aspect_ratio: None,
             overscan_appropriate_flag: None,
             color_config: None,
             chroma_sample_loc: None,
             timing_info: None,
+            nal_hrd_parameters: None,
+            vcl_hrd_parameters: None,
+            low_delay_hrd_flag: None,
+            pic_struct_present_flag: false,
+            bitstream_restriction: None,
+            trailing_bits: None,
         }
         ");
 
@@ -1502,6 +1948,72 @@ mod tests {
         assert_eq!(buf, sps);
     }
 
+    #[test]
+    fn test_parse_build_sps_x264_nal_hrd() {
+        // High@3.1 1280x720 25fps, laid out the way x264 writes it with
+        // `--nal-hrd vbr --vbv-maxrate 3000 --vbv-bufsize 6000`
+        let sps = [
+            0x67, 0x64, 0x00, 0x1f, 0xac, 0xd1, 0x40, 0x50, 0x05, 0xba, 0x10, 0x00, 0x00, 0x03,
+            0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0x2e, 0x06, 0x00, 0x02, 0xdc, 0x6c, 0x00, 0x05,
+            0xb8, 0xda, 0x6c, 0x30, 0x07, 0x8c, 0x18, 0xcb,
+        ];
+
+        let result = Sps::parse_with_emulation_prevention(std::io::Cursor::new(&sps)).unwrap();
+
+        assert_eq!(1280, result.width());
+        assert_eq!(720, result.height());
+        assert_eq!(Some(25.0), result.frame_rate());
+        assert!(result.timing_info.as_ref().unwrap().fixed_frame_rate_flag);
+        assert_eq!(result.max_num_reorder_frames(), Some(2));
+
+        let hrd = result.nal_hrd_parameters.as_ref().unwrap();
+        assert_eq!(hrd.bit_rate_scale, 0);
+        assert_eq!(hrd.cpb_size_scale, 3);
+        assert_eq!(hrd.cpb_specs.len(), 1);
+        // 3000 kbit/s and a 6000 kbit buffer
+        assert_eq!(
+            (hrd.cpb_specs[0].bit_rate_value_minus1 as u64 + 1) << 6,
+            3_000_000
+        );
+        assert_eq!(
+            (hrd.cpb_specs[0].cpb_size_value_minus1 as u64 + 1) << 7,
+            6_000_000
+        );
+        assert!(!hrd.cpb_specs[0].cbr_flag);
+        assert_eq!(hrd.cpb_removal_delay_length_minus1, 12);
+        assert_eq!(hrd.dpb_output_delay_length_minus1, 6);
+        assert!(result.vcl_hrd_parameters.is_none());
+        assert_eq!(result.low_delay_hrd_flag, Some(false));
+        assert!(!result.pic_struct_present_flag);
+
+        let restriction = result.bitstream_restriction.as_ref().unwrap();
+        assert!(restriction.motion_vectors_over_pic_boundaries_flag);
+        assert_eq!(restriction.log2_max_mv_length_horizontal, 11);
+        assert_eq!(restriction.max_dec_frame_buffering, 4);
+
+        // 36 bytes on the wire, 2 of which are emulation prevention bytes
+        assert_eq!(result.size(), 34);
+
+        let mut buf = Vec::new();
+        result.build_with_emulation_prevention(&mut buf).unwrap();
+        assert_eq!(buf, sps);
+    }
+
+    #[test]
+    fn test_parse_sps_truncated_vui() {
+        // The VUI ends right after timing_info, without the HRD and restriction flags
+        let sps = [
+            0x67, 0x64, 0x00, 0x1F, 0xAC, 0xD9, 0x41, 0xE0, 0x6D, 0xF9, 0xE6, 0xA0, 0x20, 0x20,
+            0x28, 0x00, 0x00, 0x03, 0x00, 0x08, 0x00, 0x00, 0x03, 0x01, 0xE0,
+        ];
+
+        let result = Sps::parse_with_emulation_prevention(std::io::Cursor::new(&sps)).unwrap();
+        assert!(result.timing_info.is_some());
+        assert!(result.nal_hrd_parameters.is_none());
+        assert!(result.bitstream_restriction.is_none());
+        assert_eq!(result.max_num_reorder_frames(), None);
+    }
+
     #[test]
     fn test_parse_sps_chroma_loc_info_error() {
         let mut sps = Vec::new();
@@ -1906,6 +2418,8 @@ mod tests {
 
         // vui_parameters_present_flag
         writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -1959,6 +2473,12 @@ mod tests {
             color_config: None,
             chroma_sample_loc: None,
             timing_info: None,
+            nal_hrd_parameters: None,
+            vcl_hrd_parameters: None,
+            low_delay_hrd_flag: None,
+            pic_struct_present_flag: false,
+            bitstream_restriction: None,
+            trailing_bits: None,
         }
         ");
 
@@ -2186,6 +2706,24 @@ mod tests {
         // time_scale is a u32
         writer.write_bits(960000, 32).unwrap();
         bit_count += 32;
+        // fixed_frame_rate_flag
+        writer.write_bit(false).unwrap();
+        bit_count += 1;
+        // nal_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        bit_count += 1;
+        // vcl_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        bit_count += 1;
+        // pic_struct_present_flag
+        writer.write_bit(false).unwrap();
+        bit_count += 1;
+        // bitstream_restriction_flag
+        writer.write_bit(false).unwrap();
+        bit_count += 1;
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
+        bit_count += 1;
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -2283,6 +2821,16 @@ mod tests {
 
         // timing_info_present_flag
         writer.write_bit(false).unwrap();
+        // nal_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // vcl_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // pic_struct_present_flag
+        writer.write_bit(false).unwrap();
+        // bitstream_restriction_flag
+        writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let reduced_sps = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -2370,6 +2918,16 @@ mod tests {
 
         // timing_info_present_flag
         writer.write_bit(false).unwrap();
+        // nal_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // vcl_hrd_parameters_present_flag
+        writer.write_bit(false).unwrap();
+        // pic_struct_present_flag
+        writer.write_bit(false).unwrap();
+        // bitstream_restriction_flag
+        writer.write_bit(false).unwrap();
+        // rbsp_stop_one_bit
+        writer.write_bit(true).unwrap();
         writer.finish().unwrap();
 
         let result = Sps::parse(std::io::Cursor::new(&sps)).unwrap();
@@ -2421,7 +2979,327 @@ mod tests {
             color_config: None,
             chroma_sample_loc: None,
             timing_info: None,
+            nal_hrd_parameters: None,
+            vcl_hrd_parameters: None,
+            low_delay_hrd_flag: None,
+            pic_struct_present_flag: false,
+            bitstream_restriction: None,
+            trailing_bits: None,
         }
         ");
     }
+
+    const X264_NAL_HRD_SPS: [u8; 36] = [
+        0x67, 0x64, 0x00, 0x1f, 0xac, 0xd1, 0x40, 0x50, 0x05, 0xba, 0x10, 0x00, 0x00, 0x03, 0x00,
+        0x10, 0x00, 0x00, 0x03, 0x03, 0x2e, 0x06, 0x00, 0x02, 0xdc, 0x6c, 0x00, 0x05, 0xb8, 0xda,
+        0x6c, 0x30, 0x07, 0x8c, 0x18, 0xcb,
+    ];
+
+    fn rebuild(sps: Sps) -> Sps {
+        let mut buf = Vec::new();
+        sps.build_with_emulation_prevention(&mut buf).unwrap();
+        Sps::parse_with_emulation_prevention(std::io::Cursor::new(buf)).unwrap()
+    }
+
+    #[test]
+    fn test_set_dimensions() {
+        let mut sps =
+            Sps::parse_with_emulation_prevention(std::io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+
+        for (width, height) in [(1920, 1080), (854, 480), (1280, 720), (2, 2)] {
+            sps.set_dimensions(width, height).unwrap();
+            let rebuilt = rebuild(sps.clone());
+
+            assert_eq!(rebuilt, sps);
+            assert_eq!(rebuilt.width(), width);
+            assert_eq!(rebuilt.height(), height);
+        }
+
+        sps.set_dimensions(1920, 1080).unwrap();
+        assert_eq!(sps.pic_width_in_mbs_minus1, 119);
+        assert_eq!(sps.pic_height_in_map_units_minus1, 67);
+        assert_eq!(
+            sps.frame_crop_info,
+            Some(FrameCropInfo {
+                frame_crop_left_offset: 0,
+                frame_crop_right_offset: 0,
+                frame_crop_top_offset: 0,
+                frame_crop_bottom_offset: 4,
+            })
+        );
+
+        sps.set_dimensions(1280, 720).unwrap();
+        assert_eq!(sps.frame_crop_info, None);
+    }
+
+    #[test]
+    fn test_set_dimensions_crop_units() {
+        let mut sps =
+            Sps::parse_with_emulation_prevention(std::io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+
+        // 4:2:0 can only be cropped in pairs of luma samples
+        let err = sps.set_dimensions(1279, 720).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(sps.set_dimensions(1280, 0).is_err());
+
+        // 4:4:4 frames can be cropped by single luma samples
+        if let Some(ext) = sps.ext.as_mut() {
+            ext.chroma_format_idc = 3;
+        }
+        sps.set_dimensions(1279, 719).unwrap();
+        let rebuilt = rebuild(sps);
+        assert_eq!(rebuilt.width(), 1279);
+        assert_eq!(rebuilt.height(), 719);
+
+        // interlaced 4:2:0 crops the height in units of 4 and pads to macroblock pairs
+        let mut sps =
+            Sps::parse_with_emulation_prevention(std::io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+        sps.mb_adaptive_frame_field_flag = Some(false);
+        assert!(sps.set_dimensions(1920, 1082).is_err());
+        sps.set_dimensions(1920, 1080).unwrap();
+        assert_eq!(sps.pic_height_in_map_units_minus1, 33);
+        let rebuilt = rebuild(sps);
+        assert_eq!(rebuilt.height(), 1080);
+    }
+
+    #[test]
+    fn test_set_frame_rate() {
+        let mut sps =
+            Sps::parse_with_emulation_prevention(std::io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+
+        // updating keeps the fixed_frame_rate_flag
+        sps.set_frame_rate(
+            NonZeroU32::new(1001).unwrap(),
+            NonZeroU32::new(60000).unwrap(),
+        );
+        let rebuilt = rebuild(sps.clone());
+        let timing = rebuilt.timing_info.as_ref().unwrap();
+        assert_eq!(timing.num_units_in_tick.get(), 1001);
+        assert_eq!(timing.time_scale.get(), 60000);
+        assert!(timing.fixed_frame_rate_flag);
+        assert_eq!(rebuilt.frame_rate(), sps.frame_rate());
+
+        // without any VUI parameters, setting the frame rate adds them
+        sps.sample_aspect_ratio = None;
+        sps.overscan_appropriate_flag = None;
+        sps.color_config = None;
+        sps.chroma_sample_loc = None;
+        sps.timing_info = None;
+        sps.nal_hrd_parameters = None;
+        sps.vcl_hrd_parameters = None;
+        sps.low_delay_hrd_flag = None;
+        sps.pic_struct_present_flag = false;
+        sps.bitstream_restriction = None;
+        let no_vui = rebuild(sps.clone());
+        assert_eq!(no_vui.frame_rate(), None);
+
+        sps.set_frame_rate(NonZeroU32::new(1).unwrap(), NonZeroU32::new(60).unwrap());
+        let rebuilt = rebuild(sps.clone());
+        assert_eq!(rebuilt, sps);
+        assert_eq!(rebuilt.frame_rate(), Some(30.0));
+        assert!(!rebuilt.timing_info.as_ref().unwrap().fixed_frame_rate_flag);
+        assert!(rebuilt.size() > no_vui.size());
+    }
+
+    #[test]
+    fn test_parse_lossless_round_trip() {
+        // the second one ends right after timing_info
+        let inputs: [&[u8]; 2] = [
+            &X264_NAL_HRD_SPS,
+            &[
+                0x67, 0x64, 0x00, 0x1F, 0xAC, 0xD9, 0x41, 0xE0, 0x6D, 0xF9, 0xE6, 0xA0, 0x20, 0x20,
+                0x28, 0x00, 0x00, 0x03, 0x00, 0x08, 0x00, 0x00, 0x03, 0x01, 0xE0,
+            ],
+        ];
+
+        for input in inputs {
+            let sps =
+                Sps::parse_lossless_with_emulation_prevention(std::io::Cursor::new(input)).unwrap();
+            assert!(sps.trailing_bits.is_some());
+            // two emulation prevention bytes in each
+            assert_eq!(sps.size(), input.len() as u64 - 2);
+
+            let mut buf = Vec::new();
+            sps.build_with_emulation_prevention(&mut buf).unwrap();
+            assert_eq!(buf, input);
+        }
+    }
+
+    #[test]
+    fn test_parse_lossless_patch_frame_rate() {
+        let mut sps =
+            Sps::parse_lossless_with_emulation_prevention(std::io::Cursor::new(&X264_NAL_HRD_SPS))
+                .unwrap();
+        sps.set_frame_rate(
+            NonZeroU32::new(1001).unwrap(),
+            NonZeroU32::new(60000).unwrap(),
+        );
+
+        let mut buf = Vec::new();
+        sps.build(&mut buf).unwrap();
+        assert_eq!(buf.len() as u64, sps.size());
+
+        let rebuilt = Sps::parse_lossless(std::io::Cursor::new(&buf)).unwrap();
+        assert_eq!(
+            rebuilt.timing_info.as_ref().unwrap().time_scale.get(),
+            60000
+        );
+        assert_eq!(rebuilt.nal_hrd_parameters, sps.nal_hrd_parameters);
+        assert_eq!(rebuilt.bitstream_restriction, sps.bitstream_restriction);
+        assert_eq!(rebuilt.trailing_bits, sps.trailing_bits);
+    }
+
+    #[test]
+    fn test_parse_lossless_vui_removed() {
+        let mut sps =
+            Sps::parse_lossless_with_emulation_prevention(std::io::Cursor::new(&X264_NAL_HRD_SPS))
+                .unwrap();
+        sps.sample_aspect_ratio = None;
+        sps.overscan_appropriate_flag = None;
+        sps.color_config = None;
+        sps.chroma_sample_loc = None;
+        sps.timing_info = None;
+        sps.nal_hrd_parameters = None;
+        sps.vcl_hrd_parameters = None;
+        sps.low_delay_hrd_flag = None;
+        sps.pic_struct_present_flag = false;
+        sps.bitstream_restriction = None;
+
+        let err = sps.build(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        // dropping the trailing bits builds a regular SPS
+        sps.trailing_bits = None;
+        let rebuilt = rebuild(sps.clone());
+        assert_eq!(rebuilt, sps);
+    }
+
+    #[test]
+    fn test_parse_sps_corrupted_input_terminates() {
+        // A never ending stream of zeros fails on the first Exp-Golomb code.
+        let err = Sps::parse(io::repeat(0)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Everything after the NAL header is zero.
+        let err = Sps::parse(io::Read::chain(&[0x67u8, 0x64, 0, 0x1f][..], io::repeat(0)));
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for len in 0..512 {
+            let data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+            // Random input must fail (or succeed) without hanging or panicking.
+            let _ = Sps::parse_with_emulation_prevention(io::Cursor::new(&data));
+        }
+
+        // Flip bits of a valid SPS to reach deeper into the parser.
+        let valid = X264_NAL_HRD_SPS;
+        for _ in 0..2000 {
+            let mut data = valid.to_vec();
+            for _ in 0..1 + next() % 4 {
+                let bit = next() as usize % (data.len() * 8);
+                data[bit / 8] ^= 0x80 >> (bit % 8);
+            }
+            data.truncate(data.len() - next() as usize % 8);
+            let _ = Sps::parse_with_emulation_prevention(io::Cursor::new(&data));
+        }
+    }
+
+    #[test]
+    fn test_crop_larger_than_picture() {
+        let mut sps =
+            Sps::parse_with_emulation_prevention(io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+        // A corrupted SPS can crop more than the coded picture
+        sps.frame_crop_info = Some(FrameCropInfo {
+            frame_crop_left_offset: 1000,
+            frame_crop_right_offset: 1000,
+            frame_crop_top_offset: 1000,
+            frame_crop_bottom_offset: 1000,
+        });
+        let sps = rebuild(sps);
+
+        assert_eq!(sps.width(), 0);
+        assert_eq!(sps.height(), 0);
+    }
+
+    #[test]
+    fn test_build_missing_pic_order_cnt() {
+        let mut sps =
+            Sps::parse_with_emulation_prevention(io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+        assert_eq!(sps.pic_order_cnt_type, 0);
+        sps.log2_max_pic_order_cnt_lsb_minus4 = None;
+
+        let err = sps.build(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(sps.size_with_emulation_prevention().is_err());
+
+        sps.pic_order_cnt_type = 1;
+        let err = sps.build(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(sps.size() > 0);
+    }
+
+    #[test]
+    fn test_size_with_emulation_prevention_randomized() {
+        use crate::sps::timing_info::TimingInfo;
+
+        let base =
+            Sps::parse_with_emulation_prevention(io::Cursor::new(&X264_NAL_HRD_SPS)).unwrap();
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let mut escaped = 0;
+        for _ in 0..1000 {
+            let mut sps = base.clone();
+            // Zero constraint flags and low levels put a 0x00 0x00 0x0n run in the header
+            sps.constraint_set0_flag = next() % 4 == 0;
+            sps.constraint_set1_flag = next() % 4 == 0;
+            sps.level_idc = if next() % 2 == 0 {
+                (next() % 4) as u8
+            } else {
+                31
+            };
+            sps.seq_parameter_set_id = (next() % 32) as u16;
+            sps.log2_max_frame_num_minus4 = (next() % 13) as u8;
+            sps.max_num_ref_frames = (next() % 16) as u8;
+            sps.pic_width_in_mbs_minus1 = next() % 256;
+            sps.pic_height_in_map_units_minus1 = next() % 256;
+            sps.frame_crop_info = (next() % 2 == 0).then(|| FrameCropInfo {
+                frame_crop_left_offset: next() % 8,
+                frame_crop_right_offset: next() % 8,
+                frame_crop_top_offset: next() % 8,
+                frame_crop_bottom_offset: next() % 8,
+            });
+            // Small tick counts are written as runs of zero bytes
+            sps.timing_info = (next() % 4 != 0).then(|| TimingInfo {
+                num_units_in_tick: NonZeroU32::new((next() % 0x400) as u32 + 1).unwrap(),
+                time_scale: NonZeroU32::new((next() % 0x20000) as u32 + 1).unwrap(),
+                fixed_frame_rate_flag: next() % 2 == 0,
+            });
+
+            let predicted = sps.size_with_emulation_prevention().unwrap();
+            if predicted > sps.size() {
+                escaped += 1;
+            }
+
+            let mut buf = Vec::new();
+            sps.clone()
+                .build_with_emulation_prevention(&mut buf)
+                .unwrap();
+            assert_eq!(predicted, buf.len() as u64, "{sps:?}");
+        }
+        assert!(escaped > 100, "only {escaped} SPS needed escaping");
+    }
 }
@@ -2,7 +2,8 @@ use std::io;
 
 use bytes_util::{BitReader, BitWriter};
 use expgolomb::{
-    BitReaderExpGolombExt, BitWriterExpGolombExt, size_of_exp_golomb, size_of_signed_exp_golomb,
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+    size_of_signed_exp_golomb,
 };
 
 /// `PicOrderCountType1` contains the fields that are set when `pic_order_cnt_type == 1`.
@@ -85,13 +86,17 @@ impl PicOrderCountType1 {
     /// Returns a `PicOrderCountType1` struct.
     pub fn parse<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<Self> {
         let delta_pic_order_always_zero_flag = reader.read_bit()?;
-        let offset_for_non_ref_pic = reader.read_signed_exp_golomb()?;
-        let offset_for_top_to_bottom_field = reader.read_signed_exp_golomb()?;
-        let num_ref_frames_in_pic_order_cnt_cycle = reader.read_exp_golomb()?;
+        let offset_for_non_ref_pic =
+            reader.read_signed_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let offset_for_top_to_bottom_field =
+            reader.read_signed_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let num_ref_frames_in_pic_order_cnt_cycle =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
         let mut offset_for_ref_frame = vec![];
         for _ in 0..num_ref_frames_in_pic_order_cnt_cycle {
-            offset_for_ref_frame.push(reader.read_signed_exp_golomb()?);
+            offset_for_ref_frame
+                .push(reader.read_signed_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?);
         }
 
         Ok(PicOrderCountType1 {
@@ -49,13 +49,19 @@ impl SarDimensions {
         let mut sar_height = 0; // deafults to 0, E.2.1
 
         let aspect_ratio_idc = reader.read_u8()?;
-        if aspect_ratio_idc == 255 {
+        let aspect_ratio_idc = AspectRatioIdc::try_from(aspect_ratio_idc).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid aspect_ratio_idc {aspect_ratio_idc}: {err}"),
+            )
+        })?;
+        if aspect_ratio_idc == AspectRatioIdc::ExtendedSar {
             sar_width = reader.read_bits(16)? as u16;
             sar_height = reader.read_bits(16)? as u16;
         }
 
         Ok(SarDimensions {
-            aspect_ratio_idc: AspectRatioIdc::try_from(aspect_ratio_idc).unwrap(),
+            aspect_ratio_idc,
             sar_width,
             sar_height,
         })
@@ -2,7 +2,8 @@ use std::io;
 
 use bytes_util::{BitReader, BitWriter};
 use expgolomb::{
-    BitReaderExpGolombExt, BitWriterExpGolombExt, size_of_exp_golomb, size_of_signed_exp_golomb,
+    BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS, size_of_exp_golomb,
+    size_of_signed_exp_golomb,
 };
 
 /// The Sequence Parameter Set extension.
@@ -101,15 +102,17 @@ impl SpsExtended {
     /// Parses an extended SPS from a bitstream.
     /// Returns an `SpsExtended` struct.
     pub fn parse<T: io::Read>(reader: &mut BitReader<T>) -> io::Result<Self> {
-        let chroma_format_idc = reader.read_exp_golomb()? as u8;
+        let chroma_format_idc = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
         // Defaults to false: ISO/IEC-14496-10-2022 - 7.4.2.1.1
         let mut separate_color_plane_flag = false;
         if chroma_format_idc == 3 {
             separate_color_plane_flag = reader.read_bit()?;
         }
 
-        let bit_depth_luma_minus8 = reader.read_exp_golomb()? as u8;
-        let bit_depth_chroma_minus8 = reader.read_exp_golomb()? as u8;
+        let bit_depth_luma_minus8 =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
+        let bit_depth_chroma_minus8 =
+            reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as u8;
         let qpprime_y_zero_transform_bypass_flag = reader.read_bit()?;
         let seq_scaling_matrix_present_flag = reader.read_bit()?;
         let mut scaling_matrix: Vec<Vec<i64>> = vec![];
@@ -125,7 +128,8 @@ impl SpsExtended {
                     let size = if i < 6 { 16 } else { 64 };
                     let mut next_scale = 8;
                     for _ in 0..size {
-                        let delta_scale = reader.read_signed_exp_golomb()?;
+                        let delta_scale =
+                            reader.read_signed_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                         scaling_matrix[i].push(delta_scale);
                         next_scale = (next_scale + delta_scale + 256) % 256;
                         if next_scale == 0 {
@@ -0,0 +1,116 @@
+use std::io;
+
+use bytes_util::BitWriter;
+
+/// Where in the SPS syntax the [`TrailingBits`] were captured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ResumePoint {
+    /// After a `vui_parameters_present_flag` of 0.
+    NoVui,
+    /// After `timing_info`, when the VUI fields that follow it were cut off.
+    AfterTimingInfo,
+    /// After the complete VUI.
+    AfterVui,
+}
+
+/// `TrailingBits` are the bits of an SPS RBSP that follow the last field [`Sps`](super::Sps)
+/// models, kept verbatim by [`Sps::parse_lossless`](super::Sps::parse_lossless).
+///
+/// They include the `rbsp_trailing_bits` and anything after them, so writing them in place
+/// of newly generated `rbsp_trailing_bits` reproduces the parsed SPS exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrailingBits {
+    /// The bits, most significant first, zero padded to a whole number of bytes.
+    data: Vec<u8>,
+    /// The number of bits in `data`.
+    len: u64,
+    /// Where the bits were captured.
+    pub(crate) resume_point: ResumePoint,
+}
+
+impl TrailingBits {
+    /// Captures the bits of `rbsp` from bit `position` to its end.
+    pub(crate) fn from_rbsp(rbsp: &[u8], position: u64, resume_point: ResumePoint) -> Self {
+        let total = rbsp.len() as u64 * 8;
+        let position = position.min(total);
+        let len = total - position;
+
+        let start = (position / 8) as usize;
+        let shift = (position % 8) as u32;
+        let data = (0..len.div_ceil(8) as usize)
+            .map(|i| {
+                let high = rbsp[start + i] << shift;
+                let low = match rbsp.get(start + i + 1) {
+                    Some(next) if shift != 0 => next >> (8 - shift),
+                    _ => 0,
+                };
+                high | low
+            })
+            .collect();
+
+        TrailingBits {
+            data,
+            len,
+            resume_point,
+        }
+    }
+
+    /// Returns the number of bits.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the SPS ended right after the last modelled field, without even
+    /// the `rbsp_stop_one_bit`.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Writes the bits verbatim.
+    pub fn build<T: io::Write>(&self, writer: &mut BitWriter<T>) -> io::Result<()> {
+        let mut remaining = self.len;
+        for &byte in &self.data {
+            let count = remaining.min(8) as u8;
+            writer.write_bits((byte >> (8 - count)) as u64, count)?;
+            remaining -= count as u64;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(all(test, coverage_nightly), coverage(off))]
+mod tests {
+    use bytes_util::BitWriter;
+
+    use crate::sps::trailing_bits::{ResumePoint, TrailingBits};
+
+    #[test]
+    fn test_trailing_bits_unaligned() {
+        let rbsp = [0b1010_1100, 0b0110_1000, 0b1000_0000];
+        // skip the first 5 bits
+        let bits = TrailingBits::from_rbsp(&rbsp, 5, ResumePoint::AfterVui);
+        assert_eq!(bits.len(), 19);
+        assert!(!bits.is_empty());
+
+        let mut buf = Vec::new();
+        let mut writer = BitWriter::new(&mut buf);
+        writer.write_bits(0b10101, 5).unwrap();
+        bits.build(&mut writer).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(buf, rbsp);
+    }
+
+    #[test]
+    fn test_trailing_bits_past_end() {
+        let bits = TrailingBits::from_rbsp(&[0x80], 8, ResumePoint::NoVui);
+        assert!(bits.is_empty());
+
+        let mut buf = Vec::new();
+        let mut writer = BitWriter::new(&mut buf);
+        bits.build(&mut writer).unwrap();
+        writer.finish().unwrap();
+        assert!(buf.is_empty());
+    }
+}
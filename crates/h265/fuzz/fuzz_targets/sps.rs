@@ -0,0 +1,31 @@
+#![no_main]
+
+use std::io;
+
+use h265::SpsNALUnit;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(nalu) = SpsNALUnit::parse(io::Cursor::new(data)) else {
+        return;
+    };
+
+    let sps = &nalu.rbsp;
+    let _ = (sps.cropped_width(), sps.cropped_height());
+    let _ = (sps.qp_bd_offset_y(), sps.qp_bd_offset_c());
+    let _ = sps.max_pic_order_cnt_lsb();
+    let _ = (sps.pic_width_in_ctbs_y(), sps.pic_height_in_ctbs_y());
+    let _ = (sps.pic_size_in_min_cbs_y(), sps.pic_size_in_ctbs_y());
+    let _ = sps.pic_size_in_samples_y();
+    let _ = (sps.pic_width_in_samples_c(), sps.pic_height_in_samples_c());
+    let _ = (sps.min_tb_log2_size_y(), sps.max_tb_log2_size_y());
+    let _ = sps.raw_ctu_bits();
+    if let Some(range_extension) = &sps.range_extension {
+        let _ = range_extension.wp_offset_half_range_y(sps.bit_depth_y());
+        let _ = range_extension.wp_offset_half_range_c(sps.bit_depth_c());
+    }
+
+    let _ = nalu.size_with_emulation_prevention();
+    let mut built = Vec::new();
+    let _ = nalu.build(&mut built);
+});
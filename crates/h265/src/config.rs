@@ -8,6 +8,7 @@ use bytes_util::{BitReader, BitWriter};
 
 use crate::{
     ConstantFrameRate, NALUnitType, NumTemporalLayers, ParallelismType, ProfileCompatibilityFlags,
+    SpsNALUnit,
 };
 
 /// HEVC Decoder Configuration Record.
@@ -87,7 +88,184 @@ pub struct NaluArray {
     pub nalus: Vec<Bytes>,
 }
 
+/// A field of an [`HEVCDecoderConfigurationRecord`] that does not match the SPS it contains.
+///
+/// Returned by [`HEVCDecoderConfigurationRecord::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigMismatch {
+    /// The name of the field in the [`HEVCDecoderConfigurationRecord`].
+    pub field: &'static str,
+    /// The value in the configuration record header.
+    pub record: u64,
+    /// The value in the first SPS.
+    pub sps: u64,
+}
+
 impl HEVCDecoderConfigurationRecord {
+    /// Appends a NAL unit to the array of the given type.
+    ///
+    /// If there is no array of this type yet, a new one is inserted so that the arrays stay ordered
+    /// as VPS, SPS, PPS, prefix SEI and suffix SEI. The new array is marked complete only if all
+    /// existing arrays are complete.
+    ///
+    /// Returns an error if the type is not allowed in a configuration record, if the NAL unit header
+    /// does not match the type, or if the NAL unit or array would be too large to mux.
+    pub fn push_nalu(&mut self, nal_unit_type: NALUnitType, nalu: Bytes) -> io::Result<()> {
+        check_nalu(nal_unit_type, &nalu)?;
+
+        let array = match self
+            .arrays
+            .iter()
+            .position(|array| array.nal_unit_type == nal_unit_type)
+        {
+            Some(index) => &mut self.arrays[index],
+            None => {
+                let array_completeness = !self.arrays.is_empty()
+                    && self.arrays.iter().all(|array| array.array_completeness);
+                let index = self
+                    .arrays
+                    .iter()
+                    .position(|array| array_order(array.nal_unit_type) > array_order(nal_unit_type))
+                    .unwrap_or(self.arrays.len());
+
+                self.arrays.insert(
+                    index,
+                    NaluArray {
+                        array_completeness,
+                        nal_unit_type,
+                        nalus: Vec::new(),
+                    },
+                );
+                &mut self.arrays[index]
+            }
+        };
+
+        if array.nalus.len() >= u16::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many NAL units in array",
+            ));
+        }
+
+        array.nalus.push(nalu);
+        Ok(())
+    }
+
+    /// Replaces the NAL unit at `index` within the array of the given type.
+    ///
+    /// Returns the replaced NAL unit.
+    pub fn replace_nalu(
+        &mut self,
+        nal_unit_type: NALUnitType,
+        index: usize,
+        nalu: Bytes,
+    ) -> io::Result<Bytes> {
+        check_nalu(nal_unit_type, &nalu)?;
+
+        let slot = self
+            .arrays
+            .iter_mut()
+            .filter(|array| array.nal_unit_type == nal_unit_type)
+            .flat_map(|array| array.nalus.iter_mut())
+            .nth(index)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no {nal_unit_type:?} NAL unit at index {index}"),
+                )
+            })?;
+
+        Ok(std::mem::replace(slot, nalu))
+    }
+
+    /// Replaces the SPS NAL unit at `index`.
+    ///
+    /// Returns the replaced SPS.
+    pub fn replace_sps(&mut self, index: usize, nalu: Bytes) -> io::Result<Bytes> {
+        self.replace_nalu(NALUnitType::SpsNut, index, nalu)
+    }
+
+    /// Returns an iterator over all VPS, SPS and PPS NAL units in the order they are stored.
+    pub fn parameter_sets(&self) -> impl Iterator<Item = (NALUnitType, &Bytes)> {
+        self.arrays
+            .iter()
+            .filter(|array| {
+                matches!(
+                    array.nal_unit_type,
+                    NALUnitType::VpsNut | NALUnitType::SpsNut | NALUnitType::PpsNut
+                )
+            })
+            .flat_map(|array| array.nalus.iter().map(|nalu| (array.nal_unit_type, nalu)))
+    }
+
+    /// Cross-checks the record header against the first SPS.
+    ///
+    /// Returns the list of fields that do not match, which is empty for a consistent record.
+    /// Returns an error if the record does not contain an SPS or if it cannot be parsed.
+    pub fn validate(&self) -> io::Result<Vec<ConfigMismatch>> {
+        let sps = self
+            .parameter_sets()
+            .find(|(nal_unit_type, _)| *nal_unit_type == NALUnitType::SpsNut)
+            .map(|(_, nalu)| nalu)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "no SPS entries in HEVC decoder configuration record",
+                )
+            })?;
+        let sps = SpsNALUnit::parse(io::Cursor::new(sps))?.rbsp;
+        let profile = &sps.profile_tier_level.general_profile;
+
+        let fields = [
+            (
+                "general_profile_space",
+                self.general_profile_space as u64,
+                profile.profile_space as u64,
+            ),
+            (
+                "general_tier_flag",
+                self.general_tier_flag as u64,
+                profile.tier_flag as u64,
+            ),
+            (
+                "general_profile_idc",
+                self.general_profile_idc as u64,
+                profile.profile_idc as u64,
+            ),
+            (
+                "general_profile_compatibility_flags",
+                self.general_profile_compatibility_flags.bits() as u64,
+                profile.profile_compatibility_flag.bits() as u64,
+            ),
+            (
+                "general_level_idc",
+                self.general_level_idc as u64,
+                profile.level_idc.unwrap_or_default() as u64,
+            ),
+            (
+                "chroma_format_idc",
+                self.chroma_format_idc as u64,
+                sps.chroma_format_idc as u64,
+            ),
+            (
+                "bit_depth_luma_minus8",
+                self.bit_depth_luma_minus8 as u64,
+                sps.bit_depth_luma_minus8 as u64,
+            ),
+            (
+                "bit_depth_chroma_minus8",
+                self.bit_depth_chroma_minus8 as u64,
+                sps.bit_depth_chroma_minus8 as u64,
+            ),
+        ];
+
+        Ok(fields
+            .into_iter()
+            .filter(|(_, record, sps)| record != sps)
+            .map(|(field, record, sps)| ConfigMismatch { field, record, sps })
+            .collect())
+    }
+
     /// Returns the first SPS NAL unit as a zero-copy `Bytes` slice.
     ///
     /// This is a lightweight helper for callers that only need the SPS payload
@@ -302,6 +480,33 @@ impl HEVCDecoderConfigurationRecord {
     ///
     /// Returns a muxed byte stream.
     pub fn mux<T: io::Write>(&self, writer: &mut T) -> io::Result<()> {
+        if self.arrays.len() > u8::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many NAL unit arrays",
+            ));
+        }
+
+        for array in &self.arrays {
+            if array.nalus.len() > u16::MAX as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "too many NAL units in array",
+                ));
+            }
+
+            if array
+                .nalus
+                .iter()
+                .any(|nalu| nalu.len() > u16::MAX as usize)
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "NAL unit is too large",
+                ));
+            }
+        }
+
         let mut bit_writer = BitWriter::new(writer);
 
         // This muxer only supports version 1
@@ -356,6 +561,57 @@ impl HEVCDecoderConfigurationRecord {
     }
 }
 
+/// The position of an array of the given type in a configuration record.
+fn array_order(nal_unit_type: NALUnitType) -> u8 {
+    match nal_unit_type {
+        NALUnitType::VpsNut => 0,
+        NALUnitType::SpsNut => 1,
+        NALUnitType::PpsNut => 2,
+        NALUnitType::PrefixSeiNut => 3,
+        _ => 4,
+    }
+}
+
+/// Checks that a NAL unit of the given type can be stored in a configuration record.
+fn check_nalu(nal_unit_type: NALUnitType, nalu: &[u8]) -> io::Result<()> {
+    if !matches!(
+        nal_unit_type,
+        NALUnitType::VpsNut
+            | NALUnitType::SpsNut
+            | NALUnitType::PpsNut
+            | NALUnitType::PrefixSeiNut
+            | NALUnitType::SuffixSeiNut
+    ) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid nal_unit_type",
+        ));
+    }
+
+    let Some(header) = nalu.first() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "NAL unit must not be empty",
+        ));
+    };
+
+    if (header >> 1) & 0b0011_1111 != nal_unit_type as u8 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("NAL unit header does not match {nal_unit_type:?}"),
+        ));
+    }
+
+    if nalu.len() > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "NAL unit is too large",
+        ));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
@@ -364,8 +620,8 @@ mod tests {
     use bytes::Bytes;
 
     use crate::{
-        ConstantFrameRate, HEVCDecoderConfigurationRecord, NALUnitType, NumTemporalLayers,
-        ParallelismType, ProfileCompatibilityFlags, SpsNALUnit,
+        ConfigMismatch, ConstantFrameRate, HEVCDecoderConfigurationRecord, NALUnitType,
+        NumTemporalLayers, ParallelismType, ProfileCompatibilityFlags, SpsNALUnit,
     };
 
     #[test]
@@ -462,4 +718,83 @@ mod tests {
         let err = HEVCDecoderConfigurationRecord::first_sps_nalu_bytes(&data).unwrap_err();
         assert_eq!(err.kind(), io::ErrorKind::InvalidData);
     }
+
+    const CONFIG: &[u8] = b"\x01\x01@\0\0\0\x90\0\0\0\0\0\x99\xf0\0\xfc\xfd\xf8\xf8\0\0\x0f\x03 \0\x01\0\x18@\x01\x0c\x01\xff\xff\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\x95@\x90!\0\x01\0=B\x01\x01\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\xa0\x01@ \x05\xa1e\x95R\x90\x84d_\xf8\xc0Z\x80\x80\x80\x82\0\0\x03\0\x02\0\0\x03\x01 \xc0\x0b\xbc\xa2\0\x02bX\0\x011-\x08\"\0\x01\0\x07D\x01\xc0\x93|\x0c\xc9";
+
+    #[test]
+    fn test_config_validate_level_mismatch() {
+        let mut config = HEVCDecoderConfigurationRecord::demux(io::Cursor::new(CONFIG)).unwrap();
+        assert_eq!(config.validate().unwrap(), vec![]);
+
+        // Level 4.1 in the SPS, level 4.0 in the record header.
+        let mut sps =
+            SpsNALUnit::parse(io::Cursor::new(config.arrays[1].nalus[0].clone())).unwrap();
+        sps.rbsp.profile_tier_level.general_profile.level_idc = Some(123);
+        let mut nalu = Vec::new();
+        sps.build(&mut nalu).unwrap();
+        config.replace_sps(0, nalu.into()).unwrap();
+        config.general_level_idc = 120;
+
+        assert_eq!(
+            config.validate().unwrap(),
+            vec![ConfigMismatch {
+                field: "general_level_idc",
+                record: 120,
+                sps: 123,
+            }]
+        );
+
+        let mut buf = Vec::new();
+        config.mux(&mut buf).unwrap();
+        assert_eq!(config.size(), buf.len() as u64);
+        assert_eq!(
+            HEVCDecoderConfigurationRecord::demux(io::Cursor::new(buf)).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_config_push_nalu() {
+        let mut config = HEVCDecoderConfigurationRecord::demux(io::Cursor::new(CONFIG)).unwrap();
+        let pps = config.arrays[2].nalus[0].clone();
+        let sei = Bytes::from_static(b"\x4e\x01\x05\x00\x80");
+
+        config.push_nalu(NALUnitType::PpsNut, pps.clone()).unwrap();
+        config
+            .push_nalu(NALUnitType::PrefixSeiNut, sei.clone())
+            .unwrap();
+        assert_eq!(config.arrays.len(), 4);
+        assert_eq!(config.arrays[2].nalus, vec![pps.clone(), pps.clone()]);
+        assert_eq!(config.arrays[3].nal_unit_type, NALUnitType::PrefixSeiNut);
+        assert!(!config.arrays[3].array_completeness);
+
+        let types: Vec<_> = config.parameter_sets().map(|(t, _)| t).collect();
+        assert_eq!(
+            types,
+            vec![
+                NALUnitType::VpsNut,
+                NALUnitType::SpsNut,
+                NALUnitType::PpsNut,
+                NALUnitType::PpsNut
+            ]
+        );
+
+        // Removing the SPS array and pushing a new SPS keeps the arrays ordered.
+        let sps = config.arrays.remove(1).nalus.remove(0);
+        config.push_nalu(NALUnitType::SpsNut, sps).unwrap();
+        assert_eq!(config.arrays[1].nal_unit_type, NALUnitType::SpsNut);
+
+        let err = config
+            .push_nalu(NALUnitType::SpsNut, pps.clone())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = config.push_nalu(NALUnitType::AudNut, pps).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = config.replace_sps(1, sei).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = config
+            .replace_sps(1, config.arrays[1].nalus[0].clone())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
 }
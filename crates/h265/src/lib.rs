@@ -34,6 +34,6 @@ mod nal_unit_header;
 mod rbsp_trailing_bits;
 mod sps;
 
-pub use config::{HEVCDecoderConfigurationRecord, NaluArray};
+pub use config::{ConfigMismatch, HEVCDecoderConfigurationRecord, NaluArray};
 pub use enums::*;
 pub use sps::*;
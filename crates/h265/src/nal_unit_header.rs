@@ -1,7 +1,7 @@
 use std::io;
 use std::num::NonZero;
 
-use bytes_util::{BitReader, range_check};
+use bytes_util::{BitReader, BitWriter, range_check};
 
 use crate::NALUnitType;
 
@@ -37,7 +37,10 @@ impl NALUnitHeader {
             ));
         }
 
-        let nal_unit_type = NALUnitType::from(bit_reader.read_bits(6)? as u8);
+        let nal_unit_type = bit_reader.read_bits(6)? as u8;
+        // UNSPEC48..=UNSPEC63 have no NALUnitType variant
+        range_check!(nal_unit_type, 0, 47)?;
+        let nal_unit_type = NALUnitType::from(nal_unit_type);
         let nuh_layer_id = bit_reader.read_bits(6)? as u8;
         range_check!(nuh_layer_id, 0, 63)?;
 
@@ -101,6 +104,21 @@ impl NALUnitHeader {
         })
     }
 
+    /// Writes the NAL unit header to the given writer.
+    ///
+    /// The header is always exactly 2 bytes.
+    pub fn build(&self, writer: impl io::Write) -> io::Result<()> {
+        let mut bit_writer = BitWriter::new(writer);
+
+        bit_writer.write_bit(false)?; // forbidden_zero_bit
+        bit_writer.write_bits(self.nal_unit_type as u64, 6)?;
+        bit_writer.write_bits(self.nuh_layer_id as u64, 6)?;
+        bit_writer.write_bits(self.nuh_temporal_id_plus1.get() as u64, 3)?;
+
+        bit_writer.finish()?;
+        Ok(())
+    }
+
     /// Returns the temporal id of the NAL unit.
     ///
     /// Defined as `TemporalId` (7-1) by ISO/IEC 23008-2 - 7.4.2.2.
@@ -1,6 +1,6 @@
 use std::io;
 
-use bytes_util::BitReader;
+use bytes_util::{BitReader, BitWriter};
 
 /// Described by ISO/IEC 23008-2 - 7.4.2.1
 pub(crate) fn rbsp_trailing_bits<R: io::Read>(bit_reader: &mut BitReader<R>) -> io::Result<()> {
@@ -17,3 +17,12 @@ pub(crate) fn rbsp_trailing_bits<R: io::Read>(bit_reader: &mut BitReader<R>) ->
 
     Ok(())
 }
+
+/// Writes the `rbsp_stop_one_bit` followed by `rbsp_alignment_zero_bit`s up to the next byte boundary.
+///
+/// Described by ISO/IEC 23008-2 - 7.3.2.11
+pub(crate) fn write_rbsp_trailing_bits<W: io::Write>(
+    bit_writer: &mut BitWriter<W>,
+) -> io::Result<()> {
+    bit_writer.write_trailing_one_and_align()
+}
@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::BitReader;
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Specifies the samples of the pictures in the CVS that are output from the decoding process, in terms of a rectangular
 /// region specified in picture coordinates for output.
@@ -21,10 +21,10 @@ pub struct ConformanceWindow {
 
 impl ConformanceWindow {
     pub(crate) fn parse<R: io::Read>(reader: &mut BitReader<R>) -> io::Result<Self> {
-        let conf_win_left_offset = reader.read_exp_golomb()?;
-        let conf_win_right_offset = reader.read_exp_golomb()?;
-        let conf_win_top_offset = reader.read_exp_golomb()?;
-        let conf_win_bottom_offset = reader.read_exp_golomb()?;
+        let conf_win_left_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let conf_win_right_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let conf_win_top_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let conf_win_bottom_offset = reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
         Ok(ConformanceWindow {
             conf_win_left_offset,
@@ -33,4 +33,12 @@ impl ConformanceWindow {
             conf_win_bottom_offset,
         })
     }
+
+    pub(crate) fn build<W: io::Write>(&self, writer: &mut BitWriter<W>) -> io::Result<()> {
+        writer.write_exp_golomb(self.conf_win_left_offset)?;
+        writer.write_exp_golomb(self.conf_win_right_offset)?;
+        writer.write_exp_golomb(self.conf_win_top_offset)?;
+        writer.write_exp_golomb(self.conf_win_bottom_offset)?;
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Directly part of [SPS RBSP](crate::SpsRbsp).
 #[derive(Debug, Clone, PartialEq)]
@@ -20,7 +20,8 @@ impl LongTermRefPics {
         bit_reader: &mut BitReader<R>,
         log2_max_pic_order_cnt_lsb_minus4: u8,
     ) -> Result<Self, io::Error> {
-        let num_long_term_ref_pics_sps = bit_reader.read_exp_golomb()?;
+        let num_long_term_ref_pics_sps =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(num_long_term_ref_pics_sps, 0, 32)?;
 
         let mut lt_ref_pic_poc_lsb_sps = Vec::with_capacity(num_long_term_ref_pics_sps as usize);
@@ -38,4 +39,32 @@ impl LongTermRefPics {
             used_by_curr_pic_lt_sps_flag,
         })
     }
+
+    pub(crate) fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        log2_max_pic_order_cnt_lsb_minus4: u8,
+    ) -> io::Result<()> {
+        if self.lt_ref_pic_poc_lsb_sps.len() != self.used_by_curr_pic_lt_sps_flag.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "lt_ref_pic_poc_lsb_sps and used_by_curr_pic_lt_sps_flag must have the same length",
+            ));
+        }
+
+        bit_writer.write_exp_golomb(self.lt_ref_pic_poc_lsb_sps.len() as u64)?;
+        for (lt_ref_pic_poc_lsb_sps, used_by_curr_pic_lt_sps_flag) in self
+            .lt_ref_pic_poc_lsb_sps
+            .iter()
+            .zip(self.used_by_curr_pic_lt_sps_flag.iter())
+        {
+            bit_writer.write_bits(
+                *lt_ref_pic_poc_lsb_sps,
+                log2_max_pic_order_cnt_lsb_minus4 + 4,
+            )?;
+            bit_writer.write_bit(*used_by_curr_pic_lt_sps_flag)?;
+        }
+
+        Ok(())
+    }
 }
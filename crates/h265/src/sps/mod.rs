@@ -2,12 +2,12 @@ use std::io;
 use std::num::NonZero;
 
 use bytes_util::nal_emulation_prevention::EmulationPreventionIo;
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 use crate::NALUnitType;
 use crate::nal_unit_header::NALUnitHeader;
-use crate::rbsp_trailing_bits::rbsp_trailing_bits;
+use crate::rbsp_trailing_bits::{rbsp_trailing_bits, write_rbsp_trailing_bits};
 
 mod conformance_window;
 mod long_term_ref_pics;
@@ -78,6 +78,27 @@ impl SpsNALUnit {
             rbsp,
         })
     }
+
+    /// Writes the SPS NAL unit to the given writer.
+    ///
+    /// Emulation prevention bytes are inserted into the RBSP, see [`SpsRbsp::build`].
+    pub fn build(&self, mut writer: impl io::Write) -> io::Result<()> {
+        if self.nal_unit_header.nal_unit_type != NALUnitType::SpsNut {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "nal_unit_type is not SPS_NUT",
+            ));
+        }
+
+        self.nal_unit_header.build(&mut writer)?;
+        self.rbsp.build(writer)
+    }
+
+    /// Returns the number of bytes written by [`SpsNALUnit::build`], the 2 byte NAL unit header
+    /// followed by [`SpsRbsp::size_with_emulation_prevention`].
+    pub fn size_with_emulation_prevention(&self) -> io::Result<u64> {
+        Ok(2 + self.rbsp.size_with_emulation_prevention()?)
+    }
 }
 
 /// Sequence parameter set RBSP.
@@ -217,13 +238,21 @@ pub struct SpsRbsp {
     pub scc_extension: Option<SpsSccExtension>,
 }
 
+/// The maximum number of bytes read while parsing an SPS RBSP.
+///
+/// Real world SPS NAL units are a few hundred bytes at most, this only stops
+/// corrupted input or never ending readers from being read indefinitely.
+const MAX_SPS_SIZE: u64 = 64 * 1024;
+
 impl SpsRbsp {
     /// Parses an SPS RBSP from the given reader.
     ///
     /// Uses [`EmulationPreventionIo`] to handle emulation prevention bytes.
+    /// At most 64 KiB are read from the reader.
     ///
     /// Returns an [`SpsRbsp`] struct.
     pub fn parse(reader: impl io::Read, nuh_layer_id: u8) -> io::Result<Self> {
+        let reader = io::Read::take(reader, MAX_SPS_SIZE);
         let mut bit_reader = BitReader::new(EmulationPreventionIo::new(reader));
 
         let sps_video_parameter_set_id = bit_reader.read_bits(4)? as u8;
@@ -243,10 +272,11 @@ impl SpsRbsp {
         let profile_tier_level =
             ProfileTierLevel::parse(&mut bit_reader, sps_max_sub_layers_minus1)?;
 
-        let sps_seq_parameter_set_id = bit_reader.read_exp_golomb()?;
+        let sps_seq_parameter_set_id =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(sps_seq_parameter_set_id, 0, 15)?;
 
-        let chroma_format_idc = bit_reader.read_exp_golomb()?;
+        let chroma_format_idc = bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(chroma_format_idc, 0, 3)?;
         let chroma_format_idc = chroma_format_idc as u8;
 
@@ -264,20 +294,26 @@ impl SpsRbsp {
         let sub_height_c = if chroma_format_idc == 1 { 2 } else { 1 };
 
         let pic_width_in_luma_samples =
-            NonZero::new(bit_reader.read_exp_golomb()?).ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "pic_width_in_luma_samples must not be 0",
-                )
-            })?;
+            NonZero::new(bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "pic_width_in_luma_samples must not be 0",
+                    )
+                })?;
+        // Keeps the picture size in samples within a u64
+        range_check!(pic_width_in_luma_samples.get(), 1, u64::from(u32::MAX))?;
 
         let pic_height_in_luma_samples =
-            NonZero::new(bit_reader.read_exp_golomb()?).ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "pic_height_in_luma_samples must not be 0",
-                )
-            })?;
+            NonZero::new(bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "pic_height_in_luma_samples must not be 0",
+                    )
+                })?;
+        // Keeps the picture size in samples within a u64
+        range_check!(pic_height_in_luma_samples.get(), 1, u64::from(u32::MAX))?;
 
         let conformance_window_flag = bit_reader.read_bit()?;
 
@@ -286,16 +322,19 @@ impl SpsRbsp {
             .transpose()?
             .unwrap_or_default();
 
-        let bit_depth_luma_minus8 = bit_reader.read_exp_golomb()?;
+        let bit_depth_luma_minus8 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(bit_depth_luma_minus8, 0, 8)?;
         let bit_depth_luma_minus8 = bit_depth_luma_minus8 as u8;
         let bit_depth_y = 8 + bit_depth_luma_minus8; // BitDepth_Y
-        let bit_depth_chroma_minus8 = bit_reader.read_exp_golomb()?;
+        let bit_depth_chroma_minus8 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(bit_depth_chroma_minus8, 0, 8)?;
         let bit_depth_chroma_minus8 = bit_depth_chroma_minus8 as u8;
         let bit_depth_c = 8 + bit_depth_chroma_minus8; // BitDepth_C
 
-        let log2_max_pic_order_cnt_lsb_minus4 = bit_reader.read_exp_golomb()?;
+        let log2_max_pic_order_cnt_lsb_minus4 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(log2_max_pic_order_cnt_lsb_minus4, 0, 12)?;
         let log2_max_pic_order_cnt_lsb_minus4 = log2_max_pic_order_cnt_lsb_minus4 as u8;
 
@@ -306,24 +345,34 @@ impl SpsRbsp {
             sps_max_sub_layers_minus1,
         )?;
 
-        let log2_min_luma_coding_block_size_minus3 = bit_reader.read_exp_golomb()?;
-        let log2_diff_max_min_luma_coding_block_size = bit_reader.read_exp_golomb()?;
+        let log2_min_luma_coding_block_size_minus3 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let log2_diff_max_min_luma_coding_block_size =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
         let min_cb_log2_size_y = log2_min_luma_coding_block_size_minus3 + 3;
         let ctb_log2_size_y = min_cb_log2_size_y + log2_diff_max_min_luma_coding_block_size;
+        // ISO/IEC 23008-2 - A.3
+        range_check!(ctb_log2_size_y, 4, 6)?;
 
-        let log2_min_luma_transform_block_size_minus2 = bit_reader.read_exp_golomb()?;
+        let log2_min_luma_transform_block_size_minus2 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
         let min_tb_log2_size_y = log2_min_luma_transform_block_size_minus2 + 2;
+        // MinTbLog2SizeY shall be less than MinCbLog2SizeY
+        range_check!(min_tb_log2_size_y, 2, min_cb_log2_size_y - 1)?;
 
-        let log2_diff_max_min_luma_transform_block_size = bit_reader.read_exp_golomb()?;
-        let max_transform_hierarchy_depth_inter = bit_reader.read_exp_golomb()?;
+        let log2_diff_max_min_luma_transform_block_size =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+        let max_transform_hierarchy_depth_inter =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(
             max_transform_hierarchy_depth_inter,
             0,
             ctb_log2_size_y - min_tb_log2_size_y
         )?;
-        let max_transform_hierarchy_depth_intra = bit_reader.read_exp_golomb()?;
+        let max_transform_hierarchy_depth_intra =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(
             max_transform_hierarchy_depth_intra,
             0,
@@ -356,7 +405,8 @@ impl SpsRbsp {
             )?);
         }
 
-        let num_short_term_ref_pic_sets = bit_reader.read_exp_golomb()?;
+        let num_short_term_ref_pic_sets =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(num_short_term_ref_pic_sets, 0, 64)?;
         let num_short_term_ref_pic_sets = num_short_term_ref_pic_sets as u8;
         let short_term_ref_pic_sets = ShortTermRefPicSets::parse(
@@ -487,6 +537,133 @@ impl SpsRbsp {
         })
     }
 
+    /// Writes the SPS RBSP, including the `rbsp_trailing_bits`, to the given writer.
+    ///
+    /// Uses [`EmulationPreventionIo`] to insert emulation prevention bytes.
+    ///
+    /// Some information is not retained by [`SpsRbsp::parse`], like whether a syntax element was
+    /// present or inferred, or how the scaling lists were predicted. In these cases the written
+    /// SPS can differ from the original bytes, but parsing it again yields an equal [`SpsRbsp`].
+    pub fn build(&self, writer: impl io::Write) -> io::Result<()> {
+        let mut bit_writer = BitWriter::new(EmulationPreventionIo::new(writer));
+
+        bit_writer.write_bits(self.sps_video_parameter_set_id as u64, 4)?;
+        bit_writer.write_bits(self.sps_max_sub_layers_minus1 as u64, 3)?;
+        bit_writer.write_bit(self.sps_temporal_id_nesting_flag)?;
+
+        self.profile_tier_level
+            .build(&mut bit_writer, self.sps_max_sub_layers_minus1)?;
+
+        bit_writer.write_exp_golomb(self.sps_seq_parameter_set_id)?;
+        bit_writer.write_exp_golomb(self.chroma_format_idc as u64)?;
+        if self.chroma_format_idc == 3 {
+            bit_writer.write_bit(self.separate_colour_plane_flag)?;
+        }
+
+        bit_writer.write_exp_golomb(self.pic_width_in_luma_samples.get())?;
+        bit_writer.write_exp_golomb(self.pic_height_in_luma_samples.get())?;
+
+        let conformance_window_flag = self.conformance_window != ConformanceWindow::default();
+        bit_writer.write_bit(conformance_window_flag)?;
+        if conformance_window_flag {
+            self.conformance_window.build(&mut bit_writer)?;
+        }
+
+        bit_writer.write_exp_golomb(self.bit_depth_luma_minus8 as u64)?;
+        bit_writer.write_exp_golomb(self.bit_depth_chroma_minus8 as u64)?;
+        bit_writer.write_exp_golomb(self.log2_max_pic_order_cnt_lsb_minus4 as u64)?;
+
+        self.sub_layer_ordering_info.build(&mut bit_writer)?;
+
+        bit_writer.write_exp_golomb(self.log2_min_luma_coding_block_size_minus3)?;
+        bit_writer.write_exp_golomb(self.log2_diff_max_min_luma_coding_block_size)?;
+        bit_writer.write_exp_golomb(self.log2_min_luma_transform_block_size_minus2)?;
+        bit_writer.write_exp_golomb(self.log2_diff_max_min_luma_transform_block_size)?;
+        bit_writer.write_exp_golomb(self.max_transform_hierarchy_depth_inter)?;
+        bit_writer.write_exp_golomb(self.max_transform_hierarchy_depth_intra)?;
+
+        // scaling_list_enabled_flag
+        bit_writer.write_bit(self.scaling_list_data.is_some())?;
+        if let Some(scaling_list_data) = &self.scaling_list_data {
+            bit_writer.write_bit(true)?; // sps_scaling_list_data_present_flag
+            scaling_list_data.build(&mut bit_writer)?;
+        }
+
+        bit_writer.write_bit(self.amp_enabled_flag)?;
+        bit_writer.write_bit(self.sample_adaptive_offset_enabled_flag)?;
+
+        bit_writer.write_bit(self.pcm.is_some())?; // pcm_enabled_flag
+        if let Some(pcm) = &self.pcm {
+            pcm.build(&mut bit_writer)?;
+        }
+
+        bit_writer.write_exp_golomb(self.short_term_ref_pic_sets.num_delta_pocs.len() as u64)?; // num_short_term_ref_pic_sets
+        self.short_term_ref_pic_sets.build(&mut bit_writer)?;
+
+        bit_writer.write_bit(self.long_term_ref_pics.is_some())?; // long_term_ref_pics_present_flag
+        if let Some(long_term_ref_pics) = &self.long_term_ref_pics {
+            long_term_ref_pics.build(&mut bit_writer, self.log2_max_pic_order_cnt_lsb_minus4)?;
+        }
+
+        bit_writer.write_bit(self.sps_temporal_mvp_enabled_flag)?;
+        bit_writer.write_bit(self.strong_intra_smoothing_enabled_flag)?;
+
+        bit_writer.write_bit(self.vui_parameters.is_some())?; // vui_parameters_present_flag
+        if let Some(vui_parameters) = &self.vui_parameters {
+            vui_parameters.build(&mut bit_writer, self.sps_max_sub_layers_minus1)?;
+        }
+
+        let sps_range_extension_flag = self.range_extension.is_some();
+        let sps_multilayer_extension_flag = self.multilayer_extension.is_some();
+        let sps_3d_extension_flag = self.sps_3d_extension.is_some();
+        let sps_scc_extension_flag = self.scc_extension.is_some();
+        let sps_extension_flag = sps_range_extension_flag
+            || sps_multilayer_extension_flag
+            || sps_3d_extension_flag
+            || sps_scc_extension_flag;
+
+        bit_writer.write_bit(sps_extension_flag)?;
+        if sps_extension_flag {
+            bit_writer.write_bit(sps_range_extension_flag)?;
+            bit_writer.write_bit(sps_multilayer_extension_flag)?;
+            bit_writer.write_bit(sps_3d_extension_flag)?;
+            bit_writer.write_bit(sps_scc_extension_flag)?;
+            bit_writer.write_bits(0, 4)?; // sps_extension_4bits
+
+            if let Some(range_extension) = &self.range_extension {
+                range_extension.build(&mut bit_writer)?;
+            }
+
+            if let Some(multilayer_extension) = &self.multilayer_extension {
+                multilayer_extension.build(&mut bit_writer)?;
+            }
+
+            if let Some(sps_3d_extension) = &self.sps_3d_extension {
+                sps_3d_extension.build(&mut bit_writer)?;
+            }
+
+            if let Some(scc_extension) = &self.scc_extension {
+                scc_extension.build(&mut bit_writer, self.bit_depth_y(), self.bit_depth_c())?;
+            }
+        }
+
+        write_rbsp_trailing_bits(&mut bit_writer)?;
+        bit_writer.finish()?;
+
+        Ok(())
+    }
+
+    /// Returns the number of bytes written by [`SpsRbsp::build`], emulation prevention bytes
+    /// included.
+    ///
+    /// This is the size the SPS takes in a HEVCDecoderConfigurationRecord or an Annex B stream.
+    /// The RBSP is built to count them, so this fails like [`SpsRbsp::build`].
+    pub fn size_with_emulation_prevention(&self) -> io::Result<u64> {
+        let mut buf = Vec::new();
+        self.build(&mut buf)?;
+        Ok(buf.len() as u64)
+    }
+
     /// The `croppedWidth` as a [`u64`].
     ///
     /// This is computed from other fields, and doesn't directly appear in the bitstream.
@@ -495,10 +672,11 @@ impl SpsRbsp {
     ///
     /// ISO/IEC 23008-2 - D.3.29
     pub fn cropped_width(&self) -> u64 {
-        self.pic_width_in_luma_samples.get()
-            - self.sub_width_c() as u64
+        self.pic_width_in_luma_samples.get().saturating_sub(
+            self.sub_width_c() as u64
                 * (self.conformance_window.conf_win_left_offset
-                    + self.conformance_window.conf_win_right_offset)
+                    + self.conformance_window.conf_win_right_offset),
+        )
     }
 
     /// The `croppedHeight` as a [`u64`].
@@ -509,10 +687,11 @@ impl SpsRbsp {
     ///
     /// ISO/IEC 23008-2 - D.3.29
     pub fn cropped_height(&self) -> u64 {
-        self.pic_height_in_luma_samples.get()
-            - self.sub_height_c() as u64
+        self.pic_height_in_luma_samples.get().saturating_sub(
+            self.sub_height_c() as u64
                 * (self.conformance_window.conf_win_top_offset
-                    + self.conformance_window.conf_win_bottom_offset)
+                    + self.conformance_window.conf_win_bottom_offset),
+        )
     }
 
     /// - If [`separate_colour_plane_flag`](Self::separate_colour_plane_flag) is equal to `false`, `ChromaArrayType` is set equal to [`chroma_format_idc`](Self::chroma_format_idc).
@@ -739,11 +918,29 @@ impl SpsRbsp {
 #[cfg_attr(all(test, coverage_nightly), coverage(off))]
 mod tests {
     use std::io;
+    use std::num::NonZero;
 
     use crate::SpsNALUnit;
 
     // To compare the results to an independent source, you can use: https://github.com/chemag/h265nal
 
+    /// Builds the parsed SPS again and checks that parsing the result yields the same struct.
+    fn build_round_trip(data: &[u8]) -> Vec<u8> {
+        let nalu = SpsNALUnit::parse(io::Cursor::new(data)).unwrap();
+
+        let mut built = Vec::new();
+        nalu.build(&mut built).unwrap();
+        assert_eq!(
+            nalu.size_with_emulation_prevention().unwrap(),
+            built.len() as u64
+        );
+
+        let reparsed = SpsNALUnit::parse(io::Cursor::new(&built)).unwrap();
+        assert_eq!(reparsed, nalu);
+
+        built
+    }
+
     #[test]
     fn test_sps_parse() {
         let data = b"B\x01\x01\x01@\0\0\x03\0\x90\0\0\x03\0\0\x03\0\x99\xa0\x01@ \x05\xa1e\x95R\x90\x84d_\xf8\xc0Z\x80\x80\x80\x82\0\0\x03\0\x02\0\0\x03\x01 \xc0\x0b\xbc\xa2\0\x02bX\0\x011-\x08";
@@ -780,6 +977,7 @@ mod tests {
         assert_eq!(sps.max_tb_log2_size_y(), 5);
         assert_eq!(sps.raw_ctu_bits(), 12288);
         insta::assert_debug_snapshot!(nalu);
+        assert!(data.starts_with(&build_round_trip(data)));
     }
 
     #[test]
@@ -819,6 +1017,7 @@ mod tests {
         assert_eq!(sps.max_tb_log2_size_y(), 5);
         assert_eq!(sps.raw_ctu_bits(), 12288);
         insta::assert_debug_snapshot!(nalu);
+        assert!(data.starts_with(&build_round_trip(data)));
     }
 
     #[test]
@@ -858,6 +1057,9 @@ mod tests {
         assert_eq!(sps.max_tb_log2_size_y(), 5);
         assert_eq!(sps.raw_ctu_bits(), 61440);
         insta::assert_debug_snapshot!(nalu);
+        // sps_sub_layer_ordering_info_present_flag is 0 and the scaling lists are predicted from each other,
+        // neither is retained when parsing, so only the parsed struct can be compared.
+        build_round_trip(data);
     }
 
     #[test]
@@ -897,6 +1099,8 @@ mod tests {
         assert_eq!(sps.max_tb_log2_size_y(), 5);
         assert_eq!(sps.raw_ctu_bits(), 49152);
         insta::assert_debug_snapshot!(nalu);
+        // The video signal type is present but only contains inferred values, so it is omitted when building.
+        build_round_trip(data);
     }
 
     #[test]
@@ -936,6 +1140,8 @@ mod tests {
         assert_eq!(sps.max_tb_log2_size_y(), 5);
         assert_eq!(sps.raw_ctu_bits(), 49152);
         insta::assert_debug_snapshot!(nalu);
+        // The conformance window is present but all offsets are 0, so it is omitted when building.
+        build_round_trip(data);
     }
 
     #[test]
@@ -975,6 +1181,9 @@ mod tests {
         assert_eq!(sps.max_tb_log2_size_y(), 4);
         assert_eq!(sps.raw_ctu_bits(), 20480);
         insta::assert_debug_snapshot!(nalu);
+        // sps_sub_layer_ordering_info_present_flag is 0 and the scaling lists are predicted from each other,
+        // neither is retained when parsing, so only the parsed struct can be compared.
+        build_round_trip(data);
     }
 
     #[test]
@@ -984,6 +1193,7 @@ mod tests {
 
         let nalu = SpsNALUnit::parse(io::Cursor::new(data)).unwrap();
         insta::assert_debug_snapshot!(nalu);
+        assert!(data.starts_with(&build_round_trip(data)));
     }
 
     #[test]
@@ -1007,4 +1217,141 @@ mod tests {
         assert_eq!(err.kind(), io::ErrorKind::InvalidData);
         assert_eq!(err.to_string(), "nal_unit_type is not SPS_NUT");
     }
+
+    #[test]
+    fn test_sps_corrupted_input_terminates() {
+        // A never ending stream of zeros fails on the first Exp-Golomb code.
+        let err = crate::SpsRbsp::parse(io::repeat(0), 0).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // A never ending stream of ones is cut off by the size limit.
+        let err = crate::SpsRbsp::parse(io::repeat(0xff), 0).unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+        ));
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for len in 0..512 {
+            let data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+            // Random input must fail (or succeed) without hanging or panicking.
+            let _ = crate::SpsRbsp::parse(io::Cursor::new(&data), 0);
+        }
+
+        // Flip bits of a valid SPS to reach deeper into the parser.
+        for _ in 0..2000 {
+            let mut data = CORRUPTION_BASE_SPS.to_vec();
+            for _ in 0..1 + next() % 4 {
+                let bit = next() as usize % (data.len() * 8);
+                data[bit / 8] ^= 0x80 >> (bit % 8);
+            }
+            data.truncate(data.len() - next() as usize % 8);
+            let _ = SpsNALUnit::parse(io::Cursor::new(&data));
+        }
+    }
+
+    const CORRUPTION_BASE_SPS: &[u8] = b"\x42\x01\x01\x01\x40\x00\x00\x03\x00\x90\x00\x00\x03\x00\x00\x03\x00\x78\xa0\x03\xc0\x80\x11\x07\xcb\x96\xb4\xa4\x25\x92\xe3\x01\x6a\x02\x02\x02\x08\x00\x00\x03\x00\x08\x00\x00\x03\x00\xf3\x00\x2e\xf2\x88\x00\x02\x62\x5a\x00\x00\x13\x12\xd0\x20";
+
+    #[test]
+    fn test_ctb_size_out_of_range() {
+        let mut nalu = SpsNALUnit::parse(io::Cursor::new(CORRUPTION_BASE_SPS)).unwrap();
+        // A CtbLog2SizeY of 70 used to overflow the shift of CtbSizeY
+        nalu.rbsp.log2_diff_max_min_luma_coding_block_size = 64;
+
+        let mut built = Vec::new();
+        nalu.build(&mut built).unwrap();
+        let err = SpsNALUnit::parse(io::Cursor::new(&built)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string()
+                .starts_with("ctb_log2_size_y is out of range")
+        );
+    }
+
+    #[test]
+    fn test_picture_size_out_of_range() {
+        let mut nalu = SpsNALUnit::parse(io::Cursor::new(CORRUPTION_BASE_SPS)).unwrap();
+        // The largest Exp-Golomb values accepted used to overflow PicSizeInSamplesY
+        nalu.rbsp.pic_width_in_luma_samples = NonZero::new((1 << 33) - 2).unwrap();
+        nalu.rbsp.pic_height_in_luma_samples = NonZero::new((1 << 33) - 2).unwrap();
+
+        let mut built = Vec::new();
+        nalu.build(&mut built).unwrap();
+        let err = SpsNALUnit::parse(io::Cursor::new(&built)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_conformance_window_larger_than_picture() {
+        let mut nalu = SpsNALUnit::parse(io::Cursor::new(CORRUPTION_BASE_SPS)).unwrap();
+        nalu.rbsp.conformance_window = crate::ConformanceWindow {
+            conf_win_left_offset: 5000,
+            conf_win_right_offset: 5000,
+            conf_win_top_offset: 5000,
+            conf_win_bottom_offset: 5000,
+        };
+
+        let mut built = Vec::new();
+        nalu.build(&mut built).unwrap();
+        let nalu = SpsNALUnit::parse(io::Cursor::new(&built)).unwrap();
+        assert_eq!(nalu.rbsp.cropped_width(), 0);
+        assert_eq!(nalu.rbsp.cropped_height(), 0);
+    }
+
+    #[test]
+    fn test_wp_offset_half_range_high_bit_depth() {
+        let range_extension = crate::SpsRangeExtension {
+            transform_skip_rotation_enabled_flag: false,
+            transform_skip_context_enabled_flag: false,
+            implicit_rdpcm_enabled_flag: false,
+            explicit_rdpcm_enabled_flag: false,
+            extended_precision_processing_flag: false,
+            intra_smoothing_disabled_flag: false,
+            high_precision_offsets_enabled_flag: true,
+            persistent_rice_adaptation_enabled_flag: false,
+            cabac_bypass_alignment_enabled_flag: false,
+        };
+
+        assert_eq!(range_extension.wp_offset_half_range_y(8), 128);
+        assert_eq!(range_extension.wp_offset_half_range_c(16), 1 << 15);
+    }
+
+    #[test]
+    fn test_size_with_emulation_prevention_randomized() {
+        let data = b"\x42\x01\x01\x01\x40\x00\x00\x03\x00\x90\x00\x00\x03\x00\x00\x03\x00\x78\xa0\x03\xc0\x80\x11\x07\xcb\x96\xb4\xa4\x25\x92\xe3\x01\x6a\x02\x02\x02\x08\x00\x00\x03\x00\x08\x00\x00\x03\x00\xf3\x00\x2e\xf2\x88\x00\x02\x62\x5a\x00\x00\x13\x12\xd0\x20";
+        let base = SpsNALUnit::parse(io::Cursor::new(data)).unwrap();
+
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..1000 {
+            let mut nalu = base.clone();
+            let sps = &mut nalu.rbsp;
+            sps.sps_seq_parameter_set_id = next() % 16;
+            sps.pic_width_in_luma_samples = NonZero::new(8 * (1 + next() % 512)).unwrap();
+            sps.pic_height_in_luma_samples = NonZero::new(8 * (1 + next() % 512)).unwrap();
+            sps.conformance_window.conf_win_right_offset = next() % 4;
+            sps.conformance_window.conf_win_bottom_offset = next() % 4;
+            sps.log2_max_pic_order_cnt_lsb_minus4 = (next() % 13) as u8;
+
+            let mut built = Vec::new();
+            nalu.build(&mut built).unwrap();
+            assert_eq!(
+                nalu.size_with_emulation_prevention().unwrap(),
+                built.len() as u64
+            );
+        }
+    }
 }
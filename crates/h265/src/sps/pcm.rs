@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Directly part of [SPS RBSP](crate::SpsRbsp).
 #[derive(Debug, Clone, PartialEq)]
@@ -47,7 +47,8 @@ impl Pcm {
             ));
         }
 
-        let log2_min_pcm_luma_coding_block_size_minus3 = bit_reader.read_exp_golomb()?;
+        let log2_min_pcm_luma_coding_block_size_minus3 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         let log2_min_ipcm_cb_size_y = log2_min_pcm_luma_coding_block_size_minus3 + 3;
         range_check!(
             log2_min_ipcm_cb_size_y,
@@ -55,7 +56,8 @@ impl Pcm {
             ctb_log2_size_y.min(5)
         )?;
 
-        let log2_diff_max_min_pcm_luma_coding_block_size = bit_reader.read_exp_golomb()?;
+        let log2_diff_max_min_pcm_luma_coding_block_size =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         let log2_max_ipcm_cb_size_y =
             log2_diff_max_min_pcm_luma_coding_block_size + log2_min_ipcm_cb_size_y;
         if log2_max_ipcm_cb_size_y > ctb_log2_size_y.min(5) {
@@ -74,6 +76,15 @@ impl Pcm {
         })
     }
 
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        bit_writer.write_bits(self.pcm_sample_bit_depth_luma_minus1 as u64, 4)?;
+        bit_writer.write_bits(self.pcm_sample_bit_depth_chroma_minus1 as u64, 4)?;
+        bit_writer.write_exp_golomb(self.log2_min_pcm_luma_coding_block_size_minus3)?;
+        bit_writer.write_exp_golomb(self.log2_diff_max_min_pcm_luma_coding_block_size)?;
+        bit_writer.write_bit(self.pcm_loop_filter_disabled_flag)?;
+        Ok(())
+    }
+
     /// Specifies the number of bits used to represent each of PCM sample values of the luma component.
     ///
     /// The value of `PcmBitDepthY` is less than or equal to the value of [`BitDepthY`](crate::SpsRbsp::bit_depth_y).
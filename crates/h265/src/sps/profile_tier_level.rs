@@ -1,7 +1,7 @@
 use std::io;
 
 use byteorder::{BigEndian, ReadBytesExt};
-use bytes_util::{BitReader, range_check};
+use bytes_util::{BitReader, BitWriter, range_check};
 
 use crate::ProfileCompatibilityFlags;
 
@@ -75,10 +75,8 @@ impl ProfileTierLevel {
 
         for i in 0..max_num_sub_layers_minus_1 as usize {
             if sub_layer_profile_present_flags[i] {
-                sub_layer_profiles[i] = Some(Profile::parse(
-                    bit_reader,
-                    sub_layer_level_present_flags[i],
-                )?);
+                // sub_layer_level_idc follows below, it is not part of the profile syntax
+                sub_layer_profiles[i] = Some(Profile::parse(bit_reader, false)?);
             }
 
             if sub_layer_level_present_flags[i] {
@@ -89,14 +87,22 @@ impl ProfileTierLevel {
         let mut last_profile = general_profile.clone();
         let mut sub_layer_profiles: Vec<_> = sub_layer_profiles
             .into_iter()
+            .zip(sub_layer_level_idcs)
             .rev()
-            .map(|profile| match profile {
-                Some(profile) => {
-                    let profile = profile.merge(&last_profile);
-                    last_profile = profile.clone();
-                    profile
-                }
-                None => last_profile.clone(),
+            .map(|(profile, level_idc)| {
+                let profile = match profile {
+                    Some(profile) => Profile {
+                        level_idc,
+                        ..profile
+                    }
+                    .merge(&last_profile),
+                    None => Profile {
+                        level_idc: level_idc.or(last_profile.level_idc),
+                        ..last_profile.clone()
+                    },
+                };
+                last_profile = profile.clone();
+                profile
             })
             .collect();
         sub_layer_profiles.reverse(); // reverse back to original order
@@ -106,6 +112,50 @@ impl ProfileTierLevel {
             sub_layer_profiles,
         })
     }
+
+    /// Writes the structure with `profilePresentFlag` equal to 1.
+    ///
+    /// Every sub-layer profile is written explicitly, even if it was inferred when parsing.
+    pub(crate) fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        max_num_sub_layers_minus_1: u8,
+    ) -> io::Result<()> {
+        if self.sub_layer_profiles.len() != max_num_sub_layers_minus_1 as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sub_layer_profiles must have sps_max_sub_layers_minus1 entries",
+            ));
+        }
+
+        self.general_profile.build(bit_writer)?;
+        let general_level_idc = self.general_profile.level_idc.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "general_level_idc must be present",
+            )
+        })?;
+        bit_writer.write_bits(general_level_idc as u64, 8)?;
+
+        for profile in &self.sub_layer_profiles {
+            bit_writer.write_bit(true)?; // sub_layer_profile_present_flag
+            bit_writer.write_bit(profile.level_idc.is_some())?; // sub_layer_level_present_flag
+        }
+
+        // reserved_zero_2bits
+        if max_num_sub_layers_minus_1 > 0 && max_num_sub_layers_minus_1 < 8 {
+            bit_writer.write_bits(0, 2 * (8 - max_num_sub_layers_minus_1))?;
+        }
+
+        for profile in &self.sub_layer_profiles {
+            profile.build(bit_writer)?;
+            if let Some(level_idc) = profile.level_idc {
+                bit_writer.write_bits(level_idc as u64, 8)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Profile part of the Profile, tier and level structure.
@@ -280,6 +330,62 @@ impl Profile {
         })
     }
 
+    /// Writes everything except the level, which is written by [`ProfileTierLevel::build`].
+    fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        bit_writer.write_bits(self.profile_space as u64, 2)?;
+        bit_writer.write_bit(self.tier_flag)?;
+        bit_writer.write_bits(self.profile_idc as u64, 5)?;
+        bit_writer.write_bits(self.profile_compatibility_flag.bits() as u64, 32)?;
+
+        bit_writer.write_bit(self.progressive_source_flag)?;
+        bit_writer.write_bit(self.interlaced_source_flag)?;
+        bit_writer.write_bit(self.non_packed_constraint_flag)?;
+        bit_writer.write_bit(self.frame_only_constraint_flag)?;
+
+        // All variants occupy the same 43 bits, so the layout does not depend on the profile.
+        match &self.additional_flags {
+            ProfileAdditionalFlags::Full {
+                max_12bit_constraint_flag,
+                max_10bit_constraint_flag,
+                max_8bit_constraint_flag,
+                max_422chroma_constraint_flag,
+                max_420chroma_constraint_flag,
+                max_monochrome_constraint_flag,
+                intra_constraint_flag,
+                one_picture_only_constraint_flag,
+                lower_bit_rate_constraint_flag,
+                max_14bit_constraint_flag,
+            } => {
+                bit_writer.write_bit(*max_12bit_constraint_flag)?;
+                bit_writer.write_bit(*max_10bit_constraint_flag)?;
+                bit_writer.write_bit(*max_8bit_constraint_flag)?;
+                bit_writer.write_bit(*max_422chroma_constraint_flag)?;
+                bit_writer.write_bit(*max_420chroma_constraint_flag)?;
+                bit_writer.write_bit(*max_monochrome_constraint_flag)?;
+                bit_writer.write_bit(*intra_constraint_flag)?;
+                bit_writer.write_bit(*one_picture_only_constraint_flag)?;
+                bit_writer.write_bit(*lower_bit_rate_constraint_flag)?;
+                bit_writer.write_bit(max_14bit_constraint_flag.unwrap_or(false))?;
+                bit_writer.write_bits(0, 33)?;
+            }
+            ProfileAdditionalFlags::Main10Profile {
+                one_picture_only_constraint_flag,
+            } => {
+                bit_writer.write_bits(0, 7)?; // reserved_zero_7bits
+                bit_writer.write_bit(*one_picture_only_constraint_flag)?;
+                bit_writer.write_bits(0, 35)?; // reserved_zero_35bits
+            }
+            ProfileAdditionalFlags::None => {
+                bit_writer.write_bits(0, 43)?; // reserved_zero_43bits
+            }
+        }
+
+        // inbld_flag or reserved_zero_bit
+        bit_writer.write_bit(self.inbld_flag.unwrap_or(false))?;
+
+        Ok(())
+    }
+
     fn merge(self, defaults: &Self) -> Self {
         Self {
             additional_flags: self.additional_flags.merge(&defaults.additional_flags),
@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::BitReader;
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// `ScalingList[0][0..5][i]`
 ///
@@ -71,7 +71,8 @@ impl ScalingListData {
                 if !scaling_list_pred_mode_flag {
                     // the values of the scaling list are the same as the values of a reference scaling list.
 
-                    let scaling_list_pred_matrix_id_delta = bit_reader.read_exp_golomb()? as usize;
+                    let scaling_list_pred_matrix_id_delta =
+                        bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as usize;
 
                     if scaling_list_pred_matrix_id_delta == 0 {
                         // the scaling list is inferred from the default scaling list
@@ -87,9 +88,15 @@ impl ScalingListData {
                         if size_id == 0 {
                             scaling_column[matrix_id][0..16].copy_from_slice(&TABLE_7_5);
                         } else {
-                            let ref_matrix_id = matrix_id
-                                - scaling_list_pred_matrix_id_delta
-                                    * (if size_id == 3 { 3 } else { 1 });
+                            let ref_matrix_id = scaling_list_pred_matrix_id_delta
+                                .checked_mul(if size_id == 3 { 3 } else { 1 })
+                                .and_then(|delta| matrix_id.checked_sub(delta))
+                                .ok_or_else(|| {
+                                    io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        "scaling_list_pred_matrix_id_delta is out of range",
+                                    )
+                                })?;
                             let end = usize::min(63, (1 << (4 + (size_id << 1))) - 1);
                             scaling_column[matrix_id][0..end]
                                 .copy_from_slice(&TABLE_7_6[ref_matrix_id][0..end]);
@@ -102,12 +109,14 @@ impl ScalingListData {
                     let coef_num = usize::min(64, 1 << (4 + (size_id << 1)));
 
                     if size_id > 1 {
-                        let scaling_list_dc_coef_minus8 = bit_reader.read_signed_exp_golomb()?;
+                        let scaling_list_dc_coef_minus8 =
+                            bit_reader.read_signed_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                         next_coef = scaling_list_dc_coef_minus8 + 8;
                     }
 
                     for coef in scaling_column[matrix_id].iter_mut().take(coef_num) {
-                        let scaling_list_delta_coef = bit_reader.read_signed_exp_golomb()?;
+                        let scaling_list_delta_coef =
+                            bit_reader.read_signed_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                         next_coef = (next_coef + scaling_list_delta_coef + 256) % 256;
                         *coef = next_coef;
                     }
@@ -119,4 +128,53 @@ impl ScalingListData {
 
         Ok(Self { scaling_list })
     }
+
+    /// Writes the scaling lists.
+    ///
+    /// A list that is equal to its default is written with `scaling_list_pred_matrix_id_delta` equal to 0,
+    /// every other list is signalled explicitly with `scaling_list_dc_coef_minus8` chosen so that the first
+    /// delta is 0. The prediction mode is not retained when parsing, so this does not always reproduce the
+    /// original bits, but always parses back to the same scaling lists.
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        for (size_id, scaling_column) in self.scaling_list.iter().enumerate() {
+            let mut matrix_id = 0;
+
+            while matrix_id < 6 {
+                let mut default_list = [0; 64];
+                if size_id == 0 {
+                    default_list[0..16].copy_from_slice(&TABLE_7_5);
+                } else {
+                    let end = usize::min(63, (1 << (4 + (size_id << 1))) - 1);
+                    default_list[0..end].copy_from_slice(&TABLE_7_6[matrix_id][0..end]);
+                }
+
+                if scaling_column[matrix_id] == default_list {
+                    bit_writer.write_bit(false)?; // scaling_list_pred_mode_flag
+                    bit_writer.write_exp_golomb(0)?; // scaling_list_pred_matrix_id_delta
+                } else {
+                    bit_writer.write_bit(true)?; // scaling_list_pred_mode_flag
+
+                    let mut next_coef = 8;
+                    let coef_num = usize::min(64, 1 << (4 + (size_id << 1)));
+
+                    if size_id > 1 {
+                        next_coef = scaling_column[matrix_id][0].rem_euclid(256);
+                        bit_writer.write_signed_exp_golomb(next_coef - 8)?; // scaling_list_dc_coef_minus8
+                    }
+
+                    for coef in scaling_column[matrix_id].iter().take(coef_num) {
+                        // The delta is taken modulo 256 and folded into [-128, 127].
+                        let scaling_list_delta_coef =
+                            (coef - next_coef + 128).rem_euclid(256) - 128;
+                        bit_writer.write_signed_exp_golomb(scaling_list_delta_coef)?;
+                        next_coef = coef.rem_euclid(256);
+                    }
+                }
+
+                matrix_id += if size_id == 3 { 3 } else { 1 };
+            }
+        }
+
+        Ok(())
+    }
 }
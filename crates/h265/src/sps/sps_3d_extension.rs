@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Sequence parameter set 3D extension.
 ///
@@ -151,7 +151,8 @@ impl Sps3dExtension {
     ) -> io::Result<Self> {
         let iv_di_mc_enabled_flag = bit_reader.read_bit()?;
         let iv_mv_scal_enabled_flag = bit_reader.read_bit()?;
-        let log2_ivmc_sub_pb_size_minus3 = bit_reader.read_exp_golomb()?;
+        let log2_ivmc_sub_pb_size_minus3 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(
             log2_ivmc_sub_pb_size_minus3,
             min_cb_log2_size_y.saturating_sub(3),
@@ -169,7 +170,8 @@ impl Sps3dExtension {
         };
 
         let tex_mc_enabled_flag = bit_reader.read_bit()?;
-        let log2_texmc_sub_pb_size_minus3 = bit_reader.read_exp_golomb()?;
+        let log2_texmc_sub_pb_size_minus3 =
+            bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
         range_check!(
             log2_texmc_sub_pb_size_minus3,
             min_cb_log2_size_y.saturating_sub(3),
@@ -190,4 +192,23 @@ impl Sps3dExtension {
 
         Ok(Sps3dExtension { d0, d1 })
     }
+
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        bit_writer.write_bit(self.d0.iv_di_mc_enabled_flag)?;
+        bit_writer.write_bit(self.d0.iv_mv_scal_enabled_flag)?;
+        bit_writer.write_exp_golomb(self.d0.log2_ivmc_sub_pb_size_minus3)?;
+        bit_writer.write_bit(self.d0.iv_res_pred_enabled_flag)?;
+        bit_writer.write_bit(self.d0.depth_ref_enabled_flag)?;
+        bit_writer.write_bit(self.d0.vsp_mc_enabled_flag)?;
+        bit_writer.write_bit(self.d0.dbbp_enabled_flag)?;
+
+        bit_writer.write_bit(self.d1.tex_mc_enabled_flag)?;
+        bit_writer.write_exp_golomb(self.d1.log2_texmc_sub_pb_size_minus3)?;
+        bit_writer.write_bit(self.d1.intra_contour_enabled_flag)?;
+        bit_writer.write_bit(self.d1.intra_dc_only_wedge_enabled_flag)?;
+        bit_writer.write_bit(self.d1.cqt_cu_part_pred_enabled_flag)?;
+        bit_writer.write_bit(self.d1.inter_dc_only_enabled_flag)?;
+        bit_writer.write_bit(self.d1.skip_intra_enabled_flag)?;
+        Ok(())
+    }
 }
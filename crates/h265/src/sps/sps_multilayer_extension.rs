@@ -1,6 +1,6 @@
 use std::io;
 
-use bytes_util::BitReader;
+use bytes_util::{BitReader, BitWriter};
 
 /// Sequence parameter set multilayer extension.
 ///
@@ -26,4 +26,8 @@ impl SpsMultilayerExtension {
             inter_view_mv_vert_constraint_flag: bit_reader.read_bit()?,
         })
     }
+
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        bit_writer.write_bit(self.inter_view_mv_vert_constraint_flag)
+    }
 }
@@ -1,6 +1,6 @@
 use std::io;
 
-use bytes_util::BitReader;
+use bytes_util::{BitReader, BitWriter};
 
 /// Sequence parameter set range extension.
 ///
@@ -83,6 +83,19 @@ impl SpsRangeExtension {
         })
     }
 
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        bit_writer.write_bit(self.transform_skip_rotation_enabled_flag)?;
+        bit_writer.write_bit(self.transform_skip_context_enabled_flag)?;
+        bit_writer.write_bit(self.implicit_rdpcm_enabled_flag)?;
+        bit_writer.write_bit(self.explicit_rdpcm_enabled_flag)?;
+        bit_writer.write_bit(self.extended_precision_processing_flag)?;
+        bit_writer.write_bit(self.intra_smoothing_disabled_flag)?;
+        bit_writer.write_bit(self.high_precision_offsets_enabled_flag)?;
+        bit_writer.write_bit(self.persistent_rice_adaptation_enabled_flag)?;
+        bit_writer.write_bit(self.cabac_bypass_alignment_enabled_flag)?;
+        Ok(())
+    }
+
     /// `CoeffMinY = −(1 << (extended_precision_processing_flag ? Max(15, BitDepthY + 6) : 15))` (7-27)
     ///
     /// ISO/IEC 23008-2 - 7.4.3.2.2
@@ -156,7 +169,7 @@ impl SpsRangeExtension {
     /// `WpOffsetHalfRangeY = 1 << (high_precision_offsets_enabled_flag ? (BitDepthY − 1) : 7)` (7-33)
     ///
     /// ISO/IEC 23008-2 - 7.4.3.2.2
-    pub fn wp_offset_half_range_y(&self, bit_depth_y: u8) -> i8 {
+    pub fn wp_offset_half_range_y(&self, bit_depth_y: u8) -> i32 {
         let n = if self.high_precision_offsets_enabled_flag {
             bit_depth_y.saturating_sub(1)
         } else {
@@ -168,7 +181,7 @@ impl SpsRangeExtension {
     /// `WpOffsetHalfRangeC = 1 << (high_precision_offsets_enabled_flag ? (BitDepthC − 1) : 7)` (7-34)
     ///
     /// ISO/IEC 23008-2 - 7.4.3.2.2
-    pub fn wp_offset_half_range_c(&self, bit_depth_c: u8) -> i8 {
+    pub fn wp_offset_half_range_c(&self, bit_depth_c: u8) -> i32 {
         let n = if self.high_precision_offsets_enabled_flag {
             bit_depth_c.saturating_sub(1)
         } else {
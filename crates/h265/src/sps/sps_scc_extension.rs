@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Sequence parameter set screen content coding extension.
 ///
@@ -43,8 +43,9 @@ impl SpsSccExtension {
         let mut palette_mode = None;
         let palette_mode_enabled_flag = bit_reader.read_bit()?;
         if palette_mode_enabled_flag {
-            let palette_max_size = bit_reader.read_exp_golomb()?;
-            let delta_palette_max_predictor_size = bit_reader.read_exp_golomb()?;
+            let palette_max_size = bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            let delta_palette_max_predictor_size =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
             if palette_max_size == 0 && delta_palette_max_predictor_size != 0 {
                 return Err(io::Error::new(
@@ -63,7 +64,8 @@ impl SpsSccExtension {
 
             let mut sps_palette_predictor_initializers = None;
             if sps_palette_predictor_initializers_present_flag {
-                let sps_num_palette_predictor_initializers_minus1 = bit_reader.read_exp_golomb()?;
+                let sps_num_palette_predictor_initializers_minus1 =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
                 if sps_num_palette_predictor_initializers_minus1 >= palette_max_size {
                     return Err(io::Error::new(
@@ -108,6 +110,44 @@ impl SpsSccExtension {
             intra_boundary_filtering_disabled_flag,
         })
     }
+
+    pub(crate) fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        bit_depth_y: u8,
+        bit_depth_c: u8,
+    ) -> io::Result<()> {
+        bit_writer.write_bit(self.sps_curr_pic_ref_enabled_flag)?;
+
+        bit_writer.write_bit(self.palette_mode.is_some())?;
+        if let Some(palette_mode) = &self.palette_mode {
+            bit_writer.write_exp_golomb(palette_mode.palette_max_size)?;
+            bit_writer.write_exp_golomb(palette_mode.delta_palette_max_predictor_size)?;
+
+            bit_writer.write_bit(palette_mode.sps_palette_predictor_initializers.is_some())?;
+            if let Some(initializers) = &palette_mode.sps_palette_predictor_initializers {
+                let num_initializers = initializers.first().map_or(0, |comp| comp.len());
+                if num_initializers == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "sps_palette_predictor_initializers must not be empty",
+                    ));
+                }
+                bit_writer.write_exp_golomb(num_initializers as u64 - 1)?;
+
+                for (comp, initializer) in initializers.iter().enumerate() {
+                    let bit_depth = if comp == 0 { bit_depth_y } else { bit_depth_c };
+                    for sps_palette_predictor_initializer in initializer {
+                        bit_writer.write_bits(*sps_palette_predictor_initializer, bit_depth)?;
+                    }
+                }
+            }
+        }
+
+        bit_writer.write_bits(self.motion_vector_resolution_control_idc as u64, 2)?;
+        bit_writer.write_bit(self.intra_boundary_filtering_disabled_flag)?;
+        Ok(())
+    }
 }
 
 /// Directly part of [`SpsSccExtension`].
@@ -1,8 +1,8 @@
 use std::fmt::Debug;
 use std::io;
 
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Short-term reference picture set syntax.
 ///
@@ -40,10 +40,12 @@ impl ShortTermRefPicSets {
         // num_short_term_ref_pic_sets is bound above by 64
         let mut num_positive_pics = vec![0u64; num_short_term_ref_pic_sets];
         let mut num_negative_pics = vec![0u64; num_short_term_ref_pic_sets];
-        let mut delta_poc_s1 = Vec::with_capacity(num_short_term_ref_pic_sets);
-        let mut delta_poc_s0 = Vec::with_capacity(num_short_term_ref_pic_sets);
-        let mut used_by_curr_pic_s0 = Vec::with_capacity(num_short_term_ref_pic_sets);
-        let mut used_by_curr_pic_s1 = Vec::with_capacity(num_short_term_ref_pic_sets);
+        let mut delta_poc_s1: Vec<Vec<i64>> = Vec::with_capacity(num_short_term_ref_pic_sets);
+        let mut delta_poc_s0: Vec<Vec<i64>> = Vec::with_capacity(num_short_term_ref_pic_sets);
+        let mut used_by_curr_pic_s0: Vec<Vec<bool>> =
+            Vec::with_capacity(num_short_term_ref_pic_sets);
+        let mut used_by_curr_pic_s1: Vec<Vec<bool>> =
+            Vec::with_capacity(num_short_term_ref_pic_sets);
 
         for st_rps_idx in 0..num_short_term_ref_pic_sets {
             let mut inter_ref_pic_set_prediction_flag = false;
@@ -54,7 +56,8 @@ impl ShortTermRefPicSets {
             if inter_ref_pic_set_prediction_flag {
                 let mut delta_idx_minus1 = 0;
                 if st_rps_idx == num_short_term_ref_pic_sets {
-                    delta_idx_minus1 = bit_reader.read_exp_golomb()? as usize;
+                    delta_idx_minus1 =
+                        bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)? as usize;
                     range_check!(delta_idx_minus1, 0, st_rps_idx - 1)?;
                 }
 
@@ -62,7 +65,8 @@ impl ShortTermRefPicSets {
                 let ref_rps_idx = st_rps_idx - (delta_idx_minus1 + 1);
 
                 let delta_rps_sign = bit_reader.read_bit()?;
-                let abs_delta_rps_minus1 = bit_reader.read_exp_golomb()?;
+                let abs_delta_rps_minus1 =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                 range_check!(abs_delta_rps_minus1, 0, 2u64.pow(15) - 1)?;
                 // (7-60)
                 let delta_rps = (1 - 2 * delta_rps_sign as i64) * (abs_delta_rps_minus1 + 1) as i64;
@@ -78,93 +82,37 @@ impl ShortTermRefPicSets {
                     }
                 }
 
-                delta_poc_s0.push(vec![0; len]);
-                delta_poc_s1.push(vec![0; len]);
-                used_by_curr_pic_s0.push(vec![false; len]);
-                used_by_curr_pic_s1.push(vec![false; len]);
-
-                // Calculate derived values as defined as (7-61) and (7-62) by the spec
-                let mut i = 0;
-                if let Some(start) = num_positive_pics[ref_rps_idx]
-                    .checked_sub(1)
-                    .map(|s| s as usize)
-                {
-                    for j in (0..=start).rev() {
-                        let d_poc = delta_poc_s1[ref_rps_idx][j] + delta_rps;
-                        if d_poc < 0 && use_delta_flag[num_negative_pics[ref_rps_idx] as usize + j]
-                        {
-                            delta_poc_s0[st_rps_idx][i] = d_poc;
-                            used_by_curr_pic_s0[st_rps_idx][i] =
-                                used_by_curr_pic_flag[num_negative_pics[ref_rps_idx] as usize + j];
-                            i += 1;
-                        }
-                    }
-                }
+                let derived = InterRefPicSet::derive(
+                    num_negative_pics[ref_rps_idx] as usize,
+                    &delta_poc_s0[ref_rps_idx],
+                    num_positive_pics[ref_rps_idx] as usize,
+                    &delta_poc_s1[ref_rps_idx],
+                    delta_rps,
+                    &used_by_curr_pic_flag,
+                    &use_delta_flag,
+                );
 
-                if delta_rps < 0 && use_delta_flag[num_delta_pocs[ref_rps_idx] as usize] {
-                    delta_poc_s0[st_rps_idx][i] = delta_rps;
-                    used_by_curr_pic_s0[st_rps_idx][i] =
-                        used_by_curr_pic_flag[num_delta_pocs[ref_rps_idx] as usize];
-                    i += 1;
-                }
-
-                for j in 0..num_negative_pics[ref_rps_idx] as usize {
-                    let d_poc = delta_poc_s0[ref_rps_idx][j] + delta_rps;
-                    if d_poc < 0 && use_delta_flag[j] {
-                        delta_poc_s0[st_rps_idx][i] = d_poc;
-                        used_by_curr_pic_s0[st_rps_idx][i] = used_by_curr_pic_flag[j];
-                        i += 1;
-                    }
-                }
-
-                num_negative_pics[st_rps_idx] = i as u64;
+                num_negative_pics[st_rps_idx] = derived.num_negative_pics;
+                num_positive_pics[st_rps_idx] = derived.num_positive_pics;
                 // This is a sanity check just for safety, it should be unreachable
                 // num_negative_pics is said to be bound by
                 // sps_max_dec_pic_buffering_minus1[sps_max_sub_layers_minus1]
                 // which itself is bound by 16
                 range_check!(num_negative_pics[st_rps_idx], 0, 16)?;
-
-                i = 0;
-                if let Some(start) = num_negative_pics[ref_rps_idx]
-                    .checked_sub(1)
-                    .map(|s| s as usize)
-                {
-                    for j in (0..=start).rev() {
-                        let d_poc = delta_poc_s0[ref_rps_idx][j] + delta_rps;
-                        if d_poc > 0 && use_delta_flag[j] {
-                            delta_poc_s1[st_rps_idx][i] = d_poc;
-                            used_by_curr_pic_s1[st_rps_idx][i] = used_by_curr_pic_flag[j];
-                            i += 1;
-                        }
-                    }
-                }
-
-                if delta_rps > 0 && use_delta_flag[num_delta_pocs[ref_rps_idx] as usize] {
-                    delta_poc_s1[st_rps_idx][i] = delta_rps;
-                    used_by_curr_pic_s1[st_rps_idx][i] =
-                        used_by_curr_pic_flag[num_delta_pocs[ref_rps_idx] as usize];
-                    i += 1;
-                }
-
-                for j in 0..num_positive_pics[ref_rps_idx] as usize {
-                    let d_poc = delta_poc_s1[ref_rps_idx][j] + delta_rps;
-                    if d_poc > 0 && use_delta_flag[num_negative_pics[ref_rps_idx] as usize + j] {
-                        delta_poc_s1[st_rps_idx][i] = d_poc;
-                        used_by_curr_pic_s1[st_rps_idx][i] =
-                            used_by_curr_pic_flag[num_negative_pics[ref_rps_idx] as usize + j];
-                        i += 1;
-                    }
-                }
-
-                num_positive_pics[st_rps_idx] = i as u64;
-                // This is a sanity check just for safety, it should be unreachable
                 // num_positive_pics is said to be bound by
                 // sps_max_dec_pic_buffering_minus1[sps_max_sub_layers_minus1] - num_negative_pics
                 // which itself is bound by 16
-                range_check!(num_negative_pics[st_rps_idx], 0, 16)?;
+                range_check!(num_positive_pics[st_rps_idx], 0, 16)?;
+
+                delta_poc_s0.push(derived.delta_poc_s0);
+                delta_poc_s1.push(derived.delta_poc_s1);
+                used_by_curr_pic_s0.push(derived.used_by_curr_pic_s0);
+                used_by_curr_pic_s1.push(derived.used_by_curr_pic_s1);
             } else {
-                num_negative_pics[st_rps_idx] = bit_reader.read_exp_golomb()?;
-                num_positive_pics[st_rps_idx] = bit_reader.read_exp_golomb()?;
+                num_negative_pics[st_rps_idx] =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+                num_positive_pics[st_rps_idx] =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
                 let upper_bound = if nuh_layer_id == 0 {
                     // bound above by 16
@@ -187,7 +135,8 @@ impl ShortTermRefPicSets {
                 used_by_curr_pic_s0.push(vec![false; num_negative_pics[st_rps_idx] as usize]);
 
                 for i in 0..num_negative_pics[st_rps_idx] as usize {
-                    let delta_poc_s0_minus1 = bit_reader.read_exp_golomb()?;
+                    let delta_poc_s0_minus1 =
+                        bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                     range_check!(delta_poc_s0_minus1, 0, 2u64.pow(15) - 1)?;
                     if i == 0 {
                         // (7-67)
@@ -206,7 +155,8 @@ impl ShortTermRefPicSets {
                 used_by_curr_pic_s1.push(vec![false; num_positive_pics[st_rps_idx] as usize]);
 
                 for i in 0..num_positive_pics[st_rps_idx] as usize {
-                    let delta_poc_s1_minus1 = bit_reader.read_exp_golomb()?;
+                    let delta_poc_s1_minus1 =
+                        bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                     range_check!(delta_poc_s1_minus1, 0, 2u64.pow(15) - 1)?;
                     if i == 0 {
                         // (7-68)
@@ -238,4 +188,279 @@ impl ShortTermRefPicSets {
             used_by_curr_pic_s1,
         })
     }
+
+    /// Writes all short-term reference picture sets, excluding `num_short_term_ref_pic_sets`.
+    ///
+    /// A set is written using inter RPS prediction from the previous set whenever that reproduces it exactly,
+    /// otherwise its delta POCs are written explicitly.
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        let num_short_term_ref_pic_sets = self.num_delta_pocs.len();
+        if [
+            self.num_positive_pics.len(),
+            self.num_negative_pics.len(),
+            self.delta_poc_s0.len(),
+            self.delta_poc_s1.len(),
+            self.used_by_curr_pic_s0.len(),
+            self.used_by_curr_pic_s1.len(),
+        ]
+        .iter()
+        .any(|len| *len != num_short_term_ref_pic_sets)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "all short-term reference picture set vectors must have the same length",
+            ));
+        }
+
+        for st_rps_idx in 0..num_short_term_ref_pic_sets {
+            if st_rps_idx != 0 {
+                let prediction = self.find_inter_prediction(st_rps_idx);
+                // inter_ref_pic_set_prediction_flag
+                bit_writer.write_bit(prediction.is_some())?;
+
+                if let Some((delta_rps, used_by_curr_pic_flag, use_delta_flag)) = prediction {
+                    // delta_idx_minus1 is not present because stRpsIdx is less than num_short_term_ref_pic_sets
+                    bit_writer.write_bit(delta_rps < 0)?; // delta_rps_sign
+                    bit_writer.write_exp_golomb(delta_rps.unsigned_abs() - 1)?; // abs_delta_rps_minus1
+
+                    for (used_by_curr_pic_flag, use_delta_flag) in
+                        used_by_curr_pic_flag.into_iter().zip(use_delta_flag)
+                    {
+                        bit_writer.write_bit(used_by_curr_pic_flag)?;
+                        if !used_by_curr_pic_flag {
+                            bit_writer.write_bit(use_delta_flag)?;
+                        }
+                    }
+
+                    continue;
+                }
+            }
+
+            let (delta_poc_s0, used_by_curr_pic_s0) = self.negative_pics(st_rps_idx)?;
+            let (delta_poc_s1, used_by_curr_pic_s1) = self.positive_pics(st_rps_idx)?;
+
+            bit_writer.write_exp_golomb(delta_poc_s0.len() as u64)?; // num_negative_pics
+            bit_writer.write_exp_golomb(delta_poc_s1.len() as u64)?; // num_positive_pics
+
+            let mut prev = 0;
+            for (delta_poc, used_by_curr_pic) in delta_poc_s0.iter().zip(used_by_curr_pic_s0) {
+                // inverse of (7-67) and (7-69)
+                let delta_poc_s0_minus1 = prev - delta_poc - 1;
+                if delta_poc_s0_minus1 < 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "delta_poc_s0 must be strictly decreasing and negative",
+                    ));
+                }
+                bit_writer.write_exp_golomb(delta_poc_s0_minus1 as u64)?;
+                bit_writer.write_bit(*used_by_curr_pic)?;
+                prev = *delta_poc;
+            }
+
+            let mut prev = 0;
+            for (delta_poc, used_by_curr_pic) in delta_poc_s1.iter().zip(used_by_curr_pic_s1) {
+                // inverse of (7-68) and (7-70)
+                let delta_poc_s1_minus1 = delta_poc - prev - 1;
+                if delta_poc_s1_minus1 < 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "delta_poc_s1 must be strictly increasing and positive",
+                    ));
+                }
+                bit_writer.write_exp_golomb(delta_poc_s1_minus1 as u64)?;
+                bit_writer.write_bit(*used_by_curr_pic)?;
+                prev = *delta_poc;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `DeltaPocS0[stRpsIdx]` and `UsedByCurrPicS0[stRpsIdx]`, limited to `NumNegativePics[stRpsIdx]` entries.
+    fn negative_pics(&self, st_rps_idx: usize) -> io::Result<(&[i64], &[bool])> {
+        let num_negative_pics = self.num_negative_pics[st_rps_idx] as usize;
+        self.delta_poc_s0[st_rps_idx]
+            .get(..num_negative_pics)
+            .zip(self.used_by_curr_pic_s0[st_rps_idx].get(..num_negative_pics))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "delta_poc_s0 and used_by_curr_pic_s0 must have at least num_negative_pics entries",
+                )
+            })
+    }
+
+    /// `DeltaPocS1[stRpsIdx]` and `UsedByCurrPicS1[stRpsIdx]`, limited to `NumPositivePics[stRpsIdx]` entries.
+    fn positive_pics(&self, st_rps_idx: usize) -> io::Result<(&[i64], &[bool])> {
+        let num_positive_pics = self.num_positive_pics[st_rps_idx] as usize;
+        self.delta_poc_s1[st_rps_idx]
+            .get(..num_positive_pics)
+            .zip(self.used_by_curr_pic_s1[st_rps_idx].get(..num_positive_pics))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "delta_poc_s1 and used_by_curr_pic_s1 must have at least num_positive_pics entries",
+                )
+            })
+    }
+
+    /// Finds `deltaRps`, `used_by_curr_pic_flag` and `use_delta_flag` which derive exactly
+    /// the set at `st_rps_idx` from the set at `st_rps_idx - 1`.
+    #[allow(clippy::type_complexity)]
+    fn find_inter_prediction(&self, st_rps_idx: usize) -> Option<(i64, Vec<bool>, Vec<bool>)> {
+        let ref_rps_idx = st_rps_idx - 1;
+        let (ref_delta_poc_s0, _) = self.negative_pics(ref_rps_idx).ok()?;
+        let (ref_delta_poc_s1, _) = self.positive_pics(ref_rps_idx).ok()?;
+        let (delta_poc_s0, used_by_curr_pic_s0) = self.negative_pics(st_rps_idx).ok()?;
+        let (delta_poc_s1, used_by_curr_pic_s1) = self.positive_pics(st_rps_idx).ok()?;
+
+        // The reference delta POCs indexed by j, the last entry (j = NumDeltaPocs[RefRpsIdx]) is deltaRps itself.
+        let ref_delta_pocs: Vec<i64> = ref_delta_poc_s0
+            .iter()
+            .chain(ref_delta_poc_s1)
+            .copied()
+            .chain(std::iter::once(0))
+            .collect();
+        let target: Vec<(i64, bool)> = delta_poc_s0
+            .iter()
+            .zip(used_by_curr_pic_s0)
+            .chain(delta_poc_s1.iter().zip(used_by_curr_pic_s1))
+            .map(|(delta_poc, used)| (*delta_poc, *used))
+            .collect();
+
+        let mut candidates: Vec<i64> = target
+            .iter()
+            .flat_map(|(delta_poc, _)| ref_delta_pocs.iter().map(move |r| delta_poc - r))
+            .chain([-1, 1])
+            .filter(|delta_rps| *delta_rps != 0 && delta_rps.unsigned_abs() <= 1 << 15)
+            .collect();
+        candidates.sort_by_key(|delta_rps| (delta_rps.unsigned_abs(), *delta_rps > 0));
+        candidates.dedup();
+
+        candidates.into_iter().find_map(|delta_rps| {
+            let (used_by_curr_pic_flag, use_delta_flag): (Vec<bool>, Vec<bool>) = ref_delta_pocs
+                .iter()
+                .map(|r| {
+                    target
+                        .iter()
+                        .find(|(delta_poc, _)| *delta_poc == r + delta_rps)
+                        .map_or((false, false), |(_, used)| (*used, true))
+                })
+                .unzip();
+
+            let derived = InterRefPicSet::derive(
+                ref_delta_poc_s0.len(),
+                ref_delta_poc_s0,
+                ref_delta_poc_s1.len(),
+                ref_delta_poc_s1,
+                delta_rps,
+                &used_by_curr_pic_flag,
+                &use_delta_flag,
+            );
+
+            (derived.num_negative_pics == self.num_negative_pics[st_rps_idx]
+                && derived.num_positive_pics == self.num_positive_pics[st_rps_idx]
+                && derived.delta_poc_s0 == self.delta_poc_s0[st_rps_idx]
+                && derived.delta_poc_s1 == self.delta_poc_s1[st_rps_idx]
+                && derived.used_by_curr_pic_s0 == self.used_by_curr_pic_s0[st_rps_idx]
+                && derived.used_by_curr_pic_s1 == self.used_by_curr_pic_s1[st_rps_idx])
+                .then_some((delta_rps, used_by_curr_pic_flag, use_delta_flag))
+        })
+    }
+}
+
+/// A short-term reference picture set derived by inter RPS prediction.
+struct InterRefPicSet {
+    num_negative_pics: u64,
+    num_positive_pics: u64,
+    delta_poc_s0: Vec<i64>,
+    delta_poc_s1: Vec<i64>,
+    used_by_curr_pic_s0: Vec<bool>,
+    used_by_curr_pic_s1: Vec<bool>,
+}
+
+impl InterRefPicSet {
+    /// Calculates the derived values as defined as (7-61) and (7-62) by the spec.
+    ///
+    /// The returned vectors have `NumDeltaPocs[RefRpsIdx] + 1` entries.
+    fn derive(
+        ref_num_negative_pics: usize,
+        ref_delta_poc_s0: &[i64],
+        ref_num_positive_pics: usize,
+        ref_delta_poc_s1: &[i64],
+        delta_rps: i64,
+        used_by_curr_pic_flag: &[bool],
+        use_delta_flag: &[bool],
+    ) -> Self {
+        let ref_num_delta_pocs = ref_num_negative_pics + ref_num_positive_pics;
+        let len = ref_num_delta_pocs + 1;
+
+        let mut delta_poc_s0 = vec![0; len];
+        let mut delta_poc_s1 = vec![0; len];
+        let mut used_by_curr_pic_s0 = vec![false; len];
+        let mut used_by_curr_pic_s1 = vec![false; len];
+
+        // (7-61)
+        let mut i = 0;
+        for j in (0..ref_num_positive_pics).rev() {
+            let d_poc = ref_delta_poc_s1[j] + delta_rps;
+            if d_poc < 0 && use_delta_flag[ref_num_negative_pics + j] {
+                delta_poc_s0[i] = d_poc;
+                used_by_curr_pic_s0[i] = used_by_curr_pic_flag[ref_num_negative_pics + j];
+                i += 1;
+            }
+        }
+
+        if delta_rps < 0 && use_delta_flag[ref_num_delta_pocs] {
+            delta_poc_s0[i] = delta_rps;
+            used_by_curr_pic_s0[i] = used_by_curr_pic_flag[ref_num_delta_pocs];
+            i += 1;
+        }
+
+        for j in 0..ref_num_negative_pics {
+            let d_poc = ref_delta_poc_s0[j] + delta_rps;
+            if d_poc < 0 && use_delta_flag[j] {
+                delta_poc_s0[i] = d_poc;
+                used_by_curr_pic_s0[i] = used_by_curr_pic_flag[j];
+                i += 1;
+            }
+        }
+
+        let num_negative_pics = i as u64;
+
+        // (7-62)
+        i = 0;
+        for j in (0..ref_num_negative_pics).rev() {
+            let d_poc = ref_delta_poc_s0[j] + delta_rps;
+            if d_poc > 0 && use_delta_flag[j] {
+                delta_poc_s1[i] = d_poc;
+                used_by_curr_pic_s1[i] = used_by_curr_pic_flag[j];
+                i += 1;
+            }
+        }
+
+        if delta_rps > 0 && use_delta_flag[ref_num_delta_pocs] {
+            delta_poc_s1[i] = delta_rps;
+            used_by_curr_pic_s1[i] = used_by_curr_pic_flag[ref_num_delta_pocs];
+            i += 1;
+        }
+
+        for j in 0..ref_num_positive_pics {
+            let d_poc = ref_delta_poc_s1[j] + delta_rps;
+            if d_poc > 0 && use_delta_flag[ref_num_negative_pics + j] {
+                delta_poc_s1[i] = d_poc;
+                used_by_curr_pic_s1[i] = used_by_curr_pic_flag[ref_num_negative_pics + j];
+                i += 1;
+            }
+        }
+
+        Self {
+            num_negative_pics,
+            num_positive_pics: i as u64,
+            delta_poc_s0,
+            delta_poc_s1,
+            used_by_curr_pic_s0,
+            used_by_curr_pic_s1,
+        }
+    }
 }
@@ -1,7 +1,7 @@
 use std::io;
 
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// Info for each sub-layer in the SPS.
 ///
@@ -35,7 +35,8 @@ impl SubLayerOrderingInfo {
 
         if sps_sub_layer_ordering_info_present_flag {
             for i in 0..=sps_max_sub_layers_minus1 as usize {
-                sps_max_dec_pic_buffering_minus1[i] = bit_reader.read_exp_golomb()?;
+                sps_max_dec_pic_buffering_minus1[i] =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                 // (A-2) defines MaxDpbSize which is always at most 16
                 range_check!(sps_max_dec_pic_buffering_minus1[i], 0, 16)?;
                 if i > 0
@@ -47,7 +48,8 @@ impl SubLayerOrderingInfo {
                     ));
                 }
 
-                sps_max_num_reorder_pics[i] = bit_reader.read_exp_golomb()?;
+                sps_max_num_reorder_pics[i] =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                 range_check!(
                     sps_max_num_reorder_pics[i],
                     0,
@@ -60,7 +62,8 @@ impl SubLayerOrderingInfo {
                     ));
                 }
 
-                let sps_max_latency_increase_plus1_i = bit_reader.read_exp_golomb()?;
+                let sps_max_latency_increase_plus1_i =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                 range_check!(sps_max_latency_increase_plus1_i, 0, 2u64.pow(32) - 2)?;
                 sps_max_latency_increase_plus1[i] = sps_max_latency_increase_plus1_i as u32;
             }
@@ -70,12 +73,14 @@ impl SubLayerOrderingInfo {
             // sps_sub_layer_ordering_info_present_flag being equal to 0, it is inferred to be equal to
             // sps_max_dec_pic_buffering_minus1[sps_max_sub_layers_minus1].
 
-            let sps_max_dec_pic_buffering_minus1_i = bit_reader.read_exp_golomb()?;
+            let sps_max_dec_pic_buffering_minus1_i =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             // (A-2) defines MaxDpbSize which is always at most 16
             range_check!(sps_max_dec_pic_buffering_minus1_i, 0, 16)?;
             sps_max_dec_pic_buffering_minus1.fill(sps_max_dec_pic_buffering_minus1_i);
 
-            let sps_max_num_reorder_pics_i = bit_reader.read_exp_golomb()?;
+            let sps_max_num_reorder_pics_i =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(
                 sps_max_num_reorder_pics_i,
                 0,
@@ -83,7 +88,8 @@ impl SubLayerOrderingInfo {
             )?;
             sps_max_num_reorder_pics.fill(sps_max_num_reorder_pics_i);
 
-            let sps_max_latency_increase_plus1_i = bit_reader.read_exp_golomb()?;
+            let sps_max_latency_increase_plus1_i =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(sps_max_latency_increase_plus1_i, 0, 2u64.pow(32) - 2)?;
             sps_max_latency_increase_plus1.fill(sps_max_latency_increase_plus1_i as u32);
         }
@@ -95,6 +101,33 @@ impl SubLayerOrderingInfo {
         })
     }
 
+    /// Writes `sps_sub_layer_ordering_info_present_flag` followed by the values for every sub-layer.
+    ///
+    /// The values are always written for every sub-layer, because it is not known whether they were
+    /// inferred when parsing.
+    pub(crate) fn build<W: io::Write>(&self, bit_writer: &mut BitWriter<W>) -> io::Result<()> {
+        if self.sps_max_dec_pic_buffering_minus1.len() != self.sps_max_num_reorder_pics.len()
+            || self.sps_max_dec_pic_buffering_minus1.len()
+                != self.sps_max_latency_increase_plus1.len()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "all sub-layer ordering info vectors must have the same length",
+            ));
+        }
+
+        // sps_sub_layer_ordering_info_present_flag
+        bit_writer.write_bit(true)?;
+
+        for i in 0..self.sps_max_dec_pic_buffering_minus1.len() {
+            bit_writer.write_exp_golomb(self.sps_max_dec_pic_buffering_minus1[i])?;
+            bit_writer.write_exp_golomb(self.sps_max_num_reorder_pics[i])?;
+            bit_writer.write_exp_golomb(self.sps_max_latency_increase_plus1[i] as u64)?;
+        }
+
+        Ok(())
+    }
+
     /// Specifies the maximum number of pictures with `PicOutputFlag` equal
     /// to 1 that can precede any picture with `PicOutputFlag` equal to 1 in the CVS in output order and follow that
     /// picture with `PicOutputFlag` equal to 1 in decoding order when `HighestTid` is equal to i.
@@ -1,8 +1,8 @@
 use std::io;
 
 use byteorder::ReadBytesExt;
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 /// HRD parameters.
 ///
@@ -85,6 +85,71 @@ impl HrdParameters {
             sub_layers,
         })
     }
+
+    pub(crate) fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        common_inf_present_flag: bool,
+        max_num_sub_layers_minus1: u8,
+    ) -> io::Result<()> {
+        if self.sub_layers.len() != max_num_sub_layers_minus1 as usize + 1 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sub_layers must have max_num_sub_layers_minus1 + 1 entries",
+            ));
+        }
+
+        // Every sub-layer contains the NAL parameters followed by the VCL parameters.
+        let sub_layer_parameters = self
+            .sub_layers
+            .first()
+            .map(|sub_layer| sub_layer.sub_layer_parameters.as_slice())
+            .unwrap_or_default();
+        let nal_hrd_parameters_present_flag = sub_layer_parameters.iter().any(|p| p.nal_hrd);
+        let vcl_hrd_parameters_present_flag = sub_layer_parameters.iter().any(|p| !p.nal_hrd);
+
+        if common_inf_present_flag {
+            bit_writer.write_bit(nal_hrd_parameters_present_flag)?;
+            bit_writer.write_bit(vcl_hrd_parameters_present_flag)?;
+
+            if nal_hrd_parameters_present_flag || vcl_hrd_parameters_present_flag {
+                let common_inf = &self.common_inf;
+
+                bit_writer.write_bit(common_inf.sub_pic_hrd_params.is_some())?;
+                if let Some(sub_pic_hrd_params) = &common_inf.sub_pic_hrd_params {
+                    bit_writer.write_bits(sub_pic_hrd_params.tick_divisor_minus2 as u64, 8)?;
+                    bit_writer.write_bits(
+                        sub_pic_hrd_params.du_cpb_removal_delay_increment_length_minus1 as u64,
+                        5,
+                    )?;
+                    bit_writer
+                        .write_bit(sub_pic_hrd_params.sub_pic_cpb_params_in_pic_timing_sei_flag)?;
+                    bit_writer.write_bits(
+                        sub_pic_hrd_params.dpb_output_delay_du_length_minus1 as u64,
+                        5,
+                    )?;
+                }
+
+                bit_writer.write_bits(common_inf.bit_rate_scale.unwrap_or(0) as u64, 4)?;
+                bit_writer.write_bits(common_inf.cpb_size_scale.unwrap_or(0) as u64, 4)?;
+
+                if let Some(sub_pic_hrd_params) = &common_inf.sub_pic_hrd_params {
+                    bit_writer.write_bits(sub_pic_hrd_params.cpb_size_du_scale as u64, 4)?;
+                }
+
+                bit_writer
+                    .write_bits(common_inf.initial_cpb_removal_delay_length_minus1 as u64, 5)?;
+                bit_writer.write_bits(common_inf.au_cpb_removal_delay_length_minus1 as u64, 5)?;
+                bit_writer.write_bits(common_inf.dpb_output_delay_length_minus1 as u64, 5)?;
+            }
+        }
+
+        for sub_layer in &self.sub_layers {
+            sub_layer.build(bit_writer, self.common_inf.sub_pic_hrd_params.is_some())?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Directly part of [`HrdParameters`].
@@ -201,7 +266,8 @@ impl HrdParametersSubLayer {
         let mut elemental_duration_in_tc_minus1_value = None;
         let mut low_delay_hrd_flag = false;
         if fixed_pic_rate_within_cvs_flag {
-            let elemental_duration_in_tc_minus1 = bit_reader.read_exp_golomb()?;
+            let elemental_duration_in_tc_minus1 =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(elemental_duration_in_tc_minus1, 0, 2047)?;
             elemental_duration_in_tc_minus1_value = Some(elemental_duration_in_tc_minus1);
         } else {
@@ -210,7 +276,7 @@ impl HrdParametersSubLayer {
 
         let mut cpb_cnt_minus1 = 0;
         if !low_delay_hrd_flag {
-            cpb_cnt_minus1 = bit_reader.read_exp_golomb()?;
+            cpb_cnt_minus1 = bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(cpb_cnt_minus1, 0, 31)?;
         }
 
@@ -243,6 +309,41 @@ impl HrdParametersSubLayer {
             sub_layer_parameters,
         })
     }
+
+    fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        sub_pic_hrd_params_present_flag: bool,
+    ) -> io::Result<()> {
+        bit_writer.write_bit(self.fixed_pic_rate_general_flag)?;
+        if !self.fixed_pic_rate_general_flag {
+            bit_writer.write_bit(self.fixed_pic_rate_within_cvs_flag)?;
+        }
+
+        if self.fixed_pic_rate_general_flag || self.fixed_pic_rate_within_cvs_flag {
+            let elemental_duration_in_tc_minus1 =
+                self.elemental_duration_in_tc_minus1.ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "elemental_duration_in_tc_minus1 must be present when the picture rate is fixed",
+                    )
+                })?;
+            bit_writer.write_exp_golomb(elemental_duration_in_tc_minus1)?;
+        } else {
+            bit_writer.write_bit(self.low_delay_hrd_flag)?;
+        }
+
+        if !self.low_delay_hrd_flag {
+            bit_writer.write_exp_golomb(self.cpb_cnt_minus1)?;
+        }
+
+        // NAL parameters come first, see `parse`
+        for parameters in &self.sub_layer_parameters {
+            parameters.build(bit_writer, sub_pic_hrd_params_present_flag)?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Sub-layer HRD parameters.
@@ -308,7 +409,8 @@ impl SubLayerHrdParameters {
         let mut parameters: Vec<Self> = Vec::with_capacity(cpb_cnt as usize);
 
         for i in 0..cpb_cnt as usize {
-            let bit_rate_value_minus1 = bit_reader.read_exp_golomb()?;
+            let bit_rate_value_minus1 =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(bit_rate_value_minus1, 0, 2u64.pow(32) - 2)?;
             let bit_rate_value_minus1 = bit_rate_value_minus1 as u32;
             if i > 0 && bit_rate_value_minus1 <= parameters[i - 1].bit_rate_value_minus1 {
@@ -318,7 +420,8 @@ impl SubLayerHrdParameters {
                 ));
             }
 
-            let cpb_size_value_minus1 = bit_reader.read_exp_golomb()?;
+            let cpb_size_value_minus1 =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(cpb_size_value_minus1, 0, 2u64.pow(32) - 2)?;
             let cpb_size_value_minus1 = cpb_size_value_minus1 as u32;
             if i > 0 && cpb_size_value_minus1 > parameters[i - 1].cpb_size_value_minus1 {
@@ -331,8 +434,10 @@ impl SubLayerHrdParameters {
             let mut cpb_size_du_value_minus1 = None;
             let mut bit_rate_du_value_minus1 = None;
             if sub_pic_hrd_params_present_flag {
-                cpb_size_du_value_minus1 = Some(bit_reader.read_exp_golomb()?);
-                bit_rate_du_value_minus1 = Some(bit_reader.read_exp_golomb()?);
+                cpb_size_du_value_minus1 =
+                    Some(bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?);
+                bit_rate_du_value_minus1 =
+                    Some(bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?);
             }
 
             let cbr_flag = bit_reader.read_bit()?;
@@ -350,6 +455,24 @@ impl SubLayerHrdParameters {
         Ok(parameters)
     }
 
+    fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        sub_pic_hrd_params_present_flag: bool,
+    ) -> io::Result<()> {
+        bit_writer.write_exp_golomb(self.bit_rate_value_minus1 as u64)?;
+        bit_writer.write_exp_golomb(self.cpb_size_value_minus1 as u64)?;
+
+        if sub_pic_hrd_params_present_flag {
+            bit_writer.write_exp_golomb(self.cpb_size_du_value_minus1.unwrap_or(0))?;
+            bit_writer.write_exp_golomb(self.bit_rate_du_value_minus1.unwrap_or(0))?;
+        }
+
+        bit_writer.write_bit(self.cbr_flag)?;
+
+        Ok(())
+    }
+
     /// When `SubPicHrdFlag` is equal to `false`, the bit rate in bits per second is given by:
     /// `BitRate[i] = (bit_rate_value_minus1[ i ] + 1) * 2^(6 + bit_rate_scale)` (E-77)
     ///
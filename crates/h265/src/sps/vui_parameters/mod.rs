@@ -2,8 +2,8 @@ use std::io;
 use std::num::NonZero;
 
 use byteorder::{BigEndian, ReadBytesExt};
-use bytes_util::{BitReader, range_check};
-use expgolomb::BitReaderExpGolombExt;
+use bytes_util::{BitReader, BitWriter, range_check};
+use expgolomb::{BitReaderExpGolombExt, BitWriterExpGolombExt, DEFAULT_MAX_LEADING_ZEROS};
 
 use super::{ConformanceWindow, Profile};
 use crate::{AspectRatioIdc, VideoFormat};
@@ -177,8 +177,10 @@ impl VuiParameters {
         }
 
         if chroma_loc_info_present_flag {
-            let chroma_sample_loc_type_top_field = bit_reader.read_exp_golomb()?;
-            let chroma_sample_loc_type_bottom_field = bit_reader.read_exp_golomb()?;
+            let chroma_sample_loc_type_top_field =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            let chroma_sample_loc_type_bottom_field =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
 
             chroma_loc_info = Some(ChromaLocInfo {
                 top_field: chroma_sample_loc_type_top_field,
@@ -211,10 +213,14 @@ impl VuiParameters {
 
         let default_display_window_flag = bit_reader.read_bit()?;
         if default_display_window_flag {
-            let def_disp_win_left_offset = bit_reader.read_exp_golomb()?;
-            let def_disp_win_right_offset = bit_reader.read_exp_golomb()?;
-            let def_disp_win_top_offset = bit_reader.read_exp_golomb()?;
-            let def_disp_win_bottom_offset = bit_reader.read_exp_golomb()?;
+            let def_disp_win_left_offset =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            let def_disp_win_right_offset =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            let def_disp_win_top_offset =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
+            let def_disp_win_bottom_offset =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             let left_offset = conformance_window.conf_win_left_offset + def_disp_win_left_offset;
             let right_offset = conformance_window.conf_win_right_offset + def_disp_win_right_offset;
             let top_offset = conformance_window.conf_win_top_offset + def_disp_win_top_offset;
@@ -262,7 +268,8 @@ impl VuiParameters {
             let mut num_ticks_poc_diff_one_minus1 = None;
             let vui_poc_proportional_to_timing_flag = bit_reader.read_bit()?;
             if vui_poc_proportional_to_timing_flag {
-                let vui_num_ticks_poc_diff_one_minus1 = bit_reader.read_exp_golomb()?;
+                let vui_num_ticks_poc_diff_one_minus1 =
+                    bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
                 range_check!(vui_num_ticks_poc_diff_one_minus1, 0, 2u64.pow(32) - 2)?;
                 num_ticks_poc_diff_one_minus1 = Some(vui_num_ticks_poc_diff_one_minus1 as u32);
             }
@@ -294,25 +301,30 @@ impl VuiParameters {
                 bit_reader.read_bit()?;
             bitstream_restriction.restricted_ref_pic_lists_flag = Some(bit_reader.read_bit()?);
 
-            let min_spatial_segmentation_idc = bit_reader.read_exp_golomb()?;
+            let min_spatial_segmentation_idc =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(min_spatial_segmentation_idc, 0, 4095)?;
             bitstream_restriction.min_spatial_segmentation_idc =
                 min_spatial_segmentation_idc as u16;
 
-            let max_bytes_per_pic_denom = bit_reader.read_exp_golomb()?;
+            let max_bytes_per_pic_denom =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(max_bytes_per_pic_denom, 0, 16)?;
             bitstream_restriction.max_bytes_per_pic_denom = max_bytes_per_pic_denom as u8;
 
-            let max_bits_per_min_cu_denom = bit_reader.read_exp_golomb()?;
+            let max_bits_per_min_cu_denom =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(max_bits_per_min_cu_denom, 0, 16)?;
             bitstream_restriction.max_bits_per_min_cu_denom = max_bits_per_min_cu_denom as u8;
 
-            let log2_max_mv_length_horizontal = bit_reader.read_exp_golomb()?;
+            let log2_max_mv_length_horizontal =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(log2_max_mv_length_horizontal, 0, 15)?;
             bitstream_restriction.log2_max_mv_length_horizontal =
                 log2_max_mv_length_horizontal as u8;
 
-            let log2_max_mv_length_vertical = bit_reader.read_exp_golomb()?;
+            let log2_max_mv_length_vertical =
+                bit_reader.read_exp_golomb_bounded(DEFAULT_MAX_LEADING_ZEROS)?;
             range_check!(log2_max_mv_length_vertical, 0, 15)?;
             bitstream_restriction.log2_max_mv_length_vertical = log2_max_mv_length_vertical as u8;
         }
@@ -330,6 +342,124 @@ impl VuiParameters {
             bitstream_restriction,
         })
     }
+
+    /// Presence flags are derived from the values, so syntax elements that are equal to their inferred
+    /// values are omitted.
+    pub(crate) fn build<W: io::Write>(
+        &self,
+        bit_writer: &mut BitWriter<W>,
+        sps_max_sub_layers_minus1: u8,
+    ) -> io::Result<()> {
+        match &self.aspect_ratio_info {
+            AspectRatioInfo::Predefined(AspectRatioIdc::Unspecified) => {
+                bit_writer.write_bit(false)?; // aspect_ratio_info_present_flag
+            }
+            AspectRatioInfo::Predefined(aspect_ratio_idc) => {
+                bit_writer.write_bit(true)?; // aspect_ratio_info_present_flag
+                bit_writer.write_bits(aspect_ratio_idc.clone() as u64, 8)?;
+                if *aspect_ratio_idc == AspectRatioIdc::ExtendedSar {
+                    // sar_width and sar_height, 0 means unspecified
+                    bit_writer.write_bits(0, 32)?;
+                }
+            }
+            AspectRatioInfo::ExtendedSar {
+                sar_width,
+                sar_height,
+            } => {
+                bit_writer.write_bit(true)?; // aspect_ratio_info_present_flag
+                bit_writer.write_bits(AspectRatioIdc::ExtendedSar as u64, 8)?;
+                bit_writer.write_bits(*sar_width as u64, 16)?;
+                bit_writer.write_bits(*sar_height as u64, 16)?;
+            }
+        }
+
+        bit_writer.write_bit(self.overscan_appropriate_flag.is_some())?; // overscan_info_present_flag
+        if let Some(overscan_appropriate_flag) = self.overscan_appropriate_flag {
+            bit_writer.write_bit(overscan_appropriate_flag)?;
+        }
+
+        let video_signal_type = &self.video_signal_type;
+        let default_video_signal_type = VideoSignalType::default();
+        let colour_description_present_flag = video_signal_type.colour_primaries
+            != default_video_signal_type.colour_primaries
+            || video_signal_type.transfer_characteristics
+                != default_video_signal_type.transfer_characteristics
+            || video_signal_type.matrix_coeffs != default_video_signal_type.matrix_coeffs;
+        bit_writer.write_bit(*video_signal_type != default_video_signal_type)?; // video_signal_type_present_flag
+        if *video_signal_type != default_video_signal_type {
+            bit_writer.write_bits(video_signal_type.video_format as u64, 3)?;
+            bit_writer.write_bit(video_signal_type.video_full_range_flag)?;
+            bit_writer.write_bit(colour_description_present_flag)?;
+            if colour_description_present_flag {
+                bit_writer.write_bits(video_signal_type.colour_primaries as u64, 8)?;
+                bit_writer.write_bits(video_signal_type.transfer_characteristics as u64, 8)?;
+                bit_writer.write_bits(video_signal_type.matrix_coeffs as u64, 8)?;
+            }
+        }
+
+        bit_writer.write_bit(self.chroma_loc_info.is_some())?; // chroma_loc_info_present_flag
+        if let Some(chroma_loc_info) = &self.chroma_loc_info {
+            bit_writer.write_exp_golomb(chroma_loc_info.top_field)?;
+            bit_writer.write_exp_golomb(chroma_loc_info.bottom_field)?;
+        }
+
+        bit_writer.write_bit(self.neutral_chroma_indication_flag)?;
+        bit_writer.write_bit(self.field_seq_flag)?;
+        bit_writer.write_bit(self.frame_field_info_present_flag)?;
+
+        let default_display_window_flag =
+            self.default_display_window != DefaultDisplayWindow::default();
+        bit_writer.write_bit(default_display_window_flag)?;
+        if default_display_window_flag {
+            bit_writer.write_exp_golomb(self.default_display_window.def_disp_win_left_offset)?;
+            bit_writer.write_exp_golomb(self.default_display_window.def_disp_win_right_offset)?;
+            bit_writer.write_exp_golomb(self.default_display_window.def_disp_win_top_offset)?;
+            bit_writer.write_exp_golomb(self.default_display_window.def_disp_win_bottom_offset)?;
+        }
+
+        bit_writer.write_bit(self.vui_timing_info.is_some())?; // vui_timing_info_present_flag
+        if let Some(vui_timing_info) = &self.vui_timing_info {
+            bit_writer.write_bits(vui_timing_info.num_units_in_tick.get() as u64, 32)?;
+            bit_writer.write_bits(vui_timing_info.time_scale.get() as u64, 32)?;
+
+            bit_writer.write_bit(vui_timing_info.poc_proportional_to_timing_flag)?;
+            if vui_timing_info.poc_proportional_to_timing_flag {
+                bit_writer.write_exp_golomb(
+                    vui_timing_info.num_ticks_poc_diff_one_minus1.unwrap_or(0) as u64,
+                )?;
+            }
+
+            bit_writer.write_bit(vui_timing_info.hrd_parameters.is_some())?; // vui_hrd_parameters_present_flag
+            if let Some(hrd_parameters) = &vui_timing_info.hrd_parameters {
+                hrd_parameters.build(bit_writer, true, sps_max_sub_layers_minus1)?;
+            }
+        }
+
+        // restricted_ref_pic_lists_flag is only set when the bitstream restriction was present
+        let bitstream_restriction = &self.bitstream_restriction;
+        bit_writer.write_bit(
+            bitstream_restriction
+                .restricted_ref_pic_lists_flag
+                .is_some(),
+        )?; // bitstream_restriction_flag
+        if let Some(restricted_ref_pic_lists_flag) =
+            bitstream_restriction.restricted_ref_pic_lists_flag
+        {
+            bit_writer.write_bit(bitstream_restriction.tiles_fixed_structure_flag)?;
+            bit_writer.write_bit(bitstream_restriction.motion_vectors_over_pic_boundaries_flag)?;
+            bit_writer.write_bit(restricted_ref_pic_lists_flag)?;
+            bit_writer
+                .write_exp_golomb(bitstream_restriction.min_spatial_segmentation_idc as u64)?;
+            bit_writer.write_exp_golomb(bitstream_restriction.max_bytes_per_pic_denom as u64)?;
+            bit_writer.write_exp_golomb(bitstream_restriction.max_bits_per_min_cu_denom as u64)?;
+            bit_writer
+                .write_exp_golomb(bitstream_restriction.log2_max_mv_length_horizontal as u64)?;
+            bit_writer
+                .write_exp_golomb(bitstream_restriction.log2_max_mv_length_vertical as u64)?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Specifies the value of the sample aspect ratio of the luma samples.
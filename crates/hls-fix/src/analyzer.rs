@@ -8,6 +8,9 @@
 //! - Analyzes different segment types (TS, fMP4 init, fMP4 media)
 //! - Tracks content metadata (codecs, bitrates, resolutions)
 //! - Collects statistics on segments (counts, durations, sizes)
+//! - Times fMP4 media segments from their `tfdt`/`trun` boxes and detects timeline gaps
+//!   and overlaps between consecutive segments
+//! - Reports the parameters of AV1 streams in TS segments from their AV1 video descriptors
 //!
 //! ## License
 //!
@@ -18,13 +21,17 @@
 //! - hua0512
 //!
 
-use hls::{HlsData, M4sData, SegmentType};
+use crate::fragment_timing::{FragmentTiming, FragmentTimingError, SegmentTiming, TrackTimescales};
+use av1::AV1CodecConfigurationRecord;
+use hls::{HlsData, M4sData, SegmentType, TsSegmentData};
 use mp4::fragment::{
     Av1ValidationOptions, extract_av1_track_ids_from_init,
     validate_av1_media_segment_with_track_ids_and_options,
 };
+use std::collections::HashMap;
 use std::fmt;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+use ts::{PmtRef, StreamType, TsPacketRef, TsParser};
 
 /// AV1 fMP4 sample validation policy.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -38,6 +45,42 @@ pub enum Av1SampleValidationMode {
     StrictAll,
 }
 
+/// A problem found on the timeline of an fMP4 media segment
+#[derive(Debug, Clone, PartialEq)]
+pub enum FragmentIssueKind {
+    /// The segment starts after the end of the previous one
+    Gap { track_id: u32, seconds: f64 },
+    /// The segment starts before the end of the previous one
+    Overlap { track_id: u32, seconds: f64 },
+    /// The fragment boxes could not be read
+    Malformed(FragmentTimingError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FragmentIssue {
+    /// URI of the affected media segment
+    pub uri: String,
+    pub kind: FragmentIssueKind,
+}
+
+/// An AV1 stream of the TS segments, with the parameters of its AV1 video descriptor
+#[derive(Debug, Clone, PartialEq)]
+pub struct TsAv1Stream {
+    pub pid: u16,
+    pub config: AV1CodecConfigurationRecord,
+}
+
+impl TsAv1Stream {
+    /// Bit depth of the samples
+    pub fn bit_depth(&self) -> u8 {
+        match (self.config.high_bitdepth, self.config.twelve_bit) {
+            (true, true) => 12,
+            (true, false) => 10,
+            _ => 8,
+        }
+    }
+}
+
 // Stats structure to hold all the metrics
 #[derive(Debug, Clone)]
 pub struct HlsStats {
@@ -58,6 +101,9 @@ pub struct HlsStats {
     pub mp4_init_segments_size: u64,
     pub mp4_media_segments_size: u64,
 
+    /// AV1 streams announced by the PMT of the TS segments
+    pub ts_av1_streams: Vec<TsAv1Stream>,
+
     // Duration tracking
     pub ts_segments_duration: f32,
     pub mp4_segments_duration: f32,
@@ -66,6 +112,13 @@ pub struct HlsStats {
     pub last_segment_type: Option<SegmentType>,
     pub last_segment_size: u64,
     pub last_segment_duration: f32,
+
+    // fMP4 timeline
+    /// Timing of the last fMP4 media segment, if its fragments could be read
+    pub last_fragment_timing: Option<SegmentTiming>,
+    pub fragment_gap_count: u32,
+    pub fragment_overlap_count: u32,
+    pub fragment_issues: Vec<FragmentIssue>,
 }
 
 impl Default for HlsStats {
@@ -82,11 +135,16 @@ impl Default for HlsStats {
             ts_segments_size: 0,
             mp4_init_segments_size: 0,
             mp4_media_segments_size: 0,
+            ts_av1_streams: Vec::new(),
             ts_segments_duration: 0.0,
             mp4_segments_duration: 0.0,
             last_segment_type: None,
             last_segment_size: 0,
             last_segment_duration: 0.0,
+            last_fragment_timing: None,
+            fragment_gap_count: 0,
+            fragment_overlap_count: 0,
+            fragment_issues: Vec::new(),
         }
     }
 }
@@ -162,6 +220,16 @@ impl fmt::Display for HlsStats {
                 self.ts_segments_duration
             )?;
             writeln!(f, "    TS bitrate: {:.2} kbps", self.calculate_ts_bitrate())?;
+            for stream in &self.ts_av1_streams {
+                writeln!(
+                    f,
+                    "    AV1 video on PID 0x{:04X}: profile {}, level {}, {}-bit",
+                    stream.pid,
+                    stream.config.seq_profile,
+                    stream.config.seq_level_idx_0,
+                    stream.bit_depth()
+                )?;
+            }
         }
 
         if self.has_mp4_segments {
@@ -187,6 +255,13 @@ impl fmt::Display for HlsStats {
                 "    MP4 bitrate: {:.2} kbps",
                 self.calculate_mp4_bitrate()
             )?;
+            if self.fragment_gap_count > 0 || self.fragment_overlap_count > 0 {
+                writeln!(
+                    f,
+                    "    Timeline gaps: {}, overlaps: {}",
+                    self.fragment_gap_count, self.fragment_overlap_count
+                )?;
+            }
         }
 
         // Last segment info
@@ -209,6 +284,12 @@ pub struct HlsAnalyzer {
     pub stats: HlsStats,
     last_mp4_av1_track_ids: Option<Vec<u32>>,
     av1_validation_mode: Av1SampleValidationMode,
+    /// Timescales of the tracks of the last init segment
+    track_timescales: Option<TrackTimescales>,
+    /// Decode time at the end of the last media segment of each track, in track ticks
+    fragment_ends: HashMap<u32, u64>,
+    /// Whether the PMT of the TS segments was read since the last discontinuity
+    ts_streams_known: bool,
 }
 
 impl HlsAnalyzer {
@@ -226,6 +307,122 @@ impl HlsAnalyzer {
     pub fn reset(&mut self) {
         self.stats.reset();
         self.last_mp4_av1_track_ids = None;
+        self.track_timescales = None;
+        self.fragment_ends.clear();
+        self.ts_streams_known = false;
+    }
+
+    /// Read the AV1 streams of the PMT of a TS segment.
+    ///
+    /// The PMT is read again after a discontinuity only, the segments in between are
+    /// expected to carry the same program.
+    fn analyze_ts_streams(&mut self, ts_data: &TsSegmentData) {
+        let mut found_pmt = false;
+        let mut av1_streams: Vec<TsAv1Stream> = Vec::new();
+        let result = TsParser::new().parse_packets(
+            ts_data.data.clone(),
+            |_| Ok(()),
+            |pmt: PmtRef| {
+                found_pmt = true;
+                for stream in pmt.streams().flatten() {
+                    if pmt.classify_stream(&stream) != StreamType::Av1 {
+                        continue;
+                    }
+                    let pid = stream.elementary_pid;
+                    match stream
+                        .descriptors()
+                        .flatten()
+                        .find_map(|desc| desc.as_av1_video_descriptor())
+                    {
+                        Some(Ok(descriptor)) => {
+                            av1_streams.retain(|stream| stream.pid != pid);
+                            av1_streams.push(TsAv1Stream {
+                                pid,
+                                config: descriptor.codec_configuration_record,
+                            });
+                        }
+                        Some(Err(e)) => warn!(
+                            uri = %ts_data.segment.uri,
+                            pid,
+                            error = %e,
+                            "Malformed AV1 video descriptor"
+                        ),
+                        None => debug!(pid, "AV1 stream without an AV1 video descriptor"),
+                    }
+                }
+                Ok(())
+            },
+            None::<fn(&TsPacketRef) -> ts::Result<()>>,
+        );
+
+        if let Err(e) = result {
+            debug!(uri = %ts_data.segment.uri, error = %e, "Could not read the TS program");
+        }
+        if found_pmt {
+            self.ts_streams_known = true;
+            self.stats.ts_av1_streams = av1_streams;
+        }
+    }
+
+    /// Time an fMP4 media segment and check that it continues the previous one.
+    ///
+    /// Malformed fragments are recorded as issues, they never fail the analysis.
+    fn analyze_fragment_timing(&mut self, segment: &hls::M4sSegmentData) -> Option<SegmentTiming> {
+        let timescales = self.track_timescales.as_ref()?;
+        let timing = match FragmentTiming::parse(&segment.data) {
+            Ok(timing) => timing,
+            Err(e) => {
+                warn!(uri = %segment.segment.uri, error = %e, "Malformed fMP4 fragment");
+                self.stats.fragment_issues.push(FragmentIssue {
+                    uri: segment.segment.uri.clone(),
+                    kind: FragmentIssueKind::Malformed(e),
+                });
+                return None;
+            }
+        };
+
+        // Timestamps legitimately restart after a discontinuity
+        if segment.segment.discontinuity {
+            self.fragment_ends.clear();
+        }
+
+        for track in &timing.tracks {
+            let (Some(defaults), Some(start)) =
+                (timescales.get(track.track_id), track.base_media_decode_time)
+            else {
+                continue;
+            };
+            if defaults.timescale == 0 {
+                continue;
+            }
+            let end = start.saturating_add(track.duration_ticks(defaults.default_sample_duration));
+
+            if let Some(previous_end) = self.fragment_ends.insert(track.track_id, end) {
+                let timescale = defaults.timescale as f64;
+                let kind = if start > previous_end {
+                    self.stats.fragment_gap_count += 1;
+                    FragmentIssueKind::Gap {
+                        track_id: track.track_id,
+                        seconds: (start - previous_end) as f64 / timescale,
+                    }
+                } else if start < previous_end {
+                    self.stats.fragment_overlap_count += 1;
+                    FragmentIssueKind::Overlap {
+                        track_id: track.track_id,
+                        seconds: (previous_end - start) as f64 / timescale,
+                    }
+                } else {
+                    continue;
+                };
+                debug!(uri = %segment.segment.uri, ?kind, "fMP4 timeline is not continuous");
+                self.stats.fragment_issues.push(FragmentIssue {
+                    uri: segment.segment.uri.clone(),
+                    kind,
+                });
+            }
+        }
+
+        timing.segment_timing(timescales)
     }
 
     /// Analyze a segment and update statistics
@@ -239,6 +436,10 @@ impl HlsAnalyzer {
                 self.stats.ts_segments_size += segment_size;
                 self.stats.total_size += segment_size;
 
+                if !self.ts_streams_known || ts_data.segment.discontinuity {
+                    self.analyze_ts_streams(ts_data);
+                }
+
                 let duration = ts_data.segment.duration;
                 self.stats.ts_segments_duration += duration;
                 self.stats.total_duration += duration;
@@ -257,6 +458,24 @@ impl HlsAnalyzer {
                 track_ids.dedup();
                 self.last_mp4_av1_track_ids = Some(track_ids);
 
+                self.fragment_ends.clear();
+                self.track_timescales = match TrackTimescales::from_init(&init_segment.data) {
+                    Ok(timescales) if !timescales.is_empty() => Some(timescales),
+                    Ok(_) => None,
+                    Err(e) => {
+                        warn!(
+                            uri = %init_segment.segment.uri,
+                            error = %e,
+                            "Malformed fMP4 init segment"
+                        );
+                        self.stats.fragment_issues.push(FragmentIssue {
+                            uri: init_segment.segment.uri.clone(),
+                            kind: FragmentIssueKind::Malformed(e),
+                        });
+                        None
+                    }
+                };
+
                 let segment_size = init_segment.data.len() as u64;
                 self.stats.mp4_init_segments_size += segment_size;
                 self.stats.total_size += segment_size;
@@ -308,7 +527,12 @@ impl HlsAnalyzer {
                 self.stats.mp4_media_segments_size += segment_size;
                 self.stats.total_size += segment_size;
 
-                let duration = media_segment.segment.duration;
+                // Prefer the duration of the samples over the rounded EXTINF duration
+                let timing = self.analyze_fragment_timing(media_segment);
+                let duration = timing
+                    .map(|timing| timing.duration() as f32)
+                    .unwrap_or(media_segment.segment.duration);
+                self.stats.last_fragment_timing = timing;
                 self.stats.mp4_segments_duration += duration;
                 self.stats.total_duration += duration;
 
@@ -345,7 +569,33 @@ mod tests {
     use super::*;
     use bytes::Bytes;
     use m3u8_rs::MediaSegment;
-    use mp4::test_support::{make_init_with_video_sample_entry, make_media_segment_for_track};
+    use mp4::test_support::{
+        make_init_with_timescale, make_init_with_video_sample_entry, make_media_segment_for_track,
+        make_media_segment_with_timing,
+    };
+
+    fn create_timed_init_segment(timescale: u32) -> HlsData {
+        HlsData::M4sData(M4sData::InitSegment(hls::M4sInitSegmentData {
+            segment: MediaSegment {
+                uri: "init.mp4".to_string(),
+                ..MediaSegment::empty()
+            },
+            data: make_init_with_timescale(1, timescale, 0),
+        }))
+    }
+
+    fn create_timed_media_segment(uri: &str, data: Bytes, discontinuity: bool) -> HlsData {
+        HlsData::M4sData(M4sData::Segment(hls::M4sSegmentData {
+            segment: MediaSegment {
+                uri: uri.to_string(),
+                // Rounded advertised duration
+                duration: 2.0,
+                discontinuity,
+                ..MediaSegment::empty()
+            },
+            data,
+        }))
+    }
 
     fn create_test_mp4_init_segment_with_av1(track_id: u32) -> HlsData {
         HlsData::M4sData(M4sData::InitSegment(hls::M4sInitSegmentData {
@@ -470,6 +720,90 @@ mod tests {
         assert!(!stats.has_mp4_segments);
     }
 
+    /// A TS segment with the PAT and PMT of a program with one stream on PID 0x100
+    fn create_test_ts_segment_with_pmt(
+        stream_type: ts::StreamType,
+        es_info: Vec<u8>,
+        discontinuity: bool,
+    ) -> HlsData {
+        let pat = ts::Pat {
+            table_id: 0,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![ts::PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        };
+        let pmt = ts::Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: Vec::new(),
+            streams: vec![ts::PmtStream {
+                stream_type,
+                elementary_pid: 0x100,
+                es_info,
+            }],
+        };
+        let mut writer = ts::TsWriter::new();
+        let mut data = Vec::new();
+        writer.write_pat(&pat, &mut data).unwrap();
+        writer.write_pmt(0x1000, &pmt, &mut data).unwrap();
+
+        HlsData::ts(
+            MediaSegment {
+                uri: "segment.ts".to_string(),
+                duration: 2.0,
+                discontinuity,
+                ..MediaSegment::empty()
+            },
+            Bytes::from(data),
+        )
+    }
+
+    #[test]
+    fn test_analyze_ts_av1_stream() {
+        let mut analyzer = HlsAnalyzer::new();
+        let av1 = create_test_ts_segment_with_pmt(
+            ts::StreamType::Mpeg2PrivatePes,
+            vec![
+                0x05, 0x04, b'A', b'V', b'0', b'1', // Registration
+                0x80, 0x04, 0x81, 0x08, 0x4C, 0x00, // AV1 video descriptor
+            ],
+            false,
+        );
+        analyzer.analyze_segment(&av1).unwrap();
+
+        let streams = &analyzer.stats.ts_av1_streams;
+        assert_eq!(streams.len(), 1);
+        assert_eq!(streams[0].pid, 0x100);
+        assert_eq!(streams[0].config.seq_profile, 0);
+        assert_eq!(streams[0].config.seq_level_idx_0, 8);
+        assert_eq!(streams[0].bit_depth(), 10);
+        assert!(
+            analyzer
+                .stats
+                .to_string()
+                .contains("AV1 video on PID 0x0100: profile 0, level 8, 10-bit")
+        );
+
+        // The program is only read again after a discontinuity
+        let h264 = create_test_ts_segment_with_pmt(ts::StreamType::H264, Vec::new(), false);
+        analyzer.analyze_segment(&h264).unwrap();
+        assert_eq!(analyzer.stats.ts_av1_streams.len(), 1);
+        let h264 = create_test_ts_segment_with_pmt(ts::StreamType::H264, Vec::new(), true);
+        analyzer.analyze_segment(&h264).unwrap();
+        assert!(analyzer.stats.ts_av1_streams.is_empty());
+    }
+
     #[test]
     fn test_analyze_mp4_segments() {
         let mut analyzer = HlsAnalyzer::new();
@@ -581,4 +915,103 @@ mod tests {
 
         assert!(err.contains("Reserved OBU type"));
     }
+
+    #[test]
+    fn test_mp4_duration_from_fragment_timing() {
+        let mut analyzer = HlsAnalyzer::new();
+        analyzer
+            .analyze_segment(&create_timed_init_segment(1000))
+            .unwrap();
+
+        let media = make_media_segment_with_timing(1, 1, 10_000, &[400, 400, 400, 400, 400]);
+        analyzer
+            .analyze_segment(&create_timed_media_segment("0.m4s", media, false))
+            .unwrap();
+
+        let timing = analyzer.stats.last_fragment_timing.unwrap();
+        assert_eq!(timing.start(), 10.0);
+        assert_eq!(timing.end(), 12.0);
+        assert_eq!(analyzer.stats.last_segment_duration, 2.0);
+
+        // 1.96s of samples advertised as 2s
+        let media = make_media_segment_with_timing(1, 0, 12_000, &[490, 490, 490, 490]);
+        analyzer
+            .analyze_segment(&create_timed_media_segment("1.m4s", media, false))
+            .unwrap();
+        assert!((analyzer.stats.total_duration - 3.96).abs() < 1e-4);
+        assert!(analyzer.stats.fragment_issues.is_empty());
+    }
+
+    #[test]
+    fn test_mp4_timeline_gaps_and_overlaps() {
+        let mut analyzer = HlsAnalyzer::new();
+        analyzer
+            .analyze_segment(&create_timed_init_segment(1000))
+            .unwrap();
+
+        let segments = [
+            ("0.m4s", 0, false),
+            // Starts 500ms after the end of the previous segment
+            ("1.m4s", 2_500, false),
+            // Starts 1s before the end of the previous segment
+            ("2.m4s", 3_500, false),
+            // Timestamps reset after a discontinuity
+            ("3.m4s", 0, true),
+        ];
+        for (uri, start, discontinuity) in segments {
+            let media = make_media_segment_with_timing(1, 0, start, &[1000, 1000]);
+            analyzer
+                .analyze_segment(&create_timed_media_segment(uri, media, discontinuity))
+                .unwrap();
+        }
+
+        assert_eq!(analyzer.stats.fragment_gap_count, 1);
+        assert_eq!(analyzer.stats.fragment_overlap_count, 1);
+        assert_eq!(
+            analyzer.stats.fragment_issues,
+            vec![
+                FragmentIssue {
+                    uri: "1.m4s".to_string(),
+                    kind: FragmentIssueKind::Gap {
+                        track_id: 1,
+                        seconds: 0.5
+                    },
+                },
+                FragmentIssue {
+                    uri: "2.m4s".to_string(),
+                    kind: FragmentIssueKind::Overlap {
+                        track_id: 1,
+                        seconds: 1.0
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_fragment_is_reported_not_failed() {
+        let mut analyzer = HlsAnalyzer::new();
+        analyzer
+            .analyze_segment(&create_timed_init_segment(1000))
+            .unwrap();
+
+        let mut data = make_media_segment_with_timing(1, 0, 0, &[1000]).to_vec();
+        // moof size larger than the segment
+        data[0..4].copy_from_slice(&0xFFFF_FFFFu32.to_be_bytes());
+        analyzer
+            .analyze_segment(&create_timed_media_segment(
+                "bad.m4s",
+                Bytes::from(data),
+                false,
+            ))
+            .unwrap();
+
+        // Falls back to the advertised duration
+        assert_eq!(analyzer.stats.last_segment_duration, 2.0);
+        assert!(analyzer.stats.last_fragment_timing.is_none());
+        assert!(matches!(
+            analyzer.stats.fragment_issues[0].kind,
+            FragmentIssueKind::Malformed(FragmentTimingError::InvalidBoxSize { .. })
+        ));
+    }
 }
@@ -0,0 +1,652 @@
+//! # fMP4 Fragment Timing
+//!
+//! Lightweight ISOBMFF box parser extracting the decode timeline of fMP4 media segments.
+//!
+//! Media segment durations advertised by `#EXTINF` are rounded and frequently inaccurate.
+//! The actual timeline of a fragment is described by its `moof` box: `tfdt` gives the
+//! baseMediaDecodeTime of each track fragment and `trun` lists the duration of every sample.
+//! Timescales come from the `mdhd` boxes of the init segment, default sample durations from
+//! `trex` (init segment) or `tfhd` (media segment).
+//!
+//! Boxes are read in place from the segment buffer; malformed box sizes are reported as
+//! errors instead of causing panics.
+//!
+//! ## License
+//!
+//! MIT License
+//!
+//! ## Authors
+//!
+//! - hua0512
+//!
+
+use bytes::Bytes;
+use std::collections::HashMap;
+
+/// tfhd: base-data-offset-present
+const TFHD_BASE_DATA_OFFSET: u32 = 0x01;
+/// tfhd: sample-description-index-present
+const TFHD_SAMPLE_DESCRIPTION_INDEX: u32 = 0x02;
+/// tfhd: default-sample-duration-present
+const TFHD_DEFAULT_SAMPLE_DURATION: u32 = 0x08;
+
+/// trun: data-offset-present
+const TRUN_DATA_OFFSET: u32 = 0x01;
+/// trun: first-sample-flags-present
+const TRUN_FIRST_SAMPLE_FLAGS: u32 = 0x04;
+/// trun: sample-duration-present
+const TRUN_SAMPLE_DURATION: u32 = 0x100;
+/// trun: sample-size-present
+const TRUN_SAMPLE_SIZE: u32 = 0x200;
+/// trun: sample-flags-present
+const TRUN_SAMPLE_FLAGS: u32 = 0x400;
+/// trun: sample-composition-time-offsets-present
+const TRUN_SAMPLE_CTS_OFFSET: u32 = 0x800;
+
+/// Errors raised while reading fragment timing
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FragmentTimingError {
+    #[error("Malformed '{fourcc}' box at offset {offset}: size {size} exceeds {available} bytes")]
+    InvalidBoxSize {
+        fourcc: String,
+        offset: usize,
+        size: u64,
+        available: usize,
+    },
+    #[error("Truncated box header at offset {offset}")]
+    TruncatedHeader { offset: usize },
+    #[error("'{fourcc}' box is too short: {len} bytes")]
+    TruncatedBox { fourcc: &'static str, len: usize },
+    #[error("'traf' box has no 'tfhd' box")]
+    MissingTfhd,
+    #[error("Segment has no 'moof' box")]
+    MissingMoof,
+}
+
+/// A box borrowed from its parent buffer
+#[derive(Debug, Clone, Copy)]
+struct RawBox<'a> {
+    fourcc: [u8; 4],
+    body: &'a [u8],
+}
+
+/// Iterator over the boxes of a buffer, yielding an error on the first malformed one
+struct BoxIter<'a> {
+    data: &'a [u8],
+    offset: usize,
+    /// Offset of `data` in the segment, for error reporting
+    base: usize,
+}
+
+impl<'a> BoxIter<'a> {
+    fn new(data: &'a [u8], base: usize) -> Self {
+        Self {
+            data,
+            offset: 0,
+            base,
+        }
+    }
+
+    /// Children of a box, `body_offset` being the offset of its body in the segment
+    fn children(parent: &RawBox<'a>, body_offset: usize) -> Self {
+        Self::new(parent.body, body_offset)
+    }
+
+    fn read(&mut self) -> Result<(RawBox<'a>, usize), FragmentTimingError> {
+        let remaining = &self.data[self.offset..];
+        let offset = self.base + self.offset;
+        if remaining.len() < 8 {
+            return Err(FragmentTimingError::TruncatedHeader { offset });
+        }
+
+        let fourcc = [remaining[4], remaining[5], remaining[6], remaining[7]];
+        let (size, header_size) = match read_u32(remaining, 0) {
+            1 => {
+                if remaining.len() < 16 {
+                    return Err(FragmentTimingError::TruncatedHeader { offset });
+                }
+                (read_u64(remaining, 8), 16)
+            }
+            0 => (remaining.len() as u64, 8),
+            size => (size as u64, 8),
+        };
+
+        if size < header_size as u64 || size > remaining.len() as u64 {
+            return Err(FragmentTimingError::InvalidBoxSize {
+                fourcc: String::from_utf8_lossy(&fourcc).into_owned(),
+                offset,
+                size,
+                available: remaining.len(),
+            });
+        }
+
+        let size = size as usize;
+        let body_offset = offset + header_size;
+        self.offset += size;
+        Ok((
+            RawBox {
+                fourcc,
+                body: &remaining[header_size..size],
+            },
+            body_offset,
+        ))
+    }
+}
+
+impl<'a> Iterator for BoxIter<'a> {
+    /// The box and the offset of its body in the segment
+    type Item = Result<(RawBox<'a>, usize), FragmentTimingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.data.len() {
+            return None;
+        }
+        let item = self.read();
+        if item.is_err() {
+            // Nothing after a malformed box can be trusted
+            self.offset = self.data.len();
+        }
+        Some(item)
+    }
+}
+
+#[inline]
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+#[inline]
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes([
+        data[at],
+        data[at + 1],
+        data[at + 2],
+        data[at + 3],
+        data[at + 4],
+        data[at + 5],
+        data[at + 6],
+        data[at + 7],
+    ])
+}
+
+/// Version and flags of a full box, and its payload
+fn full_box<'a>(
+    body: &'a [u8],
+    fourcc: &'static str,
+) -> Result<(u8, u32, &'a [u8]), FragmentTimingError> {
+    if body.len() < 4 {
+        return Err(FragmentTimingError::TruncatedBox {
+            fourcc,
+            len: body.len(),
+        });
+    }
+    let flags = read_u32(body, 0) & 0x00FF_FFFF;
+    Ok((body[0], flags, &body[4..]))
+}
+
+/// Bounds-checked big-endian reader over a box payload
+struct FieldReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    fourcc: &'static str,
+}
+
+impl<'a> FieldReader<'a> {
+    fn new(data: &'a [u8], fourcc: &'static str) -> Self {
+        Self {
+            data,
+            pos: 0,
+            fourcc,
+        }
+    }
+
+    fn ensure(&self, len: usize) -> Result<(), FragmentTimingError> {
+        if self.data.len().saturating_sub(self.pos) < len {
+            return Err(FragmentTimingError::TruncatedBox {
+                fourcc: self.fourcc,
+                len: self.data.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), FragmentTimingError> {
+        self.ensure(len)?;
+        self.pos += len;
+        Ok(())
+    }
+
+    fn u32(&mut self) -> Result<u32, FragmentTimingError> {
+        self.ensure(4)?;
+        let value = read_u32(self.data, self.pos);
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn u64(&mut self) -> Result<u64, FragmentTimingError> {
+        self.ensure(8)?;
+        let value = read_u64(self.data, self.pos);
+        self.pos += 8;
+        Ok(value)
+    }
+}
+
+/// Per-track values of an init segment needed to time its fragments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TrackDefaults {
+    /// Media timescale from `mdhd`, in ticks per second
+    pub timescale: u32,
+    /// Default sample duration from `trex`, in ticks
+    pub default_sample_duration: u32,
+}
+
+/// Track timescales and defaults of an init segment, keyed by track id
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TrackTimescales {
+    tracks: HashMap<u32, TrackDefaults>,
+}
+
+impl TrackTimescales {
+    /// Read the `moov` box of an init segment
+    pub fn from_init(data: &Bytes) -> Result<Self, FragmentTimingError> {
+        let mut tracks = HashMap::new();
+        let mut trex_durations = Vec::new();
+
+        for item in BoxIter::new(data, 0) {
+            let (moov, moov_offset) = item?;
+            if &moov.fourcc != b"moov" {
+                continue;
+            }
+            for child in BoxIter::children(&moov, moov_offset) {
+                let (child, child_offset) = child?;
+                match &child.fourcc {
+                    b"trak" => {
+                        if let Some((track_id, timescale)) = parse_trak(&child, child_offset)? {
+                            tracks.insert(
+                                track_id,
+                                TrackDefaults {
+                                    timescale,
+                                    default_sample_duration: 0,
+                                },
+                            );
+                        }
+                    }
+                    b"mvex" => {
+                        for trex in BoxIter::children(&child, child_offset) {
+                            let (trex, _) = trex?;
+                            if &trex.fourcc == b"trex" {
+                                let (_, _, payload) = full_box(trex.body, "trex")?;
+                                let mut reader = FieldReader::new(payload, "trex");
+                                let track_id = reader.u32()?;
+                                reader.skip(4)?; // default_sample_description_index
+                                trex_durations.push((track_id, reader.u32()?));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (track_id, duration) in trex_durations {
+            if let Some(track) = tracks.get_mut(&track_id) {
+                track.default_sample_duration = duration;
+            }
+        }
+
+        Ok(Self { tracks })
+    }
+
+    pub fn get(&self, track_id: u32) -> Option<&TrackDefaults> {
+        self.tracks.get(&track_id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+}
+
+/// Track id from `tkhd` and timescale from `mdia/mdhd`
+fn parse_trak(trak: &RawBox<'_>, offset: usize) -> Result<Option<(u32, u32)>, FragmentTimingError> {
+    let mut track_id = None;
+    let mut timescale = None;
+
+    for child in BoxIter::children(trak, offset) {
+        let (child, child_offset) = child?;
+        match &child.fourcc {
+            b"tkhd" => {
+                let (version, _, payload) = full_box(child.body, "tkhd")?;
+                let mut reader = FieldReader::new(payload, "tkhd");
+                // creation_time and modification_time
+                reader.skip(if version == 1 { 16 } else { 8 })?;
+                track_id = Some(reader.u32()?);
+            }
+            b"mdia" => {
+                for mdhd in BoxIter::children(&child, child_offset) {
+                    let (mdhd, _) = mdhd?;
+                    if &mdhd.fourcc == b"mdhd" {
+                        let (version, _, payload) = full_box(mdhd.body, "mdhd")?;
+                        let mut reader = FieldReader::new(payload, "mdhd");
+                        reader.skip(if version == 1 { 16 } else { 8 })?;
+                        timescale = Some(reader.u32()?);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(track_id.zip(timescale))
+}
+
+/// Timing of the fragments of one track within a media segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackFragmentTiming {
+    pub track_id: u32,
+    /// baseMediaDecodeTime of the first fragment, in track ticks
+    pub base_media_decode_time: Option<u64>,
+    pub sample_count: u32,
+    /// Durations listed in `trun`, in track ticks
+    pub sample_durations: Vec<u32>,
+    /// Total duration of samples timed by the `tfhd` default sample duration
+    tfhd_default_ticks: u64,
+    /// Samples without any duration in the fragment, timed by the `trex` default
+    untimed_samples: u32,
+}
+
+impl TrackFragmentTiming {
+    /// Sum of sample durations, in track ticks.
+    ///
+    /// Samples without a duration in the fragment take `trex_duration`.
+    pub fn duration_ticks(&self, trex_duration: u32) -> u64 {
+        let listed: u64 = self.sample_durations.iter().map(|&d| d as u64).sum();
+        listed
+            .saturating_add(self.tfhd_default_ticks)
+            .saturating_add(self.untimed_samples as u64 * trex_duration as u64)
+    }
+}
+
+/// Decode timeline of a media segment
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FragmentTiming {
+    /// Sequence number of the first `mfhd`
+    pub sequence_number: Option<u32>,
+    /// Tracks in order of first appearance
+    pub tracks: Vec<TrackFragmentTiming>,
+}
+
+/// Start and end of a segment on the media timeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SegmentTiming {
+    pub track_id: u32,
+    pub timescale: u32,
+    /// baseMediaDecodeTime, in track ticks
+    pub start_ticks: u64,
+    /// Decode time after the last sample, in track ticks
+    pub end_ticks: u64,
+}
+
+impl SegmentTiming {
+    pub fn start(&self) -> f64 {
+        self.start_ticks as f64 / self.timescale as f64
+    }
+
+    pub fn end(&self) -> f64 {
+        self.end_ticks as f64 / self.timescale as f64
+    }
+
+    pub fn duration(&self) -> f64 {
+        (self.end_ticks - self.start_ticks) as f64 / self.timescale as f64
+    }
+}
+
+impl FragmentTiming {
+    /// Read every `moof` box of a media segment.
+    ///
+    /// Segments made of several fragments (CMAF chunks, LL-HLS parts) contribute the start
+    /// time of their first fragment and the samples of all of them.
+    pub fn parse(data: &Bytes) -> Result<Self, FragmentTimingError> {
+        let mut timing = FragmentTiming::default();
+        let mut found_moof = false;
+
+        for item in BoxIter::new(data, 0) {
+            let (moof, moof_offset) = item?;
+            if &moof.fourcc != b"moof" {
+                continue;
+            }
+            found_moof = true;
+
+            for child in BoxIter::children(&moof, moof_offset) {
+                let (child, child_offset) = child?;
+                match &child.fourcc {
+                    b"mfhd" if timing.sequence_number.is_none() => {
+                        let (_, _, payload) = full_box(child.body, "mfhd")?;
+                        timing.sequence_number = Some(FieldReader::new(payload, "mfhd").u32()?);
+                    }
+                    b"traf" => timing.parse_traf(&child, child_offset)?,
+                    _ => {}
+                }
+            }
+        }
+
+        if !found_moof {
+            return Err(FragmentTimingError::MissingMoof);
+        }
+        Ok(timing)
+    }
+
+    fn parse_traf(&mut self, traf: &RawBox<'_>, offset: usize) -> Result<(), FragmentTimingError> {
+        let mut track_id = None;
+        let mut default_sample_duration = None;
+        let mut base_media_decode_time = None;
+        let mut sample_count = 0u32;
+        let mut sample_durations = Vec::new();
+        let mut implicit_samples = 0u32;
+
+        for child in BoxIter::children(traf, offset) {
+            let (child, _) = child?;
+            match &child.fourcc {
+                b"tfhd" => {
+                    let (_, flags, payload) = full_box(child.body, "tfhd")?;
+                    let mut reader = FieldReader::new(payload, "tfhd");
+                    track_id = Some(reader.u32()?);
+                    if flags & TFHD_BASE_DATA_OFFSET != 0 {
+                        reader.skip(8)?;
+                    }
+                    if flags & TFHD_SAMPLE_DESCRIPTION_INDEX != 0 {
+                        reader.skip(4)?;
+                    }
+                    if flags & TFHD_DEFAULT_SAMPLE_DURATION != 0 {
+                        default_sample_duration = Some(reader.u32()?);
+                    }
+                }
+                b"tfdt" => {
+                    let (version, _, payload) = full_box(child.body, "tfdt")?;
+                    let mut reader = FieldReader::new(payload, "tfdt");
+                    base_media_decode_time = Some(if version == 1 {
+                        reader.u64()?
+                    } else {
+                        reader.u32()? as u64
+                    });
+                }
+                b"trun" => {
+                    let (_, flags, payload) = full_box(child.body, "trun")?;
+                    let mut reader = FieldReader::new(payload, "trun");
+                    let count = reader.u32()?;
+                    if flags & TRUN_DATA_OFFSET != 0 {
+                        reader.skip(4)?;
+                    }
+                    if flags & TRUN_FIRST_SAMPLE_FLAGS != 0 {
+                        reader.skip(4)?;
+                    }
+
+                    let per_sample_skip =
+                        [TRUN_SAMPLE_SIZE, TRUN_SAMPLE_FLAGS, TRUN_SAMPLE_CTS_OFFSET]
+                            .iter()
+                            .filter(|&&flag| flags & flag != 0)
+                            .count()
+                            * 4;
+                    if flags & TRUN_SAMPLE_DURATION != 0 {
+                        // Validate the table size before reserving space for it
+                        reader.ensure((count as usize).saturating_mul(4 + per_sample_skip))?;
+                        sample_durations.reserve(count as usize);
+                        for _ in 0..count {
+                            sample_durations.push(reader.u32()?);
+                            reader.skip(per_sample_skip)?;
+                        }
+                    } else {
+                        implicit_samples = implicit_samples.saturating_add(count);
+                    }
+                    sample_count = sample_count.saturating_add(count);
+                }
+                _ => {}
+            }
+        }
+
+        let Some(track_id) = track_id else {
+            return Err(FragmentTimingError::MissingTfhd);
+        };
+        let (tfhd_default_ticks, untimed_samples) = match default_sample_duration {
+            Some(duration) => (implicit_samples as u64 * duration as u64, 0),
+            None => (0, implicit_samples),
+        };
+
+        match self.tracks.iter_mut().find(|t| t.track_id == track_id) {
+            Some(track) => {
+                // Later fragments of the same track only extend its samples
+                track.sample_count = track.sample_count.saturating_add(sample_count);
+                track.sample_durations.extend(sample_durations);
+                track.tfhd_default_ticks =
+                    track.tfhd_default_ticks.saturating_add(tfhd_default_ticks);
+                track.untimed_samples = track.untimed_samples.saturating_add(untimed_samples);
+                if track.base_media_decode_time.is_none() {
+                    track.base_media_decode_time = base_media_decode_time;
+                }
+            }
+            None => self.tracks.push(TrackFragmentTiming {
+                track_id,
+                base_media_decode_time,
+                sample_count,
+                sample_durations,
+                tfhd_default_ticks,
+                untimed_samples,
+            }),
+        }
+
+        Ok(())
+    }
+
+    /// Timing of the segment on the timeline of its first track with a known timescale
+    pub fn segment_timing(&self, timescales: &TrackTimescales) -> Option<SegmentTiming> {
+        self.tracks.iter().find_map(|track| {
+            let defaults = timescales.get(track.track_id)?;
+            if defaults.timescale == 0 {
+                return None;
+            }
+            let start_ticks = track.base_media_decode_time?;
+            let end_ticks =
+                start_ticks.saturating_add(track.duration_ticks(defaults.default_sample_duration));
+            Some(SegmentTiming {
+                track_id: track.track_id,
+                timescale: defaults.timescale,
+                start_ticks,
+                end_ticks,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mp4::test_support::{
+        make_box, make_full_box, make_init_with_timescale, make_media_segment_with_timing,
+    };
+
+    #[test]
+    fn test_parse_init_timescales() {
+        let timescales =
+            TrackTimescales::from_init(&make_init_with_timescale(1, 90000, 3000)).unwrap();
+        assert_eq!(
+            timescales.get(1),
+            Some(&TrackDefaults {
+                timescale: 90000,
+                default_sample_duration: 3000,
+            })
+        );
+        assert!(timescales.get(2).is_none());
+    }
+
+    #[test]
+    fn test_segment_timing_tfdt_versions() {
+        let timescales = TrackTimescales::from_init(&make_init_with_timescale(1, 1000, 0)).unwrap();
+
+        for version in [0, 1] {
+            let timing = FragmentTiming::parse(&make_media_segment_with_timing(
+                1,
+                version,
+                4000,
+                &[500, 500, 1000],
+            ))
+            .unwrap();
+            assert_eq!(timing.sequence_number, Some(1));
+            assert_eq!(timing.tracks[0].sample_count, 3);
+            assert_eq!(timing.tracks[0].sample_durations, vec![500, 500, 1000]);
+
+            let segment = timing.segment_timing(&timescales).unwrap();
+            assert_eq!(segment.start(), 4.0);
+            assert_eq!(segment.end(), 6.0);
+            assert_eq!(segment.duration(), 2.0);
+        }
+
+        // A 64-bit decode time beyond the range of version 0
+        let timing =
+            FragmentTiming::parse(&make_media_segment_with_timing(1, 1, 1 << 40, &[1000])).unwrap();
+        assert_eq!(timing.tracks[0].base_media_decode_time, Some(1 << 40));
+    }
+
+    #[test]
+    fn test_default_sample_duration_from_trex() {
+        let timescales =
+            TrackTimescales::from_init(&make_init_with_timescale(1, 1000, 40)).unwrap();
+
+        let tfhd = make_full_box(b"tfhd", 0, 0, &1u32.to_be_bytes());
+        let tfdt = make_full_box(b"tfdt", 0, 0, &0u32.to_be_bytes());
+        let trun = make_full_box(b"trun", 0, 0, &25u32.to_be_bytes());
+        let moof = make_box(b"moof", &make_box(b"traf", &[tfhd, tfdt, trun].concat()));
+
+        let timing = FragmentTiming::parse(&Bytes::from(moof)).unwrap();
+        let segment = timing.segment_timing(&timescales).unwrap();
+        assert_eq!(segment.end_ticks, 1000);
+    }
+
+    #[test]
+    fn test_malformed_box_size_is_an_error() {
+        let mut data = make_media_segment_with_timing(1, 0, 0, &[1000]).to_vec();
+        // Inflate the traf size past the end of the moof
+        data[8 + 16..8 + 20].copy_from_slice(&0xFFFFu32.to_be_bytes());
+        let err = FragmentTiming::parse(&Bytes::from(data)).unwrap_err();
+        assert!(
+            matches!(err, FragmentTimingError::InvalidBoxSize { ref fourcc, .. } if fourcc == "traf")
+        );
+
+        // A header cut short
+        let err = FragmentTiming::parse(&Bytes::from_static(&[0, 0, 0])).unwrap_err();
+        assert_eq!(err, FragmentTimingError::TruncatedHeader { offset: 0 });
+
+        // A sample table larger than its box
+        let trun = make_full_box(b"trun", 0, TRUN_SAMPLE_DURATION, &u32::MAX.to_be_bytes());
+        let moof = make_box(
+            b"moof",
+            &make_box(
+                b"traf",
+                &[make_full_box(b"tfhd", 0, 0, &1u32.to_be_bytes()), trun].concat(),
+            ),
+        );
+        let err = FragmentTiming::parse(&Bytes::from(moof)).unwrap_err();
+        assert!(matches!(
+            err,
+            FragmentTimingError::TruncatedBox { fourcc: "trun", .. }
+        ));
+    }
+}
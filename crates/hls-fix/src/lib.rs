@@ -11,12 +11,18 @@
 //! ## Component Overview
 //!
 //! - `pipeline`: HLS processing pipeline implementation
+//! - `playlist_writer`: Segment files and a local media playlist as output
 
 pub mod analyzer;
 mod crc32;
+pub mod fragment_timing;
 pub mod operators;
 pub mod pipeline;
+mod playlist_writer;
+#[cfg(test)]
+pub(crate) mod test_utils;
 mod writer_task;
 
 pub use pipeline::{HlsPipeline, HlsPipelineConfig};
+pub use playlist_writer::{HlsPlaylistWriter, HlsPlaylistWriterConfig};
 pub use writer_task::{HlsWriter, HlsWriterConfig};
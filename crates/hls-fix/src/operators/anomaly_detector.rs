@@ -0,0 +1,323 @@
+//! # Anomaly Detector Operator
+//!
+//! Reports the anomalies of the upstream encoder found in an HLS stream, without changing
+//! the stream.
+//!
+//! ## How it Works
+//!
+//! The operator forwards every segment unchanged and measures each media segment as one
+//! window of the stream:
+//!
+//! 1. The duration is taken from the samples of fMP4 segments, from EXTINF otherwise
+//! 2. The bitrate is the size of the segment over its duration, the frame rate the samples
+//!    of the first fMP4 track over its duration
+//! 3. Segments start at keyframes, so their starts stand in for the keyframes
+//! 4. The drift is the distance between the ends of the tracks of fMP4 segments
+//!
+//! Windows are placed back to back on the timeline of the segments. The measures are fed
+//! to an [`AnomalyDetector`], and the anomalies it reports are emitted as
+//! [`PipelineEvent::AnomalyDetected`](pipeline_common::PipelineEvent) events. The
+//! statistics start over at every discontinuity.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use hls::{HlsData, M4sData};
+use pipeline_common::{
+    Anomaly, AnomalyConfig, AnomalyDetector, PipelineError, Processor, StreamerContext,
+};
+
+use crate::fragment_timing::{FragmentTiming, TrackTimescales};
+
+/// Operator reporting bitrate, segment interval, frame rate and drift anomalies
+pub struct AnomalyDetectorOperator {
+    context: Arc<StreamerContext>,
+    detector: AnomalyDetector,
+    /// Timescales of the last init segment, to time fMP4 media segments
+    timescales: Option<TrackTimescales>,
+    /// Position of the next segment on the timeline, in milliseconds
+    position_ms: u64,
+}
+
+/// Measures of an fMP4 media segment
+struct FragmentMeasures {
+    duration_ms: u64,
+    frames: u64,
+    drift_ms: Option<u64>,
+}
+
+impl AnomalyDetectorOperator {
+    pub fn new(context: Arc<StreamerContext>, config: AnomalyConfig) -> Self {
+        Self {
+            context,
+            detector: AnomalyDetector::new(config),
+            timescales: None,
+            position_ms: 0,
+        }
+    }
+
+    fn report(&self, anomalies: impl IntoIterator<Item = Anomaly>) {
+        for anomaly in anomalies {
+            self.context.emit(anomaly.to_event(self.name()));
+        }
+    }
+
+    /// Report the anomalies going on and start over
+    fn restart(&mut self) {
+        let anomalies = self.detector.finish();
+        self.report(anomalies);
+        self.position_ms = 0;
+    }
+
+    /// Duration, sample count and track drift of an fMP4 media segment
+    fn measure_fragment(&self, data: &Bytes) -> Option<FragmentMeasures> {
+        let timescales = self.timescales.as_ref()?;
+        let timing = FragmentTiming::parse(data).ok()?;
+        let segment = timing.segment_timing(timescales)?;
+        let frames = timing
+            .tracks
+            .iter()
+            .find(|track| track.track_id == segment.track_id)
+            .map_or(0, |track| u64::from(track.sample_count));
+
+        // End of every timed track, in milliseconds
+        let ends: Vec<f64> = timing
+            .tracks
+            .iter()
+            .filter_map(|track| {
+                let defaults = timescales.get(track.track_id)?;
+                let start = track.base_media_decode_time?;
+                if defaults.timescale == 0 {
+                    return None;
+                }
+                let end =
+                    start.saturating_add(track.duration_ticks(defaults.default_sample_duration));
+                Some(end as f64 * 1000.0 / f64::from(defaults.timescale))
+            })
+            .collect();
+        let drift_ms = (ends.len() > 1).then(|| {
+            let max = ends.iter().copied().fold(f64::MIN, f64::max);
+            let min = ends.iter().copied().fold(f64::MAX, f64::min);
+            (max - min).round() as u64
+        });
+
+        Some(FragmentMeasures {
+            duration_ms: (segment.duration() * 1000.0).round() as u64,
+            frames,
+            drift_ms,
+        })
+    }
+
+    /// Add a media segment of `bytes` lasting `duration_ms`
+    fn analyze(&mut self, bytes: u64, duration_ms: u64, frames: Option<u64>) {
+        if duration_ms == 0 {
+            return;
+        }
+        let start_ms = self.position_ms;
+        let end_ms = start_ms + duration_ms;
+        self.position_ms = end_ms;
+
+        let anomaly = self.detector.keyframe(start_ms);
+        self.report(anomaly);
+        let anomalies = self.detector.window(start_ms, end_ms, bytes, frames);
+        self.report(anomalies);
+    }
+}
+
+/// EXTINF duration in milliseconds, zero when it is unusable
+fn extinf_ms(duration: f32) -> u64 {
+    if duration.is_finite() && duration > 0.0 {
+        (f64::from(duration) * 1000.0).round() as u64
+    } else {
+        0
+    }
+}
+
+impl Processor<HlsData> for AnomalyDetectorOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: HlsData,
+        output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        if input.is_discontinuity() {
+            self.restart();
+        }
+
+        match &input {
+            HlsData::TsData(ts) => {
+                self.analyze(ts.data.len() as u64, extinf_ms(ts.segment.duration), None);
+            }
+            HlsData::M4sData(M4sData::InitSegment(init)) => {
+                self.timescales = TrackTimescales::from_init(&init.data).ok();
+            }
+            HlsData::M4sData(M4sData::Segment(segment)) => {
+                let bytes = segment.data.len() as u64;
+                match self.measure_fragment(&segment.data) {
+                    Some(measures) => {
+                        let timestamp_ms = self.position_ms + measures.duration_ms;
+                        self.analyze(bytes, measures.duration_ms, Some(measures.frames));
+                        if let Some(drift_ms) = measures.drift_ms {
+                            let anomaly = self.detector.drift(timestamp_ms, drift_ms);
+                            self.report(anomaly);
+                        }
+                    }
+                    None => self.analyze(bytes, extinf_ms(segment.segment.duration), None),
+                }
+            }
+            HlsData::EndMarker(_) => {}
+        }
+
+        output(input)
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        self.restart();
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "AnomalyDetectorOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hls::{M4sInitSegmentData, M4sSegmentData};
+    use m3u8_rs::MediaSegment;
+    use mp4::test_support::{make_init_with_timescale, make_media_segment_with_timing};
+    use pipeline_common::{CancellationToken, EventCollector, PipelineEvent};
+
+    /// A TS segment advertised as `seconds` long, of `kbps` over that duration
+    fn ts_segment(seconds: u32, kbps: usize) -> HlsData {
+        HlsData::ts(
+            MediaSegment {
+                duration: seconds as f32,
+                ..MediaSegment::empty()
+            },
+            Bytes::from(vec![0x47; kbps * 125 * seconds as usize]),
+        )
+    }
+
+    /// Runs `items` through the operator and returns the events it emitted
+    fn run(items: Vec<HlsData>) -> Vec<PipelineEvent> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let collector = Arc::new(EventCollector::default());
+        context.events.add_handler(collector.clone());
+
+        let mut operator = AnomalyDetectorOperator::new(context.clone(), AnomalyConfig::default());
+        let mut forwarded = Vec::new();
+        let mut output = |item: HlsData| -> Result<(), PipelineError> {
+            forwarded.push(item);
+            Ok(())
+        };
+        let sizes: Vec<_> = items.iter().map(HlsData::size).collect();
+        for item in items {
+            operator.process(&context, item, &mut output).unwrap();
+        }
+        operator.finish(&context, &mut output).unwrap();
+        assert_eq!(
+            forwarded.iter().map(HlsData::size).collect::<Vec<_>>(),
+            sizes
+        );
+
+        collector.events()
+    }
+
+    fn anomaly(kind: &'static str, offset: u64, detail: &str) -> PipelineEvent {
+        PipelineEvent::AnomalyDetected {
+            operator: "AnomalyDetectorOperator",
+            kind,
+            offset: Some(offset),
+            detail: detail.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_bitrate_dip() {
+        // 2s segments at 2000kbps, with 10 seconds at 200kbps from 40s
+        let mut items: Vec<_> = (0..20).map(|_| ts_segment(2, 2000)).collect();
+        items.extend((0..5).map(|_| ts_segment(2, 200)));
+        items.extend((0..10).map(|_| ts_segment(2, 2000)));
+
+        assert_eq!(
+            run(items),
+            [anomaly(
+                "bitrate_collapse",
+                40_000,
+                "bitrate collapsed from 2000.0kbps to 200.0kbps between 40000ms and 50000ms"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_gop_change() {
+        // 2s segments, then 10s segments from 20s once the keyframes are 10s apart
+        let mut items: Vec<_> = (0..10).map(|_| ts_segment(2, 2000)).collect();
+        items.extend((0..5).map(|_| ts_segment(10, 2000)));
+
+        assert_eq!(
+            run(items),
+            [anomaly(
+                "gop_change",
+                20_000,
+                "keyframe interval changed from 2000.0ms to 10000.0ms between 20000ms and 50000ms"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_fmp4_timed_by_samples() {
+        let init = HlsData::M4sData(M4sData::InitSegment(M4sInitSegmentData {
+            segment: MediaSegment::empty(),
+            data: make_init_with_timescale(1, 1000, 0),
+        }));
+        let media = |start: u64, durations: &[u32]| {
+            HlsData::M4sData(M4sData::Segment(M4sSegmentData {
+                // The playlist advertises the same duration throughout
+                segment: MediaSegment {
+                    duration: 6.0,
+                    ..MediaSegment::empty()
+                },
+                data: make_media_segment_with_timing(1, 0, start, durations),
+            }))
+        };
+
+        // Six 2s segments, then 10s segments from 12s
+        let mut items = vec![init];
+        items.extend((0..6).map(|i| media(i * 2000, &[1000; 2])));
+        items.extend((0..4).map(|i| media(12_000 + i * 10_000, &[1000; 10])));
+
+        assert_eq!(
+            run(items),
+            [anomaly(
+                "gop_change",
+                12_000,
+                "keyframe interval changed from 2000.0ms to 10000.0ms between 12000ms and 42000ms"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_discontinuity_restarts_statistics() {
+        // The bitrate of the new period is not compared with the old one
+        let mut items: Vec<_> = (0..20).map(|_| ts_segment(2, 2000)).collect();
+        let HlsData::TsData(mut first) = ts_segment(2, 200) else {
+            unreachable!()
+        };
+        first.segment.discontinuity = true;
+        items.push(HlsData::TsData(first));
+        items.extend((0..10).map(|_| ts_segment(2, 200)));
+
+        assert!(run(items).is_empty());
+    }
+}
@@ -33,7 +33,7 @@
 use std::sync::Arc;
 
 use hls::{HlsData, M4sData, SegmentType, SplitReason, StreamProfile, StreamProfileOptions};
-use pipeline_common::{PipelineError, Processor, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
 use tracing::{debug, info, warn};
 
 pub struct DefragmentOperator {
@@ -74,6 +74,17 @@ impl DefragmentOperator {
         // Don't reset has_init_segment or last_stream_profile as they're properties of the stream
     }
 
+    // Report the buffered items as discarded
+    fn report_discarded(&self, detail: String) {
+        self.context.emit(PipelineEvent::AnomalyDetected {
+            operator: self.name(),
+            kind: "fragment_discarded",
+            offset: None,
+            detail,
+        });
+        self.context.stats.record_dropped(self.buffer.len() as u64);
+    }
+
     fn flush_buffer(
         &mut self,
         output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
@@ -163,12 +174,11 @@ impl DefragmentOperator {
     // Handle cases for FMP4s init segment
     fn handle_new_header(&mut self, data: HlsData) {
         if !self.buffer.is_empty() {
-            warn!(
-                "{} Discarded {} items, total size: {}",
-                self.context.name,
+            self.report_discarded(format!(
+                "Discarded {} items, total size: {}",
                 self.buffer.len(),
                 self.buffer.iter().map(|d| d.size()).sum::<usize>()
-            );
+            ));
             self.reset();
         }
         self.is_gathering = true;
@@ -215,11 +225,10 @@ impl DefragmentOperator {
                 self.flush_buffer(output)?;
                 self.reset();
             } else {
-                warn!(
-                    "{} Discarding incomplete segment on playlist end ({} items)",
-                    self.context.name,
+                self.report_discarded(format!(
+                    "Discarding incomplete segment on playlist end ({} items)",
                     self.buffer.len()
-                );
+                ));
                 self.reset();
             }
         }
@@ -310,9 +319,10 @@ impl DefragmentOperator {
 
                 // Clean up the buffer if it's too large
                 if self.buffer.len() >= Self::MAX_BUFFER_SIZE {
-                    warn!(
-                        "{} Buffer too large, discarding incomplete segment while waiting for init segment",
-                        self.context.name
+                    self.report_discarded(
+                        "Buffer too large, discarding incomplete segment while waiting for init \
+                         segment"
+                            .to_string(),
                     );
                     self.buffer.clear();
                 }
@@ -487,11 +497,10 @@ impl Processor<HlsData> for DefragmentOperator {
                 self.context.name, count
             );
         } else {
-            warn!(
-                "{} Discarding incomplete segment on flush ({} items)",
-                self.context.name,
+            self.report_discarded(format!(
+                "Discarding incomplete segment on flush ({} items)",
                 self.buffer.len()
-            );
+            ));
             self.reset();
         }
 
@@ -0,0 +1,474 @@
+//! # Media Info Operator
+//!
+//! Describes an HLS stream in [`StreamerContext::media_info`] as soon as its parameters
+//! are known.
+//!
+//! ## How it Works
+//!
+//! The operator forwards every segment unchanged and:
+//!
+//! 1. Takes the codecs of TS segments from their PMT, the profile, level, dimensions and
+//!    frame rate from the SPS, and the AAC sample rate and channels from the ADTS header
+//! 2. Takes the codecs, profile, level and dimensions of fMP4 streams from the codec
+//!    configuration boxes of their init segment
+//! 3. Publishes once every track is described, or after a few segments otherwise
+//! 4. Publishes again whenever a later segment changes the description
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use aac::AdtsHeader;
+use bytes::Bytes;
+use hls::{HlsData, M4sData, ResolutionDetector, TsSegmentData};
+use mp4::isobmff::{InitSegmentInfo, ParseOptions, parse_init_segment_with_options};
+use pipeline_common::{
+    AudioInfo, MediaInfo, MediaInfoValue, PipelineError, Processor, StreamerContext, VideoInfo,
+};
+use tracing::{debug, info};
+use ts::{PesHeader, StreamType, TsPacketRef};
+
+/// Extra holding the container of the segments, `ts` or `fmp4`
+const EXTRA_CONTAINER: &str = "container";
+/// Extra holding the ISO 639 language of the audio track, when the PMT declares one
+const EXTRA_AUDIO_LANGUAGE: &str = "audio_language";
+
+/// How many media segments an incomplete description is waited for
+const MAX_WAIT_SEGMENTS: u32 = 3;
+
+/// Operator publishing the [`MediaInfo`] of an HLS stream
+pub struct MediaInfoOperator {
+    context: Arc<StreamerContext>,
+    video: Option<VideoInfo>,
+    audio: Option<AudioInfo>,
+    extras: BTreeMap<String, MediaInfoValue>,
+    /// Whether every track of the latest segment is fully described
+    complete: bool,
+    segments_seen: u32,
+    published: bool,
+    /// Whether the description changed since it was last published
+    dirty: bool,
+}
+
+impl MediaInfoOperator {
+    pub fn new(context: Arc<StreamerContext>) -> Self {
+        Self {
+            context,
+            video: None,
+            audio: None,
+            extras: BTreeMap::new(),
+            complete: false,
+            segments_seen: 0,
+            published: false,
+            dirty: false,
+        }
+    }
+
+    fn media_info(&self) -> MediaInfo {
+        MediaInfo {
+            video: self.video.clone(),
+            audio: self.audio.clone(),
+            extras: self.extras.clone(),
+            changed: false,
+        }
+    }
+
+    fn set_video(&mut self, video: Option<VideoInfo>) {
+        if self.video != video {
+            self.video = video;
+            self.dirty = true;
+        }
+    }
+
+    fn set_audio(&mut self, audio: Option<AudioInfo>) {
+        if self.audio != audio {
+            self.audio = audio;
+            self.dirty = true;
+        }
+    }
+
+    fn set_extra(&mut self, key: &str, value: Option<MediaInfoValue>) {
+        let changed = match value {
+            Some(value) => self.extras.insert(key.to_string(), value.clone()) != Some(value),
+            None => self.extras.remove(key).is_some(),
+        };
+        self.dirty |= changed;
+    }
+
+    fn analyze_ts(&mut self, ts_data: &TsSegmentData) {
+        let (stream_info, packets) = match ts_data.parse_stream_and_packets() {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                debug!("{} Failed to parse TS packets: {}", self.context.name, e);
+                return;
+            }
+        };
+        // Segments without PSI tables cannot be attributed to a program
+        let Some(program) = stream_info.programs.first() else {
+            return;
+        };
+        self.set_extra(
+            EXTRA_CONTAINER,
+            Some(MediaInfoValue::Text("ts".to_string())),
+        );
+
+        let video = program.video_streams.first().map(|stream| {
+            let codec = video_codec_name(stream.stream_type);
+            let mut video = match &self.video {
+                Some(video) if video.codec == codec => video.clone(),
+                _ => blank_video(codec),
+            };
+            // SPS are carried by random access points, probe those once the stream is known
+            let should_probe =
+                video.width.is_none() || packets.iter().any(|p| p.has_random_access_indicator());
+            if should_probe
+                && let Some(parameters) = ResolutionDetector::extract_parameters_from_ts_packets(
+                    packets.iter(),
+                    &[(stream.pid, stream.stream_type)],
+                )
+            {
+                video.profile = Some(parameters.profile);
+                video.level = parameters.level;
+                video.width = Some(parameters.resolution.width);
+                video.height = Some(parameters.resolution.height);
+                video.frame_rate = parameters.frame_rate;
+            }
+            video
+        });
+
+        let audio = program.audio_streams.first().map(|stream| {
+            let codec = audio_codec_name(stream.stream_type);
+            let mut audio = match &self.audio {
+                Some(audio) if audio.codec == codec => audio.clone(),
+                _ => AudioInfo {
+                    codec,
+                    sample_rate: None,
+                    channels: None,
+                },
+            };
+            if stream.stream_type == StreamType::AdtsAac
+                && audio.sample_rate.is_none()
+                && let Some(header) = first_adts_header(&packets, stream.pid)
+            {
+                audio.sample_rate = header.sampling_frequency_index.to_freq();
+                audio.channels = Some(header.channel_configuration);
+            }
+            audio
+        });
+        let audio_stream = program.audio_streams.first();
+        let language = audio_stream.and_then(|stream| stream.language.clone());
+        // Only ADTS carries the sample rate and channels in the stream
+        let adts = audio_stream.is_some_and(|stream| stream.stream_type == StreamType::AdtsAac);
+
+        self.complete = video.as_ref().is_none_or(|video| video.width.is_some())
+            && audio
+                .as_ref()
+                .is_none_or(|audio| audio.sample_rate.is_some() || !adts);
+        self.set_video(video);
+        self.set_audio(audio);
+        self.set_extra(EXTRA_AUDIO_LANGUAGE, language.map(MediaInfoValue::Text));
+    }
+
+    fn analyze_init_segment(&mut self, data: &Bytes) {
+        let info = parse_init_segment_with_options(
+            data,
+            ParseOptions {
+                include_resolution: true,
+            },
+        );
+        self.set_extra(
+            EXTRA_CONTAINER,
+            Some(MediaInfoValue::Text("fmp4".to_string())),
+        );
+
+        let audio = if info.has_aac {
+            Some("AAC")
+        } else if info.has_ac3 {
+            Some("AC-3")
+        } else {
+            None
+        }
+        .map(|codec| AudioInfo {
+            codec: codec.to_string(),
+            sample_rate: None,
+            channels: None,
+        });
+
+        // The init segment is all there is to learn about an fMP4 stream
+        self.complete = true;
+        self.set_video(init_segment_video(&info));
+        self.set_audio(audio);
+        self.set_extra(EXTRA_AUDIO_LANGUAGE, None);
+    }
+
+    fn publish(&mut self) {
+        let media_info = self.media_info();
+        if self.context.media_info.publish(media_info.clone()) {
+            info!(
+                "{} Media info{}: {}",
+                self.context.name,
+                if self.published { " changed" } else { "" },
+                media_info
+            );
+        }
+        self.published = true;
+        self.dirty = false;
+    }
+}
+
+fn video_codec_name(stream_type: StreamType) -> String {
+    match stream_type {
+        StreamType::H264 => "AVC".to_string(),
+        StreamType::H265 => "HEVC".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+fn audio_codec_name(stream_type: StreamType) -> String {
+    match stream_type {
+        StreamType::AdtsAac | StreamType::LatmAac => "AAC".to_string(),
+        StreamType::Ac3 => "AC-3".to_string(),
+        StreamType::EAc3 => "E-AC-3".to_string(),
+        StreamType::Mpeg1Audio | StreamType::Mpeg2Audio => "MP3".to_string(),
+        other => format!("{other:?}"),
+    }
+}
+
+fn blank_video(codec: String) -> VideoInfo {
+    VideoInfo {
+        codec,
+        profile: None,
+        level: None,
+        width: None,
+        height: None,
+        frame_rate: None,
+    }
+}
+
+/// The ADTS header starting the first PES packet of `pid`
+fn first_adts_header(packets: &[TsPacketRef], pid: u16) -> Option<AdtsHeader> {
+    let payload = packets
+        .iter()
+        .filter(|p| p.pid == pid && p.payload_unit_start_indicator)
+        .find_map(|p| p.payload())?;
+    let pes_header = PesHeader::parse(&payload).ok()?;
+    AdtsHeader::parse(payload.get(pes_header.payload_offset..)?).ok()
+}
+
+/// The video track described by the codec configuration box of an init segment
+fn init_segment_video(info: &InitSegmentInfo) -> Option<VideoInfo> {
+    // Profile and level are at fixed offsets of the configuration records
+    let (codec, profile, level) = if info.has_h264 {
+        let avcc = info.avcc_data.as_deref();
+        (
+            "AVC",
+            avcc.and_then(|c| c.get(1)).copied(),
+            avcc.and_then(|c| c.get(3)).copied(),
+        )
+    } else if info.has_h265 {
+        let hvcc = info.hvcc_data.as_deref();
+        (
+            "HEVC",
+            hvcc.and_then(|c| c.get(1)).map(|b| b & 0x1F),
+            hvcc.and_then(|c| c.get(12)).copied(),
+        )
+    } else if info.has_av1 {
+        let av1c = info.av1c_data.as_deref();
+        (
+            "AV1",
+            av1c.and_then(|c| c.get(1)).map(|b| b >> 5),
+            av1c.and_then(|c| c.get(1)).map(|b| b & 0x1F),
+        )
+    } else {
+        return None;
+    };
+
+    Some(VideoInfo {
+        profile,
+        level,
+        width: info.video_resolution.map(|r| r.width),
+        height: info.video_resolution.map(|r| r.height),
+        ..blank_video(codec.to_string())
+    })
+}
+
+impl Processor<HlsData> for MediaInfoOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: HlsData,
+        output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        match &input {
+            HlsData::TsData(ts_data) => {
+                self.analyze_ts(ts_data);
+                self.segments_seen += 1;
+            }
+            HlsData::M4sData(M4sData::InitSegment(init)) => {
+                self.analyze_init_segment(&init.data);
+            }
+            HlsData::M4sData(M4sData::Segment(_)) => {
+                self.segments_seen += 1;
+            }
+            HlsData::EndMarker(_) => {}
+        }
+
+        let publish = if self.published {
+            self.dirty
+        } else {
+            let described = self.video.is_some() || self.audio.is_some();
+            described && (self.complete || self.segments_seen >= MAX_WAIT_SEGMENTS)
+        };
+        if publish {
+            self.publish();
+        }
+
+        output(input)
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        // Describe short streams with whatever is known
+        if !self.published && !self.media_info().is_empty() {
+            self.publish();
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "MediaInfoOperator"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_ts_data_with_codecs, create_ts_data_with_sps_and_adts};
+    use m3u8_rs::MediaSegment;
+    use mp4::test_support::{make_audio_sample_entry, make_box, make_full_box};
+    use mp4::test_support::{make_init_with_video_sample_entry, make_visual_sample_entry};
+    use pipeline_common::{ProgressEvent, init_test_tracing};
+    use std::sync::Mutex;
+    use tokio_util::sync::CancellationToken;
+
+    /// Runs `items` through the operator and returns the published media infos.
+    fn run(items: Vec<HlsData>) -> Vec<MediaInfo> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let published = Arc::new(Mutex::new(Vec::new()));
+        let sink = published.clone();
+        context.media_info.subscribe(move |event| {
+            if let ProgressEvent::MediaInfo(info) = event {
+                sink.lock().unwrap().push(info);
+            }
+        });
+
+        let mut operator = MediaInfoOperator::new(context.clone());
+        let mut forwarded = 0;
+        let mut output = |_: HlsData| -> Result<(), PipelineError> {
+            forwarded += 1;
+            Ok(())
+        };
+        let count = items.len();
+        for item in items {
+            operator.process(&context, item, &mut output).unwrap();
+        }
+        operator.finish(&context, &mut output).unwrap();
+        assert_eq!(forwarded, count);
+
+        published.lock().unwrap().clone()
+    }
+
+    fn ts_segment(data: Vec<u8>) -> HlsData {
+        HlsData::ts(MediaSegment::empty(), Bytes::from(data))
+    }
+
+    #[test]
+    fn test_ts_stream_is_described_from_sps_and_adts() {
+        init_test_tracing!();
+
+        // 48 kHz stereo
+        let published = run(vec![
+            ts_segment(create_ts_data_with_sps_and_adts(1280, 720, 3, 2)),
+            ts_segment(create_ts_data_with_sps_and_adts(1280, 720, 3, 2)),
+        ]);
+
+        assert_eq!(published.len(), 1);
+        let info = &published[0];
+        assert!(!info.changed);
+        let video = info.video.as_ref().unwrap();
+        assert_eq!(video.codec, "AVC");
+        assert_eq!((video.profile, video.level), (Some(77), Some(0)));
+        assert_eq!(info.resolution(), Some((1280, 720)));
+        let audio = info.audio.as_ref().unwrap();
+        assert_eq!(audio.codec, "AAC");
+        assert_eq!((audio.sample_rate, audio.channels), (Some(48000), Some(2)));
+        assert_eq!(
+            info.extras.get(EXTRA_CONTAINER),
+            Some(&MediaInfoValue::Text("ts".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_codec_change_is_published_as_change() {
+        init_test_tracing!();
+
+        let published = run(vec![
+            ts_segment(create_ts_data_with_sps_and_adts(1280, 720, 3, 2)),
+            ts_segment(create_ts_data_with_codecs(0x24, 0x0F, 1)), // H.265 + AAC
+        ]);
+
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].video.as_ref().unwrap().codec, "AVC");
+        assert!(published[1].changed);
+        let video = published[1].video.as_ref().unwrap();
+        assert_eq!(video.codec, "HEVC");
+        assert_eq!(video.width, None);
+        // The audio track is unchanged and keeps its parameters
+        assert_eq!(published[1].audio, published[0].audio);
+    }
+
+    #[test]
+    fn test_fmp4_stream_is_described_from_init_segment() {
+        init_test_tracing!();
+
+        // AVC High 4.0 without parameter sets, and AAC
+        let avcc = make_box(b"avcC", &[1, 100, 0, 40, 0xFF, 0xE0, 0]);
+        let mut entries = 2u32.to_be_bytes().to_vec();
+        entries.extend_from_slice(&make_visual_sample_entry(b"avc1", &avcc));
+        entries.extend_from_slice(&make_audio_sample_entry(b"mp4a", &[]));
+        let stsd = make_full_box(b"stsd", 0, 0, &entries);
+        let trak = make_box(
+            b"trak",
+            &make_box(b"mdia", &make_box(b"minf", &make_box(b"stbl", &stsd))),
+        );
+        let init = Bytes::from(make_box(b"moov", &trak));
+
+        let published = run(vec![
+            HlsData::mp4_init(MediaSegment::empty(), init),
+            HlsData::mp4_init(
+                MediaSegment::empty(),
+                make_init_with_video_sample_entry(1, *b"hvc1"),
+            ),
+        ]);
+
+        assert_eq!(published.len(), 2);
+        let video = published[0].video.as_ref().unwrap();
+        assert_eq!(video.codec, "AVC");
+        assert_eq!((video.profile, video.level), (Some(100), Some(40)));
+        assert_eq!(published[0].audio.as_ref().unwrap().codec, "AAC");
+        assert_eq!(
+            published[0].extras.get(EXTRA_CONTAINER),
+            Some(&MediaInfoValue::Text("fmp4".to_string()))
+        );
+
+        assert!(published[1].changed);
+        assert_eq!(published[1].video.as_ref().unwrap().codec, "HEVC");
+        assert_eq!(published[1].audio, None);
+    }
+}
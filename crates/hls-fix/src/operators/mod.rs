@@ -1,7 +1,15 @@
+mod anomaly_detector;
 mod defragment;
+mod media_info;
 mod segment_limiter;
 mod segment_split;
+mod timed_metadata;
+mod transmux;
 
+pub use anomaly_detector::AnomalyDetectorOperator;
 pub use defragment::DefragmentOperator;
+pub use media_info::MediaInfoOperator;
 pub use segment_limiter::SegmentLimiterOperator;
 pub use segment_split::SegmentSplitOperator;
+pub use timed_metadata::{OnTimedMetadata, TimedMetadataEvent, TimedMetadataOperator};
+pub use transmux::TransmuxOperator;
@@ -1,6 +1,7 @@
+use crate::fragment_timing::{FragmentTiming, TrackTimescales};
 use bytes::Bytes;
 use hls::{HlsData, M4sData, M4sInitSegmentData, SegmentType, SplitReason};
-use pipeline_common::{PipelineError, Processor, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, StreamerContext};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::debug;
@@ -15,6 +16,8 @@ pub struct SegmentLimiterOperator {
     init_segment: Option<M4sInitSegmentData>,
     // Track if we've output an init segment recently
     init_segment_sent: bool,
+    // Track timescales of the last init segment, to time fMP4 media segments
+    timescales: Option<TrackTimescales>,
 }
 
 impl SegmentLimiterOperator {
@@ -26,9 +29,19 @@ impl SegmentLimiterOperator {
             current_size: 0,
             init_segment: None,
             init_segment_sent: false,
+            timescales: None,
         }
     }
 
+    /// Duration of an fMP4 media segment from its samples, falling back to EXTINF
+    fn media_segment_duration(&self, data: &Bytes, extinf_duration: f32) -> f32 {
+        self.timescales
+            .as_ref()
+            .and_then(|timescales| FragmentTiming::parse(data).ok()?.segment_timing(timescales))
+            .map(|timing| timing.duration() as f32)
+            .unwrap_or(extinf_duration)
+    }
+
     fn safe_duration(secs: f32) -> Duration {
         if !secs.is_finite() || secs <= 0.0 {
             Duration::ZERO
@@ -113,6 +126,11 @@ impl Processor<HlsData> for SegmentLimiterOperator {
                     if let Some(reason) =
                         self.check_limit_reached(&ts_data.data, ts_data.segment.duration)
                     {
+                        context.emit(PipelineEvent::SplitOccurred {
+                            operator: self.name(),
+                            reason: Some(reason.clone()),
+                            offset: None,
+                        });
                         output(HlsData::end_marker_with_reason(reason))?;
                         self.reset_counters();
                     }
@@ -129,6 +147,7 @@ impl Processor<HlsData> for SegmentLimiterOperator {
                     if self.init_segment.is_none() {
                         self.init_segment = Some(init_segment.clone());
                     }
+                    self.timescales = TrackTimescales::from_init(&init_segment.data).ok();
 
                     // Always output the init segment when we encounter it directly
                     output(HlsData::M4sData(M4sData::InitSegment(init_segment)))?;
@@ -137,10 +156,16 @@ impl Processor<HlsData> for SegmentLimiterOperator {
             }
             SegmentType::M4sMedia => {
                 if let HlsData::M4sData(M4sData::Segment(segment)) = input {
+                    let duration =
+                        self.media_segment_duration(&segment.data, segment.segment.duration);
+
                     // Check if the current segment would exceed the limit. If so, start a new sequence.
-                    if let Some(reason) =
-                        self.check_limit_reached(&segment.data, segment.segment.duration)
-                    {
+                    if let Some(reason) = self.check_limit_reached(&segment.data, duration) {
+                        context.emit(PipelineEvent::SplitOccurred {
+                            operator: self.name(),
+                            reason: Some(reason.clone()),
+                            offset: None,
+                        });
                         output(HlsData::end_marker_with_reason(reason))?;
                         self.reset_counters();
                     }
@@ -153,7 +178,7 @@ impl Processor<HlsData> for SegmentLimiterOperator {
                         self.init_segment_sent = true;
                     }
 
-                    self.track_segment(&segment.data, segment.segment.duration);
+                    self.track_segment(&segment.data, duration);
 
                     // Unconditionally output the current media segment and track its metrics.
                     output(HlsData::M4sData(M4sData::Segment(segment)))?;
@@ -250,4 +275,47 @@ mod tests {
         assert_eq!(out.len(), 1);
         assert!(matches!(out[0], HlsData::TsData(_)));
     }
+
+    #[test]
+    fn splits_fmp4_on_sample_duration() {
+        use hls::{M4sInitSegmentData, M4sSegmentData};
+        use mp4::test_support::{make_init_with_timescale, make_media_segment_with_timing};
+
+        let token = CancellationToken::new();
+        let context = StreamerContext::arc_new(token);
+        let mut operator = SegmentLimiterOperator::new(Some(Duration::from_secs(4)), None);
+
+        let mut out = Vec::new();
+        let mut output = |item: HlsData| -> Result<(), PipelineError> {
+            out.push(item);
+            Ok(())
+        };
+
+        let init = HlsData::M4sData(M4sData::InitSegment(M4sInitSegmentData {
+            segment: MediaSegment::empty(),
+            data: make_init_with_timescale(1, 1000, 0),
+        }));
+        operator.process(&context, init, &mut output).unwrap();
+
+        // Advertised as 1s each, but every segment holds 2.1s of samples
+        for start in [0, 2100] {
+            let media = HlsData::M4sData(M4sData::Segment(M4sSegmentData {
+                segment: MediaSegment {
+                    duration: 1.0,
+                    ..MediaSegment::empty()
+                },
+                data: make_media_segment_with_timing(1, 0, start, &[1050, 1050]),
+            }));
+            operator.process(&context, media, &mut output).unwrap();
+        }
+
+        assert_eq!(out.len(), 5);
+        assert!(matches!(out[1], HlsData::M4sData(M4sData::Segment(_))));
+        assert!(matches!(
+            out[2],
+            HlsData::EndMarker(Some(SplitReason::DurationLimit))
+        ));
+        assert!(matches!(out[3], HlsData::M4sData(M4sData::InitSegment(_))));
+        assert!(matches!(out[4], HlsData::M4sData(M4sData::Segment(_))));
+    }
 }
@@ -2,7 +2,7 @@ use hls::{
     HlsData, M4sData, M4sInitSegmentData, Resolution, ResolutionDetector, StreamProfile,
     TsStreamInfo,
 };
-use pipeline_common::{PipelineError, Processor, SplitReason, StreamerContext};
+use pipeline_common::{PipelineError, PipelineEvent, Processor, SplitReason, StreamerContext};
 use std::sync::Arc;
 use tracing::{debug, info, warn};
 
@@ -20,6 +20,12 @@ use crate::crc32;
 /// - **Program number changes**: Indicates a different broadcast program
 /// - **Transport Stream ID changes**: Indicates a different stream source
 /// - **Elementary stream codec type changes**: e.g., H.264 → H.265 at PMT level
+/// - **Playlist discontinuities**: Timestamps restart after `#EXT-X-DISCONTINUITY`, so the
+///   segments on either side cannot be concatenated. Skipped if an end marker already
+///   separates them.
+///
+/// After any end marker the last init segment is re-emitted before the next fMP4 media
+/// segment, unless a new init segment arrives first.
 ///
 /// # Ignored Changes (normal in live HLS, no split)
 ///
@@ -49,6 +55,10 @@ pub struct SegmentSplitOperator {
     /// Some streams only carry SPS intermittently; once we have a baseline
     /// resolution, we only probe when we can compare against it.
     resolution_probe_remaining: u8,
+    /// Whether no media segment has been seen since the last end marker or split
+    at_period_start: bool,
+    /// Init segment to re-emit if fMP4 media follows an end marker without a new init segment
+    init_after_end_marker: Option<M4sInitSegmentData>,
 }
 
 impl SegmentSplitOperator {
@@ -66,6 +76,8 @@ impl SegmentSplitOperator {
             last_resolution: None,
             last_init_segment: None,
             resolution_probe_remaining: 50,
+            at_period_start: true,
+            init_after_end_marker: None,
         }
     }
 
@@ -547,6 +559,8 @@ impl SegmentSplitOperator {
         self.last_resolution = None;
         self.last_init_segment = None;
         self.resolution_probe_remaining = 50;
+        self.at_period_start = true;
+        self.init_after_end_marker = None;
     }
 }
 
@@ -562,28 +576,55 @@ impl Processor<HlsData> for SegmentSplitOperator {
         }
         let mut split_reason = None;
 
+        if input.is_discontinuity() && !self.at_period_start {
+            // Compare the new period against itself only, keeping the init segment for media
+            // segments that do not bring their own
+            let init_segment = self.last_init_segment.take();
+            self.reset();
+            self.last_init_segment = init_segment;
+            split_reason = Some(SplitReason::Discontinuity);
+        }
+
         // Check if we need to split based on segment type
         match &input {
             HlsData::M4sData(M4sData::InitSegment(_)) => {
                 debug!("Init segment received");
-                split_reason = self.handle_init_segment(&input)?;
+                self.init_after_end_marker = None;
+                split_reason = split_reason.or(self.handle_init_segment(&input)?);
+            }
+            HlsData::M4sData(M4sData::Segment(_)) => {
+                if let Some(init_segment) = self.init_after_end_marker.take() {
+                    debug!(
+                        "{} Re-emitting init segment after end marker",
+                        self.context.name
+                    );
+                    let init = HlsData::M4sData(M4sData::InitSegment(init_segment));
+                    self.handle_init_segment(&init)?;
+                    output(init)?;
+                }
             }
             HlsData::TsData(_) => {
-                split_reason = self.handle_ts_segment(&input)?;
+                self.init_after_end_marker = None;
+                split_reason = split_reason.or(self.handle_ts_segment(&input)?);
             }
             HlsData::EndMarker(_) => {
                 // Reset state when we see an end marker
+                let init_segment = self.last_init_segment.take();
                 self.reset();
+                self.init_after_end_marker = init_segment;
             }
-            _ => {}
         }
+        // Init segments belong to the period of the media segments that follow them
+        self.at_period_start = input.is_end_marker()
+            || (input.is_init_segment() && (self.at_period_start || split_reason.is_some()));
 
         // If we need to split, emit an end marker first
         if let Some(reason) = split_reason {
-            debug!(
-                "{} Emitting end marker for segment split",
-                self.context.name
-            );
+            context.emit(PipelineEvent::SplitOccurred {
+                operator: self.name(),
+                reason: Some(reason.clone()),
+                offset: None,
+            });
             output(HlsData::end_marker_with_reason(reason))?;
 
             // If the split was triggered by a non-init segment, we need to re-emit the last init segment.
@@ -621,195 +662,12 @@ impl Processor<HlsData> for SegmentSplitOperator {
 mod tests {
     use super::*;
     use bytes::Bytes;
+    use hls::SegmentType;
     use m3u8_rs::MediaSegment;
     use pipeline_common::init_test_tracing;
     use tokio_util::sync::CancellationToken;
 
-    // Helper function to create a working TS data with specific codec combinations
-    fn create_ts_data_with_codecs(video_codec: u8, audio_codec: u8, program_num: u16) -> Vec<u8> {
-        let mut ts_data = Vec::new();
-
-        // PAT packet (188 bytes)
-        let mut pat_packet = vec![0u8; 188];
-        pat_packet[0] = 0x47; // Sync byte
-        pat_packet[1] = 0x40; // PUSI set, PID = 0 (PAT)
-        pat_packet[2] = 0x00;
-        pat_packet[3] = 0x10; // No scrambling, payload only
-
-        // Simple PAT payload
-        pat_packet[4] = 0x00; // Pointer field
-        pat_packet[5] = 0x00; // Table ID (PAT)
-        pat_packet[6] = 0x80; // Section syntax indicator
-        pat_packet[7] = 0x0D; // Section length (13 bytes)
-        pat_packet[8] = 0x00;
-        pat_packet[9] = 0x01; // Transport stream ID
-        pat_packet[10] = 0x01; // Version 0 + current/next = 1
-        pat_packet[11] = 0x00;
-        pat_packet[12] = 0x00; // Section numbers
-        // Program entry
-        pat_packet[13] = (program_num >> 8) as u8;
-        pat_packet[14] = (program_num & 0xFF) as u8;
-        pat_packet[15] = 0xE1;
-        pat_packet[16] = 0x00; // PMT PID 0x100
-
-        // PMT packet (188 bytes)
-        let mut pmt_packet = vec![0u8; 188];
-        pmt_packet[0] = 0x47; // Sync byte
-        pmt_packet[1] = 0x41; // PUSI set, PID = 0x100
-        pmt_packet[2] = 0x00;
-        pmt_packet[3] = 0x10; // No scrambling, payload only
-
-        pmt_packet[4] = 0x00; // Pointer field
-        pmt_packet[5] = 0x02; // Table ID (PMT)
-        pmt_packet[6] = 0x80; // Section syntax indicator
-        pmt_packet[7] = 0x17; // Section length (23 bytes for 2 streams)
-        pmt_packet[8] = (program_num >> 8) as u8;
-        pmt_packet[9] = (program_num & 0xFF) as u8;
-        pmt_packet[10] = 0x01; // Version 0 + current/next = 1
-        pmt_packet[11] = 0x00;
-        pmt_packet[12] = 0x00; // Section numbers
-        pmt_packet[13] = 0xE1;
-        pmt_packet[14] = 0x00; // PCR PID 0x100
-        pmt_packet[15] = 0x00;
-        pmt_packet[16] = 0x00; // Program info length
-        // Video stream
-        pmt_packet[17] = video_codec;
-        pmt_packet[18] = 0xE1;
-        pmt_packet[19] = 0x00; // Elementary PID 0x100
-        pmt_packet[20] = 0x00;
-        pmt_packet[21] = 0x00; // ES info length
-        // Audio stream
-        pmt_packet[22] = audio_codec;
-        pmt_packet[23] = 0xE1;
-        pmt_packet[24] = 0x01; // Elementary PID 0x101
-        pmt_packet[25] = 0x00;
-        pmt_packet[26] = 0x00; // ES info length
-
-        ts_data.extend_from_slice(&pat_packet);
-        ts_data.extend_from_slice(&pmt_packet);
-        ts_data
-    }
-
-    fn make_ts_packet_header(pid: u16, pusi: bool, adaptation_field_control: u8) -> [u8; 4] {
-        // sync
-        let mut header = [0u8; 4];
-        header[0] = 0x47;
-        header[1] = ((pusi as u8) << 6) | ((pid >> 8) as u8 & 0x1F);
-        header[2] = (pid & 0xFF) as u8;
-        // no scrambling, afc + continuity 0
-        header[3] = adaptation_field_control << 4;
-        header
-    }
-
-    fn make_ts_packet_with_rai(pid: u16, rai: bool, payload: &[u8]) -> [u8; 188] {
-        // adaptation_field_control=0x03 (adaptation + payload)
-        let mut pkt = [0u8; 188];
-        let header = make_ts_packet_header(pid, true, 0x03);
-        pkt[..4].copy_from_slice(&header);
-
-        // Adaptation field: length + flags byte.
-        // We only need the flags byte for RAI; no PCR.
-        pkt[4] = 1; // adaptation_field_length
-        pkt[5] = if rai { 0x40 } else { 0x00 };
-
-        let payload_start = 6;
-        let max_payload = 188 - payload_start;
-        let payload_len = payload.len().min(max_payload);
-        pkt[payload_start..payload_start + payload_len].copy_from_slice(&payload[..payload_len]);
-        for b in &mut pkt[payload_start + payload_len..] {
-            *b = 0xFF;
-        }
-        pkt
-    }
-
-    fn make_fake_h264_sps_nal(width: u32, height: u32) -> Vec<u8> {
-        // Generate a valid H.264 SPS NAL unit that our h264 crate can parse.
-        // Start code is NOT included here.
-        use bytes_util::BitWriter;
-        use expgolomb::BitWriterExpGolombExt;
-
-        let mut out = Vec::new();
-        let mut w = BitWriter::new(&mut out);
-
-        // NAL header (forbidden_zero_bit=0, nal_ref_idc=0, nal_unit_type=7)
-        w.write_bit(false).unwrap();
-        w.write_bits(0, 2).unwrap();
-        w.write_bits(7, 5).unwrap();
-
-        // profile_idc 77 (Main), constraint flags 0, level_idc 0
-        w.write_bits(77, 8).unwrap();
-        w.write_bits(0, 8).unwrap();
-        w.write_bits(0, 8).unwrap();
-
-        // seq_parameter_set_id
-        w.write_exp_golomb(0).unwrap();
-        // log2_max_frame_num_minus4
-        w.write_exp_golomb(0).unwrap();
-        // pic_order_cnt_type
-        w.write_exp_golomb(0).unwrap();
-        // log2_max_pic_order_cnt_lsb_minus4
-        w.write_exp_golomb(0).unwrap();
-
-        // max_num_ref_frames
-        w.write_exp_golomb(0).unwrap();
-        // gaps_in_frame_num_value_allowed_flag
-        w.write_bit(false).unwrap();
-
-        // pic_width_in_mbs_minus1, pic_height_in_map_units_minus1
-        let width_mbs = (width / 16).saturating_sub(1) as u64;
-        let height_map_units = (height / 16).saturating_sub(1) as u64;
-        w.write_exp_golomb(width_mbs).unwrap();
-        w.write_exp_golomb(height_map_units).unwrap();
-
-        // frame_mbs_only_flag (progressive)
-        w.write_bit(true).unwrap();
-        // direct_8x8_inference_flag
-        w.write_bit(false).unwrap();
-        // frame_cropping_flag
-        w.write_bit(false).unwrap();
-        // vui_parameters_present_flag
-        w.write_bit(false).unwrap();
-
-        w.finish().unwrap();
-        out
-    }
-
-    fn make_fake_h264_pes_with_sps(width: u32, height: u32) -> Vec<u8> {
-        // Minimal PES header + start code prefix + SPS NAL.
-        // Our detector scans payloads for start codes.
-        let mut out = Vec::new();
-        // PES start code prefix
-        out.extend_from_slice(&[0x00, 0x00, 0x01]);
-        // stream_id (video)
-        out.push(0xE0);
-        // PES_packet_length = 0 (unknown)
-        out.extend_from_slice(&[0x00, 0x00]);
-        // flags: '10' + no scrambling etc.
-        out.push(0x80);
-        // PTS/DTS flags 00
-        out.push(0x00);
-        // header_data_length 0
-        out.push(0x00);
-
-        // Annex B start code + SPS
-        out.extend_from_slice(&[0x00, 0x00, 0x01]);
-        out.extend_from_slice(&make_fake_h264_sps_nal(width, height));
-        out
-    }
-
-    fn create_ts_data_with_rai_and_sps(
-        video_pid: u16,
-        rai: bool,
-        width: u32,
-        height: u32,
-    ) -> Vec<u8> {
-        // Reuse PAT/PMT from helper, then append one video packet with PES/SPS.
-        let mut ts_data = create_ts_data_with_codecs(0x1B, 0x0F, 1);
-        let pes = make_fake_h264_pes_with_sps(width, height);
-        let video_packet = make_ts_packet_with_rai(video_pid, rai, &pes);
-        ts_data.extend_from_slice(&video_packet);
-        ts_data
-    }
+    use crate::test_utils::{create_ts_data_with_codecs, create_ts_data_with_rai_and_sps};
 
     #[test]
     fn test_stream_change_detection() {
@@ -1054,4 +912,135 @@ mod tests {
         assert_eq!(output_items.len(), 3);
         assert!(matches!(&output_items[1], HlsData::EndMarker(_)));
     }
+
+    fn ts_segment(discontinuity: bool) -> HlsData {
+        HlsData::TsData(hls::TsSegmentData {
+            segment: MediaSegment {
+                discontinuity,
+                ..MediaSegment::empty()
+            },
+            data: Bytes::from(create_ts_data_with_codecs(0x1B, 0x0F, 1)),
+            validate_crc: false,
+            continuity_mode: ts::ContinuityMode::Warn,
+        })
+    }
+
+    fn m4s_segment(discontinuity: bool) -> HlsData {
+        HlsData::mp4_segment(
+            MediaSegment {
+                discontinuity,
+                ..MediaSegment::empty()
+            },
+            Bytes::from_static(b"\x00\x00\x00\x08moof"),
+        )
+    }
+
+    fn run(items: Vec<HlsData>) -> Vec<HlsData> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = SegmentSplitOperator::new(context.clone());
+        let mut output_items = Vec::new();
+        for item in items {
+            operator
+                .process(&context, item, &mut |item: HlsData| {
+                    output_items.push(item);
+                    Ok(())
+                })
+                .unwrap();
+        }
+        output_items
+    }
+
+    #[test]
+    fn test_ts_discontinuity_splits() {
+        init_test_tracing!();
+        let output = run(vec![ts_segment(false), ts_segment(true), ts_segment(false)]);
+
+        assert_eq!(output.len(), 4);
+        assert!(matches!(
+            &output[1],
+            HlsData::EndMarker(Some(SplitReason::Discontinuity))
+        ));
+        assert!(output[2].is_discontinuity());
+    }
+
+    #[test]
+    fn test_discontinuity_after_end_marker_does_not_split_again() {
+        init_test_tracing!();
+        let output = run(vec![
+            ts_segment(false),
+            HlsData::end_marker_with_reason(SplitReason::Discontinuity),
+            ts_segment(true),
+        ]);
+
+        assert_eq!(output.len(), 3);
+        assert_eq!(output.iter().filter(|item| item.is_end_marker()).count(), 1);
+    }
+
+    #[test]
+    fn test_init_segment_reemitted_after_discontinuity() {
+        init_test_tracing!();
+        let init = HlsData::mp4_init(MediaSegment::empty(), Bytes::from_static(b"init"));
+
+        // Discontinuity announced by an upstream end marker
+        let output = run(vec![
+            init.clone(),
+            m4s_segment(false),
+            HlsData::end_marker_with_reason(SplitReason::Discontinuity),
+            m4s_segment(true),
+        ]);
+        let types: Vec<_> = output.iter().map(|item| item.segment_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                SegmentType::M4sInit,
+                SegmentType::M4sMedia,
+                SegmentType::EndMarker,
+                SegmentType::M4sInit,
+                SegmentType::M4sMedia,
+            ]
+        );
+        assert_eq!(output[3].data(), init.data());
+
+        // Discontinuity only flagged on the segment
+        let output = run(vec![init.clone(), m4s_segment(false), m4s_segment(true)]);
+        let types: Vec<_> = output.iter().map(|item| item.segment_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                SegmentType::M4sInit,
+                SegmentType::M4sMedia,
+                SegmentType::EndMarker,
+                SegmentType::M4sInit,
+                SegmentType::M4sMedia,
+            ]
+        );
+
+        // A new init segment after the end marker replaces the old one
+        let new_init = HlsData::mp4_init(MediaSegment::empty(), Bytes::from_static(b"new init"));
+        let output = run(vec![
+            init,
+            m4s_segment(false),
+            HlsData::end_marker_with_reason(SplitReason::Discontinuity),
+            new_init.clone(),
+            m4s_segment(true),
+        ]);
+        assert_eq!(output.len(), 5);
+        assert_eq!(output[3].data(), new_init.data());
+
+        // A flagged init segment splits once for the whole period
+        let output = run(vec![
+            HlsData::mp4_init(MediaSegment::empty(), Bytes::from_static(b"init")),
+            m4s_segment(false),
+            HlsData::mp4_init(
+                MediaSegment {
+                    discontinuity: true,
+                    ..MediaSegment::empty()
+                },
+                Bytes::from_static(b"new init"),
+            ),
+            m4s_segment(true),
+        ]);
+        assert_eq!(output.iter().filter(|item| item.is_end_marker()).count(), 1);
+        assert_eq!(output.len(), 5);
+    }
 }
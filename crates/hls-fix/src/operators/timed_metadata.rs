@@ -0,0 +1,257 @@
+use std::fmt;
+use std::sync::Arc;
+
+use hls::HlsData;
+use pipeline_common::{PipelineError, Processor, StreamerContext};
+use tracing::warn;
+use ts::{TimedMetadata, TimedMetadataExtractor};
+
+/// ID3 timed metadata found in a TS segment
+#[derive(Debug, Clone)]
+pub struct TimedMetadataEvent {
+    pub metadata: TimedMetadata,
+    /// Time of the tag in milliseconds since the first PTS of the current output,
+    /// the same timeline FLV tag timestamps use
+    pub timestamp_ms: Option<u64>,
+    /// URI of the segment the tag was carried in
+    pub segment_uri: String,
+}
+
+/// Callback receiving timed metadata out of band
+#[derive(Clone)]
+pub struct OnTimedMetadata(Arc<dyn Fn(TimedMetadataEvent) + Send + Sync>);
+
+impl OnTimedMetadata {
+    pub fn new(callback: impl Fn(TimedMetadataEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub fn emit(&self, event: TimedMetadataEvent) {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for OnTimedMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnTimedMetadata")
+    }
+}
+
+/// HLS processor: Reports ID3 timed metadata (TS stream type 0x15) carried in
+/// TS segments. Segments pass through unchanged; a segment whose metadata
+/// cannot be parsed is logged and forwarded as is.
+pub struct TimedMetadataOperator {
+    extractor: TimedMetadataExtractor,
+    callback: OnTimedMetadata,
+}
+
+impl TimedMetadataOperator {
+    pub fn new(callback: OnTimedMetadata) -> Self {
+        Self {
+            extractor: TimedMetadataExtractor::new(),
+            callback,
+        }
+    }
+}
+
+impl Processor<HlsData> for TimedMetadataOperator {
+    fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: HlsData,
+        output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        match &input {
+            HlsData::TsData(ts_data) => match self.extractor.extract(ts_data.data.clone()) {
+                Ok(found) => {
+                    let base_pts = self.extractor.base_pts();
+                    for metadata in found {
+                        let timestamp_ms = base_pts.and_then(|base| metadata.relative_ms(base));
+                        self.callback.emit(TimedMetadataEvent {
+                            metadata,
+                            timestamp_ms,
+                            segment_uri: ts_data.segment.uri.clone(),
+                        });
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        segment_uri = %ts_data.segment.uri,
+                        error = %e,
+                        "Failed to scan TS segment for timed metadata"
+                    );
+                }
+            },
+            HlsData::EndMarker(_) => {
+                // A new output starts its timeline at zero
+                self.extractor.reset();
+            }
+            _ => {}
+        }
+
+        output(input)
+    }
+
+    fn finish(
+        &mut self,
+        _context: &Arc<StreamerContext>,
+        _output: &mut dyn FnMut(HlsData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "TimedMetadata"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use m3u8_rs::MediaSegment;
+    use std::sync::Mutex;
+    use tokio_util::sync::CancellationToken;
+    use ts::{Id3Frame, Pat, PatProgram, Pmt, PmtStream, StreamType, TsWriter};
+
+    const METADATA_PID: u16 = 0x102;
+
+    fn id3_tag(frame_id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut frame = frame_id.to_vec();
+        frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&[0, 0]);
+        frame.extend_from_slice(body);
+
+        // ID3v2.3, small enough that the synchsafe size is the plain length
+        let mut tag = b"ID3\x03\x00\x00\x00\x00\x00".to_vec();
+        tag.push(frame.len() as u8);
+        tag.extend_from_slice(&frame);
+        tag
+    }
+
+    fn pes(pts: u64, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x00, 0x00, 0x01, 0xBD];
+        out.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        out.extend_from_slice(&[0x84, 0x80, 0x05]);
+        out.push(0x21 | (((pts >> 30) & 0x07) as u8) << 1);
+        out.push((pts >> 22) as u8);
+        out.push(0x01 | (((pts >> 15) & 0x7F) as u8) << 1);
+        out.push((pts >> 7) as u8);
+        out.push(0x01 | ((pts & 0x7F) as u8) << 1);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn segment(uri: &str, tags: &[(u64, Vec<u8>)]) -> HlsData {
+        let mut writer = TsWriter::new();
+        let mut data = Vec::new();
+        let pat = Pat {
+            table_id: 0,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        };
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: METADATA_PID,
+            program_info: Vec::new(),
+            streams: vec![PmtStream {
+                stream_type: StreamType::MetadataPes,
+                elementary_pid: METADATA_PID,
+                es_info: Vec::new(),
+            }],
+        };
+        writer.write_pat(&pat, &mut data).unwrap();
+        writer.write_pmt(0x1000, &pmt, &mut data).unwrap();
+        for (pts, tag) in tags {
+            writer
+                .write_pes(METADATA_PID, &pes(*pts, tag), &mut data)
+                .unwrap();
+        }
+
+        HlsData::ts(
+            MediaSegment {
+                uri: uri.to_string(),
+                duration: 2.0,
+                ..MediaSegment::empty()
+            },
+            Bytes::from(data),
+        )
+    }
+
+    #[test]
+    fn reports_metadata_and_forwards_segments() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let mut operator = TimedMetadataOperator::new(OnTimedMetadata::new(move |event| {
+            sink.lock().unwrap().push(event)
+        }));
+
+        let mut out = Vec::new();
+        let mut output = |item: HlsData| -> Result<(), PipelineError> {
+            out.push(item);
+            Ok(())
+        };
+
+        let title = id3_tag(b"TIT2", b"\x00Intro");
+        let mut malformed = id3_tag(b"TIT2", b"\x00Broken");
+        malformed[17] = 0x7F;
+        let priv_frame = id3_tag(b"PRIV", b"com.example\0\x2A");
+
+        let inputs = [
+            segment("seg0.ts", &[(900_000, title), (1_080_000, malformed)]),
+            segment("seg1.ts", &[(1_170_000, priv_frame)]),
+            HlsData::end_marker(),
+            segment("seg2.ts", &[(2_700_000, id3_tag(b"TIT2", b"\x00Next"))]),
+        ];
+        for input in inputs {
+            operator.process(&context, input, &mut output).unwrap();
+        }
+
+        assert_eq!(out.len(), 4);
+        assert!(matches!(out[2], HlsData::EndMarker(_)));
+
+        let events = events.lock().unwrap();
+        let summary: Vec<_> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.segment_uri.as_str(),
+                    e.timestamp_ms,
+                    e.metadata.tag.frames[0].id().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("seg0.ts", Some(0), "TIT2".to_string()),
+                ("seg1.ts", Some(3_000), "PRIV".to_string()),
+                ("seg2.ts", Some(0), "TIT2".to_string()),
+            ]
+        );
+        assert_eq!(
+            events[1].metadata.tag.frames[0],
+            Id3Frame::Private {
+                owner: "com.example".into(),
+                data: Bytes::from_static(&[0x2A]),
+            }
+        );
+    }
+}
@@ -0,0 +1,696 @@
+//! # Transmux Operator
+//!
+//! Converts the TS segments of an HLS stream to FLV tags in-process, so a TS stream can
+//! be written by the FLV writers without remuxing it afterwards.
+//!
+//! ## How it Works
+//!
+//! 1. Takes the H.264 and AAC elementary streams of the first program from its PMT
+//! 2. Reassembles their PES packets; HLS segments start and end on PES boundaries, so
+//!    nothing is carried over from one segment to the next
+//! 3. Converts every H.264 access unit from Annex B to AVCC, preceded by an AVC sequence
+//!    header built from its SPS and PPS whenever they change
+//! 4. Strips the ADTS header of every AAC frame, preceded by an AAC sequence header with
+//!    the AudioSpecificConfig derived from the ADTS header whenever it changes
+//! 5. Times video tags by DTS with PTS − DTS as composition time, and audio tags by PTS,
+//!    relative to the first timestamp of the output
+//! 6. Starts a new output at playlist discontinuities and end markers: a split marker and
+//!    a new FLV header, which the FLV pipeline handles like a reconnect, and a timeline
+//!    starting at zero again
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use aac::{AdtsIterator, PartialAudioSpecificConfig};
+use bytes::{Bytes, BytesMut};
+use flv::data::FlvData;
+use flv::header::FlvHeader;
+use flv::tag::{FlvTag, FlvTagType};
+use h264::{AVCDecoderConfigurationRecord, NALUnitType, NalUnitIter, annex_b_to_avcc};
+use hls::{HlsData, TsSegmentData};
+use pipeline_common::{PipelineError, SplitReason, StreamerContext};
+use tracing::{debug, info, warn};
+use ts::{PesHeader, StreamType};
+
+/// PTS and DTS are 33-bit counters of a 90 kHz clock
+const TIMESTAMP_WRAP: u64 = 1 << 33;
+const TICKS_PER_MS: u64 = 90;
+
+/// Samples per channel of an AAC frame
+const AAC_FRAME_SAMPLES: u64 = 1024;
+
+/// AAC, 44 kHz, 16-bit, stereo: the only audio tag header FLV allows for AAC
+const AAC_TAG_HEADER: u8 = 0xAF;
+
+/// A frame of an elementary stream, timed on the unwrapped 90 kHz clock
+enum Frame {
+    Video {
+        dts: u64,
+        pts: u64,
+        keyframe: bool,
+        /// AVC sequence header of the SPS and PPS carried by the access unit
+        config: Option<Bytes>,
+        /// AVCC NAL units
+        data: Bytes,
+    },
+    Audio {
+        pts: u64,
+        config: PartialAudioSpecificConfig,
+        /// Raw AAC frame
+        data: Bytes,
+    },
+}
+
+impl Frame {
+    fn decode_time(&self) -> u64 {
+        match self {
+            Frame::Video { dts, .. } => *dts,
+            Frame::Audio { pts, .. } => *pts,
+        }
+    }
+}
+
+/// Operator converting the TS segments of an HLS stream to [`FlvData`]
+///
+/// Unlike the other operators it changes the type of the items, so it is driven with
+/// [`process`](Self::process) between an HLS pipeline and an FLV one rather than
+/// chained as a [`Processor`](pipeline_common::Processor).
+pub struct TransmuxOperator {
+    context: Arc<StreamerContext>,
+    video_pid: Option<u16>,
+    audio_pid: Option<u16>,
+    /// Whether the FLV header of the current output was emitted
+    header_sent: bool,
+    /// Reason of the split to announce before the next FLV header
+    split_reason: Option<SplitReason>,
+    /// Timestamp of the current output that FLV time zero maps to
+    base_timestamp: Option<u64>,
+    /// Latest unwrapped timestamp, the reference for unwrapping the next one
+    last_timestamp: Option<u64>,
+    /// Last emitted AVC sequence header
+    video_config: Option<Bytes>,
+    /// Last emitted AudioSpecificConfig
+    audio_config: Option<PartialAudioSpecificConfig>,
+    /// Whether fMP4 segments were already reported as skipped
+    mp4_warned: bool,
+}
+
+impl TransmuxOperator {
+    pub fn new(context: Arc<StreamerContext>) -> Self {
+        Self {
+            context,
+            video_pid: None,
+            audio_pid: None,
+            header_sent: false,
+            split_reason: None,
+            base_timestamp: None,
+            last_timestamp: None,
+            video_config: None,
+            audio_config: None,
+            mp4_warned: false,
+        }
+    }
+
+    /// Convert `input` to FLV and pass the resulting items to `output`.
+    ///
+    /// A segment whose packets cannot be parsed fails with [`PipelineError::InvalidData`];
+    /// malformed PES packets within a segment are logged and skipped.
+    pub fn process(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        input: HlsData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if context.token.is_cancelled() {
+            return Err(PipelineError::Cancelled);
+        }
+
+        match input {
+            HlsData::TsData(ts_data) => {
+                if ts_data.segment.discontinuity && self.header_sent {
+                    info!(
+                        "{} Playlist discontinuity, starting a new FLV output",
+                        self.context.name
+                    );
+                    self.start_new_output(Some(SplitReason::Discontinuity));
+                }
+                self.transmux_segment(&ts_data, output)
+            }
+            HlsData::EndMarker(reason) => {
+                if self.header_sent {
+                    self.start_new_output(reason);
+                }
+                Ok(())
+            }
+            HlsData::M4sData(_) => {
+                if !self.mp4_warned {
+                    self.mp4_warned = true;
+                    warn!(
+                        "{} fMP4 segments cannot be transmuxed to FLV, skipping them",
+                        self.context.name
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        "TransmuxOperator"
+    }
+
+    /// Make the next tag start a new output with its own header and timeline
+    fn start_new_output(&mut self, reason: Option<SplitReason>) {
+        self.header_sent = false;
+        self.split_reason = reason;
+        self.base_timestamp = None;
+        self.last_timestamp = None;
+        self.video_config = None;
+        self.audio_config = None;
+    }
+
+    fn transmux_segment(
+        &mut self,
+        ts_data: &TsSegmentData,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        let (stream_info, packets) = ts_data.parse_stream_and_packets().map_err(|e| {
+            PipelineError::InvalidData(format!(
+                "failed to parse TS segment {}: {e}",
+                ts_data.segment.uri
+            ))
+        })?;
+        // Segments without PSI tables keep the streams of the last PMT
+        if let Some(program) = stream_info.programs.first() {
+            self.video_pid = program
+                .video_streams
+                .iter()
+                .find(|stream| stream.stream_type == StreamType::H264)
+                .map(|stream| stream.pid);
+            self.audio_pid = program
+                .audio_streams
+                .iter()
+                .find(|stream| stream.stream_type == StreamType::AdtsAac)
+                .map(|stream| stream.pid);
+        }
+
+        let mut pending: HashMap<u16, BytesMut> = HashMap::new();
+        let mut completed = Vec::new();
+        for packet in &packets {
+            if Some(packet.pid) != self.video_pid && Some(packet.pid) != self.audio_pid {
+                continue;
+            }
+            let Some(payload) = packet.payload() else {
+                continue;
+            };
+            let buffer = pending.entry(packet.pid).or_default();
+            if packet.payload_unit_start_indicator {
+                if !buffer.is_empty() {
+                    completed.push((packet.pid, buffer.split().freeze()));
+                }
+                buffer.extend_from_slice(&payload);
+            } else if !buffer.is_empty() {
+                buffer.extend_from_slice(&payload);
+            }
+        }
+        completed.extend(
+            pending
+                .into_iter()
+                .filter(|(_, buffer)| !buffer.is_empty())
+                .map(|(pid, buffer)| (pid, buffer.freeze())),
+        );
+
+        let mut frames = Vec::new();
+        for (pid, pes) in completed {
+            let result = if Some(pid) == self.video_pid {
+                self.video_frame(&pes).map(|frame| frames.push(frame))
+            } else {
+                self.audio_frames(&pes).map(|audio| frames.extend(audio))
+            };
+            if let Err(e) = result {
+                warn!(
+                    "{} Skipping malformed PES packet on PID 0x{pid:04X} in {}: {e}",
+                    self.context.name, ts_data.segment.uri
+                );
+            }
+        }
+        if frames.is_empty() {
+            return Ok(());
+        }
+
+        // Interleave the tracks, the sort is stable so frames of a track keep their order
+        frames.sort_by_key(Frame::decode_time);
+        let base = *self
+            .base_timestamp
+            .get_or_insert_with(|| frames[0].decode_time());
+        for frame in frames {
+            self.emit_frame(frame, base, output)?;
+        }
+        Ok(())
+    }
+
+    /// The payload of `pes` and its timestamps as `(pts, dts)`, DTS defaulting to PTS
+    fn pes_payload(&mut self, pes: &Bytes) -> Result<(Bytes, u64, u64), String> {
+        let header = PesHeader::parse(pes).map_err(|e| e.to_string())?;
+        let mut end = pes.len();
+        if header.pes_packet_length > 0 {
+            end = end.min(header.pes_packet_length as usize + 6);
+        }
+        if header.payload_offset > end {
+            return Err("PES header runs past the end of the packet".to_string());
+        }
+        let pts = header.pts.ok_or("PES packet has no PTS")?;
+        let pts = self.unwrap_timestamp(pts);
+        let dts = header.dts.map_or(pts, |dts| self.unwrap_timestamp(dts));
+        Ok((pes.slice(header.payload_offset..end), pts, dts))
+    }
+
+    fn video_frame(&mut self, pes: &Bytes) -> Result<Frame, String> {
+        let (payload, pts, dts) = self.pes_payload(pes)?;
+
+        let mut keyframe = false;
+        let mut has_sps = false;
+        for nal in NalUnitIter::annex_b(&payload) {
+            match nal.map_err(|e| e.to_string())?.nal_unit_type {
+                NALUnitType::IDRSliceLayerWithoutPartitioning => keyframe = true,
+                NALUnitType::SPS => has_sps = true,
+                _ => {}
+            }
+        }
+
+        let config = if has_sps {
+            let record =
+                AVCDecoderConfigurationRecord::from_annexb(&payload).map_err(|e| e.to_string())?;
+            let mut config = Vec::with_capacity(record.encoded_size());
+            record.build(&mut config).map_err(|e| e.to_string())?;
+            Some(Bytes::from(config))
+        } else {
+            None
+        };
+        // The sequence header uses 4 byte NAL unit lengths
+        let mut data = Vec::with_capacity(payload.len());
+        annex_b_to_avcc(&payload, 3, &mut data).map_err(|e| e.to_string())?;
+        Ok(Frame::Video {
+            dts,
+            pts,
+            keyframe,
+            config,
+            data: Bytes::from(data),
+        })
+    }
+
+    fn audio_frames(&mut self, pes: &Bytes) -> Result<Vec<Frame>, String> {
+        let (payload, pts, _) = self.pes_payload(pes)?;
+
+        let mut frames = Vec::new();
+        let mut samples = 0;
+        for frame in AdtsIterator::new(&payload) {
+            let (header, raw) = frame.map_err(|e| e.to_string())?;
+            let config = header
+                .to_audio_specific_config()
+                .map_err(|e| e.to_string())?;
+            let sample_rate = u64::from(config.sampling_frequency.max(1));
+            frames.push(Frame::Audio {
+                pts: pts + samples * 90_000 / sample_rate,
+                config,
+                data: payload.slice_ref(raw),
+            });
+            samples += AAC_FRAME_SAMPLES * u64::from(header.number_of_raw_data_blocks.max(1));
+        }
+        Ok(frames)
+    }
+
+    /// Extend a 33-bit timestamp to the value closest to the previous one
+    fn unwrap_timestamp(&mut self, timestamp: u64) -> u64 {
+        let timestamp = timestamp % TIMESTAMP_WRAP;
+        let unwrapped = match self.last_timestamp {
+            None => timestamp,
+            Some(last) => {
+                let candidate = (last - last % TIMESTAMP_WRAP) + timestamp;
+                if candidate + TIMESTAMP_WRAP / 2 < last {
+                    candidate + TIMESTAMP_WRAP
+                } else if candidate > last + TIMESTAMP_WRAP / 2 && candidate >= TIMESTAMP_WRAP {
+                    candidate - TIMESTAMP_WRAP
+                } else {
+                    candidate
+                }
+            }
+        };
+        self.last_timestamp = Some(unwrapped);
+        unwrapped
+    }
+
+    fn emit_frame(
+        &mut self,
+        frame: Frame,
+        base: u64,
+        output: &mut dyn FnMut(FlvData) -> Result<(), PipelineError>,
+    ) -> Result<(), PipelineError> {
+        if matches!(frame, Frame::Video { config: None, .. }) && self.video_config.is_none() {
+            // Undecodable until the first SPS and PPS arrive
+            debug!(
+                "{} Dropping video frame before the first SPS",
+                self.context.name
+            );
+            return Ok(());
+        }
+        if !self.header_sent {
+            if let Some(reason) = self.split_reason.take() {
+                output(FlvData::Split(reason))?;
+            }
+            output(FlvData::Header(FlvHeader::new(
+                self.audio_pid.is_some(),
+                self.video_pid.is_some(),
+            )))?;
+            self.header_sent = true;
+        }
+
+        let to_ms = |timestamp: u64| (timestamp.saturating_sub(base) / TICKS_PER_MS) as u32;
+        match frame {
+            Frame::Video {
+                dts,
+                pts,
+                keyframe,
+                config,
+                data,
+            } => {
+                let timestamp_ms = to_ms(dts);
+                if let Some(config) = config
+                    && self.video_config.as_ref() != Some(&config)
+                {
+                    // Keyframe (1) + AVC (7), AVC sequence header, composition time 0
+                    let mut body = vec![0x17, 0x00, 0x00, 0x00, 0x00];
+                    body.extend_from_slice(&config);
+                    output(media_tag(FlvTagType::Video, timestamp_ms, body))?;
+                    self.video_config = Some(config);
+                }
+
+                let composition_time = to_ms(pts) as i32 - timestamp_ms as i32;
+                let frame_type = if keyframe { 0x17 } else { 0x27 };
+                let mut body = vec![frame_type, 0x01];
+                body.extend_from_slice(&composition_time.to_be_bytes()[1..]);
+                body.extend_from_slice(&data);
+                output(media_tag(FlvTagType::Video, timestamp_ms, body))
+            }
+            Frame::Audio { pts, config, data } => {
+                let timestamp_ms = to_ms(pts);
+                if self.audio_config != Some(config) {
+                    let mut body = vec![AAC_TAG_HEADER, 0x00];
+                    config.mux(&mut body)?;
+                    output(media_tag(FlvTagType::Audio, timestamp_ms, body))?;
+                    self.audio_config = Some(config);
+                }
+
+                let mut body = vec![AAC_TAG_HEADER, 0x01];
+                body.extend_from_slice(&data);
+                output(media_tag(FlvTagType::Audio, timestamp_ms, body))
+            }
+        }
+    }
+}
+
+fn media_tag(tag_type: FlvTagType, timestamp_ms: u32, body: Vec<u8>) -> FlvData {
+    FlvData::Tag(FlvTag {
+        timestamp_ms,
+        stream_id: 0,
+        tag_type,
+        is_filtered: false,
+        data: Bytes::from(body),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aac::AudioObjectType;
+    use flv_fix::FlvAnalyzer;
+    use m3u8_rs::MediaSegment;
+    use pipeline_common::init_test_tracing;
+    use tokio_util::sync::CancellationToken;
+    use ts::{Pat, PatProgram, Pmt, PmtStream, TsWriter};
+
+    const VIDEO_PID: u16 = 0x100;
+    const AUDIO_PID: u16 = 0x101;
+    /// Baseline-profile SPS for 1920x1080 and a matching PPS
+    const SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda, 0x01, 0xe0, 0x08, 0x9f, 0x95];
+    const PPS: &[u8] = &[0x68, 0xce, 0x38, 0x80];
+
+    /// 25 fps
+    const FRAME_TICKS: u64 = 3_600;
+    /// 1024 samples at 48 kHz
+    const AAC_FRAME_TICKS: u64 = 1_920;
+    /// Composition time of every video frame
+    const CTS_TICKS: u64 = 2 * FRAME_TICKS;
+
+    fn timestamp(prefix: u8, ts: u64) -> [u8; 5] {
+        [
+            (prefix << 4) | (((ts >> 30) & 0x07) as u8) << 1 | 0x01,
+            (ts >> 22) as u8,
+            (((ts >> 15) & 0x7F) as u8) << 1 | 0x01,
+            (ts >> 7) as u8,
+            ((ts & 0x7F) as u8) << 1 | 0x01,
+        ]
+    }
+
+    /// An H.264 access unit in a PES packet of unbounded length
+    fn video_pes(dts: u64, index: u8, keyframe: bool) -> Vec<u8> {
+        let mut out = vec![0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, 0x80, 0xC0, 0x0A];
+        out.extend_from_slice(&timestamp(0x3, dts + CTS_TICKS));
+        out.extend_from_slice(&timestamp(0x1, dts));
+        if keyframe {
+            let idr = [0x65, 0x88, 0x84, index + 1];
+            for nal in [SPS, PPS, &idr[..]] {
+                out.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+                out.extend_from_slice(nal);
+            }
+        } else {
+            out.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x41, 0x9a, index + 1]);
+        }
+        out
+    }
+
+    /// `count` ADTS frames of AAC LC, 48 kHz stereo, in one PES packet
+    fn audio_pes(pts: u64, first: u64, count: u64) -> Vec<u8> {
+        let config = PartialAudioSpecificConfig {
+            audio_object_type: AudioObjectType::AacLowComplexity,
+            sampling_frequency: 48_000,
+            channel_configuration: 2,
+        };
+        let mut frames = Vec::new();
+        for frame in first..first + count {
+            aac::write_adts_frame(&mut frames, &config, &[0x21, 0x10, frame as u8, 0x60]).unwrap();
+        }
+        let mut out = vec![0x00, 0x00, 0x01, 0xC0];
+        out.extend_from_slice(&((3 + 5 + frames.len()) as u16).to_be_bytes());
+        out.extend_from_slice(&[0x80, 0x80, 0x05]);
+        out.extend_from_slice(&timestamp(0x2, pts));
+        out.extend_from_slice(&frames);
+        out
+    }
+
+    /// Segment `index` of a stream whose first DTS is `origin`: a one second GOP of 25
+    /// frames and the AAC frames presented within the same second, four to a PES packet.
+    /// Audio starts with the presentation of the first video frame.
+    fn segment(index: u64, origin: u64, discontinuity: bool) -> HlsData {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        let pat = Pat {
+            table_id: 0,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        };
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: VIDEO_PID,
+            program_info: Vec::new(),
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: VIDEO_PID,
+                    es_info: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: AUDIO_PID,
+                    es_info: Vec::new(),
+                },
+            ],
+        };
+        writer.write_pat(&pat, &mut out).unwrap();
+        writer.write_pmt(0x1000, &pmt, &mut out).unwrap();
+
+        // Timestamps relative to `origin`, truncated to 33 bits when encoded
+        let start = index * 25 * FRAME_TICKS;
+        let end = start + 25 * FRAME_TICKS;
+        let mut audio_frame = start.div_ceil(AAC_FRAME_TICKS);
+        for frame in 0..25u8 {
+            let dts = start + frame as u64 * FRAME_TICKS;
+            let pes = video_pes(origin + dts, frame, frame == 0);
+            writer.write_pes(VIDEO_PID, &pes, &mut out).unwrap();
+            // Audio presented up to the next video frame
+            let until = (dts + FRAME_TICKS).min(end);
+            while audio_frame * AAC_FRAME_TICKS < until {
+                let count = (until.div_ceil(AAC_FRAME_TICKS) - audio_frame).min(4);
+                let pts = origin + CTS_TICKS + audio_frame * AAC_FRAME_TICKS;
+                let pes = audio_pes(pts, audio_frame, count);
+                writer.write_pes(AUDIO_PID, &pes, &mut out).unwrap();
+                audio_frame += count;
+            }
+        }
+
+        HlsData::TsData(TsSegmentData {
+            segment: MediaSegment {
+                uri: format!("{index}.ts"),
+                discontinuity,
+                ..MediaSegment::empty()
+            },
+            data: Bytes::from(out),
+            validate_crc: true,
+            continuity_mode: ts::ContinuityMode::Warn,
+        })
+    }
+
+    fn transmux(segments: Vec<HlsData>) -> Vec<FlvData> {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let mut operator = TransmuxOperator::new(context.clone());
+        let mut items = Vec::new();
+        for segment in segments {
+            operator
+                .process(&context, segment, &mut |item| {
+                    items.push(item);
+                    Ok(())
+                })
+                .unwrap();
+        }
+        items
+    }
+
+    fn tags(items: &[FlvData]) -> impl Iterator<Item = &FlvTag> {
+        items.iter().filter_map(|item| match item {
+            FlvData::Tag(tag) => Some(tag),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_transmuxed_segments_pass_analysis() {
+        init_test_tracing!();
+        // Starts close to the 33-bit wrap, which happens during the second segment
+        let origin = TIMESTAMP_WRAP - 135_000;
+        let items = transmux((0..3).map(|i| segment(i, origin, false)).collect());
+
+        let mut analyzer = FlvAnalyzer::default();
+        let FlvData::Header(header) = &items[0] else {
+            panic!("expected a header first, got {:?}", items[0]);
+        };
+        analyzer.analyze_header(header).unwrap();
+        for tag in tags(&items[1..]) {
+            analyzer.analyze_tag(tag).unwrap();
+        }
+        let report = analyzer.finalize_report().unwrap();
+        assert!(!report.has_issues(), "{:?}", report.issues);
+        assert_eq!(report.video_params.unwrap().width, Some(1920));
+        assert_eq!(report.audio_params.unwrap().sample_rate, Some(48_000));
+
+        let video: Vec<_> = tags(&items)
+            .filter(|tag| tag.is_video_tag() && !tag.is_video_sequence_header())
+            .collect();
+        let audio: Vec<_> = tags(&items)
+            .filter(|tag| tag.is_audio_tag() && !tag.is_audio_sequence_header())
+            .collect();
+        assert_eq!(video.len(), 75);
+        assert_eq!(video.iter().filter(|tag| tag.is_key_frame()).count(), 3);
+        assert_eq!(
+            tags(&items)
+                .filter(|t| t.is_video_sequence_header())
+                .count(),
+            1
+        );
+        assert_eq!(
+            tags(&items)
+                .filter(|t| t.is_audio_sequence_header())
+                .count(),
+            1
+        );
+
+        // Composition time, then the access unit as AVCC
+        let first = &video[0].data;
+        assert_eq!(&first[2..5], &[0x00, 0x00, 0x50]);
+        assert_eq!(&first[5..9], &(SPS.len() as u32).to_be_bytes());
+        assert_eq!(&first[9..9 + SPS.len()], SPS);
+        assert_eq!(video[1].timestamp_ms, 40);
+        // ADTS headers are stripped
+        assert_eq!(&audio[0].data[2..], &[0x21, 0x10, 0x00, 0x60]);
+
+        // Presented together at the start, and within a video frame of each other at the end
+        let video_start = video[0].timestamp_ms + 80;
+        assert_eq!(video_start, audio[0].timestamp_ms);
+        let video_end = video.last().unwrap().timestamp_ms + 80;
+        let audio_end = audio.last().unwrap().timestamp_ms;
+        assert!(
+            video_end.abs_diff(audio_end) <= 40,
+            "{video_end} {audio_end}"
+        );
+        assert_eq!(video.last().unwrap().timestamp_ms, 74 * 40);
+    }
+
+    #[test]
+    fn test_discontinuity_starts_new_output() {
+        init_test_tracing!();
+        let items = transmux(vec![
+            segment(0, 900_000, false),
+            segment(1, 900_000, false),
+            segment(0, 45_000_000, true),
+        ]);
+
+        let boundary = items
+            .iter()
+            .position(|item| matches!(item, FlvData::Split(_)))
+            .expect("split marker");
+        assert!(matches!(
+            items[boundary],
+            FlvData::Split(SplitReason::Discontinuity)
+        ));
+        assert!(matches!(items[boundary + 1], FlvData::Header(_)));
+        assert_eq!(
+            items
+                .iter()
+                .filter(|item| matches!(item, FlvData::Header(_)))
+                .count(),
+            2
+        );
+
+        // The new output has its own sequence headers and a timeline starting at zero
+        let after: Vec<_> = tags(&items[boundary..]).collect();
+        assert!(after[0].is_video_sequence_header() || after[0].is_audio_sequence_header());
+        assert_eq!(after[0].timestamp_ms, 0);
+        assert_eq!(
+            after
+                .iter()
+                .filter(|t| t.is_video_sequence_header())
+                .count(),
+            1
+        );
+        assert_eq!(
+            after
+                .iter()
+                .filter(|t| t.is_audio_sequence_header())
+                .count(),
+            1
+        );
+        let before = tags(&items[..boundary]).last().unwrap();
+        assert!(before.timestamp_ms >= 1_960, "{}", before.timestamp_ms);
+    }
+}
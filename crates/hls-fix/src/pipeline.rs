@@ -1,15 +1,24 @@
 use std::sync::Arc;
 
 use hls::HlsData;
-use pipeline_common::{ChannelPipeline, PipelineProvider, StreamerContext, config::PipelineConfig};
+use pipeline_common::{
+    AnomalyConfig, ChannelPipeline, PipelineProvider, StreamerContext, config::PipelineConfig,
+};
 
-use crate::operators::{DefragmentOperator, SegmentLimiterOperator, SegmentSplitOperator};
+use crate::operators::{
+    AnomalyDetectorOperator, DefragmentOperator, MediaInfoOperator, OnTimedMetadata,
+    SegmentLimiterOperator, SegmentSplitOperator, TimedMetadataOperator,
+};
 
 #[derive(Debug, Clone)]
 pub struct HlsPipelineConfig {
     pub defragment: bool,
     pub split_segments: bool,
     pub segment_limiter: bool,
+    /// Receives ID3 timed metadata found in TS segments
+    pub on_timed_metadata: Option<OnTimedMetadata>,
+    /// Thresholds of the anomalies reported as events (None = disabled)
+    pub anomaly_detector: Option<AnomalyConfig>,
 }
 
 impl Default for HlsPipelineConfig {
@@ -18,6 +27,8 @@ impl Default for HlsPipelineConfig {
             defragment: true,
             split_segments: true,
             segment_limiter: true,
+            on_timed_metadata: None,
+            anomaly_detector: Some(AnomalyConfig::default()),
         }
     }
 }
@@ -40,6 +51,18 @@ impl HlsPipelineConfigBuilder {
         }
     }
 
+    /// Report ID3 timed metadata found in TS segments to `callback`
+    pub fn on_timed_metadata(mut self, callback: OnTimedMetadata) -> Self {
+        self.config.on_timed_metadata = Some(callback);
+        self
+    }
+
+    /// Report encoder anomalies with the thresholds of `config`, or not at all with `None`
+    pub fn anomaly_detector(mut self, config: Option<AnomalyConfig>) -> Self {
+        self.config.anomaly_detector = config;
+        self
+    }
+
     pub fn build(self) -> HlsPipelineConfig {
         self.config
     }
@@ -66,6 +89,7 @@ impl PipelineProvider for HlsPipeline {
         common_config: &PipelineConfig,
         config: Self::Config,
     ) -> Self {
+        context.events.add_handlers(&common_config.event_handlers);
         Self {
             context,
             config,
@@ -74,13 +98,28 @@ impl PipelineProvider for HlsPipeline {
     }
 
     fn build_pipeline(&self) -> ChannelPipeline<Self::Item> {
-        let mut sync_pipeline = pipeline_common::Pipeline::new(self.context.clone());
+        let mut sync_pipeline = pipeline_common::Pipeline::new(self.context.clone())
+            .with_error_policy(self.common_config.error_policy);
+
+        if let Some(callback) = &self.config.on_timed_metadata {
+            sync_pipeline =
+                sync_pipeline.add_processor(TimedMetadataOperator::new(callback.clone()));
+        }
 
         if self.config.defragment {
             sync_pipeline =
                 sync_pipeline.add_processor(DefragmentOperator::new(self.context.clone()));
         }
 
+        sync_pipeline = sync_pipeline.add_processor(MediaInfoOperator::new(self.context.clone()));
+
+        if let Some(config) = &self.config.anomaly_detector {
+            sync_pipeline = sync_pipeline.add_processor(AnomalyDetectorOperator::new(
+                self.context.clone(),
+                config.clone(),
+            ));
+        }
+
         if self.config.split_segments {
             sync_pipeline =
                 sync_pipeline.add_processor(SegmentSplitOperator::new(self.context.clone()));
@@ -93,6 +132,8 @@ impl PipelineProvider for HlsPipeline {
             ));
         }
 
-        ChannelPipeline::new(self.context.clone()).add_processor(sync_pipeline)
+        ChannelPipeline::new(self.context.clone())
+            .with_max_in_flight_bytes(self.common_config.max_in_flight_bytes)
+            .add_processor(sync_pipeline)
     }
 }
@@ -0,0 +1,597 @@
+use std::{collections::VecDeque, fs, path::PathBuf};
+
+use bytes::Bytes;
+use hls::{HlsData, M4sData, TsSegmentData};
+use m3u8_rs::{Map, MediaPlaylist, MediaPlaylistType, MediaSegment};
+use pipeline_common::{
+    PipelineError, ProtocolWriter, WriterError, WriterState, WriterStats, expand_filename_template,
+};
+use tracing::{debug, info};
+
+use crate::analyzer::HlsAnalyzer;
+
+/// PTS values wrap around at 33 bits.
+const PTS_MODULO: u64 = 1 << 33;
+
+/// Clock of PES timestamps.
+const PTS_TIMESCALE: f64 = 90_000.0;
+
+/// Typed configuration for the HLS playlist writer.
+#[derive(Debug, Clone)]
+pub struct HlsPlaylistWriterConfig {
+    pub output_dir: PathBuf,
+    /// File name of the playlist, without the `.m3u8` extension.
+    pub playlist_name: String,
+    /// File name template of the media segments, without extension. `%i` is the number of
+    /// the segment.
+    pub segment_template: String,
+    /// Number of segments listed in live mode, where the playlist is rewritten whenever a
+    /// segment is added. `None` writes a VOD playlist listing every segment.
+    pub live_window: Option<usize>,
+}
+
+/// A media segment listed in the playlist.
+#[derive(Debug, Clone)]
+struct PlaylistEntry {
+    uri: String,
+    duration: f32,
+    discontinuity: bool,
+    /// URI of the init segment of fMP4 segments.
+    map: Option<String>,
+}
+
+/// A TS segment waiting for the next one, whose first PTS ends it.
+struct PendingTsEntry {
+    entry: PlaylistEntry,
+    first_pts: Option<u64>,
+}
+
+/// Writes HLS segments to disk as they are and lists them in a local media playlist.
+///
+/// Segment durations are measured rather than taken from the source playlist: fMP4 segments
+/// are timed by their `tfdt`/`trun` boxes, TS segments by the PTS distance to the following
+/// segment or else by their PCRs. Only when neither is available is the upstream EXTINF used.
+pub struct HlsPlaylistWriter {
+    config: HlsPlaylistWriterConfig,
+    state: WriterState,
+    analyzer: HlsAnalyzer,
+    /// Listed segments, only the last `live_window` ones in live mode.
+    entries: VecDeque<PlaylistEntry>,
+    pending_ts: Option<PendingTsEntry>,
+    /// Data and URI of the init segment of the fMP4 segments that follow.
+    current_init: Option<(Bytes, String)>,
+    init_count: u32,
+    segment_count: u32,
+    files_written: u32,
+    /// Whether the next segment starts after a discontinuity.
+    next_discontinuity: bool,
+    /// Segments dropped from the start of a live playlist.
+    media_sequence: u64,
+    /// Discontinuities dropped from the start of a live playlist.
+    discontinuity_sequence: u64,
+    /// Longest segment so far, which bounds the target duration.
+    max_duration: f32,
+}
+
+impl HlsPlaylistWriter {
+    pub fn new(config: HlsPlaylistWriterConfig) -> Self {
+        Self {
+            config,
+            state: WriterState::default(),
+            analyzer: HlsAnalyzer::new(),
+            entries: VecDeque::new(),
+            pending_ts: None,
+            current_init: None,
+            init_count: 0,
+            segment_count: 0,
+            files_written: 0,
+            next_discontinuity: false,
+            media_sequence: 0,
+            discontinuity_sequence: 0,
+            max_duration: 0.0,
+        }
+    }
+
+    /// Path of the playlist file.
+    pub fn playlist_path(&self) -> PathBuf {
+        self.config
+            .output_dir
+            .join(format!("{}.m3u8", self.config.playlist_name))
+    }
+
+    fn write_item(&mut self, item: &HlsData) -> Result<(), WriterError> {
+        match item {
+            HlsData::TsData(ts) => {
+                let discontinuity = ts.segment.discontinuity || self.take_discontinuity();
+                let first_pts = ts_first_pts(ts);
+                self.finish_pending_ts(first_pts.filter(|_| !discontinuity))?;
+
+                let uri = self.write_segment(&ts.data, "ts")?;
+                self.pending_ts = Some(PendingTsEntry {
+                    entry: PlaylistEntry {
+                        uri,
+                        duration: ts_pcr_duration(ts).unwrap_or(ts.segment.duration),
+                        discontinuity,
+                        map: None,
+                    },
+                    first_pts,
+                });
+            }
+            HlsData::M4sData(M4sData::InitSegment(init)) => {
+                self.analyze(item)?;
+                if self
+                    .current_init
+                    .as_ref()
+                    .is_some_and(|(data, _)| *data == init.data)
+                {
+                    debug!(uri = %init.segment.uri, "Skipping repeated init segment");
+                    return Ok(());
+                }
+
+                let name = format!("{}_init_{}.mp4", self.config.playlist_name, self.init_count);
+                self.init_count += 1;
+                self.write_file(&name, &init.data)?;
+                // Segments of different init segments cannot be decoded as one stream
+                if self.current_init.is_some() {
+                    self.next_discontinuity = true;
+                }
+                self.current_init = Some((init.data.clone(), name));
+            }
+            HlsData::M4sData(M4sData::Segment(segment)) => {
+                self.finish_pending_ts(None)?;
+                self.analyze(item)?;
+                // Sample-accurate when the fragment timing could be read
+                let duration = self.analyzer.stats.last_segment_duration;
+                let discontinuity = segment.segment.discontinuity || self.take_discontinuity();
+
+                let uri = self.write_segment(&segment.data, "m4s")?;
+                let map = self.current_init.as_ref().map(|(_, uri)| uri.clone());
+                self.push_entry(PlaylistEntry {
+                    uri,
+                    duration,
+                    discontinuity,
+                    map,
+                })?;
+            }
+            HlsData::EndMarker(reason) => {
+                debug!(?reason, "End marker received, keeping the playlist going");
+            }
+        }
+        Ok(())
+    }
+
+    fn analyze(&mut self, item: &HlsData) -> Result<(), WriterError> {
+        self.analyzer
+            .analyze_segment(item)
+            .map_err(|e| WriterError::Strategy(e.into()))
+    }
+
+    fn take_discontinuity(&mut self) -> bool {
+        std::mem::take(&mut self.next_discontinuity)
+    }
+
+    /// List the pending TS segment, timed up to `next_pts` when the next segment has one.
+    fn finish_pending_ts(&mut self, next_pts: Option<u64>) -> Result<(), WriterError> {
+        let Some(PendingTsEntry {
+            mut entry,
+            first_pts,
+        }) = self.pending_ts.take()
+        else {
+            return Ok(());
+        };
+
+        if let (Some(first), Some(next)) = (first_pts, next_pts) {
+            let ticks = (next + PTS_MODULO - first) % PTS_MODULO;
+            // A backwards jump is a timestamp reset rather than a long segment
+            if ticks > 0 && ticks < PTS_MODULO / 2 {
+                entry.duration = (ticks as f64 / PTS_TIMESCALE) as f32;
+            }
+        }
+        self.push_entry(entry)
+    }
+
+    fn push_entry(&mut self, entry: PlaylistEntry) -> Result<(), WriterError> {
+        self.max_duration = self.max_duration.max(entry.duration);
+        self.state.add_media_duration(entry.duration as f64);
+        self.entries.push_back(entry);
+
+        if let Some(window) = self.config.live_window {
+            while self.entries.len() > window.max(1) {
+                if let Some(removed) = self.entries.pop_front() {
+                    self.media_sequence += 1;
+                    if removed.discontinuity {
+                        self.discontinuity_sequence += 1;
+                    }
+                }
+            }
+            self.write_playlist(false)?;
+        }
+        Ok(())
+    }
+
+    fn write_segment(&mut self, data: &Bytes, extension: &str) -> Result<String, WriterError> {
+        let name = format!(
+            "{}.{extension}",
+            expand_filename_template(&self.config.segment_template, Some(self.segment_count))
+        );
+        self.segment_count += 1;
+        self.write_file(&name, data)?;
+        Ok(name)
+    }
+
+    fn write_file(&mut self, name: &str, data: &Bytes) -> Result<(), WriterError> {
+        let path = self.config.output_dir.join(name);
+        fs::write(&path, data)?;
+
+        if self.files_written > 0 {
+            self.state.file_sequence_number += 1;
+        }
+        self.files_written += 1;
+        self.state.reset_for_new_file(path);
+        self.state.items_written_current_file = 1;
+        self.state.items_written_total += 1;
+        self.state.bytes_written_current_file = data.len() as u64;
+        self.state.bytes_written_total += data.len() as u64;
+        Ok(())
+    }
+
+    fn build_playlist(&self, end_list: bool) -> MediaPlaylist {
+        let mut previous_map = None;
+        let segments = self
+            .entries
+            .iter()
+            .map(|entry| {
+                // The map applies until the next one, repeat it only when it changes
+                let map = (entry.map != previous_map)
+                    .then(|| entry.map.clone())
+                    .flatten()
+                    .map(|uri| Map {
+                        uri,
+                        byte_range: None,
+                        other_attributes: Default::default(),
+                    });
+                previous_map = entry.map.clone();
+                MediaSegment {
+                    uri: entry.uri.clone(),
+                    duration: entry.duration,
+                    discontinuity: entry.discontinuity,
+                    map,
+                    ..MediaSegment::empty()
+                }
+            })
+            .collect();
+
+        let has_map = self.entries.iter().any(|entry| entry.map.is_some());
+        MediaPlaylist {
+            version: Some(if has_map { 7 } else { 3 }),
+            target_duration: (self.max_duration.ceil() as u64).max(1),
+            media_sequence: self.media_sequence,
+            discontinuity_sequence: self.discontinuity_sequence,
+            segments,
+            end_list,
+            playlist_type: self
+                .config
+                .live_window
+                .is_none()
+                .then_some(MediaPlaylistType::Vod),
+            ..MediaPlaylist::default()
+        }
+    }
+
+    /// Write the playlist through a temporary file, so readers never see a partial one.
+    fn write_playlist(&self, end_list: bool) -> Result<(), WriterError> {
+        let mut content = Vec::new();
+        self.build_playlist(end_list).write_to(&mut content)?;
+
+        let path = self.playlist_path();
+        let tmp_path = path.with_extension("m3u8.tmp");
+        fs::write(&tmp_path, &content)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), WriterError> {
+        self.finish_pending_ts(None)?;
+        self.write_playlist(true)?;
+        info!(
+            path = %self.playlist_path().display(),
+            segments = self.segment_count,
+            duration_secs = self.state.media_duration_secs_total,
+            "Finalized playlist"
+        );
+        Ok(())
+    }
+}
+
+/// First PTS of a TS segment, from its first video stream or else its first audio stream.
+fn ts_first_pts(ts: &TsSegmentData) -> Option<u64> {
+    let (info, _) = ts.parse_stream_and_packets().ok()?;
+    let programs = &info.programs;
+    programs
+        .iter()
+        .flat_map(|program| &program.video_streams)
+        .chain(programs.iter().flat_map(|program| &program.audio_streams))
+        .find_map(|stream| stream.first_pts)
+}
+
+/// Duration of a TS segment from its PCRs, for the last segment or one before a
+/// discontinuity.
+fn ts_pcr_duration(ts: &TsSegmentData) -> Option<f32> {
+    ts::estimate_duration(&ts.data)
+        .ok()
+        .map(|duration| duration.as_secs_f32())
+}
+
+impl ProtocolWriter for HlsPlaylistWriter {
+    type Item = HlsData;
+
+    fn get_state(&self) -> &WriterState {
+        &self.state
+    }
+
+    fn run(
+        &mut self,
+        mut input: tokio::sync::mpsc::Receiver<Result<HlsData, PipelineError>>,
+    ) -> Result<WriterStats, WriterError> {
+        fs::create_dir_all(&self.config.output_dir)?;
+        while let Some(result) = input.blocking_recv() {
+            match result {
+                Ok(item) => self.write_item(&item)?,
+                Err(e) => {
+                    let _ = self.finish();
+                    return Err(WriterError::InputError(e));
+                }
+            }
+        }
+        self.finish()?;
+        Ok(WriterStats::from_state(&self.state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use m3u8_rs::{Playlist, parse_playlist_res};
+    use mp4::test_support::{make_init_with_timescale, make_media_segment_with_timing};
+
+    fn run_writer(
+        dir: &std::path::Path,
+        live_window: Option<usize>,
+        items: Vec<HlsData>,
+    ) -> (HlsPlaylistWriter, WriterStats) {
+        let mut writer = HlsPlaylistWriter::new(HlsPlaylistWriterConfig {
+            output_dir: dir.to_path_buf(),
+            playlist_name: "index".to_string(),
+            segment_template: "seg_%i".to_string(),
+            live_window,
+        });
+
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<HlsData, PipelineError>>(16);
+        let handle = std::thread::spawn(move || {
+            let stats = writer.run(rx);
+            (writer, stats)
+        });
+        for item in items {
+            tx.blocking_send(Ok(item)).unwrap();
+        }
+        drop(tx);
+
+        let (writer, stats) = handle.join().expect("writer thread join");
+        (writer, stats.expect("writer ok"))
+    }
+
+    fn read_playlist(writer: &HlsPlaylistWriter) -> MediaPlaylist {
+        let content = fs::read(writer.playlist_path()).expect("read playlist");
+        match parse_playlist_res(&content).expect("playlist should parse") {
+            Playlist::MediaPlaylist(playlist) => playlist,
+            Playlist::MasterPlaylist(_) => panic!("expected media playlist"),
+        }
+    }
+
+    /// Media segment whose upstream EXTINF deliberately disagrees with its samples.
+    fn segment(extinf: f32, discontinuity: bool) -> MediaSegment {
+        MediaSegment {
+            duration: extinf,
+            discontinuity,
+            ..MediaSegment::empty()
+        }
+    }
+
+    /// PAT, PMT with one H.264 stream on PID 0x100, and a PES packet starting at `pts`.
+    fn ts_with_pts(pts: u64) -> Bytes {
+        let mut pat = vec![0xFFu8; 188];
+        pat[..17].copy_from_slice(&[
+            0x47, 0x40, 0x00, 0x10, // PUSI, PID 0, payload only
+            0x00, // pointer field
+            0x00, 0x80, 0x0D, // PAT, section length 13
+            0x00, 0x01, 0x01, 0x00, 0x00, // transport stream ID, version, section numbers
+            0x00, 0x01, 0xE1, 0x00, // program 1 -> PMT PID 0x100
+        ]);
+
+        let mut pmt = vec![0xFFu8; 188];
+        pmt[..22].copy_from_slice(&[
+            0x47, 0x41, 0x00, 0x10, // PUSI, PID 0x100, payload only
+            0x00, // pointer field
+            0x02, 0x80, 0x12, // PMT, section length 18
+            0x00, 0x01, 0x01, 0x00, 0x00, // program 1, version, section numbers
+            0xE1, 0x00, // PCR PID 0x100
+            0x00, 0x00, // program info length
+            0x1B, 0xE1, 0x00, 0x00, 0x00, // H.264 on PID 0x100
+        ]);
+
+        let mut pes = vec![0xFFu8; 188];
+        let mut header = vec![
+            0x47, 0x41, 0x00, 0x11, // PUSI, PID 0x100, payload only, CC 1
+            0x00, 0x00, 0x01, 0xE0, 0x00, 0x00, // start code, video stream, unbounded length
+            0x80, 0x80, 0x05, // PTS only, header data length 5
+        ];
+        header.extend_from_slice(&[
+            0x21 | (((pts >> 30) as u8 & 0x07) << 1),
+            (pts >> 22) as u8,
+            ((pts >> 15) as u8 & 0x7F) << 1 | 0x01,
+            (pts >> 7) as u8,
+            ((pts as u8) & 0x7F) << 1 | 0x01,
+        ]);
+        pes[..header.len()].copy_from_slice(&header);
+
+        let mut data = pat;
+        data.extend_from_slice(&pmt);
+        data.extend_from_slice(&pes);
+        Bytes::from(data)
+    }
+
+    /// [`ts_with_pts`] followed by `packets` adaptation-only packets carrying a PCR, one
+    /// every `packet_ticks` of the 27 MHz clock counted from the first packet.
+    fn ts_with_pcrs(pts: u64, packets: u64, packet_ticks: u64) -> Bytes {
+        let mut data = ts_with_pts(pts).to_vec();
+        for index in 3..3 + packets {
+            let base = index * packet_ticks / 300;
+            let extension = (index * packet_ticks % 300) as u16;
+            let mut packet = vec![0xFFu8; 188];
+            packet[..12].copy_from_slice(&[
+                0x47,
+                0x01,
+                0x00,
+                0x21, // PID 0x100, adaptation field only, CC 1
+                0xB7,
+                0x10, // adaptation field length 183, PCR flag
+                (base >> 25) as u8,
+                (base >> 17) as u8,
+                (base >> 9) as u8,
+                (base >> 1) as u8,
+                ((base as u8 & 0x01) << 7) | 0x7E | (extension >> 8) as u8,
+                extension as u8,
+            ]);
+            data.extend_from_slice(&packet);
+        }
+        Bytes::from(data)
+    }
+
+    #[test]
+    fn writes_vod_playlist_with_measured_fmp4_durations() {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+        let init = make_init_with_timescale(1, 1000, 0);
+        let new_init = make_init_with_timescale(1, 90_000, 0);
+
+        let items = vec![
+            HlsData::mp4_init(segment(0.0, false), init.clone()),
+            HlsData::mp4_segment(
+                segment(4.0, false),
+                make_media_segment_with_timing(1, 1, 0, &[1000, 1000]),
+            ),
+            // Repeated init segments are written once
+            HlsData::mp4_init(segment(0.0, false), init),
+            HlsData::mp4_segment(
+                segment(4.0, false),
+                make_media_segment_with_timing(1, 1, 2000, &[1000, 500]),
+            ),
+            HlsData::end_marker(),
+            HlsData::mp4_init(segment(0.0, false), new_init),
+            HlsData::mp4_segment(
+                segment(4.0, false),
+                make_media_segment_with_timing(1, 1, 0, &[90_000, 90_000, 45_000]),
+            ),
+        ];
+
+        let (writer, stats) = run_writer(tempdir.path(), None, items);
+        assert_eq!(stats.files_created, 5);
+
+        let playlist = read_playlist(&writer);
+        assert!(playlist.end_list);
+        assert_eq!(playlist.playlist_type, Some(MediaPlaylistType::Vod));
+        assert_eq!(playlist.target_duration, 3);
+        assert_eq!(playlist.version, Some(7));
+
+        let durations: Vec<f32> = playlist.segments.iter().map(|s| s.duration).collect();
+        assert_eq!(durations, vec![2.0, 1.5, 2.5]);
+        let uris: Vec<&str> = playlist.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(uris, vec!["seg_0.m4s", "seg_1.m4s", "seg_2.m4s"]);
+        for uri in uris {
+            assert!(tempdir.path().join(uri).exists());
+        }
+
+        let maps: Vec<Option<&str>> = playlist
+            .segments
+            .iter()
+            .map(|s| s.map.as_ref().map(|map| map.uri.as_str()))
+            .collect();
+        assert_eq!(
+            maps,
+            vec![Some("index_init_0.mp4"), None, Some("index_init_1.mp4")]
+        );
+        let discontinuities: Vec<bool> =
+            playlist.segments.iter().map(|s| s.discontinuity).collect();
+        assert_eq!(discontinuities, vec![false, false, true]);
+    }
+
+    #[test]
+    fn times_ts_segments_by_pts_distance() {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+        let items = vec![
+            HlsData::ts(segment(6.0, false), ts_with_pts(0)),
+            HlsData::ts(segment(6.0, false), ts_with_pts(180_000)),
+            HlsData::ts(segment(6.0, false), ts_with_pts(450_000)),
+            // Timestamps restart after a discontinuity, the upstream duration is kept
+            HlsData::ts(segment(6.0, true), ts_with_pts(0)),
+        ];
+
+        let (writer, _) = run_writer(tempdir.path(), None, items);
+        let playlist = read_playlist(&writer);
+
+        let durations: Vec<f32> = playlist.segments.iter().map(|s| s.duration).collect();
+        assert_eq!(durations, vec![2.0, 3.0, 6.0, 6.0]);
+        assert!(playlist.segments[3].discontinuity);
+        assert_eq!(playlist.version, Some(3));
+        assert!(playlist.segments.iter().all(|s| s.map.is_none()));
+    }
+
+    #[test]
+    fn times_last_ts_segment_by_pcrs() {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+        let items = vec![
+            // The PTS of the next segment takes precedence over the PCRs
+            HlsData::ts(segment(6.0, false), ts_with_pcrs(0, 7, 10_800_000)),
+            // 10 packets, 0.4s apart
+            HlsData::ts(segment(6.0, false), ts_with_pcrs(180_000, 7, 10_800_000)),
+        ];
+
+        let (writer, _) = run_writer(tempdir.path(), None, items);
+        let playlist = read_playlist(&writer);
+
+        let durations: Vec<f32> = playlist.segments.iter().map(|s| s.duration).collect();
+        assert_eq!(durations, vec![2.0, 4.0]);
+        assert_eq!(playlist.target_duration, 4);
+    }
+
+    #[test]
+    fn live_mode_keeps_a_rolling_window() {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+        let init = make_init_with_timescale(1, 1000, 0);
+        let mut items = vec![HlsData::mp4_init(segment(0.0, false), init)];
+        for i in 0..4u64 {
+            items.push(HlsData::mp4_segment(
+                segment(2.0, i == 1),
+                make_media_segment_with_timing(1, 1, i * 2000, &[2000]),
+            ));
+        }
+
+        let (writer, stats) = run_writer(tempdir.path(), Some(2), items);
+        assert_eq!(stats.files_created, 5);
+
+        let playlist = read_playlist(&writer);
+        assert_eq!(playlist.playlist_type, None);
+        assert!(playlist.end_list);
+        assert_eq!(playlist.media_sequence, 2);
+        assert_eq!(playlist.discontinuity_sequence, 1);
+        let uris: Vec<&str> = playlist.segments.iter().map(|s| s.uri.as_str()).collect();
+        assert_eq!(uris, vec!["seg_2.m4s", "seg_3.m4s"]);
+        // The first listed segment carries the map of the window
+        assert_eq!(
+            playlist.segments[0]
+                .map
+                .as_ref()
+                .map(|map| map.uri.as_str()),
+            Some("index_init_0.mp4")
+        );
+    }
+}
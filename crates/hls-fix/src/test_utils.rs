@@ -0,0 +1,227 @@
+//! Builders of small MPEG-TS segments for the operator tests.
+
+/// PAT and PMT of a program with a video stream on PID 0x100 and an audio stream on 0x101.
+pub(crate) fn create_ts_data_with_codecs(
+    video_codec: u8,
+    audio_codec: u8,
+    program_num: u16,
+) -> Vec<u8> {
+    let mut ts_data = Vec::new();
+
+    // PAT packet (188 bytes)
+    let mut pat_packet = vec![0u8; 188];
+    pat_packet[0] = 0x47; // Sync byte
+    pat_packet[1] = 0x40; // PUSI set, PID = 0 (PAT)
+    pat_packet[2] = 0x00;
+    pat_packet[3] = 0x10; // No scrambling, payload only
+
+    // Simple PAT payload
+    pat_packet[4] = 0x00; // Pointer field
+    pat_packet[5] = 0x00; // Table ID (PAT)
+    pat_packet[6] = 0x80; // Section syntax indicator
+    pat_packet[7] = 0x0D; // Section length (13 bytes)
+    pat_packet[8] = 0x00;
+    pat_packet[9] = 0x01; // Transport stream ID
+    pat_packet[10] = 0x01; // Version 0 + current/next = 1
+    pat_packet[11] = 0x00;
+    pat_packet[12] = 0x00; // Section numbers
+    // Program entry
+    pat_packet[13] = (program_num >> 8) as u8;
+    pat_packet[14] = (program_num & 0xFF) as u8;
+    pat_packet[15] = 0xE1;
+    pat_packet[16] = 0x00; // PMT PID 0x100
+
+    // PMT packet (188 bytes)
+    let mut pmt_packet = vec![0u8; 188];
+    pmt_packet[0] = 0x47; // Sync byte
+    pmt_packet[1] = 0x41; // PUSI set, PID = 0x100
+    pmt_packet[2] = 0x00;
+    pmt_packet[3] = 0x10; // No scrambling, payload only
+
+    pmt_packet[4] = 0x00; // Pointer field
+    pmt_packet[5] = 0x02; // Table ID (PMT)
+    pmt_packet[6] = 0x80; // Section syntax indicator
+    pmt_packet[7] = 0x17; // Section length (23 bytes for 2 streams)
+    pmt_packet[8] = (program_num >> 8) as u8;
+    pmt_packet[9] = (program_num & 0xFF) as u8;
+    pmt_packet[10] = 0x01; // Version 0 + current/next = 1
+    pmt_packet[11] = 0x00;
+    pmt_packet[12] = 0x00; // Section numbers
+    pmt_packet[13] = 0xE1;
+    pmt_packet[14] = 0x00; // PCR PID 0x100
+    pmt_packet[15] = 0x00;
+    pmt_packet[16] = 0x00; // Program info length
+    // Video stream
+    pmt_packet[17] = video_codec;
+    pmt_packet[18] = 0xE1;
+    pmt_packet[19] = 0x00; // Elementary PID 0x100
+    pmt_packet[20] = 0x00;
+    pmt_packet[21] = 0x00; // ES info length
+    // Audio stream
+    pmt_packet[22] = audio_codec;
+    pmt_packet[23] = 0xE1;
+    pmt_packet[24] = 0x01; // Elementary PID 0x101
+    pmt_packet[25] = 0x00;
+    pmt_packet[26] = 0x00; // ES info length
+
+    ts_data.extend_from_slice(&pat_packet);
+    ts_data.extend_from_slice(&pmt_packet);
+    ts_data
+}
+
+pub(crate) fn make_ts_packet_header(pid: u16, pusi: bool, adaptation_field_control: u8) -> [u8; 4] {
+    // sync
+    let mut header = [0u8; 4];
+    header[0] = 0x47;
+    header[1] = ((pusi as u8) << 6) | ((pid >> 8) as u8 & 0x1F);
+    header[2] = (pid & 0xFF) as u8;
+    // no scrambling, afc + continuity 0
+    header[3] = adaptation_field_control << 4;
+    header
+}
+
+pub(crate) fn make_ts_packet_with_rai(pid: u16, rai: bool, payload: &[u8]) -> [u8; 188] {
+    // adaptation_field_control=0x03 (adaptation + payload)
+    let mut pkt = [0u8; 188];
+    let header = make_ts_packet_header(pid, true, 0x03);
+    pkt[..4].copy_from_slice(&header);
+
+    // Adaptation field: length + flags byte.
+    // We only need the flags byte for RAI; no PCR.
+    pkt[4] = 1; // adaptation_field_length
+    pkt[5] = if rai { 0x40 } else { 0x00 };
+
+    let payload_start = 6;
+    let max_payload = 188 - payload_start;
+    let payload_len = payload.len().min(max_payload);
+    pkt[payload_start..payload_start + payload_len].copy_from_slice(&payload[..payload_len]);
+    for b in &mut pkt[payload_start + payload_len..] {
+        *b = 0xFF;
+    }
+    pkt
+}
+
+pub(crate) fn make_fake_h264_sps_nal(width: u32, height: u32) -> Vec<u8> {
+    // Generate a valid H.264 SPS NAL unit that our h264 crate can parse.
+    // Start code is NOT included here.
+    use bytes_util::BitWriter;
+    use expgolomb::BitWriterExpGolombExt;
+
+    let mut out = Vec::new();
+    let mut w = BitWriter::new(&mut out);
+
+    // NAL header (forbidden_zero_bit=0, nal_ref_idc=0, nal_unit_type=7)
+    w.write_bit(false).unwrap();
+    w.write_bits(0, 2).unwrap();
+    w.write_bits(7, 5).unwrap();
+
+    // profile_idc 77 (Main), constraint flags 0, level_idc 0
+    w.write_bits(77, 8).unwrap();
+    w.write_bits(0, 8).unwrap();
+    w.write_bits(0, 8).unwrap();
+
+    // seq_parameter_set_id
+    w.write_exp_golomb(0).unwrap();
+    // log2_max_frame_num_minus4
+    w.write_exp_golomb(0).unwrap();
+    // pic_order_cnt_type
+    w.write_exp_golomb(0).unwrap();
+    // log2_max_pic_order_cnt_lsb_minus4
+    w.write_exp_golomb(0).unwrap();
+
+    // max_num_ref_frames
+    w.write_exp_golomb(0).unwrap();
+    // gaps_in_frame_num_value_allowed_flag
+    w.write_bit(false).unwrap();
+
+    // pic_width_in_mbs_minus1, pic_height_in_map_units_minus1
+    let width_mbs = (width / 16).saturating_sub(1) as u64;
+    let height_map_units = (height / 16).saturating_sub(1) as u64;
+    w.write_exp_golomb(width_mbs).unwrap();
+    w.write_exp_golomb(height_map_units).unwrap();
+
+    // frame_mbs_only_flag (progressive)
+    w.write_bit(true).unwrap();
+    // direct_8x8_inference_flag
+    w.write_bit(false).unwrap();
+    // frame_cropping_flag
+    w.write_bit(false).unwrap();
+    // vui_parameters_present_flag
+    w.write_bit(false).unwrap();
+
+    w.finish().unwrap();
+    out
+}
+
+pub(crate) fn make_fake_h264_pes_with_sps(width: u32, height: u32) -> Vec<u8> {
+    // Minimal PES header + start code prefix + SPS NAL.
+    // Our detector scans payloads for start codes.
+    let mut out = Vec::new();
+    // PES start code prefix
+    out.extend_from_slice(&[0x00, 0x00, 0x01]);
+    // stream_id (video)
+    out.push(0xE0);
+    // PES_packet_length = 0 (unknown)
+    out.extend_from_slice(&[0x00, 0x00]);
+    // flags: '10' + no scrambling etc.
+    out.push(0x80);
+    // PTS/DTS flags 00
+    out.push(0x00);
+    // header_data_length 0
+    out.push(0x00);
+
+    // Annex B start code + SPS
+    out.extend_from_slice(&[0x00, 0x00, 0x01]);
+    out.extend_from_slice(&make_fake_h264_sps_nal(width, height));
+    out
+}
+
+pub(crate) fn create_ts_data_with_rai_and_sps(
+    video_pid: u16,
+    rai: bool,
+    width: u32,
+    height: u32,
+) -> Vec<u8> {
+    // Reuse PAT/PMT from helper, then append one video packet with PES/SPS.
+    let mut ts_data = create_ts_data_with_codecs(0x1B, 0x0F, 1);
+    let pes = make_fake_h264_pes_with_sps(width, height);
+    let video_packet = make_ts_packet_with_rai(video_pid, rai, &pes);
+    ts_data.extend_from_slice(&video_packet);
+    ts_data
+}
+
+/// A PES packet with a single ADTS frame of AAC LC audio.
+pub(crate) fn make_fake_adts_pes(sample_rate_index: u8, channels: u8) -> Vec<u8> {
+    let frame_length: u16 = 7 + 4;
+    let mut out = Vec::new();
+    // PES start code prefix, stream_id (audio), PES_packet_length 0
+    out.extend_from_slice(&[0x00, 0x00, 0x01, 0xC0, 0x00, 0x00]);
+    // flags, no PTS/DTS, header_data_length 0
+    out.extend_from_slice(&[0x80, 0x00, 0x00]);
+    // ADTS header: MPEG-4, no CRC, AAC LC
+    out.extend_from_slice(&[
+        0xFF,
+        0xF1,
+        (1 << 6) | (sample_rate_index << 2) | (channels >> 2),
+        ((channels & 0x03) << 6) | (frame_length >> 11) as u8,
+        (frame_length >> 3) as u8,
+        ((frame_length & 0x07) as u8) << 5 | 0x1F,
+        0xFC,
+    ]);
+    out.extend_from_slice(&[0x21, 0x10, 0x04, 0x60]);
+    out
+}
+
+/// PAT and PMT of an H.264 and AAC program, followed by a keyframe carrying an SPS and
+/// an ADTS frame.
+pub(crate) fn create_ts_data_with_sps_and_adts(
+    width: u32,
+    height: u32,
+    sample_rate_index: u8,
+    channels: u8,
+) -> Vec<u8> {
+    let mut ts_data = create_ts_data_with_rai_and_sps(0x0100, true, width, height);
+    let pes = make_fake_adts_pes(sample_rate_index, channels);
+    ts_data.extend_from_slice(&make_ts_packet_with_rai(0x0101, false, &pes));
+    ts_data
+}
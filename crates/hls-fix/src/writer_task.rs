@@ -1,14 +1,14 @@
 use std::{
-    fs::OpenOptions,
     io::{BufWriter, Write},
     path::PathBuf,
 };
 
-use hls::{HlsData, M4sData};
+use hls::{HlsData, M4sData, StreamProfile};
 use pipeline_common::{
-    FormatStrategy, PipelineError, PostWriteAction, ProgressConfig, ProtocolWriter, SplitReason,
-    WriterConfig, WriterError, WriterProgress, WriterState, WriterStats, WriterTask,
-    expand_filename_template,
+    FormatStrategy, OutputSink, PipelineError, PostWriteAction, ProgressConfig, ProtocolWriter,
+    SegmentHook, SinkWriter, SplitReason, StreamMetadata, WriterConfig, WriterError,
+    WriterProgress, WriterState, WriterStats, WriterTask, expand_filename_template_with,
+    template_uses_stream_metadata,
 };
 
 use tracing::{Span, debug, info};
@@ -22,6 +22,15 @@ pub struct HlsFormatStrategy {
     target_duration: f32,
     max_file_size: Option<u64>,
     last_split_reason: Option<SplitReason>,
+    /// Stream metadata for the variables of the file name template, kept across files
+    /// as an fMP4 init segment is not repeated for every file.
+    metadata: StreamMetadata,
+    /// Stream title, set by the caller.
+    title: Option<String>,
+    /// Whether the name of the current file no longer depends on stream metadata to come.
+    name_settled: bool,
+    /// Whether a media segment was written to the current file.
+    media_written: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -40,6 +49,10 @@ impl HlsFormatStrategy {
             target_duration: 0.0,
             max_file_size,
             last_split_reason: None,
+            metadata: StreamMetadata::default(),
+            title: None,
+            name_settled: true,
+            media_written: false,
         }
     }
 
@@ -48,9 +61,34 @@ impl HlsFormatStrategy {
         self.current_offset = 0;
         self.target_duration = 0.0;
         self.last_split_reason = None;
+        self.media_written = false;
         Ok(())
     }
 
+    fn stream_metadata(&self) -> StreamMetadata {
+        StreamMetadata {
+            title: self.title.clone(),
+            ..self.metadata.clone()
+        }
+    }
+
+    /// Update the stream metadata from the profile of a TS or init segment.
+    fn update_metadata(&mut self, item: &HlsData) {
+        let Some(profile) = item.get_stream_profile() else {
+            return;
+        };
+        let metadata = &mut self.metadata;
+        if let Some(resolution) = profile.resolution {
+            metadata.resolution = Some((resolution.width, resolution.height));
+        }
+        if let Some(codec) = video_codec_name(&profile) {
+            metadata.video_codec = Some(codec.to_string());
+        }
+        if let Some(codec) = audio_codec_name(&profile) {
+            metadata.audio_codec = Some(codec.to_string());
+        }
+    }
+
     fn update_status(&self, state: &WriterState) {
         // Update the current span with progress information
         let span = Span::current();
@@ -65,17 +103,17 @@ impl HlsFormatStrategy {
 }
 
 impl FormatStrategy<HlsData> for HlsFormatStrategy {
-    type Writer = BufWriter<std::fs::File>;
+    type Writer = BufWriter<SinkWriter>;
     type StrategyError = HlsStrategyError;
 
-    fn create_writer(&self, path: &std::path::Path) -> Result<Self::Writer, Self::StrategyError> {
+    fn create_writer(
+        &self,
+        path: &std::path::Path,
+        config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
         debug!("Creating writer for path: {}", path.display());
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
-        Ok(BufWriter::with_capacity(1024 * 1024, file))
+        let output = config.open_output(path)?;
+        Ok(BufWriter::with_capacity(1024 * 1024, output))
     }
 
     fn write_item(
@@ -83,6 +121,19 @@ impl FormatStrategy<HlsData> for HlsFormatStrategy {
         writer: &mut Self::Writer,
         item: &HlsData,
     ) -> Result<u64, Self::StrategyError> {
+        if !self.name_settled {
+            if matches!(
+                item,
+                HlsData::TsData(_) | HlsData::M4sData(M4sData::InitSegment(_))
+            ) {
+                self.update_metadata(item);
+            }
+            self.media_written |= matches!(
+                item,
+                HlsData::TsData(_) | HlsData::M4sData(M4sData::Segment(_))
+            );
+        }
+
         match item {
             HlsData::TsData(ts) => {
                 self.analyzer
@@ -108,7 +159,8 @@ impl FormatStrategy<HlsData> for HlsFormatStrategy {
                     M4sData::Segment(segment) => {
                         let bytes_written = segment.data.len() as u64;
                         writer.write_all(&segment.data)?;
-                        self.target_duration += segment.segment.duration;
+                        // Sample-accurate when the fragment timing could be read
+                        self.target_duration += self.analyzer.stats.last_segment_duration;
                         bytes_written
                     }
                 };
@@ -140,20 +192,43 @@ impl FormatStrategy<HlsData> for HlsFormatStrategy {
     fn next_file_path(&self, config: &WriterConfig, state: &WriterState) -> PathBuf {
         let sequence = state.file_sequence_number;
 
-        let file_name = expand_filename_template(&config.file_name_template, Some(sequence));
+        let file_name = expand_filename_template_with(
+            &config.file_name_template,
+            Some(sequence),
+            &self.stream_metadata(),
+        );
         config
             .base_path
             .join(format!("{}.{}", file_name, config.file_extension))
     }
 
+    fn resolved_file_path(
+        &mut self,
+        config: &WriterConfig,
+        state: &WriterState,
+    ) -> Option<PathBuf> {
+        if self.name_settled {
+            return None;
+        }
+        // The first media segment carries everything the stream profile can tell
+        if !self.media_written && !self.stream_metadata().covers(&config.file_name_template) {
+            return None;
+        }
+        self.name_settled = true;
+
+        let path = self.next_file_path(config, state);
+        (path != state.current_path).then_some(path)
+    }
+
     fn on_file_open(
         &mut self,
         _writer: &mut Self::Writer,
         path: &std::path::Path,
-        _config: &WriterConfig,
+        config: &WriterConfig,
         _state: &WriterState,
     ) -> Result<u64, Self::StrategyError> {
         self.reset_for_new_file()?;
+        self.name_settled = !template_uses_stream_metadata(&config.file_name_template);
 
         info!(path = %path.display(), "Opening segment");
 
@@ -171,7 +246,7 @@ impl FormatStrategy<HlsData> for HlsFormatStrategy {
 
     fn on_file_close(
         &mut self,
-        _writer: &mut Self::Writer,
+        writer: &mut Self::Writer,
         path: &std::path::Path,
         _config: &WriterConfig,
         state: &WriterState,
@@ -187,6 +262,9 @@ impl FormatStrategy<HlsData> for HlsFormatStrategy {
             self.last_split_reason = Some(SplitReason::SizeLimit);
         }
 
+        writer.flush()?;
+        writer.get_mut().finish()?;
+
         let items_written = state.items_written_current_file;
         let duration_secs = self.target_duration;
 
@@ -234,6 +312,28 @@ impl FormatStrategy<HlsData> for HlsFormatStrategy {
     }
 }
 
+fn video_codec_name(profile: &StreamProfile) -> Option<&'static str> {
+    if profile.has_h264 {
+        Some("h264")
+    } else if profile.has_h265 {
+        Some("hevc")
+    } else if profile.has_av1 {
+        Some("av1")
+    } else {
+        None
+    }
+}
+
+fn audio_codec_name(profile: &StreamProfile) -> Option<&'static str> {
+    if profile.has_aac {
+        Some("aac")
+    } else if profile.has_ac3 {
+        Some("ac3")
+    } else {
+        None
+    }
+}
+
 /// Typed configuration for HLS writer.
 pub struct HlsWriterConfig {
     pub output_dir: PathBuf,
@@ -271,6 +371,28 @@ impl HlsWriter {
         self.writer_task.set_on_file_close_callback(callback);
     }
 
+    /// Add a hook invoked on the writer thread when segment files are opened and closed.
+    ///
+    /// A hook can rename or delete a segment once it is closed, see [`SegmentHook`].
+    pub fn add_segment_hook<H: SegmentHook>(&mut self, hook: H) {
+        self.writer_task.add_segment_hook(hook);
+    }
+
+    /// Name files that would overwrite an existing one `name_1`, `name_2`, ...
+    pub fn set_numbered_collisions(&mut self, numbered_collisions: bool) {
+        self.writer_task.config_mut().numbered_collisions = numbered_collisions;
+    }
+
+    /// Write files to `sink` instead of the local disk.
+    pub fn set_output_sink(&mut self, sink: OutputSink) {
+        self.writer_task.config_mut().sink = sink;
+    }
+
+    /// Set the stream title used by the `%title%` variable of the file name template.
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.writer_task.strategy_mut().title = Some(title.into());
+    }
+
     /// Set a progress callback with default intervals (1MB bytes, 1000ms time).
     pub fn set_progress_callback<F>(&mut self, callback: F)
     where
@@ -402,4 +524,91 @@ mod tests {
             .count();
         assert_eq!(file_count, 0);
     }
+
+    #[test]
+    fn segment_hooks_see_every_split_in_order() {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+
+        let mut writer = HlsWriter::new(HlsWriterConfig {
+            output_dir: tempdir.path().to_path_buf(),
+            base_name: "test-%i".to_string(),
+            extension: "ts".to_string(),
+            max_file_size: Some(15),
+        });
+        let (hook, mut events) = pipeline_common::QueuedSegmentHook::new();
+        writer.add_segment_hook(hook);
+
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<HlsData, PipelineError>>(16);
+
+        let handle = std::thread::spawn(move || writer.run(rx));
+
+        for byte in 0u8..3 {
+            let segment = HlsData::ts(
+                MediaSegment {
+                    duration: 1.0,
+                    ..MediaSegment::empty()
+                },
+                Bytes::from(vec![byte; 10]),
+            );
+            tx.blocking_send(Ok(segment)).unwrap();
+        }
+        drop(tx);
+
+        handle
+            .join()
+            .expect("writer thread join")
+            .expect("writer ok");
+
+        let mut order = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            order.push(match event {
+                pipeline_common::SegmentEvent::Opened { index, .. } => ("open", index),
+                pipeline_common::SegmentEvent::Renamed { index, .. } => ("rename", index),
+                pipeline_common::SegmentEvent::Closed { path, index, stats } => {
+                    assert!(path.exists());
+                    assert!(stats.size_bytes > 0);
+                    ("close", index)
+                }
+            });
+        }
+        assert_eq!(
+            order,
+            vec![("open", 0), ("close", 0), ("open", 1), ("close", 1)]
+        );
+    }
+
+    #[test]
+    fn names_files_from_title_and_falls_back_for_unknown_codecs() {
+        let tempdir = tempfile::tempdir().expect("create temp dir");
+
+        let mut writer = HlsWriter::new(HlsWriterConfig {
+            output_dir: tempdir.path().to_path_buf(),
+            base_name: "%title%_%vcodec%".to_string(),
+            extension: "ts".to_string(),
+            max_file_size: None,
+        });
+        writer.set_title("show");
+
+        let (tx, rx) = tokio::sync::mpsc::channel::<Result<HlsData, PipelineError>>(16);
+        let handle = std::thread::spawn(move || writer.run(rx));
+
+        // Not a transport stream, so the codec stays unknown
+        tx.blocking_send(Ok(HlsData::ts(
+            MediaSegment {
+                duration: 1.0,
+                ..MediaSegment::empty()
+            },
+            Bytes::from_static(&[0u8; 10]),
+        )))
+        .unwrap();
+        drop(tx);
+
+        let stats = handle
+            .join()
+            .expect("writer thread join")
+            .expect("writer ok");
+
+        assert_eq!(stats.files_created, 1);
+        assert!(tempdir.path().join("show_unknown.ts").exists());
+    }
 }
@@ -0,0 +1,60 @@
+// Helpers for EXT-X-BYTERANGE and EXT-X-MAP BYTERANGE sub-ranges
+use bytes::Bytes;
+use m3u8_rs::ByteRange;
+
+/// Start offset and exclusive end of a byte range.
+///
+/// A missing offset is treated as 0, which is what the playlist engine
+/// resolves it to when no previous sub-range of the same resource exists.
+#[inline]
+pub fn bounds(range: &ByteRange) -> (u64, u64) {
+    let start = range.offset.unwrap_or(0);
+    (start, start.saturating_add(range.length))
+}
+
+/// Value of the HTTP `Range` header requesting exactly this byte range
+pub fn http_range_header(range: &ByteRange) -> String {
+    let (start, end) = bounds(range);
+    format!("bytes={start}-{}", end.saturating_sub(1))
+}
+
+/// Cut the requested range out of a full response body.
+///
+/// For servers that ignore the `Range` header and answer with the whole
+/// resource. Returns `None` if the body is too short to contain the range.
+pub fn slice_full_body(body: &Bytes, range: &ByteRange) -> Option<Bytes> {
+    let (start, end) = bounds(range);
+    let start = usize::try_from(start).ok()?;
+    let end = usize::try_from(end).ok()?;
+    (end <= body.len()).then(|| body.slice(start..end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(length: u64, offset: Option<u64>) -> ByteRange {
+        ByteRange { length, offset }
+    }
+
+    #[test]
+    fn test_http_range_header() {
+        assert_eq!(http_range_header(&range(720, Some(0))), "bytes=0-719");
+        assert_eq!(http_range_header(&range(100, Some(720))), "bytes=720-819");
+        assert_eq!(http_range_header(&range(10, None)), "bytes=0-9");
+    }
+
+    #[test]
+    fn test_slice_full_body() {
+        let body = Bytes::from_static(b"0123456789");
+        assert_eq!(
+            slice_full_body(&body, &range(3, Some(4))).unwrap(),
+            Bytes::from_static(b"456")
+        );
+        assert_eq!(
+            slice_full_body(&body, &range(10, None)).unwrap(),
+            Bytes::from_static(b"0123456789")
+        );
+        assert!(slice_full_body(&body, &range(4, Some(8))).is_none());
+    }
+}
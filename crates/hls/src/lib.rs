@@ -1,6 +1,9 @@
 // HLS (HTTP Live Streaming) segment data handling
+pub mod byte_range;
+pub mod low_latency;
 pub mod mp4;
 pub mod profile;
+pub mod rendition;
 pub mod resolution;
 pub mod segment;
 pub mod ts;
@@ -10,6 +13,7 @@ pub use media_types::Resolution;
 pub use mp4::{M4sData, M4sInitSegmentData, M4sSegmentData};
 pub use pipeline_common::split_reason::SplitReason;
 pub use profile::{SegmentType, StreamProfile, StreamProfileOptions};
-pub use resolution::ResolutionDetector;
+pub use rendition::{HlsTrack, Rendition, RenditionGroup, RenditionKind, TrackData};
+pub use resolution::{ResolutionDetector, VideoParameters};
 pub use segment::HlsData;
 pub use ts::{ProgramInfo, StreamEntry, TsSegmentData, TsStreamInfo};
@@ -0,0 +1,371 @@
+// LL-HLS (Low-Latency HLS) media playlist extensions
+//
+// m3u8-rs attaches unknown tags to the segment that follows them and drops the ones trailing the
+// last segment, which is exactly where LL-HLS advertises the parts of the segment currently being
+// produced. The LL-HLS tags are therefore parsed from the raw playlist text.
+use m3u8_rs::ByteRange;
+
+/// `#EXT-X-SERVER-CONTROL` attributes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerControl {
+    /// Whether the server supports blocking playlist reloads (`_HLS_msn` / `_HLS_part`)
+    pub can_block_reload: bool,
+    pub can_skip_until: Option<f64>,
+    pub hold_back: Option<f64>,
+    pub part_hold_back: Option<f64>,
+}
+
+/// A partial segment advertised by `#EXT-X-PART`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialSegment {
+    /// Media sequence number of the parent segment
+    pub msn: u64,
+    /// Position of the part within its parent segment, starting at 0
+    pub index: u32,
+    pub uri: String,
+    pub duration: f64,
+    pub independent: bool,
+    /// Byte range within `uri`. A missing offset is resolved against the previous part of the
+    /// same resource.
+    pub byte_range: Option<ByteRange>,
+    pub gap: bool,
+    /// Whether an `#EXT-X-DISCONTINUITY` precedes this part. Only set on the first part of a
+    /// segment.
+    pub discontinuity: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreloadHintType {
+    Part,
+    Map,
+}
+
+/// A resource announced by `#EXT-X-PRELOAD-HINT` before it is available
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreloadHint {
+    pub hint_type: PreloadHintType,
+    pub uri: String,
+    pub byte_range_start: Option<u64>,
+    pub byte_range_length: Option<u64>,
+}
+
+/// LL-HLS information of a media playlist
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LowLatencyPlaylist {
+    pub server_control: Option<ServerControl>,
+    /// `PART-TARGET` of `#EXT-X-PART-INF`, in seconds
+    pub part_target: Option<f64>,
+    pub media_sequence: u64,
+    /// Number of complete segments in the playlist
+    pub segment_count: u64,
+    /// Partial segments in playlist order
+    pub parts: Vec<PartialSegment>,
+    pub preload_hints: Vec<PreloadHint>,
+}
+
+impl LowLatencyPlaylist {
+    /// Parse the LL-HLS tags of a media playlist. Playlists without LL-HLS tags yield an
+    /// instance for which [`is_low_latency`](Self::is_low_latency) is false.
+    pub fn parse(content: &str) -> Self {
+        let mut playlist = Self::default();
+        let mut segment_index = 0u64;
+        let mut part_index = 0u32;
+        let mut discontinuity = false;
+        let mut last_part_range: Option<(String, u64)> = None;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(tag) = line.strip_prefix('#') else {
+                // Segment URI, closing the current segment
+                segment_index += 1;
+                part_index = 0;
+                discontinuity = false;
+                continue;
+            };
+            let (name, value) = tag.split_once(':').unwrap_or((tag, ""));
+            match name {
+                "EXT-X-MEDIA-SEQUENCE" => {
+                    playlist.media_sequence = value.trim().parse().unwrap_or(0);
+                }
+                "EXT-X-DISCONTINUITY" => discontinuity = true,
+                "EXT-X-SERVER-CONTROL" => {
+                    let mut control = ServerControl::default();
+                    for (key, value) in parse_attribute_list(value) {
+                        match key {
+                            "CAN-BLOCK-RELOAD" => control.can_block_reload = value == "YES",
+                            "CAN-SKIP-UNTIL" => control.can_skip_until = value.parse().ok(),
+                            "HOLD-BACK" => control.hold_back = value.parse().ok(),
+                            "PART-HOLD-BACK" => control.part_hold_back = value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                    playlist.server_control = Some(control);
+                }
+                "EXT-X-PART-INF" => {
+                    playlist.part_target = parse_attribute_list(value)
+                        .into_iter()
+                        .find(|(key, _)| *key == "PART-TARGET")
+                        .and_then(|(_, value)| value.parse().ok());
+                }
+                "EXT-X-PART" => {
+                    let mut uri = None;
+                    let mut duration = None;
+                    let mut independent = false;
+                    let mut gap = false;
+                    let mut byte_range = None;
+                    for (key, value) in parse_attribute_list(value) {
+                        match key {
+                            "URI" => uri = Some(value.to_string()),
+                            "DURATION" => duration = value.parse().ok(),
+                            "INDEPENDENT" => independent = value == "YES",
+                            "GAP" => gap = value == "YES",
+                            "BYTERANGE" => byte_range = parse_byte_range(value),
+                            _ => {}
+                        }
+                    }
+                    let (Some(uri), Some(duration)) = (uri, duration) else {
+                        continue;
+                    };
+
+                    let byte_range = byte_range.map(|range: ByteRange| {
+                        let offset = range.offset.or_else(|| {
+                            last_part_range
+                                .as_ref()
+                                .filter(|(last_uri, _)| *last_uri == uri)
+                                .map(|(_, end)| *end)
+                        });
+                        ByteRange {
+                            length: range.length,
+                            offset,
+                        }
+                    });
+                    last_part_range = byte_range.as_ref().map(|range| {
+                        (
+                            uri.clone(),
+                            range.offset.unwrap_or(0).saturating_add(range.length),
+                        )
+                    });
+
+                    playlist.parts.push(PartialSegment {
+                        msn: segment_index,
+                        index: part_index,
+                        uri,
+                        duration,
+                        independent,
+                        byte_range,
+                        gap,
+                        discontinuity: discontinuity && part_index == 0,
+                    });
+                    part_index += 1;
+                }
+                "EXT-X-PRELOAD-HINT" => {
+                    let mut hint_type = None;
+                    let mut uri = None;
+                    let mut byte_range_start = None;
+                    let mut byte_range_length = None;
+                    for (key, value) in parse_attribute_list(value) {
+                        match key {
+                            "TYPE" => {
+                                hint_type = match value {
+                                    "PART" => Some(PreloadHintType::Part),
+                                    "MAP" => Some(PreloadHintType::Map),
+                                    _ => None,
+                                }
+                            }
+                            "URI" => uri = Some(value.to_string()),
+                            "BYTERANGE-START" => byte_range_start = value.parse().ok(),
+                            "BYTERANGE-LENGTH" => byte_range_length = value.parse().ok(),
+                            _ => {}
+                        }
+                    }
+                    if let (Some(hint_type), Some(uri)) = (hint_type, uri) {
+                        playlist.preload_hints.push(PreloadHint {
+                            hint_type,
+                            uri,
+                            byte_range_start,
+                            byte_range_length,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        playlist.segment_count = segment_index;
+        // EXT-X-MEDIA-SEQUENCE may only be known once the header is parsed
+        for part in &mut playlist.parts {
+            part.msn += playlist.media_sequence;
+        }
+        playlist
+    }
+
+    /// Whether the playlist advertises partial segments
+    #[inline]
+    pub fn is_low_latency(&self) -> bool {
+        self.part_target.is_some()
+    }
+
+    #[inline]
+    pub fn can_block_reload(&self) -> bool {
+        self.server_control
+            .as_ref()
+            .is_some_and(|control| control.can_block_reload)
+    }
+
+    /// Media sequence number of the segment currently being produced
+    #[inline]
+    pub fn next_msn(&self) -> u64 {
+        self.media_sequence + self.segment_count
+    }
+
+    /// The listed parts of the segment with the given media sequence number
+    pub fn parts_of(&self, msn: u64) -> impl Iterator<Item = &PartialSegment> {
+        self.parts.iter().filter(move |part| part.msn == msn)
+    }
+
+    /// The `(_HLS_msn, _HLS_part)` pair of the first part not yet in the playlist
+    pub fn next_part(&self) -> (u64, u32) {
+        let msn = self.next_msn();
+        (msn, self.parts_of(msn).count() as u32)
+    }
+
+    /// The preload hint of the next part, if any
+    pub fn part_hint(&self) -> Option<&PreloadHint> {
+        self.preload_hints
+            .iter()
+            .find(|hint| hint.hint_type == PreloadHintType::Part)
+    }
+}
+
+/// Split an HLS attribute list into key/value pairs, removing the quotes around quoted values
+pub fn parse_attribute_list(input: &str) -> Vec<(&str, &str)> {
+    let mut attributes = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0usize;
+    for (idx, ch) in input.char_indices() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                attributes.extend(split_attribute(&input[start..idx]));
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    attributes.extend(split_attribute(&input[start..]));
+    attributes
+}
+
+fn split_attribute(attribute: &str) -> Option<(&str, &str)> {
+    let (key, value) = attribute.split_once('=')?;
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((key.trim(), value))
+}
+
+fn parse_byte_range(value: &str) -> Option<ByteRange> {
+    let (length, offset) = match value.split_once('@') {
+        Some((length, offset)) => (length, Some(offset.trim().parse().ok()?)),
+        None => (value, None),
+    };
+    Some(ByteRange {
+        length: length.trim().parse().ok()?,
+        offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LL_PLAYLIST: &str = "#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-VERSION:9
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3.0,CAN-SKIP-UNTIL=24.0
+#EXT-X-PART-INF:PART-TARGET=1.0
+#EXT-X-MEDIA-SEQUENCE:100
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:4.0,
+seg100.mp4
+#EXT-X-PART:DURATION=1.0,URI=\"seg101.part0.mp4\",INDEPENDENT=YES
+#EXT-X-PART:DURATION=1.0,URI=\"seg101.part1.mp4\"
+#EXT-X-PART:DURATION=1.0,URI=\"seg101.part2.mp4\"
+#EXT-X-PART:DURATION=1.0,URI=\"seg101.part3.mp4\"
+#EXTINF:4.0,
+seg101.mp4
+#EXT-X-DISCONTINUITY
+#EXT-X-PART:DURATION=1.0,URI=\"seg102.mp4\",BYTERANGE=\"1000@0\",INDEPENDENT=YES
+#EXT-X-PART:DURATION=1.0,URI=\"seg102.mp4\",BYTERANGE=1200
+#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"seg102.mp4\",BYTERANGE-START=2200
+";
+
+    #[test]
+    fn test_parse_low_latency_playlist() {
+        let playlist = LowLatencyPlaylist::parse(LL_PLAYLIST);
+
+        assert!(playlist.is_low_latency());
+        assert!(playlist.can_block_reload());
+        assert_eq!(playlist.part_target, Some(1.0));
+        assert_eq!(
+            playlist.server_control.as_ref().unwrap().part_hold_back,
+            Some(3.0)
+        );
+        assert_eq!(playlist.media_sequence, 100);
+        assert_eq!(playlist.segment_count, 2);
+        assert_eq!(playlist.next_msn(), 102);
+        assert_eq!(playlist.next_part(), (102, 2));
+
+        let parts: Vec<_> = playlist
+            .parts_of(101)
+            .map(|part| (part.index, part.uri.as_str(), part.independent))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (0, "seg101.part0.mp4", true),
+                (1, "seg101.part1.mp4", false),
+                (2, "seg101.part2.mp4", false),
+                (3, "seg101.part3.mp4", false),
+            ]
+        );
+
+        let trailing: Vec<_> = playlist.parts_of(102).collect();
+        assert_eq!(trailing.len(), 2);
+        assert!(trailing[0].discontinuity);
+        assert!(!trailing[1].discontinuity);
+        assert_eq!(
+            trailing[1].byte_range,
+            Some(ByteRange {
+                length: 1200,
+                offset: Some(1000)
+            })
+        );
+
+        let hint = playlist.part_hint().unwrap();
+        assert_eq!(hint.uri, "seg102.mp4");
+        assert_eq!(hint.byte_range_start, Some(2200));
+    }
+
+    #[test]
+    fn test_classic_playlist_is_not_low_latency() {
+        let playlist = LowLatencyPlaylist::parse(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:4\n#EXT-X-MEDIA-SEQUENCE:7\n#EXTINF:4.0,\nseg7.ts\n",
+        );
+        assert!(!playlist.is_low_latency());
+        assert!(!playlist.can_block_reload());
+        assert!(playlist.parts.is_empty());
+        assert_eq!(playlist.next_msn(), 8);
+    }
+
+    #[test]
+    fn test_parse_attribute_list_keeps_quoted_commas() {
+        let attributes = parse_attribute_list("URI=\"a,b.mp4\",DURATION=1.5");
+        assert_eq!(attributes, vec![("URI", "a,b.mp4"), ("DURATION", "1.5")]);
+    }
+}
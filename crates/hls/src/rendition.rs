@@ -0,0 +1,260 @@
+//! Alternate renditions of a master playlist, declared by `#EXT-X-MEDIA`.
+//!
+//! A master playlist can offer the audio of its variants in several languages: each
+//! variant names an audio group with its `AUDIO` attribute, and the renditions of the
+//! group are listed with the same `GROUP-ID`. A rendition with a `URI` has a media
+//! playlist of its own, downloaded next to the one of the variant; one without is
+//! carried by the variant itself.
+
+use std::fmt;
+use std::sync::Arc;
+
+use m3u8_rs::{AlternativeMedia, AlternativeMediaType, MasterPlaylist, VariantStream};
+
+use crate::segment::HlsData;
+
+/// Type of an alternate rendition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenditionKind {
+    Audio,
+    Video,
+    Subtitles,
+    ClosedCaptions,
+}
+
+/// An alternate rendition declared by `#EXT-X-MEDIA`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Rendition {
+    pub kind: RenditionKind,
+    /// `GROUP-ID` of the group the rendition belongs to
+    pub group_id: String,
+    /// `NAME`, unique within the group
+    pub name: String,
+    /// `LANGUAGE`, a language tag such as `en` or `pt-BR`
+    pub language: Option<String>,
+    /// `DEFAULT=YES`: the rendition to play without a preference
+    pub default: bool,
+    /// `AUTOSELECT=YES`: the rendition may be picked by language preference
+    pub autoselect: bool,
+    /// Media playlist of the rendition, relative to the master playlist. Without it, the
+    /// rendition is carried by the variant.
+    pub uri: Option<String>,
+    /// `CHANNELS`, e.g. `2` or `6`
+    pub channels: Option<String>,
+}
+
+impl Rendition {
+    /// The rendition declared by `media`, `None` for rendition types this crate does not
+    /// know
+    pub fn from_media(media: &AlternativeMedia) -> Option<Self> {
+        let kind = match media.media_type {
+            AlternativeMediaType::Audio => RenditionKind::Audio,
+            AlternativeMediaType::Video => RenditionKind::Video,
+            AlternativeMediaType::Subtitles => RenditionKind::Subtitles,
+            AlternativeMediaType::ClosedCaptions => RenditionKind::ClosedCaptions,
+            AlternativeMediaType::Other(_) => return None,
+        };
+        Some(Self {
+            kind,
+            group_id: media.group_id.clone(),
+            name: media.name.clone(),
+            language: media.language.clone(),
+            default: media.default,
+            autoselect: media.autoselect,
+            uri: media.uri.clone().filter(|uri| !uri.is_empty()),
+            channels: media.channels.clone(),
+        })
+    }
+
+    /// Whether the rendition is in `language`, ignoring case. A language without region,
+    /// such as `en`, also matches the regional variants of the rendition, such as `en-US`.
+    pub fn matches_language(&self, language: &str) -> bool {
+        let Some(own) = self.language.as_deref() else {
+            return false;
+        };
+        own.eq_ignore_ascii_case(language)
+            || (!language.contains('-')
+                && own
+                    .split('-')
+                    .next()
+                    .is_some_and(|primary| primary.eq_ignore_ascii_case(language)))
+    }
+}
+
+impl fmt::Display for Rendition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.language {
+            Some(language) => write!(f, "{} ({language})", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// The renditions sharing a `GROUP-ID`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenditionGroup {
+    pub kind: RenditionKind,
+    pub group_id: String,
+    /// Renditions in playlist order
+    pub renditions: Vec<Rendition>,
+}
+
+impl RenditionGroup {
+    /// The rendition to use without a preference: the `DEFAULT` one, else the first one
+    pub fn default_rendition(&self) -> Option<&Rendition> {
+        self.renditions
+            .iter()
+            .find(|rendition| rendition.default)
+            .or_else(|| self.renditions.first())
+    }
+}
+
+/// The rendition groups of `master`, in the order they are first declared
+pub fn rendition_groups(master: &MasterPlaylist) -> Vec<RenditionGroup> {
+    let mut groups: Vec<RenditionGroup> = Vec::new();
+    for rendition in master.alternatives.iter().filter_map(Rendition::from_media) {
+        match groups
+            .iter_mut()
+            .find(|group| group.kind == rendition.kind && group.group_id == rendition.group_id)
+        {
+            Some(group) => group.renditions.push(rendition),
+            None => groups.push(RenditionGroup {
+                kind: rendition.kind,
+                group_id: rendition.group_id.clone(),
+                renditions: vec![rendition],
+            }),
+        }
+    }
+    groups
+}
+
+/// The audio group `variant` refers to with its `AUDIO` attribute
+pub fn audio_group<'a>(
+    groups: &'a [RenditionGroup],
+    variant: &VariantStream,
+) -> Option<&'a RenditionGroup> {
+    let group_id = variant.audio.as_deref()?;
+    groups
+        .iter()
+        .find(|group| group.kind == RenditionKind::Audio && group.group_id == group_id)
+}
+
+/// Elementary stream a segment belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HlsTrack {
+    /// The media playlist of the selected variant
+    Main,
+    /// The media playlist of an alternate audio rendition
+    Audio(Arc<Rendition>),
+}
+
+impl HlsTrack {
+    pub fn is_main(&self) -> bool {
+        matches!(self, Self::Main)
+    }
+
+    /// Suffix telling the output of the track apart from the one of the main track, e.g.
+    /// `audio_en`, or `None` for the main track
+    pub fn file_suffix(&self) -> Option<String> {
+        match self {
+            Self::Main => None,
+            Self::Audio(rendition) => {
+                let label = rendition.language.as_deref().unwrap_or(&rendition.name);
+                let label: String = label
+                    .chars()
+                    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                    .collect();
+                Some(format!("audio_{label}"))
+            }
+        }
+    }
+}
+
+/// A segment tagged with the track it belongs to
+#[derive(Debug, Clone)]
+pub struct TrackData {
+    pub track: HlsTrack,
+    pub data: HlsData,
+}
+
+impl TrackData {
+    pub fn new(track: HlsTrack, data: HlsData) -> Self {
+        Self { track, data }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two audio groups, one per audio bitrate, each in English and Spanish
+    const MULTI_AUDIO: &str = r#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-lo",NAME="English",LANGUAGE="en",DEFAULT=YES,AUTOSELECT=YES,URI="audio/lo/en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-lo",NAME="Español",LANGUAGE="es",DEFAULT=NO,AUTOSELECT=YES,URI="audio/lo/es.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-hi",NAME="English",LANGUAGE="en-US",DEFAULT=NO,AUTOSELECT=YES,CHANNELS="6",URI="audio/hi/en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-hi",NAME="Español",LANGUAGE="es",DEFAULT=YES,AUTOSELECT=YES,CHANNELS="6",URI="audio/hi/es.m3u8"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID="subs",NAME="English",LANGUAGE="en",URI="subs/en.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=1280x720,AUDIO="aac-lo",SUBTITLES="subs"
+video/720.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,AUDIO="aac-hi",SUBTITLES="subs"
+video/1080.m3u8
+"#;
+
+    fn master() -> MasterPlaylist {
+        m3u8_rs::parse_master_playlist_res(MULTI_AUDIO.as_bytes()).expect("valid master")
+    }
+
+    #[test]
+    fn test_groups_parsed_in_order() {
+        let groups = rendition_groups(&master());
+        let ids: Vec<_> = groups
+            .iter()
+            .map(|group| (group.kind, group.group_id.as_str(), group.renditions.len()))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                (RenditionKind::Audio, "aac-lo", 2),
+                (RenditionKind::Audio, "aac-hi", 2),
+                (RenditionKind::Subtitles, "subs", 1),
+            ]
+        );
+
+        let english = &groups[1].renditions[0];
+        assert_eq!(english.name, "English");
+        assert_eq!(english.language.as_deref(), Some("en-US"));
+        assert_eq!(english.uri.as_deref(), Some("audio/hi/en.m3u8"));
+        assert_eq!(english.channels.as_deref(), Some("6"));
+        assert!(!english.default && english.autoselect);
+    }
+
+    #[test]
+    fn test_variant_audio_group() {
+        let master = master();
+        let groups = rendition_groups(&master);
+        let group = audio_group(&groups, &master.variants[1]).expect("audio group");
+        assert_eq!(group.group_id, "aac-hi");
+        assert_eq!(
+            group.default_rendition().map(|r| r.name.as_str()),
+            Some("Español")
+        );
+    }
+
+    #[test]
+    fn test_language_matching() {
+        let groups = rendition_groups(&master());
+        let english = &groups[1].renditions[0];
+        assert!(english.matches_language("en"));
+        assert!(english.matches_language("EN-us"));
+        assert!(!english.matches_language("en-GB"));
+        assert!(!english.matches_language("es"));
+    }
+
+    #[test]
+    fn test_track_file_suffix() {
+        let groups = rendition_groups(&master());
+        let track = HlsTrack::Audio(Arc::new(groups[1].renditions[0].clone()));
+        assert_eq!(track.file_suffix().as_deref(), Some("audio_en_US"));
+        assert_eq!(HlsTrack::Main.file_suffix(), None);
+    }
+}
@@ -5,6 +5,18 @@ use memchr::memchr;
 use tracing::debug;
 use ts::{PesHeader, StreamType, TsPacketRef};
 
+/// Video parameters signalled by an H.264/H.265 SPS
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoParameters {
+    pub resolution: Resolution,
+    /// `profile_idc` of H.264, `general_profile_idc` of H.265
+    pub profile: u8,
+    /// `level_idc` of H.264, `general_level_idc` of H.265
+    pub level: Option<u8>,
+    /// Frame rate from the VUI timing info, if present
+    pub frame_rate: Option<f64>,
+}
+
 /// Resolution detector for HLS segments
 ///
 /// Extracts video resolution from H.264/H.265 SPS (Sequence Parameter Set) NAL units
@@ -35,6 +47,18 @@ impl ResolutionDetector {
         packets: impl Iterator<Item = &'a TsPacketRef> + Clone,
         video_streams: &[(u16, StreamType)],
     ) -> Option<Resolution> {
+        Self::extract_parameters_from_ts_packets(packets, video_streams)
+            .map(|parameters| parameters.resolution)
+    }
+
+    /// Extract the parameters of the first SPS found in pre-parsed TS packets
+    ///
+    /// Uses the same strategies as [`Self::extract_from_ts_packets`], returning the
+    /// profile, level and frame rate besides the resolution.
+    pub fn extract_parameters_from_ts_packets<'a>(
+        packets: impl Iterator<Item = &'a TsPacketRef> + Clone,
+        video_streams: &[(u16, StreamType)],
+    ) -> Option<VideoParameters> {
         if video_streams.is_empty() {
             return None;
         }
@@ -45,27 +69,27 @@ impl ResolutionDetector {
 
             for packet in video_packets {
                 if let Some(payload) = packet.payload()
-                    && let Some(resolution) = Self::scan_payload_for_sps(&payload, *stream_type)
+                    && let Some(parameters) = Self::scan_payload_for_sps(&payload, *stream_type)
                 {
                     debug!(
-                        "Found resolution {}x{} via simple scanning for PID 0x{:04X} {:?}",
-                        resolution.width, resolution.height, pid, stream_type
+                        "Found resolution {} via simple scanning for PID 0x{:04X} {:?}",
+                        parameters.resolution, pid, stream_type
                     );
-                    return Some(resolution);
+                    return Some(parameters);
                 }
             }
 
             // Second pass: PES reassembly for fragmented SPS
             // Only collect packets if simple scanning failed
-            if let Some(resolution) = Self::try_pes_reassembly_streaming(
+            if let Some(parameters) = Self::try_pes_reassembly_streaming(
                 packets.clone().filter(|packet| packet.pid == *pid),
                 *stream_type,
             ) {
                 debug!(
-                    "Found resolution {}x{} via PES reassembly for PID 0x{:04X} {:?}",
-                    resolution.width, resolution.height, pid, stream_type
+                    "Found resolution {} via PES reassembly for PID 0x{:04X} {:?}",
+                    parameters.resolution, pid, stream_type
                 );
-                return Some(resolution);
+                return Some(parameters);
             }
         }
 
@@ -74,7 +98,7 @@ impl ResolutionDetector {
 
     /// Scan a single TS packet payload for SPS NAL units
     #[inline]
-    fn scan_payload_for_sps(payload: &[u8], stream_type: StreamType) -> Option<Resolution> {
+    fn scan_payload_for_sps(payload: &[u8], stream_type: StreamType) -> Option<VideoParameters> {
         match stream_type {
             StreamType::H264 => Self::find_and_parse_h264_sps(payload),
             StreamType::H265 => Self::find_and_parse_h265_sps(payload),
@@ -86,7 +110,7 @@ impl ResolutionDetector {
     ///
     /// Uses memchr to find potential start code positions, then validates.
     /// This is faster than searching for the full 3/4 byte pattern.
-    fn find_and_parse_h264_sps(data: &[u8]) -> Option<Resolution> {
+    fn find_and_parse_h264_sps(data: &[u8]) -> Option<VideoParameters> {
         let mut pos = 0;
 
         while pos + 4 < data.len() {
@@ -131,7 +155,12 @@ impl ResolutionDetector {
                 if let Ok(sps) =
                     h264::Sps::parse_with_emulation_prevention(std::io::Cursor::new(sps_data))
                 {
-                    return Some(Resolution::new(sps.width() as u32, sps.height() as u32));
+                    return Some(VideoParameters {
+                        resolution: Resolution::new(sps.width() as u32, sps.height() as u32),
+                        profile: sps.profile_idc,
+                        level: Some(sps.level_idc),
+                        frame_rate: sps.frame_rate(),
+                    });
                 }
             }
 
@@ -142,7 +171,7 @@ impl ResolutionDetector {
     }
 
     /// Find and parse H.265 SPS using fast byte scanning
-    fn find_and_parse_h265_sps(data: &[u8]) -> Option<Resolution> {
+    fn find_and_parse_h265_sps(data: &[u8]) -> Option<VideoParameters> {
         let mut pos = 0;
 
         while pos + 5 < data.len() {
@@ -185,10 +214,24 @@ impl ResolutionDetector {
 
                 let sps_data = &data[nal_start..nal_end];
                 if let Ok(sps) = h265::SpsNALUnit::parse(std::io::Cursor::new(sps_data)) {
-                    return Some(Resolution::new(
-                        sps.rbsp.pic_width_in_luma_samples.get() as u32,
-                        sps.rbsp.pic_height_in_luma_samples.get() as u32,
-                    ));
+                    let profile = &sps.rbsp.profile_tier_level.general_profile;
+                    let frame_rate = sps
+                        .rbsp
+                        .vui_parameters
+                        .as_ref()
+                        .and_then(|vui| vui.vui_timing_info.as_ref())
+                        .map(|timing| {
+                            timing.time_scale.get() as f64 / timing.num_units_in_tick.get() as f64
+                        });
+                    return Some(VideoParameters {
+                        resolution: Resolution::new(
+                            sps.rbsp.pic_width_in_luma_samples.get() as u32,
+                            sps.rbsp.pic_height_in_luma_samples.get() as u32,
+                        ),
+                        profile: profile.profile_idc,
+                        level: profile.level_idc,
+                        frame_rate,
+                    });
                 }
             }
 
@@ -232,7 +275,7 @@ impl ResolutionDetector {
     fn try_pes_reassembly_streaming<'a>(
         packets: impl Iterator<Item = &'a TsPacketRef>,
         stream_type: StreamType,
-    ) -> Option<Resolution> {
+    ) -> Option<VideoParameters> {
         // Pre-allocate with typical PES packet size (reduces reallocations)
         let mut current_pes = BytesMut::with_capacity(4096);
         let mut in_pes_packet = false;
@@ -243,9 +286,9 @@ impl ResolutionDetector {
                     // New PES packet starting - try to parse the previous one
                     if in_pes_packet
                         && current_pes.len() >= 9
-                        && let Some(resolution) = Self::try_parse_pes(&current_pes, stream_type)
+                        && let Some(parameters) = Self::try_parse_pes(&current_pes, stream_type)
                     {
-                        return Some(resolution);
+                        return Some(parameters);
                     }
 
                     in_pes_packet = true;
@@ -267,7 +310,7 @@ impl ResolutionDetector {
 
     /// Try to parse SPS from a PES packet
     #[inline]
-    fn try_parse_pes(pes_data: &[u8], stream_type: StreamType) -> Option<Resolution> {
+    fn try_parse_pes(pes_data: &[u8], stream_type: StreamType) -> Option<VideoParameters> {
         Self::extract_elementary_stream_from_pes(pes_data)
             .and_then(|es| Self::scan_payload_for_sps(es, stream_type))
     }
@@ -1,5 +1,6 @@
 use bytes::Bytes;
-use m3u8_rs::MediaSegment;
+use m3u8_rs::{ByteRange, Map, MediaSegment};
+use pipeline_common::MemSized;
 use pipeline_common::split_reason::SplitReason;
 use ts::StreamType;
 
@@ -17,6 +18,12 @@ pub enum HlsData {
     EndMarker(Option<SplitReason>),
 }
 
+impl MemSized for HlsData {
+    fn mem_size(&self) -> usize {
+        self.size()
+    }
+}
+
 impl HlsData {
     /// Create a new TS segment
     #[inline]
@@ -94,6 +101,23 @@ impl HlsData {
         }
     }
 
+    /// Get the byte range of the segment within its resource, if the playlist declared one
+    #[inline]
+    pub fn byte_range(&self) -> Option<&ByteRange> {
+        self.media_segment()?.byte_range.as_ref()
+    }
+
+    /// Get the init segment (EXT-X-MAP) this segment depends on, with its URI resolved.
+    ///
+    /// Only set for fMP4 media segments produced by the playlist engine.
+    #[inline]
+    pub fn init_segment_map(&self) -> Option<&Map> {
+        match self {
+            HlsData::M4sData(M4sData::Segment(seg)) => seg.segment.map.as_ref(),
+            _ => None,
+        }
+    }
+
     /// Check if this is a TS segment
     #[inline]
     pub fn is_ts(&self) -> bool {
@@ -141,7 +165,8 @@ impl HlsData {
     pub fn has_keyframe(&self) -> bool {
         match self {
             HlsData::TsData(ts) => {
-                let mut parser = ts::TsParser::new();
+                // Keyframe detection only looks at adaptation fields
+                let mut parser = ts::TsParser::new().with_crc_validation(false);
                 let mut found_keyframe = false;
 
                 let parse_result = parser.parse_packets(
@@ -631,4 +656,36 @@ mod tests {
         let ts_204_data = HlsData::ts(make_media_segment(), Bytes::from(ts_204));
         assert!(!ts_204_data.has_keyframe());
     }
+
+    #[test]
+    fn test_byte_range_and_init_map() {
+        let map = Map {
+            uri: "https://example.com/init.mp4".to_string(),
+            byte_range: Some(ByteRange {
+                length: 720,
+                offset: Some(0),
+            }),
+            other_attributes: Default::default(),
+        };
+        let segment = MediaSegment {
+            uri: "https://example.com/main.mp4".to_string(),
+            byte_range: Some(ByteRange {
+                length: 5000,
+                offset: Some(720),
+            }),
+            discontinuity: true,
+            map: Some(map.clone()),
+            ..make_media_segment()
+        };
+
+        let media = HlsData::mp4_segment(segment.clone(), Bytes::from_static(b"moof"));
+        assert_eq!(media.byte_range().unwrap().offset, Some(720));
+        assert_eq!(media.init_segment_map(), Some(&map));
+        assert!(media.is_discontinuity());
+
+        let init = HlsData::mp4_init(segment, Bytes::from_static(b"moov"));
+        assert!(init.init_segment_map().is_none());
+
+        assert!(HlsData::end_marker().byte_range().is_none());
+    }
 }
@@ -2,10 +2,9 @@ use bytes::Bytes;
 use m3u8_rs::MediaSegment;
 use std::cell::{Cell, RefCell};
 use tracing::warn;
-use ts::descriptor::{
-    TAG_ISO_639_LANGUAGE, TAG_REGISTRATION, parse_iso639_language, parse_registration_descriptor,
+use ts::{
+    Descriptor, PatRef, PesHeader, PmtRef, SpliceInfoSection, StreamType, TsPacketRef, TsParser,
 };
-use ts::{PatRef, PesHeader, PmtRef, SpliceInfoSection, StreamType, TsPacketRef, TsParser};
 
 use crate::profile::SegmentType;
 
@@ -71,12 +70,9 @@ impl TsSegmentData {
     }
 
     fn make_parser(&self) -> TsParser {
-        let mut parser = TsParser::new();
-        if self.validate_crc {
-            parser = parser.with_crc_validation(true);
-        }
-        parser = parser.with_continuity_mode(self.continuity_mode);
-        parser
+        TsParser::new()
+            .with_crc_validation(self.validate_crc)
+            .with_continuity_mode(self.continuity_mode)
     }
 
     fn report_continuity_warnings(&self, parser: &TsParser) {
@@ -98,20 +94,22 @@ impl TsSegmentData {
         for stream in pmt.streams().flatten() {
             let mut language = None;
             let mut is_scte35 = false;
-
-            for desc in stream.descriptors() {
-                if desc.tag == TAG_ISO_639_LANGUAGE {
-                    let entries = parse_iso639_language(&desc.data);
-                    if let Some(entry) = entries.first() {
-                        language = Some(String::from_utf8_lossy(&entry.language_code).into_owned());
+            let descriptors: Vec<Descriptor> = stream.descriptors().flatten().collect();
+
+            for desc in &descriptors {
+                match desc {
+                    Descriptor::Iso639Language(entries) => {
+                        if let Some(entry) = entries.first() {
+                            language =
+                                Some(String::from_utf8_lossy(&entry.language_code).into_owned());
+                        }
                     }
-                }
-
-                if desc.tag == TAG_REGISTRATION
-                    && let Some(id) = parse_registration_descriptor(&desc.data)
-                    && &id == b"CUEI"
-                {
-                    is_scte35 = true;
+                    Descriptor::Registration {
+                        format_identifier, ..
+                    } if format_identifier == b"CUEI" => {
+                        is_scte35 = true;
+                    }
+                    _ => {}
                 }
             }
 
@@ -119,16 +117,17 @@ impl TsSegmentData {
                 program_info.scte35_pids.push(stream.elementary_pid);
             }
 
+            let stream_type = stream.stream_type.classify_with_descriptors(&descriptors);
             let stream_entry = StreamEntry {
                 pid: stream.elementary_pid,
-                stream_type: stream.stream_type,
+                stream_type,
                 language,
                 first_pts: None,
             };
 
-            if stream.stream_type.is_video() {
+            if stream_type.is_video() {
                 program_info.video_streams.push(stream_entry);
-            } else if stream.stream_type.is_audio() {
+            } else if stream_type.is_audio() {
                 program_info.audio_streams.push(stream_entry);
             } else {
                 program_info.other_streams.push(stream_entry);
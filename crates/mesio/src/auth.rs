@@ -0,0 +1,596 @@
+//! # Authentication Refresh
+//!
+//! Live streams often authenticate their requests with a token in the URL, a header or a
+//! cookie that expires while the download runs. Downloads keep cookies set by the origin in a
+//! cookie store, and an [`AuthRefresh`] hook lets the caller obtain new credentials when the
+//! origin rejects a request with `401` or `403`, or when their configured lifetime elapses.
+//!
+//! The [`AuthUpdate`] returned by the hook applies to every following request of the
+//! downloader: HLS playlists and segments, and the FLV stream when it reconnects. Concurrent
+//! rejections, like several segments failing at once, run the hook a single time.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use reqwest::RequestBuilder;
+use reqwest::StatusCode;
+use reqwest::cookie::Jar;
+use reqwest::header::{COOKIE, HeaderMap, HeaderValue};
+use tokio::time::Instant;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{DownloadError, DownloaderConfig};
+
+/// Error returned by an [`AuthRefresh`] hook
+pub type AuthRefreshError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A cookie sent with the requests of a download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Domain the cookie is sent to, subdomains included. Without one the cookie is sent to
+    /// the host of the first request only.
+    pub domain: Option<String>,
+    /// Path the cookie is limited to (None = every path)
+    pub path: Option<String>,
+}
+
+impl Cookie {
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            domain: None,
+            path: None,
+        }
+    }
+
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Cookies of a `Cookie` request header, e.g. `a=1; b=2`
+    pub fn parse_header(header: &str) -> Vec<Self> {
+        header
+            .split(';')
+            .filter_map(|pair| {
+                let (name, value) = pair.trim().split_once('=')?;
+                (!name.is_empty()).then(|| Self::new(name, value))
+            })
+            .collect()
+    }
+
+    /// Add the cookie to `jar`, scoped to the host of `url` when it has no domain
+    fn store(&self, jar: &Jar, url: &Url) {
+        let mut set_cookie = format!("{}={}", self.name, self.value);
+        let mut url = url.clone();
+        if let Some(domain) = &self.domain {
+            set_cookie.push_str(&format!("; Domain={domain}"));
+            // The jar rejects cookies for domains the URL is not part of
+            if url.set_host(Some(domain.trim_start_matches('.'))).is_err() {
+                warn!(cookie = %self.name, %domain, "Ignoring cookie with an invalid domain");
+                return;
+            }
+        }
+        set_cookie.push_str(&format!("; Path={}", self.path.as_deref().unwrap_or("/")));
+        jar.add_cookie_str(&set_cookie, &url);
+    }
+}
+
+/// Why credentials are refreshed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshReason {
+    /// The origin rejected a request with this status
+    Rejected(StatusCode),
+    /// The configured lifetime of the credentials elapsed
+    Expired,
+    /// A live stream reconnects after its connection dropped
+    Reconnect,
+}
+
+/// Request whose credentials an [`AuthRefresh`] hook renews
+#[derive(Debug, Clone)]
+pub struct RefreshContext {
+    /// URL the download currently requests, with earlier updates applied
+    pub url: Url,
+    pub reason: RefreshReason,
+    /// Refreshes that already succeeded during this download
+    pub refreshes: u32,
+}
+
+/// New credentials, applied to the requests following a refresh
+#[derive(Debug, Clone, Default)]
+pub struct AuthUpdate {
+    /// Replacement of the refreshed URL, e.g. with a new token in its query
+    pub url: Option<Url>,
+    /// Headers sent with every request, replacing configured headers of the same name
+    pub headers: HeaderMap,
+    /// Cookies added to the cookie store
+    pub cookies: Vec<Cookie>,
+}
+
+impl AuthUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_url(mut self, url: Url) -> Self {
+        self.url = Some(url);
+        self
+    }
+
+    pub fn with_header(mut self, name: reqwest::header::HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn with_cookie(mut self, cookie: Cookie) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+}
+
+type RefreshFn = dyn Fn(&RefreshContext) -> BoxFuture<'static, Result<AuthUpdate, AuthRefreshError>>
+    + Send
+    + Sync;
+
+/// Callback renewing the credentials of a download
+#[derive(Clone)]
+pub struct AuthRefresh {
+    callback: Arc<RefreshFn>,
+    /// Lifetime of the credentials, after which they are refreshed before the next request
+    ttl: Option<Duration>,
+}
+
+impl AuthRefresh {
+    pub fn new<F, Fut>(callback: F) -> Self
+    where
+        F: Fn(&RefreshContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<AuthUpdate, AuthRefreshError>> + Send + 'static,
+    {
+        Self {
+            callback: Arc::new(move |context: &RefreshContext| callback(context).boxed()),
+            ttl: None,
+        }
+    }
+
+    /// Refresh the credentials every `ttl`, without waiting for a rejected request
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+}
+
+impl fmt::Debug for AuthRefresh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthRefresh")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Credentials of the requests of a downloader, shared by its clients
+#[derive(Debug)]
+pub(crate) struct AuthSession {
+    refresh: Option<AuthRefresh>,
+    jar: Option<Arc<Jar>>,
+    state: Mutex<AuthState>,
+    /// Held while the hook runs, so concurrent rejections refresh once
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug)]
+struct AuthState {
+    /// URLs replaced by a refresh
+    urls: HashMap<Url, Url>,
+    /// Headers set by the refreshes
+    headers: HeaderMap,
+    /// Configured cookies, added to the jar on the first request
+    pending_cookies: Vec<Cookie>,
+    refreshes: u32,
+    /// When the current credentials were configured or refreshed
+    issued_at: Instant,
+}
+
+impl AuthSession {
+    pub(crate) fn new(config: &DownloaderConfig) -> Self {
+        let mut pending_cookies = config.cookies.clone();
+        let jar = config.uses_cookie_store().then(|| {
+            // The jar only fills in cookies for requests without a `Cookie` header
+            if let Some(header) = config.headers.get(COOKIE)
+                && let Ok(header) = header.to_str()
+            {
+                pending_cookies.extend(Cookie::parse_header(header));
+            }
+            Arc::new(Jar::default())
+        });
+        Self {
+            refresh: config.auth_refresh.clone(),
+            jar,
+            state: Mutex::new(AuthState {
+                urls: HashMap::new(),
+                headers: HeaderMap::new(),
+                pending_cookies,
+                refreshes: 0,
+                issued_at: Instant::now(),
+            }),
+            refreshing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Cookie store of the clients, when enabled
+    pub(crate) fn jar(&self) -> Option<Arc<Jar>> {
+        self.jar.clone()
+    }
+
+    /// URL to request in place of `url`
+    pub(crate) fn url(&self, url: &Url) -> Url {
+        self.state
+            .lock()
+            .urls
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| url.clone())
+    }
+
+    /// Add the refreshed credentials to a request of `url`
+    pub(crate) fn apply(&self, url: &Url, request: RequestBuilder) -> RequestBuilder {
+        let mut state = self.state.lock();
+        if let Some(jar) = &self.jar {
+            for cookie in state.pending_cookies.drain(..) {
+                cookie.store(jar, url);
+            }
+        }
+        if state.headers.is_empty() {
+            request
+        } else {
+            request.headers(state.headers.clone())
+        }
+    }
+
+    /// Number of refreshes so far, to pass to [`Self::refresh`]
+    pub(crate) fn generation(&self) -> u32 {
+        self.state.lock().refreshes
+    }
+
+    /// Refresh reason of a failed request, when a hook is configured and the origin rejected
+    /// the credentials
+    pub(crate) fn rejection(&self, error: &DownloadError) -> Option<RefreshReason> {
+        self.refresh.as_ref()?;
+        let status = error.status()?;
+        matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+            .then_some(RefreshReason::Rejected(status))
+    }
+
+    /// Refresh the credentials when their lifetime elapsed
+    pub(crate) async fn refresh_if_expired(&self, url: &Url) -> Result<(), DownloadError> {
+        let Some(ttl) = self.refresh.as_ref().and_then(AuthRefresh::ttl) else {
+            return Ok(());
+        };
+        let (expired, generation) = {
+            let state = self.state.lock();
+            (state.issued_at.elapsed() >= ttl, state.refreshes)
+        };
+        if expired {
+            self.refresh(url, RefreshReason::Expired, generation)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Run the hook for a request of `url` and apply its update.
+    ///
+    /// `generation` is the value of [`Self::generation`] when the request was sent; when
+    /// another request refreshed the credentials since, the hook is not run again.
+    pub(crate) async fn refresh(
+        &self,
+        url: &Url,
+        reason: RefreshReason,
+        generation: u32,
+    ) -> Result<(), DownloadError> {
+        let Some(hook) = &self.refresh else {
+            return Ok(());
+        };
+        let _refreshing = self.refreshing.lock().await;
+        if self.generation() != generation {
+            return Ok(());
+        }
+
+        let context = RefreshContext {
+            url: self.url(url),
+            reason,
+            refreshes: generation,
+        };
+        info!(url = %context.url, ?reason, "Refreshing download credentials");
+        let update = (hook.callback)(&context)
+            .await
+            .map_err(|e| DownloadError::auth_refresh(context.url.as_str(), e.to_string()))?;
+
+        let mut state = self.state.lock();
+        let target = update.url.unwrap_or(context.url);
+        for cookie in &update.cookies {
+            match &self.jar {
+                Some(jar) => cookie.store(jar, &target),
+                None => warn!(cookie = %cookie.name, "Cookie store disabled, ignoring cookie"),
+            }
+        }
+        state.headers.extend(update.headers);
+        if target != *url {
+            state.urls.insert(url.clone(), target);
+        }
+        state.refreshes += 1;
+        state.issued_at = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Download;
+    use crate::DownloaderConfigBuilder;
+    use crate::flv::{FlvDownloader, FlvProtocolConfig};
+    use crate::hls::{HlsConfig, HlsDownloader};
+    use futures::StreamExt;
+    use hls::HlsData;
+    use reqwest::header::HeaderName;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_util::sync::CancellationToken;
+
+    fn url(input: &str) -> Url {
+        Url::parse(input).unwrap()
+    }
+
+    /// Session whose hook counts its runs and returns `update`
+    fn session(update: AuthUpdate, ttl: Option<Duration>) -> (Arc<AuthSession>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let mut refresh = AuthRefresh::new(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let update = update.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(update)
+            }
+        });
+        if let Some(ttl) = ttl {
+            refresh = refresh.with_ttl(ttl);
+        }
+        let config = DownloaderConfigBuilder::new()
+            .with_auth_refresh(refresh)
+            .build();
+        (Arc::new(AuthSession::new(&config)), calls)
+    }
+
+    #[test]
+    fn test_parse_cookie_header() {
+        assert_eq!(
+            Cookie::parse_header("token=abc; uid=1;bad; =x"),
+            vec![Cookie::new("token", "abc"), Cookie::new("uid", "1")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_rejections_refresh_once() {
+        let update = AuthUpdate::new()
+            .with_url(url("http://localhost/live.m3u8?token=new"))
+            .with_header(
+                HeaderName::from_static("authorization"),
+                HeaderValue::from_static("Bearer new"),
+            );
+        let (session, calls) = session(update, None);
+        let original = url("http://localhost/live.m3u8?token=old");
+        let generation = session.generation();
+
+        let refreshes = (0..4).map(|_| {
+            session.refresh(
+                &original,
+                RefreshReason::Rejected(StatusCode::FORBIDDEN),
+                generation,
+            )
+        });
+        for result in futures::future::join_all(refreshes).await {
+            result.unwrap();
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(session.generation(), 1);
+        assert_eq!(session.url(&original).query(), Some("token=new"));
+        assert_eq!(session.state.lock().headers["authorization"], "Bearer new");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refreshes_expired_credentials() {
+        let (session, calls) = session(AuthUpdate::new(), Some(Duration::from_secs(60)));
+        let source = url("http://localhost/live.flv");
+
+        session.refresh_if_expired(&source).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        session.refresh_if_expired(&source).await.unwrap();
+        session.refresh_if_expired(&source).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_reported() {
+        let config = DownloaderConfigBuilder::new()
+            .with_auth_refresh(AuthRefresh::new(|_| async {
+                Err::<AuthUpdate, AuthRefreshError>("login expired".into())
+            }))
+            .build();
+        let session = AuthSession::new(&config);
+        let source = url("http://localhost/live.flv");
+
+        let error = session
+            .refresh(&source, RefreshReason::Expired, 0)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, DownloadError::AuthRefresh { url, reason }
+                if url == source.as_str() && reason == "login expired"),
+            "{error:?}"
+        );
+        assert_eq!(session.generation(), 0);
+    }
+
+    /// Server accepting the cookie `token=old` for its first `old_requests` requests and
+    /// `token=new` after that, or the same tokens in the query. Serves a VOD playlist of
+    /// `segments` TS segments at `/live.m3u8` and an FLV header at `/live.flv`.
+    async fn spawn_token_server(
+        old_requests: usize,
+        segments: usize,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("local addr"));
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    loop {
+                        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            match socket.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                            }
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&buf[..end]).to_ascii_lowercase();
+                        buf.drain(..end + 4);
+
+                        let served = counter.fetch_add(1, Ordering::SeqCst);
+                        let token = if served < old_requests { "old" } else { "new" };
+                        let target = head.split(' ').nth(1).unwrap_or_default().to_string();
+                        let authorized = head.contains(&format!("token={token}"));
+
+                        let (status, body): (&str, Vec<u8>) = if !authorized {
+                            ("403 Forbidden", b"token expired".to_vec())
+                        } else if target.starts_with("/live.m3u8") {
+                            let mut playlist = String::from(
+                                "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:1\n\
+                                 #EXT-X-MEDIA-SEQUENCE:0\n",
+                            );
+                            for i in 0..segments {
+                                playlist.push_str(&format!("#EXTINF:1.0,\nsegment{i}.ts\n"));
+                            }
+                            playlist.push_str("#EXT-X-ENDLIST\n");
+                            ("200 OK", playlist.into_bytes())
+                        } else if target.starts_with("/live.flv") {
+                            let mut flv = b"FLV\x01\x01\x00\x00\x00\x09".to_vec();
+                            flv.extend_from_slice(&[0; 4]);
+                            ("200 OK", flv)
+                        } else {
+                            let mut packet = vec![0xFF; 188];
+                            packet[..4].copy_from_slice(&[0x47, 0x1F, 0xFF, 0x10]);
+                            ("200 OK", packet)
+                        };
+                        let response = format!(
+                            "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n",
+                            body.len()
+                        );
+                        if socket.write_all(response.as_bytes()).await.is_err()
+                            || socket.write_all(&body).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (base, requests)
+    }
+
+    fn counting_refresh(update: AuthUpdate) -> (AuthRefresh, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let refresh = AuthRefresh::new(move |context: &RefreshContext| {
+            assert!(matches!(
+                context.reason,
+                RefreshReason::Rejected(StatusCode::FORBIDDEN)
+            ));
+            counter.fetch_add(1, Ordering::SeqCst);
+            let update = update.clone();
+            async move { Ok(update) }
+        });
+        (refresh, calls)
+    }
+
+    #[tokio::test]
+    async fn test_hls_refreshes_rejected_cookie() {
+        let (base, _) = spawn_token_server(20, 60).await;
+        let (refresh, calls) =
+            counting_refresh(AuthUpdate::new().with_cookie(Cookie::new("token", "new")));
+        let mut config = HlsConfig::default();
+        config.base.cookies.push(Cookie::new("token", "old"));
+        config.base.auth_refresh = Some(refresh);
+        let downloader = HlsDownloader::with_config(config).unwrap();
+
+        let items: Vec<_> = downloader
+            .download(&format!("{base}/live.m3u8"), CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        let segments = items
+            .iter()
+            .filter(|item| matches!(item, Ok(HlsData::TsData(_))))
+            .count();
+        assert_eq!(segments, 60);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flv_reconnects_with_refreshed_url() {
+        let (base, requests) = spawn_token_server(0, 0).await;
+        let new_url = url(&format!("{base}/live.flv?token=new"));
+        let (refresh, calls) = counting_refresh(AuthUpdate::new().with_url(new_url));
+        let mut config = FlvProtocolConfig::default();
+        config.base.auth_refresh = Some(refresh);
+        let downloader = FlvDownloader::with_config(config).unwrap();
+
+        let items: Vec<_> = downloader
+            .download(
+                &format!("{base}/live.flv?token=old"),
+                CancellationToken::new(),
+            )
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(!items.is_empty());
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
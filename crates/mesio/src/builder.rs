@@ -29,15 +29,28 @@
 //!             username: "user".to_string(),
 //!             password: "pass".to_string(),
 //!         }),
+//!         remote_dns: false,
+//!         no_proxy: vec!["cdn.example.com".to_string()],
 //!     })
 //!     .build();
 //! ```
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use reqwest::Client;
 use reqwest::header::{HeaderMap, HeaderValue};
 
-use crate::{CacheConfig, DownloaderConfig, proxy::ProxyConfig};
+use crate::{
+    CacheConfig, DownloaderConfig, ProtocolType,
+    auth::{AuthRefresh, Cookie},
+    downloader::ClientProvider,
+    proxy::{ProxyConfig, ProxyOverride},
+    retry::RetryPolicy,
+    tee::RawTee,
+    throttle::OnProgress,
+    watchdog::StallConfig,
+};
 
 /// Builder for creating DownloaderConfig instances with a fluent API
 #[derive(Debug, Clone)]
@@ -141,6 +154,13 @@ impl DownloaderConfigBuilder {
         self
     }
 
+    /// Override the proxy settings of the media requests of `protocol`, e.g. to download
+    /// HLS segments directly while the playlist goes through the proxy
+    pub fn with_proxy_override(mut self, protocol: ProtocolType, proxy: ProxyOverride) -> Self {
+        self.config.proxy_overrides.insert(protocol, proxy);
+        self
+    }
+
     /// Set whether to use system proxy settings if available
     pub fn with_system_proxy(mut self, use_system_proxy: bool) -> Self {
         // Only set system proxy if no explicit proxy is configured
@@ -189,6 +209,12 @@ impl DownloaderConfigBuilder {
         self
     }
 
+    /// Speak HTTP/2 without negotiating it, for origins known to support it
+    pub fn with_http2_prior_knowledge(mut self) -> Self {
+        self.config.http_version = crate::config::HttpVersionPreference::Http2PriorKnowledge;
+        self
+    }
+
     /// Set the TCP keep-alive interval for maintaining long-lived HTTP/2 connections
     ///
     /// This helps keep HTTP/2 connections alive for multiplexing benefits.
@@ -207,7 +233,95 @@ impl DownloaderConfigBuilder {
         self
     }
 
+    // --- Connection Pool Configuration Methods ---
+
+    /// Set the maximum idle connections kept per host
+    pub fn with_pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.config.pool_max_idle_per_host = max_idle;
+        self
+    }
+
+    /// Set how long idle connections are kept before closing
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.config.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Open `count` connections to the segment host when an HLS playlist is first parsed
+    pub fn with_prewarm_connections(mut self, count: usize) -> Self {
+        self.config.prewarm_connections = count;
+        self
+    }
+
+    // --- Bandwidth Configuration Methods ---
+
+    /// Limit the throughput of each download, in bytes per second
+    pub fn with_max_bytes_per_sec(mut self, max_bytes_per_sec: u64) -> Self {
+        self.config.max_bytes_per_sec = Some(max_bytes_per_sec);
+        self
+    }
+
+    /// Set the callback receiving download throughput, reported every `interval`
+    pub fn with_on_progress(mut self, on_progress: OnProgress, interval: Duration) -> Self {
+        self.config.on_progress = Some(on_progress);
+        self.config.progress_interval = interval;
+        self
+    }
+
+    /// Set how failed requests are retried
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.config.retry_policy = retry_policy;
+        self
+    }
+
     /// Build the DownloaderConfig instance
+    /// Set the detection of streams that stay connected without media progress
+    pub fn with_stall_config(mut self, stall_config: StallConfig) -> Self {
+        self.config.stall_config = Some(stall_config);
+        self
+    }
+
+    // --- Authentication Methods ---
+
+    /// Keep the cookies set by the origin and send them with the following requests
+    pub fn with_cookie_store(mut self, enabled: bool) -> Self {
+        self.config.cookie_store = enabled;
+        self
+    }
+
+    /// Add a cookie sent with every request, enabling the cookie store
+    pub fn with_cookie(mut self, cookie: Cookie) -> Self {
+        self.config.cookies.push(cookie);
+        self
+    }
+
+    /// Set the hook renewing expired or rejected credentials
+    pub fn with_auth_refresh(mut self, auth_refresh: AuthRefresh) -> Self {
+        self.config.auth_refresh = Some(auth_refresh);
+        self
+    }
+
+    // --- Raw Copy Methods ---
+
+    /// Copy the bytes received from the origin to `tee`, before any protocol parsing
+    pub fn with_raw_tee(mut self, tee: RawTee) -> Self {
+        self.config.raw_tee = Some(tee);
+        self
+    }
+
+    // --- HTTP Client Methods ---
+
+    /// Send the requests with `client` in place of one built from this configuration
+    pub fn with_client(self, client: Client) -> Self {
+        self.with_client_provider(client)
+    }
+
+    /// Send the requests of each content source with the client `provider` returns for it
+    pub fn with_client_provider(mut self, provider: impl ClientProvider + 'static) -> Self {
+        self.config.client_provider = Some(Arc::new(provider));
+        self
+    }
+
     pub fn build(self) -> DownloaderConfig {
         self.config
     }
@@ -282,6 +396,8 @@ mod tests {
                 username: "user".to_string(),
                 password: "pass".to_string(),
             }),
+            remote_dns: false,
+            no_proxy: Vec::new(),
         };
 
         // Test with explicit proxy
@@ -299,6 +415,32 @@ mod tests {
         assert_eq!(stored_proxy.proxy_type, proxy_config.proxy_type);
     }
 
+    #[test]
+    fn test_proxy_override() {
+        let proxy_config = ProxyConfig {
+            url: "proxy.example.com:1080".to_string(),
+            proxy_type: crate::ProxyType::Socks5,
+            auth: None,
+            remote_dns: true,
+            no_proxy: Vec::new(),
+        };
+        let config = DownloaderConfigBuilder::new()
+            .with_proxy(proxy_config)
+            .with_proxy_override(ProtocolType::Hls, ProxyOverride::Direct)
+            .with_proxy_override(ProtocolType::Dash, ProxyOverride::System)
+            .build();
+
+        assert!(config.media_config(ProtocolType::Flv).is_none());
+
+        let hls = config.media_config(ProtocolType::Hls).unwrap();
+        assert!(hls.proxy.is_none());
+        assert!(!hls.use_system_proxy);
+
+        let dash = config.media_config(ProtocolType::Dash).unwrap();
+        assert!(dash.proxy.is_none());
+        assert!(dash.use_system_proxy);
+    }
+
     #[test]
     fn test_http2_configuration() {
         use crate::config::HttpVersionPreference;
@@ -327,6 +469,15 @@ mod tests {
         let http1_config = DownloaderConfigBuilder::new().with_http1_only().build();
         assert_eq!(http1_config.http_version, HttpVersionPreference::Http1Only);
 
+        // Test HTTP/2 prior knowledge
+        let h2c_config = DownloaderConfigBuilder::new()
+            .with_http2_prior_knowledge()
+            .build();
+        assert_eq!(
+            h2c_config.http_version,
+            HttpVersionPreference::Http2PriorKnowledge
+        );
+
         // Test HLS optimized preset
         let hls_config = DownloaderConfigBuilder::new()
             .with_http2_hls_optimized()
@@ -336,4 +487,21 @@ mod tests {
             Some(Duration::from_secs(20))
         );
     }
+
+    #[test]
+    fn test_connection_pool_configuration() {
+        let config = DownloaderConfigBuilder::new()
+            .with_pool_max_idle_per_host(32)
+            .with_pool_idle_timeout(Duration::from_secs(90))
+            .with_prewarm_connections(4)
+            .build();
+
+        assert_eq!(config.pool_max_idle_per_host, 32);
+        assert_eq!(config.pool_idle_timeout, Duration::from_secs(90));
+        assert_eq!(config.prewarm_connections, 4);
+        assert_eq!(
+            DownloaderConfigBuilder::new().build().prewarm_connections,
+            0
+        );
+    }
 }
@@ -1,53 +1,76 @@
 //! # Cache Manager
 //!
 //! This module provides the main cache manager that coordinates between memory and file caches.
+//! Which of them are used is selected by [`CacheBackend`].
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use bytes::Bytes;
+use reqwest::header::{IF_MODIFIED_SINCE, IF_NONE_MATCH};
+use reqwest::{RequestBuilder, StatusCode};
 use tokio::io;
+use tracing::{debug, warn};
 
+use crate::DownloadError;
 use crate::cache::providers::file::FileCache;
 use crate::cache::providers::memory::MemoryCache;
 use crate::cache::providers::provider::CacheProvider;
 use crate::cache::types::{
-    CacheConfig, CacheKey, CacheLookupResult, CacheMetadata, CacheResourceType, CacheResult,
+    CacheBackend, CacheConfig, CacheKey, CacheLookupResult, CacheMetadata, CacheResourceType,
+    CacheResult, CacheStatus,
 };
+use crate::cache::utils::extract_cache_headers;
 
 /// Cache manager handling both memory and file caching
 
 #[derive(Clone)]
 pub struct CacheManager {
-    memory_cache: Arc<MemoryCache>,
-    file_cache: Arc<FileCache>,
+    memory_cache: Option<Arc<MemoryCache>>,
+    file_cache: Option<Arc<FileCache>>,
     config: Arc<CacheConfig>,
 }
 
 impl CacheManager {
     /// Create a new cache manager with the specified configuration
     pub async fn new(mut config: CacheConfig) -> io::Result<Self> {
-        // If no disk cache path provided, use system temp
-        if config.disk_cache_path.is_none() {
-            let temp_dir = std::env::temp_dir();
-            config.disk_cache_path = Some(temp_dir.join("mesio-cache"));
-        }
-
-        let cache_dir = config.disk_cache_path.as_ref().unwrap().clone();
-        let max_disk_size = config.max_disk_cache_size;
+        let (use_memory, disk) = match &config.backend {
+            CacheBackend::Memory => (true, None),
+            CacheBackend::Disk { path, max_bytes } => {
+                (false, Some((path.clone(), config.enabled, *max_bytes)))
+            }
+            CacheBackend::Tiered => {
+                // If no disk cache path provided, use system temp
+                let cache_dir = config
+                    .disk_cache_path
+                    .get_or_insert_with(|| std::env::temp_dir().join("mesio-cache"))
+                    .clone();
+                let max_disk_size = config.max_disk_cache_size;
+                (
+                    true,
+                    Some((
+                        cache_dir,
+                        max_disk_size > 0 && config.enabled,
+                        max_disk_size,
+                    )),
+                )
+            }
+        };
         let config = Arc::new(config);
 
         // Create memory cache with configured size
-        let memory_cache = Arc::new(MemoryCache::new(config.max_memory_cache_size, 0));
+        let memory_cache =
+            use_memory.then(|| Arc::new(MemoryCache::new(config.max_memory_cache_size, 0)));
 
         // Create file cache with configured directory and size limit
-        let file_cache = Arc::new(FileCache::new(
-            cache_dir,
-            max_disk_size > 0 && config.enabled,
-            max_disk_size,
-        ));
+        let file_cache = disk.map(|(cache_dir, enabled, max_size)| {
+            Arc::new(FileCache::new(cache_dir, enabled, max_size))
+        });
 
         // Initialize the cache directories in advance
-        if config.enabled {
+        if config.enabled
+            && let Some(file_cache) = &file_cache
+        {
             file_cache.ensure_initialized().await?;
         }
 
@@ -76,17 +99,22 @@ impl CacheManager {
         }
 
         // Check memory cache first
-        if let Some((data, metadata, status)) = self.memory_cache.get(key).await? {
+        if let Some(memory_cache) = &self.memory_cache
+            && let Some((data, metadata, status)) = memory_cache.get(key).await?
+        {
             return Ok(Some((data, metadata, status)));
         }
 
         // Try file cache if memory cache misses
-        if let Some((data, metadata, status)) = self.file_cache.get(key).await? {
+        if let Some(file_cache) = &self.file_cache
+            && let Some((data, metadata, status)) = file_cache.get(key).await?
+        {
             // Store in memory cache for faster access next time
-            let _ = self
-                .memory_cache
-                .put(key.clone(), data.clone(), metadata.clone())
-                .await;
+            if let Some(memory_cache) = &self.memory_cache {
+                let _ = memory_cache
+                    .put(key.clone(), data.clone(), metadata.clone())
+                    .await;
+            }
 
             return Ok(Some((data, metadata, status)));
         }
@@ -106,13 +134,17 @@ impl CacheManager {
         }
 
         // Store in memory cache
-        let _ = self
-            .memory_cache
-            .put(key.clone(), data.clone(), metadata.clone())
-            .await;
+        if let Some(memory_cache) = &self.memory_cache {
+            let _ = memory_cache
+                .put(key.clone(), data.clone(), metadata.clone())
+                .await;
+        }
 
         // Store in file cache
-        self.file_cache.put(key, data, metadata).await
+        match &self.file_cache {
+            Some(file_cache) => file_cache.put(key, data, metadata).await,
+            None => Ok(()),
+        }
     }
 
     /// Remove a key from cache
@@ -122,8 +154,14 @@ impl CacheManager {
         }
 
         // Remove from both caches
-        let mem_result = self.memory_cache.remove(key).await;
-        let file_result = self.file_cache.remove(key).await;
+        let mem_result = match &self.memory_cache {
+            Some(memory_cache) => memory_cache.remove(key).await,
+            None => Ok(()),
+        };
+        let file_result = match &self.file_cache {
+            Some(file_cache) => file_cache.remove(key).await,
+            None => Ok(()),
+        };
 
         // Return file cache error if any, otherwise memory cache error if any
         file_result.or(mem_result)
@@ -136,8 +174,14 @@ impl CacheManager {
         }
 
         // Clear both caches
-        let mem_result = self.memory_cache.clear().await;
-        let file_result = self.file_cache.clear().await;
+        let mem_result = match &self.memory_cache {
+            Some(memory_cache) => memory_cache.clear().await,
+            None => Ok(()),
+        };
+        let file_result = match &self.file_cache {
+            Some(file_cache) => file_cache.clear().await,
+            None => Ok(()),
+        };
 
         // Return file cache error if any, otherwise memory cache error if any
         file_result.or(mem_result)
@@ -150,12 +194,17 @@ impl CacheManager {
         }
 
         // Check memory cache first
-        if self.memory_cache.contains(key).await? {
+        if let Some(memory_cache) = &self.memory_cache
+            && memory_cache.contains(key).await?
+        {
             return Ok(true);
         }
 
         // Check file cache if not in memory
-        self.file_cache.contains(key).await
+        match &self.file_cache {
+            Some(file_cache) => file_cache.contains(key).await,
+            None => Ok(false),
+        }
     }
 
     // Convenience methods for common operations
@@ -209,6 +258,90 @@ impl CacheManager {
         self.put(key, data, metadata).await
     }
 
+    /// Fetch a resource through the cache
+    ///
+    /// Fresh entries are returned without sending `request`. Expired entries carrying an ETag
+    /// or Last-Modified value are revalidated with a conditional request and returned as
+    /// [`CacheStatus::Validated`] if the server answers `304 Not Modified`. Otherwise the
+    /// response body is returned as [`CacheStatus::Miss`] and cached for `ttl`.
+    pub async fn fetch(
+        &self,
+        key: CacheKey,
+        request: RequestBuilder,
+        ttl: Duration,
+    ) -> Result<(Bytes, CacheStatus), DownloadError> {
+        let cached = self.get(&key).await.unwrap_or_else(|e| {
+            warn!(url = %key.url, error = %e, "Cache lookup failed");
+            None
+        });
+
+        let mut request = request;
+        let mut stale = None;
+        match cached {
+            Some((data, _, CacheStatus::Hit)) => return Ok((data, CacheStatus::Hit)),
+            Some((data, metadata, _)) if metadata.has_validators() => {
+                if let Some(etag) = &metadata.etag {
+                    request = request.header(IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &metadata.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified);
+                }
+                stale = Some((data, metadata));
+            }
+            _ => {}
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED
+            && let Some((data, metadata)) = stale
+        {
+            debug!(url = %key.url, "Cached resource not modified");
+            let metadata = CacheMetadata::new(data.len() as u64)
+                .with_expiration(ttl)
+                .with_etag_option(metadata.etag)
+                .with_last_modified_option(metadata.last_modified)
+                .with_content_type_option(metadata.content_type);
+            if let Err(e) = self.put(key, data.clone(), metadata).await {
+                warn!(error = %e, "Failed to refresh cache entry");
+            }
+            return Ok((data, CacheStatus::Validated));
+        }
+
+        if !response.status().is_success() {
+            return Err(DownloadError::from_response(response, "cache_fetch").await);
+        }
+
+        let (etag, last_modified, content_type) = extract_cache_headers(&response);
+        let data = response.bytes().await?;
+        let metadata = CacheMetadata::new(data.len() as u64)
+            .with_expiration(ttl)
+            .with_etag_option(etag)
+            .with_last_modified_option(last_modified)
+            .with_content_type_option(content_type);
+        if let Err(e) = self.put(key, data.clone(), metadata).await {
+            warn!(error = %e, "Failed to cache fetched resource");
+        }
+        Ok((data, CacheStatus::Miss))
+    }
+
+    /// Remove expired entries that cannot be revalidated, corrupted entries and files left over
+    /// from interrupted writes from the disk cache, then enforce its size limit.
+    ///
+    /// Returns the number of entries and files removed.
+    pub async fn purge(&self) -> CacheResult<u64> {
+        if !self.config.enabled {
+            return Ok(0);
+        }
+
+        if let Some(memory_cache) = &self.memory_cache {
+            memory_cache.sweep().await?;
+        }
+        match &self.file_cache {
+            Some(file_cache) => file_cache.purge().await,
+            None => Ok(0),
+        }
+    }
+
     /// Get configuration reference
     pub fn config(&self) -> &CacheConfig {
         &self.config
@@ -222,10 +355,14 @@ impl CacheManager {
         }
 
         // Sweep memory cache (runs Moka's pending tasks for expiration)
-        self.memory_cache.sweep().await?;
+        if let Some(memory_cache) = &self.memory_cache {
+            memory_cache.sweep().await?;
+        }
 
         // Sweep file cache (enforces disk size limits via LRU eviction)
-        self.file_cache.sweep().await?;
+        if let Some(file_cache) = &self.file_cache {
+            file_cache.sweep().await?;
+        }
 
         Ok(())
     }
@@ -272,4 +409,125 @@ mod tests {
             Err(err) => assert_eq!(err.kind(), io::ErrorKind::Other),
         }
     }
+
+    /// Serve `body` with an ETag, answering 304 to requests carrying it. Returns the URL and
+    /// the If-None-Match value of each request.
+    async fn spawn_etag_server(
+        body: &'static str,
+    ) -> (String, Arc<std::sync::Mutex<Vec<Option<String>>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        const ETAG: &str = "\"v1\"";
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!("http://{}/playlist.m3u8", listener.local_addr().unwrap());
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = String::from_utf8_lossy(&head).to_ascii_lowercase();
+                let if_none_match = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("if-none-match:"))
+                    .map(|value| value.trim().to_string());
+                let response = if if_none_match.as_deref() == Some(ETAG) {
+                    format!(
+                        "HTTP/1.1 304 Not Modified\r\nETag: {ETAG}\r\nConnection: close\r\n\r\n"
+                    )
+                } else {
+                    format!(
+                        "HTTP/1.1 200 OK\r\nETag: {ETAG}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                seen.lock().unwrap().push(if_none_match);
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        (url, requests)
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_revalidates_across_restarts() {
+        let dir =
+            std::env::temp_dir().join(format!("mesio-cache-revalidate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = CacheConfig {
+            backend: CacheBackend::Disk {
+                path: dir.clone(),
+                max_bytes: 1024 * 1024,
+            },
+            ..Default::default()
+        };
+        let (url, requests) = spawn_etag_server("#EXTM3U\n").await;
+        let client = reqwest::Client::new();
+        let key = || CacheKey::new(CacheResourceType::Playlist, url.as_str(), None);
+
+        let manager = CacheManager::new(config.clone()).await.unwrap();
+        assert!(manager.memory_cache.is_none());
+        let (data, status) = manager
+            .fetch(key(), client.get(&url), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(
+            (data.as_ref(), status),
+            (&b"#EXTM3U\n"[..], CacheStatus::Miss)
+        );
+
+        // Let the entry expire, then revalidate it from a new process
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let manager = CacheManager::new(config).await.unwrap();
+        let (data, status) = manager
+            .fetch(key(), client.get(&url), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(
+            (data.as_ref(), status),
+            (&b"#EXTM3U\n"[..], CacheStatus::Validated)
+        );
+
+        // Fresh again without another request
+        let (_, status) = manager
+            .fetch(key(), client.get(&url), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(status, CacheStatus::Hit);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![None, Some("\"v1\"".to_string())]
+        );
+
+        manager.purge().await.unwrap();
+        assert!(manager.get(&key()).await.unwrap().is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_vary_headers_select_separate_entries() {
+        let base = CacheKey::new(CacheResourceType::Key, "https://a/key", None);
+        let gzip = base
+            .clone()
+            .with_vary([("Accept-Encoding", "gzip"), ("X-Token", "1")]);
+        let reordered = base
+            .clone()
+            .with_vary([("x-token", "1"), ("accept-encoding", " gzip")]);
+        let other = base
+            .clone()
+            .with_vary([("Accept-Encoding", "br"), ("X-Token", "1")]);
+
+        assert_eq!(gzip.to_filename(), reordered.to_filename());
+        assert_ne!(gzip.to_filename(), other.to_filename());
+        assert_ne!(gzip.to_filename(), base.to_filename());
+        assert_eq!(base.clone().with_vary([]).to_filename(), base.to_filename());
+    }
 }
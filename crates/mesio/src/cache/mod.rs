@@ -13,8 +13,8 @@ mod utils;
 // Re-export primary types from our various modules
 pub use manager::CacheManager;
 pub use types::{
-    CacheConfig, CacheKey, CacheLookupResult, CacheMetadata, CacheResourceType, CacheResult,
-    CacheStatus,
+    CacheBackend, CacheConfig, CacheKey, CacheLookupResult, CacheMetadata, CacheResourceType,
+    CacheResult, CacheStatus,
 };
 pub use utils::extract_cache_headers;
 
@@ -1,8 +1,19 @@
 //! # File Cache
 //!
 //! This module implements a file-based persistent cache provider.
+//!
+//! Each entry is a data file named after the hash of its key, next to a `.meta` JSON file
+//! holding its metadata. Both are written to uniquely named temporary files and renamed into
+//! place, so concurrent writers never interleave their bytes. A data file whose length does not
+//! match its metadata, or metadata that cannot be parsed, is treated as corrupted and removed.
+//!
+//! Reading an entry refreshes the modification time of its data file, and the least recently
+//! used entries are evicted first when the cache grows past its size limit.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
 
 use bytes::Bytes;
 use tokio::fs;
@@ -13,6 +24,15 @@ use crate::cache::types::{CacheKey, CacheLookupResult, CacheMetadata, CacheResul
 
 use super::CacheProvider;
 
+/// Disk usage is unknown until the cache directory was scanned
+const USAGE_UNKNOWN: u64 = u64::MAX;
+
+/// Temporary files older than this are left over from interrupted writes
+const STALE_TEMP_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Distinguishes the temporary files of concurrent writes within this process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug, Clone)]
 pub struct FileCache {
     cache_dir: PathBuf,
@@ -20,6 +40,19 @@ pub struct FileCache {
     enabled: bool,
     /// Maximum disk cache size in bytes (0 = unlimited)
     max_size: u64,
+    /// Estimated bytes used by the entries, recomputed by each sweep
+    usage: Arc<AtomicU64>,
+}
+
+/// An entry found on disk
+#[derive(Debug)]
+struct DiskEntry {
+    data_path: PathBuf,
+    meta_path: PathBuf,
+    /// Size of the data and metadata files
+    size: u64,
+    /// Modification time of the data file, refreshed when the entry is read
+    last_used: SystemTime,
 }
 
 impl FileCache {
@@ -30,6 +63,7 @@ impl FileCache {
             initialized: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
             enabled,
             max_size,
+            usage: Arc::new(AtomicU64::new(USAGE_UNKNOWN)),
         }
     }
 
@@ -93,6 +127,208 @@ impl FileCache {
         path.set_extension("meta");
         path
     }
+
+    /// Remove the files of an entry, ignoring files that are already gone
+    async fn remove_entry_files(data_path: &Path, meta_path: &Path) {
+        for path in [data_path, meta_path] {
+            if let Err(e) = fs::remove_file(path).await
+                && e.kind() != io::ErrorKind::NotFound
+            {
+                warn!(path = ?path, error = %e, "Failed to remove cache file");
+            }
+        }
+    }
+
+    /// Mark an entry as used for LRU eviction
+    async fn touch(path: &Path) {
+        let path = path.to_path_buf();
+        let result = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(SystemTime::now())
+        })
+        .await;
+        if let Ok(Err(e)) = result {
+            debug!(error = %e, "Failed to update cache entry access time");
+        }
+    }
+
+    /// Collect the entries of the cache, along with the paths of files not belonging to a
+    /// complete entry
+    async fn scan(&self) -> (Vec<DiskEntry>, Vec<PathBuf>) {
+        let mut entries = Vec::new();
+        let mut strays = Vec::new();
+
+        let mut dir_entries = match fs::read_dir(&self.cache_dir).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(dir = ?self.cache_dir, error = %e, "Failed to read cache directory");
+                return (entries, strays);
+            }
+        };
+
+        while let Ok(Some(subdir_entry)) = dir_entries.next_entry().await {
+            let subdir_path = subdir_entry.path();
+            if !subdir_path.is_dir() {
+                continue;
+            }
+
+            let mut subdir_entries = match fs::read_dir(&subdir_path).await {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            while let Ok(Some(entry)) = subdir_entries.next_entry().await {
+                let path = entry.path();
+                let Ok(file_meta) = entry.metadata().await else {
+                    continue;
+                };
+
+                match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("tmp") => {
+                        // Possibly being written by another download
+                        let age = file_meta
+                            .modified()
+                            .ok()
+                            .and_then(|modified| modified.elapsed().ok())
+                            .unwrap_or_default();
+                        if age > STALE_TEMP_FILE_AGE {
+                            strays.push(path);
+                        }
+                    }
+                    Some("meta") => {
+                        if !fs::try_exists(path.with_extension(""))
+                            .await
+                            .unwrap_or(true)
+                        {
+                            strays.push(path);
+                        }
+                    }
+                    _ => {
+                        let meta_path = path.with_extension("meta");
+                        let Ok(meta_file_meta) = fs::metadata(&meta_path).await else {
+                            strays.push(path);
+                            continue;
+                        };
+                        entries.push(DiskEntry {
+                            size: file_meta.len() + meta_file_meta.len(),
+                            last_used: file_meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                            data_path: path,
+                            meta_path,
+                        });
+                    }
+                }
+            }
+        }
+
+        (entries, strays)
+    }
+
+    /// Read and validate the entry stored in the given files
+    async fn read_entry(
+        data_path: &Path,
+        meta_path: &Path,
+    ) -> io::Result<Option<(Bytes, CacheMetadata)>> {
+        let metadata_bytes = match fs::read(meta_path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let data = match fs::read(data_path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let metadata: CacheMetadata = serde_json::from_slice(&metadata_bytes).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse cache metadata: {e}"),
+            )
+        })?;
+        if metadata.size != data.len() as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Cache data has {} bytes, its metadata records {}",
+                    data.len(),
+                    metadata.size
+                ),
+            ));
+        }
+
+        Ok(Some((Bytes::from(data), metadata)))
+    }
+
+    /// Evict the least recently used entries once the cache grows past its size limit
+    async fn enforce_size_limit(&self, added: u64) {
+        if self.max_size == 0 {
+            return;
+        }
+        let usage = self.usage.load(Ordering::Relaxed);
+        if usage != USAGE_UNKNOWN {
+            let usage = self.usage.fetch_add(added, Ordering::Relaxed) + added;
+            if usage <= self.max_size {
+                return;
+            }
+        }
+        if let Err(e) = self.sweep().await {
+            warn!(error = %e, "Failed to enforce disk cache size limit");
+        }
+    }
+
+    /// Remove expired entries that cannot be revalidated, corrupted entries and files left
+    /// over from interrupted writes, then enforce the size limit.
+    ///
+    /// Returns the number of entries and files removed.
+    pub async fn purge(&self) -> CacheResult<u64> {
+        if !self.enabled {
+            return Ok(0);
+        }
+
+        self.ensure_initialized().await?;
+
+        let (entries, strays) = self.scan().await;
+        let mut removed = 0;
+
+        for path in strays {
+            if fs::remove_file(&path).await.is_ok() {
+                removed += 1;
+            }
+        }
+
+        for entry in entries {
+            let keep = match Self::read_entry(&entry.data_path, &entry.meta_path).await {
+                Ok(Some((_, metadata))) => !metadata.is_expired() || metadata.has_validators(),
+                // Removed concurrently
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!(path = ?entry.data_path, error = %e, "Removing corrupted cache entry");
+                    false
+                }
+            };
+            if !keep {
+                Self::remove_entry_files(&entry.data_path, &entry.meta_path).await;
+                removed += 1;
+            }
+        }
+
+        debug!(removed = removed, "Purged disk cache");
+        self.sweep().await?;
+        Ok(removed)
+    }
+
+    /// A unique path to write `path` to before renaming it into place
+    fn temp_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(
+            ".{}-{}.tmp",
+            std::process::id(),
+            TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        path.with_file_name(name)
+    }
 }
 
 #[async_trait::async_trait]
@@ -126,37 +362,16 @@ impl CacheProvider for FileCache {
         let data_path = self.get_cache_path(key);
         let meta_path = self.get_metadata_path(key);
 
-        // Check if both data and metadata exist
-        let data_exists = fs::try_exists(&data_path).await?;
-        let meta_exists = fs::try_exists(&meta_path).await?;
-
-        if !data_exists || !meta_exists {
-            return Ok(None);
-        }
-
-        // Read metadata
-        let metadata_bytes = match fs::read(&meta_path).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                warn!(path = ?meta_path, error = %e, "Failed to read cache metadata file");
+        let (data, metadata) = match Self::read_entry(&data_path, &meta_path).await {
+            Ok(Some(entry)) => entry,
+            Ok(None) => return Ok(None),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                warn!(path = ?data_path, error = %e, "Removing corrupted cache entry");
+                Self::remove_entry_files(&data_path, &meta_path).await;
                 return Ok(None);
             }
-        };
-
-        let metadata: CacheMetadata = match serde_json::from_slice(&metadata_bytes) {
-            Ok(m) => m,
             Err(e) => {
-                warn!(path = ?meta_path, error = %e, "Failed to parse cache metadata");
-
-                // Delete invalid cache entry as a background task
-                // We use spawn to avoid blocking the current task
-                let data_path_clone = data_path.clone();
-                let meta_path_clone = meta_path.clone();
-                tokio::spawn(async move {
-                    let _ = fs::remove_file(&data_path_clone).await;
-                    let _ = fs::remove_file(&meta_path_clone).await;
-                });
-
+                warn!(path = ?data_path, error = %e, "Failed to read cache entry");
                 return Ok(None);
             }
         };
@@ -168,27 +383,15 @@ impl CacheProvider for FileCache {
             CacheStatus::Hit
         };
 
-        // Read data
-        let data = match fs::read(&data_path).await {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                warn!(path = ?data_path, error = %e, "Failed to read cache data file");
-                return Ok(None);
-            }
-        };
-
-        // For expired entries, we can still return the data, but remove it in the background
-        if status == CacheStatus::Expired {
-            let data_path_clone = data_path.clone();
-            let meta_path_clone = meta_path.clone();
-            tokio::spawn(async move {
-                let _ = fs::remove_file(&data_path_clone).await;
-                let _ = fs::remove_file(&meta_path_clone).await;
-            });
+        // Expired entries are still returned. They are kept for revalidation if they carry
+        // validators, and removed otherwise.
+        if status == CacheStatus::Expired && !metadata.has_validators() {
+            Self::remove_entry_files(&data_path, &meta_path).await;
+        } else {
+            Self::touch(&data_path).await;
         }
-        let bytes = Bytes::from(data);
 
-        Ok(Some((bytes, metadata, status)))
+        Ok(Some((data, metadata, status)))
     }
 
     async fn put(&self, key: CacheKey, data: Bytes, metadata: CacheMetadata) -> CacheResult<()> {
@@ -220,8 +423,8 @@ impl CacheProvider for FileCache {
 
         // Write data and metadata atomically if possible
         // First write to temporary files then rename
-        let temp_data_path = data_path.with_extension("tmp");
-        let temp_meta_path = meta_path.with_extension("tmp");
+        let temp_data_path = Self::temp_path(&data_path);
+        let temp_meta_path = Self::temp_path(&meta_path);
 
         // Write data file
         match fs::write(&temp_data_path, &data).await {
@@ -273,6 +476,8 @@ impl CacheProvider for FileCache {
         }
 
         debug!(key = ?key, "Successfully cached entry to file");
+        self.enforce_size_limit(data.len() as u64 + metadata_json.len() as u64)
+            .await;
         Ok(())
     }
 
@@ -345,6 +550,8 @@ impl CacheProvider for FileCache {
 
         debug!(count = entry_count, "Cleared cache entries");
 
+        self.usage.store(0, Ordering::Relaxed);
+
         // Reset initialized state and recreate subdirectories
         self.initialized
             .store(false, std::sync::atomic::Ordering::Relaxed);
@@ -365,72 +572,9 @@ impl CacheProvider for FileCache {
 
         self.ensure_initialized().await?;
 
-        // Collect all cached entries with their metadata
-        let mut entries: Vec<(PathBuf, PathBuf, u64, u64)> = Vec::new(); // (data_path, meta_path, size, cached_at)
-        let mut total_size: u64 = 0;
-
-        // Scan all subdirectories for cache entries
-        let mut dir_entries = match fs::read_dir(&self.cache_dir).await {
-            Ok(entries) => entries,
-            Err(e) => {
-                warn!(dir = ?self.cache_dir, error = %e, "Failed to read cache directory for sweep");
-                return Ok(());
-            }
-        };
-
-        while let Ok(Some(subdir_entry)) = dir_entries.next_entry().await {
-            let subdir_path = subdir_entry.path();
-            if !subdir_path.is_dir() {
-                continue;
-            }
-
-            let mut subdir_entries = match fs::read_dir(&subdir_path).await {
-                Ok(entries) => entries,
-                Err(_) => continue,
-            };
-
-            while let Ok(Some(entry)) = subdir_entries.next_entry().await {
-                let path = entry.path();
-
-                // Skip metadata files (we'll handle them with their data files)
-                if path.extension().is_some_and(|ext| ext == "meta") {
-                    continue;
-                }
-
-                // Skip temp files
-                if path.extension().is_some_and(|ext| ext == "tmp") {
-                    continue;
-                }
-
-                let meta_path = path.with_extension("meta");
-
-                // Get file size
-                let file_size = match fs::metadata(&path).await {
-                    Ok(m) => m.len(),
-                    Err(_) => continue,
-                };
-
-                let meta_size = match fs::metadata(&meta_path).await {
-                    Ok(m) => m.len(),
-                    Err(_) => continue, // Skip entries without valid metadata
-                };
-
-                // Read metadata to get cached_at timestamp
-                let metadata_bytes = match fs::read(&meta_path).await {
-                    Ok(bytes) => bytes,
-                    Err(_) => continue,
-                };
-
-                let metadata: CacheMetadata = match serde_json::from_slice(&metadata_bytes) {
-                    Ok(m) => m,
-                    Err(_) => continue, // Skip entries with invalid metadata
-                };
-
-                let entry_size = file_size + meta_size;
-                total_size += entry_size;
-                entries.push((path, meta_path, entry_size, metadata.cached_at));
-            }
-        }
+        let (mut entries, _) = self.scan().await;
+        let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        self.usage.store(total_size, Ordering::Relaxed);
 
         // Check if we're over the limit
         if total_size <= self.max_size {
@@ -442,37 +586,29 @@ impl CacheProvider for FileCache {
             return Ok(());
         }
 
-        // Sort by cached_at (oldest first) for LRU eviction
-        entries.sort_by_key(|(_, _, _, cached_at)| *cached_at);
+        // Least recently used first
+        entries.sort_by_key(|entry| entry.last_used);
 
         // Target 80% of max_size to avoid constant eviction cycles
         let target_size = (self.max_size as f64 * 0.8) as u64;
         let mut evicted_count = 0;
         let mut evicted_size: u64 = 0;
 
-        for (data_path, meta_path, entry_size, _) in entries {
+        for entry in entries {
             if total_size <= target_size {
                 break;
             }
 
-            // Remove both data and metadata files
-            if let Err(e) = fs::remove_file(&data_path).await
-                && e.kind() != io::ErrorKind::NotFound
-            {
-                warn!(path = ?data_path, error = %e, "Failed to remove cache data file during sweep");
-            }
-
-            if let Err(e) = fs::remove_file(&meta_path).await
-                && e.kind() != io::ErrorKind::NotFound
-            {
-                warn!(path = ?meta_path, error = %e, "Failed to remove cache metadata file during sweep");
-            }
+            Self::remove_entry_files(&entry.data_path, &entry.meta_path).await;
 
+            let entry_size = entry.size;
             total_size = total_size.saturating_sub(entry_size);
             evicted_size += entry_size;
             evicted_count += 1;
         }
 
+        self.usage.store(total_size, Ordering::Relaxed);
+
         if evicted_count > 0 {
             debug!(
                 evicted_count = evicted_count,
@@ -486,3 +622,178 @@ impl CacheProvider for FileCache {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::types::CacheResourceType;
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("mesio-file-cache-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn key(name: &str) -> CacheKey {
+        CacheKey::new(CacheResourceType::Content, name, None)
+    }
+
+    async fn put(cache: &FileCache, name: &str, data: Bytes, metadata: CacheMetadata) {
+        cache.put(key(name), data, metadata).await.unwrap();
+    }
+
+    fn set_last_used(cache: &FileCache, name: &str, secs_ago: u64) {
+        std::fs::File::options()
+            .write(true)
+            .open(cache.get_cache_path(&key(name)))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(secs_ago))
+            .unwrap();
+    }
+
+    fn expired(size: usize) -> CacheMetadata {
+        let mut metadata = CacheMetadata::new(size as u64);
+        metadata.expires_at = Some(metadata.cached_at - 10);
+        metadata
+    }
+
+    #[tokio::test]
+    async fn test_sweep_evicts_least_recently_used_first() {
+        let dir = cache_dir("lru");
+        let mut cache = FileCache::new(dir.clone(), true, 0);
+        let data = Bytes::from(vec![7u8; 2000]);
+        for name in ["a", "b", "c"] {
+            put(
+                &cache,
+                name,
+                data.clone(),
+                CacheMetadata::new(data.len() as u64),
+            )
+            .await;
+        }
+        set_last_used(&cache, "a", 300);
+        set_last_used(&cache, "b", 200);
+        set_last_used(&cache, "c", 100);
+
+        // Reading the oldest entry makes it the most recently used
+        assert!(cache.get(&key("a")).await.unwrap().is_some());
+
+        // Room for two entries, one has to go
+        cache.max_size = 6000;
+        cache.sweep().await.unwrap();
+        assert!(cache.contains(&key("a")).await.unwrap());
+        assert!(!cache.contains(&key("b")).await.unwrap());
+        assert!(cache.contains(&key("c")).await.unwrap());
+
+        // Writing past the limit evicts without an explicit sweep
+        put(
+            &cache,
+            "d",
+            data.clone(),
+            CacheMetadata::new(data.len() as u64),
+        )
+        .await;
+        assert!(!cache.contains(&key("c")).await.unwrap());
+        assert!(cache.contains(&key("a")).await.unwrap());
+        assert!(cache.contains(&key("d")).await.unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_entries_are_removed() {
+        let dir = cache_dir("corrupted");
+        let cache = FileCache::new(dir.clone(), true, 0);
+        let data = Bytes::from_static(b"#EXTM3U\n#EXT-X-TARGETDURATION:2\n");
+
+        // Truncated data
+        put(
+            &cache,
+            "truncated",
+            data.clone(),
+            CacheMetadata::new(data.len() as u64),
+        )
+        .await;
+        let data_path = cache.get_cache_path(&key("truncated"));
+        std::fs::write(&data_path, &data[..5]).unwrap();
+        assert!(cache.get(&key("truncated")).await.unwrap().is_none());
+        assert!(!data_path.exists());
+        assert!(!cache.get_metadata_path(&key("truncated")).exists());
+
+        // Unreadable metadata
+        put(
+            &cache,
+            "garbage",
+            data.clone(),
+            CacheMetadata::new(data.len() as u64),
+        )
+        .await;
+        std::fs::write(cache.get_metadata_path(&key("garbage")), b"{\"cached_at\":").unwrap();
+        assert!(cache.get(&key("garbage")).await.unwrap().is_none());
+        assert!(!cache.contains(&key("garbage")).await.unwrap());
+
+        // The entry can be written again
+        put(
+            &cache,
+            "garbage",
+            data.clone(),
+            CacheMetadata::new(data.len() as u64),
+        )
+        .await;
+        let (cached, _, status) = cache.get(&key("garbage")).await.unwrap().unwrap();
+        assert_eq!(cached, data);
+        assert_eq!(status, CacheStatus::Hit);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_purge_keeps_entries_that_can_be_revalidated() {
+        let dir = cache_dir("purge");
+        let cache = FileCache::new(dir.clone(), true, 0);
+        let data = Bytes::from_static(b"key material");
+
+        put(
+            &cache,
+            "fresh",
+            data.clone(),
+            CacheMetadata::new(data.len() as u64),
+        )
+        .await;
+        put(&cache, "expired", data.clone(), expired(data.len())).await;
+        put(
+            &cache,
+            "revalidatable",
+            data.clone(),
+            expired(data.len()).with_etag("\"v1\""),
+        )
+        .await;
+
+        // Metadata without data, and a temporary file of an interrupted write
+        let orphan = cache.get_metadata_path(&key("orphan"));
+        std::fs::write(&orphan, b"{}").unwrap();
+        let temp = FileCache::temp_path(&cache.get_cache_path(&key("interrupted")));
+        std::fs::write(&temp, b"partial").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&temp)
+            .unwrap()
+            .set_modified(SystemTime::now() - 2 * STALE_TEMP_FILE_AGE)
+            .unwrap();
+
+        assert_eq!(cache.purge().await.unwrap(), 3);
+        assert!(cache.contains(&key("fresh")).await.unwrap());
+        assert!(!cache.contains(&key("expired")).await.unwrap());
+        assert!(!orphan.exists());
+        assert!(!temp.exists());
+
+        let (_, metadata, status) = cache.get(&key("revalidatable")).await.unwrap().unwrap();
+        assert_eq!(status, CacheStatus::Expired);
+        assert_eq!(metadata.etag.as_deref(), Some("\"v1\""));
+        // Still there for the next revalidation
+        assert!(cache.contains(&key("revalidatable")).await.unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -77,6 +77,26 @@ impl CacheKey {
         let hash = hasher.finalize();
         format!("{hash:x}")
     }
+
+    /// Distinguish the resource by the request headers it varies on
+    ///
+    /// Header names are case-insensitive and the order of the headers does not matter.
+    pub fn with_vary<'a>(mut self, headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut vary: Vec<String> = headers
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name.to_ascii_lowercase(), value.trim()))
+            .collect();
+        if vary.is_empty() {
+            return self;
+        }
+        vary.sort();
+        let vary = vary.join("&");
+        self.identifier = Some(match self.identifier.take() {
+            Some(id) => format!("{id}|vary:{vary}"),
+            None => format!("vary:{vary}"),
+        });
+        self
+    }
 }
 
 /// Metadata for a cached resource
@@ -170,6 +190,11 @@ impl CacheMetadata {
         self
     }
 
+    /// Whether the resource can be revalidated with a conditional request
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+
     /// Check if the resource has expired
     pub fn is_expired(&self) -> bool {
         if let Some(expires_at) = self.expires_at {
@@ -185,14 +210,29 @@ impl CacheMetadata {
     }
 }
 
+/// Storage used by the cache
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CacheBackend {
+    /// Entries are kept in memory only, up to `max_memory_cache_size`
+    Memory,
+    /// Entries are stored under `path` only, up to `max_bytes` (0 = unlimited)
+    Disk { path: PathBuf, max_bytes: u64 },
+    /// Entries are kept in memory in front of a disk cache under `disk_cache_path`, up to
+    /// `max_disk_cache_size` (0 = memory only)
+    #[default]
+    Tiered,
+}
+
 /// Configuration for the cache system
 #[derive(Debug, Clone)]
 pub struct CacheConfig {
     /// Whether caching is enabled
     pub enabled: bool,
-    /// Path for disk cache storage
+    /// Storage used for cache entries
+    pub backend: CacheBackend,
+    /// Path for disk cache storage of the tiered backend
     pub disk_cache_path: Option<PathBuf>,
-    /// Maximum size of disk cache in bytes
+    /// Maximum size of disk cache in bytes of the tiered backend
     pub max_disk_cache_size: u64,
     /// Maximum size of memory cache in bytes
     pub max_memory_cache_size: u64,
@@ -208,6 +248,7 @@ impl Default for CacheConfig {
     fn default() -> Self {
         Self {
             enabled: true,
+            backend: CacheBackend::default(),
             disk_cache_path: None, // If None, we'll use system temp dir
             max_disk_cache_size: 500 * 1024 * 1024, // 500MB
             max_memory_cache_size: 30 * 1024 * 1024, // 30MB
@@ -0,0 +1,335 @@
+//! # Concurrent Input Processing
+//!
+//! Runs the downloads or file repairs of several inputs at once. At most `max_parallel`
+//! inputs are processed at a time, and all of them share the limits of a [`SharedLimits`]:
+//! a bandwidth budget applied to their downloads and a cap on the files and connections they
+//! hold open.
+//!
+//! A failed input does not stop the others unless fail-fast is requested, in which case the
+//! running inputs are cancelled and those not started yet are skipped. The outcome of every
+//! input is collected into an [`InputSummary`].
+
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use pipeline_common::{CancellationToken, ProgressEvent};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+use crate::DownloaderConfig;
+use crate::throttle::{OnProgress, RateLimiter};
+
+/// Limits shared by all inputs processed concurrently
+#[derive(Debug, Clone, Default)]
+pub struct SharedLimits {
+    rate_limiter: Option<RateLimiter>,
+    open_slots: Option<Arc<Semaphore>>,
+}
+
+impl SharedLimits {
+    /// Limits allowing unlimited bandwidth and open files
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the combined throughput of all downloads to `bytes_per_sec`
+    pub fn with_max_bytes_per_sec(mut self, bytes_per_sec: u64) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(bytes_per_sec));
+        self
+    }
+
+    /// Limit the number of files and connections held open at once to `max_open`
+    pub fn with_max_open(mut self, max_open: usize) -> Self {
+        self.open_slots = Some(Arc::new(Semaphore::new(max_open.max(1))));
+        self
+    }
+
+    /// The bandwidth budget shared by all downloads, if any
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Apply the bandwidth budget to the configuration of a download
+    pub fn apply_to(&self, config: &mut DownloaderConfig) {
+        if let Some(limiter) = &self.rate_limiter {
+            config.shared_rate_limiter = Some(limiter.clone());
+        }
+    }
+
+    /// Wait for a free file or connection slot, held until the returned guard is dropped
+    pub async fn acquire_open(&self) -> OpenSlot {
+        let permit = match &self.open_slots {
+            Some(slots) => Arc::clone(slots).acquire_owned().await.ok(),
+            None => None,
+        };
+        OpenSlot { _permit: permit }
+    }
+}
+
+/// A file or connection slot of a [`SharedLimits`]
+#[derive(Debug)]
+pub struct OpenSlot {
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+/// What an input is processed with
+#[derive(Debug, Clone)]
+pub struct InputContext {
+    /// Position of the input in the list of inputs
+    pub index: usize,
+    /// Limits shared with the other inputs
+    pub limits: SharedLimits,
+    /// Cancelled when processing is aborted
+    pub token: CancellationToken,
+}
+
+impl InputContext {
+    /// Wrap a progress callback so its events identify this input
+    pub fn tag_progress(&self, on_progress: &OnProgress) -> OnProgress {
+        let index = self.index;
+        let on_progress = on_progress.clone();
+        OnProgress::new(move |event| {
+            on_progress.emit(ProgressEvent::Input {
+                index,
+                event: Box::new(event),
+            })
+        })
+    }
+}
+
+/// Outcome of processing one input
+#[derive(Debug)]
+pub enum InputOutcome<E> {
+    /// The input was processed successfully
+    Succeeded,
+    /// Processing the input failed
+    Failed(E),
+    /// The input was not processed, as processing was aborted first
+    Skipped,
+}
+
+/// Outcomes of all inputs, in the order of the inputs
+#[derive(Debug)]
+pub struct InputSummary<E> {
+    pub outcomes: Vec<InputOutcome<E>>,
+}
+
+impl<E> InputSummary<E> {
+    /// Number of inputs processed successfully
+    pub fn succeeded(&self) -> usize {
+        self.count(|outcome| matches!(outcome, InputOutcome::Succeeded))
+    }
+
+    /// Number of inputs that failed
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, InputOutcome::Failed(_)))
+    }
+
+    /// Number of inputs that were not processed
+    pub fn skipped(&self) -> usize {
+        self.count(|outcome| matches!(outcome, InputOutcome::Skipped))
+    }
+
+    /// Whether every input was processed successfully
+    pub fn is_success(&self) -> bool {
+        self.succeeded() == self.outcomes.len()
+    }
+
+    /// The errors of the failed inputs with their index
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &E)> {
+        self.outcomes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, outcome)| match outcome {
+                InputOutcome::Failed(error) => Some((index, error)),
+                _ => None,
+            })
+    }
+
+    fn count(&self, predicate: impl Fn(&InputOutcome<E>) -> bool) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| predicate(outcome))
+            .count()
+    }
+}
+
+/// Process `inputs` with `process`, running at most `max_parallel` of them at once.
+///
+/// The inputs are driven concurrently on the calling task, so their futures need not be
+/// `Send`; the pipelines and writers they start run on the tokio runtime as usual. With
+/// `fail_fast`, the first failure cancels the token of the running inputs and the remaining
+/// ones are skipped. Cancelling `token` skips the inputs not started yet.
+pub async fn process_inputs_concurrent<T, E, F, Fut>(
+    inputs: Vec<T>,
+    max_parallel: usize,
+    shared: SharedLimits,
+    fail_fast: bool,
+    token: &CancellationToken,
+    mut process: F,
+) -> InputSummary<E>
+where
+    F: FnMut(T, InputContext) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    let max_parallel = max_parallel.max(1);
+    let token = token.child_token();
+    let mut outcomes: Vec<_> = inputs.iter().map(|_| InputOutcome::Skipped).collect();
+    let mut pending = inputs.into_iter().enumerate();
+    let mut running = FuturesUnordered::new();
+
+    loop {
+        while running.len() < max_parallel && !token.is_cancelled() {
+            let Some((index, input)) = pending.next() else {
+                break;
+            };
+            debug!(index, "Starting input");
+            let context = InputContext {
+                index,
+                limits: shared.clone(),
+                token: token.clone(),
+            };
+            let future = process(input, context);
+            running.push(async move { (index, future.await) });
+        }
+
+        let Some((index, result)) = running.next().await else {
+            break;
+        };
+        outcomes[index] = match result {
+            Ok(()) => InputOutcome::Succeeded,
+            Err(error) => {
+                if fail_fast && !token.is_cancelled() {
+                    warn!(index, "Input failed, aborting the remaining inputs");
+                    token.cancel();
+                }
+                InputOutcome::Failed(error)
+            }
+        };
+    }
+
+    InputSummary { outcomes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_failure_does_not_abort_other_inputs() {
+        let token = CancellationToken::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let summary = process_inputs_concurrent(
+            (0..6).collect(),
+            2,
+            SharedLimits::new(),
+            false,
+            &token,
+            |input: u32, context| {
+                let running = Arc::clone(&running);
+                let peak = Arc::clone(&peak);
+                async move {
+                    assert_eq!(context.index, input as usize);
+                    peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    if input == 1 {
+                        Err(format!("input {input}"))
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(summary.succeeded(), 5);
+        assert_eq!(summary.failed(), 1);
+        assert_eq!(summary.skipped(), 0);
+        assert!(!summary.is_success());
+        let failures: Vec<_> = summary.failures().collect();
+        assert_eq!(failures, vec![(1, &"input 1".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_fail_fast_cancels_running_and_skips_pending_inputs() {
+        let token = CancellationToken::new();
+
+        let summary = process_inputs_concurrent(
+            (0..5).collect(),
+            2,
+            SharedLimits::new(),
+            true,
+            &token,
+            |input: u32, context| async move {
+                if input == 0 {
+                    return Err("failed");
+                }
+                // Runs until cancelled by the failure of the first input
+                context.token.cancelled().await;
+                Err("cancelled")
+            },
+        )
+        .await;
+
+        assert!(matches!(
+            summary.outcomes[0],
+            InputOutcome::Failed("failed")
+        ));
+        assert!(matches!(
+            summary.outcomes[1],
+            InputOutcome::Failed("cancelled")
+        ));
+        assert_eq!(summary.skipped(), 3);
+        // The caller's token is left alone
+        assert!(!token.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_open_slots_are_shared() {
+        let token = CancellationToken::new();
+        let limits = SharedLimits::new().with_max_open(1);
+        let open = Arc::new(AtomicUsize::new(0));
+
+        let summary = process_inputs_concurrent(
+            (0..4).collect::<Vec<u32>>(),
+            4,
+            limits,
+            false,
+            &token,
+            |_, context| {
+                let open = Arc::clone(&open);
+                async move {
+                    let _slot = context.limits.acquire_open().await;
+                    assert_eq!(open.fetch_add(1, Ordering::SeqCst), 0);
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    open.fetch_sub(1, Ordering::SeqCst);
+                    Ok::<_, ()>(())
+                }
+            },
+        )
+        .await;
+
+        assert!(summary.is_success());
+    }
+
+    #[test]
+    fn test_bandwidth_budget_is_applied_to_downloads() {
+        let limits = SharedLimits::new().with_max_bytes_per_sec(1024);
+        let mut config = DownloaderConfig::default();
+        limits.apply_to(&mut config);
+        assert_eq!(
+            config
+                .shared_rate_limiter
+                .map(|limiter| limiter.bytes_per_sec()),
+            Some(1024)
+        );
+    }
+}
@@ -1,13 +1,25 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 use reqwest::header::{HeaderMap, HeaderValue};
-
-use crate::{CacheConfig, proxy::ProxyConfig};
+use serde::{Deserialize, Serialize};
+
+use crate::CacheConfig;
+use crate::auth::{AuthRefresh, Cookie};
+use crate::downloader::ClientProvider;
+use crate::factory::ProtocolType;
+use crate::proxy::{ProxyConfig, ProxyOverride};
+use crate::retry::RetryPolicy;
+use crate::tee::RawTee;
+use crate::throttle::{OnProgress, RateLimiter};
+use crate::watchdog::StallConfig;
 
 pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/142.0.0.0 Safari/537.36";
 
 /// HTTP version preference for connections
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HttpVersionPreference {
     /// Let ALPN negotiate the best version (default)
     #[default]
@@ -19,6 +31,9 @@ pub enum HttpVersionPreference {
     Http2Only,
     /// Force HTTP/1.1 only (disable HTTP/2)
     Http1Only,
+    /// Speak HTTP/2 from the first byte without negotiating it, for origins known to
+    /// support it (including cleartext `h2c`).
+    Http2PriorKnowledge,
 }
 
 /// Configurable options for the downloader
@@ -60,6 +75,10 @@ pub struct DownloaderConfig {
     /// Whether to use system proxy settings if available
     pub use_system_proxy: bool,
 
+    /// Proxy settings for the media requests of a protocol: the FLV stream and the HLS and
+    /// DASH segments. Playlists, manifests and keys keep `proxy` and `use_system_proxy`.
+    pub proxy_overrides: HashMap<ProtocolType, ProxyOverride>,
+
     pub danger_accept_invalid_certs: bool, // For reqwest's `danger_accept_invalid_certs`
 
     pub force_ipv4: bool,
@@ -86,6 +105,60 @@ pub struct DownloaderConfig {
     /// Longer timeouts improve connection reuse for streaming
     /// Default: 30 seconds
     pub pool_idle_timeout: Duration,
+
+    /// Connections opened to the segment host when the first media playlist of an HLS
+    /// download is parsed, so the first segments skip the connection setup.
+    /// Over HTTP/2 the requests share one connection. Default: 0 (disabled)
+    pub prewarm_connections: usize,
+
+    // --- Bandwidth Configuration ---
+    /// Maximum throughput of each download in bytes per second (None = unlimited)
+    pub max_bytes_per_sec: Option<u64>,
+
+    /// Limit shared with the other downloads of the same `DownloadManager`
+    pub shared_rate_limiter: Option<RateLimiter>,
+
+    /// Callback receiving the throughput of each download
+    pub on_progress: Option<OnProgress>,
+
+    /// How often throughput is reported to `on_progress`
+    /// Default: 1 second
+    pub progress_interval: Duration,
+
+    // --- Retry Configuration ---
+    /// Retries of failed requests: the FLV connection, HLS playlists, segments and keys.
+    /// HLS segment and key downloads take their attempt count and delays from
+    /// `HlsFetcherConfig` and the remaining settings from this policy.
+    pub retry_policy: RetryPolicy,
+
+    // --- Stall Detection ---
+    /// Detection of streams that stay connected without media progress (None = disabled).
+    /// `FlvProtocolConfig` and `HlsConfig` may override it.
+    pub stall_config: Option<StallConfig>,
+
+    // --- Authentication ---
+    /// Keep the cookies set by the origin and send them with the following requests.
+    /// A `Cookie` entry of `headers` is moved into the store, scoped to the host of the
+    /// first request.
+    pub cookie_store: bool,
+
+    /// Cookies sent with every request. Enables the cookie store when not empty.
+    pub cookies: Vec<Cookie>,
+
+    /// Hook renewing the credentials of a download when the origin rejects them with
+    /// `401`/`403` or when their lifetime elapses (None = disabled). Enables the cookie
+    /// store, which receives the cookies it returns.
+    pub auth_refresh: Option<AuthRefresh>,
+
+    // --- Raw Copy ---
+    /// Copy of the bytes received from the origin before any protocol parsing: the FLV
+    /// body, and each HLS segment and playlist snapshot (None = disabled)
+    pub raw_tee: Option<RawTee>,
+
+    // --- HTTP Client ---
+    /// Clients sending the requests in place of the ones built from this configuration
+    /// (None = built). See [`ClientProvider`] for the settings they replace.
+    pub client_provider: Option<Arc<dyn ClientProvider>>,
 }
 
 impl Default for DownloaderConfig {
@@ -102,6 +175,7 @@ impl Default for DownloaderConfig {
             params: Vec::new(),
             proxy: None,
             use_system_proxy: true,
+            proxy_overrides: HashMap::new(),
             danger_accept_invalid_certs: false,
             force_ipv4: false,
             force_ipv6: false,
@@ -111,6 +185,19 @@ impl Default for DownloaderConfig {
             // Connection pool defaults - optimized for HLS segment downloads
             pool_max_idle_per_host: 10,
             pool_idle_timeout: Duration::from_secs(30),
+            prewarm_connections: 0,
+            // Bandwidth defaults - unlimited
+            max_bytes_per_sec: None,
+            shared_rate_limiter: None,
+            on_progress: None,
+            progress_interval: Duration::from_secs(1),
+            retry_policy: RetryPolicy::default(),
+            stall_config: None,
+            cookie_store: false,
+            cookies: Vec::new(),
+            auth_refresh: None,
+            raw_tee: None,
+            client_provider: None,
         }
     }
 }
@@ -143,6 +230,7 @@ impl DownloaderConfig {
             params: config.params,
             proxy: config.proxy,
             use_system_proxy: config.use_system_proxy,
+            proxy_overrides: config.proxy_overrides,
             danger_accept_invalid_certs: config.danger_accept_invalid_certs,
             force_ipv4: config.force_ipv4,
             force_ipv6: config.force_ipv6,
@@ -152,9 +240,40 @@ impl DownloaderConfig {
             // Connection pool settings
             pool_max_idle_per_host: config.pool_max_idle_per_host,
             pool_idle_timeout: config.pool_idle_timeout,
+            prewarm_connections: config.prewarm_connections,
+            // Bandwidth settings
+            max_bytes_per_sec: config.max_bytes_per_sec,
+            shared_rate_limiter: config.shared_rate_limiter,
+            on_progress: config.on_progress,
+            progress_interval: config.progress_interval,
+            retry_policy: config.retry_policy,
+            stall_config: config.stall_config,
+            cookie_store: config.cookie_store,
+            cookies: config.cookies,
+            auth_refresh: config.auth_refresh,
+            raw_tee: config.raw_tee,
+            client_provider: config.client_provider,
         }
     }
 
+    /// Whether requests go through a cookie store
+    pub(crate) fn uses_cookie_store(&self) -> bool {
+        self.cookie_store || !self.cookies.is_empty() || self.auth_refresh.is_some()
+    }
+
+    /// Configuration of the media requests of `protocol`, or `None` when they use the
+    /// configured proxy settings
+    pub(crate) fn media_config(&self, protocol: ProtocolType) -> Option<DownloaderConfig> {
+        let proxy_override = self.proxy_overrides.get(&protocol)?;
+        let mut config = self.clone();
+        (config.proxy, config.use_system_proxy) = match proxy_override {
+            ProxyOverride::Direct => (None, false),
+            ProxyOverride::System => (None, true),
+            ProxyOverride::Proxy(proxy) => (Some(proxy.clone()), false),
+        };
+        Some(config)
+    }
+
     pub fn get_default_headers() -> HeaderMap {
         let mut default_headers = HeaderMap::new();
 
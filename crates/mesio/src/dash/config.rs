@@ -0,0 +1,67 @@
+//! # DASH Protocol Configuration
+//!
+//! This module defines the configuration options specific to DASH downloads.
+
+use std::time::Duration;
+
+use crate::DownloaderConfig;
+use crate::media_protocol::ProtocolConfig;
+
+/// Configuration for DASH downloads
+#[derive(Debug, Clone)]
+pub struct DashConfig {
+    /// Base downloader configuration
+    pub base: DownloaderConfig,
+    /// Which representation of each period is downloaded
+    pub selection_policy: DashRepresentationSelectionPolicy,
+    /// Timeout of a single manifest request
+    pub manifest_fetch_timeout: Duration,
+    /// Timeout of a single segment request
+    pub segment_download_timeout: Duration,
+    /// Lower bound on the interval between reloads of a live manifest
+    pub min_refresh_interval: Duration,
+    /// Number of already available segments a live download starts with
+    pub live_start_segments: usize,
+    /// Consecutive failed reloads of a live manifest before the download fails
+    pub max_refresh_failures: u32,
+}
+
+impl Default for DashConfig {
+    fn default() -> Self {
+        Self::from(DownloaderConfig::default())
+    }
+}
+
+impl From<DownloaderConfig> for DashConfig {
+    fn from(base: DownloaderConfig) -> Self {
+        Self {
+            base,
+            selection_policy: DashRepresentationSelectionPolicy::default(),
+            manifest_fetch_timeout: Duration::from_secs(15),
+            segment_download_timeout: Duration::from_secs(10),
+            min_refresh_interval: Duration::from_millis(500),
+            live_start_segments: 3,
+            max_refresh_failures: 5,
+        }
+    }
+}
+
+impl ProtocolConfig for DashConfig {}
+
+/// How a representation is chosen among those of a period.
+///
+/// Video adaptation sets are preferred, as the output is a single fMP4 stream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DashRepresentationSelectionPolicy {
+    /// The representation with the highest bandwidth
+    #[default]
+    HighestBandwidth,
+    /// The representation with the lowest bandwidth
+    LowestBandwidth,
+    /// The representation with the bandwidth closest to the given bits per second
+    ClosestToBandwidth(u64),
+    /// The highest bandwidth representation of an audio adaptation set
+    AudioOnly,
+    /// The representation with the given resolution, or the highest bandwidth one
+    MatchingResolution { width: u32, height: u32 },
+}
@@ -0,0 +1,781 @@
+//! # DASH Downloader
+//!
+//! Downloads one representation of an MPEG-DASH presentation as a stream of fMP4
+//! initialization and media segments, in the same [`HlsData`] form the HLS downloader
+//! produces so that the fMP4 processing of `hls-fix` applies unchanged.
+//!
+//! Static manifests are downloaded once. Dynamic manifests are reloaded every
+//! `minimumUpdatePeriod` until they become static or the download is cancelled.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::{Bytes, BytesMut};
+use chrono::Utc;
+use futures::StreamExt;
+use hls::{HlsData, SplitReason};
+use m3u8_rs::MediaSegment;
+use reqwest::Client;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+use url::Url;
+
+use super::config::{DashConfig, DashRepresentationSelectionPolicy};
+use super::mpd::{AdaptationSet, Mpd, Period, Representation};
+use super::segment::representation_segments;
+use crate::ProtocolType;
+use crate::downloader::{Clients, create_clients};
+use crate::media_protocol::{MultiSource, ProtocolBase};
+use crate::probe::ProbeHandoff;
+use crate::retry::{RetryAction, retry_with_backoff};
+use crate::source::ContentSource;
+use crate::throttle::{RateLimiter, Throttle};
+use crate::{BoxMediaStream, Download, DownloadError, SourceManager};
+
+/// Segments buffered between the download task and the consumer
+const SEGMENT_CHANNEL_CAPACITY: usize = 8;
+
+/// Reload interval of a live manifest that gives no hint
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone)]
+pub struct DashDownloader {
+    clients: Clients,
+    config: DashConfig,
+    /// Response of a protocol probe, used by the first download of the probed URL
+    probe: ProbeHandoff,
+}
+
+impl DashDownloader {
+    pub fn new(config: DashConfig) -> Result<Self, DownloadError> {
+        Self::with_config(config)
+    }
+
+    /// Create a new DashDownloader with custom configuration
+    pub fn with_config(config: DashConfig) -> Result<Self, DownloadError> {
+        let clients = create_clients(&config.base, ProtocolType::Dash)?;
+        Ok(Self {
+            clients,
+            config,
+            probe: ProbeHandoff::default(),
+        })
+    }
+
+    /// Use the manifest received by a protocol probe instead of requesting its URL again
+    pub(crate) fn with_probe(mut self, probe: ProbeHandoff) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    pub fn config(&self) -> &DashConfig {
+        &self.config
+    }
+
+    /// The client built from the configuration, `None` when clients are provided per source
+    pub fn client(&self) -> Option<&Client> {
+        self.clients.default_client()
+    }
+
+    /// This downloader sending the requests of `source` with the clients provided for it
+    fn for_source(&self, source: &ContentSource) -> Self {
+        Self {
+            clients: self.clients.for_source(source),
+            ..self.clone()
+        }
+    }
+
+    async fn try_download_from_source(
+        &self,
+        source: &ContentSource,
+        source_manager: &mut SourceManager,
+        token: CancellationToken,
+    ) -> Result<BoxMediaStream<HlsData, DownloadError>, DownloadError> {
+        let start_time = Instant::now();
+        match self
+            .for_source(source)
+            .perform_download(&source.url, token)
+            .await
+        {
+            Ok(stream) => {
+                source_manager.record_success(&source.url, start_time.elapsed());
+                Ok(stream)
+            }
+            Err(err) => {
+                source_manager.record_failure(&source.url, &err, start_time.elapsed());
+                warn!(
+                    url = %source.url,
+                    error = %err,
+                    "Failed to download from source"
+                );
+                Err(err)
+            }
+        }
+    }
+
+    /// Load the manifest and start downloading the selected representation
+    pub async fn perform_download(
+        &self,
+        url: &str,
+        token: CancellationToken,
+    ) -> Result<BoxMediaStream<HlsData, DownloadError>, DownloadError> {
+        let manifest_url =
+            Url::parse(url).map_err(|e| DownloadError::invalid_url(url, e.to_string()))?;
+        // The manifest and the segments share the clients provided for the URL
+        let downloader = self.for_source(&ContentSource::new(url, 0));
+        let mpd = match self.probe.take_manifest(url) {
+            Some(body) => parse_manifest(&manifest_url, &body)?,
+            None => downloader.fetch_manifest(&manifest_url, &token).await?,
+        };
+
+        // Fail early when the presentation has nothing this downloader can fetch
+        let first_period = if mpd.is_dynamic() {
+            mpd.periods.len().saturating_sub(1)
+        } else {
+            0
+        };
+        let period = mpd
+            .periods
+            .get(first_period)
+            .ok_or_else(|| DownloadError::Playlist {
+                reason: "MPD has no periods".to_string(),
+            })?;
+        let (set, representation) = select_representation(period, &self.config.selection_policy)
+            .ok_or_else(|| no_representation(first_period))?;
+        info!(
+            url,
+            dynamic = mpd.is_dynamic(),
+            periods = mpd.periods.len(),
+            representation = %representation.id,
+            bandwidth = representation.bandwidth,
+            "Starting DASH download"
+        );
+        representation_segments(
+            &manifest_url,
+            &mpd,
+            first_period,
+            set,
+            representation,
+            Utc::now(),
+        )?;
+
+        let (tx, rx) = mpsc::channel(SEGMENT_CHANNEL_CAPACITY);
+        let session = DashSession {
+            throttle: Throttle::for_download(&self.config.base, url),
+            downloader,
+            manifest_url,
+            tx,
+            token,
+            period_key: None,
+            last_time: None,
+            last_segment_duration: None,
+            started: false,
+        };
+        tokio::spawn(session.run(mpd));
+
+        Ok(ReceiverStream::new(rx).boxed())
+    }
+
+    async fn fetch_manifest(
+        &self,
+        url: &Url,
+        token: &CancellationToken,
+    ) -> Result<Mpd, DownloadError> {
+        let timeout = self.config.manifest_fetch_timeout;
+        let body = self
+            .fetch(
+                &self.clients.client_for_url(url),
+                url,
+                timeout,
+                None,
+                "manifest_fetch",
+                token,
+            )
+            .await?;
+        parse_manifest(url, &body)
+    }
+
+    /// Fetch a resource with `client` and the retry policy, limiting its body with `throttle`
+    async fn fetch(
+        &self,
+        client: &Client,
+        url: &Url,
+        timeout: Duration,
+        throttle: Option<&Throttle>,
+        operation: &'static str,
+        token: &CancellationToken,
+    ) -> Result<Bytes, DownloadError> {
+        let on_progress = self.config.base.on_progress.as_ref();
+        let policy = &self.config.base.retry_policy;
+
+        retry_with_backoff(policy, url.as_str(), on_progress, token, |_| async move {
+            let request = client
+                .get(url.clone())
+                .query(&self.config.base.params)
+                .timeout(timeout);
+            let response = tokio::select! {
+                _ = token.cancelled() => return RetryAction::Fail(DownloadError::Cancelled),
+                response = request.send() => response,
+            };
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => return RetryAction::from_request_error(e),
+            };
+            if let Some(throttle) = throttle {
+                throttle.record_response(&response);
+            }
+            if !response.status().is_success() {
+                let error = DownloadError::from_response(response, operation).await;
+                return RetryAction::from_error(error);
+            }
+
+            let mut body = BytesMut::new();
+            let mut chunks = match throttle {
+                Some(throttle) => throttle.wrap(response.bytes_stream()).boxed(),
+                None => response.bytes_stream().boxed(),
+            };
+            loop {
+                let chunk = tokio::select! {
+                    _ = token.cancelled() => return RetryAction::Fail(DownloadError::Cancelled),
+                    chunk = chunks.next() => chunk,
+                };
+                match chunk {
+                    Some(Ok(chunk)) => body.extend_from_slice(&chunk),
+                    Some(Err(e)) => return RetryAction::from_request_error(e),
+                    None => return RetryAction::Success(body.freeze()),
+                }
+            }
+        })
+        .await
+    }
+}
+
+fn parse_manifest(url: &Url, body: &[u8]) -> Result<Mpd, DownloadError> {
+    let text = std::str::from_utf8(body).map_err(|e| {
+        DownloadError::playlist_parse(None, format!("MPD {url} is not valid UTF-8: {e}"))
+    })?;
+    Mpd::parse(text)
+}
+
+fn no_representation(period_index: usize) -> DownloadError {
+    DownloadError::Playlist {
+        reason: format!(
+            "period {period_index} has no representation matching the selection policy"
+        ),
+    }
+}
+
+/// Choose the representation of a period to download
+pub(crate) fn select_representation<'a>(
+    period: &'a Period,
+    policy: &DashRepresentationSelectionPolicy,
+) -> Option<(&'a AdaptationSet, &'a Representation)> {
+    let sets: Vec<&AdaptationSet> = match policy {
+        DashRepresentationSelectionPolicy::AudioOnly => period
+            .adaptation_sets
+            .iter()
+            .filter(|set| set.is_audio() && !set.is_video())
+            .collect(),
+        _ => {
+            let video: Vec<_> = period
+                .adaptation_sets
+                .iter()
+                .filter(|set| set.is_video())
+                .collect();
+            if video.is_empty() {
+                period.adaptation_sets.iter().collect()
+            } else {
+                video
+            }
+        }
+    };
+    let candidates = sets
+        .into_iter()
+        .flat_map(|set| set.representations.iter().map(move |rep| (set, rep)));
+
+    match policy {
+        DashRepresentationSelectionPolicy::LowestBandwidth => {
+            candidates.min_by_key(|(_, rep)| rep.bandwidth)
+        }
+        DashRepresentationSelectionPolicy::ClosestToBandwidth(target) => {
+            candidates.min_by_key(|(_, rep)| rep.bandwidth.abs_diff(*target))
+        }
+        DashRepresentationSelectionPolicy::MatchingResolution { width, height } => {
+            let candidates: Vec<_> = candidates.collect();
+            candidates
+                .iter()
+                .copied()
+                .filter(|(set, rep)| {
+                    rep.width.or(set.width) == Some(*width)
+                        && rep.height.or(set.height) == Some(*height)
+                })
+                .max_by_key(|(_, rep)| rep.bandwidth)
+                .or_else(|| candidates.into_iter().max_by_key(|(_, rep)| rep.bandwidth))
+        }
+        DashRepresentationSelectionPolicy::HighestBandwidth
+        | DashRepresentationSelectionPolicy::AudioOnly => {
+            candidates.max_by_key(|(_, rep)| rep.bandwidth)
+        }
+    }
+}
+
+/// Identity of a period that survives periods being removed from a live manifest
+fn period_key(mpd: &Mpd, index: usize) -> String {
+    match &mpd.periods[index].id {
+        Some(id) => id.clone(),
+        None => format!("@{}", mpd.period_start(index).as_millis()),
+    }
+}
+
+/// State of one download, owned by its task
+struct DashSession {
+    downloader: DashDownloader,
+    manifest_url: Url,
+    throttle: Throttle,
+    tx: mpsc::Sender<Result<HlsData, DownloadError>>,
+    token: CancellationToken,
+    /// Period the last segment was emitted for
+    period_key: Option<String>,
+    /// Start time of the last segment emitted in the current period
+    last_time: Option<u64>,
+    last_segment_duration: Option<Duration>,
+    /// Whether anything was emitted yet
+    started: bool,
+}
+
+impl DashSession {
+    async fn run(mut self, mut mpd: Mpd) {
+        let mut first_load = true;
+        let mut refresh_failures = 0;
+        loop {
+            match self.process(&mpd, first_load).await {
+                Ok(true) => {}
+                Ok(false) | Err(DownloadError::Cancelled) => return,
+                Err(err) => {
+                    let _ = self.tx.send(Err(err)).await;
+                    return;
+                }
+            }
+            first_load = false;
+            if !mpd.is_dynamic() {
+                debug!(url = %self.manifest_url, "DASH presentation finished");
+                return;
+            }
+
+            let interval = mpd
+                .minimum_update_period
+                .filter(|period| !period.is_zero())
+                .or(self.last_segment_duration)
+                .unwrap_or(DEFAULT_REFRESH_INTERVAL)
+                .max(self.downloader.config.min_refresh_interval);
+            tokio::select! {
+                _ = self.token.cancelled() => return,
+                _ = self.tx.closed() => return,
+                _ = tokio::time::sleep(interval) => {}
+            }
+
+            match self
+                .downloader
+                .fetch_manifest(&self.manifest_url, &self.token)
+                .await
+            {
+                Ok(next) => {
+                    refresh_failures = 0;
+                    mpd = next;
+                }
+                Err(DownloadError::Cancelled) => return,
+                Err(err) => {
+                    refresh_failures += 1;
+                    warn!(
+                        url = %self.manifest_url,
+                        failures = refresh_failures,
+                        error = %err,
+                        "Failed to reload DASH manifest"
+                    );
+                    if refresh_failures > self.downloader.config.max_refresh_failures {
+                        let _ = self.tx.send(Err(err)).await;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emit the segments of `mpd` not emitted yet. Returns false when the consumer is gone.
+    async fn process(&mut self, mpd: &Mpd, first_load: bool) -> Result<bool, DownloadError> {
+        let policy = self.downloader.config.selection_policy.clone();
+        let live_start_segments = self.downloader.config.live_start_segments;
+        // A live download starts at the live edge of the last period
+        let start = if first_load && mpd.is_dynamic() {
+            mpd.periods.len().saturating_sub(1)
+        } else {
+            self.period_key
+                .as_ref()
+                .and_then(|key| (0..mpd.periods.len()).find(|idx| period_key(mpd, *idx) == *key))
+                .unwrap_or(0)
+        };
+
+        for index in start..mpd.periods.len() {
+            let (set, representation) = select_representation(&mpd.periods[index], &policy)
+                .ok_or_else(|| no_representation(index))?;
+            let segments = representation_segments(
+                &self.manifest_url,
+                mpd,
+                index,
+                set,
+                representation,
+                Utc::now(),
+            )?;
+
+            let key = period_key(mpd, index);
+            let mut discontinuity = false;
+            if self.period_key.as_ref() != Some(&key) {
+                if self.started {
+                    debug!(period = %key, "DASH period changed");
+                    let marker = HlsData::end_marker_with_reason(SplitReason::Discontinuity);
+                    if !self.emit(marker).await {
+                        return Ok(false);
+                    }
+                    discontinuity = true;
+                }
+                self.period_key = Some(key);
+                self.last_time = None;
+
+                if let Some(init_url) = &segments.init_url {
+                    let data = self.fetch_segment(init_url).await?;
+                    let segment = MediaSegment {
+                        uri: init_url.to_string(),
+                        ..MediaSegment::default()
+                    };
+                    if !self.emit(HlsData::mp4_init(segment, data)).await {
+                        return Ok(false);
+                    }
+                }
+            }
+
+            let pending: Vec<_> = segments
+                .segments
+                .iter()
+                .filter(|segment| self.last_time.is_none_or(|time| segment.time > time))
+                .collect();
+            let skip = if first_load && mpd.is_dynamic() && self.last_time.is_none() {
+                pending.len().saturating_sub(live_start_segments)
+            } else {
+                0
+            };
+
+            for segment in pending.into_iter().skip(skip) {
+                let data = self.fetch_segment(&segment.url).await?;
+                let seconds = segments.seconds(segment);
+                let media_segment = MediaSegment {
+                    uri: segment.url.to_string(),
+                    duration: seconds as f32,
+                    discontinuity,
+                    ..MediaSegment::default()
+                };
+                discontinuity = false;
+                if !self.emit(HlsData::mp4_segment(media_segment, data)).await {
+                    return Ok(false);
+                }
+                self.last_time = Some(segment.time);
+                self.last_segment_duration = Some(Duration::from_secs_f64(seconds));
+            }
+        }
+        Ok(true)
+    }
+
+    async fn fetch_segment(&self, url: &Url) -> Result<Bytes, DownloadError> {
+        let timeout = self.downloader.config.segment_download_timeout;
+        self.downloader
+            .fetch(
+                &self.downloader.clients.media_client_for_url(url),
+                url,
+                timeout,
+                Some(&self.throttle),
+                "segment_fetch",
+                &self.token,
+            )
+            .await
+    }
+
+    async fn emit(&mut self, data: HlsData) -> bool {
+        self.started = true;
+        self.tx.send(Ok(data)).await.is_ok()
+    }
+}
+
+impl ProtocolBase for DashDownloader {
+    type Config = DashConfig;
+
+    fn new(config: Self::Config) -> Result<Self, DownloadError> {
+        Self::with_config(config)
+    }
+
+    fn set_shared_rate_limiter(&mut self, limiter: RateLimiter) {
+        self.config.base.shared_rate_limiter = Some(limiter);
+    }
+}
+
+impl Download for DashDownloader {
+    type Data = HlsData;
+    type Error = DownloadError;
+    type Stream = BoxMediaStream<Self::Data, Self::Error>;
+
+    async fn download(
+        &self,
+        url: &str,
+        token: CancellationToken,
+    ) -> Result<Self::Stream, DownloadError> {
+        self.perform_download(url, token).await
+    }
+}
+
+impl MultiSource for DashDownloader {
+    async fn download_with_sources(
+        &self,
+        url: &str,
+        source_manager: &mut SourceManager,
+        token: CancellationToken,
+    ) -> Result<Self::Stream, DownloadError> {
+        if !source_manager.has_sources() {
+            source_manager.add_url(url, 0);
+        }
+
+        let mut last_error: Option<DownloadError> = None;
+
+        while let Some(content_source) = source_manager.select_source() {
+            match self
+                .try_download_from_source(&content_source, source_manager, token.clone())
+                .await
+            {
+                Ok(stream) => return Ok(stream),
+                Err(err) => {
+                    last_error = Some(err);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| DownloadError::source_exhausted("No source available")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve MPDs at `/manifest.mpd`, the next one on every request until the last, and
+    /// every other path as a body naming that path
+    async fn spawn_server(manifests: Vec<String>) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("local addr"));
+        let manifests = Arc::new(manifests);
+        let manifest_requests = Arc::new(AtomicUsize::new(0));
+        let requests = Arc::clone(&manifest_requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let manifests = Arc::clone(&manifests);
+                let requests = Arc::clone(&requests);
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let body = if path.starts_with("/manifest.mpd") {
+                        let idx = requests.fetch_add(1, Ordering::SeqCst);
+                        manifests[idx.min(manifests.len() - 1)].clone()
+                    } else {
+                        path
+                    };
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (format!("{base}/manifest.mpd"), manifest_requests)
+    }
+
+    /// Collect the stream as `init:<path>`, `seg:<path>` and `split` entries
+    async fn collect(downloader: &DashDownloader, url: &str) -> Vec<String> {
+        let mut stream = downloader
+            .download(url, CancellationToken::new())
+            .await
+            .expect("download starts");
+        let mut items = Vec::new();
+        while let Some(item) = stream.next().await {
+            let entry = match item.expect("stream item") {
+                HlsData::M4sData(hls::M4sData::InitSegment(init)) => {
+                    format!("init:{}", String::from_utf8_lossy(&init.data))
+                }
+                HlsData::M4sData(hls::M4sData::Segment(segment)) => {
+                    let flag = if segment.segment.discontinuity {
+                        "!"
+                    } else {
+                        ""
+                    };
+                    format!("seg{flag}:{}", String::from_utf8_lossy(&segment.data))
+                }
+                HlsData::EndMarker(_) => "split".to_string(),
+                HlsData::TsData(_) => unreachable!("DASH emits fMP4"),
+            };
+            items.push(entry);
+        }
+        items
+    }
+
+    fn test_config() -> DashConfig {
+        let mut config = DashConfig::default();
+        config.min_refresh_interval = Duration::from_millis(20);
+        config.live_start_segments = 2;
+        config
+    }
+
+    const MULTI_PERIOD_MPD: &str = r#"<MPD type="static" mediaPresentationDuration="PT6S">
+      <Period id="main" duration="PT4S">
+        <AdaptationSet contentType="video">
+          <SegmentTemplate timescale="1" duration="2" initialization="$RepresentationID$/init.mp4"
+                           media="$RepresentationID$/$Number$.m4s"/>
+          <Representation id="low" bandwidth="500000"/>
+          <Representation id="high" bandwidth="2000000"/>
+        </AdaptationSet>
+        <AdaptationSet contentType="audio">
+          <Representation id="aac" bandwidth="128000">
+            <SegmentTemplate timescale="1" duration="2" media="aac/$Number$.m4s"/>
+          </Representation>
+        </AdaptationSet>
+      </Period>
+      <Period id="ad" duration="PT2S">
+        <BaseURL>ad/</BaseURL>
+        <AdaptationSet mimeType="video/mp4">
+          <SegmentTemplate timescale="1" duration="2" initialization="init.mp4"
+                           media="$Number$.m4s"/>
+          <Representation id="only" bandwidth="1000000"/>
+        </AdaptationSet>
+      </Period>
+    </MPD>"#;
+
+    #[test]
+    fn selects_representation_by_policy() {
+        let mpd = Mpd::parse(MULTI_PERIOD_MPD).unwrap();
+        let period = &mpd.periods[0];
+        let selected = |policy| {
+            select_representation(period, &policy)
+                .map(|(_, rep)| rep.id.clone())
+                .unwrap()
+        };
+        assert_eq!(
+            selected(DashRepresentationSelectionPolicy::HighestBandwidth),
+            "high"
+        );
+        assert_eq!(
+            selected(DashRepresentationSelectionPolicy::LowestBandwidth),
+            "low"
+        );
+        assert_eq!(
+            selected(DashRepresentationSelectionPolicy::ClosestToBandwidth(
+                600_000
+            )),
+            "low"
+        );
+        assert_eq!(
+            selected(DashRepresentationSelectionPolicy::AudioOnly),
+            "aac"
+        );
+        assert_eq!(
+            selected(DashRepresentationSelectionPolicy::MatchingResolution {
+                width: 1,
+                height: 1
+            }),
+            "high"
+        );
+    }
+
+    #[tokio::test]
+    async fn downloads_static_periods_with_discontinuity() {
+        let (url, _) = spawn_server(vec![MULTI_PERIOD_MPD.to_string()]).await;
+        let downloader = DashDownloader::new(test_config()).unwrap();
+
+        assert_eq!(
+            collect(&downloader, &url).await,
+            [
+                "init:/high/init.mp4",
+                "seg:/high/1.m4s",
+                "seg:/high/2.m4s",
+                "split",
+                "init:/ad/init.mp4",
+                "seg!:/ad/1.m4s",
+            ]
+        );
+    }
+
+    fn live_mpd(kind: &str, segments: u64) -> String {
+        format!(
+            r#"<MPD type="{kind}" availabilityStartTime="1970-01-01T00:00:00Z"
+                    minimumUpdatePeriod="PT0.02S">
+              <Period id="live" start="PT0S">
+                <AdaptationSet mimeType="video/mp4">
+                  <SegmentTemplate timescale="1000" initialization="init.mp4" media="$Time$.m4s">
+                    <SegmentTimeline><S t="1000" d="1000" r="{}"/></SegmentTimeline>
+                  </SegmentTemplate>
+                  <Representation id="v" bandwidth="1"/>
+                </AdaptationSet>
+              </Period>
+            </MPD>"#,
+            segments - 1
+        )
+    }
+
+    #[tokio::test]
+    async fn follows_live_manifest_from_the_edge_until_it_ends() {
+        let (url, manifest_requests) = spawn_server(vec![
+            live_mpd("dynamic", 5),
+            live_mpd("dynamic", 5),
+            live_mpd("dynamic", 7),
+            live_mpd("static", 8),
+        ])
+        .await;
+        let downloader = DashDownloader::new(test_config()).unwrap();
+
+        assert_eq!(
+            collect(&downloader, &url).await,
+            [
+                "init:/init.mp4",
+                "seg:/4000.m4s",
+                "seg:/5000.m4s",
+                "seg:/6000.m4s",
+                "seg:/7000.m4s",
+                "seg:/8000.m4s",
+            ]
+        );
+        assert_eq!(manifest_requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn rejects_manifest_without_usable_representation() {
+        let (url, _) = spawn_server(vec![
+            r#"<MPD type="static"><Period><AdaptationSet contentType="video"/></Period></MPD>"#
+                .to_string(),
+        ])
+        .await;
+        let downloader = DashDownloader::new(test_config()).unwrap();
+        let result = downloader.download(&url, CancellationToken::new()).await;
+        assert!(
+            matches!(result, Err(DownloadError::Playlist { .. })),
+            "{:?}",
+            result.err()
+        );
+    }
+}
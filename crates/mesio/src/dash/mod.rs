@@ -0,0 +1,11 @@
+// MPEG-DASH downloader, producing the same fMP4 segment stream as the HLS downloader
+
+pub mod config;
+mod dash_downloader;
+pub mod mpd;
+mod segment;
+
+// Re-exports for easier access
+pub use config::{DashConfig, DashRepresentationSelectionPolicy};
+pub use dash_downloader::DashDownloader;
+pub use mpd::Mpd;
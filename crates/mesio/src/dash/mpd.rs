@@ -0,0 +1,653 @@
+//! # MPD Manifest
+//!
+//! A parsed MPEG-DASH Media Presentation Description, reduced to what segment addressing with
+//! `SegmentTemplate` needs. Elements this downloader does not use (content protection, roles,
+//! events, ...) are skipped.
+
+use std::borrow::Cow;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::DownloadError;
+use crate::error::line_at;
+
+/// Whether the presentation is on demand or live
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentationType {
+    #[default]
+    Static,
+    Dynamic,
+}
+
+/// Media Presentation Description
+#[derive(Debug, Clone, Default)]
+pub struct Mpd {
+    pub presentation_type: PresentationType,
+    /// Wall clock time of the start of the first period of a live presentation
+    pub availability_start_time: Option<DateTime<Utc>>,
+    pub media_presentation_duration: Option<Duration>,
+    /// How often a live manifest should be reloaded
+    pub minimum_update_period: Option<Duration>,
+    /// How long live segments stay available after their end
+    pub time_shift_buffer_depth: Option<Duration>,
+    pub base_url: Option<String>,
+    pub periods: Vec<Period>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Period {
+    pub id: Option<String>,
+    pub start: Option<Duration>,
+    pub duration: Option<Duration>,
+    pub base_url: Option<String>,
+    pub segment_template: Option<SegmentTemplate>,
+    pub adaptation_sets: Vec<AdaptationSet>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AdaptationSet {
+    pub id: Option<String>,
+    pub content_type: Option<String>,
+    pub mime_type: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub base_url: Option<String>,
+    pub segment_template: Option<SegmentTemplate>,
+    pub representations: Vec<Representation>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Representation {
+    pub id: String,
+    /// Bits per second
+    pub bandwidth: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub codecs: Option<String>,
+    pub mime_type: Option<String>,
+    pub base_url: Option<String>,
+    pub segment_template: Option<SegmentTemplate>,
+    /// Segments are addressed with `SegmentBase` or `SegmentList`, which are not supported
+    pub has_other_addressing: bool,
+}
+
+/// `SegmentTemplate` of one level, attributes missing at this level are inherited
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SegmentTemplate {
+    pub timescale: Option<u64>,
+    pub duration: Option<u64>,
+    pub start_number: Option<u64>,
+    pub presentation_time_offset: Option<u64>,
+    pub initialization: Option<String>,
+    pub media: Option<String>,
+    pub timeline: Option<Vec<TimelineEntry>>,
+}
+
+/// `S` element of a `SegmentTimeline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimelineEntry {
+    /// Start time, continuing from the previous entry when absent
+    pub t: Option<u64>,
+    pub d: u64,
+    /// Additional repetitions, -1 repeats until the next entry or the end of the period
+    pub r: i64,
+}
+
+impl SegmentTemplate {
+    /// Fill the attributes missing at this level from the enclosing level
+    pub fn inherit(&self, parent: &SegmentTemplate) -> SegmentTemplate {
+        SegmentTemplate {
+            timescale: self.timescale.or(parent.timescale),
+            duration: self.duration.or(parent.duration),
+            start_number: self.start_number.or(parent.start_number),
+            presentation_time_offset: self
+                .presentation_time_offset
+                .or(parent.presentation_time_offset),
+            initialization: self
+                .initialization
+                .clone()
+                .or_else(|| parent.initialization.clone()),
+            media: self.media.clone().or_else(|| parent.media.clone()),
+            timeline: self.timeline.clone().or_else(|| parent.timeline.clone()),
+        }
+    }
+}
+
+impl AdaptationSet {
+    /// Whether the set carries video, from its content type or MIME types
+    pub fn is_video(&self) -> bool {
+        self.has_kind("video")
+    }
+
+    /// Whether the set carries audio only
+    pub fn is_audio(&self) -> bool {
+        self.has_kind("audio")
+    }
+
+    fn has_kind(&self, kind: &str) -> bool {
+        self.content_type.as_deref() == Some(kind)
+            || self
+                .mime_type
+                .as_deref()
+                .is_some_and(|mime| mime.starts_with(kind))
+            || self.representations.iter().any(|rep| {
+                rep.mime_type
+                    .as_deref()
+                    .is_some_and(|mime| mime.starts_with(kind))
+            })
+    }
+}
+
+impl Mpd {
+    pub fn is_dynamic(&self) -> bool {
+        self.presentation_type == PresentationType::Dynamic
+    }
+
+    /// Start of a period relative to the start of the presentation
+    pub fn period_start(&self, index: usize) -> Duration {
+        let mut start = Duration::ZERO;
+        for (idx, period) in self.periods.iter().enumerate().take(index + 1) {
+            if let Some(period_start) = period.start {
+                start = period_start;
+            }
+            if idx < index {
+                start += period.duration.unwrap_or_default();
+            }
+        }
+        start
+    }
+
+    /// Length of a period, when the manifest defines it
+    pub fn period_duration(&self, index: usize) -> Option<Duration> {
+        let period = self.periods.get(index)?;
+        if let Some(duration) = period.duration {
+            return Some(duration);
+        }
+        let start = self.period_start(index);
+        match self.periods.get(index + 1).and_then(|next| next.start) {
+            Some(next_start) => Some(next_start.saturating_sub(start)),
+            None => self
+                .media_presentation_duration
+                .map(|total| total.saturating_sub(start)),
+        }
+    }
+
+    /// Parse an MPD document
+    pub fn parse(xml: &str) -> Result<Self, DownloadError> {
+        let mut reader = Reader::from_str(xml);
+        reader.config_mut().trim_text(true);
+
+        Self::read(&mut reader)
+            .map_err(|e| at_line(e, xml, reader.buffer_position()))?
+            .ok_or_else(|| manifest_error("document has no MPD element"))
+    }
+
+    fn read(reader: &mut Reader<&[u8]>) -> Result<Option<Self>, DownloadError> {
+        let mut mpd: Option<Mpd> = None;
+        let mut scope = Vec::new();
+        let mut base_url: Option<String> = None;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| manifest_error(format!("invalid MPD XML: {e}")))?;
+            match event {
+                Event::Start(element) => {
+                    let name = element.local_name().as_ref().to_vec();
+                    if name == b"BaseURL" {
+                        base_url = Some(String::new());
+                    } else {
+                        open_element(&mut mpd, &scope, &name, &element)?;
+                    }
+                    scope.push(name);
+                }
+                Event::Empty(element) => {
+                    let name = element.local_name().as_ref().to_vec();
+                    open_element(&mut mpd, &scope, &name, &element)?;
+                }
+                Event::Text(text) => {
+                    if let Some(url) = &mut base_url {
+                        url.push_str(&unescape(&text)?);
+                    }
+                }
+                Event::CData(text) => {
+                    if let Some(url) = &mut base_url {
+                        url.push_str(&String::from_utf8_lossy(&text));
+                    }
+                }
+                Event::GeneralRef(reference) => {
+                    if let Some(url) = &mut base_url {
+                        let name = String::from_utf8_lossy(&reference);
+                        url.push_str(&unescape(format!("&{name};").as_bytes())?);
+                    }
+                }
+                Event::End(_) => {
+                    let name = scope.pop().unwrap_or_default();
+                    if name == b"BaseURL"
+                        && let (Some(mpd), Some(url)) = (&mut mpd, base_url.take())
+                    {
+                        set_base_url(mpd, &scope, url.trim().to_string());
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        Ok(mpd)
+    }
+}
+
+fn manifest_error(reason: impl Into<String>) -> DownloadError {
+    DownloadError::playlist_parse(None, reason)
+}
+
+/// Point a parse error at the line of `xml` the reader stopped at
+fn at_line(error: DownloadError, xml: &str, position: u64) -> DownloadError {
+    match error {
+        DownloadError::PlaylistParse { line: None, reason } => {
+            let line = line_at(
+                xml.as_bytes(),
+                usize::try_from(position).unwrap_or(usize::MAX),
+            );
+            DownloadError::playlist_parse(Some(line), reason)
+        }
+        error => error,
+    }
+}
+
+fn unescape(raw: &[u8]) -> Result<Cow<'static, str>, DownloadError> {
+    let text = std::str::from_utf8(raw)
+        .map_err(|e| manifest_error(format!("MPD is not valid UTF-8: {e}")))?;
+    quick_xml::escape::unescape(text)
+        .map(|text| Cow::Owned(text.into_owned()))
+        .map_err(|e| manifest_error(format!("invalid MPD escape sequence: {e}")))
+}
+
+/// Attributes of an element by local name
+fn attributes(element: &BytesStart<'_>) -> Result<Vec<(String, String)>, DownloadError> {
+    element
+        .attributes()
+        .map(|attr| {
+            let attr = attr.map_err(|e| manifest_error(format!("invalid MPD attribute: {e}")))?;
+            let name = String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned();
+            Ok((name, unescape(&attr.value)?.into_owned()))
+        })
+        .collect()
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn parse_attr<T: std::str::FromStr>(
+    attrs: &[(String, String)],
+    name: &str,
+) -> Result<Option<T>, DownloadError> {
+    attr(attrs, name)
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| manifest_error(format!("invalid MPD attribute {name}=\"{value}\"")))
+        })
+        .transpose()
+}
+
+fn duration_attr(
+    attrs: &[(String, String)],
+    name: &str,
+) -> Result<Option<Duration>, DownloadError> {
+    attr(attrs, name)
+        .map(|value| {
+            parse_duration(value)
+                .ok_or_else(|| manifest_error(format!("invalid MPD duration {name}=\"{value}\"")))
+        })
+        .transpose()
+}
+
+/// Handle the opening of an element inside the current `scope` of element names
+fn open_element(
+    mpd: &mut Option<Mpd>,
+    scope: &[Vec<u8>],
+    name: &[u8],
+    element: &BytesStart<'_>,
+) -> Result<(), DownloadError> {
+    if name == b"MPD" {
+        let attrs = attributes(element)?;
+        *mpd = Some(Mpd {
+            presentation_type: match attr(&attrs, "type") {
+                Some("dynamic") => PresentationType::Dynamic,
+                _ => PresentationType::Static,
+            },
+            availability_start_time: attr(&attrs, "availabilityStartTime")
+                .map(|value| {
+                    parse_date_time(value).ok_or_else(|| {
+                        manifest_error(format!("invalid availabilityStartTime \"{value}\""))
+                    })
+                })
+                .transpose()?,
+            media_presentation_duration: duration_attr(&attrs, "mediaPresentationDuration")?,
+            minimum_update_period: duration_attr(&attrs, "minimumUpdatePeriod")?,
+            time_shift_buffer_depth: duration_attr(&attrs, "timeShiftBufferDepth")?,
+            base_url: None,
+            periods: Vec::new(),
+        });
+        return Ok(());
+    }
+    let Some(mpd) = mpd else {
+        return Ok(());
+    };
+    let parent = scope.last().map(Vec::as_slice);
+
+    match (parent, name) {
+        (Some(b"MPD"), b"Period") => {
+            let attrs = attributes(element)?;
+            mpd.periods.push(Period {
+                id: attr(&attrs, "id").map(str::to_string),
+                start: duration_attr(&attrs, "start")?,
+                duration: duration_attr(&attrs, "duration")?,
+                ..Period::default()
+            });
+        }
+        (Some(b"Period"), b"AdaptationSet") => {
+            let attrs = attributes(element)?;
+            if let Some(period) = mpd.periods.last_mut() {
+                period.adaptation_sets.push(AdaptationSet {
+                    id: attr(&attrs, "id").map(str::to_string),
+                    content_type: attr(&attrs, "contentType").map(str::to_string),
+                    mime_type: attr(&attrs, "mimeType").map(str::to_string),
+                    width: parse_attr(&attrs, "width")?,
+                    height: parse_attr(&attrs, "height")?,
+                    ..AdaptationSet::default()
+                });
+            }
+        }
+        (Some(b"AdaptationSet"), b"Representation") => {
+            let attrs = attributes(element)?;
+            if let Some(set) = last_adaptation_set(mpd) {
+                set.representations.push(Representation {
+                    id: attr(&attrs, "id").unwrap_or_default().to_string(),
+                    bandwidth: parse_attr(&attrs, "bandwidth")?.unwrap_or(0),
+                    width: parse_attr(&attrs, "width")?,
+                    height: parse_attr(&attrs, "height")?,
+                    codecs: attr(&attrs, "codecs").map(str::to_string),
+                    mime_type: attr(&attrs, "mimeType").map(str::to_string),
+                    ..Representation::default()
+                });
+            }
+        }
+        (Some(b"Representation"), b"SegmentBase" | b"SegmentList") => {
+            if let Some(rep) = last_representation(mpd) {
+                rep.has_other_addressing = true;
+            }
+        }
+        (Some(level), b"SegmentTemplate") => {
+            let attrs = attributes(element)?;
+            let template = SegmentTemplate {
+                timescale: parse_attr(&attrs, "timescale")?,
+                duration: parse_attr(&attrs, "duration")?,
+                start_number: parse_attr(&attrs, "startNumber")?,
+                presentation_time_offset: parse_attr(&attrs, "presentationTimeOffset")?,
+                initialization: attr(&attrs, "initialization").map(str::to_string),
+                media: attr(&attrs, "media").map(str::to_string),
+                timeline: None,
+            };
+            if let Some(slot) = template_slot(mpd, level) {
+                *slot = Some(template);
+            }
+        }
+        (Some(b"SegmentTemplate"), b"SegmentTimeline") => {
+            let level = scope.len().checked_sub(2).map(|idx| scope[idx].as_slice());
+            if let Some(Some(template)) = level.and_then(|level| template_slot(mpd, level)) {
+                template.timeline = Some(Vec::new());
+            }
+        }
+        (Some(b"SegmentTimeline"), b"S") => {
+            let attrs = attributes(element)?;
+            let entry = TimelineEntry {
+                t: parse_attr(&attrs, "t")?,
+                d: parse_attr(&attrs, "d")?
+                    .ok_or_else(|| manifest_error("SegmentTimeline entry without duration"))?,
+                r: parse_attr(&attrs, "r")?.unwrap_or(0),
+            };
+            let level = scope.len().checked_sub(3).map(|idx| scope[idx].as_slice());
+            if let Some(Some(template)) = level.and_then(|level| template_slot(mpd, level))
+                && let Some(timeline) = &mut template.timeline
+            {
+                timeline.push(entry);
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn last_adaptation_set(mpd: &mut Mpd) -> Option<&mut AdaptationSet> {
+    mpd.periods.last_mut()?.adaptation_sets.last_mut()
+}
+
+fn last_representation(mpd: &mut Mpd) -> Option<&mut Representation> {
+    last_adaptation_set(mpd)?.representations.last_mut()
+}
+
+/// The segment template of the innermost element named `level`
+fn template_slot<'a>(mpd: &'a mut Mpd, level: &[u8]) -> Option<&'a mut Option<SegmentTemplate>> {
+    match level {
+        b"Period" => Some(&mut mpd.periods.last_mut()?.segment_template),
+        b"AdaptationSet" => Some(&mut last_adaptation_set(mpd)?.segment_template),
+        b"Representation" => Some(&mut last_representation(mpd)?.segment_template),
+        _ => None,
+    }
+}
+
+/// Attach a `BaseURL` to the element enclosing it
+fn set_base_url(mpd: &mut Mpd, scope: &[Vec<u8>], url: String) {
+    let slot = match scope.last().map(Vec::as_slice) {
+        Some(b"MPD") => Some(&mut mpd.base_url),
+        Some(b"Period") => mpd.periods.last_mut().map(|period| &mut period.base_url),
+        Some(b"AdaptationSet") => last_adaptation_set(mpd).map(|set| &mut set.base_url),
+        Some(b"Representation") => last_representation(mpd).map(|rep| &mut rep.base_url),
+        _ => None,
+    };
+    // Only the first of alternative base URLs is used
+    if let Some(slot) = slot
+        && slot.is_none()
+    {
+        *slot = Some(url);
+    }
+}
+
+/// Parse an ISO 8601 duration such as `PT1H2M3.5S` or `P1DT12H`. Years and months have no
+/// fixed length and are rejected.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let rest = value.trim().strip_prefix('P')?;
+    let (date, time) = rest.split_once('T').unwrap_or((rest, ""));
+    let mut secs = 0.0;
+    let mut parse_part = |part: &str, units: &[(char, f64)]| -> Option<()> {
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() || c == '.' {
+                number.push(c);
+                continue;
+            }
+            let (_, factor) = units.iter().find(|(unit, _)| *unit == c)?;
+            secs += number.parse::<f64>().ok()? * factor;
+            number.clear();
+        }
+        number.is_empty().then_some(())
+    };
+    parse_part(date, &[('D', 86_400.0), ('W', 604_800.0)])?;
+    parse_part(time, &[('H', 3_600.0), ('M', 60.0), ('S', 1.0)])?;
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Parse an `xs:dateTime`, taken as UTC when it has no time zone
+pub fn parse_date_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|date| date.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOD_MPD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static"
+     mediaPresentationDuration="PT1M0.5S" minBufferTime="PT2S">
+  <BaseURL>https://cdn.example.com/vod/</BaseURL>
+  <Period id="p0" start="PT0S">
+    <AdaptationSet id="1" contentType="video" mimeType="video/mp4">
+      <SegmentTemplate timescale="90000" initialization="$RepresentationID$/init.mp4"
+                       media="$RepresentationID$/$Time$.m4s">
+        <SegmentTimeline>
+          <S t="0" d="180000" r="2"/>
+          <S d="90000"/>
+        </SegmentTimeline>
+      </SegmentTemplate>
+      <ContentProtection schemeIdUri="urn:mpeg:dash:mp4protection:2011"/>
+      <Representation id="720p" bandwidth="3000000" width="1280" height="720"
+                      codecs="avc1.64001f"/>
+      <Representation id="480p" bandwidth="1200000" width="854" height="480">
+        <BaseURL>low/?token=a&amp;b=1</BaseURL>
+        <SegmentTemplate startNumber="5"/>
+      </Representation>
+    </AdaptationSet>
+    <AdaptationSet id="2" contentType="audio" lang="en">
+      <Representation id="aac" bandwidth="128000" mimeType="audio/mp4">
+        <SegmentBase indexRange="0-100"/>
+      </Representation>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+    #[test]
+    fn parses_periods_sets_and_representations() {
+        let mpd = Mpd::parse(VOD_MPD).unwrap();
+        assert_eq!(mpd.presentation_type, PresentationType::Static);
+        assert_eq!(
+            mpd.media_presentation_duration,
+            Some(Duration::from_millis(60_500))
+        );
+        assert_eq!(
+            mpd.base_url.as_deref(),
+            Some("https://cdn.example.com/vod/")
+        );
+
+        let [period] = mpd.periods.as_slice() else {
+            panic!("expected one period: {mpd:?}");
+        };
+        assert_eq!(period.id.as_deref(), Some("p0"));
+        let [video, audio] = period.adaptation_sets.as_slice() else {
+            panic!("expected two adaptation sets: {period:?}");
+        };
+        assert!(video.is_video() && !video.is_audio());
+        assert!(audio.is_audio() && !audio.is_video());
+        assert!(audio.representations[0].has_other_addressing);
+
+        let template = video.segment_template.as_ref().unwrap();
+        assert_eq!(template.timescale, Some(90_000));
+        assert_eq!(
+            template.timeline.as_deref(),
+            Some(
+                [
+                    TimelineEntry {
+                        t: Some(0),
+                        d: 180_000,
+                        r: 2
+                    },
+                    TimelineEntry {
+                        t: None,
+                        d: 90_000,
+                        r: 0
+                    },
+                ]
+                .as_slice()
+            )
+        );
+
+        let low = &video.representations[1];
+        assert_eq!((low.id.as_str(), low.bandwidth), ("480p", 1_200_000));
+        assert_eq!(low.base_url.as_deref(), Some("low/?token=a&b=1"));
+        let inherited = low.segment_template.as_ref().unwrap().inherit(template);
+        assert_eq!(inherited.start_number, Some(5));
+        assert_eq!(
+            inherited.media.as_deref(),
+            Some("$RepresentationID$/$Time$.m4s")
+        );
+        assert_eq!(inherited.timeline, template.timeline);
+    }
+
+    #[test]
+    fn parses_live_attributes() {
+        let mpd = Mpd::parse(
+            r#"<MPD type="dynamic" availabilityStartTime="2024-05-01T12:00:00Z"
+                    minimumUpdatePeriod="PT2S" timeShiftBufferDepth="PT1M30S">
+                 <Period id="a" start="PT0S" duration="PT10M"/>
+                 <Period id="b"/>
+               </MPD>"#,
+        )
+        .unwrap();
+        assert!(mpd.is_dynamic());
+        assert_eq!(
+            mpd.availability_start_time,
+            parse_date_time("2024-05-01T12:00:00+00:00")
+        );
+        assert_eq!(mpd.minimum_update_period, Some(Duration::from_secs(2)));
+        assert_eq!(mpd.time_shift_buffer_depth, Some(Duration::from_secs(90)));
+        assert_eq!(mpd.period_start(1), Duration::from_secs(600));
+        assert_eq!(mpd.period_duration(0), Some(Duration::from_secs(600)));
+        assert_eq!(mpd.period_duration(1), None);
+    }
+
+    #[test]
+    fn parses_iso_durations_and_dates() {
+        assert_eq!(parse_duration("PT0.5S"), Some(Duration::from_millis(500)));
+        assert_eq!(
+            parse_duration("P1DT1H1M1S"),
+            Some(Duration::from_secs(86_400 + 3_661))
+        );
+        assert_eq!(parse_duration("PT"), Some(Duration::ZERO));
+        assert_eq!(parse_duration("P1M"), None);
+        assert_eq!(parse_duration("1S"), None);
+
+        let date = parse_date_time("2024-05-01T12:00:00").unwrap();
+        assert_eq!(parse_date_time("2024-05-01T12:00:00Z"), Some(date));
+        assert_eq!(parse_date_time("2024-05-01T14:00:00.000+02:00"), Some(date));
+    }
+
+    #[test]
+    fn rejects_documents_without_mpd() {
+        assert!(Mpd::parse("<html><body>offline</body></html>").is_err());
+        assert!(Mpd::parse("<MPD><Period").is_err());
+    }
+
+    #[test]
+    fn reports_line_of_parse_errors() {
+        let error =
+            Mpd::parse("<MPD type=\"static\">\n  <Period>\n  </Wrong>\n</MPD>").unwrap_err();
+        assert!(
+            matches!(error, DownloadError::PlaylistParse { line: Some(3), .. }),
+            "{error:?}"
+        );
+
+        let error = Mpd::parse("<MPD>\n  <Period start=\"soon\"/>\n</MPD>").unwrap_err();
+        assert!(
+            matches!(error, DownloadError::PlaylistParse { line: Some(2), .. }),
+            "{error:?}"
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! # DASH Segment Addressing
+//!
+//! Expands the `SegmentTemplate` of a representation into initialization and media segment
+//! URLs, either from a `SegmentTimeline` or from a fixed segment duration.
+
+use chrono::{DateTime, Utc};
+use url::Url;
+
+use super::mpd::{AdaptationSet, Mpd, Representation, SegmentTemplate};
+use crate::DownloadError;
+
+/// Upper bound on the segments listed for a live representation without `timeShiftBufferDepth`
+const MAX_LIVE_WINDOW_SEGMENTS: u64 = 100;
+
+/// Upper bound on the segments a single `SegmentTimeline` entry may repeat into
+const MAX_TIMELINE_REPEAT: u64 = 100_000;
+
+/// One media segment of a representation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SegmentRef {
+    pub number: u64,
+    /// Start time in timescale units
+    pub time: u64,
+    /// Duration in timescale units
+    pub duration: u64,
+    pub url: Url,
+}
+
+/// Segments of a representation within one period
+#[derive(Debug, Clone)]
+pub(crate) struct RepresentationSegments {
+    pub init_url: Option<Url>,
+    pub timescale: u64,
+    pub segments: Vec<SegmentRef>,
+}
+
+impl RepresentationSegments {
+    /// Duration of a segment in seconds
+    pub fn seconds(&self, segment: &SegmentRef) -> f64 {
+        segment.duration as f64 / self.timescale as f64
+    }
+}
+
+/// Values substituted into a segment template
+struct TemplateVars<'a> {
+    representation_id: &'a str,
+    bandwidth: u64,
+    number: u64,
+    time: u64,
+}
+
+/// Expand the `$...$` identifiers of a segment template
+fn expand_template(template: &str, vars: &TemplateVars<'_>) -> Result<String, DownloadError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find('$').ok_or_else(|| {
+            template_error(format!(
+                "unterminated identifier in template \"{template}\""
+            ))
+        })?;
+        let identifier = &after[..end];
+        rest = &after[end + 1..];
+
+        if identifier.is_empty() {
+            out.push('$');
+            continue;
+        }
+        if identifier == "RepresentationID" {
+            out.push_str(vars.representation_id);
+            continue;
+        }
+
+        let (name, width) = match identifier.split_once('%') {
+            Some((name, format)) => {
+                let width = match format.strip_suffix('d') {
+                    Some("") => Some(0),
+                    Some(width) => width.parse::<usize>().ok(),
+                    None => None,
+                };
+                let width = width.ok_or_else(|| {
+                    template_error(format!("unsupported format in template \"{template}\""))
+                })?;
+                (name, width)
+            }
+            None => (identifier, 0),
+        };
+        let value = match name {
+            "Number" => vars.number,
+            "Time" => vars.time,
+            "Bandwidth" => vars.bandwidth,
+            _ => {
+                return Err(template_error(format!(
+                    "unknown identifier ${identifier}$ in template \"{template}\""
+                )));
+            }
+        };
+        out.push_str(&format!("{value:0width$}"));
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn template_error(reason: String) -> DownloadError {
+    DownloadError::Playlist { reason }
+}
+
+/// Resolve the chain of `BaseURL`s down to a representation
+fn base_url(
+    manifest_url: &Url,
+    mpd: &Mpd,
+    period_index: usize,
+    set: &AdaptationSet,
+    representation: &Representation,
+) -> Result<Url, DownloadError> {
+    let levels = [
+        mpd.base_url.as_deref(),
+        mpd.periods[period_index].base_url.as_deref(),
+        set.base_url.as_deref(),
+        representation.base_url.as_deref(),
+    ];
+    levels
+        .into_iter()
+        .flatten()
+        .try_fold(manifest_url.clone(), |base, relative| {
+            base.join(relative)
+                .map_err(|e| DownloadError::invalid_url(relative, e.to_string()))
+        })
+}
+
+/// The effective segment template of a representation
+pub(crate) fn effective_template(
+    mpd: &Mpd,
+    period_index: usize,
+    set: &AdaptationSet,
+    representation: &Representation,
+) -> Option<SegmentTemplate> {
+    let levels = [
+        mpd.periods[period_index].segment_template.as_ref(),
+        set.segment_template.as_ref(),
+        representation.segment_template.as_ref(),
+    ];
+    levels
+        .into_iter()
+        .flatten()
+        .fold(None, |parent: Option<SegmentTemplate>, template| {
+            Some(match parent {
+                Some(parent) => template.inherit(&parent),
+                None => template.clone(),
+            })
+        })
+}
+
+/// List the segments of a representation that are available at `now`
+pub(crate) fn representation_segments(
+    manifest_url: &Url,
+    mpd: &Mpd,
+    period_index: usize,
+    set: &AdaptationSet,
+    representation: &Representation,
+    now: DateTime<Utc>,
+) -> Result<RepresentationSegments, DownloadError> {
+    let template = effective_template(mpd, period_index, set, representation).ok_or_else(|| {
+        let reason = if representation.has_other_addressing {
+            format!(
+                "representation {} uses SegmentBase or SegmentList, \
+                 only SegmentTemplate is supported",
+                representation.id
+            )
+        } else {
+            format!(
+                "representation {} has no SegmentTemplate",
+                representation.id
+            )
+        };
+        template_error(reason)
+    })?;
+    let media = template.media.as_deref().ok_or_else(|| {
+        template_error(format!(
+            "SegmentTemplate of representation {} has no media attribute",
+            representation.id
+        ))
+    })?;
+
+    let base = base_url(manifest_url, mpd, period_index, set, representation)?;
+    let resolve = |path: String| {
+        base.join(&path)
+            .map_err(|e| DownloadError::invalid_url(path.as_str(), e.to_string()))
+    };
+    let vars = |number, time| TemplateVars {
+        representation_id: &representation.id,
+        bandwidth: representation.bandwidth,
+        number,
+        time,
+    };
+
+    let timescale = template.timescale.unwrap_or(1).max(1);
+    let start_number = template.start_number.unwrap_or(1);
+    let offset = template.presentation_time_offset.unwrap_or(0);
+
+    let init_url = template
+        .initialization
+        .as_deref()
+        .map(|init| resolve(expand_template(init, &vars(start_number, 0))?))
+        .transpose()?;
+
+    // Time since the start of the period, for live presentations
+    let period_start = mpd.period_start(period_index);
+    let live_elapsed = match (mpd.is_dynamic(), mpd.availability_start_time) {
+        (true, Some(ast)) => Some(
+            (now - ast)
+                .to_std()
+                .unwrap_or_default()
+                .saturating_sub(period_start),
+        ),
+        (true, None) => {
+            return Err(template_error(
+                "dynamic MPD has no availabilityStartTime".to_string(),
+            ));
+        }
+        (false, _) => None,
+    };
+    let period_end_ticks = match live_elapsed {
+        Some(elapsed) => Some(elapsed),
+        None => mpd.period_duration(period_index),
+    }
+    .map(|end| offset + (end.as_secs_f64() * timescale as f64) as u64);
+
+    let mut segments = Vec::new();
+    if let Some(timeline) = &template.timeline {
+        let mut time = offset;
+        let mut number = start_number;
+        for (idx, entry) in timeline.iter().enumerate() {
+            if let Some(t) = entry.t {
+                time = t;
+            }
+            if entry.d == 0 {
+                continue;
+            }
+            let repeat = if entry.r >= 0 {
+                entry.r as u64
+            } else {
+                // Repeat up to the next explicit start, or the end of the period
+                let end = timeline
+                    .get(idx + 1)
+                    .and_then(|next| next.t)
+                    .or(period_end_ticks);
+                match end {
+                    Some(end) if end > time => (end - time).div_ceil(entry.d) - 1,
+                    _ => 0,
+                }
+            };
+            for _ in 0..=repeat.min(MAX_TIMELINE_REPEAT) {
+                let url = resolve(expand_template(media, &vars(number, time))?)?;
+                segments.push(SegmentRef {
+                    number,
+                    time,
+                    duration: entry.d,
+                    url,
+                });
+                time += entry.d;
+                number += 1;
+            }
+        }
+    } else {
+        let duration = template.duration.filter(|d| *d > 0).ok_or_else(|| {
+            template_error(format!(
+                "SegmentTemplate of representation {} has neither a timeline nor a duration",
+                representation.id
+            ))
+        })?;
+        let segment_secs = duration as f64 / timescale as f64;
+        let (first, end) = match live_elapsed {
+            Some(elapsed) => {
+                // Only segments that have completely elapsed are available
+                let mut end = (elapsed.as_secs_f64() / segment_secs).floor() as u64;
+                if let Some(period_duration) = mpd.period_duration(period_index) {
+                    end = end.min((period_duration.as_secs_f64() / segment_secs).ceil() as u64);
+                }
+                let window = match mpd.time_shift_buffer_depth {
+                    Some(depth) => (depth.as_secs_f64() / segment_secs).floor() as u64,
+                    None => MAX_LIVE_WINDOW_SEGMENTS,
+                };
+                (end.saturating_sub(window), end)
+            }
+            None => {
+                let period_duration = mpd.period_duration(period_index).ok_or_else(|| {
+                    template_error(format!(
+                        "period {period_index} has no duration to number its segments"
+                    ))
+                })?;
+                (
+                    0,
+                    (period_duration.as_secs_f64() / segment_secs).ceil() as u64,
+                )
+            }
+        };
+        for index in first..end {
+            let number = start_number + index;
+            let time = offset + index * duration;
+            let url = resolve(expand_template(media, &vars(number, time))?)?;
+            segments.push(SegmentRef {
+                number,
+                time,
+                duration,
+                url,
+            });
+        }
+    }
+
+    Ok(RepresentationSegments {
+        init_url,
+        timescale,
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    fn segments_of(xml: &str, now: DateTime<Utc>) -> RepresentationSegments {
+        let mpd = Mpd::parse(xml).unwrap();
+        let set = &mpd.periods[0].adaptation_sets[0];
+        let manifest_url = Url::parse("https://example.com/live/manifest.mpd").unwrap();
+        representation_segments(&manifest_url, &mpd, 0, set, &set.representations[0], now).unwrap()
+    }
+
+    #[test]
+    fn expands_template_identifiers() {
+        let vars = TemplateVars {
+            representation_id: "v1",
+            bandwidth: 800_000,
+            number: 42,
+            time: 900,
+        };
+        assert_eq!(
+            expand_template(
+                "$RepresentationID$/$Number%05d$-$Time$-$Bandwidth$$$.m4s",
+                &vars
+            )
+            .unwrap(),
+            "v1/00042-900-800000$.m4s"
+        );
+        assert_eq!(expand_template("seg-$Number%d$", &vars).unwrap(), "seg-42");
+        assert!(expand_template("seg-$Number", &vars).is_err());
+        assert!(expand_template("seg-$SubNumber$", &vars).is_err());
+        assert!(expand_template("seg-$Number%5x$", &vars).is_err());
+    }
+
+    #[test]
+    fn expands_timeline_with_repeats() {
+        let xml = r#"<MPD type="static" mediaPresentationDuration="PT10S">
+          <Period>
+            <AdaptationSet>
+              <BaseURL>video/</BaseURL>
+              <SegmentTemplate timescale="1000" startNumber="10"
+                               initialization="init-$RepresentationID$.mp4"
+                               media="$RepresentationID$-$Number$-$Time$.m4s">
+                <SegmentTimeline>
+                  <S t="0" d="2000" r="1"/>
+                  <S d="1000"/>
+                  <S t="5000" d="2500" r="-1"/>
+                </SegmentTimeline>
+              </SegmentTemplate>
+              <Representation id="hd" bandwidth="1000"/>
+            </AdaptationSet>
+          </Period>
+        </MPD>"#;
+        let segments = segments_of(xml, Utc::now());
+
+        assert_eq!(
+            segments.init_url.as_ref().map(Url::as_str),
+            Some("https://example.com/live/video/init-hd.mp4")
+        );
+        let listed: Vec<_> = segments
+            .segments
+            .iter()
+            .map(|s| (s.number, s.time, s.duration))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                (10, 0, 2000),
+                (11, 2000, 2000),
+                (12, 4000, 1000),
+                (13, 5000, 2500),
+                (14, 7500, 2500),
+            ]
+        );
+        assert_eq!(
+            segments.segments[4].url.as_str(),
+            "https://example.com/live/video/hd-14-7500.m4s"
+        );
+        assert_eq!(segments.seconds(&segments.segments[4]), 2.5);
+    }
+
+    #[test]
+    fn numbers_static_segments_from_duration() {
+        let xml = r#"<MPD type="static" mediaPresentationDuration="PT9S">
+          <BaseURL>https://cdn.example.com/a/</BaseURL>
+          <Period>
+            <AdaptationSet>
+              <Representation id="r" bandwidth="1">
+                <SegmentTemplate timescale="10" duration="40" media="$Number%03d$.m4s"/>
+              </Representation>
+            </AdaptationSet>
+          </Period>
+        </MPD>"#;
+        let segments = segments_of(xml, Utc::now());
+        let urls: Vec<_> = segments.segments.iter().map(|s| s.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://cdn.example.com/a/001.m4s",
+                "https://cdn.example.com/a/002.m4s",
+                "https://cdn.example.com/a/003.m4s",
+            ]
+        );
+        assert_eq!(segments.init_url, None);
+    }
+
+    #[test]
+    fn numbers_live_segments_within_time_shift_buffer() {
+        let xml = r#"<MPD type="dynamic" availabilityStartTime="2024-01-01T00:00:00Z"
+                          timeShiftBufferDepth="PT8S" minimumUpdatePeriod="PT2S">
+          <Period start="PT10S">
+            <AdaptationSet>
+              <SegmentTemplate timescale="1" duration="2" startNumber="0" media="$Number$.m4s"/>
+              <Representation id="r" bandwidth="1"/>
+            </AdaptationSet>
+          </Period>
+        </MPD>"#;
+        let ast = Mpd::parse(xml).unwrap().availability_start_time.unwrap();
+
+        // 31 seconds into the presentation, 21 into the period: 10 segments are complete
+        let segments = segments_of(xml, ast + TimeDelta::seconds(31));
+        let numbers: Vec<_> = segments.segments.iter().map(|s| s.number).collect();
+        assert_eq!(numbers, [6, 7, 8, 9]);
+
+        // Before the period starts nothing is available
+        let segments = segments_of(xml, ast + TimeDelta::seconds(5));
+        assert!(segments.segments.is_empty());
+    }
+
+    #[test]
+    fn rejects_unsupported_addressing() {
+        let mpd = Mpd::parse(
+            r#"<MPD><Period><AdaptationSet>
+                 <Representation id="r" bandwidth="1">
+                   <SegmentBase indexRange="0-10"/>
+                 </Representation>
+               </AdaptationSet></Period></MPD>"#,
+        )
+        .unwrap();
+        let set = &mpd.periods[0].adaptation_sets[0];
+        let url = Url::parse("https://example.com/a.mpd").unwrap();
+        let err = representation_segments(&url, &mpd, 0, set, &set.representations[0], Utc::now())
+            .unwrap_err();
+        assert!(err.to_string().contains("SegmentBase"), "{err}");
+    }
+}
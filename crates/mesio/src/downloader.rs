@@ -1,12 +1,14 @@
 use reqwest::Client;
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 use std::sync::OnceLock;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use crate::auth::AuthSession;
 use crate::{
-    Cacheable, Download, DownloaderConfig, MultiSource, ProtocolBase, RawDownload, RawResumable,
-    Resumable,
+    Cacheable, Download, DownloaderConfig, MultiSource, ProtocolBase, ProtocolType, RawDownload,
+    RawResumable, Resumable,
 };
 use crate::{DownloadError, proxy::build_proxy_from_config};
 use tokio_util::sync::CancellationToken;
@@ -60,6 +62,10 @@ pub fn create_client(config: &DownloaderConfig) -> Result<Client, DownloadError>
             // Default: let ALPN negotiate (HTTP/2 preferred with rustls)
             debug!("HTTP version: Auto (ALPN negotiation, HTTP/2 preferred)");
         }
+        HttpVersionPreference::Http2PriorKnowledge => {
+            client_builder = client_builder.http2_prior_knowledge();
+            debug!("HTTP/2 prior knowledge mode enabled");
+        }
     }
 
     // --- HTTP/2 Configuration Notes ---
@@ -158,7 +164,7 @@ fn native_tls_hosts_from_env() -> Vec<String> {
         .collect()
 }
 
-fn host_matches_entry(host: &str, entry: &str) -> bool {
+pub(crate) fn host_matches_entry(host: &str, entry: &str) -> bool {
     let entry = entry.trim();
     if entry.is_empty() {
         return false;
@@ -177,32 +183,218 @@ fn host_matches_entry(host: &str, entry: &str) -> bool {
             .ends_with(&format!(".{normalized}").to_ascii_lowercase())
 }
 
+/// Supplies the HTTP clients of downloads in place of the ones built from the
+/// [`DownloaderConfig`], e.g. a client with request signing or tracing middleware.
+///
+/// The provider is asked once for each content source: for every source a multi-source
+/// download tries, and for the URL of a plain download. A [`Client`] is itself a provider
+/// that returns a handle to itself for every source.
+///
+/// The client replaces the connection settings of the configuration, so creating a
+/// downloader fails when the configuration sets a proxy or proxy override, forces an IP
+/// version, accepts invalid certificates or uses a cookie store, and logs a warning when it
+/// sets headers or a user agent, which are not sent. Query parameters and the credentials of
+/// an auth refresh hook are still added to each request, except refreshed cookies.
+pub trait ClientProvider: fmt::Debug + Send + Sync {
+    /// Client of the requests downloading `source`
+    fn client(&self, source: &ContentSource) -> Client;
+}
+
+impl ClientProvider for Client {
+    fn client(&self, _source: &ContentSource) -> Client {
+        self.clone()
+    }
+}
+
+/// The clients of a downloader: built from its configuration, or supplied for each content
+/// source by its [`ClientProvider`]
+#[derive(Debug, Clone)]
+pub(crate) enum Clients {
+    /// Shared by every source
+    Pool(Arc<ClientPool>),
+    /// Not yet resolved to the clients of a source
+    Provided {
+        provider: Arc<dyn ClientProvider>,
+        auth: Arc<AuthSession>,
+    },
+}
+
+impl Clients {
+    /// Clients of a `protocol` download
+    pub(crate) fn for_protocol(
+        config: &DownloaderConfig,
+        protocol: ProtocolType,
+    ) -> Result<Self, DownloadError> {
+        let Some(provider) = &config.client_provider else {
+            return Ok(Self::Pool(Arc::new(ClientPool::for_protocol(
+                config, protocol,
+            )?)));
+        };
+
+        let unsupported = [
+            (config.proxy.is_some(), "proxy"),
+            (!config.proxy_overrides.is_empty(), "proxy_overrides"),
+            (
+                config.force_ipv4 || config.force_ipv6,
+                "force_ipv4/force_ipv6",
+            ),
+            (
+                config.danger_accept_invalid_certs,
+                "danger_accept_invalid_certs",
+            ),
+            (config.cookie_store || !config.cookies.is_empty(), "cookies"),
+        ];
+        let unsupported: Vec<_> = unsupported
+            .into_iter()
+            .filter_map(|(set, option)| set.then_some(option))
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(DownloadError::Configuration {
+                reason: format!(
+                    "{} cannot be applied to a provided HTTP client",
+                    unsupported.join(", ")
+                ),
+            });
+        }
+        if config.user_agent != crate::DEFAULT_USER_AGENT
+            || config.headers != DownloaderConfig::get_default_headers()
+        {
+            warn!(
+                ?protocol,
+                "Configured headers are not sent by the provided HTTP client"
+            );
+        }
+
+        Ok(Self::Provided {
+            provider: Arc::clone(provider),
+            auth: Arc::new(AuthSession::new(config)),
+        })
+    }
+
+    /// Clients of the requests downloading `source`
+    pub(crate) fn pool(&self, source: &ContentSource) -> Arc<ClientPool> {
+        match self {
+            Self::Pool(pool) => Arc::clone(pool),
+            Self::Provided { provider, auth } => Arc::new(ClientPool::provided(
+                provider.client(source),
+                Arc::clone(auth),
+            )),
+        }
+    }
+
+    /// These clients resolved to the ones of `source`
+    pub(crate) fn for_source(&self, source: &ContentSource) -> Self {
+        Self::Pool(self.pool(source))
+    }
+
+    /// Credentials of the requests sent with these clients
+    pub(crate) fn auth(&self) -> &AuthSession {
+        match self {
+            Self::Pool(pool) => pool.auth(),
+            Self::Provided { auth, .. } => auth,
+        }
+    }
+
+    /// The client built from the configuration, when the clients are not provided per source
+    pub(crate) fn default_client(&self) -> Option<&Client> {
+        match self {
+            Self::Pool(pool) => Some(pool.default_client()),
+            Self::Provided { .. } => None,
+        }
+    }
+
+    /// Client of a request to `url`. Unresolved provided clients are asked for `url` as a
+    /// source.
+    pub(crate) fn client_for_url(&self, url: &url::Url) -> Client {
+        match self {
+            Self::Pool(pool) => pool.client_for_url(url).clone(),
+            Self::Provided { provider, .. } => {
+                provider.client(&ContentSource::new(url.as_str(), 0))
+            }
+        }
+    }
+
+    /// Client of a media request to `url`. Unresolved provided clients are asked for `url`
+    /// as a source.
+    pub(crate) fn media_client_for_url(&self, url: &url::Url) -> Client {
+        match self {
+            Self::Pool(pool) => pool.media_client_for_url(url).clone(),
+            Self::Provided { provider, .. } => {
+                provider.client(&ContentSource::new(url.as_str(), 0))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClientPool {
     rustls: Client,
     #[cfg(feature = "tls-native-fallback")]
     native: Client,
     native_hosts: Vec<String>,
+    /// Clients of the media requests, when the protocol overrides their proxy settings
+    media: Option<Box<ClientPool>>,
+    /// Cookies and refreshed credentials, shared with the media clients
+    auth: Arc<AuthSession>,
 }
 
 impl ClientPool {
     pub fn new(config: &DownloaderConfig) -> Result<Self, DownloadError> {
-        let rustls = create_client_with_backend(config, TlsBackend::Rustls)?;
+        Self::with_auth(config, Arc::new(AuthSession::new(config)))
+    }
+
+    fn with_auth(config: &DownloaderConfig, auth: Arc<AuthSession>) -> Result<Self, DownloadError> {
+        let jar = auth.jar();
+        let rustls = create_client_with_backend(config, TlsBackend::Rustls, jar.clone())?;
 
         let mut native_hosts = default_native_tls_hosts();
         native_hosts.extend(native_tls_hosts_from_env());
 
         #[cfg(feature = "tls-native-fallback")]
-        let native = create_client_with_backend(config, TlsBackend::NativeTls)?;
+        let native = create_client_with_backend(config, TlsBackend::NativeTls, jar)?;
 
         Ok(Self {
             rustls,
             #[cfg(feature = "tls-native-fallback")]
             native,
             native_hosts,
+            media: None,
+            auth,
         })
     }
 
+    /// Pool sending every request with a provided `client`
+    fn provided(client: Client, auth: Arc<AuthSession>) -> Self {
+        Self {
+            #[cfg(feature = "tls-native-fallback")]
+            native: client.clone(),
+            rustls: client,
+            native_hosts: Vec::new(),
+            media: None,
+            auth,
+        }
+    }
+
+    /// Clients of a `protocol` download, whose media requests follow the proxy override of
+    /// the protocol
+    pub fn for_protocol(
+        config: &DownloaderConfig,
+        protocol: ProtocolType,
+    ) -> Result<Self, DownloadError> {
+        let mut pool = Self::new(config)?;
+        if let Some(media_config) = config.media_config(protocol) {
+            debug!(?protocol, "Using proxy override for media requests");
+            let media = Self::with_auth(&media_config, Arc::clone(&pool.auth))?;
+            pool.media = Some(Box::new(media));
+        }
+        Ok(pool)
+    }
+
+    /// Credentials of the requests sent with these clients
+    pub(crate) fn auth(&self) -> &AuthSession {
+        &self.auth
+    }
+
     pub fn default_client(&self) -> &Client {
         &self.rustls
     }
@@ -235,6 +427,29 @@ impl ClientPool {
     pub fn client_for_url(&self, url: &url::Url) -> &Client {
         self.client_for_host(url.host_str())
     }
+
+    /// Client of a media request (FLV stream, HLS or DASH segment) to `url`
+    pub fn media_client_for_url(&self, url: &url::Url) -> &Client {
+        self.media.as_deref().unwrap_or(self).client_for_url(url)
+    }
+
+    /// Open up to `count` pooled connections to the host of the media resource `url`.
+    ///
+    /// Sends `count` concurrent `HEAD` requests and waits for them to finish; their
+    /// connections stay in the pool for the following requests. Failures are ignored.
+    pub async fn prewarm(&self, url: &url::Url, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let client = self.media_client_for_url(url);
+        let requests = (0..count).map(|_| client.head(url.clone()).send());
+        let warmed = futures::future::join_all(requests)
+            .await
+            .iter()
+            .filter(|response| response.is_ok())
+            .count();
+        debug!(%url, count, warmed, "Pre-warmed connections");
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -247,22 +462,34 @@ enum TlsBackend {
 fn create_client_with_backend(
     config: &DownloaderConfig,
     backend: TlsBackend,
+    jar: Option<Arc<reqwest::cookie::Jar>>,
 ) -> Result<Client, DownloadError> {
     use crate::config::HttpVersionPreference;
 
     install_rustls_provider();
 
+    let mut headers = config.headers.clone();
+    if jar.is_some() {
+        // A `Cookie` header keeps the store from adding its cookies, so the session moved
+        // them into the store
+        headers.remove(reqwest::header::COOKIE);
+    }
+
     let mut client_builder = Client::builder()
         .pool_max_idle_per_host(config.pool_max_idle_per_host)
         .pool_idle_timeout(config.pool_idle_timeout)
         .user_agent(&config.user_agent)
-        .default_headers(config.headers.clone())
+        .default_headers(headers)
         .redirect(if config.follow_redirects {
             reqwest::redirect::Policy::limited(10)
         } else {
             reqwest::redirect::Policy::none()
         });
 
+    if let Some(jar) = jar {
+        client_builder = client_builder.cookie_provider(jar);
+    }
+
     match backend {
         TlsBackend::Rustls => {
             client_builder = client_builder.use_rustls_tls();
@@ -280,6 +507,9 @@ fn create_client_with_backend(
         HttpVersionPreference::Http1Only => {
             client_builder = client_builder.http1_only();
         }
+        HttpVersionPreference::Http2PriorKnowledge => {
+            client_builder = client_builder.http2_prior_knowledge();
+        }
         HttpVersionPreference::Auto => {}
     }
 
@@ -323,13 +553,29 @@ fn create_client_with_backend(
     client_builder.build().map_err(DownloadError::from)
 }
 
-pub(crate) fn create_client_pool(config: &DownloaderConfig) -> Result<ClientPool, DownloadError> {
-    ClientPool::new(config)
+#[cfg(test)]
+pub(crate) fn create_client_pool(
+    config: &DownloaderConfig,
+    protocol: ProtocolType,
+) -> Result<ClientPool, DownloadError> {
+    ClientPool::for_protocol(config, protocol)
+}
+
+pub(crate) fn create_clients(
+    config: &DownloaderConfig,
+    protocol: ProtocolType,
+) -> Result<Clients, DownloadError> {
+    Clients::for_protocol(config, protocol)
 }
 
+use pipeline_common::ShutdownHandle;
+use pipeline_common::shutdown::DEFAULT_DRAIN_TIMEOUT;
+use std::time::Duration;
+
 use crate::{
     cache::{CacheConfig, CacheManager},
     source::{ContentSource, SourceManager, SourceSelectionStrategy},
+    throttle::RateLimiter,
 };
 
 /// Configuration for the DownloadManager
@@ -343,6 +589,11 @@ pub struct DownloadManagerConfig {
     pub max_retry_count: usize,
     /// Whether to enforce SSL certificate validation
     pub enforce_certificate_validation: bool,
+    /// Maximum combined throughput of the manager's downloads in bytes per second
+    /// (None = unlimited)
+    pub max_bytes_per_sec: Option<u64>,
+    /// How long the items in flight may take to drain after a graceful shutdown
+    pub drain_timeout: Duration,
 }
 
 impl Default for DownloadManagerConfig {
@@ -352,6 +603,8 @@ impl Default for DownloadManagerConfig {
             source_strategy: SourceSelectionStrategy::default(),
             max_retry_count: 3,
             enforce_certificate_validation: true,
+            max_bytes_per_sec: None,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
         }
     }
 }
@@ -365,7 +618,6 @@ pub struct DownloadManager<P> {
     /// Optional cache manager
     cache_manager: Option<Arc<CacheManager>>,
     /// Configuration for this download manager
-    #[allow(dead_code)]
     config: DownloadManagerConfig,
     /// Cancellation token
     token: CancellationToken,
@@ -387,7 +639,7 @@ where
 
     /// Create a new download manager with custom configuration
     pub async fn with_config(
-        protocol: P,
+        mut protocol: P,
         config: DownloadManagerConfig,
         token: CancellationToken,
     ) -> Result<Self, DownloadError> {
@@ -398,6 +650,10 @@ where
             None
         };
 
+        if let Some(max_bytes_per_sec) = config.max_bytes_per_sec {
+            protocol.set_shared_rate_limiter(RateLimiter::new(max_bytes_per_sec));
+        }
+
         // Create source manager with the specified strategy
         let source_manager = SourceManager::with_strategy(config.source_strategy.clone());
 
@@ -425,6 +681,15 @@ where
     pub fn add_content_source(&mut self, source: ContentSource) {
         self.source_manager.add_source(source);
     }
+
+    /// Get a handle that stops this manager's downloads gracefully.
+    ///
+    /// Triggering it stops fetching new data; a run driven by
+    /// [`run_until_shutdown`](pipeline_common::run_until_shutdown) then drains the pipeline
+    /// within the configured drain timeout and finalizes its output.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.token.clone()).with_drain_timeout(self.config.drain_timeout)
+    }
 }
 
 // Basic download capability implementation
@@ -552,3 +817,214 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flv::{FlvDownloader, FlvProtocolConfig};
+    use crate::hls::{HlsConfig, HlsDownloader};
+    use crate::proxy::{ProxyConfig, ProxyType};
+    use crate::{DownloaderConfigBuilder, MesioDownloaderFactory};
+    use futures::StreamExt;
+    use hls::HlsData;
+    use parking_lot::Mutex;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Client standing in for a middleware stack that signs every request
+    fn signing_client() -> Client {
+        install_rustls_provider();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-signature", HeaderValue::from_static("ok"));
+        Client::builder().default_headers(headers).build().unwrap()
+    }
+
+    /// Server rejecting requests without the signature of [`signing_client`] with `403`.
+    /// Serves an FLV header at `/live.flv`, a VOD playlist of `segments` TS segments at
+    /// `/live.m3u8` and a TS packet at any other path, recording the paths requested.
+    async fn spawn_signed_server(segments: usize) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("local addr"));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let log = Arc::clone(&log);
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 1024];
+                    loop {
+                        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            match socket.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                            }
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&buf[..end]).to_ascii_lowercase();
+                        buf.drain(..end + 4);
+
+                        let target = head.split(' ').nth(1).unwrap_or_default().to_string();
+                        log.lock().push(target.clone());
+                        let (status, body): (&str, Vec<u8>) = if !head.contains("x-signature: ok") {
+                            ("403 Forbidden", b"unsigned".to_vec())
+                        } else if target.starts_with("/live.m3u8") {
+                            let mut playlist = String::from(
+                                "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:1\n\
+                                 #EXT-X-MEDIA-SEQUENCE:0\n",
+                            );
+                            for i in 0..segments {
+                                playlist.push_str(&format!("#EXTINF:1.0,\nsegment{i}.ts\n"));
+                            }
+                            playlist.push_str("#EXT-X-ENDLIST\n");
+                            ("200 OK", playlist.into_bytes())
+                        } else if target.starts_with("/live.flv") {
+                            let mut flv = b"FLV\x01\x01\x00\x00\x00\x09".to_vec();
+                            flv.extend_from_slice(&[0; 4]);
+                            ("200 OK", flv)
+                        } else {
+                            let mut packet = vec![0xFF; 188];
+                            packet[..4].copy_from_slice(&[0x47, 0x1F, 0xFF, 0x10]);
+                            ("200 OK", packet)
+                        };
+                        let response = format!(
+                            "HTTP/1.1 {status}\r\nContent-Length: {}\r\n\r\n",
+                            body.len()
+                        );
+                        if socket.write_all(response.as_bytes()).await.is_err()
+                            || socket.write_all(&body).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (base, requests)
+    }
+
+    /// Provider signing the requests of the sources whose URL contains `signed`, recording
+    /// the sources it is asked for
+    #[derive(Debug, Default)]
+    struct RecordingProvider {
+        sources: Mutex<Vec<String>>,
+    }
+
+    impl ClientProvider for Arc<RecordingProvider> {
+        fn client(&self, source: &ContentSource) -> Client {
+            self.sources.lock().push(source.url.clone());
+            if source.url.contains("signed") {
+                signing_client()
+            } else {
+                Client::new()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provided_client_sends_flv_requests() {
+        let (base, requests) = spawn_signed_server(0).await;
+        let config = DownloaderConfigBuilder::new()
+            .with_client(signing_client())
+            .build();
+        let downloader = FlvDownloader::with_config(FlvProtocolConfig {
+            base: config,
+            ..FlvProtocolConfig::default()
+        })
+        .unwrap();
+
+        let items: Vec<_> = downloader
+            .download(&format!("{base}/live.flv"), CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(!items.is_empty());
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        assert_eq!(*requests.lock(), ["/live.flv"]);
+    }
+
+    #[tokio::test]
+    async fn test_provided_client_sends_playlist_and_segment_requests() {
+        let (base, requests) = spawn_signed_server(3).await;
+        let factory = MesioDownloaderFactory::new().with_client(signing_client());
+        let manager = factory.create_hls_manager().await.unwrap();
+
+        let items: Vec<_> = manager
+            .download(&format!("{base}/live.m3u8"))
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        let segments = items
+            .iter()
+            .filter(|item| matches!(item, Ok(HlsData::TsData(_))))
+            .count();
+        assert_eq!(segments, 3);
+        let requests = requests.lock();
+        assert!(requests.iter().any(|path| path == "/live.m3u8"));
+        assert!(requests.iter().any(|path| path == "/segment2.ts"));
+    }
+
+    #[tokio::test]
+    async fn test_provider_is_asked_for_each_source() {
+        let (base, _) = spawn_signed_server(0).await;
+        let provider = Arc::new(RecordingProvider::default());
+        let config = DownloaderConfigBuilder::new()
+            .with_client_provider(Arc::clone(&provider))
+            .build();
+        let downloader = FlvDownloader::with_config(FlvProtocolConfig {
+            base: config,
+            ..FlvProtocolConfig::default()
+        })
+        .unwrap();
+        let primary = format!("{base}/live.flv?cdn=primary");
+        let backup = format!("{base}/live.flv?cdn=signed");
+        let mut sources = SourceManager::new();
+        sources.add_url(&primary, 0);
+        sources.add_url(&backup, 1);
+
+        let mut stream = downloader
+            .download_with_sources(&primary, &mut sources, CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert!(matches!(stream.next().await, Some(Ok(_))));
+        assert_eq!(*provider.sources.lock(), [primary, backup]);
+    }
+
+    #[test]
+    fn test_provided_client_rejects_connection_settings() {
+        let proxy = ProxyConfig {
+            url: "http://127.0.0.1:8080".to_string(),
+            proxy_type: ProxyType::Http,
+            auth: None,
+            remote_dns: false,
+            no_proxy: Vec::new(),
+        };
+        let configs = [
+            DownloaderConfigBuilder::new().with_force_ipv4(true),
+            DownloaderConfigBuilder::new().with_proxy(proxy),
+            DownloaderConfigBuilder::new().with_cookie_store(true),
+        ];
+
+        for builder in configs {
+            let config = builder.with_client(signing_client()).build();
+            let error = HlsDownloader::with_config(HlsConfig {
+                base: config,
+                ..HlsConfig::default()
+            })
+            .err()
+            .expect("connection settings rejected");
+            assert!(
+                matches!(error, DownloadError::Configuration { .. }),
+                "{error:?}"
+            );
+        }
+    }
+}
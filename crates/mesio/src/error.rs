@@ -1,5 +1,35 @@
 use flv::error::FlvError;
-use reqwest::StatusCode;
+use reqwest::{Response, StatusCode};
+use std::fmt;
+use std::time::Duration;
+
+use crate::retry::parse_retry_after;
+
+/// Bytes of an error response body kept in [`DownloadError::Http`]
+const BODY_SNIPPET_LEN: usize = 256;
+/// How long reading the body of an error response may take
+const BODY_SNIPPET_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Stage of a download that timed out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// Establishing the connection, TLS handshake included
+    Connect,
+    /// Waiting for the response or for more of its body
+    Read,
+    /// Waiting for a playlist to be fetched or to advance
+    Playlist,
+}
+
+impl fmt::Display for TimeoutPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Connect => "connect",
+            Self::Read => "read",
+            Self::Playlist => "playlist",
+        })
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum DownloadError {
@@ -15,20 +45,37 @@ pub enum DownloadError {
     #[error("failed to detect protocol for URL `{url}`")]
     ProtocolDetectionFailed { url: String },
 
+    #[error(
+        "unknown protocol served by `{url}`: Content-Type {}, body starts with {signature}",
+        .content_type.as_deref().unwrap_or("missing")
+    )]
+    UnknownProtocol {
+        url: String,
+        content_type: Option<String>,
+        signature: String,
+    },
+
     #[error("proxy configuration error: {reason}")]
     ProxyConfiguration { reason: String },
 
     #[error("HTTP request failed: {source}")]
-    Network {
-        #[from]
-        source: reqwest::Error,
-    },
+    Network { source: reqwest::Error },
+
+    #[error("TLS error: {source}")]
+    Tls { source: reqwest::Error },
 
-    #[error("request failed with HTTP {status} during {operation} for {url}")]
-    HttpStatus {
+    #[error(
+        "request failed with HTTP {status} during {operation} for {url}{}",
+        .body_snippet.as_deref().map(|body| format!(": {body}")).unwrap_or_default()
+    )]
+    Http {
         status: StatusCode,
         url: String,
         operation: &'static str,
+        /// Start of the response body, which often explains the failure
+        body_snippet: Option<String>,
+        /// Delay asked for by the `Retry-After` header
+        retry_after: Option<Duration>,
     },
 
     #[error("I/O error: {source}")]
@@ -43,8 +90,23 @@ pub enum DownloadError {
     #[error("playlist error: {reason}")]
     Playlist { reason: String },
 
-    #[error("segment fetch error: {reason}")]
-    SegmentFetch { reason: String, retryable: bool },
+    #[error(
+        "failed to parse playlist{}: {reason}",
+        .line.map(|line| format!(" at line {line}")).unwrap_or_default()
+    )]
+    PlaylistParse {
+        /// Line the parser stopped at, starting at 1
+        line: Option<usize>,
+        reason: String,
+    },
+
+    #[error("segment {seq} failed after {attempts} attempt(s): {source}")]
+    SegmentFailed {
+        /// Media sequence number of the segment
+        seq: u64,
+        attempts: u32,
+        source: Box<DownloadError>,
+    },
 
     #[error("segment processing error: {reason}")]
     SegmentProcess { reason: String },
@@ -52,6 +114,9 @@ pub enum DownloadError {
     #[error("decryption error: {reason}")]
     Decryption { reason: String },
 
+    #[error("unsupported encryption: {reason}")]
+    UnsupportedEncryption { reason: String },
+
     #[error("invalid content for {protocol}: {reason}")]
     InvalidContent {
         protocol: &'static str,
@@ -61,12 +126,22 @@ pub enum DownloadError {
     #[error("configuration error: {reason}")]
     Configuration { reason: String },
 
-    #[error("operation timed out: {reason}")]
-    Timeout { reason: String },
+    #[error("{phase} timed out: {reason}")]
+    Timeout { phase: TimeoutPhase, reason: String },
+
+    #[error("stream stalled: no media progress for {idle_for:?}")]
+    StreamStalled {
+        /// Last media position reached, in milliseconds
+        last_media_ts: Option<u64>,
+        idle_for: Duration,
+    },
 
     #[error("resource not found: {resource}")]
     NotFound { resource: String },
 
+    #[error("refreshing the credentials of {url} failed: {reason}")]
+    AuthRefresh { url: String, reason: String },
+
     #[error("all download sources failed: {reason}")]
     SourceExhausted { reason: String },
 
@@ -102,10 +177,62 @@ impl DownloadError {
         url: impl Into<String>,
         operation: &'static str,
     ) -> Self {
-        Self::HttpStatus {
+        Self::Http {
             status,
             url: url.into(),
             operation,
+            body_snippet: None,
+            retry_after: None,
+        }
+    }
+
+    /// Describe an unsuccessful response, keeping the `Retry-After` it asks for
+    pub fn http_response(response: &Response, operation: &'static str) -> Self {
+        Self::Http {
+            status: response.status(),
+            url: response.url().to_string(),
+            operation,
+            body_snippet: None,
+            retry_after: parse_retry_after(response.headers()),
+        }
+    }
+
+    /// Describe an unsuccessful response like [`Self::http_response`], reading the start of
+    /// its body as well
+    pub async fn from_response(response: Response, operation: &'static str) -> Self {
+        let mut error = Self::http_response(&response, operation);
+        if let Self::Http { body_snippet, .. } = &mut error {
+            *body_snippet = read_body_snippet(response).await;
+        }
+        error
+    }
+
+    pub fn timeout(phase: TimeoutPhase, reason: impl Into<String>) -> Self {
+        Self::Timeout {
+            phase,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn playlist_parse(line: Option<usize>, reason: impl Into<String>) -> Self {
+        Self::PlaylistParse {
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn segment_failed(seq: u64, attempts: u32, source: DownloadError) -> Self {
+        Self::SegmentFailed {
+            seq,
+            attempts,
+            source: Box::new(source),
+        }
+    }
+
+    pub fn auth_refresh(url: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self::AuthRefresh {
+            url: url.into(),
+            reason: reason.into(),
         }
     }
 
@@ -115,20 +242,29 @@ impl DownloadError {
         }
     }
 
+    /// Whether trying again may succeed
     pub fn is_retryable(&self) -> bool {
         match self {
             Self::Cancelled => false,
             Self::InvalidUrl { .. }
             | Self::UnsupportedProtocol { .. }
             | Self::ProtocolDetectionFailed { .. }
+            | Self::UnknownProtocol { .. }
             | Self::ProxyConfiguration { .. }
             | Self::InvalidContent { .. }
             | Self::Configuration { .. }
+            | Self::UnsupportedEncryption { .. }
             | Self::NotFound { .. } => false,
-            Self::HttpStatus { status, .. } => {
+            // The refresh hook already had its chance
+            Self::AuthRefresh { .. } => false,
+            // Certificate and protocol mismatches do not go away on their own
+            Self::Tls { .. } => false,
+            Self::Http { status, .. } => {
                 status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
             }
-            Self::SegmentFetch { retryable, .. } => *retryable,
+            Self::SegmentFailed { source, .. } => source.is_retryable(),
+            // Origins serve truncated playlists now and then
+            Self::PlaylistParse { .. } => true,
             Self::Network { .. }
             | Self::Io { .. }
             | Self::Cache { .. }
@@ -139,26 +275,431 @@ impl DownloadError {
             | Self::FlvDecode { .. }
             | Self::Protocol { .. }
             | Self::Timeout { .. }
+            | Self::StreamStalled { .. }
             | Self::Internal { .. } => true,
         }
     }
 
+    /// Delay the server asked for before trying again, from its `Retry-After` header
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Http { retry_after, .. } => *retry_after,
+            Self::SegmentFailed { source, .. } => source.retry_after(),
+            _ => None,
+        }
+    }
+
+    /// HTTP status of a failed request, including the last attempt of a segment
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Http { status, .. } => Some(*status),
+            Self::SegmentFailed { source, .. } => source.status(),
+            _ => None,
+        }
+    }
+
     pub fn is_non_recoverable_source_error(&self) -> bool {
         match self {
-            Self::HttpStatus { status, .. } => status.is_client_error(),
+            Self::Http { status, .. } => status.is_client_error(),
             Self::InvalidUrl { .. }
             | Self::UnsupportedProtocol { .. }
             | Self::ProtocolDetectionFailed { .. }
+            | Self::UnknownProtocol { .. }
             | Self::InvalidContent { .. }
-            | Self::NotFound { .. } => true,
-            Self::SegmentFetch { retryable, .. } => !retryable,
+            | Self::UnsupportedEncryption { .. }
+            | Self::NotFound { .. }
+            | Self::Tls { .. } => true,
+            Self::SegmentFailed { source, .. } => source.is_non_recoverable_source_error(),
             _ => false,
         }
     }
 }
 
+impl From<reqwest::Error> for DownloadError {
+    fn from(source: reqwest::Error) -> Self {
+        if source.is_timeout() {
+            let phase = if source.is_connect() {
+                TimeoutPhase::Connect
+            } else {
+                TimeoutPhase::Read
+            };
+            Self::timeout(phase, source.to_string())
+        } else if is_tls_failure(&source) {
+            Self::Tls { source }
+        } else {
+            Self::Network { source }
+        }
+    }
+}
+
+/// Whether a request failed in the TLS handshake. reqwest does not expose this, so the
+/// underlying errors are inspected.
+fn is_tls_failure(error: &reqwest::Error) -> bool {
+    if !error.is_connect() {
+        return false;
+    }
+    let mut source = std::error::Error::source(error);
+    while let Some(err) = source {
+        // I/O errors hide the error they wrap from `source`
+        let inner = err
+            .downcast_ref::<std::io::Error>()
+            .and_then(|io| io.get_ref())
+            .map(|inner| inner as &(dyn std::error::Error + 'static));
+        if [Some(err), inner]
+            .into_iter()
+            .flatten()
+            .any(|err| err.is::<rustls::Error>())
+        {
+            return true;
+        }
+        let message = err.to_string().to_ascii_lowercase();
+        if message.contains("certificate") || message.contains("ssl routines") {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Start of the body of an error response, `None` if it is empty or not read in time
+async fn read_body_snippet(mut response: Response) -> Option<String> {
+    let mut body = Vec::new();
+    let read = async {
+        while body.len() < BODY_SNIPPET_LEN {
+            match response.chunk().await {
+                Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+                _ => break,
+            }
+        }
+    };
+    let _ = tokio::time::timeout(BODY_SNIPPET_TIMEOUT, read).await;
+
+    let snippet = String::from_utf8_lossy(&body[..body.len().min(BODY_SNIPPET_LEN)]);
+    let snippet = snippet.trim();
+    (!snippet.is_empty()).then(|| snippet.to_string())
+}
+
+/// Line of `input` holding byte `offset`, starting at 1
+pub(crate) fn line_at(input: &[u8], offset: usize) -> usize {
+    input[..offset.min(input.len())]
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count()
+        + 1
+}
+
 impl From<DownloadError> for FlvError {
     fn from(err: DownloadError) -> Self {
         FlvError::Io(std::io::Error::other(format!("Download error: {err}")))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_error() -> reqwest::Error {
+        reqwest::Client::new().get("not a url").build().unwrap_err()
+    }
+
+    fn reason() -> String {
+        "reason".to_string()
+    }
+
+    /// One error of every variant, with whether it is retryable
+    fn classified_errors() -> Vec<(DownloadError, bool)> {
+        vec![
+            (DownloadError::Cancelled, false),
+            (DownloadError::invalid_url("ftp:", reason()), false),
+            (
+                DownloadError::UnsupportedProtocol {
+                    protocol: "rtmp".to_string(),
+                },
+                false,
+            ),
+            (
+                DownloadError::ProtocolDetectionFailed {
+                    url: "http://localhost/".to_string(),
+                },
+                false,
+            ),
+            (
+                DownloadError::UnknownProtocol {
+                    url: "http://localhost/".to_string(),
+                    content_type: None,
+                    signature: "3c68746d".to_string(),
+                },
+                false,
+            ),
+            (DownloadError::proxy_configuration(reason()), false),
+            (
+                DownloadError::Network {
+                    source: request_error(),
+                },
+                true,
+            ),
+            (
+                DownloadError::Tls {
+                    source: request_error(),
+                },
+                false,
+            ),
+            (
+                DownloadError::http_status(StatusCode::NOT_FOUND, "http://localhost/", "test"),
+                false,
+            ),
+            (
+                DownloadError::http_status(StatusCode::FORBIDDEN, "http://localhost/", "test"),
+                false,
+            ),
+            (
+                DownloadError::http_status(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "http://localhost/",
+                    "test",
+                ),
+                true,
+            ),
+            (
+                DownloadError::http_status(StatusCode::BAD_GATEWAY, "http://localhost/", "test"),
+                true,
+            ),
+            (
+                DownloadError::Io {
+                    source: std::io::Error::other("disk full"),
+                },
+                true,
+            ),
+            (DownloadError::Cache { reason: reason() }, true),
+            (DownloadError::Playlist { reason: reason() }, true),
+            (DownloadError::playlist_parse(Some(3), reason()), true),
+            (
+                DownloadError::segment_failed(
+                    7,
+                    4,
+                    DownloadError::http_status(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        "http://localhost/7.ts",
+                        "segment",
+                    ),
+                ),
+                true,
+            ),
+            (
+                DownloadError::segment_failed(
+                    7,
+                    1,
+                    DownloadError::http_status(
+                        StatusCode::GONE,
+                        "http://localhost/7.ts",
+                        "segment",
+                    ),
+                ),
+                false,
+            ),
+            (DownloadError::SegmentProcess { reason: reason() }, true),
+            (DownloadError::Decryption { reason: reason() }, true),
+            (
+                DownloadError::UnsupportedEncryption { reason: reason() },
+                false,
+            ),
+            (
+                DownloadError::InvalidContent {
+                    protocol: "flv",
+                    reason: reason(),
+                },
+                false,
+            ),
+            (DownloadError::Configuration { reason: reason() }, false),
+            (
+                DownloadError::timeout(TimeoutPhase::Connect, reason()),
+                true,
+            ),
+            (DownloadError::timeout(TimeoutPhase::Read, reason()), true),
+            (
+                DownloadError::timeout(TimeoutPhase::Playlist, reason()),
+                true,
+            ),
+            (
+                DownloadError::StreamStalled {
+                    last_media_ts: Some(1000),
+                    idle_for: Duration::from_secs(30),
+                },
+                true,
+            ),
+            (DownloadError::NotFound { resource: reason() }, false),
+            (
+                DownloadError::auth_refresh("http://localhost/live.m3u8", reason()),
+                false,
+            ),
+            (DownloadError::source_exhausted(reason()), true),
+            (
+                DownloadError::FlvDecode {
+                    source: FlvError::InvalidHeader,
+                },
+                true,
+            ),
+            (DownloadError::Protocol { reason: reason() }, true),
+            (DownloadError::Internal { reason: reason() }, true),
+        ]
+    }
+
+    #[test]
+    fn test_retryability_of_every_variant() {
+        for (error, retryable) in classified_errors() {
+            assert_eq!(error.is_retryable(), retryable, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let rate_limited = DownloadError::Http {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            url: "http://localhost/".to_string(),
+            operation: "test",
+            body_snippet: None,
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        assert_eq!(rate_limited.retry_after(), Some(Duration::from_secs(30)));
+        let segment = DownloadError::segment_failed(1, 2, rate_limited);
+        assert_eq!(segment.retry_after(), Some(Duration::from_secs(30)));
+
+        for (error, _) in classified_errors() {
+            assert_eq!(error.retry_after(), None, "{error:?}");
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let http = DownloadError::Http {
+            status: StatusCode::FORBIDDEN,
+            url: "http://localhost/live.flv".to_string(),
+            operation: "initial_request",
+            body_snippet: Some("token expired".to_string()),
+            retry_after: None,
+        };
+        assert_eq!(
+            http.to_string(),
+            "request failed with HTTP 403 Forbidden during initial_request for \
+             http://localhost/live.flv: token expired"
+        );
+        assert_eq!(
+            DownloadError::timeout(TimeoutPhase::Playlist, "no update for 30s").to_string(),
+            "playlist timed out: no update for 30s"
+        );
+        assert_eq!(
+            DownloadError::playlist_parse(Some(4), "unexpected tag").to_string(),
+            "failed to parse playlist at line 4: unexpected tag"
+        );
+        assert_eq!(
+            DownloadError::playlist_parse(None, "not UTF-8").to_string(),
+            "failed to parse playlist: not UTF-8"
+        );
+        assert_eq!(
+            DownloadError::segment_failed(42, 3, DownloadError::timeout(TimeoutPhase::Read, "5s"))
+                .to_string(),
+            "segment 42 failed after 3 attempt(s): read timed out: 5s"
+        );
+    }
+
+    #[test]
+    fn test_line_at() {
+        let input = b"#EXTM3U\n#EXT-X-VERSION:3\n#EXTINF:bad\n";
+        assert_eq!(line_at(input, 0), 1);
+        assert_eq!(line_at(input, 8), 2);
+        assert_eq!(line_at(input, 30), 3);
+        assert_eq!(line_at(input, 1000), 4);
+    }
+
+    #[tokio::test]
+    async fn test_request_errors_are_classified() {
+        use tokio::io::AsyncReadExt;
+
+        // Accepts the request and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    while matches!(socket.read(&mut buf).await, Ok(n) if n > 0) {}
+                });
+            }
+        });
+        let client = reqwest::Client::new();
+        let error = client
+            .get(format!("http://{addr}/"))
+            .timeout(Duration::from_millis(100))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            DownloadError::from(error),
+            DownloadError::Timeout {
+                phase: TimeoutPhase::Read,
+                ..
+            }
+        ));
+
+        // Nothing listens on a port that was just released
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind")
+            .local_addr()
+            .expect("local addr");
+        let error = client
+            .get(format!("http://{closed}/"))
+            .send()
+            .await
+            .unwrap_err();
+        let error = DownloadError::from(error);
+        assert!(matches!(error, DownloadError::Network { .. }), "{error:?}");
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_from_response_keeps_body_and_retry_after() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!("http://{}/live.m3u8", listener.local_addr().expect("addr"));
+        tokio::spawn(async move {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                return;
+            };
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            let body = "x".repeat(1000);
+            let response = format!(
+                "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 12\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+
+        let response = reqwest::get(&url).await.unwrap();
+        let error = DownloadError::from_response(response, "playlist_fetch").await;
+        let DownloadError::Http {
+            status,
+            url: error_url,
+            body_snippet,
+            ..
+        } = &error
+        else {
+            panic!("expected an HTTP error, got {error:?}");
+        };
+        assert_eq!(*status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(*error_url, url);
+        assert_eq!(
+            body_snippet.as_deref().map(str::len),
+            Some(BODY_SNIPPET_LEN)
+        );
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(12)));
+        assert!(error.is_retryable());
+    }
+}
@@ -1,24 +1,35 @@
+use std::sync::Arc;
+
 use crate::{
     BoxMediaStream, DownloadError, DownloadManager, DownloadManagerConfig,
+    dash::{DashConfig, DashDownloader},
+    downloader::{ClientProvider, create_client},
     flv::{FlvDownloader, FlvProtocolConfig},
     hls::{HlsConfig, HlsDownloader},
+    probe::{self, ProbeHandoff},
+    source::ContentSource,
 };
+use pipeline_common::ShutdownHandle;
+use reqwest::Client;
 use tokio_util::sync::CancellationToken;
+use tracing::warn;
 use url::Url;
 
 /// Protocol type enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ProtocolType {
     /// FLV protocol
     Flv,
     /// HLS protocol
     Hls,
-    /// Auto-detect from URL
+    /// DASH protocol
+    Dash,
+    /// Auto-detect from the response served by the URL
     Auto,
 }
 
 /// Mesio downloader factory for creating appropriate download managers
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MesioDownloaderFactory {
     /// Base download manager configuration
     download_config: DownloadManagerConfig,
@@ -26,6 +37,10 @@ pub struct MesioDownloaderFactory {
     flv_config: FlvProtocolConfig,
     /// HLS protocol configuration
     hls_config: HlsConfig,
+    /// DASH protocol configuration
+    dash_config: DashConfig,
+    /// Clients of every protocol, in place of the ones built from their configurations
+    client_provider: Option<Arc<dyn ClientProvider>>,
     /// Cancellation token
     token: CancellationToken,
 }
@@ -43,6 +58,8 @@ impl MesioDownloaderFactory {
             download_config: DownloadManagerConfig::default(),
             flv_config: FlvProtocolConfig::default(),
             hls_config: HlsConfig::default(),
+            dash_config: DashConfig::default(),
+            client_provider: None,
             token: CancellationToken::new(),
         }
     }
@@ -65,12 +82,57 @@ impl MesioDownloaderFactory {
         self
     }
 
+    /// Set DASH protocol configuration
+    pub fn with_dash_config(mut self, config: DashConfig) -> Self {
+        self.dash_config = config;
+        self
+    }
+
+    /// Send the requests of every protocol with `client`, e.g. one wrapped in signing or
+    /// tracing middleware
+    pub fn with_client(self, client: Client) -> Self {
+        self.with_client_provider(client)
+    }
+
+    /// Send the requests of each content source with the client `provider` returns for it
+    pub fn with_client_provider(mut self, provider: impl ClientProvider + 'static) -> Self {
+        self.client_provider = Some(Arc::new(provider));
+        self
+    }
+
     /// Set cancellation token
     pub fn with_token(mut self, token: CancellationToken) -> Self {
         self.token = token;
         self
     }
 
+    /// The FLV configuration, with the provided clients
+    fn flv_config(&self) -> FlvProtocolConfig {
+        let mut config = self.flv_config.clone();
+        if let Some(provider) = &self.client_provider {
+            config.base.client_provider = Some(Arc::clone(provider));
+        }
+        config
+    }
+
+    /// The HLS configuration, with the provided clients
+    fn hls_config(&self) -> HlsConfig {
+        let mut config = self.hls_config.clone();
+        if let Some(provider) = &self.client_provider {
+            config.base.client_provider = Some(Arc::clone(provider));
+        }
+        config
+    }
+
+    /// The DASH configuration, with the provided clients
+    fn dash_config(&self) -> DashConfig {
+        let mut config = self.dash_config.clone();
+        if let Some(provider) = &self.client_provider {
+            config.base.client_provider = Some(Arc::clone(provider));
+        }
+        config
+    }
+
     /// Detect protocol type from URL
     pub fn detect_protocol(url: &str) -> Result<ProtocolType, DownloadError> {
         // Parse URL
@@ -92,6 +154,10 @@ impl MesioDownloaderFactory {
             return Ok(ProtocolType::Flv);
         }
 
+        if path.ends_with(".mpd") {
+            return Ok(ProtocolType::Dash);
+        }
+
         // Check query parameters that might indicate HLS
         if let Some(query) = url.query() {
             let query = query.to_lowercase();
@@ -111,24 +177,56 @@ impl MesioDownloaderFactory {
         })
     }
 
+    /// Detect the protocol of a URL by requesting it.
+    ///
+    /// The response is handed to the downloader of the detected protocol. When the request
+    /// fails, the protocol is detected from the URL instead.
+    async fn probe_protocol(
+        &self,
+        url: &str,
+    ) -> Result<(ProtocolType, ProbeHandoff), DownloadError> {
+        // Probe with the FLV client, as an FLV response keeps streaming to the downloader
+        let config = self.flv_config();
+        let base = &config.base;
+        let client = match &base.client_provider {
+            Some(provider) => provider.client(&ContentSource::new(url, 0)),
+            None => create_client(base)?,
+        };
+        match probe::probe(&client, url, base, &self.token).await {
+            Ok(probe) => Ok((probe.protocol, probe.into_handoff())),
+            Err(err @ (DownloadError::UnknownProtocol { .. } | DownloadError::Cancelled)) => {
+                Err(err)
+            }
+            Err(err) => {
+                warn!(url, error = %err, "Protocol probe failed, detecting protocol from URL");
+                let protocol = Self::detect_protocol(url).map_err(|_| err)?;
+                Ok((protocol, ProbeHandoff::default()))
+            }
+        }
+    }
+
     /// Create appropriate download manager for the given URL and protocol type
     ///
     /// This uses the factory pattern to avoid dynamic dispatch in hot paths,
     /// returning concrete manager types for better performance.
+    ///
+    /// With [`ProtocolType::Auto`] the URL is requested to detect its protocol from the
+    /// Content-Type and the first bytes of the response, and the first download of the URL
+    /// continues that response instead of sending a new request.
     pub async fn create_for_url(
         &self,
         url: &str,
         protocol_type: ProtocolType,
     ) -> Result<DownloaderInstance, DownloadError> {
         // Detect protocol if Auto is specified
-        let protocol = match protocol_type {
-            ProtocolType::Auto => Self::detect_protocol(url)?,
-            specific => specific,
+        let (protocol, probe) = match protocol_type {
+            ProtocolType::Auto => self.probe_protocol(url).await?,
+            specific => (specific, ProbeHandoff::default()),
         };
 
         match protocol {
             ProtocolType::Flv => {
-                let flv = FlvDownloader::with_config(self.flv_config.clone())?;
+                let flv = FlvDownloader::with_config(self.flv_config())?.with_probe(probe);
                 let manager = DownloadManager::with_config(
                     flv,
                     self.download_config.clone(),
@@ -138,7 +236,7 @@ impl MesioDownloaderFactory {
                 Ok(DownloaderInstance::Flv(Box::new(manager)))
             }
             ProtocolType::Hls => {
-                let hls = HlsDownloader::with_config(self.hls_config.clone())?;
+                let hls = HlsDownloader::with_config(self.hls_config())?.with_probe(probe);
                 let manager = DownloadManager::with_config(
                     hls,
                     self.download_config.clone(),
@@ -147,6 +245,16 @@ impl MesioDownloaderFactory {
                 .await?;
                 Ok(DownloaderInstance::Hls(Box::new(manager)))
             }
+            ProtocolType::Dash => {
+                let dash = DashDownloader::with_config(self.dash_config())?.with_probe(probe);
+                let manager = DownloadManager::with_config(
+                    dash,
+                    self.download_config.clone(),
+                    self.token.clone(),
+                )
+                .await?;
+                Ok(DownloaderInstance::Dash(Box::new(manager)))
+            }
             ProtocolType::Auto => unreachable!(),
         }
     }
@@ -155,7 +263,7 @@ impl MesioDownloaderFactory {
     pub async fn create_flv_manager(
         &self,
     ) -> Result<DownloadManager<FlvDownloader>, DownloadError> {
-        let protocol = FlvDownloader::with_config(self.flv_config.clone())?;
+        let protocol = FlvDownloader::with_config(self.flv_config())?;
         DownloadManager::with_config(protocol, self.download_config.clone(), self.token.clone())
             .await
     }
@@ -164,7 +272,16 @@ impl MesioDownloaderFactory {
     pub async fn create_hls_manager(
         &self,
     ) -> Result<DownloadManager<HlsDownloader>, DownloadError> {
-        let protocol = HlsDownloader::with_config(self.hls_config.clone())?;
+        let protocol = HlsDownloader::with_config(self.hls_config())?;
+        DownloadManager::with_config(protocol, self.download_config.clone(), self.token.clone())
+            .await
+    }
+
+    /// Create a download manager for DASH protocol (direct method for when type is known)
+    pub async fn create_dash_manager(
+        &self,
+    ) -> Result<DownloadManager<DashDownloader>, DownloadError> {
+        let protocol = DashDownloader::with_config(self.dash_config())?;
         DownloadManager::with_config(protocol, self.download_config.clone(), self.token.clone())
             .await
     }
@@ -176,6 +293,7 @@ impl MesioDownloaderFactory {
 pub enum DownloaderInstance {
     Flv(Box<DownloadManager<FlvDownloader>>),
     Hls(Box<DownloadManager<HlsDownloader>>),
+    Dash(Box<DownloadManager<DashDownloader>>),
 }
 
 impl DownloaderInstance {
@@ -184,6 +302,7 @@ impl DownloaderInstance {
         match self {
             Self::Flv(_) => ProtocolType::Flv,
             Self::Hls(_) => ProtocolType::Hls,
+            Self::Dash(_) => ProtocolType::Dash,
         }
     }
 
@@ -192,6 +311,17 @@ impl DownloaderInstance {
         match self {
             Self::Flv(manager) => manager.add_source(url, priority),
             Self::Hls(manager) => manager.add_source(url, priority),
+            Self::Dash(manager) => manager.add_source(url, priority),
+        }
+    }
+
+    /// Get a handle that stops the download gracefully, see
+    /// [`DownloadManager::shutdown_handle`]
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        match self {
+            Self::Flv(manager) => manager.shutdown_handle(),
+            Self::Hls(manager) => manager.shutdown_handle(),
+            Self::Dash(manager) => manager.shutdown_handle(),
         }
     }
 
@@ -209,6 +339,10 @@ impl DownloaderInstance {
                 let stream = manager.download(url).await?;
                 Ok(DownloadStream::Hls(stream))
             }
+            Self::Dash(manager) => {
+                let stream = manager.download(url).await?;
+                Ok(DownloadStream::Hls(stream))
+            }
         }
     }
 
@@ -226,6 +360,10 @@ impl DownloaderInstance {
                 let stream = manager.download_with_sources(url).await?;
                 Ok(DownloadStream::Hls(stream))
             }
+            Self::Dash(manager) => {
+                let stream = manager.download_with_sources(url).await?;
+                Ok(DownloadStream::Hls(stream))
+            }
         }
     }
 }
@@ -235,6 +373,7 @@ impl DownloaderInstance {
 // #[derive(Debug)]
 pub enum DownloadStream {
     Flv(BoxMediaStream<flv::data::FlvData, crate::flv::error::FlvDownloadError>),
+    /// Segments of HLS and DASH downloads
     Hls(BoxMediaStream<hls::HlsData, crate::hls::HlsDownloaderError>),
 }
 
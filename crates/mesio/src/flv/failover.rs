@@ -0,0 +1,405 @@
+//! # Mid-Stream Source Failover
+//!
+//! A multi-source FLV download keeps following its sources once the stream has started. When
+//! the active source fails, or sends nothing for `source_stall_timeout`, the next source is
+//! selected according to the `SourceSelectionStrategy` and the stream is requested from it.
+//!
+//! The new source starts with its own header, script tag and sequence headers, and usually
+//! replays part of what the previous source already delivered. Its output goes through a
+//! [`ResumeFilter`] continuing from the last emitted timestamp, so the pipeline sees a single
+//! stream: the sequence headers of the new source are forwarded in front of its first keyframe
+//! past the handoff point, and tags at already emitted timestamps are dropped. If the new source
+//! carries a different codec configuration, flv-fix splits the output on its sequence headers.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use flv::data::FlvData;
+use futures::StreamExt;
+use pipeline_common::ProgressEvent;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::error::FlvDownloadError;
+use super::flv_downloader::FlvDownloader;
+use super::resume::ResumeFilter;
+use crate::media_protocol::BoxMediaStream;
+use crate::source::{ContentSource, SourceManager};
+use crate::{DownloadError, TimeoutPhase};
+
+/// Continue `stream`, received from `current` and cancelled by `source_token`, from the other
+/// sources of `sources` when it fails
+pub(super) fn follow_sources(
+    downloader: FlvDownloader,
+    sources: SourceManager,
+    current: ContentSource,
+    stream: BoxMediaStream<FlvData, FlvDownloadError>,
+    source_token: CancellationToken,
+    token: CancellationToken,
+) -> BoxMediaStream<FlvData, FlvDownloadError> {
+    let failover = Failover {
+        stall_timeout: downloader.config().source_stall_timeout,
+        downloader,
+        sources,
+        current,
+        stream,
+        last_data_at: Instant::now(),
+        handoff: None,
+        pending: VecDeque::new(),
+        last_timestamp_ms: None,
+        emitted: false,
+        source_token,
+        token,
+        finished: false,
+    };
+    futures::stream::unfold(failover, |mut failover| async move {
+        let item = failover.next().await?;
+        Some((item, failover))
+    })
+    .boxed()
+}
+
+struct Failover {
+    downloader: FlvDownloader,
+    sources: SourceManager,
+    current: ContentSource,
+    stream: BoxMediaStream<FlvData, FlvDownloadError>,
+    /// When the current source last delivered an item or was connected to
+    last_data_at: Instant,
+    /// Applied to the output of a source taken over mid-stream until it re-synchronizes
+    handoff: Option<ResumeFilter>,
+    /// Items released by the handoff filter, not yet emitted
+    pending: VecDeque<FlvData>,
+    /// Latest timestamp of the audio and video tags emitted
+    last_timestamp_ms: Option<u32>,
+    emitted: bool,
+    stall_timeout: Duration,
+    /// Cancels the connection to the current source, a child of `token`
+    source_token: CancellationToken,
+    token: CancellationToken,
+    /// Set after the error ending the stream was emitted
+    finished: bool,
+}
+
+impl Failover {
+    async fn next(&mut self) -> Option<Result<FlvData, FlvDownloadError>> {
+        loop {
+            if let Some(data) = self.pending.pop_front() {
+                return Some(Ok(self.emit(data)));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let (reason, error) =
+                match tokio::time::timeout(self.stall_timeout, self.stream.next()).await {
+                    Ok(Some(Ok(data))) => {
+                        self.last_data_at = Instant::now();
+                        let Some(filter) = &mut self.handoff else {
+                            return Some(Ok(self.emit(data)));
+                        };
+                        self.pending.extend(filter.push(data));
+                        if filter.is_resumed() {
+                            self.handoff = None;
+                        }
+                        continue;
+                    }
+                    Ok(None) => return None,
+                    Ok(Some(Err(err))) => (err.to_string(), DownloadError::from(err)),
+                    Err(_) => {
+                        let reason = format!("no data for {:?}", self.stall_timeout);
+                        (
+                            reason.clone(),
+                            DownloadError::timeout(TimeoutPhase::Read, reason),
+                        )
+                    }
+                };
+
+            if self.token.is_cancelled() {
+                return None;
+            }
+            if let Err(err) = self.switch_source(reason, error).await {
+                self.finished = true;
+                if self.token.is_cancelled() {
+                    return None;
+                }
+                return Some(Err(err));
+            }
+        }
+    }
+
+    fn emit(&mut self, data: FlvData) -> FlvData {
+        if let FlvData::Tag(tag) = &data
+            && (tag.is_audio_tag() || tag.is_video_tag())
+        {
+            self.last_timestamp_ms = Some(
+                self.last_timestamp_ms
+                    .map_or(tag.timestamp_ms, |last| last.max(tag.timestamp_ms)),
+            );
+        }
+        self.emitted = true;
+        data
+    }
+
+    /// Replace the failed current source with the next one that accepts the request
+    async fn switch_source(
+        &mut self,
+        reason: String,
+        error: DownloadError,
+    ) -> Result<(), FlvDownloadError> {
+        let from = self.current.url.clone();
+        warn!(url = %from, reason = %reason, "FLV source failed mid-stream, switching source");
+        self.sources
+            .record_failure(&from, &error, self.last_data_at.elapsed());
+        // Release the connection, a stalled one would otherwise be kept open
+        self.source_token.cancel();
+
+        let mut tried = vec![from.clone()];
+        loop {
+            let excluded: Vec<&str> = tried.iter().map(String::as_str).collect();
+            let Some(source) = self.sources.select_source_excluding(&excluded) else {
+                break;
+            };
+            let source_token = self.token.child_token();
+            match self
+                .downloader
+                .try_download_from_source(&source, &mut self.sources, source_token.clone())
+                .await
+            {
+                Ok(stream) => {
+                    info!(from = %from, to = %source.url, "Switched FLV source");
+                    if let Some(on_progress) = &self.downloader.config().base.on_progress {
+                        on_progress.emit(ProgressEvent::SourceSwitched {
+                            from: Arc::from(from.as_str()),
+                            to: Arc::from(source.url.as_str()),
+                            reason,
+                        });
+                    }
+                    self.stream = stream;
+                    self.handoff = self
+                        .emitted
+                        .then(|| ResumeFilter::after(self.last_timestamp_ms));
+                    self.current = source;
+                    self.source_token = source_token;
+                    self.last_data_at = Instant::now();
+                    return Ok(());
+                }
+                Err(DownloadError::Cancelled) => return Err(DownloadError::Cancelled.into()),
+                Err(_) => tried.push(source.url),
+            }
+        }
+
+        Err(FlvDownloadError::AllSourcesFailed(format!(
+            "no source could take over from {from}: {reason}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MultiSource;
+    use crate::flv::FlvProtocolConfig;
+    use crate::mock_origin::{FlvResponse, MockOrigin};
+    use crate::throttle::OnProgress;
+    use bytes::Bytes;
+    use flv::header::FlvHeader;
+    use flv::tag::{FlvTag, FlvTagType};
+    use flv::writer::FlvWriter;
+    use parking_lot::Mutex;
+    use std::io::Cursor;
+
+    fn tag(tag_type: FlvTagType, timestamp_ms: u32, data: Vec<u8>) -> FlvTag {
+        FlvTag {
+            timestamp_ms,
+            stream_id: 0,
+            tag_type,
+            is_filtered: false,
+            data: Bytes::from(data),
+        }
+    }
+
+    /// A live stream with a keyframe every 600ms, as served by every edge
+    fn live_tags() -> Vec<FlvTag> {
+        let mut tags = vec![
+            tag(FlvTagType::ScriptData, 0, vec![2, 0, 1, b'a']),
+            tag(FlvTagType::Video, 0, vec![0x17, 0, 0, 0, 0]),
+            tag(FlvTagType::Audio, 0, vec![0xAF, 0, 0x12, 0x10]),
+        ];
+        for i in 0..60 {
+            let frame_type = if i % 15 == 0 { 0x17 } else { 0x27 };
+            tags.push(tag(
+                FlvTagType::Video,
+                i * 40,
+                vec![frame_type, 1, 0, 0, 0, i as u8],
+            ));
+            tags.push(tag(
+                FlvTagType::Audio,
+                i * 40 + 20,
+                vec![0xAF, 1, 0x21, i as u8],
+            ));
+        }
+        tags
+    }
+
+    fn encode(tags: &[FlvTag]) -> Vec<u8> {
+        let mut writer = FlvWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_header(&FlvHeader::new(true, true)).unwrap();
+        for tag in tags {
+            writer.write_tag_f(tag).unwrap();
+        }
+        writer.writer.into_inner()
+    }
+
+    /// Download from a primary source ending with `end` after the first `served` tags of the
+    /// live stream, failing over to a backup serving all of it
+    async fn download_with_failover(
+        served: usize,
+        end: fn(FlvResponse, usize) -> FlvResponse,
+    ) -> (Vec<FlvData>, Vec<ProgressEvent>, MockOrigin) {
+        let live = live_tags();
+        let cut = encode(&live[..served]).len();
+        let origin = MockOrigin::builder()
+            .flv("/primary.flv", end(FlvResponse::new(encode(&live)), cut))
+            .flv("/backup.flv", FlvResponse::new(encode(&live)))
+            .spawn()
+            .await;
+        let primary = origin.url("/primary.flv");
+        let backup = origin.url("/backup.flv");
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let mut config = FlvProtocolConfig::builder()
+            .source_stall_timeout(Duration::from_millis(300))
+            .build();
+        config.base.on_progress = Some(OnProgress::new(move |event| sink.lock().push(event)));
+        let downloader = FlvDownloader::with_config(config).unwrap();
+
+        let mut sources = SourceManager::new();
+        sources.add_url(primary.clone(), 0);
+        sources.add_url(backup.clone(), 1);
+        let output: Vec<FlvData> = downloader
+            .download_with_sources(&primary, &mut sources, CancellationToken::new())
+            .await
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+
+        let events = events
+            .lock()
+            .iter()
+            .filter(|event| matches!(event, ProgressEvent::SourceSwitched { .. }))
+            .cloned()
+            .collect();
+        (output, events, origin)
+    }
+
+    fn assert_handoff(output: &[FlvData], served: usize, resync_ms: u32) {
+        let live = live_tags();
+        assert!(matches!(output[0], FlvData::Header(_)));
+        let tags: Vec<&FlvTag> = output[1..]
+            .iter()
+            .map(|data| match data {
+                FlvData::Tag(tag) => tag,
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect();
+
+        // Everything the primary delivered, then the sequence headers of the backup
+        assert_eq!(tags[..served], live[..served].iter().collect::<Vec<_>>());
+        assert!(tags[served].is_video_sequence_header());
+        assert!(tags[served + 1].is_audio_sequence_header());
+        assert_eq!(tags[served].timestamp_ms, resync_ms);
+        assert_eq!(tags[served + 1].timestamp_ms, resync_ms);
+
+        // The backup continues from its next keyframe without replaying emitted tags
+        let resync = live
+            .iter()
+            .position(|tag| tag.is_key_frame() && tag.timestamp_ms == resync_ms)
+            .unwrap();
+        assert_eq!(
+            tags[served + 2..],
+            live[resync..].iter().collect::<Vec<_>>()
+        );
+        let media: Vec<u32> = tags
+            .iter()
+            .filter(|tag| {
+                !tag.is_script_tag()
+                    && !tag.is_video_sequence_header()
+                    && !tag.is_audio_sequence_header()
+            })
+            .map(|tag| tag.timestamp_ms)
+            .collect();
+        assert!(media.windows(2).all(|pair| pair[0] < pair[1]), "{media:?}");
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_when_source_is_killed() {
+        // Script, sequence headers and 20 video/audio pairs up to 780ms
+        let served = 3 + 40;
+        let (output, events, origin) = download_with_failover(served, FlvResponse::reset_at).await;
+
+        assert_handoff(&output, served, 1200);
+        let [ProgressEvent::SourceSwitched { from, to, .. }] = events.as_slice() else {
+            panic!("expected one switch, got {events:?}");
+        };
+        assert_eq!(&**from, origin.url("/primary.flv"));
+        assert_eq!(&**to, origin.url("/backup.flv"));
+        let paths: Vec<_> = origin
+            .requests()
+            .iter()
+            .map(|request| request.path().to_string())
+            .collect();
+        assert_eq!(paths, ["/primary.flv", "/backup.flv"]);
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_when_source_stalls() {
+        let served = 3 + 20;
+        let (output, events, _) = download_with_failover(served, FlvResponse::stall_at).await;
+
+        assert_handoff(&output, served, 600);
+        let [ProgressEvent::SourceSwitched { reason, .. }] = events.as_slice() else {
+            panic!("expected one switch, got {events:?}");
+        };
+        assert!(reason.contains("no data"), "{reason}");
+    }
+
+    #[tokio::test]
+    async fn test_raw_tee_copies_served_body() {
+        use crate::Download;
+        use crate::tee::RawTee;
+        use sha2::{Digest, Sha256};
+
+        let served = encode(&live_tags());
+        let origin = MockOrigin::builder()
+            .flv("/live.flv", FlvResponse::new(served.clone()))
+            .spawn()
+            .await;
+        let url = origin.url("/live.flv");
+        let dir = std::env::temp_dir().join(format!("mesio-flv-tee-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let tee = RawTee::path_template(format!("{}/{{name}}", dir.display()));
+
+        let mut config = FlvProtocolConfig::builder().build();
+        config.base.raw_tee = Some(tee.clone());
+        let downloader = FlvDownloader::with_config(config).unwrap();
+        let output: Vec<FlvData> = downloader
+            .download(&url, CancellationToken::new())
+            .await
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        tee.closed().await;
+
+        // The processed stream completes while the copy holds the body as served
+        assert_eq!(output.len(), 1 + live_tags().len());
+        let raw = std::fs::read(dir.join("live.flv")).unwrap();
+        assert_eq!(Sha256::digest(&raw), Sha256::digest(&served));
+        assert_eq!(tee.bytes_written(), served.len() as u64);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
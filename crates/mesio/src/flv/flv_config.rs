@@ -4,7 +4,10 @@
 
 use crate::DownloaderConfig;
 use crate::media_protocol::ProtocolConfig;
+use crate::watchdog::StallConfig;
+use reqwest::StatusCode;
 use std::fmt::Debug;
+use std::time::{Duration, SystemTime};
 
 /// Configuration for FLV downloads
 #[derive(Debug, Clone)]
@@ -13,15 +16,28 @@ pub struct FlvProtocolConfig {
     pub base: DownloaderConfig,
     /// Buffer size for download chunks (in bytes)
     pub buffer_size: usize,
+    /// Resume into an existing file from its last complete tag instead of a byte offset
+    pub tag_resume: bool,
+    /// How long a source may send no data before a multi-source download fails over
+    pub source_stall_timeout: Duration,
+    /// Stall detection overriding the one of the base configuration
+    pub stall_config: Option<StallConfig>,
+    /// Reconnect a live stream whose connection ends before the stream does
+    pub reconnect: Option<FlvReconnectConfig>,
 }
 
 const DEFAULT_BUFFER_SIZE: usize = 64 * 1024; // 64KB default buffer size
+const DEFAULT_SOURCE_STALL_TIMEOUT: Duration = Duration::from_secs(15);
 
 impl Default for FlvProtocolConfig {
     fn default() -> Self {
         Self {
             base: DownloaderConfig::default(),
             buffer_size: DEFAULT_BUFFER_SIZE,
+            tag_resume: true,
+            source_stall_timeout: DEFAULT_SOURCE_STALL_TIMEOUT,
+            stall_config: None,
+            reconnect: None,
         }
     }
 }
@@ -33,6 +49,10 @@ impl From<DownloaderConfig> for FlvProtocolConfig {
         Self {
             base,
             buffer_size: DEFAULT_BUFFER_SIZE,
+            tag_resume: true,
+            source_stall_timeout: DEFAULT_SOURCE_STALL_TIMEOUT,
+            stall_config: None,
+            reconnect: None,
         }
     }
 }
@@ -42,6 +62,11 @@ impl FlvProtocolConfig {
     pub fn builder() -> FlvProtocolConfigBuilder {
         FlvProtocolConfigBuilder::new()
     }
+
+    /// The stall detection applying to FLV downloads
+    pub fn effective_stall_config(&self) -> Option<StallConfig> {
+        self.stall_config.or(self.base.stall_config)
+    }
 }
 
 /// Builder for FlvProtocolConfig
@@ -49,6 +74,10 @@ impl FlvProtocolConfig {
 pub struct FlvProtocolConfigBuilder {
     base: DownloaderConfig,
     buffer_size: usize,
+    tag_resume: bool,
+    source_stall_timeout: Duration,
+    stall_config: Option<StallConfig>,
+    reconnect: Option<FlvReconnectConfig>,
 }
 
 impl FlvProtocolConfigBuilder {
@@ -57,6 +86,10 @@ impl FlvProtocolConfigBuilder {
         Self {
             base: DownloaderConfig::default(),
             buffer_size: DEFAULT_BUFFER_SIZE,
+            tag_resume: true,
+            source_stall_timeout: DEFAULT_SOURCE_STALL_TIMEOUT,
+            stall_config: None,
+            reconnect: None,
         }
     }
 
@@ -72,11 +105,41 @@ impl FlvProtocolConfigBuilder {
         self
     }
 
+    /// Set whether resuming into an existing file restarts the stream and skips to the
+    /// last complete tag, rather than requesting the remaining byte range
+    pub fn tag_resume(mut self, tag_resume: bool) -> Self {
+        self.tag_resume = tag_resume;
+        self
+    }
+
+    /// Set how long a source may send no data before a multi-source download switches to
+    /// the next source
+    pub fn source_stall_timeout(mut self, timeout: Duration) -> Self {
+        self.source_stall_timeout = timeout;
+        self
+    }
+
+    /// Set the stall detection of FLV downloads, overriding the one of the base configuration
+    pub fn stall_config(mut self, stall_config: StallConfig) -> Self {
+        self.stall_config = Some(stall_config);
+        self
+    }
+
+    /// Reconnect to the origin when the connection of a live stream ends early
+    pub fn reconnect(mut self, reconnect: FlvReconnectConfig) -> Self {
+        self.reconnect = Some(reconnect);
+        self
+    }
+
     /// Build the FlvProtocolConfig
     pub fn build(self) -> FlvProtocolConfig {
         FlvProtocolConfig {
             base: self.base,
             buffer_size: self.buffer_size,
+            tag_resume: self.tag_resume,
+            source_stall_timeout: self.source_stall_timeout,
+            stall_config: self.stall_config,
+            reconnect: self.reconnect,
         }
     }
 }
@@ -86,3 +149,98 @@ impl Default for FlvProtocolConfigBuilder {
         Self::new()
     }
 }
+
+/// How a live FLV download reconnects when its connection ends before the stream does.
+///
+/// A connection closed by the origin or reset mid-stream is a transient drop, unless the
+/// stream is known to have ended: the reconnect request is answered with one of
+/// `end_statuses`, a script tag carried `end_marker`, or `expected_end` has passed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlvReconnectConfig {
+    /// Reconnects made at most over the whole download
+    pub max_reconnects: u32,
+    /// Delay before the first reconnect
+    pub initial_backoff: Duration,
+    /// Cap on the delay before a reconnect
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each reconnect
+    pub multiplier: f64,
+    /// When the stream is expected to end; a drop after it ends the download
+    pub expected_end: Option<SystemTime>,
+    /// Renew the credentials through the auth refresh hook before each reconnect
+    pub refresh_auth: bool,
+    /// Statuses of a reconnect request meaning that the stream has ended
+    pub end_statuses: Vec<StatusCode>,
+    /// Text the origin puts in a script tag, such as onMetaData, once the stream has ended
+    pub end_marker: Option<String>,
+}
+
+impl Default for FlvReconnectConfig {
+    fn default() -> Self {
+        Self {
+            max_reconnects: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            expected_end: None,
+            refresh_auth: false,
+            end_statuses: vec![StatusCode::NOT_FOUND],
+            end_marker: None,
+        }
+    }
+}
+
+impl FlvReconnectConfig {
+    /// Reconnect up to `max_reconnects` times, with the default backoff
+    pub fn new(max_reconnects: u32) -> Self {
+        Self {
+            max_reconnects,
+            ..Self::default()
+        }
+    }
+
+    /// Set the delay before the first reconnect and its cap
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Set when the stream is expected to end
+    pub fn expected_end(mut self, expected_end: SystemTime) -> Self {
+        self.expected_end = Some(expected_end);
+        self
+    }
+
+    /// Set whether the credentials are renewed before each reconnect
+    pub fn refresh_auth(mut self, refresh_auth: bool) -> Self {
+        self.refresh_auth = refresh_auth;
+        self
+    }
+
+    /// Set the statuses of a reconnect request meaning that the stream has ended
+    pub fn end_statuses(mut self, end_statuses: Vec<StatusCode>) -> Self {
+        self.end_statuses = end_statuses;
+        self
+    }
+
+    /// Set the text of a script tag meaning that the stream has ended
+    pub fn end_marker(mut self, end_marker: impl Into<String>) -> Self {
+        self.end_marker = Some(end_marker.into());
+        self
+    }
+
+    /// Delay before reconnect number `reconnect`, counted from 0
+    pub fn delay(&self, reconnect: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(i32::try_from(reconnect).unwrap_or(i32::MAX));
+        let nanos = self.initial_backoff.as_nanos() as f64 * factor;
+        if nanos.is_finite() && nanos < self.max_backoff.as_nanos() as f64 {
+            Duration::from_nanos(nanos.round() as u64)
+        } else {
+            self.max_backoff
+        }
+    }
+}
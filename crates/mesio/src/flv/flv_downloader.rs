@@ -6,8 +6,10 @@
 
 use bytes::Bytes;
 use flv::{data::FlvData, parser_async::FlvDecoderStream};
-use futures::StreamExt;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
 use reqwest::{Response, StatusCode, Url};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::mpsc;
@@ -15,12 +17,21 @@ use tokio_stream::wrappers::ReceiverStream;
 use tracing::{debug, info, instrument, warn};
 
 use super::error::FlvDownloadError;
+use super::failover;
 use super::flv_config::FlvProtocolConfig;
+use super::reconnect;
+use super::resume::{RESUME_SCAN_WINDOW, ResumeFilter, ResumePoint, find_resume_point};
+use crate::auth::RefreshReason;
 use crate::bytes_stream::BytesStreamReader;
+use crate::probe::ProbeHandoff;
+use crate::retry::{RetryAction, retry_with_backoff};
+use crate::tee::{TeeKind, TeeStream};
+use crate::throttle::{Throttle, ThrottledStream};
+use crate::watchdog;
 use crate::{
-    DownloadError,
+    DownloadError, ProtocolType,
     cache::{CacheKey, CacheManager, CacheMetadata, CacheResourceType, CacheStatus},
-    downloader::create_client_pool,
+    downloader::{Clients, create_clients},
     media_protocol::BoxMediaStream,
     source::{ContentSource, SourceManager},
 };
@@ -50,9 +61,26 @@ fn format_size_binary(bytes: u64) -> String {
 }
 
 /// FLV Downloader for streaming FLV content from URLs
+#[derive(Clone)]
 pub struct FlvDownloader {
-    clients: Arc<crate::downloader::ClientPool>,
+    clients: Clients,
     config: FlvProtocolConfig,
+    /// Response of a protocol probe, used by the first download of the probed URL
+    probe: ProbeHandoff,
+}
+
+/// Timestamp of audio and video frames, the media position tracked by the stall watchdog
+fn media_position(data: &FlvData) -> Option<u64> {
+    match data {
+        FlvData::Tag(tag)
+            if (tag.is_audio_tag() || tag.is_video_tag())
+                && !tag.is_audio_sequence_header()
+                && !tag.is_video_sequence_header() =>
+        {
+            Some(tag.timestamp_ms as u64)
+        }
+        _ => None,
+    }
 }
 
 impl FlvDownloader {
@@ -84,11 +112,37 @@ impl FlvDownloader {
 
     /// Create a new FlvDownloader with custom configuration
     pub fn with_config(config: FlvProtocolConfig) -> Result<Self, DownloadError> {
-        let clients = Arc::new(create_client_pool(&config.base)?);
-        Ok(Self { clients, config })
+        let clients = create_clients(&config.base, ProtocolType::Flv)?;
+        Ok(Self {
+            clients,
+            config,
+            probe: ProbeHandoff::default(),
+        })
+    }
+
+    /// Use the response of a protocol probe instead of requesting its URL again
+    pub(crate) fn with_probe(mut self, probe: ProbeHandoff) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// This downloader sending the requests of `source` with the clients provided for it
+    fn for_source(&self, source: &ContentSource) -> Self {
+        Self {
+            clients: self.clients.for_source(source),
+            ..self.clone()
+        }
+    }
+
+    /// The configuration of this downloader
+    pub(crate) fn config(&self) -> &FlvProtocolConfig {
+        &self.config
     }
 
-    /// Download a stream from a URL string and return an FLV data stream
+    /// Download a stream from a URL string and return an FLV data stream.
+    ///
+    /// With reconnects configured, the stream continues over new connections when the
+    /// current one drops before the stream ended.
     #[instrument(skip(self), level = "debug")]
     pub(crate) async fn download_flv(
         &self,
@@ -98,7 +152,17 @@ impl FlvDownloader {
         let url = url_str
             .parse::<Url>()
             .map_err(|e| DownloadError::invalid_url(url_str, e.to_string()))?;
-        self.download_url(url, token).await
+        let stream = self.download_url(url.clone(), token.clone()).await?;
+        match &self.config.reconnect {
+            Some(config) => Ok(reconnect::reconnect(
+                self.clone(),
+                config.clone(),
+                url,
+                stream,
+                token,
+            )),
+            None => Ok(stream),
+        }
     }
 
     /// Download a stream from a URL string and return a raw byte stream without parsing
@@ -114,27 +178,40 @@ impl FlvDownloader {
         self.download_url_raw(url, token).await
     }
 
-    /// Core method to start a download request and return the response
-    async fn start_download_request(&self, url: &Url) -> Result<Response, DownloadError> {
-        info!(url = %url, "Starting FLV download request");
-        debug!(url = %url, params = ?self.config.base.params, "Sending FLV download request");
+    /// Renew the credentials of `url` through the auth refresh hook, when one is configured
+    pub(super) async fn refresh_credentials(
+        &self,
+        url: &Url,
+        reason: RefreshReason,
+    ) -> Result<(), DownloadError> {
+        let auth = self.clients.auth();
+        auth.refresh(url, reason, auth.generation()).await
+    }
 
-        let client = self.clients.client_for_url(url);
-        let response = client
-            .get(url.clone())
-            .query(&self.config.base.params)
-            .send()
-            .await?;
+    /// Core method to start a download request and return the response.
+    /// Failed connection attempts are retried according to the configured retry policy.
+    async fn start_download_request(
+        &self,
+        url: &Url,
+        token: &CancellationToken,
+    ) -> Result<Response, DownloadError> {
+        info!(url = %url, "Starting FLV download request");
 
-        // Check response status
-        if !response.status().is_success() {
-            Self::log_unexpected_status(url, response.status(), "initial_request");
-            return Err(DownloadError::http_status(
-                response.status(),
-                url.to_string(),
-                "initial_request",
-            ));
-        }
+        // Reconnects pick up the URL and credentials of earlier refreshes
+        let auth = self.clients.auth();
+        auth.refresh_if_expired(url).await?;
+        let generation = auth.generation();
+        let response = match self.send_download_request(&auth.url(url), token).await {
+            Ok(response) => response,
+            Err(error) => match auth.rejection(&error) {
+                Some(reason) => {
+                    warn!(url = %url, "FLV request rejected, refreshing credentials: {error}");
+                    auth.refresh(url, reason, generation).await?;
+                    self.send_download_request(&auth.url(url), token).await?
+                }
+                None => return Err(error),
+            },
+        };
 
         // Fast path: Check Content-Type header if present
         // Reject obviously wrong content types early without reading body
@@ -188,6 +265,77 @@ impl FlvDownloader {
         Ok(response)
     }
 
+    /// Send the FLV request of `url`, retrying failures with the configured policy
+    async fn send_download_request(
+        &self,
+        url: &Url,
+        token: &CancellationToken,
+    ) -> Result<Response, DownloadError> {
+        debug!(url = %url, params = ?self.config.base.params, "Sending FLV download request");
+
+        let client = self.clients.media_client_for_url(url);
+        let on_progress = self.config.base.on_progress.as_ref();
+        let policy = &self.config.base.retry_policy;
+        retry_with_backoff(policy, url.as_str(), on_progress, token, |_| async {
+            let request = client.get(url.clone()).query(&self.config.base.params);
+            let response = match self.clients.auth().apply(url, request).send().await {
+                Ok(response) => response,
+                Err(e) => return RetryAction::from_request_error(e),
+            };
+
+            // Check response status
+            if !response.status().is_success() {
+                Self::log_unexpected_status(url, response.status(), "initial_request");
+                let error = DownloadError::from_response(response, "initial_request").await;
+                return RetryAction::from_error(error);
+            }
+            RetryAction::Success(response)
+        })
+        .await
+    }
+
+    /// Open the body of a download, continuing the response of a protocol probe of the same
+    /// URL when there is one
+    async fn open_body(
+        &self,
+        url: &Url,
+        token: &CancellationToken,
+    ) -> Result<ThrottledStream<BoxStream<'static, reqwest::Result<Bytes>>>, DownloadError> {
+        let body = match self.probe.take_stream(url.as_str()) {
+            Some(body) => {
+                debug!(url = %url, "Continuing the protocol probe response");
+                body
+            }
+            None => self
+                .start_download_request(url, token)
+                .await?
+                .bytes_stream()
+                .boxed(),
+        };
+        let body = self.tee_body(url, body).boxed();
+        Ok(Throttle::for_download(&self.config.base, url.as_str()).wrap(body))
+    }
+
+    /// Body of a download response, limited by the configured bandwidth
+    fn body_stream(
+        &self,
+        url: &Url,
+        response: Response,
+    ) -> ThrottledStream<impl Stream<Item = reqwest::Result<Bytes>> + Send + 'static> {
+        let body = self.tee_body(url, response.bytes_stream());
+        Throttle::for_download(&self.config.base, url.as_str()).wrap(body)
+    }
+
+    /// Copy a body to the raw tee as received, when one is configured
+    fn tee_body<S>(&self, url: &Url, body: S) -> TeeStream<S> {
+        TeeStream::new(
+            self.config.base.raw_tee.as_ref(),
+            TeeKind::Stream,
+            url.as_str(),
+            body,
+        )
+    }
+
     /// Create an FLV decoder stream from any async reader
     #[inline]
     fn create_decoder_stream<R>(&self, reader: R) -> BoxMediaStream<FlvData, FlvDownloadError>
@@ -210,6 +358,27 @@ impl FlvDownloader {
             .boxed()
     }
 
+    /// Apply the stall watchdog, if configured, to the stream of `url`.
+    ///
+    /// Each source of a multi-source download is watched on its own, so a stalled source fails
+    /// over to the next one.
+    fn watch_stalls(
+        &self,
+        url: &str,
+        stream: BoxMediaStream<FlvData, FlvDownloadError>,
+    ) -> BoxMediaStream<FlvData, FlvDownloadError> {
+        let Some(stall_config) = self.config.effective_stall_config() else {
+            return stream;
+        };
+        watchdog::watch(
+            stream,
+            url,
+            stall_config,
+            self.config.base.on_progress.clone(),
+            Box::new(media_position),
+        )
+    }
+
     /// Download a stream from a URL and return an FLV data stream
     #[instrument(skip(self), level = "debug")]
     pub(crate) async fn download_url(
@@ -222,14 +391,13 @@ impl FlvDownloader {
                 info!(url = %url, "Download cancelled");
                 return Err(DownloadError::Cancelled);
             }
-            response = self.start_download_request(&url) => {
-                let response = response?;
-                let mut byte_stream = response.bytes_stream();
+            byte_stream = self.open_body(&url, &token) => {
+                let mut byte_stream = byte_stream?;
 
                 // Read the first chunk to validate it's FLV binary data
                 let first_chunk = match byte_stream.next().await {
                     Some(Ok(chunk)) => chunk,
-                    Some(Err(e)) => return Err(DownloadError::Network { source: e }),
+                    Some(Err(e)) => return Err(DownloadError::from(e)),
                     None => return Err(DownloadError::InvalidContent {
                         protocol: "flv",
                         reason: "Empty response received".to_string(),
@@ -333,7 +501,7 @@ impl FlvDownloader {
 
                 let stream = ReceiverStream::new(rx);
                 let reader = BytesStreamReader::new(stream.boxed());
-                Ok(self.create_decoder_stream(reader))
+                Ok(self.watch_stalls(url.as_str(), self.create_decoder_stream(reader)))
             }
         }
     }
@@ -352,9 +520,8 @@ impl FlvDownloader {
                 info!(url = %url, "Download cancelled");
                 return Err(DownloadError::Cancelled);
             }
-            response = self.start_download_request(&url) => {
-                let response = response?;
-                let mut byte_stream = response.bytes_stream();
+            byte_stream = self.open_body(&url, &token) => {
+                let mut byte_stream = byte_stream?;
                 let (tx, rx) = mpsc::channel(2);
 
                 let stream_token = token.clone();
@@ -375,7 +542,7 @@ impl FlvDownloader {
                                     Some(Err(e)) => {
                                         let _ = tx
                                             .send(Err(FlvDownloadError::Download(
-                                                DownloadError::Network { source: e },
+                                                DownloadError::from(e),
                                             )))
                                             .await;
                                         break;
@@ -398,7 +565,7 @@ impl FlvDownloader {
         url: &Url,
         metadata: &CacheMetadata,
     ) -> Result<Option<Response>, DownloadError> {
-        let client = self.clients.client_for_url(url);
+        let client = self.clients.media_client_for_url(url);
         let mut req = client.get(url.clone());
 
         if let Some(etag) = &metadata.etag {
@@ -419,11 +586,7 @@ impl FlvDownloader {
         // Content was modified
         if !response.status().is_success() {
             Self::log_unexpected_status(url, response.status(), "cache_revalidation");
-            return Err(DownloadError::http_status(
-                response.status(),
-                url.to_string(),
-                "cache_revalidation",
-            ));
+            return Err(DownloadError::from_response(response, "cache_revalidation").await);
         }
 
         Ok(Some(response))
@@ -491,7 +654,7 @@ impl FlvDownloader {
         info!(url = %url, "Starting FLV download (not in cache)");
 
         // Start the request
-        let client = self.clients.client_for_url(&url);
+        let client = self.clients.media_client_for_url(&url);
         let response = client
             .get(url.clone())
             .query(&self.config.base.params)
@@ -501,18 +664,14 @@ impl FlvDownloader {
         // Check response status
         if !response.status().is_success() {
             Self::log_unexpected_status(&url, response.status(), "cache_miss_download");
-            return Err(DownloadError::http_status(
-                response.status(),
-                url.to_string(),
-                "cache_miss_download",
-            ));
+            return Err(DownloadError::from_response(response, "cache_miss_download").await);
         }
 
         // Extract caching headers
         // let (etag, last_modified, content_type) = extract_cache_headers(&response);
 
         // Get content as bytes stream
-        let bytes_stream = response.bytes_stream();
+        let bytes_stream = self.body_stream(&url, response);
 
         // TODO: I dont think caching catching the entire stream is a good idea
         // // Store in cache if smaller than 10MB
@@ -537,7 +696,11 @@ impl FlvDownloader {
     ) -> Result<BoxMediaStream<FlvData, FlvDownloadError>, DownloadError> {
         let start_time = Instant::now();
 
-        match self.download_flv(&source.url, token).await {
+        match self
+            .for_source(source)
+            .download_flv(&source.url, token)
+            .await
+        {
             Ok(stream) => {
                 // Record success for this source
                 let elapsed = start_time.elapsed();
@@ -585,7 +748,7 @@ impl FlvDownloader {
         };
 
         // Start the request with range
-        let client = self.clients.client_for_url(&url);
+        let client = self.clients.media_client_for_url(&url);
         let response = client
             .get(url.clone())
             .header("Range", range_header)
@@ -596,21 +759,74 @@ impl FlvDownloader {
         // Check response status - should be 206 Partial Content
         if response.status() != StatusCode::PARTIAL_CONTENT && response.status() != StatusCode::OK {
             Self::log_unexpected_status(&url, response.status(), "ranged_download");
-            return Err(DownloadError::http_status(
-                response.status(),
-                url.to_string(),
-                "ranged_download",
-            ));
+            return Err(DownloadError::from_response(response, "ranged_download").await);
         }
 
         // Get the bytes stream from the response
-        let bytes_stream = response.bytes_stream();
+        let bytes_stream = self.body_stream(&url, response);
 
         // Wrap the bytes stream in our adapter
         let reader = BytesStreamReader::new(bytes_stream);
 
         // Create the decoder stream
-        Ok(self.create_decoder_stream(reader))
+        Ok(self.watch_stalls(url.as_str(), self.create_decoder_stream(reader)))
+    }
+
+    /// Resume a download into an existing FLV file.
+    ///
+    /// With tag-level resume, the file is truncated after its last complete tag and the stream
+    /// is downloaded again from its start, skipping the tags the file already contains; the
+    /// returned resume point carries the timestamp to continue from. A file without a complete
+    /// tag is emptied and the download starts over. Without tag-level resume, the remaining
+    /// bytes are requested with a Range header. In every case the items of the returned stream
+    /// are meant to be appended to the file.
+    #[instrument(skip(self, token), level = "debug")]
+    pub async fn resume_file(
+        &self,
+        url_str: &str,
+        path: &Path,
+        token: CancellationToken,
+    ) -> Result<
+        (
+            BoxMediaStream<FlvData, FlvDownloadError>,
+            Option<ResumePoint>,
+        ),
+        DownloadError,
+    > {
+        if !self.config.tag_resume {
+            let len = tokio::fs::metadata(path).await?.len();
+            let stream = self.download_range(url_str, (len, None), token).await?;
+            return Ok((stream, None));
+        }
+
+        let point = find_resume_point(path, RESUME_SCAN_WINDOW).await?;
+        let file = tokio::fs::OpenOptions::new().write(true).open(path).await?;
+        file.set_len(point.map_or(0, |point| point.valid_len))
+            .await?;
+
+        let stream = self.download_flv(url_str, token).await?;
+        let Some(point) = point else {
+            info!(path = %path.display(), "No complete tag to resume from, restarting file");
+            return Ok((stream, None));
+        };
+
+        info!(
+            path = %path.display(),
+            valid_len = point.valid_len,
+            last_timestamp_ms = point.last_timestamp_ms,
+            "Resuming FLV download after the last complete tag"
+        );
+        let mut filter = ResumeFilter::new(point);
+        let stream = stream
+            .flat_map(move |item| {
+                let items = match item {
+                    Ok(data) => filter.push(data).into_iter().map(Ok).collect(),
+                    Err(err) => vec![Err(err)],
+                };
+                futures::stream::iter(items)
+            })
+            .boxed();
+        Ok((stream, Some(point)))
     }
 
     /// Attempt to resume download from a single source
@@ -624,7 +840,11 @@ impl FlvDownloader {
     ) -> Result<BoxMediaStream<FlvData, FlvDownloadError>, DownloadError> {
         let start_time = Instant::now();
 
-        match self.download_range(&source.url, range, token).await {
+        match self
+            .for_source(source)
+            .download_range(&source.url, range, token)
+            .await
+        {
             Ok(stream) => {
                 // Record success
                 let elapsed = start_time.elapsed();
@@ -650,7 +870,11 @@ impl FlvDownloader {
     ) -> Result<BoxMediaStream<Bytes, FlvDownloadError>, DownloadError> {
         let start_time = Instant::now();
 
-        match self.download_raw(&source.url, token).await {
+        match self
+            .for_source(source)
+            .download_raw(&source.url, token)
+            .await
+        {
             Ok(stream) => {
                 // Record success for this source
                 let elapsed = start_time.elapsed();
@@ -698,7 +922,7 @@ impl FlvDownloader {
         };
 
         // Start the request with range
-        let client = self.clients.client_for_url(&url);
+        let client = self.clients.media_client_for_url(&url);
         let response = client
             .get(url.clone())
             .header("Range", range_header)
@@ -709,19 +933,13 @@ impl FlvDownloader {
         // Check response status - should be 206 Partial Content
         if response.status() != StatusCode::PARTIAL_CONTENT && response.status() != StatusCode::OK {
             Self::log_unexpected_status(&url, response.status(), "ranged_raw_download");
-            return Err(DownloadError::http_status(
-                response.status(),
-                url.to_string(),
-                "ranged_raw_download",
-            ));
+            return Err(DownloadError::from_response(response, "ranged_raw_download").await);
         }
 
         // Transform the reqwest bytes stream into our raw byte stream
-        let raw_stream = response
-            .bytes_stream()
-            .map(|result| {
-                result.map_err(|e| FlvDownloadError::Download(DownloadError::Network { source: e }))
-            })
+        let raw_stream = self
+            .body_stream(&url, response)
+            .map(|result| result.map_err(|e| FlvDownloadError::Download(DownloadError::from(e))))
             .boxed();
 
         Ok(raw_stream)
@@ -738,7 +956,11 @@ impl FlvDownloader {
     ) -> Result<BoxMediaStream<Bytes, FlvDownloadError>, DownloadError> {
         let start_time = Instant::now();
 
-        match self.download_raw_range(&source.url, range, token).await {
+        match self
+            .for_source(source)
+            .download_raw_range(&source.url, range, token)
+            .await
+        {
             Ok(stream) => {
                 // Record success
                 let elapsed = start_time.elapsed();
@@ -762,6 +984,10 @@ impl ProtocolBase for FlvDownloader {
     fn new(config: Self::Config) -> Result<Self, DownloadError> {
         Self::with_config(config)
     }
+
+    fn set_shared_rate_limiter(&mut self, limiter: RateLimiter) {
+        self.config.base.shared_rate_limiter = Some(limiter);
+    }
 }
 
 // Implement core download capability
@@ -807,10 +1033,22 @@ impl MultiSource for FlvDownloader {
 
         // Try sources until one succeeds or all active sources are tried
         while let Some(source) = source_manager.select_source() {
+            let source_token = token.child_token();
             match self
-                .try_download_from_source(&source, source_manager, token.clone())
+                .try_download_from_source(&source, source_manager, source_token.clone())
                 .await
             {
+                // With fallbacks available, keep switching sources after the stream started
+                Ok(stream) if source_manager.count() > 1 => {
+                    return Ok(failover::follow_sources(
+                        self.clone(),
+                        source_manager.clone(),
+                        source,
+                        stream,
+                        source_token,
+                        token,
+                    ));
+                }
                 Ok(stream) => return Ok(stream),
                 Err(err) => {
                     last_error = Some(err);
@@ -1,7 +1,11 @@
 pub mod error;
+mod failover;
 pub mod flv_config;
 pub mod flv_downloader;
+mod reconnect;
+pub mod resume;
 
 pub use flv_downloader::FlvDownloader;
 
-pub use flv_config::FlvProtocolConfig;
+pub use flv_config::{FlvProtocolConfig, FlvReconnectConfig};
+pub use resume::{ResumeFilter, ResumePoint};
@@ -0,0 +1,430 @@
+//! # Live Stream Reconnection
+//!
+//! Live origins drop long-lived FLV connections now and then, closing them cleanly or
+//! resetting them, although the broadcast goes on. With a [`FlvReconnectConfig`], a
+//! single-source download requests the stream again after such a drop, waiting longer
+//! after each one, and continues the same output stream from the new connection.
+//!
+//! The new connection starts with its own FLV header and sequence headers. The header is
+//! skipped, and so are sequence headers identical to the ones already forwarded, so the
+//! pipeline sees a single session; its continuity operator stitches the timestamps of the
+//! new connection onto the previous ones. Changed sequence headers are forwarded, letting
+//! flv-fix split the output on them.
+//!
+//! A drop is taken as the end of the stream when the reconnect request is answered with
+//! one of the configured end statuses, a script tag carried the configured end marker, or
+//! the expected end of the stream has passed.
+
+use std::time::SystemTime;
+
+use bytes::Bytes;
+use flv::data::FlvData;
+use futures::StreamExt;
+use reqwest::Url;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::error::FlvDownloadError;
+use super::flv_config::FlvReconnectConfig;
+use super::flv_downloader::FlvDownloader;
+use crate::DownloadError;
+use crate::auth::RefreshReason;
+use crate::media_protocol::BoxMediaStream;
+
+/// Continue `stream`, received from `url`, over new connections when it drops
+pub(super) fn reconnect(
+    downloader: FlvDownloader,
+    config: FlvReconnectConfig,
+    url: Url,
+    stream: BoxMediaStream<FlvData, FlvDownloadError>,
+    token: CancellationToken,
+) -> BoxMediaStream<FlvData, FlvDownloadError> {
+    let reconnect = Reconnect {
+        downloader,
+        config,
+        url,
+        stream,
+        session: SessionFilter::default(),
+        reconnects: 0,
+        ended: false,
+        token,
+        finished: false,
+    };
+    futures::stream::unfold(reconnect, |mut reconnect| async move {
+        let item = reconnect.next().await?;
+        Some((item, reconnect))
+    })
+    .boxed()
+}
+
+struct Reconnect {
+    downloader: FlvDownloader,
+    config: FlvReconnectConfig,
+    url: Url,
+    stream: BoxMediaStream<FlvData, FlvDownloadError>,
+    /// Joins the connections into a single session
+    session: SessionFilter,
+    /// Reconnects made so far
+    reconnects: u32,
+    /// Set once a script tag carried the end marker
+    ended: bool,
+    token: CancellationToken,
+    /// Set after the last item was emitted
+    finished: bool,
+}
+
+impl Reconnect {
+    async fn next(&mut self) -> Option<Result<FlvData, FlvDownloadError>> {
+        loop {
+            if self.finished {
+                return None;
+            }
+
+            let reason = match self.stream.next().await {
+                Some(Ok(data)) => {
+                    if self.is_end_marker(&data) {
+                        info!(url = %self.url, "Stream end marker received");
+                        self.ended = true;
+                    }
+                    match self.session.push(data) {
+                        Some(data) => return Some(Ok(data)),
+                        None => continue,
+                    }
+                }
+                Some(Err(err)) => {
+                    let error = DownloadError::from(err);
+                    if !error.is_retryable() {
+                        self.finished = true;
+                        return Some(Err(error.into()));
+                    }
+                    error.to_string()
+                }
+                None => "connection closed".to_string(),
+            };
+
+            if self.token.is_cancelled() || self.ended || self.past_expected_end() {
+                return None;
+            }
+            match self.reconnect(reason).await {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(err) => {
+                    self.finished = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+
+    fn is_end_marker(&self, data: &FlvData) -> bool {
+        let (Some(marker), FlvData::Tag(tag)) = (&self.config.end_marker, data) else {
+            return false;
+        };
+        tag.is_script_tag()
+            && !marker.is_empty()
+            && tag
+                .data
+                .windows(marker.len())
+                .any(|window| window == marker.as_bytes())
+    }
+
+    fn past_expected_end(&self) -> bool {
+        self.config
+            .expected_end
+            .is_some_and(|end| SystemTime::now() >= end)
+    }
+
+    /// Request the stream again, returning whether it continues
+    async fn reconnect(&mut self, mut reason: String) -> Result<bool, FlvDownloadError> {
+        loop {
+            if self.reconnects >= self.config.max_reconnects {
+                return Err(DownloadError::source_exhausted(format!(
+                    "gave up after {} reconnects: {reason}",
+                    self.reconnects
+                ))
+                .into());
+            }
+            let delay = self.config.delay(self.reconnects);
+            self.reconnects += 1;
+            warn!(
+                url = %self.url,
+                reason = %reason,
+                attempt = self.reconnects,
+                max_reconnects = self.config.max_reconnects,
+                ?delay,
+                "FLV connection dropped, reconnecting"
+            );
+            tokio::select! {
+                _ = self.token.cancelled() => return Ok(false),
+                _ = tokio::time::sleep(delay) => {}
+            }
+
+            if self.config.refresh_auth {
+                self.downloader
+                    .refresh_credentials(&self.url, RefreshReason::Reconnect)
+                    .await?;
+            }
+            match self
+                .downloader
+                .download_url(self.url.clone(), self.token.clone())
+                .await
+            {
+                Ok(stream) => {
+                    info!(url = %self.url, "Reconnected FLV stream");
+                    self.stream = stream;
+                    self.session.reconnected();
+                    return Ok(true);
+                }
+                Err(DownloadError::Cancelled) => return Ok(false),
+                Err(err)
+                    if err
+                        .status()
+                        .is_some_and(|status| self.config.end_statuses.contains(&status)) =>
+                {
+                    info!(url = %self.url, "Stream ended: {err}");
+                    return Ok(false);
+                }
+                Err(err) if !err.is_retryable() => return Err(err.into()),
+                Err(err) => reason = err.to_string(),
+            }
+        }
+    }
+}
+
+/// Drops what a new connection repeats of the session already forwarded
+#[derive(Default)]
+struct SessionFilter {
+    /// Whether the current connection is a reconnect
+    reconnected: bool,
+    video_header: Option<Bytes>,
+    audio_header: Option<Bytes>,
+}
+
+impl SessionFilter {
+    fn reconnected(&mut self) {
+        self.reconnected = true;
+    }
+
+    fn push(&mut self, data: FlvData) -> Option<FlvData> {
+        let tag = match data {
+            FlvData::Header(_) if self.reconnected => return None,
+            FlvData::Tag(tag) => tag,
+            data => return Some(data),
+        };
+        let forwarded = if tag.is_video_sequence_header() {
+            &mut self.video_header
+        } else if tag.is_audio_sequence_header() {
+            &mut self.audio_header
+        } else {
+            return Some(FlvData::Tag(tag));
+        };
+        if self.reconnected && forwarded.as_ref() == Some(&tag.data) {
+            return None;
+        }
+        *forwarded = Some(tag.data.clone());
+        Some(FlvData::Tag(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Download;
+    use crate::flv::FlvProtocolConfig;
+    use crate::mock_origin::{FlvResponse, MockOrigin};
+    use flv::header::FlvHeader;
+    use flv::tag::{FlvTag, FlvTagType};
+    use flv::writer::FlvWriter;
+    use flv_fix::{FlvPipeline, FlvPipelineConfig, FlvWriterConfig, analyze_file};
+    use pipeline_common::config::PipelineConfig;
+    use pipeline_common::{
+        CurrentFileHook, PipelineError, PipelineProvider, ShutdownHandle, StreamerContext,
+        run_until_shutdown,
+    };
+    use reqwest::StatusCode;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn tag(tag_type: FlvTagType, timestamp_ms: u32, data: Vec<u8>) -> FlvTag {
+        FlvTag {
+            timestamp_ms,
+            stream_id: 0,
+            tag_type,
+            is_filtered: false,
+            data: Bytes::from(data),
+        }
+    }
+
+    /// onMetaData with a single numeric `property`
+    fn metadata(timestamp_ms: u32, property: &str) -> FlvTag {
+        let mut data = vec![2, 0, 10];
+        data.extend_from_slice(b"onMetaData");
+        data.push(3);
+        data.extend_from_slice(&(property.len() as u16).to_be_bytes());
+        data.extend_from_slice(property.as_bytes());
+        data.push(0);
+        data.extend_from_slice(&0f64.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 9]);
+        tag(FlvTagType::ScriptData, timestamp_ms, data)
+    }
+
+    /// What one connection of the live stream serves: its own metadata and sequence headers,
+    /// then the video/audio pairs of `pairs`, with a keyframe every second
+    fn session(pairs: std::ops::Range<u32>, property: &str) -> Vec<FlvTag> {
+        let start = pairs.start * 40;
+        let mut tags = vec![
+            metadata(start, property),
+            tag(
+                FlvTagType::Video,
+                start,
+                vec![0x17, 0, 0, 0, 0, 1, 0x64, 0, 0x28],
+            ),
+            tag(FlvTagType::Audio, start, vec![0xAF, 0, 0x12, 0x10]),
+        ];
+        for i in pairs {
+            let frame_type = if i % 25 == 0 { 0x17 } else { 0x27 };
+            tags.push(tag(
+                FlvTagType::Video,
+                i * 40,
+                vec![frame_type, 1, 0, 0, 0, i as u8],
+            ));
+            tags.push(tag(
+                FlvTagType::Audio,
+                i * 40,
+                vec![0xAF, 1, 0x21, 0x10, i as u8],
+            ));
+        }
+        tags
+    }
+
+    fn encode(tags: &[FlvTag]) -> Vec<u8> {
+        let mut writer = FlvWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_header(&FlvHeader::new(true, true)).unwrap();
+        for tag in tags {
+            writer.write_tag_f(tag).unwrap();
+        }
+        writer.writer.into_inner()
+    }
+
+    fn downloader(reconnect: FlvReconnectConfig) -> FlvDownloader {
+        let config = FlvProtocolConfig::builder()
+            .reconnect(reconnect.backoff(Duration::from_millis(10), Duration::from_millis(50)))
+            .build();
+        FlvDownloader::with_config(config).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reconnects_into_one_continuous_file() {
+        // The origin closes the first connection, resets the second in the middle of a tag
+        // and reports the stream offline once the third one ended
+        let second = encode(&session(50..101, "duration"));
+        let cut = encode(&session(50..100, "duration")).len() + 8;
+        let origin = MockOrigin::builder()
+            .flv(
+                "/live.flv",
+                FlvResponse::new(encode(&session(0..50, "duration"))),
+            )
+            .flv("/live.flv", FlvResponse::new(second).reset_at(cut))
+            .flv(
+                "/live.flv",
+                FlvResponse::new(encode(&session(100..150, "duration"))),
+            )
+            .flv("/live.flv", FlvResponse::status(StatusCode::NOT_FOUND))
+            .spawn()
+            .await;
+
+        let stream = downloader(FlvReconnectConfig::new(5))
+            .download(&origin.url("/live.flv"), CancellationToken::new())
+            .await
+            .unwrap()
+            .map(|item| item.map_err(|err| PipelineError::Strategy(Box::new(err))));
+
+        let dir = tempfile::tempdir().unwrap();
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = FlvPipeline::with_config(
+            context.clone(),
+            &PipelineConfig::default(),
+            FlvPipelineConfig::default(),
+        )
+        .build_pipeline();
+        let mut writer = flv_fix::FlvWriter::new(FlvWriterConfig {
+            output_dir: dir.path().to_path_buf(),
+            base_name: "output".to_string(),
+            enable_low_latency: true,
+        });
+        writer.add_segment_hook(CurrentFileHook::new(context.stats.clone()));
+        let shutdown = ShutdownHandle::new(CancellationToken::new());
+        let outcome = run_until_shutdown(stream, pipeline, writer, &shutdown)
+            .await
+            .unwrap();
+        assert_eq!(outcome.stats().files_created, 1);
+        assert_eq!(origin.requests_to("/live.flv").len(), 4);
+
+        // The onMetaData is patched in the background once the file is closed
+        let output_path = context.stats.snapshot().current_file.unwrap();
+        let mut report = None;
+        for _ in 0..100 {
+            report = analyze_file(&output_path).ok();
+            if report.as_ref().is_some_and(|report| !report.has_issues()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let report = report.expect("analyzable output");
+        assert!(!report.has_issues(), "{:?}", report.issues);
+        assert_eq!(report.tag_counts.video, 151);
+        assert_eq!(report.tag_counts.audio, 151);
+        assert_eq!(report.file_info.keyframe_count, 6);
+    }
+
+    #[tokio::test]
+    async fn test_end_marker_ends_stream() {
+        let origin = MockOrigin::builder()
+            .flv(
+                "/live.flv",
+                FlvResponse::new(encode(&session(0..50, "streamEnded"))),
+            )
+            .spawn()
+            .await;
+
+        let output: Vec<FlvData> = downloader(FlvReconnectConfig::new(5).end_marker("streamEnded"))
+            .download(&origin.url("/live.flv"), CancellationToken::new())
+            .await
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(output.len(), 1 + 3 + 100);
+        assert_eq!(origin.requests_to("/live.flv").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_reconnects() {
+        let origin = MockOrigin::builder()
+            .flv(
+                "/live.flv",
+                FlvResponse::new(encode(&session(0..50, "duration"))),
+            )
+            .spawn()
+            .await;
+
+        let output: Vec<_> = downloader(FlvReconnectConfig::new(2))
+            .download(&origin.url("/live.flv"), CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let (last, received) = output.split_last().unwrap();
+        assert!(matches!(
+            last,
+            Err(FlvDownloadError::Download(
+                DownloadError::SourceExhausted { .. }
+            ))
+        ));
+        // The reconnects repeat neither the header nor the sequence headers
+        assert_eq!(received.len(), 1 + 3 + 100 + 2 * (1 + 100));
+        assert!(received.iter().all(Result::is_ok));
+        assert_eq!(origin.requests_to("/live.flv").len(), 3);
+    }
+}
@@ -0,0 +1,450 @@
+//! # FLV Tag-Level Resume
+//!
+//! Live FLV origins rarely honor Range requests, and a recording that was interrupted usually
+//! ends in the middle of a tag. Instead of resuming from a byte offset, the last complete tag of
+//! the existing file is located by following its PreviousTagSize back-pointers, the file is
+//! truncated after it, and the stream is requested again from its start. Tags up to the
+//! recorded timestamp are dropped and the output re-synchronizes on the next keyframe.
+
+use std::io;
+use std::path::Path;
+
+use flv::data::FlvData;
+use flv::framing::{self, PREV_TAG_SIZE_FIELD_SIZE, TAG_HEADER_SIZE};
+use flv::tag::{FlvTag, FlvTagType};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{debug, info};
+
+/// How much of the end of an existing file is scanned for the last complete tag
+pub const RESUME_SCAN_WINDOW: u64 = 8 * 1024 * 1024;
+
+/// A restarted stream starting further than this before the resume point is treated as a new
+/// timeline instead of a replay of already written tags
+const MAX_REPLAY_MS: u32 = 60_000;
+
+/// Offset of the first tag in a file with a standard header
+const FIRST_TAG_OFFSET: u64 = 13;
+
+/// Where a download resumes into an existing file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResumePoint {
+    /// Length of the file up to and including the PreviousTagSize of its last complete tag
+    pub valid_len: u64,
+    /// Timestamp of the last complete audio or video tag
+    pub last_timestamp_ms: u32,
+}
+
+/// Find the resume point of an existing FLV file by scanning its last `window` bytes.
+///
+/// Returns `None` if no complete audio or video tag ends within the window.
+pub async fn find_resume_point(path: &Path, window: u64) -> io::Result<Option<ResumePoint>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let base = len.saturating_sub(window);
+    file.seek(io::SeekFrom::Start(base)).await?;
+    let mut tail = Vec::with_capacity((len - base) as usize);
+    file.read_to_end(&mut tail).await?;
+    Ok(scan_tail(&tail, base))
+}
+
+/// Find the resume point in `tail`, the end of a file starting at offset `base`.
+pub fn scan_tail(tail: &[u8], base: u64) -> Option<ResumePoint> {
+    // The file may end anywhere, try every position from the end as the end of a tag
+    let end = (TAG_HEADER_SIZE + PREV_TAG_SIZE_FIELD_SIZE..=tail.len())
+        .rev()
+        .find(|&end| {
+            complete_tag_at(tail, end).is_some_and(|(start, _)| {
+                let offset = base + start as u64;
+                if offset == FIRST_TAG_OFFSET {
+                    return true;
+                }
+                // Confirm with the back-pointer of the previous tag when it is in the window
+                start < PREV_TAG_SIZE_FIELD_SIZE || complete_tag_at(tail, start).is_some()
+            })
+        })?;
+
+    // Script tags carry no media time, use the last audio or video tag
+    let mut tag_end = end;
+    while let Some((start, header)) = complete_tag_at(tail, tag_end) {
+        if matches!(header.tag_type, FlvTagType::Audio | FlvTagType::Video) {
+            let point = ResumePoint {
+                valid_len: base + end as u64,
+                last_timestamp_ms: header.timestamp_ms,
+            };
+            debug!(?point, "Found last complete FLV tag");
+            return Some(point);
+        }
+        tag_end = start;
+    }
+    None
+}
+
+/// The tag whose PreviousTagSize ends at `end`, with its start offset
+fn complete_tag_at(buf: &[u8], end: usize) -> Option<(usize, framing::ParsedTagHeader)> {
+    let size_start = end.checked_sub(PREV_TAG_SIZE_FIELD_SIZE)?;
+    let size = framing::parse_prev_tag_size(buf[size_start..end].try_into().ok()?) as usize;
+    if size < TAG_HEADER_SIZE {
+        return None;
+    }
+    let start = size_start.checked_sub(size)?;
+    let header =
+        framing::parse_tag_header_bytes(buf[start..start + TAG_HEADER_SIZE].try_into().ok()?)
+            .ok()?;
+    let known_type = matches!(
+        header.tag_type,
+        FlvTagType::Audio | FlvTagType::Video | FlvTagType::ScriptData
+    );
+    (known_type && header.data_size as usize + TAG_HEADER_SIZE == size).then_some((start, header))
+}
+
+/// Drops the tags of a restarted stream that an existing file already contains.
+///
+/// The header and script tags are dropped since the file already starts with them. Media tags
+/// are dropped until one past the resume timestamp starts a new GOP, which is emitted after the
+/// latest sequence headers.
+#[derive(Debug)]
+pub struct ResumeFilter {
+    /// Tags at or before this timestamp are already written, `None` on a new timeline
+    resume_after_ms: Option<u32>,
+    resumed: bool,
+    has_video: bool,
+    first_timestamp_ms: Option<u32>,
+    video_sequence_header: Option<FlvTag>,
+    audio_sequence_header: Option<FlvTag>,
+    dropped: u64,
+}
+
+impl ResumeFilter {
+    pub fn new(point: ResumePoint) -> Self {
+        Self::after(Some(point.last_timestamp_ms))
+    }
+
+    /// Filter a stream continuing output that ended at `last_timestamp_ms`, or that has not
+    /// emitted any media tag yet if `None`
+    pub fn after(last_timestamp_ms: Option<u32>) -> Self {
+        Self {
+            resume_after_ms: last_timestamp_ms,
+            resumed: false,
+            has_video: false,
+            first_timestamp_ms: None,
+            video_sequence_header: None,
+            audio_sequence_header: None,
+            dropped: 0,
+        }
+    }
+
+    /// Whether the stream has re-synchronized and tags are passed through
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Filter one item, returning the items to emit in order
+    pub fn push(&mut self, data: FlvData) -> Vec<FlvData> {
+        if self.resumed {
+            return vec![data];
+        }
+        let tag = match data {
+            FlvData::Header(header) => {
+                self.has_video |= header.has_video;
+                return Vec::new();
+            }
+            FlvData::Tag(tag) => tag,
+            FlvData::Split(_) | FlvData::EndOfSequence(_) => return Vec::new(),
+        };
+
+        if tag.is_script_tag() || tag.data.is_empty() {
+            return Vec::new();
+        }
+        if tag.is_video_tag() {
+            self.has_video = true;
+            if tag.is_video_sequence_header() {
+                self.video_sequence_header = Some(tag);
+                return Vec::new();
+            }
+        } else if tag.is_audio_sequence_header() {
+            self.audio_sequence_header = Some(tag);
+            return Vec::new();
+        }
+
+        if self.first_timestamp_ms.is_none() {
+            self.first_timestamp_ms = Some(tag.timestamp_ms);
+            if let Some(resume_after) = self.resume_after_ms
+                && tag.timestamp_ms.saturating_add(MAX_REPLAY_MS) < resume_after
+            {
+                info!(
+                    first_timestamp_ms = tag.timestamp_ms,
+                    resume_after_ms = resume_after,
+                    "Restarted FLV stream is on a new timeline, resuming on the next keyframe"
+                );
+                self.resume_after_ms = None;
+            }
+        }
+
+        let past_resume_point = self
+            .resume_after_ms
+            .is_none_or(|resume_after| tag.timestamp_ms > resume_after);
+        let starts_gop = if self.has_video {
+            tag.is_key_frame()
+        } else {
+            tag.is_audio_tag()
+        };
+        if !past_resume_point || !starts_gop {
+            self.dropped += 1;
+            return Vec::new();
+        }
+
+        info!(
+            timestamp_ms = tag.timestamp_ms,
+            dropped = self.dropped,
+            "Resumed FLV stream after the last written tag"
+        );
+        self.resumed = true;
+        let timestamp_ms = tag.timestamp_ms;
+        let mut output: Vec<FlvData> = [
+            self.video_sequence_header.take(),
+            self.audio_sequence_header.take(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|mut header| {
+            // Keep the appended timeline monotonic
+            header.timestamp_ms = timestamp_ms;
+            FlvData::Tag(header)
+        })
+        .collect();
+        output.push(FlvData::Tag(tag));
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use flv::header::FlvHeader;
+    use flv::writer::FlvWriter;
+    use std::io::Cursor;
+    use tokio::io::AsyncReadExt;
+
+    fn tag(tag_type: FlvTagType, timestamp_ms: u32, data: Vec<u8>) -> FlvTag {
+        FlvTag {
+            timestamp_ms,
+            stream_id: 0,
+            tag_type,
+            is_filtered: false,
+            data: Bytes::from(data),
+        }
+    }
+
+    fn video(timestamp_ms: u32, keyframe: bool) -> FlvTag {
+        let frame_type = if keyframe { 0x17 } else { 0x27 };
+        tag(
+            FlvTagType::Video,
+            timestamp_ms,
+            vec![frame_type, 1, 0, 0, 0, 0xAA],
+        )
+    }
+
+    fn audio(timestamp_ms: u32) -> FlvTag {
+        tag(FlvTagType::Audio, timestamp_ms, vec![0xAF, 1, 0x21, 0x10])
+    }
+
+    fn encode(tags: &[FlvTag]) -> Vec<u8> {
+        let mut writer = FlvWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_header(&FlvHeader::new(true, true)).unwrap();
+        for tag in tags {
+            writer.write_tag_f(tag).unwrap();
+        }
+        writer.writer.into_inner()
+    }
+
+    #[test]
+    fn test_scan_finds_last_complete_tag_of_truncated_file() {
+        let tags = [
+            tag(FlvTagType::ScriptData, 0, vec![2, 0, 1, b'a']),
+            video(0, true),
+            audio(10),
+            video(40, false),
+            audio(33),
+        ];
+        let full = encode(&tags);
+        let without_last = encode(&tags[..4]).len();
+
+        // Cut inside the payload of the last tag
+        let point = scan_tail(&full[..full.len() - 6], 0).unwrap();
+        assert_eq!(point.valid_len, without_last as u64);
+        assert_eq!(point.last_timestamp_ms, 40);
+
+        // The scan also works on a window not starting at a tag boundary
+        let base = 20;
+        let point = scan_tail(&full[base..], base as u64).unwrap();
+        assert_eq!(point.valid_len, full.len() as u64);
+        assert_eq!(point.last_timestamp_ms, 33);
+
+        // A file without media tags has nothing to resume from
+        let script_only = encode(&tags[..1]);
+        assert_eq!(scan_tail(&script_only, 0), None);
+    }
+
+    #[test]
+    fn test_filter_skips_replayed_tags_and_resyncs_on_keyframe() {
+        let mut filter = ResumeFilter::new(ResumePoint {
+            valid_len: 0,
+            last_timestamp_ms: 1000,
+        });
+        let input = [
+            FlvData::Header(FlvHeader::new(true, true)),
+            FlvData::Tag(tag(FlvTagType::ScriptData, 0, vec![2, 0, 1, b'a'])),
+            FlvData::Tag(tag(FlvTagType::Video, 0, vec![0x17, 0, 0, 0, 0])),
+            FlvData::Tag(tag(FlvTagType::Audio, 0, vec![0xAF, 0, 0x12, 0x10])),
+            FlvData::Tag(video(960, true)),
+            FlvData::Tag(audio(990)),
+            FlvData::Tag(video(1040, false)),
+            FlvData::Tag(audio(1050)),
+            FlvData::Tag(video(1200, true)),
+            FlvData::Tag(audio(1210)),
+        ];
+        let output: Vec<FlvData> = input
+            .into_iter()
+            .flat_map(|data| filter.push(data))
+            .collect();
+
+        let FlvData::Tag(first) = &output[0] else {
+            panic!("expected a tag");
+        };
+        assert!(first.is_video_sequence_header());
+        assert!(matches!(&output[1], FlvData::Tag(tag) if tag.is_audio_sequence_header()));
+        assert_eq!(output[2], FlvData::Tag(video(1200, true)));
+        assert_eq!(output[3], FlvData::Tag(audio(1210)));
+        assert_eq!(output.len(), 4);
+        assert!(
+            output
+                .iter()
+                .all(|data| matches!(data, FlvData::Tag(tag) if tag.timestamp_ms >= 1200))
+        );
+    }
+
+    /// Serve `body` to every request, like a live origin restarting the stream
+    async fn spawn_live_server(body: Vec<u8>) -> String {
+        use tokio::io::AsyncWriteExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!(
+            "http://{}/live.flv",
+            listener.local_addr().expect("local addr")
+        );
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: video/x-flv\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&body);
+                let _ = socket.write_all(&response).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_resume_file_appends_cleanly_to_file_cut_mid_tag() {
+        use crate::flv::{FlvDownloader, FlvProtocolConfig};
+        use flv::parser::{FlvParser, PrevTagSizeMode};
+        use futures::StreamExt;
+        use tokio_util::sync::CancellationToken;
+
+        let mut live = vec![
+            tag(FlvTagType::ScriptData, 0, vec![2, 0, 1, b'a']),
+            tag(FlvTagType::Video, 0, vec![0x17, 0, 0, 0, 0]),
+            tag(FlvTagType::Audio, 0, vec![0xAF, 0, 0x12, 0x10]),
+        ];
+        for i in 0..60 {
+            live.push(video(i * 40, i % 15 == 0));
+            live.push(audio(i * 40 + 20));
+        }
+
+        // The recording stopped in the middle of the 40th tag
+        let path = std::env::temp_dir().join(format!(
+            "mesio-resume-{}-{:?}.flv",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let written = encode(&live[..40]).len();
+        std::fs::write(&path, &encode(&live)[..written + 7]).unwrap();
+
+        let url = spawn_live_server(encode(&live)).await;
+        let downloader = FlvDownloader::with_config(FlvProtocolConfig::default()).unwrap();
+        let (stream, point) = downloader
+            .resume_file(&url, &path, CancellationToken::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            point,
+            Some(ResumePoint {
+                valid_len: written as u64,
+                last_timestamp_ms: live[39].timestamp_ms,
+            })
+        );
+        // The partial tag was cut off
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), written as u64);
+
+        let resumed: Vec<FlvData> = stream.map(|item| item.unwrap()).collect().await;
+        let file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        let mut writer = FlvWriter::new(file).unwrap();
+        for data in &resumed {
+            let FlvData::Tag(tag) = data else {
+                panic!("resumed stream must not contain {data:?}");
+            };
+            writer.write_tag_f(tag).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut output = Cursor::new(std::fs::read(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        FlvParser::parse_header(&mut output).unwrap();
+        let mut timestamps = Vec::new();
+        FlvParser::parse_tags_with_prev_tag_size_mode(
+            &mut output,
+            &mut |tag, _, _| timestamps.push(tag.timestamp_ms),
+            13,
+            PrevTagSizeMode::Strict,
+        )
+        .unwrap();
+
+        // Written tags up to 720ms, then sequence headers and the GOP starting at 1200ms
+        assert_eq!(timestamps.len(), 40 + 2 + 2 * 30);
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(timestamps[39], 720);
+        assert_eq!(timestamps[40..43], [1200, 1200, 1200]);
+    }
+
+    #[test]
+    fn test_filter_resumes_on_new_timeline() {
+        let mut filter = ResumeFilter::new(ResumePoint {
+            valid_len: 0,
+            last_timestamp_ms: 3_600_000,
+        });
+        assert!(filter.push(FlvData::Tag(video(0, false))).is_empty());
+        assert_eq!(
+            filter.push(FlvData::Tag(video(40, true))),
+            vec![FlvData::Tag(video(40, true))]
+        );
+        assert!(filter.is_resumed());
+    }
+}
@@ -0,0 +1,386 @@
+//! # Segment Buffer Pool
+//!
+//! A [`SegmentBufferPool`] lets segment downloads reuse the allocations of the segments
+//! before them. A body is read into a pooled [`BytesMut`] sized by a moving average of
+//! the previous segment sizes, then handed through the pipeline as a [`Bytes`] view.
+//! When the last view of a segment drops, usually at the writer once the data is
+//! written, the allocation goes back to the pool for the next download.
+//!
+//! The pool is opt-in: segment downloads allocate a new buffer per segment unless a pool
+//! is set with [`HlsFetcherConfig::buffer_pool`](super::config::HlsFetcherConfig).
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use bytes::{Bytes, BytesMut};
+use parking_lot::Mutex;
+
+/// What happens to a buffer that grew past [`BufferPoolConfig::max_buffer_capacity`]
+/// when it is returned to the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShrinkPolicy {
+    /// Drop the buffer and give its memory back to the allocator
+    #[default]
+    Discard,
+    /// Keep a buffer sized by the current segment size estimate instead
+    Shrink,
+}
+
+/// Sizing limits for a [`SegmentBufferPool`]
+#[derive(Debug, Clone)]
+pub struct BufferPoolConfig {
+    /// Maximum number of idle buffers kept
+    pub max_pooled_buffers: usize,
+    /// Maximum total capacity of the idle buffers kept
+    pub max_pooled_bytes: usize,
+    /// Buffers with a larger capacity are not kept as-is, see [`ShrinkPolicy`]
+    pub max_buffer_capacity: usize,
+    /// How oversized buffers are handled
+    pub shrink_policy: ShrinkPolicy,
+    /// Weight of the latest segment in the segment size estimate, between 0 and 1
+    pub size_smoothing: f64,
+}
+
+impl Default for BufferPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_pooled_buffers: 8,
+            max_pooled_bytes: 64 * 1024 * 1024,
+            max_buffer_capacity: 16 * 1024 * 1024,
+            shrink_policy: ShrinkPolicy::Discard,
+            size_smoothing: 0.2,
+        }
+    }
+}
+
+/// Snapshot of [`SegmentBufferPool`] usage, see [`SegmentBufferPool::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BufferPoolStats {
+    /// Buffers served from an idle pooled buffer
+    pub hits: u64,
+    /// Buffers that had to be allocated
+    pub misses: u64,
+    /// Buffers given back to the pool once their last view dropped
+    pub returned: u64,
+    /// Returned buffers dropped because they were oversized or the pool was full
+    pub discarded: u64,
+    /// Number of idle pooled buffers
+    pub pooled_buffers: usize,
+    /// Capacity currently held by idle pooled buffers
+    pub pooled_bytes: usize,
+    /// Highest value `pooled_bytes` has reached
+    pub high_water_bytes: usize,
+    /// Current estimate of the segment size, used to size new buffers
+    pub estimated_segment_size: usize,
+}
+
+impl BufferPoolStats {
+    /// Share of the buffers served from the pool, between 0 and 1
+    pub fn reuse_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+#[derive(Default)]
+struct PoolCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    returned: AtomicU64,
+    discarded: AtomicU64,
+    pooled_bytes: AtomicUsize,
+    high_water_bytes: AtomicUsize,
+}
+
+impl PoolCounters {
+    fn record_acquire(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn add_pooled(&self, bytes: usize) {
+        let total = self.pooled_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.high_water_bytes.fetch_max(total, Ordering::Relaxed);
+    }
+
+    fn remove_pooled(&self, bytes: usize) {
+        self.pooled_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+struct PoolInner {
+    buffers: Mutex<Vec<BytesMut>>,
+    config: BufferPoolConfig,
+    counters: PoolCounters,
+    /// Moving average of the segment sizes, 0 until a segment is recorded
+    size_estimate: AtomicUsize,
+}
+
+impl PoolInner {
+    /// Record the size of a segment in the size estimate
+    fn record_size(&self, size: usize) {
+        let smoothing = self.config.size_smoothing.clamp(0.0, 1.0);
+        let _ = self
+            .size_estimate
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |estimate| {
+                Some(if estimate == 0 {
+                    size
+                } else {
+                    (estimate as f64 + (size as f64 - estimate as f64) * smoothing) as usize
+                })
+            });
+    }
+
+    /// Take back the buffer of a segment whose last view dropped
+    fn release(&self, mut buffer: BytesMut) {
+        self.counters.returned.fetch_add(1, Ordering::Relaxed);
+        buffer.clear();
+        if buffer.capacity() > self.config.max_buffer_capacity {
+            match self.config.shrink_policy {
+                ShrinkPolicy::Discard => {
+                    self.counters.discarded.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                ShrinkPolicy::Shrink => {
+                    let estimate = self.size_estimate.load(Ordering::Relaxed);
+                    buffer = BytesMut::with_capacity(estimate.min(self.config.max_buffer_capacity));
+                }
+            }
+        }
+
+        let capacity = buffer.capacity();
+        let mut buffers = self.buffers.lock();
+        if buffers.len() >= self.config.max_pooled_buffers
+            || self.counters.pooled_bytes.load(Ordering::Relaxed) + capacity
+                > self.config.max_pooled_bytes
+        {
+            self.counters.discarded.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.counters.add_pooled(capacity);
+        buffers.push(buffer);
+    }
+}
+
+/// Owner of a frozen segment buffer, giving it back to the pool when dropped
+struct Recycled {
+    buffer: BytesMut,
+    pool: Arc<PoolInner>,
+}
+
+impl AsRef<[u8]> for Recycled {
+    fn as_ref(&self) -> &[u8] {
+        &self.buffer
+    }
+}
+
+impl Drop for Recycled {
+    fn drop(&mut self) {
+        self.pool.release(std::mem::take(&mut self.buffer));
+    }
+}
+
+/// A shared pool of segment buffers, cheap to clone.
+///
+/// Idle buffers are capped in number and total capacity; buffers that grew past
+/// [`BufferPoolConfig::max_buffer_capacity`] are handled according to its
+/// [`ShrinkPolicy`], so a single huge segment cannot pin memory for the lifetime of the
+/// pool.
+#[derive(Clone)]
+pub struct SegmentBufferPool {
+    inner: Arc<PoolInner>,
+}
+
+impl SegmentBufferPool {
+    /// Create a pool with the default limits
+    pub fn new() -> Self {
+        Self::with_config(BufferPoolConfig::default())
+    }
+
+    /// Create a pool with custom limits
+    pub fn with_config(config: BufferPoolConfig) -> Self {
+        Self {
+            inner: Arc::new(PoolInner {
+                buffers: Mutex::new(Vec::with_capacity(config.max_pooled_buffers)),
+                config,
+                counters: PoolCounters::default(),
+                size_estimate: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// The limits this pool was created with
+    pub fn config(&self) -> &BufferPoolConfig {
+        &self.inner.config
+    }
+
+    /// Current usage statistics
+    pub fn stats(&self) -> BufferPoolStats {
+        let counters = &self.inner.counters;
+        BufferPoolStats {
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            returned: counters.returned.load(Ordering::Relaxed),
+            discarded: counters.discarded.load(Ordering::Relaxed),
+            pooled_buffers: self.inner.buffers.lock().len(),
+            pooled_bytes: counters.pooled_bytes.load(Ordering::Relaxed),
+            high_water_bytes: counters.high_water_bytes.load(Ordering::Relaxed),
+            estimated_segment_size: self.inner.size_estimate.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Get an empty buffer for a segment body of `size_hint` bytes, or of the estimated
+    /// segment size when the size is unknown (0).
+    ///
+    /// An idle buffer large enough is reused; otherwise a new one is allocated, no
+    /// smaller than the estimated segment size so that it fits the next segments too.
+    pub fn acquire(&self, size_hint: usize) -> BytesMut {
+        let estimate = self.inner.size_estimate.load(Ordering::Relaxed);
+        let needed = if size_hint > 0 { size_hint } else { estimate };
+        let pooled = {
+            let mut buffers = self.inner.buffers.lock();
+            buffers
+                .iter()
+                .position(|buffer| buffer.capacity() >= needed)
+                .map(|index| buffers.swap_remove(index))
+        };
+        self.inner.counters.record_acquire(pooled.is_some());
+        match pooled {
+            Some(buffer) => {
+                self.inner.counters.remove_pooled(buffer.capacity());
+                buffer
+            }
+            None => BytesMut::with_capacity(needed.max(estimate)),
+        }
+    }
+
+    /// Turn a filled buffer into a [`Bytes`] view that gives the buffer back to the pool
+    /// once it and every view sliced from it are dropped.
+    ///
+    /// The size of the body is recorded in the segment size estimate.
+    pub fn freeze(&self, buffer: BytesMut) -> Bytes {
+        self.inner.record_size(buffer.len());
+        Bytes::from_owner(Recycled {
+            buffer,
+            pool: Arc::clone(&self.inner),
+        })
+    }
+}
+
+impl Default for SegmentBufferPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for SegmentBufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SegmentBufferPool")
+            .field("config", &self.inner.config)
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(pool: &SegmentBufferPool, size: usize) -> Bytes {
+        let mut buffer = pool.acquire(size);
+        buffer.resize(size, 0xAB);
+        pool.freeze(buffer)
+    }
+
+    #[test]
+    fn test_buffer_reused_after_last_view_drops() {
+        let pool = SegmentBufferPool::new();
+        let segment = fill(&pool, 4096);
+        let view = segment.slice(100..200);
+        drop(segment);
+        assert_eq!(pool.stats().returned, 0, "a view is still alive");
+        assert_eq!(view.len(), 100);
+
+        drop(view);
+        let stats = pool.stats();
+        assert_eq!(stats.returned, 1);
+        assert_eq!(stats.pooled_buffers, 1);
+        assert!(stats.pooled_bytes >= 4096);
+
+        let buffer = pool.acquire(4096);
+        assert!(buffer.is_empty());
+        let stats = pool.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+        assert_eq!(stats.pooled_bytes, 0);
+        assert_eq!(stats.reuse_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_buffers_sized_by_previous_segments() {
+        let pool = SegmentBufferPool::with_config(BufferPoolConfig {
+            size_smoothing: 0.5,
+            ..Default::default()
+        });
+        drop(fill(&pool, 1000));
+        assert_eq!(pool.stats().estimated_segment_size, 1000);
+        drop(fill(&pool, 2000));
+        assert_eq!(pool.stats().estimated_segment_size, 1500);
+
+        // Without a size hint, buffers fit the estimate
+        assert!(pool.acquire(0).capacity() >= 1500);
+        assert!(pool.acquire(0).capacity() >= 1500);
+    }
+
+    #[test]
+    fn test_pool_capped() {
+        let pool = SegmentBufferPool::with_config(BufferPoolConfig {
+            max_pooled_buffers: 2,
+            ..Default::default()
+        });
+        let segments: Vec<_> = (0..4).map(|_| fill(&pool, 1024)).collect();
+        drop(segments);
+        let stats = pool.stats();
+        assert_eq!(stats.returned, 4);
+        assert_eq!(stats.discarded, 2);
+        assert_eq!(stats.pooled_buffers, 2);
+
+        let pool = SegmentBufferPool::with_config(BufferPoolConfig {
+            max_pooled_bytes: 3000,
+            ..Default::default()
+        });
+        let segments: Vec<_> = (0..4).map(|_| fill(&pool, 1024)).collect();
+        drop(segments);
+        let stats = pool.stats();
+        assert_eq!(stats.pooled_buffers, 2);
+        assert!(stats.high_water_bytes <= 3000);
+    }
+
+    #[test]
+    fn test_oversized_buffers() {
+        let config = BufferPoolConfig {
+            max_buffer_capacity: 1024,
+            size_smoothing: 1.0,
+            ..Default::default()
+        };
+        let pool = SegmentBufferPool::with_config(config.clone());
+        drop(fill(&pool, 4096));
+        let stats = pool.stats();
+        assert_eq!((stats.discarded, stats.pooled_buffers), (1, 0));
+
+        let pool = SegmentBufferPool::with_config(BufferPoolConfig {
+            shrink_policy: ShrinkPolicy::Shrink,
+            ..config
+        });
+        drop(fill(&pool, 4096));
+        drop(fill(&pool, 512));
+        let stats = pool.stats();
+        assert_eq!(stats.discarded, 0);
+        assert_eq!(stats.pooled_buffers, 2);
+        assert!(stats.pooled_bytes <= 2048);
+    }
+}
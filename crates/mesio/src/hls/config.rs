@@ -1,6 +1,11 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use crate::DownloaderConfig;
+use crate::hls::buffer_pool::SegmentBufferPool;
+use crate::hls::variant::{AudioRenditionSelection, VariantSelector};
+use crate::retry::RetryPolicy;
+use crate::watchdog::StallConfig;
 
 // --- Performance Configuration Types ---
 
@@ -130,6 +135,15 @@ pub struct HlsConfig {
     pub output_config: HlsOutputConfig,
     /// Performance optimization configuration
     pub performance_config: HlsPerformanceConfig,
+    /// Stall detection overriding the one of the base configuration
+    pub stall_config: Option<StallConfig>,
+}
+
+impl HlsConfig {
+    /// The stall detection applying to HLS downloads
+    pub fn effective_stall_config(&self) -> Option<StallConfig> {
+        self.stall_config.or(self.base.stall_config)
+    }
 }
 
 // --- Playlist Configuration ---
@@ -140,12 +154,29 @@ pub struct HlsPlaylistConfig {
     pub live_max_refresh_retries: u32,
     pub live_refresh_retry_delay: Duration,
     pub variant_selection_policy: HlsVariantSelectionPolicy,
+    /// Let the variant selection pick audio-only variants of a master playlist
+    pub include_audio_only_variants: bool,
+    /// Let the variant selection pick I-frame-only variants of a master playlist
+    pub include_i_frame_variants: bool,
+    /// Alternate audio rendition downloaded along with the variant by
+    /// [`HlsDownloader::download_tracks`](crate::hls::HlsDownloader::download_tracks)
+    pub audio_rendition: Option<AudioRenditionSelection>,
     /// Enable adaptive refresh interval based on actual segment arrival rate
     pub adaptive_refresh_enabled: bool,
     /// Minimum adaptive refresh interval (won't go below this)
     pub adaptive_refresh_min_interval: Duration,
     /// Maximum adaptive refresh interval (won't go above this)
     pub adaptive_refresh_max_interval: Duration,
+    /// Download LL-HLS partial segments as soon as the playlist advertises them
+    pub low_latency_enabled: bool,
+    /// Request the LL-HLS part announced by `#EXT-X-PRELOAD-HINT` before it is listed.
+    /// A failed hint request leaves a gap, as the part is not requested again.
+    pub low_latency_preload_hints: bool,
+    /// Number of recent segments remembered to skip segments listed again
+    pub segment_dedup_window: usize,
+    /// Query parameters ignored when comparing segment URIs, e.g. auth tokens that change
+    /// on every playlist reload
+    pub volatile_query_params: Vec<String>,
 }
 
 impl Default for HlsPlaylistConfig {
@@ -156,9 +187,32 @@ impl Default for HlsPlaylistConfig {
             live_max_refresh_retries: 5,
             live_refresh_retry_delay: Duration::from_secs(1),
             variant_selection_policy: Default::default(),
+            include_audio_only_variants: false,
+            include_i_frame_variants: false,
+            audio_rendition: None,
             adaptive_refresh_enabled: true,
             adaptive_refresh_min_interval: Duration::from_millis(500),
             adaptive_refresh_max_interval: Duration::from_secs(3),
+            low_latency_enabled: true,
+            low_latency_preload_hints: false,
+            segment_dedup_window: 100,
+            volatile_query_params: [
+                "token",
+                "expires",
+                "expire",
+                "sign",
+                "signature",
+                "auth_key",
+                "wsSecret",
+                "wsTime",
+                "txSecret",
+                "txTime",
+                "hdnts",
+                "hdnea",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
         }
     }
 }
@@ -176,6 +230,8 @@ pub enum HlsVariantSelectionPolicy {
         height: u32,
     },
     Custom(String), // For future extensibility, e.g., a name or specific tag
+    /// Delegate to a [`VariantSelector`], e.g. one of the strategies of [`crate::hls::variant`]
+    Selector(Arc<dyn VariantSelector>),
 }
 
 // --- Scheduler Configuration ---
@@ -186,6 +242,13 @@ pub struct HlsSchedulerConfig {
     /// Channel buffer multiplier for processed segments (default: 4)
     /// Actual buffer size = download_concurrency * buffer_multiplier
     pub processed_segment_buffer_multiplier: usize,
+    /// Request the oldest segment in flight again on another connection once it has been
+    /// downloading this long while all download slots are busy, so that one slow segment
+    /// does not hold back the following ones. The first copy to arrive is kept.
+    /// `None` disables it (default: 3s)
+    pub stalled_segment_reissue_after: Option<Duration>,
+    /// What to do when a segment still fails after its retries (default: emit a gap)
+    pub failure_policy: SegmentFailurePolicy,
 }
 
 impl Default for HlsSchedulerConfig {
@@ -193,10 +256,24 @@ impl Default for HlsSchedulerConfig {
         Self {
             download_concurrency: 5,
             processed_segment_buffer_multiplier: 4,
+            stalled_segment_reissue_after: Some(Duration::from_secs(3)),
+            failure_policy: SegmentFailurePolicy::default(),
         }
     }
 }
 
+/// Handling of a media segment whose download failed for good
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SegmentFailurePolicy {
+    /// Skip the segment as soon as its turn comes, reporting a
+    /// [`GapSkipped`](crate::hls::HlsStreamEvent::GapSkipped) event, and carry on with the
+    /// next ones
+    #[default]
+    EmitGap,
+    /// End the download with the error of the segment
+    Abort,
+}
+
 // --- Fetcher Configuration ---
 #[derive(Debug, Clone)]
 pub struct HlsFetcherConfig {
@@ -212,6 +289,9 @@ pub struct HlsFetcherConfig {
     /// Threshold in bytes above which segments are streamed instead of buffered entirely
     /// This reduces memory spikes for large segments (default: 2MB)
     pub streaming_threshold_bytes: usize,
+    /// Pool the segment bodies are read into, reusing the buffers of segments already
+    /// written. Unset by default, each segment then gets a buffer of its own.
+    pub buffer_pool: Option<SegmentBufferPool>,
 }
 
 impl Default for HlsFetcherConfig {
@@ -227,6 +307,31 @@ impl Default for HlsFetcherConfig {
             max_key_retry_delay: Duration::from_secs(5),
             segment_raw_cache_ttl: Duration::from_secs(60), // Default 1 minutes for raw segments
             streaming_threshold_bytes: 2 * 1024 * 1024,     // 2MB threshold for streaming
+            buffer_pool: None,
+        }
+    }
+}
+
+impl HlsFetcherConfig {
+    /// Retry policy of segment downloads: `max_segment_retries` and the segment delays
+    /// applied to the download's policy
+    pub fn segment_retry_policy(&self, base: &RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_segment_retries.saturating_add(1),
+            initial_backoff: self.segment_retry_delay_base,
+            max_backoff: self.max_segment_retry_delay,
+            ..base.clone()
+        }
+    }
+
+    /// Retry policy of key downloads: `max_key_retries` and the key delays applied to the
+    /// download's policy
+    pub fn key_retry_policy(&self, base: &RetryPolicy) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_key_retries.saturating_add(1),
+            initial_backoff: self.key_retry_delay_base,
+            max_backoff: self.max_key_retry_delay,
+            ..base.clone()
         }
     }
 }
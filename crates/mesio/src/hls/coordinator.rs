@@ -8,9 +8,10 @@ use crate::hls::events::HlsStreamEvent;
 use crate::hls::fetcher::{SegmentDownloader, SegmentFetcher};
 use crate::hls::metrics::PerformanceMetrics;
 use crate::hls::output::OutputManager;
-use crate::hls::playlist::{InitialPlaylist, PlaylistEngine, PlaylistProvider};
+use crate::hls::playlist::{AudioRendition, InitialPlaylist, PlaylistEngine, PlaylistProvider};
 use crate::hls::processor::{SegmentProcessor, SegmentTransformer};
 use crate::hls::scheduler::{ScheduledSegmentJob, SegmentScheduler};
+use crate::throttle::{SegmentWindow, Throttle};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
@@ -26,6 +27,9 @@ pub struct AllTaskHandles {
     pub output_manager_handle: JoinHandle<()>,
     /// Shared performance metrics for the pipeline
     pub performance_metrics: Arc<PerformanceMetrics>,
+    /// Alternate audio rendition selected along with the variant, not downloaded by
+    /// this pipeline
+    pub audio_rendition: Option<AudioRendition>,
 }
 
 /// HLS Stream Coordinator: Sets up and spawns all HLS download pipeline components.
@@ -38,6 +42,7 @@ impl HlsStreamCoordinator {
     /// Optional parent_span can be provided for progress bar hierarchy.
     pub async fn setup_and_spawn(
         initial_url: String,
+        probed_playlist: Option<bytes::Bytes>,
         config: Arc<HlsConfig>,
         clients: Arc<ClientPool>,
         cache_manager: Option<Arc<CacheManager>>,
@@ -54,6 +59,10 @@ impl HlsStreamCoordinator {
 
         // Create shared performance metrics for the pipeline
         let performance_metrics = Arc::new(PerformanceMetrics::new());
+        // Occupancy of the download window, reported with the download progress
+        let segment_window = Arc::new(SegmentWindow::new(
+            config.scheduler_config.download_concurrency,
+        ));
 
         let key_fetcher = Arc::new(KeyFetcher::new(
             Arc::clone(&clients),
@@ -65,13 +74,19 @@ impl HlsStreamCoordinator {
             Arc::clone(&key_fetcher),
             cache_manager.clone(),
         ));
-        let segment_fetcher: Arc<dyn SegmentDownloader> = Arc::new(SegmentFetcher::with_metrics(
-            Arc::clone(&clients),
-            Arc::clone(&config),
-            cache_manager.clone(),
-            Arc::clone(&performance_metrics),
-            token.clone(),
-        ));
+        let segment_fetcher: Arc<dyn SegmentDownloader> = Arc::new(
+            SegmentFetcher::with_metrics(
+                Arc::clone(&clients),
+                Arc::clone(&config),
+                cache_manager.clone(),
+                Arc::clone(&performance_metrics),
+                token.clone(),
+            )
+            .with_throttle(
+                Throttle::for_download(&config.base, &initial_url)
+                    .with_segment_window(Arc::clone(&segment_window)),
+            ),
+        );
         let segment_processor: Arc<dyn SegmentTransformer> =
             Arc::new(SegmentProcessor::with_metrics(
                 Arc::clone(&config),
@@ -79,11 +94,15 @@ impl HlsStreamCoordinator {
                 cache_manager.clone(),
                 Arc::clone(&performance_metrics),
             ));
-        let playlist_engine: Arc<dyn PlaylistProvider> = Arc::new(PlaylistEngine::new(
-            Arc::clone(&clients),
-            cache_manager,
-            Arc::clone(&config),
-        ));
+        let playlist_engine: Arc<dyn PlaylistProvider> = Arc::new(
+            PlaylistEngine::with_metrics(
+                Arc::clone(&clients),
+                cache_manager,
+                Arc::clone(&config),
+                Arc::clone(&performance_metrics),
+            )
+            .with_probed_playlist(probed_playlist),
+        );
 
         // Channels - sized for optimal throughput
         let (client_event_tx, client_event_rx) = mpsc::channel(32);
@@ -102,33 +121,59 @@ impl HlsStreamCoordinator {
 
         // initial playlist
         let initial_playlist_data = playlist_engine.load_initial_playlist(&initial_url).await?;
-        let (initial_media_playlist, base_url, is_live, selected_media_playlist_url) =
-            match &initial_playlist_data {
-                InitialPlaylist::Master(_master, _) => {
-                    // master is not directly used here after selection
-                    let media_details = playlist_engine
-                        .select_media_playlist(
-                            &initial_playlist_data,
-                            &config.playlist_config.variant_selection_policy,
-                        )
-                        .await?;
-                    let end_list = media_details.playlist.end_list;
-
-                    if end_list {
-                        debug!("Selected media playlist is VOD.");
-                    }
-
-                    (
-                        media_details.playlist,
-                        media_details.base_url,
-                        !end_list,
-                        Some(media_details.url),
+        let (
+            initial_media_playlist,
+            base_url,
+            is_live,
+            selected_media_playlist_url,
+            audio_rendition,
+        ) = match &initial_playlist_data {
+            InitialPlaylist::Master(_master, _) => {
+                // master is not directly used here after selection
+                let media_details = playlist_engine
+                    .select_media_playlist(
+                        &initial_playlist_data,
+                        &config.playlist_config.variant_selection_policy,
                     )
+                    .await?;
+                let end_list = media_details.playlist.end_list;
+
+                if end_list {
+                    debug!("Selected media playlist is VOD.");
                 }
-                InitialPlaylist::Media(media, base) => {
-                    (media.clone(), base.clone(), !media.end_list, None)
+
+                (
+                    media_details.playlist,
+                    media_details.base_url,
+                    !end_list,
+                    Some(media_details.url),
+                    media_details.audio_rendition,
+                )
+            }
+            InitialPlaylist::Media(media, base) => {
+                (media.clone(), base.clone(), !media.end_list, None, None)
+            }
+        };
+
+        // Open connections to the segment host while the first segments are scheduled
+        let prewarm_connections = config.base.prewarm_connections;
+        let first_segment_url = initial_media_playlist.segments.first().and_then(|segment| {
+            url::Url::parse(&base_url)
+                .and_then(|base| base.join(&segment.uri))
+                .ok()
+        });
+        if prewarm_connections > 0
+            && let Some(segment_url) = first_segment_url
+        {
+            let clients = Arc::clone(&clients);
+            let token = token_for_scheduler.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = token.cancelled() => {}
+                    _ = clients.prewarm(&segment_url, prewarm_connections) => {}
                 }
-            };
+            });
+        }
 
         // OutputManager is responsible for managing the output of the stream
         // Pass performance_metrics to log summary on stream end (Requirements 7.3)
@@ -140,7 +185,8 @@ impl HlsStreamCoordinator {
             initial_media_playlist.media_sequence,
             token_for_output_manager,
             Arc::clone(&performance_metrics),
-        );
+        )
+        .with_segment_window(Arc::clone(&segment_window));
 
         let mut segment_scheduler = SegmentScheduler::with_metrics(
             Arc::clone(&config),
@@ -150,7 +196,8 @@ impl HlsStreamCoordinator {
             processed_segments_tx,
             token_for_scheduler,
             Arc::clone(&performance_metrics),
-        );
+        )
+        .with_segment_window(segment_window);
 
         let output_manager_handle = tokio::spawn(async move {
             output_manager.run().await;
@@ -204,6 +251,7 @@ impl HlsStreamCoordinator {
             scheduler_handle,
             output_manager_handle,
             performance_metrics,
+            audio_rendition,
         };
 
         Ok((client_event_rx, handles))
@@ -248,7 +296,10 @@ mod tests {
             .with_timeout(std::time::Duration::from_secs(30))
             .with_header("referer", "http://live.douyin.com")
             .build();
-        let clients = Arc::new(crate::downloader::create_client_pool(&downloader_config).unwrap());
+        let clients = Arc::new(
+            crate::downloader::create_client_pool(&downloader_config, crate::ProtocolType::Hls)
+                .unwrap(),
+        );
         // let initial_url = "https://demo.unified-streaming.com/k8s/features/stable/video/tears-of-steel/tears-of-steel.ism/.m3u8".to_string();
         let initial_url = "http://pull-hls-f11.douyincdn.com/thirdgame/stream-693725261315179274.m3u8?arch_hrchy=h1&exp_hrchy=h1&expire=1747991885&major_anchor_level=common&sign=f0e1fa5f131404440612b895d83316bc&t_id=037-20250516171805D14BA54D125D402EA0DF-ytZ138".to_string();
 
@@ -256,6 +307,7 @@ mod tests {
 
         let result = HlsStreamCoordinator::setup_and_spawn(
             initial_url,
+            None,
             config,
             clients,
             cache,
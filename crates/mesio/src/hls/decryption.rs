@@ -4,7 +4,7 @@ use crate::CacheManager;
 use crate::cache::{CacheKey, CacheMetadata, CacheResourceType};
 use crate::hls::HlsDownloaderError;
 use crate::hls::config::HlsConfig;
-use crate::hls::retry::{RetryAction, RetryPolicy, is_retryable_reqwest_error, retry_with_backoff};
+use crate::retry::{RetryAction, retry_with_backoff};
 use aes::Aes128;
 use bytes::Bytes;
 use cipher::{BlockModeDecrypt, KeyIvInit, block_padding::Pkcs7};
@@ -105,19 +105,19 @@ impl KeyFetcher {
     }
 
     pub async fn fetch_key(&self, key_uri: &str) -> Result<Bytes, HlsDownloaderError> {
-        let policy = RetryPolicy {
-            max_retries: self.config.fetcher_config.max_key_retries,
-            base_delay: self.config.fetcher_config.key_retry_delay_base,
-            max_delay: self.config.fetcher_config.max_key_retry_delay,
-            jitter: true,
-        };
+        let policy = self
+            .config
+            .fetcher_config
+            .key_retry_policy(&self.config.base.retry_policy);
 
         let parsed_url = Url::parse(key_uri).ok();
         let clients = &self.clients;
         let config = &self.config;
         let token = &self.token;
 
-        retry_with_backoff(&policy, token, |_attempt| {
+        let on_progress = config.base.on_progress.as_ref();
+
+        retry_with_backoff(&policy, key_uri, on_progress, token, |_attempt| {
             let parsed_url = parsed_url.clone();
             async move {
                 let client = parsed_url
@@ -146,40 +146,26 @@ impl KeyFetcher {
                             };
                             match bytes {
                                 Ok(b) => RetryAction::Success(b),
-                                Err(e) => {
-                                    if is_retryable_reqwest_error(&e) {
-                                        RetryAction::Retry(HlsDownloaderError::from(e))
-                                    } else {
-                                        RetryAction::Fail(HlsDownloaderError::from(e))
-                                    }
-                                }
+                                Err(e) => RetryAction::from_request_error(e),
                             }
-                        } else if response.status().is_client_error() {
-                            RetryAction::Fail(HlsDownloaderError::Decryption {
-                                reason: format!(
-                                    "Client error {} fetching key from {}",
-                                    response.status(),
-                                    key_uri
-                                ),
-                            })
                         } else {
-                            // Server errors (5xx) are retryable
-                            RetryAction::Retry(HlsDownloaderError::Decryption {
-                                reason: format!(
-                                    "Server error {} fetching key from {}",
-                                    response.status(),
-                                    key_uri
-                                ),
-                            })
-                        }
-                    }
-                    Err(e) => {
-                        if is_retryable_reqwest_error(&e) {
-                            RetryAction::Retry(HlsDownloaderError::from(e))
-                        } else {
-                            RetryAction::Fail(HlsDownloaderError::from(e))
+                            let status = response.status();
+                            let kind = if status.is_server_error() {
+                                "Server"
+                            } else {
+                                "Client"
+                            };
+                            RetryAction::from_response(
+                                &response,
+                                HlsDownloaderError::Decryption {
+                                    reason: format!(
+                                        "{kind} error {status} fetching key from {key_uri}"
+                                    ),
+                                },
+                            )
                         }
                     }
+                    Err(e) => RetryAction::from_request_error(e),
                 }
             }
         })
@@ -187,6 +173,27 @@ impl KeyFetcher {
     }
 }
 
+/// Check that segments encrypted with `key_info` can be decrypted.
+///
+/// Only full-segment AES-128 with the default `identity` key format is supported.
+/// SAMPLE-AES encrypts individual samples inside the container and other key formats
+/// are DRM systems, both would otherwise produce unusable output.
+pub fn ensure_supported(key_info: &Key) -> Result<(), HlsDownloaderError> {
+    if key_info.method != m3u8_rs::KeyMethod::AES128 {
+        return Err(HlsDownloaderError::UnsupportedEncryption {
+            reason: format!("METHOD={} is not supported", key_info.method),
+        });
+    }
+    if let Some(keyformat) = key_info.keyformat.as_deref()
+        && keyformat != "identity"
+    {
+        return Err(HlsDownloaderError::UnsupportedEncryption {
+            reason: format!("KEYFORMAT=\"{keyformat}\" is not supported"),
+        });
+    }
+    Ok(())
+}
+
 // --- DecryptionService Struct ---
 pub struct DecryptionService {
     config: Arc<HlsConfig>,
@@ -307,13 +314,7 @@ impl DecryptionService {
         iv_override: Option<[u8; 16]>, // e.g. calculated from media sequence for AES-128 CBC
         base_url: &str,
     ) -> Result<Bytes, HlsDownloaderError> {
-        if key_info.method != m3u8_rs::KeyMethod::AES128 {
-            // Changed to AES128 (all caps)
-            // For now, only support AES-128. SAMPLE-AES would need different handling.
-            return Err(HlsDownloaderError::Decryption {
-                reason: format!("Unsupported decryption method: {key_info:?}"),
-            });
-        }
+        ensure_supported(key_info)?;
 
         let key_data = self.get_key_data(key_info, base_url).await?;
 
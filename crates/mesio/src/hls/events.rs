@@ -10,6 +10,8 @@ pub enum GapSkipReason {
     DurationThreshold(Duration),
     /// Gap skipped because both count and duration thresholds were exceeded
     BothThresholds { count: u64, duration: Duration },
+    /// Gap left by a segment that failed to download
+    SegmentFailed,
 }
 
 #[derive(Debug, Clone)]
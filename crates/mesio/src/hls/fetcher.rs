@@ -4,12 +4,15 @@ use crate::cache::{CacheMetadata, CacheResourceType};
 use crate::downloader::ClientPool;
 use crate::hls::HlsDownloaderError;
 use crate::hls::config::HlsConfig;
-use crate::hls::retry::{RetryAction, RetryPolicy, is_retryable_reqwest_error, retry_with_backoff};
+use crate::retry::{RetryAction, RetryCondition, retry_with_backoff};
+use crate::tee::TeeKind;
+use crate::throttle::Throttle;
 use crate::{CacheManager, cache::CacheKey};
 use async_trait::async_trait;
 use bytes::Bytes;
 use indicatif::ProgressStyle;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use tracing::{Span, debug, instrument, trace, warn};
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 use url::Url;
@@ -33,6 +36,8 @@ pub struct SegmentFetcher {
     performance_metrics: Option<Arc<super::metrics::PerformanceMetrics>>,
     /// Pre-built progress bar style to avoid re-parsing the template on every segment
     progress_style: ProgressStyle,
+    /// Bandwidth limits and rate statistics shared by all segments of the download
+    throttle: Throttle,
     token: CancellationToken,
 }
 
@@ -59,6 +64,7 @@ impl SegmentFetcher {
             cache_service,
             performance_metrics: None,
             progress_style,
+            throttle: Throttle::default(),
             token,
         }
     }
@@ -76,33 +82,44 @@ impl SegmentFetcher {
         fetcher
     }
 
+    /// Limit the segment downloads and report their throughput
+    pub fn with_throttle(mut self, throttle: Throttle) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
     /// Fetches a segment with retry logic.
     /// Retries on network errors and server errors (5xx).
     /// For large segments (above streaming_threshold_bytes), uses streaming to reduce memory spikes.
+    /// The last error is reported as a `SegmentFailed` of segment `seq`.
     async fn fetch_with_retries(
         &self,
+        seq: u64,
         segment_url: &Url,
         byte_range: Option<&m3u8_rs::ByteRange>,
         segment_span: &Span,
     ) -> Result<Bytes, HlsDownloaderError> {
-        let policy = RetryPolicy {
-            max_retries: self.config.fetcher_config.max_segment_retries,
-            base_delay: self.config.fetcher_config.segment_retry_delay_base,
-            max_delay: self.config.fetcher_config.max_segment_retry_delay,
-            jitter: true,
-        };
+        let policy = self
+            .config
+            .fetcher_config
+            .segment_retry_policy(&self.config.base.retry_policy);
         let streaming_threshold = self.config.fetcher_config.streaming_threshold_bytes;
 
-        retry_with_backoff(&policy, &self.token, |_attempt| async {
-            let client = self.clients.client_for_url(segment_url);
-            let mut request_builder = client
+        let on_progress = self.config.base.on_progress.as_ref();
+        let attempts = AtomicU32::new(0);
+
+        retry_with_backoff(&policy, segment_url.as_str(), on_progress, &self.token, |_| async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            let client = self.clients.media_client_for_url(segment_url);
+            let request_builder = client
                 .get(segment_url.clone())
                 .query(&self.config.base.params);
+            let mut request_builder = self.clients.auth().apply(segment_url, request_builder);
             if let Some(range) = byte_range {
-                let start = range.offset.unwrap_or(0);
-                let end = start.saturating_add(range.length).saturating_sub(1);
-                let range_str = format!("bytes={start}-{end}");
-                request_builder = request_builder.header(reqwest::header::RANGE, range_str);
+                request_builder = request_builder.header(
+                    reqwest::header::RANGE,
+                    hls::byte_range::http_range_header(range),
+                );
             }
 
             let download_start = std::time::Instant::now();
@@ -118,8 +135,12 @@ impl SegmentFetcher {
 
             match response {
                 Ok(response) => {
+                    let reused_connection = self.throttle.record_response(&response);
                     if response.status().is_success() {
                         let http_version = response.version();
+                        // Servers ignoring the Range header answer 200 with the whole resource
+                        let full_body_for_range = byte_range
+                            .filter(|_| response.status() != reqwest::StatusCode::PARTIAL_CONTENT);
 
                         trace!(
                             url = %segment_url,
@@ -132,9 +153,11 @@ impl SegmentFetcher {
                             segment_span.pb_set_length(len);
                         }
 
-                        // Use streaming for large segments to reduce memory spikes
-                        let bytes_result = if content_length
-                            .is_some_and(|len| len as usize > streaming_threshold)
+                        // Use streaming for large segments to reduce memory spikes, and
+                        // whenever the body is throttled or read into a pooled buffer
+                        let bytes_result = if self.throttle.is_active()
+                            || self.config.fetcher_config.buffer_pool.is_some()
+                            || content_length.is_some_and(|len| len as usize > streaming_threshold)
                         {
                             self.stream_response(response, segment_span).await
                         } else {
@@ -153,17 +176,42 @@ impl SegmentFetcher {
                             }
                         };
 
+                        // The raw copy keeps the body as served, before any range is cut
+                        if let (Ok(body), Some(tee)) = (&bytes_result, &self.config.base.raw_tee) {
+                            tee.write(TeeKind::Segment, segment_url.as_str(), body);
+                        }
+
+                        let bytes_result = match (bytes_result, full_body_for_range) {
+                            (Ok(body), Some(range)) => {
+                                debug!(
+                                    url = %segment_url,
+                                    "Server ignored Range request, extracting byte range from full response"
+                                );
+                                hls::byte_range::slice_full_body(&body, range).ok_or_else(|| {
+                                    HlsDownloaderError::Protocol {
+                                        reason: format!(
+                                            "Response of {} bytes does not contain byte range {}@{} for segment {}",
+                                            body.len(),
+                                            range.length,
+                                            range.offset.unwrap_or(0),
+                                            segment_url
+                                        ),
+                                    }
+                                })
+                            }
+                            (result, _) => result,
+                        };
+
                         match bytes_result {
                             Ok(bytes) => {
                                 let download_latency_ms =
                                     download_start.elapsed().as_millis() as u64;
 
                                 if let Some(metrics) = &self.performance_metrics {
-                                    let host = segment_url.host_str().unwrap_or("unknown");
-                                    metrics.record_request_with_host(
+                                    metrics.record_request(
                                         http_version,
                                         bytes.len() as u64,
-                                        host,
+                                        reused_connection,
                                     );
                                     metrics
                                         .record_download(bytes.len() as u64, download_latency_ms);
@@ -184,50 +232,50 @@ impl SegmentFetcher {
                                     metrics.record_download_error();
                                 }
                                 // Body read errors during streaming are retryable
-                                RetryAction::Retry(err)
+                                match err {
+                                    HlsDownloaderError::Network { source } => {
+                                        RetryAction::from_request_error(source)
+                                    }
+                                    err @ HlsDownloaderError::Timeout { .. } => {
+                                        RetryAction::transient(err, RetryCondition::Timeout)
+                                    }
+                                    err => RetryAction::transient(err, RetryCondition::Interrupted),
+                                }
                             }
                         }
-                    } else if response.status().is_client_error() {
-                        if let Some(metrics) = &self.performance_metrics {
+                    } else {
+                        let retryable = RetryCondition::from_status(response.status()).is_some();
+                        if !retryable && let Some(metrics) = &self.performance_metrics {
                             metrics.record_download_error();
                         }
-                        RetryAction::Fail(HlsDownloaderError::SegmentFetch {
-                            reason: format!(
-                                "Client error {} for segment {}",
-                                response.status(),
-                                segment_url
-                            ),
-                            retryable: false,
-                        })
-                    } else {
-                        // Server errors (5xx) are retryable
-                        RetryAction::Retry(HlsDownloaderError::SegmentFetch {
-                            reason: format!(
-                                "Server error {} for segment {}",
-                                response.status(),
-                                segment_url
-                            ),
-                            retryable: true,
-                        })
+                        let error = HlsDownloaderError::from_response(response, "segment").await;
+                        RetryAction::from_error(error)
                     }
                 }
                 Err(e) => {
-                    if is_retryable_reqwest_error(&e) {
-                        RetryAction::Retry(HlsDownloaderError::from(e))
-                    } else {
-                        if let Some(metrics) = &self.performance_metrics {
-                            metrics.record_download_error();
-                        }
-                        RetryAction::Fail(HlsDownloaderError::from(e))
+                    let action = RetryAction::from_request_error(e);
+                    if matches!(action, RetryAction::Fail(_))
+                        && let Some(metrics) = &self.performance_metrics
+                    {
+                        metrics.record_download_error();
                     }
+                    action
                 }
             }
         })
         .await
+        .map_err(|error| match error {
+            HlsDownloaderError::Cancelled => error,
+            error => {
+                HlsDownloaderError::segment_failed(seq, attempts.load(Ordering::Relaxed), error)
+            }
+        })
     }
 
     /// Streams a response body in chunks to reduce memory pressure for large segments.
     /// Updates progress as chunks are received.
+    /// With a buffer pool configured, the body is read into a pooled buffer that returns
+    /// to the pool once the segment is dropped.
     async fn stream_response(
         &self,
         response: reqwest::Response,
@@ -237,8 +285,12 @@ impl SegmentFetcher {
         use futures::StreamExt;
 
         let content_length = response.content_length().unwrap_or(0) as usize;
-        let mut buffer = BytesMut::with_capacity(content_length);
-        let mut stream = response.bytes_stream();
+        let pool = self.config.fetcher_config.buffer_pool.as_ref();
+        let mut buffer = match pool {
+            Some(pool) => pool.acquire(content_length),
+            None => BytesMut::with_capacity(content_length),
+        };
+        let mut stream = self.throttle.wrap(response.bytes_stream());
         let mut downloaded: u64 = 0;
 
         while let Some(chunk_result) = tokio::select! {
@@ -253,12 +305,47 @@ impl SegmentFetcher {
             segment_span.pb_set_position(downloaded);
         }
 
-        Ok(buffer.freeze())
+        Ok(match pool {
+            Some(pool) => pool.freeze(buffer),
+            None => buffer.freeze(),
+        })
     }
 }
 
 #[async_trait]
 impl SegmentDownloader for SegmentFetcher {
+    /// Fetches the segment of a job, refreshing the credentials and trying once more when
+    /// the origin rejects them.
+    async fn fetch_segment(
+        &self,
+        job: &ScheduledSegmentJob,
+        segment_url: &Url,
+        segment_span: &Span,
+    ) -> Result<Bytes, HlsDownloaderError> {
+        let auth = self.clients.auth();
+        let generation = auth.generation();
+        let byte_range = job.media_segment.byte_range.as_ref();
+        let seq = job.media_sequence_number;
+        match self
+            .fetch_with_retries(seq, segment_url, byte_range, segment_span)
+            .await
+        {
+            Err(error) => match auth.rejection(&error) {
+                Some(reason) => {
+                    warn!(
+                        msn = seq,
+                        "Segment rejected, refreshing credentials: {error}"
+                    );
+                    auth.refresh(segment_url, reason, generation).await?;
+                    self.fetch_with_retries(seq, segment_url, byte_range, segment_span)
+                        .await
+                }
+                None => Err(error),
+            },
+            result => result,
+        }
+    }
+
     /// Downloads a segment from the given job.
     /// If the segment is already cached, it retrieves it from the cache.
     /// If not, it downloads the segment and caches it.
@@ -337,13 +424,7 @@ impl SegmentDownloader for SegmentFetcher {
         let result = if let Some(bytes) = cached_bytes {
             Ok(bytes)
         } else {
-            let downloaded_bytes = self
-                .fetch_with_retries(
-                    segment_url,
-                    job.media_segment.byte_range.as_ref(),
-                    &current_span,
-                )
-                .await?;
+            let downloaded_bytes = self.fetch_segment(job, segment_url, &current_span).await?;
 
             if let Some(cache) = &self.cache_service {
                 let metadata = CacheMetadata::new(downloaded_bytes.len() as u64)
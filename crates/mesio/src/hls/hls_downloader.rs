@@ -4,23 +4,30 @@ use std::time::Instant;
 use tracing::warn;
 
 use crate::media_protocol::{Cacheable, MultiSource};
+use crate::probe::ProbeHandoff;
 use futures::StreamExt;
-use hls::HlsData;
+use hls::{HlsData, HlsTrack, TrackData};
 use reqwest::Client;
 use tokio_stream::wrappers::ReceiverStream;
 use tracing::debug;
 
 use crate::{
-    BoxMediaStream, CacheManager, Download, DownloadError, ProtocolBase, SourceManager,
-    downloader::create_client_pool, hls::HlsDownloaderError,
+    BoxMediaStream, CacheManager, Download, DownloadError, ProtocolBase, ProtocolType,
+    SourceManager,
+    downloader::{ClientPool, Clients, create_clients},
+    hls::HlsDownloaderError,
+    watchdog,
 };
 use tokio_util::sync::CancellationToken;
 
+use super::playlist::AudioRendition;
 use super::{HlsConfig, HlsStreamCoordinator, HlsStreamEvent, coordinator::AllTaskHandles};
 
 pub struct HlsDownloader {
-    clients: Arc<crate::downloader::ClientPool>,
+    clients: Clients,
     config: HlsConfig,
+    /// Response of a protocol probe, used by the first download of the probed URL
+    probe: ProbeHandoff,
 }
 
 impl HlsDownloader {
@@ -31,15 +38,26 @@ impl HlsDownloader {
     /// Create a new HlsDownloader with custom configuration
     pub fn with_config(config: HlsConfig) -> Result<Self, DownloadError> {
         let downloader_config = config.base.clone();
-        let clients = Arc::new(create_client_pool(&downloader_config)?);
-        Ok(Self { clients, config })
+        let clients = create_clients(&downloader_config, ProtocolType::Hls)?;
+        Ok(Self {
+            clients,
+            config,
+            probe: ProbeHandoff::default(),
+        })
+    }
+
+    /// Use the playlist received by a protocol probe instead of requesting its URL again
+    pub(crate) fn with_probe(mut self, probe: ProbeHandoff) -> Self {
+        self.probe = probe;
+        self
     }
 
     pub fn config(&self) -> &HlsConfig {
         &self.config
     }
 
-    pub fn client(&self) -> &Client {
+    /// The client built from the configuration, `None` when clients are provided per source
+    pub fn client(&self) -> Option<&Client> {
         self.clients.default_client()
     }
 
@@ -51,7 +69,7 @@ impl HlsDownloader {
     ) -> Result<BoxMediaStream<HlsData, HlsDownloaderError>, DownloadError> {
         let start_time = Instant::now();
         match self
-            .perform_download(&source.url, Some(source_manager), None, token)
+            .download_with_clients(&source.url, self.clients.pool(source), None, token)
             .await
         {
             Ok(stream) => {
@@ -79,6 +97,79 @@ impl HlsDownloader {
         cache_manager: Option<Arc<CacheManager>>,
         token: CancellationToken,
     ) -> Result<BoxMediaStream<HlsData, HlsDownloaderError>, DownloadError> {
+        let clients = self.clients.pool(&ContentSource::new(url, 0));
+        self.download_with_clients(url, clients, cache_manager, token)
+            .await
+    }
+
+    /// Download the selected variant along with the alternate audio rendition selected by
+    /// [`HlsPlaylistConfig::audio_rendition`](super::config::HlsPlaylistConfig), each
+    /// segment tagged with its track.
+    ///
+    /// The two media playlists are downloaded in parallel. Without an alternate audio
+    /// rendition to download, or when its playlist cannot be loaded, only the main track
+    /// is emitted.
+    pub async fn download_tracks(
+        &self,
+        url: &str,
+        token: CancellationToken,
+    ) -> Result<BoxMediaStream<TrackData, HlsDownloaderError>, DownloadError> {
+        let clients = self.clients.pool(&ContentSource::new(url, 0));
+        let (main, audio_rendition) = self
+            .download_track(url, Arc::clone(&clients), None, token.clone())
+            .await?;
+        let main = main.map(|item| item.map(|data| TrackData::new(HlsTrack::Main, data)));
+        let Some(audio_rendition) = audio_rendition else {
+            return Ok(main.boxed());
+        };
+
+        // The playlist of the rendition is a media playlist, nothing is selected from it
+        let audio = match self
+            .download_track(&audio_rendition.url, clients, None, token)
+            .await
+        {
+            Ok((audio, _)) => audio,
+            Err(e) => {
+                warn!(
+                    url = %audio_rendition.url,
+                    error = %e,
+                    "Failed to load the HLS audio rendition, downloading the variant only"
+                );
+                return Ok(main.boxed());
+            }
+        };
+        let track = HlsTrack::Audio(audio_rendition.rendition);
+        let audio = audio.map(move |item| item.map(|data| TrackData::new(track.clone(), data)));
+        Ok(futures::stream::select(main, audio).boxed())
+    }
+
+    async fn download_with_clients(
+        &self,
+        url: &str,
+        clients: Arc<ClientPool>,
+        cache_manager: Option<Arc<CacheManager>>,
+        token: CancellationToken,
+    ) -> Result<BoxMediaStream<HlsData, HlsDownloaderError>, DownloadError> {
+        self.download_track(url, clients, cache_manager, token)
+            .await
+            .map(|(stream, _)| stream)
+    }
+
+    /// Download the media playlist of `url`, or of the variant selected from it, and
+    /// return the alternate audio rendition selected along with the variant
+    async fn download_track(
+        &self,
+        url: &str,
+        clients: Arc<ClientPool>,
+        cache_manager: Option<Arc<CacheManager>>,
+        token: CancellationToken,
+    ) -> Result<
+        (
+            BoxMediaStream<HlsData, HlsDownloaderError>,
+            Option<AudioRendition>,
+        ),
+        DownloadError,
+    > {
         let config = Arc::new(self.config.clone());
 
         // Capture current span for HLS segment downloads to be children
@@ -89,10 +180,11 @@ impl HlsDownloader {
             Some(parent_span)
         };
 
-        let (client_event_rx, handles) = HlsStreamCoordinator::setup_and_spawn(
+        let (client_event_rx, mut handles) = HlsStreamCoordinator::setup_and_spawn(
             url.to_string(),
+            self.probe.take_manifest(url),
             config.clone(),
-            Arc::clone(&self.clients),
+            clients,
             cache_manager,
             token,
             parent_span,
@@ -100,6 +192,7 @@ impl HlsDownloader {
         .await?;
 
         let stream = ReceiverStream::new(client_event_rx);
+        let audio_rendition = handles.audio_rendition.take();
 
         // Spawn a separate task to await the completion of all pipeline components.
         // This ensures that graceful shutdown logic is fully executed.
@@ -145,8 +238,25 @@ impl HlsDownloader {
             }
         });
 
-        // Box the stream and return
-        Ok(stream.boxed())
+        let stream = stream.boxed();
+        let Some(stall_config) = self.config.effective_stall_config() else {
+            return Ok((stream, audio_rendition));
+        };
+        // Media time of the segments received so far, which grows as the playlist advances
+        let mut media_time_ms = 0u64;
+        let position = move |data: &HlsData| {
+            let segment = data.media_segment().filter(|_| !data.is_mp4_init())?;
+            media_time_ms += (segment.duration as f64 * 1000.0).round() as u64;
+            Some(media_time_ms)
+        };
+        let stream = watchdog::watch(
+            stream,
+            url,
+            stall_config,
+            self.config.base.on_progress.clone(),
+            Box::new(position),
+        );
+        Ok((stream, audio_rendition))
     }
 }
 
@@ -156,6 +266,10 @@ impl ProtocolBase for HlsDownloader {
     fn new(config: Self::Config) -> Result<Self, DownloadError> {
         Self::with_config(config)
     }
+
+    fn set_shared_rate_limiter(&mut self, limiter: RateLimiter) {
+        self.config.base.shared_rate_limiter = Some(limiter);
+    }
 }
 
 impl Download for HlsDownloader {
@@ -211,3 +325,577 @@ impl Cacheable for HlsDownloader {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DownloaderConfig;
+    use crate::downloader::create_client_pool;
+    use crate::hls::config::SegmentFailurePolicy;
+    use crate::hls::{
+        AudioRenditionSelection, BufferPoolConfig, BufferPoolStats, SegmentBufferPool,
+    };
+    use crate::mock_origin::{HlsPlaylist, MockOrigin};
+    use crate::throttle::OnProgress;
+    use parking_lot::Mutex;
+    use pipeline_common::ProgressEvent;
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Serve a VOD playlist of `segments` null TS packets over keep-alive connections,
+    /// counting the connections accepted
+    async fn spawn_keep_alive_server(segments: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!(
+            "http://{}/live.m3u8",
+            listener.local_addr().expect("local addr")
+        );
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&accepted);
+
+        let mut playlist = String::from(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:1\n#EXT-X-MEDIA-SEQUENCE:0\n",
+        );
+        for i in 0..segments {
+            playlist.push_str(&format!("#EXTINF:1.0,\nseg{i}.ts\n"));
+        }
+        playlist.push_str("#EXT-X-ENDLIST\n");
+        let playlist = Arc::new(playlist.into_bytes());
+        let mut packet = vec![0xFF; 188];
+        packet[..4].copy_from_slice(&[0x47, 0x1F, 0xFF, 0x10]);
+        let packet = Arc::new(packet);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::Relaxed);
+                let playlist = Arc::clone(&playlist);
+                let packet = Arc::clone(&packet);
+                tokio::spawn(async move {
+                    let mut pending = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    loop {
+                        let end = loop {
+                            if let Some(pos) = pending.windows(4).position(|w| w == b"\r\n\r\n") {
+                                break pos + 4;
+                            }
+                            match socket.read(&mut buf).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => pending.extend_from_slice(&buf[..n]),
+                            }
+                        };
+                        let head: Vec<u8> = pending.drain(..end).collect();
+                        let head = String::from_utf8_lossy(&head);
+                        let mut request_line = head.split_whitespace();
+                        let method = request_line.next().unwrap_or_default();
+                        let target = request_line.next().unwrap_or_default();
+
+                        let body: &[u8] = if target == "/live.m3u8" {
+                            &playlist
+                        } else if target.ends_with(".ts") {
+                            &packet
+                        } else {
+                            let _ = socket
+                                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                                .await;
+                            continue;
+                        };
+                        let mut response =
+                            format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len())
+                                .into_bytes();
+                        if method != "HEAD" {
+                            response.extend_from_slice(body);
+                        }
+                        if socket.write_all(&response).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (url, accepted)
+    }
+
+    #[tokio::test]
+    async fn test_segments_reuse_connections() {
+        let (url, accepted) = spawn_keep_alive_server(500).await;
+        let rates = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&rates);
+        let mut config = HlsConfig::default();
+        config.base.on_progress = Some(OnProgress::new(move |event| {
+            if let ProgressEvent::DownloadProgress { rate, .. } = event {
+                sink.lock().push(rate);
+            }
+        }));
+        let concurrency = config.scheduler_config.download_concurrency;
+        let downloader = HlsDownloader::with_config(config).unwrap();
+
+        let items: Vec<_> = downloader
+            .download(&url, CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        let segments = items
+            .iter()
+            .filter(|item| matches!(item, Ok(HlsData::TsData(_))))
+            .count();
+        assert_eq!(segments, 500);
+
+        let stats = rates.lock().last().expect("progress reported").connections;
+        assert_eq!(stats.requests, 500);
+        assert!(stats.reused() > 0, "{stats:?}");
+        // No handshake per segment: only the concurrent segment downloads open connections,
+        // plus the one of the playlist
+        assert!(stats.new_connections <= concurrency as u64, "{stats:?}");
+        assert!(accepted.load(Ordering::Relaxed) <= concurrency + 1);
+    }
+
+    #[tokio::test]
+    async fn test_prewarmed_connections_are_reused() {
+        let (url, accepted) = spawn_keep_alive_server(4).await;
+        let clients = create_client_pool(&DownloaderConfig::default(), ProtocolType::Hls).unwrap();
+        let segment = url::Url::parse(&url).unwrap().join("seg0.ts").unwrap();
+
+        clients.prewarm(&segment, 3).await;
+        assert_eq!(accepted.load(Ordering::Relaxed), 3);
+
+        let client = clients.media_client_for_url(&segment);
+        for _ in 0..3 {
+            let body = client
+                .get(segment.clone())
+                .send()
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            assert_eq!(body.len(), 188);
+        }
+        assert_eq!(accepted.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_raw_tee_copies_segments_and_playlist() {
+        use crate::tee::RawTee;
+        use bytes::Bytes;
+
+        let (url, _) = spawn_keep_alive_server(4).await;
+        let dir = std::env::temp_dir().join(format!("mesio-hls-tee-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let tee = RawTee::path_template(format!("{}/{{kind}}/{{seq}}-{{name}}", dir.display()));
+
+        let rates = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&rates);
+        let mut config = HlsConfig::default();
+        config.base.raw_tee = Some(tee.clone());
+        config.base.on_progress = Some(OnProgress::new(move |event| {
+            if let ProgressEvent::DownloadProgress { rate, .. } = event {
+                sink.lock().push(rate);
+            }
+        }));
+        let downloader = HlsDownloader::with_config(config).unwrap();
+        let items: Vec<_> = downloader
+            .download(&url, CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        tee.closed().await;
+
+        // The processed segments are all delivered
+        let segments: Vec<Bytes> = items
+            .iter()
+            .filter_map(|item| match item {
+                Ok(HlsData::TsData(ts)) => Some(ts.data.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(segments.len(), 4);
+
+        // Each segment is copied as served, next to the playlist it was listed in
+        let read_dir = |kind: &str| -> Vec<(String, Vec<u8>)> {
+            let mut files: Vec<_> = std::fs::read_dir(dir.join(kind))
+                .unwrap()
+                .map(|entry| {
+                    let path = entry.unwrap().path();
+                    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                    (name, std::fs::read(&path).unwrap())
+                })
+                .collect();
+            files.sort();
+            files
+        };
+        let raw_segments = read_dir("segment");
+        assert_eq!(raw_segments.len(), 4);
+        for (name, body) in &raw_segments {
+            assert!(name.ends_with(".ts"), "{name}");
+            assert_eq!(body[..], segments[0][..]);
+        }
+        let playlists = read_dir("playlist");
+        assert_eq!(playlists[0].0, "0-live.m3u8");
+        assert!(playlists[0].1.starts_with(b"#EXTM3U"));
+
+        let total: usize = raw_segments
+            .iter()
+            .chain(&playlists)
+            .map(|(_, body)| body.len())
+            .sum();
+        assert_eq!(tee.bytes_written(), total as u64);
+        let rate = rates.lock().last().cloned().expect("progress reported");
+        assert!(rate.raw_bytes.is_some());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// File names of the TS segments received, in order
+    fn segment_names(items: &[Result<HlsData, DownloadError>]) -> Vec<String> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                Ok(HlsData::TsData(ts)) => ts.segment.uri.rsplit('/').next().map(str::to_owned),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Download the playlist of `origin` at `/live.m3u8` to the end
+    async fn download_all(
+        origin: &MockOrigin,
+        config: HlsConfig,
+    ) -> Vec<Result<HlsData, DownloadError>> {
+        HlsDownloader::with_config(config)
+            .unwrap()
+            .download(&origin.url("/live.m3u8"), CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_failed_and_slow_segments_are_retried() {
+        let origin = MockOrigin::builder()
+            .hls(
+                "/live.m3u8",
+                HlsPlaylist::vod(4)
+                    .fail_segment(
+                        1,
+                        [StatusCode::BAD_GATEWAY, StatusCode::SERVICE_UNAVAILABLE],
+                    )
+                    .delay_segment(2, Duration::from_secs(1)),
+            )
+            .spawn()
+            .await;
+        let retries = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&retries);
+        let mut config = HlsConfig::default();
+        config.fetcher_config.segment_download_timeout = Duration::from_millis(200);
+        config.fetcher_config.segment_retry_delay_base = Duration::from_millis(10);
+        config.base.on_progress = Some(OnProgress::new(move |event| {
+            if matches!(event, ProgressEvent::RetryScheduled { .. }) {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+        let downloader = HlsDownloader::with_config(config).unwrap();
+
+        let items: Vec<_> = downloader
+            .download(&origin.url("/live.m3u8"), CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        assert_eq!(
+            segment_names(&items),
+            ["segment0.ts", "segment1.ts", "segment2.ts", "segment3.ts"]
+        );
+        // Two server errors, then a timeout
+        assert_eq!(origin.requests_to("/segment1.ts").len(), 3);
+        assert_eq!(origin.requests_to("/segment2.ts").len(), 2);
+        assert_eq!(retries.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_segments_are_emitted_in_playlist_order() {
+        // The later a segment is listed, the sooner it is served
+        let playlist = (0..6).fold(HlsPlaylist::vod(6), |playlist, sequence| {
+            playlist.delay_segment(sequence, Duration::from_millis(100 * (6 - sequence)))
+        });
+        let origin = MockOrigin::builder()
+            .hls("/live.m3u8", playlist)
+            .spawn()
+            .await;
+        let rates = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&rates);
+        let mut config = HlsConfig::default();
+        config.scheduler_config.download_concurrency = 6;
+        config.scheduler_config.stalled_segment_reissue_after = None;
+        config.base.on_progress = Some(OnProgress::new(move |event| {
+            if let ProgressEvent::DownloadProgress { rate, .. } = event {
+                sink.lock().push(rate);
+            }
+        }));
+
+        let items = download_all(&origin, config).await;
+
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        let expected: Vec<_> = (0..6)
+            .map(|sequence| format!("segment{sequence}.ts"))
+            .collect();
+        assert_eq!(segment_names(&items), expected);
+
+        let windows: Vec<_> = rates
+            .lock()
+            .iter()
+            .map(|rate| rate.segment_window.expect("segment window reported"))
+            .collect();
+        assert!(windows.iter().all(|window| window.capacity == 6));
+        assert!(
+            windows.iter().any(|window| window.in_flight > 1),
+            "{windows:?}"
+        );
+        // Segments served early wait for the ones listed before them
+        assert!(
+            windows.iter().any(|window| window.reorder_depth > 0),
+            "{windows:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_segment_throughput_scales_with_concurrency() {
+        async fn download_time(concurrency: usize) -> Duration {
+            let playlist = (0..8).fold(HlsPlaylist::vod(8), |playlist, sequence| {
+                playlist.delay_segment(sequence, Duration::from_millis(200))
+            });
+            let origin = MockOrigin::builder()
+                .hls("/live.m3u8", playlist)
+                .spawn()
+                .await;
+            let mut config = HlsConfig::default();
+            config.scheduler_config.download_concurrency = concurrency;
+
+            let started = Instant::now();
+            let items = download_all(&origin, config).await;
+            assert_eq!(segment_names(&items).len(), 8, "{items:?}");
+            started.elapsed()
+        }
+
+        let sequential = download_time(1).await;
+        let parallel = download_time(4).await;
+        assert!(sequential >= Duration::from_millis(1600), "{sequential:?}");
+        assert!(parallel * 2 < sequential, "{parallel:?} vs {sequential:?}");
+    }
+
+    #[tokio::test]
+    async fn test_stalled_segment_is_requested_again() {
+        let origin = MockOrigin::builder()
+            .hls(
+                "/live.m3u8",
+                HlsPlaylist::vod(3).delay_segment(0, Duration::from_secs(5)),
+            )
+            .spawn()
+            .await;
+        let mut config = HlsConfig::default();
+        config.scheduler_config.download_concurrency = 1;
+        config.scheduler_config.stalled_segment_reissue_after = Some(Duration::from_millis(200));
+
+        let started = Instant::now();
+        let items = download_all(&origin, config).await;
+
+        assert_eq!(
+            segment_names(&items),
+            ["segment0.ts", "segment1.ts", "segment2.ts"]
+        );
+        // The second request of the segment is answered without the delay
+        assert_eq!(origin.requests_to("/segment0.ts").len(), 2);
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_failed_segment_policy() {
+        let download_with = |policy| async move {
+            let origin = MockOrigin::builder()
+                .hls(
+                    "/live.m3u8",
+                    HlsPlaylist::vod(3).fail_segment(1, [StatusCode::NOT_FOUND]),
+                )
+                .spawn()
+                .await;
+            let mut config = HlsConfig::default();
+            config.scheduler_config.download_concurrency = 1;
+            config.scheduler_config.failure_policy = policy;
+            download_all(&origin, config).await
+        };
+
+        // The failed segment is skipped right away
+        let items = download_with(SegmentFailurePolicy::EmitGap).await;
+        assert!(items.iter().all(Result::is_ok), "{items:?}");
+        assert_eq!(segment_names(&items), ["segment0.ts", "segment2.ts"]);
+
+        // The download ends with the error of the segment
+        let items = download_with(SegmentFailurePolicy::Abort).await;
+        let failed = items.iter().position(Result::is_err).expect("error");
+        assert_eq!(segment_names(&items[..failed]), ["segment0.ts"]);
+        assert!(
+            matches!(
+                &items[failed],
+                Err(DownloadError::SegmentFailed { seq: 1, .. })
+            ),
+            "{items:?}"
+        );
+    }
+
+    /// Download `segments` segments of `segment_size` bytes through a buffer pool, dropping
+    /// each segment once received as a writer does, and return the pool statistics
+    async fn pooled_download(segments: usize, segment_size: usize) -> BufferPoolStats {
+        let origin = MockOrigin::builder()
+            .hls(
+                "/live.m3u8",
+                HlsPlaylist::vod(segments).segment_size(segment_size),
+            )
+            .spawn()
+            .await;
+        let pool = SegmentBufferPool::with_config(BufferPoolConfig {
+            max_pooled_buffers: 64,
+            max_pooled_bytes: usize::MAX,
+            ..Default::default()
+        });
+        let mut config = HlsConfig::default();
+        config.fetcher_config.buffer_pool = Some(pool.clone());
+
+        let received = HlsDownloader::with_config(config)
+            .unwrap()
+            .download(&origin.url("/live.m3u8"), CancellationToken::new())
+            .await
+            .unwrap()
+            .fold(0, |received, item| async move {
+                match item {
+                    Ok(HlsData::TsData(ts)) => {
+                        assert_eq!(ts.data.len() % 188, 0);
+                        received + 1
+                    }
+                    _ => received,
+                }
+            })
+            .await;
+        assert_eq!(received, segments);
+        pool.stats()
+    }
+
+    #[tokio::test]
+    async fn test_segment_buffers_are_reused() {
+        let stats = pooled_download(400, 64 * 1024).await;
+
+        // Only the segments alive at the same time need buffers of their own
+        assert_eq!(stats.hits + stats.misses, 400);
+        assert!(stats.misses < 100, "{stats:?}");
+        assert!(stats.reuse_rate() > 0.75, "{stats:?}");
+        assert!(stats.estimated_segment_size >= 64 * 1024);
+    }
+
+    /// Allocator pressure of thousands of 2 MB segments, run with `--ignored --nocapture`
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn test_segment_buffer_pool_allocations() {
+        let segments = 2000;
+        let started = Instant::now();
+        let stats = pooled_download(segments, 2 * 1024 * 1024).await;
+        println!(
+            "{segments} segments in {:?}: {} allocations instead of {segments}, reuse rate {:.3}, \
+             {} pooled bytes at most",
+            started.elapsed(),
+            stats.misses,
+            stats.reuse_rate(),
+            stats.high_water_bytes
+        );
+        assert!(stats.misses * 10 < segments as u64, "{stats:?}");
+    }
+
+    /// Master playlist whose variants have alternate audio in English and Spanish
+    const MULTI_AUDIO_MASTER: &str = r#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="English",LANGUAGE="en",DEFAULT=YES,AUTOSELECT=YES,URI="audio/en/live.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="Spanish",LANGUAGE="es",AUTOSELECT=YES,URI="audio/es/live.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-lo",NAME="English",LANGUAGE="en",DEFAULT=YES,URI="audio/lo/live.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=1280x720,AUDIO="aac"
+video/live.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=640000,RESOLUTION=640x360,AUDIO="aac-lo"
+video-lo/live.m3u8
+"#;
+
+    /// Download the tracks of [`MULTI_AUDIO_MASTER`] with `selection`, returning the origin
+    /// and the tracks of the segments received
+    async fn download_multi_audio(
+        selection: Option<AudioRenditionSelection>,
+    ) -> (MockOrigin, Vec<HlsTrack>) {
+        let origin = MockOrigin::builder()
+            .master("/master.m3u8", MULTI_AUDIO_MASTER)
+            .hls("/video/live.m3u8", HlsPlaylist::vod(3))
+            .hls("/audio/en/live.m3u8", HlsPlaylist::vod(3))
+            .hls("/audio/es/live.m3u8", HlsPlaylist::vod(3))
+            .spawn()
+            .await;
+        let mut config = HlsConfig::default();
+        config.playlist_config.audio_rendition = selection;
+
+        let items: Vec<_> = HlsDownloader::with_config(config)
+            .unwrap()
+            .download_tracks(&origin.url("/master.m3u8"), CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let tracks = items
+            .into_iter()
+            .map(|item| item.expect("segment"))
+            .filter(|item| item.data.is_ts())
+            .map(|item| item.track)
+            .collect();
+        (origin, tracks)
+    }
+
+    fn audio_languages(tracks: &[HlsTrack]) -> Vec<Option<String>> {
+        tracks
+            .iter()
+            .filter_map(|track| match track {
+                HlsTrack::Main => None,
+                HlsTrack::Audio(rendition) => Some(rendition.language.clone()),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_selected_audio_rendition_is_downloaded() {
+        let (origin, tracks) =
+            download_multi_audio(Some(AudioRenditionSelection::language("es"))).await;
+
+        assert_eq!(tracks.iter().filter(|track| track.is_main()).count(), 3);
+        assert_eq!(audio_languages(&tracks), [Some("es".to_string()); 3]);
+        assert!(!origin.requests_to("/audio/es/live.m3u8").is_empty());
+        for sequence in 0..3 {
+            let segment = format!("/audio/es/segment{sequence}.ts");
+            assert_eq!(origin.requests_to(&segment).len(), 1, "{segment}");
+        }
+        assert!(origin.requests_to("/audio/en/live.m3u8").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_audio_language_falls_back_to_default() {
+        let (origin, tracks) =
+            download_multi_audio(Some(AudioRenditionSelection::language("fr"))).await;
+
+        assert_eq!(audio_languages(&tracks), [Some("en".to_string()); 3]);
+        assert_eq!(origin.requests_to("/audio/en/segment2.ts").len(), 1);
+        assert!(origin.requests_to("/audio/es/live.m3u8").is_empty());
+
+        // Without a selection, only the variant is downloaded
+        let (origin, tracks) = download_multi_audio(None).await;
+        assert!(tracks.iter().all(HlsTrack::is_main));
+        assert_eq!(tracks.len(), 3);
+        assert!(origin.requests_to("/audio/en/live.m3u8").is_empty());
+    }
+}
@@ -0,0 +1,291 @@
+// HLS Low-Latency Tracker: Decides which LL-HLS parts and segments to download.
+//
+// LL-HLS playlists advertise the segment currently being produced as a list of partial
+// segments. Parts are downloaded as soon as they are listed; once a segment has been delivered
+// through its parts, the full segment that later replaces them in the playlist is skipped.
+//
+// The scheduler and output manager order jobs by a single sequence number. Since a segment
+// can be delivered as several parts, LL-HLS jobs are numbered with a contiguous delivery
+// sequence instead of the media sequence number of their parent segment.
+
+use hls::low_latency::{LowLatencyPlaylist, PartialSegment, PreloadHint};
+use m3u8_rs::MediaSegment;
+use std::time::Duration;
+use tracing::warn;
+
+/// Query of a blocking playlist reload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct BlockingReload {
+    pub msn: u64,
+    pub part: u32,
+    /// How long the server may hold the request
+    pub hold_timeout: Duration,
+}
+
+impl BlockingReload {
+    pub(super) fn query(&self) -> [(&'static str, String); 2] {
+        [
+            ("_HLS_msn", self.msn.to_string()),
+            ("_HLS_part", self.part.to_string()),
+        ]
+    }
+}
+
+/// The last delivered position in the stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    /// Segment `msn` was delivered, whole or through all of its parts
+    Segment(u64),
+    /// Parts `0..=index` of segment `msn` were delivered
+    Part { msn: u64, index: u32 },
+}
+
+/// A download planned by [`LowLatencyTracker::plan`]
+#[derive(Debug, Clone, Copy)]
+pub(super) enum PlannedItem<'a> {
+    Segment {
+        msn: u64,
+        segment: &'a MediaSegment,
+    },
+    Part(&'a PartialSegment),
+    /// The next part, requested before it is listed
+    Hint {
+        msn: u64,
+        index: u32,
+        hint: &'a PreloadHint,
+    },
+}
+
+impl PlannedItem<'_> {
+    /// Media sequence number of the parent segment
+    pub(super) fn msn(&self) -> u64 {
+        match self {
+            PlannedItem::Segment { msn, .. } | PlannedItem::Hint { msn, .. } => *msn,
+            PlannedItem::Part(part) => part.msn,
+        }
+    }
+}
+
+pub(super) struct LowLatencyTracker {
+    next_sequence: u64,
+    position: Option<Position>,
+}
+
+impl LowLatencyTracker {
+    /// Create a tracker continuing after `last_delivered_msn`, delivering from `next_sequence`.
+    pub(super) fn new(next_sequence: u64, last_delivered_msn: Option<u64>) -> Self {
+        Self {
+            next_sequence,
+            position: last_delivered_msn.map(Position::Segment),
+        }
+    }
+
+    /// Plan the downloads for a refreshed playlist, in delivery order, each with its delivery
+    /// sequence number.
+    pub(super) fn plan<'a>(
+        &mut self,
+        segments: &'a [MediaSegment],
+        media_sequence: u64,
+        low_latency: &'a LowLatencyPlaylist,
+        use_preload_hints: bool,
+    ) -> Vec<(u64, PlannedItem<'a>)> {
+        let mut items = Vec::new();
+
+        for (idx, segment) in segments.iter().enumerate() {
+            let msn = media_sequence + idx as u64;
+            match self.position {
+                Some(Position::Segment(done)) if msn <= done => continue,
+                Some(Position::Part { msn: current, .. }) if msn < current => continue,
+                Some(Position::Part {
+                    msn: current,
+                    index,
+                }) if msn == current => {
+                    // The segment completed; finish it through its remaining parts
+                    let mut listed = low_latency.parts_of(msn).peekable();
+                    if listed.peek().is_none() {
+                        warn!(
+                            msn,
+                            "Parts of segment expired before it completed, its tail is lost"
+                        );
+                    }
+                    let mut expected = index + 1;
+                    for part in listed.filter(|part| part.index > index) {
+                        if part.index != expected {
+                            warn!(msn, part = expected, "Part missing from playlist");
+                        }
+                        expected = part.index + 1;
+                        if !part.gap {
+                            items.push(PlannedItem::Part(part));
+                        }
+                    }
+                    self.position = Some(Position::Segment(msn));
+                    continue;
+                }
+                _ => {}
+            }
+            items.push(PlannedItem::Segment { msn, segment });
+            self.position = Some(Position::Segment(msn));
+        }
+
+        // The segment being produced
+        let msn = low_latency.next_msn();
+        let start_index = match self.position {
+            Some(Position::Part {
+                msn: current,
+                index,
+            }) if current == msn => Some(index + 1),
+            Some(Position::Segment(done)) if done >= msn => None,
+            Some(Position::Part { msn: current, .. }) if current > msn => None,
+            _ => Some(0),
+        };
+        if let Some(start_index) = start_index {
+            let mut expected = start_index;
+            for part in low_latency
+                .parts_of(msn)
+                .filter(|part| part.index >= start_index)
+            {
+                if part.index != expected {
+                    if expected == 0 {
+                        // Never start a segment mid-way, wait for it to complete instead
+                        break;
+                    }
+                    warn!(msn, part = expected, "Part missing from playlist");
+                }
+                expected = part.index + 1;
+                self.position = Some(Position::Part {
+                    msn,
+                    index: part.index,
+                });
+                // Parts marked as GAP are unavailable and not worth a request
+                if !part.gap {
+                    items.push(PlannedItem::Part(part));
+                }
+            }
+
+            let listed = low_latency.parts_of(msn).count() as u32;
+            // Open-ended byte ranges cannot be requested ahead of time
+            if use_preload_hints
+                && expected == listed
+                && let Some(hint) = low_latency.part_hint()
+                && (hint.byte_range_start.is_none() || hint.byte_range_length.is_some())
+            {
+                items.push(PlannedItem::Hint {
+                    msn,
+                    index: listed,
+                    hint,
+                });
+                self.position = Some(Position::Part { msn, index: listed });
+            }
+        }
+
+        items
+            .into_iter()
+            .map(|item| {
+                let sequence = self.next_sequence;
+                self.next_sequence += 1;
+                (sequence, item)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A playlist with `complete` segments of four parts each and `trailing` parts of the next
+    fn playlist(complete: u64, trailing: u32) -> (Vec<MediaSegment>, LowLatencyPlaylist) {
+        let mut content = String::from(
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-PART-INF:PART-TARGET=0.5\n#EXT-X-MEDIA-SEQUENCE:0\n",
+        );
+        let mut segments = Vec::new();
+        for msn in 0..=complete {
+            let parts = if msn < complete { 4 } else { trailing };
+            for index in 0..parts {
+                content.push_str(&format!(
+                    "#EXT-X-PART:DURATION=0.5,URI=\"seg{msn}.part{index}.ts\"\n"
+                ));
+            }
+            if msn < complete {
+                content.push_str(&format!("#EXTINF:2.0,\nseg{msn}.ts\n"));
+                segments.push(MediaSegment {
+                    uri: format!("seg{msn}.ts"),
+                    duration: 2.0,
+                    ..Default::default()
+                });
+            }
+        }
+        content.push_str(&format!(
+            "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"seg{complete}.part{trailing}.ts\"\n"
+        ));
+        (segments, LowLatencyPlaylist::parse(&content))
+    }
+
+    fn uris(items: &[(u64, PlannedItem<'_>)]) -> Vec<String> {
+        items
+            .iter()
+            .map(|(_, item)| match item {
+                PlannedItem::Segment { segment, .. } => segment.uri.clone(),
+                PlannedItem::Part(part) => part.uri.clone(),
+                PlannedItem::Hint { hint, .. } => format!("hint:{}", hint.uri),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parts_replace_full_segment() {
+        let mut tracker = LowLatencyTracker::new(0, None);
+
+        let (segments, ll) = playlist(1, 2);
+        let items = tracker.plan(&segments, 0, &ll, false);
+        assert_eq!(
+            uris(&items),
+            vec!["seg0.ts", "seg1.part0.ts", "seg1.part1.ts"]
+        );
+        assert_eq!(
+            items.iter().map(|(seq, _)| *seq).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        // Unchanged playlist yields nothing
+        assert!(tracker.plan(&segments, 0, &ll, false).is_empty());
+
+        // Segment 1 completes, only its last parts are new
+        let (segments, ll) = playlist(2, 1);
+        let items = tracker.plan(&segments, 0, &ll, false);
+        assert_eq!(
+            uris(&items),
+            vec!["seg1.part2.ts", "seg1.part3.ts", "seg2.part0.ts"]
+        );
+        assert_eq!(items[0].0, 3);
+        assert_eq!(items[2].1.msn(), 2);
+    }
+
+    #[test]
+    fn test_does_not_start_segment_mid_way() {
+        let (segments, mut ll) = playlist(1, 3);
+        // Part 0 of the segment being produced already left the playlist
+        ll.parts.retain(|part| !(part.msn == 1 && part.index == 0));
+
+        let mut tracker = LowLatencyTracker::new(5, Some(0));
+        assert!(tracker.plan(&segments, 0, &ll, false).is_empty());
+
+        let (segments, ll) = playlist(2, 0);
+        let items = tracker.plan(&segments, 0, &ll, false);
+        assert_eq!(uris(&items), vec!["seg1.ts"]);
+        assert_eq!(items[0].0, 5);
+    }
+
+    #[test]
+    fn test_preload_hint_is_not_fetched_twice() {
+        let mut tracker = LowLatencyTracker::new(0, Some(0));
+
+        let (segments, ll) = playlist(1, 1);
+        let items = tracker.plan(&segments, 0, &ll, true);
+        assert_eq!(uris(&items), vec!["seg1.part0.ts", "hint:seg1.part1.ts"]);
+
+        let (segments, ll) = playlist(1, 2);
+        let items = tracker.plan(&segments, 0, &ll, true);
+        assert_eq!(uris(&items), vec!["hint:seg1.part2.ts"]);
+    }
+}
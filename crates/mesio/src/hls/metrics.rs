@@ -1,43 +1,6 @@
-use parking_lot::Mutex;
-use std::collections::{HashSet, VecDeque};
 use std::sync::atomic::{AtomicU64, Ordering};
 use tracing::info;
 
-/// Bounded set to prevent unbounded memory growth from tracking distinct hosts.
-///
-/// Used for observability heuristics (connection reuse estimation), so it is
-/// acceptable for older entries to be evicted.
-#[derive(Debug, Default)]
-struct BoundedHostSet {
-    order: VecDeque<String>,
-    set: HashSet<String>,
-}
-
-impl BoundedHostSet {
-    const MAX_TRACKED_HOSTS: usize = 256;
-
-    fn contains(&self, host: &str) -> bool {
-        self.set.contains(host)
-    }
-
-    fn insert(&mut self, host: &str) -> bool {
-        if self.set.contains(host) {
-            return false;
-        }
-
-        let host_string = host.to_string();
-        self.set.insert(host_string.clone());
-        self.order.push_back(host_string);
-
-        while self.order.len() > Self::MAX_TRACKED_HOSTS {
-            if let Some(oldest) = self.order.pop_front() {
-                self.set.remove(&oldest);
-            }
-        }
-        true
-    }
-}
-
 /// Performance metrics for HLS pipeline
 ///
 /// Tracks various performance counters for observability and tuning.
@@ -65,12 +28,10 @@ pub struct PerformanceMetrics {
     pub http1_bytes: AtomicU64,
 
     // Connection reuse tracking
-    /// Number of connections reused (estimated via HTTP/2 multiplexing)
+    /// Number of requests sent over a pooled connection
     pub connections_reused: AtomicU64,
-    /// Number of new connections established
+    /// Number of requests that opened a new connection
     pub connections_new: AtomicU64,
-    /// Track hosts seen for connection reuse estimation
-    hosts_seen: Mutex<BoundedHostSet>,
 
     // Decryption metrics
     /// Total number of decryption operations
@@ -91,6 +52,12 @@ pub struct PerformanceMetrics {
     pub prefetch_initiated: AtomicU64,
     /// Number of prefetched segments actually used
     pub prefetch_used: AtomicU64,
+
+    // Playlist metrics
+    /// Segments listed again under another media sequence number or URI signature
+    pub duplicate_segments_skipped: AtomicU64,
+    /// Media sequence number resets of the playlist
+    pub sequence_resets_detected: AtomicU64,
 }
 
 impl PerformanceMetrics {
@@ -124,25 +91,21 @@ impl PerformanceMetrics {
         }
     }
 
-    /// Record an HTTP request with version, bytes, and connection reuse tracking.
-    ///
-    /// For HTTP/2, multiple requests to the same host share a connection. This
-    /// method tracks whether a host has been seen before to estimate reuse.
-    pub fn record_request_with_host(&self, version: reqwest::Version, bytes: u64, host: &str) {
+    /// Record an HTTP request with its version, bytes, and whether it reused a pooled
+    /// connection.
+    pub fn record_request(&self, version: reqwest::Version, bytes: u64, reused: bool) {
         let is_http2 = version == reqwest::Version::HTTP_2;
         self.record_http_version(is_http2);
 
         if is_http2 {
             self.http2_bytes.fetch_add(bytes, Ordering::Relaxed);
-            let mut hosts = self.hosts_seen.lock();
-            if hosts.contains(host) {
-                self.connections_reused.fetch_add(1, Ordering::Relaxed);
-            } else {
-                self.connections_new.fetch_add(1, Ordering::Relaxed);
-                hosts.insert(host);
-            }
         } else {
             self.http1_bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+
+        if reused {
+            self.connections_reused.fetch_add(1, Ordering::Relaxed);
+        } else {
             self.connections_new.fetch_add(1, Ordering::Relaxed);
         }
     }
@@ -194,6 +157,20 @@ impl PerformanceMetrics {
         self.prefetch_used.fetch_add(1, Ordering::Relaxed);
     }
 
+    // --- Playlist metrics recording ---
+
+    /// Record duplicate segments skipped by the playlist engine
+    pub fn record_duplicate_segments(&self, count: u64) {
+        self.duplicate_segments_skipped
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Record a media sequence number reset
+    pub fn record_sequence_reset(&self) {
+        self.sequence_resets_detected
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
     // --- Helper methods for computed metrics ---
 
     /// Get average download latency in milliseconds
@@ -284,6 +261,8 @@ impl PerformanceMetrics {
         let cache_misses = self.cache_misses.load(Ordering::Relaxed);
         let prefetch_init = self.prefetch_initiated.load(Ordering::Relaxed);
         let prefetch_used = self.prefetch_used.load(Ordering::Relaxed);
+        let duplicates = self.duplicate_segments_skipped.load(Ordering::Relaxed);
+        let sequence_resets = self.sequence_resets_detected.load(Ordering::Relaxed);
 
         let avg_latency = self
             .average_download_latency_ms()
@@ -326,6 +305,8 @@ impl PerformanceMetrics {
             prefetch_initiated = prefetch_init,
             prefetch_used = prefetch_used,
             prefetch_effectiveness = format!("{:.1}%", prefetch_rate),
+            duplicate_segments_skipped = duplicates,
+            sequence_resets_detected = sequence_resets,
             "HLS Performance Summary"
         );
     }
@@ -346,6 +327,8 @@ impl PerformanceMetrics {
             cache_misses: self.cache_misses.load(Ordering::Relaxed),
             prefetch_initiated: self.prefetch_initiated.load(Ordering::Relaxed),
             prefetch_used: self.prefetch_used.load(Ordering::Relaxed),
+            duplicate_segments_skipped: self.duplicate_segments_skipped.load(Ordering::Relaxed),
+            sequence_resets_detected: self.sequence_resets_detected.load(Ordering::Relaxed),
         }
     }
 }
@@ -366,6 +349,8 @@ pub struct MetricsSnapshot {
     pub cache_misses: u64,
     pub prefetch_initiated: u64,
     pub prefetch_used: u64,
+    pub duplicate_segments_skipped: u64,
+    pub sequence_resets_detected: u64,
 }
 
 /// Format bytes per second in human-readable form
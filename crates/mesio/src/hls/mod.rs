@@ -1,5 +1,6 @@
 // Main module for the new HLS downloader implementation
 
+pub mod buffer_pool;
 pub mod config;
 mod coordinator;
 mod decryption;
@@ -7,21 +8,28 @@ pub mod error;
 pub mod events;
 mod fetcher;
 mod hls_downloader;
+mod low_latency;
 mod metrics;
 mod output;
 mod playlist;
 mod prefetch;
 mod processor;
-pub mod retry;
 mod scheduler;
 mod segment_utils;
+mod sequence_tracker;
 mod twitch_processor;
+pub mod variant;
 
 // Re-exports for easier access
-pub use config::{BufferLimits, GapSkipStrategy, HlsConfig};
+pub use buffer_pool::{BufferPoolConfig, BufferPoolStats, SegmentBufferPool, ShrinkPolicy};
+pub use config::{BufferLimits, GapSkipStrategy, HlsConfig, SegmentFailurePolicy};
 pub use coordinator::HlsStreamCoordinator;
 pub use error::HlsDownloaderError;
 pub use events::{GapSkipReason, HlsStreamEvent};
 pub use hls_downloader::HlsDownloader;
 pub use metrics::{MetricsSnapshot, PerformanceMetrics};
 pub use prefetch::PrefetchManager;
+pub use variant::{
+    AudioRenditionSelection, ClosestResolution, CodecPreference, Custom, HighestBandwidth,
+    VariantChoice, VariantSelector,
+};
@@ -4,7 +4,8 @@
 use crate::hls::config::HlsConfig;
 use crate::hls::events::HlsStreamEvent;
 use crate::hls::scheduler::ProcessedSegmentOutput;
-use std::collections::BTreeMap;
+use crate::throttle::SegmentWindow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
@@ -223,7 +224,8 @@ impl ReorderBufferMetrics {
 }
 
 use super::HlsDownloaderError;
-use crate::hls::config::GapSkipStrategy;
+use crate::TimeoutPhase;
+use crate::hls::config::{GapSkipStrategy, SegmentFailurePolicy};
 use crate::hls::events::GapSkipReason;
 use crate::hls::metrics::PerformanceMetrics;
 use hls::SegmentType;
@@ -295,6 +297,12 @@ pub struct OutputManager {
     /// Performance metrics for the HLS pipeline
     /// Used to log performance summary on stream end
     performance_metrics: Option<Arc<PerformanceMetrics>>,
+
+    /// Segments that failed to download, skipped when their turn comes
+    failed_segments: BTreeSet<u64>,
+
+    /// Receives the reorder buffer depth reported with the download progress
+    segment_window: Option<Arc<SegmentWindow>>,
 }
 
 impl OutputManager {
@@ -332,6 +340,8 @@ impl OutputManager {
             live_gap_strategy_override: None,
             vod_gap_strategy_override: None,
             performance_metrics: None,
+            failed_segments: BTreeSet::new(),
+            segment_window: None,
         }
     }
 
@@ -358,6 +368,12 @@ impl OutputManager {
         manager
     }
 
+    /// Report the depth of the reorder buffer to `window`
+    pub(crate) fn with_segment_window(mut self, window: Arc<SegmentWindow>) -> Self {
+        self.segment_window = Some(window);
+        self
+    }
+
     /// Get current metrics snapshot.
     ///
     #[allow(dead_code)]
@@ -521,6 +537,10 @@ impl OutputManager {
         };
 
         loop {
+            if let Some(window) = &self.segment_window {
+                window.set_reorder_depth(self.reorder_buffer.len());
+            }
+
             // Determine timeout for select! based on *remaining* live stall time.
             // This must be derived from `last_input_received_time`, otherwise other periodic
             // wake-ups (e.g. gap evaluation) would continuously reset a fixed-duration timer.
@@ -558,9 +578,10 @@ impl OutputManager {
                                     "Live stream stalled for more than configured max duration ({:?}). No new segments or events received.",
                                     max_stall_duration
                                 );
-                                let _ = self.event_tx.send(Err(HlsDownloaderError::Timeout {
-                                    reason: "Stalled: No input received for max duration.".to_string()
-                                })).await;
+                                let _ = self.event_tx.send(Err(HlsDownloaderError::timeout(
+                                    TimeoutPhase::Playlist,
+                                    "Stalled: No input received for max duration.",
+                                ))).await;
                                 break; // Exit loop for live stream stall
                     }
 
@@ -687,6 +708,22 @@ impl OutputManager {
                                 break;
                             }
                         }
+                        Some(Err(HlsDownloaderError::SegmentFailed { seq, attempts, source }))
+                            if self.config.scheduler_config.failure_policy
+                                == SegmentFailurePolicy::EmitGap =>
+                        {
+                            warn!(
+                                "Segment {} failed after {} attempt(s): {}. Marking it as a gap.",
+                                seq, attempts, source
+                            );
+                            if seq >= self.expected_next_media_sequence {
+                                self.failed_segments.insert(seq);
+                            }
+                            if self.try_emit_segments().await.is_err() {
+                                error!("Error emitting segments from reorder buffer after failed segment. Exiting.");
+                                break;
+                            }
+                        }
                         Some(Err(e)) => {
                             error!("Received error from input channel: {:?}. Forwarding and exiting.", e);
                             if self.event_tx.send(Err(e)).await.is_err() {
@@ -756,8 +793,11 @@ impl OutputManager {
     /// Handles ordering, discontinuities, and gap skipping (for live streams).
     /// Returns Ok(()) if successful, Err(()) if event_tx is closed.
     async fn try_emit_segments(&mut self) -> Result<(), ()> {
-        while let Some(entry) = self.reorder_buffer.first_entry() {
-            let segment_sequence = *entry.key();
+        loop {
+            self.skip_failed_segments().await?;
+            let Some(&segment_sequence) = self.reorder_buffer.keys().next() else {
+                break;
+            };
 
             if segment_sequence == self.expected_next_media_sequence {
                 // Expected segment found
@@ -898,7 +938,7 @@ impl OutputManager {
                             .update_buffer_bytes(self.current_buffer_bytes as u64);
                     }
                 } else {
-                    // Should not happen if first_entry returned Some
+                    // Should not happen if the buffer has the key
                     break;
                 }
             } else if segment_sequence < self.expected_next_media_sequence {
@@ -1069,6 +1109,39 @@ impl OutputManager {
         Ok(())
     }
 
+    /// Skips the expected segments that failed to download, with a GapSkipped event each.
+    /// Returns Ok(()) if successful, Err(()) if event_tx is closed.
+    async fn skip_failed_segments(&mut self) -> Result<(), ()> {
+        while self
+            .failed_segments
+            .remove(&self.expected_next_media_sequence)
+        {
+            let failed_sequence = self.expected_next_media_sequence;
+            warn!(
+                "Skipping segment {} which failed to download.",
+                failed_sequence
+            );
+            if self.config.output_config.metrics_enabled {
+                self.metrics.record_gap_skip(1);
+            }
+            let gap_skipped_event = HlsStreamEvent::GapSkipped {
+                from_sequence: failed_sequence,
+                to_sequence: failed_sequence + 1,
+                reason: GapSkipReason::SegmentFailed,
+            };
+            if self.event_tx.send(Ok(gap_skipped_event)).await.is_err() {
+                return Err(());
+            }
+            self.expected_next_media_sequence += 1;
+            self.gap_state = None;
+        }
+        // Segments skipped past by the gap strategy no longer need marking
+        self.failed_segments = self
+            .failed_segments
+            .split_off(&self.expected_next_media_sequence);
+        Ok(())
+    }
+
     /// Prunes the reorder buffer based on configuration (duration/max_segments).
     /// Uses `BTreeMap::split_off` for O(log n) bulk removal of stale segments.
     ///
@@ -1739,6 +1812,80 @@ mod tests {
         let _ = join.await;
     }
 
+    #[tokio::test]
+    async fn failed_segment_is_skipped_without_waiting() {
+        // VOD streams wait indefinitely for missing segments, but not for failed ones
+        let config = HlsConfig::default();
+        let (input_tx, input_rx) = mpsc::channel(16);
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let token = CancellationToken::new();
+
+        let mut mgr = OutputManager::new(
+            Arc::new(config),
+            input_rx,
+            event_tx,
+            false,
+            0,
+            token.clone(),
+        );
+        let join = tokio::spawn(async move {
+            mgr.run().await;
+        });
+
+        let ts_segment = |msn: u64| ProcessedSegmentOutput {
+            original_segment_uri: format!("segment{msn}.ts"),
+            data: HlsData::ts(
+                m3u8_rs::MediaSegment {
+                    uri: format!("segment{msn}.ts"),
+                    duration: 1.0,
+                    ..Default::default()
+                },
+                Bytes::from_static(&[0x47]),
+            ),
+            media_sequence_number: msn,
+            discontinuity: false,
+        };
+        input_tx.send(Ok(ts_segment(2))).await.unwrap();
+        input_tx.send(Ok(ts_segment(0))).await.unwrap();
+        input_tx
+            .send(Err(HlsDownloaderError::segment_failed(
+                1,
+                4,
+                HlsDownloaderError::Cancelled,
+            )))
+            .await
+            .unwrap();
+
+        let mut events = Vec::new();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while events.len() < 3 {
+                let event = event_rx.recv().await.expect("event").expect("no error");
+                events.push(event);
+            }
+        })
+        .await
+        .expect("timed out waiting for the segments after the failed one");
+
+        let uri = |event: &HlsStreamEvent| match event {
+            HlsStreamEvent::Data(data) => data.media_segment().map(|s| s.uri.clone()),
+            _ => None,
+        };
+        assert_eq!(uri(&events[0]).as_deref(), Some("segment0.ts"));
+        assert!(matches!(
+            events[1],
+            HlsStreamEvent::GapSkipped {
+                from_sequence: 1,
+                to_sequence: 2,
+                reason: GapSkipReason::SegmentFailed,
+            }
+        ));
+        assert_eq!(uri(&events[2]).as_deref(), Some("segment2.ts"));
+
+        token.cancel();
+        drop(input_tx);
+        let _ = join.await;
+    }
+
     #[test]
     fn startup_count_based_gap_skip_is_suppressed_until_first_media_emitted() {
         let mut config = HlsConfig::default();
@@ -1,15 +1,26 @@
 // HLS Playlist Engine: Handles fetching, parsing, and managing HLS playlists.
 
-use crate::cache::{CacheKey, CacheManager, CacheMetadata, CacheResourceType};
+use crate::TimeoutPhase;
+use crate::cache::{CacheKey, CacheManager, CacheResourceType};
 use crate::downloader::ClientPool;
+use crate::error::line_at;
 use crate::hls::HlsDownloaderError;
 use crate::hls::config::{HlsConfig, HlsVariantSelectionPolicy};
+use crate::hls::low_latency::{BlockingReload, LowLatencyTracker, PlannedItem};
+use crate::hls::metrics::PerformanceMetrics;
 use crate::hls::scheduler::ScheduledSegmentJob;
+use crate::hls::sequence_tracker::SequenceTracker;
 use crate::hls::twitch_processor::TwitchPlaylistProcessor;
+use crate::hls::variant::{VariantSelector, selectable_variants};
+use crate::retry::{RetryAction, retry_with_backoff};
+use crate::tee::TeeKind;
 use async_trait::async_trait;
-use m3u8_rs::{MasterPlaylist, MediaPlaylist, MediaSegment, parse_playlist_res};
-use moka::future::Cache;
-use moka::policy::EvictionPolicy;
+use hls::Rendition;
+use hls::low_latency::LowLatencyPlaylist;
+use hls::rendition::{audio_group, rendition_groups};
+use m3u8_rs::{MasterPlaylist, MediaPlaylist, MediaSegment, VariantStream, parse_playlist_res};
+use pipeline_common::ProgressEvent;
+use reqwest::StatusCode;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -19,6 +30,40 @@ use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, trace, warn};
 use url::Url;
 
+/// Playlist requests are plain GETs and can always be sent again
+fn unclonable_request(playlist_url: &Url) -> HlsDownloaderError {
+    HlsDownloaderError::Internal {
+        reason: format!("Playlist request for {playlist_url} cannot be retried"),
+    }
+}
+
+/// Report the timeouts of playlist requests as such
+fn playlist_timeout(error: HlsDownloaderError) -> HlsDownloaderError {
+    match error {
+        HlsDownloaderError::Timeout { reason, .. } => {
+            HlsDownloaderError::timeout(TimeoutPhase::Playlist, reason)
+        }
+        error => error,
+    }
+}
+
+/// Error of a playlist the M3U8 parser rejected, pointing at the line it stopped at
+fn parse_error(
+    input: &[u8],
+    error: nom::Err<nom::error::Error<&[u8]>>,
+    context: &str,
+) -> HlsDownloaderError {
+    match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => HlsDownloaderError::playlist_parse(
+            Some(line_at(input, input.len().saturating_sub(e.input.len()))),
+            format!("{context}: {}", e.code.description()),
+        ),
+        nom::Err::Incomplete(_) => {
+            HlsDownloaderError::playlist_parse(None, format!("{context}: unexpected end"))
+        }
+    }
+}
+
 #[async_trait]
 pub trait PlaylistProvider: Send + Sync {
     async fn load_initial_playlist(&self, url: &str)
@@ -49,6 +94,17 @@ pub struct MediaPlaylistDetails {
     pub playlist: MediaPlaylist,
     pub url: String,
     pub base_url: String,
+    /// Alternate audio rendition selected along with the variant, when it has a media
+    /// playlist of its own
+    pub audio_rendition: Option<AudioRendition>,
+}
+
+/// An alternate audio rendition to download next to the variant
+#[derive(Debug, Clone)]
+pub struct AudioRendition {
+    pub rendition: Arc<Rendition>,
+    /// URL of the media playlist of the rendition
+    pub url: String,
 }
 
 #[derive(Debug, Clone)]
@@ -65,6 +121,9 @@ pub struct PlaylistEngine {
     clients: Arc<ClientPool>,
     cache_service: Option<Arc<CacheManager>>,
     config: Arc<HlsConfig>,
+    metrics: Option<Arc<PerformanceMetrics>>,
+    /// Initial playlist already received by a protocol probe
+    probed_playlist: parking_lot::Mutex<Option<bytes::Bytes>>,
 }
 
 /// Tracks segment arrival patterns to adaptively adjust playlist refresh intervals.
@@ -147,6 +206,110 @@ impl AdaptiveRefreshTracker {
     }
 }
 
+/// Resolves playlist-relative URIs and forwards the playlist's query parameters to them.
+struct UriResolver<'a> {
+    base_url: &'a str,
+    base_url_parsed: Option<Url>,
+    parent_params: Vec<(String, String)>,
+}
+
+impl<'a> UriResolver<'a> {
+    fn new(base_url: &'a str, parent_query: Option<&str>) -> Self {
+        let parent_params = parent_query
+            .map(|q| {
+                url::form_urlencoded::parse(q.as_bytes())
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            base_url,
+            base_url_parsed: Url::parse(base_url).ok(),
+            parent_params,
+        }
+    }
+
+    fn resolve(&self, relative_uri: &str) -> Result<String, url::ParseError> {
+        let resolved = if let Some(base) = self.base_url_parsed.as_ref() {
+            base.join(relative_uri).map(|u| u.to_string())
+        } else {
+            Url::parse(self.base_url)
+                .and_then(|b| b.join(relative_uri))
+                .map(|u| u.to_string())
+        };
+        if let Ok(ref url) = resolved {
+            trace!(
+                "Resolved URI: {} + {} -> {}",
+                self.base_url, relative_uri, url
+            );
+        }
+        resolved
+    }
+
+    /// Merge query params from the parent playlist if missing in the child
+    fn merge_params(&self, uri_str: &str) -> String {
+        if self.parent_params.is_empty() {
+            return uri_str.to_string();
+        }
+
+        if let Ok(mut url) = Url::parse(uri_str) {
+            let original = url.to_string();
+            for (k, v) in &self.parent_params {
+                if url
+                    .query_pairs()
+                    .any(|(existing_k, _)| existing_k == k.as_str())
+                {
+                    continue;
+                }
+                url.query_pairs_mut().append_pair(k, v);
+            }
+            let merged = url.to_string();
+            if original != merged {
+                trace!("Merged query params: {} -> {}", original, merged);
+            }
+            return merged;
+        }
+        uri_str.to_string()
+    }
+
+    /// Absolute URI with the parent params, for a URI of `kind` (used in logs)
+    fn absolute(&self, uri: &str, kind: &str) -> String {
+        let absolute_uri = if uri.starts_with("http://") || uri.starts_with("https://") {
+            uri.to_string()
+        } else {
+            self.resolve(uri).unwrap_or_else(|_| {
+                error!(
+                    "Failed to resolve {} URI '{}' with base '{}'",
+                    kind, uri, self.base_url
+                );
+                uri.to_string()
+            })
+        };
+        self.merge_params(&absolute_uri)
+    }
+
+    fn resolve_key(&self, key: &m3u8_rs::Key) -> m3u8_rs::Key {
+        let mut key = key.clone();
+        if let Some(uri) = key.uri.as_deref() {
+            key.uri = Some(self.absolute(uri, "key"));
+        }
+        key
+    }
+
+    fn resolve_map(&self, map_info: &m3u8_rs::Map) -> m3u8_rs::Map {
+        // A map BYTERANGE without offset starts at the beginning of the resource
+        let byte_range = map_info.byte_range.as_ref().map(|br| m3u8_rs::ByteRange {
+            length: br.length,
+            offset: Some(br.offset.unwrap_or(0)),
+        });
+        m3u8_rs::Map {
+            uri: self.absolute(&map_info.uri, "map"),
+            byte_range,
+            other_attributes: map_info.other_attributes.clone(),
+        }
+    }
+}
+
 #[async_trait]
 impl PlaylistProvider for PlaylistEngine {
     async fn load_initial_playlist(
@@ -156,80 +319,51 @@ impl PlaylistProvider for PlaylistEngine {
         let playlist_url = Url::parse(url_str).map_err(|e| HlsDownloaderError::Playlist {
             reason: format!("Invalid playlist URL {url_str}: {e}"),
         })?;
-        let cache_key = CacheKey::new(CacheResourceType::Playlist, playlist_url.as_str(), None);
-
-        if let Some(cache_service) = &self.cache_service
-            && let Ok(Some((cached_data, _, _))) = cache_service.get(&cache_key).await
-        {
-            let playlist_content = std::str::from_utf8(cached_data.as_ref()).map_err(|e| {
-                HlsDownloaderError::Playlist {
-                    reason: format!("Failed to parse cached playlist from UTF-8: {e}"),
-                }
-            })?;
-            let playlist_bytes_to_parse: Cow<[u8]> =
-                if TwitchPlaylistProcessor::is_twitch_playlist(playlist_url.as_str()) {
-                    let preprocessed = self.preprocess_twitch_playlist(playlist_content);
-                    Cow::Owned(preprocessed.into_bytes())
-                } else {
-                    Cow::Borrowed(cached_data.as_ref())
-                };
-            let base_url_obj =
-                playlist_url
-                    .join(".")
-                    .map_err(|e| HlsDownloaderError::Playlist {
-                        reason: format!("Failed to determine base URL: {e}"),
-                    })?;
-            let base_url = base_url_obj.to_string();
-            debug!(
-                "Derived base URL from playlist: {} -> {}",
-                playlist_url, base_url
-            );
-            return match parse_playlist_res(&playlist_bytes_to_parse) {
-                Ok(m3u8_rs::Playlist::MasterPlaylist(pl)) => {
-                    Ok(InitialPlaylist::Master(pl, base_url))
-                }
-                Ok(m3u8_rs::Playlist::MediaPlaylist(pl)) => {
-                    Ok(InitialPlaylist::Media(pl, base_url))
-                }
-                Err(e) => Err(HlsDownloaderError::Playlist {
-                    reason: format!("Failed to parse cached playlist: {e}"),
-                }),
-            };
-        }
+        let request = self.playlist_request(
+            &playlist_url,
+            self.config.playlist_config.initial_playlist_fetch_timeout,
+        );
 
-        let client = self.clients.client_for_url(&playlist_url);
-        let response = client
-            .get(playlist_url.clone())
-            .timeout(self.config.playlist_config.initial_playlist_fetch_timeout)
-            .query(&self.config.base.params)
-            .send()
-            .await
-            .map_err(|e| HlsDownloaderError::Network { source: e })?;
-        if !response.status().is_success() {
-            return Err(HlsDownloaderError::Playlist {
-                reason: format!(
-                    "Failed to fetch playlist {playlist_url}: HTTP {}",
-                    response.status()
-                ),
-            });
-        }
-        let playlist_bytes = response
-            .bytes()
+        // Cancelled by dropping the future
+        let token = CancellationToken::new();
+        let probed_playlist = self.probed_playlist.lock().take();
+        let playlist_bytes = if let Some(playlist_bytes) = probed_playlist {
+            debug!(url = %playlist_url, "Using the playlist received by the protocol probe");
+            playlist_bytes
+        } else if let Some(cache_service) = &self.cache_service {
+            // Served from the cache while fresh, then revalidated with the stored validators
+            let cache_key = CacheKey::new(CacheResourceType::Playlist, playlist_url.as_str(), None);
+            let ttl = self.config.playlist_config.initial_playlist_fetch_timeout;
+            let (playlist_bytes, status) = retry_with_backoff(
+                &self.config.base.retry_policy,
+                playlist_url.as_str(),
+                self.config.base.on_progress.as_ref(),
+                &token,
+                |_| async {
+                    let Some(request) = request.try_clone() else {
+                        return RetryAction::Fail(unclonable_request(&playlist_url));
+                    };
+                    match cache_service.fetch(cache_key.clone(), request, ttl).await {
+                        Ok(fetched) => RetryAction::Success(fetched),
+                        Err(e) => RetryAction::from_error(e),
+                    }
+                },
+            )
             .await
-            .map_err(|e| HlsDownloaderError::Network { source: e })?;
-
-        if let Some(cache_service) = &self.cache_service {
-            let metadata = CacheMetadata::new(playlist_bytes.len() as u64)
-                .with_expiration(self.config.playlist_config.initial_playlist_fetch_timeout);
+            .map_err(playlist_timeout)?;
+            debug!(url = %playlist_url, status = ?status, "Loaded initial playlist through cache");
+            playlist_bytes
+        } else {
+            self.fetch_playlist_bytes(request, &playlist_url, &token)
+                .await?
+        };
+        self.tee_playlist(&playlist_url, &playlist_bytes);
 
-            cache_service
-                .put(cache_key, playlist_bytes.clone(), metadata)
-                .await?;
-        }
         let playlist_content = std::str::from_utf8(playlist_bytes.as_ref()).map_err(|e| {
-            HlsDownloaderError::Playlist {
-                reason: format!("Playlist content is not valid UTF-8: {e}"),
-            }
+            HlsDownloaderError::playlist_parse(
+                Some(line_at(playlist_bytes.as_ref(), e.valid_up_to())),
+                format!("Playlist content is not valid UTF-8: {e}"),
+            )
         })?;
         let playlist_bytes_to_parse: Cow<[u8]> =
             if TwitchPlaylistProcessor::is_twitch_playlist(playlist_url.as_str()) {
@@ -251,9 +385,11 @@ impl PlaylistProvider for PlaylistEngine {
         match parse_playlist_res(&playlist_bytes_to_parse) {
             Ok(m3u8_rs::Playlist::MasterPlaylist(pl)) => Ok(InitialPlaylist::Master(pl, base_url)),
             Ok(m3u8_rs::Playlist::MediaPlaylist(pl)) => Ok(InitialPlaylist::Media(pl, base_url)),
-            Err(e) => Err(HlsDownloaderError::Playlist {
-                reason: format!("Failed to parse fetched playlist: {e}"),
-            }),
+            Err(e) => Err(parse_error(
+                &playlist_bytes_to_parse,
+                e,
+                "Failed to parse playlist",
+            )),
         }
     }
 
@@ -277,134 +413,77 @@ impl PlaylistProvider for PlaylistEngine {
                 reason: "Master playlist has no variants".to_string(),
             });
         }
-        let selected_variant = match policy {
-            HlsVariantSelectionPolicy::HighestBitrate => master_playlist_ref
-                .variants
-                .iter()
-                .max_by_key(|v| v.bandwidth)
-                .ok_or_else(|| HlsDownloaderError::Playlist {
-                    reason: "No variants for HighestBitrate".to_string(),
-                })?,
-            HlsVariantSelectionPolicy::LowestBitrate => master_playlist_ref
-                .variants
-                .iter()
-                .min_by_key(|v| v.bandwidth)
-                .ok_or_else(|| HlsDownloaderError::Playlist {
-                    reason: "No variants for LowestBitrate".to_string(),
-                })?,
-            HlsVariantSelectionPolicy::ClosestToBitrate(target_bw) => master_playlist_ref
-                .variants
-                .iter()
-                .min_by_key(|v| (*target_bw as i64 - v.bandwidth as i64).abs())
-                .ok_or_else(|| HlsDownloaderError::Playlist {
-                    reason: format!("No variants for ClosestToBitrate: {target_bw}"),
-                })?,
-            HlsVariantSelectionPolicy::AudioOnly => master_playlist_ref
-                .variants
-                .iter()
-                .find(|v| {
-                    v.audio.is_some()
-                        && v.video.is_none()
-                        && v.codecs.as_ref().is_some_and(|c| c.contains("mp4a"))
-                })
-                .ok_or_else(|| HlsDownloaderError::Playlist {
-                    reason: "No AudioOnly variant".to_string(),
-                })?,
-            HlsVariantSelectionPolicy::VideoOnly => master_playlist_ref
-                .variants
-                .iter()
-                .find(|v| v.video.is_some() && v.audio.is_none())
-                .ok_or_else(|| HlsDownloaderError::Playlist {
-                    reason: "No VideoOnly variant".to_string(),
-                })?,
-            HlsVariantSelectionPolicy::MatchingResolution { width, height } => master_playlist_ref
-                .variants
-                .iter()
-                .find(|v| {
-                    v.resolution
-                        .is_some_and(|r| r.width == (*width as u64) && r.height == (*height as u64))
-                })
-                .ok_or_else(|| HlsDownloaderError::Playlist {
-                    reason: format!("No variant for resolution {width}x{height}"),
-                })?,
-            HlsVariantSelectionPolicy::Custom(name) => {
-                warn!("Custom policy '{name}' selected; falling back to first variant.");
-                master_playlist_ref.variants.first().ok_or_else(|| {
-                    HlsDownloaderError::Playlist {
-                        reason: "No variants for Custom policy".to_string(),
-                    }
-                })?
-            }
-        };
         let master_playlist_url =
             Url::parse(master_base_url_str).map_err(|e| HlsDownloaderError::Playlist {
                 reason: format!("Invalid master base URL {master_base_url_str}: {e}"),
             })?;
-        let media_playlist_url = master_playlist_url
-            .join(&selected_variant.uri)
-            .map_err(|e| HlsDownloaderError::Playlist {
-                reason: format!(
-                    "Could not join master URL with variant URI {}: {e}",
-                    selected_variant.uri
-                ),
-            })?;
 
-        debug!("Selected media playlist URL: {media_playlist_url}");
-        let client = self.clients.client_for_url(&media_playlist_url);
-        let response = client
-            .get(media_playlist_url.clone())
-            .timeout(self.config.playlist_config.initial_playlist_fetch_timeout)
-            .query(&self.config.base.params)
-            .send()
-            .await
-            .map_err(|e| HlsDownloaderError::Network { source: e })?;
-        if !response.status().is_success() {
-            return Err(HlsDownloaderError::Playlist {
-                reason: format!(
-                    "Failed to fetch media playlist {media_playlist_url}: HTTP {}",
-                    response.status()
-                ),
-            });
-        }
-        let playlist_bytes = response
-            .bytes()
-            .await
-            .map_err(|e| HlsDownloaderError::Network { source: e })?;
-        let playlist_content = std::str::from_utf8(playlist_bytes.as_ref()).map_err(|e| {
-            HlsDownloaderError::Playlist {
-                reason: format!("Media playlist not UTF-8: {e}"),
-            }
-        })?;
-        let playlist_bytes_to_parse: Cow<[u8]> =
-            if TwitchPlaylistProcessor::is_twitch_playlist(media_playlist_url.as_str()) {
-                let preprocessed = self.preprocess_twitch_playlist(playlist_content);
-                Cow::Owned(preprocessed.into_bytes())
-            } else {
-                Cow::Borrowed(playlist_bytes.as_ref())
-            };
-        let base_url_obj =
-            media_playlist_url
-                .join(".")
-                .map_err(|e| HlsDownloaderError::Playlist {
-                    reason: format!("Bad base URL for media playlist: {e}"),
-                })?;
-        let media_base_url = base_url_obj.to_string();
-        debug!(
-            "Derived base URL from media playlist: {} -> {}",
-            media_playlist_url, media_base_url
+        let mut candidates = selectable_variants(
+            &master_playlist_ref.variants,
+            policy,
+            &self.config.playlist_config,
         );
-        match parse_playlist_res(&playlist_bytes_to_parse) {
-            Ok(m3u8_rs::Playlist::MediaPlaylist(pl)) => Ok(MediaPlaylistDetails {
-                playlist: pl,
-                url: media_playlist_url.to_string(),
-                base_url: media_base_url,
-            }),
-            Ok(m3u8_rs::Playlist::MasterPlaylist(_)) => Err(HlsDownloaderError::Playlist {
-                reason: "Expected Media Playlist, got Master".to_string(),
-            }),
-            Err(e) => Err(HlsDownloaderError::Playlist {
-                reason: format!("Failed to parse media playlist: {e}"),
-            }),
+        loop {
+            let choice =
+                policy
+                    .select(&candidates)
+                    .ok_or_else(|| HlsDownloaderError::Playlist {
+                        reason: format!("No variant of the master playlist matches {policy:?}"),
+                    })?;
+            let selected_variant = candidates[choice.index];
+            let media_playlist_url =
+                master_playlist_url
+                    .join(&selected_variant.uri)
+                    .map_err(|e| HlsDownloaderError::Playlist {
+                        reason: format!(
+                            "Could not join master URL with variant URI {}: {e}",
+                            selected_variant.uri
+                        ),
+                    })?;
+
+            let resolution = selected_variant.resolution.map(|r| (r.width, r.height));
+            info!(
+                url = %media_playlist_url,
+                bandwidth = selected_variant.bandwidth,
+                resolution = ?resolution,
+                codecs = ?selected_variant.codecs,
+                reason = %choice.reason,
+                "Selected HLS variant"
+            );
+            if let Some(on_progress) = &self.config.base.on_progress {
+                on_progress.emit(ProgressEvent::VariantSelected {
+                    url: Arc::from(media_playlist_url.as_str()),
+                    bandwidth: selected_variant.bandwidth,
+                    resolution,
+                    codecs: selected_variant.codecs.clone(),
+                    reason: choice.reason,
+                });
+            }
+
+            match self.fetch_media_playlist(media_playlist_url).await {
+                // A variant whose media playlist is gone is dropped and the selection re-applied
+                Err(e)
+                    if candidates.len() > 1
+                        && matches!(e.status(), Some(StatusCode::NOT_FOUND | StatusCode::GONE)) =>
+                {
+                    warn!(
+                        uri = %selected_variant.uri,
+                        error = %e,
+                        "HLS variant unavailable, selecting another one"
+                    );
+                    candidates.remove(choice.index);
+                }
+                result => {
+                    return result.map(|details| MediaPlaylistDetails {
+                        audio_rendition: self.select_audio_rendition(
+                            master_playlist_ref,
+                            selected_variant,
+                            &master_playlist_url,
+                        ),
+                        ..details
+                    });
+                }
+            }
         }
     }
 
@@ -412,11 +491,11 @@ impl PlaylistProvider for PlaylistEngine {
         &self,
         playlist_url_str: &str,
         mut current_playlist: MediaPlaylist,
-        base_url: String,
+        mut base_url: String,
         segment_request_tx: mpsc::Sender<ScheduledSegmentJob>,
         token: CancellationToken,
     ) -> Result<(), HlsDownloaderError> {
-        let playlist_url =
+        let mut playlist_url =
             Url::parse(playlist_url_str).map_err(|e| HlsDownloaderError::Playlist {
                 reason: format!("Invalid playlist URL for monitoring {playlist_url_str}: {e}"),
             })?;
@@ -425,17 +504,24 @@ impl PlaylistProvider for PlaylistEngine {
         let mut retries = 0;
         let mut last_playlist_bytes: Option<bytes::Bytes> = None;
 
+        // LL-HLS state. The tracker takes over job numbering from the last classic job.
+        let mut low_latency_tracker: Option<LowLatencyTracker> = None;
+        let mut blocking_reload: Option<BlockingReload> = None;
+        let mut part_target: Option<Duration> = None;
+        let mut next_sequence = current_playlist.media_sequence;
+        let mut last_delivered_msn: Option<u64> = None;
+
         let mut twitch_processor = if base_url.contains("ttvnw.net") {
             Some(TwitchPlaylistProcessor::new())
         } else {
             None
         };
 
-        const SEEN_SEGMENTS_LRU_CAPACITY: usize = 100;
-        let seen_segment_uris: Cache<String, ()> = Cache::builder()
-            .max_capacity(SEEN_SEGMENTS_LRU_CAPACITY as u64)
-            .eviction_policy(EvictionPolicy::lru())
-            .build();
+        const MIN_PART_REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+        let mut sequence_tracker = SequenceTracker::new(
+            self.config.playlist_config.segment_dedup_window,
+            &self.config.playlist_config.volatile_query_params,
+        );
 
         // Adaptive refresh tracking
         let mut adaptive_tracker = AdaptiveRefreshTracker::new(
@@ -444,23 +530,95 @@ impl PlaylistProvider for PlaylistEngine {
             self.config.playlist_config.adaptive_refresh_max_interval,
         );
 
+        let auth = self.clients.auth();
         loop {
+            let mut got_update = false;
+            auth.refresh_if_expired(&playlist_url).await?;
+            let refreshed_url = auth.url(&playlist_url);
+            if refreshed_url != playlist_url {
+                info!("Following refreshed playlist URL: {playlist_url} -> {refreshed_url}");
+                if let Ok(refreshed_base) = refreshed_url.join(".") {
+                    base_url = refreshed_base.to_string();
+                }
+                playlist_url = refreshed_url;
+            }
+            let auth_generation = auth.generation();
+
             match self
-                .fetch_and_parse_playlist(&playlist_url, &last_playlist_bytes, &token)
+                .fetch_and_parse_playlist(
+                    &playlist_url,
+                    &last_playlist_bytes,
+                    blocking_reload,
+                    &token,
+                )
                 .await
             {
                 Ok(Some((new_playlist, new_playlist_bytes))) => {
                     retries = 0;
-                    let jobs = self
-                        .process_segments(
-                            &new_playlist,
-                            &base_url,
-                            &seen_segment_uris,
-                            &mut last_map_uri,
-                            &mut twitch_processor,
-                            playlist_url.query(),
-                        )
-                        .await?;
+                    got_update = true;
+
+                    let low_latency = (self.config.playlist_config.low_latency_enabled
+                        && twitch_processor.is_none())
+                    .then(|| {
+                        LowLatencyPlaylist::parse(&String::from_utf8_lossy(&new_playlist_bytes))
+                    });
+
+                    let jobs = match low_latency
+                        .as_ref()
+                        .filter(|ll| ll.is_low_latency() || low_latency_tracker.is_some())
+                    {
+                        Some(ll) => {
+                            let tracker = low_latency_tracker.get_or_insert_with(|| {
+                                info!("LL-HLS playlist detected, downloading partial segments: {playlist_url}");
+                                LowLatencyTracker::new(next_sequence, last_delivered_msn)
+                            });
+                            self.process_low_latency_segments(
+                                &new_playlist,
+                                ll,
+                                &base_url,
+                                tracker,
+                                &mut last_map_uri,
+                                playlist_url.query(),
+                            )
+                        }
+                        None => {
+                            let jobs = self
+                                .process_segments(
+                                    &new_playlist,
+                                    &base_url,
+                                    &mut sequence_tracker,
+                                    &mut last_map_uri,
+                                    &mut twitch_processor,
+                                    playlist_url.query(),
+                                )
+                                .await?;
+                            last_delivered_msn = sequence_tracker.last_msn().or(last_delivered_msn);
+                            if let Some(sequence) = sequence_tracker.next_sequence() {
+                                next_sequence = next_sequence.max(sequence);
+                            }
+                            jobs
+                        }
+                    };
+
+                    blocking_reload = low_latency
+                        .as_ref()
+                        .filter(|ll| ll.is_low_latency() && ll.can_block_reload())
+                        .map(|ll| {
+                            let (msn, part) = ll.next_part();
+                            BlockingReload {
+                                msn,
+                                part,
+                                // Servers answer a blocking reload within three target durations
+                                hold_timeout: Duration::from_secs(
+                                    new_playlist.target_duration.saturating_mul(3),
+                                ),
+                            }
+                        });
+                    part_target = low_latency
+                        .as_ref()
+                        .and_then(|ll| ll.part_target)
+                        .filter(|target| target.is_finite() && *target > 0.0)
+                        .map(Duration::from_secs_f64);
 
                     // Update adaptive tracker with segment arrival info
                     let new_segments_count = jobs.len();
@@ -484,6 +642,12 @@ impl PlaylistProvider for PlaylistEngine {
                 }
                 Err(e) => {
                     error!("Error refreshing playlist {playlist_url}: {e}");
+                    // Retried below with the new credentials
+                    if let Some(reason) = auth.rejection(&e) {
+                        auth.refresh(&playlist_url, reason, auth_generation).await?;
+                    }
+                    // Fall back to a plain reload in case the server rejects the blocking one
+                    blocking_reload = None;
                     retries += 1;
                     if retries > self.config.playlist_config.live_max_refresh_retries {
                         return Err(e);
@@ -501,11 +665,18 @@ impl PlaylistProvider for PlaylistEngine {
                 }
             }
 
-            // Calculate refresh delay - use adaptive if enabled, otherwise use target_duration/2
+            // Calculate refresh delay - LL-HLS playlists are reloaded at the part target (or
+            // immediately when blocking reloads are supported), others use adaptive if enabled,
+            // otherwise target_duration/2
             let base_refresh_interval =
                 Duration::from_secs_f64(current_playlist.target_duration as f64 * 0.5)
                     .max(self.config.playlist_config.live_refresh_interval);
-            let refresh_delay = adaptive_tracker.get_refresh_interval(base_refresh_interval);
+            let refresh_delay = match (blocking_reload, part_target) {
+                // The server holds the next request until the next part is available
+                (Some(_), _) if got_update => Duration::ZERO,
+                (_, Some(part_target)) => part_target.max(MIN_PART_REFRESH_INTERVAL),
+                _ => adaptive_tracker.get_refresh_interval(base_refresh_interval),
+            };
 
             tokio::select! {
                 biased;
@@ -531,9 +702,30 @@ impl PlaylistEngine {
             clients,
             cache_service,
             config,
+            metrics: None,
+            probed_playlist: parking_lot::Mutex::new(None),
         }
     }
 
+    /// Use a playlist received by a protocol probe instead of requesting the initial
+    /// playlist again
+    pub fn with_probed_playlist(self, playlist: Option<bytes::Bytes>) -> Self {
+        *self.probed_playlist.lock() = playlist;
+        self
+    }
+
+    /// Create a new PlaylistEngine with performance metrics tracking
+    pub fn with_metrics(
+        clients: Arc<ClientPool>,
+        cache_service: Option<Arc<CacheManager>>,
+        config: Arc<HlsConfig>,
+        metrics: Arc<PerformanceMetrics>,
+    ) -> Self {
+        let mut engine = Self::new(clients, cache_service, config);
+        engine.metrics = Some(metrics);
+        engine
+    }
+
     fn parse_playlist_level_map(playlist: &MediaPlaylist) -> Option<m3u8_rs::Map> {
         let ext = playlist
             .unknown_tags
@@ -618,47 +810,196 @@ impl PlaylistEngine {
         out
     }
 
+    /// Fetch and parse the media playlist of the selected variant
+    async fn fetch_media_playlist(
+        &self,
+        media_playlist_url: Url,
+    ) -> Result<MediaPlaylistDetails, HlsDownloaderError> {
+        let request = self.playlist_request(
+            &media_playlist_url,
+            self.config.playlist_config.initial_playlist_fetch_timeout,
+        );
+        // Cancelled by dropping the future
+        let playlist_bytes = self
+            .fetch_playlist_bytes(request, &media_playlist_url, &CancellationToken::new())
+            .await?;
+        self.tee_playlist(&media_playlist_url, &playlist_bytes);
+        let playlist_content = std::str::from_utf8(playlist_bytes.as_ref()).map_err(|e| {
+            HlsDownloaderError::playlist_parse(
+                Some(line_at(playlist_bytes.as_ref(), e.valid_up_to())),
+                format!("Media playlist not UTF-8: {e}"),
+            )
+        })?;
+        let playlist_bytes_to_parse: Cow<[u8]> =
+            if TwitchPlaylistProcessor::is_twitch_playlist(media_playlist_url.as_str()) {
+                let preprocessed = self.preprocess_twitch_playlist(playlist_content);
+                Cow::Owned(preprocessed.into_bytes())
+            } else {
+                Cow::Borrowed(playlist_bytes.as_ref())
+            };
+        let base_url_obj =
+            media_playlist_url
+                .join(".")
+                .map_err(|e| HlsDownloaderError::Playlist {
+                    reason: format!("Bad base URL for media playlist: {e}"),
+                })?;
+        let media_base_url = base_url_obj.to_string();
+        debug!(
+            "Derived base URL from media playlist: {} -> {}",
+            media_playlist_url, media_base_url
+        );
+        match parse_playlist_res(&playlist_bytes_to_parse) {
+            Ok(m3u8_rs::Playlist::MediaPlaylist(pl)) => Ok(MediaPlaylistDetails {
+                playlist: pl,
+                url: media_playlist_url.to_string(),
+                base_url: media_base_url,
+                audio_rendition: None,
+            }),
+            Ok(m3u8_rs::Playlist::MasterPlaylist(_)) => Err(HlsDownloaderError::Playlist {
+                reason: "Expected Media Playlist, got Master".to_string(),
+            }),
+            Err(e) => Err(parse_error(
+                &playlist_bytes_to_parse,
+                e,
+                "Failed to parse media playlist",
+            )),
+        }
+    }
+
+    /// The alternate audio rendition of `variant` configured to be downloaded next to it,
+    /// `None` without one or when the rendition is carried by the variant
+    fn select_audio_rendition(
+        &self,
+        master: &MasterPlaylist,
+        variant: &VariantStream,
+        master_url: &Url,
+    ) -> Option<AudioRendition> {
+        let selection = self.config.playlist_config.audio_rendition.as_ref()?;
+        let groups = rendition_groups(master);
+        let Some(group) = audio_group(&groups, variant) else {
+            warn!(
+                uri = %variant.uri,
+                "Selected HLS variant has no alternate audio renditions"
+            );
+            return None;
+        };
+        let choice = selection.select(group)?;
+        let rendition = &group.renditions[choice.index];
+        let Some(uri) = &rendition.uri else {
+            info!(
+                rendition = %rendition,
+                "Selected HLS audio rendition is carried by the variant"
+            );
+            return None;
+        };
+        let url = match master_url.join(uri) {
+            Ok(url) => url,
+            Err(e) => {
+                warn!(uri = %uri, error = %e, "Invalid HLS audio rendition URI");
+                return None;
+            }
+        };
+        info!(
+            url = %url,
+            rendition = %rendition,
+            group = %group.group_id,
+            reason = %choice.reason,
+            "Selected HLS audio rendition"
+        );
+        Some(AudioRendition {
+            rendition: Arc::new(rendition.clone()),
+            url: url.to_string(),
+        })
+    }
+
+    /// Copies a playlist snapshot to the raw tee, when one is configured.
+    fn tee_playlist(&self, playlist_url: &Url, playlist_bytes: &bytes::Bytes) {
+        if let Some(tee) = &self.config.base.raw_tee {
+            tee.write(TeeKind::Playlist, playlist_url.as_str(), playlist_bytes);
+        }
+    }
+
+    /// Builds the request of a playlist, with the credentials of the latest auth refresh.
+    fn playlist_request(&self, playlist_url: &Url, timeout: Duration) -> reqwest::RequestBuilder {
+        let request = self
+            .clients
+            .client_for_url(playlist_url)
+            .get(playlist_url.clone())
+            .timeout(timeout)
+            .query(&self.config.base.params);
+        self.clients.auth().apply(playlist_url, request)
+    }
+
+    /// Fetches the body of a playlist, retrying transient failures with the configured policy.
+    async fn fetch_playlist_bytes(
+        &self,
+        request: reqwest::RequestBuilder,
+        playlist_url: &Url,
+        token: &CancellationToken,
+    ) -> Result<bytes::Bytes, HlsDownloaderError> {
+        retry_with_backoff(
+            &self.config.base.retry_policy,
+            playlist_url.as_str(),
+            self.config.base.on_progress.as_ref(),
+            token,
+            |_| async {
+                let Some(request) = request.try_clone() else {
+                    return RetryAction::Fail(unclonable_request(playlist_url));
+                };
+                let response = tokio::select! {
+                    _ = token.cancelled() => {
+                        return RetryAction::Fail(HlsDownloaderError::Cancelled);
+                    }
+                    response = request.send() => response,
+                };
+                let response = match response {
+                    Ok(response) => response,
+                    Err(e) => return RetryAction::from_request_error(e),
+                };
+                if !response.status().is_success() {
+                    let error = HlsDownloaderError::from_response(response, "playlist_fetch").await;
+                    return RetryAction::from_error(error);
+                }
+                let bytes = tokio::select! {
+                    _ = token.cancelled() => {
+                        return RetryAction::Fail(HlsDownloaderError::Cancelled);
+                    }
+                    bytes = response.bytes() => bytes,
+                };
+                match bytes {
+                    Ok(bytes) => RetryAction::Success(bytes),
+                    Err(e) => RetryAction::from_request_error(e),
+                }
+            },
+        )
+        .await
+        .map_err(playlist_timeout)
+    }
+
     /// Fetches and parses a refreshed media playlist.
     async fn fetch_and_parse_playlist(
         &self,
         playlist_url: &Url,
         last_playlist_bytes: &Option<bytes::Bytes>,
+        blocking_reload: Option<BlockingReload>,
         token: &CancellationToken,
     ) -> Result<Option<(MediaPlaylist, bytes::Bytes)>, HlsDownloaderError> {
         if token.is_cancelled() {
             return Err(HlsDownloaderError::Cancelled);
         }
 
-        let client = self.clients.client_for_url(playlist_url);
-        let response = client
-            .get(playlist_url.clone())
-            .timeout(self.config.playlist_config.initial_playlist_fetch_timeout)
-            .query(&self.config.base.params);
-
-        let response = tokio::select! {
-            _ = token.cancelled() => {
-                return Err(HlsDownloaderError::Cancelled);
-            }
-            response = response.send() => response,
+        let hold_timeout = blocking_reload.map_or(Duration::ZERO, |reload| reload.hold_timeout);
+        let mut response = self.playlist_request(
+            playlist_url,
+            self.config.playlist_config.initial_playlist_fetch_timeout + hold_timeout,
+        );
+        if let Some(reload) = blocking_reload {
+            response = response.query(&reload.query());
         }
-        .map_err(|e| HlsDownloaderError::Network { source: e })?;
 
-        if !response.status().is_success() {
-            return Err(HlsDownloaderError::Playlist {
-                reason: format!(
-                    "Failed to fetch playlist {playlist_url}: HTTP {}",
-                    response.status()
-                ),
-            });
-        }
-
-        let playlist_bytes = tokio::select! {
-            _ = token.cancelled() => {
-                return Err(HlsDownloaderError::Cancelled);
-            }
-            bytes = response.bytes() => bytes,
-        }
-        .map_err(|e| HlsDownloaderError::Network { source: e })?;
+        let playlist_bytes = self
+            .fetch_playlist_bytes(response, playlist_url, token)
+            .await?;
 
         // Fast path: check if we have a previous playlist and if lengths differ
         if let Some(last_bytes) = last_playlist_bytes.as_ref()
@@ -673,6 +1014,7 @@ impl PlaylistEngine {
                 return Ok(None);
             }
         }
+        self.tee_playlist(playlist_url, &playlist_bytes);
 
         let playlist_bytes_to_parse: Cow<[u8]> =
             if TwitchPlaylistProcessor::is_twitch_playlist(playlist_url.as_str()) {
@@ -688,9 +1030,11 @@ impl PlaylistEngine {
             Ok(m3u8_rs::Playlist::MasterPlaylist(_)) => Err(HlsDownloaderError::Playlist {
                 reason: format!("Expected Media Playlist, got Master for {playlist_url}"),
             }),
-            Err(e) => Err(HlsDownloaderError::Playlist {
-                reason: format!("Failed to parse refreshed playlist {playlist_url}: {e}"),
-            }),
+            Err(e) => Err(parse_error(
+                &playlist_bytes_to_parse,
+                e,
+                &format!("Failed to parse refreshed playlist {playlist_url}"),
+            )),
         }
     }
 
@@ -699,66 +1043,29 @@ impl PlaylistEngine {
         &self,
         new_playlist: &MediaPlaylist,
         base_url: &str,
-        seen_segment_uris: &Cache<String, ()>,
+        sequence_tracker: &mut SequenceTracker,
         last_map_uri: &mut Option<String>,
         twitch_processor: &mut Option<TwitchPlaylistProcessor>,
         parent_query: Option<&str>,
     ) -> Result<Vec<ScheduledSegmentJob>, HlsDownloaderError> {
         let mut jobs_to_send = Vec::new();
-        let base_url_parsed = Url::parse(base_url).ok();
         let base_url_arc: Arc<str> = Arc::from(base_url);
-        let playlist_level_map = Self::parse_playlist_level_map(new_playlist);
+        // The map in effect for the current segment. A segment-scoped EXT-X-MAP replaces it
+        // for every following segment, e.g. after a discontinuity.
+        let mut current_map = Self::parse_playlist_level_map(new_playlist);
+        let mut current_key: Option<m3u8_rs::Key> = None;
         let mut last_non_empty_segment_uri: Option<String> = None;
         let mut last_byterange_uri: Option<String> = None;
         let mut last_byterange_end: Option<u64> = None;
 
-        // Helper to merge query params from parent if missing in child
-        let parent_params: Vec<(String, String)> = parent_query
-            .map(|q| {
-                url::form_urlencoded::parse(q.as_bytes())
-                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
-                    .collect()
-            })
-            .unwrap_or_default();
-
-        let merge_params = |uri_str: &str| -> String {
-            if parent_params.is_empty() {
-                return uri_str.to_string();
-            }
-
-            if let Ok(mut url) = Url::parse(uri_str) {
-                let original = url.to_string();
-                for (k, v) in &parent_params {
-                    if url
-                        .query_pairs()
-                        .any(|(existing_k, _)| existing_k == k.as_str())
-                    {
-                        continue;
-                    }
-                    url.query_pairs_mut().append_pair(k, v);
-                }
-                let merged = url.to_string();
-                if original != merged {
-                    trace!("Merged query params: {} -> {}", original, merged);
-                }
-                return merged;
-            }
-            uri_str.to_string()
-        };
+        let resolver = UriResolver::new(base_url, parent_query);
 
-        let resolve_uri = |relative_uri: &str| -> Result<String, url::ParseError> {
-            let resolved = if let Some(base) = base_url_parsed.as_ref() {
-                base.join(relative_uri).map(|u| u.to_string())
-            } else {
-                Url::parse(base_url)
-                    .and_then(|b| b.join(relative_uri))
-                    .map(|u| u.to_string())
-            };
-            if let Ok(ref url) = resolved {
-                trace!("Resolved URI: {} + {} -> {}", base_url, relative_uri, url);
-            }
-            resolved
-        };
+        let duplicates_before = sequence_tracker.duplicates_skipped();
+        if sequence_tracker.begin_playlist(new_playlist.media_sequence, new_playlist.segments.len())
+            && let Some(metrics) = &self.metrics
+        {
+            metrics.record_sequence_reset();
+        }
 
         macro_rules! handle_segment {
             ($idx:expr, $segment:expr, $is_ad:expr, $discontinuity:expr) => {{
@@ -768,58 +1075,21 @@ impl PlaylistEngine {
                 let discontinuity: bool = $discontinuity;
                 let msn = new_playlist.media_sequence + idx as u64;
 
-                let resolved_key = segment.key.as_ref().map(|key| {
-                    let mut key = key.clone();
-                    if let Some(uri) = key.uri.as_deref() {
-                        let absolute_key_uri =
-                            if uri.starts_with("http://") || uri.starts_with("https://") {
-                                uri.to_string()
-                            } else {
-                                resolve_uri(uri).unwrap_or_else(|_| uri.to_string())
-                            };
-
-                        key.uri = Some(merge_params(&absolute_key_uri));
-                    }
-                    key
-                });
+                // EXT-X-KEY applies to every following segment until the next one, which
+                // m3u8-rs only attaches to the first segment it precedes
+                if let Some(key) = segment.key.as_ref() {
+                    current_key = (key.method != m3u8_rs::KeyMethod::None)
+                        .then(|| resolver.resolve_key(key));
+                }
+                let resolved_key = current_key.clone();
 
                 // m3u8-rs only attaches EXT-X-MAP to `MediaSegment.map` when it appears in the
                 // segment-scoped tag region. If it appears before the first segment, it lands in
                 // `MediaPlaylist.unknown_tags` as an `ExtTag` ("X-MAP").
-                if let Some(map_info) = segment.map.as_ref().or(playlist_level_map.as_ref()) {
-                    let absolute_map_uri = resolve_uri(&map_info.uri).unwrap_or_else(|_| {
-                        error!(
-                            "Failed to resolve map URI '{}' with base '{}'",
-                            map_info.uri, base_url
-                        );
-                        map_info.uri.clone()
-                    });
-
-                    let final_map_uri = merge_params(&absolute_map_uri);
-
-                    if last_map_uri.as_ref() != Some(&final_map_uri) {
-                        debug!("New init segment detected: {}", final_map_uri);
-                        let init_media_segment = MediaSegment {
-                            uri: final_map_uri.clone(),
-                            duration: 0.0,
-                            byte_range: map_info.byte_range.clone(),
-                            discontinuity,
-                            key: resolved_key.clone(),
-                            map: None,
-                            ..Default::default()
-                        };
-                        let init_job = ScheduledSegmentJob {
-                            base_url: Arc::clone(&base_url_arc),
-                            media_sequence_number: new_playlist.media_sequence + idx as u64,
-                            media_segment: Arc::new(init_media_segment),
-                            is_init_segment: true,
-                            is_prefetch: false,
-                            parsed_url: Url::parse(&final_map_uri).ok().map(Arc::new),
-                        };
-                        jobs_to_send.push(init_job);
-                        *last_map_uri = Some(final_map_uri);
-                    }
+                if let Some(map) = segment.map.as_ref() {
+                    current_map = Some(map.clone());
                 }
+                let resolved_map = current_map.as_ref().map(|map| resolver.resolve_map(map));
 
                 let effective_segment_uri = if segment.uri.trim().is_empty() {
                     if segment.byte_range.is_some() {
@@ -874,7 +1144,7 @@ impl PlaylistEngine {
 
                     if !should_skip {
                         let absolute_segment_uri =
-                            resolve_uri(effective_segment_uri).unwrap_or_else(|_| {
+                            resolver.resolve(effective_segment_uri).unwrap_or_else(|_| {
                                 error!(
                                     "Failed to resolve segment URI '{}' with base '{}'",
                                     effective_segment_uri, base_url
@@ -882,7 +1152,7 @@ impl PlaylistEngine {
                                 effective_segment_uri.to_string()
                             });
 
-                        let final_segment_uri = merge_params(&absolute_segment_uri);
+                        let final_segment_uri = resolver.merge_params(&absolute_segment_uri);
 
                         let segment_identity = if let Some(br) = effective_byte_range.as_ref() {
                             let offset = br
@@ -894,20 +1164,35 @@ impl PlaylistEngine {
                             final_segment_uri.clone()
                         };
 
-                        if !seen_segment_uris.contains_key(&segment_identity) {
+                        if !sequence_tracker.is_duplicate(msn, &segment_identity) {
                             if is_ad {
                                 debug!("Skipping Twitch ad segment: {}", segment.uri);
                             } else {
+                                let admitted = sequence_tracker.admit(msn, &segment_identity);
+                                // A media sequence reset is signalled downstream as a discontinuity
+                                let discontinuity = discontinuity || admitted.discontinuity;
+
+                                if let Some(map) = resolved_map.as_ref() {
+                                    jobs_to_send.extend(Self::init_segment_job(
+                                        &base_url_arc,
+                                        admitted.sequence,
+                                        map,
+                                        resolved_key.clone(),
+                                        discontinuity,
+                                        last_map_uri,
+                                    ));
+                                }
+
                                 let mut segment_for_job = segment.clone();
                                 segment_for_job.key = resolved_key.clone();
                                 segment_for_job.uri = final_segment_uri.clone();
                                 segment_for_job.byte_range = effective_byte_range.clone();
                                 segment_for_job.discontinuity = discontinuity;
-                                seen_segment_uris.insert(segment_identity, ()).await;
+                                segment_for_job.map = resolved_map.clone();
                                 trace!("New segment detected: {}", final_segment_uri);
                                 let job = ScheduledSegmentJob {
                                     base_url: Arc::clone(&base_url_arc),
-                                    media_sequence_number: msn,
+                                    media_sequence_number: admitted.sequence,
                                     media_segment: Arc::new(segment_for_job),
                                     is_init_segment: false,
                                     is_prefetch: segment.title.as_deref() == Some("PREFETCH_SEGMENT"),
@@ -940,9 +1225,163 @@ impl PlaylistEngine {
                 handle_segment!(idx, segment, false, segment.discontinuity)?;
             }
         }
+
+        let duplicates = sequence_tracker.duplicates_skipped() - duplicates_before;
+        if duplicates > 0
+            && let Some(metrics) = &self.metrics
+        {
+            metrics.record_duplicate_segments(duplicates);
+        }
         Ok(jobs_to_send)
     }
 
+    /// Creates the jobs of a refreshed LL-HLS playlist, numbered with the delivery sequence of
+    /// the tracker.
+    fn process_low_latency_segments(
+        &self,
+        new_playlist: &MediaPlaylist,
+        low_latency: &LowLatencyPlaylist,
+        base_url: &str,
+        tracker: &mut LowLatencyTracker,
+        last_map_uri: &mut Option<String>,
+        parent_query: Option<&str>,
+    ) -> Vec<ScheduledSegmentJob> {
+        let base_url_arc: Arc<str> = Arc::from(base_url);
+        let resolver = UriResolver::new(base_url, parent_query);
+
+        // Key and map in effect for each segment. The last ones also apply to the parts of the
+        // segment being produced.
+        let mut current_key = None;
+        let mut current_map = Self::parse_playlist_level_map(new_playlist);
+        let segment_context: Vec<_> = new_playlist
+            .segments
+            .iter()
+            .map(|segment| {
+                if segment.key.is_some() {
+                    current_key = segment.key.clone();
+                }
+                if segment.map.is_some() {
+                    current_map = segment.map.clone();
+                }
+                (current_key.clone(), current_map.clone())
+            })
+            .collect();
+
+        let planned = tracker.plan(
+            &new_playlist.segments,
+            new_playlist.media_sequence,
+            low_latency,
+            self.config.playlist_config.low_latency_preload_hints,
+        );
+
+        let mut jobs = Vec::with_capacity(planned.len());
+        for (sequence, item) in planned {
+            let msn = item.msn();
+            let (key, map) = msn
+                .checked_sub(new_playlist.media_sequence)
+                .and_then(|idx| segment_context.get(idx as usize))
+                .cloned()
+                .unwrap_or_else(|| (current_key.clone(), current_map.clone()));
+
+            let mut key = key.map(|key| resolver.resolve_key(&key));
+            // The default IV is derived from the media sequence number, which the delivery
+            // sequence of the job no longer carries
+            if let Some(key) = key.as_mut()
+                && key.method == m3u8_rs::KeyMethod::AES128
+                && key.iv.is_none()
+            {
+                key.iv = Some(format!("0x{msn:032x}"));
+            }
+            let map = map.map(|map| resolver.resolve_map(&map));
+
+            let mut media_segment = match item {
+                PlannedItem::Segment { segment, .. } => MediaSegment {
+                    uri: resolver.absolute(&segment.uri, "segment"),
+                    ..segment.clone()
+                },
+                PlannedItem::Part(part) => MediaSegment {
+                    uri: resolver.absolute(&part.uri, "part"),
+                    duration: part.duration as f32,
+                    byte_range: part.byte_range.clone(),
+                    discontinuity: part.discontinuity,
+                    ..Default::default()
+                },
+                PlannedItem::Hint { hint, .. } => MediaSegment {
+                    uri: resolver.absolute(&hint.uri, "preload hint"),
+                    duration: low_latency.part_target.unwrap_or_default() as f32,
+                    byte_range: hint.byte_range_start.map(|start| m3u8_rs::ByteRange {
+                        length: hint.byte_range_length.unwrap_or_default(),
+                        offset: Some(start),
+                    }),
+                    ..Default::default()
+                },
+            };
+            media_segment.key = key.clone();
+            media_segment.map = map.clone();
+
+            if let Some(map) = map.as_ref() {
+                jobs.extend(Self::init_segment_job(
+                    &base_url_arc,
+                    sequence,
+                    map,
+                    key,
+                    media_segment.discontinuity,
+                    last_map_uri,
+                ));
+            }
+
+            trace!(msn, sequence, "New LL-HLS job: {}", media_segment.uri);
+            jobs.push(ScheduledSegmentJob {
+                base_url: Arc::clone(&base_url_arc),
+                media_sequence_number: sequence,
+                parsed_url: Url::parse(&media_segment.uri).ok().map(Arc::new),
+                media_segment: Arc::new(media_segment),
+                is_init_segment: false,
+                is_prefetch: false,
+            });
+        }
+        jobs
+    }
+
+    /// Creates the job of the init segment described by `map`, unless it is the init segment
+    /// of the previous job.
+    fn init_segment_job(
+        base_url: &Arc<str>,
+        media_sequence_number: u64,
+        map: &m3u8_rs::Map,
+        key: Option<m3u8_rs::Key>,
+        discontinuity: bool,
+        last_map_uri: &mut Option<String>,
+    ) -> Option<ScheduledSegmentJob> {
+        // Maps sharing a URI but covering different ranges are different init segments
+        let map_identity = match map.byte_range.as_ref() {
+            Some(br) => format!("{}|br={}@{}", map.uri, br.length, br.offset.unwrap_or(0)),
+            None => map.uri.clone(),
+        };
+        if last_map_uri.as_ref() == Some(&map_identity) {
+            return None;
+        }
+        debug!("New init segment detected: {}", map_identity);
+        let init_media_segment = MediaSegment {
+            uri: map.uri.clone(),
+            duration: 0.0,
+            byte_range: map.byte_range.clone(),
+            discontinuity,
+            key,
+            map: None,
+            ..Default::default()
+        };
+        *last_map_uri = Some(map_identity);
+        Some(ScheduledSegmentJob {
+            base_url: Arc::clone(base_url),
+            media_sequence_number,
+            media_segment: Arc::new(init_media_segment),
+            is_init_segment: true,
+            is_prefetch: false,
+            parsed_url: Url::parse(&map.uri).ok().map(Arc::new),
+        })
+    }
+
     /// Sends the created jobs to the segment scheduler.
     async fn send_jobs(
         &self,
@@ -973,17 +1412,24 @@ impl PlaylistEngine {
 mod tests {
     use super::*;
     use crate::hls::config::HlsConfig;
-    use moka::future::Cache;
+    use bytes::Bytes;
     use std::collections::VecDeque;
     use tokio_util::sync::CancellationToken;
 
     fn test_engine() -> PlaylistEngine {
         let config = Arc::new(HlsConfig::default());
-        let clients =
-            Arc::new(crate::downloader::create_client_pool(&config.base).expect("client pool"));
+        let clients = Arc::new(
+            crate::downloader::create_client_pool(&config.base, crate::ProtocolType::Hls)
+                .expect("client pool"),
+        );
         PlaylistEngine::new(clients, None, config)
     }
 
+    fn test_sequence_tracker() -> SequenceTracker {
+        let config = HlsConfig::default().playlist_config;
+        SequenceTracker::new(config.segment_dedup_window, &config.volatile_query_params)
+    }
+
     fn parse_media_playlist(input: &str) -> MediaPlaylist {
         match parse_playlist_res(input.as_bytes()).expect("playlist should parse") {
             m3u8_rs::Playlist::MediaPlaylist(pl) => pl,
@@ -991,20 +1437,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_error_points_at_line() {
+        let input = b"<html>\n<body>offline</body>\n</html>\n";
+        let Err(error) = parse_playlist_res(input) else {
+            panic!("HTML should not parse as a playlist");
+        };
+        let error = parse_error(input, error, "Failed to parse playlist");
+        assert!(
+            matches!(
+                error,
+                HlsDownloaderError::PlaylistParse { line: Some(1), .. }
+            ),
+            "{error:?}"
+        );
+        assert!(
+            error
+                .to_string()
+                .starts_with("failed to parse playlist at line 1")
+        );
+    }
+
     #[tokio::test]
     async fn process_segments_skips_empty_uri_segment() {
         let engine = test_engine();
         let playlist = parse_media_playlist(
             "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:1\n#EXTINF:2.0,\n\n",
         );
-        let seen: Cache<String, ()> = Cache::builder().max_capacity(100).build();
+        let mut sequence_tracker = test_sequence_tracker();
         let mut last_map_uri = None;
         let mut twitch_processor = None;
         let jobs = engine
             .process_segments(
                 &playlist,
                 "https://example.com/path/",
-                &seen,
+                &mut sequence_tracker,
                 &mut last_map_uri,
                 &mut twitch_processor,
                 None,
@@ -1029,14 +1496,14 @@ mod tests {
             }),
             ..Default::default()
         });
-        let seen: Cache<String, ()> = Cache::builder().max_capacity(100).build();
+        let mut sequence_tracker = test_sequence_tracker();
         let mut last_map_uri = None;
         let mut twitch_processor = None;
         let jobs = engine
             .process_segments(
                 &playlist,
                 "https://example.com/path/",
-                &seen,
+                &mut sequence_tracker,
                 &mut last_map_uri,
                 &mut twitch_processor,
                 None,
@@ -1069,6 +1536,231 @@ mod tests {
         );
     }
 
+    const FMP4_BYTERANGE_PLAYLIST: &str = "#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-TARGETDURATION:2
+#EXT-X-MEDIA-SEQUENCE:1
+#EXT-X-MAP:URI=\"main.mp4\",BYTERANGE=\"720@0\"
+#EXTINF:2.0,
+#EXT-X-BYTERANGE:5000@720
+main.mp4
+#EXTINF:2.0,
+#EXT-X-BYTERANGE:4000
+main.mp4
+#EXT-X-DISCONTINUITY
+#EXT-X-MAP:URI=\"main.mp4\",BYTERANGE=\"700@9720\"
+#EXTINF:2.0,
+#EXT-X-BYTERANGE:3000@10420
+main.mp4
+#EXTINF:2.0,
+#EXT-X-BYTERANGE:3000
+main.mp4
+";
+
+    fn byte_range(length: u64, offset: u64) -> Option<m3u8_rs::ByteRange> {
+        Some(m3u8_rs::ByteRange {
+            length,
+            offset: Some(offset),
+        })
+    }
+
+    #[tokio::test]
+    async fn process_segments_resolves_byterange_maps_across_discontinuity() {
+        let engine = test_engine();
+        let playlist = parse_media_playlist(FMP4_BYTERANGE_PLAYLIST);
+        let mut sequence_tracker = test_sequence_tracker();
+        let mut last_map_uri = None;
+        let mut twitch_processor = None;
+        let jobs = engine
+            .process_segments(
+                &playlist,
+                "https://example.com/path/",
+                &mut sequence_tracker,
+                &mut last_map_uri,
+                &mut twitch_processor,
+                None,
+            )
+            .await
+            .expect("process_segments should succeed");
+
+        let summary: Vec<_> = jobs
+            .iter()
+            .map(|job| {
+                (
+                    job.is_init_segment,
+                    job.media_segment.byte_range.clone(),
+                    job.media_segment.discontinuity,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (true, byte_range(720, 0), false),
+                (false, byte_range(5000, 720), false),
+                (false, byte_range(4000, 5720), false),
+                (true, byte_range(700, 9720), true),
+                (false, byte_range(3000, 10420), true),
+                (false, byte_range(3000, 13420), false),
+            ]
+        );
+
+        for job in &jobs {
+            assert_eq!(job.media_segment.uri, "https://example.com/path/main.mp4");
+        }
+
+        // Media segments reference the init segment they depend on
+        let maps: Vec<_> = jobs
+            .iter()
+            .filter(|job| !job.is_init_segment)
+            .map(|job| {
+                let map = job.media_segment.map.as_ref().expect("resolved map");
+                assert_eq!(map.uri, "https://example.com/path/main.mp4");
+                map.byte_range.clone()
+            })
+            .collect();
+        assert_eq!(
+            maps,
+            vec![
+                byte_range(720, 0),
+                byte_range(720, 0),
+                byte_range(700, 9720),
+                byte_range(700, 9720),
+            ]
+        );
+        assert_eq!(jobs[3].media_sequence_number, jobs[4].media_sequence_number);
+
+        // A refresh of the same playlist schedules neither segments nor init segments again
+        let jobs = engine
+            .process_segments(
+                &playlist,
+                "https://example.com/path/",
+                &mut sequence_tracker,
+                &mut last_map_uri,
+                &mut twitch_processor,
+                None,
+            )
+            .await
+            .expect("process_segments should succeed");
+        assert!(jobs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn process_segments_marks_ts_discontinuity() {
+        let engine = test_engine();
+        let playlist = parse_media_playlist(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:10\n#EXTINF:2.0,\na.ts\n#EXT-X-DISCONTINUITY\n#EXTINF:2.0,\nb.ts\n#EXTINF:2.0,\nc.ts\n",
+        );
+        let mut sequence_tracker = test_sequence_tracker();
+        let mut last_map_uri = None;
+        let mut twitch_processor = None;
+        let jobs = engine
+            .process_segments(
+                &playlist,
+                "https://example.com/path/",
+                &mut sequence_tracker,
+                &mut last_map_uri,
+                &mut twitch_processor,
+                None,
+            )
+            .await
+            .expect("process_segments should succeed");
+
+        let flags: Vec<_> = jobs
+            .iter()
+            .map(|job| (job.media_sequence_number, job.media_segment.discontinuity))
+            .collect();
+        assert_eq!(flags, vec![(10, false), (11, true), (12, false)]);
+        assert!(jobs.iter().all(|job| job.media_segment.map.is_none()));
+        assert!(last_map_uri.is_none());
+    }
+
+    /// Process a playlist reload, returning the `(file name, sequence, discontinuity)` of jobs
+    async fn reload_segments(
+        engine: &PlaylistEngine,
+        sequence_tracker: &mut SequenceTracker,
+        playlist: &str,
+    ) -> Vec<(String, u64, bool)> {
+        let jobs = engine
+            .process_segments(
+                &parse_media_playlist(playlist),
+                "https://example.com/path/",
+                sequence_tracker,
+                &mut None,
+                &mut None,
+                None,
+            )
+            .await
+            .expect("process_segments should succeed");
+        jobs.iter()
+            .map(|job| {
+                let uri = job.media_segment.uri.rsplit('/').next().unwrap();
+                (
+                    uri.split('?').next().unwrap().to_string(),
+                    job.media_sequence_number,
+                    job.media_segment.discontinuity,
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn process_segments_skips_duplicates_and_continues_after_sequence_reset() {
+        let config = Arc::new(HlsConfig::default());
+        let clients = Arc::new(
+            crate::downloader::create_client_pool(&config.base, crate::ProtocolType::Hls)
+                .expect("client pool"),
+        );
+        let metrics = Arc::new(PerformanceMetrics::new());
+        let engine = PlaylistEngine::with_metrics(clients, None, config, Arc::clone(&metrics));
+
+        let mut sequence_tracker = test_sequence_tracker();
+        let jobs = reload_segments(
+            &engine,
+            &mut sequence_tracker,
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:500\n\
+#EXTINF:2.0,\na.ts?token=1\n#EXTINF:2.0,\nb.ts?token=1\n",
+        )
+        .await;
+        assert_eq!(
+            jobs,
+            vec![
+                ("a.ts".to_string(), 500, false),
+                ("b.ts".to_string(), 501, false)
+            ]
+        );
+
+        // Re-signed URIs of known segments are skipped
+        let jobs = reload_segments(
+            &engine,
+            &mut sequence_tracker,
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:500\n\
+#EXTINF:2.0,\na.ts?token=2\n#EXTINF:2.0,\nb.ts?token=2\n#EXTINF:2.0,\nc.ts?token=2\n",
+        )
+        .await;
+        assert_eq!(jobs, vec![("c.ts".to_string(), 502, false)]);
+
+        // The origin restarts its media sequence
+        let jobs = reload_segments(
+            &engine,
+            &mut sequence_tracker,
+            "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:0\n\
+#EXTINF:2.0,\nc.ts?token=3\n#EXTINF:2.0,\nd.ts?token=3\n#EXTINF:2.0,\ne.ts?token=3\n",
+        )
+        .await;
+        assert_eq!(
+            jobs,
+            vec![
+                ("d.ts".to_string(), 503, true),
+                ("e.ts".to_string(), 504, false)
+            ]
+        );
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.duplicate_segments_skipped, 3);
+        assert_eq!(snapshot.sequence_resets_detected, 1);
+    }
+
     #[test]
     fn preprocess_twitch_playlist_keeps_daterange_and_transforms_prefetch() {
         let engine = test_engine();
@@ -1137,8 +1829,415 @@ mod tests {
         let token = CancellationToken::new();
         token.cancel();
 
-        let res = engine.fetch_and_parse_playlist(&url, &None, &token).await;
+        let res = engine
+            .fetch_and_parse_playlist(&url, &None, None, &token)
+            .await;
 
         assert!(matches!(res, Err(HlsDownloaderError::Cancelled)));
     }
+
+    const PARTS_PER_SEGMENT: u64 = 4;
+
+    /// An LL-HLS playlist once `produced` of `total` parts are available
+    fn ll_hls_snapshot(produced: u64, total: u64) -> String {
+        let mut playlist = String::from(
+            "#EXTM3U\n#EXT-X-VERSION:9\n#EXT-X-TARGETDURATION:2\n\
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.5\n\
+#EXT-X-PART-INF:PART-TARGET=0.5\n#EXT-X-MEDIA-SEQUENCE:0\n",
+        );
+        for part in 0..produced {
+            let (msn, index) = (part / PARTS_PER_SEGMENT, part % PARTS_PER_SEGMENT);
+            playlist.push_str(&format!(
+                "#EXT-X-PART:DURATION=0.5,URI=\"seg{msn}.part{index}.ts\"\n"
+            ));
+            if index == PARTS_PER_SEGMENT - 1 {
+                playlist.push_str(&format!("#EXTINF:2.0,\nseg{msn}.ts\n"));
+            }
+        }
+        if produced == total {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+        playlist
+    }
+
+    /// Serves an LL-HLS playlist that advances to the part requested by a blocking reload
+    async fn spawn_ll_hls_server(
+        initial: u64,
+        total: u64,
+    ) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base_url = format!("http://{}/", listener.local_addr().expect("local addr"));
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let log = Arc::clone(&log);
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    let target = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    let url = Url::parse(&format!("http://localhost{target}")).expect("target");
+                    let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+                    let produced = match (query.get("_HLS_msn"), query.get("_HLS_part")) {
+                        (Some(msn), Some(part)) => {
+                            msn.parse::<u64>().unwrap() * PARTS_PER_SEGMENT
+                                + part.parse::<u64>().unwrap()
+                                + 1
+                        }
+                        _ => initial,
+                    };
+                    log.lock().unwrap().push(target);
+
+                    let body = ll_hls_snapshot(produced.min(total), total);
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/vnd.apple.mpegurl\r\n\
+Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        (base_url, requests)
+    }
+
+    #[tokio::test]
+    async fn monitor_ll_hls_playlist_downloads_parts_in_order_without_duplicates() {
+        let engine = test_engine();
+        let (base_url, requests) = spawn_ll_hls_server(6, 12).await;
+        let playlist_url = format!("{base_url}live.m3u8");
+        let initial = parse_media_playlist(&ll_hls_snapshot(6, 12));
+
+        let (tx, mut rx) = mpsc::channel(64);
+        engine
+            .monitor_media_playlist(
+                &playlist_url,
+                initial,
+                base_url.clone(),
+                tx,
+                CancellationToken::new(),
+            )
+            .await
+            .expect("monitoring should finish at ENDLIST");
+
+        let mut jobs = Vec::new();
+        while let Ok(job) = rx.try_recv() {
+            jobs.push(job);
+        }
+
+        let uris: Vec<_> = jobs
+            .iter()
+            .map(|job| {
+                job.media_segment
+                    .uri
+                    .trim_start_matches(&base_url)
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            uris,
+            vec![
+                "seg0.ts",
+                "seg1.part0.ts",
+                "seg1.part1.ts",
+                "seg1.part2.ts",
+                "seg1.part3.ts",
+                "seg2.part0.ts",
+                "seg2.part1.ts",
+                "seg2.part2.ts",
+                "seg2.part3.ts",
+            ]
+        );
+        // Contiguous delivery sequence starting at the initial media sequence
+        let sequences: Vec<_> = jobs.iter().map(|job| job.media_sequence_number).collect();
+        assert_eq!(sequences, (0..9).collect::<Vec<_>>());
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests[0], "/live.m3u8");
+        assert!(
+            requests
+                .iter()
+                .any(|target| target.contains("_HLS_msn=1") && target.contains("_HLS_part=2"))
+        );
+        assert!(
+            requests
+                .iter()
+                .any(|target| target.contains("_HLS_msn=2") && target.contains("_HLS_part=0"))
+        );
+    }
+
+    fn encrypt_segment(plaintext: &[u8], key: &[u8; 16], iv: &[u8; 16]) -> Bytes {
+        use cipher::block_padding::Pkcs7;
+        use cipher::{BlockModeEncrypt, KeyIvInit};
+
+        let cipher = cbc::Encryptor::<aes::Aes128>::new_from_slices(key, iv).unwrap();
+        let mut buffer = vec![0u8; (plaintext.len() / 16 + 1) * 16];
+        buffer[..plaintext.len()].copy_from_slice(plaintext);
+        let encrypted = cipher
+            .encrypt_padded::<Pkcs7>(&mut buffer, plaintext.len())
+            .unwrap();
+        Bytes::copy_from_slice(encrypted)
+    }
+
+    /// Serves fixed bodies by path and logs the requested paths
+    async fn spawn_static_server(
+        routes: HashMap<&'static str, Bytes>,
+    ) -> (String, Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base_url = format!("http://{}/", listener.local_addr().expect("local addr"));
+        let requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&requests);
+        let routes = Arc::new(routes);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let log = Arc::clone(&log);
+                let routes = Arc::clone(&routes);
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    let target = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    log.lock().unwrap().push(target.clone());
+
+                    let response = match routes.get(target.as_str()) {
+                        Some(body) => {
+                            let mut response = format!(
+                                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                                body.len()
+                            )
+                            .into_bytes();
+                            response.extend_from_slice(body);
+                            response
+                        }
+                        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_vec(),
+                    };
+                    let _ = socket.write_all(&response).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        (base_url, requests)
+    }
+
+    #[tokio::test]
+    async fn process_segments_decrypts_with_key_active_at_each_segment() {
+        use crate::cache::CacheConfig;
+        use crate::hls::decryption::{DecryptionService, KeyFetcher};
+        use crate::hls::processor::{SegmentProcessor, SegmentTransformer};
+
+        let key1 = [0x11u8; 16];
+        let key2 = [0x22u8; 16];
+        let explicit_iv = [0x5Au8; 16];
+        let (base_url, requests) = spawn_static_server(HashMap::from([
+            ("/keys/key1", Bytes::copy_from_slice(&key1)),
+            ("/keys/key2", Bytes::copy_from_slice(&key2)),
+        ]))
+        .await;
+
+        // The key rotates at segment 12, segment 14 is in the clear
+        let playlist = parse_media_playlist(&format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:10\n\
+#EXT-X-KEY:METHOD=AES-128,URI=\"keys/key1\"\n#EXTINF:2.0,\na.ts\n#EXTINF:2.0,\nb.ts\n\
+#EXT-X-KEY:METHOD=AES-128,URI=\"keys/key2\",IV=0x{}\n#EXTINF:2.0,\nc.ts\n#EXTINF:2.0,\nd.ts\n\
+#EXT-X-KEY:METHOD=NONE\n#EXTINF:2.0,\ne.ts\n",
+            hex::encode(explicit_iv)
+        ));
+
+        let config = Arc::new(HlsConfig::default());
+        let clients = Arc::new(
+            crate::downloader::create_client_pool(&config.base, crate::ProtocolType::Hls)
+                .expect("client pool"),
+        );
+        let cache_manager = Arc::new(
+            CacheManager::new(CacheConfig {
+                max_disk_cache_size: 0,
+                ..CacheConfig::default()
+            })
+            .await
+            .expect("cache manager"),
+        );
+        let engine =
+            PlaylistEngine::new(clients.clone(), Some(cache_manager.clone()), config.clone());
+
+        let mut sequence_tracker = test_sequence_tracker();
+        let mut last_map_uri = None;
+        let mut twitch_processor = None;
+        let jobs = engine
+            .process_segments(
+                &playlist,
+                &base_url,
+                &mut sequence_tracker,
+                &mut last_map_uri,
+                &mut twitch_processor,
+                None,
+            )
+            .await
+            .expect("process_segments should succeed");
+
+        let keys: Vec<_> = jobs
+            .iter()
+            .map(|job| {
+                job.media_segment
+                    .key
+                    .as_ref()
+                    .and_then(|key| key.uri.clone())
+                    .map(|uri| uri.trim_start_matches(&base_url).to_string())
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                Some("keys/key1".to_string()),
+                Some("keys/key1".to_string()),
+                Some("keys/key2".to_string()),
+                Some("keys/key2".to_string()),
+                None,
+            ]
+        );
+
+        let key_fetcher = Arc::new(KeyFetcher::new(
+            clients,
+            config.clone(),
+            CancellationToken::new(),
+        ));
+        let decryption_service = Arc::new(DecryptionService::new(
+            config.clone(),
+            key_fetcher,
+            Some(cache_manager),
+        ));
+        let processor = SegmentProcessor::new(config, decryption_service, None);
+
+        for job in &jobs {
+            let msn = job.media_sequence_number;
+            let plaintext = format!("segment {msn} payload").into_bytes();
+            // Without an IV attribute, the IV is the media sequence number
+            let mut derived_iv = [0u8; 16];
+            derived_iv[8..].copy_from_slice(&msn.to_be_bytes());
+            let raw = match msn {
+                10 | 11 => encrypt_segment(&plaintext, &key1, &derived_iv),
+                12 | 13 => encrypt_segment(&plaintext, &key2, &explicit_iv),
+                _ => Bytes::from(plaintext.clone()),
+            };
+
+            let data = processor
+                .process_segment_from_job(raw, job)
+                .await
+                .expect("segment should decrypt");
+            assert_eq!(data.data().unwrap().as_ref(), plaintext.as_slice());
+        }
+
+        // Each key is fetched once and then served from the cache
+        let mut requests = requests.lock().unwrap().clone();
+        requests.sort();
+        assert_eq!(requests, vec!["/keys/key1", "/keys/key2"]);
+    }
+
+    #[tokio::test]
+    async fn select_media_playlist_reselects_when_variant_is_missing() {
+        use crate::OnProgress;
+        use crate::hls::variant::CodecPreference;
+
+        let media = Bytes::from_static(
+            b"#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXT-X-MEDIA-SEQUENCE:0\n#EXTINF:2.0,\nseg0.ts\n",
+        );
+        let (base_url, requests) =
+            spawn_static_server(HashMap::from([("/avc_720.m3u8", media)])).await;
+        let master = m3u8_rs::parse_master_playlist_res(
+            b"#EXTM3U\n\
+#EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080,CODECS=\"hvc1.1.6.L120.90,mp4a.40.2\"\n\
+hevc_1080.m3u8\n\
+#EXT-X-STREAM-INF:BANDWIDTH=3000000,RESOLUTION=1280x720,CODECS=\"avc1.64001f,mp4a.40.2\"\n\
+avc_720.m3u8\n",
+        )
+        .expect("master playlist");
+
+        let events = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let mut config = HlsConfig::default();
+        config.base.on_progress = Some(OnProgress::new(move |event| sink.lock().push(event)));
+        let config = Arc::new(config);
+        let clients = Arc::new(
+            crate::downloader::create_client_pool(&config.base, crate::ProtocolType::Hls)
+                .expect("client pool"),
+        );
+        let engine = PlaylistEngine::new(clients, None, config);
+
+        let policy =
+            HlsVariantSelectionPolicy::Selector(Arc::new(CodecPreference::new(["hvc1", "avc1"])));
+        let details = engine
+            .select_media_playlist(&InitialPlaylist::Master(master, base_url.clone()), &policy)
+            .await
+            .expect("fallback variant");
+
+        assert_eq!(details.url, format!("{base_url}avc_720.m3u8"));
+        assert_eq!(details.playlist.segments.len(), 1);
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["/hevc_1080.m3u8", "/avc_720.m3u8"]
+        );
+
+        let selections: Vec<_> = events
+            .lock()
+            .iter()
+            .filter_map(|event| match event {
+                ProgressEvent::VariantSelected {
+                    url,
+                    bandwidth,
+                    resolution,
+                    reason,
+                    ..
+                } => Some((
+                    url.trim_start_matches(&base_url).to_string(),
+                    *bandwidth,
+                    *resolution,
+                    reason.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            selections,
+            vec![
+                (
+                    "hevc_1080.m3u8".to_string(),
+                    6_000_000,
+                    Some((1920, 1080)),
+                    "preferred codec hvc1".to_string()
+                ),
+                (
+                    "avc_720.m3u8".to_string(),
+                    3_000_000,
+                    Some((1280, 720)),
+                    "preferred codec avc1".to_string()
+                ),
+            ]
+        );
+    }
 }
@@ -5,7 +5,7 @@ use crate::CacheManager;
 use crate::cache::{CacheKey, CacheMetadata, CacheResourceType};
 use crate::hls::HlsDownloaderError;
 use crate::hls::config::HlsConfig;
-use crate::hls::decryption::DecryptionService;
+use crate::hls::decryption::{DecryptionService, ensure_supported};
 use crate::hls::metrics::PerformanceMetrics;
 use crate::hls::scheduler::ScheduledSegmentJob;
 use crate::hls::segment_utils::create_hls_data;
@@ -74,17 +74,17 @@ impl SegmentTransformer for SegmentProcessor {
     ) -> Result<HlsData, HlsDownloaderError> {
         let zero_copy_enabled = self.config.performance_config.zero_copy_enabled;
 
-        // Check if segment requires decryption
-        let requires_decryption = job
+        // METHOD=NONE explicitly clears a previous key
+        let key_info = job
             .media_segment
             .key
             .as_ref()
-            .is_some_and(|key_info| key_info.method == m3u8_rs::KeyMethod::AES128);
+            .filter(|key_info| key_info.method != m3u8_rs::KeyMethod::None);
 
         // Process data: either zero-copy forward or decrypt
-        let current_data = if requires_decryption {
-            // Decryption required - cannot use zero-copy
-            let key_info = job.media_segment.key.as_ref().unwrap(); // Safe: we checked
+        let current_data = if let Some(key_info) = key_info {
+            // Fail clearly instead of forwarding data that cannot be decrypted
+            ensure_supported(key_info)?;
 
             let iv_override = if key_info.iv.is_none() {
                 Some(Self::u64_to_iv_bytes(job.media_sequence_number))
@@ -109,30 +109,12 @@ impl SegmentTransformer for SegmentProcessor {
             }
 
             decrypted_data
-        } else if let Some(key_info) = &job.media_segment.key {
-            // Key exists but method is not AES128
-            if key_info.method != m3u8_rs::KeyMethod::None {
-                return Err(HlsDownloaderError::Decryption {
-                    reason: format!(
-                        "Segment processing encountered unsupported encryption method: {:?}",
-                        key_info.method
-                    ),
-                });
-            }
-            // KeyMethod::None - no decryption needed, use zero-copy if enabled
-            if zero_copy_enabled {
-                trace!(
-                    uri = %job.media_segment.uri,
-                    "Zero-copy forwarding: unencrypted segment (KeyMethod::None)"
-                );
-            }
-            raw_data_input
         } else {
-            // No key at all - unencrypted segment, use zero-copy if enabled
+            // Unencrypted segment, use zero-copy if enabled
             if zero_copy_enabled {
                 trace!(
                     uri = %job.media_segment.uri,
-                    "Zero-copy forwarding: unencrypted segment (no key)"
+                    "Zero-copy forwarding: unencrypted segment"
                 );
             }
             raw_data_input
@@ -206,7 +188,9 @@ mod tests {
 
     /// Helper to create a minimal DecryptionService for testing
     fn create_test_decryption_service(config: Arc<HlsConfig>) -> Arc<DecryptionService> {
-        let clients = Arc::new(crate::downloader::create_client_pool(&config.base).unwrap());
+        let clients = Arc::new(
+            crate::downloader::create_client_pool(&config.base, crate::ProtocolType::Hls).unwrap(),
+        );
         let key_fetcher = Arc::new(KeyFetcher::new(
             clients,
             config.clone(),
@@ -269,4 +253,41 @@ mod tests {
         // Data should still be identical even without zero-copy logging
         assert_eq!(input_bytes.as_ref(), output_bytes.as_ref());
     }
+
+    #[tokio::test]
+    async fn test_sample_aes_is_reported_as_unsupported() {
+        let config = Arc::new(HlsConfig::default());
+        let decryption_service = create_test_decryption_service(config.clone());
+        let processor = SegmentProcessor::new(config, decryption_service, None);
+
+        for (method, keyformat) in [
+            (m3u8_rs::KeyMethod::SampleAES, None),
+            (
+                m3u8_rs::KeyMethod::AES128,
+                Some("com.apple.streamingkeydelivery".to_string()),
+            ),
+        ] {
+            let mut job = create_unencrypted_job("https://example.com/segment_1.ts", 1);
+            job.media_segment = Arc::new(MediaSegment {
+                uri: "https://example.com/segment_1.ts".to_string(),
+                key: Some(m3u8_rs::Key {
+                    method,
+                    uri: Some("https://example.com/key".to_string()),
+                    keyformat,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+
+            let err = processor
+                .process_segment_from_job(Bytes::from_static(&[0u8; 32]), &job)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(err, HlsDownloaderError::UnsupportedEncryption { .. }),
+                "unexpected error: {err}"
+            );
+            assert!(!err.is_retryable());
+        }
+    }
 }
@@ -1,12 +1,14 @@
 // HLS Segment Scheduler: Manages the pipeline of segments to be downloaded and processed.
 
 use crate::hls::HlsDownloaderError;
-use crate::hls::config::{BatchSchedulerConfig, HlsConfig};
+use crate::hls::config::{BatchSchedulerConfig, HlsConfig, SegmentFailurePolicy};
 use crate::hls::fetcher::SegmentDownloader;
 use crate::hls::metrics::PerformanceMetrics;
 use crate::hls::prefetch::PrefetchManager;
 use crate::hls::processor::SegmentTransformer;
+use crate::throttle::SegmentWindow;
 use futures::StreamExt;
+use futures::future::{AbortHandle, AbortRegistration, Abortable};
 use futures::stream::FuturesUnordered;
 use hls::HlsData;
 use m3u8_rs::MediaSegment;
@@ -159,6 +161,105 @@ impl BatchScheduler {
     }
 }
 
+/// A segment being downloaded
+struct InFlightSegment {
+    job: ScheduledSegmentJob,
+    started: Instant,
+    /// Downloads of the segment in progress, two once it was reissued or prefetched as well
+    copies: usize,
+    /// Whether only prefetch jobs requested the segment
+    prefetch_only: bool,
+    reissued: bool,
+    abort_handles: Vec<AbortHandle>,
+}
+
+/// Segments being downloaded, oldest first.
+///
+/// A segment can be downloaded by several jobs at once: a prefetch and the job of the
+/// playlist, or the reissue of a stalled segment. The first download to succeed delivers the
+/// segment and aborts the others, a failure is only delivered by the last one.
+#[derive(Default)]
+struct FetchWindow {
+    /// Keyed by media sequence number, init segments before the media segment
+    segments: BTreeMap<(u64, bool), InFlightSegment>,
+}
+
+impl FetchWindow {
+    fn key(msn: u64, is_init_segment: bool) -> (u64, bool) {
+        (msn, !is_init_segment)
+    }
+
+    /// Record a download of `job` starting, returning the registration aborting it
+    fn start(&mut self, job: &ScheduledSegmentJob) -> AbortRegistration {
+        let (handle, registration) = AbortHandle::new_pair();
+        let segment = self
+            .segments
+            .entry(Self::key(job.media_sequence_number, job.is_init_segment))
+            .or_insert_with(|| InFlightSegment {
+                job: job.clone(),
+                started: Instant::now(),
+                copies: 0,
+                prefetch_only: true,
+                reissued: false,
+                abort_handles: Vec::new(),
+            });
+        segment.copies += 1;
+        segment.prefetch_only &= job.is_prefetch;
+        segment.abort_handles.push(handle);
+        registration
+    }
+
+    /// Record a download of a segment ending. Returns whether its result is to be delivered,
+    /// and if so whether only prefetch jobs requested the segment.
+    fn finish(&mut self, msn: u64, is_init_segment: bool, succeeded: bool) -> Option<bool> {
+        let key = Self::key(msn, is_init_segment);
+        // Another download of the segment delivered it already
+        let segment = self.segments.get_mut(&key)?;
+        segment.copies = segment.copies.saturating_sub(1);
+        if !succeeded && segment.copies > 0 {
+            return None;
+        }
+        let segment = self.segments.remove(&key)?;
+        for handle in segment.abort_handles {
+            handle.abort();
+        }
+        Some(segment.prefetch_only)
+    }
+
+    /// Time until the oldest segment has been downloading for `after`, unless it was
+    /// reissued already
+    fn time_until_stalled(&self, after: Duration) -> Option<Duration> {
+        let (_, oldest) = self.segments.first_key_value()?;
+        if oldest.reissued {
+            return None;
+        }
+        Some(after.saturating_sub(oldest.started.elapsed()))
+    }
+
+    /// Mark the oldest segment reissued, returning the job downloading it again
+    fn reissue_oldest(&mut self) -> Option<ScheduledSegmentJob> {
+        let mut oldest = self.segments.first_entry()?;
+        let oldest = oldest.get_mut();
+        if oldest.reissued {
+            return None;
+        }
+        oldest.reissued = true;
+        let mut job = oldest.job.clone();
+        job.is_prefetch = false;
+        Some(job)
+    }
+
+    /// Media sequence numbers of the segments being downloaded
+    fn sequences(&self) -> HashSet<u64> {
+        self.segments.keys().map(|(msn, _)| *msn).collect()
+    }
+
+    /// Number of segments being downloaded
+    fn len(&self) -> usize {
+        self.segments.len()
+    }
+}
+
 #[derive(Debug)]
 pub struct ProcessedSegmentOutput {
     #[allow(dead_code)]
@@ -183,9 +284,12 @@ pub struct SegmentScheduler {
     /// Current buffer size estimate (number of segments in flight + pending)
     buffer_size: usize,
     /// Segments currently in-flight (being downloaded), used to prevent duplicate prefetch
-    in_flight_segments: HashSet<u64>,
+    /// and to reissue stalled segments
+    fetch_window: FetchWindow,
     /// Performance metrics for tracking prefetch operations
     metrics: Option<Arc<PerformanceMetrics>>,
+    /// Occupancy of the window reported with the download progress
+    segment_window: Option<Arc<SegmentWindow>>,
 
     /// Whether any init segment jobs have been observed for this stream.
     /// Used to gate prefetching on fMP4 streams until an init segment is seen.
@@ -218,8 +322,9 @@ impl SegmentScheduler {
             prefetch_manager,
             known_segments: BTreeMap::new(),
             buffer_size: 0,
-            in_flight_segments: HashSet::new(),
+            fetch_window: FetchWindow::default(),
             metrics: None,
+            segment_window: None,
             init_required: false,
             init_seen: false,
         }
@@ -247,6 +352,12 @@ impl SegmentScheduler {
         scheduler
     }
 
+    /// Report the number of segments being downloaded to `window`
+    pub(crate) fn with_segment_window(mut self, window: Arc<SegmentWindow>) -> Self {
+        self.segment_window = Some(window);
+        self
+    }
+
     /// Result of segment processing, including metadata for prefetch tracking
     async fn perform_segment_processing(
         segment_fetcher: Arc<dyn SegmentDownloader>,
@@ -331,7 +442,7 @@ impl SegmentScheduler {
             completed_msn,
             self.buffer_size,
             &known_msns,
-            &self.in_flight_segments,
+            &self.fetch_window.sequences(),
         );
 
         if targets.is_empty() {
@@ -371,7 +482,7 @@ impl SegmentScheduler {
         batch_scheduler: &mut BatchScheduler,
         futures: &mut FuturesUnordered<F>,
         buffer_size: &mut usize,
-        in_flight_segments: &mut HashSet<u64>,
+        fetch_window: &mut FetchWindow,
         segment_fetcher: &Arc<dyn SegmentDownloader>,
         segment_processor: &Arc<dyn SegmentTransformer>,
         max_concurrency: usize,
@@ -398,13 +509,14 @@ impl SegmentScheduler {
                 leftovers.push(job);
                 continue;
             }
-            *buffer_size += 1;
-            in_flight_segments.insert(job.media_sequence_number);
-            futures.push(F::from(Box::pin(Self::perform_segment_processing(
-                Arc::clone(segment_fetcher),
-                Arc::clone(segment_processor),
+            Self::dispatch_single_to_futures(
                 job,
-            ))));
+                futures,
+                buffer_size,
+                fetch_window,
+                segment_fetcher,
+                segment_processor,
+            );
         }
         batch_scheduler.requeue_ready_jobs(leftovers);
     }
@@ -414,7 +526,7 @@ impl SegmentScheduler {
         job: ScheduledSegmentJob,
         futures: &mut FuturesUnordered<F>,
         buffer_size: &mut usize,
-        in_flight_segments: &mut HashSet<u64>,
+        fetch_window: &mut FetchWindow,
         segment_fetcher: &Arc<dyn SegmentDownloader>,
         segment_processor: &Arc<dyn SegmentTransformer>,
     ) where
@@ -434,12 +546,29 @@ impl SegmentScheduler {
         >,
     {
         *buffer_size += 1;
-        in_flight_segments.insert(job.media_sequence_number);
-        futures.push(F::from(Box::pin(Self::perform_segment_processing(
-            Arc::clone(segment_fetcher),
-            Arc::clone(segment_processor),
-            job,
-        ))));
+        let registration = fetch_window.start(&job);
+        let (msn, is_prefetch, is_init_segment) = (
+            job.media_sequence_number,
+            job.is_prefetch,
+            job.is_init_segment,
+        );
+        let processing = Abortable::new(
+            Self::perform_segment_processing(
+                Arc::clone(segment_fetcher),
+                Arc::clone(segment_processor),
+                job,
+            ),
+            registration,
+        );
+        futures.push(F::from(Box::pin(async move {
+            // An aborted download is dropped by the run loop
+            processing.await.unwrap_or((
+                msn,
+                is_prefetch,
+                is_init_segment,
+                Err(HlsDownloaderError::Cancelled),
+            ))
+        })));
     }
 
     pub async fn run(&mut self) {
@@ -475,6 +604,18 @@ impl SegmentScheduler {
                 None
             };
 
+            // The oldest segment gets another download once it holds up a full window
+            let reissue_timeout = match self.config.scheduler_config.stalled_segment_reissue_after {
+                Some(after) if !can_accept_more && !self.token.is_cancelled() => {
+                    self.fetch_window.time_until_stalled(after)
+                }
+                _ => None,
+            };
+
+            if let Some(window) = &self.segment_window {
+                window.set_in_flight(self.fetch_window.len());
+            }
+
             tokio::select! {
                 biased;
 
@@ -492,7 +633,7 @@ impl SegmentScheduler {
                             &mut self.batch_scheduler,
                             &mut futures,
                             &mut self.buffer_size,
-                            &mut self.in_flight_segments,
+                            &mut self.fetch_window,
                             &self.segment_fetcher,
                             &self.segment_processor,
                             self.config.scheduler_config.download_concurrency,
@@ -508,7 +649,7 @@ impl SegmentScheduler {
                             &mut self.batch_scheduler,
                             &mut futures,
                             &mut self.buffer_size,
-                            &mut self.in_flight_segments,
+                            &mut self.fetch_window,
                             &self.segment_fetcher,
                             &self.segment_processor,
                             self.config.scheduler_config.download_concurrency,
@@ -516,7 +657,22 @@ impl SegmentScheduler {
                     }
                 }
 
-                // 3. Receive new segment jobs
+                // 3. Reissue the oldest segment when it stalls the window
+                _ = tokio::time::sleep(reissue_timeout.unwrap_or(Duration::MAX)), if reissue_timeout.is_some() => {
+                    if let Some(job) = self.fetch_window.reissue_oldest() {
+                        debug!(msn = job.media_sequence_number, uri = %job.media_segment.uri, "Oldest segment stalls the download window; requesting it again");
+                        Self::dispatch_single_to_futures(
+                            job,
+                            &mut futures,
+                            &mut self.buffer_size,
+                            &mut self.fetch_window,
+                            &self.segment_fetcher,
+                            &self.segment_processor,
+                        );
+                    }
+                }
+
+                // 4. Receive new segment jobs
                 // This branch is disabled when `draining` is true.
                 maybe_job_request = self.segment_request_rx.recv(), if !draining && can_accept_more => {
                     if let Some(job_request) = maybe_job_request {
@@ -542,7 +698,7 @@ impl SegmentScheduler {
                                     &mut self.batch_scheduler,
                                     &mut futures,
                                     &mut self.buffer_size,
-                                    &mut self.in_flight_segments,
+                                    &mut self.fetch_window,
                                     &self.segment_fetcher,
                                     &self.segment_processor,
                                     self.config.scheduler_config.download_concurrency,
@@ -554,7 +710,7 @@ impl SegmentScheduler {
                                 job_request,
                                 &mut futures,
                                 &mut self.buffer_size,
-                                &mut self.in_flight_segments,
+                                &mut self.fetch_window,
                                 &self.segment_fetcher,
                                 &self.segment_processor,
                             );
@@ -572,7 +728,7 @@ impl SegmentScheduler {
                                 &mut self.batch_scheduler,
                                 &mut futures,
                                 &mut self.buffer_size,
-                                &mut self.in_flight_segments,
+                                &mut self.fetch_window,
                                 &self.segment_fetcher,
                                 &self.segment_processor,
                                 self.config.scheduler_config.download_concurrency,
@@ -581,7 +737,7 @@ impl SegmentScheduler {
                     }
                 }
 
-                // 4. Handle completed futures
+                // 5. Handle completed futures
                 // This branch remains active during draining to finish in-progress work.
                 Some((completed_msn, is_prefetch, is_init_segment, processed_result)) = futures.next() => {
                     // Update buffer size
@@ -589,8 +745,24 @@ impl SegmentScheduler {
                         self.buffer_size -= 1;
                     }
 
-                    // Remove segment from in-flight tracking
-                    self.in_flight_segments.remove(&completed_msn);
+                    // Aborted downloads were superseded, and the others only end cancelled
+                    // when the scheduler shuts down
+                    if matches!(processed_result, Err(HlsDownloaderError::Cancelled)) {
+                        trace!(msn = completed_msn, "Segment download cancelled.");
+                        continue;
+                    }
+
+                    // Remove segment from in-flight tracking. Only the first download of a
+                    // segment to succeed is delivered, and a failure only once no other
+                    // download of the segment is left.
+                    let Some(prefetch_only) = self.fetch_window.finish(
+                        completed_msn,
+                        is_init_segment,
+                        processed_result.is_ok(),
+                    ) else {
+                        trace!(msn = completed_msn, "Dropping superseded segment download.");
+                        continue;
+                    };
 
                     // Mark segment as completed in prefetch manager
                     if prefetch_enabled {
@@ -627,7 +799,7 @@ impl SegmentScheduler {
                                             prefetch_job,
                                             &mut futures,
                                             &mut self.buffer_size,
-                                            &mut self.in_flight_segments,
+                                            &mut self.fetch_window,
                                             &self.segment_fetcher,
                                             &self.segment_processor,
                                         );
@@ -641,21 +813,36 @@ impl SegmentScheduler {
                             }
                         }
                         Err(e) => {
-                            // Check if the error is a SegmentFetchError (e.g. 404)
-                            // We should not abort the stream for a single missing segment
-                            let should_ignore = matches!(e, HlsDownloaderError::SegmentFetch { .. });
+                            // Check if the segment was refused (e.g. 404) or served wrong
+                            let refused = matches!(
+                                &e,
+                                HlsDownloaderError::SegmentFailed { source, .. }
+                                    if matches!(
+                                        **source,
+                                        HlsDownloaderError::Http { .. }
+                                            | HlsDownloaderError::Protocol { .. }
+                                    )
+                            );
+                            // A failed media segment is forwarded for the output manager to
+                            // skip or abort on, as the failure policy says. A failed init
+                            // segment leaves no gap to mark, so it is only forwarded to abort.
+                            let emit_gap = self.config.scheduler_config.failure_policy
+                                == SegmentFailurePolicy::EmitGap;
+                            let failed_download =
+                                matches!(&e, HlsDownloaderError::SegmentFailed { .. });
+                            let should_ignore =
+                                is_init_segment && failed_download && (refused || emit_gap);
 
                             warn!(
                                 error = %e,
                                 msn = completed_msn,
-                                is_prefetch = is_prefetch,
+                                is_prefetch = prefetch_only,
                                 ignored = should_ignore,
                                 "Segment processing task failed."
                             );
 
                             // Don't propagate prefetch errors - they're opportunistic.
-                            // Also don't propagate SegmentFetchErrors - treat them as gaps.
-                            if !is_prefetch && !should_ignore
+                            if !prefetch_only && !should_ignore
                                 && self.output_tx.send(Err(e)).await.is_err() {
                                     error!("Output channel closed while sending segment-processing error. Shutting down scheduler.");
                                     break;
@@ -664,7 +851,7 @@ impl SegmentScheduler {
                     }
                 }
 
-                // 5. Shutdown condition
+                // 6. Shutdown condition
                 // This `else` branch is taken when all other branches are disabled.
                 // This happens when:
                 //  - `draining` is true, so `recv()` is disabled.
@@ -849,4 +1036,73 @@ mod tests {
         let scheduler = BatchScheduler::new(config);
         assert!(!scheduler.is_enabled());
     }
+
+    /// Whether the download started with `registration` was aborted
+    fn is_aborted(registration: AbortRegistration) -> bool {
+        use futures::FutureExt;
+        Abortable::new(std::future::pending::<()>(), registration)
+            .now_or_never()
+            .is_some()
+    }
+
+    #[test]
+    fn test_fetch_window_delivers_the_first_success_once() {
+        let mut window = FetchWindow::default();
+        let prefetch = window.start(&create_test_job_with_flags(4, false, true));
+        let playlist = window.start(&create_test_job(4));
+        assert_eq!(window.len(), 1);
+        assert_eq!(window.sequences(), HashSet::from([4]));
+
+        // A failure is held back while another download of the segment is running
+        assert_eq!(window.finish(4, false, false), None);
+        assert_eq!(window.finish(4, false, true), Some(false));
+        assert_eq!(window.len(), 0);
+        assert!(is_aborted(prefetch) && is_aborted(playlist));
+
+        // The other downloads are aborted, and a result they still deliver is dropped
+        let first = window.start(&create_test_job(5));
+        let second = window.start(&create_test_job(5));
+        assert!(!is_aborted(first));
+        assert_eq!(window.finish(5, false, true), Some(false));
+        assert!(is_aborted(second));
+        assert_eq!(window.finish(5, false, true), None);
+    }
+
+    #[test]
+    fn test_fetch_window_failure_of_prefetch_only_segment() {
+        let mut window = FetchWindow::default();
+        let _registration = window.start(&create_test_job_with_flags(7, false, true));
+        assert_eq!(window.finish(7, false, false), Some(true));
+    }
+
+    #[test]
+    fn test_fetch_window_reissues_the_oldest_segment_once() {
+        let mut window = FetchWindow::default();
+        let _registrations = [
+            window.start(&create_test_job(3)),
+            window.start(&create_test_job(2)),
+            window.start(&create_test_job_with_flags(2, true, false)),
+        ];
+
+        let after = Duration::from_secs(60);
+        let remaining = window.time_until_stalled(after).unwrap();
+        assert!(remaining > Duration::ZERO && remaining <= after);
+        assert_eq!(
+            window.time_until_stalled(Duration::ZERO),
+            Some(Duration::ZERO)
+        );
+
+        // The init segment of the oldest sequence comes first
+        let job = window.reissue_oldest().unwrap();
+        assert_eq!(job.media_sequence_number, 2);
+        assert!(job.is_init_segment);
+        assert_eq!(window.time_until_stalled(Duration::ZERO), None);
+        assert!(window.reissue_oldest().is_none());
+
+        // The next segment can be reissued once the oldest is delivered
+        assert_eq!(window.finish(2, true, true), Some(false));
+        let job = window.reissue_oldest().unwrap();
+        assert_eq!(job.media_sequence_number, 2);
+        assert!(!job.is_init_segment);
+    }
 }
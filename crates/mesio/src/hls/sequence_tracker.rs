@@ -0,0 +1,316 @@
+// HLS Sequence Tracker: De-duplicates segments across playlist reloads and keeps job numbering
+// monotonic when the origin resets its media sequence number.
+//
+// Segments are keyed by their media sequence number and a hash of their resolved URI with
+// volatile query parameters (tokens, signatures, expiry timestamps) removed, so that a segment
+// listed again with a refreshed signature is recognized. A sliding window of recent keys bounds
+// the memory used.
+//
+// The output manager orders segments by job sequence number and rejects numbers it has already
+// passed. When the playlist restarts from a much lower media sequence number, jobs keep being
+// numbered after the last delivered one and the first of them is flagged as a discontinuity.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use tracing::{debug, warn};
+use url::Url;
+
+/// A segment accepted for download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Admitted {
+    /// Sequence number of the download job
+    pub sequence: u64,
+    /// First segment after a media sequence reset
+    pub discontinuity: bool,
+}
+
+/// Where a segment was last seen
+#[derive(Debug, Clone, Copy)]
+struct SeenSegment {
+    msn: u64,
+    /// Hash of the URI including its volatile query parameters
+    raw_hash: u64,
+}
+
+pub(super) struct SequenceTracker {
+    /// Minimum number of segment keys remembered
+    window: usize,
+    /// Names of the query parameters ignored when comparing URIs
+    volatile_params: Vec<String>,
+    /// Keys in insertion order, `(media sequence number, URI hash)`
+    recent: VecDeque<(u64, u64)>,
+    seen: HashMap<u64, SeenSegment>,
+    /// Keys remembered, at least `window` and enough for the largest playlist seen
+    capacity: usize,
+    /// Added to media sequence numbers to number jobs, grows on each reset
+    offset: u64,
+    last_msn: Option<u64>,
+    last_sequence: Option<u64>,
+    /// Sequence number of the first job after a reset, not yet admitted
+    reset_sequence: Option<u64>,
+    duplicates_skipped: u64,
+    resets_detected: u64,
+}
+
+impl SequenceTracker {
+    pub(super) fn new(window: usize, volatile_params: &[String]) -> Self {
+        let window = window.max(1);
+        Self {
+            window,
+            volatile_params: volatile_params.to_vec(),
+            recent: VecDeque::with_capacity(window),
+            seen: HashMap::with_capacity(window),
+            capacity: window,
+            offset: 0,
+            last_msn: None,
+            last_sequence: None,
+            reset_sequence: None,
+            duplicates_skipped: 0,
+            resets_detected: 0,
+        }
+    }
+
+    /// Start processing a reloaded playlist. Returns true if its media sequence number was
+    /// reset.
+    pub(super) fn begin_playlist(&mut self, media_sequence: u64, segment_count: usize) -> bool {
+        // Never forget segments of the current playlist, they would be downloaded again
+        self.capacity = self.window.max(segment_count.saturating_mul(2));
+
+        let (Some(last_msn), Some(last_sequence)) = (self.last_msn, self.last_sequence) else {
+            return false;
+        };
+        if media_sequence.saturating_add(self.window as u64) >= last_msn {
+            return false;
+        }
+
+        warn!(
+            last_msn,
+            media_sequence, "Media sequence number reset, continuing after the last segment"
+        );
+        self.last_msn = None;
+        self.reset_sequence = Some(last_sequence + 1);
+        self.resets_detected += 1;
+        true
+    }
+
+    /// Whether a segment was already downloaded, counting it if it was listed under another
+    /// media sequence number or with different volatile parameters.
+    pub(super) fn is_duplicate(&mut self, msn: u64, identity: &str) -> bool {
+        let Some(seen) = self.seen.get(&self.key_hash(identity)) else {
+            return false;
+        };
+        if seen.msn != msn || seen.raw_hash != hash_str(identity) {
+            debug!(
+                msn,
+                previous_msn = seen.msn,
+                identity,
+                "Skipping duplicate segment"
+            );
+            self.duplicates_skipped += 1;
+        }
+        true
+    }
+
+    /// Record a segment to download and assign its job sequence number
+    pub(super) fn admit(&mut self, msn: u64, identity: &str) -> Admitted {
+        let key_hash = self.key_hash(identity);
+        self.seen.insert(
+            key_hash,
+            SeenSegment {
+                msn,
+                raw_hash: hash_str(identity),
+            },
+        );
+        self.recent.push_back((msn, key_hash));
+        while self.recent.len() > self.capacity {
+            if let Some((old_msn, old_hash)) = self.recent.pop_front()
+                && self
+                    .seen
+                    .get(&old_hash)
+                    .is_some_and(|seen| seen.msn == old_msn)
+            {
+                self.seen.remove(&old_hash);
+            }
+        }
+
+        // Number the first new segment after a reset right after the last job
+        let discontinuity = match self.reset_sequence.take() {
+            Some(reset_sequence) => {
+                self.offset = reset_sequence.saturating_sub(msn);
+                true
+            }
+            None => false,
+        };
+        let sequence = msn + self.offset;
+        self.last_msn = Some(self.last_msn.map_or(msn, |last| last.max(msn)));
+        self.last_sequence = Some(
+            self.last_sequence
+                .map_or(sequence, |last| last.max(sequence)),
+        );
+        Admitted {
+            sequence,
+            discontinuity,
+        }
+    }
+
+    /// Highest media sequence number admitted since the last reset
+    pub(super) fn last_msn(&self) -> Option<u64> {
+        self.last_msn
+    }
+
+    /// Sequence number following the last admitted job
+    pub(super) fn next_sequence(&self) -> Option<u64> {
+        self.last_sequence.map(|last| last + 1)
+    }
+
+    pub(super) fn duplicates_skipped(&self) -> u64 {
+        self.duplicates_skipped
+    }
+
+    pub(super) fn resets_detected(&self) -> u64 {
+        self.resets_detected
+    }
+
+    /// Hash of a segment identity with volatile query parameters removed from its URI
+    fn key_hash(&self, identity: &str) -> u64 {
+        // Byte-range segments carry their range after the URI
+        let (uri, range) = identity
+            .split_once('|')
+            .map_or((identity, None), |(uri, range)| (uri, Some(range)));
+
+        let mut hasher = DefaultHasher::new();
+        match Url::parse(uri) {
+            Ok(mut url) if url.query().is_some() && !self.volatile_params.is_empty() => {
+                let kept: Vec<(String, String)> = url
+                    .query_pairs()
+                    .filter(|(name, _)| {
+                        !self
+                            .volatile_params
+                            .iter()
+                            .any(|param| name.eq_ignore_ascii_case(param))
+                    })
+                    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+                    .collect();
+                if kept.is_empty() {
+                    url.set_query(None);
+                } else {
+                    url.query_pairs_mut().clear().extend_pairs(kept);
+                }
+                url.as_str().hash(&mut hasher);
+            }
+            _ => uri.hash(&mut hasher),
+        }
+        range.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+fn hash_str(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker(window: usize) -> SequenceTracker {
+        SequenceTracker::new(window, &["token".to_string(), "expires".to_string()])
+    }
+
+    /// Process one playlist reload, returning the admitted `(msn, sequence, discontinuity)`
+    fn reload(
+        tracker: &mut SequenceTracker,
+        media_sequence: u64,
+        uris: &[&str],
+    ) -> Vec<(u64, u64, bool)> {
+        tracker.begin_playlist(media_sequence, uris.len());
+        uris.iter()
+            .enumerate()
+            .filter_map(|(idx, uri)| {
+                let msn = media_sequence + idx as u64;
+                (!tracker.is_duplicate(msn, uri)).then(|| {
+                    let admitted = tracker.admit(msn, uri);
+                    (msn, admitted.sequence, admitted.discontinuity)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_overlapping_reloads_are_not_counted_as_duplicates() {
+        let mut tracker = tracker(10);
+        let first = reload(&mut tracker, 5, &["https://a/5.ts", "https://a/6.ts"]);
+        assert_eq!(first, vec![(5, 5, false), (6, 6, false)]);
+
+        let second = reload(&mut tracker, 6, &["https://a/6.ts", "https://a/7.ts"]);
+        assert_eq!(second, vec![(7, 7, false)]);
+        assert_eq!(tracker.duplicates_skipped(), 0);
+    }
+
+    #[test]
+    fn test_duplicates_with_refreshed_tokens_are_skipped() {
+        let mut tracker = tracker(10);
+        reload(
+            &mut tracker,
+            0,
+            &["https://a/0.ts?token=aaa&q=1", "https://a/1.ts?token=aaa"],
+        );
+
+        // Same segments re-signed, then a segment listed again under another number
+        let admitted = reload(
+            &mut tracker,
+            0,
+            &[
+                "https://a/0.ts?q=1&token=bbb",
+                "https://a/1.ts?expires=99&token=bbb",
+                "https://a/0.ts?token=ccc&q=1",
+                "https://a/3.ts?token=bbb",
+            ],
+        );
+        assert_eq!(admitted, vec![(3, 3, false)]);
+        assert_eq!(tracker.duplicates_skipped(), 3);
+
+        // Non-volatile parameters still distinguish segments
+        let admitted = reload(&mut tracker, 4, &["https://a/0.ts?q=2&token=aaa"]);
+        assert_eq!(admitted, vec![(4, 4, false)]);
+    }
+
+    #[test]
+    fn test_sequence_reset_continues_numbering_with_discontinuity() {
+        let mut tracker = tracker(5);
+        reload(&mut tracker, 100, &["https://a/100.ts", "https://a/101.ts"]);
+
+        // A reload from a lagging cache is not a reset
+        assert!(reload(&mut tracker, 98, &["https://a/100.ts"]).is_empty());
+        assert_eq!(tracker.resets_detected(), 0);
+
+        // The origin restarts from 0; a segment it lists again is not downloaded twice
+        let admitted = reload(
+            &mut tracker,
+            0,
+            &["https://a/101.ts", "https://b/0.ts", "https://b/1.ts"],
+        );
+        assert_eq!(admitted, vec![(1, 102, true), (2, 103, false)]);
+        assert_eq!(tracker.resets_detected(), 1);
+        assert_eq!(tracker.last_msn(), Some(2));
+        assert_eq!(tracker.next_sequence(), Some(104));
+
+        // Numbering carries on across later reloads
+        let admitted = reload(&mut tracker, 2, &["https://b/1.ts", "https://b/2.ts"]);
+        assert_eq!(admitted, vec![(3, 104, false)]);
+        assert_eq!(tracker.resets_detected(), 1);
+    }
+
+    #[test]
+    fn test_window_keeps_every_segment_of_large_playlists() {
+        let mut tracker = tracker(2);
+        let uris: Vec<String> = (0..6).map(|i| format!("https://a/{i}.ts")).collect();
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+
+        assert_eq!(reload(&mut tracker, 0, &uris).len(), 6);
+        assert!(reload(&mut tracker, 0, &uris).is_empty());
+    }
+}
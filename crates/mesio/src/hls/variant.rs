@@ -0,0 +1,609 @@
+// HLS Variant Selection: picks the media playlist of a master playlist to download, and the
+// alternate audio rendition to download along with it.
+
+use std::fmt;
+use std::sync::Arc;
+
+use hls::RenditionGroup;
+use m3u8_rs::VariantStream;
+
+use crate::hls::config::{HlsPlaylistConfig, HlsVariantSelectionPolicy};
+
+/// Codecs of audio renditions, as they appear in the `CODECS` attribute
+const AUDIO_CODECS: &[&str] = &["mp4a", "ac-3", "ec-3", "opus", "flac", "mp3", "alac"];
+
+/// A variant picked by a [`VariantSelector`], or a rendition picked by an
+/// [`AudioRenditionSelection`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantChoice {
+    /// Position of the variant in the candidates passed to the selector, or of the
+    /// rendition in its group
+    pub index: usize,
+    /// Why it was picked, for logs and progress events
+    pub reason: String,
+}
+
+impl VariantChoice {
+    pub fn new(index: usize, reason: impl Into<String>) -> Self {
+        Self {
+            index,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Strategy picking the variant of a master playlist to download.
+///
+/// The candidates exclude I-frame-only and audio-only variants unless the playlist
+/// configuration asks for them, and variants whose media playlist turned out to be missing.
+pub trait VariantSelector: Send + Sync + fmt::Debug {
+    /// Pick one of `variants`, or `None` when none is acceptable
+    fn select(&self, variants: &[&VariantStream]) -> Option<VariantChoice>;
+}
+
+/// The variant with the highest `BANDWIDTH`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HighestBandwidth;
+
+impl VariantSelector for HighestBandwidth {
+    fn select(&self, variants: &[&VariantStream]) -> Option<VariantChoice> {
+        highest_bandwidth(variants, |_| true)
+            .map(|index| VariantChoice::new(index, "highest bandwidth"))
+    }
+}
+
+/// The variant whose `RESOLUTION` is closest to a target, by pixel count. Ties go to the
+/// higher bandwidth; without any `RESOLUTION` the highest bandwidth is picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClosestResolution {
+    pub width: u64,
+    pub height: u64,
+}
+
+impl ClosestResolution {
+    pub fn new(width: u64, height: u64) -> Self {
+        Self { width, height }
+    }
+}
+
+impl VariantSelector for ClosestResolution {
+    fn select(&self, variants: &[&VariantStream]) -> Option<VariantChoice> {
+        let target = self.width * self.height;
+        let closest = variants
+            .iter()
+            .enumerate()
+            .filter_map(|(index, variant)| {
+                let resolution = variant.resolution?;
+                let distance = (resolution.width * resolution.height).abs_diff(target);
+                Some((index, distance, variant.bandwidth))
+            })
+            .min_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)));
+
+        match closest {
+            Some((index, _, _)) => {
+                let resolution = variants[index].resolution?;
+                Some(VariantChoice::new(
+                    index,
+                    format!(
+                        "resolution {}x{} closest to {}x{}",
+                        resolution.width, resolution.height, self.width, self.height
+                    ),
+                ))
+            }
+            None => highest_bandwidth(variants, |_| true).map(|index| {
+                VariantChoice::new(index, "no RESOLUTION advertised, highest bandwidth")
+            }),
+        }
+    }
+}
+
+/// The highest bandwidth variant of the first codec of an ordered list that any variant
+/// uses, e.g. `["hvc1", "avc1"]`. Codecs match by prefix, so `avc1` matches `avc1.64001f`.
+/// Without any listed codec the highest bandwidth is picked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecPreference {
+    pub codecs: Vec<String>,
+}
+
+impl CodecPreference {
+    pub fn new<I, S>(codecs: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            codecs: codecs.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl VariantSelector for CodecPreference {
+    fn select(&self, variants: &[&VariantStream]) -> Option<VariantChoice> {
+        for codec in &self.codecs {
+            if let Some(index) = highest_bandwidth(variants, |variant| uses_codec(variant, codec)) {
+                return Some(VariantChoice::new(
+                    index,
+                    format!("preferred codec {codec}"),
+                ));
+            }
+        }
+        highest_bandwidth(variants, |_| true).map(|index| {
+            VariantChoice::new(
+                index,
+                format!(
+                    "none of the codecs {} offered, highest bandwidth",
+                    self.codecs.join(", ")
+                ),
+            )
+        })
+    }
+}
+
+type CustomFn = dyn Fn(&[&VariantStream]) -> Option<usize> + Send + Sync;
+
+/// A selector backed by a function returning the index of the chosen variant
+#[derive(Clone)]
+pub struct Custom(Arc<CustomFn>);
+
+impl Custom {
+    pub fn new(
+        select: impl Fn(&[&VariantStream]) -> Option<usize> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(select))
+    }
+}
+
+impl fmt::Debug for Custom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Custom")
+    }
+}
+
+impl VariantSelector for Custom {
+    fn select(&self, variants: &[&VariantStream]) -> Option<VariantChoice> {
+        (self.0)(variants)
+            .filter(|index| *index < variants.len())
+            .map(|index| VariantChoice::new(index, "custom selector"))
+    }
+}
+
+impl VariantSelector for HlsVariantSelectionPolicy {
+    fn select(&self, variants: &[&VariantStream]) -> Option<VariantChoice> {
+        let find = |reason: &str, matches: &dyn Fn(&VariantStream) -> bool| {
+            variants
+                .iter()
+                .position(|variant| matches(variant))
+                .map(|index| VariantChoice::new(index, reason))
+        };
+        match self {
+            Self::HighestBitrate => HighestBandwidth.select(variants),
+            Self::LowestBitrate => variants
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, variant)| variant.bandwidth)
+                .map(|(index, _)| VariantChoice::new(index, "lowest bandwidth")),
+            Self::ClosestToBitrate(target) => variants
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, variant)| variant.bandwidth.abs_diff(*target))
+                .map(|(index, _)| {
+                    VariantChoice::new(index, format!("bandwidth closest to {target}"))
+                }),
+            Self::AudioOnly => find("audio only", &|variant| {
+                variant.audio.is_some()
+                    && variant.video.is_none()
+                    && variant.codecs.as_ref().is_some_and(|c| c.contains("mp4a"))
+            }),
+            Self::VideoOnly => find("video only", &|variant| {
+                variant.video.is_some() && variant.audio.is_none()
+            }),
+            Self::MatchingResolution { width, height } => {
+                find(&format!("resolution {width}x{height}"), &|variant| {
+                    variant.resolution.is_some_and(|r| {
+                        r.width == u64::from(*width) && r.height == u64::from(*height)
+                    })
+                })
+            }
+            Self::Selector(selector) => selector.select(variants),
+            Self::Custom(name) => {
+                tracing::warn!("Custom policy '{name}' selected; falling back to first variant.");
+                (!variants.is_empty()).then(|| VariantChoice::new(0, "first variant"))
+            }
+        }
+    }
+}
+
+/// Alternate audio rendition to download along with the selected variant.
+///
+/// The rendition is picked from the audio group of the variant: by `NAME` when a name
+/// is set, else by language. When the group offers no such rendition, its default
+/// rendition is picked instead, with a warning.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AudioRenditionSelection {
+    /// Language tag such as `en`, which also matches regional tags such as `en-US`
+    pub language: Option<String>,
+    /// `NAME` of the rendition, ignoring case
+    pub name: Option<String>,
+}
+
+impl AudioRenditionSelection {
+    /// The rendition in `language`
+    pub fn language(language: impl Into<String>) -> Self {
+        Self {
+            language: Some(language.into()),
+            name: None,
+        }
+    }
+
+    /// The rendition named `name`
+    pub fn name(name: impl Into<String>) -> Self {
+        Self {
+            language: None,
+            name: Some(name.into()),
+        }
+    }
+
+    /// Pick a rendition of `group`, `None` when the group is empty
+    pub fn select(&self, group: &RenditionGroup) -> Option<VariantChoice> {
+        let renditions = &group.renditions;
+        if let Some(name) = &self.name {
+            if let Some(index) = renditions
+                .iter()
+                .position(|rendition| rendition.name.eq_ignore_ascii_case(name))
+            {
+                return Some(VariantChoice::new(index, format!("name {name}")));
+            }
+        } else if let Some(language) = &self.language {
+            // The default rendition of the language, e.g. stereo over surround
+            let matching = || {
+                renditions
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rendition)| rendition.matches_language(language))
+            };
+            if let Some((index, _)) = matching()
+                .find(|(_, rendition)| rendition.default)
+                .or_else(|| matching().next())
+            {
+                return Some(VariantChoice::new(index, format!("language {language}")));
+            }
+        }
+
+        let default = group.default_rendition()?;
+        let index = renditions
+            .iter()
+            .position(|rendition| rendition == default)?;
+        match self.name.as_ref().or(self.language.as_ref()) {
+            Some(requested) => {
+                tracing::warn!(
+                    group = %group.group_id,
+                    requested = %requested,
+                    fallback = %default,
+                    "Requested audio rendition not offered, using the default one"
+                );
+                Some(VariantChoice::new(
+                    index,
+                    format!("{requested} not offered, default rendition"),
+                ))
+            }
+            None => Some(VariantChoice::new(index, "default rendition")),
+        }
+    }
+}
+
+/// The variants of a master playlist `policy` may pick from
+pub(crate) fn selectable_variants<'a>(
+    variants: &'a [VariantStream],
+    policy: &HlsVariantSelectionPolicy,
+    config: &HlsPlaylistConfig,
+) -> Vec<&'a VariantStream> {
+    let include_audio_only = config.include_audio_only_variants
+        || matches!(policy, HlsVariantSelectionPolicy::AudioOnly);
+    variants
+        .iter()
+        .filter(|variant| config.include_i_frame_variants || !variant.is_i_frame)
+        .filter(|variant| include_audio_only || !is_audio_only(variant))
+        .collect()
+}
+
+/// Whether a variant carries audio only: no `RESOLUTION` and only audio `CODECS`.
+/// Variants without `CODECS` are assumed to carry video.
+fn is_audio_only(variant: &VariantStream) -> bool {
+    if variant.resolution.is_some() {
+        return false;
+    }
+    let Some(codecs) = &variant.codecs else {
+        return false;
+    };
+    codecs.split(',').map(str::trim).all(|codec| {
+        AUDIO_CODECS
+            .iter()
+            .any(|audio| codec.to_ascii_lowercase().starts_with(audio))
+    })
+}
+
+fn uses_codec(variant: &VariantStream, codec: &str) -> bool {
+    variant.codecs.as_deref().is_some_and(|codecs| {
+        codecs.split(',').any(|entry| {
+            entry
+                .trim()
+                .to_ascii_lowercase()
+                .starts_with(&codec.to_ascii_lowercase())
+        })
+    })
+}
+
+/// Index of the highest bandwidth variant matching `filter`
+fn highest_bandwidth(
+    variants: &[&VariantStream],
+    filter: impl Fn(&VariantStream) -> bool,
+) -> Option<usize> {
+    variants
+        .iter()
+        .enumerate()
+        .filter(|(_, variant)| filter(variant))
+        .max_by_key(|(_, variant)| variant.bandwidth)
+        .map(|(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mixed AVC and HEVC ladder, with an audio-only rendition and an I-frame playlist
+    const MIXED_CODECS: &str = r#"#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=6000000,RESOLUTION=1920x1080,CODECS="hvc1.1.6.L120.90,mp4a.40.2"
+hevc_1080.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=8000000,RESOLUTION=1920x1080,CODECS="avc1.640028,mp4a.40.2"
+avc_1080.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=3000000,RESOLUTION=1280x720,CODECS="avc1.64001f,mp4a.40.2"
+avc_720.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1280x720,CODECS="hvc1.1.6.L93.90,mp4a.40.2"
+hevc_720.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS="mp4a.40.2"
+audio.m3u8
+#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=9000000,RESOLUTION=1920x1080,CODECS="avc1.640028",URI="iframe.m3u8"
+"#;
+
+    /// Ladder whose variants do not advertise RESOLUTION or CODECS
+    const MISSING_RESOLUTION: &str = r#"#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=1500000
+mid.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=4500000
+high.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360
+low.m3u8
+"#;
+
+    fn master(input: &str) -> Vec<VariantStream> {
+        m3u8_rs::parse_master_playlist_res(input.as_bytes())
+            .expect("valid master playlist")
+            .variants
+    }
+
+    fn select(
+        input: &str,
+        policy: HlsVariantSelectionPolicy,
+        config: &HlsPlaylistConfig,
+    ) -> Option<(String, String)> {
+        let variants = master(input);
+        let candidates = selectable_variants(&variants, &policy, config);
+        let choice = policy.select(&candidates)?;
+        Some((candidates[choice.index].uri.clone(), choice.reason))
+    }
+
+    fn selector(selector: impl VariantSelector + 'static) -> HlsVariantSelectionPolicy {
+        HlsVariantSelectionPolicy::Selector(Arc::new(selector))
+    }
+
+    #[test]
+    fn test_excludes_audio_only_and_i_frame_variants() {
+        let config = HlsPlaylistConfig::default();
+        let variants = master(MIXED_CODECS);
+        assert_eq!(variants.len(), 6);
+
+        let candidates = selectable_variants(
+            &variants,
+            &HlsVariantSelectionPolicy::HighestBitrate,
+            &config,
+        );
+        let uris: Vec<_> = candidates.iter().map(|v| v.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            [
+                "hevc_1080.m3u8",
+                "avc_1080.m3u8",
+                "avc_720.m3u8",
+                "hevc_720.m3u8"
+            ]
+        );
+
+        let (uri, _) = select(MIXED_CODECS, selector(HighestBandwidth), &config).unwrap();
+        assert_eq!(uri, "avc_1080.m3u8");
+        let (uri, _) = select(
+            MIXED_CODECS,
+            HlsVariantSelectionPolicy::LowestBitrate,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "hevc_720.m3u8");
+    }
+
+    #[test]
+    fn test_includes_excluded_variants_on_request() {
+        let config = HlsPlaylistConfig {
+            include_audio_only_variants: true,
+            include_i_frame_variants: true,
+            ..Default::default()
+        };
+        let (uri, _) = select(MIXED_CODECS, selector(HighestBandwidth), &config).unwrap();
+        assert_eq!(uri, "iframe.m3u8");
+        let (uri, _) = select(
+            MIXED_CODECS,
+            HlsVariantSelectionPolicy::LowestBitrate,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "audio.m3u8");
+
+        // The audio-only policy asks for them by itself
+        let (uri, _) = select(
+            "#EXTM3U\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=1280x720,CODECS=\"avc1.64001f\"\n\
+             video.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp4a.40.2\",AUDIO=\"aac\"\n\
+             audio.m3u8\n",
+            HlsVariantSelectionPolicy::AudioOnly,
+            &HlsPlaylistConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(uri, "audio.m3u8");
+    }
+
+    #[test]
+    fn test_codec_preference() {
+        let config = HlsPlaylistConfig::default();
+
+        let (uri, reason) = select(
+            MIXED_CODECS,
+            selector(CodecPreference::new(["hvc1", "avc1"])),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "hevc_1080.m3u8");
+        assert_eq!(reason, "preferred codec hvc1");
+
+        let (uri, _) = select(
+            MIXED_CODECS,
+            selector(CodecPreference::new(["AV01", "avc1"])),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "avc_1080.m3u8");
+
+        // Falls back to the highest bandwidth, also without CODECS
+        let (uri, reason) = select(
+            MISSING_RESOLUTION,
+            selector(CodecPreference::new(["hvc1"])),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "high.m3u8");
+        assert!(reason.contains("highest bandwidth"), "{reason}");
+    }
+
+    #[test]
+    fn test_closest_resolution() {
+        let config = HlsPlaylistConfig::default();
+
+        // Ties between codecs go to the higher bandwidth
+        let (uri, reason) = select(
+            MIXED_CODECS,
+            selector(ClosestResolution::new(1280, 720)),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "avc_720.m3u8");
+        assert_eq!(reason, "resolution 1280x720 closest to 1280x720");
+
+        let (uri, _) = select(
+            MIXED_CODECS,
+            selector(ClosestResolution::new(2560, 1440)),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "avc_1080.m3u8");
+
+        // Variants without RESOLUTION are only picked when none has one
+        let (uri, _) = select(
+            MISSING_RESOLUTION,
+            selector(ClosestResolution::new(1920, 1080)),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "low.m3u8");
+        let (uri, reason) = select(
+            &MISSING_RESOLUTION.replace(",RESOLUTION=640x360", ""),
+            selector(ClosestResolution::new(1920, 1080)),
+            &config,
+        )
+        .unwrap();
+        assert_eq!(uri, "high.m3u8");
+        assert!(reason.contains("no RESOLUTION"), "{reason}");
+    }
+
+    #[test]
+    fn test_custom_selector() {
+        let config = HlsPlaylistConfig::default();
+        let second_lowest = Custom::new(|variants| {
+            let mut order: Vec<_> = (0..variants.len()).collect();
+            order.sort_by_key(|&index| variants[index].bandwidth);
+            order.get(1).copied()
+        });
+        let (uri, reason) = select(MISSING_RESOLUTION, selector(second_lowest), &config).unwrap();
+        assert_eq!(uri, "mid.m3u8");
+        assert_eq!(reason, "custom selector");
+
+        let out_of_range = Custom::new(|variants| Some(variants.len()));
+        assert_eq!(
+            select(MISSING_RESOLUTION, selector(out_of_range), &config),
+            None
+        );
+    }
+
+    /// Two audio groups: the low group offers English by default and Spanish, the high
+    /// group English and Spanish by default in surround, and a stereo Spanish track
+    const MULTI_AUDIO: &str = r#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-lo",NAME="English",LANGUAGE="en",DEFAULT=YES,AUTOSELECT=YES,URI="audio/lo/en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-lo",NAME="Spanish",LANGUAGE="es",AUTOSELECT=YES,URI="audio/lo/es.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-hi",NAME="English",LANGUAGE="en-US",AUTOSELECT=YES,URI="audio/hi/en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-hi",NAME="Spanish 5.1",LANGUAGE="es",DEFAULT=YES,AUTOSELECT=YES,CHANNELS="6",URI="audio/hi/es-51.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac-hi",NAME="Spanish",LANGUAGE="es",AUTOSELECT=YES,URI="audio/hi/es.m3u8"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=1280x720,AUDIO="aac-lo"
+video/720.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,AUDIO="aac-hi"
+video/1080.m3u8
+"#;
+
+    /// URI of the rendition `selection` picks for the variant `variant`, and why
+    fn select_audio(variant: usize, selection: AudioRenditionSelection) -> (String, String) {
+        let master = m3u8_rs::parse_master_playlist_res(MULTI_AUDIO.as_bytes()).unwrap();
+        let groups = hls::rendition::rendition_groups(&master);
+        let group = hls::rendition::audio_group(&groups, &master.variants[variant]).unwrap();
+        let choice = selection.select(group).unwrap();
+        let uri = group.renditions[choice.index].uri.clone().unwrap();
+        (uri, choice.reason)
+    }
+
+    #[test]
+    fn test_audio_rendition_by_language() {
+        let (uri, reason) = select_audio(0, AudioRenditionSelection::language("es"));
+        assert_eq!(uri, "audio/lo/es.m3u8");
+        assert_eq!(reason, "language es");
+
+        // Regional tags match the language, and the default rendition of the language wins
+        let (uri, _) = select_audio(1, AudioRenditionSelection::language("en"));
+        assert_eq!(uri, "audio/hi/en.m3u8");
+        let (uri, _) = select_audio(1, AudioRenditionSelection::language("ES"));
+        assert_eq!(uri, "audio/hi/es-51.m3u8");
+    }
+
+    #[test]
+    fn test_audio_rendition_by_name() {
+        let (uri, reason) = select_audio(1, AudioRenditionSelection::name("spanish"));
+        assert_eq!(uri, "audio/hi/es.m3u8");
+        assert_eq!(reason, "name spanish");
+    }
+
+    #[test]
+    fn test_audio_rendition_falls_back_to_default() {
+        let (uri, reason) = select_audio(0, AudioRenditionSelection::language("fr"));
+        assert_eq!(uri, "audio/lo/en.m3u8");
+        assert_eq!(reason, "fr not offered, default rendition");
+        let (uri, _) = select_audio(1, AudioRenditionSelection::name("Commentary"));
+        assert_eq!(uri, "audio/hi/es-51.m3u8");
+        let (uri, reason) = select_audio(1, AudioRenditionSelection::default());
+        assert_eq!(uri, "audio/hi/es-51.m3u8");
+        assert_eq!(reason, "default rendition");
+    }
+}
@@ -0,0 +1,44 @@
+use pipeline_common::{PipelineError, WriterError};
+use thiserror::Error;
+
+use super::spec::JobSpecError;
+use crate::DownloadError;
+
+/// Error types of a job run by [`run_job`](super::run_job)
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("{0}")]
+    Spec(#[from] JobSpecError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Pipeline error: {0}")]
+    Pipeline(#[from] PipelineError),
+
+    #[error("Download error: {0}")]
+    Download(#[from] DownloadError),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error("Writer error: {0}")]
+    Writer(String),
+
+    #[error("Broken pipe: consumer closed the connection")]
+    BrokenPipe,
+}
+
+/// Check if an error string indicates a broken pipe error
+pub(crate) fn is_broken_pipe_error(err_str: &str) -> bool {
+    err_str.contains("Broken pipe")
+        || err_str.contains("broken pipe")
+        || err_str.contains("os error 109")  // Windows broken pipe error code
+        || err_str.contains("EPIPE")
+}
+
+impl From<WriterError> for JobError {
+    fn from(error: WriterError) -> Self {
+        JobError::Writer(error.to_string())
+    }
+}
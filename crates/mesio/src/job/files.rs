@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use crate::error::AppError;
+use super::error::JobError;
 
 /// Creates all directories in the given path, including parent directories if they don't exist.
 ///
@@ -11,12 +11,12 @@ use crate::error::AppError;
 /// # Returns
 ///
 /// * `Ok(())` if directories were created successfully
-/// * `Err(AppError::Io)` if there was an I/O error creating the directories
+/// * `Err(JobError::Io)` if there was an I/O error creating the directories
 #[inline]
-pub async fn create_dirs(path: &Path) -> Result<(), AppError> {
+pub(crate) async fn create_dirs(path: &Path) -> Result<(), JobError> {
     tokio::fs::create_dir_all(path)
         .await
-        .map_err(AppError::Io)?;
+        .map_err(JobError::Io)?;
     Ok(())
 }
 
@@ -29,18 +29,18 @@ pub async fn create_dirs(path: &Path) -> Result<(), AppError> {
 /// # Returns
 ///
 /// * `Ok(String)` containing the extracted filename (without extension, max 30 chars)
-/// * `Err(AppError::InvalidInput)` if the URL is malformed
+/// * `Err(JobError::InvalidInput)` if the URL is malformed
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// let filename = extract_filename_from_url("https://example.com/video.mp4")?;
 /// assert_eq!(filename, "video");
 /// ```
-pub fn extract_filename_from_url(url_str: &str) -> Result<String, AppError> {
+pub(crate) fn extract_filename_from_url(url_str: &str) -> Result<String, JobError> {
     let url = url_str
         .parse::<reqwest::Url>()
-        .map_err(|e| AppError::InvalidInput(e.to_string()))?;
+        .map_err(|e| JobError::InvalidInput(e.to_string()))?;
 
     let file_name = url
         .path_segments()
@@ -72,15 +72,15 @@ pub fn extract_filename_from_url(url_str: &str) -> Result<String, AppError> {
 /// # Returns
 ///
 /// * `Ok(String)` containing the expanded name with URL filename substituted
-/// * `Err(AppError)` if the URL is malformed or filename extraction fails
+/// * `Err(JobError)` if the URL is malformed or filename extraction fails
 ///
 /// # Examples
 ///
-/// ```
+/// ```ignore
 /// let expanded = expand_name_url("episode_%u", "https://example.com/video.mp4")?;
 /// assert_eq!(expanded, "episode_video");
 /// ```
-pub fn expand_name_url(name_template: &str, url_str: &str) -> Result<String, AppError> {
+pub(crate) fn expand_name_url(name_template: &str, url_str: &str) -> Result<String, JobError> {
     let url_name = extract_filename_from_url(url_str)?;
     let base_name = name_template.replace("%u", &url_name);
     Ok(base_name)
@@ -1,68 +1,63 @@
-use crate::output::pipe_flv_strategy::PipeFlvStrategy;
-use crate::output::provider::OutputFormat;
-use crate::processor::generic::{
-    process_pipe_stream, process_pipe_stream_with_processing, process_stream,
-};
-use crate::utils::{create_dirs, expand_name_url, format_bytes, spans};
-use crate::{config::ProgramConfig, error::AppError};
+use super::error::JobError;
+use super::files::{create_dirs, expand_name_url};
+use super::generic::{process_pipe_stream, process_pipe_stream_with_processing, process_stream};
+use super::pipe_flv_strategy::PipeFlvStrategy;
+use super::spans::{self, format_bytes};
+use super::spec::{JobConfig, OutputFormat};
+use crate::DownloaderInstance;
 use flv::data::FlvData;
 use flv::parser_async::FlvDecoderStream;
 use flv_fix::FlvPipeline;
 use flv_fix::FlvWriterConfig;
 use flv_fix::writer::FlvWriter;
 use futures::{Stream, StreamExt};
-use mesio_engine::DownloaderInstance;
 use pipeline_common::{
-    CancellationToken, PipelineError, ProtocolWriter, WriterStats, config::PipelineConfig,
+    CancellationToken, ChannelPipeline, PipelineError, RunCompletionError, RunOutcome,
+    ShutdownHandle, StreamerContext, WriterStats, run_until_shutdown,
 };
 use std::path::Path;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Instant;
 use tokio::fs::File;
 use tokio::io::BufReader;
-use tracing::{Level, Span, info, span};
+use tracing::{Level, info, span};
 
 async fn process_raw_stream(
     stream: Pin<Box<dyn Stream<Item = Result<FlvData, PipelineError>> + Send>>,
     output_dir: &Path,
     base_name: &str,
-    pipeline_common_config: &PipelineConfig,
-) -> Result<WriterStats, AppError> {
-    let (tx, rx) = tokio::sync::mpsc::channel(pipeline_common_config.channel_size);
+    config: &JobConfig,
+    shutdown: &ShutdownHandle,
+) -> Result<WriterStats, JobError> {
     let mut writer = FlvWriter::new(FlvWriterConfig {
         output_dir: output_dir.to_path_buf(),
         base_name: base_name.to_string(),
         enable_low_latency: false,
     });
+    writer.set_numbered_collisions(config.numbered_collisions);
 
-    // Capture the current span to propagate to the blocking task
-    let current_span = Span::current();
-    let writer_task = tokio::task::spawn_blocking(move || {
-        let _enter = current_span.enter();
-        writer.run(rx)
-    });
+    // Without processors the tags go straight to the writer
+    let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+    let pipeline =
+        ChannelPipeline::new(context).with_channel_size(config.pipeline_config.channel_size);
 
-    let mut stream = stream;
-    while let Some(item_result) = stream.next().await {
-        if tx.send(item_result).await.is_err() {
-            break;
-        }
-    }
-    drop(tx);
-
-    writer_task
+    run_until_shutdown(stream, pipeline, writer, shutdown)
         .await
-        .map_err(|e| AppError::Writer(e.to_string()))?
-        .map_err(|e| AppError::Writer(e.to_string()))
+        .map(RunOutcome::into_stats)
+        .map_err(|e| match e {
+            RunCompletionError::Writer(err) => JobError::Writer(err.to_string()),
+            RunCompletionError::Pipeline(err) => JobError::Pipeline(err),
+        })
 }
 
 /// Process a single FLV file
 pub async fn process_file(
     input_path: &Path,
     output_dir: &Path,
-    config: &ProgramConfig,
+    config: &JobConfig,
     token: &CancellationToken,
-) -> Result<(), AppError> {
+) -> Result<(), JobError> {
     // Check if we're in pipe output mode
     let is_pipe_mode = matches!(
         config.output_format,
@@ -76,7 +71,7 @@ pub async fn process_file(
 
     let base_name = input_path
         .file_stem()
-        .ok_or_else(|| AppError::InvalidInput("Invalid filename".to_string()))?
+        .ok_or_else(|| JobError::InvalidInput("Invalid filename".to_string()))?
         .to_string_lossy()
         .to_string();
 
@@ -97,6 +92,7 @@ pub async fn process_file(
     let file_size = file_reader.get_ref().metadata().await?.len();
     let decoder_stream = FlvDecoderStream::with_capacity(file_reader, 4 * 1024 * 1024) // 4MB buffer for better I/O throughput
         .map(|r| r.map_err(|e| PipelineError::Strategy(Box::new(e))));
+    let shutdown = ShutdownHandle::new(token.clone());
 
     // Use pipe output strategy when stdout mode is active
     let stats = if is_pipe_mode {
@@ -157,14 +153,17 @@ pub async fn process_file(
             config.flv_pipeline_config.clone(),
             Box::pin(decoder_stream),
             "Writing FLV output",
-            |_writer_span| {
-                FlvWriter::new(FlvWriterConfig {
+            |_writer_span, context| {
+                let mut writer = FlvWriter::new(FlvWriterConfig {
                     output_dir: output_dir.to_path_buf(),
                     base_name: base_name.to_string(),
                     enable_low_latency: config.flv_pipeline_config.enable_low_latency,
-                })
+                });
+                writer.set_numbered_collisions(config.numbered_collisions);
+                writer.set_event_context(context.clone());
+                writer
             },
-            token.clone(),
+            &shutdown,
         )
         .await?
     } else {
@@ -177,7 +176,8 @@ pub async fn process_file(
             Box::pin(decoder_stream),
             output_dir,
             &base_name,
-            &config.pipeline_config,
+            config,
+            &shutdown,
         )
         .await?
     };
@@ -200,11 +200,10 @@ pub async fn process_file(
 pub async fn process_flv_stream(
     url_str: &str,
     output_dir: &Path,
-    config: &ProgramConfig,
+    config: &JobConfig,
     name_template: &str,
     downloader: &mut DownloaderInstance,
-    token: &CancellationToken,
-) -> Result<u64, AppError> {
+) -> Result<u64, JobError> {
     // Check if we're in pipe output mode
     let is_pipe_mode = matches!(
         config.output_format,
@@ -231,11 +230,12 @@ pub async fn process_flv_stream(
     // Expand the name template with the URL filename
     let base_name = expand_name_url(name_template, url_str)?;
     downloader.add_source(url_str, 0);
+    let shutdown = downloader.shutdown_handle();
 
     let stream = match downloader {
         DownloaderInstance::Flv(flv) => flv.download_with_sources(url_str).await?,
         _ => {
-            return Err(AppError::InvalidInput(
+            return Err(JobError::InvalidInput(
                 "Expected FLV downloader".to_string(),
             ));
         }
@@ -295,24 +295,21 @@ pub async fn process_flv_stream(
             config.flv_pipeline_config.clone(),
             Box::pin(stream),
             "Writing FLV output",
-            |_writer_span| {
-                FlvWriter::new(FlvWriterConfig {
+            |_writer_span, context| {
+                let mut writer = FlvWriter::new(FlvWriterConfig {
                     output_dir: output_dir.to_path_buf(),
                     base_name: base_name.clone(),
                     enable_low_latency: config.flv_pipeline_config.enable_low_latency,
-                })
+                });
+                writer.set_numbered_collisions(config.numbered_collisions);
+                writer.set_event_context(context.clone());
+                writer
             },
-            token.clone(),
+            &shutdown,
         )
         .await?
     } else {
-        process_raw_stream(
-            Box::pin(stream),
-            output_dir,
-            &base_name,
-            &config.pipeline_config,
-        )
-        .await?
+        process_raw_stream(Box::pin(stream), output_dir, &base_name, config, &shutdown).await?
     };
 
     let elapsed = start_time.elapsed();
@@ -1,24 +1,26 @@
-use crate::error::{AppError, is_broken_pipe_error};
-use crate::utils::spans;
+use super::error::{JobError, is_broken_pipe_error};
+use super::spans::{self, format_bytes};
 use futures::{Stream, StreamExt};
 use pipeline_common::{
     CancellationToken, FormatStrategy, PipelineError, PipelineProvider, ProtocolWriter,
-    RunCompletionError, StreamerContext, WriterConfig, WriterStats, WriterTask,
-    config::PipelineConfig, settle_run,
+    RunCompletionError, RunOutcome, ShutdownHandle, StreamerContext, WriterConfig, WriterStats,
+    WriterTask, config::PipelineConfig, run_until_shutdown, settle_run,
 };
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
-use tracing::{Level, Span, span, warn};
+use std::time::Duration;
+use tracing::{Instrument, Level, Span, info, span, warn};
+use tracing_indicatif::span_ext::IndicatifSpanExt;
 
 pub async fn process_stream<P, W>(
     pipeline_common_config: &PipelineConfig,
     pipeline_config: P::Config,
     stream: Pin<Box<dyn Stream<Item = Result<P::Item, PipelineError>> + Send>>,
     writer_message: &str,
-    writer_initializer: impl FnOnce(&Span) -> W,
-    token: CancellationToken,
-) -> Result<WriterStats, AppError>
+    writer_initializer: impl FnOnce(&Span, &Arc<StreamerContext>) -> W,
+    shutdown: &ShutdownHandle,
+) -> Result<WriterStats, JobError>
 where
     P: PipelineProvider,
     P::Config: Send + 'static,
@@ -34,7 +36,7 @@ where
         stream,
         writer_span,
         writer_initializer,
-        token,
+        shutdown,
     )
     .await
 }
@@ -44,17 +46,20 @@ pub async fn process_stream_with_span<P, W>(
     pipeline_config: P::Config,
     stream: Pin<Box<dyn Stream<Item = Result<P::Item, PipelineError>> + Send>>,
     writer_span: Span,
-    writer_initializer: impl FnOnce(&Span) -> W,
-    token: CancellationToken,
-) -> Result<WriterStats, AppError>
+    writer_initializer: impl FnOnce(&Span, &Arc<StreamerContext>) -> W,
+    shutdown: &ShutdownHandle,
+) -> Result<WriterStats, JobError>
 where
     P: PipelineProvider,
     P::Config: Send + 'static,
     P::Item: Send + 'static,
     W: ProtocolWriter<Item = P::Item>,
 {
-    let context = Arc::new(StreamerContext::new(token.clone()));
-    let pipeline_provider = P::with_config(context, pipeline_common_config, pipeline_config);
+    // The pipeline gets its own token: on shutdown it drains instead of aborting
+    let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+    let in_flight = context.in_flight.clone();
+    let pipeline_provider =
+        P::with_config(context.clone(), pipeline_common_config, pipeline_config);
 
     // Create span for pipeline processing under the writer span
     let processing_span = span!(parent: &writer_span, Level::INFO, "pipeline_processing");
@@ -63,45 +68,40 @@ where
     // Build the pipeline (now ChannelPipeline)
     let pipeline = pipeline_provider.build_pipeline();
 
-    // Spawn the pipeline tasks
-    let pipeline_common::channel_pipeline::SpawnedPipeline {
-        input_tx,
-        output_rx,
-        tasks: processing_tasks,
-    } = pipeline.spawn();
-
-    // Initialize the writer using the provided span
-    let mut writer = writer_initializer(&writer_span);
-    let writer_task = {
-        let span = writer_span.clone();
-        tokio::task::spawn_blocking(move || {
-            let _enter = span.enter(); // Enter the span in the blocking task
-            writer.run(output_rx)
+    // Show the bytes buffered between the pipeline stages as memory pressure
+    let memory_task = {
+        let span = processing_span.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                span.pb_set_message(&format!(
+                    "Processing pipeline (buffered {}, peak {})",
+                    format_bytes(in_flight.current()),
+                    format_bytes(in_flight.high_water())
+                ));
+            }
         })
     };
 
-    // Ensure subsequent async work executes within the writer span
-    let _writer_guard = writer_span.enter();
+    // Initialize the writer using the provided span, it runs within that span and reports
+    // to the context of the pipeline
+    let writer = writer_initializer(&writer_span, &context);
+    let result = run_until_shutdown(stream, pipeline, writer, shutdown)
+        .instrument(writer_span)
+        .await;
+    memory_task.abort();
 
-    let mut stream = stream;
-    while let Some(item_result) = stream.next().await {
-        if input_tx.send(item_result).await.is_err() {
-            // Upstream channel closed
-            break;
+    match result {
+        Ok(RunOutcome::Interrupted(stats)) => {
+            info!(
+                items_written = stats.items_written,
+                "Shutdown requested, output finalized"
+            );
+            Ok(stats)
         }
-    }
-
-    drop(input_tx); // Close the channel to signal completion to the processing task
-    drop(_writer_guard);
-
-    let writer_result = writer_task
-        .await
-        .map_err(|e| AppError::Writer(e.to_string()))?;
-
-    match settle_run(writer_result, processing_tasks).await {
-        Ok(stats) => Ok(stats),
-        Err(RunCompletionError::Writer(err)) => Err(AppError::Writer(err.to_string())),
-        Err(RunCompletionError::Pipeline(err)) => Err(AppError::Pipeline(err)),
+        Ok(RunOutcome::Completed(stats)) => Ok(stats),
+        Err(RunCompletionError::Writer(err)) => Err(JobError::Writer(err.to_string())),
+        Err(RunCompletionError::Pipeline(err)) => Err(JobError::Pipeline(err)),
     }
 }
 
@@ -185,21 +185,21 @@ where
     })
 }
 
-/// Convert writer task result to AppError
+/// Convert writer task result to JobError
 fn handle_pipe_writer_result(
     result: Result<Result<PipeStreamStats, (String, bool)>, tokio::task::JoinError>,
-) -> Result<PipeStreamStats, AppError> {
+) -> Result<PipeStreamStats, JobError> {
     match result {
         Ok(Ok(stats)) => Ok(stats),
         Ok(Err((msg, is_broken_pipe))) => {
             if is_broken_pipe {
                 // Broken pipe is expected behavior when consumer closes
-                Err(AppError::BrokenPipe)
+                Err(JobError::BrokenPipe)
             } else {
-                Err(AppError::Writer(msg))
+                Err(JobError::Writer(msg))
             }
         }
-        Err(e) => Err(AppError::Writer(e.to_string())),
+        Err(e) => Err(JobError::Writer(e.to_string())),
     }
 }
 
@@ -212,7 +212,7 @@ pub async fn process_pipe_stream<D, S>(
     pipeline_config: &PipelineConfig,
     strategy: S,
     extension: &str,
-) -> Result<PipeStreamStats, AppError>
+) -> Result<PipeStreamStats, JobError>
 where
     D: Send + 'static,
     S: FormatStrategy<D>,
@@ -246,7 +246,7 @@ pub async fn process_pipe_stream_with_processing<P, S>(
     pipeline_type_config: P::Config,
     strategy: S,
     extension: &str,
-) -> Result<PipeStreamStats, AppError>
+) -> Result<PipeStreamStats, JobError>
 where
     P: PipelineProvider,
     P::Config: Send + 'static,
@@ -286,6 +286,6 @@ where
     match settle_run(writer_result, processing_tasks).await {
         Ok(stats) => Ok(stats),
         Err(RunCompletionError::Writer(err)) => Err(err),
-        Err(RunCompletionError::Pipeline(err)) => Err(AppError::Pipeline(err)),
+        Err(RunCompletionError::Pipeline(err)) => Err(JobError::Pipeline(err)),
     }
 }
@@ -1,13 +1,13 @@
-use crate::output::pipe_hls_strategy::PipeHlsStrategy;
-use crate::output::provider::OutputFormat;
-use crate::processor::generic::process_pipe_stream;
-use crate::utils::spans;
-use crate::{config::ProgramConfig, error::AppError, utils::create_dirs, utils::expand_name_url};
+use super::error::JobError;
+use super::files::{create_dirs, expand_name_url};
+use super::generic::process_pipe_stream;
+use super::pipe_hls_strategy::PipeHlsStrategy;
+use super::spans;
+use super::spec::{JobConfig, OutputFormat};
+use crate::{DownloadError, DownloaderInstance};
 use futures::{StreamExt, stream};
 use hls::HlsData;
 use hls_fix::{HlsPipeline, HlsWriter, HlsWriterConfig};
-use mesio_engine::{DownloadError, DownloaderInstance};
-use pipeline_common::CancellationToken;
 use pipeline_common::PipelineError;
 use std::path::Path;
 use std::time::Instant;
@@ -18,11 +18,10 @@ use tracing_indicatif::span_ext::IndicatifSpanExt;
 pub async fn process_hls_stream(
     url_str: &str,
     output_dir: &Path,
-    config: &ProgramConfig,
+    config: &JobConfig,
     name_template: &str,
     downloader: &mut DownloaderInstance,
-    token: &CancellationToken,
-) -> Result<u64, AppError> {
+) -> Result<u64, JobError> {
     // Check if we're in pipe output mode
     let is_pipe_mode = matches!(
         config.output_format,
@@ -38,6 +37,7 @@ pub async fn process_hls_stream(
 
     let base_name = expand_name_url(name_template, url_str)?;
     downloader.add_source(url_str, 10);
+    let shutdown = downloader.shutdown_handle();
 
     // Create the writer progress span up-front so downloads inherit it
     // Note: Progress bars are disabled in pipe mode via main.rs configuration
@@ -63,8 +63,11 @@ pub async fn process_hls_stream(
             DownloaderInstance::Hls(hls_manager) => {
                 hls_manager.download_with_sources(url_str).await?
             }
+            DownloaderInstance::Dash(dash_manager) => {
+                dash_manager.download_with_sources(url_str).await?
+            }
             _ => {
-                return Err(AppError::InvalidInput(
+                return Err(JobError::InvalidInput(
                     "Expected HLS downloader".to_string(),
                 ));
             }
@@ -75,13 +78,13 @@ pub async fn process_hls_stream(
     let first_segment = match stream.next().await {
         Some(Ok(segment)) => segment,
         Some(Err(e)) => {
-            return Err(AppError::InvalidInput(format!(
+            return Err(JobError::InvalidInput(format!(
                 "Failed to get first HLS segment: {e}"
             )));
         }
         None => {
             info!("HLS stream is empty.");
-            return Err(AppError::Download(DownloadError::source_exhausted(
+            return Err(JobError::Download(DownloadError::source_exhausted(
                 "HLS stream is empty",
             )));
         }
@@ -92,7 +95,7 @@ pub async fn process_hls_stream(
         HlsData::M4sData(_) => "m4s",
         // should never happen
         HlsData::EndMarker(_) => {
-            return Err(AppError::InvalidInput(
+            return Err(JobError::InvalidInput(
                 "First segment is EndMarker".to_string(),
             ));
         }
@@ -144,20 +147,22 @@ pub async fn process_hls_stream(
             None
         };
 
-        crate::processor::generic::process_stream_with_span::<HlsPipeline, HlsWriter>(
+        super::generic::process_stream_with_span::<HlsPipeline, HlsWriter>(
             &config.pipeline_config,
             hls_pipe_config,
             Box::pin(stream),
             writer_span.clone(),
-            |_writer_span| {
-                HlsWriter::new(HlsWriterConfig {
+            |_writer_span, _context| {
+                let mut writer = HlsWriter::new(HlsWriterConfig {
                     output_dir: output_dir.to_path_buf(),
                     base_name: base_name.to_string(),
                     extension: extension.to_string(),
                     max_file_size,
-                })
+                });
+                writer.set_numbered_collisions(config.numbered_collisions);
+                writer
             },
-            token.clone(),
+            &shutdown,
         )
         .await?
     };
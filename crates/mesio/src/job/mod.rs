@@ -0,0 +1,484 @@
+//! # Jobs
+//!
+//! A job downloads or repairs a list of inputs with one configuration, the library
+//! counterpart of a `mesio` CLI invocation. It is described by a [`JobSpec`], which can
+//! be deserialized from a configuration file, and run by [`run_job`]:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use mesio_engine::job::{JobSpec, run_job};
+//!
+//! let spec: JobSpec = serde_json::from_str(
+//!     r#"{ "inputs": ["https://example.com/live.flv"], "pipeline": { "fix": true } }"#,
+//! )?;
+//! let report = run_job(spec, None).await?;
+//! println!("{} of {} inputs done", report.summary.succeeded(), report.inputs.len());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! URLs are downloaded with the protocol detected for them and FLV files are repaired,
+//! written to files or piped to stdout or stderr as the spec says.
+
+mod error;
+mod files;
+mod flv;
+mod generic;
+mod hls;
+mod pipe_flv_strategy;
+mod pipe_hls_strategy;
+mod spans;
+mod spec;
+
+pub use error::JobError;
+pub use spec::{
+    FieldError, FlvPipelineSpec, FlvSpec, HlsPipelineSpec, HlsSpec, HttpSpec, JobSpec,
+    JobSpecError, LimitsSpec, OutputFormat, OutputSpec, PipelineSpec, ProxySpec, RetrySpec,
+};
+
+use crate::concurrent::{InputContext, InputOutcome, InputSummary, process_inputs_concurrent};
+use crate::dash::DashConfig;
+use crate::{DownloadManagerConfig, MesioDownloaderFactory, OnProgress, ProtocolType};
+use pipeline_common::{CancellationToken, EventCounter};
+use spec::JobConfig;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{Instrument, Level, error, info, span};
+
+/// Outcome of a job
+#[derive(Debug)]
+pub struct JobReport {
+    /// The inputs of the job, in the order of the outcomes
+    pub inputs: Vec<String>,
+    /// Outcome of each input
+    pub summary: InputSummary<JobError>,
+    /// Whether the job was cancelled before all inputs were processed
+    pub cancelled: bool,
+    /// Number of [`PipelineEvent`]s of each kind emitted by the pipelines of the inputs,
+    /// e.g. `repair_applied`
+    ///
+    /// [`PipelineEvent`]: pipeline_common::PipelineEvent
+    pub events: BTreeMap<String, u64>,
+}
+
+impl JobReport {
+    /// Whether every input was processed successfully
+    pub fn is_success(&self) -> bool {
+        self.summary.is_success()
+    }
+
+    /// The first failure of the job, if any
+    pub fn into_result(self) -> Result<(), JobError> {
+        match self
+            .summary
+            .outcomes
+            .into_iter()
+            .find_map(|outcome| match outcome {
+                InputOutcome::Failed(err) => Some(err),
+                _ => None,
+            }) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Run the job described by `spec`.
+///
+/// The spec is validated before anything is downloaded, failing with [`JobError::Spec`]
+/// listing every invalid field. Otherwise the inputs are processed, up to
+/// `spec.limits.parallel` of them at once, and the report holds the outcome of each.
+/// Download progress is reported to `on_progress` as [`ProgressEvent::Input`] events
+/// identifying the input.
+///
+/// The returned future is not `Send`, as it enters the tracing span of each input while
+/// polled; await it on the task that runs the job, or `block_on` it.
+///
+/// [`ProgressEvent::Input`]: crate::ProgressEvent::Input
+pub async fn run_job(
+    spec: JobSpec,
+    on_progress: Option<OnProgress>,
+) -> Result<JobReport, JobError> {
+    run_job_with_token(spec, on_progress, &CancellationToken::new()).await
+}
+
+/// Run the job described by `spec` until it completes or `token` is cancelled.
+///
+/// Cancelling `token` stops the downloads gracefully, finalizing their outputs, and skips
+/// the inputs not started yet. See [`run_job`].
+pub async fn run_job_with_token(
+    spec: JobSpec,
+    on_progress: Option<OnProgress>,
+    token: &CancellationToken,
+) -> Result<JobReport, JobError> {
+    let config = spec.to_config()?;
+    let processing_span = span!(
+        Level::INFO,
+        "processing_inputs",
+        count = config.inputs.len()
+    );
+    Ok(run(&config, on_progress, token)
+        .instrument(processing_span)
+        .await)
+}
+
+/// Process the inputs of `config`, up to `config.max_parallel` of them at once
+async fn run(
+    config: &JobConfig,
+    on_progress: Option<OnProgress>,
+    token: &CancellationToken,
+) -> JobReport {
+    let output_dir = config.output_dir.as_path();
+    let name_template = config.name_template.as_str();
+    let inputs_len = config.inputs.len();
+
+    info!(
+        inputs_count = inputs_len,
+        max_parallel = config.max_parallel,
+        output_format = %config.output_format,
+        "Starting processing of {} input{}",
+        inputs_len,
+        if inputs_len == 1 { "" } else { "s" }
+    );
+    info!("{}", config.pipeline_config);
+
+    // The pipelines of all inputs count their events in the report
+    let events = Arc::new(EventCounter::default());
+    let mut config = config.clone();
+    config.pipeline_config.event_handlers.push(events.clone());
+    let config = &config;
+
+    // All downloads share the bandwidth budget
+    let mut flv_config = config.flv_config.clone();
+    let mut hls_config = config.hls_config.clone();
+    let mut dash_config = DashConfig::default();
+    config.shared_limits.apply_to(&mut flv_config.base);
+    config.shared_limits.apply_to(&mut hls_config.base);
+    config.shared_limits.apply_to(&mut dash_config.base);
+
+    let factory = MesioDownloaderFactory::new()
+        .with_download_config(DownloadManagerConfig::default())
+        .with_flv_config(flv_config)
+        .with_hls_config(hls_config)
+        .with_dash_config(dash_config)
+        .with_token(token.clone());
+
+    // trim urls for better usability
+    let trimmed: Vec<String> = config
+        .inputs
+        .iter()
+        .map(|input| input.trim().to_string())
+        .collect();
+
+    let summary = process_inputs_concurrent(
+        trimmed.iter().map(String::as_str).collect(),
+        config.max_parallel,
+        config.shared_limits.clone(),
+        config.fail_fast,
+        token,
+        |input, context| {
+            // Create a span for this specific input
+            let input_span =
+                span!(Level::INFO, "process_input", index = context.index + 1, input = %input);
+            let factory = match &on_progress {
+                Some(on_progress) => with_progress(&factory, config, &context, on_progress),
+                None => factory.clone(),
+            };
+            async move {
+                process_input(input, output_dir, config, name_template, &factory, context).await
+            }
+            .instrument(input_span)
+        },
+    )
+    .await;
+
+    for (index, err) in summary.failures() {
+        error!(
+            index = index + 1,
+            "Failed to process {}: {err}", trimmed[index]
+        );
+    }
+    info!(
+        succeeded = summary.succeeded(),
+        failed = summary.failed(),
+        skipped = summary.skipped(),
+        events = ?events.counts(),
+        "Finished processing inputs"
+    );
+
+    JobReport {
+        inputs: trimmed,
+        summary,
+        cancelled: token.is_cancelled(),
+        events: events.counts(),
+    }
+}
+
+/// A factory whose downloads report their progress to `on_progress`, tagged with the input
+fn with_progress(
+    factory: &MesioDownloaderFactory,
+    config: &JobConfig,
+    context: &InputContext,
+    on_progress: &OnProgress,
+) -> MesioDownloaderFactory {
+    let on_progress = context.tag_progress(on_progress);
+    let mut flv_config = config.flv_config.clone();
+    let mut hls_config = config.hls_config.clone();
+    let mut dash_config = DashConfig::default();
+    for base in [
+        &mut flv_config.base,
+        &mut hls_config.base,
+        &mut dash_config.base,
+    ] {
+        config.shared_limits.apply_to(base);
+        base.on_progress = Some(on_progress.clone());
+    }
+    factory
+        .clone()
+        .with_flv_config(flv_config)
+        .with_hls_config(hls_config)
+        .with_dash_config(dash_config)
+}
+
+/// Determine the type of input and process accordingly
+async fn process_input(
+    input: &str,
+    output_dir: &Path,
+    config: &JobConfig,
+    name_template: &str,
+    factory: &MesioDownloaderFactory,
+    context: InputContext,
+) -> Result<(), JobError> {
+    let token = &context.token;
+
+    // Hold a file or connection slot while the input is processed
+    let _slot = context.limits.acquire_open().await;
+
+    // Process based on input type
+    if input.starts_with("http://") || input.starts_with("https://") {
+        // The download shuts down gracefully when this input is cancelled
+        let mut downloader = factory
+            .clone()
+            .with_token(token.clone())
+            .create_for_url(input, ProtocolType::Auto)
+            .await?;
+
+        let protocol_type = downloader.protocol_type();
+
+        match protocol_type {
+            ProtocolType::Flv => {
+                flv::process_flv_stream(input, output_dir, config, name_template, &mut downloader)
+                    .await?;
+            }
+            ProtocolType::Hls | ProtocolType::Dash => {
+                hls::process_hls_stream(input, output_dir, config, name_template, &mut downloader)
+                    .await?;
+            }
+            _ => {
+                error!("Unsupported protocol for: {input}");
+                return Err(JobError::InvalidInput(format!(
+                    "Unsupported protocol: {input}"
+                )));
+            }
+        }
+    } else {
+        // It's a file path
+        let path = PathBuf::from(input);
+        if path.exists() && path.is_file() {
+            // For files, check the extension to determine the type
+            if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+                match extension.to_lowercase().as_str() {
+                    "flv" => {
+                        flv::process_file(&path, output_dir, config, token).await?;
+                    }
+                    // "m3u8" | "m3u" => {
+                    //     hls::process_hls_file(&path, output_dir, config, &progress_manager).await?;
+                    // },
+                    _ => {
+                        error!("Unsupported file extension for: {input}");
+                        return Err(JobError::InvalidInput(format!(
+                            "Unsupported file extension: {input}"
+                        )));
+                    }
+                }
+            } else {
+                error!("File without extension: {input}");
+                return Err(JobError::InvalidInput(format!(
+                    "File without extension: {input}"
+                )));
+            }
+        } else {
+            error!(
+                "Input is neither a valid URL nor an existing file: {}",
+                input
+            );
+            return Err(JobError::InvalidInput(format!("Invalid input: {input}")));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concurrent::SharedLimits;
+    use ::flv::header::FlvHeader;
+    use ::flv::tag::{FlvTag, FlvTagType};
+    use ::flv::writer::FlvWriter;
+    use bytes::Bytes;
+
+    fn tag(tag_type: FlvTagType, timestamp_ms: u32, data: &[u8]) -> FlvTag {
+        FlvTag {
+            timestamp_ms,
+            stream_id: 0,
+            tag_type,
+            is_filtered: false,
+            data: Bytes::copy_from_slice(data),
+        }
+    }
+
+    fn write_flv(path: &Path) {
+        let mut writer = FlvWriter::new(std::fs::File::create(path).unwrap()).unwrap();
+        writer.write_header(&FlvHeader::new(true, true)).unwrap();
+        writer
+            .write_tag_f(&tag(FlvTagType::Video, 0, &[0x17, 0, 0, 0, 0, 1, 0x64]))
+            .unwrap();
+        writer
+            .write_tag_f(&tag(FlvTagType::Audio, 0, &[0xAF, 0, 0x12, 0x10]))
+            .unwrap();
+        for i in 0..50 {
+            let frame_type = if i % 10 == 0 { 0x17 } else { 0x27 };
+            writer
+                .write_tag_f(&tag(
+                    FlvTagType::Video,
+                    i * 40,
+                    &[frame_type, 1, 0, 0, 0, 0xAA],
+                ))
+                .unwrap();
+            writer
+                .write_tag_f(&tag(FlvTagType::Audio, i * 40, &[0xAF, 1, 0x21, 0x10]))
+                .unwrap();
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fixes_flv_files_concurrently() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let mut inputs: Vec<PathBuf> = (0..4)
+            .map(|i| input_dir.path().join(format!("input{i}.flv")))
+            .collect();
+        for input in &inputs {
+            write_flv(input);
+        }
+        inputs.insert(2, input_dir.path().join("missing.flv"));
+
+        let mut spec = JobSpec {
+            inputs: vec!["unused".to_string()],
+            ..JobSpec::default()
+        };
+        spec.pipeline.fix = true;
+        let config = spec.to_config().unwrap();
+        let token = CancellationToken::new();
+
+        let summary = process_inputs_concurrent(
+            inputs,
+            3,
+            SharedLimits::new().with_max_open(2),
+            false,
+            &token,
+            |path, context| {
+                let output_dir = output_dir.path();
+                let config = &config;
+                async move {
+                    let _slot = context.limits.acquire_open().await;
+                    if !path.exists() {
+                        return Err(JobError::InvalidInput(path.display().to_string()));
+                    }
+                    flv::process_file(&path, output_dir, config, &context.token).await
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(summary.succeeded(), 4);
+        assert_eq!(summary.failed(), 1);
+        assert_eq!(summary.skipped(), 0);
+        assert!(matches!(summary.outcomes[2], InputOutcome::Failed(_)));
+
+        let outputs: Vec<String> = std::fs::read_dir(output_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        for i in 0..4 {
+            let prefix = format!("input{i}_p");
+            assert!(
+                outputs
+                    .iter()
+                    .any(|name| name.starts_with(&prefix) && name.ends_with(".flv")),
+                "no output for input{i} in {outputs:?}"
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_runs_job_from_json_spec() {
+        let input_dir = tempfile::tempdir().unwrap();
+        let output_dir = tempfile::tempdir().unwrap();
+        let first = input_dir.path().join("first.flv");
+        let second = input_dir.path().join("second.flv");
+        write_flv(&first);
+        write_flv(&second);
+        let missing = input_dir.path().join("missing.flv");
+
+        let spec: JobSpec = serde_json::from_value(serde_json::json!({
+            "inputs": [first, missing, second],
+            "output": { "dir": output_dir.path() },
+            "pipeline": { "fix": true, "max_duration_secs": 60.0 },
+            "limits": { "parallel": 2, "max_open": 1 },
+        }))
+        .unwrap();
+
+        let report = run_job(spec, None).await.unwrap();
+        assert!(!report.cancelled);
+        assert_eq!(report.summary.succeeded(), 2);
+        assert_eq!(report.summary.failed(), 1);
+        assert!(matches!(
+            report.summary.outcomes[1],
+            InputOutcome::Failed(JobError::InvalidInput(_))
+        ));
+        assert!(report.into_result().is_err());
+
+        let outputs: Vec<String> = std::fs::read_dir(output_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        for name in ["first", "second"] {
+            assert!(
+                outputs
+                    .iter()
+                    .any(|output| output.starts_with(name) && output.ends_with(".flv")),
+                "no output for {name} in {outputs:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_spec_fails_before_processing() {
+        let output_dir = tempfile::tempdir().unwrap();
+        let spec: JobSpec = serde_json::from_value(serde_json::json!({
+            "output": { "dir": output_dir.path().join("out") },
+            "limits": { "parallel": 0 },
+        }))
+        .unwrap();
+
+        let Err(JobError::Spec(err)) = run_job(spec, None).await else {
+            panic!("expected a spec error");
+        };
+        assert!(err.field("inputs").is_some());
+        assert!(err.field("limits.parallel").is_some());
+        assert!(!output_dir.path().join("out").exists());
+    }
+}
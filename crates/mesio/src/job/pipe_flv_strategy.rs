@@ -173,7 +173,11 @@ impl FormatStrategy<FlvData> for PipeFlvStrategy {
     type Writer = BufWriter<std::io::Stdout>;
     type StrategyError = PipeFlvStrategyError;
 
-    fn create_writer(&self, _path: &Path) -> Result<Self::Writer, Self::StrategyError> {
+    fn create_writer(
+        &self,
+        _path: &Path,
+        _config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
         // For pipe output, we always write to stdout
         Ok(BufWriter::with_capacity(64 * 1024, io::stdout()))
     }
@@ -165,7 +165,11 @@ impl FormatStrategy<HlsData> for PipeHlsStrategy {
     type Writer = BufWriter<std::io::Stdout>;
     type StrategyError = PipeHlsStrategyError;
 
-    fn create_writer(&self, _path: &Path) -> Result<Self::Writer, Self::StrategyError> {
+    fn create_writer(
+        &self,
+        _path: &Path,
+        _config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
         // For pipe output, we always write to stdout
         Ok(BufWriter::with_capacity(64 * 1024, io::stdout()))
     }
@@ -0,0 +1,777 @@
+//! # Job Specification
+//!
+//! A [`JobSpec`] describes a job declaratively: the inputs to download or repair, where the
+//! output goes, the protocol, pipeline and network settings, and the limits shared by the
+//! inputs. It deserializes from any serde format, and every field has the default of the
+//! corresponding `mesio` CLI flag, so a spec only lists what it changes.
+//!
+//! Before a job runs, its spec is translated into the configurations of the downloaders and
+//! pipelines. Every invalid field is reported, by its path in the spec, in a [`JobSpecError`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use flv_fix::{ContinuityMode, FlvPipelineConfig, RepairStrategy, ScriptFillerConfig};
+use hls_fix::HlsPipelineConfig;
+use pipeline_common::config::PipelineConfig;
+use pipeline_common::validate_filename_template;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::concurrent::SharedLimits;
+use crate::config::HttpVersionPreference;
+use crate::flv::FlvProtocolConfig;
+use crate::hls::HlsConfig;
+use crate::proxy::{ProxyAuth, ProxyConfig, ProxyType};
+use crate::retry::RetryPolicy;
+use crate::{DownloaderConfig, HlsProtocolBuilder};
+
+/// Where the output of a job is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// Write to a file
+    #[default]
+    File,
+    /// Write to stdout
+    Stdout,
+    /// Write to stderr
+    Stderr,
+}
+
+impl OutputFormat {
+    /// Whether the output is piped to stdout or stderr rather than written to files
+    pub fn is_pipe(&self) -> bool {
+        matches!(self, OutputFormat::Stdout | OutputFormat::Stderr)
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::File => write!(f, "file"),
+            OutputFormat::Stdout => write!(f, "stdout"),
+            OutputFormat::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+/// Declarative description of a job
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobSpec {
+    /// URLs to download and FLV files to repair
+    pub inputs: Vec<String>,
+    pub output: OutputSpec,
+    pub pipeline: PipelineSpec,
+    pub http: HttpSpec,
+    pub proxy: ProxySpec,
+    pub flv: FlvSpec,
+    pub hls: HlsSpec,
+    pub limits: LimitsSpec,
+    /// How failed requests are retried, the default policy when unset
+    pub retry: Option<RetrySpec>,
+}
+
+/// Where and how the output files are written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputSpec {
+    /// Directory of the output files
+    pub dir: PathBuf,
+    /// Name template of the output files
+    pub name_template: String,
+    pub format: OutputFormat,
+    /// Name files that would overwrite an existing one `name_1`, `name_2`, ...
+    pub numbered_collisions: bool,
+}
+
+impl Default for OutputSpec {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("./fix"),
+            name_template: "%u%Y%m%d_%H%M%S_p%i".to_string(),
+            format: OutputFormat::File,
+            numbered_collisions: false,
+        }
+    }
+}
+
+/// Processing of the downloaded or read data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineSpec {
+    /// Run the data through the repair pipeline instead of writing it as received
+    pub fix: bool,
+    /// Maximum size of an output file in bytes, 0 for unlimited
+    pub max_file_size: u64,
+    /// Maximum duration of an output file in seconds, 0 for unlimited
+    pub max_duration_secs: f64,
+    /// Capacity of the channels between the pipeline stages
+    pub channel_size: usize,
+    /// Maximum bytes buffered between the pipeline stages, 0 for unlimited
+    pub max_in_flight_bytes: u64,
+    pub flv: FlvPipelineSpec,
+    pub hls: HlsPipelineSpec,
+}
+
+impl Default for PipelineSpec {
+    fn default() -> Self {
+        Self {
+            fix: false,
+            max_file_size: 0,
+            max_duration_secs: 0.0,
+            channel_size: 64,
+            max_in_flight_bytes: 0,
+            flv: FlvPipelineSpec::default(),
+            hls: HlsPipelineSpec::default(),
+        }
+    }
+}
+
+/// Repair of FLV data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlvPipelineSpec {
+    /// Inject a keyframe index into the metadata for better seeking
+    pub keyframe_index: bool,
+    /// Rewrite the metadata as the file is written rather than once it is closed
+    pub low_latency: bool,
+}
+
+impl Default for FlvPipelineSpec {
+    fn default() -> Self {
+        Self {
+            keyframe_index: true,
+            low_latency: true,
+        }
+    }
+}
+
+/// Repair of HLS segments
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HlsPipelineSpec {
+    pub defragment: bool,
+    pub split_segments: bool,
+    pub segment_limiter: bool,
+}
+
+impl Default for HlsPipelineSpec {
+    fn default() -> Self {
+        let config = HlsPipelineConfig::default();
+        Self {
+            defragment: config.defragment,
+            split_segments: config.split_segments,
+            segment_limiter: config.segment_limiter,
+        }
+    }
+}
+
+/// HTTP settings of the downloads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpSpec {
+    /// Overall timeout of a request in seconds, 0 for none
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// Maximum time between two chunks of a response in seconds
+    pub read_timeout_secs: u64,
+    pub write_timeout_secs: u64,
+    /// Headers added to every request
+    pub headers: BTreeMap<String, String>,
+    /// Query parameters added to every request
+    pub params: BTreeMap<String, String>,
+    pub version: HttpVersionPreference,
+    pub force_ipv4: bool,
+    pub force_ipv6: bool,
+    /// TCP keep-alive interval of HTTP/2 connections in seconds
+    pub http2_keepalive_secs: u64,
+}
+
+impl Default for HttpSpec {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 0,
+            connect_timeout_secs: 30,
+            read_timeout_secs: 30,
+            write_timeout_secs: 30,
+            headers: BTreeMap::new(),
+            params: BTreeMap::new(),
+            version: HttpVersionPreference::Auto,
+            force_ipv4: false,
+            force_ipv6: false,
+            http2_keepalive_secs: 20,
+        }
+    }
+}
+
+/// Proxy of the downloads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxySpec {
+    /// Proxy server URL, taking precedence over the system proxy
+    pub url: Option<String>,
+    #[serde(rename = "type")]
+    pub proxy_type: ProxyType,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Use the system proxy settings when no proxy URL is set
+    pub system: bool,
+    /// Disable every proxy, the system proxy included
+    pub disabled: bool,
+}
+
+impl Default for ProxySpec {
+    fn default() -> Self {
+        Self {
+            url: None,
+            proxy_type: ProxyType::Http,
+            username: None,
+            password: None,
+            system: true,
+            disabled: false,
+        }
+    }
+}
+
+/// Settings of FLV downloads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FlvSpec {
+    /// Buffer size of the download in bytes
+    pub buffer_size: usize,
+}
+
+impl Default for FlvSpec {
+    fn default() -> Self {
+        Self {
+            buffer_size: 64 * 1024,
+        }
+    }
+}
+
+/// Settings of HLS downloads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HlsSpec {
+    /// Segments downloaded at once
+    pub concurrency: usize,
+    pub playlist_fetch_timeout_secs: u64,
+    /// Minimum interval between two refreshes of a live playlist in seconds
+    pub refresh_interval_secs: u64,
+    pub playlist_retries: u32,
+    pub segment_retries: u32,
+    pub segment_timeout_secs: u64,
+}
+
+impl Default for HlsSpec {
+    fn default() -> Self {
+        Self {
+            concurrency: 3,
+            playlist_fetch_timeout_secs: 15,
+            refresh_interval_secs: 1,
+            playlist_retries: 5,
+            segment_retries: 3,
+            segment_timeout_secs: 10,
+        }
+    }
+}
+
+/// Limits shared by the inputs of a job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsSpec {
+    /// Inputs processed at once
+    pub parallel: usize,
+    /// Abort the remaining inputs when one of them fails
+    pub fail_fast: bool,
+    /// Combined download speed of all inputs in bytes per second, 0 for unlimited
+    pub max_bandwidth: u64,
+    /// Inputs holding files or connections open at once, 0 for unlimited
+    pub max_open: usize,
+}
+
+impl Default for LimitsSpec {
+    fn default() -> Self {
+        Self {
+            parallel: 1,
+            fail_fast: false,
+            max_bandwidth: 0,
+            max_open: 0,
+        }
+    }
+}
+
+/// How failed requests are retried
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetrySpec {
+    /// Attempts made in total, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Factor applied to the delay after each retry
+    pub multiplier: f64,
+    pub jitter: bool,
+}
+
+impl Default for RetrySpec {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        Self {
+            max_attempts: policy.max_attempts,
+            initial_backoff_ms: policy.initial_backoff.as_millis() as u64,
+            max_backoff_ms: policy.max_backoff.as_millis() as u64,
+            multiplier: policy.multiplier,
+            jitter: policy.jitter,
+        }
+    }
+}
+
+/// An invalid field of a [`JobSpec`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// Path of the field in the spec, such as `http.headers.Referer`
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// The invalid fields of a [`JobSpec`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobSpecError {
+    pub errors: Vec<FieldError>,
+}
+
+impl JobSpecError {
+    /// The error of `field`, if it is invalid
+    pub fn field(&self, field: &str) -> Option<&FieldError> {
+        self.errors.iter().find(|error| error.field == field)
+    }
+}
+
+impl fmt::Display for JobSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid job spec")?;
+        for error in &self.errors {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JobSpecError {}
+
+/// Collects the invalid fields of a spec
+#[derive(Default)]
+struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn error(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    fn check(&mut self, valid: bool, field: &str, message: &str) {
+        if !valid {
+            self.error(field, message);
+        }
+    }
+}
+
+/// The configurations a job runs with, translated from its spec
+#[derive(Debug, Clone)]
+pub(crate) struct JobConfig {
+    pub inputs: Vec<String>,
+    pub output_dir: PathBuf,
+    pub name_template: String,
+    pub pipeline_config: PipelineConfig,
+    pub flv_pipeline_config: FlvPipelineConfig,
+    pub hls_pipeline_config: HlsPipelineConfig,
+    pub flv_config: FlvProtocolConfig,
+    pub hls_config: HlsConfig,
+    /// Whether to enable processing pipeline (vs raw download)
+    pub enable_processing: bool,
+    pub output_format: OutputFormat,
+    /// Maximum number of inputs processed at once
+    pub max_parallel: usize,
+    /// Whether the failure of one input aborts the others
+    pub fail_fast: bool,
+    /// Bandwidth and open file limits shared by all inputs
+    pub shared_limits: SharedLimits,
+    /// Whether output files that would overwrite an existing one are named `name_1`, `name_2`, ...
+    pub numbered_collisions: bool,
+}
+
+impl JobSpec {
+    /// Check every field of the spec, reporting all the invalid ones
+    pub fn validate(&self) -> Result<(), JobSpecError> {
+        self.to_config().map(drop)
+    }
+
+    /// Translate the spec into the configurations of the downloaders and pipelines
+    pub(crate) fn to_config(&self) -> Result<JobConfig, JobSpecError> {
+        let mut v = Validator::default();
+
+        v.check(
+            !self.inputs.is_empty(),
+            "inputs",
+            "at least one input is required",
+        );
+        for (index, input) in self.inputs.iter().enumerate() {
+            v.check(
+                !input.trim().is_empty(),
+                &format!("inputs[{index}]"),
+                "must not be empty",
+            );
+        }
+
+        if let Err(err) = validate_filename_template(&self.output.name_template) {
+            v.error("output.name_template", err.to_string());
+        }
+        v.check(
+            !self.output.dir.as_os_str().is_empty() || self.output.format.is_pipe(),
+            "output.dir",
+            "must not be empty",
+        );
+
+        let pipeline = &self.pipeline;
+        v.check(
+            pipeline.channel_size > 0,
+            "pipeline.channel_size",
+            "must be at least 1",
+        );
+        v.check(
+            pipeline.max_duration_secs.is_finite() && pipeline.max_duration_secs >= 0.0,
+            "pipeline.max_duration_secs",
+            "must be a non-negative number of seconds",
+        );
+
+        let http = &self.http;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &http.headers {
+            let field = format!("http.headers.{name}");
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    headers.insert(name, value);
+                }
+                (Err(_), _) => v.error(field, "invalid header name"),
+                (_, Err(_)) => v.error(field, "invalid header value"),
+            }
+        }
+        v.check(
+            !(http.force_ipv4 && http.force_ipv6),
+            "http.force_ipv6",
+            "conflicts with http.force_ipv4",
+        );
+
+        let proxy = self.proxy_config(&mut v);
+
+        v.check(
+            self.flv.buffer_size > 0,
+            "flv.buffer_size",
+            "must be at least 1",
+        );
+        v.check(
+            self.hls.concurrency > 0,
+            "hls.concurrency",
+            "must be at least 1",
+        );
+        v.check(
+            self.limits.parallel > 0,
+            "limits.parallel",
+            "must be at least 1",
+        );
+
+        let retry_policy = self.retry.as_ref().map(|retry| {
+            v.check(
+                retry.max_attempts > 0,
+                "retry.max_attempts",
+                "must be at least 1",
+            );
+            v.check(
+                retry.multiplier.is_finite() && retry.multiplier >= 1.0,
+                "retry.multiplier",
+                "must be a number of at least 1",
+            );
+            v.check(
+                retry.initial_backoff_ms <= retry.max_backoff_ms,
+                "retry.max_backoff_ms",
+                "must not be less than retry.initial_backoff_ms",
+            );
+            RetryPolicy {
+                max_attempts: retry.max_attempts,
+                initial_backoff: Duration::from_millis(retry.initial_backoff_ms),
+                max_backoff: Duration::from_millis(retry.max_backoff_ms),
+                multiplier: retry.multiplier,
+                jitter: retry.jitter,
+                ..RetryPolicy::default()
+            }
+        });
+
+        if !v.errors.is_empty() {
+            return Err(JobSpecError { errors: v.errors });
+        }
+
+        let pipeline_config = PipelineConfig::builder()
+            .max_file_size(pipeline.max_file_size)
+            .max_duration_s(pipeline.max_duration_secs)
+            .channel_size(pipeline.channel_size)
+            .max_in_flight_bytes(pipeline.max_in_flight_bytes)
+            .build();
+
+        let flv_pipeline_config = FlvPipelineConfig::builder()
+            .duplicate_tag_filtering(false)
+            .repair_strategy(RepairStrategy::Strict)
+            .continuity_mode(ContinuityMode::Reset)
+            .keyframe_index_config(if pipeline.flv.keyframe_index {
+                if pipeline.max_duration_secs > 0.0 {
+                    info!("Keyframe index will be injected into metadata for better seeking");
+                    Some(ScriptFillerConfig {
+                        keyframe_duration_ms: (pipeline.max_duration_secs * 1000.0) as u32,
+                        ..Default::default()
+                    })
+                } else {
+                    info!("Keyframe index enabled with default configuration");
+                    Some(ScriptFillerConfig::default())
+                }
+            } else {
+                None
+            })
+            .enable_low_latency(pipeline.flv.low_latency)
+            .pipe_mode(self.output.format.is_pipe())
+            .build();
+
+        let hls_pipeline_config = HlsPipelineConfig {
+            defragment: pipeline.hls.defragment,
+            split_segments: pipeline.hls.split_segments,
+            segment_limiter: pipeline.hls.segment_limiter,
+            ..HlsPipelineConfig::default()
+        };
+
+        let download_config = {
+            let mut builder = DownloaderConfig::builder()
+                .with_timeout(Duration::from_secs(http.timeout_secs))
+                .with_connect_timeout(Duration::from_secs(http.connect_timeout_secs))
+                .with_read_timeout(Duration::from_secs(http.read_timeout_secs))
+                .with_write_timeout(Duration::from_secs(http.write_timeout_secs))
+                .with_headers(headers)
+                .with_params(
+                    http.params
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect(),
+                )
+                .with_caching_enabled(false)
+                .with_force_ipv4(http.force_ipv4)
+                .with_force_ipv6(http.force_ipv6)
+                .with_http_version(http.version)
+                .with_http2_keep_alive_interval(Duration::from_secs(http.http2_keepalive_secs));
+            if let Some(retry_policy) = retry_policy {
+                builder = builder.with_retry_policy(retry_policy);
+            }
+            builder = match proxy {
+                Some(proxy) => builder.with_proxy(proxy),
+                None => builder.with_system_proxy(!self.proxy.disabled && self.proxy.system),
+            };
+            builder.build()
+        };
+
+        let flv_config = FlvProtocolConfig::builder()
+            .with_base_config(download_config.clone())
+            .buffer_size(self.flv.buffer_size)
+            .build();
+
+        let hls_config = HlsProtocolBuilder::new()
+            .with_base_config(download_config)
+            .download_concurrency(self.hls.concurrency)
+            .initial_playlist_fetch_timeout(Duration::from_secs(
+                self.hls.playlist_fetch_timeout_secs,
+            ))
+            .live_refresh_interval(Duration::from_secs(self.hls.refresh_interval_secs))
+            .live_max_refresh_retries(self.hls.playlist_retries)
+            .max_segment_retries(self.hls.segment_retries)
+            .segment_download_timeout(Duration::from_secs(self.hls.segment_timeout_secs))
+            .get_config();
+
+        let mut shared_limits = SharedLimits::new();
+        if self.limits.max_bandwidth > 0 {
+            shared_limits = shared_limits.with_max_bytes_per_sec(self.limits.max_bandwidth);
+        }
+        if self.limits.max_open > 0 {
+            shared_limits = shared_limits.with_max_open(self.limits.max_open);
+        }
+
+        Ok(JobConfig {
+            inputs: self.inputs.clone(),
+            output_dir: self.output.dir.clone(),
+            name_template: self.output.name_template.clone(),
+            pipeline_config,
+            flv_pipeline_config,
+            hls_pipeline_config,
+            flv_config,
+            hls_config,
+            enable_processing: pipeline.fix,
+            output_format: self.output.format,
+            max_parallel: self.limits.parallel,
+            fail_fast: self.limits.fail_fast,
+            shared_limits,
+            numbered_collisions: self.output.numbered_collisions,
+        })
+    }
+
+    /// The explicit proxy of the spec, if any
+    fn proxy_config(&self, v: &mut Validator) -> Option<ProxyConfig> {
+        let spec = &self.proxy;
+        if spec.disabled {
+            info!("All proxy settings disabled");
+            return None;
+        }
+        let Some(url) = &spec.url else {
+            if spec.system {
+                info!("Using system proxy settings for downloads");
+            } else {
+                info!("No proxy settings configured for downloads");
+            }
+            return None;
+        };
+
+        if url::Url::parse(url).is_err() {
+            v.error("proxy.url", "invalid URL");
+        }
+        let auth = match (&spec.username, &spec.password) {
+            (Some(username), Some(password)) => Some(ProxyAuth {
+                username: username.clone(),
+                password: password.clone(),
+            }),
+            (Some(_), None) => {
+                v.error("proxy.password", "required with proxy.username");
+                None
+            }
+            (None, Some(_)) => {
+                v.error("proxy.username", "required with proxy.password");
+                None
+            }
+            (None, None) => None,
+        };
+
+        info!(
+            proxy_url = %url,
+            proxy_type = ?spec.proxy_type,
+            has_auth = auth.is_some(),
+            "Using explicit proxy configuration for downloads"
+        );
+        Some(ProxyConfig {
+            url: url.clone(),
+            proxy_type: spec.proxy_type,
+            auth,
+            remote_dns: false,
+            no_proxy: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserializes_partial_spec_with_defaults() {
+        let spec: JobSpec = serde_json::from_str(
+            r#"{
+                "inputs": ["https://example.com/live.flv"],
+                "output": { "dir": "/tmp/out", "format": "stdout" },
+                "pipeline": { "fix": true, "flv": { "low_latency": false } },
+                "proxy": { "url": "socks5://127.0.0.1:1080", "type": "socks5" },
+                "retry": { "max_attempts": 2 }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(spec.output.format, OutputFormat::Stdout);
+        assert_eq!(
+            spec.output.name_template,
+            OutputSpec::default().name_template
+        );
+        assert!(spec.pipeline.fix);
+        assert!(spec.pipeline.flv.keyframe_index);
+        assert!(!spec.pipeline.flv.low_latency);
+        assert_eq!(spec.proxy.proxy_type, ProxyType::Socks5);
+        assert_eq!(spec.limits, LimitsSpec::default());
+
+        let config = spec.to_config().unwrap();
+        assert!(config.enable_processing);
+        assert!(config.flv_pipeline_config.pipe_mode);
+        assert!(!config.flv_pipeline_config.enable_low_latency);
+        assert_eq!(config.flv_config.base.retry_policy.max_attempts, 2);
+        assert!(config.flv_config.base.proxy.is_some());
+    }
+
+    #[test]
+    fn test_rejects_unknown_fields() {
+        let err =
+            serde_json::from_str::<JobSpec>(r#"{ "output": { "directory": "out" } }"#).unwrap_err();
+        assert!(err.to_string().contains("directory"), "{err}");
+    }
+
+    #[test]
+    fn test_reports_every_invalid_field() {
+        let mut spec = JobSpec {
+            inputs: vec!["in.flv".to_string(), " ".to_string()],
+            ..JobSpec::default()
+        };
+        spec.output.name_template = "%nope%".to_string();
+        spec.http
+            .headers
+            .insert("Bad Header".to_string(), "value".to_string());
+        spec.http.force_ipv4 = true;
+        spec.http.force_ipv6 = true;
+        spec.proxy.url = Some("http://proxy:8080".to_string());
+        spec.proxy.username = Some("user".to_string());
+        spec.limits.parallel = 0;
+        spec.retry = Some(RetrySpec {
+            multiplier: 0.5,
+            ..RetrySpec::default()
+        });
+
+        let err = spec.validate().unwrap_err();
+        let fields: Vec<&str> = err.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "inputs[1]",
+                "output.name_template",
+                "http.headers.Bad Header",
+                "http.force_ipv6",
+                "proxy.password",
+                "limits.parallel",
+                "retry.multiplier",
+            ]
+        );
+        assert_eq!(
+            err.field("limits.parallel").unwrap().message,
+            "must be at least 1"
+        );
+        assert!(
+            err.to_string()
+                .contains("proxy.password: required with proxy.username")
+        );
+    }
+}
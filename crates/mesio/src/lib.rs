@@ -6,32 +6,54 @@
 //!
 //! ## Features
 //!
-//! - Multiple protocol support (HLS, FLV)
+//! - Multiple protocol support (HLS, FLV, DASH)
 //! - Efficient download management with caching
 //! - Source selection with fallback capabilities
 //! - Factory pattern for protocol instantiation
-//! - Protocol auto-detection from URLs
+//! - Protocol auto-detection from URLs and the responses they serve
+//! - Concurrent processing of several inputs with shared bandwidth and connection limits
+//! - Detection of streams that stay connected without delivering media
+//! - Cookie store and renewal of expiring credentials during long downloads
+//! - HLS variant selection by bandwidth, resolution or codec, with failover to other variants
+//! - Raw copy of the received bytes next to the processed output
+//! - Declarative jobs deserialized from a spec, run end to end (`job` feature)
 
+pub mod auth;
 pub mod builder;
 pub mod bytes_stream;
 pub mod cache;
+pub mod concurrent;
 pub mod config;
+pub mod dash;
 pub mod downloader;
 pub mod error;
 pub mod factory;
 pub mod flv;
 pub mod hls;
+#[cfg(feature = "job")]
+pub mod job;
 pub mod media_protocol;
+#[cfg(test)]
+pub(crate) mod mock_origin;
+pub mod probe;
 pub mod protocol_builder;
 pub mod proxy;
+pub mod retry;
 pub mod source;
+pub mod tee;
+pub mod throttle;
+pub mod watchdog;
 
 pub use config::DEFAULT_USER_AGENT;
 
+pub use auth::{AuthRefresh, AuthRefreshError, AuthUpdate, Cookie, RefreshContext, RefreshReason};
 pub use builder::DownloaderConfigBuilder;
-pub use cache::{CacheConfig, CacheManager};
+pub use cache::{CacheBackend, CacheConfig, CacheManager};
+pub use concurrent::{
+    InputContext, InputOutcome, InputSummary, SharedLimits, process_inputs_concurrent,
+};
 pub use config::{DownloaderConfig, HttpVersionPreference};
-pub use error::DownloadError;
+pub use error::{DownloadError, TimeoutPhase};
 
 // Re-export legacy protocol traits for backward compatibility
 pub use media_protocol::{BoxMediaStream, ProtocolConfig};
@@ -54,14 +76,22 @@ pub use media_protocol::{
 };
 
 // Re-export protocol builders
-pub use protocol_builder::{FlvProtocolBuilder, HlsProtocolBuilder, ProtocolBuilder};
+pub use protocol_builder::{
+    DashProtocolBuilder, FlvProtocolBuilder, HlsProtocolBuilder, ProtocolBuilder,
+};
 pub use source::{ContentSource, SourceManager, SourceSelectionStrategy};
+pub use tee::{RawTee, TeeKind, TeeTarget};
+pub use throttle::{OnProgress, RateLimiter};
+pub use watchdog::{StallAction, StallConfig};
 
 // Re-export downloader utilities
-pub use downloader::{DownloadManager, DownloadManagerConfig, create_client};
+pub use downloader::{ClientProvider, DownloadManager, DownloadManagerConfig, create_client};
 
 // Re-export factory types
 pub use factory::{DownloadStream, DownloaderInstance, MesioDownloaderFactory, ProtocolType};
 
 // Re-export proxy utilities
-pub use proxy::{ProxyAuth, ProxyConfig, ProxyType};
+pub use proxy::{ProxyAuth, ProxyConfig, ProxyOverride, ProxyType};
+
+// Re-export retry types
+pub use retry::{RetryCondition, RetryPolicy};
@@ -9,8 +9,8 @@ use std::pin::Pin;
 use std::{fmt::Debug, sync::Arc};
 
 use crate::{
-    DownloadError, cache::CacheManager, flv::FlvProtocolConfig, hls::HlsConfig,
-    source::SourceManager,
+    DownloadError, cache::CacheManager, dash::DashConfig, flv::FlvProtocolConfig, hls::HlsConfig,
+    source::SourceManager, throttle::RateLimiter,
 };
 use tokio_util::sync::CancellationToken;
 
@@ -25,6 +25,8 @@ pub enum Protocol {
     Flv(Box<FlvProtocolConfig>),
     /// HLS protocol
     Hls(Box<HlsConfig>),
+    /// DASH protocol
+    Dash(Box<DashConfig>),
 }
 
 /// Shared protocol configuration trait
@@ -36,6 +38,9 @@ pub trait ProtocolBase: Send + Sync + 'static {
     fn new(config: Self::Config) -> Result<Self, DownloadError>
     where
         Self: Sized;
+
+    /// Share a bandwidth limit with other downloads, on top of the per-download limit
+    fn set_shared_rate_limiter(&mut self, limiter: RateLimiter);
 }
 
 /// Base download capability
@@ -77,6 +82,9 @@ pub trait Resumable: Download {
 /// Enables downloading with fallback sources and source management.
 pub trait MultiSource: Download {
     /// Download media content with support for multiple sources
+    ///
+    /// Protocols may keep failing over between the sources after the stream has started, using
+    /// a copy of the source health taken when it started.
     fn download_with_sources(
         &self,
         url: &str,
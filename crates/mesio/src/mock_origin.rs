@@ -0,0 +1,641 @@
+//! # Mock Media Origin
+//!
+//! A programmable origin on a local port for tests that exercise the downloaders end to end.
+//!
+//! Each route of a [`MockOrigin`] is scripted when it is built:
+//!
+//! - An FLV route serves a [`FlvResponse`] per request, in order, the last one to every
+//!   further request. A response can pause or stall at a byte offset, or reset the
+//!   connection there, and reconnects can be served different bytes.
+//! - An HLS route serves an [`HlsPlaylist`] that publishes its segments on a timer, and the
+//!   segments it lists. Segments can fail with scripted statuses or be delayed, and be
+//!   sized to exercise large bodies.
+//! - A master playlist route serves a fixed master playlist listing the playlists of other
+//!   routes.
+//!
+//! Every request received is recorded with its headers, for assertions on what the
+//! downloader sent.
+//!
+//! ```ignore
+//! let origin = MockOrigin::builder()
+//!     .flv("/live.flv", FlvResponse::new(body.clone()).reset_at(4096))
+//!     .flv("/live.flv", FlvResponse::new(body))
+//!     .hls("/live.m3u8", HlsPlaylist::vod(3).fail_segment(1, [StatusCode::BAD_GATEWAY]))
+//!     .spawn()
+//!     .await;
+//! let url = origin.url("/live.flv");
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+/// Packet the HLS segments are made of: a null TS packet
+const SEGMENT_PACKET: [u8; 4] = [0x47, 0x1F, 0xFF, 0x10];
+
+/// Size of a TS packet
+const TS_PACKET_SIZE: usize = 188;
+
+/// A request received by a [`MockOrigin`]
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedRequest {
+    pub method: String,
+    /// Path and query of the request
+    pub target: String,
+    pub headers: HeaderMap,
+}
+
+impl RecordedRequest {
+    /// The path of the request, without its query
+    pub fn path(&self) -> &str {
+        self.target
+            .split_once('?')
+            .map_or(self.target.as_str(), |(path, _)| path)
+    }
+}
+
+/// Scripted point of an [`FlvResponse`]
+#[derive(Debug, Clone, Copy)]
+enum FlvEvent {
+    /// Stop sending for a while, then continue
+    Pause(Duration),
+    /// Drop the connection in the middle of the body
+    Reset,
+    /// Keep the connection open without sending anything more
+    Stall,
+}
+
+/// Response of an FLV route to one request: a chunked body with scripted events
+#[derive(Debug, Clone)]
+pub(crate) struct FlvResponse {
+    status: StatusCode,
+    body: Vec<u8>,
+    /// Events by byte offset, in order
+    events: Vec<(usize, FlvEvent)>,
+}
+
+impl FlvResponse {
+    /// Serve `body` completely
+    pub fn new(body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            body: body.into(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Answer with `status` and an empty body
+    pub fn status(status: StatusCode) -> Self {
+        Self {
+            status,
+            ..Self::new(Vec::new())
+        }
+    }
+
+    /// Stop for `duration` after the first `offset` bytes of the body
+    pub fn pause_at(self, offset: usize, duration: Duration) -> Self {
+        self.event(offset, FlvEvent::Pause(duration))
+    }
+
+    /// Drop the connection after the first `offset` bytes of the body
+    pub fn reset_at(self, offset: usize) -> Self {
+        self.event(offset, FlvEvent::Reset)
+    }
+
+    /// Send nothing more after the first `offset` bytes of the body, keeping the connection
+    pub fn stall_at(self, offset: usize) -> Self {
+        self.event(offset, FlvEvent::Stall)
+    }
+
+    fn event(mut self, offset: usize, event: FlvEvent) -> Self {
+        let offset = offset.min(self.body.len());
+        let index = self.events.partition_point(|(at, _)| *at <= offset);
+        self.events.insert(index, (offset, event));
+        self
+    }
+
+    async fn serve(&self, socket: &mut TcpStream) {
+        if !self.status.is_success() {
+            let _ = socket.write_all(&head(self.status, 0, "")).await;
+            return;
+        }
+
+        let response = b"HTTP/1.1 200 OK\r\nContent-Type: video/x-flv\r\n\
+            Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n";
+        if socket.write_all(response).await.is_err() {
+            return;
+        }
+        let mut sent = 0;
+        for &(offset, event) in &self.events {
+            if socket
+                .write_all(&chunk(&self.body[sent..offset]))
+                .await
+                .is_err()
+            {
+                return;
+            }
+            sent = offset;
+            match event {
+                FlvEvent::Pause(duration) => tokio::time::sleep(duration).await,
+                FlvEvent::Reset => return,
+                FlvEvent::Stall => std::future::pending().await,
+            }
+        }
+        let mut rest = chunk(&self.body[sent..]);
+        rest.extend_from_slice(b"0\r\n\r\n");
+        if socket.write_all(&rest).await.is_ok() {
+            let _ = socket.shutdown().await;
+        }
+    }
+}
+
+/// Media playlist of an HLS route, listing TS segments named `segment{n}.ts`
+#[derive(Debug, Clone)]
+pub(crate) struct HlsPlaylist {
+    /// Segments published in total
+    segments: usize,
+    /// Segments published when the origin starts
+    initial: usize,
+    /// Publication interval of the following segments, also their duration
+    interval: Duration,
+    /// Segments listed by the playlist, the whole stream when `None`
+    window: Option<usize>,
+    /// Statuses answered to the first requests of a segment
+    failures: HashMap<u64, VecDeque<StatusCode>>,
+    /// Delays before answering the first requests of a segment
+    delays: HashMap<u64, VecDeque<Duration>>,
+    /// TS packets in each segment
+    segment_packets: usize,
+}
+
+impl HlsPlaylist {
+    /// A VOD playlist of `segments` segments of one second
+    pub fn vod(segments: usize) -> Self {
+        Self {
+            segments,
+            initial: segments,
+            interval: Duration::from_secs(1),
+            window: None,
+            failures: HashMap::new(),
+            delays: HashMap::new(),
+            segment_packets: 1,
+        }
+    }
+
+    /// A live playlist listing its last three segments, publishing a new one every
+    /// `interval` until `segments` are published, then ending
+    pub fn live(segments: usize, interval: Duration) -> Self {
+        Self {
+            initial: segments.min(3),
+            interval,
+            window: Some(3),
+            ..Self::vod(segments)
+        }
+    }
+
+    /// List the last `window` published segments
+    pub fn window(mut self, window: usize) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Answer the first requests of segment `sequence` with `statuses`, in order
+    pub fn fail_segment(
+        mut self,
+        sequence: u64,
+        statuses: impl IntoIterator<Item = StatusCode>,
+    ) -> Self {
+        self.failures.entry(sequence).or_default().extend(statuses);
+        self
+    }
+
+    /// Wait `delay` before answering the next request of segment `sequence` that is not
+    /// delayed yet
+    pub fn delay_segment(mut self, sequence: u64, delay: Duration) -> Self {
+        self.delays.entry(sequence).or_default().push_back(delay);
+        self
+    }
+
+    /// Serve segments of at least `bytes` bytes, in whole TS packets
+    pub fn segment_size(mut self, bytes: usize) -> Self {
+        self.segment_packets = bytes.div_ceil(TS_PACKET_SIZE).max(1);
+        self
+    }
+
+    /// Segments published `elapsed` after the origin started
+    fn published(&self, elapsed: Duration) -> usize {
+        let advanced = (elapsed.as_secs_f64() / self.interval.as_secs_f64()) as usize;
+        self.segments.min(self.initial.saturating_add(advanced))
+    }
+
+    fn render(&self, elapsed: Duration) -> String {
+        let published = self.published(elapsed);
+        let first = published.saturating_sub(self.window.unwrap_or(published));
+        let duration = self.interval.as_secs_f64();
+        let mut playlist = format!(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:{first}\n",
+            duration.ceil().max(1.0)
+        );
+        for sequence in first..published {
+            playlist.push_str(&format!("#EXTINF:{duration:.3},\nsegment{sequence}.ts\n"));
+        }
+        if published == self.segments {
+            playlist.push_str("#EXT-X-ENDLIST\n");
+        }
+        playlist
+    }
+}
+
+/// Route of a [`MockOrigin`]
+#[derive(Debug)]
+enum Route {
+    /// Responses in order, the last one repeated
+    Flv(VecDeque<FlvResponse>),
+    Hls(HlsPlaylist),
+    /// A master playlist, served as is
+    Master(String),
+}
+
+/// Builder of a [`MockOrigin`]
+#[derive(Debug, Default)]
+pub(crate) struct MockOriginBuilder {
+    routes: HashMap<String, Route>,
+}
+
+impl MockOriginBuilder {
+    /// Serve `response` to the next request of `path`. Responses added to the same path are
+    /// served in order, the last one to every further request.
+    pub fn flv(mut self, path: impl Into<String>, response: FlvResponse) -> Self {
+        match self
+            .routes
+            .entry(path.into())
+            .or_insert_with(|| Route::Flv(VecDeque::new()))
+        {
+            Route::Flv(responses) => responses.push_back(response),
+            Route::Hls(_) | Route::Master(_) => panic!("path already serves an HLS playlist"),
+        }
+        self
+    }
+
+    /// Serve `playlist` at `path` and its segments next to it
+    pub fn hls(mut self, path: impl Into<String>, playlist: HlsPlaylist) -> Self {
+        self.routes.insert(path.into(), Route::Hls(playlist));
+        self
+    }
+
+    /// Serve the master playlist `playlist` at `path`, listing playlists of other routes
+    pub fn master(mut self, path: impl Into<String>, playlist: impl Into<String>) -> Self {
+        self.routes
+            .insert(path.into(), Route::Master(playlist.into()));
+        self
+    }
+
+    /// Start serving on a local port
+    pub async fn spawn(self) -> MockOrigin {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("local addr"));
+        let state = Arc::new(OriginState {
+            routes: Mutex::new(self.routes),
+            requests: Mutex::new(Vec::new()),
+            started: Instant::now(),
+        });
+
+        let origin = MockOrigin {
+            base,
+            state: Arc::clone(&state),
+        };
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(Arc::clone(&state).serve_connection(socket));
+            }
+        });
+        origin
+    }
+}
+
+#[derive(Debug)]
+struct OriginState {
+    routes: Mutex<HashMap<String, Route>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    started: Instant,
+}
+
+/// What to answer to a request, taken from the routes while they are locked
+enum Reply {
+    Flv(FlvResponse),
+    Body {
+        status: StatusCode,
+        content_type: &'static str,
+        body: Vec<u8>,
+        delay: Option<Duration>,
+    },
+}
+
+impl OriginState {
+    async fn serve_connection(self: Arc<Self>, mut socket: TcpStream) {
+        let mut pending = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            let Some(end) = pending.windows(4).position(|w| w == b"\r\n\r\n") else {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => pending.extend_from_slice(&buf[..n]),
+                }
+                continue;
+            };
+            let request = parse_request(&String::from_utf8_lossy(&pending[..end]));
+            pending.drain(..end + 4);
+
+            let reply = self.reply(&request);
+            self.requests.lock().push(request);
+            match reply {
+                // The FLV body ends with the connection
+                Reply::Flv(response) => return response.serve(&mut socket).await,
+                Reply::Body {
+                    status,
+                    content_type,
+                    body,
+                    delay,
+                } => {
+                    if let Some(delay) = delay {
+                        tokio::time::sleep(delay).await;
+                    }
+                    let mut response = head(status, body.len(), content_type);
+                    response.extend_from_slice(&body);
+                    if socket.write_all(&response).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn reply(&self, request: &RecordedRequest) -> Reply {
+        let not_found = Reply::Body {
+            status: StatusCode::NOT_FOUND,
+            content_type: "text/plain",
+            body: Vec::new(),
+            delay: None,
+        };
+        let path = request.path();
+        let mut routes = self.routes.lock();
+
+        if let Some(route) = routes.get_mut(path) {
+            return match route {
+                Route::Flv(responses) if responses.len() > 1 => {
+                    Reply::Flv(responses.pop_front().expect("response"))
+                }
+                Route::Flv(responses) => Reply::Flv(responses[0].clone()),
+                Route::Hls(playlist) => Reply::Body {
+                    status: StatusCode::OK,
+                    content_type: "application/vnd.apple.mpegurl",
+                    body: playlist.render(self.started.elapsed()).into_bytes(),
+                    delay: None,
+                },
+                Route::Master(playlist) => Reply::Body {
+                    status: StatusCode::OK,
+                    content_type: "application/vnd.apple.mpegurl",
+                    body: playlist.clone().into_bytes(),
+                    delay: None,
+                },
+            };
+        }
+
+        // Segments are served next to the playlist listing them
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+        let Some(sequence) = name
+            .strip_prefix("segment")
+            .and_then(|name| name.strip_suffix(".ts"))
+            .and_then(|sequence| sequence.parse::<u64>().ok())
+        else {
+            return not_found;
+        };
+        let playlist = routes
+            .iter_mut()
+            .find_map(|(route_path, route)| match route {
+                Route::Hls(playlist)
+                    if route_path.rsplit_once('/').map(|(d, _)| d) == Some(dir) =>
+                {
+                    Some(playlist)
+                }
+                _ => None,
+            });
+        let Some(playlist) = playlist else {
+            return not_found;
+        };
+        if sequence >= playlist.published(self.started.elapsed()) as u64 {
+            return not_found;
+        }
+
+        let delay = playlist
+            .delays
+            .get_mut(&sequence)
+            .and_then(VecDeque::pop_front);
+        if let Some(status) = playlist
+            .failures
+            .get_mut(&sequence)
+            .and_then(VecDeque::pop_front)
+        {
+            return Reply::Body {
+                status,
+                content_type: "text/plain",
+                body: Vec::new(),
+                delay,
+            };
+        }
+        let mut packet = vec![0xFF; TS_PACKET_SIZE];
+        packet[..SEGMENT_PACKET.len()].copy_from_slice(&SEGMENT_PACKET);
+        Reply::Body {
+            status: StatusCode::OK,
+            content_type: "video/mp2t",
+            body: packet.repeat(playlist.segment_packets),
+            delay,
+        }
+    }
+}
+
+/// A programmable origin serving scripted FLV streams and HLS playlists on a local port,
+/// until the test runtime stops
+#[derive(Debug, Clone)]
+pub(crate) struct MockOrigin {
+    base: String,
+    state: Arc<OriginState>,
+}
+
+impl MockOrigin {
+    pub fn builder() -> MockOriginBuilder {
+        MockOriginBuilder::default()
+    }
+
+    /// URL of `path` on this origin
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base)
+    }
+
+    /// The requests received so far, in order
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.requests.lock().clone()
+    }
+
+    /// The requests of `path` received so far, whatever their query
+    pub fn requests_to(&self, path: &str) -> Vec<RecordedRequest> {
+        self.state
+            .requests
+            .lock()
+            .iter()
+            .filter(|request| request.path() == path)
+            .cloned()
+            .collect()
+    }
+}
+
+fn parse_request(head: &str) -> RecordedRequest {
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default().to_string();
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':')
+            && let Ok(name) = HeaderName::from_bytes(name.trim().as_bytes())
+            && let Ok(value) = HeaderValue::from_str(value.trim())
+        {
+            headers.append(name, value);
+        }
+    }
+    RecordedRequest {
+        method,
+        target,
+        headers,
+    }
+}
+
+/// Head of a response with a body of `length` bytes, keeping the connection open
+fn head(status: StatusCode, length: usize, content_type: &str) -> Vec<u8> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {length}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+    if !content_type.is_empty() {
+        head.push_str(&format!("Content-Type: {content_type}\r\n"));
+    }
+    head.push_str("\r\n");
+    head.into_bytes()
+}
+
+fn chunk(data: &[u8]) -> Vec<u8> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+    let mut chunk = format!("{:x}\r\n", data.len()).into_bytes();
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(b"\r\n");
+    chunk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(url: &str) -> Result<(StatusCode, Vec<u8>), reqwest::Error> {
+        let response = reqwest::Client::new()
+            .get(url)
+            .header("x-test", "1")
+            .send()
+            .await?;
+        let status = response.status();
+        Ok((status, response.bytes().await?.to_vec()))
+    }
+
+    #[tokio::test]
+    async fn test_flv_responses_are_served_in_order() {
+        let body = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00".to_vec();
+        let origin = MockOrigin::builder()
+            .flv("/live.flv", FlvResponse::status(StatusCode::BAD_GATEWAY))
+            .flv("/live.flv", FlvResponse::new(body.clone()).reset_at(5))
+            .flv("/live.flv", FlvResponse::new(body.clone()))
+            .spawn()
+            .await;
+        let url = origin.url("/live.flv?token=1");
+
+        assert_eq!(get(&url).await.unwrap().0, StatusCode::BAD_GATEWAY);
+        // The reset cuts the chunked body short
+        assert!(get(&url).await.is_err());
+        assert_eq!(get(&url).await.unwrap(), (StatusCode::OK, body.clone()));
+        assert_eq!(get(&url).await.unwrap(), (StatusCode::OK, body));
+
+        let requests = origin.requests_to("/live.flv");
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[0].target, "/live.flv?token=1");
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].headers["x-test"], "1");
+        assert_eq!(
+            get(&origin.url("/other")).await.unwrap().0,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flv_pause_delays_the_rest_of_the_body() {
+        let origin = MockOrigin::builder()
+            .flv(
+                "/live.flv",
+                FlvResponse::new(vec![1; 64]).pause_at(16, Duration::from_millis(200)),
+            )
+            .spawn()
+            .await;
+
+        let started = std::time::Instant::now();
+        let (_, body) = get(&origin.url("/live.flv")).await.unwrap();
+        assert_eq!(body, vec![1; 64]);
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_live_playlist_advances_and_fails_segments() {
+        let origin = MockOrigin::builder()
+            .hls(
+                "/hls/live.m3u8",
+                HlsPlaylist::live(4, Duration::from_millis(200))
+                    .window(2)
+                    .fail_segment(0, [StatusCode::SERVICE_UNAVAILABLE]),
+            )
+            .spawn()
+            .await;
+
+        let (_, first) = get(&origin.url("/hls/live.m3u8")).await.unwrap();
+        let first = String::from_utf8(first).unwrap();
+        assert!(first.contains("#EXT-X-MEDIA-SEQUENCE:1\n"), "{first}");
+        assert!(first.contains("segment2.ts") && !first.contains("segment0.ts"));
+        assert!(!first.contains("#EXT-X-ENDLIST"));
+        // Unpublished segments are not found
+        let unpublished = get(&origin.url("/hls/segment3.ts")).await.unwrap();
+        assert_eq!(unpublished.0, StatusCode::NOT_FOUND);
+
+        tokio::time::sleep(Duration::from_millis(450)).await;
+        let (_, last) = get(&origin.url("/hls/live.m3u8")).await.unwrap();
+        let last = String::from_utf8(last).unwrap();
+        assert!(last.contains("#EXT-X-MEDIA-SEQUENCE:2\n"), "{last}");
+        assert!(last.contains("segment3.ts") && last.contains("#EXT-X-ENDLIST"));
+
+        let segment = origin.url("/hls/segment0.ts");
+        assert_eq!(
+            get(&segment).await.unwrap().0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        let (status, packet) = get(&segment).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(packet.len(), TS_PACKET_SIZE);
+        assert_eq!(packet[..4], SEGMENT_PACKET);
+    }
+}
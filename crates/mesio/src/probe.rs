@@ -0,0 +1,459 @@
+//! # Protocol Probe
+//!
+//! Detects the protocol of a URL from the response it serves rather than from its path: the
+//! Content-Type header when it names a streaming format, otherwise the first bytes of the
+//! body. The probe response is then handed to the downloader of the detected protocol, so
+//! detection does not cost an extra request.
+
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use parking_lot::Mutex;
+use reqwest::Client;
+use tokio_util::sync::CancellationToken;
+use tracing::debug;
+use url::Url;
+
+use crate::retry::{RetryAction, retry_with_backoff};
+use crate::{DownloadError, DownloaderConfig, ProtocolType};
+
+/// Most body bytes read to recognize a format signature
+const SNIFF_LIMIT: usize = 4096;
+
+/// Body of a probe response, in the form the detected protocol consumes it
+pub(crate) enum ProbedBody {
+    /// A media stream, starting with the bytes already read from it
+    Stream(BoxStream<'static, reqwest::Result<Bytes>>),
+    /// A complete manifest
+    Complete(Bytes),
+}
+
+/// Outcome of probing a URL
+pub struct Probe {
+    /// The detected protocol
+    pub protocol: ProtocolType,
+    url: Url,
+    body: ProbedBody,
+}
+
+impl Probe {
+    /// Make the probe response available to the first download of the probed URL
+    pub(crate) fn into_handoff(self) -> ProbeHandoff {
+        ProbeHandoff(Arc::new(Mutex::new(Some((self.url, self.body)))))
+    }
+}
+
+/// A probe response waiting to be used instead of a new request, shared by the clones of
+/// a downloader
+#[derive(Clone, Default)]
+pub(crate) struct ProbeHandoff(Arc<Mutex<Option<(Url, ProbedBody)>>>);
+
+impl ProbeHandoff {
+    /// Take the probe response if it was received for `url`. A response for another URL is
+    /// dropped, as it would never be used.
+    pub fn take(&self, url: &str) -> Option<ProbedBody> {
+        let (probed_url, body) = self.0.lock().take()?;
+        (Url::parse(url).ok()? == probed_url).then_some(body)
+    }
+
+    /// Take the probe response of `url` if it is a complete manifest
+    pub fn take_manifest(&self, url: &str) -> Option<Bytes> {
+        match self.take(url)? {
+            ProbedBody::Complete(bytes) => Some(bytes),
+            ProbedBody::Stream(_) => None,
+        }
+    }
+
+    /// Take the probe response of `url` if it is a media stream
+    pub fn take_stream(&self, url: &str) -> Option<BoxStream<'static, reqwest::Result<Bytes>>> {
+        match self.take(url)? {
+            ProbedBody::Stream(body) => Some(body),
+            ProbedBody::Complete(_) => None,
+        }
+    }
+}
+
+/// The protocol named by a Content-Type header
+fn protocol_from_content_type(content_type: &str) -> Option<ProtocolType> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    match mime.as_str() {
+        "video/x-flv" | "video/flv" => Some(ProtocolType::Flv),
+        "application/vnd.apple.mpegurl"
+        | "application/x-mpegurl"
+        | "audio/mpegurl"
+        | "audio/x-mpegurl" => Some(ProtocolType::Hls),
+        "application/dash+xml" => Some(ProtocolType::Dash),
+        _ => None,
+    }
+}
+
+/// The protocol recognized from the start of a body, if enough of it was read to decide
+fn protocol_from_signature(head: &[u8]) -> Option<ProtocolType> {
+    if head.len() >= 4 && head.starts_with(b"FLV") && head[3] == 1 {
+        return Some(ProtocolType::Flv);
+    }
+    let text = head.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(head);
+    let start = text
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let text = &text[start..];
+    if text.starts_with(b"#EXTM3U") {
+        return Some(ProtocolType::Hls);
+    }
+    if text.starts_with(b"<") && text.windows(4).any(|w| w == b"<MPD") {
+        return Some(ProtocolType::Dash);
+    }
+    None
+}
+
+/// A printable summary of the first bytes of a body
+fn describe_signature(head: &[u8]) -> String {
+    if head.is_empty() {
+        return "an empty body".to_string();
+    }
+    let shown = &head[..head.len().min(16)];
+    let text: String = shown
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                (b as char).to_string()
+            } else {
+                format!("\\x{b:02x}")
+            }
+        })
+        .collect();
+    format!("\"{text}\"")
+}
+
+/// Send a GET request to `url` and detect the protocol of the response.
+///
+/// Failed requests are retried with the retry policy of `config`.
+pub async fn probe(
+    client: &Client,
+    url: &str,
+    config: &DownloaderConfig,
+    token: &CancellationToken,
+) -> Result<Probe, DownloadError> {
+    let parsed = Url::parse(url).map_err(|e| DownloadError::invalid_url(url, e.to_string()))?;
+    let on_progress = config.on_progress.as_ref();
+    let response = retry_with_backoff(&config.retry_policy, url, on_progress, token, |_| async {
+        let request = client.get(parsed.clone()).query(&config.params);
+        let response = tokio::select! {
+            _ = token.cancelled() => return RetryAction::Fail(DownloadError::Cancelled),
+            response = request.send() => response,
+        };
+        match response {
+            Ok(response) if response.status().is_success() => RetryAction::Success(response),
+            Ok(response) => {
+                let error = DownloadError::from_response(response, "protocol_probe").await;
+                RetryAction::from_error(error)
+            }
+            Err(e) => RetryAction::from_request_error(e),
+        }
+    })
+    .await?;
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let mut chunks = response.bytes_stream();
+    let mut head = BytesMut::new();
+    let header_protocol = content_type.as_deref().and_then(protocol_from_content_type);
+    let protocol = match header_protocol {
+        Some(protocol) => protocol,
+        None => loop {
+            if let Some(protocol) = protocol_from_signature(&head) {
+                break protocol;
+            }
+            let chunk = if head.len() < SNIFF_LIMIT {
+                tokio::select! {
+                    _ = token.cancelled() => return Err(DownloadError::Cancelled),
+                    chunk = chunks.next() => chunk.transpose()?,
+                }
+            } else {
+                None
+            };
+            match chunk {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => {
+                    return Err(DownloadError::UnknownProtocol {
+                        url: url.to_string(),
+                        content_type,
+                        signature: describe_signature(&head),
+                    });
+                }
+            }
+        },
+    };
+    debug!(url, ?protocol, content_type = ?content_type, "Probed stream protocol");
+
+    let body = match protocol {
+        ProtocolType::Flv => {
+            let head = (!head.is_empty()).then(|| Ok(head.freeze()));
+            ProbedBody::Stream(stream::iter(head).chain(chunks).boxed())
+        }
+        _ => {
+            // Manifests are small, read them whole
+            loop {
+                let chunk = tokio::select! {
+                    _ = token.cancelled() => return Err(DownloadError::Cancelled),
+                    chunk = chunks.next() => chunk.transpose()?,
+                };
+                match chunk {
+                    Some(chunk) => head.extend_from_slice(&chunk),
+                    None => break,
+                }
+            }
+            ProbedBody::Complete(head.freeze())
+        }
+    };
+
+    Ok(Probe {
+        protocol,
+        url: parsed,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DownloadManagerConfig, DownloadStream, MesioDownloaderFactory};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const FLV_HEADER: &[u8] = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00";
+    const PLAYLIST: &str =
+        "#EXTM3U\n#EXT-X-TARGETDURATION:2\n#EXTINF:2,\nseg0.ts\n#EXT-X-ENDLIST\n";
+    const MPD: &str = r#"<?xml version="1.0"?>
+<MPD type="static" mediaPresentationDuration="PT2S">
+  <Period>
+    <AdaptationSet mimeType="video/mp4">
+      <SegmentTemplate timescale="1" duration="2" initialization="init" media="seg$Number$"/>
+      <Representation id="v" bandwidth="1"/>
+    </AdaptationSet>
+  </Period>
+</MPD>"#;
+
+    /// Serve `(path, content type, body)` routes ignoring query strings, counting requests
+    async fn spawn_server(
+        routes: Vec<(&'static str, Option<&'static str>, Vec<u8>)>,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let base = format!("http://{}", listener.local_addr().expect("local addr"));
+        let routes = Arc::new(routes);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let routes = Arc::clone(&routes);
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let head = String::from_utf8_lossy(&head);
+                    let target = head.split_whitespace().nth(1).unwrap_or("/");
+                    let path = target.split('?').next().unwrap_or_default();
+                    let Some((_, content_type, body)) = routes.iter().find(|(p, ..)| *p == path)
+                    else {
+                        let _ = socket
+                            .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                            .await;
+                        return;
+                    };
+                    let content_type = content_type
+                        .map(|ct| format!("Content-Type: {ct}\r\n"))
+                        .unwrap_or_default();
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\n{content_type}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(body);
+                    let _ = socket.write_all(&response).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+        (base, requests)
+    }
+
+    async fn probe_url(url: &str) -> Result<Probe, DownloadError> {
+        let config = DownloaderConfig::default();
+        let client = crate::create_client(&config).unwrap();
+        probe(&client, url, &config, &CancellationToken::new()).await
+    }
+
+    #[tokio::test]
+    async fn detects_protocol_from_content_type() {
+        let (base, _) = spawn_server(vec![
+            ("/live/stream", Some("video/x-flv"), FLV_HEADER.to_vec()),
+            (
+                "/live/hls",
+                Some("application/vnd.apple.mpegURL; charset=utf-8"),
+                PLAYLIST.into(),
+            ),
+            ("/live/dash", Some("application/dash+xml"), MPD.into()),
+        ])
+        .await;
+
+        for (path, expected) in [
+            ("/live/stream?token=abc", ProtocolType::Flv),
+            ("/live/hls?token=abc", ProtocolType::Hls),
+            ("/live/dash", ProtocolType::Dash),
+        ] {
+            let probe = probe_url(&format!("{base}{path}")).await.unwrap();
+            assert_eq!(probe.protocol, expected, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_protocol_from_body_signature() {
+        let (base, _) = spawn_server(vec![
+            (
+                "/flv",
+                Some("application/octet-stream"),
+                FLV_HEADER.to_vec(),
+            ),
+            (
+                "/hls",
+                Some("text/plain"),
+                format!("\u{feff}{PLAYLIST}").into(),
+            ),
+            ("/dash", None, MPD.into()),
+        ])
+        .await;
+
+        let flv = probe_url(&format!("{base}/flv")).await.unwrap();
+        assert_eq!(flv.protocol, ProtocolType::Flv);
+        // The sniffed bytes are still part of the handed off body
+        let handoff = flv.into_handoff();
+        let body: Vec<_> = handoff
+            .take_stream(&format!("{base}/flv"))
+            .expect("stream body")
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(body.concat(), FLV_HEADER);
+
+        let hls = probe_url(&format!("{base}/hls")).await.unwrap();
+        assert_eq!(hls.protocol, ProtocolType::Hls);
+        let handoff = hls.into_handoff();
+        assert!(handoff.take_manifest(&format!("{base}/other")).is_none());
+        assert!(handoff.take_manifest(&format!("{base}/hls")).is_none());
+
+        let dash = probe_url(&format!("{base}/dash")).await.unwrap();
+        assert_eq!(dash.protocol, ProtocolType::Dash);
+        let manifest = dash.into_handoff().take_manifest(&format!("{base}/dash"));
+        assert_eq!(manifest.as_deref(), Some(MPD.as_bytes()));
+    }
+
+    #[tokio::test]
+    async fn reports_what_was_seen_for_unknown_content() {
+        let (base, _) = spawn_server(vec![(
+            "/offline",
+            Some("text/html"),
+            b"<html>offline</html>".to_vec(),
+        )])
+        .await;
+
+        match probe_url(&format!("{base}/offline")).await {
+            Err(DownloadError::UnknownProtocol {
+                content_type,
+                signature,
+                ..
+            }) => {
+                assert_eq!(content_type.as_deref(), Some("text/html"));
+                assert_eq!(signature, "\"<html>offline</h\"");
+            }
+            other => panic!("expected UnknownProtocol, got {:?}", other.err()),
+        }
+    }
+
+    fn factory() -> MesioDownloaderFactory {
+        MesioDownloaderFactory::new().with_download_config(DownloadManagerConfig {
+            cache_config: None,
+            ..DownloadManagerConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn factory_downloads_flv_from_the_probe_response() {
+        let (base, requests) = spawn_server(vec![(
+            "/live/stream",
+            Some("application/octet-stream"),
+            FLV_HEADER.to_vec(),
+        )])
+        .await;
+        let url = format!("{base}/live/stream?token=abc");
+
+        let mut downloader = factory()
+            .create_for_url(&url, ProtocolType::Auto)
+            .await
+            .unwrap();
+        assert_eq!(downloader.protocol_type(), ProtocolType::Flv);
+        let DownloadStream::Flv(mut stream) = downloader.download_with_sources(&url).await.unwrap()
+        else {
+            panic!("expected an FLV stream");
+        };
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(flv::data::FlvData::Header(_)))
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn factory_downloads_dash_from_the_probe_response() {
+        let (base, requests) = spawn_server(vec![
+            ("/manifest", None, MPD.into()),
+            ("/init", None, b"init".to_vec()),
+            ("/seg1", None, b"seg1".to_vec()),
+        ])
+        .await;
+        let url = format!("{base}/manifest");
+
+        let downloader = factory()
+            .create_for_url(&url, ProtocolType::Auto)
+            .await
+            .unwrap();
+        assert_eq!(downloader.protocol_type(), ProtocolType::Dash);
+        let DownloadStream::Hls(stream) = downloader.download(&url).await.unwrap() else {
+            panic!("expected a segment stream");
+        };
+        let segments: Vec<_> = stream.map(|item| item.unwrap()).collect().await;
+        assert_eq!(segments.len(), 2);
+        // Manifest once for the probe, then the init and media segments
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn factory_rejects_unknown_content() {
+        let (base, _) =
+            spawn_server(vec![("/stream", Some("application/json"), b"{}".to_vec())]).await;
+        let result = factory()
+            .create_for_url(&format!("{base}/stream"), ProtocolType::Auto)
+            .await;
+        assert!(matches!(result, Err(DownloadError::UnknownProtocol { .. })));
+    }
+}
@@ -5,15 +5,21 @@
 
 use crate::{
     CacheConfig, DownloadError, DownloaderConfig,
-    flv::{FlvDownloader, FlvProtocolConfig},
+    dash::{DashConfig, DashDownloader, DashRepresentationSelectionPolicy},
+    downloader::ClientProvider,
+    flv::{FlvDownloader, FlvProtocolConfig, FlvReconnectConfig},
     hls::{
-        HlsDownloader,
+        AudioRenditionSelection, HlsDownloader, SegmentBufferPool, SegmentFailurePolicy,
+        VariantSelector,
         config::{HlsConfig, HlsVariantSelectionPolicy as NewHlsVariantSelectionPolicy},
     },
     proxy::ProxyConfig,
+    retry::RetryPolicy,
+    watchdog::StallConfig,
 };
+use reqwest::Client;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use std::{str::FromStr, time::Duration};
+use std::{str::FromStr, sync::Arc, time::Duration};
 
 macro_rules! impl_base_downloader_config_methods {
     ($($base:ident).+) => {
@@ -53,6 +59,12 @@ macro_rules! impl_base_downloader_config_methods {
             self
         }
 
+        /// Set how failed requests are retried
+        pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+            self.$($base).+.retry_policy = retry_policy;
+            self
+        }
+
         /// Add a single HTTP header
         pub fn add_header(mut self, name: &str, value: &str) -> Self {
             if let (Ok(name), Ok(value)) = (HeaderName::from_str(name), HeaderValue::from_str(value)) {
@@ -60,6 +72,17 @@ macro_rules! impl_base_downloader_config_methods {
             }
             self
         }
+
+        /// Send the requests with `client` instead of one built from this configuration
+        pub fn client(self, client: Client) -> Self {
+            self.client_provider(client)
+        }
+
+        /// Send the requests of each content source with the client `provider` returns for it
+        pub fn client_provider(mut self, provider: impl ClientProvider + 'static) -> Self {
+            self.$($base).+.client_provider = Some(Arc::new(provider));
+            self
+        }
     };
 }
 
@@ -91,6 +114,30 @@ impl FlvProtocolBuilder {
         self
     }
 
+    /// Set whether resuming into an existing file skips to its last complete tag
+    pub fn tag_resume(mut self, enabled: bool) -> Self {
+        self.config.tag_resume = enabled;
+        self
+    }
+
+    /// Set how long a source may send no data before switching to the next source
+    pub fn source_stall_timeout(mut self, timeout: Duration) -> Self {
+        self.config.source_stall_timeout = timeout;
+        self
+    }
+
+    /// Set the stall detection of FLV downloads, overriding the base configuration
+    pub fn stall_config(mut self, stall_config: StallConfig) -> Self {
+        self.config.stall_config = Some(stall_config);
+        self
+    }
+
+    /// Reconnect to the origin when the connection of a live stream ends early
+    pub fn reconnect(mut self, reconnect: FlvReconnectConfig) -> Self {
+        self.config.reconnect = Some(reconnect);
+        self
+    }
+
     impl_base_downloader_config_methods!(config.base);
 
     /// Access the raw configuration for more advanced customization
@@ -200,6 +247,30 @@ impl HlsProtocolBuilder {
         self
     }
 
+    /// Enable or disable downloading LL-HLS partial segments.
+    pub fn low_latency_enabled(mut self, enabled: bool) -> Self {
+        self.config.playlist_config.low_latency_enabled = enabled;
+        self
+    }
+
+    /// Enable or disable requesting LL-HLS preload hints ahead of time.
+    pub fn low_latency_preload_hints(mut self, enabled: bool) -> Self {
+        self.config.playlist_config.low_latency_preload_hints = enabled;
+        self
+    }
+
+    /// Set how many recent segments are remembered to skip duplicates.
+    pub fn segment_dedup_window(mut self, window: usize) -> Self {
+        self.config.playlist_config.segment_dedup_window = window;
+        self
+    }
+
+    /// Set the query parameters ignored when comparing segment URIs.
+    pub fn volatile_query_params(mut self, params: Vec<String>) -> Self {
+        self.config.playlist_config.volatile_query_params = params;
+        self
+    }
+
     /// Set the variant selection policy.
     pub fn variant_selection_policy(mut self, policy: NewHlsVariantSelectionPolicy) -> Self {
         self.config.playlist_config.variant_selection_policy = policy;
@@ -227,6 +298,44 @@ impl HlsProtocolBuilder {
         self
     }
 
+    /// Select the variant with a [`VariantSelector`], e.g.
+    /// [`ClosestResolution`](crate::hls::ClosestResolution) or
+    /// [`CodecPreference`](crate::hls::CodecPreference).
+    pub fn variant_selector(mut self, selector: impl VariantSelector + 'static) -> Self {
+        self.config.playlist_config.variant_selection_policy =
+            NewHlsVariantSelectionPolicy::Selector(Arc::new(selector));
+        self
+    }
+
+    /// Let the variant selection pick audio-only variants.
+    pub fn include_audio_only_variants(mut self, include: bool) -> Self {
+        self.config.playlist_config.include_audio_only_variants = include;
+        self
+    }
+
+    /// Let the variant selection pick I-frame-only variants.
+    pub fn include_i_frame_variants(mut self, include: bool) -> Self {
+        self.config.playlist_config.include_i_frame_variants = include;
+        self
+    }
+
+    /// Select the alternate audio rendition in `language`, e.g. `en`, downloaded along with
+    /// the variant by [`HlsDownloader::download_tracks`]. Without a rendition in that
+    /// language, the default one is used.
+    pub fn audio_language(mut self, language: impl Into<String>) -> Self {
+        self.config.playlist_config.audio_rendition =
+            Some(AudioRenditionSelection::language(language));
+        self
+    }
+
+    /// Select the alternate audio rendition named `name`, downloaded along with the
+    /// variant by [`HlsDownloader::download_tracks`]. Without a rendition of that name,
+    /// the default one is used.
+    pub fn audio_rendition_name(mut self, name: impl Into<String>) -> Self {
+        self.config.playlist_config.audio_rendition = Some(AudioRenditionSelection::name(name));
+        self
+    }
+
     // --- HLS SchedulerConfig methods ---
 
     /// Set maximum concurrent segment downloads.
@@ -235,6 +344,19 @@ impl HlsProtocolBuilder {
         self
     }
 
+    /// Request the oldest segment in flight again once it has been downloading for `delay`
+    /// while all download slots are busy, or never with `None`.
+    pub fn stalled_segment_reissue_after(mut self, delay: Option<Duration>) -> Self {
+        self.config.scheduler_config.stalled_segment_reissue_after = delay;
+        self
+    }
+
+    /// Set what to do when a segment still fails after its retries.
+    pub fn segment_failure_policy(mut self, policy: SegmentFailurePolicy) -> Self {
+        self.config.scheduler_config.failure_policy = policy;
+        self
+    }
+
     // --- HLS FetcherConfig methods ---
 
     /// Set timeout for downloading a single segment.
@@ -244,6 +366,9 @@ impl HlsProtocolBuilder {
     }
 
     /// Set maximum number of retries for downloading a segment.
+    ///
+    /// Segment downloads make `retries + 1` attempts, retrying on the conditions of the
+    /// download's [`RetryPolicy`].
     pub fn max_segment_retries(mut self, retries: u32) -> Self {
         self.config.fetcher_config.max_segment_retries = retries;
         self
@@ -279,6 +404,13 @@ impl HlsProtocolBuilder {
         self
     }
 
+    /// Read segment bodies into buffers of `pool`, which are reused once the segments
+    /// are written. Keep a clone of the pool to read its [`stats`](SegmentBufferPool::stats).
+    pub fn segment_buffer_pool(mut self, pool: SegmentBufferPool) -> Self {
+        self.config.fetcher_config.buffer_pool = Some(pool);
+        self
+    }
+
     // --- HLS ProcessorConfig methods ---
 
     /// Set TTL for caching processed (decrypted) segments.
@@ -342,6 +474,12 @@ impl HlsProtocolBuilder {
         self
     }
 
+    /// Set the stall detection of HLS downloads, overriding the base configuration
+    pub fn stall_config(mut self, stall_config: StallConfig) -> Self {
+        self.config.stall_config = Some(stall_config);
+        self
+    }
+
     // --- General Builder Methods ---
 
     /// Access the raw HLS configuration for more advanced customization.
@@ -372,3 +510,88 @@ impl Default for HlsProtocolBuilder {
         Self::new()
     }
 }
+
+/// Builder for DASH protocol handlers
+pub struct DashProtocolBuilder {
+    config: DashConfig,
+}
+
+impl DashProtocolBuilder {
+    /// Create a new DASH protocol builder with default configuration
+    pub fn new() -> Self {
+        Self {
+            config: DashConfig::default(),
+        }
+    }
+
+    pub fn with_base_config(mut self, base_config: DownloaderConfig) -> Self {
+        self.config.base = base_config;
+        self
+    }
+
+    impl_base_downloader_config_methods!(config.base);
+
+    /// Set the representation selection policy.
+    pub fn selection_policy(mut self, policy: DashRepresentationSelectionPolicy) -> Self {
+        self.config.selection_policy = policy;
+        self
+    }
+
+    /// Set timeout for fetching the manifest.
+    pub fn manifest_fetch_timeout(mut self, timeout: Duration) -> Self {
+        self.config.manifest_fetch_timeout = timeout;
+        self
+    }
+
+    /// Set timeout for downloading a single segment.
+    pub fn segment_download_timeout(mut self, timeout: Duration) -> Self {
+        self.config.segment_download_timeout = timeout;
+        self
+    }
+
+    /// Set minimum interval for reloading live manifests.
+    pub fn min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.config.min_refresh_interval = interval;
+        self
+    }
+
+    /// Set how many available segments a live download starts with.
+    pub fn live_start_segments(mut self, segments: usize) -> Self {
+        self.config.live_start_segments = segments;
+        self
+    }
+
+    /// Set how many consecutive manifest reloads may fail before a live download fails.
+    pub fn max_refresh_failures(mut self, failures: u32) -> Self {
+        self.config.max_refresh_failures = failures;
+        self
+    }
+
+    /// Access the raw DASH configuration for more advanced customization.
+    pub fn with_config<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut DashConfig),
+    {
+        f(&mut self.config);
+        self
+    }
+
+    /// Get a copy of the current DASH configuration.
+    pub fn get_config(&self) -> DashConfig {
+        self.config.clone()
+    }
+}
+
+impl ProtocolBuilder for DashProtocolBuilder {
+    type Protocol = DashDownloader;
+
+    fn build(self) -> Result<Self::Protocol, DownloadError> {
+        DashDownloader::with_config(self.config)
+    }
+}
+
+impl Default for DashProtocolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,12 @@
 use reqwest::Proxy;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::downloader::host_matches_entry;
 
 /// Proxy configuration types
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 #[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
 pub enum ProxyType {
     /// HTTP proxy
@@ -33,6 +38,36 @@ pub struct ProxyConfig {
     pub proxy_type: ProxyType,
     /// Authentication for the proxy (optional)
     pub auth: Option<ProxyAuth>,
+    /// Resolve target hostnames through a SOCKS5 proxy (`socks5h`) instead of locally.
+    ///
+    /// HTTP(S) proxies always resolve hostnames themselves, so this only affects SOCKS5.
+    /// A `socks5h://` URL enables it regardless of this flag.
+    pub remote_dns: bool,
+    /// Hosts reached directly instead of through the proxy.
+    ///
+    /// An entry matches the host itself and its subdomains (`example.com` and `.example.com`
+    /// both match `cdn.example.com`); `*` matches every host.
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Whether requests to `host` bypass the proxy according to `no_proxy`
+    pub fn bypasses(&self, host: &str) -> bool {
+        self.no_proxy
+            .iter()
+            .any(|entry| entry.trim() == "*" || host_matches_entry(host, entry))
+    }
+}
+
+/// Proxy settings replacing the configured ones for the media requests of a protocol
+#[derive(Debug, Clone)]
+pub enum ProxyOverride {
+    /// Connect directly, without any proxy
+    Direct,
+    /// Use the system proxy settings
+    System,
+    /// Use this proxy
+    Proxy(ProxyConfig),
 }
 
 fn normalize_proxy_url(proxy_url: &str, proxy_type: ProxyType) -> String {
@@ -47,16 +82,42 @@ fn normalize_proxy_url(proxy_url: &str, proxy_type: ProxyType) -> String {
     }
 }
 
+/// Resolve the URL of the proxy server, checking its scheme against the proxy type.
+fn resolve_proxy_url(config: &ProxyConfig) -> Result<String, String> {
+    let proxy_url = normalize_proxy_url(&config.url, config.proxy_type);
+    let Some((scheme, rest)) = proxy_url.split_once("://") else {
+        return Err(format!("Invalid proxy URL: {proxy_url}"));
+    };
+
+    match (config.proxy_type, scheme.to_ascii_lowercase().as_str()) {
+        (ProxyType::Http, "http") | (ProxyType::Https, "https") => Ok(proxy_url),
+        (ProxyType::Socks5, "socks5h") => Ok(proxy_url),
+        (ProxyType::Socks5, "socks5") if config.remote_dns => Ok(format!("socks5h://{rest}")),
+        (ProxyType::Socks5, "socks5") => Ok(proxy_url),
+        (proxy_type, scheme) => Err(format!(
+            "Proxy URL scheme `{scheme}` does not match proxy type {proxy_type:?}"
+        )),
+    }
+}
+
 /// Build a reqwest `Proxy` object from our proxy configuration.
 pub fn build_proxy_from_config(config: &ProxyConfig) -> Result<Proxy, String> {
-    let proxy_url = match config.proxy_type {
-        ProxyType::Socks5 if config.url.starts_with("socks5h://") => config.url.clone(),
-        proxy_type => normalize_proxy_url(&config.url, proxy_type),
-    };
+    let proxy_url = resolve_proxy_url(config)?;
 
     // Use `all` so both http and https requests follow the configured proxy.
     let mut proxy = Proxy::all(&proxy_url).map_err(|e| format!("Invalid proxy URL: {e}"))?;
 
+    // Hosts in `no_proxy` are matched by `ProxyConfig::bypasses`, so the client and
+    // callers agree on which requests go direct.
+    if !config.no_proxy.is_empty() {
+        let target = Url::parse(&proxy_url).map_err(|e| format!("Invalid proxy URL: {e}"))?;
+        let config = config.clone();
+        proxy = Proxy::custom(move |url| match url.host_str() {
+            Some(host) if config.bypasses(host) => None,
+            _ => Some(target.clone()),
+        });
+    }
+
     // Add authentication if provided
     if let Some(auth) = &config.auth {
         proxy = proxy.basic_auth(&auth.username, &auth.password);
@@ -107,6 +168,8 @@ mod tests {
             url: "proxy.example.com:8080".to_string(),
             proxy_type: ProxyType::Http,
             auth: None,
+            remote_dns: false,
+            no_proxy: Vec::new(),
         };
 
         build_proxy_from_config(&config).expect("proxy should build with implicit scheme");
@@ -118,8 +181,74 @@ mod tests {
             url: "socks5h://proxy.example.com:1080".to_string(),
             proxy_type: ProxyType::Socks5,
             auth: None,
+            remote_dns: false,
+            no_proxy: Vec::new(),
         };
 
         build_proxy_from_config(&config).expect("socks5h proxy should build");
     }
+
+    fn socks5_config(url: &str, remote_dns: bool) -> ProxyConfig {
+        ProxyConfig {
+            url: url.to_string(),
+            proxy_type: ProxyType::Socks5,
+            auth: None,
+            remote_dns,
+            no_proxy: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn remote_dns_uses_socks5h() {
+        for url in ["proxy.example.com:1080", "socks5://proxy.example.com:1080"] {
+            let config = socks5_config(url, true);
+            assert_eq!(
+                resolve_proxy_url(&config).unwrap(),
+                "socks5h://proxy.example.com:1080"
+            );
+            let proxy = build_proxy_from_config(&config).unwrap();
+            assert!(format!("{proxy:?}").contains("socks5h"), "{proxy:?}");
+        }
+
+        let config = socks5_config("proxy.example.com:1080", false);
+        let proxy = format!("{:?}", build_proxy_from_config(&config).unwrap());
+        assert!(
+            proxy.contains("socks5") && !proxy.contains("socks5h"),
+            "{proxy}"
+        );
+    }
+
+    #[test]
+    fn scheme_must_match_proxy_type() {
+        let mut config = socks5_config("socks5://proxy.example.com:1080", false);
+        config.proxy_type = ProxyType::Http;
+        let err = build_proxy_from_config(&config).unwrap_err();
+        assert!(err.contains("`socks5`") && err.contains("Http"), "{err}");
+
+        let config = socks5_config("http://proxy.example.com:8080", true);
+        assert!(build_proxy_from_config(&config).is_err());
+
+        let mut config = socks5_config("HTTPS://proxy.example.com:443", false);
+        config.proxy_type = ProxyType::Https;
+        build_proxy_from_config(&config).expect("scheme is case-insensitive");
+    }
+
+    #[test]
+    fn no_proxy_matches_hosts_and_subdomains() {
+        let mut config = socks5_config("proxy.example.com:1080", true);
+        config.no_proxy = vec!["cdn.example.com".to_string(), ".local".to_string()];
+
+        assert!(config.bypasses("cdn.example.com"));
+        assert!(config.bypasses("edge.cdn.example.com"));
+        assert!(config.bypasses("CDN.Example.com"));
+        assert!(config.bypasses("nas.local"));
+        assert!(!config.bypasses("example.com"));
+        assert!(!config.bypasses("notcdn.example.com"));
+        assert!(!config.bypasses("local"));
+
+        config.no_proxy = vec!["*".to_string()];
+        assert!(config.bypasses("anything.example.org"));
+
+        build_proxy_from_config(&config).expect("proxy with bypass list should build");
+    }
 }
@@ -0,0 +1,635 @@
+//! # Request Retries
+//!
+//! Retries of individual HTTP requests with exponential backoff. The FLV connection, HLS
+//! playlist fetches and segment and key downloads all go through [`retry_with_backoff`], driven
+//! by the [`RetryPolicy`] of `DownloaderConfig`.
+//!
+//! Each failed attempt is classified into a [`RetryCondition`]. Only the conditions listed in
+//! the policy are retried, everything else fails the request immediately. Rate limited
+//! responses (429 and 503) wait at least as long as their `Retry-After` header asks.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use pipeline_common::ProgressEvent;
+use rand::RngExt;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Response, StatusCode};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::DownloadError;
+use crate::throttle::OnProgress;
+
+/// Kind of transient failure a request can be retried on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetryCondition {
+    /// The connection could not be established or was closed before a response
+    Connect,
+    /// The host name could not be resolved
+    Dns,
+    /// The request or a read of the response timed out
+    Timeout,
+    /// The response body was cut off or could not be decoded
+    Interrupted,
+    /// HTTP 429 or 503, waiting for the `Retry-After` the server asks for
+    RateLimited,
+    /// Any other HTTP 5xx
+    ServerError,
+}
+
+impl RetryCondition {
+    /// Every condition, the default of [`RetryPolicy::retry_on`]
+    pub const ALL: [RetryCondition; 6] = [
+        RetryCondition::Connect,
+        RetryCondition::Dns,
+        RetryCondition::Timeout,
+        RetryCondition::Interrupted,
+        RetryCondition::RateLimited,
+        RetryCondition::ServerError,
+    ];
+
+    /// Classify a failed request, `None` if retrying cannot help (invalid request, redirect
+    /// loop)
+    pub fn from_request_error(error: &reqwest::Error) -> Option<Self> {
+        if error.is_timeout() {
+            Some(Self::Timeout)
+        } else if error.is_connect() {
+            Some(if is_dns_failure(error) {
+                Self::Dns
+            } else {
+                Self::Connect
+            })
+        } else if error.is_body() || error.is_decode() {
+            Some(Self::Interrupted)
+        } else if error.is_request() {
+            Some(Self::Connect)
+        } else {
+            None
+        }
+    }
+
+    /// Classify an unsuccessful response status, `None` for client errors other than 429
+    pub fn from_status(status: StatusCode) -> Option<Self> {
+        match status {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                Some(Self::RateLimited)
+            }
+            status if status.is_server_error() => Some(Self::ServerError),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a connect error was caused by name resolution. reqwest does not expose this, so
+/// the messages of the underlying errors are inspected.
+fn is_dns_failure(error: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(err) = source {
+        let message = err.to_string().to_ascii_lowercase();
+        if message.contains("dns error") || message.contains("failed to lookup address") {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Delay requested by a `Retry-After` header, given in seconds or as an HTTP date
+pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// How failed requests are retried
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts made in total, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Cap on the delay between attempts, `Retry-After` excluded
+    pub max_backoff: Duration,
+    /// Cap on the delay a `Retry-After` header can ask for, so that a bogus value does not
+    /// stall the download
+    pub max_retry_after: Duration,
+    /// Factor applied to the delay after each retry
+    pub multiplier: f64,
+    /// Add up to half of `initial_backoff` at random to each delay, so that downloads failing
+    /// together do not retry together
+    pub jitter: bool,
+    /// Failures that are retried
+    pub retry_on: Vec<RetryCondition>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            max_retry_after: Duration::from_secs(300),
+            multiplier: 2.0,
+            jitter: true,
+            retry_on: RetryCondition::ALL.to_vec(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy making a single attempt
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Whether failures of this kind are retried
+    pub fn retries_on(&self, condition: RetryCondition) -> bool {
+        self.retry_on.contains(&condition)
+    }
+
+    /// Delay before retry number `retry` (0-indexed), without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(i32::try_from(retry).unwrap_or(i32::MAX));
+        let nanos = self.initial_backoff.as_nanos() as f64 * factor;
+        if nanos.is_finite() && nanos < self.max_backoff.as_nanos() as f64 {
+            Duration::from_nanos(nanos.round() as u64)
+        } else {
+            self.max_backoff
+        }
+    }
+
+    /// Delay before retry number `retry`, honoring the `Retry-After` of the failed attempt
+    fn delay_for_retry(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self.backoff(retry);
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_retry_after).max(backoff);
+        }
+        if !self.jitter {
+            return backoff;
+        }
+
+        // Jitter is limited so the final delay never exceeds `max_backoff`.
+        let jitter_range_ms =
+            u64::try_from(self.initial_backoff.as_millis()).unwrap_or(u64::MAX) / 2;
+        let remaining_ms =
+            u64::try_from(self.max_backoff.saturating_sub(backoff).as_millis()).unwrap_or(0);
+        let jitter_limit_ms = jitter_range_ms.min(remaining_ms);
+        if jitter_limit_ms == 0 {
+            return backoff;
+        }
+
+        let jitter_ms = rand::rng().random_range(0..jitter_limit_ms);
+        (backoff + Duration::from_millis(jitter_ms)).min(self.max_backoff)
+    }
+}
+
+/// Result of a single attempt, used by the caller to signal retryability.
+pub enum RetryAction<T> {
+    /// Operation succeeded.
+    Success(T),
+    /// Operation failed transiently, retried if the policy retries on `condition`.
+    Retry {
+        error: DownloadError,
+        condition: RetryCondition,
+        /// Delay asked for by the server
+        retry_after: Option<Duration>,
+    },
+    /// Operation failed with a non-retryable error (4xx, parse error).
+    Fail(DownloadError),
+}
+
+impl<T> RetryAction<T> {
+    /// A transient failure of the given kind
+    pub fn transient(error: DownloadError, condition: RetryCondition) -> Self {
+        Self::Retry {
+            error,
+            condition,
+            retry_after: None,
+        }
+    }
+
+    /// Classify a failed request
+    pub fn from_request_error(error: reqwest::Error) -> Self {
+        match RetryCondition::from_request_error(&error) {
+            Some(condition) => Self::transient(error.into(), condition),
+            None => Self::Fail(error.into()),
+        }
+    }
+
+    /// Classify an unsuccessful response, described by `error`
+    pub fn from_response(response: &Response, error: DownloadError) -> Self {
+        match RetryCondition::from_status(response.status()) {
+            Some(condition) => Self::Retry {
+                error,
+                condition,
+                retry_after: (condition == RetryCondition::RateLimited)
+                    .then(|| parse_retry_after(response.headers()))
+                    .flatten(),
+            },
+            None => Self::Fail(error),
+        }
+    }
+
+    /// Classify the error of an operation that sent its own requests
+    pub fn from_error(error: DownloadError) -> Self {
+        let condition = match &error {
+            DownloadError::Network { source } => RetryCondition::from_request_error(source),
+            DownloadError::Http { status, .. } => RetryCondition::from_status(*status),
+            DownloadError::Timeout { .. } => Some(RetryCondition::Timeout),
+            _ => None,
+        };
+        match condition {
+            Some(condition) => Self::Retry {
+                retry_after: (condition == RetryCondition::RateLimited)
+                    .then(|| error.retry_after())
+                    .flatten(),
+                error,
+                condition,
+            },
+            None => Self::Fail(error),
+        }
+    }
+}
+
+/// Execute an async operation with retry-and-backoff.
+///
+/// The `operation` closure receives the current attempt number (0-indexed) and
+/// returns a [`RetryAction`] indicating whether the result is a success,
+/// retryable failure, or permanent failure. Each retry is logged and reported to
+/// `on_progress` as [`ProgressEvent::RetryScheduled`].
+pub async fn retry_with_backoff<F, Fut, T>(
+    policy: &RetryPolicy,
+    url: &str,
+    on_progress: Option<&OnProgress>,
+    token: &CancellationToken,
+    operation: F,
+) -> Result<T, DownloadError>
+where
+    F: Fn(u32) -> Fut,
+    Fut: Future<Output = RetryAction<T>>,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        if token.is_cancelled() {
+            return Err(DownloadError::Cancelled);
+        }
+
+        let (error, condition, retry_after) = match operation(attempt).await {
+            RetryAction::Success(value) => return Ok(value),
+            RetryAction::Fail(err) => return Err(err),
+            RetryAction::Retry {
+                error,
+                condition,
+                retry_after,
+            } => (error, condition, retry_after),
+        };
+        attempt += 1;
+        if attempt >= max_attempts || !policy.retries_on(condition) {
+            return Err(error);
+        }
+
+        let delay = policy.delay_for_retry(attempt - 1, retry_after);
+        warn!(
+            url,
+            attempt,
+            max_attempts,
+            ?condition,
+            delay_ms = delay.as_millis() as u64,
+            error = %error,
+            "Retrying after transient error"
+        );
+        if let Some(on_progress) = on_progress {
+            on_progress.emit(ProgressEvent::RetryScheduled {
+                url: Arc::from(url),
+                attempt,
+                max_attempts,
+                delay,
+                reason: error.to_string(),
+            });
+        }
+        tokio::select! {
+            _ = token.cancelled() => {
+                return Err(DownloadError::Cancelled);
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flv::FlvDownloader;
+    use crate::flv::FlvProtocolConfig;
+    use futures::StreamExt;
+    use parking_lot::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn policy(max_attempts: u32, initial_backoff: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff,
+            max_backoff: Duration::from_secs(10),
+            max_retry_after: Duration::from_secs(300),
+            multiplier: 2.0,
+            jitter: false,
+            retry_on: RetryCondition::ALL.to_vec(),
+        }
+    }
+
+    fn server_error() -> DownloadError {
+        DownloadError::http_status(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "http://localhost/",
+            "test",
+        )
+    }
+
+    /// Serves `failures` in order, one per request, then `body` with a 200
+    async fn spawn_flaky_server(
+        failures: Vec<&'static str>,
+        body: &'static [u8],
+    ) -> (String, Arc<AtomicU32>) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!("http://{}/live.flv", listener.local_addr().expect("addr"));
+        let requests = Arc::new(AtomicU32::new(0));
+        let counter = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let served = counter.fetch_add(1, Ordering::SeqCst) as usize;
+                let response = match failures.get(served) {
+                    Some(failure) => format!(
+                        "HTTP/1.1 {failure}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .into_bytes(),
+                    None => {
+                        let mut response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: video/x-flv\r\n\
+Content-Length: {}\r\nConnection: close\r\n\r\n",
+                            body.len()
+                        )
+                        .into_bytes();
+                        response.extend_from_slice(body);
+                        response
+                    }
+                };
+                let _ = socket.write_all(&response).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        (url, requests)
+    }
+
+    /// Collects the `(attempt, delay)` of each retry reported
+    fn retry_recorder() -> (OnProgress, Arc<Mutex<Vec<(u32, Duration)>>>) {
+        let retries = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&retries);
+        let on_progress = OnProgress::new(move |event| {
+            if let ProgressEvent::RetryScheduled { attempt, delay, .. } = event {
+                sink.lock().push((attempt, delay));
+            }
+        });
+        (on_progress, retries)
+    }
+
+    #[test]
+    fn backoff_grows_by_multiplier_up_to_cap() {
+        let policy = RetryPolicy {
+            multiplier: 3.0,
+            max_backoff: Duration::from_secs(1),
+            ..policy(10, Duration::from_millis(100))
+        };
+        let delays: Vec<Duration> = (0..4).map(|retry| policy.backoff(retry)).collect();
+        assert_eq!(
+            delays,
+            [100, 300, 900, 1000].map(Duration::from_millis).to_vec()
+        );
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn delay_with_jitter_stays_within_bounds() {
+        let policy = RetryPolicy {
+            jitter: true,
+            max_backoff: Duration::from_secs(1),
+            ..policy(3, Duration::from_millis(100))
+        };
+        // Base is 100ms, jitter range is [0, 50ms)
+        for _ in 0..32 {
+            let delay = policy.delay_for_retry(0, None);
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay < Duration::from_millis(150));
+            assert!(policy.delay_for_retry(10, None) <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn retry_after_is_not_capped_by_max_backoff() {
+        let policy = policy(5, Duration::from_millis(500));
+        assert_eq!(
+            policy.delay_for_retry(0, Some(Duration::from_secs(60))),
+            Duration::from_secs(60)
+        );
+        // Only the sanity cap applies
+        assert_eq!(
+            policy.delay_for_retry(0, Some(Duration::from_secs(3600))),
+            Duration::from_secs(300)
+        );
+        // A short Retry-After does not cut the backoff
+        assert_eq!(
+            policy.delay_for_retry(3, Some(Duration::from_millis(1))),
+            Duration::from_secs(4)
+        );
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(7)));
+
+        let date = (Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        headers.insert(RETRY_AFTER, date.parse().unwrap());
+        let delay = parse_retry_after(&headers).unwrap();
+        assert!(delay > Duration::from_secs(25) && delay <= Duration::from_secs(30));
+
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert(RETRY_AFTER, "soon".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn statuses_are_classified() {
+        assert_eq!(
+            RetryCondition::from_status(StatusCode::TOO_MANY_REQUESTS),
+            Some(RetryCondition::RateLimited)
+        );
+        assert_eq!(
+            RetryCondition::from_status(StatusCode::SERVICE_UNAVAILABLE),
+            Some(RetryCondition::RateLimited)
+        );
+        assert_eq!(
+            RetryCondition::from_status(StatusCode::BAD_GATEWAY),
+            Some(RetryCondition::ServerError)
+        );
+        assert_eq!(RetryCondition::from_status(StatusCode::NOT_FOUND), None);
+    }
+
+    #[tokio::test]
+    async fn retry_exhausts_attempts_then_fails() {
+        let attempts = AtomicU32::new(0);
+        let result: Result<u32, _> = retry_with_backoff(
+            &policy(3, Duration::from_millis(1)),
+            "http://localhost/",
+            None,
+            &CancellationToken::new(),
+            |_| {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                async { RetryAction::transient(server_error(), RetryCondition::ServerError) }
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(DownloadError::Http { .. })));
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_respects_cancellation() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let result: Result<u32, _> = retry_with_backoff(
+            &policy(10, Duration::from_secs(100)),
+            "http://localhost/",
+            None,
+            &token,
+            |_| async { RetryAction::Success(1u32) },
+        )
+        .await;
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn flv_connection_backs_off_until_server_recovers() {
+        const FLV_HEADER: &[u8] = b"FLV\x01\x05\x00\x00\x00\x09\x00\x00\x00\x00";
+        let (url, requests) = spawn_flaky_server(
+            vec![
+                "500 Internal Server Error",
+                "502 Bad Gateway",
+                "500 Internal Server Error",
+            ],
+            FLV_HEADER,
+        )
+        .await;
+        let (on_progress, retries) = retry_recorder();
+        let mut config = FlvProtocolConfig::default();
+        config.base.retry_policy = policy(5, Duration::from_millis(20));
+        config.base.on_progress = Some(on_progress);
+
+        let downloader = FlvDownloader::with_config(config).unwrap();
+        let mut stream = downloader
+            .download_flv(&url, CancellationToken::new())
+            .await
+            .expect("connects after the server recovers");
+        assert!(stream.next().await.expect("header").is_ok());
+
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+        assert_eq!(
+            *retries.lock(),
+            vec![
+                (1, Duration::from_millis(20)),
+                (2, Duration::from_millis(40)),
+                (3, Duration::from_millis(80)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rate_limited_response_waits_for_retry_after() {
+        let (url, requests) =
+            spawn_flaky_server(vec!["429 Too Many Requests\r\nRetry-After: 1"], b"ok").await;
+        let (on_progress, retries) = retry_recorder();
+        let client = reqwest::Client::new();
+
+        let body = retry_with_backoff(
+            &policy(3, Duration::from_millis(10)),
+            &url,
+            Some(&on_progress),
+            &CancellationToken::new(),
+            |_| async {
+                let response = match client.get(&url).send().await {
+                    Ok(response) => response,
+                    Err(e) => return RetryAction::from_request_error(e),
+                };
+                if !response.status().is_success() {
+                    let error = DownloadError::http_status(response.status(), &url, "test");
+                    return RetryAction::from_response(&response, error);
+                }
+                match response.bytes().await {
+                    Ok(body) => RetryAction::Success(body),
+                    Err(e) => RetryAction::from_request_error(e),
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(&body[..], b"ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        assert_eq!(*retries.lock(), vec![(1, Duration::from_secs(1))]);
+    }
+
+    #[tokio::test]
+    async fn conditions_not_listed_fail_immediately() {
+        let (url, requests) = spawn_flaky_server(vec!["503 Service Unavailable"], b"").await;
+        let mut config = FlvProtocolConfig::default();
+        config.base.retry_policy = RetryPolicy {
+            retry_on: vec![RetryCondition::Connect, RetryCondition::Dns],
+            ..policy(5, Duration::from_millis(1))
+        };
+
+        let downloader = FlvDownloader::with_config(config).unwrap();
+        let result = downloader
+            .download_flv(&url, CancellationToken::new())
+            .await;
+        assert!(matches!(
+            result,
+            Err(DownloadError::Http { status, .. }) if status == StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -99,7 +99,7 @@ impl ContentSource {
 }
 
 /// Manager for handling multiple content sources
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SourceManager {
     /// Available content sources
     sources: Vec<ContentSource>,
@@ -258,6 +258,28 @@ impl SourceManager {
         source
     }
 
+    /// Select a source for the next request, skipping the given URLs
+    ///
+    /// Used to fail over from a source that just failed without selecting it again.
+    pub fn select_source_excluding(&mut self, excluded: &[&str]) -> Option<ContentSource> {
+        let mut restore = Vec::with_capacity(excluded.len());
+        for url in excluded {
+            if let Some(health) = self.health.get_mut(*url) {
+                restore.push((*url, health.active));
+                health.active = false;
+            }
+        }
+
+        let source = self.select_source();
+
+        for (url, active) in restore {
+            if let Some(health) = self.health.get_mut(url) {
+                health.active = active;
+            }
+        }
+        source
+    }
+
     /// Select a source using the priority strategy
     fn select_by_priority(&self) -> Option<ContentSource> {
         // Sources are kept sorted by priority.
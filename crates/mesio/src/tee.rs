@@ -0,0 +1,415 @@
+//! # Raw Tee
+//!
+//! A copy of the bytes received from the origin, kept next to the processed output for
+//! debugging. Bodies are copied as received, before any protocol parsing, so the copy holds
+//! exactly what the origin served: the continuous body of an FLV download, and every HLS
+//! segment and playlist snapshot in a sink of its own.
+//!
+//! Each sink is written by a task of its own through a buffered writer, so a slow disk never
+//! holds back the download. A sink failing to open or write disables the tee with a warning;
+//! the download carries on without it.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures::Stream;
+use futures::future::BoxFuture;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+use tokio::sync::{Notify, mpsc};
+use tracing::warn;
+
+/// Buffer of the writer of each sink
+const SINK_BUFFER_SIZE: usize = 256 * 1024;
+
+/// What a sink of the tee receives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeeKind {
+    /// The body of a continuous stream (FLV)
+    Stream,
+    /// One media segment (HLS)
+    Segment,
+    /// One snapshot of a playlist (HLS)
+    Playlist,
+}
+
+impl fmt::Display for TeeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TeeKind::Stream => "stream",
+            TeeKind::Segment => "segment",
+            TeeKind::Playlist => "playlist",
+        })
+    }
+}
+
+/// The body a sink of the tee is opened for
+#[derive(Debug, Clone)]
+pub struct TeeTarget {
+    pub kind: TeeKind,
+    /// URL the body is received from
+    pub url: String,
+    /// Number of the sink among those of the tee, starting at 0
+    pub sequence: u64,
+}
+
+impl TeeTarget {
+    /// Last path segment of the URL, or the kind when the path has none
+    pub fn file_name(&self) -> String {
+        url::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| {
+                url.path_segments()?
+                    .next_back()
+                    .filter(|segment| !segment.is_empty())
+                    .map(str::to_owned)
+            })
+            .unwrap_or_else(|| self.kind.to_string())
+    }
+}
+
+/// Writer of one sink
+pub type TeeWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+type SinkFactory =
+    Arc<dyn Fn(TeeTarget) -> BoxFuture<'static, io::Result<TeeWriter>> + Send + Sync>;
+
+#[derive(Debug, Default)]
+struct TeeState {
+    disabled: AtomicBool,
+    bytes: AtomicU64,
+    sequence: AtomicU64,
+    open_sinks: AtomicUsize,
+    closed: Notify,
+}
+
+/// Copies the bodies received by downloads to raw sinks.
+///
+/// Clones share their sinks, statistics and failure state.
+#[derive(Clone)]
+pub struct RawTee {
+    factory: SinkFactory,
+    state: Arc<TeeState>,
+}
+
+impl RawTee {
+    /// Write each sink to the writer `factory` opens for it
+    pub fn new<F, Fut, W>(factory: F) -> Self
+    where
+        F: Fn(TeeTarget) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<W>> + Send + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let factory: SinkFactory =
+            Arc::new(move |target| -> BoxFuture<'static, io::Result<TeeWriter>> {
+                let open = factory(target);
+                Box::pin(async move { Ok(Box::new(open.await?) as TeeWriter) })
+            });
+        Self {
+            factory,
+            state: Arc::default(),
+        }
+    }
+
+    /// Write each sink to the file at `template`, where `{kind}` is replaced with the kind
+    /// of the sink, `{seq}` with its number and `{name}` with the file name of its URL.
+    /// Missing directories are created.
+    pub fn path_template(template: impl Into<String>) -> Self {
+        let template = template.into();
+        Self::new(move |target| {
+            let path = PathBuf::from(expand_template(&template, &target));
+            async move {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                tokio::fs::File::create(path).await
+            }
+        })
+    }
+
+    /// Bytes written to the sinks so far
+    pub fn bytes_written(&self) -> u64 {
+        self.state.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Whether bodies are still copied, false once a sink failed
+    pub fn is_enabled(&self) -> bool {
+        !self.state.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Wait until every sink opened so far has been flushed and closed
+    pub async fn closed(&self) {
+        loop {
+            // Registered before the check, so a sink closing in between is not missed
+            let closed = self.state.closed.notified();
+            if self.state.open_sinks.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            closed.await;
+        }
+    }
+
+    /// Open a sink for a body received from `url`, or `None` once the tee is disabled
+    pub(crate) fn open(&self, kind: TeeKind, url: &str) -> Option<TeeSink> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let target = TeeTarget {
+            kind,
+            url: url.to_owned(),
+            sequence: self.state.sequence.fetch_add(1, Ordering::Relaxed),
+        };
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.state.open_sinks.fetch_add(1, Ordering::AcqRel);
+        tokio::spawn(write_sink(
+            (self.factory)(target.clone()),
+            target,
+            receiver,
+            Arc::clone(&self.state),
+        ));
+        Some(TeeSink {
+            sender,
+            state: Arc::clone(&self.state),
+        })
+    }
+
+    /// Copy a complete body received from `url` to a sink of its own
+    pub(crate) fn write(&self, kind: TeeKind, url: &str, body: &Bytes) {
+        if let Some(sink) = self.open(kind, url) {
+            sink.write(body);
+        }
+    }
+}
+
+impl fmt::Debug for RawTee {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawTee")
+            .field("enabled", &self.is_enabled())
+            .field("bytes_written", &self.bytes_written())
+            .finish()
+    }
+}
+
+fn expand_template(template: &str, target: &TeeTarget) -> String {
+    template
+        .replace("{kind}", &target.kind.to_string())
+        .replace("{seq}", &target.sequence.to_string())
+        .replace("{name}", &target.file_name())
+}
+
+/// Write the chunks of one sink until it is dropped
+async fn write_sink(
+    open: BoxFuture<'static, io::Result<TeeWriter>>,
+    target: TeeTarget,
+    mut receiver: mpsc::UnboundedReceiver<Bytes>,
+    state: Arc<TeeState>,
+) {
+    let result = async {
+        let mut writer = BufWriter::with_capacity(SINK_BUFFER_SIZE, open.await?);
+        while let Some(chunk) = receiver.recv().await {
+            writer.write_all(&chunk).await?;
+            state.bytes.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+        writer.shutdown().await
+    }
+    .await;
+
+    if let Err(error) = result
+        && !state.disabled.swap(true, Ordering::AcqRel)
+    {
+        warn!(
+            kind = %target.kind,
+            url = %target.url,
+            error = %error,
+            "Raw tee sink failed, disabling the tee"
+        );
+    }
+    if state.open_sinks.fetch_sub(1, Ordering::AcqRel) == 1 {
+        state.closed.notify_waiters();
+    }
+}
+
+/// One open sink of a [`RawTee`], closed when dropped
+pub(crate) struct TeeSink {
+    sender: mpsc::UnboundedSender<Bytes>,
+    state: Arc<TeeState>,
+}
+
+impl TeeSink {
+    pub(crate) fn write(&self, chunk: &Bytes) {
+        if !chunk.is_empty() && !self.state.disabled.load(Ordering::Relaxed) {
+            // The writer has stopped when this fails, and has disabled the tee
+            let _ = self.sender.send(chunk.clone());
+        }
+    }
+}
+
+/// A response body copied to a sink of a [`RawTee`] as it is read
+pub struct TeeStream<S> {
+    inner: Pin<Box<S>>,
+    sink: Option<TeeSink>,
+}
+
+impl<S> TeeStream<S> {
+    /// Copy `body`, received from `url`, to a new sink of `tee` when there is one
+    pub(crate) fn new(tee: Option<&RawTee>, kind: TeeKind, url: &str, body: S) -> Self {
+        Self {
+            inner: Box::pin(body),
+            sink: tee.and_then(|tee| tee.open(kind, url)),
+        }
+    }
+}
+
+impl<S, E> Stream for TeeStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.as_mut().poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(sink) = &self.sink {
+                    sink.write(chunk);
+                }
+            }
+            // Close the sink as soon as the body ends
+            Poll::Ready(None) => self.sink = None,
+            _ => {}
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use parking_lot::Mutex;
+    use std::collections::BTreeMap;
+
+    /// Sink contents by sink number
+    type Written = Arc<Mutex<BTreeMap<u64, Vec<u8>>>>;
+
+    /// A writer appending to the entry of its sink
+    struct MemoryWriter {
+        sequence: u64,
+        written: Written,
+        /// Fail once this many bytes were written
+        fail_after: Option<usize>,
+    }
+
+    impl AsyncWrite for MemoryWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let mut written = self.written.lock();
+            let entry = written.entry(self.sequence).or_default();
+            if self.fail_after.is_some_and(|limit| entry.len() >= limit) {
+                return Poll::Ready(Err(io::Error::other("disk full")));
+            }
+            entry.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn memory_tee(fail_after: Option<usize>) -> (RawTee, Written) {
+        let written = Written::default();
+        let sinks = Arc::clone(&written);
+        let tee = RawTee::new(move |target| {
+            let writer = MemoryWriter {
+                sequence: target.sequence,
+                written: Arc::clone(&sinks),
+                fail_after,
+            };
+            async move { io::Result::Ok(writer) }
+        });
+        (tee, written)
+    }
+
+    fn body(chunks: usize) -> (Vec<u8>, impl Stream<Item = Result<Bytes, io::Error>>) {
+        let chunks: Vec<Bytes> = (0..chunks)
+            .map(|i| Bytes::from(vec![i as u8; 64 * 1024]))
+            .collect();
+        let all = chunks.concat();
+        (all, futures::stream::iter(chunks.into_iter().map(Ok)))
+    }
+
+    #[tokio::test]
+    async fn test_stream_is_copied_as_received() {
+        let (tee, written) = memory_tee(None);
+        let (served, body) = body(10);
+
+        let stream = TeeStream::new(Some(&tee), TeeKind::Stream, "http://cdn/live.flv", body);
+        let received: Vec<Bytes> = stream.map(Result::unwrap).collect().await;
+        tee.closed().await;
+
+        assert_eq!(received.concat(), served);
+        assert_eq!(written.lock()[&0], served);
+        assert_eq!(tee.bytes_written(), served.len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_failing_sink_disables_tee_without_failing_body() {
+        let (tee, written) = memory_tee(Some(100 * 1024));
+        let (served, body) = body(10);
+
+        let stream = TeeStream::new(Some(&tee), TeeKind::Stream, "http://cdn/live.flv", body);
+        let received: Vec<Bytes> = stream.map(Result::unwrap).collect().await;
+        tee.closed().await;
+
+        assert_eq!(received.concat(), served);
+        assert!(!tee.is_enabled());
+        assert!(written.lock()[&0].len() < served.len());
+
+        // Later bodies are not copied
+        assert!(tee.open(TeeKind::Segment, "http://cdn/seg1.ts").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_path_template_writes_a_file_per_sink() {
+        let dir = std::env::temp_dir().join(format!("mesio-tee-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let template = format!("{}/{{kind}}/{{seq}}-{{name}}", dir.display());
+        let tee = RawTee::path_template(template);
+
+        tee.write(
+            TeeKind::Playlist,
+            "http://cdn/live/index.m3u8?token=1",
+            &Bytes::from_static(b"#EXTM3U\n"),
+        );
+        tee.write(
+            TeeKind::Segment,
+            "http://cdn/live/seg7.ts",
+            &Bytes::from_static(&[0x47; 188]),
+        );
+        tee.closed().await;
+
+        assert_eq!(
+            std::fs::read(dir.join("playlist/0-index.m3u8")).unwrap(),
+            b"#EXTM3U\n"
+        );
+        assert_eq!(
+            std::fs::read(dir.join("segment/1-seg7.ts")).unwrap(),
+            vec![0x47; 188]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
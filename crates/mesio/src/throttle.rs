@@ -0,0 +1,503 @@
+//! # Download Throttling
+//!
+//! Bandwidth limiting and throughput reporting for HTTP response bodies. FLV streams, HLS
+//! segments and raw downloads all read their bodies through a [`ThrottledStream`], so the same
+//! limits apply to every protocol.
+//!
+//! Limits are token buckets holding up to one second of data. A download is limited by its own
+//! bucket (`DownloaderConfig::max_bytes_per_sec`) and by a bucket shared with the other downloads
+//! of the same `DownloadManager`. Chunks are passed through as received and the stream waits
+//! after a chunk that overdrew a bucket until it has refilled.
+//!
+//! The throttle also records the connection each response arrived on, so the reported
+//! statistics show how many requests of a download reused a pooled connection.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::Stream;
+use hyper_util::client::legacy::connect::HttpInfo;
+use parking_lot::Mutex;
+use pipeline_common::{
+    ConnectionStats, DownloadRate, ProgressEvent, ProgressTracker, SegmentWindowStats,
+};
+use tokio::time::{Instant, Sleep};
+
+use crate::DownloaderConfig;
+use crate::tee::RawTee;
+
+/// Callback receiving download progress events
+#[derive(Clone)]
+pub struct OnProgress(Arc<dyn Fn(ProgressEvent) + Send + Sync>);
+
+impl OnProgress {
+    pub fn new(callback: impl Fn(ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub fn emit(&self, event: ProgressEvent) {
+        (self.0)(event)
+    }
+
+    /// Follow each event with a [`ProgressEvent::Metrics`] of smoothed rates and ETA
+    pub fn tracked(self) -> Self {
+        Self::new(ProgressTracker::wrap(move |event| self.emit(event)))
+    }
+}
+
+impl fmt::Debug for OnProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnProgress")
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket limiting the throughput of the downloads sharing it
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `bytes_per_sec`, with a burst of one second of data
+    pub fn new(bytes_per_sec: u64) -> Self {
+        let bytes_per_sec = bytes_per_sec.max(1);
+        Self {
+            bytes_per_sec,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: bytes_per_sec as f64,
+                updated: Instant::now(),
+            })),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait until it is no longer overdrawn
+    fn reserve(&self, bytes: usize) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let mut bucket = self.bucket.lock();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * rate;
+        bucket.tokens = (bucket.tokens + refill).min(rate) - bytes as f64;
+        bucket.updated = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+}
+
+/// Local and remote address of a connection
+type ConnectionId = (SocketAddr, SocketAddr);
+
+/// Connections seen before a download forgets them and counts them as new again
+const MAX_TRACKED_CONNECTIONS: usize = 1024;
+
+/// Connections used by the requests of a download
+#[derive(Debug, Default)]
+struct ConnectionLog {
+    seen: Mutex<HashSet<ConnectionId>>,
+    requests: AtomicU64,
+    new_connections: AtomicU64,
+}
+
+impl ConnectionLog {
+    /// Record a request sent over `connection`, returning whether it was used before.
+    /// Requests over an unknown connection count as new ones.
+    fn record(&self, connection: Option<ConnectionId>) -> bool {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let reused = connection.is_some_and(|id| {
+            let mut seen = self.seen.lock();
+            if seen.len() >= MAX_TRACKED_CONNECTIONS && !seen.contains(&id) {
+                seen.clear();
+            }
+            !seen.insert(id)
+        });
+        if !reused {
+            self.new_connections.fetch_add(1, Ordering::Relaxed);
+        }
+        reused
+    }
+
+    fn stats(&self) -> ConnectionStats {
+        ConnectionStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            new_connections: self.new_connections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Occupancy of the segment download window of a download, kept up to date by the tasks
+/// downloading and reordering its segments
+#[derive(Debug, Default)]
+pub(crate) struct SegmentWindow {
+    in_flight: AtomicU64,
+    capacity: AtomicU64,
+    reorder_depth: AtomicU64,
+}
+
+impl SegmentWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: AtomicU64::new(capacity as u64),
+            ..Self::default()
+        }
+    }
+
+    pub fn set_in_flight(&self, segments: usize) {
+        self.in_flight.store(segments as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_reorder_depth(&self, segments: usize) {
+        self.reorder_depth.store(segments as u64, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> SegmentWindowStats {
+        SegmentWindowStats {
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            capacity: self.capacity.load(Ordering::Relaxed),
+            reorder_depth: self.reorder_depth.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Throughput of a download, reported at a fixed interval
+#[derive(Debug)]
+struct RateTracker {
+    url: Arc<str>,
+    connections: Arc<ConnectionLog>,
+    on_progress: OnProgress,
+    raw_tee: Option<RawTee>,
+    segment_window: Option<Arc<SegmentWindow>>,
+    interval: Duration,
+    started: Instant,
+    bytes: u64,
+    last_report: Instant,
+    bytes_at_last_report: u64,
+}
+
+impl RateTracker {
+    fn record(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+        if self.last_report.elapsed() >= self.interval {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        let now = Instant::now();
+        let since_last = now.duration_since(self.last_report).as_secs_f64();
+        let elapsed = now.duration_since(self.started);
+        let rate = |bytes: u64, secs: f64| if secs > 0.0 { bytes as f64 / secs } else { 0.0 };
+
+        self.on_progress.emit(ProgressEvent::DownloadProgress {
+            url: Arc::clone(&self.url),
+            rate: DownloadRate {
+                bytes_downloaded: self.bytes,
+                current_rate: rate(self.bytes - self.bytes_at_last_report, since_last),
+                average_rate: rate(self.bytes, elapsed.as_secs_f64()),
+                elapsed,
+                connections: self.connections.stats(),
+                raw_bytes: self.raw_tee.as_ref().map(RawTee::bytes_written),
+                segment_window: self.segment_window.as_deref().map(SegmentWindow::stats),
+            },
+        });
+        self.last_report = now;
+        self.bytes_at_last_report = self.bytes;
+    }
+
+    /// Report the bytes received since the last report when a body ends
+    fn flush(&mut self) {
+        if self.bytes > self.bytes_at_last_report {
+            self.report();
+        }
+    }
+}
+
+/// Limits and rate statistics of one download, shared by all of its response bodies
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    limiters: Vec<RateLimiter>,
+    tracker: Option<Arc<Mutex<RateTracker>>>,
+    connections: Arc<ConnectionLog>,
+}
+
+impl Throttle {
+    /// Create the throttle of a new download of `url`
+    pub fn for_download(config: &DownloaderConfig, url: &str) -> Self {
+        let limiters = config
+            .max_bytes_per_sec
+            .map(RateLimiter::new)
+            .into_iter()
+            .chain(config.shared_rate_limiter.clone())
+            .collect();
+        let connections = Arc::new(ConnectionLog::default());
+        let tracker = config.on_progress.clone().map(|on_progress| {
+            let now = Instant::now();
+            Arc::new(Mutex::new(RateTracker {
+                url: Arc::from(url),
+                connections: Arc::clone(&connections),
+                on_progress,
+                raw_tee: config.raw_tee.clone(),
+                segment_window: None,
+                interval: config.progress_interval,
+                started: now,
+                bytes: 0,
+                last_report: now,
+                bytes_at_last_report: 0,
+            }))
+        });
+        Self {
+            limiters,
+            tracker,
+            connections,
+        }
+    }
+
+    /// Report the occupancy of `window` with the throughput of the download
+    pub(crate) fn with_segment_window(self, window: Arc<SegmentWindow>) -> Self {
+        if let Some(tracker) = &self.tracker {
+            tracker.lock().segment_window = Some(window);
+        }
+        self
+    }
+
+    /// Record the connection a response of the download arrived on, returning whether the
+    /// request reused a connection of an earlier one
+    pub fn record_response(&self, response: &reqwest::Response) -> bool {
+        let connection = response
+            .extensions()
+            .get::<HttpInfo>()
+            .map(|info| (info.local_addr(), info.remote_addr()));
+        self.connections.record(connection)
+    }
+
+    /// Connection usage of the requests recorded so far
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.connections.stats()
+    }
+
+    /// Apply the throttle to a response body
+    pub fn wrap<S, E>(&self, stream: S) -> ThrottledStream<S>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+    {
+        ThrottledStream {
+            inner: Box::pin(stream),
+            throttle: self.clone(),
+            delay: None,
+            pending: None,
+        }
+    }
+
+    /// Whether bodies are limited or their throughput reported
+    pub fn is_active(&self) -> bool {
+        !self.limiters.is_empty() || self.tracker.is_some()
+    }
+
+    fn release(&self, bytes: usize) {
+        if let Some(tracker) = &self.tracker {
+            tracker.lock().record(bytes);
+        }
+    }
+}
+
+/// A response body limited by a [`Throttle`]
+pub struct ThrottledStream<S: Stream> {
+    inner: Pin<Box<S>>,
+    throttle: Throttle,
+    delay: Option<Pin<Box<Sleep>>>,
+    /// Chunk held back until `delay` elapses
+    pending: Option<S::Item>,
+}
+
+// The held back chunk is never pinned
+impl<S: Stream> Unpin for ThrottledStream<S> {}
+
+impl<S, E> Stream for ThrottledStream<S>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    type Item = Result<Bytes, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(delay) = &mut this.delay {
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.delay = None;
+            if let Some(Ok(chunk)) = &this.pending {
+                this.throttle.release(chunk.len());
+            }
+            return Poll::Ready(this.pending.take());
+        }
+
+        match this.inner.as_mut().poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                let wait = this
+                    .throttle
+                    .limiters
+                    .iter()
+                    .map(|limiter| limiter.reserve(chunk.len()))
+                    .max()
+                    .unwrap_or_default();
+                if wait.is_zero() {
+                    this.throttle.release(chunk.len());
+                    return Poll::Ready(Some(Ok(chunk)));
+                }
+                this.pending = Some(Ok(chunk));
+                this.delay = Some(Box::pin(tokio::time::sleep(wait)));
+                // Register the timer
+                self.poll_next(cx)
+            }
+            Poll::Ready(None) => {
+                if let Some(tracker) = &this.throttle.tracker {
+                    tracker.lock().flush();
+                }
+                Poll::Ready(None)
+            }
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    const MB: usize = 1024 * 1024;
+
+    /// A download delivering `total` bytes as fast as it is read
+    fn mock_body(total: usize) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+        let chunk = Bytes::from(vec![0u8; 64 * 1024]);
+        futures::stream::iter((0..total / chunk.len()).map(move |_| Ok(chunk.clone())))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_holds_throughput() {
+        let config = DownloaderConfig {
+            max_bytes_per_sec: Some(MB as u64),
+            ..Default::default()
+        };
+        let throttle = Throttle::for_download(&config, "http://localhost/stream.flv");
+
+        let started = Instant::now();
+        let received: usize = throttle
+            .wrap(mock_body(10 * MB))
+            .map(|chunk| chunk.unwrap().len())
+            .fold(0, |total, len| async move { total + len })
+            .await;
+        let elapsed = started.elapsed().as_secs_f64();
+
+        assert_eq!(received, 10 * MB);
+        // One second of burst, then 9 MB at 1 MB/s
+        assert!((8.5..=9.5).contains(&elapsed), "took {elapsed}s");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shared_limit_spans_downloads() {
+        let config = DownloaderConfig {
+            shared_rate_limiter: Some(RateLimiter::new(MB as u64)),
+            ..Default::default()
+        };
+        let first = Throttle::for_download(&config, "http://localhost/a.flv");
+        let second = Throttle::for_download(&config, "http://localhost/b.flv");
+
+        let started = Instant::now();
+        let drain = |throttle: Throttle| async move {
+            throttle
+                .wrap(mock_body(3 * MB))
+                .for_each(|chunk| {
+                    chunk.unwrap();
+                    futures::future::ready(())
+                })
+                .await
+        };
+        futures::join!(drain(first), drain(second));
+        let elapsed = started.elapsed().as_secs_f64();
+
+        // 6 MB through one 1 MB/s bucket
+        assert!((4.5..=5.5).contains(&elapsed), "took {elapsed}s");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_progress_events_carry_rates() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let config = DownloaderConfig {
+            max_bytes_per_sec: Some(MB as u64),
+            on_progress: Some(OnProgress::new(move |event| sink.lock().push(event))),
+            progress_interval: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let throttle = Throttle::for_download(&config, "http://localhost/stream.flv");
+        throttle
+            .wrap(mock_body(4 * MB))
+            .for_each(|_| futures::future::ready(()))
+            .await;
+
+        let rates: Vec<DownloadRate> = events
+            .lock()
+            .iter()
+            .map(|event| match event {
+                ProgressEvent::DownloadProgress { url, rate } => {
+                    assert_eq!(&**url, "http://localhost/stream.flv");
+                    rate.clone()
+                }
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect();
+
+        assert!(rates.len() >= 3, "{rates:?}");
+        let last = rates.last().unwrap();
+        assert_eq!(last.bytes_downloaded, 4 * MB as u64);
+        assert!(rates.windows(2).all(|w| w[0].elapsed < w[1].elapsed));
+        // Once the burst is spent, the current rate settles at the limit
+        let steady = &rates[rates.len() - 2];
+        let limit = MB as f64;
+        assert!(
+            (steady.current_rate - limit).abs() < limit * 0.1,
+            "{steady:?}"
+        );
+        assert!(last.average_rate > 0.0 && last.average_rate < 1.5 * limit);
+    }
+
+    #[test]
+    fn test_connection_log_counts_reuse() {
+        let log = ConnectionLog::default();
+        let local = |port| SocketAddr::from(([127, 0, 0, 1], port));
+        let first = (local(50000), local(8080));
+        let second = (local(50001), local(8080));
+
+        assert!(!log.record(Some(first)));
+        assert!(log.record(Some(first)));
+        assert!(!log.record(Some(second)));
+        assert!(log.record(Some(first)));
+        assert!(!log.record(None));
+
+        let stats = log.stats();
+        assert_eq!(stats.requests, 5);
+        assert_eq!(stats.new_connections, 3);
+        assert_eq!(stats.reused(), 2);
+    }
+}
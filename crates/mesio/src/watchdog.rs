@@ -0,0 +1,332 @@
+//! # Stall Watchdog
+//!
+//! An origin may keep a connection open and stop sending media, e.g. when a broadcast ends
+//! without closing the stream. Read timeouts do not catch this when the origin still sends
+//! something, such as script tags or a playlist that never advances.
+//!
+//! The watchdog wraps the media stream of a download and tracks the media position reached by
+//! its items: the timestamp of FLV audio and video tags, or the media time of the HLS segments
+//! received so far. Items that do not move the position, like headers or repeated timestamps,
+//! are passed through but do not count as progress. When the position has not moved for the
+//! configured threshold, the watchdog either ends the stream with
+//! [`DownloadError::StreamStalled`] or keeps waiting and emits a
+//! [`ProgressEvent::Stalled`] every threshold until media resumes.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use pipeline_common::ProgressEvent;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::DownloadError;
+use crate::media_protocol::BoxMediaStream;
+use crate::throttle::OnProgress;
+
+/// What a download does once its stream stalls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StallAction {
+    /// End the download with [`DownloadError::StreamStalled`]
+    #[default]
+    Fail,
+    /// Keep waiting, emitting a [`ProgressEvent::Stalled`] every threshold, and carry on
+    /// silently when media resumes
+    Wait,
+}
+
+/// Stall detection settings of a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StallConfig {
+    /// How long the stream may go without media progress before it counts as stalled
+    pub threshold: Duration,
+    /// What to do once the stream stalled
+    pub action: StallAction,
+}
+
+impl StallConfig {
+    pub fn new(threshold: Duration, action: StallAction) -> Self {
+        Self { threshold, action }
+    }
+}
+
+/// Media position reached by an item, in milliseconds, or `None` for items without media
+pub(crate) type MediaPosition<T> = Box<dyn FnMut(&T) -> Option<u64> + Send>;
+
+/// Watch `stream` of `url` for stalls according to `config`
+pub(crate) fn watch<T, E>(
+    stream: BoxMediaStream<T, E>,
+    url: &str,
+    config: StallConfig,
+    on_progress: Option<OnProgress>,
+    position: MediaPosition<T>,
+) -> BoxMediaStream<T, E>
+where
+    T: Send + 'static,
+    E: From<DownloadError> + Send + 'static,
+{
+    let watchdog = Watchdog {
+        stream,
+        url: Arc::from(url),
+        config,
+        on_progress,
+        position,
+        last_position: None,
+        last_progress_at: Instant::now(),
+        stalled_reports: 0,
+        finished: false,
+    };
+    futures::stream::unfold(watchdog, |mut watchdog| async move {
+        let item = watchdog.next().await?;
+        Some((item, watchdog))
+    })
+    .boxed()
+}
+
+struct Watchdog<T, E> {
+    stream: BoxMediaStream<T, E>,
+    url: Arc<str>,
+    config: StallConfig,
+    on_progress: Option<OnProgress>,
+    position: MediaPosition<T>,
+    last_position: Option<u64>,
+    /// When the media position last moved, or the stream started
+    last_progress_at: Instant,
+    /// Stalled events emitted since the last progress
+    stalled_reports: u32,
+    /// Set after the error ending the stream was emitted
+    finished: bool,
+}
+
+impl<T, E: From<DownloadError>> Watchdog<T, E> {
+    async fn next(&mut self) -> Option<Result<T, E>> {
+        loop {
+            if self.finished {
+                return None;
+            }
+
+            let deadline =
+                self.last_progress_at + self.config.threshold * (self.stalled_reports + 1);
+            match tokio::time::timeout_at(deadline, self.stream.next()).await {
+                Ok(Some(Ok(item))) => {
+                    self.record(&item);
+                    return Some(Ok(item));
+                }
+                Ok(other) => return other,
+                Err(_) => {
+                    let idle_for = self.last_progress_at.elapsed();
+                    match self.config.action {
+                        StallAction::Fail => {
+                            warn!(
+                                url = %self.url,
+                                ?idle_for,
+                                "Stream stalled, ending the download"
+                            );
+                            self.finished = true;
+                            return Some(Err(DownloadError::StreamStalled {
+                                last_media_ts: self.last_position,
+                                idle_for,
+                            }
+                            .into()));
+                        }
+                        StallAction::Wait => {
+                            warn!(
+                                url = %self.url,
+                                ?idle_for,
+                                "Stream stalled, waiting for media"
+                            );
+                            self.stalled_reports += 1;
+                            if let Some(on_progress) = &self.on_progress {
+                                on_progress.emit(ProgressEvent::Stalled {
+                                    url: Arc::clone(&self.url),
+                                    last_media_ts: self.last_position,
+                                    idle_for,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Update the media position with an item passed on
+    fn record(&mut self, item: &T) {
+        let Some(position) = (self.position)(item) else {
+            return;
+        };
+        // Any change counts, as a reconnected stream may restart its timestamps
+        if self.last_position == Some(position) {
+            return;
+        }
+        if self.stalled_reports > 0 {
+            info!(
+                url = %self.url,
+                idle_for = ?self.last_progress_at.elapsed(),
+                "Stream resumed after stalling"
+            );
+        }
+        self.last_position = Some(position);
+        self.last_progress_at = Instant::now();
+        self.stalled_reports = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flv::{FlvDownloader, FlvProtocolConfig};
+    use crate::{Download, flv::error::FlvDownloadError};
+    use bytes::Bytes;
+    use flv::data::FlvData;
+    use flv::header::FlvHeader;
+    use flv::tag::{FlvTag, FlvTagType};
+    use flv::writer::FlvWriter;
+    use parking_lot::Mutex;
+    use std::io::Cursor;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_util::sync::CancellationToken;
+
+    /// Two seconds of video at 25fps, encoded as an FLV file
+    fn encode_stream() -> (Vec<u8>, usize) {
+        let mut writer = FlvWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.write_header(&FlvHeader::new(true, false)).unwrap();
+        let mut offsets = Vec::new();
+        for i in 0..50u32 {
+            offsets.push(writer.writer.get_ref().len());
+            let frame_type = if i % 25 == 0 { 0x17 } else { 0x27 };
+            writer
+                .write_tag_f(&FlvTag {
+                    timestamp_ms: i * 40,
+                    stream_id: 0,
+                    tag_type: FlvTagType::Video,
+                    is_filtered: false,
+                    data: Bytes::from(vec![frame_type, 1, 0, 0, 0, i as u8]),
+                })
+                .unwrap();
+        }
+        (writer.writer.into_inner(), offsets[25])
+    }
+
+    /// Serve `body` as a chunked response, pausing after `split` bytes for `pause`, or for
+    /// good without one
+    async fn spawn_pausing_source(body: Vec<u8>, split: usize, pause: Option<Duration>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let url = format!(
+            "http://{}/live.flv",
+            listener.local_addr().expect("local addr")
+        );
+        tokio::spawn(async move {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                return;
+            };
+            let mut head = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                match socket.read(&mut buf).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => head.extend_from_slice(&buf[..n]),
+                }
+            }
+            let chunk = |data: &[u8]| {
+                let mut chunk = format!("{:x}\r\n", data.len()).into_bytes();
+                chunk.extend_from_slice(data);
+                chunk.extend_from_slice(b"\r\n");
+                chunk
+            };
+            let mut response = b"HTTP/1.1 200 OK\r\nContent-Type: video/x-flv\r\n\
+                Transfer-Encoding: chunked\r\n\r\n"
+                .to_vec();
+            response.extend(chunk(&body[..split]));
+            if socket.write_all(&response).await.is_err() {
+                return;
+            }
+            let Some(pause) = pause else {
+                return std::future::pending().await;
+            };
+            tokio::time::sleep(pause).await;
+            let mut rest = chunk(&body[split..]);
+            rest.extend_from_slice(b"0\r\n\r\n");
+            let _ = socket.write_all(&rest).await;
+            let _ = socket.shutdown().await;
+        });
+        url
+    }
+
+    async fn download(
+        url: &str,
+        action: StallAction,
+    ) -> (Vec<Result<FlvData, FlvDownloadError>>, Vec<ProgressEvent>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&events);
+        let mut config = FlvProtocolConfig::builder()
+            .stall_config(StallConfig::new(Duration::from_millis(300), action))
+            .build();
+        config.base.on_progress = Some(OnProgress::new(move |event| sink.lock().push(event)));
+        let downloader = FlvDownloader::with_config(config).unwrap();
+
+        let items = downloader
+            .download(url, CancellationToken::new())
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        let events = events
+            .lock()
+            .iter()
+            .filter(|event| matches!(event, ProgressEvent::Stalled { .. }))
+            .cloned()
+            .collect();
+        (items, events)
+    }
+
+    #[tokio::test]
+    async fn test_fails_stalled_stream() {
+        let (body, split) = encode_stream();
+        let url = spawn_pausing_source(body, split, None).await;
+
+        let (items, events) = download(&url, StallAction::Fail).await;
+
+        // The header and the first 25 frames, then the stall error
+        let (last, received) = items.split_last().unwrap();
+        assert_eq!(received.len(), 26);
+        assert!(received.iter().all(Result::is_ok));
+        match last {
+            Err(FlvDownloadError::Download(DownloadError::StreamStalled {
+                last_media_ts,
+                idle_for,
+            })) => {
+                assert_eq!(*last_media_ts, Some(24 * 40));
+                assert!(*idle_for >= Duration::from_millis(300), "{idle_for:?}");
+            }
+            other => panic!("expected a stall error, got {other:?}"),
+        }
+        assert!(events.is_empty(), "{events:?}");
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_stalled_stream_to_resume() {
+        let (body, split) = encode_stream();
+        let url = spawn_pausing_source(body, split, Some(Duration::from_millis(800))).await;
+
+        let (items, events) = download(&url, StallAction::Wait).await;
+
+        // Everything arrives once the source resumes
+        assert_eq!(items.len(), 51);
+        assert!(items.iter().all(Result::is_ok));
+        // One event per threshold of the pause
+        assert!(matches!(events.len(), 2..=3), "{events:?}");
+        let ProgressEvent::Stalled {
+            url: stalled_url,
+            last_media_ts,
+            ..
+        } = &events[0]
+        else {
+            unreachable!();
+        };
+        assert_eq!(&**stalled_url, url);
+        assert_eq!(*last_media_ts, Some(24 * 40));
+    }
+}
@@ -122,3 +122,82 @@ pub fn make_media_segment_for_track(track_id: u32, sample: &[u8]) -> Bytes {
     out.extend_from_slice(&mdat);
     Bytes::from(out)
 }
+
+/// Init segment with a single track, its `mdhd` timescale and `trex` default sample duration.
+pub fn make_init_with_timescale(track_id: u32, timescale: u32, trex_duration: u32) -> Bytes {
+    let mut tkhd_payload = Vec::new();
+    tkhd_payload.extend_from_slice(&[0u8; 8]); // creation_time, modification_time
+    tkhd_payload.extend_from_slice(&track_id.to_be_bytes());
+    tkhd_payload.extend_from_slice(&0u32.to_be_bytes());
+    let tkhd = make_full_box(b"tkhd", 0, 0, &tkhd_payload);
+
+    let mut mdhd_payload = Vec::new();
+    mdhd_payload.extend_from_slice(&[0u8; 16]); // 64-bit creation_time, modification_time
+    mdhd_payload.extend_from_slice(&timescale.to_be_bytes());
+    mdhd_payload.extend_from_slice(&[0u8; 12]); // duration, language, pre_defined
+    let mdia = make_box(b"mdia", &make_full_box(b"mdhd", 1, 0, &mdhd_payload));
+
+    let mut trak_body = Vec::new();
+    trak_body.extend_from_slice(&tkhd);
+    trak_body.extend_from_slice(&mdia);
+    let trak = make_box(b"trak", &trak_body);
+
+    let mut trex_payload = Vec::new();
+    trex_payload.extend_from_slice(&track_id.to_be_bytes());
+    trex_payload.extend_from_slice(&1u32.to_be_bytes()); // default_sample_description_index
+    trex_payload.extend_from_slice(&trex_duration.to_be_bytes());
+    trex_payload.extend_from_slice(&[0u8; 8]); // default_sample_size, default_sample_flags
+    let mvex = make_box(b"mvex", &make_full_box(b"trex", 0, 0, &trex_payload));
+
+    let mut moov_body = Vec::new();
+    moov_body.extend_from_slice(&trak);
+    moov_body.extend_from_slice(&mvex);
+
+    let mut out = make_box(b"ftyp", b"isom");
+    out.extend_from_slice(&make_box(b"moov", &moov_body));
+    Bytes::from(out)
+}
+
+/// Media segment with a single fragment starting at `base_media_decode_time`, with one
+/// 4-byte sample per entry of `durations`.
+pub fn make_media_segment_with_timing(
+    track_id: u32,
+    tfdt_version: u8,
+    base_media_decode_time: u64,
+    durations: &[u32],
+) -> Bytes {
+    let mfhd = make_full_box(b"mfhd", 0, 0, &1u32.to_be_bytes());
+    let tfhd = make_full_box(b"tfhd", 0, 0, &track_id.to_be_bytes());
+    let tfdt = if tfdt_version == 1 {
+        make_full_box(b"tfdt", 1, 0, &base_media_decode_time.to_be_bytes())
+    } else {
+        make_full_box(
+            b"tfdt",
+            0,
+            0,
+            &(base_media_decode_time as u32).to_be_bytes(),
+        )
+    };
+
+    // trun flags: sample_duration_present + sample_size_present
+    let mut trun_payload = Vec::new();
+    trun_payload.extend_from_slice(&(durations.len() as u32).to_be_bytes());
+    for duration in durations {
+        trun_payload.extend_from_slice(&duration.to_be_bytes());
+        trun_payload.extend_from_slice(&4u32.to_be_bytes());
+    }
+    let trun = make_full_box(b"trun", 0, 0x000100 | 0x000200, &trun_payload);
+
+    let mut traf_body = Vec::new();
+    traf_body.extend_from_slice(&tfhd);
+    traf_body.extend_from_slice(&tfdt);
+    traf_body.extend_from_slice(&trun);
+    let traf = make_box(b"traf", &traf_body);
+
+    let mut moof_body = mfhd;
+    moof_body.extend_from_slice(&traf);
+
+    let mut out = make_box(b"moof", &moof_body);
+    out.extend_from_slice(&make_box(b"mdat", &vec![0u8; 4 * durations.len()]));
+    Bytes::from(out)
+}
@@ -0,0 +1,585 @@
+//! # Stream Anomalies
+//!
+//! Rolling statistics of a stream compared with its recent past, to point at problems of
+//! the upstream encoder in a recording: the bitrate collapsing, the keyframe interval
+//! changing, the frame rate dropping and audio drifting away from video.
+//!
+//! The operators of the FLV and HLS pipelines measure the stream, feed the measures to an
+//! [`AnomalyDetector`] and emit the [`Anomaly`]s it reports as
+//! [`PipelineEvent::AnomalyDetected`] events. The detector only keeps a few numbers per
+//! measure, so it is cheap enough to run on every stream.
+//!
+//! A collapse, drop or drift is reported once it is over, with the range it lasted and
+//! the values before and during it. A keyframe interval change is reported once the new
+//! interval is confirmed.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::PipelineEvent;
+
+/// Kind of an [`Anomaly`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnomalyKind {
+    /// The bitrate fell far below its recent average
+    BitrateCollapse,
+    /// The interval between keyframes changed
+    GopChange,
+    /// The frame rate fell below its recent average
+    FrameRateDrop,
+    /// Audio and video timestamps drifted apart
+    AvDrift,
+}
+
+impl AnomalyKind {
+    /// Name of the kind, the `kind` of the emitted events
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BitrateCollapse => "bitrate_collapse",
+            Self::GopChange => "gop_change",
+            Self::FrameRateDrop => "frame_rate_drop",
+            Self::AvDrift => "av_drift",
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Self::BitrateCollapse => "kbps",
+            Self::GopChange | Self::AvDrift => "ms",
+            Self::FrameRateDrop => "fps",
+        }
+    }
+}
+
+impl fmt::Display for AnomalyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An anomaly of the stream between two timestamps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub kind: AnomalyKind,
+    /// Timestamp in milliseconds the anomaly started at
+    pub start_ms: u64,
+    /// Timestamp in milliseconds the anomaly ended at, or was confirmed at for a change
+    pub end_ms: u64,
+    /// Value before the anomaly: the bitrate in kbps, the keyframe interval in ms, the
+    /// frame rate, or the audio/video drift in ms
+    pub before: f64,
+    /// Value during the anomaly: the average bitrate or frame rate, the new keyframe
+    /// interval, or the largest drift
+    pub after: f64,
+}
+
+impl Anomaly {
+    /// The event reporting the anomaly, emitted by `operator`
+    pub fn to_event(&self, operator: &'static str) -> PipelineEvent {
+        PipelineEvent::AnomalyDetected {
+            operator,
+            kind: self.kind.as_str(),
+            offset: Some(self.start_ms),
+            detail: self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            AnomalyKind::BitrateCollapse => "bitrate collapsed",
+            AnomalyKind::GopChange => "keyframe interval changed",
+            AnomalyKind::FrameRateDrop => "frame rate dropped",
+            AnomalyKind::AvDrift => "audio/video drift grew",
+        };
+        let unit = self.kind.unit();
+        write!(
+            f,
+            "{what} from {:.1}{unit} to {:.1}{unit} between {}ms and {}ms",
+            self.before, self.after, self.start_ms, self.end_ms
+        )
+    }
+}
+
+/// Thresholds of the [`AnomalyDetector`]
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    /// Length of the windows the bitrate and frame rate are measured over, in
+    /// milliseconds, for the operators measuring the stream tag by tag.
+    /// Defaults to 1 second.
+    pub window_ms: u64,
+
+    /// Number of windows averaged into the baseline a window is compared with. Nothing
+    /// is reported before the baseline is complete.
+    /// Defaults to 10.
+    pub baseline_windows: usize,
+
+    /// A bitrate below this fraction of the baseline is a collapse.
+    /// Defaults to 0.25.
+    pub bitrate_collapse_ratio: f64,
+
+    /// A frame rate below this fraction of the baseline is a drop.
+    /// Defaults to 0.75.
+    pub frame_rate_drop_ratio: f64,
+
+    /// How long a collapse or a drop must last to be reported, in milliseconds.
+    /// Defaults to 3 seconds.
+    pub min_duration_ms: u64,
+
+    /// A keyframe interval longer or shorter than the usual one by this factor is a change.
+    /// Defaults to 1.5.
+    pub gop_change_ratio: f64,
+
+    /// Number of consecutive keyframe intervals of the new length confirming a change,
+    /// so that keyframes forced by scene cuts are not reported.
+    /// Defaults to 3.
+    pub gop_confirmations: usize,
+
+    /// Audio and video timestamps further apart than this, in milliseconds, have drifted.
+    /// Defaults to 1 second.
+    pub max_av_drift_ms: u64,
+}
+
+impl Default for AnomalyConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: 1000,
+            baseline_windows: 10,
+            bitrate_collapse_ratio: 0.25,
+            frame_rate_drop_ratio: 0.75,
+            min_duration_ms: 3000,
+            gop_change_ratio: 1.5,
+            gop_confirmations: 3,
+            max_av_drift_ms: 1000,
+        }
+    }
+}
+
+/// Windows below the baseline, not yet reported
+#[derive(Debug)]
+struct Episode {
+    start_ms: u64,
+    end_ms: u64,
+    /// Baseline when the episode started
+    before: f64,
+    /// Sum of the levels of the windows weighted by their duration
+    weighted_sum: f64,
+    duration_ms: u64,
+}
+
+/// A level measured over windows, compared with the average of the windows before it
+#[derive(Debug)]
+struct LevelTracker {
+    kind: AnomalyKind,
+    baseline: VecDeque<f64>,
+    baseline_sum: f64,
+    episode: Option<Episode>,
+}
+
+impl LevelTracker {
+    fn new(kind: AnomalyKind) -> Self {
+        Self {
+            kind,
+            baseline: VecDeque::new(),
+            baseline_sum: 0.0,
+            episode: None,
+        }
+    }
+
+    /// Add the level of the window `[start_ms, end_ms)`, returning the anomaly it ends
+    fn push(
+        &mut self,
+        start_ms: u64,
+        end_ms: u64,
+        level: f64,
+        ratio: f64,
+        config: &AnomalyConfig,
+    ) -> Option<Anomaly> {
+        if self.baseline.len() >= config.baseline_windows.max(1) {
+            let before = self.baseline_sum / self.baseline.len() as f64;
+            if level < before * ratio {
+                let episode = self.episode.get_or_insert(Episode {
+                    start_ms,
+                    end_ms,
+                    before,
+                    weighted_sum: 0.0,
+                    duration_ms: 0,
+                });
+                let duration_ms = end_ms.saturating_sub(start_ms);
+                episode.end_ms = end_ms;
+                episode.weighted_sum += level * duration_ms as f64;
+                episode.duration_ms += duration_ms;
+                return None;
+            }
+        }
+
+        let anomaly = self.finish(config);
+        self.baseline.push_back(level);
+        self.baseline_sum += level;
+        while self.baseline.len() > config.baseline_windows.max(1) {
+            self.baseline_sum -= self.baseline.pop_front().unwrap_or_default();
+        }
+        anomaly
+    }
+
+    /// End the current episode, returning it if it lasted long enough to be reported
+    fn finish(&mut self, config: &AnomalyConfig) -> Option<Anomaly> {
+        let episode = self.episode.take()?;
+        if episode.duration_ms < config.min_duration_ms.max(1) {
+            return None;
+        }
+        Some(Anomaly {
+            kind: self.kind,
+            start_ms: episode.start_ms,
+            end_ms: episode.end_ms,
+            before: episode.before,
+            after: episode.weighted_sum / episode.duration_ms as f64,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.baseline.clear();
+        self.baseline_sum = 0.0;
+        self.episode = None;
+    }
+}
+
+/// Intervals between keyframes, compared with the usual one
+#[derive(Debug, Default)]
+struct GopTracker {
+    last_keyframe_ms: Option<u64>,
+    /// Interval the stream settled on
+    settled_ms: Option<u64>,
+    /// Consecutive intervals differing from the settled one
+    candidates: Vec<u64>,
+    /// Keyframe starting the first candidate interval
+    candidate_start_ms: u64,
+}
+
+impl GopTracker {
+    fn keyframe(&mut self, timestamp_ms: u64, config: &AnomalyConfig) -> Option<Anomaly> {
+        let last = self.last_keyframe_ms.replace(timestamp_ms)?;
+        let interval = timestamp_ms.checked_sub(last).filter(|&i| i > 0)?;
+        let differs = |a: u64, b: u64| a.max(b) as f64 > a.min(b) as f64 * config.gop_change_ratio;
+
+        let Some(settled) = self.settled_ms else {
+            self.settled_ms = Some(interval);
+            return None;
+        };
+        if !differs(interval, settled) {
+            self.candidates.clear();
+            return None;
+        }
+        match self.candidates.first() {
+            Some(&first) if !differs(interval, first) => {}
+            _ => {
+                self.candidates.clear();
+                self.candidate_start_ms = last;
+            }
+        }
+        self.candidates.push(interval);
+        if self.candidates.len() < config.gop_confirmations.max(1) {
+            return None;
+        }
+
+        let after = self.candidates.iter().sum::<u64>() / self.candidates.len() as u64;
+        self.candidates.clear();
+        self.settled_ms = Some(after);
+        Some(Anomaly {
+            kind: AnomalyKind::GopChange,
+            start_ms: self.candidate_start_ms,
+            end_ms: timestamp_ms,
+            before: settled as f64,
+            after: after as f64,
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Distance between the audio and video timestamps
+#[derive(Debug, Default)]
+struct DriftTracker {
+    /// Drift before it crossed the threshold
+    last_normal_ms: u64,
+    /// Start, last sample and largest drift of the drift above the threshold
+    episode: Option<(u64, u64, u64)>,
+}
+
+impl DriftTracker {
+    fn push(
+        &mut self,
+        timestamp_ms: u64,
+        drift_ms: u64,
+        config: &AnomalyConfig,
+    ) -> Option<Anomaly> {
+        if drift_ms > config.max_av_drift_ms {
+            let (_, end, peak) = self
+                .episode
+                .get_or_insert((timestamp_ms, timestamp_ms, drift_ms));
+            *end = timestamp_ms;
+            *peak = (*peak).max(drift_ms);
+            return None;
+        }
+        let anomaly = self.finish();
+        self.last_normal_ms = drift_ms;
+        anomaly
+    }
+
+    fn finish(&mut self) -> Option<Anomaly> {
+        let (start_ms, end_ms, peak) = self.episode.take()?;
+        Some(Anomaly {
+            kind: AnomalyKind::AvDrift,
+            start_ms,
+            end_ms,
+            before: self.last_normal_ms as f64,
+            after: peak as f64,
+        })
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Rolling statistics of a stream, reporting its anomalies.
+///
+/// The measures are passed in timestamp order; [`reset`](Self::reset) starts over, e.g.
+/// on a new FLV header or an HLS discontinuity.
+#[derive(Debug)]
+pub struct AnomalyDetector {
+    config: AnomalyConfig,
+    bitrate: LevelTracker,
+    frame_rate: LevelTracker,
+    gop: GopTracker,
+    drift: DriftTracker,
+}
+
+impl AnomalyDetector {
+    pub fn new(config: AnomalyConfig) -> Self {
+        Self {
+            config,
+            bitrate: LevelTracker::new(AnomalyKind::BitrateCollapse),
+            frame_rate: LevelTracker::new(AnomalyKind::FrameRateDrop),
+            gop: GopTracker::default(),
+            drift: DriftTracker::default(),
+        }
+    }
+
+    pub fn config(&self) -> &AnomalyConfig {
+        &self.config
+    }
+
+    /// Add the window `[start_ms, end_ms)` of the stream, carrying `bytes` of media and
+    /// `frames` video frames, `None` when the frames are not counted.
+    ///
+    /// Returns the collapse and the drop the window ends, if any.
+    pub fn window(
+        &mut self,
+        start_ms: u64,
+        end_ms: u64,
+        bytes: u64,
+        frames: Option<u64>,
+    ) -> impl Iterator<Item = Anomaly> + use<> {
+        let mut anomalies = [None, None];
+        let duration_ms = end_ms.saturating_sub(start_ms);
+        if duration_ms > 0 {
+            // Bits per millisecond are kilobits per second
+            let kbps = (bytes * 8) as f64 / duration_ms as f64;
+            anomalies[0] = self.bitrate.push(
+                start_ms,
+                end_ms,
+                kbps,
+                self.config.bitrate_collapse_ratio,
+                &self.config,
+            );
+            if let Some(frames) = frames {
+                let fps = frames as f64 * 1000.0 / duration_ms as f64;
+                anomalies[1] = self.frame_rate.push(
+                    start_ms,
+                    end_ms,
+                    fps,
+                    self.config.frame_rate_drop_ratio,
+                    &self.config,
+                );
+            }
+        }
+        anomalies.into_iter().flatten()
+    }
+
+    /// Add a keyframe at `timestamp_ms`, returning the keyframe interval change it confirms
+    pub fn keyframe(&mut self, timestamp_ms: u64) -> Option<Anomaly> {
+        self.gop.keyframe(timestamp_ms, &self.config)
+    }
+
+    /// Add the distance between the audio and video timestamps at `timestamp_ms`,
+    /// returning the drift it ends
+    pub fn drift(&mut self, timestamp_ms: u64, drift_ms: u64) -> Option<Anomaly> {
+        self.drift.push(timestamp_ms, drift_ms, &self.config)
+    }
+
+    /// End the anomalies still going on at the end of the stream and start over
+    pub fn finish(&mut self) -> impl Iterator<Item = Anomaly> + use<> {
+        let anomalies = [
+            self.bitrate.finish(&self.config),
+            self.frame_rate.finish(&self.config),
+            self.drift.finish(),
+        ];
+        self.reset();
+        anomalies.into_iter().flatten()
+    }
+
+    /// Forget the statistics, without reporting the anomalies going on
+    pub fn reset(&mut self) {
+        self.bitrate.reset();
+        self.frame_rate.reset();
+        self.gop.reset();
+        self.drift.reset();
+    }
+}
+
+impl Default for AnomalyDetector {
+    fn default() -> Self {
+        Self::new(AnomalyConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds one-second windows of `kbps` and `fps` from `start_s`
+    fn windows(
+        detector: &mut AnomalyDetector,
+        start_s: u64,
+        levels: &[(u64, u64)],
+    ) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        for (i, &(kbps, fps)) in levels.iter().enumerate() {
+            let start_ms = (start_s + i as u64) * 1000;
+            anomalies.extend(detector.window(start_ms, start_ms + 1000, kbps * 125, Some(fps)));
+        }
+        anomalies
+    }
+
+    #[test]
+    fn test_bitrate_collapse_reported_when_over() {
+        let mut detector = AnomalyDetector::default();
+        assert!(windows(&mut detector, 0, &[(2000, 30); 20]).is_empty());
+        // Two low windows are too short to matter
+        assert!(windows(&mut detector, 20, &[(100, 30), (100, 30), (2000, 30)]).is_empty());
+        assert!(windows(&mut detector, 23, &[(200, 30); 5]).is_empty());
+
+        let anomalies = windows(&mut detector, 28, &[(2000, 30)]);
+        assert_eq!(
+            anomalies,
+            [Anomaly {
+                kind: AnomalyKind::BitrateCollapse,
+                start_ms: 23_000,
+                end_ms: 28_000,
+                before: 2000.0,
+                after: 200.0,
+            }]
+        );
+        assert_eq!(
+            anomalies[0].to_string(),
+            "bitrate collapsed from 2000.0kbps to 200.0kbps between 23000ms and 28000ms"
+        );
+        assert_eq!(
+            anomalies[0].to_event("AnomalyDetectorOperator").name(),
+            "anomaly_detected"
+        );
+    }
+
+    #[test]
+    fn test_frame_rate_drop_reported_at_finish() {
+        let mut detector = AnomalyDetector::default();
+        let mut levels = vec![(2000, 30); 10];
+        levels.extend([(2000, 12); 4]);
+        assert!(windows(&mut detector, 0, &levels).is_empty());
+
+        let anomalies: Vec<_> = detector.finish().collect();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, AnomalyKind::FrameRateDrop);
+        assert_eq!(
+            (anomalies[0].start_ms, anomalies[0].end_ms),
+            (10_000, 14_000)
+        );
+        assert_eq!((anomalies[0].before, anomalies[0].after), (30.0, 12.0));
+        assert!(detector.finish().next().is_none());
+    }
+
+    #[test]
+    fn test_gop_change_confirmed() {
+        let mut detector = AnomalyDetector::default();
+        let mut anomalies = Vec::new();
+        // 2s GOPs with a scene cut at 11s, then 10s GOPs from 20s
+        let keyframes = [
+            0, 2, 4, 6, 8, 10, 11, 12, 14, 16, 18, 20, 30, 40, 50, 60, 70,
+        ];
+        for seconds in keyframes {
+            anomalies.extend(detector.keyframe(seconds * 1000));
+        }
+        assert_eq!(
+            anomalies,
+            [Anomaly {
+                kind: AnomalyKind::GopChange,
+                start_ms: 20_000,
+                end_ms: 50_000,
+                before: 2000.0,
+                after: 10_000.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_av_drift() {
+        let mut detector = AnomalyDetector::default();
+        let mut anomalies = Vec::new();
+        for (timestamp, drift) in [(0, 40), (1000, 60), (2000, 1500), (3000, 2500), (4000, 80)] {
+            anomalies.extend(detector.drift(timestamp, drift));
+        }
+        assert_eq!(
+            anomalies,
+            [Anomaly {
+                kind: AnomalyKind::AvDrift,
+                start_ms: 2000,
+                end_ms: 3000,
+                before: 60.0,
+                after: 2500.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_random_levels_within_ratio_are_quiet() {
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut detector = AnomalyDetector::default();
+        for i in 0..10_000u64 {
+            // Bitrates between 1000 and 3000kbps and 25 to 30fps never cross the thresholds
+            let kbps = 1000 + next() % 2000;
+            let fps = 25 + next() % 6;
+            let start_ms = i * 1000;
+            assert!(
+                detector
+                    .window(start_ms, start_ms + 1000, kbps * 125, Some(fps))
+                    .next()
+                    .is_none()
+            );
+            if i % 2 == 0 {
+                assert!(detector.keyframe(start_ms).is_none());
+            }
+            assert!(detector.drift(start_ms, next() % 1000).is_none());
+        }
+        assert!(detector.finish().next().is_none());
+    }
+}
@@ -4,8 +4,15 @@
 //! in its own task, connected by channels. This allows for pipeline parallelism and
 //! better backpressure handling.
 
-use crate::{PipelineError, Processor, StreamerContext};
+use crate::error_policy::{ErrorRecovery, Recovery};
+use crate::memory::{InFlightBytes, MemSized};
+use crate::stats::PipelineStats;
+use crate::{
+    CancellationToken, ErrorPolicy, PipelineError, Processor, ProgressEvent, StreamerContext,
+};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tracing::{debug, error};
@@ -33,6 +40,127 @@ fn stage_finish_error(
     }
 }
 
+/// Output channel of a stage that accounts for the bytes queued in it.
+///
+/// Bytes are added before an item is sent and released by the [`StageReceiver`]
+/// on the other end as soon as the item is received.
+struct StageSender<T> {
+    tx: mpsc::Sender<Result<T, PipelineError>>,
+    size_of: Option<fn(&T) -> usize>,
+    max_in_flight_bytes: u64,
+    in_flight: Arc<InFlightBytes>,
+    /// Bytes of the items sent on this channel and not received yet
+    queued: Arc<AtomicU64>,
+    /// Set on the last stage, which records the items leaving the pipeline
+    output_stats: Option<Arc<PipelineStats>>,
+}
+
+impl<T> StageSender<T> {
+    /// Sends an item, first waiting while the pipeline is over its byte budget.
+    fn blocking_send(&mut self, item: Result<T, PipelineError>) -> Result<(), ()> {
+        let is_item = item.is_ok();
+        let size = match (&item, self.size_of) {
+            (Ok(item), Some(size_of)) => size_of(item) as u64,
+            _ => 0,
+        };
+
+        self.send_within_budget(item, size)?;
+        if is_item && let Some(stats) = &self.output_stats {
+            stats.record_output(size);
+        }
+        Ok(())
+    }
+
+    /// A stage with nothing queued never waits, so a single item larger than the
+    /// budget, or bytes held by other stages, cannot stall the pipeline.
+    fn send_within_budget(&mut self, item: Result<T, PipelineError>, size: u64) -> Result<(), ()> {
+        if self.size_of.is_none() {
+            return self.tx.blocking_send(item).map_err(|_| ());
+        }
+
+        if self.max_in_flight_bytes > 0 {
+            let (tx, queued) = (&self.tx, &self.queued);
+            self.in_flight
+                .wait_for_room(size, self.max_in_flight_bytes, || {
+                    queued.load(Ordering::Relaxed) == 0 || tx.is_closed()
+                });
+        }
+
+        // Added before sending, so the receiver never releases bytes not added yet
+        self.queued.fetch_add(size, Ordering::Relaxed);
+        self.in_flight.add(size);
+        if self.tx.blocking_send(item).is_err() {
+            self.queued.fetch_sub(size, Ordering::Relaxed);
+            self.in_flight.release(size);
+            return Err(());
+        }
+        Ok(())
+    }
+}
+
+/// Bytes accounted for by the sender of a [`StageReceiver`]
+struct QueuedBytes<T> {
+    size_of: fn(&T) -> usize,
+    in_flight: Arc<InFlightBytes>,
+    queued: Arc<AtomicU64>,
+}
+
+/// Input channel of a stage, releasing the bytes of the items it receives.
+struct StageReceiver<T> {
+    rx: mpsc::Receiver<Result<T, PipelineError>>,
+    /// Unset when the sender does not account for bytes, as the pipeline input
+    accounting: Option<QueuedBytes<T>>,
+}
+
+impl<T> StageReceiver<T> {
+    fn blocking_recv(&mut self) -> Option<Result<T, PipelineError>> {
+        let item = self.rx.blocking_recv();
+        self.release(&item);
+        item
+    }
+
+    async fn recv(&mut self) -> Option<Result<T, PipelineError>> {
+        let item = self.rx.recv().await;
+        self.release(&item);
+        item
+    }
+
+    /// The channel itself, for a receiver whose bytes are not accounted for
+    fn into_inner(mut self) -> mpsc::Receiver<Result<T, PipelineError>> {
+        debug_assert!(self.accounting.is_none());
+        // Leaves a closed channel behind for `drop`
+        std::mem::replace(&mut self.rx, mpsc::channel(1).1)
+    }
+
+    fn release(&self, item: &Option<Result<T, PipelineError>>) {
+        if let (Some(accounting), Some(Ok(item))) = (&self.accounting, item) {
+            let size = (accounting.size_of)(item) as u64;
+            accounting.queued.fetch_sub(size, Ordering::Relaxed);
+            accounting.in_flight.release(size);
+        }
+    }
+}
+
+impl<T> Drop for StageReceiver<T> {
+    fn drop(&mut self) {
+        // Items left in the channel are dropped with it
+        self.rx.close();
+        while let Ok(item) = self.rx.try_recv() {
+            self.release(&Some(item));
+        }
+        // Senders waiting for room see the channel closed
+        if let Some(accounting) = &self.accounting {
+            accounting.in_flight.wake();
+        }
+    }
+}
+
+/// Periodic delivery of [`ProgressEvent::Stats`] snapshots.
+struct StatsReporter {
+    interval: Duration,
+    callback: Arc<dyn Fn(ProgressEvent) + Send + Sync>,
+}
+
 /// A channel-based pipeline for processing data through a series of processors.
 ///
 /// Unlike the synchronous `Pipeline`, this implementation spawns a Tokio task for
@@ -44,8 +172,15 @@ fn stage_finish_error(
 /// - Stage processors are expected to be synchronous (`Processor<T>`).
 pub struct ChannelPipeline<T> {
     processors: Vec<Box<dyn Processor<T> + Send>>,
+    /// Error recovery of each processor, by position
+    recoveries: Vec<ErrorRecovery<T>>,
+    error_policy: ErrorPolicy,
+    clone_of: Option<fn(&T) -> T>,
     context: Arc<StreamerContext>,
     channel_size: usize,
+    max_in_flight_bytes: u64,
+    size_of: Option<fn(&T) -> usize>,
+    stats_reporter: Option<StatsReporter>,
 }
 
 /// Result of spawning a pipeline
@@ -63,8 +198,14 @@ where
     pub fn new(context: Arc<StreamerContext>) -> Self {
         Self {
             processors: Vec::new(),
+            recoveries: Vec::new(),
+            error_policy: ErrorPolicy::default(),
+            clone_of: None,
             context,
             channel_size: DEFAULT_CHANNEL_CAPACITY,
+            max_in_flight_bytes: 0,
+            size_of: None,
+            stats_reporter: None,
         }
     }
 
@@ -74,9 +215,75 @@ where
         self
     }
 
+    /// Limit the bytes queued between stages, in addition to the channel size.
+    ///
+    /// A stage blocks before emitting an item that would take the pipeline over
+    /// the budget, until downstream stages or the consumer catch up. Queued bytes
+    /// are recorded in [`StreamerContext::in_flight`] and released as soon as the
+    /// next stage or the consumer receives the item; a budget of 0 only records them.
+    ///
+    /// The output is handed over through a channel of a single item, which is the
+    /// only item not accounted for. The synchronous [`Pipeline`](crate::Pipeline)
+    /// queues nothing between its processors, so it has no budget.
+    pub fn with_max_in_flight_bytes(mut self, max_in_flight_bytes: u64) -> Self
+    where
+        T: MemSized,
+    {
+        self.max_in_flight_bytes = max_in_flight_bytes;
+        self.size_of = Some(T::mem_size);
+        self
+    }
+
+    /// Report the statistics of the pipeline every `interval`, and once more when the
+    /// last stage has finished.
+    ///
+    /// The snapshots of [`StreamerContext::stats`] are passed to `callback` as
+    /// [`ProgressEvent::Stats`]. The reporter runs as one of the spawned tasks, so the
+    /// final snapshot has been delivered once all tasks have completed.
+    pub fn with_stats_reporter<F>(mut self, interval: Duration, callback: F) -> Self
+    where
+        F: Fn(ProgressEvent) + Send + Sync + 'static,
+    {
+        self.stats_reporter = Some(StatsReporter {
+            interval,
+            callback: Arc::new(callback),
+        });
+        self
+    }
+
+    /// Set what the processors without a policy of their own do with items they fail on.
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self
+    where
+        T: Clone,
+    {
+        self.error_policy = error_policy;
+        self.clone_of = Some(T::clone);
+        for recovery in &mut self.recoveries {
+            recovery.set_default(error_policy, T::clone);
+        }
+        self
+    }
+
     /// Add a processor to the end of the pipeline.
     pub fn add_processor<P: Processor<T> + Send + 'static>(mut self, processor: P) -> Self {
         self.processors.push(Box::new(processor));
+        self.recoveries
+            .push(ErrorRecovery::new(self.error_policy, self.clone_of));
+        self
+    }
+
+    /// Add a processor to the end of the pipeline, with its own [`ErrorPolicy`].
+    pub fn add_processor_with_error_policy<P: Processor<T> + Send + 'static>(
+        mut self,
+        processor: P,
+        error_policy: ErrorPolicy,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        self.processors.push(Box::new(processor));
+        self.recoveries
+            .push(ErrorRecovery::overriding(error_policy, T::clone));
         self
     }
 
@@ -85,22 +292,53 @@ where
     /// This allows for fully async integration where the caller drives the input and consumes the output.
     pub fn spawn(self) -> SpawnedPipeline<T> {
         let mut tasks: Vec<JoinHandle<Result<(), PipelineError>>> = Vec::new();
+        let stage_count = self.processors.len();
+        let size_of = self.size_of;
+        // Cancelled once the last stage has exited, however it exits
+        let done = CancellationToken::new();
+        if stage_count == 0 {
+            done.cancel();
+        }
 
         // Channel for the initial input to the first processor
-        let (first_tx, mut current_rx) =
-            mpsc::channel::<Result<T, PipelineError>>(self.channel_size);
+        let (first_tx, first_rx) = mpsc::channel::<Result<T, PipelineError>>(self.channel_size);
+        let mut current_rx = StageReceiver {
+            rx: first_rx,
+            accounting: None,
+        };
 
         // Iterate through processors and chain them
-        for mut processor in self.processors {
+        let stages = self.processors.into_iter().zip(self.recoveries);
+        for (index, (mut processor, mut recovery)) in stages.enumerate() {
             let (next_tx, next_rx) = mpsc::channel::<Result<T, PipelineError>>(self.channel_size);
             let context = self.context.clone();
             let processor_name = processor.name();
+            let is_first = index == 0;
+            let is_last = index + 1 == stage_count;
+            let queued = Arc::new(AtomicU64::new(0));
+            let mut tx = StageSender {
+                tx: next_tx,
+                size_of,
+                max_in_flight_bytes: self.max_in_flight_bytes,
+                in_flight: self.context.in_flight.clone(),
+                queued: queued.clone(),
+                output_stats: is_last.then(|| self.context.stats.clone()),
+            };
+            let next_rx = StageReceiver {
+                rx: next_rx,
+                accounting: size_of.map(|size_of| QueuedBytes {
+                    size_of,
+                    in_flight: self.context.in_flight.clone(),
+                    queued,
+                }),
+            };
+            let done_guard = is_last.then(|| done.clone().drop_guard());
 
             // Spawn processor task
             // We use spawn_blocking because processors are synchronous
             let task = tokio::task::spawn_blocking(move || {
+                let _done_guard = done_guard;
                 let mut input_rx = current_rx;
-                let tx = next_tx;
                 let mut processed_items: usize = 0;
                 let mut emitted_items: usize = 0;
                 let mut next_progress_log_at: usize = 10_000;
@@ -109,7 +347,12 @@ where
                 while let Some(item_result) = input_rx.blocking_recv() {
                     match item_result {
                         Ok(item) => {
+                            if is_first {
+                                let size = size_of.map_or(0, |size_of| size_of(&item) as u64);
+                                context.stats.record_input(size);
+                            }
                             let mut output_fn = |processed_item: T| {
+                                recovery.observe(&processed_item);
                                 if tx.blocking_send(Ok(processed_item)).is_err() {
                                     return Err(PipelineError::ChannelClosed("downstream"));
                                 }
@@ -117,20 +360,43 @@ where
                                 Ok(())
                             };
 
-                            if let Err(e) = processor.process(&context, item, &mut output_fn) {
-                                if matches!(e, PipelineError::Cancelled) {
-                                    return Err(PipelineError::Cancelled);
+                            let result = processor.process(&context, item, &mut output_fn);
+                            match result.map_err(|e| recovery.failed(&context, processor_name, e)) {
+                                Ok(()) => recovery.succeeded(),
+                                Err(Recovery::Abort(e)) => {
+                                    if matches!(e, PipelineError::Cancelled) {
+                                        return Err(PipelineError::Cancelled);
+                                    }
+                                    error!(
+                                        processor = processor_name,
+                                        error = ?e,
+                                        "Processor failed"
+                                    );
+                                    let msg = e.to_string();
+                                    // Channel gets the original typed error; task return gets a
+                                    // string copy
+                                    let _ = tx.blocking_send(Err(e));
+                                    return Err(stage_process_error(
+                                        processor_name,
+                                        std::io::Error::other(msg),
+                                    ));
+                                }
+                                Err(Recovery::Skip) => {}
+                                Err(Recovery::Substitute(item)) => {
+                                    if tx.blocking_send(Ok(item)).is_err() {
+                                        break;
+                                    }
+                                    emitted_items = emitted_items.saturating_add(1);
                                 }
-                                error!(processor = processor_name, error = ?e, "Processor failed");
-                                let msg = e.to_string();
-                                // Channel gets the original typed error; task return gets a string copy
-                                let _ = tx.blocking_send(Err(e));
-                                return Err(stage_process_error(
-                                    processor_name,
-                                    std::io::Error::other(msg),
-                                ));
                             }
                             processed_items = processed_items.saturating_add(1);
+                            if processor.is_finished() {
+                                debug!(
+                                    processor = processor_name,
+                                    "Processor finished, ending input"
+                                );
+                                break;
+                            }
                             if processed_items >= next_progress_log_at {
                                 debug!(
                                     processor = processor_name,
@@ -177,9 +443,47 @@ where
             current_rx = next_rx;
         }
 
+        if let Some(reporter) = self.stats_reporter {
+            let stats = self.context.stats.clone();
+            tasks.push(tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(reporter.interval);
+                // The first tick completes immediately
+                ticker.tick().await;
+                loop {
+                    tokio::select! {
+                        _ = done.cancelled() => break,
+                        _ = ticker.tick() => {
+                            (reporter.callback)(ProgressEvent::Stats(stats.snapshot()));
+                        }
+                    }
+                }
+                (reporter.callback)(ProgressEvent::Stats(stats.snapshot()));
+                Ok(())
+            }));
+        }
+
+        let output_rx = if current_rx.accounting.is_some() {
+            // Hand the items over one at a time, releasing their bytes once the
+            // consumer has taken the previous one
+            let (output_tx, output_rx) = mpsc::channel(1);
+            let mut last_rx = current_rx;
+            tasks.push(tokio::spawn(async move {
+                while let Ok(permit) = output_tx.reserve().await {
+                    let Some(item) = last_rx.recv().await else {
+                        break;
+                    };
+                    permit.send(item);
+                }
+                Ok(())
+            }));
+            output_rx
+        } else {
+            current_rx.into_inner()
+        };
+
         SpawnedPipeline {
             input_tx: first_tx,
-            output_rx: current_rx,
+            output_rx,
             tasks,
         }
     }
@@ -423,6 +727,100 @@ mod tests {
         }
     }
 
+    /// Rejects items containing "bad" as invalid data
+    struct RejectingProcessor;
+
+    impl Processor<String> for RejectingProcessor {
+        fn name(&self) -> &'static str {
+            "RejectingProcessor"
+        }
+
+        fn process(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            input: String,
+            output: &mut dyn FnMut(String) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            if input.contains("bad") {
+                return Err(PipelineError::InvalidData(input));
+            }
+            output(input)
+        }
+
+        fn finish(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            _output: &mut dyn FnMut(String) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_policy_per_stage() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let pipeline = ChannelPipeline::new(context.clone())
+            .with_error_policy(ErrorPolicy::SkipItem {
+                max_consecutive: 1,
+                max_total: 2,
+            })
+            .add_processor(TestProcessor::new("first", counter.clone()))
+            .add_processor(RejectingProcessor)
+            .add_processor(TestProcessor::new("last", counter.clone()));
+        let input = ["a", "bad1", "b", "bad2", "c"].map(|item| Ok(item.to_string()));
+
+        let results = tokio::task::spawn_blocking(move || {
+            let mut results = Vec::new();
+            let mut output_fn = |res: Result<String, PipelineError>| results.push(res.unwrap());
+            pipeline.run(input.into_iter(), &mut output_fn).unwrap();
+            results
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            results,
+            vec![
+                "a-processed-processed",
+                "b-processed-processed",
+                "c-processed-processed"
+            ]
+        );
+        assert_eq!(counter.load(Ordering::SeqCst), 8);
+        assert_eq!(
+            context.stats.snapshot().errors.get("RejectingProcessor"),
+            Some(&2)
+        );
+
+        // A third failure goes over the total
+        let pipeline = ChannelPipeline::new(StreamerContext::arc_new(CancellationToken::new()))
+            .add_processor_with_error_policy(
+                RejectingProcessor,
+                ErrorPolicy::SkipItem {
+                    max_consecutive: 1,
+                    max_total: 2,
+                },
+            );
+        let input = ["bad1", "a", "bad2", "b", "bad3"].map(|item| Ok(item.to_string()));
+
+        let (result, results) = tokio::task::spawn_blocking(move || {
+            let mut results = Vec::new();
+            let mut output_fn = |res: Result<String, PipelineError>| results.push(res.unwrap());
+            let result = pipeline.run(input.into_iter(), &mut output_fn);
+            (result, results)
+        })
+        .await
+        .unwrap();
+
+        match result {
+            Err(PipelineError::InvalidData(item)) => assert_eq!(item, "bad3"),
+            other => panic!("Expected InvalidData error, got {:?}", other),
+        }
+        assert_eq!(results, vec!["a", "b"]);
+    }
+
     /// Test that spawn() task handles return StageProcess with the original error type preserved
     /// in the channel output.
     #[tokio::test]
@@ -507,4 +905,164 @@ mod tests {
             other => panic!("Expected StageFinish error on task, got {:?}", other),
         }
     }
+
+    const MB: usize = 1024 * 1024;
+
+    /// An item that only declares its size, so the tests allocate nothing.
+    struct Blob(usize);
+
+    impl MemSized for Blob {
+        fn mem_size(&self) -> usize {
+            self.0
+        }
+    }
+
+    struct PassThroughProcessor;
+
+    impl Processor<Blob> for PassThroughProcessor {
+        fn name(&self) -> &'static str {
+            "PassThroughProcessor"
+        }
+
+        fn process(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            input: Blob,
+            output: &mut dyn FnMut(Blob) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            output(input)
+        }
+
+        fn finish(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            _output: &mut dyn FnMut(Blob) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            Ok(())
+        }
+    }
+
+    /// Pushes 4 MB items through a pipeline drained by a slow writer and returns
+    /// the high-water mark of the bytes in flight.
+    async fn in_flight_high_water(max_in_flight_bytes: u64) -> u64 {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let pipeline = ChannelPipeline::new(context.clone())
+            .with_channel_size(64)
+            .with_max_in_flight_bytes(max_in_flight_bytes)
+            .add_processor(PassThroughProcessor);
+        let SpawnedPipeline {
+            input_tx,
+            mut output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        let writer = tokio::task::spawn_blocking(move || {
+            let mut received = 0;
+            while let Some(item) = output_rx.blocking_recv() {
+                assert!(item.is_ok());
+                received += 1;
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            received
+        });
+
+        for _ in 0..48 {
+            input_tx.send(Ok(Blob(4 * MB))).await.unwrap();
+        }
+        drop(input_tx);
+
+        assert_eq!(writer.await.unwrap(), 48);
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+        assert_eq!(context.in_flight.current(), 0);
+        context.in_flight.high_water()
+    }
+
+    #[tokio::test]
+    async fn test_byte_budget_bounds_in_flight_bytes() {
+        let budget = 16 * MB as u64;
+
+        let bounded = in_flight_high_water(budget).await;
+        assert!(
+            bounded <= budget,
+            "{bounded} bytes in flight over a {budget} byte budget"
+        );
+
+        // The item count alone lets the whole input pile up in front of the writer
+        let unbounded = in_flight_high_water(0).await;
+        assert!(
+            unbounded > 4 * budget,
+            "only {unbounded} bytes in flight without a budget"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_received_items_release_bytes_while_producer_idle() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let pipeline = ChannelPipeline::new(context.clone())
+            .with_max_in_flight_bytes(MB as u64)
+            .add_processor(PassThroughProcessor)
+            .add_processor(PassThroughProcessor);
+        let SpawnedPipeline {
+            input_tx,
+            mut output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        for size in [100, 200, 300] {
+            input_tx.send(Ok(Blob(size))).await.unwrap();
+        }
+        for size in [100, 200, 300] {
+            let item = output_rx.recv().await.unwrap().unwrap();
+            assert_eq!(item.0, size);
+        }
+        // The input is still open, nothing is sent anymore
+        assert_eq!(context.in_flight.current(), 0);
+        assert!(context.in_flight.high_water() >= 300);
+
+        drop(input_tx);
+        assert!(output_rx.recv().await.is_none());
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_reporter_emits_final_snapshot() {
+        let context = StreamerContext::arc_new(CancellationToken::new());
+        let snapshots = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let pipeline = {
+            let snapshots = snapshots.clone();
+            ChannelPipeline::new(context.clone())
+                .with_max_in_flight_bytes(0)
+                .with_stats_reporter(Duration::from_secs(3600), move |event| {
+                    if let ProgressEvent::Stats(snapshot) = event {
+                        snapshots.lock().unwrap().push(snapshot);
+                    }
+                })
+                .add_processor(PassThroughProcessor)
+                .add_processor(PassThroughProcessor)
+        };
+        let SpawnedPipeline {
+            input_tx,
+            mut output_rx,
+            tasks,
+        } = pipeline.spawn();
+
+        for size in [100, 200, 300] {
+            input_tx.send(Ok(Blob(size))).await.unwrap();
+        }
+        drop(input_tx);
+        while output_rx.recv().await.is_some() {}
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let snapshots = snapshots.lock().unwrap();
+        assert_eq!(snapshots.len(), 1);
+        let snapshot = &snapshots[0];
+        assert_eq!((snapshot.items_in, snapshot.items_out), (3, 3));
+        assert_eq!((snapshot.bytes_in, snapshot.bytes_out), (600, 600));
+    }
 }
@@ -1,4 +1,7 @@
-use std::{fmt::Display, time::Duration};
+use std::{fmt::Display, sync::Arc, time::Duration};
+
+use crate::ErrorPolicy;
+use crate::events::EventHandler;
 
 #[derive(Debug, Clone)]
 pub struct PipelineConfig {
@@ -10,6 +13,15 @@ pub struct PipelineConfig {
 
     /// Size of internal processing channels
     pub channel_size: usize,
+
+    /// Maximum bytes queued between pipeline stages (0 = unlimited)
+    pub max_in_flight_bytes: u64,
+
+    /// What the processors do with items they fail on, unless overridden per processor
+    pub error_policy: ErrorPolicy,
+
+    /// Handlers of the events of the pipelines run with the config, next to the logging one
+    pub event_handlers: Vec<Arc<dyn EventHandler>>,
 }
 
 impl Default for PipelineConfig {
@@ -18,6 +30,9 @@ impl Default for PipelineConfig {
             max_file_size: 0,
             max_duration: None,
             channel_size: 64,
+            max_in_flight_bytes: 0,
+            error_policy: ErrorPolicy::default(),
+            event_handlers: Vec::new(),
         }
     }
 }
@@ -35,10 +50,21 @@ impl Display for PipelineConfig {
             None => "unlimited".to_string(),
         };
 
+        let max_in_flight_display = if self.max_in_flight_bytes == 0 {
+            "unlimited".to_string()
+        } else {
+            format!("{} bytes", self.max_in_flight_bytes)
+        };
+
         write!(
             f,
-            "PipelineConfig {{ max_file_size: {}, max_duration: {}, channel_size: {} }}",
-            max_size_display, max_duration_display, self.channel_size
+            "PipelineConfig {{ max_file_size: {}, max_duration: {}, channel_size: {}, \
+             max_in_flight: {}, error_policy: {:?} }}",
+            max_size_display,
+            max_duration_display,
+            self.channel_size,
+            max_in_flight_display,
+            self.error_policy
         )
     }
 }
@@ -77,6 +103,21 @@ impl PipelineConfigBuilder {
         self
     }
 
+    pub fn max_in_flight_bytes(mut self, max_in_flight_bytes: u64) -> Self {
+        self.config.max_in_flight_bytes = max_in_flight_bytes;
+        self
+    }
+
+    pub fn error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.config.error_policy = error_policy;
+        self
+    }
+
+    pub fn event_handler(mut self, handler: Arc<dyn EventHandler>) -> Self {
+        self.config.event_handlers.push(handler);
+        self
+    }
+
     pub fn build(self) -> PipelineConfig {
         self.config
     }
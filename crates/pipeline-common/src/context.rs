@@ -3,19 +3,34 @@
 //! This module provides the context and configuration structures needed for
 //! stream processing. It includes the shared context for operators in the processing pipeline.
 
+use std::sync::Arc;
+
 use crate::cancellation::CancellationToken;
+use crate::events::{EventSink, PipelineEvent};
+use crate::media_info::SharedMediaInfo;
+use crate::memory::InFlightBytes;
+use crate::stats::PipelineStats;
 
 /// Shared context for stream processing operations
 ///
 /// Provides a common context shared across the processing pipeline including
-/// the stream name and cancellation token. This context is used
-/// by operators to coordinate their actions and share information.
+/// the stream name, cancellation token, in-flight memory, processing statistics, event
+/// handlers and media info. This context is used by operators to coordinate their actions
+/// and share information.
 #[derive(Debug, Clone)]
 pub struct StreamerContext {
     /// Name of the stream/file being processed
     pub name: String,
     /// The cancellation token
     pub token: CancellationToken,
+    /// Bytes queued between the stages of the pipeline
+    pub in_flight: Arc<InFlightBytes>,
+    /// Counters of the items processed, dropped and repaired by the pipeline
+    pub stats: Arc<PipelineStats>,
+    /// Codecs and parameters of the stream, once known
+    pub media_info: Arc<SharedMediaInfo>,
+    /// Handlers of the events the operators emit
+    pub events: Arc<EventSink>,
 }
 
 impl StreamerContext {
@@ -24,6 +39,10 @@ impl StreamerContext {
         Self {
             name: "DefaultStreamer".to_string(),
             token,
+            in_flight: Arc::new(InFlightBytes::default()),
+            stats: Arc::new(PipelineStats::default()),
+            media_info: Arc::new(SharedMediaInfo::default()),
+            events: Arc::new(EventSink::default()),
         }
     }
 
@@ -37,4 +56,15 @@ impl StreamerContext {
             ..Self::new(token)
         }
     }
+
+    /// Count `event` in the statistics and pass it to the event handlers.
+    ///
+    /// A repair is also counted as a repair of its operator.
+    pub fn emit(&self, event: PipelineEvent) {
+        if let PipelineEvent::RepairApplied { operator, .. } = &event {
+            self.stats.record_repair(*operator);
+        }
+        self.stats.record_event(event.name());
+        self.events.emit(&self.name, &event);
+    }
 }
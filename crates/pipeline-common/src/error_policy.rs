@@ -0,0 +1,145 @@
+//! # Error Recovery Policy
+//!
+//! How a pipeline reacts when a processor fails on a single item. By default any error
+//! aborts the pipeline; an [`ErrorPolicy`] lets a malformed item (a corrupt tag, a
+//! truncated segment) be skipped or replaced instead of ending a long recording.
+//!
+//! Only item-level errors ([`PipelineError::is_item_error`]) are recovered from. I/O
+//! errors, cancellation and failures while finishing a processor always abort.
+
+use std::sync::Arc;
+
+use crate::{PipelineError, StreamerContext};
+
+/// What a pipeline does when a processor fails on an item.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Abort the pipeline with the error.
+    #[default]
+    Abort,
+    /// Drop the item, aborting once more than `max_consecutive` items in a row or more
+    /// than `max_total` items in all failed.
+    SkipItem {
+        max_consecutive: u32,
+        max_total: u64,
+    },
+    /// Emit the last item the processor produced in place of the failed one, or drop the
+    /// item when it has produced none yet.
+    SubstituteLast,
+}
+
+/// What to do with an item a processor failed on.
+pub(crate) enum Recovery<T> {
+    Abort(PipelineError),
+    Skip,
+    Substitute(T),
+}
+
+/// Applies the [`ErrorPolicy`] of one processor and keeps the state it needs.
+pub(crate) struct ErrorRecovery<T> {
+    policy: ErrorPolicy,
+    /// Set when the processor was given its own policy
+    overridden: bool,
+    clone_of: Option<fn(&T) -> T>,
+    consecutive: u32,
+    total: u64,
+    last: Option<T>,
+}
+
+impl<T> ErrorRecovery<T> {
+    /// Recovery following the policy of the pipeline.
+    pub(crate) fn new(policy: ErrorPolicy, clone_of: Option<fn(&T) -> T>) -> Self {
+        Self {
+            policy,
+            overridden: false,
+            clone_of,
+            consecutive: 0,
+            total: 0,
+            last: None,
+        }
+    }
+
+    /// Recovery following a policy of the processor's own.
+    pub(crate) fn overriding(policy: ErrorPolicy, clone_of: fn(&T) -> T) -> Self {
+        Self {
+            overridden: true,
+            ..Self::new(policy, Some(clone_of))
+        }
+    }
+
+    /// Follow a new policy of the pipeline, unless the processor has its own.
+    pub(crate) fn set_default(&mut self, policy: ErrorPolicy, clone_of: fn(&T) -> T) {
+        if !self.overridden {
+            self.policy = policy;
+            self.clone_of = Some(clone_of);
+        }
+    }
+
+    /// Remember an item the processor produced, when the policy may substitute it.
+    pub(crate) fn observe(&mut self, item: &T) {
+        if self.policy == ErrorPolicy::SubstituteLast
+            && let Some(clone_of) = self.clone_of
+        {
+            self.last = Some(clone_of(item));
+        }
+    }
+
+    /// Record that the processor handled an item.
+    pub(crate) fn succeeded(&mut self) {
+        self.consecutive = 0;
+    }
+
+    /// Decide what happens after `processor` failed with `error`.
+    pub(crate) fn failed(
+        &mut self,
+        context: &Arc<StreamerContext>,
+        processor: &'static str,
+        error: PipelineError,
+    ) -> Recovery<T> {
+        if self.policy == ErrorPolicy::Abort || !error.is_item_error() {
+            return Recovery::Abort(error);
+        }
+
+        context.stats.record_error(processor);
+        self.consecutive = self.consecutive.saturating_add(1);
+        self.total = self.total.saturating_add(1);
+
+        match self.policy {
+            ErrorPolicy::Abort => Recovery::Abort(error),
+            ErrorPolicy::SkipItem {
+                max_consecutive,
+                max_total,
+            } => {
+                if self.consecutive > max_consecutive || self.total > max_total {
+                    tracing::error!(
+                        processor,
+                        consecutive = self.consecutive,
+                        total = self.total,
+                        "Too many items failed, aborting"
+                    );
+                    return Recovery::Abort(error);
+                }
+                tracing::warn!(processor, error = %error, "Skipping item that failed");
+                Recovery::Skip
+            }
+            ErrorPolicy::SubstituteLast => match (&self.last, self.clone_of) {
+                (Some(last), Some(clone_of)) => {
+                    tracing::warn!(
+                        processor,
+                        error = %error,
+                        "Substituting last item for item that failed"
+                    );
+                    Recovery::Substitute(clone_of(last))
+                }
+                _ => {
+                    tracing::warn!(
+                        processor,
+                        error = %error,
+                        "Skipping item that failed, no item to substitute yet"
+                    );
+                    Recovery::Skip
+                }
+            },
+        }
+    }
+}
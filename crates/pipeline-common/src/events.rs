@@ -0,0 +1,310 @@
+//! # Pipeline Events
+//!
+//! A structured account of what the operators did to a stream, next to the counters of
+//! [`stats`](crate::stats): the repairs they applied, the anomalies they found without
+//! repairing them, the splits of the output and the metadata rewritten once a file is
+//! complete.
+//!
+//! Operators emit a [`PipelineEvent`] with [`StreamerContext::emit`], which counts it in the
+//! [`PipelineStats`](crate::PipelineStats) and passes it to the [`EventHandler`]s of the
+//! context's [`EventSink`]. A new sink logs the events with `tracing` through
+//! [`TracingEventHandler`], the output the operators used to log themselves; a frontend
+//! adds its own handler, or an [`EventCollector`] to inspect the events once the run is
+//! over. The handlers of [`PipelineConfig::event_handlers`] are added to the contexts of
+//! the pipelines run with the config, e.g. an [`EventCounter`] summing up a whole job.
+//!
+//! [`PipelineConfig::event_handlers`]: crate::config::PipelineConfig::event_handlers
+//! [`StreamerContext::emit`]: crate::StreamerContext::emit
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+
+use tracing::{info, warn};
+
+use crate::split_reason::SplitReason;
+
+/// Something an operator did to the stream or found in it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineEvent {
+    /// The stream was changed to repair it.
+    RepairApplied {
+        /// Name of the operator that applied the repair
+        operator: &'static str,
+        /// What was repaired, e.g. `timestamp_jump`
+        kind: &'static str,
+        /// Timestamp in milliseconds of the repaired item, when it has one
+        offset: Option<u64>,
+        detail: String,
+    },
+    /// A problem of the stream was left as is.
+    AnomalyDetected {
+        operator: &'static str,
+        kind: &'static str,
+        offset: Option<u64>,
+        detail: String,
+    },
+    /// The output continues in a new file.
+    SplitOccurred {
+        operator: &'static str,
+        reason: Option<SplitReason>,
+        offset: Option<u64>,
+    },
+    /// The metadata of a complete file was rewritten.
+    MetadataRewritten { path: PathBuf, detail: String },
+}
+
+impl PipelineEvent {
+    /// Name of the kind of event, under which it is counted in the statistics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RepairApplied { .. } => "repair_applied",
+            Self::AnomalyDetected { .. } => "anomaly_detected",
+            Self::SplitOccurred { .. } => "split_occurred",
+            Self::MetadataRewritten { .. } => "metadata_rewritten",
+        }
+    }
+}
+
+impl fmt::Display for PipelineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepairApplied {
+                operator, detail, ..
+            }
+            | Self::AnomalyDetected {
+                operator, detail, ..
+            } => write!(f, "{operator}: {detail}"),
+            Self::SplitOccurred {
+                operator,
+                reason: Some(reason),
+                ..
+            } => write!(f, "{operator}: stream split on {reason}"),
+            Self::SplitOccurred { operator, .. } => write!(f, "{operator}: stream split"),
+            Self::MetadataRewritten { path, detail } => {
+                write!(f, "{}: {detail}", path.display())
+            }
+        }
+    }
+}
+
+/// Receives the events of a pipeline.
+///
+/// Handlers are called on the thread of the operator emitting the event and must return
+/// quickly.
+pub trait EventHandler: fmt::Debug + Send + Sync {
+    /// Handle `event` of the stream named `stream`.
+    fn handle(&self, stream: &str, event: &PipelineEvent);
+}
+
+/// Logs the events with `tracing`: anomalies as warnings, everything else as info.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TracingEventHandler;
+
+impl EventHandler for TracingEventHandler {
+    fn handle(&self, stream: &str, event: &PipelineEvent) {
+        match event {
+            PipelineEvent::AnomalyDetected { kind, offset, .. } => {
+                warn!(kind, offset, "{stream} {event}")
+            }
+            PipelineEvent::RepairApplied { kind, offset, .. } => {
+                info!(kind, offset, "{stream} {event}")
+            }
+            _ => info!("{stream} {event}"),
+        }
+    }
+}
+
+/// Keeps the events it receives.
+#[derive(Debug, Default)]
+pub struct EventCollector {
+    events: Mutex<Vec<PipelineEvent>>,
+}
+
+impl EventCollector {
+    /// The events received so far, in order.
+    pub fn events(&self) -> Vec<PipelineEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl EventHandler for EventCollector {
+    fn handle(&self, _stream: &str, event: &PipelineEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event.clone());
+    }
+}
+
+/// Counts the events it receives by [name](PipelineEvent::name), across streams.
+#[derive(Debug, Default)]
+pub struct EventCounter {
+    counts: Mutex<BTreeMap<String, u64>>,
+}
+
+impl EventCounter {
+    /// Number of events received of each kind.
+    pub fn counts(&self) -> BTreeMap<String, u64> {
+        self.counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl EventHandler for EventCounter {
+    fn handle(&self, _stream: &str, event: &PipelineEvent) {
+        *self
+            .counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(event.name().to_string())
+            .or_default() += 1;
+    }
+}
+
+/// The handlers the events of a context are passed to.
+pub struct EventSink {
+    handlers: RwLock<Vec<Arc<dyn EventHandler>>>,
+}
+
+impl Default for EventSink {
+    fn default() -> Self {
+        Self {
+            handlers: RwLock::new(vec![Arc::new(TracingEventHandler)]),
+        }
+    }
+}
+
+impl fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let handlers = self.handlers.read().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("EventSink")
+            .field("handlers", &handlers.len())
+            .finish()
+    }
+}
+
+impl EventSink {
+    /// Pass the events to `handler` as well.
+    pub fn add_handler(&self, handler: Arc<dyn EventHandler>) {
+        self.handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(handler);
+    }
+
+    /// Pass the events to `handler` only, e.g. to stop logging them.
+    pub fn set_handler(&self, handler: Arc<dyn EventHandler>) {
+        *self.handlers.write().unwrap_or_else(|e| e.into_inner()) = vec![handler];
+    }
+
+    /// Pass the events to each of `handlers` as well.
+    pub fn add_handlers<'a>(&self, handlers: impl IntoIterator<Item = &'a Arc<dyn EventHandler>>) {
+        self.handlers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(handlers.into_iter().cloned());
+    }
+
+    /// Pass `event` of the stream named `stream` to every handler.
+    pub fn emit(&self, stream: &str, event: &PipelineEvent) {
+        for handler in self
+            .handlers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            handler.handle(stream, event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CancellationToken, StreamerContext};
+
+    #[test]
+    fn test_events_reach_handlers_and_stats() {
+        let context = StreamerContext::new(CancellationToken::new());
+        let collector = Arc::new(EventCollector::default());
+        context.events.add_handler(collector.clone());
+
+        let repair = PipelineEvent::RepairApplied {
+            operator: "TimingRepairOperator",
+            kind: "timestamp_jump",
+            offset: Some(4000),
+            detail: "timestamp jumped back 2000ms".to_string(),
+        };
+        let split = PipelineEvent::SplitOccurred {
+            operator: "LimitOperator",
+            reason: Some(SplitReason::DurationLimit),
+            offset: Some(6000),
+        };
+        context.emit(repair.clone());
+        context.emit(split.clone());
+        context.emit(repair.clone());
+
+        assert_eq!(collector.events(), [repair.clone(), split, repair]);
+        let snapshot = context.stats.snapshot();
+        assert_eq!(snapshot.events.get("repair_applied"), Some(&2));
+        assert_eq!(snapshot.events.get("split_occurred"), Some(&1));
+        assert_eq!(snapshot.repairs.get("TimingRepairOperator"), Some(&2));
+        assert_eq!(snapshot.total_events(), 3);
+    }
+
+    #[test]
+    fn test_set_handler_replaces_logging() {
+        let sink = EventSink::default();
+        let collector = Arc::new(EventCollector::default());
+        sink.set_handler(collector.clone());
+        assert_eq!(format!("{sink:?}"), "EventSink { handlers: 1 }");
+
+        let event = PipelineEvent::MetadataRewritten {
+            path: PathBuf::from("out.flv"),
+            detail: "injected stats".to_string(),
+        };
+        sink.emit("stream", &event);
+        assert_eq!(event.to_string(), "out.flv: injected stats");
+        assert_eq!(collector.events(), [event]);
+    }
+
+    #[test]
+    fn test_counter_shared_by_sinks() {
+        let counter = Arc::new(EventCounter::default());
+        let handler: Arc<dyn EventHandler> = counter.clone();
+        let first = EventSink::default();
+        let second = EventSink::default();
+        first.add_handlers([&handler]);
+        second.add_handlers([&handler]);
+
+        let split = PipelineEvent::SplitOccurred {
+            operator: "SegmentLimiterOperator",
+            reason: None,
+            offset: None,
+        };
+        first.emit("first", &split);
+        second.emit("second", &split);
+        second.emit(
+            "second",
+            &PipelineEvent::AnomalyDetected {
+                operator: "DefragmentOperator",
+                kind: "fragment",
+                offset: Some(0),
+                detail: "fragmented stream".to_string(),
+            },
+        );
+
+        let mut expected = BTreeMap::new();
+        expected.insert("anomaly_detected".to_string(), 1);
+        expected.insert("split_occurred".to_string(), 2);
+        assert_eq!(format!("{second:?}"), "EventSink { handlers: 2 }");
+        assert_eq!(counter.counts(), expected);
+    }
+}
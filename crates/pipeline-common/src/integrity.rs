@@ -0,0 +1,753 @@
+//! # Integrity Manifests
+//!
+//! Checksums of the files written by a [`WriterTask`](crate::WriterTask), recorded in a
+//! JSON manifest so that a recording can later be checked for corruption with
+//! [`verify_manifest`].
+//!
+//! Files are hashed while they are written: every [`SinkWriter`](crate::SinkWriter) opened
+//! through a [`WriterConfig`](crate::WriterConfig) with an [`IntegrityRecorder`] feeds the
+//! data it writes to the configured [`HashAlgorithm`]s, so no file is read a second time.
+//! An [`IntegrityManifest`] added as a segment hook turns the result into a
+//! [`ManifestEntry`] when each file is closed, either in a manifest next to the file or in
+//! one manifest listing every part of the job.
+//!
+//! Strategies patch headers reserved at the start of a file once it is complete, such as
+//! the `onMetaData` of an FLV file. The first [`IntegrityConfig::head_bytes`] of a file are
+//! therefore hashed separately, when it is closed, and only the rest is hashed as it is
+//! written. The hash of a file with algorithm `H` is `H(H(head) || H(body))`. A local file
+//! that was changed elsewhere, or whose size differs from what was written, is hashed
+//! again from the disk.
+//!
+//! Changes made after the segment hooks ran are not covered: a file modified afterwards
+//! no longer matches its manifest.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tracing::{debug, warn};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::media_info::SharedMediaInfo;
+use crate::writer_task::{PostWriteAction, SegmentHook, SegmentStats};
+
+/// Version of the manifest format written by this crate.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Size of the reads when hashing a file from the disk.
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Errors of [`verify_manifest`].
+#[derive(Debug, Error)]
+pub enum IntegrityError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid manifest: {0}")]
+    Manifest(#[from] serde_json::Error),
+    #[error("Unsupported manifest version {0}")]
+    UnsupportedVersion(u32),
+}
+
+/// A hash function files can be checked with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// 64-bit XXH3, fast enough to keep up with any stream.
+    Xxh3,
+    /// SHA-256, for checks against deliberate tampering.
+    Sha256,
+}
+
+/// Incremental state of a [`HashAlgorithm`].
+enum Hasher {
+    Xxh3(Box<Xxh3>),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::new(Xxh3::new())),
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Xxh3(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Self::Xxh3(hasher) => hasher.digest().to_be_bytes().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+/// `H(H(head) || body)`, hex encoded, where `body` is the digest of the rest of the file.
+fn combine(algorithm: HashAlgorithm, head: &[u8], body: &[u8]) -> String {
+    let mut head_hasher = Hasher::new(algorithm);
+    head_hasher.update(head);
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update(&head_hasher.finish());
+    hasher.update(body);
+    hex::encode(hasher.finish())
+}
+
+/// Hashes the file at `path` the way it is hashed while written, returning its size and
+/// hashes.
+pub fn hash_file(
+    path: &Path,
+    algorithms: &[HashAlgorithm],
+    head_bytes: usize,
+) -> io::Result<(u64, BTreeMap<HashAlgorithm, String>)> {
+    let mut file = File::open(path)?;
+    let mut head = Vec::with_capacity(head_bytes.min(READ_CHUNK_SIZE));
+    (&mut file).take(head_bytes as u64).read_to_end(&mut head)?;
+
+    let mut hashers: Vec<_> = algorithms.iter().map(|&a| (a, Hasher::new(a))).collect();
+    let mut size = head.len() as u64;
+    let mut buf = vec![0; READ_CHUNK_SIZE];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        size += read as u64;
+        for (_, hasher) in &mut hashers {
+            hasher.update(&buf[..read]);
+        }
+    }
+
+    let hashes = hashers
+        .into_iter()
+        .map(|(algorithm, hasher)| (algorithm, combine(algorithm, &head, &hasher.finish())))
+        .collect();
+    Ok((size, hashes))
+}
+
+/// Where an [`IntegrityManifest`] writes its entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestMode {
+    /// A manifest next to every file, named after it, e.g. `part.flv.manifest.json`.
+    PerFile,
+    /// A single manifest at the given path listing every file of the job, rewritten
+    /// whenever a file is closed.
+    PerJob(PathBuf),
+}
+
+/// Configuration of an [`IntegrityManifest`].
+#[derive(Debug, Clone)]
+pub struct IntegrityConfig {
+    /// Hashes recorded for every file.
+    pub algorithms: Vec<HashAlgorithm>,
+    /// Where the manifest is written.
+    pub mode: ManifestMode,
+    /// Length of the start of a file that may still be patched before it is closed,
+    /// hashed separately once it is final.
+    pub head_bytes: usize,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self {
+            algorithms: vec![HashAlgorithm::Xxh3],
+            mode: ManifestMode::PerFile,
+            head_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl IntegrityConfig {
+    /// One manifest at `path` for every file of the job.
+    pub fn per_job(path: impl Into<PathBuf>) -> Self {
+        Self {
+            mode: ManifestMode::PerJob(path.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Set the hashes recorded for every file.
+    pub fn with_algorithms(mut self, algorithms: impl Into<Vec<HashAlgorithm>>) -> Self {
+        self.algorithms = algorithms.into();
+        self
+    }
+}
+
+/// A file as seen by its writer.
+#[derive(Debug)]
+struct WrittenFile {
+    local: bool,
+    size: u64,
+    head: Vec<u8>,
+    /// Digests of the data after the head, `None` if it was overwritten.
+    body: Option<Vec<(HashAlgorithm, Vec<u8>)>>,
+}
+
+#[derive(Debug)]
+struct RecorderInner {
+    algorithms: Vec<HashAlgorithm>,
+    head_bytes: usize,
+    written: Mutex<HashMap<PathBuf, WrittenFile>>,
+}
+
+/// Collects the hashes of the files written through a [`WriterConfig`](crate::WriterConfig)
+/// until the [`IntegrityManifest`] it belongs to records them.
+#[derive(Debug, Clone)]
+pub struct IntegrityRecorder {
+    inner: Arc<RecorderInner>,
+}
+
+impl IntegrityRecorder {
+    /// Starts hashing a file opened at `path`.
+    pub(crate) fn tap(&self, path: &Path, local: bool) -> DigestTap {
+        DigestTap {
+            recorder: self.clone(),
+            path: path.to_path_buf(),
+            local,
+            head: Vec::new(),
+            body: self
+                .inner
+                .algorithms
+                .iter()
+                .map(|&a| (a, Hasher::new(a)))
+                .collect(),
+            position: 0,
+            hashed: self.inner.head_bytes as u64,
+            size: 0,
+            overwritten: false,
+        }
+    }
+
+    fn take(&self, path: &Path) -> Option<WrittenFile> {
+        self.inner
+            .written
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(path)
+    }
+}
+
+/// Hashes the data written to a [`SinkWriter`](crate::SinkWriter), handing the result over
+/// to its [`IntegrityRecorder`] when the writer is dropped.
+pub(crate) struct DigestTap {
+    recorder: IntegrityRecorder,
+    path: PathBuf,
+    local: bool,
+    head: Vec<u8>,
+    body: Vec<(HashAlgorithm, Hasher)>,
+    /// Offset the next write goes to.
+    position: u64,
+    /// End of the data fed to the body hashers.
+    hashed: u64,
+    size: u64,
+    overwritten: bool,
+}
+
+impl DigestTap {
+    /// Records `data`, just written at the current position.
+    pub(crate) fn record(&mut self, data: &[u8]) {
+        let head_bytes = self.recorder.inner.head_bytes as u64;
+        let start = self.position;
+        let end = start + data.len() as u64;
+
+        if start < head_bytes {
+            let head_end = end.min(head_bytes) as usize;
+            if self.head.len() < head_end {
+                self.head.resize(head_end, 0);
+            }
+            self.head[start as usize..head_end].copy_from_slice(&data[..head_end - start as usize]);
+        }
+
+        let body_start = start.max(head_bytes);
+        if end > body_start {
+            if body_start == self.hashed && !self.overwritten {
+                let body = &data[(body_start - start) as usize..];
+                for (_, hasher) in &mut self.body {
+                    hasher.update(body);
+                }
+                self.hashed = end;
+            } else {
+                self.overwritten = true;
+            }
+        }
+
+        self.position = end;
+        self.size = self.size.max(end);
+    }
+
+    /// Records a seek to `position`.
+    pub(crate) fn seeked(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Replaces the head with `head`, the start of the file as it will be stored.
+    pub(crate) fn sync_head(&mut self, head: &[u8]) {
+        let len = self.head.len().min(head.len());
+        self.head[..len].copy_from_slice(&head[..len]);
+    }
+}
+
+impl Drop for DigestTap {
+    fn drop(&mut self) {
+        let body = (!self.overwritten).then(|| {
+            std::mem::take(&mut self.body)
+                .into_iter()
+                .map(|(algorithm, hasher)| (algorithm, hasher.finish()))
+                .collect()
+        });
+        let file = WrittenFile {
+            local: self.local,
+            size: self.size,
+            head: std::mem::take(&mut self.head),
+            body,
+        };
+        let path = std::mem::take(&mut self.path);
+        self.recorder
+            .inner
+            .written
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path, file);
+    }
+}
+
+/// A file listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path of the file, relative to the directory of the manifest unless absolute.
+    pub name: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Media duration in seconds.
+    pub duration_secs: f64,
+    /// When the file was opened, in RFC 3339 format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    /// Length of the head hashed separately, see the [module documentation](self).
+    pub head_bytes: usize,
+    /// Hex encoded hashes of the file.
+    pub hashes: BTreeMap<HashAlgorithm, String>,
+    /// Summary of the stream parameters when the file was closed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_info: Option<String>,
+}
+
+/// The content of a manifest file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    fn new(files: Vec<ManifestEntry>) -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            files,
+        }
+    }
+
+    /// Writes the manifest to `path`, replacing any previous version at once.
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temp, path)
+    }
+}
+
+/// Segment hook writing the [`Manifest`] of the files of a
+/// [`WriterTask`](crate::WriterTask).
+///
+/// Added with [`WriterTask::add_integrity_manifest`](crate::WriterTask::add_integrity_manifest),
+/// which also makes the writer hash its files. It should be the last hook to rename files,
+/// so that the manifest lists them under their final name.
+pub struct IntegrityManifest {
+    config: IntegrityConfig,
+    recorder: IntegrityRecorder,
+    media_info: Option<Arc<SharedMediaInfo>>,
+    /// Path the open file was created at, under which its writer records it.
+    opened: Option<PathBuf>,
+    created: Option<SystemTime>,
+    /// Files of the job, for [`ManifestMode::PerJob`].
+    files: Vec<ManifestEntry>,
+}
+
+impl IntegrityManifest {
+    pub fn new(config: IntegrityConfig) -> Self {
+        let recorder = IntegrityRecorder {
+            inner: Arc::new(RecorderInner {
+                algorithms: config.algorithms.clone(),
+                head_bytes: config.head_bytes,
+                written: Mutex::new(HashMap::new()),
+            }),
+        };
+        Self {
+            config,
+            recorder,
+            media_info: None,
+            opened: None,
+            created: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Record a summary of `media_info` with every file.
+    pub fn with_media_info(mut self, media_info: Arc<SharedMediaInfo>) -> Self {
+        self.media_info = Some(media_info);
+        self
+    }
+
+    /// The recorder to hash files with, to be set as
+    /// [`WriterConfig::integrity`](crate::WriterConfig::integrity).
+    pub fn recorder(&self) -> IntegrityRecorder {
+        self.recorder.clone()
+    }
+
+    /// The hashes of `written`, now stored at `path`.
+    fn hashes(
+        &self,
+        path: &Path,
+        written: &WrittenFile,
+    ) -> io::Result<(u64, BTreeMap<HashAlgorithm, String>)> {
+        let head_bytes = self.config.head_bytes;
+        let mut head = &written.head;
+        let stored_head;
+        if written.local {
+            let file = File::open(path)?;
+            let size = file.metadata()?.len();
+            if written.body.is_none() || size != written.size {
+                debug!(path = %path.display(), "File changed since it was written, hashing it again");
+                return hash_file(path, &self.config.algorithms, head_bytes);
+            }
+            let mut buf = Vec::with_capacity(written.head.len());
+            file.take(head_bytes as u64).read_to_end(&mut buf)?;
+            stored_head = buf;
+            head = &stored_head;
+        }
+
+        let Some(body) = &written.body else {
+            return Err(io::Error::other(
+                "remote file was overwritten while written",
+            ));
+        };
+        let hashes = body
+            .iter()
+            .map(|(algorithm, digest)| (*algorithm, combine(*algorithm, head, digest)))
+            .collect();
+        Ok((written.size, hashes))
+    }
+
+    /// Name of `path` in a manifest at `manifest`.
+    fn entry_name(path: &Path, manifest: &Path) -> String {
+        let relative = manifest
+            .parent()
+            .and_then(|dir| path.strip_prefix(dir).ok())
+            .unwrap_or(path);
+        relative.to_string_lossy().into_owned()
+    }
+
+    fn write_entry(&mut self, path: &Path, mut entry: ManifestEntry) -> io::Result<()> {
+        match &self.config.mode {
+            ManifestMode::PerFile => {
+                let mut manifest = path.as_os_str().to_owned();
+                manifest.push(".manifest.json");
+                let manifest = PathBuf::from(manifest);
+                entry.name = Self::entry_name(path, &manifest);
+                Manifest::new(vec![entry]).write_to(&manifest)
+            }
+            ManifestMode::PerJob(manifest) => {
+                entry.name = Self::entry_name(path, manifest);
+                self.files.push(entry);
+                Manifest::new(self.files.clone()).write_to(manifest)
+            }
+        }
+    }
+}
+
+impl SegmentHook for IntegrityManifest {
+    fn on_segment_open(&mut self, path: &Path, _index: u32) {
+        self.opened = Some(path.to_path_buf());
+        self.created = Some(SystemTime::now());
+    }
+
+    fn on_segment_close(
+        &mut self,
+        path: &Path,
+        _index: u32,
+        stats: &SegmentStats,
+    ) -> PostWriteAction {
+        let opened = self.opened.take().unwrap_or_else(|| path.to_path_buf());
+        let Some(written) = self.recorder.take(&opened) else {
+            warn!(path = %path.display(), "No hashes recorded, file missing from the manifest");
+            return PostWriteAction::None;
+        };
+
+        let (size, hashes) = match self.hashes(path, &written) {
+            Ok(hashes) => hashes,
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to hash file for the manifest");
+                return PostWriteAction::None;
+            }
+        };
+        let entry = ManifestEntry {
+            name: String::new(),
+            size,
+            duration_secs: stats.duration_secs,
+            created: self
+                .created
+                .take()
+                .and_then(|created| OffsetDateTime::from(created).format(&Rfc3339).ok()),
+            head_bytes: self.config.head_bytes,
+            hashes,
+            media_info: self
+                .media_info
+                .as_ref()
+                .and_then(|info| info.get())
+                .map(|info| info.to_string()),
+        };
+        if let Err(e) = self.write_entry(path, entry) {
+            warn!(path = %path.display(), error = %e, "Failed to write integrity manifest");
+        }
+        PostWriteAction::None
+    }
+}
+
+/// Why a file does not match its [`ManifestEntry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MismatchKind {
+    /// The file does not exist.
+    Missing,
+    /// The file has a different size.
+    Size { expected: u64, actual: u64 },
+    /// The file has a different hash.
+    Hash {
+        algorithm: HashAlgorithm,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// A file that does not match its [`ManifestEntry`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub kind: MismatchKind,
+}
+
+/// Outcome of [`verify_manifest`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Files matching their entry.
+    pub verified: Vec<PathBuf>,
+    /// Files that do not.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerifyReport {
+    /// Whether every file matches its entry.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Hashes the files listed in the manifest at `path` again and reports the ones that no
+/// longer match.
+pub fn verify_manifest(path: &Path) -> Result<VerifyReport, IntegrityError> {
+    let manifest: Manifest = serde_json::from_slice(&fs::read(path)?)?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(IntegrityError::UnsupportedVersion(manifest.version));
+    }
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut report = VerifyReport::default();
+    for entry in manifest.files {
+        let file = dir.join(&entry.name);
+        let algorithms: Vec<_> = entry.hashes.keys().copied().collect();
+        let (size, hashes) = match hash_file(&file, &algorithms, entry.head_bytes) {
+            Ok(hashed) => hashed,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                report.mismatches.push(Mismatch {
+                    path: file,
+                    kind: MismatchKind::Missing,
+                });
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mismatch = if size != entry.size {
+            Some(MismatchKind::Size {
+                expected: entry.size,
+                actual: size,
+            })
+        } else {
+            entry.hashes.into_iter().find_map(|(algorithm, expected)| {
+                let actual = hashes.get(&algorithm).cloned().unwrap_or_default();
+                (actual != expected).then_some(MismatchKind::Hash {
+                    algorithm,
+                    expected,
+                    actual,
+                })
+            })
+        };
+        match mismatch {
+            Some(kind) => report.mismatches.push(Mismatch { path: file, kind }),
+            None => report.verified.push(file),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WriterConfig;
+    use std::io::{Seek, SeekFrom, Write};
+    use tempfile::tempdir;
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn test_manifest(mode: ManifestMode) -> IntegrityManifest {
+        IntegrityManifest::new(IntegrityConfig {
+            algorithms: vec![HashAlgorithm::Xxh3, HashAlgorithm::Sha256],
+            mode,
+            head_bytes: 256,
+        })
+    }
+
+    /// Writes `data` to `path` the way a strategy does, patching the header at the start
+    /// of the file once the rest is written.
+    fn record(manifest: &mut IntegrityManifest, path: &Path, data: &[u8]) -> PostWriteAction {
+        let mut config = WriterConfig::new(PathBuf::new(), String::new(), String::new());
+        config.integrity = Some(manifest.recorder());
+        manifest.on_segment_open(path, 0);
+
+        let mut writer = config.open_output(path).unwrap();
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.seek(SeekFrom::Start(4)).unwrap();
+        writer.write_all(b"HEAD").unwrap();
+        writer.seek(SeekFrom::End(0)).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let stats = SegmentStats {
+            items_written: 1,
+            size_bytes: data.len() as u64,
+            duration_secs: 1.5,
+            split_reason: None,
+        };
+        manifest.on_segment_close(path, 0, &stats)
+    }
+
+    #[test]
+    fn test_written_hashes_match_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("part.flv");
+        let mut manifest = test_manifest(ManifestMode::PerFile);
+        record(&mut manifest, &path, &test_data(10_000));
+
+        let manifest_path = dir.path().join("part.flv.manifest.json");
+        let written: Manifest = serde_json::from_slice(&fs::read(&manifest_path).unwrap()).unwrap();
+        let [entry] = written.files.as_slice() else {
+            panic!("expected a single entry, got {:?}", written.files);
+        };
+        assert_eq!(entry.name, "part.flv");
+        assert_eq!(entry.size, 10_000);
+        assert_eq!(entry.duration_secs, 1.5);
+        assert!(entry.created.is_some());
+
+        // The streamed hash covers the patched header
+        let algorithms = [HashAlgorithm::Xxh3, HashAlgorithm::Sha256];
+        let (size, hashes) = hash_file(&path, &algorithms, 256).unwrap();
+        assert_eq!(size, 10_000);
+        assert_eq!(hashes, entry.hashes);
+
+        let report = verify_manifest(&manifest_path).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.verified, [path]);
+    }
+
+    #[test]
+    fn test_files_changed_before_close_are_hashed_again() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("part.flv");
+        let mut manifest = test_manifest(ManifestMode::PerFile);
+        manifest.on_segment_open(&path, 0);
+
+        let mut config = WriterConfig::new(PathBuf::new(), String::new(), String::new());
+        config.integrity = Some(manifest.recorder());
+        let mut writer = config.open_output(&path).unwrap();
+        writer.write_all(&test_data(5000)).unwrap();
+        drop(writer);
+
+        // Rewritten with a larger header, shifting the rest of the file
+        let mut data = test_data(5000);
+        data.splice(100..100, [0xAA; 64]);
+        fs::write(&path, &data).unwrap();
+
+        let stats = SegmentStats {
+            items_written: 1,
+            size_bytes: 5000,
+            duration_secs: 1.0,
+            split_reason: None,
+        };
+        manifest.on_segment_close(&path, 0, &stats);
+
+        let report = verify_manifest(&dir.path().join("part.flv.manifest.json")).unwrap();
+        assert!(report.is_ok(), "{report:?}");
+    }
+
+    #[test]
+    fn test_verify_reports_changed_and_missing_files() {
+        let dir = tempdir().unwrap();
+        let manifest_path = dir.path().join("job.json");
+        let mut manifest = test_manifest(ManifestMode::PerJob(manifest_path.clone()));
+        let names = ["a.ts", "b.ts", "c.ts"];
+        for name in names {
+            record(&mut manifest, &dir.path().join(name), &test_data(4000));
+        }
+        assert!(verify_manifest(&manifest_path).unwrap().is_ok());
+
+        // A flipped bit after the head, and a lost file
+        let a = dir.path().join("a.ts");
+        let mut data = fs::read(&a).unwrap();
+        data[3000] ^= 0x01;
+        fs::write(&a, data).unwrap();
+        fs::remove_file(dir.path().join("b.ts")).unwrap();
+
+        let report = verify_manifest(&manifest_path).unwrap();
+        assert_eq!(report.verified, [dir.path().join("c.ts")]);
+        assert_eq!(report.mismatches.len(), 2);
+        assert_eq!(report.mismatches[0].path, a);
+        assert!(matches!(
+            report.mismatches[0].kind,
+            MismatchKind::Hash {
+                algorithm: HashAlgorithm::Xxh3,
+                ..
+            }
+        ));
+        assert_eq!(report.mismatches[1].kind, MismatchKind::Missing);
+    }
+}
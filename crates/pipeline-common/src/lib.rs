@@ -9,6 +9,14 @@
 //! - Generic `Processor<T>` trait for processing any type of data
 //! - Generic `Pipeline<T>` implementation for chaining processors
 //! - Common error types and context sharing utilities
+//! - Structured events of the repairs and splits applied, with pluggable handlers
+//! - Rolling statistics reporting anomalies of the upstream encoder
+//! - Per-processor recovery from items that fail to process
+//! - Writing output files to the local disk, or uploading them over HTTP(S) with the
+//!   `upload` feature
+//! - A unified description of the codecs and parameters of a stream
+//! - Graceful shutdown that drains the pipeline and finalizes the output
+//! - Checksum manifests of the written files, with the `integrity` feature
 //!
 //! ## License
 //!
@@ -23,32 +31,58 @@ use std::sync::Arc;
 
 use thiserror::Error;
 
+pub mod anomaly;
 pub mod cancellation;
 pub mod channel_pipeline;
 pub mod config;
 mod context;
+pub mod error_policy;
+pub mod events;
+#[cfg(feature = "integrity")]
+pub mod integrity;
+pub mod media_info;
+pub mod memory;
+pub mod output_sink;
 pub mod pipeline;
 pub mod processor;
 pub mod progress;
 mod run_completion;
+pub mod shutdown;
 pub mod split_reason;
+pub mod stats;
 mod utils;
 mod writer_task;
 
 /// Re-export key traits and types
+pub use anomaly::{Anomaly, AnomalyConfig, AnomalyDetector, AnomalyKind};
 pub use channel_pipeline::ChannelPipeline;
 pub use context::StreamerContext;
+pub use error_policy::ErrorPolicy;
+pub use events::{
+    EventCollector, EventCounter, EventHandler, EventSink, PipelineEvent, TracingEventHandler,
+};
+pub use media_info::{AudioInfo, MediaInfo, MediaInfoValue, SharedMediaInfo, VideoInfo};
+pub use memory::{InFlightBytes, MemSized};
+pub use output_sink::{OutputSink, SinkWriter, UploadOptions};
 pub use pipeline::Pipeline;
 pub use processor::Processor;
-pub use progress::{Progress, ProgressEvent};
+pub use progress::{
+    ConnectionStats, DownloadRate, Progress, ProgressEvent, ProgressMetrics, ProgressSource,
+    ProgressState, ProgressTracker, ProgressTrackerConfig, SegmentWindowStats,
+};
 pub use run_completion::{RunCompletionError, settle_run};
+pub use shutdown::{RunOutcome, ShutdownHandle, run_until_shutdown};
+pub use stats::{CurrentFileHook, PipelineStats, StatsSnapshot};
 pub use utils::{
-    expand_filename_template, expand_path_template, expand_path_template_at, sanitize_filename,
+    StreamMetadata, TemplateError, expand_filename_template, expand_filename_template_with,
+    expand_path_template, expand_path_template_at, sanitize_filename,
+    template_uses_stream_metadata, validate_filename_template,
 };
 
 pub use writer_task::{
-    FormatStrategy, PostWriteAction, ProgressCallback, ProgressConfig, WriterConfig, WriterError,
-    WriterProgress, WriterState, WriterStats, WriterTask,
+    FormatStrategy, PostWriteAction, ProgressCallback, ProgressConfig, QueuedSegmentHook,
+    SegmentEvent, SegmentHook, SegmentStats, WriterConfig, WriterError, WriterProgress,
+    WriterState, WriterStats, WriterTask,
 };
 
 pub use split_reason::{AudioCodecInfo, SplitReason, VideoCodecInfo};
@@ -84,6 +118,20 @@ pub enum PipelineError {
         #[source]
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+
+    #[error("Processing failed: {0}")]
+    Processing(String),
+
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+}
+
+impl PipelineError {
+    /// Whether the error concerns a single item, so that the pipeline can carry on
+    /// without it under an [`ErrorPolicy`].
+    pub fn is_item_error(&self) -> bool {
+        matches!(self, Self::Processing(_) | Self::InvalidData(_))
+    }
 }
 
 pub trait ProtocolWriter: Send + 'static {
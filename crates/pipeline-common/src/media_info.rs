@@ -0,0 +1,285 @@
+//! # Media Info
+//!
+//! A description of what a stream carries, shared by the FLV and HLS pipelines: the video
+//! and audio codecs with their parameters, plus container-specific details.
+//!
+//! Pipelines publish it to [`StreamerContext::media_info`](crate::StreamerContext) as soon
+//! as the stream parameters are known, and again whenever they change mid-stream. Every
+//! publication is delivered to the subscribers as a [`ProgressEvent::MediaInfo`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::progress::ProgressEvent;
+use crate::split_reason::{AudioCodecInfo, VideoCodecInfo};
+
+/// Parameters of the video track.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoInfo {
+    /// Codec identifier (e.g. "AVC", "HEVC", "AV1").
+    pub codec: String,
+    /// Profile (e.g. 100 for AVC High, general_profile_idc for HEVC, seq_profile for AV1).
+    pub profile: Option<u8>,
+    /// Level (e.g. 40 for AVC Level 4.0, general_level_idc for HEVC, seq_level_idx_0 for AV1).
+    pub level: Option<u8>,
+    /// Width in pixels.
+    pub width: Option<u32>,
+    /// Height in pixels.
+    pub height: Option<u32>,
+    /// Frames per second, as signalled by the stream.
+    pub frame_rate: Option<f64>,
+}
+
+impl From<&VideoCodecInfo> for VideoInfo {
+    fn from(info: &VideoCodecInfo) -> Self {
+        Self {
+            codec: info.codec.clone(),
+            profile: info.profile,
+            level: info.level,
+            width: info.width,
+            height: info.height,
+            frame_rate: None,
+        }
+    }
+}
+
+/// Parameters of the audio track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioInfo {
+    /// Codec identifier (e.g. "AAC", "MP3").
+    pub codec: String,
+    /// Sample rate in Hz.
+    pub sample_rate: Option<u32>,
+    /// Number of channels.
+    pub channels: Option<u8>,
+}
+
+impl From<&AudioCodecInfo> for AudioInfo {
+    fn from(info: &AudioCodecInfo) -> Self {
+        Self {
+            codec: info.codec.clone(),
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+        }
+    }
+}
+
+/// A container-specific detail of a [`MediaInfo`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaInfoValue {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl fmt::Display for MediaInfoValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Text(value) => write!(f, "{value}"),
+            Self::Bool(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// What a stream carries.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaInfo {
+    /// The video track, if the stream has one.
+    pub video: Option<VideoInfo>,
+    /// The audio track, if the stream has one.
+    pub audio: Option<AudioInfo>,
+    /// Container-specific details, e.g. the `encoder` of an FLV `onMetaData` or the
+    /// `CODECS` advertised by an HLS playlist.
+    pub extras: BTreeMap<String, MediaInfoValue>,
+    /// Whether this replaces the media info published earlier in the stream.
+    pub changed: bool,
+}
+
+impl MediaInfo {
+    /// Whether nothing is known about the stream.
+    pub fn is_empty(&self) -> bool {
+        self.video.is_none() && self.audio.is_none() && self.extras.is_empty()
+    }
+
+    /// The video dimensions as width and height, if known.
+    pub fn resolution(&self) -> Option<(u32, u32)> {
+        let video = self.video.as_ref()?;
+        Some((video.width?, video.height?))
+    }
+
+    /// Whether both describe the same stream parameters, regardless of `changed`.
+    fn same_media(&self, other: &MediaInfo) -> bool {
+        self.video == other.video && self.audio == other.audio && self.extras == other.extras
+    }
+}
+
+impl fmt::Display for MediaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.video {
+            Some(video) => {
+                write!(f, "video: {}", video.codec)?;
+                if let (Some(width), Some(height)) = (video.width, video.height) {
+                    write!(f, " {width}x{height}")?;
+                }
+                if let Some(frame_rate) = video.frame_rate {
+                    write!(f, " @ {frame_rate:.2} fps")?;
+                }
+            }
+            None => write!(f, "video: none")?,
+        }
+        match &self.audio {
+            Some(audio) => {
+                write!(f, ", audio: {}", audio.codec)?;
+                if let Some(sample_rate) = audio.sample_rate {
+                    write!(f, " {sample_rate} Hz")?;
+                }
+                if let Some(channels) = audio.channels {
+                    write!(f, " {channels} ch")?;
+                }
+            }
+            None => write!(f, ", audio: none")?,
+        }
+        Ok(())
+    }
+}
+
+type Listener = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+/// The latest [`MediaInfo`] of a stream, shared through the
+/// [`StreamerContext`](crate::StreamerContext).
+#[derive(Default)]
+pub struct SharedMediaInfo {
+    current: Mutex<Option<MediaInfo>>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl fmt::Debug for SharedMediaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedMediaInfo")
+            .field("current", &self.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SharedMediaInfo {
+    /// The media info published last, if any.
+    pub fn get(&self) -> Option<MediaInfo> {
+        self.current
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Deliver every publication to `callback` as a [`ProgressEvent::MediaInfo`].
+    ///
+    /// A media info published before the subscription is delivered right away.
+    pub fn subscribe<F>(&self, callback: F)
+    where
+        F: Fn(ProgressEvent) + Send + Sync + 'static,
+    {
+        let callback: Listener = Arc::new(callback);
+        self.listeners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(callback.clone());
+        if let Some(info) = self.get() {
+            callback(ProgressEvent::MediaInfo(info));
+        }
+    }
+
+    /// Publish `info`, unless it describes the stream as published last.
+    ///
+    /// The first publication has `changed` unset, all later ones have it set. Returns
+    /// whether `info` was published.
+    pub fn publish(&self, mut info: MediaInfo) -> bool {
+        {
+            let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            match current.as_ref() {
+                Some(previous) if previous.same_media(&info) => return false,
+                previous => info.changed = previous.is_some(),
+            }
+            *current = Some(info.clone());
+        }
+
+        // Listeners run outside the locks, so they may read the shared info themselves
+        let listeners = self
+            .listeners
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for listener in listeners {
+            listener(ProgressEvent::MediaInfo(info.clone()));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(width: u32) -> MediaInfo {
+        MediaInfo {
+            video: Some(VideoInfo {
+                codec: "AVC".to_string(),
+                profile: Some(100),
+                level: Some(40),
+                width: Some(width),
+                height: Some(width * 9 / 16),
+                frame_rate: Some(30.0),
+            }),
+            audio: Some(AudioInfo {
+                codec: "AAC".to_string(),
+                sample_rate: Some(44100),
+                channels: Some(2),
+            }),
+            ..MediaInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_publish_flags_changes_only() {
+        let shared = SharedMediaInfo::default();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        shared.subscribe(move |event| {
+            if let ProgressEvent::MediaInfo(info) = event {
+                sink.lock().unwrap().push(info);
+            }
+        });
+
+        assert!(shared.publish(info(1920)));
+        assert!(!shared.publish(info(1920)));
+        assert!(shared.publish(info(1280)));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert!(!received[0].changed);
+        assert_eq!(received[0].resolution(), Some((1920, 1080)));
+        assert!(received[1].changed);
+        assert_eq!(received[1].resolution(), Some((1280, 720)));
+        assert_eq!(shared.get().as_ref(), Some(&received[1]));
+    }
+
+    #[test]
+    fn test_late_subscriber_receives_current_info() {
+        let shared = SharedMediaInfo::default();
+        shared.publish(info(1920));
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        shared.subscribe(move |event| sink.lock().unwrap().push(event));
+
+        let received = received.lock().unwrap();
+        assert!(matches!(
+            &received[..],
+            [ProgressEvent::MediaInfo(info)] if info.resolution() == Some((1920, 1080))
+        ));
+        assert_eq!(
+            info(1920).to_string(),
+            "video: AVC 1920x1080 @ 30.00 fps, audio: AAC 44100 Hz 2 ch"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! # In-Flight Memory Accounting
+//!
+//! Items travelling through a pipeline vary wildly in size, from a few bytes of
+//! FLV audio to multi-megabyte fMP4 segments, so an item count alone does not
+//! bound memory. Items implementing [`MemSized`] report their payload size, which
+//! lets a [`ChannelPipeline`](crate::ChannelPipeline) enforce a byte budget on the
+//! items queued between its stages and record how many bytes are in flight.
+//!
+//! The budget only applies to the channel pipeline. A [`Pipeline`](crate::Pipeline)
+//! runs its processors on one thread and hands each item straight to the next one,
+//! so nothing is queued between them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+/// Types that can report the approximate number of bytes they hold.
+pub trait MemSized {
+    /// Approximate heap size of the item in bytes.
+    fn mem_size(&self) -> usize;
+}
+
+impl MemSized for Vec<u8> {
+    fn mem_size(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Bytes queued between the stages of a pipeline.
+#[derive(Debug, Default)]
+pub struct InFlightBytes {
+    current: AtomicU64,
+    high_water: AtomicU64,
+    /// Held while checking for room and when notifying, so no release is missed
+    waiters: Mutex<()>,
+    /// Notified when bytes are released or a channel closes
+    released: Condvar,
+}
+
+impl InFlightBytes {
+    /// Bytes currently queued.
+    pub fn current(&self) -> u64 {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Highest number of bytes queued at once.
+    pub fn high_water(&self) -> u64 {
+        self.high_water.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add(&self, bytes: u64) {
+        let current = self.current.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.high_water.fetch_max(current, Ordering::Relaxed);
+    }
+
+    /// Releases `bytes` and wakes the stages waiting for room.
+    pub(crate) fn release(&self, bytes: u64) {
+        self.current.fetch_sub(bytes, Ordering::Relaxed);
+        self.wake();
+    }
+
+    /// Wakes the stages waiting for room, so they check again.
+    pub(crate) fn wake(&self) {
+        let _guard = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+        self.released.notify_all();
+    }
+
+    /// Blocks until `bytes` more fit within `budget`, or until `proceed` holds.
+    ///
+    /// `proceed` is checked again on every release and [`wake`](Self::wake).
+    pub(crate) fn wait_for_room(&self, bytes: u64, budget: u64, proceed: impl Fn() -> bool) {
+        let mut guard = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+        while self.current() + bytes > budget && !proceed() {
+            guard = self.released.wait(guard).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! A minimal HTTP server accepting the uploads of the remote [`OutputSink`]s.
+//!
+//! This module is available for local tests and optionally for downstream crate tests
+//! when the `test-utils` feature is enabled.
+//!
+//! [`OutputSink`]: super::OutputSink
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+#[derive(Default)]
+struct MockState {
+    /// Stored objects by request path.
+    objects: HashMap<String, Vec<u8>>,
+    /// Parts of the multipart uploads in progress, by upload id.
+    uploads: HashMap<String, (String, BTreeMap<u32, Vec<u8>>)>,
+    next_upload_id: u32,
+    aborted_uploads: usize,
+    /// Number of part uploads still to be answered with an error.
+    failing_parts: usize,
+    /// Number of plain `PUT`s still to be answered with an error.
+    failing_puts: usize,
+}
+
+/// Server storing plain `PUT`s and S3-style multipart uploads in memory.
+///
+/// Every request is answered on its own connection, which is closed afterwards.
+pub struct MockUploadServer {
+    address: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockUploadServer {
+    /// Starts a server on a free local port, running until the process exits.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock upload server");
+        let address = listener.local_addr().expect("mock upload server address");
+        let state = Arc::new(Mutex::new(MockState::default()));
+
+        let server_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = server_state.clone();
+                thread::spawn(move || {
+                    // Clients aborting a streamed upload close the connection mid-body
+                    let _ = handle_connection(stream, &state);
+                });
+            }
+        });
+
+        Self { address, state }
+    }
+
+    /// Base URL of the server, without a trailing slash.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Answers the next `count` part uploads with `500 Internal Server Error`.
+    pub fn fail_part_uploads(&self, count: usize) {
+        self.state.lock().unwrap().failing_parts = count;
+    }
+
+    /// Answers the next `count` plain `PUT`s with `500 Internal Server Error`.
+    pub fn fail_puts(&self, count: usize) {
+        self.state.lock().unwrap().failing_puts = count;
+    }
+
+    /// The object stored at `path`, e.g. `/bucket/file.flv`.
+    pub fn object(&self, path: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().objects.get(path).cloned()
+    }
+
+    /// Number of multipart uploads neither completed nor aborted.
+    pub fn pending_uploads(&self) -> usize {
+        self.state.lock().unwrap().uploads.len()
+    }
+
+    /// Number of multipart uploads aborted by the client.
+    pub fn aborted_uploads(&self) -> usize {
+        self.state.lock().unwrap().aborted_uploads
+    }
+}
+
+struct Reply {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Reply {
+    fn status(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, state: &Mutex<MockState>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut request_line = request_line.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let body = if headers
+        .get("transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        read_chunked(&mut reader)?
+    } else {
+        let len = headers
+            .get("content-length")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        body
+    };
+
+    let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    let query: HashMap<&str, &str> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect();
+    let reply = state.lock().unwrap().respond(&method, path, &query, body);
+
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reply.status,
+        reply.body.len()
+    );
+    for (name, value) in &reply.headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str("\r\n");
+    response.push_str(&reply.body);
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line)?;
+        let size = size_line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if size == 0 {
+            // Skip trailers
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+                    return Ok(body);
+                }
+            }
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        let mut crlf = [0; 2];
+        reader.read_exact(&mut crlf)?;
+    }
+}
+
+impl MockState {
+    fn respond(
+        &mut self,
+        method: &str,
+        path: &str,
+        query: &HashMap<&str, &str>,
+        body: Vec<u8>,
+    ) -> Reply {
+        match (method, query.get("uploadId")) {
+            ("POST", None) if query.contains_key("uploads") => {
+                self.next_upload_id += 1;
+                let upload_id = format!("upload-{}", self.next_upload_id);
+                self.uploads
+                    .insert(upload_id.clone(), (path.to_string(), BTreeMap::new()));
+                Reply {
+                    body: format!(
+                        "<InitiateMultipartUploadResult><UploadId>{upload_id}</UploadId>\
+                         </InitiateMultipartUploadResult>"
+                    ),
+                    ..Reply::status("200 OK")
+                }
+            }
+            ("PUT", None) => {
+                if self.failing_puts > 0 {
+                    self.failing_puts -= 1;
+                    return Reply::status("500 Internal Server Error");
+                }
+                self.objects.insert(path.to_string(), body);
+                Reply::status("200 OK")
+            }
+            ("PUT", Some(upload_id)) => {
+                let part_number = query.get("partNumber").and_then(|n| n.parse().ok());
+                let (Some((_, parts)), Some(part_number)) =
+                    (self.uploads.get_mut(*upload_id), part_number)
+                else {
+                    return Reply::status("404 Not Found");
+                };
+                if self.failing_parts > 0 {
+                    self.failing_parts -= 1;
+                    return Reply::status("500 Internal Server Error");
+                }
+                parts.insert(part_number, body);
+                Reply {
+                    headers: vec![("ETag", format!("\"etag-{part_number}\""))],
+                    ..Reply::status("200 OK")
+                }
+            }
+            ("POST", Some(upload_id)) => {
+                let Some((key, parts)) = self.uploads.remove(*upload_id) else {
+                    return Reply::status("404 Not Found");
+                };
+                self.objects
+                    .insert(key, parts.into_values().flatten().collect());
+                Reply {
+                    body: "<CompleteMultipartUploadResult></CompleteMultipartUploadResult>"
+                        .to_string(),
+                    ..Reply::status("200 OK")
+                }
+            }
+            ("DELETE", Some(upload_id)) => {
+                if self.uploads.remove(*upload_id).is_none() {
+                    return Reply::status("404 Not Found");
+                }
+                self.aborted_uploads += 1;
+                Reply::status("204 No Content")
+            }
+            _ => Reply::status("400 Bad Request"),
+        }
+    }
+}
@@ -0,0 +1,408 @@
+//! # Output Sinks
+//!
+//! Where a [`WriterTask`](crate::WriterTask) puts the files it writes. By default they go
+//! to the local disk; an [`OutputSink`] can instead stream every file to an HTTP(S) target,
+//! either as a single chunked `PUT` or as an S3-compatible multipart upload.
+//!
+//! Each upload runs on its own thread. Data is handed over in chunks through a bounded
+//! queue, so writes block once the network falls behind the stream instead of buffering
+//! without limit. An upload is only complete once [`SinkWriter::finish`] is called, which
+//! strategies do when the file is closed; a writer dropped before that aborts its upload.
+//!
+//! Remote files cannot be seeked. Multipart uploads hold back their first part until the
+//! file is finished, so that headers reserved at the start of the file can still be
+//! rewritten through [`SinkWriter::retained_head`].
+//!
+//! Uploading needs the `upload` feature, which brings in the HTTP client. Without it,
+//! opening a file on a remote sink fails with [`io::ErrorKind::Unsupported`].
+//!
+//! With the `integrity` feature, a writer can also hash the data it writes for an
+//! [`IntegrityManifest`](crate::integrity::IntegrityManifest).
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod mock;
+#[cfg(feature = "upload")]
+mod upload;
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+    time::Duration,
+};
+
+#[cfg(feature = "upload")]
+use upload::Upload;
+
+/// Destination of the files written by a [`WriterTask`](crate::WriterTask).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum OutputSink {
+    /// Files on the local disk.
+    #[default]
+    Local,
+    /// One streamed HTTP `PUT` per file, using chunked transfer encoding.
+    ///
+    /// `{name}` in the URL template is replaced by the file name. A failed request is sent
+    /// again from the start while all data sent so far fits in
+    /// [`UploadOptions::put_replay_bytes`]; beyond that, a failure ends the upload.
+    HttpPut {
+        url_template: String,
+        headers: Vec<(String, String)>,
+    },
+    /// One S3-compatible multipart upload per file, to `{endpoint}/{name}`.
+    ///
+    /// Failed parts are retried. S3 requires every part but the last to be at least 5 MiB.
+    Multipart { endpoint: String, part_size: usize },
+}
+
+impl OutputSink {
+    /// Whether files are written to the local disk.
+    pub fn is_local(&self) -> bool {
+        matches!(self, OutputSink::Local)
+    }
+
+    /// URL the file at `path` is uploaded to, `None` for local files.
+    pub fn object_url(&self, path: &Path) -> Option<String> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        match self {
+            OutputSink::Local => None,
+            OutputSink::HttpPut { url_template, .. } => Some(url_template.replace("{name}", &name)),
+            OutputSink::Multipart { endpoint, .. } => {
+                Some(format!("{}/{name}", endpoint.trim_end_matches('/')))
+            }
+        }
+    }
+}
+
+/// Tuning of uploads to remote [`OutputSink`]s.
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    /// Number of times a failed request is retried.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every further one.
+    pub retry_backoff: Duration,
+    /// Data of a file queued for upload before writes block, besides the part being
+    /// uploaded and the first part held back by multipart uploads.
+    pub max_buffered_bytes: usize,
+    /// Data of a streamed `PUT` kept to send it again if the request fails.
+    pub put_replay_bytes: usize,
+    /// Timeout for connecting to the target.
+    pub connect_timeout: Duration,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+            max_buffered_bytes: 64 * 1024 * 1024,
+            put_replay_bytes: 16 * 1024 * 1024,
+            connect_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Writer of a single file on an [`OutputSink`].
+///
+/// Local files support seeking as usual. Remote files only report their position, see
+/// [`SinkWriter::is_seekable`].
+pub struct SinkWriter {
+    output: Output,
+    #[cfg(feature = "integrity")]
+    digest: Option<crate::integrity::DigestTap>,
+}
+
+enum Output {
+    File(File),
+    #[cfg(feature = "upload")]
+    Upload(Upload),
+}
+
+impl SinkWriter {
+    /// Creates the file at `path` on `sink`, truncating local files that already exist.
+    #[cfg_attr(not(feature = "upload"), allow(unused_variables))]
+    pub fn open(sink: &OutputSink, options: &UploadOptions, path: &Path) -> io::Result<Self> {
+        let output = match sink {
+            OutputSink::Local => Output::File(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)?,
+            ),
+            #[cfg(feature = "upload")]
+            OutputSink::HttpPut { headers, .. } => Output::Upload(Upload::put(
+                sink.object_url(path).unwrap_or_default(),
+                headers.clone(),
+                options.clone(),
+            )?),
+            #[cfg(feature = "upload")]
+            OutputSink::Multipart { part_size, .. } => Output::Upload(Upload::multipart(
+                sink.object_url(path).unwrap_or_default(),
+                *part_size,
+                options.clone(),
+            )?),
+            #[cfg(not(feature = "upload"))]
+            OutputSink::HttpPut { .. } | OutputSink::Multipart { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "uploading files needs the `upload` feature of pipeline-common",
+                ));
+            }
+        };
+        Ok(Self {
+            output,
+            #[cfg(feature = "integrity")]
+            digest: None,
+        })
+    }
+
+    /// Hashes everything written from now on for `recorder`.
+    #[cfg(feature = "integrity")]
+    pub(crate) fn with_digest(
+        mut self,
+        recorder: &crate::integrity::IntegrityRecorder,
+        path: &Path,
+    ) -> Self {
+        self.digest = Some(recorder.tap(path, self.is_seekable()));
+        self
+    }
+
+    /// Whether written data can be read back and overwritten by seeking.
+    pub fn is_seekable(&self) -> bool {
+        matches!(self.output, Output::File(_))
+    }
+
+    /// The start of a multipart upload, which is not sent before the file is finished.
+    ///
+    /// Holds the first `part_size` bytes of the file, or everything written so far in a
+    /// smaller file. Changes must keep its length so that later parts stay in place.
+    pub fn retained_head(&mut self) -> Option<&mut Vec<u8>> {
+        match &mut self.output {
+            Output::File(_) => None,
+            #[cfg(feature = "upload")]
+            Output::Upload(upload) => upload.retained_head(),
+        }
+    }
+
+    /// Completes the file: sends the remaining data and finishes the upload, waiting for
+    /// the target to accept it.
+    ///
+    /// Nothing can be written afterwards. Dropping a writer of an unfinished upload
+    /// aborts it instead.
+    pub fn finish(&mut self) -> io::Result<()> {
+        #[cfg(all(feature = "integrity", feature = "upload"))]
+        if let Some(digest) = &mut self.digest
+            && let Output::Upload(upload) = &mut self.output
+            && let Some(head) = upload.retained_head()
+        {
+            digest.sync_head(head);
+        }
+        match &mut self.output {
+            Output::File(file) => file.flush(),
+            #[cfg(feature = "upload")]
+            Output::Upload(upload) => upload.finish(),
+        }
+    }
+}
+
+impl Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match &mut self.output {
+            Output::File(file) => file.write(buf)?,
+            #[cfg(feature = "upload")]
+            Output::Upload(upload) => upload.write(buf)?,
+        };
+        #[cfg(feature = "integrity")]
+        if let Some(digest) = &mut self.digest {
+            digest.record(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    /// Flushes local files. Uploads send data in whole chunks and keep the rest until
+    /// [`SinkWriter::finish`].
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.output {
+            Output::File(file) => file.flush(),
+            #[cfg(feature = "upload")]
+            Output::Upload(_) => Ok(()),
+        }
+    }
+}
+
+impl Seek for SinkWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match &mut self.output {
+            Output::File(file) => file.seek(pos)?,
+            #[cfg(feature = "upload")]
+            Output::Upload(upload) => upload.seek(pos)?,
+        };
+        #[cfg(feature = "integrity")]
+        if let Some(digest) = &mut self.digest {
+            digest.seeked(position);
+        }
+        Ok(position)
+    }
+}
+
+#[cfg(all(test, feature = "upload"))]
+mod tests {
+    use super::mock::MockUploadServer;
+    use super::*;
+
+    fn test_data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+    }
+
+    /// Writes `data` in uneven pieces and finishes the file.
+    fn write_file(sink: &OutputSink, path: &Path, data: &[u8]) -> io::Result<()> {
+        let mut writer = SinkWriter::open(sink, &UploadOptions::default(), path)?;
+        for piece in data.chunks(3001) {
+            writer.write_all(piece)?;
+        }
+        writer.finish()
+    }
+
+    fn multipart(server: &MockUploadServer, part_size: usize) -> OutputSink {
+        OutputSink::Multipart {
+            endpoint: format!("{}/bucket/", server.url()),
+            part_size,
+        }
+    }
+
+    #[test]
+    fn test_uploads_match_local_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockUploadServer::start();
+        let data = test_data(300_000);
+
+        let local = dir.path().join("local.bin");
+        write_file(&OutputSink::Local, &local, &data).unwrap();
+        let put = OutputSink::HttpPut {
+            url_template: format!("{}/put/{{name}}", server.url()),
+            headers: vec![("x-test".to_string(), "1".to_string())],
+        };
+        write_file(&put, Path::new("put.bin"), &data).unwrap();
+        write_file(
+            &multipart(&server, 64 * 1024),
+            Path::new("parts.bin"),
+            &data,
+        )
+        .unwrap();
+        write_file(
+            &multipart(&server, 1024 * 1024),
+            Path::new("small.bin"),
+            &data,
+        )
+        .unwrap();
+
+        let local = std::fs::read(local).unwrap();
+        assert_eq!(local, data);
+        assert_eq!(server.object("/put/put.bin").unwrap(), local);
+        assert_eq!(server.object("/bucket/parts.bin").unwrap(), local);
+        assert_eq!(server.object("/bucket/small.bin").unwrap(), local);
+        assert_eq!(server.pending_uploads(), 0);
+    }
+
+    #[test]
+    fn test_failed_parts_are_retried() {
+        let server = MockUploadServer::start();
+        server.fail_part_uploads(2);
+        let options = UploadOptions {
+            retry_backoff: Duration::from_millis(1),
+            ..UploadOptions::default()
+        };
+        let data = test_data(50_000);
+
+        let path = Path::new("retried.bin");
+        let mut writer = SinkWriter::open(&multipart(&server, 16 * 1024), &options, path).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(server.object("/bucket/retried.bin").unwrap(), data);
+    }
+
+    #[test]
+    fn test_failed_put_is_sent_again() {
+        let server = MockUploadServer::start();
+        let put = OutputSink::HttpPut {
+            url_template: format!("{}/put/{{name}}", server.url()),
+            headers: Vec::new(),
+        };
+        let options = UploadOptions {
+            retry_backoff: Duration::from_millis(1),
+            put_replay_bytes: 64 * 1024,
+            ..UploadOptions::default()
+        };
+        let data = test_data(50_000);
+
+        server.fail_puts(2);
+        let mut writer = SinkWriter::open(&put, &options, Path::new("replayed.bin")).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(server.object("/put/replayed.bin").unwrap(), data);
+
+        // A body larger than the replay limit is no longer at hand
+        server.fail_puts(1);
+        let mut writer = SinkWriter::open(&put, &options, Path::new("large.bin")).unwrap();
+        writer.write_all(&test_data(300_000)).unwrap();
+        assert!(writer.finish().is_err());
+        assert!(server.object("/put/large.bin").is_none());
+    }
+
+    #[test]
+    fn test_unfinished_upload_is_aborted() {
+        let server = MockUploadServer::start();
+        let sink = multipart(&server, 16 * 1024);
+
+        let mut writer =
+            SinkWriter::open(&sink, &UploadOptions::default(), Path::new("aborted.bin")).unwrap();
+        writer.write_all(&test_data(40_000)).unwrap();
+        drop(writer);
+
+        assert!(server.object("/bucket/aborted.bin").is_none());
+        assert_eq!(server.aborted_uploads(), 1);
+        assert_eq!(server.pending_uploads(), 0);
+    }
+
+    #[test]
+    fn test_retained_head_is_patched_before_upload() {
+        let server = MockUploadServer::start();
+        let sink = multipart(&server, 16 * 1024);
+        let mut data = test_data(40_000);
+
+        let path = Path::new("patched.bin");
+        let mut writer = SinkWriter::open(&sink, &UploadOptions::default(), path).unwrap();
+        assert!(!writer.is_seekable());
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.stream_position().unwrap(), data.len() as u64);
+        assert!(writer.seek(SeekFrom::Start(0)).is_err());
+
+        let head = writer.retained_head().unwrap();
+        assert_eq!(head.len(), 16 * 1024);
+        head[..4].copy_from_slice(b"FLV!");
+        writer.finish().unwrap();
+
+        data[..4].copy_from_slice(b"FLV!");
+        assert_eq!(server.object("/bucket/patched.bin").unwrap(), data);
+    }
+}
+
+#[cfg(all(test, not(feature = "upload")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_sinks_need_upload_feature() {
+        let sink = OutputSink::Multipart {
+            endpoint: "http://127.0.0.1:1/bucket".to_string(),
+            part_size: 1024,
+        };
+        let result = SinkWriter::open(&sink, &UploadOptions::default(), Path::new("a.bin"));
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,544 @@
+//! Upload threads of the remote output sinks.
+
+use std::{
+    io::{self, Read, SeekFrom},
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock,
+        mpsc::{self, Receiver, SyncSender},
+    },
+    thread::{self, JoinHandle},
+};
+
+use reqwest::blocking::{Body, Client, Response};
+use tracing::{debug, warn};
+
+use super::UploadOptions;
+
+/// Size of the chunks a streamed `PUT` is sent in.
+const PUT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Data handed to an upload thread.
+enum Message {
+    Data(Vec<u8>),
+    /// The file is complete, with the held back first part of a multipart upload.
+    Finish(Option<Vec<u8>>),
+}
+
+/// Writing side of an upload running on its own thread.
+pub(super) struct Upload {
+    url: String,
+    chunk_size: usize,
+    /// Data not handed to the upload thread yet.
+    buffer: Vec<u8>,
+    /// Whether the first chunk is held back until the upload is finished.
+    retain_head: bool,
+    head: Option<Vec<u8>>,
+    position: u64,
+    sender: Option<SyncSender<Message>>,
+    worker: Option<JoinHandle<io::Result<()>>>,
+    finished: bool,
+}
+
+impl Upload {
+    /// Starts a streamed `PUT` of a file to `url`.
+    pub(super) fn put(
+        url: String,
+        headers: Vec<(String, String)>,
+        options: UploadOptions,
+    ) -> io::Result<Self> {
+        let target = url.clone();
+        let max_buffered_bytes = options.max_buffered_bytes;
+        Self::start(
+            url,
+            PUT_CHUNK_SIZE,
+            false,
+            max_buffered_bytes,
+            move |receiver| put_file(&target, &headers, &options, receiver),
+        )
+    }
+
+    /// Starts a multipart upload of a file to `url`.
+    pub(super) fn multipart(
+        url: String,
+        part_size: usize,
+        options: UploadOptions,
+    ) -> io::Result<Self> {
+        let target = url.clone();
+        let max_buffered_bytes = options.max_buffered_bytes;
+        Self::start(
+            url,
+            part_size.max(1),
+            true,
+            max_buffered_bytes,
+            move |receiver| multipart_upload(&target, &options, receiver),
+        )
+    }
+
+    fn start<F>(
+        url: String,
+        chunk_size: usize,
+        retain_head: bool,
+        max_buffered_bytes: usize,
+        run: F,
+    ) -> io::Result<Self>
+    where
+        F: FnOnce(Receiver<Message>) -> io::Result<()> + Send + 'static,
+    {
+        let capacity = (max_buffered_bytes / chunk_size).max(1);
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let worker = thread::Builder::new()
+            .name("output-upload".to_string())
+            .spawn(move || run(receiver))?;
+        debug!("Started upload to {url}");
+
+        Ok(Self {
+            url,
+            chunk_size,
+            buffer: Vec::new(),
+            retain_head,
+            head: None,
+            position: 0,
+            sender: Some(sender),
+            worker: Some(worker),
+            finished: false,
+        })
+    }
+
+    pub(super) fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.sender.is_none() {
+            return Err(io::Error::other(format!(
+                "upload to {} is closed",
+                self.url
+            )));
+        }
+        self.buffer.extend_from_slice(buf);
+        self.position += buf.len() as u64;
+
+        while self.buffer.len() >= self.chunk_size {
+            let rest = self.buffer.split_off(self.chunk_size);
+            let chunk = std::mem::replace(&mut self.buffer, rest);
+            if self.retain_head && self.head.is_none() {
+                self.head = Some(chunk);
+            } else {
+                self.send(Message::Data(chunk))?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Only answers position queries, remote files cannot be seeked.
+    pub(super) fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) | SeekFrom::End(0) => Ok(self.position),
+            SeekFrom::Start(offset) if offset == self.position => Ok(self.position),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "remote outputs cannot seek",
+            )),
+        }
+    }
+
+    pub(super) fn retained_head(&mut self) -> Option<&mut Vec<u8>> {
+        if !self.retain_head {
+            return None;
+        }
+        // Until the first part is complete, everything written is still buffered
+        match &mut self.head {
+            Some(head) => Some(head),
+            None => Some(&mut self.buffer),
+        }
+    }
+
+    pub(super) fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        let mut rest = std::mem::take(&mut self.buffer);
+        let head = self.retain_head.then(|| {
+            self.head
+                .take()
+                .unwrap_or_else(|| std::mem::take(&mut rest))
+        });
+        if !rest.is_empty() {
+            self.send(Message::Data(rest))?;
+        }
+        self.send(Message::Finish(head))?;
+        self.join()?;
+
+        self.finished = true;
+        debug!("Finished upload to {} ({} bytes)", self.url, self.position);
+        Ok(())
+    }
+
+    /// Hands `message` to the upload thread, returning its error if it has stopped.
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(message).is_ok());
+        if sent {
+            return Ok(());
+        }
+        match self.join() {
+            Err(e) => Err(e),
+            Ok(()) => Err(io::Error::other(format!(
+                "upload to {} ended early",
+                self.url
+            ))),
+        }
+    }
+
+    /// Closes the queue and waits for the upload thread.
+    fn join(&mut self) -> io::Result<()> {
+        self.sender = None;
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("upload thread panicked"))),
+            None => Err(io::Error::other(format!(
+                "upload to {} already failed",
+                self.url
+            ))),
+        }
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        if self.finished || self.worker.is_none() {
+            return;
+        }
+        // Closing the queue without finishing makes the upload thread abort
+        if let Err(e) = self.join() {
+            debug!("Upload to {} aborted: {e}", self.url);
+        }
+    }
+}
+
+/// Body of a streamed `PUT`, reading the chunks handed to the upload thread.
+///
+/// Everything read is kept while it fits in the replay limit, so that a failed request
+/// can be sent again from the start.
+struct PutBody {
+    receiver: Receiver<Message>,
+    chunk: Vec<u8>,
+    offset: usize,
+    /// Data read from the queue, `None` once it outgrew the replay limit.
+    sent: Option<Vec<u8>>,
+    replay_limit: usize,
+    done: bool,
+    aborted: bool,
+}
+
+impl PutBody {
+    fn new(receiver: Receiver<Message>, replay_limit: usize) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            offset: 0,
+            sent: Some(Vec::new()),
+            replay_limit,
+            done: false,
+            aborted: false,
+        }
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.receiver.recv() {
+                Ok(Message::Data(chunk)) => {
+                    self.keep(&chunk);
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                Ok(Message::Finish(_)) => self.done = true,
+                // Fail the request so that the target does not keep a truncated file
+                Err(_) => {
+                    self.aborted = true;
+                    return Err(aborted());
+                }
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.offset);
+        buf[..len].copy_from_slice(&self.chunk[self.offset..self.offset + len]);
+        self.offset += len;
+        Ok(len)
+    }
+
+    fn keep(&mut self, chunk: &[u8]) {
+        let fits = self
+            .sent
+            .as_ref()
+            .is_some_and(|sent| sent.len() + chunk.len() <= self.replay_limit);
+        if !fits {
+            self.sent = None;
+        } else if let Some(sent) = &mut self.sent {
+            sent.extend_from_slice(chunk);
+        }
+    }
+
+    /// Starts over for another request, if everything read so far was kept.
+    fn rewind(&mut self) -> bool {
+        match &self.sent {
+            Some(sent) if !self.aborted => {
+                self.chunk = sent.clone();
+                self.offset = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Reader handed to a request, sharing the body with the later attempts.
+struct PutReader(Arc<Mutex<PutBody>>);
+
+impl Read for PutReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        lock(&self.0).read(buf)
+    }
+}
+
+fn lock(body: &Mutex<PutBody>) -> MutexGuard<'_, PutBody> {
+    body.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn put_file(
+    url: &str,
+    headers: &[(String, String)],
+    options: &UploadOptions,
+    receiver: Receiver<Message>,
+) -> io::Result<()> {
+    let client = build_client(options)?;
+    let body = Arc::new(Mutex::new(PutBody::new(receiver, options.put_replay_bytes)));
+    let can_retry = || lock(&body).rewind();
+    retry_while(options, "upload file", can_retry, || {
+        let mut request = client.put(url).body(Body::new(PutReader(body.clone())));
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().map_err(io::Error::other)?;
+        check_status(response, "PUT")?;
+        Ok(())
+    })
+}
+
+fn multipart_upload(
+    url: &str,
+    options: &UploadOptions,
+    receiver: Receiver<Message>,
+) -> io::Result<()> {
+    let client = build_client(options)?;
+    let upload_id = with_retries(options, "initiate multipart upload", || {
+        initiate_multipart(&client, url)
+    })?;
+    debug!("Initiated multipart upload {upload_id} to {url}");
+
+    let result = upload_parts(&client, url, &upload_id, options, &receiver);
+    if result.is_err() {
+        abort_multipart(&client, url, &upload_id);
+    }
+    result
+}
+
+fn upload_parts(
+    client: &Client,
+    url: &str,
+    upload_id: &str,
+    options: &UploadOptions,
+    receiver: &Receiver<Message>,
+) -> io::Result<()> {
+    // Part 1 is the held back head, sent last
+    let mut parts = Vec::new();
+    let mut part_number = 2;
+    loop {
+        match receiver.recv() {
+            Ok(Message::Data(data)) => {
+                let etag = with_retries(options, "upload part", || {
+                    upload_part(client, url, upload_id, part_number, &data)
+                })?;
+                parts.push((part_number, etag));
+                part_number += 1;
+            }
+            Ok(Message::Finish(head)) => {
+                if let Some(head) = head {
+                    let etag = with_retries(options, "upload part", || {
+                        upload_part(client, url, upload_id, 1, &head)
+                    })?;
+                    parts.insert(0, (1, etag));
+                }
+                return with_retries(options, "complete multipart upload", || {
+                    complete_multipart(client, url, upload_id, &parts)
+                });
+            }
+            Err(_) => return Err(aborted()),
+        }
+    }
+}
+
+fn initiate_multipart(client: &Client, url: &str) -> io::Result<String> {
+    let response = client
+        .post(format!("{url}?uploads"))
+        .send()
+        .map_err(io::Error::other)?;
+    let body = check_status(response, "initiate multipart upload")?
+        .text()
+        .map_err(io::Error::other)?;
+    xml_value(&body, "UploadId")
+        .map(str::to_string)
+        .ok_or_else(|| io::Error::other("no UploadId in multipart upload response"))
+}
+
+fn upload_part(
+    client: &Client,
+    url: &str,
+    upload_id: &str,
+    part_number: u32,
+    data: &[u8],
+) -> io::Result<String> {
+    let response = client
+        .put(url)
+        .query(&[
+            ("partNumber", part_number.to_string()),
+            ("uploadId", upload_id.to_string()),
+        ])
+        .body(data.to_vec())
+        .send()
+        .map_err(io::Error::other)?;
+    let response = check_status(response, "upload part")?;
+    response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string)
+        .ok_or_else(|| io::Error::other(format!("no ETag for part {part_number}")))
+}
+
+fn complete_multipart(
+    client: &Client,
+    url: &str,
+    upload_id: &str,
+    parts: &[(u32, String)],
+) -> io::Result<()> {
+    let mut body = String::from("<CompleteMultipartUpload>");
+    for (number, etag) in parts {
+        body.push_str(&format!(
+            "<Part><PartNumber>{number}</PartNumber><ETag>{etag}</ETag></Part>"
+        ));
+    }
+    body.push_str("</CompleteMultipartUpload>");
+
+    let response = client
+        .post(url)
+        .query(&[("uploadId", upload_id)])
+        .body(body)
+        .send()
+        .map_err(io::Error::other)?;
+    let text = check_status(response, "complete multipart upload")?
+        .text()
+        .map_err(io::Error::other)?;
+    // S3 may report a failed completion in the body of a successful response
+    if let Some(code) = xml_value(&text, "Code") {
+        return Err(io::Error::other(format!(
+            "complete multipart upload failed: {code}"
+        )));
+    }
+    Ok(())
+}
+
+fn abort_multipart(client: &Client, url: &str, upload_id: &str) {
+    let result = client
+        .delete(url)
+        .query(&[("uploadId", upload_id)])
+        .send()
+        .map_err(io::Error::other)
+        .and_then(|response| check_status(response, "abort multipart upload"));
+    match result {
+        Ok(_) => debug!("Aborted multipart upload {upload_id} to {url}"),
+        Err(e) => warn!("Failed to abort multipart upload {upload_id} to {url}: {e}"),
+    }
+}
+
+/// Runs `request`, retrying failures with an exponential backoff.
+fn with_retries<T>(
+    options: &UploadOptions,
+    action: &str,
+    request: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    retry_while(options, action, || true, request)
+}
+
+/// Like [`with_retries`], but only retries a failure while `can_retry` allows it.
+fn retry_while<T>(
+    options: &UploadOptions,
+    action: &str,
+    mut can_retry: impl FnMut() -> bool,
+    mut request: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    let mut attempt = 0;
+    loop {
+        match request() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < options.max_retries && can_retry() => {
+                let delay = options
+                    .retry_backoff
+                    .saturating_mul(2u32.saturating_pow(attempt));
+                attempt += 1;
+                warn!("Failed to {action} (attempt {attempt}), retrying in {delay:?}: {e}");
+                thread::sleep(delay);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn check_status(response: Response, action: &str) -> io::Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(io::Error::other(format!(
+            "{action} failed with HTTP {status}"
+        )))
+    }
+}
+
+fn build_client(options: &UploadOptions) -> io::Result<Client> {
+    install_rustls_provider();
+    // Streams are uploaded for as long as they last, only connecting may time out
+    Client::builder()
+        .connect_timeout(options.connect_timeout)
+        .timeout(None)
+        .build()
+        .map_err(io::Error::other)
+}
+
+fn install_rustls_provider() {
+    // `reqwest` is configured with `rustls-tls-*-no-provider`; install one globally.
+    static PROVIDER_INSTALLED: OnceLock<()> = OnceLock::new();
+    PROVIDER_INSTALLED.get_or_init(|| {
+        if let Err(e) = rustls::crypto::aws_lc_rs::default_provider().install_default() {
+            debug!(existing_provider = ?e, "rustls CryptoProvider already installed");
+        }
+    });
+}
+
+/// Text of the first `<tag>` element in an XML document.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let start = xml.find(&open)? + open.len();
+    let len = xml[start..].find(&format!("</{tag}>"))?;
+    Some(&xml[start..start + len])
+}
+
+fn aborted() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::ConnectionAborted,
+        "upload aborted before the file was finished",
+    )
+}
@@ -9,7 +9,8 @@
 //! trait. Then process a stream of data through the pipeline.
 //!
 
-use crate::{PipelineError, Processor, StreamerContext};
+use crate::error_policy::{ErrorRecovery, Recovery};
+use crate::{ErrorPolicy, PipelineError, Processor, StreamerContext};
 use std::sync::Arc;
 use tracing_indicatif::span_ext::IndicatifSpanExt;
 
@@ -22,6 +23,10 @@ const DEFAULT_STAGE_CAPACITY: usize = 8;
 /// receiving outputs from the previous one in the chain.
 pub struct Pipeline<T> {
     processors: Vec<Box<dyn Processor<T> + Send>>,
+    /// Error recovery of each processor, by position
+    recoveries: Vec<ErrorRecovery<T>>,
+    error_policy: ErrorPolicy,
+    clone_of: Option<fn(&T) -> T>,
     context: Arc<StreamerContext>,
 }
 
@@ -30,6 +35,9 @@ impl<T> Pipeline<T> {
     pub fn new(context: Arc<StreamerContext>) -> Self {
         Self {
             processors: Vec::new(),
+            recoveries: Vec::new(),
+            error_policy: ErrorPolicy::default(),
+            clone_of: None,
             context,
         }
     }
@@ -39,6 +47,36 @@ impl<T> Pipeline<T> {
     /// Returns self for method chaining.
     pub fn add_processor<P: Processor<T> + Send + 'static>(mut self, processor: P) -> Self {
         self.processors.push(Box::new(processor));
+        self.recoveries
+            .push(ErrorRecovery::new(self.error_policy, self.clone_of));
+        self
+    }
+
+    /// Add a processor to the end of the pipeline, with its own [`ErrorPolicy`].
+    pub fn add_processor_with_error_policy<P: Processor<T> + Send + 'static>(
+        mut self,
+        processor: P,
+        error_policy: ErrorPolicy,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        self.processors.push(Box::new(processor));
+        self.recoveries
+            .push(ErrorRecovery::overriding(error_policy, T::clone));
+        self
+    }
+
+    /// Set what the processors without a policy of their own do with items they fail on.
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self
+    where
+        T: Clone,
+    {
+        self.error_policy = error_policy;
+        self.clone_of = Some(T::clone);
+        for recovery in &mut self.recoveries {
+            recovery.set_default(error_policy, T::clone);
+        }
         self
     }
 
@@ -101,27 +139,38 @@ impl<T> Pipeline<T> {
                     for processor_index in 0..self.processors.len() {
                         next_stage_items.clear(); // Reuse allocation
                         let processor = &mut self.processors[processor_index];
+                        let recovery = &mut self.recoveries[processor_index];
 
                         // Process all items in current stage
                         for item_to_process in current_stage_items.drain(..) {
                             let mut processor_output_handler = |processed_item: T| {
+                                recovery.observe(&processed_item);
                                 next_stage_items.push(processed_item);
                                 Ok(())
                             };
 
-                            if let Err(e) = processor.process(
+                            let result = processor.process(
                                 &self.context,
                                 item_to_process,
                                 &mut processor_output_handler,
-                            ) {
-                                // Enhanced error context
-                                tracing::error!(
-                                    processor = processor.name(),
-                                    processor_index,
-                                    item_index,
-                                    "Processor failed during processing"
-                                );
-                                return Err(e);
+                            );
+                            let Err(e) = result else {
+                                recovery.succeeded();
+                                continue;
+                            };
+                            match recovery.failed(&self.context, processor.name(), e) {
+                                Recovery::Abort(e) => {
+                                    // Enhanced error context
+                                    tracing::error!(
+                                        processor = processor.name(),
+                                        processor_index,
+                                        item_index,
+                                        "Processor failed during processing"
+                                    );
+                                    return Err(e);
+                                }
+                                Recovery::Skip => {}
+                                Recovery::Substitute(item) => next_stage_items.push(item),
                             }
                         }
 
@@ -151,11 +200,23 @@ impl<T> Pipeline<T> {
             let span = tracing::Span::current();
             span.pb_set_position(item_index as u64);
             span.pb_set_message(&format!("Processing item {}", item_index));
+
+            if self.any_finished() {
+                tracing::debug!(item_index, "A processor finished, ending input");
+                break;
+            }
         }
 
         Ok(())
     }
 
+    /// Whether any processor accepts no further input.
+    fn any_finished(&self) -> bool {
+        self.processors
+            .iter()
+            .any(|processor| processor.is_finished())
+    }
+
     /// Finalize all processors and route flushed data through remaining stages
     fn finalize_processors<O, E>(&mut self, output: &mut O) -> Result<(), PipelineError>
     where
@@ -196,28 +257,40 @@ impl<T> Pipeline<T> {
             items_for_subsequent.append(&mut items_flushed_by_current);
 
             // Process flushed items through all remaining processors
-            for (subsequent_index, subsequent_processor) in
-                subsequent_processors_slice.iter_mut().enumerate()
+            for (subsequent_index, (subsequent_processor, recovery)) in subsequent_processors_slice
+                .iter_mut()
+                .zip(&mut self.recoveries[i + 1..])
+                .enumerate()
             {
                 next_stage_items.clear();
 
                 for item_to_process in items_for_subsequent.drain(..) {
                     let mut subsequent_process_handler = |processed_item: T| {
+                        recovery.observe(&processed_item);
                         next_stage_items.push(processed_item);
                         Ok(())
                     };
 
-                    if let Err(e) = subsequent_processor.process(
+                    let result = subsequent_processor.process(
                         &self.context,
                         item_to_process,
                         &mut subsequent_process_handler,
-                    ) {
-                        tracing::error!(
-                            processor = subsequent_processor.name(),
-                            processor_index = i + 1 + subsequent_index,
-                            "Processor failed during finalization cascade"
-                        );
-                        return Err(e);
+                    );
+                    let Err(e) = result else {
+                        recovery.succeeded();
+                        continue;
+                    };
+                    match recovery.failed(&self.context, subsequent_processor.name(), e) {
+                        Recovery::Abort(e) => {
+                            tracing::error!(
+                                processor = subsequent_processor.name(),
+                                processor_index = i + 1 + subsequent_index,
+                                "Processor failed during finalization cascade"
+                            );
+                            return Err(e);
+                        }
+                        Recovery::Skip => {}
+                        Recovery::Substitute(item) => next_stage_items.push(item),
                     }
                 }
 
@@ -316,6 +389,10 @@ where
             Ok(())
         }
     }
+
+    fn is_finished(&self) -> bool {
+        self.any_finished()
+    }
 }
 
 #[cfg(test)]
@@ -508,6 +585,59 @@ mod tests {
         assert!(matches!(result, Err(PipelineError::Cancelled)));
     }
 
+    // Processor that accepts items up to a limit
+    struct TakeProcessor {
+        remaining: usize,
+    }
+
+    impl Processor<u32> for TakeProcessor {
+        fn process(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            input: u32,
+            output: &mut dyn FnMut(u32) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            self.remaining -= 1;
+            output(input)
+        }
+
+        fn finish(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            _output: &mut dyn FnMut(u32) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "TakeProcessor"
+        }
+
+        fn is_finished(&self) -> bool {
+            self.remaining == 0
+        }
+    }
+
+    #[test]
+    fn test_finished_processor_ends_input() {
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context)
+            .add_processor(TakeProcessor { remaining: 2 })
+            .add_processor(BufferingProcessor::new());
+
+        let mut consumed = 0;
+        let input = (1..=5).inspect(|_| consumed += 1).map(Ok);
+        let mut results = Vec::new();
+        let mut output = |res: Result<u32, PipelineError>| {
+            results.push(res.unwrap());
+        };
+        pipeline.run(input, &mut output).unwrap();
+
+        // Input stops after the second item, which is still flushed by the buffering processor
+        assert_eq!(consumed, 2);
+        assert_eq!(results, vec![1, 2]);
+    }
+
     #[test]
     fn test_empty_pipeline() {
         let context = Arc::new(StreamerContext::new(CancellationToken::new()));
@@ -524,4 +654,181 @@ mod tests {
         // Empty pipeline passes through unchanged
         assert_eq!(results, vec![1, 2, 3]);
     }
+
+    // Processor that fails on the listed inputs and passes the others on
+    struct FailOnProcessor {
+        fail_on: Vec<u32>,
+        io_error: bool,
+    }
+
+    impl FailOnProcessor {
+        fn new(fail_on: &[u32]) -> Self {
+            Self {
+                fail_on: fail_on.to_vec(),
+                io_error: false,
+            }
+        }
+    }
+
+    impl Processor<u32> for FailOnProcessor {
+        fn process(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            input: u32,
+            output: &mut dyn FnMut(u32) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            if !self.fail_on.contains(&input) {
+                return output(input);
+            }
+            if self.io_error {
+                Err(std::io::Error::other("disk full").into())
+            } else {
+                Err(PipelineError::InvalidData(format!("corrupt item {input}")))
+            }
+        }
+
+        fn finish(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            _output: &mut dyn FnMut(u32) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "FailOnProcessor"
+        }
+    }
+
+    fn run_collect(
+        pipeline: Pipeline<u32>,
+        input: impl IntoIterator<Item = u32>,
+    ) -> (Result<(), PipelineError>, Vec<u32>) {
+        let mut results = Vec::new();
+        let mut output = |res: Result<u32, PipelineError>| {
+            results.push(res.unwrap());
+        };
+        let result = pipeline.run(input.into_iter().map(Ok), &mut output);
+        (result, results)
+    }
+
+    #[test]
+    fn test_skip_item_policy_skips_failed_items() {
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context.clone())
+            .with_error_policy(ErrorPolicy::SkipItem {
+                max_consecutive: 2,
+                max_total: 3,
+            })
+            .add_processor(IncrementProcessor)
+            .add_processor(FailOnProcessor::new(&[3, 4, 7]))
+            .add_processor(IncrementProcessor);
+
+        let (result, results) = run_collect(pipeline, 1..=8);
+
+        result.unwrap();
+        assert_eq!(results, vec![3, 6, 7, 9, 10]);
+        let stats = context.stats.snapshot();
+        assert_eq!(stats.errors.get("FailOnProcessor"), Some(&3));
+        assert_eq!(stats.total_errors(), 3);
+    }
+
+    #[test]
+    fn test_skip_item_policy_aborts_over_thresholds() {
+        let policy = ErrorPolicy::SkipItem {
+            max_consecutive: 1,
+            max_total: 10,
+        };
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context.clone())
+            .with_error_policy(policy)
+            .add_processor(IncrementProcessor)
+            .add_processor(FailOnProcessor::new(&[3, 5, 6]))
+            .add_processor(IncrementProcessor);
+
+        let (result, results) = run_collect(pipeline, 1..=8);
+
+        // The second failure in a row aborts
+        assert!(
+            matches!(result, Err(PipelineError::InvalidData(_))),
+            "{result:?}"
+        );
+        assert_eq!(results, vec![3, 5]);
+        assert_eq!(context.stats.snapshot().total_errors(), 3);
+
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context.clone())
+            .with_error_policy(ErrorPolicy::SkipItem {
+                max_consecutive: 5,
+                max_total: 2,
+            })
+            .add_processor(FailOnProcessor::new(&[1, 3, 5]));
+
+        let (result, results) = run_collect(pipeline, 1..=8);
+
+        assert!(
+            matches!(result, Err(PipelineError::InvalidData(_))),
+            "{result:?}"
+        );
+        assert_eq!(results, vec![2, 4]);
+    }
+
+    #[test]
+    fn test_substitute_last_policy_repeats_last_output() {
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context.clone())
+            .add_processor(IncrementProcessor)
+            .add_processor_with_error_policy(
+                FailOnProcessor::new(&[2, 5, 6]),
+                ErrorPolicy::SubstituteLast,
+            )
+            .add_processor(IncrementProcessor);
+
+        let (result, results) = run_collect(pipeline, 1..=6);
+
+        // Nothing to substitute for the first item, which is dropped
+        result.unwrap();
+        assert_eq!(results, vec![4, 5, 5, 5, 8]);
+        assert_eq!(
+            context.stats.snapshot().errors.get("FailOnProcessor"),
+            Some(&3)
+        );
+    }
+
+    #[test]
+    fn test_unrecoverable_errors_abort_regardless_of_policy() {
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context.clone())
+            .with_error_policy(ErrorPolicy::SkipItem {
+                max_consecutive: 10,
+                max_total: 10,
+            })
+            .add_processor(FailOnProcessor {
+                fail_on: vec![2],
+                io_error: true,
+            });
+
+        let (result, results) = run_collect(pipeline, 1..=4);
+
+        assert!(matches!(result, Err(PipelineError::Io(_))), "{result:?}");
+        assert_eq!(results, vec![1]);
+        assert_eq!(context.stats.snapshot().total_errors(), 0);
+    }
+
+    #[test]
+    fn test_abort_policy_is_the_default() {
+        let context = Arc::new(StreamerContext::new(CancellationToken::new()));
+        let pipeline = Pipeline::new(context.clone())
+            .add_processor(FailOnProcessor::new(&[2]))
+            .add_processor(IncrementProcessor);
+
+        let (result, results) = run_collect(pipeline, 1..=4);
+
+        assert!(
+            matches!(result, Err(PipelineError::InvalidData(_))),
+            "{result:?}"
+        );
+        assert_eq!(results, vec![2]);
+        assert_eq!(context.stats.snapshot().total_errors(), 0);
+    }
 }
@@ -38,7 +38,10 @@ pub trait Processor<T> {
     ///
     /// # Returns
     ///
-    /// `Result<(), PipelineError>` - Success or an error if processing failed
+    /// `Result<(), PipelineError>` - Success or an error if processing failed. A failure
+    /// confined to `input` is reported as [`PipelineError::InvalidData`] or
+    /// [`PipelineError::Processing`], which the pipeline's [`ErrorPolicy`](crate::ErrorPolicy)
+    /// may recover from.
     fn process(
         &mut self,
         context: &Arc<StreamerContext>,
@@ -67,6 +70,14 @@ pub trait Processor<T> {
 
     /// Get the name of this processor for logging and debugging.
     fn name(&self) -> &'static str;
+
+    /// Whether the processor accepts no further input.
+    ///
+    /// Once it returns `true`, the pipeline stops reading input and finishes its
+    /// processors, so buffered items are still flushed to the output.
+    fn is_finished(&self) -> bool {
+        false
+    }
 }
 
 // /// Trait for automatically adapting types that implement a specific processor trait
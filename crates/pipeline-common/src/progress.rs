@@ -1,6 +1,10 @@
+use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::media_info::MediaInfo;
+use crate::stats::StatsSnapshot;
 
 /// A struct to hold progress information.
 #[derive(Debug, Clone)]
@@ -17,6 +21,52 @@ pub struct Progress {
     pub duration: Option<Duration>,
 }
 
+/// Throughput statistics of a download.
+#[derive(Debug, Clone)]
+pub struct DownloadRate {
+    /// The number of bytes downloaded so far.
+    pub bytes_downloaded: u64,
+    /// The throughput since the previous update in bytes per second.
+    pub current_rate: f64,
+    /// The throughput since the download started in bytes per second.
+    pub average_rate: f64,
+    /// The time elapsed since the download started.
+    pub elapsed: Duration,
+    /// The connections used by the requests of the download.
+    pub connections: ConnectionStats,
+    /// The number of bytes copied to the raw tee of the download, when it has one.
+    pub raw_bytes: Option<u64>,
+    /// The segment downloads in flight, for downloads of segmented streams.
+    pub segment_window: Option<SegmentWindowStats>,
+}
+
+/// Occupancy of the segment download window of a segmented download.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SegmentWindowStats {
+    /// The number of segments being downloaded.
+    pub in_flight: u64,
+    /// The number of segments downloaded at once at most.
+    pub capacity: u64,
+    /// The number of downloaded segments waiting for an earlier one to be emitted.
+    pub reorder_depth: u64,
+}
+
+/// Connection usage of a download.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// The number of requests sent.
+    pub requests: u64,
+    /// The number of requests sent over a newly opened connection.
+    pub new_connections: u64,
+}
+
+impl ConnectionStats {
+    /// The number of requests sent over a pooled connection.
+    pub fn reused(&self) -> u64 {
+        self.requests.saturating_sub(self.new_connections)
+    }
+}
+
 /// An enum to represent different progress events.
 #[derive(Debug, Clone)]
 pub enum ProgressEvent {
@@ -37,4 +87,705 @@ pub enum ProgressEvent {
         /// The path to the file that was closed.
         path: Arc<Path>,
     },
+    /// An update on the throughput of a download.
+    DownloadProgress {
+        /// The URL being downloaded.
+        url: Arc<str>,
+        /// The rate statistics.
+        rate: DownloadRate,
+    },
+    /// Indicates that a download switched to another source mid-stream.
+    SourceSwitched {
+        /// The URL of the source that failed.
+        from: Arc<str>,
+        /// The URL of the source the download continues from.
+        to: Arc<str>,
+        /// Why the previous source was abandoned.
+        reason: String,
+    },
+    /// Indicates which variant of a master playlist a download picked, and why.
+    VariantSelected {
+        /// The URL of the chosen media playlist.
+        url: Arc<str>,
+        /// The bandwidth advertised by the variant, in bits per second.
+        bandwidth: u64,
+        /// The resolution advertised by the variant, as width and height.
+        resolution: Option<(u64, u64)>,
+        /// The codecs advertised by the variant.
+        codecs: Option<String>,
+        /// Why the variant was picked.
+        reason: String,
+    },
+    /// Indicates that a failed request will be retried after a delay.
+    RetryScheduled {
+        /// The URL of the request.
+        url: Arc<str>,
+        /// The number of the attempt that failed, starting at 1.
+        attempt: u32,
+        /// The number of attempts allowed in total.
+        max_attempts: u32,
+        /// How long the request waits before the next attempt.
+        delay: Duration,
+        /// Why the attempt failed.
+        reason: String,
+    },
+    /// Indicates that a download has made no media progress for a while and keeps waiting.
+    Stalled {
+        /// The URL being downloaded.
+        url: Arc<str>,
+        /// The last media position reached, in milliseconds.
+        last_media_ts: Option<u64>,
+        /// How long the download has made no media progress.
+        idle_for: Duration,
+    },
+    /// An event of one of several inputs processed concurrently.
+    Input {
+        /// The position of the input the event belongs to.
+        index: usize,
+        /// The event itself.
+        event: Box<ProgressEvent>,
+    },
+    /// A periodic snapshot of the statistics of a pipeline, and a final one at completion.
+    Stats(StatsSnapshot),
+    /// The codecs and parameters of the stream once known, and again whenever they change.
+    MediaInfo(MediaInfo),
+    /// Smoothed metrics of a download or of the files written, emitted by a callback wrapped
+    /// with [`ProgressTracker::wrap`] after each event they were derived from.
+    Metrics {
+        /// The download or file the metrics describe.
+        source: ProgressSource,
+        /// The metrics themselves.
+        metrics: ProgressMetrics,
+    },
+}
+
+/// What a [`ProgressEvent::Metrics`] describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressSource {
+    /// A download, by the URL of its latest event.
+    Download(Arc<str>),
+    /// The file being written.
+    File(Arc<Path>),
+}
+
+impl ProgressSource {
+    /// The download or file an event belongs to, if it describes one.
+    fn of(event: &ProgressEvent) -> Option<Self> {
+        match event {
+            ProgressEvent::FileOpened { path }
+            | ProgressEvent::ProgressUpdate { path, .. }
+            | ProgressEvent::FileClosed { path } => Some(Self::File(path.clone())),
+            ProgressEvent::DownloadProgress { url, .. }
+            | ProgressEvent::RetryScheduled { url, .. }
+            | ProgressEvent::Stalled { url, .. }
+            | ProgressEvent::SourceSwitched { to: url, .. } => Some(Self::Download(url.clone())),
+            ProgressEvent::Input { .. }
+            | ProgressEvent::VariantSelected { .. }
+            | ProgressEvent::Stats(_)
+            | ProgressEvent::MediaInfo(_)
+            | ProgressEvent::Metrics { .. } => None,
+        }
+    }
+}
+
+/// The activity of a tracked download or file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressState {
+    /// No data has arrived yet.
+    Starting,
+    /// Data is arriving.
+    Active,
+    /// The download has made no media progress for a while.
+    Stalled,
+    /// A failed request is retried, or the download switched to another source.
+    Retrying,
+    /// The file has been closed.
+    Finished,
+}
+
+/// Smoothed metrics derived by a [`ProgressTracker`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressMetrics {
+    /// The current activity.
+    pub state: ProgressState,
+    /// The time spent in `state`, measured with a monotonic clock.
+    pub time_in_state: Duration,
+    /// The number of bytes downloaded or written so far.
+    pub bytes: u64,
+    /// The number of items processed so far.
+    pub items: u64,
+    /// The duration of the media processed so far, if reported.
+    pub duration: Option<Duration>,
+    /// The smoothed throughput in bytes per second, decaying towards zero while no data
+    /// arrives.
+    pub byte_rate: f64,
+    /// The smoothed number of items processed per second.
+    pub item_rate: f64,
+    /// The total number of bytes, once known.
+    pub total_bytes: Option<u64>,
+    /// The total duration of the media, once known.
+    pub total_duration: Option<Duration>,
+    /// The completed share of the total in percent, once a total is known.
+    pub percent: Option<f64>,
+    /// The estimated time until the total is reached, while data is arriving.
+    pub eta: Option<Duration>,
+}
+
+/// Settings of a [`ProgressTracker`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgressTrackerConfig {
+    /// The age at which a sample weighs half as much in the smoothed rates.
+    pub half_life: Duration,
+    /// How long the rates keep their value without new events before they decay.
+    pub idle_grace: Duration,
+}
+
+impl Default for ProgressTrackerConfig {
+    fn default() -> Self {
+        Self {
+            half_life: Duration::from_secs(3),
+            idle_grace: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Samples closer than this are merged with the next one.
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+/// Smoothed rates below this are reported as zero.
+const MIN_RATE: f64 = 0.01;
+
+/// The exponentially weighted rate of a growing counter.
+#[derive(Debug, Clone, Default)]
+struct RateEstimator {
+    value: f64,
+    sampled_at: Option<Instant>,
+    rate: Option<f64>,
+}
+
+impl RateEstimator {
+    /// Record the counter at `now`, returning whether it grew.
+    fn sample(&mut self, value: f64, now: Instant, config: &ProgressTrackerConfig) -> bool {
+        let Some(sampled_at) = self.sampled_at else {
+            self.value = value;
+            self.sampled_at = Some(now);
+            return value > 0.0;
+        };
+        if value < self.value {
+            // The counter restarted; the rate carries over
+            self.value = value;
+            self.sampled_at = Some(now);
+            return false;
+        }
+        let grew = value > self.value;
+        let elapsed = now.saturating_duration_since(sampled_at);
+        if elapsed < MIN_SAMPLE_INTERVAL {
+            return grew;
+        }
+
+        let instant_rate = (value - self.value) / elapsed.as_secs_f64();
+        let weight = 1.0 - decay(elapsed, config.half_life);
+        self.rate = Some(match self.rate {
+            Some(rate) => rate + weight * (instant_rate - rate),
+            None => instant_rate,
+        });
+        self.value = value;
+        self.sampled_at = Some(now);
+        grew
+    }
+
+    /// The smoothed rate at `now`, decayed by the time without samples past the grace period.
+    fn rate_at(&self, now: Instant, config: &ProgressTrackerConfig) -> f64 {
+        let (Some(rate), Some(sampled_at)) = (self.rate, self.sampled_at) else {
+            return 0.0;
+        };
+        let idle = now
+            .saturating_duration_since(sampled_at)
+            .saturating_sub(config.idle_grace);
+        let rate = rate * decay(idle, config.half_life);
+        if rate < MIN_RATE { 0.0 } else { rate }
+    }
+}
+
+/// The weight left to a value after `elapsed`.
+fn decay(elapsed: Duration, half_life: Duration) -> f64 {
+    if half_life.is_zero() {
+        return 0.0;
+    }
+    0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64())
+}
+
+/// Derives smoothed metrics from the raw [`ProgressEvent`]s of one download or of the files
+/// written one after the other.
+///
+/// Rates are exponentially weighted moving averages, so the jitter of the deltas between
+/// events is smoothed out. While no data arrives the rates decay towards zero instead of
+/// keeping their last value. Totals may become known at any point, e.g. when an HLS VOD
+/// playlist arrives after the download started, and the percentage and ETA follow from then
+/// on. A [`ProgressEvent::FileOpened`] starts over for the new file.
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    config: ProgressTrackerConfig,
+    bytes: u64,
+    items: u64,
+    duration: Option<Duration>,
+    byte_rate: RateEstimator,
+    item_rate: RateEstimator,
+    media_rate: RateEstimator,
+    total_bytes: Option<u64>,
+    total_duration: Option<Duration>,
+    state: ProgressState,
+    state_since: Instant,
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressTracker {
+    /// Create a tracker with the default smoothing.
+    pub fn new() -> Self {
+        Self::with_config(ProgressTrackerConfig::default())
+    }
+
+    /// Create a tracker with the given smoothing.
+    pub fn with_config(config: ProgressTrackerConfig) -> Self {
+        Self {
+            config,
+            bytes: 0,
+            items: 0,
+            duration: None,
+            byte_rate: RateEstimator::default(),
+            item_rate: RateEstimator::default(),
+            media_rate: RateEstimator::default(),
+            total_bytes: None,
+            total_duration: None,
+            state: ProgressState::Starting,
+            state_since: Instant::now(),
+        }
+    }
+
+    /// Wrap a progress callback, so each event it receives is followed by a
+    /// [`ProgressEvent::Metrics`] derived from it.
+    ///
+    /// Downloads and files are tracked separately, as are the inputs of
+    /// [`ProgressEvent::Input`] events, whose metrics are wrapped the same way.
+    pub fn wrap<F>(inner: F) -> impl Fn(ProgressEvent) + Send + Sync + 'static
+    where
+        F: Fn(ProgressEvent) + Send + Sync + 'static,
+    {
+        Self::wrap_with_config(ProgressTrackerConfig::default(), inner)
+    }
+
+    /// Like [`Self::wrap`], with the given smoothing.
+    pub fn wrap_with_config<F>(
+        config: ProgressTrackerConfig,
+        inner: F,
+    ) -> impl Fn(ProgressEvent) + Send + Sync + 'static
+    where
+        F: Fn(ProgressEvent) + Send + Sync + 'static,
+    {
+        let trackers = Mutex::new(TrackerSet {
+            config,
+            trackers: HashMap::new(),
+        });
+        move |event| {
+            let metrics = trackers.lock().unwrap_or_else(|e| e.into_inner()).record(
+                None,
+                &event,
+                Instant::now(),
+            );
+            inner(event);
+            if let Some(metrics) = metrics {
+                inner(metrics);
+            }
+        }
+    }
+
+    /// Set the total number of bytes, e.g. once the size of a resource is known.
+    pub fn set_total_bytes(&mut self, total_bytes: u64) {
+        self.total_bytes = Some(total_bytes);
+    }
+
+    /// Set the total duration of the media, e.g. once a VOD playlist is known.
+    pub fn set_total_duration(&mut self, total_duration: Duration) {
+        self.total_duration = Some(total_duration);
+    }
+
+    /// Update the metrics with an event received now.
+    ///
+    /// Returns the metrics after the event, or `None` for events that do not describe a
+    /// download or a file.
+    pub fn record(&mut self, event: &ProgressEvent) -> Option<ProgressMetrics> {
+        self.record_at(event, Instant::now())
+    }
+
+    /// Update the metrics with an event received at `now`.
+    pub fn record_at(&mut self, event: &ProgressEvent, now: Instant) -> Option<ProgressMetrics> {
+        let config = self.config;
+        match event {
+            ProgressEvent::FileOpened { .. } => {
+                *self = Self::with_config(config);
+                self.state_since = now;
+            }
+            ProgressEvent::ProgressUpdate { progress, .. } => {
+                if let Some(total_bytes) = progress.total_bytes {
+                    self.total_bytes = Some(total_bytes);
+                }
+                let grew = self
+                    .byte_rate
+                    .sample(progress.bytes_written as f64, now, &config);
+                self.item_rate
+                    .sample(progress.items_processed as f64, now, &config);
+                if let Some(duration) = progress.duration {
+                    self.media_rate.sample(duration.as_secs_f64(), now, &config);
+                    self.duration = Some(duration);
+                }
+                self.bytes = progress.bytes_written;
+                self.items = progress.items_processed;
+                if grew {
+                    self.set_state(ProgressState::Active, now);
+                }
+            }
+            ProgressEvent::FileClosed { .. } => self.set_state(ProgressState::Finished, now),
+            ProgressEvent::DownloadProgress { rate, .. } => {
+                let grew = self
+                    .byte_rate
+                    .sample(rate.bytes_downloaded as f64, now, &config);
+                self.bytes = rate.bytes_downloaded;
+                if grew {
+                    self.set_state(ProgressState::Active, now);
+                }
+            }
+            ProgressEvent::Stalled { .. } => self.set_state(ProgressState::Stalled, now),
+            ProgressEvent::RetryScheduled { .. } | ProgressEvent::SourceSwitched { .. } => {
+                self.set_state(ProgressState::Retrying, now)
+            }
+            ProgressEvent::Input { .. }
+            | ProgressEvent::VariantSelected { .. }
+            | ProgressEvent::Stats(_)
+            | ProgressEvent::MediaInfo(_)
+            | ProgressEvent::Metrics { .. } => {
+                return None;
+            }
+        }
+        Some(self.metrics_at(now))
+    }
+
+    /// The metrics at `now`, with the rates decayed by the time since the last event.
+    pub fn metrics_at(&self, now: Instant) -> ProgressMetrics {
+        let byte_rate = self.byte_rate.rate_at(now, &self.config);
+        let media_rate = self.media_rate.rate_at(now, &self.config);
+
+        let (percent, eta) = match (self.total_bytes, self.total_duration, self.duration) {
+            (Some(total), _, _) if total > 0 => {
+                let remaining = total.saturating_sub(self.bytes) as f64;
+                (
+                    Some(self.bytes as f64 / total as f64),
+                    estimate(remaining, byte_rate),
+                )
+            }
+            (_, Some(total), Some(duration)) if !total.is_zero() => {
+                let remaining = total.saturating_sub(duration).as_secs_f64();
+                (
+                    Some(duration.as_secs_f64() / total.as_secs_f64()),
+                    estimate(remaining, media_rate),
+                )
+            }
+            _ => (None, None),
+        };
+
+        ProgressMetrics {
+            state: self.state,
+            time_in_state: now.saturating_duration_since(self.state_since),
+            bytes: self.bytes,
+            items: self.items,
+            duration: self.duration,
+            byte_rate,
+            item_rate: self.item_rate.rate_at(now, &self.config),
+            total_bytes: self.total_bytes,
+            total_duration: self.total_duration,
+            percent: percent.map(|share| (share * 100.0).min(100.0)),
+            eta,
+        }
+    }
+
+    fn set_state(&mut self, state: ProgressState, now: Instant) {
+        if self.state != state {
+            self.state = state;
+            self.state_since = now;
+        }
+    }
+}
+
+/// The time to cover `remaining` at `rate`, if it is covered at all.
+fn estimate(remaining: f64, rate: f64) -> Option<Duration> {
+    if remaining <= 0.0 {
+        Some(Duration::ZERO)
+    } else if rate > 0.0 {
+        Duration::try_from_secs_f64(remaining / rate).ok()
+    } else {
+        None
+    }
+}
+
+/// The trackers of a wrapped callback, one per input and kind of source.
+struct TrackerSet {
+    config: ProgressTrackerConfig,
+    trackers: HashMap<(Option<usize>, bool), ProgressTracker>,
+}
+
+impl TrackerSet {
+    fn record(
+        &mut self,
+        input: Option<usize>,
+        event: &ProgressEvent,
+        now: Instant,
+    ) -> Option<ProgressEvent> {
+        if let ProgressEvent::Input { index, event } = event {
+            let metrics = self.record(Some(*index), event, now)?;
+            return Some(ProgressEvent::Input {
+                index: *index,
+                event: Box::new(metrics),
+            });
+        }
+
+        let source = ProgressSource::of(event)?;
+        let key = (input, matches!(source, ProgressSource::File(_)));
+        let config = self.config;
+        let metrics = self
+            .trackers
+            .entry(key)
+            .or_insert_with(|| ProgressTracker::with_config(config))
+            .record_at(event, now)?;
+        Some(ProgressEvent::Metrics { source, metrics })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn download(bytes: u64) -> ProgressEvent {
+        ProgressEvent::DownloadProgress {
+            url: Arc::from("http://localhost/live.flv"),
+            rate: DownloadRate {
+                bytes_downloaded: bytes,
+                current_rate: 0.0,
+                average_rate: 0.0,
+                elapsed: Duration::ZERO,
+                connections: ConnectionStats::default(),
+                raw_bytes: None,
+                segment_window: None,
+            },
+        }
+    }
+
+    fn written(bytes: u64, total_bytes: Option<u64>, duration: Duration) -> ProgressEvent {
+        ProgressEvent::ProgressUpdate {
+            path: Arc::from(Path::new("out.flv")),
+            progress: Progress {
+                bytes_written: bytes,
+                total_bytes,
+                items_processed: bytes / 1000,
+                rate: 0.0,
+                duration: Some(duration),
+            },
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= expected * tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn test_smooths_jittery_deltas() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::new();
+        let mut bytes = 0;
+        let mut metrics = None;
+        for second in 1..=30 {
+            // Alternating half and one and a half megabytes a second
+            bytes += if second % 2 == 0 { MB / 2 } else { 3 * MB / 2 };
+            let now = start + Duration::from_secs(second);
+            metrics = tracker.record_at(&download(bytes), now);
+        }
+
+        let metrics = metrics.unwrap();
+        assert_eq!(metrics.state, ProgressState::Active);
+        assert_eq!(metrics.bytes, bytes);
+        assert_close(metrics.byte_rate, MB as f64, 0.1);
+        assert_eq!(metrics.percent, None);
+        assert_eq!(metrics.eta, None);
+    }
+
+    #[test]
+    fn test_rate_decays_while_stalled() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::new();
+        for second in 0..=20 {
+            let now = start + Duration::from_secs(second);
+            tracker.record_at(&download(second * MB), now);
+        }
+        let last = start + Duration::from_secs(20);
+        let steady = tracker.metrics_at(last).byte_rate;
+        assert_close(steady, MB as f64, 0.01);
+
+        // Held through the grace period, halved one half-life later
+        assert_eq!(
+            tracker.metrics_at(last + Duration::from_secs(2)).byte_rate,
+            steady
+        );
+        assert_close(
+            tracker.metrics_at(last + Duration::from_secs(5)).byte_rate,
+            steady / 2.0,
+            0.01,
+        );
+        assert!(tracker.metrics_at(last + Duration::from_secs(30)).byte_rate < steady * 0.01);
+        assert_eq!(
+            tracker
+                .metrics_at(last + Duration::from_secs(300))
+                .byte_rate,
+            0.0
+        );
+
+        let stalled = last + Duration::from_secs(10);
+        let metrics = tracker
+            .record_at(
+                &ProgressEvent::Stalled {
+                    url: Arc::from("http://localhost/live.flv"),
+                    last_media_ts: None,
+                    idle_for: Duration::from_secs(10),
+                },
+                stalled,
+            )
+            .unwrap();
+        assert_eq!(metrics.state, ProgressState::Stalled);
+        assert_eq!(metrics.time_in_state, Duration::ZERO);
+        let later = tracker.metrics_at(stalled + Duration::from_secs(4));
+        assert_eq!(later.time_in_state, Duration::from_secs(4));
+
+        // Data flowing again makes the download active
+        let resumed = stalled + Duration::from_secs(5);
+        let metrics = tracker.record_at(&download(21 * MB), resumed).unwrap();
+        assert_eq!(metrics.state, ProgressState::Active);
+    }
+
+    #[test]
+    fn test_total_known_late() {
+        let start = Instant::now();
+        let mut tracker = ProgressTracker::new();
+        for second in 0..=10 {
+            let now = start + Duration::from_secs(second);
+            let metrics = tracker
+                .record_at(
+                    &written(second * MB, None, Duration::from_secs(second)),
+                    now,
+                )
+                .unwrap();
+            assert_eq!(metrics.percent, None);
+            assert_eq!(metrics.eta, None);
+        }
+
+        // The media duration is known first, e.g. from a VOD playlist
+        tracker.set_total_duration(Duration::from_secs(40));
+        let now = start + Duration::from_secs(10);
+        let metrics = tracker.metrics_at(now);
+        assert_close(metrics.percent.unwrap(), 25.0, 0.01);
+        assert_close(metrics.eta.unwrap().as_secs_f64(), 30.0, 0.02);
+        assert_close(metrics.item_rate, MB as f64 / 1000.0, 0.01);
+
+        // A byte total takes precedence
+        let now = start + Duration::from_secs(11);
+        let metrics = tracker
+            .record_at(
+                &written(11 * MB, Some(22 * MB), Duration::from_secs(11)),
+                now,
+            )
+            .unwrap();
+        assert_eq!(metrics.total_bytes, Some(22 * MB));
+        assert_close(metrics.percent.unwrap(), 50.0, 0.01);
+        assert_close(metrics.eta.unwrap().as_secs_f64(), 11.0, 0.02);
+
+        let metrics = tracker
+            .record_at(
+                &written(22 * MB, Some(22 * MB), Duration::from_secs(22)),
+                now + Duration::from_secs(11),
+            )
+            .unwrap();
+        assert_eq!(metrics.percent, Some(100.0));
+        assert_eq!(metrics.eta, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_new_file_starts_over() {
+        let start = Instant::now();
+        let path: Arc<Path> = Arc::from(Path::new("out.flv"));
+        let mut tracker = ProgressTracker::new();
+        tracker.record_at(&written(MB, Some(2 * MB), Duration::from_secs(1)), start);
+        let closed = tracker
+            .record_at(
+                &ProgressEvent::FileClosed { path: path.clone() },
+                start + Duration::from_secs(1),
+            )
+            .unwrap();
+        assert_eq!(closed.state, ProgressState::Finished);
+
+        let opened = tracker
+            .record_at(
+                &ProgressEvent::FileOpened { path },
+                start + Duration::from_secs(2),
+            )
+            .unwrap();
+        assert_eq!(opened.state, ProgressState::Starting);
+        assert_eq!(opened.bytes, 0);
+        assert_eq!(opened.byte_rate, 0.0);
+        assert_eq!(opened.total_bytes, None);
+    }
+
+    #[test]
+    fn test_wrap_follows_each_event_with_metrics() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&received);
+        let callback = ProgressTracker::wrap(move |event| sink.lock().unwrap().push(event));
+
+        callback(download(MB));
+        callback(ProgressEvent::Input {
+            index: 1,
+            event: Box::new(download(2 * MB)),
+        });
+        callback(ProgressEvent::Stats(StatsSnapshot::default()));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 5, "{received:?}");
+        assert!(matches!(
+            received[0],
+            ProgressEvent::DownloadProgress { .. }
+        ));
+        match &received[1] {
+            ProgressEvent::Metrics {
+                source: ProgressSource::Download(url),
+                metrics,
+            } => {
+                assert_eq!(&**url, "http://localhost/live.flv");
+                assert_eq!(metrics.bytes, MB);
+            }
+            other => panic!("expected metrics, got {other:?}"),
+        }
+        // Inputs are tracked apart from each other
+        match &received[3] {
+            ProgressEvent::Input { index: 1, event } => match &**event {
+                ProgressEvent::Metrics { metrics, .. } => assert_eq!(metrics.bytes, 2 * MB),
+                other => panic!("expected metrics, got {other:?}"),
+            },
+            other => panic!("expected input metrics, got {other:?}"),
+        }
+        assert!(matches!(received[4], ProgressEvent::Stats(_)));
+    }
 }
@@ -0,0 +1,325 @@
+//! # Graceful Shutdown
+//!
+//! A [`ShutdownHandle`] asks a running download and its pipeline to stop without losing
+//! what was already received: [`run_until_shutdown`] stops reading the source, lets the
+//! items in flight drain through the pipeline and closes the writer, which finalizes its
+//! output as if the stream had ended.
+
+use std::time::Duration;
+
+use futures::{Stream, StreamExt};
+use tokio::sync::mpsc;
+use tracing::{Span, debug, warn};
+
+use crate::cancellation::CancellationToken;
+use crate::channel_pipeline::{ChannelPipeline, SpawnedPipeline};
+use crate::run_completion::{RunCompletionError, settle_run};
+use crate::{PipelineError, ProtocolWriter, WriterError, WriterStats};
+
+/// How long the items in flight may take to drain by default
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Cloneable handle that stops a running download and its pipeline gracefully.
+///
+/// Triggering the handle cancels its token, which stops the download; a run driven by
+/// [`run_until_shutdown`] then drains and finalizes its output.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    token: CancellationToken,
+    drain_timeout: Duration,
+}
+
+impl ShutdownHandle {
+    /// Create a handle that triggers `token`
+    pub fn new(token: CancellationToken) -> Self {
+        Self {
+            token,
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+        }
+    }
+
+    /// Set how long the items in flight may take to drain once shutdown is requested.
+    ///
+    /// When it elapses, the writer is closed with what it has received so far.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    /// How long the items in flight may take to drain
+    pub fn drain_timeout(&self) -> Duration {
+        self.drain_timeout
+    }
+
+    /// The token cancelled by [`shutdown`](Self::shutdown)
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Request shutdown
+    pub fn shutdown(&self) {
+        self.token.cancel();
+    }
+
+    /// Whether shutdown was requested
+    pub fn is_shutdown(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Wait until shutdown is requested
+    pub async fn requested(&self) {
+        self.token.cancelled().await
+    }
+}
+
+/// How a run driven by [`run_until_shutdown`] ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome<S> {
+    /// The stream ended by itself
+    Completed(S),
+    /// Shutdown was requested before the stream ended; the output is still finalized
+    Interrupted(S),
+}
+
+impl<S> RunOutcome<S> {
+    /// Whether shutdown was requested before the stream ended
+    pub fn is_interrupted(&self) -> bool {
+        matches!(self, Self::Interrupted(_))
+    }
+
+    /// The statistics of the run
+    pub fn stats(&self) -> &S {
+        match self {
+            Self::Completed(stats) | Self::Interrupted(stats) => stats,
+        }
+    }
+
+    /// Take the statistics of the run
+    pub fn into_stats(self) -> S {
+        match self {
+            Self::Completed(stats) | Self::Interrupted(stats) => stats,
+        }
+    }
+}
+
+/// Feed `stream` through `pipeline` into `writer` until the stream ends or `shutdown`
+/// is requested.
+///
+/// On shutdown the stream is dropped, which stops fetching new data, and the items
+/// already in the pipeline drain into the writer within the drain timeout of
+/// `shutdown`. The writer is then closed, finalizing its output, and the run resolves
+/// with [`RunOutcome::Interrupted`]. The writer runs on a blocking task within the
+/// current span.
+pub async fn run_until_shutdown<T, W, S>(
+    stream: S,
+    pipeline: ChannelPipeline<T>,
+    mut writer: W,
+    shutdown: &ShutdownHandle,
+) -> Result<RunOutcome<WriterStats>, RunCompletionError<WriterError>>
+where
+    T: Send + 'static,
+    W: ProtocolWriter<Item = T>,
+    S: Stream<Item = Result<T, PipelineError>> + Send,
+{
+    let SpawnedPipeline {
+        input_tx,
+        mut output_rx,
+        tasks,
+    } = pipeline.spawn();
+
+    // The writer reads through a relay, so that it can be closed while the pipeline
+    // is still draining
+    let (writer_tx, writer_rx) = mpsc::channel(1);
+    let relay = tokio::spawn(async move {
+        while let Some(item) = output_rx.recv().await {
+            if writer_tx.send(item).await.is_err() {
+                break;
+            }
+        }
+    });
+    let span = Span::current();
+    let mut writer_task = tokio::task::spawn_blocking(move || {
+        let _enter = span.enter();
+        writer.run(writer_rx)
+    });
+
+    let mut stream = Box::pin(stream);
+    let interrupted = loop {
+        tokio::select! {
+            biased;
+            _ = shutdown.requested() => break true,
+            item = stream.next() => match item {
+                Some(item) => {
+                    if input_tx.send(item).await.is_err() {
+                        // Upstream channel closed
+                        break false;
+                    }
+                }
+                None => break false,
+            },
+        }
+    };
+    drop(stream);
+    drop(input_tx);
+
+    let mut drained = true;
+    let writer_result = if interrupted {
+        debug!("Shutdown requested, draining the pipeline");
+        match tokio::time::timeout(shutdown.drain_timeout(), &mut writer_task).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    timeout = ?shutdown.drain_timeout(),
+                    "Pipeline did not drain in time, closing the writer"
+                );
+                drained = false;
+                relay.abort();
+                writer_task.await
+            }
+        }
+    } else {
+        writer_task.await
+    };
+    let writer_result = writer_result.unwrap_or_else(|e| Err(WriterError::Internal(e.to_string())));
+
+    let outcome: fn(WriterStats) -> RunOutcome<WriterStats> = if interrupted {
+        RunOutcome::Interrupted
+    } else {
+        RunOutcome::Completed
+    };
+    if !drained {
+        // The stages still running fail on the closed writer channel, which is expected
+        return writer_result
+            .map(outcome)
+            .map_err(RunCompletionError::Writer);
+    }
+    settle_run(writer_result, tasks).await.map(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Processor, StreamerContext, WriterState};
+    use futures::stream;
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    /// Counts the items it receives
+    #[derive(Default)]
+    struct CountingWriter {
+        state: WriterState,
+    }
+
+    impl ProtocolWriter for CountingWriter {
+        type Item = u32;
+
+        fn get_state(&self) -> &WriterState {
+            &self.state
+        }
+
+        fn run(
+            &mut self,
+            mut input: mpsc::Receiver<Result<u32, PipelineError>>,
+        ) -> Result<WriterStats, WriterError> {
+            while let Some(item) = input.blocking_recv() {
+                item.map_err(WriterError::InputError)?;
+                self.state.items_written_total += 1;
+            }
+            Ok(WriterStats::from_state(&self.state))
+        }
+    }
+
+    /// Takes `delay` to pass each item on
+    struct SlowProcessor {
+        delay: Duration,
+    }
+
+    impl Processor<u32> for SlowProcessor {
+        fn name(&self) -> &'static str {
+            "SlowProcessor"
+        }
+
+        fn process(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            input: u32,
+            output: &mut dyn FnMut(u32) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            std::thread::sleep(self.delay);
+            output(input)
+        }
+
+        fn finish(
+            &mut self,
+            _context: &Arc<StreamerContext>,
+            _output: &mut dyn FnMut(u32) -> Result<(), PipelineError>,
+        ) -> Result<(), PipelineError> {
+            Ok(())
+        }
+    }
+
+    fn pipeline() -> ChannelPipeline<u32> {
+        ChannelPipeline::new(StreamerContext::arc_new(CancellationToken::new()))
+    }
+
+    /// Yields `count` items, requests shutdown with the last one and then never ends
+    fn interrupted_stream(
+        count: u32,
+        shutdown: &ShutdownHandle,
+    ) -> impl Stream<Item = Result<u32, PipelineError>> + Send + use<> {
+        let shutdown = shutdown.clone();
+        stream::iter(0..count)
+            .inspect(move |&item| {
+                if item + 1 == count {
+                    shutdown.shutdown();
+                }
+            })
+            .map(Ok)
+            .chain(stream::pending())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_run_completes_when_stream_ends() {
+        let shutdown = ShutdownHandle::new(CancellationToken::new());
+        let items = stream::iter((0..10).map(Ok));
+
+        let outcome = run_until_shutdown(items, pipeline(), CountingWriter::default(), &shutdown)
+            .await
+            .unwrap();
+        assert!(!outcome.is_interrupted());
+        assert_eq!(outcome.stats().items_written, 10);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_shutdown_drains_items_in_flight() {
+        let shutdown = ShutdownHandle::new(CancellationToken::new());
+        let pipeline = pipeline().add_processor(SlowProcessor {
+            delay: Duration::from_millis(5),
+        });
+        let items = interrupted_stream(20, &shutdown);
+
+        let outcome = run_until_shutdown(items, pipeline, CountingWriter::default(), &shutdown)
+            .await
+            .unwrap();
+        assert!(outcome.is_interrupted());
+        assert_eq!(outcome.into_stats().items_written, 20);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_drain_timeout_closes_writer() {
+        let shutdown = ShutdownHandle::new(CancellationToken::new())
+            .with_drain_timeout(Duration::from_millis(100));
+        let pipeline = pipeline().add_processor(SlowProcessor {
+            delay: Duration::from_millis(50),
+        });
+        let items = interrupted_stream(30, &shutdown);
+
+        let started = Instant::now();
+        let outcome = run_until_shutdown(items, pipeline, CountingWriter::default(), &shutdown)
+            .await
+            .unwrap();
+        assert!(outcome.is_interrupted());
+        assert!(outcome.stats().items_written < 30);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
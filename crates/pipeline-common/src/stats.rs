@@ -0,0 +1,221 @@
+//! # Pipeline Statistics
+//!
+//! Counters shared through [`StreamerContext`](crate::StreamerContext) and filled in by the
+//! pipeline and its operators: items and bytes entering and leaving the pipeline, items
+//! dropped or filtered as duplicates, the repairs each operator applied, the items each
+//! operator failed on and the [events](crate::events) of each kind.
+//!
+//! A [`StatsSnapshot`] captures the counters at one point in time. Snapshots are emitted as
+//! [`ProgressEvent::Stats`](crate::ProgressEvent::Stats) by a
+//! [`ChannelPipeline`](crate::ChannelPipeline) with a stats reporter, and can be serialized
+//! with the `serde` feature, e.g. to store a run summary next to its output.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::writer_task::SegmentHook;
+
+/// Counters of a pipeline run.
+#[derive(Debug)]
+pub struct PipelineStats {
+    started: Instant,
+    items_in: AtomicU64,
+    items_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    dropped: AtomicU64,
+    duplicates: AtomicU64,
+    duplicates_kept: AtomicU64,
+    repairs: Mutex<BTreeMap<&'static str, u64>>,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+    events: Mutex<BTreeMap<&'static str, u64>>,
+    current_file: Mutex<Option<PathBuf>>,
+}
+
+impl Default for PipelineStats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            items_in: AtomicU64::new(0),
+            items_out: AtomicU64::new(0),
+            bytes_in: AtomicU64::new(0),
+            bytes_out: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            duplicates: AtomicU64::new(0),
+            duplicates_kept: AtomicU64::new(0),
+            repairs: Mutex::new(BTreeMap::new()),
+            errors: Mutex::new(BTreeMap::new()),
+            events: Mutex::new(BTreeMap::new()),
+            current_file: Mutex::new(None),
+        }
+    }
+}
+
+impl PipelineStats {
+    /// Record an item entering the pipeline.
+    pub fn record_input(&self, bytes: u64) {
+        self.items_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record an item leaving the pipeline.
+    pub fn record_output(&self, bytes: u64) {
+        self.items_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record items an operator discarded.
+    pub fn record_dropped(&self, items: u64) {
+        self.dropped.fetch_add(items, Ordering::Relaxed);
+    }
+
+    /// Record an item discarded as a duplicate of an earlier one.
+    pub fn record_duplicate(&self) {
+        self.duplicates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an item passed on although it duplicates an earlier one, such as a repeated
+    /// sequence header that must not be dropped.
+    pub fn record_duplicate_kept(&self) {
+        self.duplicates_kept.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a repair applied by `operator`.
+    pub fn record_repair(&self, operator: &'static str) {
+        let mut repairs = self.repairs.lock().unwrap_or_else(|e| e.into_inner());
+        *repairs.entry(operator).or_default() += 1;
+    }
+
+    /// Record an item `operator` failed on under an error policy other than aborting.
+    pub fn record_error(&self, operator: &'static str) {
+        let mut errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        *errors.entry(operator).or_default() += 1;
+    }
+
+    /// Record an event of the kind named `name`.
+    pub fn record_event(&self, name: &'static str) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        *events.entry(name).or_default() += 1;
+    }
+
+    /// Set the file the output is currently written to.
+    pub fn set_current_file(&self, path: Option<&Path>) {
+        *self.current_file.lock().unwrap_or_else(|e| e.into_inner()) = path.map(Path::to_path_buf);
+    }
+
+    /// Capture the current values of the counters.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let repairs = self
+            .repairs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(operator, count)| (operator.to_string(), *count))
+            .collect();
+        let errors = self
+            .errors
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(operator, count)| (operator.to_string(), *count))
+            .collect();
+        let events = self
+            .events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(name, count)| (name.to_string(), *count))
+            .collect();
+        let current_file = self
+            .current_file
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        StatsSnapshot {
+            items_in: self.items_in.load(Ordering::Relaxed),
+            items_out: self.items_out.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            duplicates: self.duplicates.load(Ordering::Relaxed),
+            duplicates_kept: self.duplicates_kept.load(Ordering::Relaxed),
+            repairs,
+            errors,
+            events,
+            current_file,
+            elapsed_secs: self.started.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+/// Statistics of a pipeline run at one point in time.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct StatsSnapshot {
+    /// Items (tags or segments) that entered the pipeline.
+    pub items_in: u64,
+    /// Items that left the pipeline.
+    pub items_out: u64,
+    /// Bytes that entered the pipeline, when item sizes are known.
+    pub bytes_in: u64,
+    /// Bytes that left the pipeline, when item sizes are known.
+    pub bytes_out: u64,
+    /// Items discarded by operators.
+    pub dropped: u64,
+    /// Items discarded as duplicates.
+    pub duplicates: u64,
+    /// Duplicate items passed on because they must not be dropped.
+    pub duplicates_kept: u64,
+    /// Repairs applied, per operator.
+    pub repairs: BTreeMap<String, u64>,
+    /// Items that failed under an error policy other than aborting, per operator.
+    pub errors: BTreeMap<String, u64>,
+    /// Events emitted, per kind.
+    pub events: BTreeMap<String, u64>,
+    /// File the output is currently written to.
+    pub current_file: Option<PathBuf>,
+    /// Seconds since the run started.
+    pub elapsed_secs: f64,
+}
+
+impl StatsSnapshot {
+    /// Total number of repairs applied by all operators.
+    pub fn total_repairs(&self) -> u64 {
+        self.repairs.values().sum()
+    }
+
+    /// Total number of items the operators failed on.
+    pub fn total_errors(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// Total number of events emitted.
+    pub fn total_events(&self) -> u64 {
+        self.events.values().sum()
+    }
+}
+
+/// A [`SegmentHook`] recording the segment being written as the current file.
+pub struct CurrentFileHook {
+    stats: Arc<PipelineStats>,
+}
+
+impl CurrentFileHook {
+    pub fn new(stats: Arc<PipelineStats>) -> Self {
+        Self { stats }
+    }
+}
+
+impl SegmentHook for CurrentFileHook {
+    fn on_segment_open(&mut self, path: &Path, _index: u32) {
+        self.stats.set_current_file(Some(path));
+    }
+
+    fn on_segment_renamed(&mut self, _from: &Path, to: &Path, _index: u32) {
+        self.stats.set_current_file(Some(to));
+    }
+}
@@ -1,5 +1,113 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use thiserror::Error;
+
+/// Value used for a stream variable that is not known yet.
+const UNKNOWN_VALUE: &str = "unknown";
+
+/// Stream variables of filename templates, written `%name%`.
+const STREAM_VARIABLES: [&str; 5] = ["res", "vcodec", "acodec", "seq", "title"];
+
+/// Stream variables whose value comes from the stream itself rather than the writer.
+const METADATA_VARIABLES: [&str; 4] = ["res", "vcodec", "acodec", "title"];
+
+/// Error in a filename template
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Unknown template variable: %{0}%")]
+    UnknownVariable(String),
+}
+
+/// Stream properties available to the `%name%` variables of a filename template.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamMetadata {
+    /// Video resolution as width and height, from the sequence header.
+    pub resolution: Option<(u32, u32)>,
+    /// Video codec name, e.g. `h264`.
+    pub video_codec: Option<String>,
+    /// Audio codec name, e.g. `aac`.
+    pub audio_codec: Option<String>,
+    /// Stream title, from onMetaData or the HLS session data.
+    pub title: Option<String>,
+}
+
+impl StreamMetadata {
+    /// Whether every stream variable used by `template` has a value.
+    pub fn covers(&self, template: &str) -> bool {
+        template_variables(template).all(|name| match name {
+            "res" => self.resolution.is_some(),
+            "vcodec" => self.video_codec.is_some(),
+            "acodec" => self.audio_codec.is_some(),
+            "title" => self.title.is_some(),
+            _ => true,
+        })
+    }
+
+    fn value(&self, name: &str, sequence_number: Option<u32>) -> Option<String> {
+        let value = match name {
+            "res" => self
+                .resolution
+                .map(|(width, height)| format!("{width}x{height}")),
+            "vcodec" => self.video_codec.clone(),
+            "acodec" => self.audio_codec.clone(),
+            "seq" => Some(format!("{:03}", sequence_number.unwrap_or(0))),
+            "title" => self.title.clone(),
+            _ => return None,
+        };
+        Some(value.unwrap_or_else(|| UNKNOWN_VALUE.to_string()))
+    }
+}
+
+/// Names of the `%name%` variables in a template, in order.
+///
+/// A variable name has at least two lowercase letters and nothing else, so single-letter
+/// placeholders like `%d%H` or `%u_%Y` are never mistaken for one.
+fn template_variables(template: &str) -> impl Iterator<Item = &str> {
+    let mut rest = template;
+    std::iter::from_fn(move || {
+        loop {
+            let start = rest.find('%')?;
+            let after = &rest[start + 1..];
+            if after.starts_with('%') {
+                // Escaped percent sign
+                rest = &after[1..];
+                continue;
+            }
+            match variable_name(after) {
+                Some(name) => {
+                    rest = &after[name.len() + 1..];
+                    return Some(name);
+                }
+                None => rest = after,
+            }
+        }
+    })
+}
+
+/// The variable name at the start of `text` if it is followed by a closing `%`.
+fn variable_name(text: &str) -> Option<&str> {
+    let len = text
+        .find(|c: char| !c.is_ascii_lowercase())
+        .unwrap_or(text.len());
+    (len >= 2 && text[len..].starts_with('%')).then(|| &text[..len])
+}
+
+/// Check that a filename template only uses known `%name%` variables.
+///
+/// Call this when the template is configured, so a typo is reported before the first
+/// file is written rather than ending up in its name.
+pub fn validate_filename_template(template: &str) -> Result<(), TemplateError> {
+    match template_variables(template).find(|name| !STREAM_VARIABLES.contains(name)) {
+        Some(name) => Err(TemplateError::UnknownVariable(name.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Whether a filename template uses variables that come from the stream metadata.
+pub fn template_uses_stream_metadata(template: &str) -> bool {
+    template_variables(template).any(|name| METADATA_VARIABLES.contains(&name))
+}
+
 /// Expand path template with placeholders similar to FFmpeg.
 ///
 /// Unlike `expand_filename_template`, this function does NOT sanitize the result,
@@ -16,7 +124,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 /// - `%t` - Unix timestamp
 /// - `%%` - Literal percent sign
 pub fn expand_path_template(template: &str) -> String {
-    expand_template_internal(template, None, false, None)
+    expand_template_internal(template, None, false, None, None)
 }
 
 /// Expand path template with placeholders using a specific reference timestamp.
@@ -24,12 +132,34 @@ pub fn expand_path_template(template: &str) -> String {
 /// Same as `expand_path_template`, but uses the provided timestamp (Unix epoch milliseconds)
 /// instead of the current time.
 pub fn expand_path_template_at(template: &str, reference_timestamp_ms: Option<i64>) -> String {
-    expand_template_internal(template, None, false, reference_timestamp_ms)
+    expand_template_internal(template, None, false, reference_timestamp_ms, None)
 }
 
 /// Expand filename template with placeholders similar to FFmpeg
+///
+/// Stream variables (`%res%`, `%vcodec%`, `%acodec%`, `%title%`) expand to `unknown`,
+/// see [`expand_filename_template_with`] to supply their values.
 pub fn expand_filename_template(template: &str, sequence_number: Option<u32>) -> String {
-    expand_template_internal(template, sequence_number, true, None)
+    expand_filename_template_with(template, sequence_number, &StreamMetadata::default())
+}
+
+/// Expand filename template with placeholders and stream variables.
+///
+/// In addition to the placeholders of [`expand_path_template`] and `%i` (output index),
+/// supports:
+/// - `%res%` - Video resolution (e.g. `1920x1080`)
+/// - `%vcodec%` - Video codec (e.g. `h264`)
+/// - `%acodec%` - Audio codec (e.g. `aac`)
+/// - `%seq%` - Output index, padded to 3 digits
+/// - `%title%` - Stream title
+///
+/// Variables without a value in `metadata` expand to `unknown`.
+pub fn expand_filename_template_with(
+    template: &str,
+    sequence_number: Option<u32>,
+    metadata: &StreamMetadata,
+) -> String {
+    expand_template_internal(template, sequence_number, true, None, Some(metadata))
 }
 
 /// Internal implementation for expanding templates.
@@ -40,11 +170,13 @@ pub fn expand_filename_template(template: &str, sequence_number: Option<u32>) ->
 /// * `sanitize` - Whether to sanitize the result for use as a filename
 /// * `reference_timestamp_ms` - Optional reference timestamp in Unix epoch milliseconds.
 ///   If None, uses the current time.
+/// * `metadata` - Values of the `%name%` stream variables, which are left as is if None
 fn expand_template_internal(
     template: &str,
     sequence_number: Option<u32>,
     sanitize: bool,
     reference_timestamp_ms: Option<i64>,
+    metadata: Option<&StreamMetadata>,
 ) -> String {
     let now = if let Some(ts_ms) = reference_timestamp_ms {
         time::OffsetDateTime::from_unix_timestamp(ts_ms / 1000)
@@ -61,11 +193,21 @@ fn expand_template_internal(
                 .as_secs()
         });
     let mut result = String::with_capacity(template.len() * 2);
-    let mut chars = template.chars().peekable();
+    let mut chars = template.chars();
 
     while let Some(c) = chars.next() {
         if c == '%' {
-            if let Some(&next_char) = chars.peek() {
+            // Stream variables
+            if let Some(metadata) = metadata
+                && let Some(name) = variable_name(chars.as_str())
+                && let Some(value) = metadata.value(name, sequence_number)
+            {
+                result.push_str(&value);
+                chars.nth(name.len());
+                continue;
+            }
+
+            if let Some(next_char) = chars.clone().next() {
                 match next_char {
                     // Date and time placeholders
                     'Y' => {
@@ -171,3 +313,62 @@ pub fn sanitize_filename(input: &str) -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_variables() {
+        let metadata = StreamMetadata {
+            resolution: Some((1920, 1080)),
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+            title: Some("Live: show".to_string()),
+        };
+        assert_eq!(
+            expand_filename_template_with(
+                "%title%_%res%_%vcodec%_%acodec%_%seq%",
+                Some(7),
+                &metadata
+            ),
+            "Live_ show_1920x1080_h264_aac_007"
+        );
+        assert!(metadata.covers("%res%_%title%"));
+    }
+
+    #[test]
+    fn test_unknown_stream_variables_fall_back() {
+        let metadata = StreamMetadata {
+            video_codec: Some("hevc".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            expand_filename_template_with("%vcodec%-%res%-%%res%", None, &metadata),
+            "hevc-unknown-%res%"
+        );
+        assert!(!metadata.covers("%vcodec%-%res%"));
+        assert!(metadata.covers("%vcodec%-%seq%"));
+    }
+
+    #[test]
+    fn test_single_letter_placeholders_are_not_variables() {
+        assert_eq!(validate_filename_template("%Y%m%d_%H%M%S_p%i"), Ok(()));
+        assert_eq!(validate_filename_template("%u_%Y%m%d_%title%"), Ok(()));
+        assert_eq!(
+            expand_filename_template_with("p%i%d", Some(2), &StreamMetadata::default()).len(),
+            "p002".len() + 2
+        );
+        assert!(!template_uses_stream_metadata("%Y%m%d_%seq%"));
+        assert!(template_uses_stream_metadata("%Y%m%d_%res%"));
+    }
+
+    #[test]
+    fn test_unknown_variable_is_rejected() {
+        assert_eq!(
+            validate_filename_template("%title%_%resolution%"),
+            Err(TemplateError::UnknownVariable("resolution".to_string()))
+        );
+        assert_eq!(validate_filename_template("100%%done%"), Ok(()));
+    }
+}
@@ -2,5 +2,7 @@ mod files;
 pub mod tracing;
 
 pub use files::{
-    expand_filename_template, expand_path_template, expand_path_template_at, sanitize_filename,
+    StreamMetadata, TemplateError, expand_filename_template, expand_filename_template_with,
+    expand_path_template, expand_path_template_at, sanitize_filename,
+    template_uses_stream_metadata, validate_filename_template,
 };
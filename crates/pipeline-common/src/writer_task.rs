@@ -1,13 +1,14 @@
 use std::error::Error;
-use std::fs::{File, OpenOptions};
 use std::io::{self, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::PipelineError;
+use crate::output_sink::{OutputSink, SinkWriter, UploadOptions};
 use crate::split_reason::SplitReason;
+use crate::utils::{TemplateError, validate_filename_template};
 
 /// Progress information from writer.
 /// Contains metrics about bytes written, items processed, media duration, and performance.
@@ -89,8 +90,12 @@ pub fn expand_filename_template(template: &str, sequence_number: Option<u32>) ->
     }
 }
 
-/// Action to take after writing an item.
-#[derive(Debug, Clone, Copy)]
+/// Action to take after writing an item or closing a segment.
+///
+/// Returned by [`FormatStrategy::after_item_written`] and [`SegmentHook::on_segment_close`].
+/// `Close` and `Rotate` have no effect when returned from a segment hook, as the
+/// segment is already closed by then.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PostWriteAction {
     /// Do nothing.
     None,
@@ -98,6 +103,129 @@ pub enum PostWriteAction {
     Close,
     /// Rotate the current file.
     Rotate,
+    /// Close the current file and rename it to the given path.
+    Rename(PathBuf),
+    /// Close the current file and delete it.
+    Delete,
+}
+
+/// Statistics of a segment file, passed to [`SegmentHook::on_segment_close`].
+#[derive(Debug, Clone)]
+pub struct SegmentStats {
+    /// Number of items written to the segment.
+    pub items_written: usize,
+    /// Size of the segment in bytes.
+    pub size_bytes: u64,
+    /// Media duration of the segment in seconds.
+    pub duration_secs: f64,
+    /// Why the segment was split, if known.
+    pub split_reason: Option<SplitReason>,
+}
+
+impl SegmentStats {
+    fn from_state(state: &WriterState, split_reason: Option<SplitReason>) -> Self {
+        Self {
+            items_written: state.items_written_current_file,
+            size_bytes: state.bytes_written_current_file,
+            duration_secs: state.media_duration_secs_current_file,
+            split_reason,
+        }
+    }
+}
+
+/// Hook invoked when the writer opens or closes a segment file.
+///
+/// Hooks run synchronously on the writer thread: `on_segment_close` of segment N
+/// returns before segment N + 1 is created, and `on_segment_open` runs once the
+/// new file has been created and its header written. Slow work should be
+/// offloaded, e.g. through a [`QueuedSegmentHook`].
+pub trait SegmentHook: Send + 'static {
+    /// Called after a segment file has been opened.
+    fn on_segment_open(&mut self, _path: &Path, _index: u32) {}
+
+    /// Called after an open segment file has been renamed, e.g. once the stream
+    /// metadata used by its name became known.
+    fn on_segment_renamed(&mut self, _from: &Path, _to: &Path, _index: u32) {}
+
+    /// Called after a segment file has been flushed and closed.
+    ///
+    /// Returning [`PostWriteAction::Rename`] or [`PostWriteAction::Delete`] renames
+    /// or deletes the closed file; later hooks then see the new path, or are not
+    /// called at all for a deleted file.
+    fn on_segment_close(
+        &mut self,
+        _path: &Path,
+        _index: u32,
+        _stats: &SegmentStats,
+    ) -> PostWriteAction {
+        PostWriteAction::None
+    }
+}
+
+/// Segment event queued by a [`QueuedSegmentHook`].
+#[derive(Debug, Clone)]
+pub enum SegmentEvent {
+    /// A segment file was opened.
+    Opened { path: PathBuf, index: u32 },
+    /// An open segment file was renamed.
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+        index: u32,
+    },
+    /// A segment file was closed.
+    Closed {
+        path: PathBuf,
+        index: u32,
+        stats: SegmentStats,
+    },
+}
+
+/// A [`SegmentHook`] that queues segment events for asynchronous consumers.
+///
+/// Events are sent in order without blocking the writer thread, so work such as
+/// remuxing or uploading a finished segment can run on the async runtime.
+pub struct QueuedSegmentHook {
+    tx: tokio::sync::mpsc::UnboundedSender<SegmentEvent>,
+}
+
+impl QueuedSegmentHook {
+    /// Creates a hook and the receiver its events are queued on.
+    pub fn new() -> (Self, tokio::sync::mpsc::UnboundedReceiver<SegmentEvent>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+}
+
+impl SegmentHook for QueuedSegmentHook {
+    fn on_segment_open(&mut self, path: &Path, index: u32) {
+        let _ = self.tx.send(SegmentEvent::Opened {
+            path: path.to_path_buf(),
+            index,
+        });
+    }
+
+    fn on_segment_renamed(&mut self, from: &Path, to: &Path, index: u32) {
+        let _ = self.tx.send(SegmentEvent::Renamed {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            index,
+        });
+    }
+
+    fn on_segment_close(
+        &mut self,
+        path: &Path,
+        index: u32,
+        stats: &SegmentStats,
+    ) -> PostWriteAction {
+        let _ = self.tx.send(SegmentEvent::Closed {
+            path: path.to_path_buf(),
+            index,
+            stats: stats.clone(),
+        });
+        PostWriteAction::None
+    }
 }
 
 /// Configuration for the writer task.
@@ -109,6 +237,19 @@ pub struct WriterConfig {
     pub file_name_template: String,
     /// File name extension.
     pub file_extension: String,
+    /// Name files that would overwrite an existing one `name_1`, `name_2`, ... instead of
+    /// `name-000`, `name-dup0001`, ...
+    pub numbered_collisions: bool,
+    /// Where files are written, the local disk by default.
+    ///
+    /// Files on a remote sink are never renamed or deleted and overwrite existing ones.
+    pub sink: OutputSink,
+    /// Tuning of uploads to a remote sink.
+    pub upload: UploadOptions,
+    /// Hashes every file written for an integrity manifest, see
+    /// [`WriterTask::add_integrity_manifest`].
+    #[cfg(feature = "integrity")]
+    pub integrity: Option<crate::integrity::IntegrityRecorder>,
 }
 
 impl WriterConfig {
@@ -117,8 +258,57 @@ impl WriterConfig {
             base_path,
             file_name_template,
             file_extension,
+            numbered_collisions: false,
+            sink: OutputSink::default(),
+            upload: UploadOptions::default(),
+            #[cfg(feature = "integrity")]
+            integrity: None,
         }
     }
+
+    /// Set how files that would overwrite an existing one are named.
+    pub fn with_numbered_collisions(mut self, numbered_collisions: bool) -> Self {
+        self.numbered_collisions = numbered_collisions;
+        self
+    }
+
+    /// Set where files are written.
+    pub fn with_output_sink(mut self, sink: OutputSink) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    /// Creates the file at `path` on the configured sink.
+    pub fn open_output(&self, path: &Path) -> io::Result<SinkWriter> {
+        let writer = SinkWriter::open(&self.sink, &self.upload, path)?;
+        #[cfg(feature = "integrity")]
+        let writer = match &self.integrity {
+            Some(recorder) => writer.with_digest(recorder, path),
+            None => writer,
+        };
+        Ok(writer)
+    }
+
+    /// Whether files are hashed for an integrity manifest once they are closed.
+    ///
+    /// Strategies must then complete every change to a file in
+    /// [`FormatStrategy::on_file_close`], as later changes are not covered by its hash.
+    #[cfg(feature = "integrity")]
+    pub fn records_integrity(&self) -> bool {
+        self.integrity.is_some()
+    }
+
+    /// Whether files are hashed for an integrity manifest, never without the `integrity`
+    /// feature.
+    #[cfg(not(feature = "integrity"))]
+    pub fn records_integrity(&self) -> bool {
+        false
+    }
+
+    /// Check the file name template for unknown variables.
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        validate_filename_template(&self.file_name_template)
+    }
 }
 
 /// State of the writer task.
@@ -252,8 +442,13 @@ pub trait FormatStrategy<D>: Send + Sync + 'static {
     type StrategyError: Error + Send + Sync + 'static;
 
     /// Creates a new writer for the given path.
-    /// This is typically a `BufWriter<File>` or similar.
-    fn create_writer(&self, path: &Path) -> Result<Self::Writer, Self::StrategyError>;
+    /// This is typically a `BufWriter<SinkWriter>` opened with [`WriterConfig::open_output`],
+    /// in which case `on_file_close` must call [`SinkWriter::finish`] to complete the file.
+    fn create_writer(
+        &self,
+        path: &Path,
+        config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError>;
 
     /// Writes a single data item to the writer.
     /// Should return the number of bytes written.
@@ -300,6 +495,20 @@ pub trait FormatStrategy<D>: Send + Sync + 'static {
         Ok(PostWriteAction::None)
     }
 
+    /// Optional: Returns the path the open file should be moved to, checked after every
+    /// item written.
+    ///
+    /// Lets a strategy open a file under a provisional name and rename it once the
+    /// values its name depends on are known, e.g. the stream metadata variables of
+    /// the file name template. The writer keeps writing to the renamed file.
+    fn resolved_file_path(
+        &mut self,
+        _config: &WriterConfig,
+        _state: &WriterState,
+    ) -> Option<PathBuf> {
+        None
+    }
+
     /// Optional: Called if an error occurs during writing an item.
     fn on_write_error(&mut self, _error: &Self::StrategyError, _item: &D) {
         // Default: do nothing
@@ -335,6 +544,7 @@ pub struct WriterTask<D, S: FormatStrategy<D>> {
     on_file_open_callback: Option<FileOpenCallback>,
     on_file_close_callback: Option<FileCloseCallback>,
     on_progress_callback: Option<ProgressCallback>,
+    segment_hooks: Vec<Box<dyn SegmentHook>>,
     progress_config: ProgressConfig,
     start_time: Instant,
     last_progress_bytes: u64,
@@ -343,9 +553,12 @@ pub struct WriterTask<D, S: FormatStrategy<D>> {
 
 impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
     fn ensure_unique_output_path(&self, candidate: PathBuf) -> PathBuf {
-        if !candidate.exists() {
+        if !self.config.sink.is_local() || !candidate.exists() {
             return candidate;
         }
+        if self.config.numbered_collisions {
+            return numbered_output_path(candidate);
+        }
 
         let has_sequence_placeholder = self.config.file_name_template.contains("%i");
         let sequence_number = self.state.file_sequence_number;
@@ -394,9 +607,11 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
     }
 
     pub fn new(config: WriterConfig, strategy: S) -> Self {
-        std::fs::create_dir_all(&config.base_path).unwrap_or_else(|e| {
-            eprintln!("Failed to create base path {:?}: {}", &config.base_path, e);
-        });
+        if config.sink.is_local() {
+            std::fs::create_dir_all(&config.base_path).unwrap_or_else(|e| {
+                eprintln!("Failed to create base path {:?}: {}", &config.base_path, e);
+            });
+        }
         Self {
             config,
             state: WriterState::default(),
@@ -405,6 +620,7 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
             on_file_open_callback: None,
             on_file_close_callback: None,
             on_progress_callback: None,
+            segment_hooks: Vec::new(),
             progress_config: ProgressConfig::default(),
             start_time: Instant::now(),
             last_progress_bytes: 0,
@@ -426,6 +642,23 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
         self.on_file_close_callback = Some(Box::new(callback));
     }
 
+    /// Add a hook invoked when segment files are opened and closed.
+    ///
+    /// Hooks run in the order they were added.
+    pub fn add_segment_hook<H: SegmentHook>(&mut self, hook: H) {
+        self.segment_hooks.push(Box::new(hook));
+    }
+
+    /// Hash every file as it is written and record it in the manifest of `manifest`
+    /// once closed.
+    ///
+    /// The manifest is added as a segment hook, after the hooks added so far.
+    #[cfg(feature = "integrity")]
+    pub fn add_integrity_manifest(&mut self, manifest: crate::integrity::IntegrityManifest) {
+        self.config.integrity = Some(manifest.recorder());
+        self.add_segment_hook(manifest);
+    }
+
     /// Set a progress callback with default intervals (1MB bytes, 1000ms time).
     pub fn set_progress_callback<F>(&mut self, callback: F)
     where
@@ -469,7 +702,9 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
 
         let initial_path = self.strategy.next_file_path(&self.config, &self.state);
         let initial_path = self.ensure_unique_output_path(initial_path);
-        if let Some(parent) = initial_path.parent() {
+        if self.config.sink.is_local()
+            && let Some(parent) = initial_path.parent()
+        {
             std::fs::create_dir_all(parent).map_err(TaskError::Io)?;
         }
 
@@ -477,7 +712,7 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
 
         let mut new_writer = self
             .strategy
-            .create_writer(&initial_path)
+            .create_writer(&initial_path, &self.config)
             .map_err(TaskError::Strategy)?;
         self.state.reset_for_new_file(initial_path.clone());
 
@@ -490,9 +725,7 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
         self.state.bytes_written_current_file += bytes_opened;
         self.state.bytes_written_total += bytes_opened;
 
-        if let Some(cb) = &self.on_file_open_callback {
-            cb(&initial_path, self.state.file_sequence_number);
-        }
+        self.notify_file_opened(&initial_path);
 
         debug!("Initial writer opened for file: {:?}", initial_path);
 
@@ -502,36 +735,12 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
 
     fn rotate_file(&mut self) -> Result<(), TaskError<S::StrategyError>> {
         // close the existing writer
-        if let Some(mut writer) = self.writer.take() {
+        if let Some(writer) = self.writer.take() {
             debug!(
                 "Closing file for rotation: {:?}",
                 self.state.current_file_path
             );
-            if let Some(path) = &self.state.current_file_path {
-                let bytes_closed = self
-                    .strategy
-                    .on_file_close(&mut writer, path, &self.config, &self.state)
-                    .map_err(TaskError::Strategy)?;
-                self.state.bytes_written_current_file += bytes_closed;
-                self.state.bytes_written_total += bytes_closed;
-
-                writer.flush().map_err(TaskError::Io)?;
-
-                // Capture duration before callback (current file duration)
-                let duration_secs = self.state.media_duration_secs_current_file;
-                let size_bytes = self.state.bytes_written_current_file;
-                let split_reason = self.strategy.close_context();
-
-                if let Some(cb) = &self.on_file_close_callback {
-                    cb(
-                        path,
-                        self.state.file_sequence_number,
-                        duration_secs,
-                        size_bytes,
-                        split_reason.as_ref(),
-                    );
-                }
-            }
+            self.finish_file(writer, PostWriteAction::None)?;
         } else {
             // This should not happen if called from ensure_writer_open
             return Err(TaskError::Internal(
@@ -545,7 +754,9 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
         // open the new writer
         let next_path = self.strategy.next_file_path(&self.config, &self.state);
         let next_path = self.ensure_unique_output_path(next_path);
-        if let Some(parent) = next_path.parent() {
+        if self.config.sink.is_local()
+            && let Some(parent) = next_path.parent()
+        {
             std::fs::create_dir_all(parent).map_err(TaskError::Io)?;
         }
 
@@ -553,7 +764,7 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
 
         let mut new_writer = self
             .strategy
-            .create_writer(&next_path)
+            .create_writer(&next_path, &self.config)
             .map_err(TaskError::Strategy)?;
         self.state.reset_for_new_file(next_path.clone());
 
@@ -566,9 +777,7 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
         self.state.bytes_written_current_file += bytes_opened;
         self.state.bytes_written_total += bytes_opened;
 
-        if let Some(cb) = &self.on_file_open_callback {
-            cb(&next_path, self.state.file_sequence_number);
-        }
+        self.notify_file_opened(&next_path);
 
         debug!("Writer opened for file: {:?}", next_path);
 
@@ -576,6 +785,35 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
         Ok(())
     }
 
+    /// Moves the open file to `target`, keeping it open for writing.
+    fn rename_current_file(&mut self, target: PathBuf) -> Result<(), TaskError<S::StrategyError>> {
+        let Some(current) = self.state.current_file_path.clone() else {
+            return Ok(());
+        };
+        if target == current {
+            return Ok(());
+        }
+        if !self.config.sink.is_local() {
+            warn!("Keeping {current:?} instead of {target:?}, remote files cannot be renamed");
+            return Ok(());
+        }
+
+        let target = self.ensure_unique_output_path(target);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).map_err(TaskError::Io)?;
+        }
+        debug!("Renaming open file {:?} to {:?}", current, target);
+        std::fs::rename(&current, &target).map_err(TaskError::Io)?;
+        self.state.current_path = target.clone();
+        self.state.current_file_path = Some(target.clone());
+
+        let index = self.state.file_sequence_number;
+        for hook in &mut self.segment_hooks {
+            hook.on_segment_renamed(&current, &target, index);
+        }
+        Ok(())
+    }
+
     pub fn process_item(&mut self, item: D) -> Result<(), WriterError> {
         self.process_item_inner(item).map_err(WriterError::from)
     }
@@ -598,6 +836,11 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
                     // Check and emit progress if thresholds exceeded
                     self.maybe_emit_progress();
 
+                    if let Some(path) = self.strategy.resolved_file_path(&self.config, &self.state)
+                    {
+                        self.rename_current_file(path)?;
+                    }
+
                     let post_write_action = self
                         .strategy
                         .after_item_written(&item, bytes_written, &self.state)
@@ -605,11 +848,14 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
                     match post_write_action {
                         PostWriteAction::None => {}
                         PostWriteAction::Close => {
-                            self.close_inner()?;
+                            self.close_inner(PostWriteAction::None)?;
                         }
                         PostWriteAction::Rotate => {
                             self.rotate_file()?;
                         }
+                        action @ (PostWriteAction::Rename(_) | PostWriteAction::Delete) => {
+                            self.close_inner(action)?;
+                        }
                     }
                     Ok(())
                 }
@@ -656,41 +902,83 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
     }
 
     pub fn close(&mut self) -> Result<(), WriterError> {
-        self.close_inner().map_err(WriterError::from)
+        self.close_inner(PostWriteAction::None)
+            .map_err(WriterError::from)
     }
 
-    fn close_inner(&mut self) -> Result<(), TaskError<S::StrategyError>> {
-        if let Some(mut writer) = self.writer.take()
-            && let Some(path) = &self.state.current_file_path
-        {
-            let bytes_closed = self
-                .strategy
-                .on_file_close(&mut writer, path, &self.config, &self.state)
-                .map_err(TaskError::Strategy)?;
-            self.state.bytes_written_current_file += bytes_closed;
-            self.state.bytes_written_total += bytes_closed;
-            writer.flush().map_err(TaskError::Io)?;
-
-            // Capture duration before callback (current file duration)
-            let duration_secs = self.state.media_duration_secs_current_file;
-            let size_bytes = self.state.bytes_written_current_file;
-            let split_reason = self.strategy.close_context();
-
-            if let Some(cb) = &self.on_file_close_callback {
-                cb(
-                    path,
-                    self.state.file_sequence_number,
-                    duration_secs,
-                    size_bytes,
-                    split_reason.as_ref(),
-                );
-            }
+    fn close_inner(&mut self, action: PostWriteAction) -> Result<(), TaskError<S::StrategyError>> {
+        if let Some(writer) = self.writer.take() {
+            self.finish_file(writer, action)?;
         }
 
         self.state.current_file_path = None;
         Ok(())
     }
 
+    /// Writes the footer of the current file, flushes and drops its writer, then
+    /// applies `action` and the segment hooks before invoking the close callback.
+    ///
+    /// The close callback receives the final path of the file and is skipped if
+    /// the file was deleted.
+    fn finish_file(
+        &mut self,
+        mut writer: S::Writer,
+        action: PostWriteAction,
+    ) -> Result<(), TaskError<S::StrategyError>> {
+        let Some(path) = self.state.current_file_path.clone() else {
+            return Ok(());
+        };
+
+        let bytes_closed = self
+            .strategy
+            .on_file_close(&mut writer, &path, &self.config, &self.state)
+            .map_err(TaskError::Strategy)?;
+        self.state.bytes_written_current_file += bytes_closed;
+        self.state.bytes_written_total += bytes_closed;
+        writer.flush().map_err(TaskError::Io)?;
+        // Release the file handle before it is renamed or deleted
+        drop(writer);
+
+        let split_reason = self.strategy.close_context();
+        let index = self.state.file_sequence_number;
+
+        let local = self.config.sink.is_local();
+        let mut final_path = apply_close_action(path, action, local)?;
+        if final_path.is_some() && !self.segment_hooks.is_empty() {
+            let stats = SegmentStats::from_state(&self.state, split_reason.clone());
+            for hook in &mut self.segment_hooks {
+                let Some(path) = final_path.take() else {
+                    break;
+                };
+                let action = hook.on_segment_close(&path, index, &stats);
+                final_path = apply_close_action(path, action, local)?;
+            }
+        }
+
+        if let Some(path) = final_path
+            && let Some(cb) = &self.on_file_close_callback
+        {
+            cb(
+                &path,
+                index,
+                self.state.media_duration_secs_current_file,
+                self.state.bytes_written_current_file,
+                split_reason.as_ref(),
+            );
+        }
+        Ok(())
+    }
+
+    fn notify_file_opened(&mut self, path: &Path) {
+        let index = self.state.file_sequence_number;
+        for hook in &mut self.segment_hooks {
+            hook.on_segment_open(path, index);
+        }
+        if let Some(cb) = &self.on_file_open_callback {
+            cb(path, index);
+        }
+    }
+
     /// De-duplicated blocking_recv loop for reading from a channel and writing items.
     ///
     /// The `pre_filter` closure can inspect each item and the current writer state
@@ -731,6 +1019,11 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
         &self.config
     }
 
+    /// Returns a mutable reference to the configuration.
+    pub fn config_mut(&mut self) -> &mut WriterConfig {
+        &mut self.config
+    }
+
     /// Returns a reference to the strategy.
     pub fn strategy(&self) -> &S {
         &self.strategy
@@ -742,6 +1035,52 @@ impl<D, S: FormatStrategy<D>> WriterTask<D, S> {
     }
 }
 
+/// Returns the first `name_N.ext` next to `path` that does not exist yet.
+fn numbered_output_path(path: PathBuf) -> PathBuf {
+    let (Some(parent), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return path;
+    };
+    let stem = stem.to_string_lossy();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1u32..)
+        .map(|n| parent.join(format!("{stem}_{n}{ext}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path)
+}
+
+/// Applies a rename or delete requested for a closed file, ignored for remote files.
+/// Returns the path of the file afterwards, or `None` if it was deleted.
+fn apply_close_action(
+    path: PathBuf,
+    action: PostWriteAction,
+    local: bool,
+) -> io::Result<Option<PathBuf>> {
+    match action {
+        PostWriteAction::Rename(_) | PostWriteAction::Delete if !local => {
+            warn!("Ignoring {action:?} of {path:?}, remote files cannot be changed once written");
+            Ok(Some(path))
+        }
+        PostWriteAction::Rename(target) => {
+            debug!("Renaming closed file {:?} to {:?}", path, target);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&path, &target)?;
+            Ok(Some(target))
+        }
+        PostWriteAction::Delete => {
+            debug!("Deleting closed file {:?}", path);
+            std::fs::remove_file(&path)?;
+            Ok(None)
+        }
+        PostWriteAction::None | PostWriteAction::Close | PostWriteAction::Rotate => Ok(Some(path)),
+    }
+}
+
 /// A default file-based strategy for convenience.
 /// This can be used directly or as a template for more complex strategies.
 #[allow(dead_code)]
@@ -755,16 +1094,15 @@ pub enum DefaultStrategyError {
 }
 
 impl<D: Send + Sync + 'static> FormatStrategy<D> for DefaultFileStrategy {
-    type Writer = BufWriter<File>;
+    type Writer = BufWriter<SinkWriter>;
     type StrategyError = DefaultStrategyError;
 
-    fn create_writer(&self, path: &Path) -> Result<Self::Writer, Self::StrategyError> {
-        let file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
-        Ok(BufWriter::new(file))
+    fn create_writer(
+        &self,
+        path: &Path,
+        config: &WriterConfig,
+    ) -> Result<Self::Writer, Self::StrategyError> {
+        Ok(BufWriter::new(config.open_output(path)?))
     }
 
     fn write_item(
@@ -803,11 +1141,13 @@ impl<D: Send + Sync + 'static> FormatStrategy<D> for DefaultFileStrategy {
 
     fn on_file_close(
         &mut self,
-        _writer: &mut Self::Writer,
+        writer: &mut Self::Writer,
         _path: &Path,
         _config: &WriterConfig,
         _state: &WriterState,
     ) -> Result<u64, Self::StrategyError> {
+        writer.flush()?;
+        writer.get_mut().finish()?;
         Ok(0) // No footer by default
     }
 }
@@ -815,6 +1155,8 @@ impl<D: Send + Sync + 'static> FormatStrategy<D> for DefaultFileStrategy {
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "upload")]
+    use crate::output_sink::mock::MockUploadServer;
     use std::fs;
     use tempfile::tempdir;
 
@@ -838,15 +1180,16 @@ mod tests {
     }
 
     impl FormatStrategy<TestData> for TestStrategy {
-        type Writer = BufWriter<File>;
+        type Writer = BufWriter<SinkWriter>;
         type StrategyError = TestStrategyError;
 
-        fn create_writer(&self, path: &Path) -> Result<Self::Writer, Self::StrategyError> {
-            OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
+        fn create_writer(
+            &self,
+            path: &Path,
+            config: &WriterConfig,
+        ) -> Result<Self::Writer, Self::StrategyError> {
+            config
+                .open_output(path)
                 .map(BufWriter::new)
                 .map_err(|e| TestStrategyError(format!("Failed to create writer: {e}")))
         }
@@ -909,13 +1252,17 @@ mod tests {
             if let Some(footer) = &self.footer_content {
                 writer
                     .write_all(footer.as_bytes())
+                    .and_then(|_| writer.write_all(b"\n"))
                     .map_err(|e| TestStrategyError(e.to_string()))?;
-                writer
-                    .write_all(b"\n")
-                    .map_err(|e| TestStrategyError(e.to_string()))?;
-                return Ok((footer.len() + 1) as u64);
             }
-            Ok(0)
+            writer
+                .flush()
+                .and_then(|_| writer.get_mut().finish())
+                .map_err(|e| TestStrategyError(e.to_string()))?;
+            Ok(self
+                .footer_content
+                .as_ref()
+                .map_or(0, |f| (f.len() + 1) as u64))
         }
     }
 
@@ -946,6 +1293,53 @@ mod tests {
         assert_eq!(task.get_state().items_written_total, 2);
     }
 
+    #[test]
+    #[cfg(feature = "upload")]
+    fn test_writer_task_uploads_match_local_files() {
+        let dir = tempdir().unwrap();
+        let server = MockUploadServer::start();
+        let sinks = [
+            OutputSink::Local,
+            OutputSink::HttpPut {
+                url_template: format!("{}/put/{{name}}", server.url()),
+                headers: Vec::new(),
+            },
+            OutputSink::Multipart {
+                endpoint: format!("{}/parts", server.url()),
+                part_size: 64,
+            },
+        ];
+        for sink in sinks {
+            let config = WriterConfig::new(
+                dir.path().to_path_buf(),
+                "test_upload_%i".to_string(),
+                "log".to_string(),
+            )
+            .with_output_sink(sink);
+            let strategy = TestStrategy {
+                item_count_to_rotate: 20,
+                header_content: Some("HEADER".to_string()),
+                footer_content: Some("FOOTER".to_string()),
+                items_written_for_rotation_check: 0,
+            };
+            let mut task = WriterTask::new(config, strategy);
+            for i in 0..50 {
+                task.process_item(TestData(format!("item{i}"))).unwrap();
+            }
+            task.close().unwrap();
+            assert_eq!(task.get_state().file_sequence_number, 2);
+        }
+
+        // Only the local run wrote to the disk
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+        for index in 0..3 {
+            let name = format!("test_upload_{index}.log");
+            let local = fs::read(dir.path().join(&name)).unwrap();
+            assert_eq!(server.object(&format!("/put/{name}")).unwrap(), local);
+            assert_eq!(server.object(&format!("/parts/{name}")).unwrap(), local);
+        }
+    }
+
     #[test]
     fn test_writer_task_rotation() {
         let dir = tempdir().unwrap();
@@ -1029,4 +1423,215 @@ mod tests {
         assert_eq!(content1, "data2\n");
         assert_eq!(content2, "data3\n");
     }
+
+    /// Records segment events, checking the files on disk as they happen.
+    struct RecordingHook {
+        events: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+        base_path: PathBuf,
+        close_action: fn(u32, &Path) -> PostWriteAction,
+    }
+
+    impl SegmentHook for RecordingHook {
+        fn on_segment_open(&mut self, path: &Path, index: u32) {
+            assert!(
+                path.exists(),
+                "segment {index} opened before its file exists"
+            );
+            self.events.lock().unwrap().push(format!("open {index}"));
+        }
+
+        fn on_segment_close(
+            &mut self,
+            path: &Path,
+            index: u32,
+            stats: &SegmentStats,
+        ) -> PostWriteAction {
+            let next = self.base_path.join(format!("test_hooks_{}.log", index + 1));
+            assert!(
+                !next.exists(),
+                "segment {} created before {index} closed",
+                index + 1
+            );
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("close {index} ({} items)", stats.items_written));
+            (self.close_action)(index, path)
+        }
+    }
+
+    fn hook_test_task(
+        dir: &Path,
+        close_action: fn(u32, &Path) -> PostWriteAction,
+    ) -> (
+        WriterTask<TestData, TestStrategy>,
+        std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+    ) {
+        let config = WriterConfig::new(
+            dir.to_path_buf(),
+            "test_hooks_%i".to_string(),
+            "log".to_string(),
+        );
+        let strategy = TestStrategy {
+            item_count_to_rotate: 2,
+            header_content: None,
+            footer_content: None,
+            items_written_for_rotation_check: 0,
+        };
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut task = WriterTask::new(config, strategy);
+        task.add_segment_hook(RecordingHook {
+            events: events.clone(),
+            base_path: dir.to_path_buf(),
+            close_action,
+        });
+        (task, events)
+    }
+
+    #[test]
+    fn test_segment_hooks_close_before_next_open() {
+        let dir = tempdir().unwrap();
+        let (mut task, events) = hook_test_task(dir.path(), |_, _| PostWriteAction::None);
+
+        for i in 0..5 {
+            task.process_item(TestData(format!("data{i}"))).unwrap();
+        }
+        task.close().unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "open 0",
+                "close 0 (2 items)",
+                "open 1",
+                "close 1 (2 items)",
+                "open 2",
+                "close 2 (1 items)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_segment_hook_renames_and_deletes_closed_files() {
+        let dir = tempdir().unwrap();
+        let (mut task, _events) = hook_test_task(dir.path(), |index, path| match index {
+            0 => PostWriteAction::Rename(path.with_extension("done")),
+            1 => PostWriteAction::Delete,
+            _ => PostWriteAction::None,
+        });
+        let closed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let closed_clone = closed.clone();
+        task.set_on_file_close_callback(move |path, _, _, _, _| {
+            closed_clone.lock().unwrap().push(path.to_path_buf());
+        });
+
+        for i in 0..5 {
+            task.process_item(TestData(format!("data{i}"))).unwrap();
+        }
+        task.close().unwrap();
+
+        let renamed = dir.path().join("test_hooks_0.done");
+        assert_eq!(fs::read_to_string(&renamed).unwrap(), "data0\ndata1\n");
+        assert!(!dir.path().join("test_hooks_0.log").exists());
+        assert!(!dir.path().join("test_hooks_1.log").exists());
+        assert!(dir.path().join("test_hooks_2.log").exists());
+
+        // The close callback sees the renamed file and is skipped for the deleted one
+        assert_eq!(
+            *closed.lock().unwrap(),
+            vec![renamed, dir.path().join("test_hooks_2.log")]
+        );
+    }
+
+    #[test]
+    fn test_queued_segment_hook_preserves_order() {
+        let dir = tempdir().unwrap();
+        let (mut task, _events) = hook_test_task(dir.path(), |_, _| PostWriteAction::None);
+        let (hook, mut rx) = QueuedSegmentHook::new();
+        task.add_segment_hook(hook);
+
+        for i in 0..3 {
+            task.process_item(TestData(format!("data{i}"))).unwrap();
+        }
+        task.close().unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(match event {
+                SegmentEvent::Opened { index, .. } => format!("open {index}"),
+                SegmentEvent::Renamed { index, .. } => format!("rename {index}"),
+                SegmentEvent::Closed { index, stats, .. } => {
+                    format!("close {index} ({} bytes)", stats.size_bytes)
+                }
+            });
+        }
+        assert_eq!(
+            events,
+            vec![
+                "open 0",
+                "close 0 (12 bytes)",
+                "open 1",
+                "close 1 (6 bytes)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_numbered_collisions() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("fixed.log"), "existing\n").unwrap();
+        let config = WriterConfig::new(dir.path().to_path_buf(), "fixed".into(), "log".into())
+            .with_numbered_collisions(true);
+        let strategy = TestStrategy {
+            item_count_to_rotate: 1,
+            header_content: None,
+            footer_content: None,
+            items_written_for_rotation_check: 0,
+        };
+        let mut task = WriterTask::new(config, strategy);
+
+        task.process_item(TestData("first".into())).unwrap();
+        task.process_item(TestData("second".into())).unwrap();
+        task.close().unwrap();
+
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("fixed.log"), "existing\n");
+        assert_eq!(read("fixed_1.log"), "first\n");
+        assert_eq!(read("fixed_2.log"), "second\n");
+    }
+
+    #[cfg(feature = "integrity")]
+    #[test]
+    fn test_integrity_manifest_lists_every_part() {
+        use crate::integrity::{IntegrityConfig, IntegrityManifest, verify_manifest};
+
+        let dir = tempdir().unwrap();
+        let manifest_path = dir.path().join("job.manifest.json");
+        let config = WriterConfig::new(
+            dir.path().to_path_buf(),
+            "test_manifest_%i".to_string(),
+            "log".to_string(),
+        );
+        let strategy = TestStrategy {
+            item_count_to_rotate: 2,
+            header_content: Some("HEADER".to_string()),
+            footer_content: Some("FOOTER".to_string()),
+            items_written_for_rotation_check: 0,
+        };
+        let mut task = WriterTask::new(config, strategy);
+        task.add_integrity_manifest(IntegrityManifest::new(IntegrityConfig::per_job(
+            &manifest_path,
+        )));
+        for i in 0..5 {
+            task.process_item(TestData(format!("data{i}"))).unwrap();
+        }
+        task.close().unwrap();
+
+        let report = verify_manifest(&manifest_path).unwrap();
+        assert!(report.is_ok(), "{report:?}");
+        let parts: Vec<_> = (0..3)
+            .map(|i| dir.path().join(format!("test_manifest_{i}.log")))
+            .collect();
+        assert_eq!(report.verified, parts);
+    }
 }
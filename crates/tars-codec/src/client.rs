@@ -0,0 +1,186 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use futures::{
+    SinkExt, StreamExt,
+    stream::{SplitSink, SplitStream},
+};
+use rustc_hash::FxHashMap;
+use tokio::{
+    net::{TcpStream, ToSocketAddrs},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+use tokio_util::codec::Framed;
+
+use crate::{
+    codec::TarsCodec,
+    error::TarsError,
+    types::{TarsMessage, TarsRequestHeader},
+};
+
+type Transport = Framed<TcpStream, TarsCodec>;
+type Waiter = oneshot::Sender<Result<TarsMessage, TarsError>>;
+
+#[derive(Default)]
+struct Pending {
+    waiters: FxHashMap<i32, Waiter>,
+    /// Set by the reader task once the connection is gone, under the same lock
+    /// as `waiters` so no request can register after the final drain
+    closed: bool,
+}
+
+/// Request/response client multiplexing concurrent calls over one TCP connection
+///
+/// Each [`invoke`](Self::invoke) gets its own request id and the background
+/// reader routes responses back by that id, so calls may complete in any
+/// order. Messages carrying an id that was never sent (server pushes) are
+/// forwarded to the optional push channel given to [`TarsClient::new`].
+///
+/// When the peer disconnects, every in-flight call fails with
+/// [`TarsError::ConnectionClosed`], as do all later calls.
+pub struct TarsClient {
+    sink: tokio::sync::Mutex<SplitSink<Transport, TarsMessage>>,
+    pending: Arc<Mutex<Pending>>,
+    next_request_id: AtomicI32,
+    reader: JoinHandle<()>,
+}
+
+impl TarsClient {
+    /// Connect to `addr` without a push channel
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, TarsError> {
+        let stream = TcpStream::connect(addr).await?;
+        Ok(Self::new(stream, None))
+    }
+
+    /// Wrap an established connection
+    ///
+    /// Server-pushed messages are sent to `push_tx` if given, and dropped otherwise.
+    pub fn new(stream: TcpStream, push_tx: Option<mpsc::UnboundedSender<TarsMessage>>) -> Self {
+        let (sink, stream) = Framed::new(stream, TarsCodec).split();
+        let pending = Arc::new(Mutex::new(Pending::default()));
+        let reader = tokio::spawn(read_loop(stream, pending.clone(), push_tx));
+
+        Self {
+            sink: tokio::sync::Mutex::new(sink),
+            pending,
+            next_request_id: AtomicI32::new(1),
+            reader,
+        }
+    }
+
+    /// Whether the connection has been closed by the peer or by a decode error
+    pub fn is_closed(&self) -> bool {
+        self.pending.lock().unwrap().closed
+    }
+
+    /// Send a request and wait up to `timeout` for the matching response
+    pub async fn invoke(
+        &self,
+        servant: &str,
+        func: &str,
+        body: FxHashMap<String, Bytes>,
+        timeout: Duration,
+    ) -> Result<TarsMessage, TarsError> {
+        let request_id = self.next_request_id();
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(TarsError::ConnectionClosed);
+            }
+            pending.waiters.insert(request_id, tx);
+        }
+
+        let message = TarsMessage {
+            header: TarsRequestHeader {
+                version: 3,
+                packet_type: 0,
+                message_type: 0,
+                request_id,
+                servant_name: servant.to_string(),
+                func_name: func.to_string(),
+                timeout: timeout.as_millis().min(i32::MAX as u128) as i32,
+                context: FxHashMap::default(),
+                status: FxHashMap::default(),
+            },
+            body,
+        };
+
+        if let Err(e) = self.sink.lock().await.send(message).await {
+            self.forget(request_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            // The reader task ended without answering, e.g. it was aborted
+            Ok(Err(_)) => Err(TarsError::ConnectionClosed),
+            Err(_) => {
+                self.forget(request_id);
+                Err(TarsError::Timeout)
+            }
+        }
+    }
+
+    /// Allocate a request id, skipping 0 which servers commonly use for pushes
+    fn next_request_id(&self) -> i32 {
+        loop {
+            let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+            if id != 0 {
+                return id;
+            }
+        }
+    }
+
+    fn forget(&self, request_id: i32) {
+        self.pending.lock().unwrap().waiters.remove(&request_id);
+    }
+}
+
+impl Drop for TarsClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn read_loop(
+    mut stream: SplitStream<Transport>,
+    pending: Arc<Mutex<Pending>>,
+    push_tx: Option<mpsc::UnboundedSender<TarsMessage>>,
+) {
+    while let Some(Ok(message)) = stream.next().await {
+        let waiter = pending
+            .lock()
+            .unwrap()
+            .waiters
+            .remove(&message.header.request_id);
+
+        match waiter {
+            Some(tx) => {
+                // The caller may have given up already
+                let _ = tx.send(Ok(message));
+            }
+            None => {
+                if let Some(push_tx) = &push_tx {
+                    let _ = push_tx.send(message);
+                }
+            }
+        }
+    }
+
+    let waiters = {
+        let mut pending = pending.lock().unwrap();
+        pending.closed = true;
+        std::mem::take(&mut pending.waiters)
+    };
+    for (_, tx) in waiters {
+        let _ = tx.send(Err(TarsError::ConnectionClosed));
+    }
+}
@@ -2,6 +2,29 @@ use crate::{de::TarsDeserializer, error::TarsError, ser::TarsSerializer, types::
 use bytes::{BufMut, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
+/// Default upper bound for a single framed message, including the 4-byte length prefix.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Reads and validates the big-endian length prefix at the start of `src`.
+///
+/// Returns `Ok(None)` if fewer than 4 bytes are available. The length counts the
+/// prefix itself, so anything below 4 is rejected, as is anything above `max`.
+pub(crate) fn read_frame_len(src: &[u8], max: usize) -> Result<Option<usize>, TarsError> {
+    let Some(prefix) = src.first_chunk::<4>() else {
+        return Ok(None);
+    };
+
+    let len = u32::from_be_bytes(*prefix) as usize;
+    if len < 4 {
+        return Err(TarsError::InvalidLength(len));
+    }
+    if len > max {
+        return Err(TarsError::MessageTooLarge { len, max });
+    }
+
+    Ok(Some(len))
+}
+
 #[derive(Default)]
 pub struct TarsCodec;
 
@@ -54,13 +77,9 @@ impl Decoder for TarsCodec {
     type Error = TarsError;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if src.len() < 4 {
+        let Some(len) = read_frame_len(src, DEFAULT_MAX_MESSAGE_SIZE)? else {
             return Ok(None);
-        }
-
-        let mut len_bytes = [0u8; 4];
-        len_bytes.copy_from_slice(&src[..4]);
-        let len = u32::from_be_bytes(len_bytes) as usize;
+        };
 
         if src.len() < len {
             src.reserve(len - src.len());
@@ -1,15 +1,33 @@
 use crate::{
     error::TarsError,
+    simd::utf8_simd,
     types::{TarsMessage, TarsRequestHeader, TarsType, TarsValue},
 };
 use bytes::{Buf, Bytes};
 use rustc_hash::FxHashMap;
 use smallvec::SmallVec;
 
+/// How string fields that are not valid UTF-8 are handled when decoding
+///
+/// Some servers put GBK or other legacy encodings in fields declared as `string`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringPolicy {
+    /// Fail with [`TarsError::InvalidUtf8`]
+    #[default]
+    Strict,
+    /// Replace invalid sequences with U+FFFD. Valid strings are still zero-copy
+    /// when `zero_copy_strings` is set; only invalid ones are re-allocated.
+    Lossy,
+    /// Skip validation and surface every string as [`TarsValue::Binary`]
+    Raw,
+}
+
 pub struct TarsDeserializer {
     buffer: Bytes,
     /// When true, strings are parsed as StringRef (zero-copy) instead of String
     pub zero_copy_strings: bool,
+    /// How strings that are not valid UTF-8 are handled
+    pub string_policy: StringPolicy,
 }
 
 impl TarsDeserializer {
@@ -17,6 +35,7 @@ impl TarsDeserializer {
         Self {
             buffer,
             zero_copy_strings: false, // Default to backward compatibility
+            string_policy: StringPolicy::Strict,
         }
     }
 
@@ -24,6 +43,16 @@ impl TarsDeserializer {
         Self {
             buffer,
             zero_copy_strings: true,
+            string_policy: StringPolicy::Strict,
+        }
+    }
+
+    /// Create a deserializer with the given [`StringPolicy`]
+    pub fn with_string_policy(buffer: Bytes, string_policy: StringPolicy) -> Self {
+        Self {
+            buffer,
+            zero_copy_strings: false,
+            string_policy,
         }
     }
 
@@ -53,17 +82,18 @@ impl TarsDeserializer {
                 2 => header.packet_type = value.try_into_u8()?,
                 3 => header.message_type = value.try_into_i32()?,
                 4 => header.request_id = value.try_into_i32()?,
-                5 => header.servant_name = value.try_into_string()?,
-                6 => header.func_name = value.try_into_string()?,
+                5 => header.servant_name = self.message_string(value)?,
+                6 => header.func_name = self.message_string(value)?,
                 7 => {
                     let body_bytes = value.try_into_simple_list()?;
-                    let mut body_de = TarsDeserializer::new(body_bytes);
+                    let mut body_de =
+                        TarsDeserializer::with_string_policy(body_bytes, self.string_policy);
                     let (_tag, body_value) = body_de.read_value()?;
                     let body_map = body_value.try_into_map()?;
                     body = body_map
                         .into_iter()
                         .map(|(k, v)| {
-                            let k = k.try_into_string()?;
+                            let k = self.message_string(k)?;
                             let v = v.try_into_simple_list()?;
                             Ok((k, v))
                         })
@@ -74,14 +104,14 @@ impl TarsDeserializer {
                     header.context = value
                         .try_into_map()?
                         .into_iter()
-                        .map(|(k, v)| Ok((k.try_into_string()?, v.try_into_string()?)))
+                        .map(|(k, v)| Ok((self.message_string(k)?, self.message_string(v)?)))
                         .collect::<Result<_, TarsError>>()?
                 }
                 10 => {
                     header.status = value
                         .try_into_map()?
                         .into_iter()
-                        .map(|(k, v)| Ok((k.try_into_string()?, v.try_into_string()?)))
+                        .map(|(k, v)| Ok((self.message_string(k)?, self.message_string(v)?)))
                         .collect::<Result<_, TarsError>>()?
                 }
                 _ => {} // Ignore unknown tags
@@ -91,6 +121,19 @@ impl TarsDeserializer {
         Ok(TarsMessage { header, body })
     }
 
+    /// Convert a string field of [`TarsMessage`] into an owned `String`
+    ///
+    /// The message fields are typed as `String`, so under [`StringPolicy::Raw`]
+    /// they are converted lossily instead of being kept as bytes.
+    fn message_string(&self, value: TarsValue) -> Result<String, TarsError> {
+        match (self.string_policy, value) {
+            (StringPolicy::Lossy | StringPolicy::Raw, TarsValue::Binary(bytes)) => {
+                Ok(String::from_utf8_lossy(&bytes).into_owned())
+            }
+            (_, value) => value.try_into_string(),
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
@@ -185,10 +228,50 @@ impl TarsDeserializer {
         let bytes = self.buffer.split_to(len);
         // println!("TarsDeserializer::read_string_ref: {:?}", bytes);
         // Validate UTF-8 without allocating
-        std::str::from_utf8(&bytes).map_err(TarsError::from)?;
+        utf8_simd::validate_utf8(&bytes)?;
         Ok(bytes)
     }
 
+    /// Read a string payload of `len` bytes according to the [`StringPolicy`]
+    fn read_string_value(&mut self, len: usize) -> Result<TarsValue, TarsError> {
+        match self.string_policy {
+            StringPolicy::Strict => {
+                if self.zero_copy_strings {
+                    self.read_string_ref(len).map(TarsValue::StringRef)
+                } else {
+                    self.read_string(len).map(TarsValue::String)
+                }
+            }
+            StringPolicy::Lossy => {
+                let bytes = self.take_bytes(len)?;
+                match std::str::from_utf8(&bytes) {
+                    Ok(_) if self.zero_copy_strings => Ok(TarsValue::StringRef(bytes)),
+                    Ok(s) => Ok(TarsValue::String(s.to_owned())),
+                    Err(_) => {
+                        let lossy = String::from_utf8_lossy(&bytes).into_owned();
+                        if self.zero_copy_strings {
+                            Ok(TarsValue::StringRef(Bytes::from(lossy)))
+                        } else {
+                            Ok(TarsValue::String(lossy))
+                        }
+                    }
+                }
+            }
+            StringPolicy::Raw => self.take_bytes(len).map(TarsValue::Binary),
+        }
+    }
+
+    /// Split `len` bytes off the buffer, failing instead of panicking if there are fewer
+    fn take_bytes(&mut self, len: usize) -> Result<Bytes, TarsError> {
+        if self.buffer.len() < len {
+            return Err(TarsError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "Unexpected EOF reading string",
+            )));
+        }
+        Ok(self.buffer.split_to(len))
+    }
+
     pub fn read_struct(&mut self) -> Result<FxHashMap<u8, TarsValue>, TarsError> {
         let mut map = FxHashMap::default();
         loop {
@@ -271,19 +354,11 @@ impl TarsDeserializer {
             TarsType::Double => self.read_f64().map(TarsValue::Double),
             TarsType::String1 => {
                 let len = self.buffer.get_u8() as usize;
-                if self.zero_copy_strings {
-                    self.read_string_ref(len).map(TarsValue::StringRef)
-                } else {
-                    self.read_string(len).map(TarsValue::String)
-                }
+                self.read_string_value(len)
             }
             TarsType::String4 => {
                 let len = self.buffer.get_u32() as usize;
-                if self.zero_copy_strings {
-                    self.read_string_ref(len).map(TarsValue::StringRef)
-                } else {
-                    self.read_string(len).map(TarsValue::String)
-                }
+                self.read_string_value(len)
             }
             TarsType::StructBegin => self.read_struct().map(TarsValue::Struct),
             TarsType::Map => self.read_map().map(TarsValue::Map),
@@ -448,3 +523,169 @@ pub fn from_bytes(buffer: Bytes) -> Result<TarsValue, TarsError> {
     let mut deserializer = TarsDeserializer::new(buffer);
     TarsValue::deserialize(&mut deserializer)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TarsCodecPool, decode_response_zero_copy_with_policy, encode_request};
+
+    /// GBK for "你" and "好", neither of which is valid UTF-8
+    const GBK_KEY: [u8; 2] = [0xc4, 0xe3];
+    const GBK_VALUE: [u8; 2] = [0xba, 0xc3];
+
+    /// A map with a single entry whose key and value are both GBK strings
+    fn gbk_map() -> Bytes {
+        let mut data = vec![
+            0x08, // tag 0, Map
+            0x00, 0x01, // length: tag 0, Int1, 1
+            0x06, 0x02, // key: tag 0, String1, 2 bytes
+        ];
+        data.extend_from_slice(&GBK_KEY);
+        data.extend_from_slice(&[0x16, 0x02]); // value: tag 1, String1, 2 bytes
+        data.extend_from_slice(&GBK_VALUE);
+        Bytes::from(data)
+    }
+
+    /// An encoded message whose context has a GBK key and value
+    fn gbk_message() -> Bytes {
+        let mut context = FxHashMap::default();
+        context.insert("KK".to_string(), "VV".to_string());
+        let message = TarsMessage {
+            header: TarsRequestHeader {
+                version: 3,
+                packet_type: 0,
+                message_type: 0,
+                request_id: 1,
+                servant_name: "test".to_string(),
+                func_name: "echo".to_string(),
+                timeout: 0,
+                context,
+                status: FxHashMap::default(),
+            },
+            body: FxHashMap::default(),
+        };
+
+        // Swap the placeholders for invalid bytes of the same length
+        let mut data = encode_request(&message).unwrap().to_vec();
+        for (placeholder, replacement) in [(b"KK", GBK_KEY), (b"VV", GBK_VALUE)] {
+            let pos = data
+                .windows(2)
+                .position(|w| w == placeholder)
+                .expect("placeholder present");
+            data[pos..pos + 2].copy_from_slice(&replacement);
+        }
+        Bytes::from(data)
+    }
+
+    fn read_map(policy: StringPolicy, zero_copy: bool) -> Result<TarsValue, TarsError> {
+        let mut de = TarsDeserializer::with_string_policy(gbk_map(), policy);
+        de.zero_copy_strings = zero_copy;
+        de.read_value().map(|(_, value)| value)
+    }
+
+    #[test]
+    fn test_strict_rejects_invalid_utf8() {
+        for zero_copy in [false, true] {
+            assert!(matches!(
+                read_map(StringPolicy::Strict, zero_copy),
+                Err(TarsError::InvalidUtf8(_))
+            ));
+        }
+        assert!(matches!(
+            decode_response_zero_copy_with_policy(gbk_message(), StringPolicy::Strict),
+            Err(TarsError::InvalidUtf8(_))
+        ));
+    }
+
+    #[test]
+    fn test_lossy_replaces_invalid_sequences() {
+        for zero_copy in [false, true] {
+            let map = read_map(StringPolicy::Lossy, zero_copy)
+                .unwrap()
+                .try_into_map()
+                .unwrap();
+            let (key, value) = map.into_iter().next().unwrap();
+            assert_eq!(key.as_str(), Some("\u{fffd}\u{fffd}"));
+            assert_eq!(value.as_str(), Some("\u{fffd}\u{fffd}"));
+        }
+
+        let message =
+            decode_response_zero_copy_with_policy(gbk_message(), StringPolicy::Lossy).unwrap();
+        assert_eq!(message.header.func_name, "echo");
+        assert_eq!(
+            message
+                .header
+                .context
+                .get("\u{fffd}\u{fffd}")
+                .map(String::as_str),
+            Some("\u{fffd}\u{fffd}")
+        );
+    }
+
+    #[test]
+    fn test_lossy_keeps_valid_strings_zero_copy() {
+        let data = Bytes::from_static(&[0x06, 0x03, b'a', b'b', b'c']);
+        let mut de = TarsDeserializer::with_string_policy(data, StringPolicy::Lossy);
+        de.zero_copy_strings = true;
+
+        let (_, value) = de.read_value().unwrap();
+        assert_eq!(value, TarsValue::StringRef(Bytes::from_static(b"abc")));
+    }
+
+    #[test]
+    fn test_raw_surfaces_bytes() {
+        for zero_copy in [false, true] {
+            let map = read_map(StringPolicy::Raw, zero_copy)
+                .unwrap()
+                .try_into_map()
+                .unwrap();
+            let (key, value) = map.into_iter().next().unwrap();
+            assert_eq!(key, TarsValue::Binary(Bytes::copy_from_slice(&GBK_KEY)));
+            assert_eq!(value, TarsValue::Binary(Bytes::copy_from_slice(&GBK_VALUE)));
+        }
+
+        // Message header fields are typed as String, so they fall back to lossy
+        let payload = gbk_message().slice(4..);
+        let message = TarsCodecPool::new(1)
+            .decode_pooled_with_policy(payload, StringPolicy::Raw)
+            .unwrap();
+        assert_eq!(message.header.servant_name, "test");
+        assert_eq!(
+            message
+                .header
+                .context
+                .get("\u{fffd}\u{fffd}")
+                .map(String::as_str),
+            Some("\u{fffd}\u{fffd}")
+        );
+    }
+
+    #[test]
+    fn test_truncated_string_is_an_error() {
+        let data = Bytes::from_static(&[0x06, 0x05, b'a']);
+        for policy in [StringPolicy::Lossy, StringPolicy::Raw] {
+            let mut de = TarsDeserializer::with_string_policy(data.clone(), policy);
+            assert!(matches!(de.read_value(), Err(TarsError::Io(_))));
+        }
+    }
+
+    #[test]
+    fn test_simd_and_scalar_validation_agree() {
+        let mut long = vec![b'a'; 40];
+        long.extend_from_slice(&GBK_KEY);
+        let cases: [&[u8]; 4] = [
+            b"",
+            "plain ascii only!!".as_bytes(),
+            "中文字符串".as_bytes(),
+            &long,
+        ];
+
+        for case in cases {
+            assert_eq!(
+                utf8_simd::validate_utf8(case).is_ok(),
+                utf8_simd::validate_utf8_scalar(case).is_ok(),
+                "{case:02x?}"
+            );
+        }
+    }
+}
@@ -29,6 +29,24 @@ pub enum TarsError {
         actual: &'static str,
     },
 
+    #[error("Value {value} out of range for {expected}")]
+    OutOfRange { value: i64, expected: &'static str },
+
+    #[error("Invalid message length: {0}")]
+    InvalidLength(usize),
+
+    #[error("Message length {len} exceeds the maximum of {max} bytes")]
+    MessageTooLarge { len: usize, max: usize },
+
+    #[error("Truncated message: expected {expected} bytes, got {actual}")]
+    Truncated { expected: usize, actual: usize },
+
+    #[error("Connection closed")]
+    ConnectionClosed,
+
+    #[error("Request timed out")]
+    Timeout,
+
     #[error("Unknown error")]
     Unknown,
 }
@@ -0,0 +1,459 @@
+use bytes::Bytes;
+use rustc_hash::FxHashMap;
+use std::hash::Hash;
+
+use crate::{error::TarsError, types::TarsValue};
+
+impl TarsValue {
+    /// Name of the variant, used in [`TarsError::TypeMismatch`]
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TarsValue::Bool(_) => "Bool",
+            TarsValue::Byte(_) => "Byte",
+            TarsValue::Short(_) => "Short",
+            TarsValue::Int(_) => "Int",
+            TarsValue::Long(_) => "Long",
+            TarsValue::Float(_) => "Float",
+            TarsValue::Double(_) => "Double",
+            TarsValue::String(_) => "String",
+            TarsValue::StringRef(_) => "StringRef",
+            TarsValue::Struct(_) => "Struct",
+            TarsValue::Map(_) => "Map",
+            TarsValue::List(_) => "List",
+            TarsValue::SimpleList(_) => "SimpleList",
+            TarsValue::Binary(_) => "Binary",
+            TarsValue::StructBegin => "StructBegin",
+            TarsValue::StructEnd => "StructEnd",
+        }
+    }
+
+    fn mismatch(&self, expected: &'static str) -> TarsError {
+        TarsError::TypeMismatch {
+            expected,
+            actual: self.type_name(),
+        }
+    }
+
+    /// Get a boolean, accepting the `0`/`1` integers booleans are encoded as
+    pub fn get_bool(&self) -> Result<bool, TarsError> {
+        match self {
+            TarsValue::Bool(v) => Ok(*v),
+            TarsValue::Byte(0) => Ok(false),
+            TarsValue::Byte(1) => Ok(true),
+            _ => Err(self.mismatch("Bool")),
+        }
+    }
+
+    /// Get an integer of any width, sign-extended to `i64`
+    ///
+    /// The serializer writes integers in the narrowest type that fits, so a
+    /// field declared as `long` may arrive as a `Byte`, `Short` or `Int`.
+    pub fn get_i64(&self) -> Result<i64, TarsError> {
+        match self {
+            // Int1 is signed on the wire and stored as its raw byte
+            TarsValue::Byte(v) => Ok(*v as i8 as i64),
+            TarsValue::Short(v) => Ok(*v as i64),
+            TarsValue::Int(v) => Ok(*v as i64),
+            TarsValue::Long(v) => Ok(*v),
+            _ => Err(self.mismatch("Long")),
+        }
+    }
+
+    /// Get an integer that fits in an `i32`
+    pub fn get_i32(&self) -> Result<i32, TarsError> {
+        let value = self.get_i64().map_err(|_| self.mismatch("Int"))?;
+        i32::try_from(value).map_err(|_| TarsError::OutOfRange {
+            value,
+            expected: "Int",
+        })
+    }
+
+    /// Get a floating point number, accepting the `Zero` encoding of `0.0`
+    pub fn get_f64(&self) -> Result<f64, TarsError> {
+        match self {
+            TarsValue::Float(v) => Ok(*v as f64),
+            TarsValue::Double(v) => Ok(*v),
+            TarsValue::Byte(0) => Ok(0.0),
+            _ => Err(self.mismatch("Double")),
+        }
+    }
+
+    /// Get a string slice, validating `StringRef` data as UTF-8
+    pub fn get_str(&self) -> Result<&str, TarsError> {
+        match self {
+            TarsValue::String(s) => Ok(s),
+            TarsValue::StringRef(bytes) => Ok(std::str::from_utf8(bytes)?),
+            _ => Err(self.mismatch("String")),
+        }
+    }
+
+    /// Get raw bytes from a `SimpleList`, `Binary` or `StringRef`
+    pub fn get_bytes(&self) -> Result<&Bytes, TarsError> {
+        self.as_bytes().ok_or_else(|| self.mismatch("SimpleList"))
+    }
+
+    /// Get the entries of a map
+    pub fn get_map(&self) -> Result<&FxHashMap<TarsValue, TarsValue>, TarsError> {
+        match self {
+            TarsValue::Map(map) => Ok(map),
+            _ => Err(self.mismatch("Map")),
+        }
+    }
+
+    /// Get the items of a list
+    pub fn get_list(&self) -> Result<&[Box<TarsValue>], TarsError> {
+        match self {
+            TarsValue::List(list) => Ok(list),
+            _ => Err(self.mismatch("List")),
+        }
+    }
+
+    /// Get the tag to value map of a struct
+    pub fn get_struct(&self) -> Result<&FxHashMap<u8, TarsValue>, TarsError> {
+        match self {
+            TarsValue::Struct(fields) => Ok(fields),
+            _ => Err(self.mismatch("Struct")),
+        }
+    }
+}
+
+/// Conversion from a decoded [`TarsValue`] into a Rust type
+///
+/// Integer types accept any narrower wire width and fail with
+/// [`TarsError::OutOfRange`] if the value does not fit.
+pub trait FromTarsValue: Sized {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError>;
+}
+
+macro_rules! impl_from_tars_int {
+    ($($ty:ty => $name:literal),* $(,)?) => {
+        $(
+            impl FromTarsValue for $ty {
+                fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+                    let v = value.get_i64().map_err(|_| value.mismatch($name))?;
+                    <$ty>::try_from(v).map_err(|_| TarsError::OutOfRange {
+                        value: v,
+                        expected: $name,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_from_tars_int!(i8 => "Byte", i16 => "Short", i32 => "Int", i64 => "Long");
+
+impl FromTarsValue for u8 {
+    /// Reads the raw byte, so `-1` on the wire becomes `255`
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        match value {
+            TarsValue::Byte(v) => Ok(*v),
+            _ => Err(value.mismatch("Byte")),
+        }
+    }
+}
+
+impl FromTarsValue for bool {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        value.get_bool()
+    }
+}
+
+impl FromTarsValue for f32 {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        match value {
+            TarsValue::Float(v) => Ok(*v),
+            TarsValue::Byte(0) => Ok(0.0),
+            _ => Err(value.mismatch("Float")),
+        }
+    }
+}
+
+impl FromTarsValue for f64 {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        value.get_f64()
+    }
+}
+
+impl FromTarsValue for String {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        value.get_str().map(str::to_owned)
+    }
+}
+
+impl FromTarsValue for Bytes {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        value.get_bytes().cloned()
+    }
+}
+
+impl FromTarsValue for TarsValue {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromTarsValue> FromTarsValue for Vec<T> {
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        value
+            .get_list()?
+            .iter()
+            .map(|item| T::from_tars_value(item))
+            .collect()
+    }
+}
+
+impl<K, V> FromTarsValue for FxHashMap<K, V>
+where
+    K: FromTarsValue + Eq + Hash,
+    V: FromTarsValue,
+{
+    fn from_tars_value(value: &TarsValue) -> Result<Self, TarsError> {
+        value
+            .get_map()?
+            .iter()
+            .map(|(k, v)| Ok((K::from_tars_value(k)?, V::from_tars_value(v)?)))
+            .collect()
+    }
+}
+
+/// Typed access to the fields of a decoded TARS struct
+///
+/// Follows TARS tag semantics: a `require` field must be present, while an
+/// `optional` field that was not written takes its declared default. A field
+/// that is present but has the wrong type is an error in both cases.
+///
+/// ```
+/// # use tars_codec::{TarsError, TarsStructReader, TarsValue};
+/// # fn demo(value: &TarsValue) -> Result<(), TarsError> {
+/// let reader = TarsStructReader::from_value(value)?;
+/// let uid: i64 = reader.read_required(0)?;
+/// let nick: String = reader.read_optional(1, String::new())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TarsStructReader<'a> {
+    fields: &'a FxHashMap<u8, TarsValue>,
+}
+
+impl<'a> TarsStructReader<'a> {
+    /// Wrap a struct's tag to value map
+    pub fn new(fields: &'a FxHashMap<u8, TarsValue>) -> Self {
+        Self { fields }
+    }
+
+    /// Wrap a [`TarsValue::Struct`], failing for any other variant
+    pub fn from_value(value: &'a TarsValue) -> Result<Self, TarsError> {
+        value.get_struct().map(Self::new)
+    }
+
+    /// Get the raw value of a field
+    pub fn get(&self, tag: u8) -> Option<&'a TarsValue> {
+        self.fields.get(&tag)
+    }
+
+    /// Read a field that must be present
+    pub fn read_required<T: FromTarsValue>(&self, tag: u8) -> Result<T, TarsError> {
+        let value = self.get(tag).ok_or(TarsError::MissingRequiredField(tag))?;
+        T::from_tars_value(value)
+    }
+
+    /// Read a field, falling back to `default` if it is absent
+    pub fn read_optional<T: FromTarsValue>(&self, tag: u8, default: T) -> Result<T, TarsError> {
+        match self.get(tag) {
+            Some(value) => T::from_tars_value(value),
+            None => Ok(default),
+        }
+    }
+
+    /// Read a nested struct field that must be present
+    pub fn read_struct(&self, tag: u8) -> Result<TarsStructReader<'a>, TarsError> {
+        let value = self.get(tag).ok_or(TarsError::MissingRequiredField(tag))?;
+        Self::from_value(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smallvec::smallvec;
+
+    use super::*;
+    use crate::{de::TarsDeserializer, ser::TarsSerializer};
+
+    fn all_variants() -> Vec<TarsValue> {
+        vec![
+            TarsValue::Bool(true),
+            TarsValue::Byte(0xff),
+            TarsValue::Short(-300),
+            TarsValue::Int(70_000),
+            TarsValue::Long(1 << 40),
+            TarsValue::Float(1.5),
+            TarsValue::Double(2.5),
+            TarsValue::String("abc".to_string()),
+            TarsValue::StringRef(Bytes::from_static(b"def")),
+            TarsValue::Struct(FxHashMap::default()),
+            TarsValue::Map(FxHashMap::default()),
+            TarsValue::List(smallvec![Box::new(TarsValue::Int(1))]),
+            TarsValue::SimpleList(Bytes::from_static(&[1, 2])),
+            TarsValue::Binary(Bytes::from_static(&[3])),
+            TarsValue::StructBegin,
+            TarsValue::StructEnd,
+        ]
+    }
+
+    /// Names of the variants for which `get` does not return an error
+    fn accepted(get: impl Fn(&TarsValue) -> Option<TarsError>) -> Vec<&'static str> {
+        all_variants()
+            .iter()
+            .filter(|value| match get(value) {
+                None => true,
+                Some(TarsError::TypeMismatch { actual, .. }) => {
+                    assert_eq!(actual, value.type_name());
+                    false
+                }
+                Some(e) => panic!("unexpected error for {value:?}: {e}"),
+            })
+            .map(TarsValue::type_name)
+            .collect()
+    }
+
+    #[test]
+    fn test_getter_matrix() {
+        assert_eq!(
+            accepted(|v| v.get_i64().err()),
+            ["Byte", "Short", "Int", "Long"]
+        );
+        assert_eq!(accepted(|v| v.get_bool().err()), ["Bool"]);
+        assert_eq!(accepted(|v| v.get_f64().err()), ["Float", "Double"]);
+        assert_eq!(accepted(|v| v.get_str().err()), ["String", "StringRef"]);
+        assert_eq!(
+            accepted(|v| v.get_bytes().err()),
+            ["StringRef", "SimpleList", "Binary"]
+        );
+        assert_eq!(accepted(|v| v.get_map().err()), ["Map"]);
+        assert_eq!(accepted(|v| v.get_list().err()), ["List"]);
+        assert_eq!(accepted(|v| v.get_struct().err()), ["Struct"]);
+    }
+
+    #[test]
+    fn test_mismatch_error_names_both_types() {
+        let err = TarsValue::Double(1.0).get_str().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch: expected String, got Double"
+        );
+    }
+
+    #[test]
+    fn test_integer_promotion_sign_extends() {
+        assert_eq!(TarsValue::Byte(0xff).get_i64().unwrap(), -1);
+        assert_eq!(TarsValue::Byte(0x7f).get_i64().unwrap(), 127);
+        assert_eq!(TarsValue::Short(-300).get_i64().unwrap(), -300);
+        assert_eq!(TarsValue::Int(i32::MIN).get_i64().unwrap(), i32::MIN as i64);
+
+        assert_eq!(i8::from_tars_value(&TarsValue::Byte(0x80)).unwrap(), -128);
+        assert_eq!(u8::from_tars_value(&TarsValue::Byte(0x80)).unwrap(), 0x80);
+        assert_eq!(i16::from_tars_value(&TarsValue::Byte(5)).unwrap(), 5);
+        assert_eq!(i32::from_tars_value(&TarsValue::Short(-2)).unwrap(), -2);
+    }
+
+    #[test]
+    fn test_integer_narrowing_out_of_range() {
+        assert!(matches!(
+            TarsValue::Long(1 << 40).get_i32(),
+            Err(TarsError::OutOfRange {
+                value: 1_099_511_627_776,
+                expected: "Int"
+            })
+        ));
+        assert!(matches!(
+            i8::from_tars_value(&TarsValue::Short(200)),
+            Err(TarsError::OutOfRange { value: 200, .. })
+        ));
+        assert!(matches!(
+            i16::from_tars_value(&TarsValue::String("1".into())),
+            Err(TarsError::TypeMismatch {
+                expected: "Short",
+                actual: "String"
+            })
+        ));
+    }
+
+    #[test]
+    fn test_zero_encoded_values() {
+        // `false` and `0.0` are written with the Zero type and decode as Byte(0)
+        assert!(!TarsValue::Byte(0).get_bool().unwrap());
+        assert!(TarsValue::Byte(1).get_bool().unwrap());
+        assert!(TarsValue::Byte(2).get_bool().is_err());
+        assert_eq!(TarsValue::Byte(0).get_f64().unwrap(), 0.0);
+        assert_eq!(f32::from_tars_value(&TarsValue::Byte(0)).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_invalid_utf8_string_ref() {
+        let value = TarsValue::StringRef(Bytes::from_static(&[0xff, 0xfe]));
+        assert!(matches!(value.get_str(), Err(TarsError::InvalidUtf8(_))));
+    }
+
+    #[test]
+    fn test_collections() {
+        let list = TarsValue::List(smallvec![
+            Box::new(TarsValue::Byte(1)),
+            Box::new(TarsValue::Int(100_000)),
+        ]);
+        assert_eq!(
+            Vec::<i64>::from_tars_value(&list).unwrap(),
+            vec![1, 100_000]
+        );
+        assert!(Vec::<i16>::from_tars_value(&list).is_err());
+
+        let mut map = FxHashMap::default();
+        map.insert(TarsValue::String("a".into()), TarsValue::Short(7));
+        let map = TarsValue::Map(map);
+        let decoded = FxHashMap::<String, i32>::from_tars_value(&map).unwrap();
+        assert_eq!(decoded["a"], 7);
+    }
+
+    #[test]
+    fn test_struct_reader_roundtrip() {
+        let mut inner = FxHashMap::default();
+        inner.insert(0, TarsValue::String("inner".into()));
+
+        let mut fields = FxHashMap::default();
+        fields.insert(0, TarsValue::Long(42));
+        fields.insert(1, TarsValue::String("nick".into()));
+        fields.insert(3, TarsValue::Struct(inner));
+
+        let mut ser = TarsSerializer::new();
+        ser.write_struct_fields(&fields).unwrap();
+        let mut de = TarsDeserializer::new(ser.into_bytes());
+        let decoded = TarsValue::Struct(de.read_struct_naked().unwrap());
+
+        let reader = TarsStructReader::from_value(&decoded).unwrap();
+        // 42 fits in Int1 so it comes back as a Byte
+        assert_eq!(reader.get(0), Some(&TarsValue::Byte(42)));
+        assert_eq!(reader.read_required::<i64>(0).unwrap(), 42);
+        assert_eq!(reader.read_required::<String>(1).unwrap(), "nick");
+        assert_eq!(reader.read_optional(2, -1i32).unwrap(), -1);
+        assert_eq!(
+            reader
+                .read_struct(3)
+                .unwrap()
+                .read_required::<String>(0)
+                .unwrap(),
+            "inner"
+        );
+
+        assert!(matches!(
+            reader.read_required::<i64>(2),
+            Err(TarsError::MissingRequiredField(2))
+        ));
+        // Present but mistyped optional fields are not silently defaulted
+        assert!(matches!(
+            reader.read_optional(1, 0i64),
+            Err(TarsError::TypeMismatch { .. })
+        ));
+        assert!(TarsStructReader::from_value(&TarsValue::Int(1)).is_err());
+    }
+}
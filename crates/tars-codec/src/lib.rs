@@ -1,16 +1,28 @@
+#[cfg(feature = "client")]
+pub mod client;
 pub mod codec;
 pub mod de;
 pub mod error;
+pub mod extract;
 pub mod pool;
 pub mod ser;
 pub mod simd;
+pub mod stream;
 pub mod types;
 
+#[cfg(feature = "client")]
+pub use crate::client::TarsClient;
 pub use crate::{
-    codec::TarsCodec,
+    codec::{DEFAULT_MAX_MESSAGE_SIZE, TarsCodec},
+    de::StringPolicy,
     error::TarsError,
-    pool::{PooledByteBuffer, PooledDeserializer, PooledSerializer, TarsCodecPool},
+    extract::{FromTarsValue, TarsStructReader},
+    pool::{
+        PoolConfig, PoolStats, PooledByteBuffer, PooledDeserializer, PooledSerializer,
+        ShrinkPolicy, TarsCodecPool,
+    },
     simd::{bulk_ops, utf8_simd},
+    stream::TarsStreamDecoder,
     types::{TarsMessage, TarsRequestHeader, TarsValue, ValidatedBytes, next_request_id},
 };
 use bytes::{Bytes, BytesMut};
@@ -78,33 +90,53 @@ pub fn decode_response(src: &mut BytesMut) -> Result<Option<TarsMessage>, TarsEr
     codec.decode(src)
 }
 
-/// High-performance TARS response decoding from owned bytes
-pub fn decode_response_from_bytes(bytes: Bytes) -> Result<TarsMessage, TarsError> {
-    if bytes.len() < 4 {
-        return Err(TarsError::Unknown);
+/// Splits the first framed message off `bytes`, returning its payload and total length.
+fn split_frame(bytes: &Bytes) -> Result<(Bytes, usize), TarsError> {
+    let len = codec::read_frame_len(bytes, usize::MAX)?.ok_or(TarsError::Truncated {
+        expected: 4,
+        actual: bytes.len(),
+    })?;
+    if bytes.len() < len {
+        return Err(TarsError::Truncated {
+            expected: len,
+            actual: bytes.len(),
+        });
     }
 
-    let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
-    if bytes.len() != len {
-        return Err(TarsError::Unknown);
-    }
+    Ok((bytes.slice(4..len), len))
+}
 
-    let mut de = de::TarsDeserializer::new(bytes.slice(4..));
-    de.read_message()
+/// High-performance TARS response decoding from owned bytes
+///
+/// Only the first message is decoded; any bytes after it are ignored.
+pub fn decode_response_from_bytes(bytes: Bytes) -> Result<TarsMessage, TarsError> {
+    decode_response_from_bytes_partial(bytes).map(|(message, _)| message)
+}
+
+/// Decodes the first message in `bytes`, also returning the number of bytes it consumed
+///
+/// Use this when a buffer may hold several back-to-back responses.
+pub fn decode_response_from_bytes_partial(bytes: Bytes) -> Result<(TarsMessage, usize), TarsError> {
+    let (payload, len) = split_frame(&bytes)?;
+    let mut de = de::TarsDeserializer::new(payload);
+    Ok((de.read_message()?, len))
 }
 
 /// Zero-copy TARS response decoding - avoids string allocations where possible
+///
+/// Only the first message is decoded; any bytes after it are ignored.
 pub fn decode_response_zero_copy(bytes: Bytes) -> Result<TarsMessage, TarsError> {
-    if bytes.len() < 4 {
-        return Err(TarsError::Unknown);
-    }
-
-    let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
-    if bytes.len() != len {
-        return Err(TarsError::Unknown);
-    }
+    decode_response_zero_copy_with_policy(bytes, StringPolicy::Strict)
+}
 
-    let mut de = de::TarsDeserializer::new_zero_copy(bytes.slice(4..));
+/// Zero-copy TARS response decoding with a configurable [`StringPolicy`]
+pub fn decode_response_zero_copy_with_policy(
+    bytes: Bytes,
+    policy: StringPolicy,
+) -> Result<TarsMessage, TarsError> {
+    let (payload, _) = split_frame(&bytes)?;
+    let mut de = de::TarsDeserializer::new_zero_copy(payload);
+    de.string_policy = policy;
     de.read_message()
 }
 
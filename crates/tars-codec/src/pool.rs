@@ -1,30 +1,129 @@
-use crate::{TarsError, TarsMessage, de::TarsDeserializer, ser::TarsSerializer};
+use crate::{
+    TarsError, TarsMessage,
+    de::{StringPolicy, TarsDeserializer},
+    ser::TarsSerializer,
+};
 use bytes::{Bytes, BytesMut};
-use std::sync::Mutex;
+use std::sync::{
+    Mutex,
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+/// What happens to a buffer that grew past [`PoolConfig::max_buffer_capacity`]
+/// when it is returned to the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShrinkPolicy {
+    /// Drop the object and give its memory back to the allocator
+    #[default]
+    Discard,
+    /// Keep the object but replace its buffer with a fresh one of
+    /// [`PoolConfig::initial_buffer_capacity`]
+    Shrink,
+}
+
+/// Sizing limits for a [`TarsCodecPool`]
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Maximum number of idle objects kept per kind
+    pub max_pooled_objects: usize,
+    /// Buffers with a larger capacity are not kept as-is, see [`ShrinkPolicy`]
+    pub max_buffer_capacity: usize,
+    /// Capacity of newly created byte buffers
+    pub initial_buffer_capacity: usize,
+    /// How oversized buffers are handled
+    pub shrink_policy: ShrinkPolicy,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_pooled_objects: 16,
+            max_buffer_capacity: 256 * 1024,
+            initial_buffer_capacity: 1024,
+            shrink_policy: ShrinkPolicy::Discard,
+        }
+    }
+}
+
+/// Snapshot of [`TarsCodecPool`] usage, see [`TarsCodecPool::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Requests served from an idle pooled object
+    pub hits: u64,
+    /// Requests that had to create a new object
+    pub misses: u64,
+    /// Oversized buffers dropped or shrunk on return
+    pub oversized_returns: u64,
+    /// Buffer capacity currently held by idle pooled objects
+    pub pooled_bytes: usize,
+    /// Highest value `pooled_bytes` has reached
+    pub high_water_bytes: usize,
+}
+
+#[derive(Default)]
+struct PoolCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    oversized_returns: AtomicU64,
+    pooled_bytes: AtomicUsize,
+    high_water_bytes: AtomicUsize,
+}
+
+impl PoolCounters {
+    fn record_get(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn add_pooled(&self, bytes: usize) {
+        let total = self.pooled_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.high_water_bytes.fetch_max(total, Ordering::Relaxed);
+    }
+
+    fn remove_pooled(&self, bytes: usize) {
+        self.pooled_bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
 
 /// A thread-safe object pool for reusing TARS codec components
 /// Reduces allocation overhead in high-throughput scenarios
+///
+/// Buffers that grew past [`PoolConfig::max_buffer_capacity`] while in use are
+/// not kept at that size, so one huge message cannot pin memory for the
+/// lifetime of the pool.
 pub struct TarsCodecPool {
     serializers: Mutex<Vec<TarsSerializer>>,
     deserializers: Mutex<Vec<TarsDeserializer>>,
     #[allow(dead_code)]
     message_buffers: Mutex<Vec<TarsMessage>>,
     byte_buffers: Mutex<Vec<BytesMut>>,
+    config: PoolConfig,
+    counters: PoolCounters,
 }
 
 impl TarsCodecPool {
     /// Create a new codec pool with initial capacity
     pub fn new(initial_capacity: usize) -> Self {
+        Self::with_config(initial_capacity, PoolConfig::default())
+    }
+
+    /// Create a new codec pool with initial capacity and custom limits
+    pub fn with_config(initial_capacity: usize, config: PoolConfig) -> Self {
         let mut serializers = Vec::with_capacity(initial_capacity);
         let deserializers = Vec::with_capacity(initial_capacity);
         let mut message_buffers = Vec::with_capacity(initial_capacity);
         let mut byte_buffers = Vec::with_capacity(initial_capacity);
+        let counters = PoolCounters::default();
 
         // Pre-populate with reusable objects
         for _ in 0..initial_capacity {
-            serializers.push(TarsSerializer::new());
+            let serializer = TarsSerializer::new();
+            counters.add_pooled(serializer.buffer().capacity());
+            serializers.push(serializer);
             message_buffers.push(Self::create_empty_message());
-            byte_buffers.push(BytesMut::with_capacity(1024)); // Default buffer size
+            let buffer = BytesMut::with_capacity(config.initial_buffer_capacity);
+            counters.add_pooled(buffer.capacity());
+            byte_buffers.push(buffer);
         }
 
         Self {
@@ -32,12 +131,39 @@ impl TarsCodecPool {
             deserializers: Mutex::new(deserializers),
             message_buffers: Mutex::new(message_buffers),
             byte_buffers: Mutex::new(byte_buffers),
+            config,
+            counters,
+        }
+    }
+
+    /// The limits this pool was created with
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Current usage statistics
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            oversized_returns: self.counters.oversized_returns.load(Ordering::Relaxed),
+            pooled_bytes: self.counters.pooled_bytes.load(Ordering::Relaxed),
+            high_water_bytes: self.counters.high_water_bytes.load(Ordering::Relaxed),
         }
     }
 
     /// Get a pooled serializer, or create a new one if pool is empty
     pub fn get_serializer(&self) -> PooledSerializer<'_> {
-        let serializer = self.serializers.lock().unwrap().pop().unwrap_or_default();
+        let pooled = self.serializers.lock().unwrap().pop();
+        self.counters.record_get(pooled.is_some());
+        let serializer = match pooled {
+            Some(serializer) => {
+                self.counters.remove_pooled(serializer.buffer().capacity());
+                serializer
+            }
+            None => TarsSerializer::new(),
+        };
+
         PooledSerializer {
             inner: Some(serializer),
             pool: self,
@@ -46,16 +172,23 @@ impl TarsCodecPool {
 
     /// Get a pooled deserializer for the given bytes
     pub fn get_deserializer(&self, bytes: Bytes) -> PooledDeserializer<'_> {
-        let deserializer = self
-            .deserializers
-            .lock()
-            .unwrap()
-            .pop()
-            .unwrap_or_else(|| TarsDeserializer::new(bytes.clone()));
+        self.get_deserializer_with_policy(bytes, StringPolicy::Strict)
+    }
+
+    /// Get a pooled deserializer that handles invalid strings according to `policy`
+    pub fn get_deserializer_with_policy(
+        &self,
+        bytes: Bytes,
+        policy: StringPolicy,
+    ) -> PooledDeserializer<'_> {
+        let pooled = self.deserializers.lock().unwrap().pop();
+        self.counters.record_get(pooled.is_some());
+        let deserializer = pooled.unwrap_or_else(|| TarsDeserializer::new(bytes.clone()));
 
         // Reset the deserializer with new bytes
         let mut de = deserializer;
         de.reset(bytes);
+        de.string_policy = policy;
 
         PooledDeserializer {
             inner: Some(de),
@@ -65,12 +198,15 @@ impl TarsCodecPool {
 
     /// Get a pooled byte buffer for encoding
     pub fn get_byte_buffer(&self, estimated_size: usize) -> PooledByteBuffer<'_> {
-        let mut buffer = self
-            .byte_buffers
-            .lock()
-            .unwrap()
-            .pop()
-            .unwrap_or_else(|| BytesMut::with_capacity(estimated_size));
+        let pooled = self.byte_buffers.lock().unwrap().pop();
+        self.counters.record_get(pooled.is_some());
+        let mut buffer = match pooled {
+            Some(buffer) => {
+                self.counters.remove_pooled(buffer.capacity());
+                buffer
+            }
+            None => BytesMut::with_capacity(estimated_size),
+        };
 
         // Ensure buffer has sufficient capacity
         if buffer.capacity() < estimated_size {
@@ -84,20 +220,48 @@ impl TarsCodecPool {
         }
     }
 
+    /// Apply the [`ShrinkPolicy`] to a buffer being returned
+    ///
+    /// Returns `false` if the owning object should be dropped instead of pooled.
+    fn admit_buffer(&self, buffer: &mut BytesMut) -> bool {
+        if buffer.capacity() <= self.config.max_buffer_capacity {
+            return true;
+        }
+
+        self.counters
+            .oversized_returns
+            .fetch_add(1, Ordering::Relaxed);
+        match self.config.shrink_policy {
+            ShrinkPolicy::Discard => false,
+            ShrinkPolicy::Shrink => {
+                *buffer = BytesMut::with_capacity(self.config.initial_buffer_capacity);
+                true
+            }
+        }
+    }
+
     /// Return a serializer to the pool
     fn return_serializer(&self, mut serializer: TarsSerializer) {
         serializer.reset(); // Clear internal state
+        if !self.admit_buffer(serializer.buffer_mut()) {
+            return;
+        }
+
         let mut pool = self.serializers.lock().unwrap();
-        if pool.len() < 16 {
+        if pool.len() < self.config.max_pooled_objects {
             // Limit pool size to prevent unbounded growth
+            self.counters.add_pooled(serializer.buffer().capacity());
             pool.push(serializer);
         }
     }
 
     /// Return a deserializer to the pool  
-    fn return_deserializer(&self, deserializer: TarsDeserializer) {
+    fn return_deserializer(&self, mut deserializer: TarsDeserializer) {
+        // Release the input so an idle deserializer does not keep it alive
+        deserializer.reset(Bytes::new());
+
         let mut pool = self.deserializers.lock().unwrap();
-        if pool.len() < 16 {
+        if pool.len() < self.config.max_pooled_objects {
             // Limit pool size
             pool.push(deserializer);
         }
@@ -106,9 +270,14 @@ impl TarsCodecPool {
     /// Return a byte buffer to the pool
     fn return_byte_buffer(&self, mut buffer: BytesMut) {
         buffer.clear(); // Reset content but keep capacity
+        if !self.admit_buffer(&mut buffer) {
+            return;
+        }
+
         let mut pool = self.byte_buffers.lock().unwrap();
-        if pool.len() < 16 && buffer.capacity() <= 16384 {
-            // Limit pool size and buffer size
+        if pool.len() < self.config.max_pooled_objects {
+            // Limit pool size
+            self.counters.add_pooled(buffer.capacity());
             pool.push(buffer);
         }
     }
@@ -207,6 +376,11 @@ pub struct PooledByteBuffer<'a> {
 }
 
 impl<'a> PooledByteBuffer<'a> {
+    /// Get a buffer from `pool` sized by [`estimate_message_size`](crate::estimate_message_size)
+    pub fn with_capacity_hint(pool: &'a TarsCodecPool, message: &TarsMessage) -> Self {
+        pool.get_byte_buffer(crate::estimate_message_size(message))
+    }
+
     /// Get mutable access to the buffer
     pub fn buffer(&mut self) -> &mut BytesMut {
         self.inner.as_mut().unwrap()
@@ -230,8 +404,7 @@ impl<'a> Drop for PooledByteBuffer<'a> {
 impl TarsCodecPool {
     /// Encode a message using a pooled serializer and buffer (most efficient)
     pub fn encode_pooled(&self, message: &TarsMessage) -> Result<BytesMut, TarsError> {
-        let estimated_size = crate::estimate_message_size(message);
-        let mut buffer = self.get_byte_buffer(estimated_size);
+        let mut buffer = PooledByteBuffer::with_capacity_hint(self, message);
         let mut serializer = self.get_serializer();
 
         // Use the pooled objects to encode directly to buffer (no intermediate cloning)
@@ -251,4 +424,116 @@ impl TarsCodecPool {
         let mut deserializer = self.get_deserializer(bytes);
         deserializer.decode_message()
     }
+
+    /// Decode a message using a pooled deserializer and the given [`StringPolicy`]
+    pub fn decode_pooled_with_policy(
+        &self,
+        bytes: Bytes,
+        policy: StringPolicy,
+    ) -> Result<TarsMessage, TarsError> {
+        let mut deserializer = self.get_deserializer_with_policy(bytes, policy);
+        deserializer.decode_message()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEN_MB: usize = 10 * 1024 * 1024;
+
+    fn huge_message() -> TarsMessage {
+        let mut message = TarsCodecPool::create_empty_message();
+        message
+            .body
+            .insert("tReq".to_string(), Bytes::from(vec![0x5a; TEN_MB]));
+        message
+    }
+
+    fn push_huge_message(pool: &TarsCodecPool) {
+        let message = huge_message();
+
+        // Grows the serializer's internal buffer
+        let encoded = pool
+            .get_serializer()
+            .encode_message(&message)
+            .unwrap()
+            .clone()
+            .freeze();
+        // Grows a pooled byte buffer, which is returned on drop
+        pool.get_byte_buffer(encoded.len())
+            .buffer()
+            .extend_from_slice(&encoded);
+        // The deserializer must not keep the payload alive
+        pool.decode_pooled(encoded.slice(4..)).unwrap();
+    }
+
+    #[test]
+    fn test_discard_keeps_pool_under_threshold() {
+        let pool = TarsCodecPool::new(2);
+        let threshold = pool.config().max_buffer_capacity;
+
+        push_huge_message(&pool);
+
+        let stats = pool.stats();
+        assert!(stats.pooled_bytes < threshold, "{stats:?}");
+        assert!(stats.high_water_bytes < threshold, "{stats:?}");
+        assert_eq!(stats.oversized_returns, 2);
+    }
+
+    #[test]
+    fn test_shrink_keeps_objects_but_trims_buffers() {
+        let config = PoolConfig {
+            shrink_policy: ShrinkPolicy::Shrink,
+            ..PoolConfig::default()
+        };
+        let pool = TarsCodecPool::with_config(1, config);
+
+        push_huge_message(&pool);
+
+        let stats = pool.stats();
+        assert!(
+            stats.pooled_bytes < pool.config().max_buffer_capacity,
+            "{stats:?}"
+        );
+        assert_eq!(stats.oversized_returns, 2);
+        assert_eq!(pool.serializers.lock().unwrap().len(), 1);
+        assert_eq!(pool.byte_buffers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_hits_and_misses() {
+        let pool = TarsCodecPool::new(1);
+
+        let first = pool.get_byte_buffer(16);
+        let second = pool.get_byte_buffer(16);
+        drop((first, second));
+        let _third = pool.get_byte_buffer(16);
+
+        let stats = pool.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 1);
+        // One of the two returned buffers is still idle
+        assert_eq!(
+            stats.pooled_bytes,
+            pool.serializers.lock().unwrap()[0].buffer().capacity() + 1024
+        );
+    }
+
+    #[test]
+    fn test_with_capacity_hint() {
+        let pool = TarsCodecPool::new(0);
+        let mut message = TarsCodecPool::create_empty_message();
+        message
+            .body
+            .insert("tReq".to_string(), Bytes::from(vec![0; 4096]));
+
+        let mut buffer = PooledByteBuffer::with_capacity_hint(&pool, &message);
+        assert!(buffer.buffer().capacity() >= crate::estimate_message_size(&message));
+        drop(buffer);
+
+        let encoded = pool.encode_pooled(&message).unwrap().freeze();
+        let decoded = pool.decode_pooled(encoded.slice(4..)).unwrap();
+        assert_eq!(decoded.body["tReq"].len(), 4096);
+    }
 }
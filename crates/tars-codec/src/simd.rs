@@ -5,23 +5,41 @@ use bytes::Bytes;
 pub mod utf8_simd {
     use super::*;
 
-    /// Fast UTF-8 validation using SIMD when available
+    /// Whether the SIMD validator can be used on the running CPU
+    ///
+    /// Detected at runtime, so a binary built for a generic target still picks
+    /// the scalar path on CPUs without the required features.
     #[inline]
-    pub fn validate_utf8(bytes: &[u8]) -> Result<(), std::str::Utf8Error> {
+    pub fn simd_available() -> bool {
         #[cfg(target_arch = "x86_64")]
         {
-            if is_x86_feature_detected!("sse2") {
-                unsafe { validate_utf8_sse2(bytes) }
-            } else {
-                std::str::from_utf8(bytes).map(|_| ())
-            }
+            is_x86_feature_detected!("sse2")
         }
         #[cfg(not(target_arch = "x86_64"))]
         {
-            std::str::from_utf8(bytes).map(|_| ())
+            false
         }
     }
 
+    /// Fast UTF-8 validation using SIMD when available
+    #[inline]
+    pub fn validate_utf8(bytes: &[u8]) -> Result<(), std::str::Utf8Error> {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if simd_available() {
+                // SAFETY: sse2 support was checked at runtime above
+                return unsafe { validate_utf8_sse2(bytes) };
+            }
+        }
+        validate_utf8_scalar(bytes)
+    }
+
+    /// Portable UTF-8 validation used when SIMD is unavailable
+    #[inline]
+    pub fn validate_utf8_scalar(bytes: &[u8]) -> Result<(), std::str::Utf8Error> {
+        std::str::from_utf8(bytes).map(|_| ())
+    }
+
     /// Validate and create a ValidatedBytes with potential SIMD acceleration
     #[inline]
     pub fn validated_bytes_fast(
@@ -33,6 +51,7 @@ pub mod utf8_simd {
     }
 
     #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
     unsafe fn validate_utf8_sse2(bytes: &[u8]) -> Result<(), std::str::Utf8Error> {
         use std::arch::x86_64::*;
 
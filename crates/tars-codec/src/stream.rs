@@ -0,0 +1,243 @@
+use bytes::BytesMut;
+
+use crate::{
+    codec::{DEFAULT_MAX_MESSAGE_SIZE, read_frame_len},
+    de::TarsDeserializer,
+    error::TarsError,
+    types::TarsMessage,
+};
+
+/// Incremental decoder for length-prefixed TARS messages
+///
+/// Bytes are appended with [`feed`](Self::feed) as they arrive from the transport and
+/// complete messages are taken out with [`next_message`](Self::next_message). A chunk may
+/// end in the middle of a length prefix or contain several back-to-back messages.
+///
+/// The length prefix is checked against the configured maximum before any space is
+/// reserved, so a bogus prefix cannot make the decoder allocate gigabytes. Once
+/// [`next_message`](Self::next_message) has returned an error the stream is out of sync
+/// and the decoder should be discarded.
+pub struct TarsStreamDecoder {
+    buffer: BytesMut,
+    max_message_size: usize,
+    zero_copy_strings: bool,
+}
+
+impl Default for TarsStreamDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TarsStreamDecoder {
+    /// Create a decoder limited to [`DEFAULT_MAX_MESSAGE_SIZE`]
+    pub fn new() -> Self {
+        Self::with_max_message_size(DEFAULT_MAX_MESSAGE_SIZE)
+    }
+
+    /// Create a decoder that rejects messages larger than `max_message_size` bytes,
+    /// including the 4-byte length prefix
+    pub fn with_max_message_size(max_message_size: usize) -> Self {
+        Self {
+            buffer: BytesMut::new(),
+            max_message_size,
+            zero_copy_strings: false,
+        }
+    }
+
+    /// Decode strings as [`TarsValue::StringRef`](crate::TarsValue::StringRef) instead of `String`
+    pub fn zero_copy_strings(mut self, enabled: bool) -> Self {
+        self.zero_copy_strings = enabled;
+        self
+    }
+
+    /// Maximum accepted message size in bytes
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Number of bytes buffered but not yet returned as a message
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Append bytes received from the transport
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Take the next complete message out of the buffer
+    ///
+    /// Returns `Ok(None)` when more bytes are needed.
+    pub fn next_message(&mut self) -> Result<Option<TarsMessage>, TarsError> {
+        let Some(len) = read_frame_len(&self.buffer, self.max_message_size)? else {
+            return Ok(None);
+        };
+
+        if self.buffer.len() < len {
+            // The prefix is trusted at this point, so reserve the rest of the frame up front
+            self.buffer.reserve(len - self.buffer.len());
+            return Ok(None);
+        }
+
+        let frame = self.buffer.split_to(len).freeze();
+        let payload = frame.slice(4..);
+        let mut de = if self.zero_copy_strings {
+            TarsDeserializer::new_zero_copy(payload)
+        } else {
+            TarsDeserializer::new(payload)
+        };
+
+        de.read_message().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rustc_hash::FxHashMap;
+
+    use super::*;
+    use crate::{encode_request, types::TarsRequestHeader};
+
+    fn message(request_id: i32) -> TarsMessage {
+        let mut body = FxHashMap::default();
+        body.insert("tReq".to_string(), Bytes::from(vec![request_id as u8; 8]));
+
+        TarsMessage {
+            header: TarsRequestHeader {
+                version: 3,
+                packet_type: 0,
+                message_type: 0,
+                request_id,
+                servant_name: "test".to_string(),
+                func_name: "echo".to_string(),
+                timeout: 0,
+                context: FxHashMap::default(),
+                status: FxHashMap::default(),
+            },
+            body,
+        }
+    }
+
+    fn encoded(request_ids: &[i32]) -> Vec<u8> {
+        request_ids
+            .iter()
+            .flat_map(|&id| encode_request(&message(id)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_three_messages_in_one_buffer() {
+        let mut decoder = TarsStreamDecoder::new();
+        decoder.feed(&encoded(&[1, 2, 3]));
+
+        for id in 1..=3 {
+            let message = decoder.next_message().unwrap().unwrap();
+            assert_eq!(message.header.request_id, id);
+            assert_eq!(message.header.func_name, "echo");
+            assert_eq!(message.body["tReq"].as_ref(), &[id as u8; 8]);
+        }
+
+        assert!(decoder.next_message().unwrap().is_none());
+        assert_eq!(decoder.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_byte_at_a_time() {
+        let data = encoded(&[7, 8]);
+        let mut decoder = TarsStreamDecoder::new();
+        let mut ids = Vec::new();
+
+        for byte in &data {
+            decoder.feed(std::slice::from_ref(byte));
+            while let Some(message) = decoder.next_message().unwrap() {
+                ids.push(message.header.request_id);
+            }
+        }
+
+        assert_eq!(ids, vec![7, 8]);
+    }
+
+    #[test]
+    fn test_partial_length_prefix() {
+        let data = encoded(&[5]);
+        let mut decoder = TarsStreamDecoder::new();
+
+        decoder.feed(&data[..2]);
+        assert!(decoder.next_message().unwrap().is_none());
+        decoder.feed(&data[2..6]);
+        assert!(decoder.next_message().unwrap().is_none());
+        decoder.feed(&data[6..]);
+        assert_eq!(
+            decoder.next_message().unwrap().unwrap().header.request_id,
+            5
+        );
+    }
+
+    #[test]
+    fn test_rejects_2gb_length_prefix() {
+        let mut decoder = TarsStreamDecoder::new();
+        decoder.feed(&0x8000_0000u32.to_be_bytes());
+        decoder.feed(&[0; 16]);
+
+        match decoder.next_message() {
+            Err(TarsError::MessageTooLarge { len, max }) => {
+                assert_eq!(len, 0x8000_0000);
+                assert_eq!(max, DEFAULT_MAX_MESSAGE_SIZE);
+            }
+            other => panic!("expected MessageTooLarge, got {other:?}"),
+        }
+        // Nothing was reserved for the claimed length
+        assert!(decoder.buffer.capacity() < DEFAULT_MAX_MESSAGE_SIZE);
+    }
+
+    #[test]
+    fn test_custom_max_message_size() {
+        let data = encoded(&[1]);
+        let mut decoder = TarsStreamDecoder::with_max_message_size(data.len() - 1);
+        decoder.feed(&data);
+
+        assert!(matches!(
+            decoder.next_message(),
+            Err(TarsError::MessageTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_length_below_prefix() {
+        let mut decoder = TarsStreamDecoder::new();
+        decoder.feed(&[0, 0, 0, 2, 0xff, 0xff]);
+
+        assert!(matches!(
+            decoder.next_message(),
+            Err(TarsError::InvalidLength(2))
+        ));
+    }
+
+    #[test]
+    fn test_decode_response_from_bytes_trailing_data() {
+        let data = Bytes::from(encoded(&[1, 2]));
+        let first_len = encode_request(&message(1)).unwrap().len();
+
+        let (message, consumed) = crate::decode_response_from_bytes_partial(data.clone()).unwrap();
+        assert_eq!(message.header.request_id, 1);
+        assert_eq!(consumed, first_len);
+
+        let (message, _) =
+            crate::decode_response_from_bytes_partial(data.slice(consumed..)).unwrap();
+        assert_eq!(message.header.request_id, 2);
+
+        assert_eq!(
+            crate::decode_response_from_bytes(data.clone())
+                .unwrap()
+                .header
+                .request_id,
+            1
+        );
+        assert!(matches!(
+            crate::decode_response_from_bytes(data.slice(..first_len - 1)),
+            Err(TarsError::Truncated { .. })
+        ));
+    }
+}
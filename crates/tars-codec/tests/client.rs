@@ -0,0 +1,140 @@
+#![cfg(feature = "client")]
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use rustc_hash::FxHashMap;
+use tars_codec::{TarsClient, TarsCodec, TarsError, TarsMessage, TarsRequestHeader};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_util::codec::Framed;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn listen() -> (TcpListener, String) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    (listener, addr)
+}
+
+fn body(payload: &[u8]) -> FxHashMap<String, Bytes> {
+    let mut body = FxHashMap::default();
+    body.insert("tReq".to_string(), Bytes::copy_from_slice(payload));
+    body
+}
+
+/// Echo the request back as a response, keyed by "tRsp"
+fn respond(mut request: TarsMessage) -> TarsMessage {
+    let payload = request.body.remove("tReq").unwrap_or_default();
+    request.body.insert("tRsp".to_string(), payload);
+    request
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_answered_out_of_order() {
+    let (listener, addr) = listen().await;
+
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(socket, TarsCodec);
+
+        let mut requests = Vec::new();
+        while requests.len() < 3 {
+            requests.push(framed.next().await.unwrap().unwrap());
+        }
+
+        // A push the client never asked for, then the responses in reverse order
+        let mut push_body = FxHashMap::default();
+        push_body.insert("push".to_string(), Bytes::from_static(b"hi"));
+        let push = TarsMessage {
+            header: TarsRequestHeader {
+                request_id: 0,
+                ..requests[0].header.clone()
+            },
+            body: push_body,
+        };
+        framed.send(push).await.unwrap();
+
+        for request in requests.into_iter().rev() {
+            framed.send(respond(request)).await.unwrap();
+        }
+    });
+
+    let (push_tx, mut push_rx) = mpsc::unbounded_channel();
+    let client = TarsClient::new(TcpStream::connect(&addr).await.unwrap(), Some(push_tx));
+
+    let (a, b, c) = tokio::join!(
+        client.invoke("test", "echo", body(b"a"), TIMEOUT),
+        client.invoke("test", "echo", body(b"b"), TIMEOUT),
+        client.invoke("test", "echo", body(b"c"), TIMEOUT),
+    );
+    for (response, expected) in [(a, b"a"), (b, b"b"), (c, b"c")] {
+        let response = response.unwrap();
+        assert_eq!(response.header.func_name, "echo");
+        assert_eq!(response.body["tRsp"].as_ref(), expected);
+    }
+
+    let push = push_rx.recv().await.unwrap();
+    assert_eq!(push.header.request_id, 0);
+    assert_eq!(push.body["push"].as_ref(), b"hi");
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_peer_disconnect_fails_pending_requests() {
+    let (listener, addr) = listen().await;
+
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(socket, TarsCodec);
+        // Read the request and hang up without answering
+        framed.next().await.unwrap().unwrap();
+    });
+
+    let client = TarsClient::connect(&addr).await.unwrap();
+    let result = client.invoke("test", "echo", body(b"x"), TIMEOUT).await;
+    assert!(matches!(result, Err(TarsError::ConnectionClosed)));
+    assert!(client.is_closed());
+
+    let result = client.invoke("test", "echo", body(b"y"), TIMEOUT).await;
+    assert!(matches!(result, Err(TarsError::ConnectionClosed)));
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_timeout_without_response() {
+    let (listener, addr) = listen().await;
+
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut framed = Framed::new(socket, TarsCodec);
+        let request = framed.next().await.unwrap().unwrap();
+        // Answer only the second request
+        let second = framed.next().await.unwrap().unwrap();
+        framed.send(respond(second)).await.unwrap();
+        drop(request);
+        // Keep the connection open until the client is done
+        let _ = framed.next().await;
+    });
+
+    let client = TarsClient::connect(&addr).await.unwrap();
+    let result = client
+        .invoke("test", "echo", body(b"slow"), Duration::from_millis(100))
+        .await;
+    assert!(matches!(result, Err(TarsError::Timeout)));
+
+    let response = client
+        .invoke("test", "echo", body(b"fast"), TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(response.body["tRsp"].as_ref(), b"fast");
+    assert!(!client.is_closed());
+
+    drop(client);
+    server.await.unwrap();
+}
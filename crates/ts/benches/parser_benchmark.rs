@@ -2,7 +2,7 @@ use std::hint::black_box;
 
 use bytes::Bytes;
 use criterion::{Criterion, criterion_group, criterion_main};
-use ts::TsParser;
+use ts::{Pat, PatProgram, Pmt, PmtStream, StreamType, TsParser, TsWriter};
 
 fn benchmark_parsers(c: &mut Criterion) {
     let mut group = c.benchmark_group("Parser Performance");
@@ -11,6 +11,7 @@ fn benchmark_parsers(c: &mut Criterion) {
     let mixed_format_data = Bytes::from(create_mixed_packet_size_data());
     let noisy_resync_data = Bytes::from(create_noisy_resync_data());
     let continuity_heavy_data = Bytes::from(create_continuity_heavy_data());
+    let mpts_data = Bytes::from(create_mpts_data(20));
 
     let mut parser = TsParser::new();
     group.bench_function("Zero-Copy Parser (Base)", |b| {
@@ -68,6 +69,41 @@ fn benchmark_parsers(c: &mut Criterion) {
         })
     });
 
+    // Payload work done for every packet reaching the caller
+    let inspect_packet = |packet: &ts::TsPacketRef| -> ts::Result<()> {
+        black_box(packet.payload().map(|payload| payload.len()));
+        Ok(())
+    };
+
+    let mut parser = TsParser::new().with_continuity_mode(ts::ContinuityMode::Warn);
+    group.bench_function("Zero-Copy Parser (20-program MPTS)", |b| {
+        b.iter(|| {
+            parser
+                .parse_packets(
+                    black_box(mpts_data.clone()),
+                    |_| Ok(()),
+                    |_| Ok(()),
+                    Some(inspect_packet),
+                )
+                .unwrap();
+        })
+    });
+
+    let mut parser = TsParser::new().with_continuity_mode(ts::ContinuityMode::Warn);
+    parser.set_program_filter(&[7]);
+    group.bench_function("Zero-Copy Parser (20-program MPTS, one program)", |b| {
+        b.iter(|| {
+            parser
+                .parse_packets(
+                    black_box(mpts_data.clone()),
+                    |_| Ok(()),
+                    |_| Ok(()),
+                    Some(inspect_packet),
+                )
+                .unwrap();
+        })
+    });
+
     group.finish();
 }
 
@@ -186,6 +222,10 @@ fn create_complex_ts_data() -> Vec<u8> {
     pmt2_packet[29] = 0x00;
     pmt2_packet[30] = 0x00;
 
+    for packet in [&mut pat_packet, &mut pmt1_packet, &mut pmt2_packet] {
+        seal_section_crc(packet);
+    }
+
     ts_data.extend_from_slice(&pat_packet);
     ts_data.extend_from_slice(&pmt1_packet);
     ts_data.extend_from_slice(&pmt2_packet);
@@ -203,6 +243,14 @@ fn create_complex_ts_data() -> Vec<u8> {
     ts_data
 }
 
+/// Fill in the CRC_32 of the PSI section starting after the pointer field.
+fn seal_section_crc(packet: &mut [u8]) {
+    let section_length = (((packet[6] & 0x0F) as usize) << 8) | packet[7] as usize;
+    let crc_offset = 5 + 3 + section_length - 4;
+    let crc = ts::mpeg2_crc32(&packet[5..crc_offset]);
+    packet[crc_offset..crc_offset + 4].copy_from_slice(&crc.to_be_bytes());
+}
+
 fn wrap_m2ts_packet(ts_packet: &[u8]) -> Vec<u8> {
     let mut out = Vec::with_capacity(192);
     out.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
@@ -269,3 +317,65 @@ fn create_continuity_heavy_data() -> Vec<u8> {
 
     out
 }
+
+/// A multi-program stream: program `n` has its PMT on `0x1000 + n`, video on
+/// `0x100 + 2n` and audio on `0x101 + 2n`, with the tables repeated between
+/// bursts of PES packets of every program.
+fn create_mpts_data(programs: u16) -> Vec<u8> {
+    let mut writer = TsWriter::new();
+    let mut out = Vec::new();
+    let pat = Pat {
+        table_id: 0x00,
+        transport_stream_id: 1,
+        version_number: 0,
+        current_next_indicator: true,
+        section_number: 0,
+        last_section_number: 0,
+        programs: (1..=programs)
+            .map(|n| PatProgram {
+                program_number: n,
+                pmt_pid: 0x1000 + n,
+            })
+            .collect(),
+    };
+    let pmts: Vec<Pmt> = (1..=programs)
+        .map(|n| Pmt {
+            table_id: 0x02,
+            program_number: n,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100 + 2 * n,
+            program_info: Vec::new(),
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: 0x100 + 2 * n,
+                    es_info: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: 0x101 + 2 * n,
+                    es_info: Vec::new(),
+                },
+            ],
+        })
+        .collect();
+
+    let video = vec![0u8; 4000];
+    let audio = vec![0u8; 600];
+    for _ in 0..10 {
+        writer.write_pat(&pat, &mut out).unwrap();
+        for pmt in &pmts {
+            writer
+                .write_pmt(0x1000 + pmt.program_number, pmt, &mut out)
+                .unwrap();
+        }
+        for n in 1..=programs {
+            writer.write_pes(0x100 + 2 * n, &video, &mut out).unwrap();
+            writer.write_pes(0x101 + 2 * n, &audio, &mut out).unwrap();
+        }
+    }
+    out
+}
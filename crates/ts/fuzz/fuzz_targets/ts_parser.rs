@@ -0,0 +1,48 @@
+#![no_main]
+
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+use ts::{PesHeader, TsPacketRef, TsParser};
+
+fn on_packet(packet: &TsPacketRef) -> ts::Result<()> {
+    let _ = packet.parse_adaptation_field();
+    if packet.payload_unit_start_indicator
+        && let Some(payload) = packet.payload()
+    {
+        let _ = PesHeader::parse(&payload);
+    }
+    Ok(())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let data = Bytes::copy_from_slice(data);
+
+    // Fuzzed sections hardly ever carry a valid CRC, skip it to reach the table parsers
+    let mut parser = TsParser::new().with_crc_validation(false);
+    let _ = parser.parse_packets_with_scte35(
+        data.clone(),
+        |pat| {
+            pat.programs().for_each(drop);
+            Ok(())
+        },
+        |pmt| {
+            pmt.program_descriptors().for_each(drop);
+            for stream in pmt.streams().flatten() {
+                stream.descriptors().for_each(drop);
+            }
+            Ok(())
+        },
+        Some(on_packet),
+        |_| Ok(()),
+    );
+    let _ = parser.complete_tables();
+
+    let mut parser = TsParser::new().with_crc_validation(false);
+    let _ = parser.parse_packets_with_sdt(
+        data,
+        |_| Ok(()),
+        |_| Ok(()),
+        None::<fn(&TsPacketRef) -> ts::Result<()>>,
+        |_| Ok(()),
+    );
+});
@@ -1,5 +1,8 @@
 use bytes::Bytes;
 
+/// PCR values wrap around after 2^33 periods of the 90 kHz base, counted at 27 MHz
+pub const PCR_MODULO: u64 = (1 << 33) * 300;
+
 /// Program Clock Reference (PCR) — 33-bit base @ 90kHz + 9-bit extension @ 27MHz
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Pcr {
@@ -26,11 +29,26 @@ impl Pcr {
         Some(Pcr { base, extension })
     }
 
+    /// PCR of a 27 MHz clock value, wrapping around [`PCR_MODULO`].
+    pub fn from_27mhz(ticks: u64) -> Self {
+        let ticks = ticks % PCR_MODULO;
+        Pcr {
+            base: ticks / 300,
+            extension: (ticks % 300) as u16,
+        }
+    }
+
     /// Full PCR value at 27 MHz resolution.
     pub fn as_27mhz(&self) -> u64 {
         self.base * 300 + self.extension as u64
     }
 
+    /// Ticks of the 27 MHz clock from `earlier` to this PCR, across a wraparound of the
+    /// 33-bit base.
+    pub fn ticks_since(&self, earlier: Pcr) -> u64 {
+        (self.as_27mhz() % PCR_MODULO + PCR_MODULO - earlier.as_27mhz() % PCR_MODULO) % PCR_MODULO
+    }
+
     /// PCR as seconds (floating point).
     pub fn as_seconds(&self) -> f64 {
         self.as_27mhz() as f64 / 27_000_000.0
@@ -252,6 +270,16 @@ mod tests {
         assert!((seconds - 1.0).abs() < 1e-9);
     }
 
+    #[test]
+    fn test_pcr_ticks_since_wraparound() {
+        let before = Pcr::from_27mhz(PCR_MODULO - 300);
+        let after = Pcr::from_27mhz(PCR_MODULO + 150);
+        assert_eq!(after.base, 0);
+        assert_eq!(after.extension, 150);
+        assert_eq!(after.ticks_since(before), 450);
+        assert_eq!(before.ticks_since(after), PCR_MODULO - 450);
+    }
+
     #[test]
     fn test_adaptation_field_flags_only() {
         // Flags byte with only RAI set, no optional fields
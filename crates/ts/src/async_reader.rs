@@ -0,0 +1,413 @@
+//! Async front-end for the zero-copy [`TsParser`].
+//!
+//! [`AsyncTsReader`] pulls bytes from a tokio [`AsyncRead`] into an internal
+//! buffer and hands whole packets to the parser, so callers receiving a TS
+//! stream over the network do not have to chunk it themselves. Packets are
+//! located the same way [`TsParser::parse_packets`] locates them in a slice:
+//! reading a stream through the reader yields the packets the parser yields
+//! for the same bytes, however the reads split them.
+
+use bytes::{Buf, BytesMut};
+use memchr::memchr_iter;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{
+    Pat, Pmt, Result, TsError,
+    parser_zero_copy::{PacketFormat, TsPacketRef, TsParser},
+};
+
+/// Number of 188-byte packets read at once by default
+const DEFAULT_READ_PACKETS: usize = 64;
+
+/// Largest offset of the sync byte within a packet (M2TS timestamp prefix)
+const MAX_SYNC_OFFSET: usize = 4;
+
+/// Outcome of looking for the next packet boundary in the buffer
+enum Boundary {
+    /// A packet of this format starts at the beginning of the buffer
+    Packet(PacketFormat),
+    /// More data is needed to decide
+    NeedMore,
+}
+
+/// Reads TS packets from an [`AsyncRead`].
+///
+/// The reader resynchronizes on lost alignment and handles 188, 192 (M2TS)
+/// and 204-byte packets like [`TsParser`]. All parser settings, including
+/// program and PID filters, apply; configure them through
+/// [`parser_mut`](Self::parser_mut) or [`with_parser`](Self::with_parser).
+///
+/// # Cancellation safety
+///
+/// [`next_packet`](Self::next_packet) and [`read_tables`](Self::read_tables)
+/// only await reads into the buffer, which either complete or leave it
+/// untouched, so their futures may be dropped at any point. A packet is only
+/// taken from the buffer when it is returned; packets consumed by an
+/// interrupted `read_tables` still count towards the tables.
+pub struct AsyncTsReader<R> {
+    reader: R,
+    parser: TsParser,
+    /// Bytes read but not yet consumed, starting at a packet boundary once
+    /// the format is locked
+    buffer: BytesMut,
+    /// Number of bytes requested per read
+    read_size: usize,
+    /// Format of the last packet, checked first for the next one
+    locked_format: Option<PacketFormat>,
+    /// Whether the underlying reader reached its end
+    eof: bool,
+    /// Stream offset of the first buffered byte
+    stream_offset: u64,
+}
+
+impl<R: AsyncRead + Unpin> AsyncTsReader<R> {
+    /// Create a reader with a default parser
+    pub fn new(reader: R) -> Self {
+        Self::with_parser(reader, TsParser::new())
+    }
+
+    /// Create a reader feeding the given parser
+    pub fn with_parser(reader: R, parser: TsParser) -> Self {
+        let read_size = DEFAULT_READ_PACKETS * 188;
+        Self {
+            reader,
+            parser,
+            buffer: BytesMut::with_capacity(read_size),
+            read_size,
+            locked_format: None,
+            eof: false,
+            stream_offset: 0,
+        }
+    }
+
+    /// Read up to `packets` 188-byte packets at once instead of 64
+    pub fn with_read_packets(mut self, packets: usize) -> Self {
+        self.read_size = packets.max(1) * 188;
+        self
+    }
+
+    /// The parser fed by this reader
+    pub fn parser(&self) -> &TsParser {
+        &self.parser
+    }
+
+    /// The parser fed by this reader, e.g. to change its filters
+    pub fn parser_mut(&mut self) -> &mut TsParser {
+        &mut self.parser
+    }
+
+    /// Read the next packet passing the parser's filters, or `None` at the
+    /// end of the stream.
+    ///
+    /// The PAT and PMTs carried by the packets are tracked by the parser, and
+    /// a trailing partial packet is discarded.
+    pub async fn next_packet(&mut self) -> Result<Option<TsPacketRef>> {
+        loop {
+            while let Boundary::Packet(format) = self.next_boundary() {
+                let packet = self.buffer.split_to(format.packet_size()).freeze();
+                let sync_offset = format.sync_offset();
+                let chunk = packet.slice(sync_offset..sync_offset + 188);
+
+                // Errors report offsets in the stream rather than in the chunk
+                self.parser.set_stream_offset(self.stream_offset);
+                self.stream_offset += packet.len() as u64;
+
+                let mut parsed = None;
+                self.parser.parse_packets(
+                    chunk,
+                    |_| Ok(()),
+                    |_| Ok(()),
+                    Some(|packet: &TsPacketRef| {
+                        parsed = Some(packet.clone());
+                        Ok(())
+                    }),
+                )?;
+                if parsed.is_some() {
+                    return Ok(parsed);
+                }
+            }
+
+            if self.eof {
+                self.discard(self.buffer.len());
+                return Ok(None);
+            }
+            self.fill().await?;
+        }
+    }
+
+    /// Read packets until the PAT and the PMTs of all selected programs were
+    /// received, and return them with the PMTs ordered by program number.
+    ///
+    /// Returns right away when the tables are already known. Fails with
+    /// [`TsError::IncompleteTables`] if the stream ends first.
+    pub async fn read_tables(&mut self) -> Result<(Pat, Vec<Pmt>)> {
+        loop {
+            if let Some(tables) = self.parser.complete_tables() {
+                return Ok(tables);
+            }
+            if self.next_packet().await?.is_none() {
+                return Err(TsError::IncompleteTables);
+            }
+        }
+    }
+
+    /// Read more data into the buffer. This is the only await point, so
+    /// dropping the future here leaves the buffer as it was.
+    async fn fill(&mut self) -> Result<()> {
+        self.buffer.reserve(self.read_size);
+        if self.reader.read_buf(&mut self.buffer).await? == 0 {
+            self.eof = true;
+        }
+        Ok(())
+    }
+
+    /// Align the buffer on the next packet, dropping the bytes before it.
+    fn next_boundary(&mut self) -> Boundary {
+        if let Some(format) = self.locked_format {
+            match self.starts_at(0, format) {
+                Some(true) => return Boundary::Packet(format),
+                Some(false) => {}
+                None => return Boundary::NeedMore,
+            }
+        }
+
+        for sync_pos in memchr_iter(0x47, &self.buffer) {
+            for format in TsParser::PACKET_FORMATS {
+                let sync_offset = format.sync_offset();
+                if sync_pos < sync_offset {
+                    continue;
+                }
+
+                let offset = sync_pos - sync_offset;
+                match self.starts_at(offset, format) {
+                    Some(true) => {
+                        self.discard(offset);
+                        self.locked_format = Some(format);
+                        return Boundary::Packet(format);
+                    }
+                    Some(false) => {}
+                    None => {
+                        // Keep the bytes a packet starting here would need
+                        self.discard(sync_pos.saturating_sub(MAX_SYNC_OFFSET));
+                        return Boundary::NeedMore;
+                    }
+                }
+            }
+        }
+
+        // No packet boundary in the buffer, but the last bytes may prefix
+        // the sync byte of the next read
+        let keep = if self.eof { 0 } else { MAX_SYNC_OFFSET };
+        self.discard(self.buffer.len().saturating_sub(keep));
+        Boundary::NeedMore
+    }
+
+    /// Drop `count` bytes that are not part of any packet
+    fn discard(&mut self, count: usize) {
+        self.buffer.advance(count);
+        self.stream_offset += count as u64;
+    }
+
+    /// Whether a packet of `format` starts at `offset`, or `None` when that
+    /// depends on bytes not read yet.
+    ///
+    /// Like the parser, a packet is confirmed by the sync byte of the packet
+    /// after it, except at the end of the stream.
+    fn starts_at(&self, offset: usize, format: PacketFormat) -> Option<bool> {
+        let next_sync = offset + format.sync_offset() + format.packet_size();
+        if !self.eof && next_sync >= self.buffer.len() {
+            return None;
+        }
+        Some(TsParser::packet_starts_at(&self.buffer, offset, format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PatProgram, PmtStream, StreamType, TsWriter};
+    use bytes::Bytes;
+    use tokio::io::AsyncWriteExt;
+
+    /// Two programs with video and audio, followed by PES packets of both
+    fn build_stream() -> Vec<u8> {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: (1..=2)
+                .map(|n| PatProgram {
+                    program_number: n,
+                    pmt_pid: 0x1000 + n,
+                })
+                .collect(),
+        };
+        writer.write_pat(&pat, &mut out).unwrap();
+        for n in 1..=2u16 {
+            let pmt = Pmt {
+                table_id: 0x02,
+                program_number: n,
+                version_number: 0,
+                current_next_indicator: true,
+                section_number: 0,
+                last_section_number: 0,
+                pcr_pid: 0x100 + 2 * n,
+                program_info: Vec::new(),
+                streams: vec![
+                    PmtStream {
+                        stream_type: StreamType::H264,
+                        elementary_pid: 0x100 + 2 * n,
+                        es_info: Vec::new(),
+                    },
+                    PmtStream {
+                        stream_type: StreamType::AdtsAac,
+                        elementary_pid: 0x101 + 2 * n,
+                        es_info: Vec::new(),
+                    },
+                ],
+            };
+            writer.write_pmt(0x1000 + n, &pmt, &mut out).unwrap();
+        }
+        for round in 0..6u8 {
+            for n in 1..=2u16 {
+                writer
+                    .write_pes(0x100 + 2 * n, &vec![round; 700], &mut out)
+                    .unwrap();
+                writer
+                    .write_pes(0x101 + 2 * n, &vec![round; 150], &mut out)
+                    .unwrap();
+            }
+        }
+        out
+    }
+
+    /// Insert garbage containing stray sync bytes between two packets
+    fn with_garbage(mut data: Vec<u8>, packet: usize) -> Vec<u8> {
+        let garbage = [0x47, 0x00, 0x11, 0x47, 0x12, 0x47, 0x47, 0x99, 0x00];
+        data.splice(packet * 188..packet * 188, garbage);
+        data
+    }
+
+    /// Packets as reported by the sync parser over the whole data
+    fn sync_packets(data: &[u8]) -> Vec<(u16, u8, Option<Bytes>)> {
+        let mut packets = Vec::new();
+        TsParser::new()
+            .parse_packets(
+                Bytes::copy_from_slice(data),
+                |_| Ok(()),
+                |_| Ok(()),
+                Some(|packet: &TsPacketRef| {
+                    packets.push((packet.pid, packet.continuity_counter, packet.payload()));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        packets
+    }
+
+    /// Write `data` to a duplex pipe in chunks of irregular sizes
+    fn feed(data: Vec<u8>) -> tokio::io::DuplexStream {
+        let (mut client, server) = tokio::io::duplex(97);
+        tokio::spawn(async move {
+            let sizes = [1, 187, 3, 400, 13, 188, 76, 1000, 2];
+            let mut rest = &data[..];
+            for size in sizes.iter().cycle() {
+                if rest.is_empty() {
+                    break;
+                }
+                let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+                client.write_all(chunk).await.unwrap();
+                rest = tail;
+            }
+        });
+        server
+    }
+
+    async fn async_packets<R: AsyncRead + Unpin>(
+        reader: &mut AsyncTsReader<R>,
+    ) -> Vec<(u16, u8, Option<Bytes>)> {
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().await.unwrap() {
+            packets.push((packet.pid, packet.continuity_counter, packet.payload()));
+        }
+        packets
+    }
+
+    #[tokio::test]
+    async fn matches_sync_parser_over_irregular_chunks() {
+        let data = with_garbage(build_stream(), 5);
+        let expected = sync_packets(&data);
+
+        let mut reader = AsyncTsReader::new(feed(data.clone())).with_read_packets(2);
+        let packets = async_packets(&mut reader).await;
+
+        assert_eq!(packets.len(), data.len() / 188);
+        assert_eq!(packets, expected);
+    }
+
+    #[tokio::test]
+    async fn matches_sync_parser_on_m2ts() {
+        let mut data = Vec::new();
+        for (index, packet) in build_stream().chunks(188).enumerate() {
+            // Timestamp prefixes containing sync bytes
+            data.extend_from_slice(&[0x47, 0x47, index as u8, 0x47]);
+            data.extend_from_slice(packet);
+        }
+        let packet_count = data.len() / 192;
+        data.splice(3 * 192..3 * 192, [0x47, 0x00, 0x47, 0x11, 0x47]);
+        let expected = sync_packets(&data);
+
+        let mut reader = AsyncTsReader::new(feed(data));
+        let packets = async_packets(&mut reader).await;
+        assert_eq!(packets.len(), packet_count);
+        assert_eq!(packets, expected);
+    }
+
+    #[tokio::test]
+    async fn reads_tables() {
+        let mut reader = AsyncTsReader::new(feed(build_stream()));
+        let (pat, pmts) = reader.read_tables().await.unwrap();
+
+        assert_eq!(pat.programs.len(), 2);
+        let programs: Vec<u16> = pmts.iter().map(|pmt| pmt.program_number).collect();
+        assert_eq!(programs, [1, 2]);
+        assert_eq!(pmts[1].streams[1].elementary_pid, 0x105);
+        assert_eq!(pmts[1].streams[1].stream_type, StreamType::AdtsAac);
+
+        // The remaining packets are still returned
+        assert_eq!(reader.next_packet().await.unwrap().unwrap().pid, 0x102);
+
+        let data = build_stream();
+        let mut truncated = AsyncTsReader::new(&data[..188 * 2]);
+        assert!(matches!(
+            truncated.read_tables().await,
+            Err(TsError::IncompleteTables)
+        ));
+    }
+
+    #[tokio::test]
+    async fn dropped_read_keeps_buffer_state() {
+        let data = build_stream();
+        let expected = sync_packets(&data);
+        let (mut client, server) = tokio::io::duplex(4096);
+        let mut reader = AsyncTsReader::new(server);
+
+        // Half a packet, then a read that is abandoned while waiting for more
+        client.write_all(&data[..100]).await.unwrap();
+        tokio::select! {
+            biased;
+            _ = reader.next_packet() => panic!("no complete packet was written"),
+            _ = std::future::ready(()) => {}
+        }
+
+        tokio::spawn(async move {
+            client.write_all(&data[100..]).await.unwrap();
+        });
+        assert_eq!(async_packets(&mut reader).await, expected);
+    }
+}
@@ -39,6 +39,30 @@ pub fn validate_section_crc32(section_data: &[u8]) -> bool {
     mpeg2_crc32(section_data) == 0x0000_0000
 }
 
+/// Verify the CRC-32 of a PSI section whose header declares its own length.
+///
+/// Data shorter than the declared section is left for the table parser to reject.
+pub(crate) fn verify_section_crc32(data: &[u8]) -> crate::Result<()> {
+    if data.len() < 7 {
+        return Ok(());
+    }
+    let section_length = ((data[1] as usize & 0x0F) << 8) | data[2] as usize;
+    let section_end = 3 + section_length;
+    if section_end > data.len() || section_end < 4 || validate_section_crc32(&data[..section_end]) {
+        return Ok(());
+    }
+    let stored = u32::from_be_bytes([
+        data[section_end - 4],
+        data[section_end - 3],
+        data[section_end - 2],
+        data[section_end - 1],
+    ]);
+    Err(crate::TsError::Crc32Mismatch {
+        expected: stored,
+        calculated: mpeg2_crc32(&data[..section_end - 4]),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
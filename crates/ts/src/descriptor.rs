@@ -1,9 +1,18 @@
+use crate::{Result, TsError};
 use bytes::{Buf, Bytes};
 
+/// Video stream descriptor (tag 0x02)
+pub const TAG_VIDEO_STREAM: u8 = 0x02;
 /// Registration descriptor (tag 0x05)
 pub const TAG_REGISTRATION: u8 = 0x05;
 /// ISO 639 language descriptor (tag 0x0A)
 pub const TAG_ISO_639_LANGUAGE: u8 = 0x0A;
+/// Metadata descriptor (tag 0x26)
+pub const TAG_METADATA: u8 = 0x26;
+/// AVC video descriptor (tag 0x28)
+pub const TAG_AVC_VIDEO: u8 = 0x28;
+/// HEVC video descriptor (tag 0x38)
+pub const TAG_HEVC_VIDEO: u8 = 0x38;
 /// AC-3 audio descriptor (tag 0x6A)
 pub const TAG_AC3: u8 = 0x6A;
 /// Enhanced AC-3 audio descriptor (tag 0x7A)
@@ -12,8 +21,13 @@ pub const TAG_EAC3: u8 = 0x7A;
 pub const TAG_DTS: u8 = 0x7B;
 /// AAC audio descriptor (tag 0x7C)
 pub const TAG_AAC: u8 = 0x7C;
+/// DVB service descriptor (tag 0x48)
+pub const TAG_SERVICE: u8 = 0x48;
 /// Subtitling descriptor (tag 0x59)
 pub const TAG_SUBTITLING: u8 = 0x59;
+/// AV1 video descriptor (tag 0x80, user private), in the ES info of streams
+/// registered as "AV01"
+pub const TAG_AV1_VIDEO: u8 = 0x80;
 
 /// Zero-copy descriptor reference.
 #[derive(Debug, Clone)]
@@ -22,9 +36,17 @@ pub struct DescriptorRef {
     pub data: Bytes,
 }
 
+impl DescriptorRef {
+    /// Decode this descriptor into its typed representation.
+    pub fn parse(&self) -> Result<Descriptor> {
+        Descriptor::parse(self.tag, self.data.clone())
+    }
+}
+
 /// Iterator over descriptors in a TLV descriptor loop.
 ///
-/// Each descriptor is `[tag: u8][length: u8][data: length bytes]`.
+/// Each descriptor is `[tag: u8][length: u8][data: length bytes]`. A descriptor
+/// whose length runs past the end of the loop yields an error and ends iteration.
 #[derive(Debug, Clone)]
 pub struct DescriptorIterator {
     data: Bytes,
@@ -38,27 +60,403 @@ impl DescriptorIterator {
 }
 
 impl Iterator for DescriptorIterator {
-    type Item = DescriptorRef;
+    type Item = Result<DescriptorRef>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.data.remaining() < 2 {
+        if self.data.is_empty() {
             return None;
         }
+        if self.data.remaining() < 2 {
+            let tag = self.data[0];
+            self.data.clear();
+            return Some(Err(TsError::InvalidDescriptorLength {
+                tag,
+                length: 0,
+                available: 0,
+            }));
+        }
         let tag = self.data[0];
         let length = self.data[1] as usize;
         self.data.advance(2);
 
         if self.data.remaining() < length {
-            // Malformed descriptor — consume remaining and stop
-            self.data.advance(self.data.remaining());
-            return None;
+            let available = self.data.remaining();
+            self.data.clear();
+            return Some(Err(TsError::InvalidDescriptorLength {
+                tag,
+                length,
+                available,
+            }));
         }
 
         let data = self.data.split_to(length);
-        Some(DescriptorRef { tag, data })
+        Some(Ok(DescriptorRef { tag, data }))
+    }
+}
+
+/// `(tag, data)` of the descriptors of a borrowed descriptor loop, up to the
+/// first one running past the end of the loop.
+pub(crate) fn raw_descriptors(mut data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let [tag, length, rest @ ..] = data else {
+            return None;
+        };
+        let (body, remaining) = rest.split_at_checked(*length as usize)?;
+        data = remaining;
+        Some((*tag, body))
+    })
+}
+
+/// Iterator over a descriptor loop that yields typed [`Descriptor`] values.
+#[derive(Debug, Clone)]
+pub struct Descriptors {
+    inner: DescriptorIterator,
+}
+
+impl Descriptors {
+    /// Create a typed descriptor iterator from a descriptor loop byte sequence.
+    pub fn new(data: Bytes) -> Self {
+        Descriptors {
+            inner: DescriptorIterator::new(data),
+        }
+    }
+
+    /// Access the underlying raw TLV iterator.
+    pub fn raw(self) -> DescriptorIterator {
+        self.inner
+    }
+}
+
+impl Iterator for Descriptors {
+    type Item = Result<Descriptor>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|raw| raw.and_then(|d| d.parse()))
+    }
+}
+
+/// Typed descriptor from a PMT program or ES info loop.
+#[derive(Debug, Clone)]
+pub enum Descriptor {
+    /// Video stream descriptor (tag 0x02)
+    VideoStream(VideoStreamDescriptor),
+    /// Registration descriptor (tag 0x05)
+    Registration {
+        format_identifier: [u8; 4],
+        additional_info: Bytes,
+    },
+    /// ISO 639 language descriptor (tag 0x0A)
+    Iso639Language(Vec<LanguageEntry>),
+    /// AVC video descriptor (tag 0x28)
+    AvcVideo(AvcVideoDescriptor),
+    /// HEVC video descriptor (tag 0x38)
+    HevcVideo(HevcVideoDescriptor),
+    /// DVB service descriptor (tag 0x48)
+    Service(ServiceDescriptor),
+    /// AC-3 audio descriptor (tag 0x6A)
+    Ac3(Ac3Descriptor),
+    /// Any descriptor without a typed representation
+    Unknown { tag: u8, data: Bytes },
+}
+
+impl Descriptor {
+    /// Decode a descriptor payload for the given tag.
+    ///
+    /// Known tags whose payload is too short for the fixed fields return
+    /// [`TsError::InsufficientData`].
+    pub fn parse(tag: u8, data: Bytes) -> Result<Self> {
+        match tag {
+            TAG_VIDEO_STREAM => VideoStreamDescriptor::parse(&data).map(Descriptor::VideoStream),
+            TAG_REGISTRATION => {
+                let format_identifier =
+                    parse_registration_descriptor(&data).ok_or(TsError::InsufficientData {
+                        expected: 4,
+                        actual: data.len(),
+                    })?;
+                Ok(Descriptor::Registration {
+                    format_identifier,
+                    additional_info: data.slice(4..),
+                })
+            }
+            TAG_ISO_639_LANGUAGE => Ok(Descriptor::Iso639Language(parse_iso639_language(&data))),
+            TAG_AVC_VIDEO => AvcVideoDescriptor::parse(&data).map(Descriptor::AvcVideo),
+            TAG_HEVC_VIDEO => HevcVideoDescriptor::parse(&data).map(Descriptor::HevcVideo),
+            TAG_SERVICE => ServiceDescriptor::parse(&data).map(Descriptor::Service),
+            TAG_AC3 => parse_ac3_descriptor(&data).map(Descriptor::Ac3),
+            _ => Ok(Descriptor::Unknown { tag, data }),
+        }
+    }
+
+    /// Decode an AV1 video descriptor, `None` for descriptors of another tag.
+    ///
+    /// The tag is user private, so it only denotes an AV1 video descriptor in
+    /// the ES info of a stream classified as [`StreamType::Av1`](crate::StreamType::Av1).
+    #[cfg(feature = "av1")]
+    pub fn as_av1_video_descriptor(&self) -> Option<Result<av1::AV1VideoDescriptor>> {
+        match self {
+            Descriptor::Unknown {
+                tag: TAG_AV1_VIDEO,
+                data,
+            } => Some(av1::AV1VideoDescriptor::demux_payload(data.clone()).map_err(TsError::from)),
+            _ => None,
+        }
+    }
+
+    /// Descriptor tag of this value.
+    pub fn tag(&self) -> u8 {
+        match self {
+            Descriptor::VideoStream(_) => TAG_VIDEO_STREAM,
+            Descriptor::Registration { .. } => TAG_REGISTRATION,
+            Descriptor::Iso639Language(_) => TAG_ISO_639_LANGUAGE,
+            Descriptor::AvcVideo(_) => TAG_AVC_VIDEO,
+            Descriptor::HevcVideo(_) => TAG_HEVC_VIDEO,
+            Descriptor::Service(_) => TAG_SERVICE,
+            Descriptor::Ac3(_) => TAG_AC3,
+            Descriptor::Unknown { tag, .. } => *tag,
+        }
+    }
+}
+
+/// Parsed video stream descriptor (tag 0x02).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoStreamDescriptor {
+    pub multiple_frame_rate_flag: bool,
+    pub frame_rate_code: u8,
+    pub mpeg_1_only_flag: bool,
+    pub constrained_parameter_flag: bool,
+    pub still_picture_flag: bool,
+    /// Present only when `mpeg_1_only_flag` is not set
+    pub profile_and_level_indication: Option<u8>,
+    /// Present only when `mpeg_1_only_flag` is not set
+    pub chroma_format: Option<u8>,
+    /// Present only when `mpeg_1_only_flag` is not set
+    pub frame_rate_extension_flag: Option<bool>,
+}
+
+impl VideoStreamDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.is_empty() {
+            return Err(TsError::InsufficientData {
+                expected: 1,
+                actual: 0,
+            });
+        }
+        let flags = data[0];
+        let mpeg_1_only_flag = (flags & 0x04) != 0;
+        let (profile_and_level_indication, chroma_format, frame_rate_extension_flag) =
+            if mpeg_1_only_flag {
+                (None, None, None)
+            } else {
+                if data.len() < 3 {
+                    return Err(TsError::InsufficientData {
+                        expected: 3,
+                        actual: data.len(),
+                    });
+                }
+                (
+                    Some(data[1]),
+                    Some((data[2] >> 6) & 0x03),
+                    Some((data[2] & 0x20) != 0),
+                )
+            };
+
+        Ok(VideoStreamDescriptor {
+            multiple_frame_rate_flag: (flags & 0x80) != 0,
+            frame_rate_code: (flags >> 3) & 0x0F,
+            mpeg_1_only_flag,
+            constrained_parameter_flag: (flags & 0x02) != 0,
+            still_picture_flag: (flags & 0x01) != 0,
+            profile_and_level_indication,
+            chroma_format,
+            frame_rate_extension_flag,
+        })
+    }
+}
+
+/// Parsed AVC video descriptor (tag 0x28).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvcVideoDescriptor {
+    pub profile_idc: u8,
+    /// constraint_set0..5 flags followed by the 2-bit AVC_compatible_flags
+    pub constraint_flags: u8,
+    pub level_idc: u8,
+    pub still_present: bool,
+    pub avc_24_hour_picture_flag: bool,
+    pub frame_packing_sei_not_present_flag: bool,
+}
+
+impl AvcVideoDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(TsError::InsufficientData {
+                expected: 4,
+                actual: data.len(),
+            });
+        }
+        Ok(AvcVideoDescriptor {
+            profile_idc: data[0],
+            constraint_flags: data[1],
+            level_idc: data[2],
+            still_present: (data[3] & 0x80) != 0,
+            avc_24_hour_picture_flag: (data[3] & 0x40) != 0,
+            frame_packing_sei_not_present_flag: (data[3] & 0x20) != 0,
+        })
+    }
+}
+
+/// Parsed HEVC video descriptor (tag 0x38).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HevcVideoDescriptor {
+    pub profile_space: u8,
+    pub tier_flag: bool,
+    pub profile_idc: u8,
+    pub profile_compatibility_indication: u32,
+    pub progressive_source_flag: bool,
+    pub interlaced_source_flag: bool,
+    pub non_packed_constraint_flag: bool,
+    pub frame_only_constraint_flag: bool,
+    /// Remaining 44 bits of the general constraint indicator flags
+    pub copied_44bits: u64,
+    pub level_idc: u8,
+    pub hevc_still_present_flag: bool,
+    pub hevc_24hr_picture_present_flag: bool,
+    pub sub_pic_hrd_params_not_present_flag: bool,
+    pub hdr_wcg_idc: u8,
+    /// `(temporal_id_min, temporal_id_max)` when temporal_layer_subset_flag is set
+    pub temporal_id_range: Option<(u8, u8)>,
+}
+
+impl HevcVideoDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 13 {
+            return Err(TsError::InsufficientData {
+                expected: 13,
+                actual: data.len(),
+            });
+        }
+        let copied_44bits = ((data[5] as u64 & 0x0F) << 40)
+            | ((data[6] as u64) << 32)
+            | ((data[7] as u64) << 24)
+            | ((data[8] as u64) << 16)
+            | ((data[9] as u64) << 8)
+            | data[10] as u64;
+        let level_idc = data[11];
+
+        let flags = data[12];
+        let temporal_layer_subset_flag = (flags & 0x80) != 0;
+        let temporal_id_range = if temporal_layer_subset_flag {
+            if data.len() < 15 {
+                return Err(TsError::InsufficientData {
+                    expected: 15,
+                    actual: data.len(),
+                });
+            }
+            Some(((data[13] >> 5) & 0x07, (data[14] >> 5) & 0x07))
+        } else {
+            None
+        };
+
+        Ok(HevcVideoDescriptor {
+            profile_space: (data[0] >> 6) & 0x03,
+            tier_flag: (data[0] & 0x20) != 0,
+            profile_idc: data[0] & 0x1F,
+            profile_compatibility_indication: u32::from_be_bytes([
+                data[1], data[2], data[3], data[4],
+            ]),
+            progressive_source_flag: (data[5] & 0x80) != 0,
+            interlaced_source_flag: (data[5] & 0x40) != 0,
+            non_packed_constraint_flag: (data[5] & 0x20) != 0,
+            frame_only_constraint_flag: (data[5] & 0x10) != 0,
+            copied_44bits,
+            level_idc,
+            hevc_still_present_flag: (flags & 0x40) != 0,
+            hevc_24hr_picture_present_flag: (flags & 0x20) != 0,
+            sub_pic_hrd_params_not_present_flag: (flags & 0x10) != 0,
+            hdr_wcg_idc: flags & 0x03,
+            temporal_id_range,
+        })
     }
 }
 
+/// Parsed DVB service descriptor (tag 0x48), carried in the SDT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDescriptor {
+    /// Service type (0x01 = digital television, 0x02 = digital radio, ...)
+    pub service_type: u8,
+    /// Provider name, decoded from DVB text
+    pub provider_name: String,
+    /// Service name, decoded from DVB text
+    pub service_name: String,
+}
+
+impl ServiceDescriptor {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let short = || TsError::InsufficientData {
+            expected: 3,
+            actual: data.len(),
+        };
+        let service_type = *data.first().ok_or_else(short)?;
+        let provider_len = *data.get(1).ok_or_else(short)? as usize;
+        let provider_end = 2 + provider_len;
+        let provider = data.get(2..provider_end).ok_or(TsError::InsufficientData {
+            expected: provider_end + 1,
+            actual: data.len(),
+        })?;
+        let service_len = *data.get(provider_end).ok_or(TsError::InsufficientData {
+            expected: provider_end + 1,
+            actual: data.len(),
+        })? as usize;
+        let service_end = provider_end + 1 + service_len;
+        let service = data
+            .get(provider_end + 1..service_end)
+            .ok_or(TsError::InsufficientData {
+                expected: service_end,
+                actual: data.len(),
+            })?;
+
+        Ok(ServiceDescriptor {
+            service_type,
+            provider_name: decode_dvb_text(provider),
+            service_name: decode_dvb_text(service),
+        })
+    }
+}
+
+/// Decode a DVB text field (ETSI EN 300 468 Annex A).
+///
+/// UTF-8 (0x15), UCS-2 (0x11) and ISO/IEC 8859 (0x01..=0x0B, 0x10) selectors
+/// are honoured. The default table (ISO/IEC 6937) and the non-Latin 8859
+/// parts are decoded as Latin-1, which is exact for the ASCII range that
+/// virtually all service names use. Control codes in 0x80..=0x9F are dropped,
+/// except the CR/LF code 0x8A which becomes a newline.
+pub fn decode_dvb_text(data: &[u8]) -> String {
+    let Some(&first) = data.first() else {
+        return String::new();
+    };
+
+    let text = match first {
+        0x15 => return String::from_utf8_lossy(&data[1..]).into_owned(),
+        0x11 => {
+            let units: Vec<u16> = data[1..]
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            return String::from_utf16_lossy(&units);
+        }
+        0x10 => data.get(3..).unwrap_or_default(),
+        0x01..=0x1F => &data[1..],
+        _ => data,
+    };
+
+    text.iter()
+        .filter_map(|&b| match b {
+            0x8A => Some('\n'),
+            0x80..=0x9F => None,
+            _ => Some(b as char),
+        })
+        .collect()
+}
+
 /// Parse a registration descriptor (tag 0x05).
 ///
 /// Returns the 4-byte format_identifier if the descriptor data is at least 4 bytes.
@@ -108,51 +506,38 @@ pub struct Ac3Descriptor {
 }
 
 /// Parse AC-3 descriptor (tag 0x6A).
-pub fn parse_ac3_descriptor(data: &[u8]) -> Option<Ac3Descriptor> {
-    if data.is_empty() {
-        return None;
-    }
-
-    let flags = data[0];
+///
+/// Every field announced by the flags must be present, a truncated one is
+/// reported as [`TsError::InsufficientData`].
+pub fn parse_ac3_descriptor(data: &[u8]) -> Result<Ac3Descriptor> {
+    let flags = *data.first().ok_or(TsError::InsufficientData {
+        expected: 1,
+        actual: 0,
+    })?;
     let component_type_flag = (flags & 0x80) != 0;
     let bsid_flag = (flags & 0x40) != 0;
     let mainid_flag = (flags & 0x20) != 0;
     let asvc_flag = (flags & 0x10) != 0;
 
     let mut offset = 1;
-
-    let component_type = if component_type_flag && offset < data.len() {
-        let val = data[offset];
-        offset += 1;
-        Some(val)
-    } else {
-        None
-    };
-
-    let bsid = if bsid_flag && offset < data.len() {
-        let val = data[offset];
-        offset += 1;
-        Some(val)
-    } else {
-        None
-    };
-
-    let mainid = if mainid_flag && offset < data.len() {
-        let val = data[offset];
+    let mut field = |present: bool| -> Result<Option<u8>> {
+        if !present {
+            return Ok(None);
+        }
+        let val = *data.get(offset).ok_or(TsError::InsufficientData {
+            expected: offset + 1,
+            actual: data.len(),
+        })?;
         offset += 1;
-        Some(val)
-    } else {
-        None
+        Ok(Some(val))
     };
 
-    let asvc = if asvc_flag && offset < data.len() {
-        let val = data[offset];
-        Some(val)
-    } else {
-        None
-    };
+    let component_type = field(component_type_flag)?;
+    let bsid = field(bsid_flag)?;
+    let mainid = field(mainid_flag)?;
+    let asvc = field(asvc_flag)?;
 
-    Some(Ac3Descriptor {
+    Ok(Ac3Descriptor {
         component_type_flag,
         bsid_flag,
         mainid_flag,
@@ -178,7 +563,7 @@ mod tests {
     fn test_descriptor_iterator_single() {
         // One descriptor: tag=0x05, length=4, data="CUEI"
         let data = Bytes::from_static(&[0x05, 0x04, b'C', b'U', b'E', b'I']);
-        let descriptors: Vec<_> = DescriptorIterator::new(data).collect();
+        let descriptors: Vec<_> = DescriptorIterator::new(data).flatten().collect();
         assert_eq!(descriptors.len(), 1);
         assert_eq!(descriptors[0].tag, TAG_REGISTRATION);
         assert_eq!(&descriptors[0].data[..], b"CUEI");
@@ -191,7 +576,9 @@ mod tests {
         data.extend_from_slice(&[0x05, 0x04, b'C', b'U', b'E', b'I']);
         // ISO 639 language descriptor
         data.extend_from_slice(&[0x0A, 0x04, b'e', b'n', b'g', 0x00]);
-        let descriptors: Vec<_> = DescriptorIterator::new(Bytes::from(data)).collect();
+        let descriptors: Vec<_> = DescriptorIterator::new(Bytes::from(data))
+            .flatten()
+            .collect();
         assert_eq!(descriptors.len(), 2);
         assert_eq!(descriptors[0].tag, TAG_REGISTRATION);
         assert_eq!(descriptors[1].tag, TAG_ISO_639_LANGUAGE);
@@ -202,7 +589,134 @@ mod tests {
         // Tag + length that exceeds remaining data
         let data = Bytes::from_static(&[0x05, 0xFF]);
         let descriptors: Vec<_> = DescriptorIterator::new(data).collect();
-        assert_eq!(descriptors.len(), 0);
+        assert_eq!(descriptors.len(), 1);
+        assert!(matches!(
+            descriptors[0],
+            Err(TsError::InvalidDescriptorLength {
+                tag: 0x05,
+                length: 255,
+                available: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_descriptor_iterator_truncated_header() {
+        let data = Bytes::from_static(&[0x0A]);
+        let mut iter = DescriptorIterator::new(data);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(TsError::InvalidDescriptorLength { tag: 0x0A, .. }))
+        ));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_typed_descriptors() {
+        let mut data = Vec::new();
+        data.extend_from_slice(&[0x05, 0x04, b'A', b'C', b'-', b'3']);
+        data.extend_from_slice(&[0x0A, 0x04, b'e', b'n', b'g', 0x00]);
+        // AVC: High profile, level 4.0, still_present set
+        data.extend_from_slice(&[0x28, 0x04, 0x64, 0x00, 0x28, 0xBF]);
+        data.extend_from_slice(&[0xF0, 0x02, 0xAB, 0xCD]);
+
+        let descriptors: Vec<_> = Descriptors::new(Bytes::from(data))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(descriptors.len(), 4);
+        assert!(matches!(
+            &descriptors[0],
+            Descriptor::Registration { format_identifier, .. } if format_identifier == b"AC-3"
+        ));
+        assert!(matches!(
+            &descriptors[1],
+            Descriptor::Iso639Language(entries) if &entries[0].language_code == b"eng"
+        ));
+        let Descriptor::AvcVideo(avc) = &descriptors[2] else {
+            panic!("expected AVC descriptor");
+        };
+        assert_eq!(avc.profile_idc, 100);
+        assert_eq!(avc.level_idc, 40);
+        assert!(avc.still_present);
+        assert!(!avc.avc_24_hour_picture_flag);
+        assert!(matches!(
+            &descriptors[3],
+            Descriptor::Unknown { tag: 0xF0, data } if data[..] == [0xAB, 0xCD]
+        ));
+    }
+
+    #[test]
+    fn test_parse_video_stream_descriptor() {
+        // frame_rate_code=3 (25fps), MPEG-2 with profile/level 0x48, chroma 4:2:0
+        let desc = VideoStreamDescriptor::parse(&[0x18, 0x48, 0x5F]).unwrap();
+        assert!(!desc.multiple_frame_rate_flag);
+        assert_eq!(desc.frame_rate_code, 3);
+        assert!(!desc.mpeg_1_only_flag);
+        assert_eq!(desc.profile_and_level_indication, Some(0x48));
+        assert_eq!(desc.chroma_format, Some(1));
+        assert_eq!(desc.frame_rate_extension_flag, Some(false));
+
+        let mpeg1 = VideoStreamDescriptor::parse(&[0x1C]).unwrap();
+        assert!(mpeg1.mpeg_1_only_flag);
+        assert!(mpeg1.profile_and_level_indication.is_none());
+
+        assert!(VideoStreamDescriptor::parse(&[0x18]).is_err());
+    }
+
+    #[test]
+    fn test_parse_hevc_video_descriptor() {
+        let data = [
+            0x01, // profile_space=0, tier=0, profile_idc=1 (Main)
+            0x60, 0x00, 0x00, 0x00, // profile compatibility
+            0x90, 0x00, 0x00, 0x00, 0x00, 0x00, // progressive + frame_only
+            0x5D, // level_idc 93 (3.1)
+            0x80, // temporal_layer_subset_flag
+            0x20, 0x40, // temporal_id_min=1, temporal_id_max=2
+        ];
+        let desc = HevcVideoDescriptor::parse(&data).unwrap();
+        assert_eq!(desc.profile_idc, 1);
+        assert!(!desc.tier_flag);
+        assert_eq!(desc.profile_compatibility_indication, 0x6000_0000);
+        assert!(desc.progressive_source_flag);
+        assert!(!desc.interlaced_source_flag);
+        assert!(desc.frame_only_constraint_flag);
+        assert_eq!(desc.level_idc, 93);
+        assert_eq!(desc.temporal_id_range, Some((1, 2)));
+
+        assert!(HevcVideoDescriptor::parse(&data[..12]).is_err());
+        assert!(HevcVideoDescriptor::parse(&data[..13]).is_err());
+    }
+
+    #[test]
+    fn test_parse_service_descriptor() {
+        let mut data = vec![0x01, 0x03];
+        data.extend_from_slice(b"BBC");
+        data.push(0x0B);
+        data.extend_from_slice(b"\x15BBC ONE \xc3\x9f");
+        let desc = ServiceDescriptor::parse(&data).unwrap();
+        assert_eq!(desc.service_type, 0x01);
+        assert_eq!(desc.provider_name, "BBC");
+        assert_eq!(desc.service_name, "BBC ONE \u{df}");
+
+        // service_name_length runs past the descriptor
+        assert!(ServiceDescriptor::parse(&[0x01, 0x00, 0x05, b'a']).is_err());
+    }
+
+    #[test]
+    fn test_decode_dvb_text() {
+        assert_eq!(decode_dvb_text(b""), "");
+        assert_eq!(decode_dvb_text(b"Plain"), "Plain");
+        // ISO 8859-1 via the three-byte selector
+        assert_eq!(decode_dvb_text(&[0x10, 0x00, 0x01, b'C', 0xE9]), "C\u{e9}");
+        // UCS-2
+        assert_eq!(decode_dvb_text(&[0x11, 0x00, b'H', 0x00, b'i']), "Hi");
+        // Emphasis on/off control codes are dropped, CR/LF is kept
+        assert_eq!(decode_dvb_text(&[0x86, b'A', 0x87, 0x8A, b'B']), "A\nB");
+    }
+
+    #[test]
+    fn test_registration_descriptor_too_short_is_error() {
+        assert!(Descriptor::parse(TAG_REGISTRATION, Bytes::from_static(b"CU")).is_err());
     }
 
     #[test]
@@ -252,6 +766,32 @@ mod tests {
 
     #[test]
     fn test_parse_ac3_descriptor_empty() {
-        assert!(parse_ac3_descriptor(&[]).is_none());
+        assert!(matches!(
+            parse_ac3_descriptor(&[]),
+            Err(TsError::InsufficientData {
+                expected: 1,
+                actual: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parse_ac3_descriptor_truncated_field() {
+        // component_type, bsid and mainid announced, mainid missing
+        let data = [0xE0, 0x48, 0x08];
+        assert!(matches!(
+            parse_ac3_descriptor(&data),
+            Err(TsError::InsufficientData {
+                expected: 4,
+                actual: 3
+            })
+        ));
+        assert!(matches!(
+            Descriptor::parse(TAG_AC3, Bytes::from_static(&data)),
+            Err(TsError::InsufficientData {
+                expected: 4,
+                actual: 3
+            })
+        ));
     }
 }
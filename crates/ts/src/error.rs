@@ -1,5 +1,76 @@
+use std::fmt;
+
 use thiserror::Error;
 
+/// Where in the stream an error occurred, as far as it is known
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// PID of the packet or section
+    pub pid: Option<u16>,
+    /// Byte offset of the packet within all input the parser processed
+    pub offset: Option<u64>,
+    /// Table ID of the section
+    pub table_id: Option<u8>,
+    /// Section number of a section using the long section syntax
+    pub section_number: Option<u8>,
+}
+
+impl ErrorContext {
+    /// Context of the packet of `pid` starting at `offset`
+    pub(crate) fn packet(pid: u16, offset: u64) -> Self {
+        Self {
+            pid: Some(pid),
+            offset: Some(offset),
+            ..Self::default()
+        }
+    }
+
+    /// Context of a PSI section completed by the packet of `pid` starting at
+    /// `offset`, reading the table ID and section number from its header
+    pub(crate) fn section(pid: u16, offset: u64, section: &[u8]) -> Self {
+        let long_syntax = section.get(1).is_some_and(|byte| byte & 0x80 != 0);
+        Self {
+            table_id: section.first().copied(),
+            section_number: section.get(6).copied().filter(|_| long_syntax),
+            ..Self::packet(pid, offset)
+        }
+    }
+
+    /// Fill the fields unknown here from `other`
+    fn or(self, other: ErrorContext) -> Self {
+        Self {
+            pid: self.pid.or(other.pid),
+            offset: self.offset.or(other.offset),
+            table_id: self.table_id.or(other.table_id),
+            section_number: self.section_number.or(other.section_number),
+        }
+    }
+}
+
+/// Space separated `key=value` pairs of the known fields, e.g.
+/// `pid=0x101 offset=376432 table=0x02 section=0`
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        if let Some(pid) = self.pid {
+            write!(f, "pid={pid:#x}")?;
+            separator = " ";
+        }
+        if let Some(offset) = self.offset {
+            write!(f, "{separator}offset={offset}")?;
+            separator = " ";
+        }
+        if let Some(table_id) = self.table_id {
+            write!(f, "{separator}table={table_id:#04x}")?;
+            separator = " ";
+        }
+        if let Some(section_number) = self.section_number {
+            write!(f, "{separator}section={section_number}")?;
+        }
+        Ok(())
+    }
+}
+
 /// Errors that can occur during TS parsing
 #[derive(Error, Debug)]
 pub enum TsError {
@@ -21,6 +92,13 @@ pub enum TsError {
     #[error("CRC32 mismatch: expected 0x{expected:08x}, calculated 0x{calculated:08x}")]
     Crc32Mismatch { expected: u32, calculated: u32 },
 
+    #[error("Invalid descriptor 0x{tag:02x}: length {length} exceeds {available} remaining bytes")]
+    InvalidDescriptorLength {
+        tag: u8,
+        length: usize,
+        available: usize,
+    },
+
     #[error("Invalid program number: {0}")]
     InvalidProgramNumber(u16),
 
@@ -38,6 +116,9 @@ pub enum TsError {
     #[error("Duplicate packet detected on PID 0x{pid:04x} with continuity counter {cc}")]
     DuplicatePacket { pid: u16, cc: u8 },
 
+    #[error("Lost sync: skipped {skipped} bytes without finding a packet boundary")]
+    SyncLost { skipped: usize },
+
     #[error("Invalid PES start code")]
     InvalidPesStartCode,
 
@@ -46,4 +127,70 @@ pub enum TsError {
 
     #[error("Invalid SCTE-35 section: {0}")]
     InvalidScte35(String),
+
+    #[error("Invalid ID3 tag: {0}")]
+    InvalidId3(String),
+
+    #[error("Stream ended before the PAT and PMTs were complete")]
+    IncompleteTables,
+
+    #[error("Not enough PCRs to time the stream: found {0}")]
+    InsufficientPcr(usize),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// An error raised by a parser, with where in the stream it occurred
+    #[error("{context}: {error}")]
+    InContext {
+        context: ErrorContext,
+        error: Box<TsError>,
+    },
+}
+
+impl TsError {
+    /// Where in the stream the error occurred.
+    ///
+    /// Errors returned by the parsers carry the PID and byte offset of the
+    /// packet they were raised on, and for sections also the table ID and
+    /// section number. Other errors only report the PID of variants that
+    /// have one.
+    pub fn context(&self) -> ErrorContext {
+        match self {
+            TsError::InContext { context, error } => context.or(error.context()),
+            TsError::ContinuityError { pid, .. } | TsError::DuplicatePacket { pid, .. } => {
+                ErrorContext {
+                    pid: Some(*pid),
+                    ..ErrorContext::default()
+                }
+            }
+            _ => ErrorContext::default(),
+        }
+    }
+
+    /// The error with the context attached by the parsers removed, for
+    /// matching on the kind of error
+    pub fn without_context(&self) -> &TsError {
+        match self {
+            TsError::InContext { error, .. } => error.without_context(),
+            error => error,
+        }
+    }
+
+    /// Attach where in the stream the error occurred
+    pub(crate) fn in_context(self, context: ErrorContext) -> Self {
+        match self {
+            TsError::InContext {
+                context: inner,
+                error,
+            } => TsError::InContext {
+                context: inner.or(context),
+                error,
+            },
+            error => TsError::InContext {
+                context,
+                error: Box::new(error),
+            },
+        }
+    }
 }
@@ -0,0 +1,381 @@
+use bytes::Bytes;
+
+use crate::{Result, TsError};
+
+/// ID3v2 tag header size
+pub const ID3_HEADER_SIZE: usize = 10;
+
+const FLAG_UNSYNCHRONISATION: u8 = 0x80;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+
+/// A parsed ID3v2 tag, as carried in timed metadata PES packets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Id3Tag {
+    /// Major version (3 or 4)
+    pub major_version: u8,
+    pub revision: u8,
+    pub frames: Vec<Id3Frame>,
+}
+
+/// A single ID3v2 frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Id3Frame {
+    /// User defined text information (TXXX)
+    UserText { description: String, value: String },
+    /// Text information frame (T000-TZZZ, e.g. TIT2)
+    Text { id: String, value: String },
+    /// Private frame (PRIV) with its owner identifier
+    Private { owner: String, data: Bytes },
+    /// Any other frame, kept as raw bytes
+    Other { id: String, data: Bytes },
+}
+
+impl Id3Frame {
+    /// Four character frame ID
+    pub fn id(&self) -> &str {
+        match self {
+            Id3Frame::UserText { .. } => "TXXX",
+            Id3Frame::Text { id, .. } | Id3Frame::Other { id, .. } => id,
+            Id3Frame::Private { .. } => "PRIV",
+        }
+    }
+}
+
+/// Decode a 28-bit synchsafe integer (7 bits per byte).
+fn synchsafe(data: &[u8]) -> Option<usize> {
+    if data.iter().any(|byte| byte & 0x80 != 0) {
+        return None;
+    }
+    Some(
+        data.iter()
+            .fold(0usize, |acc, &byte| (acc << 7) | byte as usize),
+    )
+}
+
+fn invalid(message: impl Into<String>) -> TsError {
+    TsError::InvalidId3(message.into())
+}
+
+impl Id3Tag {
+    /// Check whether `data` starts with an ID3v2 tag header.
+    pub fn is_id3(data: &[u8]) -> bool {
+        data.len() >= ID3_HEADER_SIZE && &data[..3] == b"ID3"
+    }
+
+    /// Parse an ID3v2.3 or ID3v2.4 tag from the start of `data`.
+    ///
+    /// Trailing bytes after the tag are ignored. Unsynchronised tags are
+    /// rejected since timed metadata never uses them.
+    pub fn parse(data: &Bytes) -> Result<Self> {
+        if !Self::is_id3(data) {
+            return Err(invalid("missing ID3 header"));
+        }
+
+        let major_version = data[3];
+        let revision = data[4];
+        let flags = data[5];
+        if !matches!(major_version, 3 | 4) {
+            return Err(invalid(format!("unsupported version 2.{major_version}")));
+        }
+        if flags & FLAG_UNSYNCHRONISATION != 0 {
+            return Err(invalid("unsynchronised tags are not supported"));
+        }
+
+        let size = synchsafe(&data[6..10]).ok_or_else(|| invalid("tag size is not synchsafe"))?;
+        let end = ID3_HEADER_SIZE + size;
+        if data.len() < end {
+            return Err(TsError::InsufficientData {
+                expected: end,
+                actual: data.len(),
+            });
+        }
+
+        let mut pos = ID3_HEADER_SIZE;
+        if flags & FLAG_EXTENDED_HEADER != 0 {
+            let header = data
+                .get(pos..pos + 4)
+                .ok_or_else(|| invalid("truncated extended header"))?;
+            // v2.4 counts the size field itself, v2.3 does not
+            let extended = if major_version == 4 {
+                synchsafe(header).ok_or_else(|| invalid("extended header size"))?
+            } else {
+                u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize + 4
+            };
+            pos += extended;
+            if pos > end {
+                return Err(invalid("extended header exceeds tag"));
+            }
+        }
+
+        let mut frames = Vec::new();
+        while pos + ID3_HEADER_SIZE <= end {
+            let header = &data[pos..pos + ID3_HEADER_SIZE];
+            if header[0] == 0 {
+                // Padding
+                break;
+            }
+
+            let id = std::str::from_utf8(&header[..4])
+                .ok()
+                .filter(|id| {
+                    id.bytes()
+                        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+                })
+                .ok_or_else(|| invalid(format!("invalid frame ID {:02X?}", &header[..4])))?
+                .to_string();
+            let frame_size = if major_version == 4 {
+                synchsafe(&header[4..8])
+                    .ok_or_else(|| invalid(format!("{id} frame size is not synchsafe")))?
+            } else {
+                u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize
+            };
+
+            let body_start = pos + ID3_HEADER_SIZE;
+            let body_end = body_start + frame_size;
+            if body_end > end {
+                return Err(invalid(format!(
+                    "{id} frame of {frame_size} bytes exceeds tag"
+                )));
+            }
+
+            frames.push(Self::parse_frame(id, data.slice(body_start..body_end))?);
+            pos = body_end;
+        }
+
+        Ok(Id3Tag {
+            major_version,
+            revision,
+            frames,
+        })
+    }
+
+    /// Total size of the tag starting at `data`, including header and footer.
+    pub fn tag_size(data: &[u8]) -> Option<usize> {
+        if !Self::is_id3(data) {
+            return None;
+        }
+        let footer = if data[5] & FLAG_FOOTER != 0 {
+            ID3_HEADER_SIZE
+        } else {
+            0
+        };
+        Some(ID3_HEADER_SIZE + synchsafe(&data[6..10])? + footer)
+    }
+
+    fn parse_frame(id: String, body: Bytes) -> Result<Id3Frame> {
+        match id.as_str() {
+            "TXXX" => {
+                let (&encoding, text) = body
+                    .split_first()
+                    .ok_or_else(|| invalid("empty TXXX frame"))?;
+                let mut strings = decode_strings(encoding, text)?.into_iter();
+                let description = strings.next().unwrap_or_default();
+                let value = strings.next().unwrap_or_default();
+                Ok(Id3Frame::UserText { description, value })
+            }
+            "PRIV" => {
+                let owner_end = body
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or_else(|| invalid("PRIV frame without owner terminator"))?;
+                let owner = String::from_utf8_lossy(&body[..owner_end]).into_owned();
+                Ok(Id3Frame::Private {
+                    owner,
+                    data: body.slice(owner_end + 1..),
+                })
+            }
+            _ if id.starts_with('T') => {
+                let (&encoding, text) = body
+                    .split_first()
+                    .ok_or_else(|| invalid(format!("empty {id} frame")))?;
+                let value = decode_strings(encoding, text)?
+                    .into_iter()
+                    .next()
+                    .unwrap_or_default();
+                Ok(Id3Frame::Text { id, value })
+            }
+            _ => Ok(Id3Frame::Other { id, data: body }),
+        }
+    }
+}
+
+/// Decode the null separated strings of a text frame body.
+fn decode_strings(encoding: u8, data: &[u8]) -> Result<Vec<String>> {
+    match encoding {
+        // ISO-8859-1
+        0 => Ok(split_narrow(data)
+            .map(|s| s.iter().map(|&b| b as char).collect())
+            .collect()),
+        // UTF-16 with BOM, UTF-16BE
+        1 | 2 => split_wide(data)
+            .map(|s| {
+                let (big_endian, s) = match s {
+                    [0xFF, 0xFE, rest @ ..] => (false, rest),
+                    [0xFE, 0xFF, rest @ ..] => (true, rest),
+                    _ => (encoding == 2, s),
+                };
+                let units = s.chunks_exact(2).map(|pair| {
+                    if big_endian {
+                        u16::from_be_bytes([pair[0], pair[1]])
+                    } else {
+                        u16::from_le_bytes([pair[0], pair[1]])
+                    }
+                });
+                char::decode_utf16(units)
+                    .collect::<std::result::Result<String, _>>()
+                    .map_err(|_| invalid("invalid UTF-16 text"))
+            })
+            .collect(),
+        // UTF-8
+        3 => split_narrow(data)
+            .map(|s| String::from_utf8(s.to_vec()).map_err(|_| invalid("invalid UTF-8 text")))
+            .collect(),
+        other => Err(invalid(format!("unknown text encoding {other}"))),
+    }
+}
+
+/// Split on single null terminators, dropping a trailing empty string.
+fn split_narrow(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let data = data.strip_suffix(&[0]).unwrap_or(data);
+    data.split(|&b| b == 0)
+}
+
+/// Split on aligned double null terminators, dropping a trailing empty string.
+fn split_wide(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let data = data.strip_suffix(&[0, 0]).unwrap_or(data);
+    let mut rest = Some(data);
+    std::iter::from_fn(move || {
+        let current = rest?;
+        match (0..current.len() / 2).find(|i| current[i * 2] == 0 && current[i * 2 + 1] == 0) {
+            Some(i) => {
+                rest = Some(&current[i * 2 + 2..]);
+                Some(&current[..i * 2])
+            }
+            None => {
+                rest = None;
+                Some(current)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn encode_synchsafe(value: usize) -> [u8; 4] {
+        [
+            ((value >> 21) & 0x7F) as u8,
+            ((value >> 14) & 0x7F) as u8,
+            ((value >> 7) & 0x7F) as u8,
+            (value & 0x7F) as u8,
+        ]
+    }
+
+    pub(crate) fn frame(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend_from_slice(&encode_synchsafe(body.len()));
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// Build an ID3v2.4 tag from encoded frames, followed by `padding` zero bytes.
+    pub(crate) fn tag(frames: &[Vec<u8>], padding: usize) -> Vec<u8> {
+        let body_len: usize = frames.iter().map(Vec::len).sum::<usize>() + padding;
+        let mut out = b"ID3".to_vec();
+        out.extend_from_slice(&[4, 0, 0]);
+        out.extend_from_slice(&encode_synchsafe(body_len));
+        for frame in frames {
+            out.extend_from_slice(frame);
+        }
+        out.resize(out.len() + padding, 0);
+        out
+    }
+
+    #[test]
+    fn test_parse_txxx_tit2_priv() {
+        let data = Bytes::from(tag(
+            &[
+                frame(b"TXXX", b"\x03segment\0cue-42"),
+                frame(b"TIT2", b"\x00Live\xE9"),
+                frame(
+                    b"PRIV",
+                    b"com.apple.streaming.transportStreamTimestamp\0\0\0\0\0\0\x01\x5F\x90",
+                ),
+            ],
+            16,
+        ));
+
+        let parsed = Id3Tag::parse(&data).unwrap();
+        assert_eq!(parsed.major_version, 4);
+        assert_eq!(
+            parsed.frames,
+            vec![
+                Id3Frame::UserText {
+                    description: "segment".into(),
+                    value: "cue-42".into(),
+                },
+                Id3Frame::Text {
+                    id: "TIT2".into(),
+                    value: "Live\u{e9}".into(),
+                },
+                Id3Frame::Private {
+                    owner: "com.apple.streaming.transportStreamTimestamp".into(),
+                    data: Bytes::from_static(&[0, 0, 0, 0, 0, 1, 0x5F, 0x90]),
+                },
+            ]
+        );
+        assert_eq!(Id3Tag::tag_size(&data), Some(data.len()));
+    }
+
+    #[test]
+    fn test_parse_utf16_text() {
+        let mut body = vec![0x01, 0xFF, 0xFE];
+        for unit in "Title".encode_utf16() {
+            body.extend_from_slice(&unit.to_le_bytes());
+        }
+        body.extend_from_slice(&[0, 0]);
+        let data = Bytes::from(tag(&[frame(b"TIT2", &body)], 0));
+
+        let parsed = Id3Tag::parse(&data).unwrap();
+        assert_eq!(parsed.frames[0].id(), "TIT2");
+        assert_eq!(
+            parsed.frames[0],
+            Id3Frame::Text {
+                id: "TIT2".into(),
+                value: "Title".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_tags() {
+        // Not an ID3 tag
+        assert!(Id3Tag::parse(&Bytes::from_static(b"not an id3 tag")).is_err());
+
+        // Frame size larger than the tag
+        let mut oversized = tag(&[frame(b"TIT2", b"\x00abc")], 0);
+        oversized[ID3_HEADER_SIZE + 7] = 0x7F;
+        assert!(matches!(
+            Id3Tag::parse(&Bytes::from(oversized)),
+            Err(TsError::InvalidId3(_))
+        ));
+
+        // Truncated tag
+        let truncated = tag(&[frame(b"TIT2", b"\x00abc")], 0);
+        let truncated = Bytes::copy_from_slice(&truncated[..truncated.len() - 2]);
+        assert!(matches!(
+            Id3Tag::parse(&truncated),
+            Err(TsError::InsufficientData { .. })
+        ));
+
+        // Unknown text encoding
+        let encoding = Bytes::from(tag(&[frame(b"TIT2", b"\x09abc")], 0));
+        assert!(matches!(
+            Id3Tag::parse(&encoding),
+            Err(TsError::InvalidId3(_))
+        ));
+    }
+}
@@ -1,38 +1,62 @@
 //! Transport Stream (TS) parser for MPEG-2 Transport Stream data
 //!
 //! This crate provides functionality to parse Program Association Table (PAT),
-//! Program Map Table (PMT), PES headers, adaptation fields, descriptors,
-//! and SCTE-35 splice information from MPEG-TS (Transport Stream) data.
+//! Program Map Table (PMT), Service Description Table (SDT), PES headers,
+//! adaptation fields, descriptors, SCTE-35 splice information and ID3 timed
+//! metadata from MPEG-TS (Transport Stream) data, and a [`TsWriter`] for
+//! packetizing PAT/PMT sections and PES payloads. The [`pcr`] module times
+//! streams by their Program Clock References.
 
 pub mod adaptation_field;
+#[cfg(feature = "tokio")]
+pub mod async_reader;
 pub mod crc32;
 pub mod descriptor;
 pub mod error;
+pub mod id3;
 pub mod packet;
 pub mod parser_owned;
 pub mod parser_zero_copy;
 pub mod pat;
+pub mod pcr;
 pub mod pes;
 pub mod pmt;
+mod psi;
 pub mod scte35;
+pub mod sdt;
+pub mod section;
+mod table_update;
+pub mod timed_metadata;
+pub mod writer;
 
 pub use adaptation_field::{AdaptationField, AdaptationFieldRef, Pcr};
+#[cfg(feature = "tokio")]
+pub use async_reader::AsyncTsReader;
 pub use crc32::{mpeg2_crc32, validate_section_crc32};
-pub use descriptor::{Ac3Descriptor, DescriptorIterator, DescriptorRef, LanguageEntry};
-pub use error::TsError;
-pub use packet::{ContinuityMode, ContinuityStatus, PID_CAT, PID_NULL, PID_PAT, TsPacket};
+pub use descriptor::{
+    Ac3Descriptor, AvcVideoDescriptor, Descriptor, DescriptorIterator, DescriptorRef, Descriptors,
+    HevcVideoDescriptor, LanguageEntry, ServiceDescriptor, VideoStreamDescriptor, decode_dvb_text,
+};
+pub use error::{ErrorContext, TsError};
+pub use id3::{Id3Frame, Id3Tag};
+pub use packet::{ContinuityMode, ContinuityStatus, PID_CAT, PID_NULL, PID_PAT, PID_SDT, TsPacket};
 pub use parser_owned::OwnedTsParser;
 pub use parser_zero_copy::{
     PatProgramIterator, PatProgramRef, PatRef, PmtRef, PmtStreamIterator, PmtStreamRef,
     TsPacketRef, TsParser,
 };
 pub use pat::{Pat, PatProgram};
+pub use pcr::{PcrEvent, PcrTracker, estimate_duration, estimate_mux_bitrate};
 pub use pes::{PesHeader, PesHeaderRef};
-pub use pmt::{Pmt, PmtStream, StreamType};
+pub use pmt::{Codec, Pmt, PmtStream, StreamType};
 pub use scte35::{
     BreakDuration, SpliceCommand, SpliceCommandType, SpliceInfoSection, SpliceInfoSectionRef,
     SpliceInsert, TimeSignal,
 };
+pub use sdt::{Sdt, SdtService};
+pub use section::SectionAssembler;
+pub use timed_metadata::{TimedMetadata, TimedMetadataExtractor};
+pub use writer::TsWriter;
 
 /// Result type for TS parsing operations
 pub type Result<T> = std::result::Result<T, TsError>;
@@ -4,6 +4,9 @@ use bytes::Bytes;
 /// PAT PID (always 0x0000)
 pub const PID_PAT: u16 = 0x0000;
 
+/// SDT/BAT PID (always 0x0011)
+pub const PID_SDT: u16 = 0x0011;
+
 /// NULL PID (always 0x1FFF)
 pub const PID_NULL: u16 = 0x1FFF;
 
@@ -1,25 +1,30 @@
 use crate::{
-    error::TsError,
+    error::{ErrorContext, TsError},
     packet::{ContinuityMode, ContinuityStatus, PID_NULL, PID_PAT, TsPacket},
     pat::Pat,
     pmt::Pmt,
+    psi::{PsiSection, PsiTables},
+    table_update::TableUpdateCallbacks,
 };
-use bytes::{Buf, Bytes};
+use bytes::{Buf, Bytes, BytesMut};
 use memchr::memchr;
 use std::collections::HashMap;
 
+const TS_PACKET_SIZE: usize = 188;
+
+/// Default number of bytes [`OwnedTsParser::push_bytes`] may skip while
+/// searching for sync before giving up.
+pub const DEFAULT_RESYNC_LIMIT: usize = 64 * TS_PACKET_SIZE;
+
 /// Transport Stream parser for PAT and PMT tables
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct OwnedTsParser {
-    /// Cached PAT table
+    /// Cached PAT table, with the programs of all its sections
     pat: Option<Pat>,
-    /// Cached PMT tables by program number
-    pmts: HashMap<u16, Pmt>,
-    /// Buffer for incomplete PSI sections
-    psi_buffers: HashMap<u16, Vec<u8>>,
-    /// Current version numbers to detect updates
-    pat_version: Option<u8>,
-    pmt_versions: HashMap<u16, u8>, // program_number -> version
+    /// PAT sections and PMTs of the selected programs
+    psi: PsiTables<Pat, Pmt>,
+    /// Callbacks notified when a new PAT/PMT version is committed
+    table_callbacks: TableUpdateCallbacks<Pat, Pmt>,
     /// Whether to validate CRC-32/MPEG-2 on PAT/PMT sections
     validate_crc: bool,
     /// Continuity counter tracking per PID: pid -> last_cc
@@ -28,6 +33,40 @@ pub struct OwnedTsParser {
     continuity_issue_count: usize,
     continuity_duplicate_count: usize,
     continuity_discontinuity_count: usize,
+    /// Bytes received through `push_bytes` that do not yet form a full packet
+    pending: BytesMut,
+    /// Whether `pending` currently starts on a confirmed packet boundary
+    in_sync: bool,
+    /// Maximum consecutive bytes skipped while resynchronizing
+    resync_limit: usize,
+    /// Consecutive bytes skipped since the last valid packet
+    resync_run: usize,
+    /// Total bytes skipped while resynchronizing
+    skipped_bytes: usize,
+    /// Stream offset of the next byte to be consumed, reported in errors
+    stream_offset: u64,
+}
+
+impl Default for OwnedTsParser {
+    fn default() -> Self {
+        Self {
+            pat: None,
+            psi: PsiTables::default(),
+            table_callbacks: TableUpdateCallbacks::default(),
+            validate_crc: true,
+            continuity_counters: HashMap::new(),
+            continuity_mode: ContinuityMode::Disabled,
+            continuity_issue_count: 0,
+            continuity_duplicate_count: 0,
+            continuity_discontinuity_count: 0,
+            pending: BytesMut::new(),
+            in_sync: true,
+            resync_limit: DEFAULT_RESYNC_LIMIT,
+            resync_run: 0,
+            skipped_bytes: 0,
+            stream_offset: 0,
+        }
+    }
 }
 
 impl OwnedTsParser {
@@ -37,6 +76,9 @@ impl OwnedTsParser {
     }
 
     /// Enable or disable CRC-32/MPEG-2 validation on PAT/PMT sections.
+    ///
+    /// Enabled by default. Some encoders emit wrong CRCs; disable this to
+    /// accept their tables anyway.
     pub fn with_crc_validation(mut self, enable: bool) -> Self {
         self.validate_crc = enable;
         self
@@ -57,6 +99,66 @@ impl OwnedTsParser {
         self
     }
 
+    /// Register a callback invoked whenever a new PAT version is committed.
+    ///
+    /// It receives the previous PAT, if any, and the new one. Repeated
+    /// sections of the current version and sections that are not yet current
+    /// do not invoke it.
+    pub fn on_pat_update<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(Option<&Pat>, &Pat) + Send + 'static,
+    {
+        self.table_callbacks.set_pat(callback);
+        self
+    }
+
+    /// Register a callback invoked whenever a new PMT version is committed.
+    ///
+    /// It receives the program number, the previous PMT of that program, if
+    /// any, and the new one, so callers can tell which elementary streams
+    /// appeared or disappeared. A PAT update keeps the PMTs of programs whose
+    /// PMT PID did not change.
+    pub fn on_pmt_update<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(u16, Option<&Pmt>, &Pmt) + Send + 'static,
+    {
+        self.table_callbacks.set_pmt(callback);
+        self
+    }
+
+    /// Only process the given programs; an empty list removes the filter.
+    ///
+    /// The PAT is always processed to find the PMTs of the selected programs.
+    /// Packets of other programs are skipped before their payload is looked
+    /// at, and the PMTs of other programs are neither parsed nor reported.
+    pub fn set_program_filter(&mut self, program_numbers: &[u16]) {
+        self.psi.set_program_filter(program_numbers);
+    }
+
+    /// Only process packets of the given PIDs; an empty list removes the filter.
+    ///
+    /// The PAT and the PMTs of the selected programs are always processed.
+    pub fn set_pid_filter(&mut self, pids: &[u16]) {
+        self.psi.set_pid_filter(pids);
+    }
+
+    /// Set how many consecutive bytes `push_bytes` may discard while looking
+    /// for a sync byte before returning [`TsError::SyncLost`].
+    pub fn with_resync_limit(mut self, limit: usize) -> Self {
+        self.resync_limit = limit;
+        self
+    }
+
+    /// Total bytes discarded by `push_bytes` while resynchronizing.
+    pub fn skipped_bytes(&self) -> usize {
+        self.skipped_bytes
+    }
+
+    /// Bytes buffered by `push_bytes` that do not yet form a complete packet.
+    pub fn buffered_len(&self) -> usize {
+        self.pending.len()
+    }
+
     pub fn continuity_issue_count(&self) -> usize {
         self.continuity_issue_count
     }
@@ -143,7 +245,14 @@ impl OwnedTsParser {
     }
 
     /// Parse TS packets from bytes and extract PAT/PMT information
+    ///
+    /// Errors carry the PID and stream offset of the offending packet, see
+    /// [`TsError::context`]. Offsets count all input passed since the parser
+    /// was created or reset.
     pub fn parse_packets(&mut self, data: Bytes) -> Result<(), TsError> {
+        let input_offset = self.stream_offset;
+        let input_len = data.len();
+        self.stream_offset += input_len as u64;
         let mut remaining_data = data;
 
         while !remaining_data.is_empty() {
@@ -163,14 +272,8 @@ impl OwnedTsParser {
 
             match TsPacket::parse(chunk) {
                 Ok(packet) => {
-                    if self.continuity_mode != ContinuityMode::Disabled {
-                        let status = self.check_cc(&packet);
-                        self.handle_continuity_status(packet.pid, status)?;
-                    }
-
-                    if packet.payload_unit_start_indicator {
-                        self.process_packet(&packet)?;
-                    }
+                    let offset = input_offset + (input_len - remaining_data.len()) as u64;
+                    self.handle_packet(&packet, offset)?;
                     remaining_data.advance(188);
                 }
                 Err(_) => {
@@ -184,81 +287,185 @@ impl OwnedTsParser {
         Ok(())
     }
 
-    /// Process a single TS packet
-    fn process_packet(&mut self, packet: &TsPacket) -> Result<(), TsError> {
-        if let Some(psi_payload) = packet.get_psi_payload() {
-            if psi_payload.is_empty() {
-                return Ok(());
+    /// Feed an arbitrary chunk of a transport stream.
+    ///
+    /// Unlike [`parse_packets`](Self::parse_packets), chunks do not need to be
+    /// packet aligned: partial packets are buffered until the rest arrives.
+    /// When alignment is lost the parser scans forward for a sync byte that is
+    /// confirmed by another sync byte one packet later, counting discarded
+    /// bytes in [`skipped_bytes`](Self::skipped_bytes). If more than the
+    /// configured resync limit is discarded in a row, the buffer is dropped
+    /// and [`TsError::SyncLost`] is returned; later calls start afresh.
+    pub fn push_bytes(&mut self, data: &[u8]) -> Result<(), TsError> {
+        self.pending.extend_from_slice(data);
+
+        while !self.pending.is_empty() {
+            if self.pending[0] != 0x47 {
+                let skip = memchr(0x47, &self.pending).unwrap_or(self.pending.len());
+                self.skip_pending(skip)?;
+                continue;
             }
 
-            let table_id = psi_payload[0];
+            // After a resync, wait for the next sync byte to confirm the boundary.
+            let needed = if self.in_sync {
+                TS_PACKET_SIZE
+            } else {
+                TS_PACKET_SIZE + 1
+            };
+            if self.pending.len() < needed {
+                break;
+            }
+            if self.pending.len() > TS_PACKET_SIZE && self.pending[TS_PACKET_SIZE] != 0x47 {
+                self.skip_pending(1)?;
+                continue;
+            }
 
-            match packet.pid {
-                PID_PAT if table_id == 0x00 => {
-                    let pat = if self.validate_crc {
-                        Pat::parse_with_crc(&psi_payload)?
-                    } else {
-                        Pat::parse(&psi_payload)?
-                    };
-                    self.process_pat(pat)?;
-                }
-                pid if self.is_pmt_pid(pid) && table_id == 0x02 => {
-                    self.process_pmt(pid, &psi_payload)?;
-                }
-                _ => {
-                    // Not a PAT or PMT packet we are interested in
+            let chunk = self.pending.split_to(TS_PACKET_SIZE).freeze();
+            let offset = self.stream_offset;
+            self.stream_offset += TS_PACKET_SIZE as u64;
+            match TsPacket::parse(chunk) {
+                Ok(packet) => {
+                    self.in_sync = true;
+                    self.resync_run = 0;
+                    self.handle_packet(&packet, offset)?;
                 }
+                Err(_) => self.count_skipped(TS_PACKET_SIZE)?,
             }
         }
 
         Ok(())
     }
 
-    /// Check if a PID is a PMT PID based on current PAT
-    fn is_pmt_pid(&self, pid: u16) -> bool {
-        if let Some(pat) = &self.pat {
-            pat.programs.iter().any(|prog| prog.pmt_pid == pid)
-        } else {
-            false
+    /// Parse what [`push_bytes`](Self::push_bytes) still buffers at the end of
+    /// the input.
+    ///
+    /// After a resync the last packet waits for the next sync byte to confirm
+    /// its boundary; at the end of the input it is parsed as is. A trailing
+    /// partial packet is discarded and counted in
+    /// [`skipped_bytes`](Self::skipped_bytes). Later calls to `push_bytes`
+    /// start on a packet boundary.
+    pub fn flush(&mut self) -> Result<(), TsError> {
+        if self.pending.len() >= TS_PACKET_SIZE {
+            self.in_sync = true;
+            self.push_bytes(&[])?;
         }
+
+        let rest = self.pending.len();
+        self.pending.clear();
+        self.stream_offset += rest as u64;
+        self.skipped_bytes += rest;
+        self.in_sync = true;
+        self.resync_run = 0;
+        Ok(())
     }
 
-    /// Parse PAT from payload
-    fn process_pat(&mut self, pat: Pat) -> Result<(), TsError> {
-        let is_new = self.pat_version != Some(pat.version_number);
-        if is_new {
-            self.pat_version = Some(pat.version_number);
-            self.pmts.clear();
-            self.pmt_versions.clear();
-            self.pat = Some(pat);
+    fn skip_pending(&mut self, count: usize) -> Result<(), TsError> {
+        self.pending.advance(count);
+        self.stream_offset += count as u64;
+        self.count_skipped(count)
+    }
+
+    /// Account for `count` bytes discarded while resynchronizing, giving up
+    /// once the resync limit is exceeded
+    fn count_skipped(&mut self, count: usize) -> Result<(), TsError> {
+        self.in_sync = false;
+        self.skipped_bytes += count;
+        self.resync_run += count;
+        if self.resync_run > self.resync_limit {
+            let skipped = self.resync_run;
+            // Report where the skipped run started
+            let context = ErrorContext {
+                offset: Some(self.stream_offset - skipped as u64),
+                ..ErrorContext::default()
+            };
+            self.stream_offset += self.pending.len() as u64;
+            self.pending.clear();
+            self.resync_run = 0;
+            return Err(TsError::SyncLost { skipped }.in_context(context));
         }
         Ok(())
     }
 
-    /// Parse PMT from payload
-    fn process_pmt(&mut self, pid: u16, payload: &[u8]) -> Result<(), TsError> {
-        if let Some(pat) = &self.pat
-            && let Some(program) = pat.programs.iter().find(|p| p.pmt_pid == pid)
+    /// Process a packet starting at stream offset `offset`
+    fn handle_packet(&mut self, packet: &TsPacket, offset: u64) -> Result<(), TsError> {
+        if !self.psi.wants_pid(packet.pid) {
+            return Ok(());
+        }
+        if self.continuity_mode != ContinuityMode::Disabled {
+            let status = self.check_cc(packet);
+            self.handle_continuity_status(packet.pid, status)
+                .map_err(|e| e.in_context(ErrorContext::packet(packet.pid, offset)))?;
+        }
+
+        if (packet.pid == PID_PAT || self.psi.is_pmt_pid(packet.pid))
+            && let Some(payload) = &packet.payload
         {
-            let pmt = if self.validate_crc {
-                Pmt::parse_with_crc(payload)?
-            } else {
-                Pmt::parse(payload)?
-            };
-            let is_new = self
-                .pmt_versions
-                .get(&program.program_number)
-                .is_none_or(|&v| v != pmt.version_number);
-
-            if is_new {
-                self.pmt_versions
-                    .insert(program.program_number, pmt.version_number);
-                self.pmts.insert(program.program_number, pmt);
+            let sections =
+                self.psi
+                    .push_payload(packet.pid, payload, packet.payload_unit_start_indicator);
+            for section in sections {
+                let context = ErrorContext::section(packet.pid, offset, &section);
+                self.process_section(packet.pid, section)
+                    .map_err(|e| e.in_context(context))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process a complete PSI section
+    fn process_section(&mut self, pid: u16, section: Bytes) -> Result<(), TsError> {
+        let Some(&table_id) = section.first() else {
+            return Ok(());
+        };
+
+        match pid {
+            PID_PAT if table_id == 0x00 => {
+                let pat = Pat::parse_on_pid(pid, section, self.validate_crc)?;
+                self.process_pat(pat);
+            }
+            pid if self.psi.is_pmt_pid(pid) && table_id == 0x02 => {
+                let pmt = Pmt::parse_on_pid(pid, section, self.validate_crc)?;
+                self.process_pmt(pid, pmt);
+            }
+            _ => {
+                // Not a PAT or PMT section we are interested in
             }
         }
+
         Ok(())
     }
 
+    /// Apply a PAT section, see [`PsiTables::accept_pat`] for the version
+    /// rules. The committed PAT carries the programs of all its sections.
+    fn process_pat(&mut self, section: Pat) {
+        if self.psi.accept_pat(section).is_none() {
+            return;
+        }
+        let mut sections = self.psi.pat_sections().to_vec();
+        let mut pat = sections.remove(0);
+        for section in sections {
+            pat.programs.extend(section.programs);
+        }
+
+        let previous = self.pat.take();
+        self.table_callbacks.pat_updated(previous.as_ref(), &pat);
+        self.pat = Some(pat);
+        self.psi.retain_buffers(|_| false);
+    }
+
+    /// Apply a PMT section of the program carried on `pid`
+    fn process_pmt(&mut self, pid: u16, pmt: Pmt) {
+        let Some(program_number) = self.psi.program_of_pmt_pid(pid) else {
+            return;
+        };
+        if self.psi.is_new_pmt(program_number, &pmt) {
+            let previous = self.psi.commit_pmt(program_number, pmt);
+            let current = &self.psi.pmts()[&program_number];
+            self.table_callbacks
+                .pmt_updated(program_number, previous.as_ref(), current);
+        }
+    }
+
     /// Get the parsed PAT
     pub fn pat(&self) -> Option<&Pat> {
         self.pat.as_ref()
@@ -266,25 +473,27 @@ impl OwnedTsParser {
 
     /// Get all parsed PMTs
     pub fn pmts(&self) -> &HashMap<u16, Pmt> {
-        &self.pmts
+        self.psi.pmts()
     }
 
     /// Get a specific PMT by program number
     pub fn pmt(&self, program_number: u16) -> Option<&Pmt> {
-        self.pmts.get(&program_number)
+        self.psi.pmts().get(&program_number)
     }
 
-    /// Reset the parser state
+    /// Reset the parser state, keeping the program and PID filters
     pub fn reset(&mut self) {
         self.pat = None;
-        self.pmts.clear();
-        self.psi_buffers.clear();
-        self.pat_version = None;
-        self.pmt_versions.clear();
+        self.psi.reset();
         self.continuity_counters.clear();
         self.continuity_issue_count = 0;
         self.continuity_duplicate_count = 0;
         self.continuity_discontinuity_count = 0;
+        self.pending.clear();
+        self.in_sync = true;
+        self.resync_run = 0;
+        self.skipped_bytes = 0;
+        self.stream_offset = 0;
     }
 }
 
@@ -308,6 +517,372 @@ mod tests {
         data
     }
 
+    fn build_pat_pmt_stream() -> Vec<u8> {
+        use crate::{PatProgram, PmtStream, StreamType, TsWriter};
+
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        };
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: Vec::new(),
+            streams: vec![PmtStream {
+                stream_type: StreamType::H264,
+                elementary_pid: 0x100,
+                es_info: Vec::new(),
+            }],
+        };
+
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        writer.write_pat(&pat, &mut out).unwrap();
+        writer.write_pmt(0x1000, &pmt, &mut out).unwrap();
+        writer.write_pes(0x100, &[0u8; 300], &mut out).unwrap();
+        out
+    }
+
+    fn make_pmt(version_number: u8, current: bool, audio: bool) -> Pmt {
+        use crate::{PmtStream, StreamType};
+
+        let mut streams = vec![PmtStream {
+            stream_type: StreamType::H264,
+            elementary_pid: 0x100,
+            es_info: Vec::new(),
+        }];
+        if audio {
+            streams.push(PmtStream {
+                stream_type: StreamType::AdtsAac,
+                elementary_pid: 0x101,
+                es_info: Vec::new(),
+            });
+        }
+        Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number,
+            current_next_indicator: current,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: Vec::new(),
+            streams,
+        }
+    }
+
+    #[test]
+    fn test_pmt_version_bump_reports_added_stream_once() {
+        use crate::TsWriter;
+        use std::sync::{Arc, Mutex};
+
+        let mut writer = TsWriter::new();
+        let mut out = build_pat_pmt_stream();
+        writer
+            .write_pmt(0x1000, &make_pmt(0, true, false), &mut out)
+            .unwrap();
+        // Announced ahead of time, applied once it is sent as current
+        writer
+            .write_pmt(0x1000, &make_pmt(1, false, true), &mut out)
+            .unwrap();
+        for _ in 0..2 {
+            writer
+                .write_pmt(0x1000, &make_pmt(1, true, true), &mut out)
+                .unwrap();
+        }
+
+        let pat_updates = Arc::new(Mutex::new(0));
+        let pmt_updates = Arc::new(Mutex::new(Vec::new()));
+        let pat_sink = Arc::clone(&pat_updates);
+        let pmt_sink = Arc::clone(&pmt_updates);
+        let mut parser = OwnedTsParser::new()
+            .on_pat_update(move |previous, _| {
+                assert!(previous.is_none());
+                *pat_sink.lock().unwrap() += 1;
+            })
+            .on_pmt_update(move |program_number, previous, current| {
+                let stream_pids = |pmt: &Pmt| -> Vec<u16> {
+                    pmt.streams.iter().map(|s| s.elementary_pid).collect()
+                };
+                let previous_pids = previous.map(stream_pids).unwrap_or_default();
+                let added: Vec<u16> = stream_pids(current)
+                    .into_iter()
+                    .filter(|pid| !previous_pids.contains(pid))
+                    .collect();
+                pmt_sink.lock().unwrap().push((
+                    program_number,
+                    previous.map(|pmt| pmt.version_number),
+                    current.version_number,
+                    added,
+                ));
+            });
+        parser.parse_packets(Bytes::from(out)).unwrap();
+
+        assert_eq!(*pat_updates.lock().unwrap(), 1);
+        assert_eq!(
+            *pmt_updates.lock().unwrap(),
+            vec![(1, None, 0, vec![0x100]), (1, Some(0), 1, vec![0x101])]
+        );
+        assert_eq!(parser.pmt(1).unwrap().streams.len(), 2);
+    }
+
+    #[test]
+    fn test_pmt_spanning_several_packets() {
+        use crate::TsWriter;
+
+        let mut pmt = make_pmt(0, true, false);
+        pmt.streams = (0..117)
+            .map(|i| crate::PmtStream {
+                stream_type: crate::StreamType::H264,
+                elementary_pid: 0x100 + i,
+                es_info: Vec::new(),
+            })
+            .collect();
+        assert_eq!(pmt.encode_section().unwrap().len(), 601);
+
+        let mut writer = TsWriter::new();
+        let mut out = build_pat_pmt_stream();
+        writer
+            .write_pmt(0x1000, &make_pmt(1, true, false), &mut out)
+            .unwrap();
+        pmt.version_number = 2;
+        writer.write_pmt(0x1000, &pmt, &mut out).unwrap();
+
+        let mut parser = OwnedTsParser::new();
+        for chunk in out.chunks(100) {
+            parser.push_bytes(chunk).unwrap();
+        }
+
+        let pmt = parser.pmt(1).unwrap();
+        assert_eq!(pmt.version_number, 2);
+        assert_eq!(pmt.streams.len(), 117);
+        assert_eq!(pmt.streams[116].elementary_pid, 0x100 + 116);
+    }
+
+    #[test]
+    fn test_push_bytes_one_byte_at_a_time() {
+        let stream = build_pat_pmt_stream();
+        let mut parser = OwnedTsParser::new().with_continuity_mode(ContinuityMode::Strict);
+        for byte in &stream {
+            parser.push_bytes(std::slice::from_ref(byte)).unwrap();
+        }
+
+        assert_eq!(parser.skipped_bytes(), 0);
+        assert_eq!(parser.buffered_len(), 0);
+        assert_eq!(parser.pat().unwrap().get_pmt_pid(1), Some(0x1000));
+        assert_eq!(parser.pmt(1).unwrap().streams[0].elementary_pid, 0x100);
+    }
+
+    #[test]
+    fn test_push_bytes_resyncs_after_garbage() {
+        let stream = build_pat_pmt_stream();
+        // Garbage that includes a decoy sync byte
+        let mut data = vec![0x00, 0x13, 0x47, 0x99, 0x42, 0x10, 0x11];
+        let garbage_len = data.len();
+        data.extend_from_slice(&stream);
+
+        let mut parser = OwnedTsParser::new();
+        for chunk in data.chunks(50) {
+            parser.push_bytes(chunk).unwrap();
+        }
+
+        assert_eq!(parser.skipped_bytes(), garbage_len);
+        assert!(parser.pat().is_some());
+        assert!(parser.pmt(1).is_some());
+    }
+
+    #[test]
+    fn test_push_bytes_gives_up_after_resync_limit() {
+        let mut parser = OwnedTsParser::new().with_resync_limit(100);
+        let err = parser.push_bytes(&[0u8; 101]).unwrap_err();
+        assert!(matches!(
+            err.without_context(),
+            TsError::SyncLost { skipped: 101 }
+        ));
+        assert_eq!(err.context().offset, Some(0));
+        assert_eq!(parser.buffered_len(), 0);
+
+        // A clean stream afterwards is parsed normally
+        parser.push_bytes(&build_pat_pmt_stream()).unwrap();
+        assert!(parser.pat().is_some());
+    }
+
+    #[test]
+    fn test_flush_parses_last_packet_after_resync() {
+        let stream = build_pat_pmt_stream();
+        let mut data = vec![0x00, 0x13, 0x47, 0x99];
+        data.extend_from_slice(&stream[..TS_PACKET_SIZE]);
+
+        let mut parser = OwnedTsParser::new();
+        parser.push_bytes(&data).unwrap();
+        // The PAT packet waits for a sync byte that never comes
+        assert!(parser.pat().is_none());
+        assert_eq!(parser.buffered_len(), TS_PACKET_SIZE);
+
+        parser.flush().unwrap();
+        assert_eq!(parser.buffered_len(), 0);
+        assert_eq!(parser.skipped_bytes(), 4);
+        assert_eq!(parser.pat().unwrap().get_pmt_pid(1), Some(0x1000));
+
+        // A partial packet at the end of the input is discarded
+        parser
+            .push_bytes(&stream[TS_PACKET_SIZE..TS_PACKET_SIZE + 50])
+            .unwrap();
+        parser.flush().unwrap();
+        assert_eq!(parser.buffered_len(), 0);
+        assert_eq!(parser.skipped_bytes(), 4 + 50);
+
+        // The next input starts on a packet boundary
+        parser.push_bytes(&stream[TS_PACKET_SIZE..]).unwrap();
+        assert!(parser.pmt(1).is_some());
+        assert_eq!(parser.skipped_bytes(), 4 + 50);
+    }
+
+    #[test]
+    fn test_unparseable_packets_count_toward_resync_limit() {
+        // Sync bytes one packet apart, with an adaptation field past the packet end
+        let mut packet = make_ts_packet(0x100, 0, 0x02);
+        packet[4] = 200;
+        let data = packet.repeat(3);
+
+        let mut parser = OwnedTsParser::new().with_resync_limit(300);
+        let err = parser.push_bytes(&data).unwrap_err();
+        assert!(matches!(
+            err.without_context(),
+            TsError::SyncLost { skipped: 376 }
+        ));
+        assert_eq!(err.context().offset, Some(0));
+        assert_eq!(parser.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_crc_mismatch_reports_pid() {
+        // PAT captured from an ffmpeg mpegts mux, with its CRC_32 corrupted
+        let pat = [
+            0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1,
+            0x04, 0xB3,
+        ];
+        let mut packet = vec![0xFF; 188];
+        packet[..5].copy_from_slice(&[0x47, 0x40, 0x00, 0x10, 0x00]);
+        packet[5..5 + pat.len()].copy_from_slice(&pat);
+
+        let mut parser = OwnedTsParser::new();
+        let err = parser
+            .parse_packets(Bytes::from(packet.clone()))
+            .unwrap_err();
+        assert!(matches!(
+            err.without_context(),
+            TsError::Crc32Mismatch {
+                expected: 0x2AB1_04B3,
+                calculated: 0x2AB1_04B2,
+            }
+        ));
+        assert_eq!(err.context().pid, Some(PID_PAT));
+
+        let mut parser = OwnedTsParser::new().with_crc_validation(false);
+        parser.parse_packets(Bytes::from(packet)).unwrap();
+        assert_eq!(parser.pat().unwrap().get_pmt_pid(1), Some(0x1000));
+    }
+
+    #[test]
+    fn test_errors_report_stream_offsets() {
+        let mut stream = build_pat_pmt_stream();
+        // Corrupt the PCR PID of the PMT, carried by the second packet
+        stream[188 + 5 + 8] ^= 0x01;
+        let mut data = vec![0x00, 0x13, 0x47, 0x99];
+        data.extend_from_slice(&stream);
+
+        let mut parser = OwnedTsParser::new();
+        let err = data
+            .chunks(50)
+            .map(|chunk| parser.push_bytes(chunk))
+            .find_map(Result::err)
+            .unwrap();
+        assert!(matches!(
+            err.without_context(),
+            TsError::Crc32Mismatch { .. }
+        ));
+        assert_eq!(
+            err.context(),
+            ErrorContext {
+                pid: Some(0x1000),
+                offset: Some(4 + 188),
+                table_id: Some(0x02),
+                section_number: Some(0),
+            }
+        );
+        assert!(
+            err.to_string()
+                .starts_with("pid=0x1000 offset=192 table=0x02 section=0: CRC32 mismatch"),
+            "{err}"
+        );
+
+        // Offsets keep counting across calls
+        let mut bytes = make_ts_packet(0x0033, 0, 0x01);
+        bytes.extend_from_slice(&make_ts_packet(0x0033, 2, 0x01));
+        let mut parser = OwnedTsParser::new().with_continuity_mode(ContinuityMode::Strict);
+        parser
+            .parse_packets(Bytes::from(stream[..188].to_vec()))
+            .unwrap();
+        let err = parser.parse_packets(Bytes::from(bytes)).unwrap_err();
+        assert_eq!(err.context(), ErrorContext::packet(0x0033, 2 * 188));
+    }
+
+    #[test]
+    fn test_program_filter() {
+        use crate::TsWriter;
+
+        let mut writer = TsWriter::new();
+        let mut second = make_pmt(0, true, true);
+        second.program_number = 2;
+        let mut stream = Vec::new();
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 1,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![
+                crate::PatProgram {
+                    program_number: 1,
+                    pmt_pid: 0x1000,
+                },
+                crate::PatProgram {
+                    program_number: 2,
+                    pmt_pid: 0x1001,
+                },
+            ],
+        };
+        writer.write_pat(&pat, &mut stream).unwrap();
+        writer
+            .write_pmt(0x1000, &make_pmt(0, true, false), &mut stream)
+            .unwrap();
+        writer.write_pmt(0x1001, &second, &mut stream).unwrap();
+
+        let mut parser = OwnedTsParser::new();
+        parser.set_program_filter(&[2]);
+        parser.parse_packets(Bytes::from(stream)).unwrap();
+
+        assert_eq!(parser.pat().unwrap().programs.len(), 2);
+        assert_eq!(parser.pmts().len(), 1);
+        assert_eq!(parser.pmt(2).unwrap().streams.len(), 2);
+        assert!(parser.pmt(1).is_none());
+    }
+
     #[test]
     fn test_parser_creation() {
         let parser = OwnedTsParser::new();
@@ -340,12 +915,12 @@ mod tests {
         let mut parser = OwnedTsParser::new().with_continuity_mode(ContinuityMode::Strict);
         let err = parser.parse_packets(Bytes::from(bytes)).unwrap_err();
         assert!(matches!(
-            err,
+            err.without_context(),
             TsError::ContinuityError {
                 pid: p,
                 expected: 1,
                 actual: 2
-            } if p == pid
+            } if *p == pid
         ));
     }
 
@@ -358,7 +933,10 @@ mod tests {
 
         let mut parser = OwnedTsParser::new().with_continuity_mode(ContinuityMode::Strict);
         let err = parser.parse_packets(Bytes::from(bytes)).unwrap_err();
-        assert!(matches!(err, TsError::DuplicatePacket { pid: p, cc: 0 } if p == pid));
+        assert!(matches!(
+            err.without_context(),
+            TsError::DuplicatePacket { pid: p, cc: 0 } if *p == pid
+        ));
     }
 
     #[test]
@@ -379,12 +957,12 @@ mod tests {
         let mut parser = OwnedTsParser::new().with_continuity_mode(ContinuityMode::Strict);
         let err = parser.parse_packets(Bytes::from(bytes)).unwrap_err();
         assert!(matches!(
-            err,
+            err.without_context(),
             TsError::ContinuityError {
                 pid: p,
                 expected: 5,
                 actual: 6
-            } if p == pid
+            } if *p == pid
         ));
     }
 }
@@ -1,10 +1,15 @@
-use crate::{ContinuityMode, Result, StreamType, TsError};
-use bytes::{Buf, Bytes, BytesMut};
+use crate::{
+    Codec, ContinuityMode, ErrorContext, Pat, PatProgram, Pmt, PmtStream, Result, StreamType,
+    TsError,
+    packet::{PID_PAT, PID_SDT},
+    psi::{PID_SPACE, PsiSection, PsiTables},
+    sdt::{Sdt, TABLE_ID_SDT_ACTUAL, TABLE_ID_SDT_OTHER},
+    table_update::TableUpdateCallbacks,
+};
+use bytes::{Buf, Bytes};
 use memchr::memchr_iter;
 use std::collections::{HashMap, HashSet};
 
-const PID_SPACE: usize = 8192;
-
 /// Zero-copy TS packet parser
 #[derive(Debug, Clone)]
 pub struct TsPacketRef {
@@ -212,26 +217,7 @@ impl PatRef {
 
     /// Parse PAT from PSI section data with CRC-32/MPEG-2 validation.
     pub fn parse_with_crc(data: Bytes) -> Result<Self> {
-        if data.len() >= 7 {
-            let section_length = ((data[1] as u16 & 0x0F) << 8) | data[2] as u16;
-            let section_end = 3 + section_length as usize;
-            if section_end <= data.len()
-                && section_end >= 4
-                && !crate::crc32::validate_section_crc32(&data[..section_end])
-            {
-                let stored = u32::from_be_bytes([
-                    data[section_end - 4],
-                    data[section_end - 3],
-                    data[section_end - 2],
-                    data[section_end - 1],
-                ]);
-                let calculated = crate::crc32::mpeg2_crc32(&data[..section_end - 4]);
-                return Err(TsError::Crc32Mismatch {
-                    expected: stored,
-                    calculated,
-                });
-            }
-        }
+        crate::crc32::verify_section_crc32(&data)?;
         Self::parse(data)
     }
 
@@ -273,6 +259,26 @@ impl Iterator for PatProgramIterator {
     }
 }
 
+impl From<&PatRef> for Pat {
+    fn from(pat: &PatRef) -> Self {
+        Pat {
+            table_id: pat.table_id,
+            transport_stream_id: pat.transport_stream_id,
+            version_number: pat.version_number,
+            current_next_indicator: pat.current_next_indicator,
+            section_number: pat.section_number,
+            last_section_number: pat.last_section_number,
+            programs: pat
+                .programs()
+                .map(|program| PatProgram {
+                    program_number: program.program_number,
+                    pmt_pid: program.pmt_pid,
+                })
+                .collect(),
+        }
+    }
+}
+
 /// Zero-copy PAT program entry
 #[derive(Debug, Clone, Copy)]
 pub struct PatProgramRef {
@@ -377,26 +383,7 @@ impl PmtRef {
 
     /// Parse PMT from PSI section data with CRC-32/MPEG-2 validation.
     pub fn parse_with_crc(data: Bytes) -> Result<Self> {
-        if data.len() >= 7 {
-            let section_length = ((data[1] as u16 & 0x0F) << 8) | data[2] as u16;
-            let section_end = 3 + section_length as usize;
-            if section_end <= data.len()
-                && section_end >= 4
-                && !crate::crc32::validate_section_crc32(&data[..section_end])
-            {
-                let stored = u32::from_be_bytes([
-                    data[section_end - 4],
-                    data[section_end - 3],
-                    data[section_end - 2],
-                    data[section_end - 1],
-                ]);
-                let calculated = crate::crc32::mpeg2_crc32(&data[..section_end - 4]);
-                return Err(TsError::Crc32Mismatch {
-                    expected: stored,
-                    calculated,
-                });
-            }
-        }
+        crate::crc32::verify_section_crc32(&data)?;
         Self::parse(data)
     }
 
@@ -416,9 +403,79 @@ impl PmtRef {
         }
     }
 
-    /// Iterate over program info descriptors.
-    pub fn program_descriptors(&self) -> crate::descriptor::DescriptorIterator {
-        crate::descriptor::DescriptorIterator::new(self.program_info())
+    /// Iterate over typed program info descriptors.
+    pub fn program_descriptors(&self) -> crate::descriptor::Descriptors {
+        crate::descriptor::Descriptors::new(self.program_info())
+    }
+
+    /// Stream type of `stream` refined with its descriptors and the program
+    /// descriptors, see [`StreamType::classify_with_descriptors`].
+    pub fn classify_stream(&self, stream: &PmtStreamRef) -> StreamType {
+        stream
+            .stream_type
+            .classify_with_descriptor_loops(&stream.es_info, self.program_info_slice())
+    }
+
+    /// Video streams, without allocating. Malformed stream entries are left out.
+    pub fn video_streams(&self) -> impl Iterator<Item = PmtStreamRef> + '_ {
+        self.streams()
+            .flatten()
+            .filter(|s| self.classify_stream(s).is_video())
+    }
+
+    /// Audio streams, including those only identified by descriptor, without
+    /// allocating. Malformed stream entries are left out.
+    pub fn audio_streams(&self) -> impl Iterator<Item = PmtStreamRef> + '_ {
+        self.streams()
+            .flatten()
+            .filter(|s| self.classify_stream(s).is_audio())
+    }
+
+    /// First video stream in PMT order
+    pub fn first_video(&self) -> Option<PmtStreamRef> {
+        self.video_streams().next()
+    }
+
+    /// First audio stream in PMT order
+    pub fn first_audio(&self) -> Option<PmtStreamRef> {
+        self.audio_streams().next()
+    }
+
+    /// First stream carrying `codec`
+    pub fn find_by_codec(&self, codec: Codec) -> Option<PmtStreamRef> {
+        self.streams()
+            .flatten()
+            .find(|s| self.classify_stream(s).codec() == Some(codec))
+    }
+
+    fn program_info_slice(&self) -> &[u8] {
+        &self.data[self.program_info_offset..self.program_info_offset + self.program_info_length]
+    }
+}
+
+/// Malformed stream entries are left out, as when iterating [`PmtRef::streams`]
+/// with `flatten`.
+impl From<&PmtRef> for Pmt {
+    fn from(pmt: &PmtRef) -> Self {
+        Pmt {
+            table_id: pmt.table_id,
+            program_number: pmt.program_number,
+            version_number: pmt.version_number,
+            current_next_indicator: pmt.current_next_indicator,
+            section_number: pmt.section_number,
+            last_section_number: pmt.last_section_number,
+            pcr_pid: pmt.pcr_pid,
+            program_info: pmt.program_info().to_vec(),
+            streams: pmt
+                .streams()
+                .flatten()
+                .map(|stream| PmtStream {
+                    stream_type: stream.stream_type,
+                    elementary_pid: stream.elementary_pid,
+                    es_info: stream.es_info.to_vec(),
+                })
+                .collect(),
+        }
     }
 }
 
@@ -441,9 +498,12 @@ impl Iterator for PmtStreamIterator {
             self.data.advance(5);
 
             if self.data.remaining() < es_info_length {
+                let actual = self.data.remaining();
+                // Stop after reporting a stream that runs past the section
+                self.data.clear();
                 return Some(Err(TsError::InsufficientData {
                     expected: es_info_length,
-                    actual: self.data.remaining(),
+                    actual,
                 }));
             }
 
@@ -454,6 +514,14 @@ impl Iterator for PmtStreamIterator {
                 elementary_pid,
                 es_info,
             }))
+        } else if self.data.has_remaining() {
+            // A partial stream entry at the end of the loop
+            let actual = self.data.remaining();
+            self.data.clear();
+            Some(Err(TsError::InsufficientData {
+                expected: 5,
+                actual,
+            }))
         } else {
             None
         }
@@ -469,24 +537,31 @@ pub struct PmtStreamRef {
 }
 
 impl PmtStreamRef {
-    /// Iterate over ES info descriptors.
-    pub fn descriptors(&self) -> crate::descriptor::DescriptorIterator {
-        crate::descriptor::DescriptorIterator::new(self.es_info.clone())
+    /// Iterate over typed ES info descriptors.
+    pub fn descriptors(&self) -> crate::descriptor::Descriptors {
+        crate::descriptor::Descriptors::new(self.es_info.clone())
+    }
+}
+
+/// Surface CRC failures for a PSI section while ignoring other malformed tables.
+fn checked_section<T>(context: ErrorContext, result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(section) => Ok(Some(section)),
+        Err(e) if matches!(e.without_context(), TsError::Crc32Mismatch { .. }) => {
+            Err(e.in_context(context))
+        }
+        Err(_) => Ok(None),
     }
 }
 
 /// Zero-copy streaming TS parser with minimal memory footprint
 #[derive(Debug)]
 pub struct TsParser {
-    /// Program mapping: program_number -> pmt_pid
-    program_pids: HashMap<u16, u16>,
-    /// Reverse PMT PID lookup: pmt_pid -> program_number
-    pmt_pids: HashMap<u16, u16>,
-    /// Fast PMT PID membership table
-    pmt_pid_flags: [bool; PID_SPACE],
-    /// Current version numbers to detect updates
-    pat_version: Option<u8>,
-    pmt_versions: HashMap<u16, u8>, // program_number -> version
+    /// PAT sections and PMTs of the selected programs, whose versions are
+    /// used to detect updates
+    psi: PsiTables<PatRef, PmtRef>,
+    /// Callbacks notified when a new PAT/PMT version is committed
+    table_callbacks: TableUpdateCallbacks<PatRef, PmtRef>,
     /// Whether to validate CRC-32/MPEG-2 on PAT/PMT sections
     validate_crc: bool,
     /// Last continuity counter value for each PID
@@ -505,19 +580,20 @@ pub struct TsParser {
     scte35_pids: HashSet<u16>,
     /// Fast SCTE-35 PID membership table
     scte35_pid_flags: [bool; PID_SPACE],
-    /// Buffers for incomplete PSI sections, keyed by PID
-    psi_buffers: HashMap<u16, BytesMut>,
+    /// SDT versions keyed by (table_id, transport_stream_id, section_number)
+    sdt_versions: HashMap<(u8, u16, u8), u8>,
+    /// Bytes of input passed to the parser so far
+    stream_offset: u64,
+    /// Stream offset of the packet being processed, reported in errors
+    packet_offset: u64,
 }
 
 impl Default for TsParser {
     fn default() -> Self {
         Self {
-            program_pids: HashMap::new(),
-            pmt_pids: HashMap::new(),
-            pmt_pid_flags: [false; PID_SPACE],
-            pat_version: None,
-            pmt_versions: HashMap::new(),
-            validate_crc: false,
+            psi: PsiTables::default(),
+            table_callbacks: TableUpdateCallbacks::default(),
+            validate_crc: true,
             continuity_counters: [0; PID_SPACE],
             continuity_seen: [false; PID_SPACE],
             continuity_mode: ContinuityMode::Disabled,
@@ -526,20 +602,22 @@ impl Default for TsParser {
             continuity_discontinuity_count: 0,
             scte35_pids: HashSet::new(),
             scte35_pid_flags: [false; PID_SPACE],
-            psi_buffers: HashMap::new(),
+            sdt_versions: HashMap::new(),
+            stream_offset: 0,
+            packet_offset: 0,
         }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
-enum PacketFormat {
+pub(crate) enum PacketFormat {
     Ts188,
     M2ts192,
     Ts204,
 }
 
 impl PacketFormat {
-    const fn packet_size(self) -> usize {
+    pub(crate) const fn packet_size(self) -> usize {
         match self {
             Self::Ts188 => 188,
             Self::M2ts192 => 192,
@@ -547,7 +625,7 @@ impl PacketFormat {
         }
     }
 
-    const fn sync_offset(self) -> usize {
+    pub(crate) const fn sync_offset(self) -> usize {
         match self {
             Self::Ts188 => 0,
             Self::M2ts192 => 4,
@@ -557,9 +635,7 @@ impl PacketFormat {
 }
 
 impl TsParser {
-    const MAX_PSI_SECTION_LENGTH: usize = 0x0FFF;
-    const MAX_PSI_BUFFER_SIZE: usize = 64 * 1024;
-    const PACKET_FORMATS: [PacketFormat; 3] = [
+    pub(crate) const PACKET_FORMATS: [PacketFormat; 3] = [
         PacketFormat::Ts188,
         PacketFormat::M2ts192,
         PacketFormat::Ts204,
@@ -586,7 +662,7 @@ impl TsParser {
         None
     }
 
-    fn packet_starts_at(data: &Bytes, offset: usize, format: PacketFormat) -> bool {
+    pub(crate) fn packet_starts_at(data: &[u8], offset: usize, format: PacketFormat) -> bool {
         let packet_size = format.packet_size();
         let sync_offset = format.sync_offset();
 
@@ -665,7 +741,12 @@ impl TsParser {
         }
     }
 
-    /// Enable or disable CRC-32/MPEG-2 validation on PAT/PMT sections.
+    /// Enable or disable CRC-32/MPEG-2 validation on PAT/PMT/SDT sections.
+    ///
+    /// Enabled by default, in which case a corrupted section fails parsing with
+    /// [`TsError::Crc32Mismatch`], in the context of the PID of the section.
+    /// Some encoders emit wrong CRCs; disable this to
+    /// accept their tables anyway.
     pub fn with_crc_validation(mut self, enable: bool) -> Self {
         self.validate_crc = enable;
         self
@@ -687,6 +768,35 @@ impl TsParser {
         self
     }
 
+    /// Register a callback invoked whenever a new PAT version is committed.
+    ///
+    /// It receives the previous PAT, if any, and the new one; for a PAT split
+    /// across sections it is invoked per section with the section of the same
+    /// number from the previous version. Repeated
+    /// sections of the current version and sections that are not yet current
+    /// do not invoke it.
+    pub fn on_pat_update<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(Option<&PatRef>, &PatRef) + Send + 'static,
+    {
+        self.table_callbacks.set_pat(callback);
+        self
+    }
+
+    /// Register a callback invoked whenever a new PMT version is committed.
+    ///
+    /// It receives the program number, the previous PMT of that program, if
+    /// any, and the new one, so callers can tell which elementary streams
+    /// appeared or disappeared. A PAT update keeps the PMTs of programs whose
+    /// PMT PID did not change.
+    pub fn on_pmt_update<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(u16, Option<&PmtRef>, &PmtRef) + Send + 'static,
+    {
+        self.table_callbacks.set_pmt(callback);
+        self
+    }
+
     /// Number of continuity issues observed during parsing.
     pub fn continuity_issue_count(&self) -> usize {
         self.continuity_issue_count
@@ -754,6 +864,10 @@ impl TsParser {
     }
 
     /// Parse TS packets with zero-copy approach and call handlers for found PSI
+    ///
+    /// Errors raised by the parser carry the PID and stream offset of the
+    /// offending packet, see [`TsError::context`]. Offsets count all input
+    /// passed since the parser was created or reset.
     pub fn parse_packets<F, G, H>(
         &mut self,
         data: Bytes,
@@ -772,6 +886,7 @@ impl TsParser {
             on_pmt,
             on_packet,
             None::<fn(crate::scte35::SpliceInfoSectionRef) -> Result<()>>,
+            None::<fn(Sdt) -> Result<()>>,
         )
     }
 
@@ -793,27 +908,68 @@ impl TsParser {
         H: FnMut(&TsPacketRef) -> Result<()>,
         S: FnMut(crate::scte35::SpliceInfoSectionRef) -> Result<()>,
     {
-        self.parse_packets_inner(data, on_pat, on_pmt, on_packet, Some(on_scte35))
+        self.parse_packets_inner(
+            data,
+            on_pat,
+            on_pmt,
+            on_packet,
+            Some(on_scte35),
+            None::<fn(Sdt) -> Result<()>>,
+        )
+    }
+
+    /// Parse TS packets with Service Description Table support.
+    ///
+    /// SDT sections on PID 0x0011 are reassembled across packets and passed to
+    /// `on_sdt` whenever a new version of a section is seen. Both the actual
+    /// (0x42) and other (0x46) tables are reported; see [`Sdt::is_actual`].
+    pub fn parse_packets_with_sdt<F, G, H, D>(
+        &mut self,
+        data: Bytes,
+        on_pat: F,
+        on_pmt: G,
+        on_packet: Option<H>,
+        on_sdt: D,
+    ) -> Result<()>
+    where
+        F: FnMut(PatRef) -> Result<()>,
+        G: FnMut(PmtRef) -> Result<()>,
+        H: FnMut(&TsPacketRef) -> Result<()>,
+        D: FnMut(Sdt) -> Result<()>,
+    {
+        self.parse_packets_inner(
+            data,
+            on_pat,
+            on_pmt,
+            on_packet,
+            None::<fn(crate::scte35::SpliceInfoSectionRef) -> Result<()>>,
+            Some(on_sdt),
+        )
     }
 
-    fn parse_packets_inner<F, G, H, S>(
+    fn parse_packets_inner<F, G, H, S, D>(
         &mut self,
         mut data: Bytes,
         mut on_pat: F,
         mut on_pmt: G,
         mut on_packet: Option<H>,
         mut on_scte35: Option<S>,
+        mut on_sdt: Option<D>,
     ) -> Result<()>
     where
         F: FnMut(PatRef) -> Result<()>,
         G: FnMut(PmtRef) -> Result<()>,
         H: FnMut(&TsPacketRef) -> Result<()>,
         S: FnMut(crate::scte35::SpliceInfoSectionRef) -> Result<()>,
+        D: FnMut(Sdt) -> Result<()>,
     {
         self.continuity_issue_count = 0;
         self.continuity_duplicate_count = 0;
         self.continuity_discontinuity_count = 0;
         let mut locked_format: Option<PacketFormat> = None;
+        let input_offset = self.stream_offset;
+        let input_len = data.len();
+        self.stream_offset += input_len as u64;
 
         while !data.is_empty() {
             let packet_format = if let Some(format) = locked_format {
@@ -861,11 +1017,23 @@ impl TsParser {
                 continue;
             };
 
+            // Skip filtered-out PIDs before any further parsing
+            let pid = ((chunk[1] as u16 & 0x1F) << 8) | chunk[2] as u16;
+            if !self.psi.wants_pid(pid) {
+                data.advance(packet_size);
+                continue;
+            }
+
             if let Ok(packet) = TsPacketRef::parse(chunk) {
+                self.packet_offset = input_offset + (input_len - data.len()) as u64;
+
                 // Check continuity counter if enabled
                 if self.continuity_mode != ContinuityMode::Disabled {
                     let status = self.check_cc(&packet);
-                    self.handle_continuity_status(packet.pid, status)?;
+                    self.handle_continuity_status(packet.pid, status)
+                        .map_err(|e| {
+                            e.in_context(ErrorContext::packet(packet.pid, self.packet_offset))
+                        })?;
                 }
 
                 // Successfully parsed a packet.
@@ -873,7 +1041,8 @@ impl TsParser {
                     on_packet_cb(&packet)?;
                 }
 
-                if self.is_relevant_psi_pid(packet.pid)
+                if (self.is_relevant_psi_pid(packet.pid)
+                    || (on_sdt.is_some() && packet.pid == PID_SDT))
                     && let Some(payload) = packet.payload()
                 {
                     self.process_packet_psi_payload(
@@ -883,6 +1052,7 @@ impl TsParser {
                         &mut on_pat,
                         &mut on_pmt,
                         &mut on_scte35,
+                        &mut on_sdt,
                     )?;
                 }
                 data.advance(packet_size);
@@ -899,12 +1069,13 @@ impl TsParser {
     #[inline]
     fn is_relevant_psi_pid(&self, pid: u16) -> bool {
         let pid_idx = pid as usize;
-        pid == 0x0000
-            || (pid_idx < PID_SPACE
-                && (self.pmt_pid_flags[pid_idx] || self.scte35_pid_flags[pid_idx]))
+        pid == PID_PAT
+            || self.psi.is_pmt_pid(pid)
+            || (pid_idx < PID_SPACE && self.scte35_pid_flags[pid_idx])
     }
 
-    fn process_packet_psi_payload<F, G, S>(
+    #[allow(clippy::too_many_arguments)]
+    fn process_packet_psi_payload<F, G, S, D>(
         &mut self,
         pid: u16,
         payload: Bytes,
@@ -912,123 +1083,45 @@ impl TsParser {
         on_pat: &mut F,
         on_pmt: &mut G,
         on_scte35: &mut Option<S>,
+        on_sdt: &mut Option<D>,
     ) -> Result<()>
     where
         F: FnMut(PatRef) -> Result<()>,
         G: FnMut(PmtRef) -> Result<()>,
         S: FnMut(crate::scte35::SpliceInfoSectionRef) -> Result<()>,
+        D: FnMut(Sdt) -> Result<()>,
     {
-        if payload.is_empty() {
-            return Ok(());
-        }
-
-        if payload_unit_start_indicator {
-            let pointer_field = payload[0] as usize;
-            let pointer_end = 1 + pointer_field;
-            if pointer_end > payload.len() {
-                return Ok(());
-            }
-
-            if pointer_field > 0 {
-                self.append_psi_bytes(pid, &payload[1..pointer_end], on_pat, on_pmt, on_scte35)?;
-            }
-
-            if pointer_end < payload.len() {
-                if let Some(buffer) = self.psi_buffers.get_mut(&pid)
-                    && !buffer.is_empty()
-                {
-                    buffer.clear();
-                }
-                self.append_psi_bytes(pid, &payload[pointer_end..], on_pat, on_pmt, on_scte35)?;
-            }
-        } else {
-            self.append_psi_bytes(pid, &payload, on_pat, on_pmt, on_scte35)?;
-        }
-
-        Ok(())
-    }
-
-    fn append_psi_bytes<F, G, S>(
-        &mut self,
-        pid: u16,
-        data: &[u8],
-        on_pat: &mut F,
-        on_pmt: &mut G,
-        on_scte35: &mut Option<S>,
-    ) -> Result<()>
-    where
-        F: FnMut(PatRef) -> Result<()>,
-        G: FnMut(PmtRef) -> Result<()>,
-        S: FnMut(crate::scte35::SpliceInfoSectionRef) -> Result<()>,
-    {
-        if data.is_empty() {
-            return Ok(());
-        }
-
-        let sections = {
-            let buffer = self.psi_buffers.entry(pid).or_default();
-            buffer.extend_from_slice(data);
-            if buffer.len() > Self::MAX_PSI_BUFFER_SIZE {
-                buffer.clear();
-                return Ok(());
-            }
-            let mut sections = Vec::new();
-
-            loop {
-                let stuffing_prefix = buffer.iter().take_while(|&&b| b == 0xFF).count();
-                if stuffing_prefix > 0 {
-                    let _ = buffer.split_to(stuffing_prefix);
-                }
-
-                if buffer.len() < 3 {
-                    break;
-                }
-
-                let section_length = (((buffer[1] as usize) & 0x0F) << 8) | buffer[2] as usize;
-                if section_length > Self::MAX_PSI_SECTION_LENGTH {
-                    let _ = buffer.split_to(1);
-                    continue;
-                }
-
-                let section_size = 3 + section_length;
-                if buffer.len() < section_size {
-                    break;
-                }
-
-                sections.push(buffer.split_to(section_size).freeze());
-            }
-
-            sections
-        };
+        let sections = self
+            .psi
+            .push_payload(pid, &payload, payload_unit_start_indicator);
 
         for section in sections {
-            self.process_psi_payload_inner(pid, section, on_pat, on_pmt, on_scte35)?;
+            self.process_psi_payload_inner(pid, section, on_pat, on_pmt, on_scte35, on_sdt)?;
         }
 
         Ok(())
     }
 
     /// Internal PSI payload processing with optional SCTE-35 support.
-    fn process_psi_payload_inner<F, G, S>(
+    fn process_psi_payload_inner<F, G, S, D>(
         &mut self,
         pid: u16,
         psi_payload: Bytes,
         on_pat: &mut F,
         on_pmt: &mut G,
         on_scte35: &mut Option<S>,
+        on_sdt: &mut Option<D>,
     ) -> Result<()>
     where
         F: FnMut(PatRef) -> Result<()>,
         G: FnMut(PmtRef) -> Result<()>,
         S: FnMut(crate::scte35::SpliceInfoSectionRef) -> Result<()>,
+        D: FnMut(Sdt) -> Result<()>,
     {
-        if pid == 0x0000 {
-            let parse_result = if self.validate_crc {
-                PatRef::parse_with_crc(psi_payload)
-            } else {
-                PatRef::parse(psi_payload)
-            };
-            if let Ok(pat) = parse_result {
+        let context = ErrorContext::section(pid, self.packet_offset, &psi_payload);
+        if pid == PID_PAT {
+            let parse_result = PatRef::parse_on_pid(pid, psi_payload, self.validate_crc);
+            if let Some(pat) = checked_section(context, parse_result)? {
                 self.process_pat(pat, on_pat)?;
             }
         } else if (pid as usize) < PID_SPACE && self.scte35_pid_flags[pid as usize] {
@@ -1040,7 +1133,7 @@ impl TsParser {
             {
                 on_scte35_cb(section)?;
             }
-        } else if (pid as usize) < PID_SPACE && self.pmt_pid_flags[pid as usize] {
+        } else if self.psi.is_pmt_pid(pid) {
             // It could be a PAT on a PMT PID, check table_id
             if psi_payload.is_empty() {
                 return Ok(());
@@ -1048,109 +1141,142 @@ impl TsParser {
             match psi_payload[0] {
                 0x00 => {
                     // PAT packet on a PMT PID, re-process PAT
-                    let parse_result = if self.validate_crc {
-                        PatRef::parse_with_crc(psi_payload)
-                    } else {
-                        PatRef::parse(psi_payload)
-                    };
-                    if let Ok(pat) = parse_result {
+                    let parse_result = PatRef::parse_on_pid(pid, psi_payload, self.validate_crc);
+                    if let Some(pat) = checked_section(context, parse_result)? {
                         self.process_pat(pat, on_pat)?;
                     }
                 }
                 0x02 => {
                     // PMT packet
-                    let parse_result = if self.validate_crc {
-                        PmtRef::parse_with_crc(psi_payload)
-                    } else {
-                        PmtRef::parse(psi_payload)
-                    };
-                    if let Ok(pmt) = parse_result {
-                        let program_number = self.pmt_pids.get(&pid).copied().unwrap_or(0);
-                        let is_new = self
-                            .pmt_versions
-                            .get(&program_number)
-                            .is_none_or(|&v| v != pmt.version_number);
-                        if is_new {
-                            self.pmt_versions.insert(program_number, pmt.version_number);
-                            // Detect SCTE-35 PIDs from this PMT
-                            self.detect_scte35_pids(&pmt);
-                            on_pmt(pmt)?;
-                        }
+                    let parse_result = PmtRef::parse_on_pid(pid, psi_payload, self.validate_crc);
+                    if let Some(pmt) = checked_section(context, parse_result)? {
+                        let program_number = self.psi.program_of_pmt_pid(pid).unwrap_or(0);
+                        self.process_pmt(program_number, pmt, on_pmt)?;
                     }
                 }
                 _ => {
                     // Unknown table ID on a PMT PID, ignore
                 }
             }
+        } else if pid == PID_SDT
+            && let Some(on_sdt_cb) = on_sdt
+            && matches!(
+                psi_payload.first(),
+                Some(&TABLE_ID_SDT_ACTUAL | &TABLE_ID_SDT_OTHER)
+            )
+        {
+            let parse_result = if self.validate_crc {
+                Sdt::parse_with_crc(psi_payload)
+            } else {
+                Sdt::parse(psi_payload)
+            };
+            if let Some(sdt) = checked_section(context, parse_result)? {
+                let key = (sdt.table_id, sdt.transport_stream_id, sdt.section_number);
+                if self.sdt_versions.get(&key) != Some(&sdt.version_number) {
+                    self.sdt_versions.insert(key, sdt.version_number);
+                    on_sdt_cb(sdt)?;
+                }
+            }
         }
         Ok(())
     }
 
-    /// Detect SCTE-35 PIDs from a PMT by looking for streams with
-    /// registration descriptor format identifier "CUEI".
-    fn detect_scte35_pids(&mut self, pmt: &PmtRef) {
-        for stream in pmt.streams().flatten() {
-            // Check ES info descriptors for registration descriptor "CUEI"
-            for desc in stream.descriptors() {
-                if desc.tag == crate::descriptor::TAG_REGISTRATION
-                    && let Some(format_id) =
-                        crate::descriptor::parse_registration_descriptor(&desc.data)
-                    && &format_id == b"CUEI"
-                {
-                    self.scte35_pids.insert(stream.elementary_pid);
-                    let pid_idx = stream.elementary_pid as usize;
-                    if pid_idx < PID_SPACE {
-                        self.scte35_pid_flags[pid_idx] = true;
+    /// Rebuild the SCTE-35 PIDs from the current PMTs, looking for streams
+    /// with registration descriptor format identifier "CUEI".
+    fn rebuild_scte35_pids(&mut self) {
+        self.scte35_pids.clear();
+        self.scte35_pid_flags = [false; PID_SPACE];
+        for pmt in self.psi.pmts().values() {
+            for stream in pmt.streams().flatten() {
+                // Check ES info descriptors for registration descriptor "CUEI"
+                for desc in stream.descriptors().flatten() {
+                    if let crate::descriptor::Descriptor::Registration {
+                        format_identifier, ..
+                    } = desc
+                        && &format_identifier == b"CUEI"
+                    {
+                        self.scte35_pids.insert(stream.elementary_pid);
+                        let pid_idx = stream.elementary_pid as usize;
+                        if pid_idx < PID_SPACE {
+                            self.scte35_pid_flags[pid_idx] = true;
+                        }
                     }
                 }
             }
         }
     }
 
-    /// Process a parsed PAT
-    fn process_pat<F>(&mut self, pat: PatRef, on_pat: &mut F) -> Result<()>
+    /// Process a parsed PAT section, see [`PsiTables::accept_pat`] for the
+    /// version rules.
+    fn process_pat<F>(&mut self, section: PatRef, on_pat: &mut F) -> Result<()>
     where
         F: FnMut(PatRef) -> Result<()>,
     {
-        let is_new = self.pat_version != Some(pat.version_number);
-        if is_new {
-            self.pat_version = Some(pat.version_number);
-
-            // A new PAT version has been received, clear all program-related state.
-            self.program_pids.clear();
-            self.pmt_pids.clear();
-            self.pmt_pid_flags = [false; PID_SPACE];
-            self.pmt_versions.clear();
-            self.scte35_pids.clear();
-            self.scte35_pid_flags = [false; PID_SPACE];
-            self.psi_buffers.clear();
-
-            // Populate the maps with the new program data.
-            for program in pat.programs() {
-                if program.program_number != 0 {
-                    self.program_pids
-                        .insert(program.program_number, program.pmt_pid);
-                    self.pmt_pids
-                        .insert(program.pmt_pid, program.program_number);
-                    let pid_idx = program.pmt_pid as usize;
-                    if pid_idx < PID_SPACE {
-                        self.pmt_pid_flags[pid_idx] = true;
-                    }
-                }
-            }
-
-            on_pat(pat)?;
+        let Some(previous) = self.psi.accept_pat(section) else {
+            return Ok(());
+        };
+        self.rebuild_scte35_pids();
+
+        // Drop partial sections of PIDs that no longer carry tables
+        let scte35_pid_flags = &self.scte35_pid_flags;
+        self.psi.retain_buffers(|pid| {
+            let pid_idx = pid as usize;
+            pid == PID_SDT || (pid_idx < PID_SPACE && scte35_pid_flags[pid_idx])
+        });
+
+        let sections = self.psi.pat_sections().to_vec();
+        for section in &sections {
+            let previous_section = previous
+                .iter()
+                .find(|previous| previous.section_number == section.section_number);
+            self.table_callbacks.pat_updated(previous_section, section);
+        }
+        for section in sections {
+            on_pat(section)?;
         }
         Ok(())
     }
 
-    /// Reset parser state
+    /// Process a parsed PMT of `program_number`, following the same version
+    /// rules as [`process_pat`](Self::process_pat).
+    fn process_pmt<G>(&mut self, program_number: u16, pmt: PmtRef, on_pmt: &mut G) -> Result<()>
+    where
+        G: FnMut(PmtRef) -> Result<()>,
+    {
+        if !self.psi.is_new_pmt(program_number, &pmt) {
+            return Ok(());
+        }
+
+        let previous = self.psi.commit_pmt(program_number, pmt.clone());
+        // Detect SCTE-35 PIDs from this PMT
+        self.rebuild_scte35_pids();
+        self.table_callbacks
+            .pmt_updated(program_number, previous.as_ref(), &pmt);
+        on_pmt(pmt)
+    }
+
+    /// Only process the given programs; an empty list removes the filter.
+    ///
+    /// The PAT is always processed to find the PMTs of the selected programs,
+    /// and `on_pat` still receives every section. Packets of other programs
+    /// are skipped before they are parsed: they neither reach `on_packet` nor
+    /// have their continuity checked, and their PMTs are never reported.
+    pub fn set_program_filter(&mut self, program_numbers: &[u16]) {
+        self.psi.set_program_filter(program_numbers);
+        self.rebuild_scte35_pids();
+    }
+
+    /// Only process packets of the given PIDs; an empty list removes the filter.
+    ///
+    /// The PAT and the PMTs of the selected programs are always processed.
+    /// Combined with a program filter, a packet must pass both.
+    pub fn set_pid_filter(&mut self, pids: &[u16]) {
+        self.psi.set_pid_filter(pids);
+    }
+
+    /// Reset parser state, keeping the program and PID filters
     pub fn reset(&mut self) {
-        self.program_pids.clear();
-        self.pmt_pids.clear();
-        self.pmt_pid_flags = [false; PID_SPACE];
-        self.pat_version = None;
-        self.pmt_versions.clear();
+        self.psi.reset();
         self.continuity_counters = [0; PID_SPACE];
         self.continuity_seen = [false; PID_SPACE];
         self.continuity_issue_count = 0;
@@ -1158,21 +1284,57 @@ impl TsParser {
         self.continuity_discontinuity_count = 0;
         self.scte35_pids.clear();
         self.scte35_pid_flags = [false; PID_SPACE];
-        self.psi_buffers.clear();
+        self.sdt_versions.clear();
+        self.stream_offset = 0;
+    }
+
+    /// Continue counting stream offsets from `offset`, for callers that feed
+    /// the parser only part of their input
+    pub(crate) fn set_stream_offset(&mut self, offset: u64) {
+        self.stream_offset = offset;
     }
 
     /// Get estimated memory usage for the parser (for debugging/profiling)
     pub fn estimated_memory_usage(&self) -> usize {
-        std::mem::size_of::<Self>()
-            + self.program_pids.capacity() * (std::mem::size_of::<u16>() * 2)
-            + self.pmt_pids.capacity() * (std::mem::size_of::<u16>() * 2)
-            + self.pmt_versions.capacity()
-                * (std::mem::size_of::<u16>() + std::mem::size_of::<u8>())
+        std::mem::size_of::<Self>() + self.psi.estimated_memory_usage()
     }
 
     /// Get number of tracked programs (for debugging)
     pub fn program_count(&self) -> usize {
-        self.program_pids.len()
+        self.psi.program_count()
+    }
+
+    /// The first section of the current PAT, if one was received
+    pub fn pat(&self) -> Option<&PatRef> {
+        self.psi.pat_sections().first()
+    }
+
+    /// All sections of the current PAT, ordered by section number
+    pub fn pat_sections(&self) -> &[PatRef] {
+        self.psi.pat_sections()
+    }
+
+    /// The current PMT of a program, if one was received
+    pub fn pmt(&self, program_number: u16) -> Option<&PmtRef> {
+        self.psi.pmts().get(&program_number)
+    }
+
+    /// The PAT, with the programs of all its sections, and the PMTs of the
+    /// selected programs ordered by program number, once all of them were
+    /// received
+    pub fn complete_tables(&self) -> Option<(Pat, Vec<Pmt>)> {
+        if !self.psi.has_all_pmts() {
+            return None;
+        }
+
+        let (first, rest) = self.psi.pat_sections().split_first()?;
+        let mut pat = Pat::from(first);
+        for section in rest {
+            pat.programs.extend(Pat::from(section).programs);
+        }
+        let mut pmts: Vec<Pmt> = self.psi.pmts().values().map(Pmt::from).collect();
+        pmts.sort_by_key(|pmt| pmt.program_number);
+        Some((pat, pmts))
     }
 }
 
@@ -1222,7 +1384,8 @@ mod tests {
             section.push((pmt_pid & 0xFF) as u8);
         }
 
-        section.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        let crc = crate::crc32::mpeg2_crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
         section
     }
 
@@ -1259,7 +1422,8 @@ mod tests {
             section.push(0x00);
         }
 
-        section.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+        let crc = crate::crc32::mpeg2_crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
         section
     }
 
@@ -1350,10 +1514,11 @@ mod tests {
 
     #[test]
     fn handles_pointer_field_completing_previous_section() {
-        let pat_v0 = build_pat_section(0, 1, 0x0100);
+        // Large enough that the first section spills into the next packet
+        let pat_v0 = build_pat_section(0, 50, 0x0100);
         let pat_v1 = build_pat_section(1, 1, 0x0100);
 
-        let split_at = 6;
+        let split_at = 183;
         let mut payload_1 = Vec::with_capacity(184);
         payload_1.push(0x00);
         payload_1.extend_from_slice(&pat_v0[..split_at]);
@@ -1389,6 +1554,180 @@ mod tests {
         assert_eq!(versions, vec![0, 1]);
     }
 
+    #[test]
+    fn reassembles_sdt_section_across_packets() {
+        let services: Vec<(u16, String, String)> = (0..8)
+            .map(|i| {
+                (
+                    i + 1,
+                    format!("Provider {i}"),
+                    format!("Service number {i}"),
+                )
+            })
+            .collect();
+        let service_refs: Vec<(u16, &str, &str)> = services
+            .iter()
+            .map(|(id, p, n)| (*id, p.as_str(), n.as_str()))
+            .collect();
+        let sdt_section = crate::sdt::tests::build_sdt_section(0, &service_refs);
+        assert!(sdt_section.len() > 183);
+        let split_at = 183;
+
+        let mut payload_1 = Vec::with_capacity(184);
+        payload_1.push(0x00);
+        payload_1.extend_from_slice(&sdt_section[..split_at]);
+        let payload_2 = sdt_section[split_at..].to_vec();
+
+        let mut stream = Vec::new();
+        stream.extend_from_slice(&build_ts_packet(PID_SDT, true, 0, &payload_1));
+        stream.extend_from_slice(&build_ts_packet(PID_SDT, false, 1, &payload_2));
+        // Repeated section with the same version must not be reported again
+        stream.extend_from_slice(&build_ts_packet(PID_SDT, true, 2, &payload_1));
+        stream.extend_from_slice(&build_ts_packet(PID_SDT, false, 3, &payload_2));
+
+        let mut parser = TsParser::new().with_crc_validation(true);
+        let mut names = Vec::new();
+        let mut sdt_count = 0usize;
+
+        parser
+            .parse_packets_with_sdt(
+                Bytes::from(stream),
+                |_pat| Ok(()),
+                |_pmt| Ok(()),
+                None::<fn(&TsPacketRef) -> Result<()>>,
+                |sdt| {
+                    sdt_count += 1;
+                    names.extend(
+                        sdt.services
+                            .iter()
+                            .filter_map(|s| s.service_name().map(str::to_owned)),
+                    );
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        assert_eq!(sdt_count, 1);
+        assert_eq!(names.len(), 8);
+        assert_eq!(names[7], "Service number 7");
+    }
+
+    // PAT and PMT sections captured from an ffmpeg mpegts mux (H.264 + AAC)
+    const FFMPEG_PAT: [u8; 16] = [
+        0x00, 0xB0, 0x0D, 0x00, 0x01, 0xC1, 0x00, 0x00, 0x00, 0x01, 0xF0, 0x00, 0x2A, 0xB1, 0x04,
+        0xB2,
+    ];
+    const FFMPEG_PMT: [u8; 26] = [
+        0x02, 0xB0, 0x17, 0x00, 0x01, 0xC1, 0x00, 0x00, 0xE1, 0x00, 0xF0, 0x00, 0x1B, 0xE1, 0x00,
+        0xF0, 0x00, 0x0F, 0xE1, 0x01, 0xF0, 0x00, 0x2F, 0x44, 0xB9, 0x9B,
+    ];
+
+    fn ffmpeg_psi_stream(pat: &[u8], pmt: &[u8]) -> Vec<u8> {
+        let mut stream = Vec::new();
+        for (pid, section) in [(0x0000, pat), (0x1000, pmt)] {
+            let mut payload = vec![0x00];
+            payload.extend_from_slice(section);
+            stream.extend_from_slice(&build_ts_packet(pid, true, 0, &payload));
+        }
+        stream
+    }
+
+    fn collect_streams(parser: &mut TsParser, stream: Vec<u8>) -> Result<Vec<(u8, u16)>> {
+        let mut streams = Vec::new();
+        parser.parse_packets(
+            Bytes::from(stream),
+            |_pat| Ok(()),
+            |pmt| {
+                for stream in pmt.streams() {
+                    let stream = stream?;
+                    streams.push((u8::from(stream.stream_type), stream.elementary_pid));
+                }
+                Ok(())
+            },
+            None::<fn(&TsPacketRef) -> Result<()>>,
+        )?;
+        Ok(streams)
+    }
+
+    #[test]
+    fn validates_crc_of_captured_sections_by_default() {
+        let mut parser = TsParser::new();
+        let streams =
+            collect_streams(&mut parser, ffmpeg_psi_stream(&FFMPEG_PAT, &FFMPEG_PMT)).unwrap();
+        assert_eq!(streams, vec![(0x1B, 0x0100), (0x0F, 0x0101)]);
+    }
+
+    #[test]
+    fn corrupted_pmt_reports_crc_mismatch_with_pid() {
+        let mut pmt = FFMPEG_PMT;
+        // Flip the audio elementary PID from 0x101 to 0x102
+        pmt[19] = 0x02;
+
+        let mut parser = TsParser::new();
+        let err = collect_streams(&mut parser, ffmpeg_psi_stream(&FFMPEG_PAT, &pmt)).unwrap_err();
+        assert!(matches!(
+            err.without_context(),
+            TsError::Crc32Mismatch {
+                expected: 0x2F44_B99B,
+                ..
+            }
+        ));
+        assert_eq!(err.context().pid, Some(0x1000));
+
+        let mut parser = TsParser::new().with_crc_validation(false);
+        let streams = collect_streams(&mut parser, ffmpeg_psi_stream(&FFMPEG_PAT, &pmt)).unwrap();
+        assert_eq!(streams, vec![(0x1B, 0x0100), (0x0F, 0x0102)]);
+    }
+
+    #[test]
+    fn corrupted_pat_reports_crc_mismatch_with_pid() {
+        let mut pat = FFMPEG_PAT;
+        pat[11] = 0x01;
+
+        let mut parser = TsParser::new();
+        let err = collect_streams(&mut parser, ffmpeg_psi_stream(&pat, &FFMPEG_PMT)).unwrap_err();
+        assert!(matches!(
+            err.without_context(),
+            TsError::Crc32Mismatch { .. }
+        ));
+        assert_eq!(err.context().pid, Some(0x0000));
+    }
+
+    #[test]
+    fn errors_report_stream_offsets() {
+        let mut pmt = FFMPEG_PMT;
+        pmt[19] = 0x02;
+
+        let mut parser = TsParser::new();
+        let filler: Vec<u8> = (0..3)
+            .flat_map(|cc| build_ts_packet(0x0100, false, cc, &[0x00]))
+            .collect();
+        collect_streams(&mut parser, filler).unwrap();
+
+        // Garbage before the PAT and the corrupted PMT, in a second call
+        let mut stream = vec![0x00; 5];
+        stream.extend(ffmpeg_psi_stream(&FFMPEG_PAT, &pmt));
+        let err = collect_streams(&mut parser, stream).unwrap_err();
+        assert_eq!(
+            err.context(),
+            ErrorContext {
+                pid: Some(0x1000),
+                offset: Some(3 * 188 + 5 + 188),
+                table_id: Some(0x02),
+                section_number: Some(0),
+            }
+        );
+        assert!(
+            err.to_string()
+                .starts_with("pid=0x1000 offset=757 table=0x02 section=0: CRC32 mismatch"),
+            "{err}"
+        );
+
+        parser.reset();
+        let err = collect_streams(&mut parser, ffmpeg_psi_stream(&FFMPEG_PAT, &pmt)).unwrap_err();
+        assert_eq!(err.context().offset, Some(188));
+    }
+
     #[test]
     fn continuity_warn_mode_reports_issues_without_failing() {
         let packet_1 = build_ts_packet(0x0100, false, 0, &[0x00]);
@@ -1427,14 +1766,21 @@ mod tests {
             None::<fn(&TsPacketRef) -> Result<()>>,
         );
 
+        let err = result.unwrap_err();
         assert!(matches!(
-            result,
-            Err(TsError::ContinuityError {
+            err.without_context(),
+            TsError::ContinuityError {
                 pid: 0x0100,
                 expected: 1,
                 actual: 5
-            })
+            }
         ));
+        assert_eq!(err.context(), ErrorContext::packet(0x0100, 188));
+        assert_eq!(
+            err.to_string(),
+            "pid=0x100 offset=188: Continuity counter discontinuity on PID 0x0100: \
+             expected 1, got 5"
+        );
     }
 
     #[test]
@@ -1542,4 +1888,361 @@ mod tests {
 
         assert_eq!(versions, vec![0, 1]);
     }
+
+    #[test]
+    fn pmt_version_bump_reports_added_stream_once() {
+        use crate::TsWriter;
+        use std::sync::{Arc, Mutex};
+
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        };
+        let pmt = |version_number: u8, current_next_indicator: bool, audio: bool| {
+            let mut streams = vec![PmtStream {
+                stream_type: StreamType::H264,
+                elementary_pid: 0x100,
+                es_info: Vec::new(),
+            }];
+            if audio {
+                streams.push(PmtStream {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: 0x101,
+                    es_info: Vec::new(),
+                });
+            }
+            Pmt {
+                table_id: 0x02,
+                program_number: 1,
+                version_number,
+                current_next_indicator,
+                section_number: 0,
+                last_section_number: 0,
+                pcr_pid: 0x100,
+                program_info: Vec::new(),
+                streams,
+            }
+        };
+
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        writer.write_pat(&pat, &mut out).unwrap();
+        writer
+            .write_pmt(0x1000, &pmt(0, true, false), &mut out)
+            .unwrap();
+        writer
+            .write_pmt(0x1000, &pmt(0, true, false), &mut out)
+            .unwrap();
+        // Announced ahead of time, applied once it is sent as current
+        writer
+            .write_pmt(0x1000, &pmt(1, false, true), &mut out)
+            .unwrap();
+        writer.write_pat(&pat, &mut out).unwrap();
+        writer
+            .write_pmt(0x1000, &pmt(1, true, true), &mut out)
+            .unwrap();
+        writer
+            .write_pmt(0x1000, &pmt(1, true, true), &mut out)
+            .unwrap();
+
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&updates);
+        let mut parser = TsParser::new().on_pmt_update(move |program_number, previous, current| {
+            let stream_pids = |pmt: &PmtRef| -> Vec<u16> {
+                pmt.streams().flatten().map(|s| s.elementary_pid).collect()
+            };
+            let previous_pids = previous.map(stream_pids).unwrap_or_default();
+            let added: Vec<u16> = stream_pids(current)
+                .into_iter()
+                .filter(|pid| !previous_pids.contains(pid))
+                .collect();
+            sink.lock().unwrap().push((
+                program_number,
+                previous.map(|pmt| pmt.version_number),
+                current.version_number,
+                added,
+            ));
+        });
+        let mut pat_count = 0usize;
+        let mut pmt_count = 0usize;
+
+        parser
+            .parse_packets(
+                Bytes::from(out),
+                |_pat| {
+                    pat_count += 1;
+                    Ok(())
+                },
+                |_pmt| {
+                    pmt_count += 1;
+                    Ok(())
+                },
+                None::<fn(&TsPacketRef) -> Result<()>>,
+            )
+            .unwrap();
+
+        assert_eq!(pat_count, 1);
+        assert_eq!(pmt_count, 2);
+        assert_eq!(
+            *updates.lock().unwrap(),
+            vec![(1, None, 0, vec![0x100]), (1, Some(0), 1, vec![0x101])]
+        );
+        assert_eq!(parser.pmt(1).unwrap().version_number, 1);
+    }
+
+    /// Rewrite the section numbers of a PSI section and refresh its CRC.
+    fn renumber_section(mut section: Vec<u8>, number: u8, last: u8) -> Vec<u8> {
+        section[6] = number;
+        section[7] = last;
+        let crc_offset = section.len() - 4;
+        let crc = crate::crc32::mpeg2_crc32(&section[..crc_offset]);
+        section[crc_offset..].copy_from_slice(&crc.to_be_bytes());
+        section
+    }
+
+    #[test]
+    fn parses_pmt_spanning_several_packets() {
+        let pmt_section = build_pmt_section(0, 1, 0x0101, 117, 0x0101);
+        assert_eq!(pmt_section.len(), 601);
+
+        let mut writer = crate::TsWriter::new();
+        let mut stream = Vec::new();
+        writer
+            .write_section(0x0000, &build_pat_section(0, 1, 0x0100), &mut stream)
+            .unwrap();
+        let packets = writer
+            .write_section(0x0100, &pmt_section, &mut stream)
+            .unwrap();
+        assert_eq!(packets, 4);
+
+        let mut parser = TsParser::new();
+        let mut stream_pids = Vec::new();
+        parser
+            .parse_packets(
+                Bytes::from(stream),
+                |_pat| Ok(()),
+                |pmt| {
+                    for stream in pmt.streams() {
+                        stream_pids.push(stream?.elementary_pid);
+                    }
+                    Ok(())
+                },
+                None::<fn(&TsPacketRef) -> Result<()>>,
+            )
+            .unwrap();
+
+        assert_eq!(stream_pids, (0x0101..0x0101 + 117).collect::<Vec<_>>());
+        assert_eq!(parser.pmt(1).unwrap().streams().count(), 117);
+    }
+
+    #[test]
+    fn pmt_errors_instead_of_truncating() {
+        let section = build_pmt_section(0, 1, 0x0101, 2, 0x0101);
+
+        // section_length claims more bytes than were assembled
+        let err = PmtRef::parse(Bytes::copy_from_slice(&section[..section.len() - 6])).unwrap_err();
+        assert!(matches!(err, TsError::InsufficientData { .. }), "{err:?}");
+
+        // A stream loop ending in a partial entry
+        let mut section = section;
+        let crc_offset = section.len() - 4;
+        section.splice(crc_offset..crc_offset, [0x1B, 0xE1, 0x10]);
+        let section_length = section.len() - 3;
+        section[1] = 0xB0 | (section_length >> 8) as u8;
+        section[2] = section_length as u8;
+        let pmt = PmtRef::parse(Bytes::from(section)).unwrap();
+        let streams: Vec<_> = pmt.streams().collect();
+        assert_eq!(streams.len(), 3);
+        assert!(streams[..2].iter().all(Result::is_ok));
+        assert!(matches!(
+            streams[2],
+            Err(TsError::InsufficientData {
+                expected: 5,
+                actual: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn commits_pat_once_all_sections_arrived() {
+        let section_0 = renumber_section(build_pat_section(0, 1, 0x0100), 0, 1);
+        // Second section carries program 1 on another PID, renumbered to program 2
+        let mut section_1 = build_pat_section(0, 1, 0x0200);
+        section_1[9] = 0x02;
+        let section_1 = renumber_section(section_1, 1, 1);
+
+        let mut writer = crate::TsWriter::new();
+        let mut stream = Vec::new();
+        writer
+            .write_section(0x0000, &section_1, &mut stream)
+            .unwrap();
+        writer
+            .write_section(0x0000, &section_1, &mut stream)
+            .unwrap();
+        let first_len = stream.len();
+        writer
+            .write_section(0x0000, &section_0, &mut stream)
+            .unwrap();
+        writer
+            .write_section(0x0000, &section_1, &mut stream)
+            .unwrap();
+
+        let mut parser = TsParser::new();
+        let mut sections = Vec::new();
+        let mut on_pat = |pat: PatRef| -> Result<()> {
+            sections.push(pat.section_number);
+            Ok(())
+        };
+        let no_packets = None::<fn(&TsPacketRef) -> Result<()>>;
+        parser
+            .parse_packets(
+                Bytes::copy_from_slice(&stream[..first_len]),
+                &mut on_pat,
+                |_pmt| Ok(()),
+                no_packets,
+            )
+            .unwrap();
+        assert!(parser.pat().is_none());
+
+        parser
+            .parse_packets(
+                Bytes::copy_from_slice(&stream[first_len..]),
+                &mut on_pat,
+                |_pmt| Ok(()),
+                no_packets,
+            )
+            .unwrap();
+        assert_eq!(sections, vec![0, 1]);
+        assert_eq!(parser.pat_sections().len(), 2);
+        assert_eq!(parser.program_count(), 2);
+    }
+
+    /// A stream of `programs` programs: program `n` has its PMT on
+    /// `0x1000 + n`, video on `0x100 + 2n` and audio on `0x101 + 2n`.
+    fn build_mpts(programs: u16) -> Vec<u8> {
+        use crate::TsWriter;
+
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: (1..=programs)
+                .map(|n| PatProgram {
+                    program_number: n,
+                    pmt_pid: 0x1000 + n,
+                })
+                .collect(),
+        };
+        writer.write_pat(&pat, &mut out).unwrap();
+        for n in 1..=programs {
+            let video_pid = 0x100 + 2 * n;
+            let pmt = Pmt {
+                table_id: 0x02,
+                program_number: n,
+                version_number: 0,
+                current_next_indicator: true,
+                section_number: 0,
+                last_section_number: 0,
+                pcr_pid: video_pid,
+                program_info: Vec::new(),
+                streams: vec![
+                    PmtStream {
+                        stream_type: StreamType::H264,
+                        elementary_pid: video_pid,
+                        es_info: Vec::new(),
+                    },
+                    PmtStream {
+                        stream_type: StreamType::AdtsAac,
+                        elementary_pid: video_pid + 1,
+                        es_info: Vec::new(),
+                    },
+                ],
+            };
+            writer.write_pmt(0x1000 + n, &pmt, &mut out).unwrap();
+        }
+        for n in 1..=programs {
+            writer
+                .write_pes(0x100 + 2 * n, &[0u8; 300], &mut out)
+                .unwrap();
+            writer
+                .write_pes(0x101 + 2 * n, &[0u8; 100], &mut out)
+                .unwrap();
+        }
+        out
+    }
+
+    /// Parses `data` and returns the programs of the reported PMTs and the
+    /// PIDs of the packets handed to `on_packet`.
+    fn parse_filtered(parser: &mut TsParser, data: Vec<u8>) -> (Vec<u16>, HashSet<u16>) {
+        let mut programs = Vec::new();
+        let mut pids = HashSet::new();
+        parser
+            .parse_packets(
+                Bytes::from(data),
+                |_pat| Ok(()),
+                |pmt| {
+                    programs.push(pmt.program_number);
+                    Ok(())
+                },
+                Some(|packet: &TsPacketRef| {
+                    pids.insert(packet.pid);
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        (programs, pids)
+    }
+
+    #[test]
+    fn program_filter_skips_other_programs() {
+        let mut parser = TsParser::new();
+        parser.set_program_filter(&[7]);
+        let (programs, pids) = parse_filtered(&mut parser, build_mpts(20));
+
+        assert_eq!(programs, vec![7]);
+        assert_eq!(pids, HashSet::from([PID_PAT, 0x1007, 0x10E, 0x10F]));
+        assert_eq!(parser.program_count(), 1);
+        assert!(parser.pmt(8).is_none());
+        // The PAT itself is reported in full
+        assert_eq!(parser.pat().unwrap().program_count(), 20);
+
+        // Removing the filter brings the other programs back
+        parser.reset();
+        parser.set_program_filter(&[]);
+        let (programs, _) = parse_filtered(&mut parser, build_mpts(20));
+        assert_eq!(programs.len(), 20);
+    }
+
+    #[test]
+    fn pid_filter_keeps_tables() {
+        let mut parser = TsParser::new();
+        parser.set_pid_filter(&[0x10E]);
+        let (programs, pids) = parse_filtered(&mut parser, build_mpts(3));
+        assert_eq!(programs, vec![1, 2, 3]);
+        assert_eq!(
+            pids,
+            HashSet::from([PID_PAT, 0x1001, 0x1002, 0x1003, 0x10E])
+        );
+
+        // Both filters must pass
+        let mut parser = TsParser::new();
+        parser.set_program_filter(&[2]);
+        parser.set_pid_filter(&[0x104, 0x107]);
+        let (programs, pids) = parse_filtered(&mut parser, build_mpts(3));
+        assert_eq!(programs, vec![2]);
+        assert_eq!(pids, HashSet::from([PID_PAT, 0x1002, 0x104]));
+    }
 }
@@ -102,30 +102,38 @@ impl Pat {
 
     /// Parse PAT from PSI section data with CRC-32/MPEG-2 validation.
     pub fn parse_with_crc(data: &[u8]) -> Result<Self> {
-        // Validate CRC before parsing
-        if data.len() >= 7 {
-            let section_length = ((data[1] as u16 & 0x0F) << 8) | data[2] as u16;
-            let section_end = 3 + section_length as usize;
-            if section_end <= data.len()
-                && section_end >= 4
-                && !crate::crc32::validate_section_crc32(&data[..section_end])
-            {
-                let stored = u32::from_be_bytes([
-                    data[section_end - 4],
-                    data[section_end - 3],
-                    data[section_end - 2],
-                    data[section_end - 1],
-                ]);
-                let calculated = crate::crc32::mpeg2_crc32(&data[..section_end - 4]);
-                return Err(TsError::Crc32Mismatch {
-                    expected: stored,
-                    calculated,
-                });
-            }
-        }
+        crate::crc32::verify_section_crc32(data)?;
         Self::parse(data)
     }
 
+    /// Serialize this PAT into a complete PSI section, including the CRC-32.
+    pub fn encode_section(&self) -> Result<Vec<u8>> {
+        // transport_stream_id..last_section_number (5) + programs + CRC (4)
+        let section_length = 5 + self.programs.len() * 4 + 4;
+        if section_length > crate::writer::MAX_SECTION_LENGTH {
+            return Err(TsError::InvalidSectionLength(section_length as u16));
+        }
+
+        let mut section = Vec::with_capacity(3 + section_length);
+        section.push(self.table_id);
+        section.push(0xB0 | ((section_length >> 8) as u8 & 0x0F));
+        section.push(section_length as u8);
+        section.extend_from_slice(&self.transport_stream_id.to_be_bytes());
+        section
+            .push(0xC0 | ((self.version_number & 0x1F) << 1) | self.current_next_indicator as u8);
+        section.push(self.section_number);
+        section.push(self.last_section_number);
+        for program in &self.programs {
+            section.extend_from_slice(&program.program_number.to_be_bytes());
+            section.push(0xE0 | ((program.pmt_pid >> 8) as u8 & 0x1F));
+            section.push(program.pmt_pid as u8);
+        }
+
+        let crc = crate::crc32::mpeg2_crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        Ok(section)
+    }
+
     /// Get the Network PID (program number 0)
     pub fn network_pid(&self) -> Option<u16> {
         self.programs
@@ -201,4 +209,33 @@ mod tests {
         assert_eq!(pat.programs[0].program_number, 1);
         assert_eq!(pat.programs[0].pmt_pid, 0x1000);
     }
+
+    #[test]
+    fn test_pat_encode_section_round_trip() {
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 0x1234,
+            version_number: 5,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![
+                PatProgram {
+                    program_number: 0,
+                    pmt_pid: 0x0010,
+                },
+                PatProgram {
+                    program_number: 1,
+                    pmt_pid: 0x1000,
+                },
+            ],
+        };
+
+        let section = pat.encode_section().unwrap();
+        let parsed = Pat::parse_with_crc(&section).unwrap();
+        assert_eq!(parsed.transport_stream_id, 0x1234);
+        assert_eq!(parsed.version_number, 5);
+        assert_eq!(parsed.network_pid(), Some(0x0010));
+        assert_eq!(parsed.get_pmt_pid(1), Some(0x1000));
+    }
 }
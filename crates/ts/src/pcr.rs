@@ -0,0 +1,491 @@
+//! Timing of transport stream data from its Program Clock References.
+//!
+//! The PCR of a program stamps the time its packets leave the multiplexer, so the
+//! duration and mux bitrate of a chunk of transport stream, such as an HLS segment,
+//! follow from the PCRs it carries without decoding any media. PCRs are read on the PCR
+//! PID of the PMT, or on the first PID carrying them when the chunk has no PMT.
+//!
+//! [`estimate_duration`] and [`estimate_mux_bitrate`] time a whole chunk, while a
+//! [`PcrTracker`] follows a stream packet by packet and reports the discontinuities and
+//! jitter of its PCRs.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::adaptation_field::PCR_MODULO;
+use crate::packet::PID_NULL;
+use crate::writer::TS_PACKET_SIZE;
+use crate::{Pcr, PmtRef, Result, TsError, TsPacketRef, TsParser};
+
+/// Ticks per second of the PCR clock
+const PCR_HZ: u64 = 27_000_000;
+
+/// Bits of a packet, the unit of the mux bitrate
+const PACKET_BITS: u64 = TS_PACKET_SIZE as u64 * 8;
+
+/// Duration of `ticks` of the PCR clock
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos((ticks as u128 * 1000 / 27) as u64)
+}
+
+/// Ticks of the PCR clock in `duration`
+fn duration_to_ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() * 27 / 1000) as u64
+}
+
+/// Whether `ticks` between two PCRs are a step back rather than a wraparound
+fn is_backwards(ticks: u64) -> bool {
+    ticks >= PCR_MODULO / 2
+}
+
+/// A PCR and the index of the packet carrying it
+#[derive(Debug, Clone, Copy)]
+struct PcrSample {
+    packet: u64,
+    pcr: Pcr,
+    discontinuity: bool,
+}
+
+/// The PCRs of a chunk on its PCR PID
+struct PcrSamples {
+    samples: Vec<PcrSample>,
+    /// Number of packets of the chunk
+    packets: u64,
+}
+
+impl PcrSamples {
+    fn collect(data: &[u8]) -> Result<Self> {
+        let mut pcr_pid = None;
+        let mut found: Vec<(u16, PcrSample)> = Vec::new();
+        let mut packets = 0u64;
+
+        TsParser::new().parse_packets(
+            Bytes::copy_from_slice(data),
+            |_| Ok(()),
+            |pmt: PmtRef| {
+                if pcr_pid.is_none() && pmt.pcr_pid != PID_NULL {
+                    pcr_pid = Some(pmt.pcr_pid);
+                }
+                Ok(())
+            },
+            Some(|packet: &TsPacketRef| {
+                if let Some(field) = packet.parse_adaptation_field()
+                    && let Some(pcr) = field.pcr()
+                {
+                    let sample = PcrSample {
+                        packet: packets,
+                        pcr,
+                        discontinuity: field.discontinuity_indicator,
+                    };
+                    found.push((packet.pid, sample));
+                }
+                packets += 1;
+                Ok(())
+            }),
+        )?;
+
+        let pid = pcr_pid.or_else(|| found.first().map(|(pid, _)| *pid));
+        let samples = found
+            .into_iter()
+            .filter(|(sample_pid, _)| Some(*sample_pid) == pid)
+            .map(|(_, sample)| sample)
+            .collect();
+        Ok(Self { samples, packets })
+    }
+
+    /// Ticks from the first to the last PCR and the number of packets they span, leaving
+    /// out the intervals across a discontinuity
+    fn span(&self) -> Result<(u64, u64)> {
+        let (mut ticks, mut packets) = (0, 0);
+        for pair in self.samples.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let delta = to.pcr.ticks_since(from.pcr);
+            if to.discontinuity || is_backwards(delta) {
+                continue;
+            }
+            ticks += delta;
+            packets += to.packet - from.packet;
+        }
+        if ticks == 0 || packets == 0 {
+            return Err(TsError::InsufficientPcr(self.samples.len()));
+        }
+        Ok((ticks, packets))
+    }
+}
+
+/// Estimate the duration of the transport stream `data` from its PCRs.
+///
+/// The time between the first and the last PCR is extended to the packets before and
+/// after them at the mux bitrate, so that back to back chunks add up to the duration of
+/// the stream. Intervals across a PCR discontinuity are left out. Fails with
+/// [`TsError::InsufficientPcr`] when `data` has fewer than two PCRs to time it by.
+pub fn estimate_duration(data: &[u8]) -> Result<Duration> {
+    let samples = PcrSamples::collect(data)?;
+    let (ticks, packets) = samples.span()?;
+    let total = ticks as u128 * samples.packets as u128 / packets as u128;
+    Ok(ticks_to_duration(total as u64))
+}
+
+/// Estimate the mux bitrate of the transport stream `data` in bits per second, from the
+/// bytes sent between its PCRs.
+///
+/// Fails like [`estimate_duration`].
+pub fn estimate_mux_bitrate(data: &[u8]) -> Result<u64> {
+    let (ticks, packets) = PcrSamples::collect(data)?.span()?;
+    Ok((packets as u128 * PACKET_BITS as u128 * PCR_HZ as u128 / ticks as u128) as u64)
+}
+
+/// An irregularity of the PCRs followed by a [`PcrTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcrEvent {
+    /// The PCR stepped back, jumped ahead further than the threshold, or its packet
+    /// signalled a discontinuity.
+    Discontinuity {
+        previous: Pcr,
+        pcr: Pcr,
+        /// Whether the discontinuity indicator of the packet was set
+        signalled: bool,
+    },
+    /// The PCR is off the time its packet was expected at, given the bytes sent since
+    /// the previous PCR and the mux bitrate so far, by more than the threshold.
+    Jitter { jitter: Duration },
+}
+
+/// Follows the PCRs of a stream packet by packet.
+///
+/// Every packet of the stream is passed to [`push`](Self::push), which counts it towards
+/// the mux bitrate and checks the PCR it carries on the PCR PID. The PCR PID is the one
+/// set with [`set_pid`](Self::set_pid), from the PMT, or else the first PID a PCR is
+/// seen on.
+#[derive(Debug, Clone)]
+pub struct PcrTracker {
+    pid: Option<u16>,
+    discontinuity_threshold: u64,
+    jitter_threshold: u64,
+    /// Last PCR and the index of its packet
+    last: Option<(Pcr, u64)>,
+    packets: u64,
+    /// Ticks and packets between PCRs, discontinuities left out
+    elapsed_ticks: u64,
+    elapsed_packets: u64,
+    discontinuities: u64,
+    max_jitter: u64,
+}
+
+impl Default for PcrTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PcrTracker {
+    /// A tracker reporting jumps of more than 1s as discontinuities and PCRs more than
+    /// 10ms off as jitter
+    pub fn new() -> Self {
+        Self {
+            pid: None,
+            discontinuity_threshold: duration_to_ticks(Duration::from_secs(1)),
+            jitter_threshold: duration_to_ticks(Duration::from_millis(10)),
+            last: None,
+            packets: 0,
+            elapsed_ticks: 0,
+            elapsed_packets: 0,
+            discontinuities: 0,
+            max_jitter: 0,
+        }
+    }
+
+    /// Read the PCRs on `pid`
+    pub fn with_pid(mut self, pid: u16) -> Self {
+        self.set_pid(pid);
+        self
+    }
+
+    /// Report PCRs jumping ahead by more than `threshold` as discontinuities
+    pub fn with_discontinuity_threshold(mut self, threshold: Duration) -> Self {
+        self.discontinuity_threshold = duration_to_ticks(threshold);
+        self
+    }
+
+    /// Report PCRs off by more than `threshold` as jitter
+    pub fn with_jitter_threshold(mut self, threshold: Duration) -> Self {
+        self.jitter_threshold = duration_to_ticks(threshold);
+        self
+    }
+
+    /// Read the PCRs on `pid`, e.g. the PCR PID of an updated PMT. The timing continues
+    /// from the last PCR of the previous PID.
+    pub fn set_pid(&mut self, pid: u16) {
+        self.pid = Some(pid);
+    }
+
+    /// PID the PCRs are read on, once known
+    pub fn pid(&self) -> Option<u16> {
+        self.pid
+    }
+
+    /// Track `packet`, returning the irregularity of the PCR it carries, if any.
+    pub fn push(&mut self, packet: &TsPacketRef) -> Option<PcrEvent> {
+        let index = self.packets;
+        self.packets += 1;
+
+        if self.pid.is_some_and(|pid| pid != packet.pid) {
+            return None;
+        }
+        let field = packet.parse_adaptation_field()?;
+        let pcr = field.pcr()?;
+        self.pid = Some(packet.pid);
+        let (previous, previous_index) = self.last.replace((pcr, index))?;
+
+        let ticks = pcr.ticks_since(previous);
+        if field.discontinuity_indicator
+            || is_backwards(ticks)
+            || ticks > self.discontinuity_threshold
+        {
+            self.discontinuities += 1;
+            return Some(PcrEvent::Discontinuity {
+                previous,
+                pcr,
+                signalled: field.discontinuity_indicator,
+            });
+        }
+
+        let packets = index - previous_index;
+        let expected = (self.elapsed_packets > 0).then(|| {
+            (packets as u128 * self.elapsed_ticks as u128 / self.elapsed_packets as u128) as u64
+        });
+        self.elapsed_ticks += ticks;
+        self.elapsed_packets += packets;
+
+        let jitter = expected?.abs_diff(ticks);
+        self.max_jitter = self.max_jitter.max(jitter);
+        (jitter > self.jitter_threshold).then(|| PcrEvent::Jitter {
+            jitter: ticks_to_duration(jitter),
+        })
+    }
+
+    /// Time elapsed between the PCRs so far, discontinuities left out
+    pub fn elapsed(&self) -> Duration {
+        ticks_to_duration(self.elapsed_ticks)
+    }
+
+    /// Mux bitrate so far in bits per second, once two PCRs were seen
+    pub fn mux_bitrate(&self) -> Option<u64> {
+        (self.elapsed_ticks > 0).then(|| {
+            (self.elapsed_packets as u128 * PACKET_BITS as u128 * PCR_HZ as u128
+                / self.elapsed_ticks as u128) as u64
+        })
+    }
+
+    /// Number of discontinuities reported
+    pub fn discontinuity_count(&self) -> u64 {
+        self.discontinuities
+    }
+
+    /// Largest jitter seen, whether reported or not
+    pub fn max_jitter(&self) -> Duration {
+        ticks_to_duration(self.max_jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Pat, PatProgram, Pmt, PmtStream, StreamType, TsWriter};
+
+    const PMT_PID: u16 = 0x1000;
+    const VIDEO_PID: u16 = 0x100;
+    const AUDIO_PID: u16 = 0x101;
+
+    /// PAT and PMT of one program with video and audio, timed by `pcr_pid`
+    fn tables(pcr_pid: u16) -> Vec<u8> {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        let pat = Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: PMT_PID,
+            }],
+        };
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid,
+            program_info: Vec::new(),
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: VIDEO_PID,
+                    es_info: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: AUDIO_PID,
+                    es_info: Vec::new(),
+                },
+            ],
+        };
+        writer.write_pat(&pat, &mut out).unwrap();
+        writer.write_pmt(PMT_PID, &pmt, &mut out).unwrap();
+        out
+    }
+
+    /// A packet of `pid`, carrying `pcr` in its adaptation field if any
+    fn packet(pid: u16, pcr: Option<u64>, discontinuity: bool) -> Vec<u8> {
+        let mut packet = vec![0x47, (pid >> 8) as u8 & 0x1F, pid as u8, 0x30];
+        let mut flags = 0u8;
+        if discontinuity {
+            flags |= 0x80;
+        }
+        let mut field = Vec::new();
+        if let Some(ticks) = pcr {
+            flags |= 0x10;
+            let Pcr { base, extension } = Pcr::from_27mhz(ticks);
+            field.extend_from_slice(&[
+                (base >> 25) as u8,
+                (base >> 17) as u8,
+                (base >> 9) as u8,
+                (base >> 1) as u8,
+                ((base as u8 & 0x01) << 7) | 0x7E | (extension >> 8) as u8,
+                extension as u8,
+            ]);
+        }
+        packet.push(1 + field.len() as u8);
+        packet.push(flags);
+        packet.extend_from_slice(&field);
+        packet.resize(TS_PACKET_SIZE, 0xFF);
+        packet
+    }
+
+    /// A stream at a constant mux rate: the tables, then `count` packets alternating
+    /// video and audio, a PCR on the video PID every 10 packets. Packets are sent every
+    /// `packet_ticks` starting at PCR `start`.
+    fn constant_rate_stream(start: u64, packet_ticks: u64, count: u64) -> Vec<u8> {
+        let mut data = tables(VIDEO_PID);
+        let first = data.len() as u64 / TS_PACKET_SIZE as u64;
+        for i in 0..count {
+            let index = first + i;
+            let pid = if i % 2 == 0 { VIDEO_PID } else { AUDIO_PID };
+            let pcr = (i % 10 == 0).then_some(start + index * packet_ticks);
+            data.extend(packet(pid, pcr, false));
+        }
+        data
+    }
+
+    #[test]
+    fn test_estimates_duration_and_bitrate() {
+        // 1000 packets a second: 1.504 Mbit/s
+        let data = constant_rate_stream(0, 27_000, 2000);
+        let duration = estimate_duration(&data).unwrap();
+        assert_eq!(duration, Duration::from_millis(2002));
+        assert_eq!(estimate_mux_bitrate(&data).unwrap(), 1_504_000);
+    }
+
+    #[test]
+    fn test_duration_across_pcr_wraparound() {
+        // The PCR base wraps around halfway through
+        let start = PCR_MODULO - 27_000 * 1000;
+        let data = constant_rate_stream(start, 27_000, 2000);
+        assert_eq!(
+            estimate_duration(&data).unwrap(),
+            Duration::from_millis(2002)
+        );
+        assert_eq!(estimate_mux_bitrate(&data).unwrap(), 1_504_000);
+    }
+
+    #[test]
+    fn test_pcr_pid_taken_from_pmt() {
+        // The audio PID carries PCRs too, on a clock the PMT does not point to
+        let mut data = tables(VIDEO_PID);
+        for i in 0..100u64 {
+            data.extend(packet(AUDIO_PID, Some(i * 1_000_000), false));
+            data.extend(packet(
+                VIDEO_PID,
+                (i % 10 == 0).then_some(i * 54_000),
+                false,
+            ));
+        }
+        // 1000 packets a second on the video clock
+        let duration = estimate_duration(&data).unwrap();
+        assert_eq!(duration, Duration::from_millis(202));
+
+        let data = constant_rate_stream(0, 27_000, 1);
+        assert!(matches!(
+            estimate_duration(&data),
+            Err(TsError::InsufficientPcr(1))
+        ));
+    }
+
+    #[test]
+    fn test_tracker_reports_discontinuities_and_jitter() {
+        let mut tracker = PcrTracker::new().with_jitter_threshold(Duration::from_millis(5));
+        let mut events = Vec::new();
+        let mut push = |tracker: &mut PcrTracker, data: Vec<u8>| {
+            let packet = TsPacketRef::parse(Bytes::from(data)).unwrap();
+            if let Some(event) = tracker.push(&packet) {
+                events.push(event);
+            }
+        };
+
+        // A PCR every 10 packets, 10ms apart
+        let mut pcr = 0;
+        for i in 0..100u64 {
+            let carries_pcr = i % 10 == 0;
+            push(
+                &mut tracker,
+                packet(VIDEO_PID, carries_pcr.then_some(pcr), false),
+            );
+            if carries_pcr {
+                pcr += 270_000;
+            }
+        }
+        // 20ms late, then a jump 5s ahead, signalled
+        pcr += 540_000;
+        push(&mut tracker, packet(VIDEO_PID, Some(pcr), false));
+        push(
+            &mut tracker,
+            packet(VIDEO_PID, Some(pcr + 5 * PCR_HZ), true),
+        );
+        // A step back, not signalled
+        push(&mut tracker, packet(VIDEO_PID, Some(pcr), false));
+
+        assert_eq!(tracker.pid(), Some(VIDEO_PID));
+        assert_eq!(
+            events[0],
+            PcrEvent::Jitter {
+                jitter: Duration::from_millis(20)
+            }
+        );
+        assert!(matches!(
+            events[1],
+            PcrEvent::Discontinuity {
+                signalled: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[2],
+            PcrEvent::Discontinuity {
+                signalled: false,
+                ..
+            }
+        ));
+        assert_eq!(events.len(), 3);
+        assert_eq!(tracker.discontinuity_count(), 2);
+        assert_eq!(tracker.max_jitter(), Duration::from_millis(20));
+        assert_eq!(tracker.elapsed(), Duration::from_millis(120));
+        // 10 packets every 10ms until the late PCR
+        assert_eq!(tracker.mux_bitrate(), Some(1_504_000 * 100 / 120));
+    }
+}
@@ -1,7 +1,16 @@
-use crate::{Result, TsError};
+use crate::{
+    Result, TsError,
+    descriptor::{
+        Descriptor, TAG_AC3, TAG_DTS, TAG_EAC3, TAG_METADATA, TAG_REGISTRATION, raw_descriptors,
+    },
+};
 use bytes::Bytes;
 
 /// Stream types defined in MPEG-2 and other standards
+///
+/// Values in the user private range (0x80..=0xFF) follow ATSC/SCTE usage.
+/// Codecs that are only identified by descriptors, like Opus, AV1 or AC-3 in
+/// DVB, are resolved with [`StreamType::classify_with_descriptors`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum StreamType {
@@ -111,16 +120,27 @@ pub enum StreamType {
     Dts = 0x82,
     /// Dolby TrueHD audio stream
     TrueHd = 0x83,
-    /// E-AC-3 audio stream (ATSC A/52B)
-    EAc3 = 0x84,
     /// DTS-HD audio stream
     DtsHd = 0x85,
-    /// DTS-HD Master Audio stream
-    DtsHdMa = 0x86,
-    /// Dolby E audio stream
-    DolbyE = 0x87,
-    /// Unknown stream type
-    Unknown(u8),
+    /// SCTE-35 splice information (ANSI/SCTE 35)
+    Scte35 = 0x86,
+    /// E-AC-3 audio stream (ATSC A/52B)
+    EAc3 = 0x87,
+    /// DTS-HD Master Audio stream, stream type 0x86 in Blu-ray (HDMV) streams
+    DtsHdMa,
+    /// Opus audio stream, carried as private PES data with an "Opus"
+    /// registration descriptor
+    Opus,
+    /// AV1 video stream, carried as private PES data with an "AV01"
+    /// registration descriptor
+    Av1,
+    /// ID3 timed metadata, carried as metadata PES data with an "ID3 "
+    /// metadata descriptor
+    Id3,
+    /// Stream type reserved by ITU-T Rec. H.222.0 | ISO/IEC 13818-1
+    Reserved(u8),
+    /// User private stream type without a known mapping
+    Private(u8),
 }
 
 impl From<u8> for StreamType {
@@ -180,10 +200,78 @@ impl From<u8> for StreamType {
             0x83 => StreamType::TrueHd,
             0x84 => StreamType::EAc3,
             0x85 => StreamType::DtsHd,
-            0x86 => StreamType::DtsHdMa,
-            0x87 => StreamType::DolbyE,
+            0x86 => StreamType::Scte35,
+            0x87 => StreamType::EAc3,
             0xA1 => StreamType::DiracI,
-            _ => StreamType::Unknown(value),
+            0x80..=0xFF => StreamType::Private(value),
+            _ => StreamType::Reserved(value),
+        }
+    }
+}
+
+impl From<StreamType> for u8 {
+    fn from(value: StreamType) -> Self {
+        match value {
+            StreamType::Mpeg1Video => 0x01,
+            StreamType::Mpeg2Video => 0x02,
+            StreamType::Mpeg1Audio => 0x03,
+            StreamType::Mpeg2Audio => 0x04,
+            StreamType::Mpeg2PrivateSections => 0x05,
+            StreamType::Mpeg2PrivatePes => 0x06,
+            StreamType::Mheg => 0x07,
+            StreamType::DsmCc => 0x08,
+            StreamType::H2221 => 0x09,
+            StreamType::Iso13818_6TypeA => 0x0A,
+            StreamType::Iso13818_6TypeB => 0x0B,
+            StreamType::Iso13818_6TypeC => 0x0C,
+            StreamType::Iso13818_6TypeD => 0x0D,
+            StreamType::Mpeg2Auxiliary => 0x0E,
+            StreamType::AdtsAac => 0x0F,
+            StreamType::Mpeg4Visual => 0x10,
+            StreamType::LatmAac => 0x11,
+            StreamType::Mpeg4SlPes => 0x12,
+            StreamType::Mpeg4SlSections => 0x13,
+            StreamType::Iso13818_6Sdp => 0x14,
+            StreamType::MetadataPes => 0x15,
+            StreamType::MetadataSections => 0x16,
+            StreamType::MetadataDataCarousel => 0x17,
+            StreamType::MetadataObjectCarousel => 0x18,
+            StreamType::MetadataSdp => 0x19,
+            StreamType::Ipmp => 0x1A,
+            StreamType::H264 => 0x1B,
+            StreamType::Mpeg4Audio => 0x1C,
+            StreamType::Mpeg4VisualPlain => 0x1D,
+            StreamType::Svc => 0x1E,
+            StreamType::Mvc => 0x1F,
+            StreamType::H264Additional => 0x20,
+            StreamType::Jpeg2000 => 0x21,
+            StreamType::H262Additional => 0x22,
+            StreamType::H264AdditionalView => 0x23,
+            StreamType::H265 => 0x24,
+            StreamType::Mvcd => 0x25,
+            StreamType::Timeline => 0x26,
+            StreamType::H265Temporal => 0x27,
+            StreamType::H265Enhancement => 0x28,
+            StreamType::H265TemporalEnhancement => 0x29,
+            StreamType::H265Tile => 0x2A,
+            StreamType::JpegXs => 0x32,
+            StreamType::H266 => 0x33,
+            StreamType::Evc => 0x34,
+            StreamType::Lcevc => 0x35,
+            StreamType::Avs2 => 0x40,
+            StreamType::Avs3 => 0x41,
+            StreamType::Avs3P10 => 0x42,
+            StreamType::Ac3 => 0x81,
+            StreamType::Dts => 0x82,
+            StreamType::TrueHd => 0x83,
+            StreamType::DtsHd => 0x85,
+            StreamType::Scte35 => 0x86,
+            StreamType::EAc3 => 0x87,
+            StreamType::DiracI => 0xA1,
+            StreamType::DtsHdMa => 0x86,
+            StreamType::Opus | StreamType::Av1 => 0x06,
+            StreamType::Id3 => 0x15,
+            StreamType::Reserved(value) | StreamType::Private(value) => value,
         }
     }
 }
@@ -218,6 +306,7 @@ impl StreamType {
                 | StreamType::Avs3
                 | StreamType::Avs3P10
                 | StreamType::DiracI
+                | StreamType::Av1
         )
     }
 
@@ -236,9 +325,169 @@ impl StreamType {
                 | StreamType::DtsHd
                 | StreamType::DtsHdMa
                 | StreamType::TrueHd
-                | StreamType::DolbyE
+                | StreamType::Opus
+        )
+    }
+
+    /// Check if this stream type carries metadata or signaling rather than media
+    pub fn is_metadata(&self) -> bool {
+        matches!(
+            self,
+            StreamType::MetadataPes
+                | StreamType::MetadataSections
+                | StreamType::MetadataDataCarousel
+                | StreamType::MetadataObjectCarousel
+                | StreamType::MetadataSdp
+                | StreamType::Timeline
+                | StreamType::Scte35
+                | StreamType::Id3
+        )
+    }
+
+    /// Refine this stream type with the ES info descriptors of the stream,
+    /// optionally followed by the program descriptors.
+    ///
+    /// DVB signals AC-3, E-AC-3, DTS and Opus as private PES data and tells
+    /// them apart by descriptor, AV1 is registered as "AV01" on private PES data
+    /// or a user private stream type, ID3 metadata is identified by its metadata
+    /// descriptor, and Blu-ray streams reuse private stream types. Types the
+    /// descriptors say nothing about are returned unchanged.
+    ///
+    /// The result describes the codec; a PMT being re-encoded should keep the
+    /// original stream type, as the refined one may encode to another value.
+    pub fn classify_with_descriptors(self, descriptors: &[Descriptor]) -> StreamType {
+        self.classify(
+            |id| {
+                descriptors.iter().any(|desc| {
+                    matches!(desc, Descriptor::Registration { format_identifier, .. }
+                        if format_identifier == id)
+                })
+            },
+            |tag| descriptors.iter().any(|desc| desc.tag() == tag),
+            || {
+                descriptors.iter().any(|desc| match desc {
+                    Descriptor::Unknown {
+                        tag: TAG_METADATA,
+                        data,
+                    } => metadata_format_identifier(data) == Some(*b"ID3 "),
+                    _ => false,
+                })
+            },
         )
     }
+
+    /// Like [`classify_with_descriptors`](Self::classify_with_descriptors), reading
+    /// the descriptors straight from the raw ES info and program info loops without
+    /// decoding or allocating them.
+    pub fn classify_with_descriptor_loops(self, es_info: &[u8], program_info: &[u8]) -> StreamType {
+        let descriptors = || raw_descriptors(es_info).chain(raw_descriptors(program_info));
+        self.classify(
+            |id| {
+                descriptors()
+                    .any(|(tag, data)| tag == TAG_REGISTRATION && data.get(..4) == Some(&id[..]))
+            },
+            |tag| descriptors().any(|(other, _)| other == tag),
+            || {
+                descriptors().any(|(tag, data)| {
+                    tag == TAG_METADATA && metadata_format_identifier(data) == Some(*b"ID3 ")
+                })
+            },
+        )
+    }
+
+    /// Codec the stream type stands for, once classified, if it is one
+    /// [`Codec`] knows.
+    pub fn codec(&self) -> Option<Codec> {
+        let codec = match self {
+            StreamType::Mpeg1Video | StreamType::Mpeg2Video => Codec::Mpeg2Video,
+            StreamType::H264 => Codec::H264,
+            StreamType::H265 => Codec::H265,
+            StreamType::H266 => Codec::H266,
+            StreamType::Mpeg1Audio | StreamType::Mpeg2Audio => Codec::MpegAudio,
+            StreamType::AdtsAac => Codec::Aac,
+            StreamType::LatmAac => Codec::AacLatm,
+            StreamType::Ac3 => Codec::Ac3,
+            StreamType::EAc3 => Codec::Eac3,
+            StreamType::Dts | StreamType::DtsHd | StreamType::DtsHdMa => Codec::Dts,
+            StreamType::TrueHd => Codec::TrueHd,
+            StreamType::Opus => Codec::Opus,
+            StreamType::Av1 => Codec::Av1,
+            _ => return None,
+        };
+        Some(codec)
+    }
+
+    /// The classification shared by the typed and the raw descriptors, asking
+    /// whether a format identifier is registered, whether a descriptor tag is
+    /// present and whether a metadata descriptor announces ID3.
+    fn classify(
+        self,
+        registered: impl Fn(&[u8; 4]) -> bool,
+        has_tag: impl Fn(u8) -> bool,
+        id3_metadata: impl Fn() -> bool,
+    ) -> StreamType {
+        match self {
+            StreamType::Mpeg2PrivatePes => {
+                if has_tag(TAG_AC3) || registered(b"AC-3") {
+                    StreamType::Ac3
+                } else if has_tag(TAG_EAC3) || registered(b"EAC3") {
+                    StreamType::EAc3
+                } else if has_tag(TAG_DTS) {
+                    StreamType::Dts
+                } else if registered(b"Opus") {
+                    StreamType::Opus
+                } else if registered(b"AV01") {
+                    StreamType::Av1
+                } else {
+                    self
+                }
+            }
+            StreamType::MetadataPes if registered(b"ID3 ") || id3_metadata() => StreamType::Id3,
+            StreamType::Scte35 if registered(b"HDMV") => StreamType::DtsHdMa,
+            StreamType::Private(0x84) if registered(b"HDMV") => StreamType::EAc3,
+            StreamType::Private(_) if registered(b"AV01") => StreamType::Av1,
+            _ => self,
+        }
+    }
+}
+
+/// Codecs the elementary streams of a PMT are looked up by, whether they are
+/// signaled by stream type or by descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// MPEG-1 or MPEG-2 video
+    Mpeg2Video,
+    H264,
+    H265,
+    H266,
+    Av1,
+    /// MPEG-1 or MPEG-2 audio
+    MpegAudio,
+    /// AAC in ADTS
+    Aac,
+    /// AAC in LATM/LOAS
+    AacLatm,
+    Ac3,
+    Eac3,
+    /// DTS, including DTS-HD
+    Dts,
+    TrueHd,
+    Opus,
+}
+
+/// Format identifier of the data of a metadata descriptor (tag 0x26), if it
+/// has one.
+fn metadata_format_identifier(data: &[u8]) -> Option<[u8; 4]> {
+    // metadata_application_format, followed by its identifier when 0xFFFF
+    let mut offset = 2;
+    if data.get(..2)? == [0xFF, 0xFF] {
+        offset += 4;
+    }
+    // metadata_format, followed by metadata_format_identifier when 0xFF
+    if *data.get(offset)? != 0xFF {
+        return None;
+    }
+    data.get(offset + 1..offset + 5)?.try_into().ok()
 }
 
 /// Program Map Table (PMT) - Table ID 0x02
@@ -385,45 +634,101 @@ impl Pmt {
 
     /// Parse PMT from PSI section data with CRC-32/MPEG-2 validation.
     pub fn parse_with_crc(data: &[u8]) -> Result<Self> {
-        if data.len() >= 7 {
-            let section_length = ((data[1] as u16 & 0x0F) << 8) | data[2] as u16;
-            let section_end = 3 + section_length as usize;
-            if section_end <= data.len()
-                && section_end >= 4
-                && !crate::crc32::validate_section_crc32(&data[..section_end])
-            {
-                let stored = u32::from_be_bytes([
-                    data[section_end - 4],
-                    data[section_end - 3],
-                    data[section_end - 2],
-                    data[section_end - 1],
-                ]);
-                let calculated = crate::crc32::mpeg2_crc32(&data[..section_end - 4]);
-                return Err(TsError::Crc32Mismatch {
-                    expected: stored,
-                    calculated,
-                });
+        crate::crc32::verify_section_crc32(data)?;
+        Self::parse(data)
+    }
+
+    /// Serialize this PMT into a complete PSI section, including the CRC-32.
+    pub fn encode_section(&self) -> Result<Vec<u8>> {
+        let streams_len: usize = self.streams.iter().map(|s| 5 + s.es_info.len()).sum();
+        // program_number..program_info_length (9) + descriptors + streams + CRC (4)
+        let section_length = 9 + self.program_info.len() + streams_len + 4;
+        if section_length > crate::writer::MAX_SECTION_LENGTH {
+            return Err(TsError::InvalidSectionLength(section_length as u16));
+        }
+
+        let mut section = Vec::with_capacity(3 + section_length);
+        section.push(self.table_id);
+        section.push(0xB0 | ((section_length >> 8) as u8 & 0x0F));
+        section.push(section_length as u8);
+        section.extend_from_slice(&self.program_number.to_be_bytes());
+        section
+            .push(0xC0 | ((self.version_number & 0x1F) << 1) | self.current_next_indicator as u8);
+        section.push(self.section_number);
+        section.push(self.last_section_number);
+        section.push(0xE0 | ((self.pcr_pid >> 8) as u8 & 0x1F));
+        section.push(self.pcr_pid as u8);
+        let program_info_length = self.program_info.len();
+        section.push(0xF0 | ((program_info_length >> 8) as u8 & 0x0F));
+        section.push(program_info_length as u8);
+        section.extend_from_slice(&self.program_info);
+
+        for stream in &self.streams {
+            if stream.es_info.len() > 0x03FF {
+                return Err(TsError::ParseError(format!(
+                    "ES info for PID 0x{:04x} too long: {} bytes",
+                    stream.elementary_pid,
+                    stream.es_info.len()
+                )));
             }
+            section.push(stream.stream_type.into());
+            section.push(0xE0 | ((stream.elementary_pid >> 8) as u8 & 0x1F));
+            section.push(stream.elementary_pid as u8);
+            section.push(0xF0 | ((stream.es_info.len() >> 8) as u8 & 0x0F));
+            section.push(stream.es_info.len() as u8);
+            section.extend_from_slice(&stream.es_info);
         }
-        Self::parse(data)
+
+        let crc = crate::crc32::mpeg2_crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        Ok(section)
+    }
+
+    /// Stream type of `stream` refined with its descriptors and the program
+    /// descriptors, see [`StreamType::classify_with_descriptors`].
+    pub fn classify_stream(&self, stream: &PmtStream) -> StreamType {
+        stream
+            .stream_type
+            .classify_with_descriptor_loops(&stream.es_info, &self.program_info)
     }
 
     /// Get all video streams
     pub fn video_streams(&self) -> Vec<&PmtStream> {
         self.streams
             .iter()
-            .filter(|s| s.stream_type.is_video())
+            .filter(|s| self.classify_stream(s).is_video())
             .collect()
     }
 
-    /// Get all audio streams
+    /// Get all audio streams, including those only identified by descriptor
     pub fn audio_streams(&self) -> Vec<&PmtStream> {
         self.streams
             .iter()
-            .filter(|s| s.stream_type.is_audio())
+            .filter(|s| self.classify_stream(s).is_audio())
             .collect()
     }
 
+    /// First video stream in PMT order
+    pub fn first_video(&self) -> Option<&PmtStream> {
+        self.streams
+            .iter()
+            .find(|s| self.classify_stream(s).is_video())
+    }
+
+    /// First audio stream in PMT order
+    pub fn first_audio(&self) -> Option<&PmtStream> {
+        self.streams
+            .iter()
+            .find(|s| self.classify_stream(s).is_audio())
+    }
+
+    /// First stream carrying `codec`
+    pub fn find_by_codec(&self, codec: Codec) -> Option<&PmtStream> {
+        self.streams
+            .iter()
+            .find(|s| self.classify_stream(s).codec() == Some(codec))
+    }
+
     /// Get stream by PID
     pub fn get_stream(&self, pid: u16) -> Option<&PmtStream> {
         self.streams.iter().find(|s| s.elementary_pid == pid)
@@ -438,29 +743,411 @@ impl Pmt {
 }
 
 impl PmtStream {
-    /// Iterate over ES info descriptors.
-    pub fn descriptors(&self) -> crate::descriptor::DescriptorIterator {
-        crate::descriptor::DescriptorIterator::new(Bytes::from(self.es_info.clone()))
+    /// Iterate over typed ES info descriptors.
+    pub fn descriptors(&self) -> crate::descriptor::Descriptors {
+        crate::descriptor::Descriptors::new(Bytes::from(self.es_info.clone()))
     }
 }
 
 impl Pmt {
-    /// Iterate over program info descriptors.
-    pub fn program_descriptors(&self) -> crate::descriptor::DescriptorIterator {
-        crate::descriptor::DescriptorIterator::new(Bytes::from(self.program_info.clone()))
+    /// Iterate over typed program info descriptors.
+    pub fn program_descriptors(&self) -> crate::descriptor::Descriptors {
+        crate::descriptor::Descriptors::new(Bytes::from(self.program_info.clone()))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::descriptor::{Descriptor, Descriptors};
 
     #[test]
     fn test_stream_type_conversion() {
         assert_eq!(StreamType::from(0x1B), StreamType::H264);
         assert_eq!(StreamType::from(0x24), StreamType::H265);
         assert_eq!(StreamType::from(0x0F), StreamType::AdtsAac);
-        assert_eq!(StreamType::from(0xFF), StreamType::Unknown(0xFF));
+        assert_eq!(StreamType::from(0xFF), StreamType::Private(0xFF));
+        assert_eq!(StreamType::from(0x00), StreamType::Reserved(0x00));
+    }
+
+    #[test]
+    fn test_stream_type_assigned_values() {
+        use StreamType::*;
+
+        // (value, type, video, audio, metadata)
+        let table = [
+            (0x01, Mpeg1Video, true, false, false),
+            (0x02, Mpeg2Video, true, false, false),
+            (0x03, Mpeg1Audio, false, true, false),
+            (0x04, Mpeg2Audio, false, true, false),
+            (0x05, Mpeg2PrivateSections, false, false, false),
+            (0x06, Mpeg2PrivatePes, false, false, false),
+            (0x07, Mheg, false, false, false),
+            (0x08, DsmCc, false, false, false),
+            (0x09, H2221, false, false, false),
+            (0x0A, Iso13818_6TypeA, false, false, false),
+            (0x0B, Iso13818_6TypeB, false, false, false),
+            (0x0C, Iso13818_6TypeC, false, false, false),
+            (0x0D, Iso13818_6TypeD, false, false, false),
+            (0x0E, Mpeg2Auxiliary, false, false, false),
+            (0x0F, AdtsAac, false, true, false),
+            (0x10, Mpeg4Visual, true, false, false),
+            (0x11, LatmAac, false, true, false),
+            (0x12, Mpeg4SlPes, false, false, false),
+            (0x13, Mpeg4SlSections, false, false, false),
+            (0x14, Iso13818_6Sdp, false, false, false),
+            (0x15, MetadataPes, false, false, true),
+            (0x16, MetadataSections, false, false, true),
+            (0x17, MetadataDataCarousel, false, false, true),
+            (0x18, MetadataObjectCarousel, false, false, true),
+            (0x19, MetadataSdp, false, false, true),
+            (0x1A, Ipmp, false, false, false),
+            (0x1B, H264, true, false, false),
+            (0x1C, Mpeg4Audio, false, true, false),
+            (0x1D, Mpeg4VisualPlain, true, false, false),
+            (0x1E, Svc, true, false, false),
+            (0x1F, Mvc, true, false, false),
+            (0x20, H264Additional, true, false, false),
+            (0x21, Jpeg2000, true, false, false),
+            (0x22, H262Additional, true, false, false),
+            (0x23, H264AdditionalView, true, false, false),
+            (0x24, H265, true, false, false),
+            (0x25, Mvcd, true, false, false),
+            (0x26, Timeline, false, false, true),
+            (0x27, H265Temporal, true, false, false),
+            (0x28, H265Enhancement, true, false, false),
+            (0x29, H265TemporalEnhancement, true, false, false),
+            (0x2A, H265Tile, true, false, false),
+            (0x32, JpegXs, true, false, false),
+            (0x33, H266, true, false, false),
+            (0x34, Evc, true, false, false),
+            (0x35, Lcevc, true, false, false),
+            (0x40, Avs2, true, false, false),
+            (0x41, Avs3, true, false, false),
+            (0x42, Avs3P10, true, false, false),
+            (0x81, Ac3, false, true, false),
+            (0x82, Dts, false, true, false),
+            (0x83, TrueHd, false, true, false),
+            (0x85, DtsHd, false, true, false),
+            (0x86, Scte35, false, false, true),
+            (0x87, EAc3, false, true, false),
+            (0xA1, DiracI, true, false, false),
+            (0x00, Reserved(0x00), false, false, false),
+            (0x2B, Reserved(0x2B), false, false, false),
+            (0x7F, Reserved(0x7F), false, false, false),
+            (0x80, Private(0x80), false, false, false),
+            (0x84, Private(0x84), false, false, false),
+            (0xFF, Private(0xFF), false, false, false),
+        ];
+
+        for (value, stream_type, video, audio, metadata) in table {
+            assert_eq!(StreamType::from(value), stream_type, "0x{value:02X}");
+            assert_eq!(u8::from(stream_type), value, "{stream_type:?}");
+            assert_eq!(stream_type.is_video(), video, "{stream_type:?}");
+            assert_eq!(stream_type.is_audio(), audio, "{stream_type:?}");
+            assert_eq!(stream_type.is_metadata(), metadata, "{stream_type:?}");
+        }
+    }
+
+    #[test]
+    fn test_stream_type_classify_with_descriptors() {
+        let descriptors = |es_info: &[u8]| -> Vec<Descriptor> {
+            crate::descriptor::Descriptors::new(Bytes::copy_from_slice(es_info))
+                .flatten()
+                .collect()
+        };
+        let id3_metadata = [
+            0x26, 0x0D, 0xFF, 0xFF, b'I', b'D', b'3', b' ', 0xFF, b'I', b'D', b'3', b' ', 0x00,
+            0x0F,
+        ];
+
+        let cases: [(StreamType, &[u8], StreamType); 9] = [
+            (
+                StreamType::Mpeg2PrivatePes,
+                &[0x6A, 0x01, 0x00],
+                StreamType::Ac3,
+            ),
+            (
+                StreamType::Mpeg2PrivatePes,
+                &[0x7A, 0x01, 0x00],
+                StreamType::EAc3,
+            ),
+            (StreamType::Mpeg2PrivatePes, &[0x7B, 0x00], StreamType::Dts),
+            (
+                StreamType::Mpeg2PrivatePes,
+                &[0x05, 0x04, b'O', b'p', b'u', b's'],
+                StreamType::Opus,
+            ),
+            (
+                StreamType::Mpeg2PrivatePes,
+                &[],
+                StreamType::Mpeg2PrivatePes,
+            ),
+            (StreamType::MetadataPes, &id3_metadata, StreamType::Id3),
+            (
+                StreamType::Scte35,
+                &[0x05, 0x04, b'H', b'D', b'M', b'V'],
+                StreamType::DtsHdMa,
+            ),
+            (
+                StreamType::Private(0x84),
+                &[0x05, 0x04, b'H', b'D', b'M', b'V'],
+                StreamType::EAc3,
+            ),
+            (
+                StreamType::Mpeg2PrivatePes,
+                &[
+                    0x05, 0x04, b'A', b'V', b'0', b'1', 0x80, 0x04, 0x81, 0x08, 0x0C, 0x00,
+                ],
+                StreamType::Av1,
+            ),
+            (
+                StreamType::Private(0xDB),
+                &[0x05, 0x04, b'A', b'V', b'0', b'1'],
+                StreamType::Av1,
+            ),
+            (StreamType::H264, &[0x6A, 0x01, 0x00], StreamType::H264),
+        ];
+
+        for (stream_type, es_info, expected) in cases {
+            let refined = stream_type.classify_with_descriptors(&descriptors(es_info));
+            assert_eq!(refined, expected, "{stream_type:?} with {es_info:02X?}");
+        }
+        assert!(StreamType::Opus.is_audio());
+        assert!(StreamType::Av1.is_video());
+        assert_eq!(u8::from(StreamType::Av1), 0x06);
+        assert!(StreamType::Id3.is_metadata());
+    }
+
+    #[test]
+    fn test_stream_type_to_u8_round_trip() {
+        for value in 0..=u8::MAX {
+            assert_eq!(u8::from(StreamType::from(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_pmt_encode_section_round_trip() {
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 7,
+            version_number: 3,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: vec![0x05, 0x04, b'H', b'D', b'M', b'V'],
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: 0x100,
+                    es_info: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::AdtsAac,
+                    elementary_pid: 0x101,
+                    es_info: vec![0x0A, 0x04, b'e', b'n', b'g', 0x00],
+                },
+            ],
+        };
+
+        let section = pmt.encode_section().unwrap();
+        let parsed = Pmt::parse_with_crc(&section).unwrap();
+        assert_eq!(parsed.program_number, 7);
+        assert_eq!(parsed.version_number, 3);
+        assert_eq!(parsed.pcr_pid, 0x100);
+        assert_eq!(parsed.program_info, pmt.program_info);
+        assert_eq!(parsed.streams.len(), 2);
+        assert_eq!(parsed.streams[1].stream_type, StreamType::AdtsAac);
+        assert_eq!(parsed.streams[1].es_info, pmt.streams[1].es_info);
+    }
+
+    /// A PMT with an H.264 video stream and an AC-3 audio stream signaled by
+    /// `audio_type` and the descriptors
+    fn ac3_service(audio_type: StreamType, es_info: Vec<u8>, program_info: Vec<u8>) -> Pmt {
+        Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info,
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: 0x100,
+                    es_info: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::Mpeg2PrivatePes,
+                    elementary_pid: 0x102,
+                    // Teletext, private data the selectors must skip
+                    es_info: vec![0x56, 0x05, b'e', b'n', b'g', 0x09, 0x00],
+                },
+                PmtStream {
+                    stream_type: audio_type,
+                    elementary_pid: 0x101,
+                    es_info,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_dvb_and_atsc_ac3_resolve_to_same_codec() {
+        // DVB: private PES data with an AC-3 descriptor
+        let dvb = ac3_service(
+            StreamType::Mpeg2PrivatePes,
+            vec![0x0A, 0x04, b'e', b'n', b'g', 0x00, 0x6A, 0x01, 0x00],
+            Vec::new(),
+        );
+        // ATSC: stream type 0x81 in a program registered as GA94
+        let atsc = ac3_service(
+            StreamType::from(0x81),
+            Vec::new(),
+            vec![0x05, 0x04, b'G', b'A', b'9', b'4'],
+        );
+
+        for pmt in [dvb, atsc] {
+            let audio = pmt.find_by_codec(Codec::Ac3).expect("AC-3 stream");
+            assert_eq!(audio.elementary_pid, 0x101);
+            assert_eq!(pmt.classify_stream(audio).codec(), Some(Codec::Ac3));
+            assert_eq!(pmt.first_audio().map(|s| s.elementary_pid), Some(0x101));
+            assert_eq!(pmt.first_video().map(|s| s.elementary_pid), Some(0x100));
+            assert_eq!(pmt.audio_streams().len(), 1);
+            assert_eq!(pmt.video_streams().len(), 1);
+            assert!(pmt.find_by_codec(Codec::Eac3).is_none());
+
+            let section = Bytes::from(pmt.encode_section().unwrap());
+            let pmt_ref = crate::PmtRef::parse_with_crc(section).unwrap();
+            let audio = pmt_ref.find_by_codec(Codec::Ac3).expect("AC-3 stream");
+            assert_eq!(audio.elementary_pid, 0x101);
+            assert_eq!(pmt_ref.classify_stream(&audio).codec(), Some(Codec::Ac3));
+            assert_eq!(pmt_ref.first_audio().map(|s| s.elementary_pid), Some(0x101));
+            assert_eq!(pmt_ref.first_video().map(|s| s.elementary_pid), Some(0x100));
+            assert_eq!(pmt_ref.audio_streams().count(), 1);
+            assert_eq!(pmt_ref.video_streams().count(), 1);
+            assert!(pmt_ref.find_by_codec(Codec::H264).is_some());
+        }
+    }
+
+    #[cfg(feature = "av1")]
+    #[test]
+    fn test_av1_video_descriptor_from_pmt() {
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: Vec::new(),
+            streams: vec![PmtStream {
+                stream_type: StreamType::Mpeg2PrivatePes,
+                elementary_pid: 0x100,
+                es_info: vec![
+                    0x05, 0x04, b'A', b'V', b'0', b'1', // Registration
+                    0x80, 0x04, 0x81, 0x4D, 0x5E, 0x7A, // AV1 video descriptor
+                ],
+            }],
+        };
+        let section = Bytes::from(pmt.encode_section().unwrap());
+        let pmt_ref = crate::PmtRef::parse_with_crc(section).unwrap();
+        let stream = pmt_ref.first_video().expect("AV1 stream");
+        assert_eq!(pmt_ref.classify_stream(&stream), StreamType::Av1);
+        assert_eq!(
+            pmt_ref.find_by_codec(Codec::Av1).map(|s| s.elementary_pid),
+            Some(0x100)
+        );
+
+        let descriptor = stream
+            .descriptors()
+            .flatten()
+            .find_map(|desc| desc.as_av1_video_descriptor())
+            .expect("AV1 video descriptor")
+            .unwrap();
+        assert_eq!(
+            descriptor,
+            av1::AV1VideoDescriptor {
+                tag: 0x80,
+                length: 4,
+                codec_configuration_record: av1::AV1CodecConfigurationRecord {
+                    seq_profile: 2,
+                    seq_level_idx_0: 13,
+                    seq_tier_0: false,
+                    high_bitdepth: true,
+                    twelve_bit: false,
+                    monochrome: true,
+                    chroma_subsampling_x: true,
+                    chroma_subsampling_y: true,
+                    chroma_sample_position: 2,
+                    hdr_wcg_idc: 1,
+                    initial_presentation_delay_minus_one: Some(10),
+                    config_obu: Bytes::new(),
+                },
+            }
+        );
+
+        let truncated = Descriptor::Unknown {
+            tag: crate::descriptor::TAG_AV1_VIDEO,
+            data: Bytes::from_static(&[0x81, 0x4D]),
+        };
+        assert!(matches!(
+            truncated.as_av1_video_descriptor(),
+            Some(Err(TsError::Io(_)))
+        ));
+        let registration = stream.descriptors().next().unwrap().unwrap();
+        assert!(registration.as_av1_video_descriptor().is_none());
+    }
+
+    #[test]
+    fn test_classify_with_descriptor_loops_matches_typed() {
+        use StreamType::*;
+        let cases: [(StreamType, &[u8], &[u8], StreamType); 6] = [
+            (Mpeg2PrivatePes, &[0x7A, 0x00], &[], EAc3),
+            (
+                Mpeg2PrivatePes,
+                &[0x05, 0x04, b'O', b'p', b'u', b's'],
+                &[],
+                Opus,
+            ),
+            (Mpeg2PrivatePes, &[0x7B, 0x00], &[], Dts),
+            (
+                Private(0x84),
+                &[],
+                &[0x05, 0x04, b'H', b'D', b'M', b'V'],
+                EAc3,
+            ),
+            (
+                MetadataPes,
+                &[
+                    0x26, 0x0B, 0xFF, 0xFF, b'I', b'D', b'3', b' ', 0xFF, b'I', b'D', b'3', b' ',
+                ],
+                &[],
+                Id3,
+            ),
+            // A truncated descriptor ends the loop
+            (
+                Mpeg2PrivatePes,
+                &[0x0A, 0x08, b'e', b'n', b'g', 0x6A],
+                &[],
+                Mpeg2PrivatePes,
+            ),
+        ];
+        for (stream_type, es_info, program_info, expected) in cases {
+            let typed: Vec<_> = Descriptors::new(Bytes::copy_from_slice(es_info))
+                .chain(Descriptors::new(Bytes::copy_from_slice(program_info)))
+                .flatten()
+                .collect();
+            let classified = stream_type.classify_with_descriptor_loops(es_info, program_info);
+            assert_eq!(classified, expected, "{stream_type:?} {es_info:02X?}");
+            assert_eq!(classified, stream_type.classify_with_descriptors(&typed));
+        }
+        assert_eq!(StreamType::Private(0x84).codec(), None);
+        assert_eq!(StreamType::LatmAac.codec(), Some(Codec::AacLatm));
     }
 
     #[test]
@@ -509,4 +1196,53 @@ mod tests {
         assert_eq!(pmt.streams[0].elementary_pid, 0x100);
         assert!(pmt.streams[0].stream_type.is_video());
     }
+
+    #[test]
+    fn test_pmt_descriptors() {
+        let data = vec![
+            0x02, 0x80, 0x1E, // Table ID, section length 30
+            0x00, 0x01, 0x01, 0x00, 0x00, // Program 1, version 0
+            0xE1, 0x00, // PCR PID
+            0x00, 0x06, // Program info length (6)
+            0x05, 0x04, b'H', b'D', b'M', b'V', // Registration
+            0x0F, // ADTS AAC
+            0xE1, 0x01, // Elementary PID
+            0x00, 0x06, // ES info length (6)
+            0x0A, 0x04, b'j', b'p', b'n', 0x00, // Language
+            0x00, 0x00, 0x00, 0x00, // CRC32 placeholder
+        ];
+        let pmt = Pmt::parse(&data).unwrap();
+
+        let program: Vec<_> = pmt.program_descriptors().collect::<Result<_>>().unwrap();
+        assert!(matches!(
+            &program[..],
+            [Descriptor::Registration { format_identifier, .. }] if format_identifier == b"HDMV"
+        ));
+
+        let es: Vec<_> = pmt.streams[0].descriptors().collect::<Result<_>>().unwrap();
+        assert!(matches!(
+            &es[..],
+            [Descriptor::Iso639Language(entries)] if &entries[0].language_code == b"jpn"
+        ));
+    }
+
+    #[test]
+    fn test_pmt_descriptor_overrun_is_error() {
+        let stream = PmtStream {
+            stream_type: StreamType::H264,
+            elementary_pid: 0x100,
+            // Declares 8 bytes of payload but only carries 2
+            es_info: vec![0x28, 0x08, 0x64, 0x00],
+        };
+        let mut descriptors = stream.descriptors();
+        assert!(matches!(
+            descriptors.next(),
+            Some(Err(TsError::InvalidDescriptorLength {
+                tag: 0x28,
+                length: 8,
+                available: 2
+            }))
+        ));
+        assert!(descriptors.next().is_none());
+    }
 }
@@ -0,0 +1,462 @@
+//! PAT/PMT handling shared by [`OwnedTsParser`](crate::OwnedTsParser) and
+//! [`TsParser`](crate::TsParser).
+//!
+//! Each parser keeps its own table types. [`PsiTables`] works on them through
+//! the [`PatSection`] and [`PmtSection`] traits to reassemble sections, apply
+//! the version rules, track the PIDs carrying PMTs and filter programs and
+//! PIDs.
+
+use std::collections::{HashMap, HashSet};
+
+use bytes::Bytes;
+
+use crate::{
+    Result,
+    error::ErrorContext,
+    packet::{PID_PAT, PID_SDT},
+    parser_zero_copy::{PatRef, PmtRef},
+    pat::Pat,
+    pmt::Pmt,
+    section::SectionAssembler,
+};
+
+/// Number of distinct 13-bit PIDs
+pub(crate) const PID_SPACE: usize = 8192;
+
+/// A versioned PSI table section
+pub(crate) trait PsiSection: Clone + Sized {
+    fn parse_section(data: Bytes) -> Result<Self>;
+
+    fn parse_section_with_crc(data: Bytes) -> Result<Self>;
+
+    fn version_number(&self) -> u8;
+
+    fn current_next_indicator(&self) -> bool;
+
+    /// Parse a section received on `pid`, reporting CRC failures in the
+    /// context of the PID
+    fn parse_on_pid(pid: u16, data: Bytes, validate_crc: bool) -> Result<Self> {
+        if validate_crc {
+            Self::parse_section_with_crc(data).map_err(|e| {
+                e.in_context(ErrorContext {
+                    pid: Some(pid),
+                    ..ErrorContext::default()
+                })
+            })
+        } else {
+            Self::parse_section(data)
+        }
+    }
+}
+
+/// A PAT section
+pub(crate) trait PatSection: PsiSection {
+    fn section_number(&self) -> u8;
+
+    fn last_section_number(&self) -> u8;
+
+    /// `(program_number, pmt_pid)` of the programs listed by the section
+    fn program_entries(&self) -> impl Iterator<Item = (u16, u16)> + '_;
+}
+
+/// A PMT section
+pub(crate) trait PmtSection: PsiSection {
+    fn pcr_pid(&self) -> u16;
+
+    fn elementary_pids(&self) -> impl Iterator<Item = u16> + '_;
+}
+
+/// PAT and PMTs of a transport stream, restricted to the selected programs
+#[derive(Debug)]
+pub(crate) struct PsiTables<P, M> {
+    /// Sections of the current PAT, ordered by section number
+    pat: Vec<P>,
+    /// Sections of a new PAT version received so far
+    pending_pat: Vec<P>,
+    /// Program mapping of the selected programs: program_number -> pmt_pid
+    program_pids: HashMap<u16, u16>,
+    /// Reverse PMT PID lookup: pmt_pid -> program_number
+    pmt_pids: HashMap<u16, u16>,
+    /// Fast PMT PID membership table
+    pmt_pid_flags: Box<[bool; PID_SPACE]>,
+    /// Current PMTs by program number
+    pmts: HashMap<u16, M>,
+    /// Assemblers for PSI sections spanning several packets, keyed by PID
+    psi_buffers: HashMap<u16, SectionAssembler>,
+    /// Programs to process, all when unset
+    program_filter: Option<HashSet<u16>>,
+    /// PIDs to process besides the PAT and PMTs, all when unset
+    pid_filter: Option<HashSet<u16>>,
+    /// PIDs passing the filters, everything when no filter is set
+    wanted_pids: Option<Box<[bool; PID_SPACE]>>,
+}
+
+impl<P, M> Default for PsiTables<P, M> {
+    fn default() -> Self {
+        Self {
+            pat: Vec::new(),
+            pending_pat: Vec::new(),
+            program_pids: HashMap::new(),
+            pmt_pids: HashMap::new(),
+            pmt_pid_flags: Box::new([false; PID_SPACE]),
+            pmts: HashMap::new(),
+            psi_buffers: HashMap::new(),
+            program_filter: None,
+            pid_filter: None,
+            wanted_pids: None,
+        }
+    }
+}
+
+impl<P: PatSection, M: PmtSection> PsiTables<P, M> {
+    /// Only process the given programs; an empty list removes the filter.
+    pub(crate) fn set_program_filter(&mut self, program_numbers: &[u16]) {
+        self.program_filter =
+            (!program_numbers.is_empty()).then(|| program_numbers.iter().copied().collect());
+        self.rebuild_programs();
+        self.pmts
+            .retain(|program_number, _| self.program_pids.contains_key(program_number));
+        self.rebuild_wanted_pids();
+    }
+
+    /// Only process packets of the given PIDs, besides the PAT and the PMTs of
+    /// the selected programs; an empty list removes the filter.
+    pub(crate) fn set_pid_filter(&mut self, pids: &[u16]) {
+        self.pid_filter = (!pids.is_empty()).then(|| pids.iter().copied().collect());
+        self.rebuild_wanted_pids();
+    }
+
+    /// Whether packets of `pid` pass the filters. The PAT always does.
+    #[inline]
+    pub(crate) fn wants_pid(&self, pid: u16) -> bool {
+        self.wanted_pids
+            .as_ref()
+            .is_none_or(|wanted| wanted[pid as usize % PID_SPACE])
+    }
+
+    /// Whether `pid` carries the PMT of a selected program
+    #[inline]
+    pub(crate) fn is_pmt_pid(&self, pid: u16) -> bool {
+        self.pmt_pid_flags[pid as usize % PID_SPACE]
+    }
+
+    /// The program whose PMT is carried on `pid`
+    pub(crate) fn program_of_pmt_pid(&self, pid: u16) -> Option<u16> {
+        self.pmt_pids.get(&pid).copied()
+    }
+
+    /// Feed the payload of a packet of `pid` and return the sections it completed
+    pub(crate) fn push_payload(
+        &mut self,
+        pid: u16,
+        payload: &[u8],
+        payload_unit_start_indicator: bool,
+    ) -> Vec<Bytes> {
+        self.psi_buffers
+            .entry(pid)
+            .or_default()
+            .push(payload, payload_unit_start_indicator)
+    }
+
+    /// Drop partial sections of PIDs that no longer carry tables, keeping the
+    /// PAT, the PMTs and the PIDs for which `keep` holds
+    pub(crate) fn retain_buffers(&mut self, keep: impl Fn(u16) -> bool) {
+        let pmt_pid_flags = &self.pmt_pid_flags;
+        self.psi_buffers.retain(|&pid, _| {
+            pid == PID_PAT || pmt_pid_flags[pid as usize % PID_SPACE] || keep(pid)
+        });
+    }
+
+    /// Apply a PAT section.
+    ///
+    /// Sections that are not yet applicable (`current_next_indicator` unset)
+    /// are ignored until they are sent again as current, and so are repeats
+    /// of the current version. A PAT split across sections is committed once
+    /// every section up to `last_section_number` of the new version arrived;
+    /// the sections of the replaced version are then returned.
+    pub(crate) fn accept_pat(&mut self, section: P) -> Option<Vec<P>> {
+        let is_new = section.current_next_indicator()
+            && section.section_number() <= section.last_section_number()
+            && self
+                .pat
+                .first()
+                .is_none_or(|current| current.version_number() != section.version_number());
+        if !is_new {
+            return None;
+        }
+
+        // Sections of another version restart the collection
+        if self.pending_pat.first().is_some_and(|pending| {
+            pending.version_number() != section.version_number()
+                || pending.last_section_number() != section.last_section_number()
+        }) {
+            self.pending_pat.clear();
+        }
+        if self
+            .pending_pat
+            .iter()
+            .all(|pending| pending.section_number() != section.section_number())
+        {
+            self.pending_pat.push(section);
+        }
+        if self.pending_pat.len() <= self.pending_pat[0].last_section_number() as usize {
+            return None;
+        }
+        let mut sections = std::mem::take(&mut self.pending_pat);
+        sections.sort_by_key(|section| section.section_number());
+        let previous = std::mem::replace(&mut self.pat, sections);
+
+        // Programs still carried on the same PMT PID keep their PMT, so they are
+        // not reported again and their next version can be compared to it.
+        let previous_program_pids = self.program_pids.clone();
+        self.rebuild_programs();
+        let program_pids = &self.program_pids;
+        self.pmts.retain(|program_number, _| {
+            let pmt_pid = program_pids.get(program_number);
+            pmt_pid.is_some() && previous_program_pids.get(program_number) == pmt_pid
+        });
+        self.rebuild_wanted_pids();
+        Some(previous)
+    }
+
+    /// Whether `pmt` is a new version of the PMT of `program_number`
+    pub(crate) fn is_new_pmt(&self, program_number: u16, pmt: &M) -> bool {
+        pmt.current_next_indicator()
+            && self
+                .pmts
+                .get(&program_number)
+                .is_none_or(|current| current.version_number() != pmt.version_number())
+    }
+
+    /// Make `pmt` the current PMT of `program_number`, returning the previous one
+    pub(crate) fn commit_pmt(&mut self, program_number: u16, pmt: M) -> Option<M> {
+        let previous = self.pmts.insert(program_number, pmt);
+        self.rebuild_wanted_pids();
+        previous
+    }
+
+    /// Sections of the current PAT, ordered by section number
+    pub(crate) fn pat_sections(&self) -> &[P] {
+        &self.pat
+    }
+
+    /// Current PMTs of the selected programs, by program number
+    pub(crate) fn pmts(&self) -> &HashMap<u16, M> {
+        &self.pmts
+    }
+
+    /// Whether a PAT was received along with the PMTs of all its selected
+    /// programs
+    pub(crate) fn has_all_pmts(&self) -> bool {
+        !self.pat.is_empty()
+            && self
+                .program_pids
+                .keys()
+                .all(|program_number| self.pmts.contains_key(program_number))
+    }
+
+    /// Number of selected programs listed by the current PAT
+    pub(crate) fn program_count(&self) -> usize {
+        self.program_pids.len()
+    }
+
+    pub(crate) fn estimated_memory_usage(&self) -> usize {
+        self.program_pids.capacity() * (std::mem::size_of::<u16>() * 2)
+            + self.pmt_pids.capacity() * (std::mem::size_of::<u16>() * 2)
+            + self.pmts.capacity() * (std::mem::size_of::<u16>() + std::mem::size_of::<M>())
+    }
+
+    /// Forget all tables, keeping the filters
+    pub(crate) fn reset(&mut self) {
+        self.pat.clear();
+        self.pending_pat.clear();
+        self.program_pids.clear();
+        self.pmt_pids.clear();
+        self.pmt_pid_flags.fill(false);
+        self.pmts.clear();
+        self.psi_buffers.clear();
+        self.rebuild_wanted_pids();
+    }
+
+    /// Rebuild the program mapping of the selected programs from the current PAT
+    fn rebuild_programs(&mut self) {
+        self.program_pids.clear();
+        self.pmt_pids.clear();
+        self.pmt_pid_flags.fill(false);
+        for (program_number, pmt_pid) in self
+            .pat
+            .iter()
+            .flat_map(|section| section.program_entries())
+        {
+            // Program 0 points to the network information table
+            let selected = self
+                .program_filter
+                .as_ref()
+                .is_none_or(|programs| programs.contains(&program_number));
+            if program_number != 0 && selected {
+                self.program_pids.insert(program_number, pmt_pid);
+                self.pmt_pids.insert(pmt_pid, program_number);
+                self.pmt_pid_flags[pmt_pid as usize % PID_SPACE] = true;
+            }
+        }
+    }
+
+    /// Recompute the PIDs passing the filters after a table or filter change
+    fn rebuild_wanted_pids(&mut self) {
+        if self.program_filter.is_none() && self.pid_filter.is_none() {
+            self.wanted_pids = None;
+            return;
+        }
+
+        let mut wanted = self
+            .wanted_pids
+            .take()
+            .unwrap_or_else(|| Box::new([false; PID_SPACE]));
+        wanted.fill(self.program_filter.is_none());
+        if self.program_filter.is_some() {
+            // The SDT names the services of every program
+            wanted[PID_SDT as usize] = true;
+            for pmt in self.pmts.values() {
+                wanted[pmt.pcr_pid() as usize % PID_SPACE] = true;
+                for pid in pmt.elementary_pids() {
+                    wanted[pid as usize % PID_SPACE] = true;
+                }
+            }
+        }
+        if let Some(pids) = &self.pid_filter {
+            for (pid, wanted) in wanted.iter_mut().enumerate() {
+                *wanted &= pids.contains(&(pid as u16));
+            }
+        }
+
+        // Tables are always followed to find the streams of the selected programs
+        wanted[PID_PAT as usize] = true;
+        for (pid, wanted) in wanted.iter_mut().enumerate() {
+            *wanted |= self.pmt_pid_flags[pid];
+        }
+        self.wanted_pids = Some(wanted);
+    }
+}
+
+impl PsiSection for Pat {
+    fn parse_section(data: Bytes) -> Result<Self> {
+        Pat::parse(&data)
+    }
+
+    fn parse_section_with_crc(data: Bytes) -> Result<Self> {
+        Pat::parse_with_crc(&data)
+    }
+
+    fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+}
+
+impl PatSection for Pat {
+    fn section_number(&self) -> u8 {
+        self.section_number
+    }
+
+    fn last_section_number(&self) -> u8 {
+        self.last_section_number
+    }
+
+    fn program_entries(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.programs
+            .iter()
+            .map(|program| (program.program_number, program.pmt_pid))
+    }
+}
+
+impl PsiSection for Pmt {
+    fn parse_section(data: Bytes) -> Result<Self> {
+        Pmt::parse(&data)
+    }
+
+    fn parse_section_with_crc(data: Bytes) -> Result<Self> {
+        Pmt::parse_with_crc(&data)
+    }
+
+    fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+}
+
+impl PmtSection for Pmt {
+    fn pcr_pid(&self) -> u16 {
+        self.pcr_pid
+    }
+
+    fn elementary_pids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.streams.iter().map(|stream| stream.elementary_pid)
+    }
+}
+
+impl PsiSection for PatRef {
+    fn parse_section(data: Bytes) -> Result<Self> {
+        PatRef::parse(data)
+    }
+
+    fn parse_section_with_crc(data: Bytes) -> Result<Self> {
+        PatRef::parse_with_crc(data)
+    }
+
+    fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+}
+
+impl PatSection for PatRef {
+    fn section_number(&self) -> u8 {
+        self.section_number
+    }
+
+    fn last_section_number(&self) -> u8 {
+        self.last_section_number
+    }
+
+    fn program_entries(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        self.programs()
+            .map(|program| (program.program_number, program.pmt_pid))
+    }
+}
+
+impl PsiSection for PmtRef {
+    fn parse_section(data: Bytes) -> Result<Self> {
+        PmtRef::parse(data)
+    }
+
+    fn parse_section_with_crc(data: Bytes) -> Result<Self> {
+        PmtRef::parse_with_crc(data)
+    }
+
+    fn version_number(&self) -> u8 {
+        self.version_number
+    }
+
+    fn current_next_indicator(&self) -> bool {
+        self.current_next_indicator
+    }
+}
+
+impl PmtSection for PmtRef {
+    fn pcr_pid(&self) -> u16 {
+        self.pcr_pid
+    }
+
+    fn elementary_pids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.streams().flatten().map(|stream| stream.elementary_pid)
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    Result, TsError,
+    descriptor::{Descriptor, Descriptors, ServiceDescriptor},
+};
+use bytes::Bytes;
+
+/// SDT table ID for the actual transport stream
+pub const TABLE_ID_SDT_ACTUAL: u8 = 0x42;
+/// SDT table ID for other transport streams
+pub const TABLE_ID_SDT_OTHER: u8 = 0x46;
+
+/// Service Description Table (SDT) - Table ID 0x42 / 0x46, PID 0x0011
+#[derive(Debug, Clone)]
+pub struct Sdt {
+    /// Table ID (0x42 = actual TS, 0x46 = other TS)
+    pub table_id: u8,
+    /// Transport Stream ID
+    pub transport_stream_id: u16,
+    /// Version number
+    pub version_number: u8,
+    /// Current/next indicator
+    pub current_next_indicator: bool,
+    /// Section number
+    pub section_number: u8,
+    /// Last section number
+    pub last_section_number: u8,
+    /// Original network ID
+    pub original_network_id: u16,
+    /// Services described in this section
+    pub services: Vec<SdtService>,
+}
+
+/// Service entry in SDT
+#[derive(Debug, Clone)]
+pub struct SdtService {
+    /// Service ID (matches the PAT/PMT program number)
+    pub service_id: u16,
+    /// EIT schedule information is present for this service
+    pub eit_schedule_flag: bool,
+    /// EIT present/following information is present for this service
+    pub eit_present_following_flag: bool,
+    /// Running status (1 = not running, 2 = starts soon, 3 = pausing, 4 = running, ...)
+    pub running_status: u8,
+    /// One or more component streams are scrambled
+    pub free_ca_mode: bool,
+    /// Parsed service descriptor (tag 0x48), if present
+    pub service: Option<ServiceDescriptor>,
+    /// Service descriptors
+    pub descriptors: Bytes,
+}
+
+impl Sdt {
+    /// Parse SDT from PSI section data
+    pub fn parse(data: Bytes) -> Result<Self> {
+        if data.len() < 11 {
+            return Err(TsError::InsufficientData {
+                expected: 11,
+                actual: data.len(),
+            });
+        }
+
+        let table_id = data[0];
+        if table_id != TABLE_ID_SDT_ACTUAL && table_id != TABLE_ID_SDT_OTHER {
+            return Err(TsError::InvalidTableId {
+                expected: TABLE_ID_SDT_ACTUAL,
+                actual: table_id,
+            });
+        }
+
+        let section_syntax_indicator = (data[1] & 0x80) != 0;
+        if !section_syntax_indicator {
+            return Err(TsError::ParseError(
+                "SDT must have section syntax indicator set".to_string(),
+            ));
+        }
+
+        let section_length = ((data[1] as u16 & 0x0F) << 8) | data[2] as u16;
+        if section_length < 12 {
+            return Err(TsError::InvalidSectionLength(section_length));
+        }
+
+        if data.len() < (3 + section_length as usize) {
+            return Err(TsError::InsufficientData {
+                expected: 3 + section_length as usize,
+                actual: data.len(),
+            });
+        }
+
+        let transport_stream_id = ((data[3] as u16) << 8) | data[4] as u16;
+        let version_number = (data[5] >> 1) & 0x1F;
+        let current_next_indicator = (data[5] & 0x01) != 0;
+        let section_number = data[6];
+        let last_section_number = data[7];
+        let original_network_id = ((data[8] as u16) << 8) | data[9] as u16;
+
+        let mut services = Vec::new();
+        let mut offset = 11;
+        let services_end = 3 + section_length as usize - 4; // Exclude CRC32
+
+        while offset + 5 <= services_end {
+            let service_id = ((data[offset] as u16) << 8) | data[offset + 1] as u16;
+            let eit_schedule_flag = (data[offset + 2] & 0x02) != 0;
+            let eit_present_following_flag = (data[offset + 2] & 0x01) != 0;
+            let running_status = (data[offset + 3] >> 5) & 0x07;
+            let free_ca_mode = (data[offset + 3] & 0x10) != 0;
+            let descriptors_loop_length =
+                (((data[offset + 3] as usize) & 0x0F) << 8) | data[offset + 4] as usize;
+            offset += 5;
+
+            if offset + descriptors_loop_length > services_end {
+                return Err(TsError::InsufficientData {
+                    expected: offset + descriptors_loop_length,
+                    actual: services_end,
+                });
+            }
+            let descriptors = data.slice(offset..offset + descriptors_loop_length);
+            offset += descriptors_loop_length;
+
+            let mut service = None;
+            for descriptor in Descriptors::new(descriptors.clone()) {
+                if let Descriptor::Service(parsed) = descriptor? {
+                    service = Some(parsed);
+                }
+            }
+
+            services.push(SdtService {
+                service_id,
+                eit_schedule_flag,
+                eit_present_following_flag,
+                running_status,
+                free_ca_mode,
+                service,
+                descriptors,
+            });
+        }
+
+        Ok(Sdt {
+            table_id,
+            transport_stream_id,
+            version_number,
+            current_next_indicator,
+            section_number,
+            last_section_number,
+            original_network_id,
+            services,
+        })
+    }
+
+    /// Parse SDT from PSI section data with CRC-32/MPEG-2 validation.
+    pub fn parse_with_crc(data: Bytes) -> Result<Self> {
+        crate::crc32::verify_section_crc32(&data)?;
+        Self::parse(data)
+    }
+
+    /// Whether this section describes the transport stream it was carried in
+    pub fn is_actual(&self) -> bool {
+        self.table_id == TABLE_ID_SDT_ACTUAL
+    }
+
+    /// Get service by service ID
+    pub fn get_service(&self, service_id: u16) -> Option<&SdtService> {
+        self.services.iter().find(|s| s.service_id == service_id)
+    }
+}
+
+impl SdtService {
+    /// Iterate over typed service descriptors.
+    pub fn descriptors(&self) -> Descriptors {
+        Descriptors::new(self.descriptors.clone())
+    }
+
+    /// Service name from the service descriptor
+    pub fn service_name(&self) -> Option<&str> {
+        self.service.as_ref().map(|s| s.service_name.as_str())
+    }
+
+    /// Provider name from the service descriptor
+    pub fn provider_name(&self) -> Option<&str> {
+        self.service.as_ref().map(|s| s.provider_name.as_str())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn build_sdt_section(version: u8, services: &[(u16, &str, &str)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (service_id, provider, name) in services {
+            let mut descriptor = vec![0x48, 0, 0x01, provider.len() as u8];
+            descriptor.extend_from_slice(provider.as_bytes());
+            descriptor.push(name.len() as u8);
+            descriptor.extend_from_slice(name.as_bytes());
+            descriptor[1] = (descriptor.len() - 2) as u8;
+
+            body.extend_from_slice(&service_id.to_be_bytes());
+            body.push(0xFC | 0x01); // EIT present/following only
+            // running_status = 4 (running), free_CA_mode = 0
+            body.push(0x80 | ((descriptor.len() >> 8) as u8 & 0x0F));
+            body.push(descriptor.len() as u8);
+            body.extend_from_slice(&descriptor);
+        }
+
+        let section_length = 8 + body.len() + 4;
+        let mut section = vec![
+            TABLE_ID_SDT_ACTUAL,
+            0xF0 | ((section_length >> 8) as u8 & 0x0F),
+            section_length as u8,
+            0x00,
+            0x01, // transport_stream_id
+            0xC0 | ((version & 0x1F) << 1) | 0x01,
+            0x00,
+            0x00,
+            0x20,
+            0x85, // original_network_id
+            0xFF, // reserved_future_use
+        ];
+        section.extend_from_slice(&body);
+        let crc = crate::crc32::mpeg2_crc32(&section);
+        section.extend_from_slice(&crc.to_be_bytes());
+        section
+    }
+
+    #[test]
+    fn test_sdt_basic_parsing() {
+        let section = build_sdt_section(2, &[(0x0101, "Provider", "Channel One")]);
+        let sdt = Sdt::parse_with_crc(Bytes::from(section)).unwrap();
+        assert!(sdt.is_actual());
+        assert_eq!(sdt.transport_stream_id, 1);
+        assert_eq!(sdt.version_number, 2);
+        assert_eq!(sdt.original_network_id, 0x2085);
+        assert_eq!(sdt.services.len(), 1);
+
+        let service = sdt.get_service(0x0101).unwrap();
+        assert!(!service.eit_schedule_flag);
+        assert!(service.eit_present_following_flag);
+        assert_eq!(service.running_status, 4);
+        assert!(!service.free_ca_mode);
+        assert_eq!(service.provider_name(), Some("Provider"));
+        assert_eq!(service.service_name(), Some("Channel One"));
+    }
+
+    #[test]
+    fn test_sdt_invalid_table_id() {
+        let mut section = build_sdt_section(0, &[]);
+        section[0] = 0x02;
+        assert!(matches!(
+            Sdt::parse(Bytes::from(section)),
+            Err(TsError::InvalidTableId { actual: 0x02, .. })
+        ));
+    }
+
+    #[test]
+    fn test_sdt_descriptor_loop_overrun() {
+        let mut section = build_sdt_section(0, &[(1, "P", "S")]);
+        // Inflate descriptors_loop_length past the section end
+        section[14] = 0x8F;
+        assert!(Sdt::parse(Bytes::from(section)).is_err());
+    }
+}
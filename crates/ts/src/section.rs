@@ -0,0 +1,169 @@
+//! Reassembly of PSI sections carried over several TS packets.
+
+use bytes::{Bytes, BytesMut};
+
+/// Largest `section_length` a PSI section may declare
+const MAX_SECTION_LENGTH: usize = 0x0FFF;
+/// Bytes buffered before giving up on completing a section
+const MAX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reassembles the PSI sections of one PID from the payloads of its packets.
+///
+/// A section may span several packets and a packet may carry the end of one
+/// section and the start of others. Every complete section is handed out as
+/// its own [`Bytes`], ready for [`PatRef::parse`](crate::PatRef::parse) or
+/// [`PmtRef::parse`](crate::PmtRef::parse), so tables larger than a single
+/// packet payload are parsed in full.
+#[derive(Debug, Default)]
+pub struct SectionAssembler {
+    buffer: BytesMut,
+}
+
+impl SectionAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the payload of a packet and return the sections it completed.
+    ///
+    /// With `payload_unit_start_indicator` set the payload starts with a
+    /// pointer field; the bytes before the pointed-to section finish the
+    /// section in progress. A section interrupted by the start of a new one
+    /// is dropped.
+    pub fn push(&mut self, payload: &[u8], payload_unit_start_indicator: bool) -> Vec<Bytes> {
+        let mut sections = Vec::new();
+        if payload.is_empty() {
+            return sections;
+        }
+
+        if payload_unit_start_indicator {
+            let pointer_end = 1 + payload[0] as usize;
+            if pointer_end > payload.len() {
+                return sections;
+            }
+
+            self.extend(&payload[1..pointer_end], &mut sections);
+            if pointer_end < payload.len() {
+                self.buffer.clear();
+                self.extend(&payload[pointer_end..], &mut sections);
+            }
+        } else {
+            self.extend(payload, &mut sections);
+        }
+
+        sections
+    }
+
+    /// Bytes of a section that is not complete yet.
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Drop the section in progress.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    fn extend(&mut self, data: &[u8], sections: &mut Vec<Bytes>) {
+        if data.is_empty() {
+            return;
+        }
+
+        self.buffer.extend_from_slice(data);
+        if self.buffer.len() > MAX_BUFFER_SIZE {
+            self.buffer.clear();
+            return;
+        }
+
+        loop {
+            let stuffing_prefix = self.buffer.iter().take_while(|&&b| b == 0xFF).count();
+            if stuffing_prefix > 0 {
+                let _ = self.buffer.split_to(stuffing_prefix);
+            }
+
+            if self.buffer.len() < 3 {
+                break;
+            }
+
+            let section_length =
+                (((self.buffer[1] as usize) & 0x0F) << 8) | self.buffer[2] as usize;
+            if section_length > MAX_SECTION_LENGTH {
+                let _ = self.buffer.split_to(1);
+                continue;
+            }
+
+            let section_size = 3 + section_length;
+            if self.buffer.len() < section_size {
+                break;
+            }
+
+            sections.push(self.buffer.split_to(section_size).freeze());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(table_id: u8, body_len: usize) -> Vec<u8> {
+        let mut section = vec![table_id, 0xB0 | (body_len >> 8) as u8, body_len as u8];
+        section.extend((0..body_len).map(|i| i as u8));
+        section
+    }
+
+    #[test]
+    fn test_reassembles_section_across_payloads() {
+        let long = section(0x02, 600);
+        let mut assembler = SectionAssembler::new();
+
+        let mut first = vec![0x00];
+        first.extend_from_slice(&long[..183]);
+        assert!(assembler.push(&first, true).is_empty());
+        assert!(assembler.push(&long[183..367], false).is_empty());
+        assert_eq!(assembler.buffered_len(), 367);
+
+        let sections = assembler.push(&long[367..], false);
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0][..], long[..]);
+        assert_eq!(assembler.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_pointer_field_finishes_previous_section() {
+        let first = section(0x00, 200);
+        let second = section(0x00, 20);
+        let mut assembler = SectionAssembler::new();
+
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&first[..150]);
+        assert!(assembler.push(&payload, true).is_empty());
+
+        let rest = &first[150..];
+        let mut payload = vec![rest.len() as u8];
+        payload.extend_from_slice(rest);
+        payload.extend_from_slice(&second);
+        payload.extend_from_slice(&[0xFF; 8]);
+        let sections = assembler.push(&payload, true);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0][..], first[..]);
+        assert_eq!(sections[1][..], second[..]);
+    }
+
+    #[test]
+    fn test_new_section_drops_interrupted_one() {
+        let first = section(0x02, 300);
+        let second = section(0x02, 20);
+        let mut assembler = SectionAssembler::new();
+
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&first[..100]);
+        assert!(assembler.push(&payload, true).is_empty());
+
+        let mut payload = vec![0x00];
+        payload.extend_from_slice(&second);
+        let sections = assembler.push(&payload, true);
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0][..], second[..]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Callbacks registered on the parsers to observe PAT/PMT version changes.
+
+use std::fmt;
+
+type PatUpdateFn<P> = dyn FnMut(Option<&P>, &P) + Send;
+type PmtUpdateFn<P> = dyn FnMut(u16, Option<&P>, &P) + Send;
+
+/// Table update callbacks of a parser, generic over its PAT and PMT types
+pub(crate) struct TableUpdateCallbacks<Pat, Pmt> {
+    on_pat_update: Option<Box<PatUpdateFn<Pat>>>,
+    on_pmt_update: Option<Box<PmtUpdateFn<Pmt>>>,
+}
+
+impl<Pat, Pmt> Default for TableUpdateCallbacks<Pat, Pmt> {
+    fn default() -> Self {
+        Self {
+            on_pat_update: None,
+            on_pmt_update: None,
+        }
+    }
+}
+
+impl<Pat, Pmt> fmt::Debug for TableUpdateCallbacks<Pat, Pmt> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableUpdateCallbacks")
+            .field("on_pat_update", &self.on_pat_update.is_some())
+            .field("on_pmt_update", &self.on_pmt_update.is_some())
+            .finish()
+    }
+}
+
+impl<Pat, Pmt> TableUpdateCallbacks<Pat, Pmt> {
+    pub(crate) fn set_pat<F>(&mut self, callback: F)
+    where
+        F: FnMut(Option<&Pat>, &Pat) + Send + 'static,
+    {
+        self.on_pat_update = Some(Box::new(callback));
+    }
+
+    pub(crate) fn set_pmt<F>(&mut self, callback: F)
+    where
+        F: FnMut(u16, Option<&Pmt>, &Pmt) + Send + 'static,
+    {
+        self.on_pmt_update = Some(Box::new(callback));
+    }
+
+    /// Report a newly committed PAT version
+    pub(crate) fn pat_updated(&mut self, previous: Option<&Pat>, current: &Pat) {
+        if let Some(callback) = &mut self.on_pat_update {
+            callback(previous, current);
+        }
+    }
+
+    /// Report a newly committed PMT version of `program_number`
+    pub(crate) fn pmt_updated(
+        &mut self,
+        program_number: u16,
+        previous: Option<&Pmt>,
+        current: &Pmt,
+    ) {
+        if let Some(callback) = &mut self.on_pmt_update {
+            callback(program_number, previous, current);
+        }
+    }
+}
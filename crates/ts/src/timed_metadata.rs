@@ -0,0 +1,358 @@
+use std::collections::{HashMap, HashSet};
+
+use bytes::{Bytes, BytesMut};
+use tracing::warn;
+
+use crate::{
+    Descriptor, PesHeader, PmtRef, Result, StreamType, TsError, TsPacketRef, TsParser, id3::Id3Tag,
+};
+
+/// An ID3 tag carried on a timed metadata PID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedMetadata {
+    pub pid: u16,
+    /// PTS of the PES packet carrying the tag, in 90 kHz units
+    pub pts: Option<u64>,
+    pub tag: Id3Tag,
+}
+
+impl TimedMetadata {
+    /// PTS in milliseconds relative to `base_pts`, wrapping around the 33-bit PTS range.
+    pub fn relative_ms(&self, base_pts: u64) -> Option<u64> {
+        const PTS_MASK: u64 = (1 << 33) - 1;
+        self.pts
+            .map(|pts| (pts.wrapping_sub(base_pts) & PTS_MASK) / 90)
+    }
+}
+
+/// Extracts ID3 timed metadata (stream type 0x15) from transport stream data.
+///
+/// Metadata PIDs are learned from the PMT. PES packets on those PIDs are
+/// reassembled across TS packets and parsed as ID3v2 tags. Malformed tags are
+/// logged and skipped so a bad metadata packet never fails the stream.
+///
+/// State is kept between calls to [`extract`](Self::extract), so a PES packet
+/// split across HLS segments is still reassembled.
+#[derive(Debug, Default)]
+pub struct TimedMetadataExtractor {
+    /// Partially assembled PES packet per metadata PID
+    pending: HashMap<u16, BytesMut>,
+    /// Elementary PIDs of all streams in the PMT
+    stream_pids: HashSet<u16>,
+    base_pts: Option<u64>,
+    skipped: u64,
+}
+
+impl TimedMetadataExtractor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of metadata packets skipped because they could not be parsed
+    pub fn skipped_count(&self) -> u64 {
+        self.skipped
+    }
+
+    /// First PTS seen on any elementary stream since creation or [`reset`](Self::reset),
+    /// for mapping metadata onto a timeline starting at zero
+    pub fn base_pts(&self) -> Option<u64> {
+        self.base_pts
+    }
+
+    /// Check whether a PMT stream carries ID3 timed metadata.
+    pub fn is_metadata_stream(stream_type: StreamType, descriptors: &[Descriptor]) -> bool {
+        matches!(
+            stream_type.classify_with_descriptors(descriptors),
+            StreamType::MetadataPes | StreamType::Id3
+        )
+    }
+
+    /// Parse `data` and return the timed metadata completed in it.
+    ///
+    /// Returns an error only when the transport stream itself cannot be parsed.
+    pub fn extract(&mut self, data: Bytes) -> Result<Vec<TimedMetadata>> {
+        let mut parser = TsParser::new();
+        let mut found = Vec::new();
+        self.extract_with(&mut parser, data, |metadata| found.push(metadata))?;
+        Ok(found)
+    }
+
+    /// Parse `data` with `parser`, calling `on_metadata` for each tag as it completes.
+    pub fn extract_with<F>(
+        &mut self,
+        parser: &mut TsParser,
+        data: Bytes,
+        mut on_metadata: F,
+    ) -> Result<()>
+    where
+        F: FnMut(TimedMetadata),
+    {
+        let pending = std::cell::RefCell::new(std::mem::take(&mut self.pending));
+        let stream_pids = std::cell::RefCell::new(std::mem::take(&mut self.stream_pids));
+        let base_pts = &mut self.base_pts;
+        let mut completed = Vec::new();
+
+        parser.parse_packets(
+            data,
+            |_| Ok(()),
+            |pmt: PmtRef| {
+                let mut pending = pending.borrow_mut();
+                let mut stream_pids = stream_pids.borrow_mut();
+                for stream in pmt.streams().flatten() {
+                    stream_pids.insert(stream.elementary_pid);
+                    let descriptors: Vec<Descriptor> = stream.descriptors().flatten().collect();
+                    if Self::is_metadata_stream(stream.stream_type, &descriptors) {
+                        pending.entry(stream.elementary_pid).or_default();
+                    }
+                }
+                Ok(())
+            },
+            Some(|packet: &TsPacketRef| {
+                if base_pts.is_none()
+                    && packet.payload_unit_start_indicator
+                    && stream_pids.borrow().contains(&packet.pid)
+                    && let Some(payload) = packet.payload()
+                    && let Ok(header) = PesHeader::parse(&payload)
+                {
+                    *base_pts = header.pts;
+                }
+
+                let mut pending = pending.borrow_mut();
+                if let Some(buffer) = pending.get_mut(&packet.pid)
+                    && let Some(payload) = packet.payload()
+                {
+                    if packet.payload_unit_start_indicator {
+                        if !buffer.is_empty() {
+                            completed.push((packet.pid, buffer.split().freeze()));
+                        }
+                        buffer.extend_from_slice(&payload);
+                    } else if !buffer.is_empty() {
+                        buffer.extend_from_slice(&payload);
+                    }
+                }
+                Ok(())
+            }),
+        )?;
+
+        self.pending = pending.into_inner();
+        self.stream_pids = stream_pids.into_inner();
+
+        // A PES packet with a known length completes without waiting for the next one
+        for (&pid, buffer) in self.pending.iter_mut() {
+            if let Ok(header) = PesHeader::parse(&buffer[..])
+                && header.pes_packet_length > 0
+                && buffer.len() >= header.pes_packet_length as usize + 6
+            {
+                completed.push((pid, buffer.split().freeze()));
+            }
+        }
+
+        for (pid, pes) in completed {
+            match Self::parse_pes(pid, &pes) {
+                Ok(tags) => tags.into_iter().for_each(&mut on_metadata),
+                Err(e) => {
+                    self.skipped += 1;
+                    warn!(pid, error = %e, "Skipping malformed ID3 timed metadata");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drop partially assembled packets and the base PTS, e.g. after a discontinuity.
+    pub fn reset(&mut self) {
+        self.pending.values_mut().for_each(BytesMut::clear);
+        self.base_pts = None;
+    }
+
+    fn parse_pes(pid: u16, pes: &Bytes) -> Result<Vec<TimedMetadata>> {
+        let header = PesHeader::parse(pes)?;
+        let mut end = pes.len();
+        if header.pes_packet_length > 0 {
+            end = end.min(header.pes_packet_length as usize + 6);
+        }
+        if header.payload_offset > end {
+            return Err(TsError::InsufficientData {
+                expected: header.payload_offset,
+                actual: end,
+            });
+        }
+
+        // A PES packet may carry several consecutive tags
+        let mut payload = pes.slice(header.payload_offset..end);
+        let mut tags = Vec::new();
+        while let Some(size) = Id3Tag::tag_size(&payload) {
+            let tag = Id3Tag::parse(&payload)?;
+            tags.push(TimedMetadata {
+                pid,
+                pts: header.pts,
+                tag,
+            });
+            if size >= payload.len() {
+                break;
+            }
+            payload = payload.slice(size..);
+        }
+
+        if tags.is_empty() {
+            return Err(TsError::InvalidId3(
+                "PES payload does not start with an ID3 tag".into(),
+            ));
+        }
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id3::{
+        Id3Frame,
+        tests::{frame, tag},
+    };
+    use crate::{Pat, PatProgram, Pmt, PmtStream, TsWriter};
+
+    const METADATA_PID: u16 = 0x102;
+
+    /// Wrap `payload` in a PES packet on private_stream_1 with the given PTS.
+    fn pes(pts: u64, payload: &[u8]) -> Vec<u8> {
+        let length = 3 + 5 + payload.len();
+        let mut out = vec![0x00, 0x00, 0x01, 0xBD];
+        out.extend_from_slice(&(length as u16).to_be_bytes());
+        out.extend_from_slice(&[0x84, 0x80, 0x05]);
+        out.push(0x21 | (((pts >> 30) & 0x07) as u8) << 1);
+        out.push((pts >> 22) as u8);
+        out.push(0x01 | (((pts >> 15) & 0x7F) as u8) << 1);
+        out.push((pts >> 7) as u8);
+        out.push(0x01 | ((pts & 0x7F) as u8) << 1);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    /// Transport stream with a PAT, a PMT announcing an ID3 metadata PID, and
+    /// one PES packet per entry of `tags`.
+    fn fixture(tags: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        let pat = Pat {
+            table_id: 0,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        };
+        let pmt = Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: Vec::new(),
+            streams: vec![
+                PmtStream {
+                    stream_type: StreamType::H264,
+                    elementary_pid: 0x100,
+                    es_info: Vec::new(),
+                },
+                PmtStream {
+                    stream_type: StreamType::MetadataPes,
+                    elementary_pid: METADATA_PID,
+                    // metadata_descriptor with format identifier "ID3 "
+                    es_info: vec![
+                        0x26, 0x0D, 0xFF, 0xFF, b'I', b'D', b'3', b' ', 0xFF, b'I', b'D', b'3',
+                        b' ', 0x00, 0x0F,
+                    ],
+                },
+            ],
+        };
+        writer.write_pat(&pat, &mut out).unwrap();
+        writer.write_pmt(0x1000, &pmt, &mut out).unwrap();
+        for (pts, payload) in tags {
+            writer
+                .write_pes(METADATA_PID, &pes(*pts, payload), &mut out)
+                .unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_extracts_tags_and_skips_malformed() {
+        let good = tag(
+            &[
+                frame(b"TXXX", b"\x03event\0ad-start"),
+                frame(b"TIT2", b"\x03Show"),
+                frame(b"PRIV", b"com.example\0\x01\x02"),
+            ],
+            0,
+        );
+        // Frame size runs past the end of the tag
+        let mut malformed = tag(&[frame(b"TIT2", b"\x03abc")], 0);
+        malformed[17] = 0x7F;
+        // Large enough to span several TS packets
+        let large = tag(
+            &[frame(
+                b"TXXX",
+                &[b"\x03big\0".as_slice(), &[b'x'; 400]].concat(),
+            )],
+            0,
+        );
+
+        let data = fixture(&[(90_000, good), (180_000, malformed), (270_000, large)]);
+
+        let mut extractor = TimedMetadataExtractor::new();
+        let found = extractor.extract(Bytes::from(data)).unwrap();
+
+        assert_eq!(found.len(), 2);
+        assert_eq!(extractor.skipped_count(), 1);
+        assert_eq!(extractor.base_pts(), Some(90_000));
+
+        assert_eq!(found[0].pid, METADATA_PID);
+        assert_eq!(found[0].pts, Some(90_000));
+        assert_eq!(found[0].relative_ms(45_000), Some(500));
+        assert_eq!(
+            found[0].tag.frames,
+            vec![
+                Id3Frame::UserText {
+                    description: "event".into(),
+                    value: "ad-start".into(),
+                },
+                Id3Frame::Text {
+                    id: "TIT2".into(),
+                    value: "Show".into(),
+                },
+                Id3Frame::Private {
+                    owner: "com.example".into(),
+                    data: Bytes::from_static(&[1, 2]),
+                },
+            ]
+        );
+
+        assert_eq!(found[1].pts, Some(270_000));
+        let Id3Frame::UserText { value, .. } = &found[1].tag.frames[0] else {
+            panic!("expected TXXX frame");
+        };
+        assert_eq!(value.len(), 400);
+    }
+
+    #[test]
+    fn test_relative_ms_wraps_pts() {
+        let metadata = TimedMetadata {
+            pid: METADATA_PID,
+            pts: Some(900),
+            tag: Id3Tag {
+                major_version: 4,
+                revision: 0,
+                frames: Vec::new(),
+            },
+        };
+        assert_eq!(metadata.relative_ms((1 << 33) - 900), Some(20));
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{Result, TsError, packet::PID_PAT, pat::Pat, pmt::Pmt};
+use std::collections::HashMap;
+
+/// TS packet size in bytes
+pub const TS_PACKET_SIZE: usize = 188;
+
+/// Maximum `section_length` for PAT/PMT sections (ISO/IEC 13818-1 2.4.4.3)
+pub const MAX_SECTION_LENGTH: usize = 1021;
+
+const TS_HEADER_SIZE: usize = 4;
+const TS_PAYLOAD_SIZE: usize = TS_PACKET_SIZE - TS_HEADER_SIZE;
+
+/// Transport Stream packetizer for PSI tables and PES payloads.
+///
+/// Tracks one continuity counter per PID so consecutive calls for the same PID
+/// produce a continuous stream. Output packets are appended to a caller-provided
+/// buffer, which is always a multiple of 188 bytes after each call.
+#[derive(Debug, Default)]
+pub struct TsWriter {
+    continuity_counters: HashMap<u16, u8>,
+}
+
+impl TsWriter {
+    /// Create a new writer with all continuity counters at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Packetize a PAT on PID 0x0000. Returns the number of packets written.
+    pub fn write_pat(&mut self, pat: &Pat, out: &mut Vec<u8>) -> Result<usize> {
+        let section = pat.encode_section()?;
+        self.write_section(PID_PAT, &section, out)
+    }
+
+    /// Packetize a PMT on the given PMT PID. Returns the number of packets written.
+    pub fn write_pmt(&mut self, pmt_pid: u16, pmt: &Pmt, out: &mut Vec<u8>) -> Result<usize> {
+        let section = pmt.encode_section()?;
+        self.write_section(pmt_pid, &section, out)
+    }
+
+    /// Packetize a complete PSI section.
+    ///
+    /// The first packet carries a zero pointer_field; unused bytes in the last
+    /// packet are filled with 0xFF, which PSI decoders treat as stuffing.
+    pub fn write_section(&mut self, pid: u16, section: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+        Self::validate_pid(pid)?;
+        if section.is_empty() {
+            return Ok(0);
+        }
+
+        let mut remaining = section;
+        let mut first = true;
+        let mut packets = 0;
+
+        while first || !remaining.is_empty() {
+            let cc = self.next_cc(pid);
+            Self::push_header(out, pid, first, 0x01, cc);
+
+            let mut capacity = TS_PAYLOAD_SIZE;
+            if first {
+                out.push(0x00);
+                capacity -= 1;
+            }
+
+            let take = remaining.len().min(capacity);
+            out.extend_from_slice(&remaining[..take]);
+            out.resize(out.len() + (capacity - take), 0xFF);
+            remaining = &remaining[take..];
+            first = false;
+            packets += 1;
+        }
+
+        Ok(packets)
+    }
+
+    /// Packetize a complete PES packet for `pid`.
+    ///
+    /// The first TS packet sets payload_unit_start_indicator. When the final
+    /// chunk does not fill a packet, an adaptation field is inserted and padded
+    /// with stuffing bytes so the payload ends exactly at the packet boundary.
+    pub fn write_pes(&mut self, pid: u16, pes: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+        Self::validate_pid(pid)?;
+        if pes.is_empty() {
+            return Ok(0);
+        }
+
+        let mut remaining = pes;
+        let mut first = true;
+        let mut packets = 0;
+
+        while !remaining.is_empty() {
+            let cc = self.next_cc(pid);
+            let take = remaining.len().min(TS_PAYLOAD_SIZE);
+
+            if take == TS_PAYLOAD_SIZE {
+                Self::push_header(out, pid, first, 0x01, cc);
+            } else {
+                Self::push_header(out, pid, first, 0x03, cc);
+                // adaptation_field_length excludes its own length byte
+                let af_length = TS_PAYLOAD_SIZE - take - 1;
+                out.push(af_length as u8);
+                if af_length > 0 {
+                    out.push(0x00);
+                    out.resize(out.len() + af_length - 1, 0xFF);
+                }
+            }
+
+            out.extend_from_slice(&remaining[..take]);
+            remaining = &remaining[take..];
+            first = false;
+            packets += 1;
+        }
+
+        Ok(packets)
+    }
+
+    /// Continuity counter that the next payload-bearing packet on `pid` will use
+    pub fn continuity_counter(&self, pid: u16) -> u8 {
+        self.continuity_counters.get(&pid).copied().unwrap_or(0)
+    }
+
+    /// Reset all continuity counters
+    pub fn reset(&mut self) {
+        self.continuity_counters.clear();
+    }
+
+    fn next_cc(&mut self, pid: u16) -> u8 {
+        let counter = self.continuity_counters.entry(pid).or_insert(0);
+        let cc = *counter;
+        *counter = (cc + 1) & 0x0F;
+        cc
+    }
+
+    fn validate_pid(pid: u16) -> Result<()> {
+        if pid > 0x1FFF {
+            return Err(TsError::InvalidPid(pid));
+        }
+        Ok(())
+    }
+
+    fn push_header(
+        out: &mut Vec<u8>,
+        pid: u16,
+        payload_unit_start_indicator: bool,
+        adaptation_field_control: u8,
+        cc: u8,
+    ) {
+        out.push(0x47);
+        out.push(((payload_unit_start_indicator as u8) << 6) | ((pid >> 8) as u8 & 0x1F));
+        out.push(pid as u8);
+        out.push((adaptation_field_control << 4) | (cc & 0x0F));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        PatRef, PmtRef, StreamType, TsPacket, TsPacketRef, TsParser, pat::PatProgram,
+        pmt::PmtStream,
+    };
+    use bytes::Bytes;
+
+    fn sample_pat() -> Pat {
+        Pat {
+            table_id: 0x00,
+            transport_stream_id: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            programs: vec![PatProgram {
+                program_number: 1,
+                pmt_pid: 0x1000,
+            }],
+        }
+    }
+
+    fn sample_pmt(stream_count: usize) -> Pmt {
+        Pmt {
+            table_id: 0x02,
+            program_number: 1,
+            version_number: 0,
+            current_next_indicator: true,
+            section_number: 0,
+            last_section_number: 0,
+            pcr_pid: 0x100,
+            program_info: Vec::new(),
+            streams: (0..stream_count)
+                .map(|i| PmtStream {
+                    stream_type: if i == 0 {
+                        StreamType::H264
+                    } else {
+                        StreamType::AdtsAac
+                    },
+                    elementary_pid: 0x100 + i as u16,
+                    es_info: vec![0x0A, 0x04, b'e', b'n', b'g', 0x00],
+                })
+                .collect(),
+        }
+    }
+
+    fn parse_tables(data: Vec<u8>) -> (Vec<u16>, Vec<Vec<u16>>) {
+        let mut parser = TsParser::new().with_crc_validation(true);
+        let mut pat_pids = Vec::new();
+        let mut pmt_streams = Vec::new();
+        parser
+            .parse_packets(
+                Bytes::from(data),
+                |pat: PatRef| {
+                    pat_pids.extend(pat.programs().map(|p| p.pmt_pid));
+                    Ok(())
+                },
+                |pmt: PmtRef| {
+                    pmt_streams.push(pmt.streams().flatten().map(|s| s.elementary_pid).collect());
+                    Ok(())
+                },
+                None::<fn(&TsPacketRef) -> Result<()>>,
+            )
+            .unwrap();
+        (pat_pids, pmt_streams)
+    }
+
+    #[test]
+    fn test_pat_pmt_round_trip() {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        assert_eq!(writer.write_pat(&sample_pat(), &mut out).unwrap(), 1);
+        assert_eq!(
+            writer.write_pmt(0x1000, &sample_pmt(2), &mut out).unwrap(),
+            1
+        );
+        assert_eq!(out.len(), 2 * TS_PACKET_SIZE);
+        assert!(out.chunks(TS_PACKET_SIZE).all(|p| p[0] == 0x47));
+
+        let (pat_pids, pmt_streams) = parse_tables(out);
+        assert_eq!(pat_pids, vec![0x1000]);
+        assert_eq!(pmt_streams, vec![vec![0x100, 0x101]]);
+    }
+
+    #[test]
+    fn test_multi_packet_section_round_trip() {
+        let pmt = sample_pmt(30);
+        let section_len = pmt.encode_section().unwrap().len();
+        assert!(section_len > TS_PAYLOAD_SIZE);
+
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        writer.write_pat(&sample_pat(), &mut out).unwrap();
+        let packets = writer.write_pmt(0x1000, &pmt, &mut out).unwrap();
+        assert_eq!(packets, (section_len + 1).div_ceil(TS_PAYLOAD_SIZE));
+
+        let second = TsPacket::parse(Bytes::copy_from_slice(
+            &out[2 * TS_PACKET_SIZE..3 * TS_PACKET_SIZE],
+        ))
+        .unwrap();
+        assert!(!second.payload_unit_start_indicator);
+        assert_eq!(second.continuity_counter, 1);
+
+        let (_, pmt_streams) = parse_tables(out);
+        assert_eq!(pmt_streams.len(), 1);
+        assert_eq!(pmt_streams[0].len(), 30);
+    }
+
+    #[test]
+    fn test_pes_packetization_with_stuffing() {
+        let pes: Vec<u8> = (0..400u32).map(|i| i as u8).collect();
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        assert_eq!(writer.write_pes(0x100, &pes, &mut out).unwrap(), 3);
+        assert_eq!(out.len(), 3 * TS_PACKET_SIZE);
+
+        let packets: Vec<_> = out
+            .chunks(TS_PACKET_SIZE)
+            .map(|p| TsPacket::parse(Bytes::copy_from_slice(p)).unwrap())
+            .collect();
+        assert!(packets[0].payload_unit_start_indicator);
+        assert!(!packets[1].payload_unit_start_indicator);
+        assert_eq!(
+            packets
+                .iter()
+                .map(|p| p.continuity_counter)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert!(!packets[1].has_adaptation_field());
+        assert!(packets[2].has_adaptation_field());
+
+        let reassembled: Vec<u8> = packets
+            .iter()
+            .flat_map(|p| p.payload.clone().unwrap().to_vec())
+            .collect();
+        assert_eq!(reassembled, pes);
+        assert_eq!(writer.continuity_counter(0x100), 3);
+    }
+
+    #[test]
+    fn test_pes_single_byte_short_stuffing() {
+        // 183 bytes leaves room only for a zero-length adaptation field
+        let pes = vec![0xAB; 183];
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        writer.write_pes(0x100, &pes, &mut out).unwrap();
+        assert_eq!(out.len(), TS_PACKET_SIZE);
+        assert_eq!(out[4], 0);
+
+        let packet = TsPacket::parse(Bytes::from(out)).unwrap();
+        assert_eq!(packet.payload.unwrap().len(), 183);
+    }
+
+    #[test]
+    fn test_continuity_counter_wraps() {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        for _ in 0..17 {
+            writer.write_pes(0x100, &[0u8; 10], &mut out).unwrap();
+        }
+        let last = &out[16 * TS_PACKET_SIZE..];
+        assert_eq!(last[3] & 0x0F, 0);
+    }
+
+    #[test]
+    fn test_invalid_pid() {
+        let mut writer = TsWriter::new();
+        let mut out = Vec::new();
+        assert!(matches!(
+            writer.write_pes(0x2000, &[0u8; 4], &mut out),
+            Err(TsError::InvalidPid(0x2000))
+        ));
+    }
+}
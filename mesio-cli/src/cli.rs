@@ -2,7 +2,7 @@ use clap::Parser;
 use mesio_engine::ProxyType;
 use std::path::PathBuf;
 
-use crate::output::provider::OutputFormat;
+use mesio_engine::job::OutputFormat;
 
 /// Define CLI arguments
 #[derive(Parser)]
@@ -103,6 +103,43 @@ pub struct CliArgs {
     )]
     pub channel_size: usize,
 
+    /// Maximum bytes buffered between processing stages
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Maximum bytes buffered between processing stages with optional unit (B, KB, MB, GB). Examples: \"16MB\". Use 0 for unlimited."
+    )]
+    pub max_buffer_size: String,
+
+    /// Number of inputs processed at once
+    #[arg(
+        short = 'j',
+        long,
+        default_value = "1",
+        help = "Number of inputs processed at once"
+    )]
+    pub parallel: usize,
+
+    /// Abort all inputs when one fails
+    #[arg(long, help = "Abort the remaining inputs when one of them fails")]
+    pub fail_fast: bool,
+
+    /// Bandwidth shared by all downloads
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Maximum combined download speed of all inputs in bytes per second, with optional unit (B, KB, MB, GB). Examples: \"10MB\". Use 0 for unlimited."
+    )]
+    pub max_bandwidth: String,
+
+    /// Open files and connections shared by all inputs
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Maximum number of inputs holding files or connections open at once. Use 0 for unlimited."
+    )]
+    pub max_open: usize,
+
     /// Download buffer size
     #[arg(
         long,
@@ -174,11 +211,18 @@ pub struct CliArgs {
     #[arg(
         short = 'n',
         long = "name",
-        help = "Output file name template with placeholders (e.g., %u%Y%m%d_%H%M%S_p%i'). Supported placeholders: %u (unique identifier), %Y (year), %m (month), %d (day), %H (hour), %M (minute), %S (second), %i (output index)",
+        help = "Output file name template with placeholders (e.g., %u%Y%m%d_%H%M%S_p%i'). Supported placeholders: %u (unique identifier), %Y (year), %m (month), %d (day), %H (hour), %M (minute), %S (second), %i (output index). Stream variables: %res% (resolution), %vcodec% (video codec), %acodec% (audio codec), %seq% (output index), %title% (stream title)",
         default_value = "%u%Y%m%d_%H%M%S_p%i"
     )]
     pub output_name_template: String,
 
+    /// Number output files instead of overwriting existing ones
+    #[arg(
+        long,
+        help = "Name output files that would overwrite an existing file name_1, name_2, ..."
+    )]
+    pub numbered_names: bool,
+
     /// Custom HTTP headers for download requests
     #[arg(
         long = "header",
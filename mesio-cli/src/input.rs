@@ -31,6 +31,29 @@ pub async fn input_handler(token: CancellationToken) {
     }
 }
 
+/// Cancels the token on Ctrl+C (SIGINT), so that downloads shut down gracefully.
+///
+/// A second Ctrl+C exits immediately, without waiting for the outputs to be finalized.
+pub async fn signal_handler(token: CancellationToken) {
+    tokio::select! {
+        _ = token.cancelled() => return,
+        result = tokio::signal::ctrl_c() => {
+            if result.is_err() {
+                info!("Failed to listen for Ctrl+C.");
+                return;
+            }
+        }
+    }
+
+    eprintln!("Cancellation requested. Shutting down gracefully, press Ctrl+C again to exit...");
+    token.cancel();
+
+    if tokio::signal::ctrl_c().await.is_ok() {
+        // 128 + SIGINT
+        std::process::exit(130);
+    }
+}
+
 /// Handle input from terminal keyboard events
 async fn handle_terminal_input(token: CancellationToken) {
     if terminal::enable_raw_mode().is_err() {
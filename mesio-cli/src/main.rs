@@ -1,32 +1,26 @@
+use std::collections::BTreeMap;
 use std::io;
-use std::{path::PathBuf, time::Duration};
 
 use clap::Parser;
-use config::ProgramConfig;
 use error::AppError;
-use flv_fix::FlvPipelineConfig;
-use flv_fix::RepairStrategy;
-use flv_fix::ScriptFillerConfig;
-use hls_fix::HlsPipelineConfig;
-use mesio_engine::flv::FlvProtocolConfig;
-use mesio_engine::{DownloaderConfig, HlsProtocolBuilder, ProxyAuth, ProxyConfig, ProxyType};
-use output::provider::OutputFormat;
-use pipeline_common::{CancellationToken, config::PipelineConfig};
+use mesio_engine::HttpVersionPreference;
+use mesio_engine::job::{
+    FlvPipelineSpec, FlvSpec, HlsPipelineSpec, HlsSpec, HttpSpec, JobError, JobSpec, LimitsSpec,
+    OutputFormat, OutputSpec, PipelineSpec, ProxySpec, run_job_with_token,
+};
+use pipeline_common::CancellationToken;
 use tracing::{Level, error, info};
 use tracing_indicatif::IndicatifLayer;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
 mod cli;
-mod config;
 mod error;
 mod input;
-mod output;
-mod processor;
 mod utils;
 
 use cli::CliArgs;
-use input::input_handler;
+use input::{input_handler, signal_handler};
 use utils::{parse_headers, parse_params, parse_size, parse_time};
 
 #[global_allocator]
@@ -36,7 +30,7 @@ fn main() {
     if let Err(e) = bootstrap() {
         // Check if it's a broken pipe error - this is expected behavior
         // when the consumer closes the pipe (e.g., `mesio ... | head -c 1000`)
-        if matches!(e, AppError::BrokenPipe) {
+        if matches!(e, AppError::Job(JobError::BrokenPipe)) {
             // Exit code 141 is the standard for SIGPIPE (128 + 13)
             // This indicates the pipe was closed by the consumer, which is normal
             eprintln!("Pipe closed by consumer");
@@ -58,8 +52,9 @@ async fn bootstrap() -> Result<(), AppError> {
     // Create a cancellation token
     let token = CancellationToken::new();
 
-    // Spawn the input handler
+    // Spawn the input handler, and stop gracefully on Ctrl+C outside of the terminal's raw mode
     tokio::spawn(input_handler(token.clone()));
+    tokio::spawn(signal_handler(token.clone()));
 
     // Setup logging with tracing-indicatif
     let log_level = if args.verbose {
@@ -148,183 +143,19 @@ async fn bootstrap() -> Result<(), AppError> {
     info!("GitHub: https://github.com/hua0512/rust-srec");
     info!("==================================================================");
 
-    // Max size in bytes
-    let file_size_limit = parse_size(&args.max_size)?;
-
-    // Max duration in seconds
-    let duration_limit_s = parse_time(&args.max_duration)?;
-
-    let pipeline_config = PipelineConfig::builder()
-        .max_file_size(file_size_limit)
-        .max_duration_s(duration_limit_s)
-        .channel_size(args.channel_size)
-        .build();
-
-    info!("{pipeline_config}");
-
     // Log HTTP timeout settings
     info!(
         "HTTP timeout configuration: overall={}s, connect={}s, read={}s, write={}s",
         args.timeout, args.connect_timeout, args.read_timeout, args.write_timeout
     );
 
-    // Check if we're in pipe mode (stdout/stderr output)
-    let is_pipe_mode = matches!(
-        args.output_format,
-        OutputFormat::Stdout | OutputFormat::Stderr
-    );
-
-    // Configure flv pipeline config
-    let flv_pipeline_config = FlvPipelineConfig::builder()
-        .duplicate_tag_filtering(false)
-        .repair_strategy(RepairStrategy::Strict)
-        .continuity_mode(flv_fix::ContinuityMode::Reset)
-        .keyframe_index_config(if args.keyframe_index {
-            if duration_limit_s > 0.0 {
-                info!("Keyframe index will be injected into metadata for better seeking");
-                Some(ScriptFillerConfig {
-                    keyframe_duration_ms: (duration_limit_s * 1000.0) as u32,
-                })
-            } else {
-                info!("Keyframe index enabled with default configuration");
-                Some(ScriptFillerConfig::default())
-            }
-        } else {
-            None
-        })
-        .enable_low_latency(args.low_latency_fix)
-        .pipe_mode(is_pipe_mode)
-        .build();
-
-    // Configure HLS pipeline config
-    let hls_pipeline_config = HlsPipelineConfig::builder().build();
-
-    // Determine output directory
-    let output_dir = args.output_dir.unwrap_or_else(|| PathBuf::from("./fix"));
-
-    // Handle proxy configuration
-    let (proxy_config, use_system_proxy) = if args.no_proxy {
-        // No proxy flag overrides everything else
-        info!("All proxy settings disabled (--no-proxy flag)");
-        (None, false)
-    } else if let Some(proxy_url) = args.proxy.as_ref() {
-        // Explicit proxy configuration
-        // Parse proxy type
-        let proxy_type: ProxyType = args.proxy_type;
-
-        // Configure proxy authentication if both username and password are provided
-        let auth = if let (Some(username), Some(password)) = (&args.proxy_user, &args.proxy_pass) {
-            Some(ProxyAuth {
-                username: username.clone(),
-                password: password.clone(),
-            })
-        } else {
-            None
-        };
-
-        info!(
-            proxy_url = %proxy_url,
-            proxy_type = ?proxy_type,
-            has_auth = auth.is_some(),
-            "Using explicit proxy configuration for downloads"
-        );
-
-        // Create the proxy configuration
-        let proxy = ProxyConfig {
-            url: proxy_url.clone(),
-            proxy_type,
-            auth,
-        };
-
-        (Some(proxy), false) // Don't use system proxy when explicit proxy is configured
-    } else if args.use_system_proxy {
-        // Use system proxy settings
-        info!("Using system proxy settings for downloads");
-        (None, true)
-    } else {
-        // No proxy settings at all
-        info!("No proxy settings configured for downloads");
-        (None, false)
-    };
-
-    // Create common download configuration
-    let download_config = {
-        use mesio_engine::config::HttpVersionPreference;
-
-        let mut builder = DownloaderConfig::builder()
-            .with_timeout(Duration::from_secs(args.timeout))
-            .with_connect_timeout(Duration::from_secs(args.connect_timeout))
-            .with_read_timeout(Duration::from_secs(args.read_timeout))
-            .with_write_timeout(Duration::from_secs(args.write_timeout))
-            .with_headers(parse_headers(&args.headers))
-            .with_params(parse_params(&args.params)?)
-            .with_caching_enabled(false)
-            .with_force_ipv4(args.force_ipv4)
-            .with_force_ipv6(args.force_ipv6);
-
-        // Configure HTTP version preference
-        let http_version = match args.http_version.as_str() {
-            "http1" => HttpVersionPreference::Http1Only,
-            "http2" => HttpVersionPreference::Http2Only,
-            _ => HttpVersionPreference::Auto,
-        };
-        builder = builder
-            .with_http_version(http_version)
-            .with_http2_keep_alive_interval(Duration::from_secs(args.http2_keepalive));
-
-        if let Some(proxy) = proxy_config {
-            builder = builder.with_proxy(proxy);
-        } else {
-            builder = builder.with_system_proxy(use_system_proxy);
-        }
-        builder.build()
-    };
-
-    // Create FLV-specific configuration
-    let flv_config = FlvProtocolConfig::builder()
-        .with_base_config(download_config.clone())
-        .buffer_size(args.download_buffer)
-        .build();
-
-    // Create HLS-specific configuration
-    let hls_config = HlsProtocolBuilder::new()
-        .with_base_config(download_config)
-        .download_concurrency(
-            args.hls_concurrency
-                .try_into()
-                .map_err(|_| AppError::InvalidInput("Invalid HLS concurrency".to_string()))?,
-        )
-        .initial_playlist_fetch_timeout(Duration::from_secs(args.hls_playlist_fetch_timeout))
-        .live_refresh_interval(Duration::from_secs(args.hls_playlist_min_refresh_interval))
-        .live_max_refresh_retries(args.hls_playlist_retries)
-        .max_segment_retries(args.hls_retries)
-        .segment_download_timeout(Duration::from_secs(args.hls_segment_timeout))
-        .get_config();
-
-    // Create the program configuration
-    let program_config = ProgramConfig::builder()
-        .pipeline_config(pipeline_config)
-        .flv_pipeline_config(flv_pipeline_config)
-        .hls_pipeline_config(hls_pipeline_config)
-        .flv_config(flv_config)
-        .hls_config(hls_config)
-        .enable_processing(args.enable_fix)
-        .output_format(args.output_format)
-        .build()
-        .map_err(|err| AppError::InvalidInput(err.to_string()))?;
-
-    // Log output format
-    info!("Output format: {}", args.output_format);
+    let spec = job_spec(args)?;
 
     // Process input files
-    let result = processor::process_inputs(
-        &args.input,
-        &output_dir,
-        &program_config,
-        &args.output_name_template,
-        &token,
-    )
-    .await;
+    let result = run_job_with_token(spec, None, &token)
+        .await
+        .map_err(AppError::from)
+        .and_then(|report| report.into_result().map_err(AppError::from));
 
     // Ensure the token is always cancelled to terminate the input_handler.
     let final_result = if token.is_cancelled() {
@@ -341,3 +172,74 @@ async fn bootstrap() -> Result<(), AppError> {
 
     final_result
 }
+
+/// Describe the job requested on the command line
+fn job_spec(args: CliArgs) -> Result<JobSpec, AppError> {
+    let http_version = match args.http_version.as_str() {
+        "http1" => HttpVersionPreference::Http1Only,
+        "http2" => HttpVersionPreference::Http2Only,
+        _ => HttpVersionPreference::Auto,
+    };
+
+    Ok(JobSpec {
+        inputs: args.input,
+        output: OutputSpec {
+            dir: args.output_dir.unwrap_or_else(|| OutputSpec::default().dir),
+            name_template: args.output_name_template,
+            format: args.output_format,
+            numbered_collisions: args.numbered_names,
+        },
+        pipeline: PipelineSpec {
+            fix: args.enable_fix,
+            max_file_size: parse_size(&args.max_size)?,
+            max_duration_secs: parse_time(&args.max_duration)?,
+            channel_size: args.channel_size,
+            max_in_flight_bytes: parse_size(&args.max_buffer_size)?,
+            flv: FlvPipelineSpec {
+                keyframe_index: args.keyframe_index,
+                low_latency: args.low_latency_fix,
+            },
+            hls: HlsPipelineSpec::default(),
+        },
+        http: HttpSpec {
+            timeout_secs: args.timeout,
+            connect_timeout_secs: args.connect_timeout,
+            read_timeout_secs: args.read_timeout,
+            write_timeout_secs: args.write_timeout,
+            headers: parse_headers(&args.headers),
+            params: parse_params(&args.params)?
+                .into_iter()
+                .collect::<BTreeMap<_, _>>(),
+            version: http_version,
+            force_ipv4: args.force_ipv4,
+            force_ipv6: args.force_ipv6,
+            http2_keepalive_secs: args.http2_keepalive,
+        },
+        proxy: ProxySpec {
+            url: args.proxy,
+            proxy_type: args.proxy_type,
+            username: args.proxy_user,
+            password: args.proxy_pass,
+            system: args.use_system_proxy,
+            disabled: args.no_proxy,
+        },
+        flv: FlvSpec {
+            buffer_size: args.download_buffer,
+        },
+        hls: HlsSpec {
+            concurrency: args.hls_concurrency as usize,
+            playlist_fetch_timeout_secs: args.hls_playlist_fetch_timeout,
+            refresh_interval_secs: args.hls_playlist_min_refresh_interval,
+            playlist_retries: args.hls_playlist_retries,
+            segment_retries: args.hls_retries,
+            segment_timeout_secs: args.hls_segment_timeout,
+        },
+        limits: LimitsSpec {
+            parallel: args.parallel,
+            fail_fast: args.fail_fast,
+            max_bandwidth: parse_size(&args.max_bandwidth)?,
+            max_open: args.max_open,
+        },
+        retry: None,
+    })
+}
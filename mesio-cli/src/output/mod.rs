@@ -1,3 +0,0 @@
-pub mod pipe_flv_strategy;
-pub mod pipe_hls_strategy;
-pub mod provider;
@@ -1,10 +1,11 @@
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::BTreeMap;
+
 use tracing::info;
 
-/// Parse a header string in format "Name: Value" and add it to the HeaderMap
-pub fn parse_and_add_header(headers: &mut HeaderMap, header_str: &str) {
+/// Parse a header string in format "Name: Value" and add it to the header map
+pub fn parse_and_add_header(headers: &mut BTreeMap<String, String>, header_str: &str) {
     // Find the first colon which separates name and value
-    let Some(colon_pos) = header_str.find(':') else {
+    let Some((name, value)) = header_str.split_once(':') else {
         tracing::warn!(
             "Invalid header format: '{}'. Expected 'Name: Value'",
             header_str
@@ -12,27 +13,16 @@ pub fn parse_and_add_header(headers: &mut HeaderMap, header_str: &str) {
         return;
     };
 
-    let name = header_str[..colon_pos].trim();
-    let value = header_str[colon_pos + 1..].trim();
-
-    // Try to create a header name and value
-    let Ok(header_name) = HeaderName::from_bytes(name.as_bytes()) else {
-        tracing::warn!("Invalid header name: '{}'", name);
-        return;
-    };
-
-    let Ok(header_value) = HeaderValue::from_str(value) else {
-        tracing::warn!("Invalid header value: '{}'", value);
-        return;
-    };
-
+    // Header names and values are validated with the rest of the job spec
+    let name = name.trim();
+    let value = value.trim();
     info!("Adding header: {}: {}", name, value);
-    headers.insert(header_name, header_value);
+    headers.insert(name.to_string(), value.to_string());
 }
 
-/// Parse a collection of header strings and return a HeaderMap
-pub fn parse_headers(header_strings: &[String]) -> HeaderMap {
-    let mut headers = HeaderMap::new();
+/// Parse a collection of header strings into a map of header names to values
+pub fn parse_headers(header_strings: &[String]) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::new();
 
     for header_str in header_strings {
         parse_and_add_header(&mut headers, header_str);
@@ -1,15 +1,11 @@
-mod files;
 mod headers;
 mod params;
 mod size;
-pub mod spans;
 mod time;
 
 // Export utility functions
-pub use self::files::{create_dirs, expand_name_url};
 pub use self::headers::parse_headers;
 pub use self::params::parse_params;
-pub use self::size::format_bytes;
 pub use self::size::parse_size;
 #[allow(unused_imports)]
 pub use self::time::format_duration;
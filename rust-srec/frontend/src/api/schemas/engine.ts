@@ -243,7 +243,7 @@ export const MesioConfigSchema = z.object({
   flv_fix: z
     .object({
       sequence_header_change_mode: z
-        .enum(['crc32', 'semantic_signature'])
+        .enum(['crc32', 'semantic_signature', 'parameters'])
         .default('crc32'),
       drop_duplicate_sequence_headers: z.boolean().default(false),
       duplicate_tag_filtering: z.boolean().default(true),
@@ -308,7 +308,7 @@ const MesioDuplicateTagFilterOverrideSchema = z
 const MesioFlvFixOverrideSchema = z
   .object({
     sequence_header_change_mode: z
-      .enum(['crc32', 'semantic_signature'])
+      .enum(['crc32', 'semantic_signature', 'parameters'])
       .optional(),
     drop_duplicate_sequence_headers: z.boolean().optional(),
     duplicate_tag_filtering: z.boolean().optional(),
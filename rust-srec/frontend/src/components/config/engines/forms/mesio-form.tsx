@@ -101,6 +101,17 @@ const MesioFlvForm = React.memo(({ control, basePath }: SubFormProps) => {
                           </span>
                         </div>
                       </SelectItem>
+                      <SelectItem value="parameters" className="py-2.5">
+                        <div className="flex flex-col gap-0.5">
+                          <span className="font-medium text-xs">parameters</span>
+                          <span className="text-[10px] text-muted-foreground leading-relaxed max-w-[300px]">
+                            <Trans>
+                              Split only when the codec, resolution or audio
+                              format changes.
+                            </Trans>
+                          </span>
+                        </div>
+                      </SelectItem>
                     </SelectContent>
                   </Select>
                   <FormMessage />
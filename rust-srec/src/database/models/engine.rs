@@ -165,6 +165,8 @@ pub enum MesioSequenceHeaderChangeMode {
     Crc32,
     /// Split only when the codec configuration meaningfully changes.
     SemanticSignature,
+    /// Split only when the codec, resolution or audio format changes.
+    Parameters,
 }
 
 /// Overrides for the FLV duplicate media-tag filter.
@@ -203,6 +205,9 @@ impl MesioFlvFixConfig {
                 MesioSequenceHeaderChangeMode::SemanticSignature => {
                     flv_fix::SequenceHeaderChangeMode::SemanticSignature
                 }
+                MesioSequenceHeaderChangeMode::Parameters => {
+                    flv_fix::SequenceHeaderChangeMode::Parameters
+                }
             };
         }
 
@@ -475,6 +475,8 @@ fn parse_proxy_url(url: &str) -> ProxyConfig {
         url: url.to_string(),
         proxy_type,
         auth,
+        remote_dns: url_lower.starts_with("socks5h://"),
+        no_proxy: Vec::new(),
     }
 }
 
@@ -811,6 +813,7 @@ mod tests {
             defragment: false,
             split_segments: true,
             segment_limiter: false,
+            ..HlsPipelineConfig::default()
         });
 
         let hls_pipeline_config = build_hls_pipeline_config(&config);
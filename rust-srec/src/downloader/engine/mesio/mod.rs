@@ -46,34 +46,32 @@ use reqwest::StatusCode;
 /// the mesio engine wrappers and do not leak into the shared traits.
 pub(super) fn classify_download_error(err: &DownloadError) -> DownloadFailureKind {
     match err {
-        DownloadError::HttpStatus { status, .. } => classify_http_status(*status),
-        DownloadError::Network { .. } | DownloadError::Timeout { .. } => {
-            DownloadFailureKind::Network
-        }
+        DownloadError::Http { status, .. } => classify_http_status(*status),
+        DownloadError::Network { .. }
+        | DownloadError::Timeout { .. }
+        | DownloadError::Tls { .. } => DownloadFailureKind::Network,
         DownloadError::Io { .. } => DownloadFailureKind::Io,
-        DownloadError::NotFound { .. } | DownloadError::SourceExhausted { .. } => {
-            DownloadFailureKind::SourceUnavailable
-        }
+        DownloadError::NotFound { .. }
+        | DownloadError::SourceExhausted { .. }
+        | DownloadError::StreamStalled { .. }
+        | DownloadError::AuthRefresh { .. } => DownloadFailureKind::SourceUnavailable,
         DownloadError::InvalidUrl { .. }
         | DownloadError::UnsupportedProtocol { .. }
         | DownloadError::ProtocolDetectionFailed { .. }
+        | DownloadError::UnknownProtocol { .. }
         | DownloadError::ProxyConfiguration { .. }
         | DownloadError::Configuration { .. }
+        | DownloadError::UnsupportedEncryption { .. }
         | DownloadError::InvalidContent { .. } => DownloadFailureKind::Configuration,
         DownloadError::FlvDecode { .. }
         | DownloadError::SegmentProcess { .. }
         | DownloadError::Decryption { .. }
         | DownloadError::Protocol { .. } => DownloadFailureKind::Processing,
-        DownloadError::SegmentFetch { retryable, .. } => {
-            if *retryable {
-                DownloadFailureKind::Network
-            } else {
-                DownloadFailureKind::SourceUnavailable
-            }
-        }
+        DownloadError::SegmentFailed { source, .. } => classify_download_error(source),
         DownloadError::Cancelled => DownloadFailureKind::Cancelled,
         DownloadError::Cache { .. }
         | DownloadError::Playlist { .. }
+        | DownloadError::PlaylistParse { .. }
         | DownloadError::Internal { .. } => DownloadFailureKind::Network,
     }
 }